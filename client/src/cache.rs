@@ -0,0 +1,537 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Cache of answers, and of negative responses, RFC 2308, for clients and resolvers
+
+use std::cmp;
+use std::collections::HashMap;
+use std::time::Duration;
+
+use op::{Message, Query, ResponseCode};
+use rr::{DNSClass, LowerName, RData, Record, RecordType};
+
+/// Default upper bound, in seconds, on the time an answer is cached, whatever its TTL
+pub const DEFAULT_MAX_TTL: u32 = 86400;
+
+/// Default upper bound, in seconds, on the time a negative response is cached, RFC 2308 section 5
+///  recommends one to three hours
+pub const DEFAULT_MAX_NEGATIVE_TTL: u32 = 10800;
+
+/// What the cache holds for a query
+#[derive(Clone, Debug, PartialEq)]
+pub enum Cached {
+    /// The answers, with their TTLs reduced by the time they have been cached
+    Records(Vec<Record>),
+    /// The name does not exist, with the SOA of the zone to put in the authority section
+    NxDomain(Record),
+    /// The name exists but has no records of the type, with the SOA of the zone
+    NoData(Record),
+}
+
+/// The upper bounds, in milliseconds, of the buckets of a `LatencyHistogram`, the last bucket
+///  has none
+pub const LATENCY_BUCKETS_MS: [u64; 12] = [1, 2, 5, 10, 20, 50, 100, 200, 500, 1000, 2000, 5000];
+
+/// Counters of the lookups in a `DnsCache`
+///
+/// The counters are kept from when the cache is created, `DnsCache::clear()` does not reset them.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DnsCacheStats {
+    hits: u64,
+    negative_hits: u64,
+    misses: u64,
+    expired: u64,
+    evictions: u64,
+    hits_by_type: HashMap<RecordType, u64>,
+    misses_by_type: HashMap<RecordType, u64>,
+}
+
+impl DnsCacheStats {
+    /// Lookups answered from the cache, including the negative ones
+    pub fn get_hits(&self) -> u64 {
+        self.hits
+    }
+
+    /// Lookups answered with a cached NXDOMAIN or NODATA
+    pub fn get_negative_hits(&self) -> u64 {
+        self.negative_hits
+    }
+
+    /// Lookups for which nothing, or only something expired, was cached
+    pub fn get_misses(&self) -> u64 {
+        self.misses
+    }
+
+    /// Misses for which something was cached, but had expired
+    pub fn get_expired(&self) -> u64 {
+        self.expired
+    }
+
+    /// Entries removed because their TTL had run out
+    pub fn get_evictions(&self) -> u64 {
+        self.evictions
+    }
+
+    /// Hits, by the type of the query
+    pub fn get_hits_by_type(&self) -> &HashMap<RecordType, u64> {
+        &self.hits_by_type
+    }
+
+    /// Misses, by the type of the query
+    pub fn get_misses_by_type(&self) -> &HashMap<RecordType, u64> {
+        &self.misses_by_type
+    }
+
+    /// The share of the lookups which were hits, None before any lookup
+    pub fn get_hit_rate(&self) -> Option<f64> {
+        hit_rate(self.hits, self.misses)
+    }
+
+    /// The share of the lookups of the type which were hits, None before any lookup of the type
+    pub fn get_type_hit_rate(&self, query_type: RecordType) -> Option<f64> {
+        hit_rate(self.hits_by_type.get(&query_type).cloned().unwrap_or(0),
+                 self.misses_by_type.get(&query_type).cloned().unwrap_or(0))
+    }
+}
+
+fn hit_rate(hits: u64, misses: u64) -> Option<f64> {
+    match hits + misses {
+        0 => None,
+        lookups => Some(hits as f64 / lookups as f64),
+    }
+}
+
+/// The distribution of latencies, e.g. of answers from a cache or from upstream, over the
+///  buckets of `LATENCY_BUCKETS_MS`
+///
+/// The buckets are exported as those of a Prometheus histogram, cumulative, with the count and
+///  sum of all the latencies.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LatencyHistogram {
+    /// the count in each bucket, the last for those beyond the highest bound
+    counts: Vec<u64>,
+    count: u64,
+    sum: Duration,
+}
+
+impl Default for LatencyHistogram {
+    fn default() -> Self {
+        LatencyHistogram {
+            counts: vec![0; LATENCY_BUCKETS_MS.len() + 1],
+            count: 0,
+            sum: Duration::from_millis(0),
+        }
+    }
+}
+
+impl LatencyHistogram {
+    /// Counts the latency in the lowest bucket with a bound at or above it
+    pub fn record(&mut self, latency: Duration) {
+        let millis = latency.as_secs()
+            .saturating_mul(1000)
+            .saturating_add(latency.subsec_nanos() as u64 / 1_000_000);
+        let bucket = LATENCY_BUCKETS_MS.iter()
+            .position(|&bound| millis <= bound)
+            .unwrap_or(LATENCY_BUCKETS_MS.len());
+
+        self.counts[bucket] += 1;
+        self.count += 1;
+        self.sum = self.sum + latency;
+    }
+
+    /// The cumulative counts of the buckets, with their upper bounds, None for the last which is
+    ///  unbounded and the count of all the latencies
+    pub fn get_buckets(&self) -> Vec<(Option<Duration>, u64)> {
+        let mut cumulative = 0;
+        self.counts
+            .iter()
+            .enumerate()
+            .map(|(bucket, count)| {
+                cumulative += *count;
+                (LATENCY_BUCKETS_MS.get(bucket).map(|&bound| Duration::from_millis(bound)),
+                 cumulative)
+            })
+            .collect()
+    }
+
+    /// The number of latencies recorded
+    pub fn get_count(&self) -> u64 {
+        self.count
+    }
+
+    /// The sum of the latencies recorded
+    pub fn get_sum(&self) -> Duration {
+        self.sum
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Entry {
+    value: Cached,
+    inserted: u32,
+    expires: u32,
+}
+
+impl Entry {
+    /// The cached value with the TTLs counted down to `now`
+    fn get(&self, now: u32) -> Cached {
+        let elapsed = now.saturating_sub(self.inserted);
+        let age = |record: &Record| {
+            let mut record = record.clone();
+            let ttl = record.get_ttl().saturating_sub(elapsed);
+            record.ttl(ttl);
+            record
+        };
+
+        match self.value {
+            Cached::Records(ref records) => Cached::Records(records.iter().map(age).collect()),
+            Cached::NxDomain(ref soa) => Cached::NxDomain(age(soa)),
+            Cached::NoData(ref soa) => Cached::NoData(age(soa)),
+        }
+    }
+}
+
+type Key = (LowerName, RecordType, DNSClass);
+
+/// A cache of positive and negative responses, expiring each with its TTL
+///
+/// Answers are kept against the name, type and class of the query. A name error, NXDOMAIN, is
+///  kept against the name and class, it answers queries of every type for the name. Negative
+///  responses are only cached if they carry the SOA of the zone, for as long as the lower of the
+///  SOA TTL and minimum field, RFC 2308 section 5.
+///
+/// Expired entries are removed when they are looked up, or by `evict_expired()`. All times, `now`,
+///  are seconds since the epoch.
+#[derive(Clone, Debug)]
+pub struct DnsCache {
+    records: HashMap<Key, Entry>,
+    nxdomains: HashMap<(LowerName, DNSClass), Entry>,
+    max_ttl: u32,
+    max_negative_ttl: u32,
+    stats: DnsCacheStats,
+}
+
+impl Default for DnsCache {
+    fn default() -> Self {
+        DnsCache::new(DEFAULT_MAX_TTL, DEFAULT_MAX_NEGATIVE_TTL)
+    }
+}
+
+impl DnsCache {
+    /// Returns a new, empty, cache
+    ///
+    /// # Arguments
+    ///
+    /// * `max_ttl` - seconds an answer is cached at most, whatever its TTL
+    /// * `max_negative_ttl` - seconds an NXDOMAIN or NODATA response is cached at most
+    pub fn new(max_ttl: u32, max_negative_ttl: u32) -> Self {
+        DnsCache {
+            records: HashMap::new(),
+            nxdomains: HashMap::new(),
+            max_ttl: max_ttl,
+            max_negative_ttl: max_negative_ttl,
+            stats: DnsCacheStats::default(),
+        }
+    }
+
+    /// Stores the answers to the query, for the lowest of their TTLs
+    pub fn insert_records(&mut self, query: &Query, records: Vec<Record>, now: u32) {
+        let ttl = match records.iter().map(|record| record.get_ttl()).min() {
+            Some(ttl) => cmp::min(ttl, self.max_ttl),
+            None => return,
+        };
+
+        self.records.insert(key(query),
+                            Entry {
+                                value: Cached::Records(records),
+                                inserted: now,
+                                expires: now.saturating_add(ttl),
+                            });
+    }
+
+    /// Stores the response to the query, if it can be cached
+    ///
+    /// A successful response with answers is cached as `Records`, an NXDOMAIN as `NxDomain`, and a
+    ///  successful response without answers as `NoData`, if the authority section holds an SOA.
+    ///  An NXDOMAIN with answers, i.e. for the target of a CNAME, is not cached, nor is anything
+    ///  truncated or with another response code.
+    ///
+    /// # Return value
+    ///
+    /// true if the response was cached
+    pub fn insert_response(&mut self, query: &Query, response: &Message, now: u32) -> bool {
+        if response.is_truncated() {
+            return false;
+        }
+
+        let nxdomain = match (response.get_response_code(), response.get_answers().is_empty()) {
+            (ResponseCode::NoError, false) => {
+                self.insert_records(query, response.get_answers().to_vec(), now);
+                return true;
+            }
+            (ResponseCode::NoError, true) => false,
+            (ResponseCode::NXDomain, true) => true,
+            _ => return false,
+        };
+
+        let soa = match response.get_name_servers()
+            .iter()
+            .find(|record| record.get_rr_type() == RecordType::SOA) {
+            Some(soa) => soa,
+            None => return false,
+        };
+        let ttl = match *soa.get_rdata() {
+            RData::SOA(ref rdata) => cmp::min(soa.get_ttl(), rdata.get_minimum()),
+            _ => return false,
+        };
+        let ttl = cmp::min(ttl, self.max_negative_ttl);
+
+        let mut soa = soa.clone();
+        soa.ttl(ttl);
+        let entry = |value: Cached| {
+            Entry {
+                value: value,
+                inserted: now,
+                expires: now.saturating_add(ttl),
+            }
+        };
+
+        if nxdomain {
+            let name = LowerName::new(query.get_name());
+            self.nxdomains.insert((name, query.get_query_class()), entry(Cached::NxDomain(soa)));
+        } else {
+            self.records.insert(key(query), entry(Cached::NoData(soa)));
+        }
+        true
+    }
+
+    /// Returns what is cached for the query, an NXDOMAIN for the name before anything else
+    pub fn get(&mut self, query: &Query, now: u32) -> Option<Cached> {
+        let name_key = (LowerName::new(query.get_name()), query.get_query_class());
+        let record_key = key(query);
+
+        let mut cached = None;
+        if let Some(entry) = self.nxdomains.get(&name_key) {
+            if entry.expires > now {
+                cached = Some(entry.get(now));
+            }
+        }
+        if cached.is_none() {
+            if let Some(entry) = self.records.get(&record_key) {
+                if entry.expires > now {
+                    cached = Some(entry.get(now));
+                }
+            }
+        }
+
+        let query_type = query.get_query_type();
+        match cached {
+            Some(Cached::Records(records)) => {
+                self.stats.hits += 1;
+                *self.stats.hits_by_type.entry(query_type).or_insert(0) += 1;
+                Some(Cached::Records(records))
+            }
+            Some(negative) => {
+                self.stats.hits += 1;
+                self.stats.negative_hits += 1;
+                *self.stats.hits_by_type.entry(query_type).or_insert(0) += 1;
+                Some(negative)
+            }
+            None => {
+                self.stats.misses += 1;
+                *self.stats.misses_by_type.entry(query_type).or_insert(0) += 1;
+
+                let mut expired = false;
+                if self.nxdomains.remove(&name_key).is_some() {
+                    self.stats.evictions += 1;
+                    expired = true;
+                }
+                if self.records.remove(&record_key).is_some() {
+                    self.stats.evictions += 1;
+                    expired = true;
+                }
+                if expired {
+                    self.stats.expired += 1;
+                }
+                None
+            }
+        }
+    }
+
+    /// Removes everything that has expired
+    ///
+    /// # Return value
+    ///
+    /// The number of entries removed
+    pub fn evict_expired(&mut self, now: u32) -> usize {
+        let expired_records: Vec<Key> = self.records
+            .iter()
+            .filter(|&(_, entry)| entry.expires <= now)
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in &expired_records {
+            self.records.remove(key);
+        }
+
+        let expired_nxdomains: Vec<(LowerName, DNSClass)> = self.nxdomains
+            .iter()
+            .filter(|&(_, entry)| entry.expires <= now)
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in &expired_nxdomains {
+            self.nxdomains.remove(key);
+        }
+
+        let evicted = expired_records.len() + expired_nxdomains.len();
+        self.stats.evictions += evicted as u64;
+        evicted
+    }
+
+    /// Removes everything
+    pub fn clear(&mut self) {
+        self.records.clear();
+        self.nxdomains.clear();
+    }
+
+    /// The number of entries, including expired ones not yet evicted
+    pub fn len(&self) -> usize {
+        self.records.len() + self.nxdomains.len()
+    }
+
+    /// Returns true if nothing is cached
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The counters of the lookups since the cache was created
+    pub fn get_stats(&self) -> DnsCacheStats {
+        self.stats.clone()
+    }
+}
+
+fn key(query: &Query) -> Key {
+    (LowerName::new(query.get_name()), query.get_query_type(), query.get_query_class())
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::Ipv4Addr;
+    use std::time::Duration;
+
+    use op::*;
+    use rr::*;
+    use rr::rdata::SOA;
+    use super::*;
+
+    fn query(name: &str, query_type: RecordType) -> Query {
+        let mut query = Query::new();
+        query.name(Name::parse(name, None).unwrap()).query_type(query_type);
+        query
+    }
+
+    fn soa(ttl: u32, minimum: u32) -> Record {
+        let origin = Name::parse("example.com.", None).unwrap();
+        Record::from_rdata(origin.clone(),
+                           ttl,
+                           RecordType::SOA,
+                           RData::SOA(SOA::new(origin.clone(),
+                                               origin,
+                                               1,
+                                               3600,
+                                               600,
+                                               86400,
+                                               minimum)))
+    }
+
+    fn negative(response_code: ResponseCode) -> Message {
+        let mut response = Message::new();
+        response.message_type(MessageType::Response)
+            .response_code(response_code)
+            .add_name_server(soa(3600, 300));
+        response
+    }
+
+    #[test]
+    fn test_records_expire() {
+        let mut cache = DnsCache::default();
+        let www = query("www.example.com.", RecordType::A);
+        let a = Record::from_rdata(Name::parse("www.example.com.", None).unwrap(),
+                                   60,
+                                   RecordType::A,
+                                   RData::A(Ipv4Addr::new(192, 0, 2, 1)));
+
+        let mut response = Message::new();
+        response.message_type(MessageType::Response).add_answer(a.clone());
+        assert!(cache.insert_response(&www, &response, 1000));
+
+        // case does not matter, the TTL counts down
+        let mut aged = a.clone();
+        aged.ttl(20);
+        assert_eq!(cache.get(&query("WWW.example.com.", RecordType::A), 1040),
+                   Some(Cached::Records(vec![aged])));
+        assert_eq!(cache.get(&query("www.example.com.", RecordType::AAAA), 1040), None);
+
+        assert_eq!(cache.get(&www, 1060), None);
+        assert!(cache.is_empty());
+
+        let stats = cache.get_stats();
+        assert_eq!(stats.get_hits(), 1);
+        assert_eq!(stats.get_misses(), 2);
+        assert_eq!(stats.get_expired(), 1);
+        assert_eq!(stats.get_evictions(), 1);
+        assert_eq!(stats.get_hit_rate(), Some(1.0 / 3.0));
+        assert_eq!(stats.get_type_hit_rate(RecordType::A), Some(0.5));
+        assert_eq!(stats.get_type_hit_rate(RecordType::AAAA), Some(0.0));
+        assert_eq!(stats.get_type_hit_rate(RecordType::MX), None);
+        assert_eq!(stats.get_misses_by_type().get(&RecordType::A), Some(&1));
+    }
+
+    #[test]
+    fn test_latency_histogram() {
+        let mut histogram = LatencyHistogram::default();
+        histogram.record(Duration::from_millis(0));
+        histogram.record(Duration::from_millis(15));
+        histogram.record(Duration::from_millis(20));
+        histogram.record(Duration::from_secs(10));
+
+        let buckets = histogram.get_buckets();
+        assert_eq!(buckets.len(), LATENCY_BUCKETS_MS.len() + 1);
+        assert_eq!(buckets[0], (Some(Duration::from_millis(1)), 1));
+        assert_eq!(buckets[3], (Some(Duration::from_millis(10)), 1));
+        assert_eq!(buckets[4], (Some(Duration::from_millis(20)), 3));
+        assert_eq!(buckets[11], (Some(Duration::from_secs(5)), 3));
+        assert_eq!(buckets[12], (None, 4));
+        assert_eq!(histogram.get_count(), 4);
+        assert_eq!(histogram.get_sum(), Duration::from_millis(10035));
+    }
+
+    #[test]
+    fn test_negative() {
+        let mut cache = DnsCache::default();
+        let www = query("www.example.com.", RecordType::A);
+        let mail = query("mail.example.com.", RecordType::MX);
+
+        // the NXDOMAIN answers every type, for the SOA minimum
+        assert!(cache.insert_response(&www, &negative(ResponseCode::NXDomain), 1000));
+        assert!(cache.insert_response(&mail, &negative(ResponseCode::NoError), 1000));
+        assert_eq!(cache.get(&query("www.example.com.", RecordType::TXT), 1100),
+                   Some(Cached::NxDomain(soa(200, 300))));
+        assert_eq!(cache.get(&mail, 1100), Some(Cached::NoData(soa(200, 300))));
+        assert_eq!(cache.get(&query("mail.example.com.", RecordType::A), 1100), None);
+
+        assert_eq!(cache.evict_expired(1300), 2);
+        assert_eq!(cache.get(&www, 1300), None);
+        assert_eq!(cache.get_stats().get_negative_hits(), 2);
+
+        // without an SOA it is not known for how long
+        let mut response = Message::new();
+        response.message_type(MessageType::Response).response_code(ResponseCode::NXDomain);
+        assert!(!cache.insert_response(&www, &response, 1000));
+        assert!(!cache.insert_response(&www, &negative(ResponseCode::ServFail), 1000));
+        assert!(cache.is_empty());
+    }
+}
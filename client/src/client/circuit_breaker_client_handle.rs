@@ -0,0 +1,271 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use futures::{failed, Future};
+
+use client::ClientHandle;
+use ::error::*;
+use op::{Message, ResponseCode};
+
+#[derive(Debug)]
+enum State {
+    /// Requests are sent, counting the consecutive failures
+    Closed(u32),
+    /// Requests fail without being sent until the cooldown is over
+    Open(Instant),
+    /// The cooldown is over, a single probe is in flight and all other requests fail
+    HalfOpen,
+}
+
+/// The state of the circuit while a probe is in flight, the circuit is reset if the probe is
+///  dropped before its response, e.g. by a timeout of the caller, otherwise it would stay half
+///  open and fail every request from then on
+struct Probe {
+    state: Option<Rc<RefCell<State>>>,
+}
+
+impl Probe {
+    /// The probe completed, its result sets the state
+    fn complete(&mut self) {
+        self.state = None;
+    }
+}
+
+impl Drop for Probe {
+    fn drop(&mut self) {
+        if let Some(ref state) = self.state {
+            let mut state = state.borrow_mut();
+            if let State::HalfOpen = *state {
+                debug!("probe dropped, probing again on the next request");
+                *state = State::Open(Instant::now());
+            }
+        }
+    }
+}
+
+/// Stops sending to an upstream which keeps failing
+///
+/// After `failures` consecutive errors, or SERVFAIL responses, the circuit opens and every request
+///  fails immediately with `CircuitOpen` for the cooldown, rather than waiting for yet another
+///  timeout. After the cooldown a single request is sent as a probe, if it succeeds the circuit
+///  closes, otherwise it opens for another cooldown. All clones share the one circuit.
+#[derive(Clone)]
+#[must_use = "queries can only be sent through a ClientHandle"]
+pub struct CircuitBreakerClientHandle<H: ClientHandle> {
+    client: H,
+    failures: u32,
+    cooldown: Duration,
+    state: Rc<RefCell<State>>,
+}
+
+impl<H> CircuitBreakerClientHandle<H>
+    where H: ClientHandle
+{
+    /// Returns a new handle wrapping the specified client
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - the client for the upstream
+    /// * `failures` - consecutive failures after which the circuit opens
+    /// * `cooldown` - time the circuit stays open before a probe is sent
+    pub fn new(client: H, failures: u32, cooldown: Duration) -> CircuitBreakerClientHandle<H> {
+        CircuitBreakerClientHandle {
+            client: client,
+            failures: failures,
+            cooldown: cooldown,
+            state: Rc::new(RefCell::new(State::Closed(0))),
+        }
+    }
+
+    /// Returns true if requests are currently failing fast
+    pub fn is_open(&self) -> bool {
+        match *self.state.borrow() {
+            State::Closed(_) => false,
+            State::Open(until) => Instant::now() < until,
+            State::HalfOpen => true,
+        }
+    }
+}
+
+impl<H> ClientHandle for CircuitBreakerClientHandle<H>
+    where H: ClientHandle
+{
+    fn send(&mut self, message: Message) -> Box<Future<Item = Message, Error = ClientError>> {
+        let mut probe = Probe { state: None };
+        {
+            let mut state = self.state.borrow_mut();
+            let next = match *state {
+                State::Closed(_) => None,
+                State::Open(until) if Instant::now() >= until => {
+                    debug!("cooldown is over, probing upstream");
+                    Some(State::HalfOpen)
+                }
+                State::Open(_) | State::HalfOpen => {
+                    return Box::new(failed(ClientErrorKind::CircuitOpen.into()))
+                }
+            };
+
+            if let Some(next) = next {
+                *state = next;
+                probe.state = Some(self.state.clone());
+            }
+        }
+
+        let state = self.state.clone();
+        let failures = self.failures;
+        let cooldown = self.cooldown;
+
+        Box::new(self.client.send(message).then(move |result| {
+            probe.complete();
+
+            let is_failure = match result {
+                Ok(ref response) => response.get_response_code() == ResponseCode::ServFail,
+                Err(_) => true,
+            };
+
+            let mut state = state.borrow_mut();
+            let next = if !is_failure {
+                State::Closed(0)
+            } else {
+                match *state {
+                    State::Closed(count) if count + 1 < failures => State::Closed(count + 1),
+                    // a failed probe, or too many failures
+                    _ => {
+                        warn!("upstream failing, opening circuit for: {:?}", cooldown);
+                        State::Open(Instant::now() + cooldown)
+                    }
+                }
+            };
+            *state = next;
+
+            result
+        }))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::Cell;
+    use std::rc::Rc;
+    use std::time::Duration;
+
+    use ::client::*;
+    use ::error::*;
+    use ::op::*;
+    use futures::*;
+
+    #[derive(Clone)]
+    struct TestClient {
+        fail: Rc<Cell<bool>>,
+        sent: Rc<Cell<usize>>,
+        /// the response never arrives
+        hang: Rc<Cell<bool>>,
+    }
+
+    impl ClientHandle for TestClient {
+        fn send(&mut self, _: Message) -> Box<Future<Item = Message, Error = ClientError>> {
+            self.sent.set(self.sent.get() + 1);
+
+            if self.hang.get() {
+                Box::new(empty())
+            } else if self.fail.get() {
+                Box::new(failed(ClientErrorKind::Timeout.into()))
+            } else {
+                Box::new(finished(Message::new()))
+            }
+        }
+    }
+
+    fn test_client() -> (TestClient, Rc<Cell<bool>>, Rc<Cell<usize>>) {
+        let fail = Rc::new(Cell::new(true));
+        let sent = Rc::new(Cell::new(0));
+
+        (TestClient {
+            fail: fail.clone(),
+            sent: sent.clone(),
+            hang: Rc::new(Cell::new(false)),
+        },
+         fail,
+         sent)
+    }
+
+    #[test]
+    fn test_open_after_failures() {
+        let (client, _, sent) = test_client();
+        let mut client = CircuitBreakerClientHandle::new(client, 2, Duration::from_secs(3600));
+
+        assert!(client.send(Message::new()).wait().is_err());
+        assert!(!client.is_open());
+        assert!(client.send(Message::new()).wait().is_err());
+        assert!(client.is_open());
+
+        if let &ClientErrorKind::CircuitOpen = client.send(Message::new())
+            .wait()
+            .unwrap_err()
+            .kind() {
+            ()
+        } else {
+            assert!(false);
+        }
+        assert_eq!(sent.get(), 2);
+    }
+
+    #[test]
+    fn test_half_open_probe() {
+        let (client, fail, sent) = test_client();
+        let mut client = CircuitBreakerClientHandle::new(client, 1, Duration::from_secs(0));
+
+        assert!(client.send(Message::new()).wait().is_err());
+
+        // the cooldown is over, a failed probe opens it again
+        assert!(client.send(Message::new()).wait().is_err());
+        assert_eq!(sent.get(), 2);
+
+        fail.set(false);
+        assert!(client.send(Message::new()).wait().is_ok());
+        assert!(!client.is_open());
+        assert!(client.send(Message::new()).wait().is_ok());
+        assert_eq!(sent.get(), 4);
+    }
+
+    #[test]
+    fn test_dropped_probe() {
+        let (client, fail, sent) = test_client();
+        let hang = client.hang.clone();
+        let mut client = CircuitBreakerClientHandle::new(client, 1, Duration::from_secs(0));
+
+        assert!(client.send(Message::new()).wait().is_err());
+
+        // the probe is given up on before its response, the next request probes again
+        hang.set(true);
+        drop(client.send(Message::new()));
+        assert!(!client.is_open());
+
+        hang.set(false);
+        fail.set(false);
+        assert!(client.send(Message::new()).wait().is_ok());
+        assert_eq!(sent.get(), 3);
+    }
+
+    #[test]
+    fn test_success_resets_count() {
+        let (client, fail, sent) = test_client();
+        let mut client = CircuitBreakerClientHandle::new(client, 2, Duration::from_secs(3600));
+
+        assert!(client.send(Message::new()).wait().is_err());
+        fail.set(false);
+        assert!(client.send(Message::new()).wait().is_ok());
+        fail.set(true);
+        assert!(client.send(Message::new()).wait().is_err());
+        assert!(!client.is_open());
+        assert_eq!(sent.get(), 3);
+    }
+}
@@ -14,17 +14,30 @@
 
 use std::cell::{RefCell, RefMut};
 use std::io;
+use std::net::{IpAddr, SocketAddr};
 
 use futures::Stream;
+use rand;
 use tokio_core::reactor::Core;
 
-use client::{ClientHandle, BasicClientHandle, ClientConnection, ClientFuture, SecureClientHandle};
+use client::{ClientHandle, BasicClientHandle, ClientConnection, ClientFuture, EdnsClientHandle,
+             FailoverClientHandle, PerQueryTcpClientHandle, RetryPolicy, SecureClientHandle,
+             TcpFallbackClientHandle, TimeoutConfig, FALLBACK_EDNS_PAYLOAD};
+use client::ddr::{addresses_of, ddr_name, designated_resolvers, DesignatedResolver};
+use client::search_list::{SearchList, SearchOutcome};
+use client::srv::{sort_srv, srv_name};
+use client::update_target::{zone_of_response, UpdateTarget};
 use ::error::*;
-use rr::{domain, DNSClass, IntoRecordSet, RecordType, Record};
-use rr::dnssec::Signer;
+use rr::{domain, DNSClass, IntoRecordSet, RData, RecordType, Record};
+use rr::dnssec::{DnssecChain, Signer};
+use rr::rdata::opt::{ClientSubnet, EdnsOption};
 #[cfg(feature = "openssl")]
 use rr::dnssec::TrustAnchor;
-use op::Message;
+use op::{IxfrResponse, Message, MessageType, OpCode, Query};
+use udp::{UdpClientConnection, UdpClientStream};
+
+/// The most zones whose keys `Client::export_dnssec_chain` collects, from the root down
+pub const MAX_CHAIN_ZONES: usize = 16;
 
 /// Client trait which implements basic DNS Client operations.
 ///
@@ -62,6 +75,297 @@ pub trait Client<C: ClientHandle> {
             .run(self.get_client_handle().query(name.clone(), query_class, query_type))
     }
 
+    /// Queries the name, relative to the search domains unless it is fully qualified, and
+    ///  returns the first response with an answer, as the resolver of libc does
+    ///
+    /// The names are tried in the order of `SearchList::get_names`. A name which does not exist
+    ///  or whose query fails is passed over; if none of the names have records of the type, the
+    ///  response is the first NODATA, or else the last response or error.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - the name, relative or ending with `.`
+    /// * `search_list` - the search domains and `ndots`, e.g. of `SystemConf::get_search_list()`
+    /// * `query_class` - most likely this should always be DNSClass::IN
+    /// * `query_type` - record type to lookup
+    fn search(&self,
+              name: &str,
+              search_list: &SearchList,
+              query_class: DNSClass,
+              query_type: RecordType)
+              -> ClientResult<Message> {
+        let mut no_data: Option<Message> = None;
+        let mut last: Option<ClientResult<Message>> = None;
+
+        let names = try!(search_list.get_names(name).map_err(|e| {
+            ClientError::from(ClientErrorKind::Msg(format!("bad name {}: {}", name, e)))
+        }));
+
+        for name in names {
+            let result = self.query(&name, query_class, query_type);
+            let outcome = match result {
+                Ok(ref response) => SearchOutcome::of(response),
+                Err(ref e) => {
+                    debug!("search of {} failed: {}", name, e);
+                    SearchOutcome::NotFound
+                }
+            };
+
+            match outcome {
+                SearchOutcome::Answer => return result,
+                SearchOutcome::NoData if no_data.is_none() => no_data = result.ok(),
+                _ => last = Some(result),
+            }
+        }
+
+        match (no_data, last) {
+            (Some(response), _) => Ok(response),
+            (None, Some(result)) => result,
+            (None, None) => Err(ClientErrorKind::Message("no names to search").into()),
+        }
+    }
+
+    /// Looks up the IPv4 and IPv6 addresses of the name, with the A and AAAA queries sent at once,
+    ///  see `ClientHandle::lookup_ip`
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - the name of the host
+    fn lookup_ip(&self, name: &domain::Name) -> ClientResult<Vec<IpAddr>> {
+        self.get_io_loop().run(self.get_client_handle().lookup_ip(name.clone()))
+    }
+
+    /// Looks up the SRV records of a service, and returns the addresses of their targets in the
+    ///  order they should be connected to, RFC 2782
+    ///
+    /// The records are ordered by priority, and within a priority by the weighted random
+    ///  selection of the RFC, see `sort_srv`. The A and AAAA records of each target are taken from
+    ///  the additional section of the response, or else looked up; a target which can not be
+    ///  resolved is skipped. The result is empty if the name has no SRV records, or if the only
+    ///  record has the target `.`, i.e. the service is decidedly not available.
+    ///
+    /// # Arguments
+    ///
+    /// * `service` - the symbolic name of the service, e.g. `sip` or `_sip`
+    /// * `proto` - the protocol of the service, e.g. `tcp` or `_tcp`
+    /// * `domain` - the domain of the service
+    fn lookup_srv(&self,
+                  service: &str,
+                  proto: &str,
+                  domain: &domain::Name)
+                  -> ClientResult<Vec<SocketAddr>> {
+        let response = try!(self.query(&srv_name(service, proto, domain),
+                                       DNSClass::IN,
+                                       RecordType::SRV));
+
+        let records = response.get_answers()
+            .iter()
+            .filter_map(|record| if let RData::SRV(ref srv) = *record.get_rdata() {
+                Some(srv.clone())
+            } else {
+                None
+            })
+            .collect::<Vec<_>>();
+        if records.len() == 1 && records[0].get_target().is_root() {
+            return Ok(vec![]);
+        }
+
+        let addresses_of = |records: &[Record], target: &domain::Name| {
+            records.iter()
+                .filter(|record| record.get_name() == target)
+                .filter_map(|record| match *record.get_rdata() {
+                    RData::A(address) => Some(IpAddr::V4(address)),
+                    RData::AAAA(address) => Some(IpAddr::V6(address)),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let mut socket_addrs: Vec<SocketAddr> = Vec::new();
+        for srv in sort_srv(records, &mut rand::thread_rng()) {
+            let mut addresses = addresses_of(response.get_additionals(), srv.get_target());
+            if addresses.is_empty() {
+                for query_type in &[RecordType::A, RecordType::AAAA] {
+                    match self.query(srv.get_target(), DNSClass::IN, *query_type) {
+                        Ok(response) => {
+                            addresses.extend(addresses_of(response.get_answers(),
+                                                          srv.get_target()))
+                        }
+                        Err(e) => {
+                            debug!("could not look up {:?} of {}: {}",
+                                   query_type,
+                                   srv.get_target(),
+                                   e)
+                        }
+                    }
+                }
+            }
+
+            socket_addrs.extend(addresses.into_iter()
+                .map(|address| SocketAddr::new(address, srv.get_port())));
+        }
+
+        Ok(socket_addrs)
+    }
+
+    /// Discovers the encrypted resolvers designated by the resolver of this client, DDR, RFC 9462
+    ///
+    /// The SVCB records of `_dns.resolver.arpa.` are queried, see `designated_resolvers` for those
+    ///  which are used; the addresses of a target without hints or additional records are looked
+    ///  up. The designations are not trustworthy as such, the connection to a designated resolver
+    ///  must be verified against the address of this resolver, e.g. by
+    ///  `DesignatedResolver::tls_connection()`. The result is empty if there are none.
+    fn discover_designated_resolvers(&self) -> ClientResult<Vec<DesignatedResolver>> {
+        let response = try!(self.query(&ddr_name(), DNSClass::IN, RecordType::SVCB));
+
+        let mut resolvers = designated_resolvers(&response);
+        for resolver in resolvers.iter_mut() {
+            if !resolver.get_addresses().is_empty() {
+                continue;
+            }
+
+            let target = resolver.get_target().clone();
+            for query_type in &[RecordType::A, RecordType::AAAA] {
+                match self.query(&target, DNSClass::IN, *query_type) {
+                    Ok(response) => resolver.add_addresses(addresses_of(response.get_answers(),
+                                                                        &target)),
+                    Err(e) => debug!("could not look up {:?} of {}: {}", query_type, target, e),
+                }
+            }
+        }
+
+        Ok(resolvers)
+    }
+
+    /// Finds the zone of the name and the primary master of the zone, which the dynamic updates
+    ///  of the name are sent to, as RFC 2136 section 4 and e.g. nsupdate do
+    ///
+    /// The SOA of the name is queried, and then of its parent and so on until a response has the
+    ///  SOA of the zone, in the answers or the authority section. The MNAME of the SOA, the
+    ///  primary master, is then resolved with `lookup_ip`.
+    ///
+    /// ```no_run
+    /// use std::net::SocketAddr;
+    /// use trust_dns::client::{Client, SyncClient};
+    /// use trust_dns::rr::{DNSClass, Record, RecordType, RData};
+    /// use trust_dns::rr::domain::Name;
+    /// use trust_dns::udp::UdpClientConnection;
+    ///
+    /// let resolver: SocketAddr = "192.0.2.53:53".parse().unwrap();
+    /// let client = SyncClient::new(UdpClientConnection::new(resolver).unwrap());
+    ///
+    /// let name = Name::parse("host.example.com.", None).unwrap();
+    /// let target = client.find_update_target(&name).unwrap();
+    ///
+    /// let mut record = Record::with(name, RecordType::A, 300);
+    /// record.dns_class(DNSClass::IN).rdata(RData::A("192.0.2.1".parse().unwrap()));
+    /// target.client(None).unwrap().create(record, target.get_zone().clone()).unwrap();
+    /// ```
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - the name which is to be updated
+    fn find_update_target(&self, name: &domain::Name) -> ClientResult<UpdateTarget> {
+        let mut query_name = name.clone();
+        loop {
+            let response = try!(self.query(&query_name, DNSClass::IN, RecordType::SOA));
+            if let Some((zone, soa)) = zone_of_response(&query_name, &response) {
+                let addresses = try!(self.lookup_ip(soa.get_mname()));
+                if addresses.is_empty() {
+                    return Err(ClientErrorKind::Message("could not resolve the primary master")
+                        .into());
+                }
+
+                return Ok(UpdateTarget::new(zone, soa.get_mname().clone(), addresses));
+            }
+
+            if query_name.is_root() {
+                return Err(ClientErrorKind::Message("no zone of the name was found").into());
+            }
+            query_name = query_name.base_name();
+        }
+    }
+
+    /// Collects the chain of trust of the answer to the query, for verifying it offline, e.g.
+    ///  for an audit trail, see `DnssecChain::verify`
+    ///
+    /// The query is sent with DO set, and with the CHAIN option of RFC 7901 asking for the chain
+    ///  from the root, which a server implementing it returns in the authority section. For each
+    ///  signer of an RRSIG in the chain whose keys are missing, up to the root, the DNSKEY and,
+    ///  below the root, the DS records are then queried. The records are not validated here, the
+    ///  answer must be signed though.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - the name to lookup
+    /// * `query_class` - most likely this should always be DNSClass::IN
+    /// * `query_type` - record type to lookup
+    fn export_dnssec_chain(&self,
+                           name: &domain::Name,
+                           query_class: DNSClass,
+                           query_type: RecordType)
+                           -> ClientResult<DnssecChain> {
+        let mut query = Query::new();
+        query.name(name.clone()).query_class(query_class).query_type(query_type);
+
+        let response = try!(self.get_io_loop()
+            .run(self.get_client_handle().send(dnssec_query(query.clone(), true))));
+        let mut records: Vec<Record> = Vec::new();
+        add_records(&mut records, response.get_answers());
+        if !records.iter().any(|record| record.get_rr_type() == RecordType::RRSIG) {
+            return Err(ClientErrorKind::Msg(format!("the answer is not signed: {}", query))
+                .into());
+        }
+        let chain: Vec<Record> = response.get_name_servers()
+            .iter()
+            .filter(|record| is_chain_type(record))
+            .cloned()
+            .collect();
+        add_records(&mut records, &chain);
+
+        let mut zones: Vec<domain::Name> = Vec::new();
+        let mut visited: Vec<domain::Name> = Vec::new();
+        loop {
+            for signer in signers_of(&records) {
+                if !visited.contains(&signer) && !zones.contains(&signer) {
+                    zones.push(signer);
+                }
+            }
+            let zone = match zones.pop() {
+                Some(zone) => zone,
+                None => break,
+            };
+            if visited.len() >= MAX_CHAIN_ZONES {
+                return Err(ClientErrorKind::Msg(format!("more than {} zones in the chain of: {}",
+                                                        MAX_CHAIN_ZONES,
+                                                        query))
+                    .into());
+            }
+
+            let mut chain_types = vec![RecordType::DNSKEY];
+            if !zone.is_root() {
+                chain_types.push(RecordType::DS);
+            }
+            for chain_type in chain_types {
+                let is_present = records.iter()
+                    .any(|record| record.get_rr_type() == chain_type && record.get_name() == &zone);
+                if is_present {
+                    continue;
+                }
+
+                let mut chain_query = Query::new();
+                chain_query.name(zone.clone()).query_class(query_class).query_type(chain_type);
+                let response = try!(self.get_io_loop()
+                    .run(self.get_client_handle().send(dnssec_query(chain_query, false))));
+                add_records(&mut records, response.get_answers());
+            }
+
+            visited.push(zone);
+        }
+
+        Ok(DnssecChain::new(query, records))
+    }
+
     /// Sends a NOTIFY message to the remote system
     ///
     /// # Arguments
@@ -82,6 +386,22 @@ pub trait Client<C: ClientHandle> {
             .run(self.get_client_handle().notify(name, query_class, query_type, rrset))
     }
 
+    /// Requests the changes to a zone since a serial, an incremental zone transfer, see
+    ///  `ClientHandle::ixfr`
+    ///
+    /// # Arguments
+    ///
+    /// * `zone` - the origin of the zone
+    /// * `query_class` - most likely this should always be DNSClass::IN
+    /// * `serial` - the serial of the SOA of the version of the zone the client has
+    fn ixfr(&self,
+            zone: domain::Name,
+            query_class: DNSClass,
+            serial: u32)
+            -> ClientResult<IxfrResponse> {
+        self.get_io_loop().run(self.get_client_handle().ixfr(zone, query_class, serial))
+    }
+
     /// Sends a record to create on the server, this will fail if the record exists (atomicity
     ///  depends on the server)
     ///
@@ -331,13 +651,74 @@ pub trait Client<C: ClientHandle> {
     }
 }
 
+/// The query, with DO set, and with the CHAIN option for the chain from the root, RFC 7901
+fn dnssec_query(query: Query, is_chain: bool) -> Message {
+    let mut message: Message = Message::new();
+    message.id(rand::random())
+        .message_type(MessageType::Query)
+        .op_code(OpCode::Query)
+        .recursion_desired(true);
+
+    {
+        let edns = message.get_edns_mut();
+        edns.set_max_payload(1500);
+        edns.set_version(0);
+        edns.set_dnssec_ok(true);
+        if is_chain {
+            // the closest trust point is the root
+            edns.set_option(EdnsOption::Chain(domain::Name::root()));
+        }
+    }
+
+    message.add_query(query);
+    message
+}
+
+/// DNSKEY, DS and the RRSIGs over them, the records of a chain of trust
+fn is_chain_type(record: &Record) -> bool {
+    match *record.get_rdata() {
+        RData::DNSKEY(..) | RData::DS(..) => true,
+        RData::SIG(ref sig) => {
+            sig.get_type_covered() == RecordType::DNSKEY || sig.get_type_covered() == RecordType::DS
+        }
+        _ => false,
+    }
+}
+
+/// Adds the records which are not already in the chain
+fn add_records(chain: &mut Vec<Record>, records: &[Record]) {
+    for record in records {
+        if !chain.contains(record) {
+            chain.push(record.clone());
+        }
+    }
+}
+
+/// The signers of the RRSIGs among the records, the zones whose keys verify them
+fn signers_of(records: &[Record]) -> Vec<domain::Name> {
+    let mut signers: Vec<domain::Name> = Vec::new();
+    for record in records {
+        if let RData::SIG(ref sig) = *record.get_rdata() {
+            if !signers.contains(sig.get_signer_name()) {
+                signers.push(sig.get_signer_name().clone());
+            }
+        }
+    }
+
+    signers
+}
+
 /// The Client is abstracted over either trust_dns::tcp::TcpClientConnection or
 ///  trust_dns::udp::UdpClientConnection.
 ///
 /// Usage of TCP or UDP is up to the user. Some DNS servers
 ///  disallow TCP in some cases, so if TCP double check if UDP works.
-pub struct SyncClient {
-    client_handle: RefCell<BasicClientHandle>,
+///
+/// With `with_failover()` the queries are sent to a list of name servers, see
+///  `FailoverClientHandle`, and with `with_tcp_fallback()` truncated UDP responses are retried
+///  over TCP.
+pub struct SyncClient<H: ClientHandle = BasicClientHandle> {
+    client_handle: RefCell<H>,
     io_loop: RefCell<Core>,
 }
 
@@ -349,9 +730,13 @@ impl SyncClient {
     /// * `client_connection` - the client_connection to use for all communication
   pub fn new<CC: ClientConnection>(client_connection: CC) -> SyncClient
   where <CC as ClientConnection>::MessageStream: Stream<Item=Vec<u8>, Error=io::Error> + 'static {
+        let timeouts = client_connection.get_timeouts();
+        let edns_payload = client_connection.get_edns_payload();
         let (io_loop, stream, stream_handle) = client_connection.unwrap();
 
-        let client = ClientFuture::new(stream, stream_handle, io_loop.handle(), None);
+        let mut client =
+            ClientFuture::with_timeouts(stream, stream_handle, io_loop.handle(), timeouts, None);
+        client.set_edns_payload(edns_payload);
 
         SyncClient {
             client_handle: RefCell::new(client),
@@ -369,9 +754,16 @@ impl SyncClient {
     /// * `signer` - signer to use, this needs an associated private key
   pub fn with_signer<CC: ClientConnection>(client_connection: CC, signer: Signer) -> SyncClient
   where <CC as ClientConnection>::MessageStream: Stream<Item=Vec<u8>, Error=io::Error> + 'static {
+        let timeouts = client_connection.get_timeouts();
+        let edns_payload = client_connection.get_edns_payload();
         let (io_loop, stream, stream_handle) = client_connection.unwrap();
 
-        let client = ClientFuture::new(stream, stream_handle, io_loop.handle(), Some(signer));
+        let mut client = ClientFuture::with_timeouts(stream,
+                                                     stream_handle,
+                                                     io_loop.handle(),
+                                                     timeouts,
+                                                     Some(signer));
+        client.set_edns_payload(edns_payload);
 
         SyncClient {
             client_handle: RefCell::new(client),
@@ -380,12 +772,118 @@ impl SyncClient {
     }
 }
 
-impl Client<BasicClientHandle> for SyncClient {
+impl SyncClient {
+    /// Sends the message, returning the response along with the bytes exactly as received
+    ///
+    /// See `BasicClientHandle::send_raw`
+    ///
+    /// # Arguments
+    ///
+    /// * `message` - the fully constructed Message to send, the query id will be rewritten
+    pub fn send_raw(&self, message: Message) -> ClientResult<(Message, Vec<u8>)> {
+        self.io_loop.borrow_mut().run(self.client_handle.borrow_mut().send_raw(message))
+    }
+
+    /// Attaches the client subnet, RFC 7871, to all subsequent queries
+    ///
+    /// See `BasicClientHandle::set_client_subnet`
+    ///
+    /// # Arguments
+    ///
+    /// * `client_subnet` - the subnet on whose behalf the queries are made, None for no subnet
+    pub fn set_client_subnet(&self, client_subnet: Option<ClientSubnet>) {
+        self.client_handle.borrow_mut().set_client_subnet(client_subnet)
+    }
+}
+
+impl SyncClient<FailoverClientHandle<BasicClientHandle>> {
+    /// Creates a new DNS client over UDP which sends each query to the name servers in turn,
+    ///  until one responds
+    ///
+    /// # Arguments
+    ///
+    /// * `name_servers` - addresses of the name servers to use for queries, in order
+    /// * `timeouts` - only the query timeout applies to UDP, it is that of each attempt
+    /// * `policy` - the retries, rotation between the name servers, and backoff
+    pub fn with_failover(name_servers: &[SocketAddr],
+                         timeouts: TimeoutConfig,
+                         policy: RetryPolicy)
+                         -> ClientResult<Self> {
+        let io_loop = try!(Core::new());
+        let clients = name_servers.iter()
+            .map(|name_server| {
+                let (stream, stream_handle) = UdpClientStream::new(*name_server, io_loop.handle());
+                ClientFuture::with_timeouts(stream, stream_handle, io_loop.handle(), timeouts, None)
+            })
+            .collect();
+        let client = FailoverClientHandle::new(clients, policy, io_loop.handle());
+
+        Ok(SyncClient {
+            client_handle: RefCell::new(client),
+            io_loop: RefCell::new(io_loop),
+        })
+    }
+}
+
+impl SyncClient<EdnsClientHandle<BasicClientHandle>> {
+    /// Creates a new DNS client which advertises the EDNS payload size of the connection, see
+    ///  `ClientConnection::get_edns_payload` and `EdnsClientHandle`
+    ///
+    /// # Arguments
+    ///
+    /// * `client_connection` - the client_connection to use for all communication, without an
+    ///                         EDNS payload size `FALLBACK_EDNS_PAYLOAD` is advertised
+    pub fn with_edns<CC>(client_connection: CC) -> Self
+        where CC: ClientConnection,
+              <CC as ClientConnection>::MessageStream: Stream<Item = Vec<u8>, Error = io::Error> +
+                                                       'static
+    {
+        let max_payload = client_connection.get_edns_payload().unwrap_or(FALLBACK_EDNS_PAYLOAD);
+        let timeouts = client_connection.get_timeouts();
+        let (io_loop, stream, stream_handle) = client_connection.unwrap();
+
+        let client =
+            ClientFuture::with_timeouts(stream, stream_handle, io_loop.handle(), timeouts, None);
+
+        SyncClient {
+            client_handle: RefCell::new(EdnsClientHandle::new(client, max_payload)),
+            io_loop: RefCell::new(io_loop),
+        }
+    }
+}
+
+impl SyncClient<TcpFallbackClientHandle<BasicClientHandle, PerQueryTcpClientHandle>> {
+    /// Creates a new DNS client over UDP which retries the queries over TCP, to the same name
+    ///  server, when their responses are truncated, see `TcpFallbackClientHandle`
+    ///
+    /// Each query retried over TCP is sent over a new connection, see `PerQueryTcpClientHandle`,
+    ///  with the timeouts of the UDP connection.
+    ///
+    /// # Arguments
+    ///
+    /// * `client_connection` - the UDP connection to send the queries over first
+    pub fn with_tcp_fallback(client_connection: UdpClientConnection) -> Self {
+        let name_server = client_connection.get_name_server();
+        let timeouts = client_connection.get_timeouts();
+        let (io_loop, stream, stream_handle) = client_connection.unwrap();
+
+        let udp_client =
+            ClientFuture::with_timeouts(stream, stream_handle, io_loop.handle(), timeouts, None);
+        let tcp_client = PerQueryTcpClientHandle::new(name_server, io_loop.handle(), timeouts);
+
+        SyncClient {
+            client_handle: RefCell::new(TcpFallbackClientHandle::new(udp_client, tcp_client)),
+            io_loop: RefCell::new(io_loop),
+        }
+    }
+}
+
+impl<H: ClientHandle> Client<H> for SyncClient<H> {
     fn get_io_loop(&self) -> RefMut<Core> {
         self.io_loop.borrow_mut()
     }
 
-    fn get_client_handle(&self) -> RefMut<BasicClientHandle> {
+    fn get_client_handle(&self) -> RefMut<H> {
         self.client_handle.borrow_mut()
     }
 }
@@ -492,13 +990,17 @@ where CC: ClientConnection,
   }
 
   pub fn build(self) -> SecureSyncClient {
+    let timeouts = self.client_connection.get_timeouts();
+    let edns_payload = self.client_connection.get_edns_payload();
     let (io_loop, stream, stream_handle) = self.client_connection.unwrap();
 
-    let client = ClientFuture::new(
+    let mut client = ClientFuture::with_timeouts(
       stream,
       stream_handle,
       io_loop.handle(),
+      timeouts,
       self.signer);
+    client.set_edns_payload(edns_payload);
 
     let client = SecureClientHandle::with_trust_anchor(client, self.trust_anchor.unwrap_or(Default::default()));
 
@@ -19,11 +19,22 @@ use std::io;
 use futures::Future;
 use tokio_core::reactor::Core;
 
-use client::ClientStreamHandle;
+use client::{ClientStreamHandle, TimeoutConfig};
 
 /// Trait for client connections
 pub trait ClientConnection: Sized {
     type MessageStream;
 
+    /// The timeouts for the `ClientFuture` driving this connection
+    fn get_timeouts(&self) -> TimeoutConfig {
+        TimeoutConfig::default()
+    }
+
+    /// The UDP payload size to advertise with EDNS, see `BasicClientHandle::set_edns_payload` and
+    ///  `SyncClient::with_edns`, None for the default of the client
+    fn get_edns_payload(&self) -> Option<u16> {
+        None
+    }
+
     fn unwrap(self) -> (Core, Box<Future<Item=Self::MessageStream, Error=io::Error>>, Box<ClientStreamHandle>);
 }
@@ -5,12 +5,15 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
-use std::collections::{HashMap, HashSet};
+use std::cmp;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::io;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::time::Duration;
 
-use chrono::UTC;
-use futures::{Async, Complete, Future, Poll, task};
+use futures::{failed, Async, Complete, Future, Poll, task};
 use futures::IntoFuture;
 use futures::stream::{Peekable, Fuse as StreamFuse, Stream};
 use futures::sync::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
@@ -20,11 +23,15 @@ use rand::Rng;
 use rand;
 use tokio_core::reactor::{Handle, Timeout};
 
+use client::{OutstandingConfig, OverflowPolicy, TimeoutConfig};
+use client::lookup_ip::merge_responses;
+use clock::{Clock, SystemClock};
 use ::error::*;
-use op::{Message, MessageType, OpCode, Query, UpdateMessage};
+use op::{IxfrResponse, Message, MessageType, OpCode, Query, ResponseCode, UpdateMessage};
 use rr::{domain, DNSClass, IntoRecordSet, RData, Record, RecordType};
 use rr::dnssec::Signer;
-use rr::rdata::NULL;
+use rr::rdata::{NULL, SOA};
+use rr::rdata::opt::{ClientSubnet, EdnsCode, EdnsOption};
 
 const QOS_MAX_RECEIVE_MSGS: usize = 100; // max number of messages to receive from the UDP socket
 
@@ -42,6 +49,102 @@ impl ClientStreamHandle for StreamHandle {
     }
 }
 
+/// A response along with the bytes it was decoded from
+type RawResult = ClientResult<(Message, Vec<u8>)>;
+
+/// A message to send, with the timeout overriding the default for its query, if any
+type Request = (Message, Option<Duration>, Complete<RawResult>);
+
+/// An outstanding query
+struct ActiveRequest {
+    complete: Complete<RawResult>,
+    timeout: Option<Timeout>,
+    /// the order it was sent in, for `OverflowPolicy::DropOldest`
+    sent: u64,
+    /// the question the response must echo
+    queries: Vec<Query>,
+    /// the messages received so far of a zone transfer, which may span several
+    transfer: Option<Transfer>,
+}
+
+/// The messages of a response to an AXFR or IXFR query, which are received until the SOA of the
+///  zone ends it, RFC 5936 section 2.2 and RFC 1995 section 4
+struct Transfer {
+    /// the serial of the SOA of an IXFR query, None for AXFR
+    request_serial: Option<u32>,
+    /// the first message, the answers of the later ones are appended to it
+    response: Option<(Message, Vec<u8>)>,
+}
+
+impl Transfer {
+    /// The transfer of the query, None if it is not an AXFR or IXFR
+    fn for_query(message: &Message) -> Option<Transfer> {
+        let request_serial = match message.get_queries().first().map(Query::get_query_type) {
+            Some(RecordType::AXFR) => None,
+            Some(RecordType::IXFR) => {
+                message.get_name_servers().iter().filter_map(soa_serial).next()
+            }
+            _ => return None,
+        };
+
+        Some(Transfer {
+            request_serial: request_serial,
+            response: None,
+        })
+    }
+
+    /// Adds the message to the response, returning it once it is complete, with the bytes of the
+    ///  first message
+    fn add(&mut self, mut message: Message, buffer: Vec<u8>) -> Option<(Message, Vec<u8>)> {
+        // an error ends the transfer
+        if message.get_response_code() != ResponseCode::NoError {
+            return Some((message, buffer));
+        }
+
+        let response = match self.response.take() {
+            Some((mut response, response_buffer)) => {
+                response.add_answers(message.take_answers());
+                (response, response_buffer)
+            }
+            None => (message, buffer),
+        };
+
+        if self.is_complete(response.0.get_answers()) {
+            Some(response)
+        } else {
+            self.response = Some(response);
+            None
+        }
+    }
+
+    /// Only the first and the last answers are looked at, a long transfer is not read again with
+    ///  each message
+    fn is_complete(&self, answers: &[Record]) -> bool {
+        let serial = match answers.first().and_then(soa_serial) {
+            Some(serial) => serial,
+            // not a transfer, it is left to the caller to reject
+            None => return true,
+        };
+
+        // an IXFR for a zone which has not changed since, with serial number arithmetic, the
+        //  first message of an AXFR, or of an IXFR to a newer serial, may hold only the SOA too
+        if answers.len() == 1 {
+            return self.request_serial
+                .map_or(false, |request_serial| serial.wrapping_sub(request_serial) as i32 <= 0);
+        }
+
+        answers.last().and_then(soa_serial) == Some(serial)
+    }
+}
+
+/// The serial of the SOA record, None for other records
+fn soa_serial(record: &Record) -> Option<u32> {
+    match *record.get_rdata() {
+        RData::SOA(ref soa) => Some(soa.get_serial()),
+        _ => None,
+    }
+}
+
 /// A DNS Client implemented over futures-rs.
 ///
 /// This Client is generic and capable of wrapping UDP, TCP, and other underlying DNS protocol
@@ -53,15 +156,23 @@ pub struct ClientFuture<S: Stream<Item = Vec<u8>, Error = io::Error>> {
     timeout_duration: Duration,
     // TODO genericize and remove this Box
     stream_handle: Box<ClientStreamHandle>,
-    new_receiver: Peekable<StreamFuse<UnboundedReceiver<(Message,
-                                                         Complete<ClientResult<Message>>)>>>,
-    active_requests: HashMap<u16, (Complete<ClientResult<Message>>, Timeout)>,
+    new_receiver: Peekable<StreamFuse<UnboundedReceiver<Request>>>,
+    active_requests: HashMap<u16, ActiveRequest>,
+    sent_requests: u64,
+    /// the ids of the requests in the order they were sent, with their `ActiveRequest::sent`, for
+    ///  `OverflowPolicy::DropOldest`; those already answered are skipped, ids are reused
+    sent_order: VecDeque<(u16, u64)>,
+    outstanding: OutstandingConfig,
+    stats: Arc<OutstandingStats>,
     // TODO: Maybe make a typed version of ClientFuture for Updates?
     signer: Option<Signer>,
+    /// the inception time of the SIG(0) of updates
+    clock: Arc<Clock>,
 }
 
 impl<S: Stream<Item = Vec<u8>, Error = io::Error> + 'static> ClientFuture<S> {
-    /// Spawns a new ClientFuture Stream. This uses a default timeout of 5 seconds for all requests.
+    /// Spawns a new ClientFuture Stream. This uses the default `TimeoutConfig`, i.e. 5 seconds for
+    ///  connecting and for each request.
     ///
     /// # Arguments
     ///
@@ -76,11 +187,11 @@ impl<S: Stream<Item = Vec<u8>, Error = io::Error> + 'static> ClientFuture<S> {
                loop_handle: Handle,
                signer: Option<Signer>)
                -> BasicClientHandle {
-        Self::with_timeout(stream,
-                           stream_handle,
-                           loop_handle,
-                           Duration::from_secs(5),
-                           signer)
+        Self::with_timeouts(stream,
+                            stream_handle,
+                            loop_handle,
+                            TimeoutConfig::default(),
+                            signer)
     }
 
     /// Spawns a new ClientFuture Stream.
@@ -101,7 +212,88 @@ impl<S: Stream<Item = Vec<u8>, Error = io::Error> + 'static> ClientFuture<S> {
                         timeout_duration: Duration,
                         signer: Option<Signer>)
                         -> BasicClientHandle {
+        Self::with_timeouts(stream,
+                            stream_handle,
+                            loop_handle,
+                            TimeoutConfig { query: timeout_duration, ..TimeoutConfig::default() },
+                            signer)
+    }
+
+    /// Spawns a new ClientFuture Stream.
+    ///
+    /// # Arguments
+    ///
+    /// * `stream` - A stream of bytes that can be used to send/receive DNS messages
+    ///              (see TcpClientStream or UdpClientStream)
+    /// * `loop_handle` - A Handle to the Tokio reactor Core, this is the Core on which the
+    ///                   the Stream will be spawned
+    /// * `timeouts` - the `connect` timeout applies to `stream`, and `query` is the time to wait
+    ///                for a response before canceling the request. The read and write timeouts
+    ///                are applied by the stream, see e.g. `TcpClientStream::with_timeouts()`.
+    /// * `stream_handle` - The handle for the `stream` on which bytes can be sent/received.
+    /// * `signer` - An optional signer for requests, needed for Updates with Sig0, otherwise not needed
+    pub fn with_timeouts(stream: Box<Future<Item = S, Error = io::Error>>,
+                         stream_handle: Box<ClientStreamHandle>,
+                         loop_handle: Handle,
+                         timeouts: TimeoutConfig,
+                         signer: Option<Signer>)
+                         -> BasicClientHandle {
+        Self::with_outstanding(stream,
+                               stream_handle,
+                               loop_handle,
+                               timeouts,
+                               OutstandingConfig::default(),
+                               signer)
+    }
+
+    /// Spawns a new ClientFuture Stream, with the bound on the queries awaiting their responses.
+    ///
+    /// # Arguments
+    ///
+    /// * `stream` - A stream of bytes that can be used to send/receive DNS messages
+    ///              (see TcpClientStream or UdpClientStream)
+    /// * `stream_handle` - The handle for the `stream` on which bytes can be sent/received.
+    /// * `loop_handle` - A Handle to the Tokio reactor Core, this is the Core on which the
+    ///                   the Stream will be spawned
+    /// * `timeouts` - see `with_timeouts()`
+    /// * `outstanding` - the most queries awaiting their responses, and what happens to those
+    ///                   beyond, see `BasicClientHandle::get_outstanding_stats()` for the counts
+    /// * `signer` - An optional signer for requests, needed for Updates with Sig0, otherwise not needed
+    pub fn with_outstanding(stream: Box<Future<Item = S, Error = io::Error>>,
+                            stream_handle: Box<ClientStreamHandle>,
+                            loop_handle: Handle,
+                            timeouts: TimeoutConfig,
+                            outstanding: OutstandingConfig,
+                            signer: Option<Signer>)
+                            -> BasicClientHandle {
+        Self::with_clock(stream,
+                         stream_handle,
+                         loop_handle,
+                         timeouts,
+                         outstanding,
+                         signer,
+                         Arc::new(SystemClock))
+    }
+
+    /// Spawns a new ClientFuture Stream, signing updates at the time of the clock
+    ///
+    /// # Arguments
+    ///
+    /// * `clock` - the source of the inception time of the SIG(0) of updates, see
+    ///             `Message::sign()`, the other arguments are those of `with_outstanding()`
+    pub fn with_clock(stream: Box<Future<Item = S, Error = io::Error>>,
+                      stream_handle: Box<ClientStreamHandle>,
+                      loop_handle: Handle,
+                      timeouts: TimeoutConfig,
+                      outstanding: OutstandingConfig,
+                      signer: Option<Signer>,
+                      clock: Arc<Clock>)
+                      -> BasicClientHandle {
         let (sender, rx) = unbounded();
+        let stats = Arc::new(OutstandingStats::default());
+        let stats_clone = stats.clone();
+        let stream = connect_timeout(stream, timeouts.connect, &loop_handle);
+        let timeout_duration = timeouts.query;
 
         let loop_handle_clone = loop_handle.clone();
         loop_handle.spawn(stream.map(move |stream| {
@@ -112,7 +304,12 @@ impl<S: Stream<Item = Vec<u8>, Error = io::Error> + 'static> ClientFuture<S> {
                     stream_handle: stream_handle,
                     new_receiver: rx.fuse().peekable(),
                     active_requests: HashMap::new(),
+                    sent_requests: 0,
+                    sent_order: VecDeque::new(),
+                    outstanding: outstanding,
+                    stats: stats_clone,
                     signer: signer,
+                    clock: clock,
                 }
             })
             .flatten()
@@ -120,7 +317,21 @@ impl<S: Stream<Item = Vec<u8>, Error = io::Error> + 'static> ClientFuture<S> {
                 error!("error in Client: {}", e);
             }));
 
-        BasicClientHandle { message_sender: sender }
+        BasicClientHandle {
+            message_sender: sender,
+            client_subnet: None,
+            edns_payload: None,
+            stats: stats,
+        }
+    }
+
+    /// creates the Timeout for a query, none if it is disabled with a zero duration
+    fn query_timeout(&self, timeout_duration: Duration) -> io::Result<Option<Timeout>> {
+        if timeout_duration > Duration::from_millis(0) {
+            Ok(Some(try!(Timeout::new(timeout_duration, &self.reactor_handle))))
+        } else {
+            Ok(None)
+        }
     }
 
     /// loop over active_requests and remove cancelled requests
@@ -128,12 +339,16 @@ impl<S: Stream<Item = Vec<u8>, Error = io::Error> + 'static> ClientFuture<S> {
     fn drop_cancelled(&mut self) {
         // TODO: should we have a timeout here? or always expect the caller to do this?
         let mut canceled = HashSet::new();
-        for (&id, &mut (ref mut req, ref mut timeout)) in self.active_requests.iter_mut() {
-            if let Ok(Async::Ready(())) = req.poll_cancel() {
+        for (&id, request) in self.active_requests.iter_mut() {
+            if let Ok(Async::Ready(())) = request.complete.poll_cancel() {
         canceled.insert(id);
       }
 
             // check for timeouts...
+            let timeout = match request.timeout {
+                Some(ref mut timeout) => timeout,
+                None => continue,
+            };
             match timeout.poll() {
                 Ok(Async::Ready(_)) => {
                     warn!("request timeout: {}", id);
@@ -149,17 +364,67 @@ impl<S: Stream<Item = Vec<u8>, Error = io::Error> + 'static> ClientFuture<S> {
 
         // drop all the canceled requests
         for id in canceled {
-            if let Some((req, _)) = self.active_requests.remove(&id) {
+            if let Some(request) = self.active_requests.remove(&id) {
                 // TODO, perhaps there is a different reason timeout? but there shouldn't be...
                 //  being lazy and always returning timeout in this case (if it was canceled then the
                 //  then the otherside isn't really paying attention anyway)
 
                 // complete the request, it's failed...
-                req.complete(Err(ClientErrorKind::Timeout.into()));
+                request.complete.complete(Err(ClientErrorKind::Timeout.into()));
             }
         }
     }
 
+    /// makes room for a new request at the maximum of outstanding requests, returns false if
+    ///  the new request is to be rejected
+    fn make_room(&mut self) -> bool {
+        let max_outstanding = cmp::max(self.outstanding.max_outstanding, 1);
+        if self.active_requests.len() < max_outstanding {
+            return true;
+        }
+
+        match self.outstanding.overflow {
+            OverflowPolicy::RejectNew => {
+                warn!("outstanding requests at maximum: {}, rejecting", max_outstanding);
+                self.stats.rejected.fetch_add(1, Ordering::Relaxed);
+                false
+            }
+            OverflowPolicy::DropOldest => {
+                let mut oldest = None;
+                while let Some((id, sent)) = self.sent_order.pop_front() {
+                    if self.active_requests.get(&id).map_or(false, |request| request.sent == sent) {
+                        oldest = self.active_requests.remove(&id);
+                        break;
+                    }
+                }
+
+                if let Some(request) = oldest {
+                    warn!("outstanding requests at maximum: {}, dropping oldest", max_outstanding);
+                    self.stats.dropped.fetch_add(1, Ordering::Relaxed);
+                    request.complete
+                        .complete(Err(ClientErrorKind::TooManyOutstanding(max_outstanding).into()));
+                }
+                true
+            }
+        }
+    }
+
+    /// remembers the order of the requests, forgetting those answered once they outnumber the
+    ///  outstanding ones, so that the queue stays in proportion to them
+    fn track_sent(&mut self, id: u16) {
+        if self.outstanding.overflow != OverflowPolicy::DropOldest {
+            return;
+        }
+
+        self.sent_order.push_back((id, self.sent_requests));
+        if self.sent_order.len() > 2 * self.active_requests.len() {
+            let active_requests = &self.active_requests;
+            self.sent_order.retain(|&(id, sent)| {
+                active_requests.get(&id).map_or(false, |request| request.sent == sent)
+            });
+        }
+    }
+
     /// creates random query_id, validates against all active queries
     fn next_random_query_id(&self) -> Async<u16> {
         let mut rand = rand::thread_rng();
@@ -207,7 +472,7 @@ impl<S: Stream<Item = Vec<u8>, Error = io::Error> + 'static> Future for ClientFu
 
             // finally pop the reciever
             match self.new_receiver.poll() {
-                Ok(Async::Ready(Some((mut message, complete)))) => {
+                Ok(Async::Ready(Some((mut message, timeout_duration, complete)))) => {
                     // if there was a message, and the above succesion was succesful,
                     //  register the new message, if not do not register, and set the complete to error.
                     // getting a random query id, this mitigates potential cache poisoning.
@@ -215,11 +480,18 @@ impl<S: Stream<Item = Vec<u8>, Error = io::Error> + 'static> Future for ClientFu
                     let query_id = query_id.expect("query_id should have been set above");
                     message.id(query_id);
 
+                    if !self.make_room() {
+                        let max_outstanding = cmp::max(self.outstanding.max_outstanding, 1);
+                        complete.complete(Err(ClientErrorKind::TooManyOutstanding(max_outstanding)
+                            .into()));
+                        continue; // to the next message...
+                    }
+
                     // update messages need to be signed.
                     if let OpCode::Update = message.get_op_code() {
                         if let Some(ref signer) = self.signer {
                             // TODO: it's too bad this happens here...
-                            if let Err(e) = message.sign(signer, UTC::now().timestamp() as u32) {
+                            if let Err(e) = message.sign(signer, self.clock.now()) {
                                 warn!("could not sign message: {}", e);
                                 complete.complete(Err(e.into()));
                                 continue; // to the next message...
@@ -228,7 +500,8 @@ impl<S: Stream<Item = Vec<u8>, Error = io::Error> + 'static> Future for ClientFu
                     }
 
                     // store a Timeout for this message before sending
-                    let timeout = match Timeout::new(self.timeout_duration, &self.reactor_handle) {
+                    let timeout_duration = timeout_duration.unwrap_or(self.timeout_duration);
+                    let timeout = match self.query_timeout(timeout_duration) {
                         Ok(timeout) => timeout,
                         Err(e) => {
                             warn!("could not create timer: {}", e);
@@ -244,7 +517,18 @@ impl<S: Stream<Item = Vec<u8>, Error = io::Error> + 'static> Future for ClientFu
                             try!(self.stream_handle.send(buffer));
                             // add to the map -after- the client send b/c we don't want to put it in the map if
                             //  we ended up returning from the send.
-                            self.active_requests.insert(message.get_id(), (complete, timeout));
+                            self.sent_requests += 1;
+                            self.active_requests.insert(message.get_id(),
+                                                        ActiveRequest {
+                                                            complete: complete,
+                                                            timeout: timeout,
+                                                            sent: self.sent_requests,
+                                                            queries: message.get_queries()
+                                                                .to_vec(),
+                                                            transfer: Transfer::for_query(&message),
+                                                        });
+                            self.track_sent(message.get_id());
+                            self.stats.set_outstanding(self.active_requests.len());
                         }
                         Err(e) => {
                             debug!("error message id: {} error: {}", query_id, e);
@@ -273,9 +557,40 @@ impl<S: Stream<Item = Vec<u8>, Error = io::Error> + 'static> Future for ClientFu
                     //   deserialize or log decode_error
                     match Message::from_vec(&buffer) {
                         Ok(message) => {
-                            match self.active_requests.remove(&message.get_id()) {
-                                Some((complete, _)) => complete.complete(Ok(message)),
-                                None => debug!("unexpected request_id: {}", message.get_id()),
+                            let id = message.get_id();
+                            let echoes = match self.active_requests.get(&id) {
+                                Some(request) => {
+                                    is_echo(&request.queries, &message) ||
+                                    // the later messages of a transfer may omit the question
+                                    (message.get_queries().is_empty() &&
+                                     request.transfer
+                                        .as_ref()
+                                        .map_or(false, |transfer| transfer.response.is_some()))
+                                }
+                                None => {
+                                    debug!("unexpected request_id: {}", id);
+                                    continue;
+                                }
+                            };
+
+                            // possibly spoofed, the request waits for the genuine response
+                            if !echoes {
+                                debug!("response id: {} does not echo the question, ignoring", id);
+                                continue;
+                            }
+
+                            // the messages of a transfer are received until it is complete
+                            let response = match self.active_requests
+                                .get_mut(&id)
+                                .and_then(|request| request.transfer.as_mut()) {
+                                Some(transfer) => transfer.add(message, buffer),
+                                None => Some((message, buffer)),
+                            };
+
+                            if let Some(response) = response {
+                                if let Some(request) = self.active_requests.remove(&id) {
+                                    request.complete.complete(Ok(response));
+                                }
                             }
                         }
                         // TODO: return src address for diagnostics
@@ -288,6 +603,8 @@ impl<S: Stream<Item = Vec<u8>, Error = io::Error> + 'static> Future for ClientFu
             }
         }
 
+        self.stats.set_outstanding(self.active_requests.len());
+
         // Clean shutdown happens when all pending requests are done and the
         // incoming channel has been closed (e.g. you'll never receive another
         // request). try! will early return the error...
@@ -312,6 +629,127 @@ impl<S: Stream<Item = Vec<u8>, Error = io::Error> + 'static> Future for ClientFu
     }
 }
 
+/// Returns true if the response echoes the question of the request, as a response forged with
+///  a guessed ID is unlikely to
+///
+/// The names are compared without case. Error responses, other than NXDOMAIN, may omit the
+///  question, as e.g. a FORMERR to a request which could not be parsed does.
+fn is_echo(queries: &[Query], response: &Message) -> bool {
+    let echoed = response.get_queries();
+    if echoed == queries {
+        return true;
+    }
+
+    echoed.is_empty() &&
+    match response.get_response_code() {
+        ResponseCode::NoError | ResponseCode::NXDomain => false,
+        _ => true,
+    }
+}
+
+/// Fails the connection if it is not established within the timeout, unless it is zero
+fn connect_timeout<S: 'static>(stream: Box<Future<Item = S, Error = io::Error>>,
+                               timeout_duration: Duration,
+                               loop_handle: &Handle)
+                               -> Box<Future<Item = S, Error = io::Error>> {
+    if timeout_duration == Duration::from_millis(0) {
+        return stream;
+    }
+
+    let timeout = match Timeout::new(timeout_duration, loop_handle) {
+        Ok(timeout) => timeout,
+        Err(e) => return Box::new(failed(e)),
+    };
+
+    let timeout = timeout.and_then(move |()| -> io::Result<S> {
+        Err(io::Error::new(io::ErrorKind::TimedOut,
+                           format!("connection not established within {:?}", timeout_duration)))
+    });
+
+    Box::new(stream.select(timeout).map(|(stream, _)| stream).map_err(|(e, _)| e))
+}
+
+/// Builds the NOTIFY of a change to the records of a zone, see `ClientHandle::notify`
+pub fn notify_message<R>(name: domain::Name,
+                         query_class: DNSClass,
+                         query_type: RecordType,
+                         rrset: Option<R>)
+                         -> Message
+    where R: IntoRecordSet
+{
+    let mut message: Message = Message::new();
+    let id: u16 = rand::random();
+    message.id(id)
+       // 3.3. NOTIFY is similar to QUERY in that it has a request message with
+       // the header QR flag "clear" and a response message with QR "set".  The
+       // response message contains no useful information, but its reception by
+       // the master is an indication that the slave has received the NOTIFY
+       // and that the master can remove the slave from any retry queue for
+       // this NOTIFY event.
+       .message_type(MessageType::Query)
+       .op_code(OpCode::Notify);
+
+    // Extended dns
+    {
+        let edns = message.get_edns_mut();
+        edns.set_max_payload(1500);
+        edns.set_version(0);
+    }
+
+    // add the query
+    let mut query: Query = Query::new();
+    query.name(name).query_class(query_class).query_type(query_type);
+    message.add_query(query);
+
+    // add the notify message, see https://tools.ietf.org/html/rfc1996, section 3.7
+    if let Some(rrset) = rrset {
+        message.add_answers(rrset.into_record_set());
+    }
+
+    message
+}
+
+/// The counts of the outstanding queries of a `ClientFuture`, see
+///  `BasicClientHandle::get_outstanding_stats()`
+#[derive(Debug, Default)]
+pub struct OutstandingStats {
+    outstanding: AtomicUsize,
+    peak: AtomicUsize,
+    rejected: AtomicUsize,
+    dropped: AtomicUsize,
+}
+
+impl OutstandingStats {
+    /// Queries currently awaiting their responses
+    pub fn get_outstanding(&self) -> usize {
+        self.outstanding.load(Ordering::Relaxed)
+    }
+
+    /// The most queries which awaited their responses at once
+    pub fn get_peak(&self) -> usize {
+        self.peak.load(Ordering::Relaxed)
+    }
+
+    /// New queries failed at the maximum with `OverflowPolicy::RejectNew`
+    pub fn get_rejected(&self) -> usize {
+        self.rejected.load(Ordering::Relaxed)
+    }
+
+    /// Outstanding queries failed at the maximum with `OverflowPolicy::DropOldest`
+    pub fn get_dropped(&self) -> usize {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Records the current number of outstanding queries, and the peak
+    fn set_outstanding(&self, outstanding: usize) {
+        self.outstanding.store(outstanding, Ordering::Relaxed);
+        // only the ClientFuture records, there is no race between the load and the store
+        if outstanding > self.peak.load(Ordering::Relaxed) {
+            self.peak.store(outstanding, Ordering::Relaxed);
+        }
+    }
+}
+
 /// Root ClientHandle implementaton returned by ClientFuture
 ///
 /// This can be used directly to perform queries. See `trust_dns::client::SecureClientHandle` for
@@ -319,15 +757,99 @@ impl<S: Stream<Item = Vec<u8>, Error = io::Error> + 'static> Future for ClientFu
 #[derive(Clone)]
 #[must_use = "queries can only be sent through a ClientHandle"]
 pub struct BasicClientHandle {
-    message_sender: UnboundedSender<(Message, Complete<ClientResult<Message>>)>,
+    message_sender: UnboundedSender<Request>,
+    client_subnet: Option<ClientSubnet>,
+    edns_payload: Option<u16>,
+    stats: Arc<OutstandingStats>,
 }
 
-impl ClientHandle for BasicClientHandle {
-    fn send(&mut self, message: Message) -> Box<Future<Item = Message, Error = ClientError>> {
+impl BasicClientHandle {
+    /// Attaches the client subnet, RFC 7871, to all subsequent queries sent through this handle,
+    ///  and clones of it made afterwards, unless the query carries one already
+    ///
+    /// # Arguments
+    ///
+    /// * `client_subnet` - the subnet of the client on whose behalf the queries are made, None
+    ///                     to send the queries without one
+    pub fn set_client_subnet(&mut self, client_subnet: Option<ClientSubnet>) {
+        self.client_subnet = client_subnet;
+    }
+
+    /// The client subnet attached to queries, see `set_client_subnet`
+    pub fn get_client_subnet(&self) -> Option<&ClientSubnet> {
+        self.client_subnet.as_ref()
+    }
+
+    /// Advertises the UDP payload size with EDNS in all subsequent messages sent through this
+    ///  handle, and clones of it made afterwards, see `EdnsClientHandle` to also lower it for a
+    ///  name server which fails with it
+    ///
+    /// # Arguments
+    ///
+    /// * `edns_payload` - the UDP payload size to advertise, None to send the messages as they are
+    pub fn set_edns_payload(&mut self, edns_payload: Option<u16>) {
+        self.edns_payload = edns_payload;
+    }
+
+    /// The UDP payload size advertised, see `set_edns_payload`
+    pub fn get_edns_payload(&self) -> Option<u16> {
+        self.edns_payload
+    }
+
+    /// The counts of the queries awaiting their responses in the `ClientFuture`, shared by all
+    ///  clones of this handle, see `OutstandingConfig`
+    pub fn get_outstanding_stats(&self) -> &OutstandingStats {
+        &self.stats
+    }
+
+    /// Sends the message, waiting for the response for the specified time rather than the query
+    ///  timeout of the `ClientFuture`
+    ///
+    /// # Arguments
+    ///
+    /// * `message` - the fully constructed Message to send, the query id will be rewritten
+    /// * `timeout` - time to wait for the response, zero waits until the response arrives or the
+    ///               connection fails
+    pub fn send_with_timeout(&mut self,
+                             message: Message,
+                             timeout: Duration)
+                             -> Box<Future<Item = Message, Error = ClientError>> {
+        Box::new(self.send_request(message, Some(timeout)).map(|(message, _)| message))
+    }
+
+    /// Sends the message, returning the response along with the bytes exactly as received
+    ///
+    /// This is for diagnostics, e.g. to dump the response and compare it with how it was parsed.
+    ///
+    /// # Arguments
+    ///
+    /// * `message` - the fully constructed Message to send, the query id will be rewritten
+    pub fn send_raw(&mut self,
+                    message: Message)
+                    -> Box<Future<Item = (Message, Vec<u8>), Error = ClientError>> {
+        self.send_request(message, None)
+    }
+
+    fn send_request(&mut self,
+                    mut message: Message,
+                    timeout: Option<Duration>)
+                    -> Box<Future<Item = (Message, Vec<u8>), Error = ClientError>> {
+        if let Some(max_payload) = self.edns_payload {
+            message.get_edns_mut().set_max_payload(max_payload);
+        }
+
+        if let Some(ref client_subnet) = self.client_subnet {
+            if message.get_message_type() == MessageType::Query &&
+               message.get_op_code() == OpCode::Query &&
+               message.get_edns().and_then(|edns| edns.get_option(&EdnsCode::Subnet)).is_none() {
+                message.get_edns_mut().set_option(EdnsOption::ClientSubnet(client_subnet.clone()));
+            }
+        }
+
         let (complete, receiver) = oneshot::channel();
         let message_sender: &mut _ = &mut self.message_sender;
 
-        let receiver = match message_sender.send((message, complete)) {
+        let receiver = match message_sender.send((message, timeout, complete)) {
             Ok(()) => receiver,
             Err(e) => {
                 let (complete, receiver) = oneshot::channel();
@@ -343,6 +865,12 @@ impl ClientHandle for BasicClientHandle {
     }
 }
 
+impl ClientHandle for BasicClientHandle {
+    fn send(&mut self, message: Message) -> Box<Future<Item = Message, Error = ClientError>> {
+        Box::new(self.send_raw(message).map(|(message, _)| message))
+    }
+}
+
 /// A trait for implementing high level functions of DNS.
 #[must_use = "queries can only be sent through a ClientHandle"]
 pub trait ClientHandle: Clone {
@@ -396,6 +924,27 @@ pub trait ClientHandle: Clone {
         self.send(message)
     }
 
+    /// Looks up the IPv4 and IPv6 addresses of the name, with the A and AAAA queries sent at once
+    ///
+    /// The addresses of both are merged and ordered for connecting, IPv6 first, see
+    ///  `client::sort_addresses`. If either query fails, the addresses of the other are returned;
+    ///  it is an error only if both fail. CNAMEs are not followed by the client, the addresses of
+    ///  the targets of the CNAMEs in the answers are taken as they are.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - the name of the host
+    fn lookup_ip(&mut self,
+                 name: domain::Name)
+                 -> Box<Future<Item = Vec<IpAddr>, Error = ClientError>> {
+        let aaaa = self.query(name.clone(), DNSClass::IN, RecordType::AAAA)
+            .then(|result| Ok::<_, ClientError>(result));
+        let a = self.query(name, DNSClass::IN, RecordType::A)
+            .then(|result| Ok::<_, ClientError>(result));
+
+        Box::new(aaaa.join(a).and_then(|(aaaa, a)| merge_responses(aaaa, a)))
+    }
+
     /// Sends a NOTIFY message to the remote system
     ///
     /// [RFC 1996](https://tools.ietf.org/html/rfc1996), DNS NOTIFY, August 1996
@@ -466,18 +1015,44 @@ pub trait ClientHandle: Clone {
     {
         debug!("notifying: {} {:?}", name, query_type);
 
+        self.send(notify_message(name, query_class, query_type, rrset))
+    }
+
+    /// Requests the changes to a zone since a serial, an incremental zone transfer
+    ///
+    /// [RFC 1995](https://tools.ietf.org/html/rfc1995), Incremental Zone Transfer, August 1996
+    ///
+    /// ```text
+    /// 3. Query Format
+    ///
+    ///    The IXFR query packet format is the same as that of a normal DNS
+    ///    query, but with the query type being IXFR and the authority section
+    ///    containing the SOA record of client's version of the zone.
+    /// ```
+    ///
+    /// The response is either the deltas from the serial, all the records of the zone if the
+    ///  server does not have them, or just the SOA if the zone has not changed since, see
+    ///  `IxfrResponse::apply` to update the records of the zone with it. A response which is split
+    ///  over several messages is received until the SOA which ends it, within the query timeout.
+    ///
+    /// # Arguments
+    ///
+    /// * `zone` - the origin of the zone
+    /// * `query_class` - most likely this should always be DNSClass::IN
+    /// * `serial` - the serial of the SOA of the version of the zone the client has
+    fn ixfr(&mut self,
+            zone: domain::Name,
+            query_class: DNSClass,
+            serial: u32)
+            -> Box<Future<Item = IxfrResponse, Error = ClientError>> {
+        debug!("ixfr: {} from serial: {}", zone, serial);
+
         // build the message
         let mut message: Message = Message::new();
         let id: u16 = rand::random();
         message.id(id)
-           // 3.3. NOTIFY is similar to QUERY in that it has a request message with
-           // the header QR flag "clear" and a response message with QR "set".  The
-           // response message contains no useful information, but its reception by
-           // the master is an indication that the slave has received the NOTIFY
-           // and that the master can remove the slave from any retry queue for
-           // this NOTIFY event.
-           .message_type(MessageType::Query)
-           .op_code(OpCode::Notify);
+            .message_type(MessageType::Query)
+            .op_code(OpCode::Query);
 
         // Extended dns
         {
@@ -488,15 +1063,22 @@ pub trait ClientHandle: Clone {
 
         // add the query
         let mut query: Query = Query::new();
-        query.name(name.clone()).query_class(query_class).query_type(query_type);
+        query.name(zone.clone()).query_class(query_class).query_type(RecordType::IXFR);
         message.add_query(query);
 
-        // add the notify message, see https://tools.ietf.org/html/rfc1996, section 3.7
-        if let Some(rrset) = rrset {
-            message.add_answers(rrset.into_record_set());
-        }
+        // only the serial of the SOA is compared by the server
+        let soa = SOA::new(zone.clone(), zone.clone(), serial, 0, 0, 0, 0);
+        let mut soa = Record::from_rdata(zone, 0, RecordType::SOA, RData::SOA(soa));
+        soa.dns_class(query_class);
+        message.add_name_server(soa);
 
-        self.send(message)
+        Box::new(self.send(message).and_then(|response| {
+            if response.get_response_code() != ResponseCode::NoError {
+                return Err(ClientErrorKind::ErrorResponse(response.get_response_code()).into());
+            }
+
+            IxfrResponse::from_records(response.get_answers())
+        }))
     }
 
     /// Sends a record to create on the server, this will fail if the record exists (atomicity
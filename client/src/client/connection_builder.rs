@@ -0,0 +1,351 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! One entry point for the client connections of all the protocols, with their shared options
+
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs};
+
+use futures::{Future, Stream};
+#[cfg(target_os = "linux")]
+use openssl::x509::X509 as OpensslX509;
+#[cfg(target_os = "macos")]
+use security_framework::certificate::SecCertificate;
+use tokio_core::reactor::Core;
+
+use ::error::*;
+use client::{ClientConnection, ClientStreamHandle, TimeoutConfig, DEFAULT_DOH_PORT};
+use https::{HttpsClientConnection, HttpsClientConnectionBuilder, DEFAULT_DNS_QUERY_PATH};
+use tcp::TcpClientConnection;
+use tls::{TlsClientConnection, TlsClientConnectionBuilder};
+use udp::{LocalSocket, SourceValidation, UdpClientConnection};
+
+/// The messages received over a `BoxedClientConnection`, whatever its protocol
+pub type BoxedMessageStream = Box<Stream<Item = Vec<u8>, Error = io::Error>>;
+
+enum Protocol {
+    Udp(SocketAddr),
+    Tcp(SocketAddr),
+    Tls(SocketAddr, String, TlsClientConnectionBuilder),
+    Https(String, HttpsClientConnectionBuilder),
+}
+
+/// Builds a client connection over UDP, TCP, TLS or HTTPS, with the options they share
+///
+/// ```rust,no_run
+/// use trust_dns::client::{ConnectionBuilder, SyncClient, TimeoutConfig};
+///
+/// let mut builder = ConnectionBuilder::tls("8.8.8.8:853".parse().unwrap(),
+///                                          "dns.google".to_string());
+/// builder.timeouts(TimeoutConfig::default());
+/// let client = SyncClient::new(builder.build().unwrap());
+/// ```
+///
+/// The connections built are all `BoxedClientConnection`s, so that the protocol may be chosen at
+///  runtime, e.g. from configuration. The constructors of `UdpClientConnection`,
+///  `TcpClientConnection`, and the builders of `TlsClientConnection` and
+///  `HttpsClientConnection`, remain for the options specific to a protocol.
+pub struct ConnectionBuilder {
+    protocol: Protocol,
+    timeouts: TimeoutConfig,
+    bind_addr: Option<SocketAddr>,
+    edns_payload: Option<u16>,
+}
+
+impl ConnectionBuilder {
+    fn new(protocol: Protocol) -> Self {
+        ConnectionBuilder {
+            protocol: protocol,
+            timeouts: TimeoutConfig::default(),
+            bind_addr: None,
+            edns_payload: None,
+        }
+    }
+
+    /// A connection over UDP, see `UdpClientConnection`
+    ///
+    /// # Arguments
+    ///
+    /// * `name_server` - address of the name server to use for queries
+    pub fn udp(name_server: SocketAddr) -> Self {
+        Self::new(Protocol::Udp(name_server))
+    }
+
+    /// A connection over TCP, see `TcpClientConnection`
+    ///
+    /// # Arguments
+    ///
+    /// * `name_server` - address of the name server to use for queries
+    pub fn tcp(name_server: SocketAddr) -> Self {
+        Self::new(Protocol::Tcp(name_server))
+    }
+
+    /// A connection over TLS, see `TlsClientConnection`
+    ///
+    /// # Arguments
+    ///
+    /// * `name_server` - address of the name server to use for queries, usually port 853
+    /// * `dns_name` - name of the server, as associated to its certificate
+    pub fn tls(name_server: SocketAddr, dns_name: String) -> Self {
+        Self::new(Protocol::Tls(name_server, dns_name, TlsClientConnection::builder()))
+    }
+
+    /// A connection over HTTPS, RFC 8484, see `HttpsClientConnection`
+    ///
+    /// The host of the URL is resolved when the connection is built, the queries are sent with
+    ///  POST requests.
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - the URI template of the server, e.g. `https://dns.example.com/dns-query`, the
+    ///           port defaults to 443 and the path to `DEFAULT_DNS_QUERY_PATH`
+    pub fn https(url: &str) -> Self {
+        Self::new(Protocol::Https(url.to_string(), HttpsClientConnection::builder()))
+    }
+
+    /// Connect, read, write and query timeouts, the defaults are used if this is not called
+    ///
+    /// Only the query timeout applies to UDP, see the connection of each protocol.
+    pub fn timeouts(&mut self, timeouts: TimeoutConfig) {
+        self.timeouts = timeouts;
+    }
+
+    /// The local address the queries are sent from, e.g. for firewall rules, with port 0 a random
+    ///  port is used
+    ///
+    /// The UDP socket is bound to it, or the TCP connection under TCP, TLS and HTTPS is made from
+    ///  it.
+    pub fn bind_addr(&mut self, bind_addr: SocketAddr) {
+        self.bind_addr = Some(bind_addr);
+    }
+
+    /// The UDP payload size to advertise with EDNS in queries
+    ///
+    /// `SyncClient::new` advertises it in every message, `SyncClient::with_edns` also lowers it
+    ///  for a name server which fails with it.
+    pub fn edns_payload(&mut self, max_payload: u16) {
+        self.edns_payload = Some(max_payload);
+    }
+
+    /// Trusts the certificate authority for TLS and HTTPS, no effect over UDP and TCP
+    #[cfg(target_os = "macos")]
+    pub fn add_ca(&mut self, ca: SecCertificate) {
+        match self.protocol {
+            Protocol::Tls(_, _, ref mut builder) => builder.add_ca(ca),
+            Protocol::Https(_, ref mut builder) => builder.add_ca(ca),
+            Protocol::Udp(_) | Protocol::Tcp(_) => (),
+        }
+    }
+
+    /// Trusts the certificate authority for TLS and HTTPS, no effect over UDP and TCP
+    #[cfg(target_os = "linux")]
+    pub fn add_ca(&mut self, ca: OpensslX509) {
+        match self.protocol {
+            Protocol::Tls(_, _, ref mut builder) => builder.add_ca(ca),
+            Protocol::Https(_, ref mut builder) => builder.add_ca(ca),
+            Protocol::Udp(_) | Protocol::Tcp(_) => (),
+        }
+    }
+
+    /// Creates the client connection
+    ///
+    /// *Note* this has side affects of binding the socket, or establishing the connection, and
+    ///        starting the event_loop, as the connection of each protocol does.
+    pub fn build(self) -> ClientResult<BoxedClientConnection> {
+        let timeouts = self.timeouts;
+        let edns_payload = self.edns_payload;
+
+        let connection = match self.protocol {
+            Protocol::Udp(name_server) => {
+                let local_socket = match self.bind_addr {
+                    Some(bind_addr) => LocalSocket::Bound(bind_addr),
+                    None => LocalSocket::default(),
+                };
+                let source_validation = SourceValidation::default();
+                let connection = try!(UdpClientConnection::with_options(name_server,
+                                                                         source_validation,
+                                                                         local_socket,
+                                                                         timeouts));
+                BoxedClientConnection::new(connection)
+            }
+            Protocol::Tcp(name_server) => {
+                BoxedClientConnection::new(try!(TcpClientConnection::with_bind_addr(name_server,
+                                                                                    self.bind_addr,
+                                                                                    timeouts)))
+            }
+            Protocol::Tls(name_server, dns_name, mut builder) => {
+                builder.timeouts(timeouts);
+                if let Some(bind_addr) = self.bind_addr {
+                    builder.bind_addr(bind_addr);
+                }
+                BoxedClientConnection::new(try!(builder.build(name_server, dns_name)))
+            }
+            Protocol::Https(url, mut builder) => {
+                let (name_server, host, path) = try!(parse_https_url(&url));
+                builder.path(&path);
+                builder.timeouts(timeouts);
+                if let Some(bind_addr) = self.bind_addr {
+                    builder.bind_addr(bind_addr);
+                }
+                BoxedClientConnection::new(try!(builder.build(name_server, host)))
+            }
+        };
+
+        Ok(BoxedClientConnection { edns_payload: edns_payload, ..connection })
+    }
+}
+
+/// A client connection of any protocol, with a boxed message stream, see `ConnectionBuilder`
+pub struct BoxedClientConnection {
+    io_loop: Core,
+    client_stream: Box<Future<Item = BoxedMessageStream, Error = io::Error>>,
+    client_stream_handle: Box<ClientStreamHandle>,
+    timeouts: TimeoutConfig,
+    edns_payload: Option<u16>,
+}
+
+impl BoxedClientConnection {
+    /// Boxes the message stream of the connection, and its future
+    pub fn new<CC>(client_connection: CC) -> Self
+        where CC: ClientConnection,
+              <CC as ClientConnection>::MessageStream: Stream<Item = Vec<u8>, Error = io::Error> +
+                                                       'static
+    {
+        let timeouts = client_connection.get_timeouts();
+        let edns_payload = client_connection.get_edns_payload();
+        let (io_loop, stream, stream_handle) = client_connection.unwrap();
+
+        BoxedClientConnection {
+            io_loop: io_loop,
+            client_stream: Box::new(stream.map(|stream| Box::new(stream) as BoxedMessageStream)),
+            client_stream_handle: stream_handle,
+            timeouts: timeouts,
+            edns_payload: edns_payload,
+        }
+    }
+}
+
+impl ClientConnection for BoxedClientConnection {
+    type MessageStream = BoxedMessageStream;
+
+    fn get_timeouts(&self) -> TimeoutConfig {
+        self.timeouts
+    }
+
+    fn get_edns_payload(&self) -> Option<u16> {
+        self.edns_payload
+    }
+
+    fn unwrap(self)
+              -> (Core,
+                  Box<Future<Item = Self::MessageStream, Error = io::Error>>,
+                  Box<ClientStreamHandle>) {
+        (self.io_loop, self.client_stream, self.client_stream_handle)
+    }
+}
+
+/// The address, host and path of the URL of a DNS over HTTPS server, its host resolved
+fn parse_https_url(url: &str) -> ClientResult<(SocketAddr, String, String)> {
+    let bad_url = || ClientError::from(ClientErrorKind::Msg(format!("bad https url: {}", url)));
+
+    if !url.starts_with("https://") {
+        return Err(bad_url());
+    }
+    let rest = &url["https://".len()..];
+    let (authority, path) = match rest.find('/') {
+        Some(index) => (&rest[..index], &rest[index..]),
+        None => (rest, ""),
+    };
+    // the template variables of RFC 8484, e.g. `{?dns}`, are not part of the path
+    let path = path.split(|c| c == '?' || c == '{').next().unwrap_or("");
+    let path = if path.is_empty() || path == "/" {
+        DEFAULT_DNS_QUERY_PATH
+    } else {
+        path
+    };
+
+    // an IPv6 address is in brackets, e.g. `[2001:db8::1]:443`
+    let (host, port) = if authority.starts_with('[') {
+        let end = try!(authority.find(']').ok_or_else(&bad_url));
+        (&authority[1..end], &authority[end + 1..])
+    } else {
+        match authority.rfind(':') {
+            Some(index) => (&authority[..index], &authority[index..]),
+            None => (authority, ""),
+        }
+    };
+    let port: u16 = if port.is_empty() {
+        DEFAULT_DOH_PORT
+    } else if port.starts_with(':') {
+        try!(port[1..].parse().map_err(|_| bad_url()))
+    } else {
+        return Err(bad_url());
+    };
+    if host.is_empty() {
+        return Err(bad_url());
+    }
+
+    let name_server = try!(try!((host, port).to_socket_addrs()).next().ok_or_else(&bad_url));
+    Ok((name_server, host.to_string(), path.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+    use std::time::Duration;
+
+    use client::{ClientConnection, TimeoutConfig};
+    use https::DEFAULT_DNS_QUERY_PATH;
+    use super::{parse_https_url, ConnectionBuilder};
+
+    #[test]
+    fn test_parse_https_url() {
+        let (name_server, host, path) = parse_https_url("https://127.0.0.1/dns-query{?dns}")
+            .unwrap();
+        assert_eq!(name_server,
+                   SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 443));
+        assert_eq!(host, "127.0.0.1");
+        assert_eq!(path, "/dns-query");
+
+        let (name_server, host, path) = parse_https_url("https://[::1]:8443/resolve").unwrap();
+        assert_eq!(name_server,
+                   SocketAddr::new(IpAddr::V6(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 1)), 8443));
+        assert_eq!(host, "::1");
+        assert_eq!(path, "/resolve");
+
+        assert_eq!(parse_https_url("https://127.0.0.1:853").unwrap().2,
+                   DEFAULT_DNS_QUERY_PATH);
+        assert!(parse_https_url("http://127.0.0.1/dns-query").is_err());
+        assert!(parse_https_url("https://127.0.0.1:dns/dns-query").is_err());
+        assert!(parse_https_url("https://:443/dns-query").is_err());
+    }
+
+    #[test]
+    fn test_build_options() {
+        let name_server = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 53);
+        let bind_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 0);
+        let timeouts = TimeoutConfig {
+            query: Duration::from_secs(1),
+            ..TimeoutConfig::default()
+        };
+
+        let mut builder = ConnectionBuilder::udp(name_server);
+        builder.timeouts(timeouts);
+        builder.bind_addr(bind_addr);
+        builder.edns_payload(1232);
+        let connection = builder.build().unwrap();
+        assert_eq!(connection.get_timeouts(), timeouts);
+        assert_eq!(connection.get_edns_payload(), Some(1232));
+
+        // the TCP connection is made from the bound address when the client first polls it
+        let mut builder = ConnectionBuilder::tcp(name_server);
+        builder.bind_addr(bind_addr);
+        builder.edns_payload(1232);
+        let connection = builder.build().unwrap();
+        assert_eq!(connection.get_edns_payload(), Some(1232));
+    }
+}
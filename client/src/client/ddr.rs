@@ -0,0 +1,345 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Discovery of Designated Resolvers, DDR, RFC 9462
+
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+
+use ::error::*;
+use https::{HttpsClientConnection, DEFAULT_DNS_QUERY_PATH};
+use op::Message;
+use rr::{RData, Record};
+use rr::domain::Name;
+use rr::rdata::SVCB;
+use rr::rdata::svcb::SvcParam;
+use tls::TlsClientConnection;
+
+/// The port of DNS over TLS, if the record does not have one, RFC 7858
+pub const DEFAULT_DOT_PORT: u16 = 853;
+/// The port of DNS over HTTPS, if the record does not have one
+pub const DEFAULT_DOH_PORT: u16 = 443;
+
+/// The keys of the SvcParams which are understood, a record with any other mandatory key is not
+///  usable, RFC 9460 section 8
+const KNOWN_KEYS: &'static [u16] = &[1, 2, 3, 4, 6, 7];
+
+/// The name queried for the designations of the resolver, `_dns.resolver.arpa.`, RFC 9462
+///  section 4
+pub fn ddr_name() -> Name {
+    Name::new().label("_dns").label("resolver").label("arpa")
+}
+
+/// The encrypted protocol of a designated resolver
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DesignatedProtocol {
+    /// DNS over TLS, RFC 7858, the `dot` ALPN
+    Tls,
+    /// DNS over HTTPS, RFC 8484, the `h2` or `http/1.1` ALPN, with the path of the DNS API
+    Https {
+        /// the path of the `dohpath` URI template, without the variables
+        path: String,
+    },
+}
+
+/// An encrypted resolver designated by the unencrypted resolver which was queried
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DesignatedResolver {
+    target: Name,
+    priority: u16,
+    protocol: DesignatedProtocol,
+    port: u16,
+    addresses: Vec<IpAddr>,
+}
+
+impl DesignatedResolver {
+    /// The name of the designated resolver, which its certificate must be issued for
+    pub fn get_target(&self) -> &Name {
+        &self.target
+    }
+
+    /// The priority of the SVCB record, lower values are preferred
+    pub fn get_priority(&self) -> u16 {
+        self.priority
+    }
+
+    /// The protocol of the designated resolver
+    pub fn get_protocol(&self) -> &DesignatedProtocol {
+        &self.protocol
+    }
+
+    /// The port of the designated resolver, the default of the protocol if the record has none
+    pub fn get_port(&self) -> u16 {
+        self.port
+    }
+
+    /// The addresses of the target, from the hints of the record and the additional section
+    pub fn get_addresses(&self) -> &[IpAddr] {
+        &self.addresses
+    }
+
+    /// Adds addresses of the target, e.g. looked up separately
+    pub fn add_addresses(&mut self, addresses: Vec<IpAddr>) {
+        for address in addresses {
+            if !self.addresses.contains(&address) {
+                self.addresses.push(address);
+            }
+        }
+    }
+
+    /// The socket addresses to connect to, the addresses of the target with the port
+    pub fn get_socket_addrs(&self) -> Vec<SocketAddr> {
+        self.addresses.iter().map(|address| SocketAddr::new(*address, self.port)).collect()
+    }
+
+    /// The name of the target as for the TLS handshake, without the final `.`
+    pub fn get_subject_name(&self) -> String {
+        self.target.to_string().trim_right_matches('.').to_string()
+    }
+
+    /// Connects over TLS to the first address of the designated resolver, verifying that it is
+    ///  designated by the resolver at `resolver_ip`, RFC 9462 section 4.2
+    ///
+    /// The certificate of the designated resolver must be valid for its name and also have the
+    ///  address of the unencrypted resolver in its subjectAltName.
+    pub fn tls_connection(&self, resolver_ip: IpAddr) -> ClientResult<TlsClientConnection> {
+        if self.protocol != DesignatedProtocol::Tls {
+            return Err(ClientErrorKind::Message("not a DNS over TLS resolver").into());
+        }
+        let name_server = try!(self.first_socket_addr());
+
+        let mut builder = TlsClientConnection::builder();
+        builder.verify_ip_address(resolver_ip);
+        builder.build(name_server, self.get_subject_name())
+    }
+
+    /// Connects over HTTPS to the first address of the designated resolver, verifying that it is
+    ///  designated by the resolver at `resolver_ip`, see `tls_connection()`
+    pub fn https_connection(&self, resolver_ip: IpAddr) -> ClientResult<HttpsClientConnection> {
+        let path = match self.protocol {
+            DesignatedProtocol::Https { ref path } => path,
+            _ => return Err(ClientErrorKind::Message("not a DNS over HTTPS resolver").into()),
+        };
+        let name_server = try!(self.first_socket_addr());
+
+        let mut builder = HttpsClientConnection::builder();
+        builder.path(path);
+        builder.verify_ip_address(resolver_ip);
+        builder.build(name_server, self.get_subject_name())
+    }
+
+    fn first_socket_addr(&self) -> ClientResult<SocketAddr> {
+        self.get_socket_addrs()
+            .into_iter()
+            .next()
+            .ok_or(ClientErrorKind::Message("no address of the designated resolver").into())
+    }
+}
+
+impl fmt::Display for DesignatedResolver {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.protocol {
+            DesignatedProtocol::Tls => try!(write!(f, "tls://{}:{}", self.target, self.port)),
+            DesignatedProtocol::Https { ref path } => {
+                try!(write!(f, "https://{}:{}{}", self.target, self.port, path))
+            }
+        }
+
+        if !self.addresses.is_empty() {
+            let addresses = self.addresses.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+            try!(write!(f, " ({})", addresses.join(", ")));
+        }
+        Ok(())
+    }
+}
+
+/// The designated resolvers of the response to the SVCB query for `ddr_name()`, ordered by
+///  priority
+///
+/// AliasMode records, those of which any mandatory key is not understood, and those without a
+///  supported protocol are skipped, as is a target of `.`, which would designate
+///  `resolver.arpa.` itself. A record with both the `dot` and an HTTP ALPN gives a resolver for
+///  each protocol. Requests over HTTPS are made with HTTP/1.1, also to servers only advertising
+///  `h2`, as most do serve both.
+pub fn designated_resolvers(response: &Message) -> Vec<DesignatedResolver> {
+    let mut records = response.get_answers()
+        .iter()
+        .filter(|record| record.get_name() == &ddr_name())
+        .filter_map(|record| if let RData::SVCB(ref svcb) = *record.get_rdata() {
+            Some(svcb)
+        } else {
+            None
+        })
+        .filter(|svcb| !svcb.is_alias() && !svcb.get_target().is_root())
+        .filter(|svcb| is_usable(svcb))
+        .collect::<Vec<_>>();
+    records.sort_by_key(|svcb| svcb.get_priority());
+
+    let mut resolvers: Vec<DesignatedResolver> = Vec::new();
+    for svcb in records {
+        let mut addresses = svcb.get_address_hints();
+        for address in addresses_of(response.get_additionals(), svcb.get_target()) {
+            if !addresses.contains(&address) {
+                addresses.push(address);
+            }
+        }
+
+        let alpn = svcb.get_alpn();
+        let mut protocols: Vec<(DesignatedProtocol, u16)> = Vec::new();
+        if alpn.iter().any(|id| id == "dot") {
+            protocols.push((DesignatedProtocol::Tls, DEFAULT_DOT_PORT));
+        }
+        if alpn.iter().any(|id| id == "h2" || id == "http/1.1") {
+            if let Some(path) = svcb.get_dohpath().and_then(doh_path) {
+                protocols.push((DesignatedProtocol::Https { path: path }, DEFAULT_DOH_PORT));
+            }
+        }
+
+        for (protocol, default_port) in protocols {
+            resolvers.push(DesignatedResolver {
+                target: svcb.get_target().clone(),
+                priority: svcb.get_priority(),
+                protocol: protocol,
+                port: svcb.get_port().unwrap_or(default_port),
+                addresses: addresses.clone(),
+            });
+        }
+    }
+
+    resolvers
+}
+
+/// The A and AAAA addresses of the name among the records
+pub fn addresses_of(records: &[Record], name: &Name) -> Vec<IpAddr> {
+    records.iter()
+        .filter(|record| record.get_name() == name)
+        .filter_map(|record| match *record.get_rdata() {
+            RData::A(address) => Some(IpAddr::V4(address)),
+            RData::AAAA(address) => Some(IpAddr::V6(address)),
+            _ => None,
+        })
+        .collect()
+}
+
+fn is_usable(svcb: &SVCB) -> bool {
+    svcb.get_params().iter().all(|param| match *param {
+        SvcParam::Mandatory(ref keys) => keys.iter().all(|key| KNOWN_KEYS.contains(key)),
+        _ => true,
+    })
+}
+
+/// The path of the `dohpath` URI template, RFC 9461 section 5, e.g. `/dns-query` of
+///  `/dns-query{?dns}`, None if it is not a relative path
+fn doh_path(template: &str) -> Option<String> {
+    if !template.starts_with('/') {
+        return None;
+    }
+
+    let path = template.split('{').next().unwrap_or(template);
+    if path == "/" {
+        Some(DEFAULT_DNS_QUERY_PATH.to_string())
+    } else {
+        Some(path.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+    use op::Message;
+    use rr::{RData, Record, RecordType};
+    use rr::domain::Name;
+    use rr::rdata::SVCB;
+    use rr::rdata::svcb::SvcParam;
+    use super::*;
+
+    fn svcb(priority: u16, target: &str, params: Vec<SvcParam>) -> Record {
+        Record::from_rdata(ddr_name(),
+                           300,
+                           RecordType::SVCB,
+                           RData::SVCB(SVCB::new(priority,
+                                                 Name::parse(target, None).unwrap(),
+                                                 params)))
+    }
+
+    fn alpn(ids: &[&str]) -> SvcParam {
+        SvcParam::Alpn(ids.iter().map(|id| id.to_string()).collect())
+    }
+
+    #[test]
+    fn test_ddr_name() {
+        assert_eq!(ddr_name(), Name::parse("_dns.resolver.arpa.", None).unwrap());
+    }
+
+    #[test]
+    fn test_designated_resolvers() {
+        let dot = Name::parse("dot.example.net.", None).unwrap();
+        let mut response = Message::new();
+        response.add_answer(svcb(2,
+                                 "doh.example.net.",
+                                 vec![alpn(&["h2"]),
+                                      SvcParam::Ipv6Hint(vec![Ipv6Addr::new(0x2001, 0xdb8, 0,
+                                                                            0, 0, 0, 0, 1)]),
+                                      SvcParam::DohPath("/dns-query{?dns}".to_string())]));
+        response.add_answer(svcb(1,
+                                 "dot.example.net.",
+                                 vec![alpn(&["dot"]),
+                                      SvcParam::Ipv4Hint(vec![Ipv4Addr::new(192, 0, 2, 1)])]));
+        // not usable
+        response.add_answer(svcb(0, "dot.example.net.", vec![]));
+        response.add_answer(svcb(1, ".", vec![alpn(&["dot"])]));
+        response.add_answer(svcb(1, "doq.example.net.", vec![alpn(&["doq"])]));
+        response.add_answer(svcb(1, "doh.example.net.", vec![alpn(&["h2"])]));
+        response.add_answer(svcb(1,
+                                 "dot.example.net.",
+                                 vec![SvcParam::Mandatory(vec![1, 65001]), alpn(&["dot"])]));
+        response.add_additional(Record::from_rdata(dot.clone(),
+                                                   300,
+                                                   RecordType::A,
+                                                   RData::A(Ipv4Addr::new(192, 0, 2, 2))));
+
+        let resolvers = designated_resolvers(&response);
+        assert_eq!(resolvers.len(), 2);
+
+        assert_eq!(resolvers[0].get_target(), &dot);
+        assert_eq!(resolvers[0].get_protocol(), &DesignatedProtocol::Tls);
+        assert_eq!(resolvers[0].get_port(), 853);
+        assert_eq!(resolvers[0].get_addresses(),
+                   &[IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1)),
+                     IpAddr::V4(Ipv4Addr::new(192, 0, 2, 2))]);
+        assert_eq!(resolvers[0].get_subject_name(), "dot.example.net");
+        assert_eq!(resolvers[0].to_string(),
+                   "tls://dot.example.net.:853 (192.0.2.1, 192.0.2.2)");
+
+        assert_eq!(resolvers[1].get_protocol(),
+                   &DesignatedProtocol::Https { path: "/dns-query".to_string() });
+        assert_eq!(resolvers[1].get_port(), 443);
+        assert_eq!(resolvers[1].get_socket_addrs().len(), 1);
+    }
+
+    #[test]
+    fn test_both_protocols() {
+        let mut response = Message::new();
+        response.add_answer(svcb(1,
+                                 "dns.example.net.",
+                                 vec![alpn(&["dot", "http/1.1"]),
+                                      SvcParam::Port(8443),
+                                      SvcParam::DohPath("/{?dns}".to_string())]));
+
+        let resolvers = designated_resolvers(&response);
+        assert_eq!(resolvers.len(), 2);
+        assert_eq!(resolvers[0].get_protocol(), &DesignatedProtocol::Tls);
+        assert_eq!(resolvers[0].get_port(), 8443);
+        assert_eq!(resolvers[1].get_protocol(),
+                   &DesignatedProtocol::Https { path: "/dns-query".to_string() });
+
+        // without an address there is nothing to connect to
+        assert!(resolvers[0].tls_connection(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 53))).is_err());
+        assert!(resolvers[0].https_connection(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 53))).is_err());
+    }
+}
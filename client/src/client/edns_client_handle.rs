@@ -0,0 +1,307 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::cell::Cell;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use futures::{Future, IntoFuture};
+
+use client::ClientHandle;
+use clock::{Clock, SystemClock};
+use ::error::*;
+use op::{Message, ResponseCode};
+
+/// The UDP payload size retried with after a larger one failed, that of the DNS flag day 2020,
+///  which fits in the minimum IPv6 MTU without fragmentation
+pub const FALLBACK_EDNS_PAYLOAD: u16 = 1232;
+
+/// Seconds a lowered payload size is kept before the configured one is tried again, see
+///  `EdnsClientHandle::set_probe_interval()`
+pub const DEFAULT_EDNS_PROBE_INTERVAL: u32 = 600;
+
+/// The payload size advertised, and until when a lowered one is kept
+#[derive(Clone, Copy, Debug)]
+struct EdnsState {
+    max_payload: Option<u16>,
+    /// seconds since the epoch, the configured size is tried again from then on
+    lowered_until: u32,
+}
+
+/// Advertises the EDNS UDP payload size in each query, RFC 6891, and lowers it for a name server
+///  which fails with it
+///
+/// Some name servers, or middleboxes in front of them, answer FORMERR to queries with an OPT
+///  record, or drop the fragmented responses of a large payload size. After a FORMERR or a
+///  timeout, the query is sent again with `FALLBACK_EDNS_PAYLOAD`, and after another, without
+///  EDNS at all. The lowered size is kept for the further queries through this handle and its
+///  clones, so the handle should wrap the client of a single name server; see
+///  `FailoverClientHandle` for several.
+///
+/// Since a timeout may as well be a lost packet, the lowered size is only kept for the probe
+///  interval, `DEFAULT_EDNS_PROBE_INTERVAL`, after which the configured size is tried again.
+#[derive(Clone)]
+#[must_use = "queries can only be sent through a ClientHandle"]
+pub struct EdnsClientHandle<H: ClientHandle> {
+    client: H,
+    configured: u16,
+    state: Rc<Cell<EdnsState>>,
+    probe_interval: u32,
+    clock: Arc<Clock>,
+}
+
+impl<H> EdnsClientHandle<H>
+    where H: ClientHandle
+{
+    /// Returns a new handle wrapping the specified client
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - the client of the name server
+    /// * `max_payload` - the UDP payload size to advertise, at least 512
+    pub fn new(client: H, max_payload: u16) -> EdnsClientHandle<H> {
+        Self::with_clock(client, max_payload, Arc::new(SystemClock))
+    }
+
+    /// Returns a new handle wrapping the specified client, expiring lowered sizes by the clock
+    pub fn with_clock(client: H, max_payload: u16, clock: Arc<Clock>) -> EdnsClientHandle<H> {
+        EdnsClientHandle {
+            client: client,
+            configured: max_payload,
+            state: Rc::new(Cell::new(EdnsState {
+                max_payload: Some(max_payload),
+                lowered_until: 0,
+            })),
+            probe_interval: DEFAULT_EDNS_PROBE_INTERVAL,
+            clock: clock,
+        }
+    }
+
+    /// Seconds a lowered payload size is kept, after which the configured size is tried again
+    pub fn set_probe_interval(&mut self, probe_interval: u32) {
+        self.probe_interval = probe_interval;
+    }
+
+    /// The UDP payload size advertised to the name server, None while it is taken not to support
+    ///  EDNS
+    pub fn get_max_payload(&self) -> Option<u16> {
+        self.state.get().max_payload
+    }
+}
+
+/// The payload size to try after the sent one failed, None to try without EDNS
+fn lower_payload(sent: u16) -> Option<u16> {
+    if sent > FALLBACK_EDNS_PAYLOAD {
+        Some(FALLBACK_EDNS_PAYLOAD)
+    } else {
+        None
+    }
+}
+
+/// Sends the message with the current payload size, lowering it and sending again on failure
+fn send_edns<H>(mut client: H,
+                message: Message,
+                state: Rc<Cell<EdnsState>>,
+                probe_interval: u32,
+                clock: Arc<Clock>)
+                -> Box<Future<Item = Message, Error = ClientError>>
+    where H: ClientHandle + 'static
+{
+    let sent = state.get().max_payload;
+    let mut request = message.clone();
+    match sent {
+        Some(payload) => request.get_edns_mut().set_max_payload(payload),
+        None => {
+            request.take_edns();
+        }
+    }
+
+    let retry_client = client.clone();
+    let response = client.send(request);
+    Box::new(response.then(move |result| -> Box<Future<Item = Message, Error = ClientError>> {
+        let failed = match result {
+            Ok(ref response) => response.get_response_code() == ResponseCode::FormErr,
+            Err(ref error) => {
+                match *error.kind() {
+                    ClientErrorKind::Timeout => true,
+                    _ => false,
+                }
+            }
+        };
+
+        let sent_payload = match sent {
+            Some(payload) if failed => payload,
+            _ => return Box::new(result.into_future()),
+        };
+
+        // a concurrent query may have lowered it already
+        if state.get().max_payload == sent {
+            state.set(EdnsState {
+                max_payload: lower_payload(sent_payload),
+                lowered_until: clock.now().saturating_add(probe_interval),
+            });
+        }
+        debug!("EDNS payload: {} failed, retrying with: {:?}",
+               sent_payload,
+               state.get().max_payload);
+
+        send_edns(retry_client, message, state, probe_interval, clock)
+    }))
+}
+
+impl<H> ClientHandle for EdnsClientHandle<H>
+    where H: ClientHandle + 'static
+{
+    fn send(&mut self, message: Message) -> Box<Future<Item = Message, Error = ClientError>> {
+        // probe with the configured size again, once the lowered one has been kept long enough
+        let state = self.state.get();
+        if state.max_payload != Some(self.configured) &&
+           self.clock.now() >= state.lowered_until {
+            debug!("EDNS payload: {:?} expired, probing with: {}",
+                   state.max_payload,
+                   self.configured);
+            self.state.set(EdnsState {
+                max_payload: Some(self.configured),
+                lowered_until: 0,
+            });
+        }
+
+        send_edns(self.client.clone(),
+                  message,
+                  self.state.clone(),
+                  self.probe_interval,
+                  self.clock.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::{Cell, RefCell};
+    use std::rc::Rc;
+    use std::sync::Arc;
+
+    use ::client::*;
+    use ::clock::ManualClock;
+    use ::error::*;
+    use ::op::*;
+    use ::rr::*;
+    use futures::*;
+
+    /// Fails the queries with EDNS, FORMERR or a timeout, above the payload size it supports
+    #[derive(Clone)]
+    struct TestClient {
+        max_payload: Rc<Cell<Option<u16>>>,
+        formerr: bool,
+        sent: Rc<RefCell<Vec<Option<u16>>>>,
+    }
+
+    impl ClientHandle for TestClient {
+        fn send(&mut self, request: Message) -> Box<Future<Item = Message, Error = ClientError>> {
+            let payload = request.get_edns().map(|edns| edns.get_max_payload());
+            self.sent.borrow_mut().push(payload);
+
+            let supported = match (payload, self.max_payload.get()) {
+                (Some(payload), Some(max_payload)) => payload <= max_payload,
+                (Some(_), None) => false,
+                (None, _) => true,
+            };
+
+            let mut response = Message::new();
+            if supported {
+                response.response_code(ResponseCode::NoError);
+            } else if self.formerr {
+                response.response_code(ResponseCode::FormErr);
+            } else {
+                return Box::new(failed(ClientErrorKind::Timeout.into()));
+            }
+            Box::new(finished(response))
+        }
+    }
+
+    fn test_client(max_payload: Option<u16>, formerr: bool) -> TestClient {
+        TestClient {
+            max_payload: Rc::new(Cell::new(max_payload)),
+            formerr: formerr,
+            sent: Rc::new(RefCell::new(Vec::new())),
+        }
+    }
+
+    fn query(client: &mut EdnsClientHandle<TestClient>) -> ClientResult<Message> {
+        client.query(Name::parse("www.example.com.", None).unwrap(),
+                   DNSClass::IN,
+                   RecordType::A)
+            .wait()
+    }
+
+    #[test]
+    fn test_supported() {
+        let server = test_client(Some(4096), true);
+        let mut client = EdnsClientHandle::new(server.clone(), 4096);
+
+        assert!(query(&mut client).is_ok());
+        assert_eq!(*server.sent.borrow(), vec![Some(4096)]);
+        assert_eq!(client.get_max_payload(), Some(4096));
+    }
+
+    #[test]
+    fn test_smaller_payload() {
+        let server = test_client(Some(1400), false);
+        let mut client = EdnsClientHandle::new(server.clone(), 4096);
+
+        assert!(query(&mut client).is_ok());
+        assert_eq!(client.get_max_payload(), Some(FALLBACK_EDNS_PAYLOAD));
+
+        // the next query starts with the size which worked
+        assert!(query(&mut client.clone()).is_ok());
+        assert_eq!(*server.sent.borrow(),
+                   vec![Some(4096), Some(FALLBACK_EDNS_PAYLOAD), Some(FALLBACK_EDNS_PAYLOAD)]);
+    }
+
+    #[test]
+    fn test_without_edns() {
+        let server = test_client(None, true);
+        let mut client = EdnsClientHandle::new(server.clone(), 4096);
+
+        assert!(query(&mut client).is_ok());
+        assert!(query(&mut client).is_ok());
+        assert_eq!(*server.sent.borrow(),
+                   vec![Some(4096), Some(FALLBACK_EDNS_PAYLOAD), None, None]);
+        assert_eq!(client.get_max_payload(), None);
+    }
+
+    #[test]
+    fn test_timeouts() {
+        let server = test_client(None, false);
+        let mut client = EdnsClientHandle::new(server.clone(), 4096);
+
+        assert!(query(&mut client).is_ok());
+        assert_eq!(*server.sent.borrow(), vec![Some(4096), Some(FALLBACK_EDNS_PAYLOAD), None]);
+        assert_eq!(client.get_max_payload(), None);
+    }
+
+    #[test]
+    fn test_probe_again() {
+        let clock = ManualClock::new(1000);
+        let server = test_client(None, false);
+        let mut client = EdnsClientHandle::with_clock(server.clone(),
+                                                      4096,
+                                                      Arc::new(clock.clone()));
+        client.set_probe_interval(60);
+
+        assert!(query(&mut client).is_ok());
+        assert!(query(&mut client).is_ok());
+        assert_eq!(client.get_max_payload(), None);
+
+        // the timeouts were transient, the configured size is tried again after the interval
+        clock.advance(60);
+        server.sent.borrow_mut().clear();
+        server.max_payload.set(Some(4096));
+        assert!(query(&mut client).is_ok());
+        assert_eq!(*server.sent.borrow(), vec![Some(4096)]);
+        assert_eq!(client.get_max_payload(), Some(4096));
+    }
+}
@@ -0,0 +1,328 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::cell::Cell;
+use std::rc::Rc;
+use std::time::Duration;
+
+use futures::{failed, Async, Future, Poll};
+use tokio_core::reactor::{Handle, Timeout};
+
+use client::{ClientHandle, RetryPolicy};
+use ::error::*;
+use op::{Message, ResponseCode};
+
+/// Sends each query to a list of name servers in turn, until one responds
+///
+/// After a timeout, or another error, or a SERVFAIL response, the query is sent again to the
+///  next name server, wrapping around, up to the `retries` of the policy, waiting the
+///  exponential backoff of the policy before each retry. Once the retries are exhausted the
+///  last SERVFAIL is returned, or else the last error. With `rotate`, each query starts with the
+///  next name server, otherwise always with the first; all clones share the rotation.
+///
+/// The timeout of each attempt is that of the wrapped handles, see `TimeoutConfig::query`.
+#[derive(Clone)]
+#[must_use = "queries can only be sent through a ClientHandle"]
+pub struct FailoverClientHandle<H: ClientHandle> {
+    clients: Vec<H>,
+    policy: RetryPolicy,
+    reactor_handle: Handle,
+    next: Rc<Cell<usize>>,
+}
+
+impl<H> FailoverClientHandle<H>
+    where H: ClientHandle
+{
+    /// Returns a new handle over the specified clients, one for each name server, in order
+    ///
+    /// # Arguments
+    ///
+    /// * `clients` - the clients of the name servers
+    /// * `policy` - the retries, rotation and backoff
+    /// * `reactor_handle` - the reactor the backoff waits on
+    pub fn new(clients: Vec<H>,
+               policy: RetryPolicy,
+               reactor_handle: Handle)
+               -> FailoverClientHandle<H> {
+        FailoverClientHandle {
+            clients: clients,
+            policy: policy,
+            reactor_handle: reactor_handle,
+            next: Rc::new(Cell::new(0)),
+        }
+    }
+
+    /// The retry policy of the queries
+    pub fn get_policy(&self) -> &RetryPolicy {
+        &self.policy
+    }
+}
+
+impl<H> ClientHandle for FailoverClientHandle<H>
+    where H: ClientHandle + 'static
+{
+    fn send(&mut self, message: Message) -> Box<Future<Item = Message, Error = ClientError>> {
+        if self.clients.is_empty() {
+            return Box::new(failed(ClientErrorKind::Message("no name servers").into()));
+        }
+
+        let first = if self.policy.rotate {
+            let first = self.next.get() % self.clients.len();
+            self.next.set(first + 1);
+            first
+        } else {
+            0
+        };
+
+        let mut future = FailoverSendFuture {
+            message: message,
+            clients: self.clients.clone(),
+            policy: self.policy,
+            reactor_handle: self.reactor_handle.clone(),
+            next: first,
+            retry: 0,
+            attempt: Attempt::Waiting(None),
+            servfail: None,
+        };
+        future.send();
+
+        Box::new(future)
+    }
+}
+
+/// What the query is waiting for
+enum Attempt {
+    Sending(Box<Future<Item = Message, Error = ClientError>>),
+    /// the backoff before the next retry, None if there is none
+    Waiting(Option<Timeout>),
+}
+
+/// What a poll of the attempt came to, other than the response, or the lack of one
+enum Outcome {
+    Send,
+    ServFail(Message),
+    Failed(ClientError),
+}
+
+/// A future which sends the query to the next name server while it fails and retries remain
+struct FailoverSendFuture<H: ClientHandle> {
+    message: Message,
+    clients: Vec<H>,
+    policy: RetryPolicy,
+    reactor_handle: Handle,
+    next: usize,
+    retry: usize,
+    attempt: Attempt,
+    servfail: Option<Message>,
+}
+
+impl<H> FailoverSendFuture<H>
+    where H: ClientHandle
+{
+    /// Sends the query to the next name server
+    fn send(&mut self) {
+        let index = self.next % self.clients.len();
+        self.next = index + 1;
+
+        let future = self.clients[index].send(self.message.clone());
+        self.attempt = Attempt::Sending(future);
+    }
+
+    /// Waits for the backoff before the next retry
+    fn backoff(&mut self) {
+        let backoff = self.policy.get_backoff(self.retry);
+        self.retry += 1;
+
+        let timeout = if backoff > Duration::from_millis(0) {
+            Timeout::new(backoff, &self.reactor_handle).ok()
+        } else {
+            None
+        };
+        self.attempt = Attempt::Waiting(timeout);
+    }
+}
+
+impl<H> Future for FailoverSendFuture<H>
+    where H: ClientHandle
+{
+    type Item = Message;
+    type Error = ClientError;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        loop {
+            let outcome = match self.attempt {
+                Attempt::Sending(ref mut future) => {
+                    match future.poll() {
+                        Ok(Async::Ready(response)) => {
+                            if response.get_response_code() != ResponseCode::ServFail {
+                                return Ok(Async::Ready(response));
+                            }
+                            Outcome::ServFail(response)
+                        }
+                        Ok(Async::NotReady) => return Ok(Async::NotReady),
+                        Err(error) => Outcome::Failed(error),
+                    }
+                }
+                Attempt::Waiting(Some(ref mut timeout)) => {
+                    match timeout.poll() {
+                        Ok(Async::NotReady) => return Ok(Async::NotReady),
+                        // a failed timer only ends the wait early
+                        Ok(Async::Ready(())) | Err(_) => Outcome::Send,
+                    }
+                }
+                Attempt::Waiting(None) => Outcome::Send,
+            };
+
+            match outcome {
+                Outcome::Send => self.send(),
+                Outcome::ServFail(response) => {
+                    if self.retry >= self.policy.retries {
+                        return Ok(Async::Ready(response));
+                    }
+
+                    debug!("SERVFAIL, retrying: {}", self.retry + 1);
+                    self.servfail = Some(response);
+                    self.backoff();
+                }
+                Outcome::Failed(error) => {
+                    if self.retry >= self.policy.retries {
+                        return match self.servfail.take() {
+                            Some(response) => Ok(Async::Ready(response)),
+                            None => Err(error),
+                        };
+                    }
+
+                    debug!("query failed, retrying: {}: {}", self.retry + 1, error);
+                    self.backoff();
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::Cell;
+    use std::rc::Rc;
+    use std::time::Duration;
+
+    use tokio_core::reactor::Core;
+
+    use ::client::*;
+    use ::error::*;
+    use ::op::*;
+    use ::rr::*;
+    use futures::*;
+
+    /// Fails, responds SERVFAIL, or responds, counting the queries it is sent
+    #[derive(Clone)]
+    struct TestClient {
+        response_code: Option<ResponseCode>,
+        sent: Rc<Cell<usize>>,
+    }
+
+    impl ClientHandle for TestClient {
+        fn send(&mut self, _: Message) -> Box<Future<Item = Message, Error = ClientError>> {
+            self.sent.set(self.sent.get() + 1);
+
+            match self.response_code {
+                Some(response_code) => {
+                    let mut response = Message::new();
+                    response.response_code(response_code);
+                    Box::new(finished(response))
+                }
+                None => Box::new(failed(ClientErrorKind::Timeout.into())),
+            }
+        }
+    }
+
+    fn test_client(response_code: Option<ResponseCode>) -> TestClient {
+        TestClient {
+            response_code: response_code,
+            sent: Rc::new(Cell::new(0)),
+        }
+    }
+
+    fn query(core: &mut Core,
+             client: &mut FailoverClientHandle<TestClient>)
+             -> ClientResult<Message> {
+        core.run(client.query(Name::parse("www.example.com.", None).unwrap(),
+                              DNSClass::IN,
+                              RecordType::A))
+    }
+
+    #[test]
+    fn test_failover() {
+        let mut core = Core::new().unwrap();
+        let timeout = test_client(None);
+        let servfail = test_client(Some(ResponseCode::ServFail));
+        let good = test_client(Some(ResponseCode::NoError));
+        let policy = RetryPolicy { backoff: Duration::from_millis(1), ..RetryPolicy::default() };
+        let mut client = FailoverClientHandle::new(vec![timeout.clone(),
+                                                        servfail.clone(),
+                                                        good.clone()],
+                                                   policy,
+                                                   core.handle());
+
+        let response = query(&mut core, &mut client).unwrap();
+        assert_eq!(response.get_response_code(), ResponseCode::NoError);
+        assert_eq!((timeout.sent.get(), servfail.sent.get(), good.sent.get()), (1, 1, 1));
+
+        // without rotation, the next query starts with the first again
+        query(&mut core, &mut client).unwrap();
+        assert_eq!((timeout.sent.get(), servfail.sent.get(), good.sent.get()), (2, 2, 2));
+    }
+
+    #[test]
+    fn test_retries_exhausted() {
+        let mut core = Core::new().unwrap();
+        let timeout = test_client(None);
+        let servfail = test_client(Some(ResponseCode::ServFail));
+        let policy = RetryPolicy {
+            retries: 3,
+            backoff: Duration::from_millis(0),
+            ..RetryPolicy::default()
+        };
+
+        let mut client = FailoverClientHandle::new(vec![timeout.clone()], policy, core.handle());
+        assert!(query(&mut core, &mut client).is_err());
+        assert_eq!(timeout.sent.get(), 4);
+
+        // a SERVFAIL is a better answer than a timeout
+        let mut client = FailoverClientHandle::new(vec![servfail.clone(), timeout.clone()],
+                                                   policy,
+                                                   core.handle());
+        let response = query(&mut core, &mut client).unwrap();
+        assert_eq!(response.get_response_code(), ResponseCode::ServFail);
+        assert_eq!(servfail.sent.get(), 2);
+    }
+
+    #[test]
+    fn test_rotate() {
+        let mut core = Core::new().unwrap();
+        let first = test_client(Some(ResponseCode::NoError));
+        let second = test_client(Some(ResponseCode::NoError));
+        let policy = RetryPolicy { rotate: true, ..RetryPolicy::default() };
+        let mut client = FailoverClientHandle::new(vec![first.clone(), second.clone()],
+                                                   policy,
+                                                   core.handle());
+
+        for _ in 0..4 {
+            query(&mut core, &mut client).unwrap();
+        }
+        assert_eq!((first.sent.get(), second.sent.get()), (2, 2));
+    }
+
+    #[test]
+    fn test_backoff() {
+        let policy = RetryPolicy::default();
+        assert_eq!(policy.get_backoff(0), Duration::from_millis(100));
+        assert_eq!(policy.get_backoff(3), Duration::from_millis(800));
+        assert_eq!(policy.get_backoff(10), Duration::from_secs(2));
+        assert_eq!(policy.get_backoff(100), Duration::from_secs(2));
+    }
+}
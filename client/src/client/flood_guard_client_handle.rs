@@ -0,0 +1,591 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::cell::RefCell;
+use std::cmp;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::{failed, finished, Future};
+
+use client::ClientHandle;
+use client::nxdomain::NxDomain;
+use clock::{Clock, SystemClock};
+use ::error::*;
+use op::{Message, ResponseCode};
+use rr::{DNSClass, LowerName, Name, RData};
+
+/// Nonexistent names remembered per zone, once reached expired names are dropped, and if none
+///  are, new names are not remembered
+const MAX_NXDOMAINS: usize = 1024;
+
+/// Zones tracked, once reached idle zones are dropped, and if none are, new zones are not tracked
+const MAX_ZONES: usize = 4096;
+
+/// When the queries for a zone are considered a random subdomain attack, and how it is throttled
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FloodThreshold {
+    /// responses for names in the zone within the window, before the NXDOMAIN ratio is considered
+    pub min_responses: u32,
+    /// fraction of the responses within the window which are NXDOMAIN, from 0.0 to 1.0
+    pub nxdomain_ratio: f64,
+    /// queries for the zone sent upstream each second during an attack
+    pub queries_per_second: u32,
+}
+
+impl Default for FloodThreshold {
+    fn default() -> Self {
+        FloodThreshold {
+            min_responses: 100,
+            nxdomain_ratio: 0.8,
+            queries_per_second: 10,
+        }
+    }
+}
+
+/// Counters of a zone, see `FloodGuardClientHandle::get_stats()`
+#[derive(Clone, Debug, PartialEq)]
+pub struct FloodGuardStats {
+    zone: Name,
+    responses: u64,
+    nxdomains: u64,
+    synthesized: u64,
+    throttled: u64,
+    attacked: bool,
+}
+
+impl FloodGuardStats {
+    /// The origin of the zone, from the SOA of its NXDOMAIN responses
+    pub fn get_zone(&self) -> &Name {
+        &self.zone
+    }
+
+    /// Responses received for names in the zone
+    pub fn get_responses(&self) -> u64 {
+        self.responses
+    }
+
+    /// NXDOMAIN responses received for names in the zone
+    pub fn get_nxdomains(&self) -> u64 {
+        self.nxdomains
+    }
+
+    /// NXDOMAIN responses synthesized during an attack, without sending the query
+    pub fn get_synthesized(&self) -> u64 {
+        self.synthesized
+    }
+
+    /// Queries failed with `Throttled` during an attack, without being sent
+    pub fn get_throttled(&self) -> u64 {
+        self.throttled
+    }
+
+    /// Returns true if the zone is currently considered under attack
+    pub fn is_attacked(&self) -> bool {
+        self.attacked
+    }
+}
+
+struct Zone {
+    origin: LowerName,
+    threshold: FloodThreshold,
+    /// start of the current window, seconds since the epoch
+    window_start: u32,
+    window_responses: u32,
+    window_nxdomains: u32,
+    /// the second, and the number of queries sent in it, during an attack
+    sent: (u32, u32),
+    nxdomains: HashMap<LowerName, NxDomain>,
+    stats: FloodGuardStats,
+}
+
+impl Zone {
+    fn new(origin: LowerName, threshold: FloodThreshold, now: u32) -> Self {
+        Zone {
+            stats: FloodGuardStats {
+                zone: (*origin).clone(),
+                responses: 0,
+                nxdomains: 0,
+                synthesized: 0,
+                throttled: 0,
+                attacked: false,
+            },
+            origin: origin,
+            threshold: threshold,
+            window_start: now,
+            window_responses: 0,
+            window_nxdomains: 0,
+            sent: (now, 0),
+            nxdomains: HashMap::new(),
+        }
+    }
+
+    /// Returns true if the NXDOMAIN ratio of the window is at or above the threshold
+    fn exceeds(&self, min_responses: u32) -> bool {
+        self.window_responses > 0 && self.window_responses >= min_responses &&
+        self.window_nxdomains as f64 >=
+        self.threshold.nxdomain_ratio * self.window_responses as f64
+    }
+
+    /// Starts a new window once the current one is over, an attack ends with the first window in
+    ///  which the ratio is below the threshold
+    ///
+    /// During an attack the queries are throttled, so any number of responses is enough.
+    fn roll_window(&mut self, window: u32, now: u32) {
+        let end = self.window_start.saturating_add(window);
+        if now < end {
+            return;
+        }
+
+        if self.stats.attacked && (now >= end.saturating_add(window) || !self.exceeds(1)) {
+            info!("random subdomain attack on: {} has subsided", self.origin);
+            self.stats.attacked = false;
+        }
+
+        self.window_start = now;
+        self.window_responses = 0;
+        self.window_nxdomains = 0;
+    }
+
+    /// Returns true if the window of the zone is over, and it is not under attack
+    fn is_idle(&self, window: u32, now: u32) -> bool {
+        !self.stats.attacked && now >= self.window_start.saturating_add(window)
+    }
+
+    /// Returns the NXDOMAIN response to the query, if the name or a name above it in the zone is
+    ///  known not to exist, RFC 8020
+    fn find_nxdomain(&self, request: &Message, name: &LowerName, now: u32) -> Option<Message> {
+        let dnssec_ok = request.get_edns().map_or(false, |edns| edns.is_dnssec_ok());
+        let checking_disabled = request.is_checking_disabled();
+
+        let mut name: Name = (**name).clone();
+        while self.origin != name && !name.is_root() {
+            if let Some(nxdomain) = self.nxdomains.get(&LowerName::new(&name)) {
+                if nxdomain.answers(dnssec_ok, checking_disabled, now) {
+                    return Some(nxdomain.synthesize(request));
+                }
+            }
+
+            name = name.base_name();
+        }
+
+        None
+    }
+
+    fn insert_nxdomain(&mut self, name: LowerName, nxdomain: NxDomain, now: u32) {
+        if self.nxdomains.len() >= MAX_NXDOMAINS {
+            let expired: Vec<LowerName> = self.nxdomains
+                .iter()
+                .filter(|&(_, nxdomain)| nxdomain.expires <= now)
+                .map(|(name, _)| name.clone())
+                .collect();
+            for name in expired {
+                self.nxdomains.remove(&name);
+            }
+
+            if self.nxdomains.len() >= MAX_NXDOMAINS {
+                return;
+            }
+        }
+
+        self.nxdomains.insert(name, nxdomain);
+    }
+
+    /// Returns true if another query may be sent upstream in this second
+    fn take_query(&mut self, now: u32) -> bool {
+        if self.sent.0 != now {
+            self.sent = (now, 0);
+        }
+
+        if self.sent.1 >= self.threshold.queries_per_second {
+            return false;
+        }
+
+        self.sent.1 += 1;
+        true
+    }
+}
+
+struct Guard {
+    /// seconds
+    window: u32,
+    threshold: FloodThreshold,
+    zone_thresholds: HashMap<LowerName, FloodThreshold>,
+    zones: HashMap<(LowerName, DNSClass), Zone>,
+}
+
+impl Guard {
+    /// Returns the key of the closest tracked zone containing the name
+    fn find_zone(&self, name: &LowerName, class: DNSClass) -> Option<(LowerName, DNSClass)> {
+        let mut name: Name = (**name).clone();
+        loop {
+            let key = (LowerName::new(&name), class);
+            if self.zones.contains_key(&key) {
+                return Some(key);
+            }
+
+            if name.is_root() {
+                return None;
+            }
+            name = name.base_name();
+        }
+    }
+
+    fn insert_zone(&mut self, origin: LowerName, class: DNSClass, now: u32) {
+        if self.zones.len() >= MAX_ZONES {
+            let window = self.window;
+            let idle: Vec<(LowerName, DNSClass)> = self.zones
+                .iter()
+                .filter(|&(_, zone)| zone.is_idle(window, now))
+                .map(|(key, _)| key.clone())
+                .collect();
+            for key in idle {
+                self.zones.remove(&key);
+            }
+
+            if self.zones.len() >= MAX_ZONES {
+                return;
+            }
+        }
+
+        let threshold = *self.zone_thresholds.get(&origin).unwrap_or(&self.threshold);
+        self.zones.insert((origin.clone(), class), Zone::new(origin, threshold, now));
+    }
+
+    /// Counts the response for the zone of the name, tracking the zone from the SOA of an NXDOMAIN
+    fn observe(&mut self,
+               name: &LowerName,
+               class: DNSClass,
+               request: &Message,
+               response: &Message,
+               now: u32) {
+        // the NXDOMAIN is for the target of any CNAME, not the name of the query
+        let is_nxdomain = response.get_response_code() == ResponseCode::NXDomain &&
+                          response.get_answers().is_empty();
+
+        // the negative TTL, RFC 2308
+        let soa = response.get_name_servers()
+            .iter()
+            .filter_map(|record| match *record.get_rdata() {
+                RData::SOA(ref soa) => {
+                    Some((LowerName::new(record.get_name()),
+                          cmp::min(record.get_ttl(), soa.get_minimum())))
+                }
+                _ => None,
+            })
+            .next();
+
+        if let Some((ref origin, _)) = soa {
+            if is_nxdomain && origin.zone_of(name) &&
+               !self.zones.contains_key(&(origin.clone(), class)) {
+                self.insert_zone(origin.clone(), class, now);
+            }
+        }
+
+        let key = match self.find_zone(name, class) {
+            Some(key) => key,
+            None => return,
+        };
+        let window = self.window;
+        let zone = self.zones.get_mut(&key).expect("zone not found");
+
+        zone.roll_window(window, now);
+        zone.stats.responses += 1;
+        zone.window_responses += 1;
+        if !is_nxdomain {
+            return;
+        }
+
+        zone.stats.nxdomains += 1;
+        zone.window_nxdomains += 1;
+
+        if let Some((_, ttl)) = soa {
+            if ttl > 0 && zone.origin != **name {
+                let nxdomain = NxDomain {
+                    response: response.clone(),
+                    dnssec_ok: request.get_edns().map_or(false, |edns| edns.is_dnssec_ok()),
+                    checking_disabled: request.is_checking_disabled(),
+                    expires: now.saturating_add(ttl),
+                };
+                zone.insert_nxdomain(name.clone(), nxdomain, now);
+            }
+        }
+
+        let min_responses = zone.threshold.min_responses;
+        if !zone.stats.attacked && zone.exceeds(min_responses) {
+            warn!("random subdomain attack on: {}, {} of {} responses are NXDOMAIN",
+                  zone.origin,
+                  zone.window_nxdomains,
+                  zone.window_responses);
+            zone.stats.attacked = true;
+        }
+    }
+}
+
+/// Mitigates random subdomain attacks, floods of queries for random names in a victim zone
+///
+/// Zones are tracked by the SOA of their NXDOMAIN responses. Once, within a window, the ratio of
+///  NXDOMAIN responses to all the responses for names in a zone reaches the threshold, the zone is
+///  considered under attack: queries for names at or below a name known not to exist are answered
+///  NXDOMAIN without being sent, RFC 8020, and of the others only `queries_per_second` are sent,
+///  the rest fail with `Throttled`. The attack ends with the first window below the threshold.
+///  All clones share the zones and their counters.
+#[derive(Clone)]
+#[must_use = "queries can only be sent through a ClientHandle"]
+pub struct FloodGuardClientHandle<H: ClientHandle> {
+    client: H,
+    guard: Rc<RefCell<Guard>>,
+    clock: Arc<Clock>,
+}
+
+impl<H> FloodGuardClientHandle<H>
+    where H: ClientHandle
+{
+    /// Returns a new handle wrapping the specified client
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - the client for the upstream
+    /// * `window` - period over which the NXDOMAIN ratio is measured, at least a second
+    /// * `threshold` - the threshold for the zones without one of their own
+    pub fn new(client: H, window: Duration, threshold: FloodThreshold) -> Self {
+        Self::with_clock(client, window, threshold, Arc::new(SystemClock))
+    }
+
+    /// Returns a new handle wrapping the specified client, measuring the windows by the clock
+    pub fn with_clock(client: H,
+                      window: Duration,
+                      threshold: FloodThreshold,
+                      clock: Arc<Clock>)
+                      -> Self {
+        FloodGuardClientHandle {
+            client: client,
+            guard: Rc::new(RefCell::new(Guard {
+                window: cmp::max(window.as_secs(), 1) as u32,
+                threshold: threshold,
+                zone_thresholds: HashMap::new(),
+                zones: HashMap::new(),
+            })),
+            clock: clock,
+        }
+    }
+
+    /// Sets the threshold for the zone, in place of the default one
+    pub fn set_zone_threshold(&mut self, zone: &Name, threshold: FloodThreshold) {
+        let origin = LowerName::new(zone);
+        let mut guard = self.guard.borrow_mut();
+
+        for tracked in guard.zones.values_mut().filter(|tracked| tracked.origin == origin) {
+            tracked.threshold = threshold;
+        }
+        guard.zone_thresholds.insert(origin, threshold);
+    }
+
+    /// Returns the counters of the tracked zones
+    pub fn get_stats(&self) -> Vec<FloodGuardStats> {
+        self.guard.borrow().zones.values().map(|zone| zone.stats.clone()).collect()
+    }
+}
+
+impl<H> ClientHandle for FloodGuardClientHandle<H>
+    where H: ClientHandle
+{
+    fn send(&mut self, message: Message) -> Box<Future<Item = Message, Error = ClientError>> {
+        let (name, class) = match message.get_queries().first() {
+            Some(query) => (LowerName::new(query.get_name()), query.get_query_class()),
+            None => return self.client.send(message),
+        };
+        let now = self.clock.now();
+
+        {
+            let mut guard = self.guard.borrow_mut();
+            let window = guard.window;
+
+            let key = guard.find_zone(&name, class);
+            if let Some(key) = key {
+                let zone = guard.zones.get_mut(&key).expect("zone not found");
+                zone.roll_window(window, now);
+
+                if zone.stats.attacked {
+                    if let Some(response) = zone.find_nxdomain(&message, &name, now) {
+                        zone.stats.synthesized += 1;
+                        return Box::new(finished(response));
+                    }
+
+                    if !zone.take_query(now) {
+                        zone.stats.throttled += 1;
+                        return Box::new(failed(ClientErrorKind::Throttled(zone.stats.zone.clone())
+                            .into()));
+                    }
+                }
+            }
+        }
+
+        let guard = self.guard.clone();
+        let clock = self.clock.clone();
+        let request = message.clone();
+
+        Box::new(self.client.send(message).map(move |response| {
+            guard.borrow_mut().observe(&name, class, &request, &response, clock.now());
+            response
+        }))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::Cell;
+    use std::rc::Rc;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use ::client::*;
+    use ::clock::ManualClock;
+    use ::error::*;
+    use ::op::*;
+    use ::rr::*;
+    use ::rr::rdata::SOA;
+    use futures::*;
+
+    /// Answers every name starting with `www` in example.com, and NXDOMAIN for all others
+    #[derive(Clone)]
+    struct VictimClient {
+        sends: Rc<Cell<u32>>,
+    }
+
+    impl ClientHandle for VictimClient {
+        fn send(&mut self, request: Message) -> Box<Future<Item = Message, Error = ClientError>> {
+            self.sends.set(self.sends.get() + 1);
+
+            let name = request.get_queries()[0].get_name().clone();
+            let mut message = Message::new();
+            message.id(request.get_id()).message_type(MessageType::Response);
+            if name.to_string().starts_with("www.") {
+                message.add_answer(Record::from_rdata(name,
+                                                      3600,
+                                                      RecordType::A,
+                                                      RData::A("192.0.2.1".parse().unwrap())));
+            } else {
+                let soa = SOA::new(Name::parse("ns.example.com.", None).unwrap(),
+                                   Name::parse("hostmaster.example.com.", None).unwrap(),
+                                   1,
+                                   3600,
+                                   600,
+                                   86400,
+                                   60);
+                message.response_code(ResponseCode::NXDomain)
+                    .add_name_server(Record::from_rdata(Name::parse("example.com.", None)
+                                                            .unwrap(),
+                                                        3600,
+                                                        RecordType::SOA,
+                                                        RData::SOA(soa)));
+            }
+
+            Box::new(finished(message))
+        }
+    }
+
+    fn query(name: &str) -> Message {
+        let mut query = Query::new();
+        query.name(Name::parse(name, None).unwrap()).query_type(RecordType::A);
+
+        let mut message = Message::new();
+        message.add_query(query);
+        message
+    }
+
+    fn threshold() -> FloodThreshold {
+        FloodThreshold {
+            min_responses: 4,
+            nxdomain_ratio: 0.5,
+            queries_per_second: 2,
+        }
+    }
+
+    #[test]
+    fn test_attack_throttled() {
+        let sends = Rc::new(Cell::new(0));
+        let clock = ManualClock::new(1000);
+        let mut client = FloodGuardClientHandle::with_clock(VictimClient { sends: sends.clone() },
+                                                            Duration::from_secs(10),
+                                                            threshold(),
+                                                            Arc::new(clock.clone()));
+
+        assert!(client.send(query("www.example.com.")).wait().is_ok());
+        assert!(client.get_stats().is_empty());
+
+        // random labels
+        for label in &["a1", "b2", "c3", "d4"] {
+            let name = format!("{}.example.com.", label);
+            assert_eq!(client.send(query(&name)).wait().unwrap().get_response_code(),
+                       ResponseCode::NXDomain);
+        }
+        let stats = client.get_stats();
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].get_zone(), &Name::parse("example.com.", None).unwrap());
+        assert_eq!(stats[0].get_nxdomains(), 4);
+        assert!(stats[0].is_attacked());
+        assert_eq!(sends.get(), 5);
+
+        // two queries a second
+        assert!(client.send(query("e5.example.com.")).wait().is_ok());
+        assert!(client.send(query("www.example.com.")).wait().is_ok());
+        if let &ClientErrorKind::Throttled(ref zone) = client.send(query("f6.example.com."))
+            .wait()
+            .unwrap_err()
+            .kind() {
+            assert_eq!(zone, &Name::parse("example.com.", None).unwrap());
+        } else {
+            assert!(false);
+        }
+        assert_eq!(sends.get(), 7);
+        assert_eq!(client.get_stats()[0].get_throttled(), 1);
+
+        // other zones are not affected
+        assert!(client.send(query("a1.example.net.")).wait().is_ok());
+
+        clock.advance(1);
+        assert!(client.send(query("f6.example.com.")).wait().is_ok());
+
+        // a window of answered queries ends the attack
+        clock.advance(10);
+        for _ in 0..2 {
+            assert!(client.send(query("www.example.com.")).wait().is_ok());
+        }
+        clock.advance(10);
+        assert!(client.send(query("www.example.com.")).wait().is_ok());
+        assert!(!client.get_stats()[0].is_attacked());
+    }
+
+    #[test]
+    fn test_attack_synthesized() {
+        let sends = Rc::new(Cell::new(0));
+        let clock = ManualClock::new(1000);
+        let mut client = FloodGuardClientHandle::with_clock(VictimClient { sends: sends.clone() },
+                                                            Duration::from_secs(10),
+                                                            FloodThreshold::default(),
+                                                            Arc::new(clock.clone()));
+        client.set_zone_threshold(&Name::parse("EXAMPLE.com.", None).unwrap(), threshold());
+
+        for label in &["a1", "b2", "c3", "d4"] {
+            let name = format!("{}.nx.example.com.", label);
+            client.send(query(&name)).wait().unwrap();
+        }
+        assert!(client.get_stats()[0].is_attacked());
+        assert_eq!(sends.get(), 4);
+
+        // below a nonexistent name, not sent
+        let response = client.send(query("x.a1.nx.example.com.")).wait().unwrap();
+        assert_eq!(response.get_response_code(), ResponseCode::NXDomain);
+        assert_eq!(response.get_queries()[0].get_name(),
+                   &Name::parse("x.a1.nx.example.com.", None).unwrap());
+        assert_eq!(sends.get(), 4);
+        assert_eq!(client.get_stats()[0].get_synthesized(), 1);
+    }
+}
@@ -0,0 +1,300 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::cell::{Ref, RefCell};
+use std::fs;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::{Duration, Instant, SystemTime};
+
+use futures::{finished, Future};
+
+use client::ClientHandle;
+use ::error::*;
+use op::{Message, MessageType, OpCode, ResponseCode};
+use rr::{DNSClass, RData, Record, RecordType};
+use system_conf::{read_hosts_file, Hosts};
+
+/// The TTL of the records answered from the hosts, zero so that they are not cached and a change
+///  of the hosts file applies at once
+pub const HOSTS_TTL: u32 = 0;
+
+/// The hosts file which is read again when it changes
+struct Watch {
+    path: PathBuf,
+    interval: Duration,
+    checked: Instant,
+    modified: Option<(SystemTime, u64)>,
+}
+
+/// The modification time and length of the file, either changes with its contents, the time may
+///  not on a file system which only keeps seconds
+fn modified(path: &Path) -> Option<(SystemTime, u64)> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified().map(|modified| (modified, metadata.len())))
+        .ok()
+}
+
+/// Answers the queries for the names of the hosts file without sending them
+///
+/// A and AAAA queries for a name of the hosts are answered with its addresses, or NODATA if it
+///  has none of the type, and PTR queries for an address of the hosts with its canonical name.
+///  Every other query is sent with the wrapped handle. All clones share the one hosts.
+#[derive(Clone)]
+#[must_use = "queries can only be sent through a ClientHandle"]
+pub struct HostsClientHandle<H: ClientHandle> {
+    client: H,
+    hosts: Rc<RefCell<Hosts>>,
+    watch: Option<Rc<RefCell<Watch>>>,
+}
+
+impl<H> HostsClientHandle<H>
+    where H: ClientHandle
+{
+    /// Returns a new handle wrapping the specified client, answering from the hosts
+    pub fn new(client: H, hosts: Hosts) -> HostsClientHandle<H> {
+        HostsClientHandle {
+            client: client,
+            hosts: Rc::new(RefCell::new(hosts)),
+            watch: None,
+        }
+    }
+
+    /// Returns a new handle wrapping the specified client, answering from the hosts file at the
+    ///  path, which is read again once it changes
+    ///
+    /// The modification time and length of the file are checked when a query is sent, at most
+    ///  once every `interval`. If the changed file cannot be read, its previous hosts are kept.
+    pub fn watch(client: H,
+                 path: &Path,
+                 interval: Duration)
+                 -> ClientResult<HostsClientHandle<H>> {
+        let modified = modified(path);
+        let mut handle = Self::new(client, try!(read_hosts_file(path)));
+        handle.watch = Some(Rc::new(RefCell::new(Watch {
+            path: path.to_owned(),
+            interval: interval,
+            checked: Instant::now(),
+            modified: modified,
+        })));
+
+        Ok(handle)
+    }
+
+    /// The hosts the queries are answered from
+    pub fn get_hosts(&self) -> Ref<Hosts> {
+        self.hosts.borrow()
+    }
+
+    /// Reads the watched hosts file again if it has changed, and the interval has passed
+    fn reload(&self) {
+        let mut watch = match self.watch {
+            Some(ref watch) => watch.borrow_mut(),
+            None => return,
+        };
+
+        let now = Instant::now();
+        if now.duration_since(watch.checked) < watch.interval {
+            return;
+        }
+        watch.checked = now;
+
+        let modified = modified(&watch.path);
+        if modified == watch.modified {
+            return;
+        }
+
+        match read_hosts_file(&watch.path) {
+            Ok(hosts) => {
+                info!("reloaded hosts: {:?}", watch.path);
+                *self.hosts.borrow_mut() = hosts;
+                watch.modified = modified;
+            }
+            Err(e) => warn!("could not reload hosts: {:?}: {}", watch.path, e),
+        }
+    }
+
+    /// The response to the request from the hosts, None if it is to be sent
+    fn answer(&self, request: &Message) -> Option<Message> {
+        if request.get_op_code() != OpCode::Query || request.get_queries().len() != 1 {
+            return None;
+        }
+        let query = &request.get_queries()[0];
+        if query.get_query_class() != DNSClass::IN {
+            return None;
+        }
+
+        let name = query.get_name();
+        let hosts = self.hosts.borrow();
+        let answers: Vec<Record> = match query.get_query_type() {
+            record_type @ RecordType::A |
+            record_type @ RecordType::AAAA => {
+                match hosts.lookup(name, record_type) {
+                    Some(addresses) => {
+                        addresses.into_iter()
+                            .map(|address| {
+                                let rdata = match address {
+                                    IpAddr::V4(ipv4) => RData::A(ipv4),
+                                    IpAddr::V6(ipv6) => RData::AAAA(ipv6),
+                                };
+                                Record::from_rdata(name.clone(), HOSTS_TTL, record_type, rdata)
+                            })
+                            .collect()
+                    }
+                    None => return None,
+                }
+            }
+            RecordType::PTR => {
+                match hosts.lookup_ptr(name) {
+                    Some(target) => {
+                        vec![Record::from_rdata(name.clone(),
+                                                HOSTS_TTL,
+                                                RecordType::PTR,
+                                                RData::PTR(target.clone()))]
+                    }
+                    None => return None,
+                }
+            }
+            _ => return None,
+        };
+
+        debug!("answering from hosts: {:?}", query);
+        let mut response = Message::new();
+        response.id(request.get_id())
+            .message_type(MessageType::Response)
+            .op_code(OpCode::Query)
+            .response_code(ResponseCode::NoError)
+            .authoritative(true)
+            .recursion_desired(request.is_recursion_desired())
+            .recursion_available(true)
+            .add_queries(request.get_queries().iter().cloned())
+            .add_answers(answers);
+        Some(response)
+    }
+}
+
+impl<H> ClientHandle for HostsClientHandle<H>
+    where H: ClientHandle
+{
+    fn send(&mut self, message: Message) -> Box<Future<Item = Message, Error = ClientError>> {
+        self.reload();
+
+        match self.answer(&message) {
+            Some(response) => Box::new(finished(response)),
+            None => self.client.send(message),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::Cell;
+    use std::fs::File;
+    use std::io::Write;
+    use std::net::{Ipv4Addr, Ipv6Addr};
+    use std::rc::Rc;
+    use std::time::Duration;
+
+    use ::client::*;
+    use ::error::*;
+    use ::op::*;
+    use ::rr::*;
+    use ::system_conf::parse_hosts;
+    use futures::*;
+    use tempdir::TempDir;
+
+    #[derive(Clone)]
+    struct TestClient {
+        sent: Rc<Cell<usize>>,
+    }
+
+    impl ClientHandle for TestClient {
+        fn send(&mut self, _: Message) -> Box<Future<Item = Message, Error = ClientError>> {
+            self.sent.set(self.sent.get() + 1);
+            Box::new(finished(Message::new()))
+        }
+    }
+
+    fn query(client: &mut HostsClientHandle<TestClient>,
+             name: &str,
+             query_type: RecordType)
+             -> Message {
+        client.query(Name::parse(name, None).unwrap(), DNSClass::IN, query_type)
+            .wait()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_answer_from_hosts() {
+        let sent = Rc::new(Cell::new(0));
+        let mut client = HostsClientHandle::new(TestClient { sent: sent.clone() },
+                                                parse_hosts("192.0.2.1 www.example.com\n\
+                                                             2001:db8::1 www.example.com\n"));
+
+        let response = query(&mut client, "WWW.example.com.", RecordType::A);
+        assert_eq!(response.get_response_code(), ResponseCode::NoError);
+        assert_eq!(response.get_answers().len(), 1);
+        assert_eq!(response.get_answers()[0].get_rdata(),
+                   &RData::A(Ipv4Addr::new(192, 0, 2, 1)));
+        assert_eq!(response.get_answers()[0].get_ttl(), HOSTS_TTL);
+
+        let response = query(&mut client, "www.example.com.", RecordType::AAAA);
+        assert_eq!(response.get_answers()[0].get_rdata(),
+                   &RData::AAAA(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1)));
+
+        let response = query(&mut client, "1.2.0.192.in-addr.arpa.", RecordType::PTR);
+        assert_eq!(response.get_answers()[0].get_rdata(),
+                   &RData::PTR(Name::parse("www.example.com.", None).unwrap()));
+        assert_eq!(sent.get(), 0);
+
+        // other types, and other names, are sent
+        query(&mut client, "www.example.com.", RecordType::MX);
+        query(&mut client, "example.com.", RecordType::A);
+        query(&mut client, "2.2.0.192.in-addr.arpa.", RecordType::PTR);
+        assert_eq!(sent.get(), 3);
+    }
+
+    #[test]
+    fn test_nodata_from_hosts() {
+        let sent = Rc::new(Cell::new(0));
+        let mut client = HostsClientHandle::new(TestClient { sent: sent.clone() },
+                                                parse_hosts("192.0.2.1 www.example.com\n"));
+
+        let response = query(&mut client, "www.example.com.", RecordType::AAAA);
+        assert_eq!(response.get_response_code(), ResponseCode::NoError);
+        assert!(response.get_answers().is_empty());
+        assert_eq!(sent.get(), 0);
+    }
+
+    #[test]
+    fn test_watch_hosts() {
+        let dir = TempDir::new("trust_dns_hosts_client_handle").unwrap();
+        let path = dir.path().join("hosts");
+        File::create(&path).unwrap().write_all(b"192.0.2.1 www.example.com\n").unwrap();
+
+        let sent = Rc::new(Cell::new(0));
+        let mut client = HostsClientHandle::watch(TestClient { sent: sent.clone() },
+                                                  &path,
+                                                  Duration::from_secs(0))
+            .unwrap();
+        assert!(!client.get_hosts().is_empty());
+
+        // the length of the file changes with the new contents, if its time does not
+        File::create(&path).unwrap().write_all(b"192.0.2.22 www.example.com\n").unwrap();
+
+        let response = query(&mut client, "www.example.com.", RecordType::A);
+        assert_eq!(response.get_answers()[0].get_rdata(),
+                   &RData::A(Ipv4Addr::new(192, 0, 2, 22)));
+        assert_eq!(sent.get(), 0);
+
+        assert!(HostsClientHandle::watch(TestClient { sent: sent.clone() },
+                                         &path.with_extension("missing"),
+                                         Duration::from_secs(0))
+            .is_err());
+    }
+}
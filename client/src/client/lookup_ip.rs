@@ -0,0 +1,196 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::cmp::Ordering;
+use std::net::{IpAddr, Ipv6Addr};
+
+use ::error::*;
+use op::Message;
+use rr::RData;
+
+/// Merges the responses to the AAAA and A queries of `ClientHandle::lookup_ip`, the addresses
+///  ordered by `sort_addresses`
+///
+/// Either query may fail, the addresses of the other are returned all the same; only if both
+///  failed is the error of the A query returned.
+pub fn merge_responses(aaaa: ClientResult<Message>,
+                       a: ClientResult<Message>)
+                       -> ClientResult<Vec<IpAddr>> {
+    let (aaaa, a) = match (aaaa, a) {
+        (Err(aaaa_error), Err(a_error)) => {
+            debug!("AAAA query failed: {}", aaaa_error);
+            return Err(a_error);
+        }
+        (aaaa, a) => (aaaa, a),
+    };
+
+    let mut addresses: Vec<IpAddr> = Vec::new();
+    for response in aaaa.iter().chain(a.iter()) {
+        // the answers are those of the name or of the targets of its CNAME chain
+        for record in response.get_answers() {
+            let address = match *record.get_rdata() {
+                RData::A(address) => IpAddr::V4(address),
+                RData::AAAA(address) => IpAddr::V6(address),
+                _ => continue,
+            };
+
+            if !addresses.contains(&address) {
+                addresses.push(address);
+            }
+        }
+    }
+
+    Ok(sort_addresses(addresses))
+}
+
+/// Orders destination addresses by the default policy table of RFC 6724, section 2.1
+///
+/// Without the source addresses of the host only two of the rules of section 6 apply: the
+///  higher precedence of the policy table first, rule 6, e.g. IPv6 before IPv4 but IPv4 before
+///  unique local and 6to4 IPv6 addresses, and then the smaller scope, rule 8. The sort is stable,
+///  addresses of the same precedence and scope stay in the order of the responses, rule 10.
+///
+/// ```text
+///   Prefix        Precedence Label
+///   ::1/128               50     0
+///   ::/0                  40     1
+///   ::ffff:0:0/96         35     4
+///   2002::/16             30     2
+///   2001::/32              5     5
+///   fc00::/7               3    13
+///   ::/96                  1     3
+///   fec0::/10              1    11
+///   3ffe::/16              1    12
+/// ```
+pub fn sort_addresses(mut addresses: Vec<IpAddr>) -> Vec<IpAddr> {
+    addresses.sort_by(|a, b| match precedence(b).cmp(&precedence(a)) {
+        Ordering::Equal => scope(a).cmp(&scope(b)),
+        ordering => ordering,
+    });
+    addresses
+}
+
+fn precedence(address: &IpAddr) -> u8 {
+    let address = match *address {
+        IpAddr::V4(_) => return 35,
+        IpAddr::V6(ref address) => address,
+    };
+    let segments = address.segments();
+
+    if *address == Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 1) {
+        50
+    } else if segments[..5] == [0, 0, 0, 0, 0] && segments[5] == 0xffff {
+        35
+    } else if segments[0] == 0x2002 {
+        30
+    } else if segments[0] == 0x2001 && segments[1] == 0 {
+        5
+    } else if segments[0] & 0xfe00 == 0xfc00 {
+        3
+    } else if segments[..6] == [0, 0, 0, 0, 0, 0] || segments[0] & 0xffc0 == 0xfec0 ||
+              segments[0] == 0x3ffe {
+        1
+    } else {
+        40
+    }
+}
+
+/// The scope of the address, RFC 4291 section 2.7 and RFC 6724 section 3.2 for IPv4
+fn scope(address: &IpAddr) -> u8 {
+    const LINK_LOCAL: u8 = 0x2;
+    const SITE_LOCAL: u8 = 0x5;
+    const GLOBAL: u8 = 0xe;
+
+    match *address {
+        IpAddr::V4(ref address) => {
+            let octets = address.octets();
+            if octets[0] == 127 || (octets[0] == 169 && octets[1] == 254) {
+                LINK_LOCAL
+            } else {
+                GLOBAL
+            }
+        }
+        IpAddr::V6(ref address) => {
+            let segments = address.segments();
+            if segments[0] & 0xff00 == 0xff00 {
+                (segments[0] & 0x000f) as u8
+            } else if *address == Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 1) ||
+                      segments[0] & 0xffc0 == 0xfe80 {
+                LINK_LOCAL
+            } else if segments[0] & 0xffc0 == 0xfec0 {
+                SITE_LOCAL
+            } else {
+                GLOBAL
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+    use ::error::*;
+    use op::Message;
+    use rr::{RData, Record, RecordType};
+    use rr::domain::Name;
+    use super::*;
+
+    fn v6(first: u16, last: u16) -> IpAddr {
+        IpAddr::V6(Ipv6Addr::new(first, 0xdb8, 0, 0, 0, 0, 0, last))
+    }
+
+    fn v4(last: u8) -> IpAddr {
+        IpAddr::V4(Ipv4Addr::new(192, 0, 2, last))
+    }
+
+    fn response(addresses: &[IpAddr]) -> Message {
+        let name = Name::parse("www.example.com.", None).unwrap();
+        let mut message = Message::new();
+        for address in addresses {
+            let (rr_type, rdata) = match *address {
+                IpAddr::V4(address) => (RecordType::A, RData::A(address)),
+                IpAddr::V6(address) => (RecordType::AAAA, RData::AAAA(address)),
+            };
+            message.add_answer(Record::from_rdata(name.clone(), 300, rr_type, rdata));
+        }
+        message
+    }
+
+    #[test]
+    fn test_sort_addresses() {
+        let loopback = IpAddr::V6(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 1));
+        let ula = v6(0xfd00, 1);
+        let six_to_four = v6(0x2002, 1);
+        let global = v6(0x2001, 1);
+        let link_local_v4 = IpAddr::V4(Ipv4Addr::new(169, 254, 0, 1));
+
+        assert_eq!(sort_addresses(vec![v4(1), ula, link_local_v4, six_to_four, global, loopback]),
+                   vec![loopback, global, link_local_v4, v4(1), six_to_four, ula]);
+
+        // the order of the responses is kept otherwise
+        assert_eq!(sort_addresses(vec![v4(2), v6(0x2001, 2), v4(1), v6(0x2001, 1)]),
+                   vec![v6(0x2001, 2), v6(0x2001, 1), v4(2), v4(1)]);
+    }
+
+    #[test]
+    fn test_merge_responses() {
+        let addresses = merge_responses(Ok(response(&[v6(0x2001, 1)])),
+                                        Ok(response(&[v4(1), v4(2)])))
+            .unwrap();
+        assert_eq!(addresses, vec![v6(0x2001, 1), v4(1), v4(2)]);
+
+        // either may fail
+        let error = || -> ClientResult<Message> { Err(ClientErrorKind::Message("timeout").into()) };
+        assert_eq!(merge_responses(error(), Ok(response(&[v4(1)]))).unwrap(),
+                   vec![v4(1)]);
+        assert_eq!(merge_responses(Ok(response(&[v6(0x2001, 1)])), error()).unwrap(),
+                   vec![v6(0x2001, 1)]);
+        assert!(merge_responses(error(), error()).is_err());
+        assert!(merge_responses(Ok(Message::new()), Ok(Message::new())).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,155 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! A bounded map, for the memoized responses and nonexistent names of the `MemoizeClientHandle`
+
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
+
+/// At most `max_entries` values, the least recently used is evicted to make room for a new one
+pub struct LruCache<K: Clone + Eq + Hash, V> {
+    entries: HashMap<K, (V, u64)>,
+    /// the keys of the entries, by their last use
+    used: BTreeMap<u64, K>,
+    uses: u64,
+    max_entries: usize,
+}
+
+impl<K: Clone + Eq + Hash, V> LruCache<K, V> {
+    /// Returns an empty cache, of at least one entry
+    pub fn new(max_entries: usize) -> Self {
+        LruCache {
+            entries: HashMap::new(),
+            used: BTreeMap::new(),
+            uses: 0,
+            max_entries: ::std::cmp::max(max_entries, 1),
+        }
+    }
+
+    /// The number of values, expired ones included
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// True if inserting a new key evicts the least recently used
+    pub fn is_full(&self) -> bool {
+        self.entries.len() >= self.max_entries
+    }
+
+    /// Changes the maximum, evicting the least recently used values beyond it
+    pub fn set_max_entries(&mut self, max_entries: usize) {
+        self.max_entries = ::std::cmp::max(max_entries, 1);
+        while self.entries.len() > self.max_entries {
+            self.evict();
+        }
+    }
+
+    /// Returns the value, which is then the most recently used
+    pub fn get(&mut self, key: &K) -> Option<&V> {
+        self.uses += 1;
+        let uses = self.uses;
+        match self.entries.get_mut(key) {
+            Some(&mut (ref value, ref mut used)) => {
+                self.used.remove(used);
+                self.used.insert(uses, key.clone());
+                *used = uses;
+                Some(value)
+            }
+            None => None,
+        }
+    }
+
+    /// Inserts or replaces the value, evicting the least recently used one at the maximum
+    pub fn insert(&mut self, key: K, value: V) {
+        self.uses += 1;
+        if let Some((_, used)) = self.entries.remove(&key) {
+            self.used.remove(&used);
+        }
+        while self.entries.len() >= self.max_entries {
+            self.evict();
+        }
+
+        self.used.insert(self.uses, key.clone());
+        self.entries.insert(key, (value, self.uses));
+    }
+
+    /// Keeps only the values for which the predicate is true, e.g. to drop the expired ones
+    pub fn retain<F>(&mut self, mut keep: F)
+        where F: FnMut(&K, &V) -> bool
+    {
+        let removed: Vec<(K, u64)> = self.entries
+            .iter()
+            .filter(|&(key, &(ref value, _))| !keep(key, value))
+            .map(|(key, &(_, used))| (key.clone(), used))
+            .collect();
+
+        for (key, used) in removed {
+            self.entries.remove(&key);
+            self.used.remove(&used);
+        }
+    }
+
+    fn evict(&mut self) {
+        let oldest = match self.used.keys().next() {
+            Some(&used) => used,
+            None => return,
+        };
+        if let Some(key) = self.used.remove(&oldest) {
+            debug!("evicting the least recently used entry");
+            self.entries.remove(&key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_least_recently_used() {
+        let mut cache = LruCache::new(2);
+        cache.insert("a", 1);
+        cache.insert("b", 2);
+        assert!(cache.get(&"a").is_some());
+
+        // b was used least recently
+        cache.insert("c", 3);
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get(&"b").is_none());
+        assert!(cache.get(&"a").is_some());
+        assert!(cache.get(&"c").is_some());
+
+        // replacing a value does not evict another
+        cache.insert("c", 4);
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get(&"c"), Some(&4));
+
+        cache.set_max_entries(1);
+        assert_eq!(cache.len(), 1);
+        assert!(cache.get(&"c").is_some());
+    }
+
+    #[test]
+    fn test_retain() {
+        let mut cache = LruCache::new(3);
+        cache.insert("a", 1);
+        cache.insert("b", 2);
+        cache.insert("c", 3);
+        assert!(cache.is_full());
+
+        cache.retain(|_, value| *value != 2);
+        assert_eq!(cache.len(), 2);
+        assert!(!cache.is_full());
+        assert!(cache.get(&"b").is_none());
+
+        // the order of use is kept for the others
+        cache.insert("d", 4);
+        cache.insert("e", 5);
+        assert!(cache.get(&"a").is_none());
+        assert!(cache.get(&"c").is_some());
+    }
+}
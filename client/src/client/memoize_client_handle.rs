@@ -4,29 +4,150 @@
 // http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
+use std::cmp;
 use std::rc::Rc;
-use std::collections::HashMap;
+use std::sync::Arc;
 
-use futures::Future;
+use futures::{finished, Future};
 
 use client::ClientHandle;
+use client::lru_cache::LruCache;
+use client::nxdomain::NxDomain;
 use client::rc_future::{rc_future, RcFuture};
+use clock::{Clock, SystemClock};
 use ::error::*;
-use op::{Message, Query};
+use op::{Message, Query, ResponseCode};
+use recursor::strip_unrelated_answers;
+use rr::{DNSClass, LowerName, Name, RData, Record, RecordType};
+
+/// The upper bound, in seconds, on the time a failure is reused, see
+///  `MemoizeClientHandle::set_error_ttl()`
+///
+/// [RFC 2308, Negative Caching, March 1998](https://tools.ietf.org/html/rfc2308#section-7.1)
+///
+/// ```text
+/// 7.1 Server Failure (OPTIONAL)
+///
+///    In either case a resolver MAY cache a server failure response.  If it
+///    does so it MUST NOT cache it for longer than five (5) minutes, and it
+///    MUST be cached against the specific query tuple <query name, type,
+///    class, server IP address>.
+/// ```
+pub const MAX_ERROR_TTL: u32 = 300;
+
+/// The nonexistent names remembered by default, see `MemoizeClientHandle::set_max_nxdomains()`
+pub const DEFAULT_MAX_NXDOMAINS: usize = 4096;
+
+/// The responses memoized by default, see `MemoizeClientHandle::set_max_queries()`
+pub const DEFAULT_MAX_QUERIES: usize = 4096;
+
+type NxDomains = LruCache<(LowerName, DNSClass), NxDomain>;
+type Queries = LruCache<QueryKey, Memoized>;
+
+/// Queries are memoized by their normalized name, so that case or escaping differences do not
+///  create distinct, or worse colliding, entries. The DO and CD bits are part of the key, a
+///  response to a query without DO will not have the RRSIGs a DNSSec aware requestor needs, and
+///  an unchecked (CD) response must not be handed to a requestor expecting validated data.
+type QueryKey = (LowerName, RecordType, DNSClass, bool, bool);
+
+fn query_key(message: &Message) -> QueryKey {
+    let query: &Query = message.get_queries().first().expect("no query!");
+    let dnssec_ok = message.get_edns().map_or(false, |edns| edns.is_dnssec_ok());
+
+    (LowerName::new(query.get_name()),
+     query.get_query_type(),
+     query.get_query_class(),
+     dnssec_ok,
+     message.is_checking_disabled())
+}
+
+/// The lowest TTL of all the records in the response, for a negative response this is capped by
+///  the SOA minimum, RFC 2308. A response without records is not kept at all.
+fn response_ttl(response: &Message) -> u32 {
+    response.get_answers()
+        .iter()
+        .chain(response.get_name_servers())
+        .chain(response.get_additionals())
+        .map(|record| match *record.get_rdata() {
+            RData::SOA(ref soa) => cmp::min(record.get_ttl(), soa.get_minimum()),
+            _ => record.get_ttl(),
+        })
+        .min()
+        .unwrap_or(0)
+}
+
+/// The response code is that of a failure, rather than an answer, NXDOMAIN included
+fn is_error_response(response: &Message) -> bool {
+    match response.get_response_code() {
+        ResponseCode::NoError | ResponseCode::NXDomain => false,
+        _ => true,
+    }
+}
+
+/// A query which is in flight, or has been answered
+struct Memoized {
+    response: RcFuture<Box<Future<Item = Message, Error = ClientError>>>,
+    /// set once the response is received, seconds since the epoch
+    expires: Rc<Cell<Option<u32>>>,
+    /// when the response was received, seconds since the epoch, the TTLs are counted down from it
+    received: Rc<Cell<u32>>,
+}
+
+impl Memoized {
+    fn is_expired(&self, now: u32) -> bool {
+        self.expires.get().map_or(false, |expires| expires <= now)
+    }
+}
+
+/// Counts the TTLs of the records down by the seconds since the response was received
+fn age_response(response: &mut Message, elapsed: u32) {
+    let age = |records: Vec<Record>| -> Vec<Record> {
+        records.into_iter()
+            .map(|mut record| {
+                let ttl = record.get_ttl().saturating_sub(elapsed);
+                record.ttl(ttl);
+                record
+            })
+            .collect()
+    };
+
+    let answers = age(response.take_answers());
+    response.insert_answers(answers);
+    let name_servers = age(response.take_name_servers());
+    response.insert_name_servers(name_servers);
+    let additionals = age(response.take_additionals());
+    response.insert_additionals(additionals);
+}
 
 /// Will return memoized (cached) responses to queries
 ///
 /// This wraps a ClientHandle, changing the implementation `send()` to store the response against
 ///  the Message.Query that was sent. This should reduce network traffic especially during things
-///  like DNSSec validation. *Warning* this will currently cache for the life of the Client.
+///  like DNSSec validation.
+///
+/// Requests for the same query while it is in flight all wait for the one response, after it is
+///  received it is reused for the lowest TTL of its records, with the TTLs counted down by the
+///  time since. Failures, errors and responses such as SERVFAIL, are only reused for the error
+///  TTL, none by default, see `set_error_ttl()`. Every requestor gets the response with the ID of
+///  its own request. At most `DEFAULT_MAX_QUERIES` responses are kept, the expired ones are
+///  dropped first and then the least recently used.
+///
+/// Answers which are not related to the question, see `strip_unrelated_answers`, are removed from
+///  the response before it is memoized.
+///
+/// An NXDOMAIN means that nothing exists below the name either, RFC 8020. For its negative TTL,
+///  queries for the name, or any name below it, are answered NXDOMAIN without being sent, and
+///  the responses memoized for names below it are dropped. At most `DEFAULT_MAX_NXDOMAINS` names
+///  are remembered, the least recently used are forgotten first.
 #[derive(Clone)]
 #[must_use = "queries can only be sent through a ClientHandle"]
 pub struct MemoizeClientHandle<H: ClientHandle> {
     client: H,
-    active_queries: Rc<RefCell<HashMap<Query,
-                                       RcFuture<Box<Future<Item = Message,
-                                                           Error = ClientError>>>>>>,
+    active_queries: Rc<RefCell<Queries>>,
+    nxdomains: Rc<RefCell<NxDomains>>,
+    error_ttl: u32,
+    clock: Arc<Clock>,
 }
 
 impl<H> MemoizeClientHandle<H>
@@ -34,70 +155,261 @@ impl<H> MemoizeClientHandle<H>
 {
     /// Returns a new handle wrapping the specified client
     pub fn new(client: H) -> MemoizeClientHandle<H> {
+        Self::with_clock(client, Arc::new(SystemClock))
+    }
+
+    /// Returns a new handle wrapping the specified client, expiring responses by the clock
+    pub fn with_clock(client: H, clock: Arc<Clock>) -> MemoizeClientHandle<H> {
         MemoizeClientHandle {
             client: client,
-            active_queries: Rc::new(RefCell::new(HashMap::new())),
+            active_queries: Rc::new(RefCell::new(LruCache::new(DEFAULT_MAX_QUERIES))),
+            nxdomains: Rc::new(RefCell::new(LruCache::new(DEFAULT_MAX_NXDOMAINS))),
+            error_ttl: 0,
+            clock: clock,
+        }
+    }
+
+    /// Seconds for which a failure to answer a query is reused, rather than the query being sent
+    ///  again, at most `MAX_ERROR_TTL`
+    ///
+    /// The failures are the responses with a response code other than NOERROR and NXDOMAIN, such
+    ///  as SERVFAIL, and the errors of the wrapped handle. Wrapping a `SecureClientHandle`, a
+    ///  response which fails validation is not validated again for this time, which bounds the
+    ///  work of repeated queries for a broken zone. Zero, the default, reuses no failures.
+    pub fn set_error_ttl(&mut self, error_ttl: u32) {
+        self.error_ttl = cmp::min(error_ttl, MAX_ERROR_TTL);
+    }
+
+    /// The most nonexistent names remembered, beyond it the least recently used are forgotten
+    pub fn set_max_nxdomains(&mut self, max_nxdomains: usize) {
+        self.nxdomains.borrow_mut().set_max_entries(max_nxdomains);
+    }
+
+    /// The most responses memoized, in flight included, beyond it the expired ones are dropped
+    ///  and then the least recently used
+    pub fn set_max_queries(&mut self, max_queries: usize) {
+        self.active_queries.borrow_mut().set_max_entries(max_queries);
+    }
+
+    /// Returns the NXDOMAIN response to the query, if the name or a name above it is known not
+    ///  to exist
+    fn find_nxdomain(&self, message: &Message, query: &QueryKey, now: u32) -> Option<Message> {
+        let &(ref name, _, class, dnssec_ok, checking_disabled) = query;
+        let mut nxdomains = self.nxdomains.borrow_mut();
+
+        let mut name: Name = (**name).clone();
+        loop {
+            if let Some(nxdomain) = nxdomains.get(&(LowerName::new(&name), class)) {
+                if nxdomain.answers(dnssec_ok, checking_disabled, now) {
+                    debug!("{} is below the nonexistent: {}",
+                           query.0,
+                           name);
+                    return Some(nxdomain.synthesize(message));
+                }
+            }
+
+            if name.is_root() {
+                return None;
+            }
+            name = name.base_name();
         }
     }
 }
 
+/// Remembers the name of an NXDOMAIN response, and drops the responses to queries below it
+fn insert_nxdomain(nxdomains: &RefCell<NxDomains>,
+                   active_queries: &RefCell<Queries>,
+                   query: &QueryKey,
+                   response: &Message,
+                   expires: u32) {
+    let &(ref name, _, class, dnssec_ok, checking_disabled) = query;
+
+    // the NXDOMAIN is for the target of any CNAME, not the name of the query
+    if response.get_response_code() != ResponseCode::NXDomain ||
+       !response.get_answers().is_empty() || name.is_root() {
+        return;
+    }
+
+    active_queries.borrow_mut().retain(|key, memoized| {
+        !(key.2 == class && name.zone_of(&key.0) && memoized.expires.get().is_some())
+    });
+
+    nxdomains.borrow_mut().insert((name.clone(), class),
+                                  NxDomain {
+                                      response: response.clone(),
+                                      dnssec_ok: dnssec_ok,
+                                      checking_disabled: checking_disabled,
+                                      expires: expires,
+                                  });
+}
+
 impl<H> ClientHandle for MemoizeClientHandle<H>
     where H: ClientHandle
 {
     fn send(&mut self, message: Message) -> Box<Future<Item = Message, Error = ClientError>> {
-        let query = message.get_queries().first().expect("no query!").clone();
+        let query = query_key(&message);
+        let id = message.get_id();
+        let now = self.clock.now();
 
-        if let Some(rc_future) = self.active_queries.borrow().get(&query) {
-            // FIXME check TTLs?
-            return Box::new(rc_future.clone());
+        if let Some(response) = self.find_nxdomain(&message, &query, now) {
+            return Box::new(finished(response));
         }
 
-        // check if there are active queries
-        {
-            let map = self.active_queries.borrow();
-            let request = map.get(&query);
-            if request.is_some() {
-                return Box::new(request.unwrap().clone());
+        // reuse the in flight or unexpired response
+        let cached = self.active_queries.borrow_mut().get(&query).and_then(|memoized| {
+            if memoized.is_expired(now) {
+                None
+            } else {
+                Some((memoized.response.clone(), memoized.received.clone()))
             }
-        }
+        });
+
+        let (response, received) = match cached {
+            Some(cached) => cached,
+            None => {
+                let expires = Rc::new(Cell::new(None));
+                let expires_on_response = expires.clone();
+                let expires_on_error = expires.clone();
+                let received = Rc::new(Cell::new(now));
+                let received_on_response = received.clone();
+                let clock = self.clock.clone();
+                let nxdomains = self.nxdomains.clone();
+                let active_queries = self.active_queries.clone();
+                let nxdomain_query = query.clone();
+                let question = message.get_queries()[0].clone();
+                let error_ttl = self.error_ttl;
+                let error_clock = self.clock.clone();
 
-        let request = rc_future(self.client.send(message));
-        let mut map = self.active_queries.borrow_mut();
-        map.insert(query, request.clone());
+                let response: Box<Future<Item = Message, Error = ClientError>> =
+                    Box::new(self.client
+                        .send(message)
+                        .map(move |mut response| {
+                            strip_unrelated_answers(&mut response, &question);
 
-        return Box::new(request);
+                            let is_error = is_error_response(&response);
+                            let ttl = if is_error {
+                                error_ttl
+                            } else {
+                                response_ttl(&response)
+                            };
+                            let now = clock.now();
+                            let expires = now.saturating_add(ttl);
+                            expires_on_response.set(Some(expires));
+                            received_on_response.set(now);
+
+                            if ttl > 0 && !is_error {
+                                insert_nxdomain(&nxdomains,
+                                                &active_queries,
+                                                &nxdomain_query,
+                                                &response,
+                                                expires);
+                            }
+                            response
+                        })
+                        .map_err(move |e| {
+                            let expires = if error_ttl > 0 {
+                                error_clock.now().saturating_add(error_ttl)
+                            } else {
+                                0
+                            };
+                            expires_on_error.set(Some(expires));
+                            e
+                        }));
+                let response = rc_future(response);
+
+                let mut active_queries = self.active_queries.borrow_mut();
+                if active_queries.is_full() {
+                    active_queries.retain(|_, memoized| !memoized.is_expired(now));
+                }
+                active_queries.insert(query,
+                                      Memoized {
+                                          response: response.clone(),
+                                          expires: expires,
+                                          received: received.clone(),
+                                      });
+                (response, received)
+            }
+        };
+
+        let clock = self.clock.clone();
+        Box::new(response.map(move |mut response| {
+            response.id(id);
+            let elapsed = clock.now().saturating_sub(received.get());
+            if elapsed > 0 {
+                age_response(&mut response, elapsed);
+            }
+            response
+        }))
     }
 }
 
 #[cfg(test)]
 mod test {
     use std::cell::Cell;
+    use std::net::{Ipv4Addr, Ipv6Addr};
+    use std::rc::Rc;
+    use std::sync::Arc;
     use ::client::*;
+    use ::clock::ManualClock;
     use ::error::*;
     use ::op::*;
     use ::rr::*;
+    use ::rr::rdata::SOA;
     use futures::*;
 
     #[derive(Clone)]
     struct TestClient {
-        i: Cell<u16>,
+        i: Cell<u32>,
+        ttl: u32,
+    }
+
+    impl TestClient {
+        fn new(ttl: u32) -> Self {
+            TestClient {
+                i: Cell::new(0),
+                ttl: ttl,
+            }
+        }
     }
 
     impl ClientHandle for TestClient {
-        fn send(&mut self, _: Message) -> Box<Future<Item = Message, Error = ClientError>> {
+        fn send(&mut self, request: Message) -> Box<Future<Item = Message, Error = ClientError>> {
             let mut message = Message::new();
             let i = self.i.get();
 
-            message.id(i);
+            // the address tells which upstream response this is
+            let record = match request.get_queries()[0].get_query_type() {
+                RecordType::AAAA => {
+                    Record::from_rdata(Name::root(),
+                                       self.ttl,
+                                       RecordType::AAAA,
+                                       RData::AAAA(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, i as u16)))
+                }
+                _ => {
+                    Record::from_rdata(Name::root(),
+                                       self.ttl,
+                                       RecordType::A,
+                                       RData::A(Ipv4Addr::from(i)))
+                }
+            };
+            message.add_answer(record);
             self.i.set(i + 1);
 
             Box::new(finished(message))
         }
     }
 
+    fn upstream(message: &Message) -> u32 {
+        match *message.get_answers()[0].get_rdata() {
+            RData::A(addr) => u32::from(addr),
+            RData::AAAA(addr) => addr.segments()[7] as u32,
+            _ => panic!("not an A or AAAA record"),
+        }
+    }
+
     #[test]
     fn test_memoized() {
-        let mut client = MemoizeClientHandle::new(TestClient { i: Cell::new(0) });
+        let mut client = MemoizeClientHandle::new(TestClient::new(3600));
 
         let mut test1 = Message::new();
         test1.add_query(Query::new().query_type(RecordType::A).clone());
@@ -106,17 +418,321 @@ mod test {
         test2.add_query(Query::new().query_type(RecordType::AAAA).clone());
 
         let result = client.send(test1.clone()).wait().ok().unwrap();
-        assert_eq!(result.get_id(), 0);
+        assert_eq!(upstream(&result), 0);
 
         let result = client.send(test2.clone()).wait().ok().unwrap();
-        assert_eq!(result.get_id(), 1);
+        assert_eq!(upstream(&result), 1);
 
         // should get the same result for each...
         let result = client.send(test1).wait().ok().unwrap();
-        assert_eq!(result.get_id(), 0);
+        assert_eq!(upstream(&result), 0);
 
         let result = client.send(test2).wait().ok().unwrap();
-        assert_eq!(result.get_id(), 1);
+        assert_eq!(upstream(&result), 1);
+    }
+
+    #[test]
+    fn test_memoized_dnssec_bits() {
+        let mut client = MemoizeClientHandle::new(TestClient::new(3600));
+
+        let mut test1 = Message::new();
+        test1.add_query(Query::new().query_type(RecordType::A).clone());
+
+        let mut test2 = test1.clone();
+        test2.get_edns_mut().set_dnssec_ok(true);
+
+        let mut test3 = test2.clone();
+        test3.checking_disabled(true);
+
+        let result = client.send(test1.clone()).wait().ok().unwrap();
+        assert_eq!(upstream(&result), 0);
+
+        // a DO request must not be answered from the cache without RRSIGs
+        let result = client.send(test2.clone()).wait().ok().unwrap();
+        assert_eq!(upstream(&result), 1);
+
+        let result = client.send(test3.clone()).wait().ok().unwrap();
+        assert_eq!(upstream(&result), 2);
+
+        let result = client.send(test2).wait().ok().unwrap();
+        assert_eq!(upstream(&result), 1);
+    }
+
+    #[test]
+    fn test_memoized_ids() {
+        let mut client = MemoizeClientHandle::new(TestClient::new(3600));
+
+        let mut test1 = Message::new();
+        test1.id(10).add_query(Query::new().query_type(RecordType::A).clone());
+
+        let mut test2 = test1.clone();
+        test2.id(20);
+
+        // both wait on the one request in flight
+        let first = client.send(test1);
+        let second = client.send(test2);
+
+        let result = second.wait().ok().unwrap();
+        assert_eq!(upstream(&result), 0);
+        assert_eq!(result.get_id(), 20);
+
+        let result = first.wait().ok().unwrap();
+        assert_eq!(upstream(&result), 0);
+        assert_eq!(result.get_id(), 10);
+    }
+
+    #[test]
+    fn test_memoized_expired() {
+        let mut client = MemoizeClientHandle::new(TestClient::new(0));
+
+        let mut test1 = Message::new();
+        test1.add_query(Query::new().query_type(RecordType::A).clone());
+
+        let result = client.send(test1.clone()).wait().ok().unwrap();
+        assert_eq!(upstream(&result), 0);
+
+        // the response has expired as soon as it was received
+        let result = client.send(test1).wait().ok().unwrap();
+        assert_eq!(upstream(&result), 1);
+    }
+
+    #[test]
+    fn test_memoized_clock() {
+        let clock = ManualClock::new(1000);
+        let mut client = MemoizeClientHandle::with_clock(TestClient::new(60),
+                                                         Arc::new(clock.clone()));
+
+        let mut test1 = Message::new();
+        test1.add_query(Query::new().query_type(RecordType::A).clone());
+
+        let result = client.send(test1.clone()).wait().ok().unwrap();
+        assert_eq!(upstream(&result), 0);
+
+        clock.advance(59);
+        let result = client.send(test1.clone()).wait().ok().unwrap();
+        assert_eq!(upstream(&result), 0);
+
+        clock.advance(1);
+        let result = client.send(test1).wait().ok().unwrap();
+        assert_eq!(upstream(&result), 1);
+    }
+
+    #[test]
+    fn test_memoized_ttl_counted_down() {
+        let clock = ManualClock::new(1000);
+        let mut client = MemoizeClientHandle::with_clock(TestClient::new(60),
+                                                         Arc::new(clock.clone()));
+
+        let mut test1 = Message::new();
+        test1.add_query(Query::new().query_type(RecordType::A).clone());
+
+        let result = client.send(test1.clone()).wait().ok().unwrap();
+        assert_eq!(result.get_answers()[0].get_ttl(), 60);
+
+        clock.advance(20);
+        let result = client.send(test1).wait().ok().unwrap();
+        assert_eq!(upstream(&result), 0);
+        assert_eq!(result.get_answers()[0].get_ttl(), 40);
+    }
+
+    #[test]
+    fn test_max_queries() {
+        let clock = ManualClock::new(1000);
+        let mut client = MemoizeClientHandle::with_clock(TestClient::new(60),
+                                                         Arc::new(clock.clone()));
+        client.set_max_queries(2);
+
+        let mut test1 = Message::new();
+        test1.add_query(Query::new().query_type(RecordType::A).clone());
+        let mut test2 = Message::new();
+        test2.add_query(Query::new().query_type(RecordType::AAAA).clone());
+        let mut test3 = test1.clone();
+        test3.checking_disabled(true);
+
+        assert_eq!(upstream(&client.send(test1.clone()).wait().ok().unwrap()), 0);
+        clock.advance(30);
+        assert_eq!(upstream(&client.send(test2.clone()).wait().ok().unwrap()), 1);
+        assert_eq!(upstream(&client.send(test1.clone()).wait().ok().unwrap()), 0);
+
+        // the expired response is dropped rather than the least recently used one
+        clock.advance(35);
+        assert_eq!(upstream(&client.send(test3.clone()).wait().ok().unwrap()), 2);
+        assert_eq!(upstream(&client.send(test2).wait().ok().unwrap()), 1);
+
+        // otherwise the least recently used
+        assert_eq!(upstream(&client.send(test1).wait().ok().unwrap()), 3);
+        assert_eq!(upstream(&client.send(test3).wait().ok().unwrap()), 4);
     }
 
+    /// answers NXDOMAIN for the one name, and A records for all others
+    #[derive(Clone)]
+    struct NxClient {
+        sends: Rc<Cell<u32>>,
+        nxdomain: Name,
+    }
+
+    impl ClientHandle for NxClient {
+        fn send(&mut self, request: Message) -> Box<Future<Item = Message, Error = ClientError>> {
+            self.sends.set(self.sends.get() + 1);
+
+            let name = request.get_queries()[0].get_name().clone();
+            let mut message = Message::new();
+            message.id(request.get_id()).message_type(MessageType::Response);
+            if name == self.nxdomain {
+                let soa = SOA::new(Name::parse("ns.example.com.", None).unwrap(),
+                                   Name::parse("hostmaster.example.com.", None).unwrap(),
+                                   1,
+                                   3600,
+                                   600,
+                                   86400,
+                                   60);
+                message.response_code(ResponseCode::NXDomain)
+                    .add_name_server(Record::from_rdata(Name::parse("example.com.", None)
+                                                            .unwrap(),
+                                                        3600,
+                                                        RecordType::SOA,
+                                                        RData::SOA(soa)));
+            } else {
+                message.add_answer(Record::from_rdata(name,
+                                                      3600,
+                                                      RecordType::A,
+                                                      RData::A(Ipv4Addr::new(192, 0, 2, 1))));
+            }
+
+            Box::new(finished(message))
+        }
+    }
+
+    fn query(name: &str, query_type: RecordType) -> Message {
+        let mut message = Message::new();
+        message.id(10).add_query(Query::new()
+            .name(Name::parse(name, None).unwrap())
+            .query_type(query_type)
+            .clone());
+        message
+    }
+
+    #[test]
+    fn test_nxdomain_below() {
+        let clock = ManualClock::new(1000);
+        let sends = Rc::new(Cell::new(0));
+        let nx_client = NxClient {
+            sends: sends.clone(),
+            nxdomain: Name::parse("nx.example.com.", None).unwrap(),
+        };
+        let mut client = MemoizeClientHandle::with_clock(nx_client, Arc::new(clock.clone()));
+
+        let result = client.send(query("nx.example.com.", RecordType::A)).wait().unwrap();
+        assert_eq!(result.get_response_code(), ResponseCode::NXDomain);
+        assert_eq!(sends.get(), 1);
+
+        // nothing exists below it
+        let request = query("www.NX.example.com.", RecordType::AAAA);
+        let result = client.send(request.clone()).wait().unwrap();
+        assert_eq!(result.get_response_code(), ResponseCode::NXDomain);
+        assert_eq!(result.get_id(), 10);
+        assert_eq!(result.get_queries(), request.get_queries());
+        assert_eq!(result.get_name_servers()[0].get_rr_type(), RecordType::SOA);
+        assert_eq!(sends.get(), 1);
+
+        // but above it, and beside it, names are looked up
+        client.send(query("example.com.", RecordType::A)).wait().unwrap();
+        client.send(query("nx2.example.com.", RecordType::A)).wait().unwrap();
+        assert_eq!(sends.get(), 3);
+
+        // the proof was not requested, so a DO query is sent
+        let mut dnssec = query("www.nx.example.com.", RecordType::A);
+        dnssec.get_edns_mut().set_dnssec_ok(true);
+        client.send(dnssec).wait().unwrap();
+        assert_eq!(sends.get(), 4);
+
+        // the SOA minimum is the negative ttl
+        clock.advance(60);
+        let result = client.send(query("www.nx.example.com.", RecordType::AAAA)).wait().unwrap();
+        assert_eq!(result.get_response_code(), ResponseCode::NoError);
+        assert_eq!(sends.get(), 5);
+    }
+
+    #[test]
+    fn test_nxdomain_prunes() {
+        let clock = ManualClock::new(1000);
+        let sends = Rc::new(Cell::new(0));
+        let nx_client = NxClient {
+            sends: sends.clone(),
+            nxdomain: Name::parse("gone.example.com.", None).unwrap(),
+        };
+        let mut client = MemoizeClientHandle::with_clock(nx_client, Arc::new(clock.clone()));
+
+        client.send(query("www.gone.example.com.", RecordType::A)).wait().unwrap();
+        client.send(query("gone.example.com.", RecordType::A)).wait().unwrap();
+        assert_eq!(sends.get(), 2);
+
+        // once the NXDOMAIN expires, the earlier answer is not reused
+        clock.advance(60);
+        client.send(query("www.gone.example.com.", RecordType::A)).wait().unwrap();
+        assert_eq!(sends.get(), 3);
+    }
+
+    /// fails every query, with a SERVFAIL response or an error
+    #[derive(Clone)]
+    struct FailClient {
+        sends: Rc<Cell<u32>>,
+        servfail: bool,
+    }
+
+    impl ClientHandle for FailClient {
+        fn send(&mut self, request: Message) -> Box<Future<Item = Message, Error = ClientError>> {
+            self.sends.set(self.sends.get() + 1);
+
+            if !self.servfail {
+                return Box::new(failed(ClientErrorKind::Message("validation failed").into()));
+            }
+
+            let mut message = Message::new();
+            message.id(request.get_id())
+                .message_type(MessageType::Response)
+                .response_code(ResponseCode::ServFail);
+            Box::new(finished(message))
+        }
+    }
+
+    #[test]
+    fn test_error_ttl() {
+        for servfail in [true, false].iter() {
+            let clock = ManualClock::new(1000);
+            let sends = Rc::new(Cell::new(0));
+            let fail_client = FailClient {
+                sends: sends.clone(),
+                servfail: *servfail,
+            };
+            let mut client = MemoizeClientHandle::with_clock(fail_client.clone(),
+                                                             Arc::new(clock.clone()));
+
+            // by default every failure is sent again
+            client.send(query("www.example.com.", RecordType::A)).wait().ok();
+            client.send(query("www.example.com.", RecordType::A)).wait().ok();
+            assert_eq!(sends.get(), 2);
+
+            let mut client = MemoizeClientHandle::with_clock(fail_client,
+                                                             Arc::new(clock.clone()));
+            client.set_error_ttl(5);
+
+            let result = client.send(query("www.example.com.", RecordType::A)).wait();
+            assert_eq!(result.is_err(), !*servfail);
+            clock.advance(4);
+            let result = client.send(query("www.example.com.", RecordType::A)).wait();
+            assert_eq!(result.map(|response| response.get_response_code()).ok(),
+                       if *servfail { Some(ResponseCode::ServFail) } else { None });
+            assert_eq!(sends.get(), 3);
+
+            clock.advance(1);
+            client.send(query("www.example.com.", RecordType::A)).wait().ok();
+            assert_eq!(sends.get(), 4);
+        }
+
+        // at most five minutes, RFC 2308
+        let mut client = MemoizeClientHandle::new(TestClient::new(0));
+        client.set_error_ttl(3600);
+        assert_eq!(client.error_ttl, MAX_ERROR_TTL);
+    }
 }
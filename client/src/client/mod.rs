@@ -16,19 +16,63 @@
 
 //! DNS Client associated classes for performing queries and other operations.
 
+mod circuit_breaker_client_handle;
 mod client;
 mod client_connection;
+mod connection_builder;
 mod client_future;
+mod ddr;
+mod edns_client_handle;
+mod failover_client_handle;
+mod flood_guard_client_handle;
+mod hosts_client_handle;
+mod lookup_ip;
+mod lru_cache;
 mod memoize_client_handle;
+mod notify_sender;
+mod nxdomain;
+mod outstanding_config;
+mod per_query_tcp_client_handle;
+mod race_client_handle;
 mod rc_future;
 mod retry_client_handle;
+mod retry_policy;
+mod search_list;
 mod secure_client_handle;
+mod srv;
+mod tcp_fallback_client_handle;
+mod timeout_config;
+mod update_target;
+mod validation_cache;
 
+pub use self::circuit_breaker_client_handle::CircuitBreakerClientHandle;
 #[allow(deprecated)]
-pub use self::client::{Client, SecureSyncClient, SyncClient};
+pub use self::client::{Client, SecureSyncClient, SyncClient, MAX_CHAIN_ZONES};
 pub use self::client_connection::ClientConnection;
+pub use self::connection_builder::{BoxedClientConnection, BoxedMessageStream, ConnectionBuilder};
 pub use self::client_future::{ClientFuture, BasicClientHandle, ClientHandle, StreamHandle,
-                              ClientStreamHandle};
-pub use self::memoize_client_handle::MemoizeClientHandle;
+                              ClientStreamHandle, OutstandingStats};
+pub use self::ddr::{ddr_name, designated_resolvers, DesignatedProtocol, DesignatedResolver,
+                    DEFAULT_DOH_PORT, DEFAULT_DOT_PORT};
+pub use self::edns_client_handle::{EdnsClientHandle, DEFAULT_EDNS_PROBE_INTERVAL,
+                                    FALLBACK_EDNS_PAYLOAD};
+pub use self::failover_client_handle::FailoverClientHandle;
+pub use self::flood_guard_client_handle::{FloodGuardClientHandle, FloodGuardStats,
+                                          FloodThreshold};
+pub use self::hosts_client_handle::{HostsClientHandle, HOSTS_TTL};
+pub use self::lookup_ip::sort_addresses;
+pub use self::memoize_client_handle::{MemoizeClientHandle, DEFAULT_MAX_NXDOMAINS,
+                                       DEFAULT_MAX_QUERIES, MAX_ERROR_TTL};
+pub use self::notify_sender::{NotifySender, NotifyResults};
+pub use self::outstanding_config::{OutstandingConfig, OverflowPolicy};
+pub use self::per_query_tcp_client_handle::PerQueryTcpClientHandle;
+pub use self::race_client_handle::RaceClientHandle;
 pub use self::retry_client_handle::RetryClientHandle;
+pub use self::retry_policy::RetryPolicy;
+pub use self::search_list::{SearchList, SearchOutcome};
 pub use self::secure_client_handle::SecureClientHandle;
+pub use self::srv::{sort_srv, srv_name};
+pub use self::tcp_fallback_client_handle::TcpFallbackClientHandle;
+pub use self::timeout_config::TimeoutConfig;
+pub use self::update_target::{zone_of_response, UpdateTarget};
+pub use self::validation_cache::DEFAULT_VALIDATION_CACHE_SIZE;
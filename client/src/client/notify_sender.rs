@@ -0,0 +1,270 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use futures::{collect, failed, Future};
+use tokio_core::reactor::Handle;
+
+use client::{BasicClientHandle, ClientFuture, ClientHandle, RetryClientHandle, TimeoutConfig};
+use super::client_future::notify_message;
+use clock::{Clock, SystemClock};
+use ::error::*;
+use op::ResponseCode;
+use rr::{DNSClass, Record, RecordType};
+use rr::domain::Name;
+use rr::dnssec::{TSigner, TsigChain};
+use udp::UdpClientStream;
+
+/// The response code each secondary answered the NOTIFY with, or the error of the last attempt
+pub type NotifyResults = Vec<(SocketAddr, ClientResult<ResponseCode>)>;
+
+/// Sends a NOTIFY of a zone to each of its secondaries, RFC 1996, so that they refresh it
+///
+/// This is for applications which change a zone, through this crate or otherwise, and act as
+///  its primary. The NOTIFYs are sent concurrently, and the response code of each secondary is
+///  collected, NOTIMP included, which section 3.12 treats as complete. A secondary which fails
+///  to respond does not fail the others.
+///
+/// The NOTIFYs are signed with TSIG when a signer is given, the TSIG of the responses is not
+///  verified.
+pub struct NotifySender<H: ClientHandle = RetryClientHandle<BasicClientHandle>> {
+    secondaries: Vec<(SocketAddr, H)>,
+    tsigner: Option<Arc<TSigner>>,
+    clock: Arc<Clock>,
+}
+
+impl NotifySender<RetryClientHandle<BasicClientHandle>> {
+    /// Returns a sender to the secondaries over UDP
+    ///
+    /// # Arguments
+    ///
+    /// * `secondaries` - the addresses of the secondaries
+    /// * `timeouts` - the `query` timeout is the wait for the response to each NOTIFY
+    /// * `retries` - the number of times a NOTIFY is retransmitted without a response, section
+    ///               3.6
+    /// * `tsigner` - the TSIG signer of the NOTIFYs, if the secondaries authenticate them
+    /// * `reactor_handle` - the reactor on which the NOTIFYs are sent
+    pub fn new(secondaries: &[SocketAddr],
+               timeouts: TimeoutConfig,
+               retries: usize,
+               tsigner: Option<Arc<TSigner>>,
+               reactor_handle: &Handle)
+               -> Self {
+        let secondaries = secondaries.iter()
+            .map(|&secondary| {
+                let (stream, stream_handle) = UdpClientStream::new(secondary,
+                                                                   reactor_handle.clone());
+                let client = ClientFuture::with_timeouts(stream,
+                                                         stream_handle,
+                                                         reactor_handle.clone(),
+                                                         timeouts,
+                                                         None);
+                (secondary, RetryClientHandle::new(client, retries))
+            })
+            .collect();
+
+        NotifySender::from_clients(secondaries, tsigner)
+    }
+}
+
+impl<H> NotifySender<H>
+    where H: ClientHandle + 'static
+{
+    /// Returns a sender over the clients of the secondaries, e.g. over TCP
+    ///
+    /// # Arguments
+    ///
+    /// * `secondaries` - the addresses of the secondaries, with the client of each
+    /// * `tsigner` - the TSIG signer of the NOTIFYs, if the secondaries authenticate them
+    pub fn from_clients(secondaries: Vec<(SocketAddr, H)>, tsigner: Option<Arc<TSigner>>) -> Self {
+        NotifySender {
+            secondaries: secondaries,
+            tsigner: tsigner,
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Replaces the clock of the time signed of the TSIG, the system clock by default
+    pub fn set_clock(&mut self, clock: Arc<Clock>) {
+        self.clock = clock;
+    }
+
+    /// The addresses of the secondaries
+    pub fn get_secondaries(&self) -> Vec<SocketAddr> {
+        self.secondaries.iter().map(|&(secondary, _)| secondary).collect()
+    }
+
+    /// Notifies every secondary of the zone, once they all responded or failed
+    ///
+    /// # Arguments
+    ///
+    /// * `zone` - the origin of the zone which changed
+    /// * `class` - the class of the zone, most likely `DNSClass::IN`
+    /// * `soa` - the new SOA of the zone, a hint to the secondaries, section 3.7
+    pub fn notify(&mut self,
+                  zone: Name,
+                  class: DNSClass,
+                  soa: Option<Record>)
+                  -> Box<Future<Item = NotifyResults, Error = ClientError>> {
+        let mut message = notify_message(zone.clone(), class, RecordType::SOA, soa);
+        if let Some(ref tsigner) = self.tsigner {
+            let now = self.clock.now() as u64;
+            if let Err(e) = message.sign_tsig(tsigner, TsigChain::Request, now) {
+                warn!("could not sign notify of zone: {}: {}", zone, e);
+                return Box::new(failed(e.into()));
+            }
+        }
+
+        let notifies = self.secondaries
+            .iter_mut()
+            .map(|&mut (secondary, ref mut client)| {
+                debug!("notifying secondary: {} of zone: {}", secondary, zone);
+                client.send(message.clone())
+                    .then(move |result| {
+                        Ok::<_, ClientError>((secondary,
+                                              result.map(|response| response.get_response_code())))
+                    })
+            })
+            .collect::<Vec<_>>();
+
+        Box::new(collect(notifies))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::RefCell;
+    use std::net::SocketAddr;
+    use std::rc::Rc;
+
+    use tokio_core::reactor::Core;
+
+    use ::client::*;
+    use ::error::*;
+    use ::op::*;
+    use ::rr::*;
+    use futures::*;
+
+    /// Answers with the response code, or fails, recording the NOTIFY it is sent
+    #[derive(Clone)]
+    struct TestClient {
+        response_code: Option<ResponseCode>,
+        notified: Rc<RefCell<Option<Message>>>,
+    }
+
+    impl ClientHandle for TestClient {
+        fn send(&mut self, message: Message) -> Box<Future<Item = Message, Error = ClientError>> {
+            *self.notified.borrow_mut() = Some(message);
+
+            match self.response_code {
+                Some(response_code) => {
+                    let mut response = Message::new();
+                    response.response_code(response_code);
+                    Box::new(finished(response))
+                }
+                None => Box::new(failed(ClientErrorKind::Timeout.into())),
+            }
+        }
+    }
+
+    fn test_client(response_code: Option<ResponseCode>) -> TestClient {
+        TestClient {
+            response_code: response_code,
+            notified: Rc::new(RefCell::new(None)),
+        }
+    }
+
+    #[test]
+    fn test_notify() {
+        let mut core = Core::new().unwrap();
+        let first: SocketAddr = "192.0.2.1:53".parse().unwrap();
+        let second: SocketAddr = "192.0.2.2:53".parse().unwrap();
+        let third: SocketAddr = "192.0.2.3:53".parse().unwrap();
+        let good = test_client(Some(ResponseCode::NoError));
+        let notimp = test_client(Some(ResponseCode::NotImp));
+        let timeout = test_client(None);
+
+        let mut sender = NotifySender::from_clients(vec![(first, good.clone()),
+                                                         (second, notimp.clone()),
+                                                         (third, timeout.clone())],
+                                                    None);
+        assert_eq!(sender.get_secondaries(), vec![first, second, third]);
+
+        let origin = Name::parse("example.com.", None).unwrap();
+        let name = |name: &str| Name::parse(name, None).unwrap();
+        let soa = rdata::SOA::new(name("ns.example.com."),
+                                  name("root.example.com."),
+                                  2017,
+                                  3600,
+                                  600,
+                                  86400,
+                                  3600);
+        let soa = Record::from_rdata(origin.clone(), 3600, RecordType::SOA, RData::SOA(soa));
+
+        let results = core.run(sender.notify(origin, DNSClass::IN, Some(soa))).unwrap();
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].0, first);
+        assert_eq!(*results[0].1.as_ref().unwrap(), ResponseCode::NoError);
+        assert_eq!(results[1].0, second);
+        assert_eq!(*results[1].1.as_ref().unwrap(), ResponseCode::NotImp);
+        assert_eq!(results[2].0, third);
+        assert!(results[2].1.is_err());
+
+        // each was sent the NOTIFY with the SOA, unsigned
+        for client in &[good, notimp, timeout] {
+            let notified = client.notified.borrow();
+            let notified = notified.as_ref().unwrap();
+            assert_eq!(notified.get_op_code(), OpCode::Notify);
+            assert_eq!(notified.get_answers().len(), 1);
+            assert!(UpdateMessage::get_sig0(notified).is_empty());
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "openssl")]
+    fn test_notify_tsig() {
+        use std::sync::Arc;
+        use ::clock::ManualClock;
+        use ::rr::dnssec::{TSigner, TsigAlgorithm, TsigChain};
+
+        let mut core = Core::new().unwrap();
+        let secondary: SocketAddr = "192.0.2.1:53".parse().unwrap();
+        let client = test_client(Some(ResponseCode::NoError));
+        let key_name = Name::parse("notify-key.example.com.", None).unwrap();
+        let tsigner = Arc::new(TSigner::new(key_name.clone(),
+                                            TsigAlgorithm::HmacSha256,
+                                            b"the secret of the secondaries".to_vec()));
+
+        let mut sender = NotifySender::from_clients(vec![(secondary, client.clone())],
+                                                    Some(tsigner.clone()));
+        sender.set_clock(Arc::new(ManualClock::new(1_500_000_000)));
+        let origin = Name::parse("example.com.", None).unwrap();
+        let results = core.run(sender.notify(origin, DNSClass::IN, None)).unwrap();
+        assert_eq!(*results[0].1.as_ref().unwrap(), ResponseCode::NoError);
+
+        let notified = client.notified.borrow();
+        let notified = notified.as_ref().unwrap();
+        let tsigs = UpdateMessage::get_sig0(notified);
+        assert_eq!(tsigs.len(), 1);
+        assert_eq!(tsigs[0].get_name(), &key_name);
+        assert_eq!(tsigs[0].get_dns_class(), DNSClass::ANY);
+
+        // the MAC is over the message without its TSIG, as the secondary computes it
+        if let &RData::TSIG(ref tsig) = tsigs[0].get_rdata() {
+            assert_eq!(tsig.get_algorithm(), &TsigAlgorithm::HmacSha256.to_name());
+            assert_eq!(tsig.get_original_id(), notified.get_id());
+            assert_eq!(tsig.get_time_signed(), 1_500_000_000);
+            assert_eq!(tsig.get_mac(),
+                       &tsigner.sign_message(notified, TsigChain::Request, tsig.get_time_signed())
+                           .unwrap()[..]);
+        } else {
+            panic!("not a TSIG: {:?}", tsigs[0]);
+        }
+    }
+}
@@ -0,0 +1,47 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Nonexistent names, RFC 8020, shared by the `MemoizeClientHandle` and `FloodGuardClientHandle`
+
+use op::{Message, MessageType, ResponseCode};
+
+/// A name which does not exist, and so neither does anything below it
+pub struct NxDomain {
+    /// the authority section, the SOA and any proof of nonexistence, is reused for the names below
+    pub response: Message,
+    /// the response was to a query with DO, so it holds the proof
+    pub dnssec_ok: bool,
+    /// the response was to a query with CD, it may not have been validated
+    pub checking_disabled: bool,
+    /// seconds since the epoch
+    pub expires: u32,
+}
+
+impl NxDomain {
+    /// Returns true if the NXDOMAIN may be used to answer a query with the DO and CD bits
+    pub fn answers(&self, dnssec_ok: bool, checking_disabled: bool, now: u32) -> bool {
+        self.expires > now && (self.dnssec_ok || !dnssec_ok) &&
+        (!self.checking_disabled || checking_disabled)
+    }
+
+    /// Returns the NXDOMAIN response to the request, for a name at or below the nonexistent one
+    pub fn synthesize(&self, request: &Message) -> Message {
+        let mut response = Message::new();
+        response.id(request.get_id())
+            .message_type(MessageType::Response)
+            .op_code(request.get_op_code())
+            .response_code(ResponseCode::NXDomain)
+            .authoritative(self.response.is_authoritative())
+            .recursion_desired(request.is_recursion_desired())
+            .recursion_available(self.response.is_recursion_available())
+            .authentic_data(self.response.is_authentic_data())
+            .checking_disabled(request.is_checking_disabled())
+            .add_queries(request.get_queries().iter().cloned())
+            .add_name_servers(self.response.get_name_servers().iter().cloned());
+        response
+    }
+}
@@ -0,0 +1,44 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+/// What happens to a query sent while `max_outstanding` queries await their responses
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// the new query fails at once
+    RejectNew,
+    /// the query outstanding the longest fails to make room for the new one
+    DropOldest,
+}
+
+/// The bound on the queries of a `ClientFuture` awaiting their responses
+///
+/// Without a bound, an upstream which stops responding while queries keep being sent makes the
+///  pending queries grow until they time out, or without end if the query timeout is disabled.
+///  The queries failed for the bound fail with `ClientErrorKind::TooManyOutstanding`.
+///
+/// ```
+/// use trust_dns::client::{OutstandingConfig, OverflowPolicy};
+///
+/// let outstanding = OutstandingConfig { overflow: OverflowPolicy::DropOldest,
+///                                       ..OutstandingConfig::default() };
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OutstandingConfig {
+    /// the most queries awaiting their responses at once, at least 1
+    pub max_outstanding: usize,
+    /// what happens to the queries beyond the maximum
+    pub overflow: OverflowPolicy,
+}
+
+impl Default for OutstandingConfig {
+    fn default() -> Self {
+        OutstandingConfig {
+            max_outstanding: 4096,
+            overflow: OverflowPolicy::RejectNew,
+        }
+    }
+}
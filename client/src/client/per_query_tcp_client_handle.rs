@@ -0,0 +1,71 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::net::SocketAddr;
+
+use futures::Future;
+use tokio_core::reactor::Handle;
+
+use client::{ClientFuture, ClientHandle, TimeoutConfig};
+use ::error::*;
+use op::Message;
+use tcp::TcpClientStream;
+
+/// Sends every query over a new TCP connection, closed once the response is received
+///
+/// This is for the queries rarely sent over TCP, such as those retried by
+///  `TcpFallbackClientHandle` after a truncated UDP response, where a connection kept open in
+///  between would be closed by the idle timeout of the name server.
+#[derive(Clone)]
+#[must_use = "queries can only be sent through a ClientHandle"]
+pub struct PerQueryTcpClientHandle {
+    name_server: SocketAddr,
+    reactor_handle: Handle,
+    timeouts: TimeoutConfig,
+}
+
+impl PerQueryTcpClientHandle {
+    /// Returns a new handle, connections are only established as queries are sent
+    ///
+    /// # Arguments
+    ///
+    /// * `name_server` - address of the name server to use for queries
+    /// * `reactor_handle` - the reactor the connections are made on
+    /// * `timeouts` - the timeouts of each connection, see `ClientFuture::with_timeouts()`
+    pub fn new(name_server: SocketAddr,
+               reactor_handle: Handle,
+               timeouts: TimeoutConfig)
+               -> PerQueryTcpClientHandle {
+        PerQueryTcpClientHandle {
+            name_server: name_server,
+            reactor_handle: reactor_handle,
+            timeouts: timeouts,
+        }
+    }
+
+    /// The address of the name server queries are sent to
+    pub fn get_name_server(&self) -> SocketAddr {
+        self.name_server
+    }
+}
+
+impl ClientHandle for PerQueryTcpClientHandle {
+    fn send(&mut self, message: Message) -> Box<Future<Item = Message, Error = ClientError>> {
+        debug!("connecting for the query over TCP: {}", self.name_server);
+        let (stream, stream_handle) = TcpClientStream::with_timeouts(self.name_server,
+                                                                     self.reactor_handle.clone(),
+                                                                     self.timeouts);
+
+        // once this handle is dropped and the response received, the connection is closed
+        let mut client = ClientFuture::with_timeouts(stream,
+                                                     stream_handle,
+                                                     self.reactor_handle.clone(),
+                                                     self.timeouts,
+                                                     None);
+        client.send(message)
+    }
+}
@@ -0,0 +1,204 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use futures::{failed, select_ok, Future};
+
+use client::ClientHandle;
+use ::error::*;
+use op::{Message, Query, ResponseCode};
+use recursor::strip_unrelated_answers;
+
+/// Sends each query to all the upstreams at once, and returns the first valid response
+///
+/// A response is valid if it is NOERROR or NXDOMAIN, and is to the question that was asked. The
+///  answers unrelated to the question, see `strip_unrelated_answers`, are removed from it. Once
+///  one response is valid, the queries still in flight to the other upstreams are cancelled; if
+///  none is, the error of the last to fail is returned.
+///
+/// This trades load on the upstreams for the latency of the fastest of them. For DNSSEC, each
+///  upstream is a `SecureClientHandle`, a response which fails validation is then an error, and
+///  with `set_require_authentic_data()` the responses of validating upstreams must have AD.
+#[derive(Clone)]
+#[must_use = "queries can only be sent through a ClientHandle"]
+pub struct RaceClientHandle<H: ClientHandle> {
+    clients: Vec<H>,
+    require_authentic_data: bool,
+}
+
+impl<H> RaceClientHandle<H>
+    where H: ClientHandle
+{
+    /// Returns a new handle racing the specified clients, one for each upstream
+    pub fn new(clients: Vec<H>) -> RaceClientHandle<H> {
+        RaceClientHandle {
+            clients: clients,
+            require_authentic_data: false,
+        }
+    }
+
+    /// Whether only the responses with the AD bit are valid, those an upstream which validates
+    ///  DNSSEC found authentic, false by default
+    pub fn set_require_authentic_data(&mut self, require_authentic_data: bool) {
+        self.require_authentic_data = require_authentic_data;
+    }
+}
+
+/// The response if it is valid for the question, otherwise the reason it is not
+fn validate(mut response: Message,
+            query: Option<&Query>,
+            require_authentic_data: bool)
+            -> ClientResult<Message> {
+    match response.get_response_code() {
+        ResponseCode::NoError | ResponseCode::NXDomain => (),
+        response_code => return Err(ClientErrorKind::ErrorResponse(response_code).into()),
+    }
+
+    if let Some(query) = query {
+        if response.get_queries().first() != Some(query) {
+            return Err(ClientErrorKind::Message("response is not to the question").into());
+        }
+        strip_unrelated_answers(&mut response, query);
+    }
+
+    if require_authentic_data && !response.is_authentic_data() {
+        return Err(ClientErrorKind::Message("response is not authentic").into());
+    }
+
+    Ok(response)
+}
+
+impl<H> ClientHandle for RaceClientHandle<H>
+    where H: ClientHandle
+{
+    fn send(&mut self, message: Message) -> Box<Future<Item = Message, Error = ClientError>> {
+        if self.clients.is_empty() {
+            return Box::new(failed(ClientErrorKind::Message("no upstreams to race").into()));
+        }
+
+        let query = message.get_queries().first().cloned();
+        let require_authentic_data = self.require_authentic_data;
+        let races: Vec<Box<Future<Item = Message, Error = ClientError>>> = self.clients
+            .iter_mut()
+            .map(|client| {
+                let query = query.clone();
+                Box::new(client.send(message.clone()).and_then(move |response| {
+                    validate(response, query.as_ref(), require_authentic_data)
+                })) as Box<Future<Item = Message, Error = ClientError>>
+            })
+            .collect();
+
+        // dropping the rest of the races cancels them
+        Box::new(select_ok(races).map(|(response, rest)| {
+            debug!("race won, cancelling: {}", rest.len());
+            response
+        }))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::Cell;
+    use std::net::Ipv4Addr;
+    use std::rc::Rc;
+
+    use ::client::*;
+    use ::error::*;
+    use ::op::*;
+    use ::rr::*;
+    use futures::*;
+
+    /// Responds with the response code, or never if it is None
+    #[derive(Clone)]
+    struct TestClient {
+        response_code: Option<ResponseCode>,
+        address: Ipv4Addr,
+        sent: Rc<Cell<usize>>,
+    }
+
+    impl ClientHandle for TestClient {
+        fn send(&mut self, request: Message) -> Box<Future<Item = Message, Error = ClientError>> {
+            self.sent.set(self.sent.get() + 1);
+
+            let response_code = match self.response_code {
+                Some(response_code) => response_code,
+                None => return Box::new(empty()),
+            };
+
+            let query = request.get_queries()[0].clone();
+            let mut response = Message::new();
+            response.response_code(response_code)
+                .add_answer(Record::from_rdata(query.get_name().clone(),
+                                               300,
+                                               RecordType::A,
+                                               RData::A(self.address)))
+                .add_answer(Record::from_rdata(Name::parse("unrelated.example.", None).unwrap(),
+                                               300,
+                                               RecordType::A,
+                                               RData::A(self.address)))
+                .add_query(query);
+            Box::new(finished(response))
+        }
+    }
+
+    fn test_client(response_code: Option<ResponseCode>,
+                   last_octet: u8,
+                   sent: &Rc<Cell<usize>>)
+                   -> TestClient {
+        TestClient {
+            response_code: response_code,
+            address: Ipv4Addr::new(192, 0, 2, last_octet),
+            sent: sent.clone(),
+        }
+    }
+
+    fn query(client: &mut RaceClientHandle<TestClient>) -> ClientResult<Message> {
+        client.query(Name::parse("www.example.com.", None).unwrap(),
+                   DNSClass::IN,
+                   RecordType::A)
+            .wait()
+    }
+
+    #[test]
+    fn test_first_valid_response() {
+        let sent = Rc::new(Cell::new(0));
+        let mut client = RaceClientHandle::new(vec![test_client(Some(ResponseCode::ServFail),
+                                                                1,
+                                                                &sent),
+                                                    test_client(None, 2, &sent),
+                                                    test_client(Some(ResponseCode::NoError),
+                                                                3,
+                                                                &sent)]);
+
+        let response = query(&mut client).unwrap();
+        assert_eq!(sent.get(), 3);
+        assert_eq!(response.get_answers().len(), 1);
+        assert_eq!(response.get_answers()[0].get_rdata(),
+                   &RData::A(Ipv4Addr::new(192, 0, 2, 3)));
+    }
+
+    #[test]
+    fn test_no_valid_response() {
+        let sent = Rc::new(Cell::new(0));
+        let mut client = RaceClientHandle::new(vec![test_client(Some(ResponseCode::ServFail),
+                                                                1,
+                                                                &sent),
+                                                    test_client(Some(ResponseCode::Refused),
+                                                                2,
+                                                                &sent)]);
+        assert!(query(&mut client).is_err());
+
+        // without AD, nothing is authentic
+        let mut client = RaceClientHandle::new(vec![test_client(Some(ResponseCode::NoError),
+                                                                1,
+                                                                &sent)]);
+        assert!(query(&mut client).is_ok());
+        client.set_require_authentic_data(true);
+        assert!(query(&mut client).is_err());
+
+        assert!(query(&mut RaceClientHandle::new(vec![])).is_err());
+    }
+}
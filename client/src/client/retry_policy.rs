@@ -0,0 +1,54 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::cmp;
+use std::time::Duration;
+
+/// How a query is retried over a list of name servers, see `FailoverClientHandle`
+///
+/// As with `TimeoutConfig`, start from the defaults and override only what differs:
+///
+/// ```
+/// use std::time::Duration;
+/// use trust_dns::client::RetryPolicy;
+///
+/// let policy = RetryPolicy { retries: 4, rotate: true, ..RetryPolicy::default() };
+/// assert_eq!(policy.get_backoff(1), Duration::from_millis(200));
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RetryPolicy {
+    /// the number of times a query is sent again after a timeout, or another error, or a
+    ///  SERVFAIL, each time to the next name server
+    pub retries: usize,
+    /// each query starts with the name server after the one the previous started with, spreading
+    ///  the queries over them, rather than always with the first
+    pub rotate: bool,
+    /// the wait before the first retry, doubled for each one after, zero for none
+    pub backoff: Duration,
+    /// the longest wait before a retry
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            retries: 2,
+            rotate: false,
+            backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(2),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// The wait before the retry, counted from 0, the exponential backoff bounded by
+    ///  `max_backoff`
+    pub fn get_backoff(&self, retry: usize) -> Duration {
+        let factor = 1u32 << cmp::min(retry, 16);
+        cmp::min(self.backoff * factor, self.max_backoff)
+    }
+}
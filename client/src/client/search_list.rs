@@ -0,0 +1,145 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use ::error::*;
+use op::{Message, ResponseCode};
+use rr::domain::Name;
+
+/// The search domains of relative names and the `ndots` threshold, as of resolv.conf(5), see
+///  `Client::search`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchList {
+    search: Vec<Name>,
+    ndots: usize,
+}
+
+impl SearchList {
+    /// The search domains, in order, and the number of dots a name must have to be looked up as
+    ///  it is first
+    pub fn new(search: Vec<Name>, ndots: usize) -> Self {
+        SearchList {
+            search: search,
+            ndots: ndots,
+        }
+    }
+
+    /// The search domains, in order
+    pub fn get_search(&self) -> &[Name] {
+        &self.search
+    }
+
+    /// The number of dots a name must have to be looked up as it is before the search domains
+    pub fn get_ndots(&self) -> usize {
+        self.ndots
+    }
+
+    /// The names to look up for the name, in order, as the resolver of libc does
+    ///
+    /// A fully qualified name, ending with `.`, is only itself. Otherwise the name is first tried
+    ///  as it is, if it has at least `ndots` dots, and then in each of the search domains; a
+    ///  name with fewer dots is tried as it is last.
+    pub fn get_names(&self, name: &str) -> ParseResult<Vec<Name>> {
+        let root = Name::root();
+        let absolute = try!(Name::parse(name, Some(&root)));
+        if name.ends_with('.') {
+            return Ok(vec![absolute]);
+        }
+
+        let mut names: Vec<Name> = Vec::with_capacity(self.search.len() + 1);
+        let first = name.matches('.').count() >= self.ndots;
+        if first {
+            names.push(absolute.clone());
+        }
+        for domain in &self.search {
+            names.push(try!(Name::parse(name, Some(domain))));
+        }
+        if !first {
+            names.push(absolute);
+        }
+
+        Ok(names)
+    }
+}
+
+impl Default for SearchList {
+    /// No search domains, and an `ndots` of 1
+    fn default() -> Self {
+        SearchList::new(vec![], 1)
+    }
+}
+
+/// What the response to the query of one of the names of a search tells
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchOutcome {
+    /// the name has records of the type, the search ends
+    Answer,
+    /// the name exists without records of the type, NODATA, the search goes on, but this is the
+    ///  response if no other name has records
+    NoData,
+    /// the name does not exist, or the query failed, the search goes on
+    NotFound,
+}
+
+impl SearchOutcome {
+    /// The outcome of the response, as the resolver of libc takes it
+    pub fn of(response: &Message) -> Self {
+        match response.get_response_code() {
+            ResponseCode::NoError if !response.get_answers().is_empty() => SearchOutcome::Answer,
+            ResponseCode::NoError => SearchOutcome::NoData,
+            _ => SearchOutcome::NotFound,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use op::{Message, ResponseCode};
+    use rr::{RData, Record, RecordType};
+    use rr::domain::Name;
+    use super::*;
+
+    #[test]
+    fn test_get_names() {
+        let search_list = SearchList::new(vec![Name::parse("example.com.", None).unwrap(),
+                                               Name::parse("example.net.", None).unwrap()],
+                                          2);
+        let names = |name: &str| {
+            search_list.get_names(name)
+                .unwrap()
+                .iter()
+                .map(|name| name.to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(names("www"),
+                   vec!["www.example.com.", "www.example.net.", "www."]);
+        assert_eq!(names("www.dept"),
+                   vec!["www.dept.", "www.dept.example.com.", "www.dept.example.net."]);
+        assert_eq!(names("www.example.org."), vec!["www.example.org."]);
+        assert_eq!(SearchList::default().get_names("www.dept").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_search_outcome() {
+        let mut response = Message::new();
+        assert_eq!(SearchOutcome::of(&response), SearchOutcome::NoData);
+
+        response.add_answer(Record::from_rdata(Name::parse("www.example.com.", None).unwrap(),
+                                               300,
+                                               RecordType::A,
+                                               RData::A(Ipv4Addr::new(192, 0, 2, 1))));
+        assert_eq!(SearchOutcome::of(&response), SearchOutcome::Answer);
+
+        let mut response = Message::new();
+        response.response_code(ResponseCode::NXDomain);
+        assert_eq!(SearchOutcome::of(&response), SearchOutcome::NotFound);
+        response.response_code(ResponseCode::ServFail);
+        assert_eq!(SearchOutcome::of(&response), SearchOutcome::NotFound);
+    }
+}
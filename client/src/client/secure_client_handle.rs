@@ -5,23 +5,34 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
+use std::cell::RefCell;
 use std::clone::Clone;
 use std::collections::HashSet;
 use std::mem;
 use std::rc::Rc;
+use std::sync::Arc;
 
+#[cfg(feature = "openssl")]
+use data_encoding::base32hex;
 use futures::*;
 
 use client::ClientHandle;
+use client::validation_cache::{ValidationCache, DEFAULT_VALIDATION_CACHE_SIZE};
+use clock::{Clock, SystemClock};
 use ::error::*;
-use op::{Message, OpCode, Query};
+use op::{Message, OpCode, Query, ResponseCode};
 use rr::{domain, DNSClass, RData, Record, RecordType};
-use rr::dnssec::{Algorithm, KeyPair, SupportedAlgorithms, TrustAnchor};
-#[cfg(feature = "openssl")]
-use rr::dnssec::Signer;
+use rr::dnssec::{self, is_sig_current, Algorithm, SupportedAlgorithms, TrustAnchor};
 use rr::rdata::{DNSKEY, SIG};
+#[cfg(feature = "openssl")]
+use rr::rdata::NSEC3;
 use rr::rdata::opt::EdnsOption;
 
+/// The most iterations of the NSEC3 hash a denial of existence is validated with, those of more
+///  are rejected, RFC 9276 section 3.2
+#[cfg(feature = "openssl")]
+const MAX_NSEC3_ITERATIONS: u16 = 150;
+
 #[derive(Debug)]
 struct Rrset {
     pub name: domain::Name,
@@ -43,6 +54,8 @@ pub struct SecureClientHandle<H: ClientHandle + 'static> {
     request_depth: usize,
     minimum_key_len: usize,
     minimum_algorithm: Algorithm, // used to prevent down grade attacks...
+    clock: Arc<Clock>,
+    validation_cache: Rc<RefCell<ValidationCache>>,
 }
 
 impl<H> SecureClientHandle<H>
@@ -66,15 +79,42 @@ impl<H> SecureClientHandle<H>
     /// * `client` - client to use for all connections to a remote server.
     /// * `trust_anchor` - custom DNSKEYs that will be trusted, can be used to pin trusted keys.
     pub fn with_trust_anchor(client: H, trust_anchor: TrustAnchor) -> SecureClientHandle<H> {
+        Self::with_clock(client, trust_anchor, Arc::new(SystemClock))
+    }
+
+    /// Create a new SecureClientHandle wrapping the speicified client.
+    ///
+    /// RRSIGs are only accepted between their inception and expiration by the clock.
+    ///
+    /// # Arguments
+    /// * `client` - client to use for all connections to a remote server.
+    /// * `trust_anchor` - custom DNSKEYs that will be trusted, can be used to pin trusted keys.
+    /// * `clock` - source of the current time for checking the RRSIG validity period.
+    pub fn with_clock(client: H,
+                      trust_anchor: TrustAnchor,
+                      clock: Arc<Clock>)
+                      -> SecureClientHandle<H> {
         SecureClientHandle {
             client: client,
             trust_anchor: Rc::new(trust_anchor),
             request_depth: 0,
             minimum_key_len: 0,
             minimum_algorithm: Algorithm::RSASHA256,
+            clock: clock,
+            validation_cache:
+                Rc::new(RefCell::new(ValidationCache::new(DEFAULT_VALIDATION_CACHE_SIZE))),
         }
     }
 
+    /// Sets the number of RRSIG verification outcomes to keep, zero disables the cache
+    ///
+    /// The outcomes are kept until the expiration of the signatures, such that repeated queries
+    ///  for the same rrsets do not repeat the public key operations. The cache is shared with the
+    ///  clones of the handle made after this, and is replaced by an empty one.
+    pub fn set_validation_cache_size(&mut self, max_entries: usize) {
+        self.validation_cache = Rc::new(RefCell::new(ValidationCache::new(max_entries)));
+    }
+
     /// An internal function used to clone the client, but maintain some information back to the
     ///  original client, such as the request_depth such that infinite recurssion does
     ///  not occur.
@@ -85,6 +125,8 @@ impl<H> SecureClientHandle<H>
             request_depth: self.request_depth + 1,
             minimum_key_len: self.minimum_key_len,
             minimum_algorithm: self.minimum_algorithm,
+            clock: self.clock.clone(),
+            validation_cache: self.validation_cache.clone(),
         }
     }
 }
@@ -130,8 +172,19 @@ impl<H> ClientHandle for SecureClientHandle<H>
                 edns.set_option(dhu);
             }
 
+            // CD from the requestor means it will validate itself, pass everything through as is
+            //  (DO is still set above, so the RRSIGs are returned), but never claim AD for data
+            //  which was not validated here.
+            if message.is_checking_disabled() {
+                return Box::new(self.client
+                    .send(message)
+                    .map(|mut message_response| {
+                        message_response.authentic_data(false);
+                        message_response
+                    }));
+            }
+
             message.authentic_data(true);
-            message.checking_disabled(false);
             let dns_class =
                 message.get_queries().first().map_or(DNSClass::IN, |q| q.get_query_class());
 
@@ -145,22 +198,45 @@ impl<H> ClientHandle for SecureClientHandle<H>
                 })
                 .and_then(move |verified_message| {
                     // at this point all of the message is verified.
-                    //  This is where NSEC (and possibly NSEC3) validation occurs
-                    // As of now, only NSEC is supported.
+                    //  This is where NSEC and NSEC3 validation occurs
+                    let mut authentic = true;
                     if verified_message.get_answers().is_empty() {
-                        let nsecs = verified_message.get_name_servers()
+                        let nsec3s = verified_message.get_name_servers()
                             .iter()
-                            .filter(|rr| rr.get_rr_type() == RecordType::NSEC)
+                            .filter(|rr| rr.get_rr_type() == RecordType::NSEC3)
                             .collect::<Vec<_>>();
 
-                        if !verify_nsec(&query, nsecs) {
-                            // TODO change this to remove the NSECs, like we do for the others?
-                            return Err(ClientErrorKind::Message("could not validate nxdomain \
-                                                                 with NSEC")
-                                .into());
+                        if !nsec3s.is_empty() {
+                            let response_code = verified_message.get_response_code();
+                            match verify_nsec3(&query, response_code, nsec3s) {
+                                Nsec3Proof::Secure => (),
+                                // the denial holds, but an unsigned delegation could be hidden
+                                //  in the opted out span, RFC 5155 section 9.2
+                                Nsec3Proof::OptOut => authentic = false,
+                                Nsec3Proof::Invalid => {
+                                    return Err(ClientErrorKind::Message("could not validate \
+                                                                         nxdomain with NSEC3")
+                                        .into())
+                                }
+                            }
+                        } else {
+                            let nsecs = verified_message.get_name_servers()
+                                .iter()
+                                .filter(|rr| rr.get_rr_type() == RecordType::NSEC)
+                                .collect::<Vec<_>>();
+
+                            if !verify_nsec(&query, nsecs) {
+                                // TODO change this to remove the NSECs, like we do for the others?
+                                return Err(ClientErrorKind::Message("could not validate \
+                                                                     nxdomain with NSEC")
+                                    .into());
+                            }
                         }
                     }
 
+                    // everything remaining was validated locally, regardless of the upstream AD
+                    let mut verified_message = verified_message;
+                    verified_message.authentic_data(authentic);
                     Ok(verified_message)
                 }));
         }
@@ -353,54 +429,39 @@ impl Future for VerifyRrsetsFuture {
 
 /// Generic entrypoint to verify any RRSET against the provided signatures.
 ///
-/// Generally, the RRSET will be validated by `verify_default_rrset()`. There are additional
-///  checks that happen after the RRSET is successfully validated. In the case of DNSKEYs this
-///  triggers `verify_dnskey_rrset()`. If it's an NSEC record, then the NSEC record will be
-///  validated to prove it's correctness. There is a special case for DNSKEY, where if the RRSET
-///  is unsigned, `rrsigs` is empty, then an immediate `verify_dnskey_rrset()` is triggered. In
-///  this case, it's possible the DNSKEY is a trust_anchor and is not self-signed.
+/// Generally, the RRSET will be validated by `verify_default_rrset()`. DNSKEYs are instead
+///  validated by `verify_dnskey_rrset()`, signed or not, as an unsigned DNSKEY may be a
+///  trust_anchor.
 fn verify_rrset<H>(client: SecureClientHandle<H>,
                    rrset: Rrset,
                    rrsigs: Vec<Record>)
                    -> Box<Future<Item = Rrset, Error = ClientError>>
     where H: ClientHandle
 {
-    // Special case for unsigned DNSKEYs, it's valid for a DNSKEY to be bare in the zone if
-    //  it's a trust_anchor, though some DNS servers choose to self-sign in this case,
-    //  for self-signed KEYS they will drop through to the standard validation logic.
+    // DNSKEYs are validated against the trust_anchor or DS records, it's valid for a DNSKEY to
+    //  be bare in the zone if it's a trust_anchor, though some DNS servers choose to self-sign
+    //  in this case.
     if let RecordType::DNSKEY = rrset.record_type {
-        if rrsigs.is_empty() {
-            debug!("unsigned key: {}, {:?}", rrset.name, rrset.record_type);
-            // FIXME: validate that this DNSKEY is stronger than the one lower in the chain,
-            //  also, set the min algorithm to this algorithm to prevent downgrade attacks.
-            return verify_dnskey_rrset(client.clone_with_context(), rrset);
-        }
+        // FIXME: validate that this DNSKEY is stronger than the one lower in the chain,
+        //  also, set the min algorithm to this algorithm to prevent downgrade attacks.
+        return verify_dnskey_rrset(client.clone_with_context(), rrset, rrsigs);
     }
 
     // standard validation path
-    Box::new(verify_default_rrset(client.clone_with_context(), rrset, rrsigs)
-        .and_then(|rrset|
-          // POST validation
-          match rrset.record_type {
-            RecordType::DNSKEY => verify_dnskey_rrset(client, rrset),
-            // RecordType::DS => verify_ds_rrset(client, name, record_type, record_class, rrset, rrsigs),
-            _ => Box::new(finished(rrset)),
-          }
-        )
-        .map_err(|e| {
-          debug!("rrset failed validation: {}", e);
-          e
-        })
-      )
+    Box::new(verify_default_rrset(client.clone_with_context(), rrset, rrsigs).map_err(|e| {
+        debug!("rrset failed validation: {}", e);
+        e
+    }))
 }
 
 /// Verifies a dnskey rrset
 ///
-/// This first checks to see if the key is in the set of trust_anchors. If so then it's returned
-///  as a success. Otherwise, a query is sent to get the DS record, and the DNSKEY is validated
-///  against the DS record.
+/// This first checks to see if any of the keys are in the set of trust_anchors. Otherwise, a
+///  query is sent to get the DS record, and the DNSKEYs are validated against the DS records.
+///  The keys found this way are the entry points, see `trust_dnskey_rrset()`.
 fn verify_dnskey_rrset<H>(mut client: SecureClientHandle<H>,
-                          rrset: Rrset)
+                          rrset: Rrset,
+                          rrsigs: Vec<Record>)
                           -> Box<Future<Item = Rrset, Error = ClientError>>
     where H: ClientHandle
 {
@@ -408,6 +469,18 @@ fn verify_dnskey_rrset<H>(mut client: SecureClientHandle<H>,
            rrset.name,
            rrset.record_type);
 
+    // only current self-signatures are of interest for DNSKEYs
+    let now = client.clock.now();
+    let sigs = rrsigs.into_iter()
+        .filter_map(|rrsig| if let RData::SIG(sig) = rrsig.unwrap_rdata() {
+            Some(sig)
+        } else {
+            None
+        })
+        .filter(|sig| sig.get_signer_name() == &rrset.name)
+        .filter(|sig| is_sig_current(sig, now))
+        .collect::<Vec<SIG>>();
+
     // check the DNSKEYS against the trust_anchor, if it's approved allow it.
     {
         let anchored_keys = rrset.records
@@ -428,19 +501,53 @@ fn verify_dnskey_rrset<H>(mut client: SecureClientHandle<H>,
             .collect::<Vec<usize>>();
 
         if !anchored_keys.is_empty() {
-            let mut rrset = rrset;
-            preserve(&mut rrset.records, anchored_keys);
+            return Box::new(done(trust_dnskey_rrset(rrset,
+                                                    &sigs,
+                                                    anchored_keys,
+                                                    &client.validation_cache,
+                                                    now)));
+        }
+    }
 
-            debug!("validated dnskey with trust_anchor: {}, {}",
-                   rrset.name,
-                   rrset.records.len());
-            return Box::new(finished((rrset)));
+    // the DS rrset need not be queried again while the matches of all the keys are cached
+    let mut valid_keys = Vec::new();
+    let mut is_cached = true;
+    for (i, rr) in rrset.records.iter().enumerate() {
+        if let &RData::DNSKEY(ref rdata) = rr.get_rdata() {
+            let key = ValidationCache::ds_key(rdata, &rrset.name, rrset.record_class);
+            match client.validation_cache.borrow_mut().get(&key, now) {
+                Some(true) => valid_keys.push(i),
+                Some(false) => (),
+                None => {
+                    is_cached = false;
+                    break;
+                }
+            }
         }
     }
+    if is_cached {
+        debug!("cached ds matches of dnskey: {}", rrset.name);
+        return Box::new(done(trust_dnskey_rrset(rrset,
+                                                &sigs,
+                                                valid_keys,
+                                                &client.validation_cache,
+                                                now)));
+    }
 
     // need to get DS records for each DNSKEY
+    let validation_cache = client.validation_cache.clone();
     let valid_dnskey = client.query(rrset.name.clone(), rrset.record_class, RecordType::DS)
         .and_then(move |ds_message| {
+            let ds_records = ds_message.get_answers()
+                .iter()
+                .filter(|ds| ds.get_rr_type() == RecordType::DS)
+                .collect::<Vec<&Record>>();
+            // the matches are kept as long as the DS rrset, there is nothing to keep without one
+            let expiration = ds_records.iter()
+                .map(|ds| ds.get_ttl())
+                .min()
+                .map(|ttl| now.wrapping_add(ttl));
+
             let valid_keys = rrset.records
                 .iter()
                 .enumerate()
@@ -451,9 +558,7 @@ fn verify_dnskey_rrset<H>(mut client: SecureClientHandle<H>,
                     None
                 })
                 .filter(|&(_, key_rdata)| {
-                    ds_message.get_answers()
-                              .iter()
-                              .filter(|ds| ds.get_rr_type() == RecordType::DS)
+                    let is_covered = ds_records.iter()
                               .filter_map(|ds| if let &RData::DS(ref ds_rdata) = ds.get_rdata() {
                                 Some(ds_rdata)
                               } else {
@@ -461,25 +566,74 @@ fn verify_dnskey_rrset<H>(mut client: SecureClientHandle<H>,
                               })
                               // must be convered by at least one DS record
                               .any(|ds_rdata| ds_rdata.covers(&rrset.name, key_rdata)
-                                                      .unwrap_or(false))
+                                                      .unwrap_or(false));
+
+                    if let Some(expiration) = expiration {
+                        let key =
+                            ValidationCache::ds_key(key_rdata, &rrset.name, rrset.record_class);
+                        validation_cache.borrow_mut().insert(key, is_covered, expiration, now);
+                    }
+                    is_covered
                 })
                 .map(|(i, _)| i)
                 .collect::<Vec<usize>>();
 
-            if !valid_keys.is_empty() {
-                let mut rrset = rrset;
-                preserve(&mut rrset.records, valid_keys);
-
-                debug!("validated dnskey: {}, {}", rrset.name, rrset.records.len());
-                Ok(rrset)
-            } else {
-                Err(ClientErrorKind::Message("Could not validate all DNSKEYs").into())
-            }
+            trust_dnskey_rrset(rrset, &sigs, valid_keys, &validation_cache, now)
         });
 
     Box::new(valid_dnskey)
 }
 
+/// Decides which keys of the DNSKEY rrset are trusted, given the trusted entry points
+///
+/// A signed rrset must be signed by one of the entry points, after which every key in it is
+///  trusted, RFC 4035 section 5.2. This is what allows a zone to be served by multiple signers,
+///  RFC 8901, where the DNSKEY rrset holds the keys of every provider and records may be signed
+///  by any of them. An unsigned rrset, a bare trust_anchor, only has the entry points trusted.
+///
+/// # Arguments
+///
+/// * `rrset` - the DNSKEY rrset
+/// * `sigs` - the self-signatures over the rrset
+/// * `entry_points` - ordered list of indexes of the keys that are trusted
+/// * `validation_cache` - outcomes of earlier verifications
+/// * `now` - the current time, for the cache
+fn trust_dnskey_rrset(mut rrset: Rrset,
+                      sigs: &[SIG],
+                      entry_points: Vec<usize>,
+                      validation_cache: &RefCell<ValidationCache>,
+                      now: u32)
+                      -> ClientResult<Rrset> {
+    if entry_points.is_empty() {
+        return Err(ClientErrorKind::Message("Could not validate all DNSKEYs").into());
+    }
+
+    if sigs.is_empty() {
+        preserve(&mut rrset.records, entry_points);
+
+        debug!("validated dnskey: {}, {}", rrset.name, rrset.records.len());
+        return Ok(rrset);
+    }
+
+    let is_signed = sigs.iter().any(|sig| {
+        entry_points.iter().any(|&i| if let &RData::DNSKEY(ref dnskey) = rrset.records[i]
+            .get_rdata() {
+            verify_rrset_with_dnskey(validation_cache, now, dnskey, sig, &rrset).is_ok()
+        } else {
+            false
+        })
+    });
+
+    if is_signed {
+        debug!("validated signed dnskey: {}, {}",
+               rrset.name,
+               rrset.records.len());
+        Ok(rrset)
+    } else {
+        Err(ClientErrorKind::Message("dnskey not signed by a trusted key").into())
+    }
+}
+
 /// Preseves the specified indexes in vec, all others will be removed
 ///
 /// # Arguments
@@ -555,52 +709,6 @@ fn verify_default_rrset<H>(client: SecureClientHandle<H>,
            rrset.name,
            rrset.record_type);
 
-    // Special case for self-signed DNSKEYS, validate with itself...
-    if rrsigs.iter()
-        .filter(|rrsig| rrsig.get_rr_type() == RecordType::RRSIG)
-        .any(|rrsig| if let &RData::SIG(ref sig) = rrsig.get_rdata() {
-            return RecordType::DNSKEY == rrset.record_type && sig.get_signer_name() == &rrset.name;
-        } else {
-            panic!("expected a SIG here");
-        }) {
-        // in this case it was looks like a self-signed key, first validate the signature
-        //  then return rrset. Like the standard case below, the DNSKEY is validated
-        //  after this function. This function is only responsible for validating the signature
-        //  the DNSKey validation should come after, see verify_rrset().
-        return Box::new(done(
-      rrsigs.into_iter()
-            // this filter is technically unnecessary, can probably remove it...
-            .filter(|rrsig| rrsig.get_rr_type() == RecordType::RRSIG)
-            .map(|rrsig|
-              if let RData::SIG(sig) = rrsig.unwrap_rdata() {
-                // setting up the context explicitly.
-                sig
-              } else {
-                panic!("expected a SIG here");
-              }
-            )
-            .filter_map(|sig| {
-              let rrset = rrset.clone();
-
-              if rrset.records.iter()
-                              .any(|r| {
-                                if let &RData::DNSKEY(ref dnskey) = r.get_rdata() {
-                                  verify_rrset_with_dnskey(dnskey, &sig, &rrset).is_ok()
-                                } else {
-                                  panic!("expected a DNSKEY here: {:?}", r.get_rdata());
-                                }
-                              }) {
-                                Some(rrset)
-                              } else {
-                                None
-                              }
-                            })
-                            .next()
-                            .ok_or(ClientErrorKind::Message("self-signed dnskey is invalid").into())
-      ).map(move |rrset| Rc::try_unwrap(rrset).expect("unable to unwrap Rc"))
-    );
-    }
-
     // we can validate with any of the rrsigs...
     //  i.e. the first that validates is good enough
     //  TODO: could there be a cert downgrade attack here with a MITM stripping stronger RRSIGs?
@@ -610,6 +718,7 @@ fn verify_default_rrset<H>(client: SecureClientHandle<H>,
     //         succeptable until that algorithm is removed as an option.
     //        dns over TLS will mitigate this.
     //  TODO: strip RRSIGS to accepted algorithms and make algorithms configurable.
+    let now = client.clock.now();
     let verifications = rrsigs.into_iter()
                             // this filter is technically unnecessary, can probably remove it...
                             .filter(|rrsig| rrsig.get_rr_type() == RecordType::RRSIG)
@@ -621,9 +730,12 @@ fn verify_default_rrset<H>(client: SecureClientHandle<H>,
                                 panic!("expected a SIG here");
                               }
                             )
+                            // expired or not yet valid signatures are ignored
+                            .filter(|sig| is_sig_current(sig, now))
                             .map(|sig| {
                               let rrset = rrset.clone();
                               let mut client = client.clone_with_context();
+                              let validation_cache = client.validation_cache.clone();
 
                               client.query(sig.get_signer_name().clone(), rrset.record_class, RecordType::DNSKEY)
                                     .and_then(move |message|
@@ -633,7 +745,12 @@ fn verify_default_rrset<H>(client: SecureClientHandle<H>,
                                              .filter(|r| r.get_rr_type() == RecordType::DNSKEY)
                                              .find(|r|
                                                if let &RData::DNSKEY(ref dnskey) = r.get_rdata() {
-                                                 verify_rrset_with_dnskey(dnskey, &sig, &rrset).is_ok()
+                                                 verify_rrset_with_dnskey(&validation_cache,
+                                                                          now,
+                                                                          dnskey,
+                                                                          &sig,
+                                                                          &rrset)
+                                                     .is_ok()
                                                } else {
                                                  panic!("expected a DNSKEY here: {:?}", r.get_rdata());
                                                }
@@ -664,54 +781,36 @@ fn verify_default_rrset<H>(client: SecureClientHandle<H>,
     Box::new(select)
 }
 
-/// Verifies the given SIG of the RRSET with the DNSKEY.
-#[cfg(feature = "openssl")]
-fn verify_rrset_with_dnskey(dnskey: &DNSKEY, sig: &SIG, rrset: &Rrset) -> ClientResult<()> {
-    if dnskey.is_revoke() {
-        debug!("revoked");
-        return Err(ClientErrorKind::Message("revoked").into());
-    } // TODO: does this need to be validated? RFC 5011
-    if !dnskey.is_zone_key() {
-        return Err(ClientErrorKind::Message("is not a zone key").into());
-    }
-    if *dnskey.get_algorithm() != sig.get_algorithm() {
-        return Err(ClientErrorKind::Message("mismatched algorithm").into());
-    }
-
-    let pkey = KeyPair::from_public_bytes(dnskey.get_public_key(), *dnskey.get_algorithm());
-    if let Err(e) = pkey {
-        debug!("error getting key from vec: {}", e);
-        return Err(ClientErrorKind::Message("error getting key from vec").into());
+/// Verifies the given SIG of the RRSET with the DNSKEY, or takes the outcome from the cache
+fn verify_rrset_with_dnskey(validation_cache: &RefCell<ValidationCache>,
+                            now: u32,
+                            dnskey: &DNSKEY,
+                            sig: &SIG,
+                            rrset: &Rrset)
+                            -> ClientResult<()> {
+    let key = ValidationCache::key(dnskey, sig, &rrset.name, rrset.record_class, &rrset.records);
+    if let Some(verified) = validation_cache.borrow_mut().get(&key, now) {
+        debug!("cached validation of {}, {:?}: {}",
+               rrset.name,
+               rrset.record_type,
+               verified);
+        return if verified {
+            Ok(())
+        } else {
+            Err(ClientErrorKind::Message("validation failed before").into())
+        };
     }
-    let pkey = pkey.unwrap();
-
-    let signer: Signer = Signer::new_verifier(*dnskey.get_algorithm(),
-                                              pkey,
-                                              sig.get_signer_name().clone(),
-                                              dnskey.is_zone_key(),
-                                              false);
-
-    signer.hash_rrset_with_sig(&rrset.name, rrset.record_class, sig, &rrset.records)
-        .map_err(|e| e.into())
-        .and_then(|rrset_hash| {
-            signer.verify(&rrset_hash, sig.get_sig())
-                .map(|_| {
-                    debug!("verified rrset: {}, type: {:?}",
-                           rrset.name,
-                           rrset.record_type);
-                    ()
-                })
-                .map_err(|e| e.into())
-        })
-}
 
-/// Will always return an error. To enable record verification compile with the openssl feature.
-#[cfg(not(feature = "openssl"))]
-fn verify_rrset_with_dnskey(_: &DNSKEY, _: &SIG, _: &Rrset) -> ClientResult<()> {
-    Err(ClientErrorKind::Message("openssl feature not enabled").into())
+    let verified: ClientResult<()> = dnssec::verify_rrset_with_dnskey(dnskey,
+                                                                      sig,
+                                                                      &rrset.name,
+                                                                      rrset.record_class,
+                                                                      &rrset.records)
+        .map_err(|e| e.into());
+    validation_cache.borrow_mut().insert(key, verified.is_ok(), sig.get_sig_expiration(), now);
+    verified
 }
 
-
 /// Verifies NSEC records
 ///
 /// ```text
@@ -793,3 +892,480 @@ fn verify_nsec(query: &Query, nsecs: Vec<&Record>) -> bool {
     // if we got here, then there are no matching NSEC records, no validation
     false
 }
+
+/// The outcome of an NSEC3 proof of the denial of existence
+#[derive(Debug, PartialEq, Eq)]
+enum Nsec3Proof {
+    /// The records prove that the name, or type at the name, does not exist
+    Secure,
+    /// The records prove the denial, though an NSEC3 with the Opt-Out flag covers the name
+    OptOut,
+    /// The records do not prove the denial
+    Invalid,
+}
+
+/// Validates the denial of existence of the query with NSEC3 records
+///
+/// [RFC 5155, NSEC3, March 2008](https://tools.ietf.org/html/rfc5155#section-8)
+///
+/// ```text
+/// 8.3.  Closest Encloser Proof
+///
+///    For some NSEC3 responses, namely Name Error responses and Wildcard
+///    NODATA responses, the validator must be able to prove the closest
+///    encloser of the query name (QNAME).
+///
+///    In order to verify a closest encloser proof, the validator MUST find
+///    the longest name, X, such that
+///
+///    o  X is an ancestor of QNAME that is matched by an NSEC3 RR present
+///       in the response.  This is a candidate for the closest encloser, and
+///
+///    o  The name one label longer than X (but still an ancestor of -- or
+///       equal to -- QNAME) is covered by an NSEC3 RR present in the
+///       response.
+///
+/// 8.4.  Validating Name Error Responses
+///
+///    A validator MUST verify that there is a closest encloser proof for
+///    QNAME present in the response and that there is an NSEC3 RR that
+///    covers the wildcard at the closest encloser (i.e., the name formed by
+///    prepending the asterisk label to the closest encloser).
+///
+/// 8.5.  Validating No Data Responses, QTYPE is not DS
+///
+///    The validator MUST verify that an NSEC3 RR that matches QNAME is
+///    present and that both the QTYPE and the CNAME type are not set in its
+///    Type Bit Maps field.
+///
+/// 8.6.  Validating No Data Responses, QTYPE is DS
+///
+///    If there is an NSEC3 RR that matches QNAME present in the response,
+///    then that NSEC3 RR MUST NOT have the bits corresponding to DS and
+///    CNAME set in its Type Bit Maps field.
+///
+///    If there is no such NSEC3 RR, then the validator MUST verify that a
+///    closest provable encloser proof for QNAME is present in the response,
+///    and that the NSEC3 RR that covers the "next closer" name has the Opt-
+///    Out bit set.
+///
+/// 8.7.  Validating Wildcard No Data Responses
+///
+///    The validator MUST verify a closest encloser proof for QNAME and MUST
+///    find an NSEC3 RR present in the response that matches the wildcard
+///    name generated by prepending the asterisk label to the closest
+///    encloser.  Furthermore, the bits corresponding to both QTYPE and
+///    CNAME MUST NOT be set in the wildcard matching NSEC3 RR.
+/// ```
+///
+/// The zone and the NSEC3 parameters are those of the first record, records of other zones or
+///  parameters are ignored, as are records with an unknown hash algorithm, RFC 5155 section 8.1.
+///  Records of more than `MAX_NSEC3_ITERATIONS` are not validated. The proof must be the one of
+///  the response code, NODATA for NOERROR and a name error for NXDOMAIN.
+#[cfg(feature = "openssl")]
+fn verify_nsec3(query: &Query,
+                response_code: ResponseCode,
+                nsec3s: Vec<&Record>)
+                -> Nsec3Proof {
+    let (zone, first) = match nsec3s.first() {
+        Some(r) => {
+            if let &RData::NSEC3(ref rdata) = r.get_rdata() {
+                (r.get_name().base_name(), rdata)
+            } else {
+                // valid panic, never should happen
+                panic!("expected NSEC3 was {:?}", r.get_rr_type())
+            }
+        }
+        None => return Nsec3Proof::Invalid,
+    };
+
+    let name = query.get_name();
+    if !zone.zone_of(name) {
+        return Nsec3Proof::Invalid;
+    }
+
+    // each iteration costs the validator a hash of every name, RFC 9276 section 3.2
+    if first.get_iterations() > MAX_NSEC3_ITERATIONS {
+        warn!("nsec3 iterations: {} above the maximum: {}",
+              first.get_iterations(),
+              MAX_NSEC3_ITERATIONS);
+        return Nsec3Proof::Invalid;
+    }
+
+    let no_data = response_code == ResponseCode::NoError;
+    let name_error = response_code == ResponseCode::NXDomain;
+
+    // the owner hash and the NSEC3 of each record in the chain of the zone
+    let chain = nsec3s.iter()
+        .filter(|r| r.get_name().num_labels() == zone.num_labels() + 1 &&
+                    r.get_name().base_name() == zone)
+        .filter_map(|r| if let &RData::NSEC3(ref rdata) = r.get_rdata() {
+            Some((r.get_name()[0].to_lowercase(), rdata))
+        } else {
+            None
+        })
+        .filter(|&(_, rdata)| {
+            rdata.get_hash_algorithm() == first.get_hash_algorithm() &&
+            rdata.get_iterations() == first.get_iterations() &&
+            rdata.get_salt() == first.get_salt()
+        })
+        .collect::<Vec<_>>();
+
+    let hash = |name: &domain::Name| -> Option<String> {
+        first.get_hash_algorithm()
+            .hash(first.get_salt(), name, first.get_iterations())
+            .map(|hash| base32hex::encode(&hash).to_lowercase())
+            .map_err(|e| warn!("could not hash {} for nsec3: {}", name, e))
+            .ok()
+    };
+    let matching = |name: &domain::Name| {
+        hash(name).and_then(|hash| {
+            chain.iter().find(|&&(ref owner, _)| *owner == hash).map(|&(_, rdata)| rdata)
+        })
+    };
+    let covering = |name: &domain::Name| {
+        hash(name).and_then(|hash| {
+            chain.iter()
+                .find(|&&(ref owner, rdata)| nsec3_covers(owner, rdata, &hash))
+                .map(|&(_, rdata)| rdata)
+        })
+    };
+    let denies_type = |rdata: &NSEC3| {
+        let type_bit_maps = rdata.get_type_bit_maps();
+        !type_bit_maps.contains(&query.get_query_type()) &&
+        !type_bit_maps.contains(&RecordType::CNAME)
+    };
+
+    // the name exists, this is a NODATA response
+    if let Some(rdata) = matching(name) {
+        return if no_data && denies_type(rdata) {
+            Nsec3Proof::Secure
+        } else {
+            Nsec3Proof::Invalid
+        };
+    }
+
+    // closest encloser proof, the longest ancestor of the name which exists
+    let closest_encloser = match (zone.num_labels()..name.num_labels())
+        .rev()
+        .map(|num_labels| name.trim_to(num_labels as usize))
+        .find(|ancestor| matching(ancestor).is_some()) {
+        Some(closest_encloser) => closest_encloser,
+        None => return Nsec3Proof::Invalid,
+    };
+
+    // the next closer name must not exist
+    let next_closer = name.trim_to(closest_encloser.num_labels() as usize + 1);
+    let opt_out = match covering(&next_closer) {
+        Some(rdata) => rdata.is_opt_out(),
+        None => return Nsec3Proof::Invalid,
+    };
+
+    // an unsigned delegation for the DS may be in the opted out span
+    if opt_out && query.get_query_type() == RecordType::DS && no_data {
+        return Nsec3Proof::OptOut;
+    }
+
+    // and neither may the wildcard at the closest encloser, or have the type, a wildcard NODATA
+    //  or a name error
+    let wildcard = closest_encloser.prepend_label(Rc::new("*".to_string()));
+    let wildcard_denied = if let Some(rdata) = matching(&wildcard) {
+        no_data && denies_type(rdata)
+    } else {
+        name_error && covering(&wildcard).is_some()
+    };
+
+    match (wildcard_denied, opt_out) {
+        (false, _) => Nsec3Proof::Invalid,
+        (true, false) => Nsec3Proof::Secure,
+        (true, true) => Nsec3Proof::OptOut,
+    }
+}
+
+/// NSEC3 validation hashes names with openssl, without it the denial can not be validated
+#[cfg(not(feature = "openssl"))]
+fn verify_nsec3(_: &Query, _: ResponseCode, _: Vec<&Record>) -> Nsec3Proof {
+    warn!("NSEC3 validation requires the openssl feature");
+    Nsec3Proof::Invalid
+}
+
+/// Returns true if the hash is between the owner hash and the next hashed owner name of the
+///  NSEC3, the last NSEC3 in the chain covers the hashes after it and before the first
+#[cfg(feature = "openssl")]
+fn nsec3_covers(owner: &str, nsec3: &NSEC3, hash: &str) -> bool {
+    // the hashes are all of the same length, and base32hex keeps their order
+    let next = base32hex::encode(nsec3.get_next_hashed_owner_name()).to_lowercase();
+    let next = next.as_str();
+
+    if owner < next {
+        owner < hash && hash < next
+    } else {
+        owner < hash || hash < next
+    }
+}
+
+#[cfg(feature = "openssl")]
+#[cfg(test)]
+fn nsec3_chain(names: &[(&str, Vec<RecordType>)], opt_out: bool, iterations: u16) -> Vec<Record> {
+    use rr::dnssec::Nsec3HashAlgorithm;
+
+    let zone = domain::Name::parse("example.", None).unwrap();
+    let salt = vec![0xAA, 0xBB, 0xCC, 0xDD];
+    let mut hashes = names.iter()
+        .map(|&(name, ref types)| {
+            let name = domain::Name::parse(name, None).unwrap();
+            (Nsec3HashAlgorithm::SHA1.hash(&salt, &name, iterations).unwrap(), types.clone())
+        })
+        .collect::<Vec<_>>();
+    hashes.sort_by(|a, b| a.0.cmp(&b.0));
+
+    (0..hashes.len())
+        .map(|i| {
+            let next = hashes[(i + 1) % hashes.len()].0.clone();
+            let rdata = NSEC3::new(Nsec3HashAlgorithm::SHA1,
+                                   opt_out,
+                                   iterations,
+                                   salt.clone(),
+                                   next,
+                                   hashes[i].1.clone());
+            Record::from_rdata(dnssec::hashed_owner_name(&hashes[i].0, &zone),
+                               3600,
+                               RecordType::NSEC3,
+                               RData::NSEC3(rdata))
+        })
+        .collect()
+}
+
+#[cfg(feature = "openssl")]
+#[test]
+fn test_verify_nsec3() {
+    use rr::dnssec::Nsec3HashAlgorithm;
+
+    let chain = nsec3_chain(&[("example.", vec![RecordType::SOA, RecordType::NS]),
+                              ("a.example.", vec![RecordType::A]),
+                              ("w.example.", vec![]),
+                              ("*.w.example.", vec![RecordType::TXT])],
+                            false,
+                            12);
+    let verify_with = |name: &str,
+                       query_type: RecordType,
+                       response_code: ResponseCode,
+                       records: Vec<&Record>| {
+        let mut query = Query::new();
+        query.name(domain::Name::parse(name, None).unwrap()).query_type(query_type);
+        verify_nsec3(&query, response_code, records)
+    };
+    // the response code of the proof, NODATA for the names which exist
+    let verify = |name: &str, query_type: RecordType, records: Vec<&Record>| {
+        let response_code = match name {
+            "a.example." | "b.w.example." => ResponseCode::NoError,
+            _ if query_type == RecordType::DS => ResponseCode::NoError,
+            _ => ResponseCode::NXDomain,
+        };
+        verify_with(name, query_type, response_code, records)
+    };
+
+    // no data
+    assert_eq!(verify("a.example.", RecordType::MX, chain.iter().collect()),
+               Nsec3Proof::Secure);
+    assert_eq!(verify("a.example.", RecordType::A, chain.iter().collect()),
+               Nsec3Proof::Invalid);
+
+    // name error
+    assert_eq!(verify("b.example.", RecordType::A, chain.iter().collect()),
+               Nsec3Proof::Secure);
+    assert_eq!(verify("c.b.example.", RecordType::A, chain.iter().collect()),
+               Nsec3Proof::Secure);
+    assert_eq!(verify("b.example.", RecordType::A, vec![]), Nsec3Proof::Invalid);
+    assert_eq!(verify("b.other.", RecordType::A, chain.iter().collect()),
+               Nsec3Proof::Invalid);
+
+    // wildcard no data, the wildcard has the TXT
+    assert_eq!(verify("b.w.example.", RecordType::A, chain.iter().collect()),
+               Nsec3Proof::Secure);
+    assert_eq!(verify("b.w.example.", RecordType::TXT, chain.iter().collect()),
+               Nsec3Proof::Invalid);
+
+    // the proof of NODATA does not hold for NXDOMAIN, nor that of a name error for NOERROR
+    assert_eq!(verify_with("a.example.",
+                           RecordType::MX,
+                           ResponseCode::NXDomain,
+                           chain.iter().collect()),
+               Nsec3Proof::Invalid);
+    assert_eq!(verify_with("b.w.example.",
+                           RecordType::A,
+                           ResponseCode::NXDomain,
+                           chain.iter().collect()),
+               Nsec3Proof::Invalid);
+    assert_eq!(verify_with("b.example.",
+                           RecordType::A,
+                           ResponseCode::NoError,
+                           chain.iter().collect()),
+               Nsec3Proof::Invalid);
+    assert_eq!(verify_with("b.example.",
+                           RecordType::A,
+                           ResponseCode::ServFail,
+                           chain.iter().collect()),
+               Nsec3Proof::Invalid);
+
+    // only the closest encloser, without the cover of the next closer name
+    let name = domain::Name::parse("b.example.", None).unwrap();
+    let hash = Nsec3HashAlgorithm::SHA1.hash(&[0xAA, 0xBB, 0xCC, 0xDD], &name, 12).unwrap();
+    let hash = base32hex::encode(&hash).to_lowercase();
+    let uncovered = chain.iter()
+        .filter(|r| if let &RData::NSEC3(ref rdata) = r.get_rdata() {
+            !nsec3_covers(&r.get_name()[0], rdata, &hash)
+        } else {
+            false
+        })
+        .collect();
+    assert_eq!(verify("b.example.", RecordType::A, uncovered), Nsec3Proof::Invalid);
+
+    // opt-out, the denial holds but is not secure
+    let chain = nsec3_chain(&[("example.", vec![RecordType::SOA, RecordType::NS]),
+                              ("a.example.", vec![RecordType::A])],
+                            true,
+                            12);
+    assert_eq!(verify("b.example.", RecordType::DS, chain.iter().collect()),
+               Nsec3Proof::OptOut);
+    assert_eq!(verify("b.example.", RecordType::A, chain.iter().collect()),
+               Nsec3Proof::OptOut);
+
+    // too many iterations, however sound the proof
+    let chain = nsec3_chain(&[("example.", vec![RecordType::SOA, RecordType::NS]),
+                              ("a.example.", vec![RecordType::A])],
+                            false,
+                            MAX_NSEC3_ITERATIONS + 1);
+    assert_eq!(verify("a.example.", RecordType::MX, chain.iter().collect()),
+               Nsec3Proof::Invalid);
+    assert_eq!(verify("b.example.", RecordType::A, chain.iter().collect()),
+               Nsec3Proof::Invalid);
+}
+
+/// The NSEC3 chain of the example zone of RFC 5155 appendix A, salt AABBCCDD, 12 iterations
+#[cfg(feature = "openssl")]
+#[cfg(test)]
+fn rfc5155_nsec3s() -> Vec<Record> {
+    use rr::dnssec::Nsec3HashAlgorithm;
+    use rr::RecordType::{A, AAAA, DNSKEY, DS, MX, NS, NSEC3PARAM, RRSIG, SOA};
+
+    // HINFO, RFC 1035
+    const HINFO: RecordType = RecordType::Unknown(13);
+
+    let chain = [("0p9mhaveqvm6t7vbl5lop2u3t2rp3tom",
+                  "2t7b4g4vsa5smi47k61mv5bv1a22bojr",
+                  vec![MX, DNSKEY, NS, SOA, NSEC3PARAM, RRSIG]),
+                 ("2t7b4g4vsa5smi47k61mv5bv1a22bojr",
+                  "2vptu5timamqttgl4luu9kg21e0aor3s",
+                  vec![A, RRSIG]),
+                 ("2vptu5timamqttgl4luu9kg21e0aor3s",
+                  "35mthgpgcu1qg68fab165klnsnk3dpvl",
+                  vec![MX, RRSIG]),
+                 ("35mthgpgcu1qg68fab165klnsnk3dpvl",
+                  "b4um86eghhds6nea196smvmlo4ors995",
+                  vec![NS, DS, RRSIG]),
+                 ("b4um86eghhds6nea196smvmlo4ors995",
+                  "gjeqe526plbf1g8mklp59enfd789njgi",
+                  vec![MX, RRSIG]),
+                 ("gjeqe526plbf1g8mklp59enfd789njgi",
+                  "ji6neoaepv8b5o6k4ev33abha8ht9fgc",
+                  vec![HINFO, A, AAAA, RRSIG]),
+                 ("ji6neoaepv8b5o6k4ev33abha8ht9fgc",
+                  "k8udemvp1j2f7eg6jebps17vp3n8i58h",
+                  vec![]),
+                 ("k8udemvp1j2f7eg6jebps17vp3n8i58h",
+                  "kohar7mbb8dc2ce8a9qvl8hon4k53uhi",
+                  vec![]),
+                 ("kohar7mbb8dc2ce8a9qvl8hon4k53uhi",
+                  "q04jkcevqvmu85r014c7dkba38o0ji5r",
+                  vec![A, RRSIG]),
+                 ("q04jkcevqvmu85r014c7dkba38o0ji5r",
+                  "r53bq7cc2uvmubfu5ocmm6pers9tk9en",
+                  vec![A, RRSIG]),
+                 ("r53bq7cc2uvmubfu5ocmm6pers9tk9en",
+                  "t644ebqk9bibcna874givr6joj62mlhv",
+                  vec![MX, RRSIG]),
+                 ("t644ebqk9bibcna874givr6joj62mlhv",
+                  "0p9mhaveqvm6t7vbl5lop2u3t2rp3tom",
+                  vec![A, HINFO, AAAA, RRSIG])];
+
+    chain.iter()
+        .map(|&(owner, next, ref types)| {
+            let rdata = NSEC3::new(Nsec3HashAlgorithm::SHA1,
+                                   true,
+                                   12,
+                                   vec![0xAA, 0xBB, 0xCC, 0xDD],
+                                   base32hex::decode(next.to_uppercase().as_bytes()).unwrap(),
+                                   types.clone());
+            Record::from_rdata(domain::Name::parse(&format!("{}.example.", owner), None).unwrap(),
+                               3600,
+                               RecordType::NSEC3,
+                               RData::NSEC3(rdata))
+        })
+        .collect()
+}
+
+/// [RFC 5155](https://tools.ietf.org/html/rfc5155#appendix-B), NSEC3, the example responses,
+///  the chain has the Opt-Out flag so the proofs of a name which does not exist are not secure
+#[cfg(feature = "openssl")]
+#[test]
+fn test_verify_nsec3_rfc5155_examples() {
+    let chain = rfc5155_nsec3s();
+    let verify = |name: &str,
+                  query_type: RecordType,
+                  response_code: ResponseCode,
+                  owners: &[&str]| {
+        let mut query = Query::new();
+        query.name(domain::Name::parse(name, None).unwrap()).query_type(query_type);
+        let nsec3s = chain.iter()
+            .filter(|r| owners.iter().any(|owner| r.get_name()[0].starts_with(owner)))
+            .collect();
+        verify_nsec3(&query, response_code, nsec3s)
+    };
+
+    // B.1, name error
+    assert_eq!(verify("a.c.x.w.example.",
+                      RecordType::A,
+                      ResponseCode::NXDomain,
+                      &["0p9mhave", "b4um86eg", "35mthgpg"]),
+               Nsec3Proof::OptOut);
+    // without the cover of the wildcard at the closest encloser
+    assert_eq!(verify("a.c.x.w.example.",
+                      RecordType::A,
+                      ResponseCode::NXDomain,
+                      &["0p9mhave", "b4um86eg"]),
+               Nsec3Proof::Invalid);
+
+    // B.2, no data
+    assert_eq!(verify("ns1.example.", RecordType::MX, ResponseCode::NoError, &["2t7b4g4v"]),
+               Nsec3Proof::Secure);
+    assert_eq!(verify("ns1.example.", RecordType::A, ResponseCode::NoError, &["2t7b4g4v"]),
+               Nsec3Proof::Invalid);
+
+    // B.2.1, no data, empty non-terminal
+    assert_eq!(verify("y.w.example.", RecordType::A, ResponseCode::NoError, &["ji6neoae"]),
+               Nsec3Proof::Secure);
+
+    // B.3, the referral to the unsigned delegation c.example, in the opted out span
+    assert_eq!(verify("c.example.",
+                      RecordType::DS,
+                      ResponseCode::NoError,
+                      &["35mthgpg", "0p9mhave"]),
+               Nsec3Proof::OptOut);
+
+    // B.4, wildcard no data
+    assert_eq!(verify("a.z.w.example.",
+                      RecordType::AAAA,
+                      ResponseCode::NoError,
+                      &["k8udemvp", "q04jkcev", "r53bq7cc"]),
+               Nsec3Proof::OptOut);
+    assert_eq!(verify("a.z.w.example.",
+                      RecordType::MX,
+                      ResponseCode::NoError,
+                      &["k8udemvp", "q04jkcev", "r53bq7cc"]),
+               Nsec3Proof::Invalid);
+
+    // B.5, DS child zone no data
+    assert_eq!(verify("example.", RecordType::DS, ResponseCode::NoError, &["0p9mhave"]),
+               Nsec3Proof::Secure);
+}
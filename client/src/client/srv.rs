@@ -0,0 +1,123 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::rc::Rc;
+
+use rand::Rng;
+
+use rr::domain::Name;
+use rr::rdata::SRV;
+
+/// The name of the SRV records of the service and protocol in the domain,
+///  `_<service>._<proto>.<domain>`, the underscores are added if missing
+pub fn srv_name(service: &str, proto: &str, domain: &Name) -> Name {
+    let label = |label: &str| if label.starts_with('_') {
+        Rc::new(label.to_string())
+    } else {
+        Rc::new(format!("_{}", label))
+    };
+
+    domain.prepend_label(label(proto)).prepend_label(label(service))
+}
+
+/// Orders the SRV records in which their targets should be contacted, RFC 2782
+///
+/// Records of a lower priority come first. Those of the same priority are ordered by the weighted
+///  random selection of the RFC: each next record is picked with a probability proportional to
+///  its weight, records of weight zero only having a very small chance ahead of the others.
+///
+/// ```text
+/// To select a target to be contacted next, arrange all SRV RRs
+/// (that have not been ordered yet) in any order, except that all
+/// those with weight 0 are placed at the beginning of the list.
+///
+/// Compute the sum of the weights of those RRs, and with each RR
+/// associate the running sum in the selected order. Then choose a
+/// uniform random number between 0 and the sum computed
+/// (inclusive), and select the RR whose running sum value is the
+/// first in the selected order which is greater than or equal to
+/// the random number selected.
+/// ```
+pub fn sort_srv<R: Rng>(mut records: Vec<SRV>, rng: &mut R) -> Vec<SRV> {
+    // any order, with the weight zero records at the beginning of each priority, kept by the
+    //  stable sorts
+    rng.shuffle(&mut records);
+    records.sort_by_key(|srv| srv.get_weight() != 0);
+    records.sort_by_key(|srv| srv.get_priority());
+
+    let mut sorted: Vec<SRV> = Vec::with_capacity(records.len());
+    while !records.is_empty() {
+        let priority = records[0].get_priority();
+        let same_priority = records.iter().take_while(|srv| srv.get_priority() == priority).count();
+
+        let total: u32 = records[..same_priority].iter().map(|srv| srv.get_weight() as u32).sum();
+        let selected = rng.gen_range(0, total + 1);
+
+        let mut running_sum = 0;
+        let index = records[..same_priority]
+            .iter()
+            .position(|srv| {
+                running_sum += srv.get_weight() as u32;
+                running_sum >= selected
+            })
+            .unwrap_or(0);
+
+        sorted.push(records.remove(index));
+    }
+
+    sorted
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{SeedableRng, XorShiftRng};
+
+    use rr::domain::Name;
+    use rr::rdata::SRV;
+    use super::*;
+
+    fn srv(priority: u16, weight: u16, target: &str) -> SRV {
+        SRV::new(priority, weight, 5060, Name::parse(target, None).unwrap())
+    }
+
+    #[test]
+    fn test_srv_name() {
+        let domain = Name::parse("example.com.", None).unwrap();
+        let name = Name::parse("_sip._udp.example.com.", None).unwrap();
+        assert_eq!(srv_name("sip", "udp", &domain), name);
+        assert_eq!(srv_name("_sip", "_udp", &domain), name);
+    }
+
+    #[test]
+    fn test_sort_srv() {
+        let mut rng = XorShiftRng::from_seed([1, 2, 3, 4]);
+
+        // priorities first, whatever the weights
+        let sorted = sort_srv(vec![srv(20, 100, "c."), srv(10, 0, "a."), srv(15, 1, "b.")],
+                              &mut rng);
+        assert_eq!(sorted.iter().map(|srv| srv.get_priority()).collect::<Vec<_>>(),
+                   vec![10, 15, 20]);
+
+        // the heavier record comes first about nine times in ten
+        let mut heavy_first = 0;
+        for _ in 0..1000 {
+            let sorted = sort_srv(vec![srv(10, 10, "light."), srv(10, 90, "heavy.")], &mut rng);
+            assert_eq!(sorted.len(), 2);
+            if sorted[0].get_target() == &Name::parse("heavy.", None).unwrap() {
+                heavy_first += 1;
+            }
+        }
+        assert!(heavy_first > 850 && heavy_first < 950, "heavy first: {}", heavy_first);
+
+        // all weights zero, either may come first
+        let firsts = (0..100)
+            .map(|_| sort_srv(vec![srv(10, 0, "a."), srv(10, 0, "b.")], &mut rng)[0].clone())
+            .collect::<Vec<_>>();
+        assert!(firsts.contains(&srv(10, 0, "a.")));
+        assert!(firsts.contains(&srv(10, 0, "b.")));
+    }
+}
@@ -0,0 +1,122 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use futures::{finished, Future};
+
+use client::ClientHandle;
+use ::error::*;
+use op::Message;
+
+/// Sends queries over UDP, and again over TCP if the response is truncated
+///
+/// A truncated response is never returned, nor combined with the TCP response, any records in it
+///  may be a partial RRSet. Both clients should be connected to the same name server.
+#[derive(Clone)]
+#[must_use = "queries can only be sent through a ClientHandle"]
+pub struct TcpFallbackClientHandle<U: ClientHandle, T: ClientHandle> {
+    udp_client: U,
+    tcp_client: T,
+}
+
+impl<U, T> TcpFallbackClientHandle<U, T>
+    where U: ClientHandle,
+          T: ClientHandle
+{
+    /// Returns a new handle
+    ///
+    /// # Arguments
+    ///
+    /// * `udp_client` - the client through which queries are first sent
+    /// * `tcp_client` - the client to retry through, when the response was truncated
+    pub fn new(udp_client: U, tcp_client: T) -> TcpFallbackClientHandle<U, T> {
+        TcpFallbackClientHandle {
+            udp_client: udp_client,
+            tcp_client: tcp_client,
+        }
+    }
+}
+
+impl<U, T> ClientHandle for TcpFallbackClientHandle<U, T>
+    where U: ClientHandle,
+          T: ClientHandle + 'static
+{
+    fn send(&mut self, message: Message) -> Box<Future<Item = Message, Error = ClientError>> {
+        let mut tcp_client = self.tcp_client.clone();
+
+        Box::new(self.udp_client
+            .send(message.clone())
+            .and_then(move |response| -> Box<Future<Item = Message, Error = ClientError>> {
+                if response.is_truncated() {
+                    debug!("response id: {} truncated, retrying over TCP", response.get_id());
+                    tcp_client.send(message)
+                } else {
+                    Box::new(finished(response))
+                }
+            }))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    use ::client::*;
+    use ::error::*;
+    use ::op::*;
+    use futures::*;
+
+    #[derive(Clone)]
+    struct TestClient {
+        truncated: bool,
+        sent: Rc<Cell<usize>>,
+    }
+
+    impl ClientHandle for TestClient {
+        fn send(&mut self, message: Message) -> Box<Future<Item = Message, Error = ClientError>> {
+            self.sent.set(self.sent.get() + 1);
+
+            let mut response = Message::new();
+            response.id(message.get_id()).truncated(self.truncated);
+            Box::new(finished(response))
+        }
+    }
+
+    fn test_client(truncated: bool) -> (TestClient, Rc<Cell<usize>>) {
+        let sent = Rc::new(Cell::new(0));
+
+        (TestClient {
+            truncated: truncated,
+            sent: sent.clone(),
+        },
+         sent)
+    }
+
+    #[test]
+    fn test_no_fallback() {
+        let (udp_client, udp_sent) = test_client(false);
+        let (tcp_client, tcp_sent) = test_client(false);
+        let mut client = TcpFallbackClientHandle::new(udp_client, tcp_client);
+
+        let response = client.send(Message::new()).wait().expect("send failed");
+        assert!(!response.is_truncated());
+        assert_eq!(udp_sent.get(), 1);
+        assert_eq!(tcp_sent.get(), 0);
+    }
+
+    #[test]
+    fn test_fallback_on_truncation() {
+        let (udp_client, udp_sent) = test_client(true);
+        let (tcp_client, tcp_sent) = test_client(false);
+        let mut client = TcpFallbackClientHandle::new(udp_client, tcp_client);
+
+        let response = client.send(Message::new()).wait().expect("send failed");
+        assert!(!response.is_truncated());
+        assert_eq!(udp_sent.get(), 1);
+        assert_eq!(tcp_sent.get(), 1);
+    }
+}
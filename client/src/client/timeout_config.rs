@@ -0,0 +1,49 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::time::Duration;
+
+/// Timeouts for connecting, reading, writing and waiting for responses
+///
+/// These are accepted by the connections, `ClientFuture`, and the server's TCP and TLS listeners.
+///  Start from the defaults and override only what differs:
+///
+/// ```
+/// use std::time::Duration;
+/// use trust_dns::client::TimeoutConfig;
+///
+/// let timeouts = TimeoutConfig { query: Duration::from_secs(2), ..TimeoutConfig::default() };
+/// ```
+///
+/// A zero duration disables that timeout. A single query can wait longer or shorter than
+///  `query`, see `BasicClientHandle::send_with_timeout()`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TimeoutConfig {
+    /// establishing a connection, including the TLS handshake
+    pub connect: Duration,
+    /// once the first byte of a TCP message is read, the entire message must arrive within this
+    ///  time
+    pub read: Duration,
+    /// a TCP message must be entirely written within this time
+    pub write: Duration,
+    /// the time to wait for the response to a query, from when it is sent
+    pub query: Duration,
+    /// the server closes TCP connections which do not start a request within this time
+    pub tcp_idle: Duration,
+}
+
+impl Default for TimeoutConfig {
+    fn default() -> Self {
+        TimeoutConfig {
+            connect: Duration::from_secs(5),
+            read: Duration::from_secs(2),
+            write: Duration::from_secs(2),
+            query: Duration::from_secs(5),
+            tcp_idle: Duration::from_secs(5),
+        }
+    }
+}
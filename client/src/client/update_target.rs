@@ -0,0 +1,159 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::net::{IpAddr, SocketAddr};
+
+use client::SyncClient;
+use ::error::*;
+use op::Message;
+use rr::{RData, Record};
+use rr::domain::Name;
+use rr::dnssec::Signer;
+use rr::rdata::SOA;
+use tcp::TcpClientConnection;
+
+/// The zone of a name and its primary master, the server which dynamic updates of the name are
+///  sent to, RFC 2136 section 4, see `Client::find_update_target`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UpdateTarget {
+    zone: Name,
+    primary: Name,
+    addresses: Vec<IpAddr>,
+}
+
+impl UpdateTarget {
+    /// A target of the zone with the primary master at the addresses
+    pub fn new(zone: Name, primary: Name, addresses: Vec<IpAddr>) -> Self {
+        UpdateTarget {
+            zone: zone,
+            primary: primary,
+            addresses: addresses,
+        }
+    }
+
+    /// The name of the zone, the `zone_origin` of the updates
+    pub fn get_zone(&self) -> &Name {
+        &self.zone
+    }
+
+    /// The name of the primary master, the MNAME of the SOA of the zone
+    pub fn get_primary(&self) -> &Name {
+        &self.primary
+    }
+
+    /// The addresses of the primary master
+    pub fn get_addresses(&self) -> &[IpAddr] {
+        &self.addresses
+    }
+
+    /// The addresses of the primary master on the DNS port
+    pub fn get_socket_addrs(&self) -> Vec<SocketAddr> {
+        self.addresses.iter().map(|address| SocketAddr::new(*address, 53)).collect()
+    }
+
+    /// A client of the first address of the primary master, over TCP, to send the updates with
+    ///
+    /// # Arguments
+    ///
+    /// * `signer` - the SIG(0) signer of the updates, if the server requires them to be signed
+    pub fn client(&self, signer: Option<Signer>) -> ClientResult<SyncClient> {
+        let name_server = try!(self.get_socket_addrs()
+            .into_iter()
+            .next()
+            .ok_or(ClientError::from(ClientErrorKind::Message("no address of the primary"))));
+        let connection = try!(TcpClientConnection::new(name_server));
+
+        Ok(match signer {
+            Some(signer) => SyncClient::with_signer(connection, signer),
+            None => SyncClient::new(connection),
+        })
+    }
+}
+
+/// The zone and its SOA from the response to the SOA query of the name, None if the response
+///  does not tell
+///
+/// The SOA is in the answers if the name is the apex of a zone, or else in the authority section
+///  of the negative response, owned by the apex of the zone of the name.
+pub fn zone_of_response(name: &Name, response: &Message) -> Option<(Name, SOA)> {
+    soa_of(name, response.get_answers()).or_else(|| soa_of(name, response.get_name_servers()))
+}
+
+fn soa_of(name: &Name, records: &[Record]) -> Option<(Name, SOA)> {
+    records.iter()
+        .filter_map(|record| match *record.get_rdata() {
+            RData::SOA(ref soa) if record.get_name().zone_of(name) => {
+                Some((record.get_name().clone(), soa.clone()))
+            }
+            _ => None,
+        })
+        .next()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+    use op::{Message, ResponseCode};
+    use rr::{RData, Record, RecordType};
+    use rr::domain::Name;
+    use rr::rdata::SOA;
+    use super::*;
+
+    fn soa(zone: &Name) -> Record {
+        Record::from_rdata(zone.clone(),
+                           3600,
+                           RecordType::SOA,
+                           RData::SOA(SOA::new(Name::parse("ns1.example.com.", None).unwrap(),
+                                               Name::parse("hostmaster.example.com.", None)
+                                                   .unwrap(),
+                                               1,
+                                               7200,
+                                               600,
+                                               3600000,
+                                               60)))
+    }
+
+    #[test]
+    fn test_zone_of_response() {
+        let zone = Name::parse("example.com.", None).unwrap();
+        let www = Name::parse("www.example.com.", None).unwrap();
+
+        // the apex
+        let mut response = Message::new();
+        response.add_answer(soa(&zone));
+        let (found, soa) = zone_of_response(&zone, &response).unwrap();
+        assert_eq!(found, zone);
+        assert_eq!(soa.get_mname(), &Name::parse("ns1.example.com.", None).unwrap());
+
+        // NODATA or NXDOMAIN of a name in the zone
+        let mut response = Message::new();
+        response.response_code(ResponseCode::NXDomain);
+        response.add_name_server(soa(&zone));
+        assert_eq!(zone_of_response(&www, &response).unwrap().0, zone);
+
+        // a SOA which is not of the name, nor of any zone of it
+        let mut response = Message::new();
+        response.add_name_server(soa(&Name::parse("example.net.", None).unwrap()));
+        assert!(zone_of_response(&www, &response).is_none());
+        assert!(zone_of_response(&www, &Message::new()).is_none());
+    }
+
+    #[test]
+    fn test_update_target() {
+        let target = UpdateTarget::new(Name::parse("example.com.", None).unwrap(),
+                                       Name::parse("ns1.example.com.", None).unwrap(),
+                                       vec![IpAddr::V4(Ipv4Addr::new(192, 0, 2, 53))]);
+        assert_eq!(target.get_socket_addrs(),
+                   vec![SocketAddr::new(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 53)), 53)]);
+
+        let unresolved = UpdateTarget::new(Name::parse("example.com.", None).unwrap(),
+                                           Name::parse("ns1.example.com.", None).unwrap(),
+                                           vec![]);
+        assert!(unresolved.client(None).is_err());
+    }
+}
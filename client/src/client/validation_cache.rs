@@ -0,0 +1,225 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::collections::HashMap;
+
+use rr::{DNSClass, Record};
+use rr::domain::Name;
+use rr::rdata::{DNSKEY, SIG};
+use serialize::binary::BinEncoder;
+
+/// entries of the cache of a `SecureClientHandle`, unless set otherwise
+pub const DEFAULT_VALIDATION_CACHE_SIZE: usize = 1024;
+
+/// The key of a cached outcome, all that it depends on, compared in whole on a hit
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ValidationKey {
+    /// the verification of the RRSIG over the rrset, of which the owner, class and the data of
+    ///  the records in canonical order, with the DNSKEY
+    Rrsig(Name, u16, Vec<Vec<u8>>, SIG, DNSKEY),
+    /// whether the DNSKEY of the zone, with its owner and class, is covered by the DS rrset
+    Ds(Name, u16, DNSKEY),
+}
+
+/// The outcomes of RRSIG verifications, and of the matching of DNSKEYs against the DS rrset of
+///  their zone, such that repeated queries for the same rrset do not repeat the public key
+///  operations, nor the DS queries
+///
+/// An outcome, whether the signature verified or not, is kept until the expiration of the
+///  signature, that of a DS match for the TTL of the DS rrset. The key is everything the outcome
+///  depends on: the owner, class and data of the rrset, the whole RRSIG and the DNSKEY, not only
+///  its key tag, which is not unique; it is compared in whole, a hash collision is no hit.
+pub struct ValidationCache {
+    verified: HashMap<ValidationKey, (bool, u32)>,
+    max_entries: usize,
+}
+
+impl ValidationCache {
+    /// A cache of at most `max_entries` outcomes, zero disables it
+    pub fn new(max_entries: usize) -> Self {
+        ValidationCache {
+            verified: HashMap::new(),
+            max_entries: max_entries,
+        }
+    }
+
+    /// The key of the verification of the RRSIG over the rrset with the DNSKEY
+    pub fn key(dnskey: &DNSKEY,
+               sig: &SIG,
+               name: &Name,
+               dns_class: DNSClass,
+               records: &[Record])
+               -> ValidationKey {
+        // the records in canonical order, the TTLs are those of the RRSIG
+        let mut rdatas = records.iter()
+            .map(|record| {
+                let mut bytes: Vec<u8> = Vec::new();
+                {
+                    let mut encoder = BinEncoder::new(&mut bytes);
+                    if let Err(e) = record.get_rdata().emit(&mut encoder) {
+                        debug!("could not encode rdata of {}: {}", name, e);
+                    }
+                }
+                bytes
+            })
+            .collect::<Vec<_>>();
+        rdatas.sort();
+
+        // names compare without regard to case
+        ValidationKey::Rrsig(name.clone(),
+                             u16::from(dns_class),
+                             rdatas,
+                             sig.clone(),
+                             dnskey.clone())
+    }
+
+    /// The key of the match of the DNSKEY of the zone against its DS rrset
+    pub fn ds_key(dnskey: &DNSKEY, name: &Name, dns_class: DNSClass) -> ValidationKey {
+        ValidationKey::Ds(name.clone(), u16::from(dns_class), dnskey.clone())
+    }
+
+    /// Returns whether the signature verified, None if it is not known or has since expired
+    pub fn get(&mut self, key: &ValidationKey, now: u32) -> Option<bool> {
+        let expired = match self.verified.get(key) {
+            Some(&(verified, expiration)) if !Self::is_expired(expiration, now) => {
+                return Some(verified)
+            }
+            Some(_) => true,
+            None => false,
+        };
+
+        if expired {
+            self.verified.remove(key);
+        }
+        None
+    }
+
+    /// Keeps the outcome of a verification until the expiration of the signature, or that of a DS
+    ///  match until the TTL of the DS rrset runs out
+    ///
+    /// A full cache first drops the expired outcomes, and then all of them if none had expired.
+    pub fn insert(&mut self, key: ValidationKey, verified: bool, expiration: u32, now: u32) {
+        if self.max_entries == 0 || Self::is_expired(expiration, now) {
+            return;
+        }
+
+        if self.verified.len() >= self.max_entries && !self.verified.contains_key(&key) {
+            let expired = self.verified
+                .iter()
+                .filter(|&(_, &(_, expiration))| Self::is_expired(expiration, now))
+                .map(|(key, _)| key.clone())
+                .collect::<Vec<_>>();
+
+            if expired.is_empty() {
+                self.verified.clear();
+            } else {
+                for key in expired {
+                    self.verified.remove(&key);
+                }
+            }
+        }
+
+        self.verified.insert(key, (verified, expiration));
+    }
+
+    /// Serial number arithmetic, as for the validity of the RRSIG, RFC 1982
+    fn is_expired(expiration: u32, now: u32) -> bool {
+        (expiration.wrapping_sub(now) as i32) < 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use rr::{DNSClass, RData, Record, RecordType};
+    use rr::domain::Name;
+    use rr::dnssec::Algorithm;
+    use rr::rdata::{DNSKEY, SIG};
+    use super::*;
+
+    fn key(address: Ipv4Addr, expiration: u32, public_key: u8) -> ValidationKey {
+        let name = Name::parse("www.example.com.", None).unwrap();
+        let record = Record::from_rdata(name.clone(), 86400, RecordType::A, RData::A(address));
+        let sig = SIG::new(RecordType::A,
+                           Algorithm::RSASHA256,
+                           3,
+                           86400,
+                           expiration,
+                           0,
+                           1234,
+                           Name::parse("example.com.", None).unwrap(),
+                           vec![1, 2, 3]);
+        let dnskey = DNSKEY::new(true, false, false, Algorithm::RSASHA256, vec![public_key]);
+
+        ValidationCache::key(&dnskey, &sig, &name, DNSClass::IN, &[record])
+    }
+
+    #[test]
+    fn test_key() {
+        let www = key(Ipv4Addr::new(93, 184, 216, 34), 2000, 1);
+        assert_eq!(www, key(Ipv4Addr::new(93, 184, 216, 34), 2000, 1));
+
+        assert!(www != key(Ipv4Addr::new(192, 0, 2, 1), 2000, 1));
+        assert!(www != key(Ipv4Addr::new(93, 184, 216, 34), 3000, 1));
+        // another key with the same key tag
+        assert!(www != key(Ipv4Addr::new(93, 184, 216, 34), 2000, 2));
+
+        let name = Name::parse("example.com.", None).unwrap();
+        let dnskey = DNSKEY::new(true, true, false, Algorithm::RSASHA256, vec![1]);
+        let ds = ValidationCache::ds_key(&dnskey, &name, DNSClass::IN);
+        assert_eq!(ds, ValidationCache::ds_key(&dnskey, &name, DNSClass::IN));
+        assert!(ds != ValidationCache::ds_key(&dnskey, &name, DNSClass::CH));
+        assert!(ds != www);
+    }
+
+    #[test]
+    fn test_get_and_expire() {
+        let mut cache = ValidationCache::new(2);
+        let valid = key(Ipv4Addr::new(93, 184, 216, 34), 2000, 1);
+        let invalid = key(Ipv4Addr::new(192, 0, 2, 1), 2000, 1);
+
+        assert_eq!(cache.get(&valid, 1000), None);
+        cache.insert(valid.clone(), true, 2000, 1000);
+        cache.insert(invalid.clone(), false, 2000, 1000);
+        assert_eq!(cache.get(&valid, 1000), Some(true));
+        assert_eq!(cache.get(&invalid, 2000), Some(false));
+
+        // expired with the signature
+        assert_eq!(cache.get(&valid, 2001), None);
+        assert_eq!(cache.verified.len(), 1);
+
+        // already expired signatures are not kept
+        cache.insert(valid.clone(), true, 999, 1000);
+        assert_eq!(cache.get(&valid, 1000), None);
+    }
+
+    #[test]
+    fn test_max_entries() {
+        let mut cache = ValidationCache::new(2);
+        let first = key(Ipv4Addr::new(192, 0, 2, 1), 1500, 1);
+        let second = key(Ipv4Addr::new(192, 0, 2, 2), 2000, 1);
+        let third = key(Ipv4Addr::new(192, 0, 2, 3), 2000, 1);
+
+        cache.insert(first.clone(), true, 1500, 1000);
+        cache.insert(second.clone(), true, 2000, 1000);
+
+        // the expired outcome makes room
+        cache.insert(third, true, 2000, 1600);
+        assert_eq!(cache.verified.len(), 2);
+        assert_eq!(cache.get(&second, 1600), Some(true));
+
+        // otherwise the cache starts over
+        cache.insert(first.clone(), true, 2500, 1600);
+        assert_eq!(cache.verified.len(), 1);
+        assert_eq!(cache.get(&first, 1600), Some(true));
+
+        let mut disabled = ValidationCache::new(0);
+        disabled.insert(first.clone(), true, 2000, 1000);
+        assert_eq!(disabled.get(&first, 1000), None);
+    }
+}
@@ -0,0 +1,64 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Source of the current time for TTLs and signature validity
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use chrono::UTC;
+
+/// A source of the current time
+///
+/// Anything that compares against the current time, e.g. cached TTLs or RRSIG validity, takes a
+///  `Clock` rather than reading the system time. Tests can then move time forward, and systems
+///  without a reliable clock can provide their own.
+pub trait Clock: Send + Sync {
+    /// Seconds since the Unix epoch, as used in DNS, e.g. for RRSIG inception and expiration
+    fn now(&self) -> u32;
+}
+
+/// The system clock, the default everywhere a `Clock` is used
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> u32 {
+        UTC::now().timestamp() as u32
+    }
+}
+
+/// A clock which only moves when told to, for tests
+///
+/// All clones share the same time.
+#[derive(Clone, Debug)]
+pub struct ManualClock {
+    now: Arc<AtomicUsize>,
+}
+
+impl ManualClock {
+    /// Returns a new clock set to `now`, seconds since the Unix epoch
+    pub fn new(now: u32) -> Self {
+        ManualClock { now: Arc::new(AtomicUsize::new(now as usize)) }
+    }
+
+    /// Sets the time, seconds since the Unix epoch
+    pub fn set(&self, now: u32) {
+        self.now.store(now as usize, Ordering::SeqCst);
+    }
+
+    /// Moves the clock forward by `seconds`
+    pub fn advance(&self, seconds: u32) {
+        self.now.fetch_add(seconds as usize, Ordering::SeqCst);
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> u32 {
+        self.now.load(Ordering::SeqCst) as u32
+    }
+}
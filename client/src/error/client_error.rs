@@ -15,6 +15,7 @@
  */
 
 use std::io::Error as IoError;
+use std::net::IpAddr;
 
 use futures::Canceled;
 use futures::sync::mpsc::SendError;
@@ -129,6 +130,31 @@ error_chain! {
         description("request timeout")
         display("request timed out")
       }
+
+      LameDelegation(server: IpAddr, zone: Name) {
+        description("server is not authoritative for the zone delegated to it")
+        display("server: {} is lame for zone: {}", server, zone)
+      }
+
+      CircuitOpen {
+        description("upstream is failing, requests are not sent until the cooldown is over")
+        display("upstream is failing, requests are not sent until the cooldown is over")
+      }
+
+      AllServersUnavailable(zone: Name) {
+        description("all name servers are lame or down")
+        display("all name servers are lame or down for zone: {}", zone)
+      }
+
+      Throttled(zone: Name) {
+        description("the zone is under a random subdomain attack, queries are throttled")
+        display("the zone: {} is under a random subdomain attack, queries are throttled", zone)
+      }
+
+      TooManyOutstanding(max: usize) {
+        description("the maximum of outstanding queries was reached")
+        display("the maximum of outstanding queries: {} was reached", max)
+      }
     }
 }
 
@@ -117,5 +117,30 @@ error_chain! {
       description("incorrect rdata length read")
       display("incorrect rdata length read: {} expected: {}", read, len)
     }
+
+    MessageTooLong(len: usize, max: usize) {
+      description("message exceeds the maximum size")
+      display("message of {} bytes exceeds the maximum size: {}", len, max)
+    }
+
+    TooManyRecords(count: usize, max: usize) {
+      description("message claims too many records")
+      display("message claims {} records, maximum is: {}", count, max)
+    }
+
+    TooManyNames(max: usize) {
+      description("message contains too many names")
+      display("message contains more than the maximum names: {}", max)
+    }
+
+    RDataTooLong(len: u16, max: u16) {
+      description("rdata exceeds the maximum length")
+      display("rdata length {} exceeds the maximum: {}", len, max)
+    }
+
+    InvalidRData(reason: String) {
+      description("invalid rdata")
+      display("invalid rdata: {}", reason)
+    }
   }
 }
@@ -36,6 +36,7 @@ pub use self::encode_error::ErrorKind as EncodeErrorKind;
 pub use self::client_error::ErrorKind as ClientErrorKind;
 pub use self::lexer_error::ErrorKind as LexerErrorKind;
 pub use self::parse_error::ErrorKind as ParseErrorKind;
+pub use self::parse_error::Location as ParseErrorLocation;
 
 pub use self::decode_error::ChainErr as DecodeChainErr;
 pub use self::dnssec_error::ChainErr as DnsSecChainErr;
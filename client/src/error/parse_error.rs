@@ -13,12 +13,15 @@
  * See the License for the specific language governing permissions and
  * limitations under the License.
  */
+use std::fmt;
 use std::num;
 use std::io;
 use std::net::AddrParseError;
+use std::path::{Path, PathBuf};
 
 use super::decode_error;
 use super::lexer_error;
+use rr::RecordType;
 use serialize::txt::Token;
 
 error_chain! {
@@ -80,5 +83,79 @@ error_chain! {
         description("invalid time string")
         display("invalid time string: {}", string)
       }
+
+      UnsupportedRecordType(record_type: RecordType) {
+        description("record type is not supported in zone files")
+        display("record type is not supported in zone files: {}", record_type)
+      }
+
+      Located(location: Location, error: Box<Error>) {
+        description("error in zone file")
+        display("{}: {}{}",
+                location,
+                error,
+                location.get_token()
+                    .map(|token| format!(", near `{}`", token))
+                    .unwrap_or_else(String::new))
+      }
+
+      Multiple(errors: Vec<Error>) {
+        description("errors in zone file")
+        display("{} errors in zone file:\n{}",
+                errors.len(),
+                errors.iter().map(|error| error.to_string()).collect::<Vec<_>>().join("\n"))
+      }
+    }
+}
+
+/// Where in a zone file an error is, see `ErrorKind::Located`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Location {
+    file: Option<PathBuf>,
+    line: usize,
+    column: usize,
+    token: Option<Token>,
+}
+
+impl Location {
+    /// The position, counted from 1, in the file, if it has a name, and the offending token, if
+    ///  the error is of one
+    pub fn new(file: Option<PathBuf>, line: usize, column: usize, token: Option<Token>) -> Self {
+        Location {
+            file: file,
+            line: line,
+            column: column,
+            token: token,
+        }
+    }
+
+    /// The zone file, or `$INCLUDE`d file, None if the parser was not given its name
+    pub fn get_file(&self) -> Option<&Path> {
+        self.file.as_ref().map(|file| file.as_path())
+    }
+
+    /// The line, counted from 1
+    pub fn get_line(&self) -> usize {
+        self.line
+    }
+
+    /// The column, counted in characters from 1
+    pub fn get_column(&self) -> usize {
+        self.column
+    }
+
+    /// The token the error is of, None if it is of the entry as a whole
+    pub fn get_token(&self) -> Option<&Token> {
+        self.token.as_ref()
+    }
+}
+
+impl fmt::Display for Location {
+    /// `file:line:column`, or `line:column` without a file name
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(ref file) = self.file {
+            try!(write!(f, "{}:", file.display()));
+        }
+        write!(f, "{}:{}", self.line, self.column)
     }
 }
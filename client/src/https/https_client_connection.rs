@@ -0,0 +1,125 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! DNS over HTTPS based DNS client
+
+use std::net::{IpAddr, SocketAddr};
+use std::io;
+
+use futures::Future;
+use native_tls::Pkcs12;
+#[cfg(target_os = "linux")]
+use openssl::x509::X509 as OpensslX509;
+#[cfg(target_os = "macos")]
+use security_framework::certificate::SecCertificate;
+use tokio_core::reactor::Core;
+
+use ::error::*;
+use client::{ClientConnection, ClientStreamHandle, TimeoutConfig};
+use https::{HttpsClientStream, HttpsClientStreamBuilder, HttpsMethod};
+
+/// DNS over HTTPS based DNS client, RFC 8484
+pub struct HttpsClientConnection {
+    io_loop: Core,
+    https_client_stream: Box<Future<Item = HttpsClientStream, Error = io::Error>>,
+    client_stream_handle: Box<ClientStreamHandle>,
+    timeouts: TimeoutConfig,
+}
+
+impl HttpsClientConnection {
+    pub fn builder() -> HttpsClientConnectionBuilder {
+        HttpsClientConnectionBuilder(HttpsClientStream::builder(), TimeoutConfig::default())
+    }
+}
+
+impl ClientConnection for HttpsClientConnection {
+    type MessageStream = HttpsClientStream;
+
+    fn get_timeouts(&self) -> TimeoutConfig {
+        self.timeouts
+    }
+
+    fn unwrap(self)
+              -> (Core,
+                  Box<Future<Item = Self::MessageStream, Error = io::Error>>,
+                  Box<ClientStreamHandle>) {
+        (self.io_loop, self.https_client_stream, self.client_stream_handle)
+    }
+}
+
+pub struct HttpsClientConnectionBuilder(HttpsClientStreamBuilder, TimeoutConfig);
+
+impl HttpsClientConnectionBuilder {
+    #[cfg(target_os = "macos")]
+    pub fn add_ca(&mut self, ca: SecCertificate) {
+        self.0.add_ca(ca);
+    }
+
+    #[cfg(target_os = "linux")]
+    pub fn add_ca(&mut self, ca: OpensslX509) {
+        self.0.add_ca(ca);
+    }
+
+    /// Client side identity for client auth in TLS (aka mutual TLS auth)
+    pub fn identity(&mut self, pkcs12: Pkcs12) {
+        self.0.identity(pkcs12);
+    }
+
+    /// Requires the certificate of the server to also be issued for the IP address, see
+    ///  `TlsStreamBuilder::verify_ip_address()`
+    pub fn verify_ip_address(&mut self, ip_address: IpAddr) {
+        self.0.verify_ip_address(ip_address);
+    }
+
+    /// The local address to connect from, see `TlsStreamBuilder::bind_addr()`
+    pub fn bind_addr(&mut self, bind_addr: SocketAddr) {
+        self.0.bind_addr(bind_addr);
+    }
+
+    /// The URI path of the DNS API on the server, `/dns-query` if this is not called
+    pub fn path(&mut self, path: &str) {
+        self.0.path(path);
+    }
+
+    /// GET or POST requests, POST if this is not called
+    pub fn method(&mut self, method: HttpsMethod) {
+        self.0.method(method);
+    }
+
+    /// Connect and query timeouts, the defaults are used if this is not called
+    ///
+    /// The read and write timeouts are not applied to HTTP requests, each is bounded by the query
+    ///  timeout.
+    pub fn timeouts(&mut self, timeouts: TimeoutConfig) {
+        self.1 = timeouts;
+    }
+
+    /// Creates a new client connection.
+    ///
+    /// *Note* this has side affects of establishing the connection to the specified DNS server and
+    ///        starting the event_loop. Expect this to change in the future.
+    ///
+    /// # Arguments
+    ///
+    /// * `name_server` - address of the name server to use for queries, usually port 443
+    /// * `subject_name` - name of the server, as associated to its certificate
+    pub fn build(self,
+                 name_server: SocketAddr,
+                 subject_name: String)
+                 -> ClientResult<HttpsClientConnection> {
+        let io_loop = try!(Core::new());
+        let (https_client_stream, handle) =
+            self.0.build(name_server, subject_name, io_loop.handle());
+
+        Ok(HttpsClientConnection {
+            io_loop: io_loop,
+            https_client_stream: https_client_stream,
+            client_stream_handle: handle,
+            timeouts: self.1,
+        })
+    }
+}
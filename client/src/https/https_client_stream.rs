@@ -0,0 +1,489 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::collections::VecDeque;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::str;
+
+use data_encoding::base64url;
+use futures::{Async, Future, Poll};
+use futures::stream::{Fuse, Stream};
+use futures::sync::mpsc::{unbounded, UnboundedReceiver};
+use native_tls::Pkcs12;
+#[cfg(target_os = "linux")]
+use openssl::x509::X509 as OpensslX509;
+#[cfg(target_os = "macos")]
+use security_framework::certificate::SecCertificate;
+use tokio_core::io::Io;
+use tokio_core::net::TcpStream as TokioTcpStream;
+use tokio_core::reactor::Handle;
+use tokio_tls::TlsStream as TokioTlsStream;
+
+use {BufClientStreamHandle, BufStreamHandle};
+use client::ClientStreamHandle;
+use tls::{TlsStream, TlsStreamBuilder};
+
+/// The URI path of the DNS API, if none is configured
+pub const DEFAULT_DNS_QUERY_PATH: &'static str = "/dns-query";
+
+/// The media type of DNS messages in requests and responses, RFC 8484 section 6
+const DNS_MESSAGE_MEDIA_TYPE: &'static str = "application/dns-message";
+
+/// Responses whose header is larger than this are rejected
+const MAX_HEADER_LEN: usize = 8192;
+
+/// The HTTP method used for DNS queries, RFC 8484 section 4.1
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HttpsMethod {
+    /// The message is the base64url encoded `dns` parameter of the URI, with an id of 0 so that
+    ///  the response may be cached by HTTP caches
+    Get,
+    /// The message is the body of the request
+    Post,
+}
+
+/// A stream of DNS messages exchanged as HTTP/1.1 requests and responses, RFC 8484
+///
+/// Requests are pipelined on the connection, the responses are returned in the order of the
+///  requests, with the id of the request.
+#[must_use = "futures do nothing unless polled"]
+pub struct HttpsClientStream<S = TokioTlsStream<TokioTcpStream>> {
+    socket: S,
+    name_server: SocketAddr,
+    host: String,
+    path: String,
+    method: HttpsMethod,
+    outbound_messages: Fuse<UnboundedReceiver<(Vec<u8>, SocketAddr)>>,
+    /// the HTTP request being written, with the number of bytes already written
+    send_state: Option<(usize, Vec<u8>)>,
+    /// the message ids of the sent requests, which have not been answered yet
+    outstanding: VecDeque<u16>,
+    /// the bytes read of the responses which are not yet complete
+    read_buf: Vec<u8>,
+}
+
+impl HttpsClientStream {
+    pub fn builder() -> HttpsClientStreamBuilder {
+        HttpsClientStreamBuilder {
+            tls: TlsStream::builder(),
+            path: DEFAULT_DNS_QUERY_PATH.to_string(),
+            method: HttpsMethod::Post,
+        }
+    }
+}
+
+impl<S: Io> HttpsClientStream<S> {
+    /// Exchanges messages over an established connection, e.g. the TLS connection to the server
+    ///
+    /// # Arguments
+    ///
+    /// * `socket` - the established IO stream for communication
+    /// * `name_server` - address of the server, messages to other addresses are an error
+    /// * `host` - value of the HTTP Host header, the name of the server
+    /// * `path` - the URI path of the DNS API, e.g. `DEFAULT_DNS_QUERY_PATH`
+    /// * `method` - GET or POST
+    pub fn from_stream(socket: S,
+                       name_server: SocketAddr,
+                       host: String,
+                       path: String,
+                       method: HttpsMethod)
+                       -> (Self, BufStreamHandle) {
+        let (message_sender, outbound_messages) = unbounded();
+
+        let stream = Self::from_stream_with_receiver(socket,
+                                                     name_server,
+                                                     host,
+                                                     path,
+                                                     method,
+                                                     outbound_messages);
+
+        (stream, message_sender)
+    }
+
+    fn from_stream_with_receiver(socket: S,
+                                 name_server: SocketAddr,
+                                 host: String,
+                                 path: String,
+                                 method: HttpsMethod,
+                                 receiver: UnboundedReceiver<(Vec<u8>, SocketAddr)>)
+                                 -> Self {
+        HttpsClientStream {
+            socket: socket,
+            name_server: name_server,
+            host: host,
+            path: path,
+            method: method,
+            outbound_messages: receiver.fuse(),
+            send_state: None,
+            outstanding: VecDeque::new(),
+            read_buf: Vec::new(),
+        }
+    }
+
+    /// The HTTP request for the message, recording its id
+    fn request(&mut self, mut message: Vec<u8>) -> io::Result<Vec<u8>> {
+        if message.len() < 2 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "message without an id"));
+        }
+        self.outstanding.push_back((message[0] as u16) << 8 | message[1] as u16);
+
+        let request = match self.method {
+            HttpsMethod::Get => {
+                message[0] = 0;
+                message[1] = 0;
+
+                // base64url without padding, RFC 8484 section 6
+                let encoded = base64url::encode(&message);
+                let separator = if self.path.contains('?') { '&' } else { '?' };
+                format!("GET {}{}dns={} HTTP/1.1\r\nHost: {}\r\nAccept: {}\r\n\r\n",
+                        self.path,
+                        separator,
+                        encoded.trim_right_matches('='),
+                        self.host,
+                        DNS_MESSAGE_MEDIA_TYPE)
+                    .into_bytes()
+            }
+            HttpsMethod::Post => {
+                let mut request = format!("POST {} HTTP/1.1\r\nHost: {}\r\nAccept: {}\r\n\
+                                           Content-Type: {}\r\nContent-Length: {}\r\n\r\n",
+                                          self.path,
+                                          self.host,
+                                          DNS_MESSAGE_MEDIA_TYPE,
+                                          DNS_MESSAGE_MEDIA_TYPE,
+                                          message.len())
+                    .into_bytes();
+                request.extend_from_slice(&message);
+                request
+            }
+        };
+
+        Ok(request)
+    }
+}
+
+impl<S: Io> Stream for HttpsClientStream<S> {
+    type Item = Vec<u8>;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        // like the TcpStream, all pending requests are written before reading
+        loop {
+            let written = match self.send_state {
+                Some((ref mut pos, ref request)) => {
+                    let wrote = try_nb!(self.socket.write(&request[*pos..]));
+                    if wrote == 0 && *pos < request.len() {
+                        return Err(io::Error::new(io::ErrorKind::WriteZero,
+                                                  "closed while writing request"));
+                    }
+                    *pos += wrote;
+                    *pos == request.len()
+                }
+                None => false,
+            };
+
+            if written {
+                try_nb!(self.socket.flush());
+                self.send_state = None;
+            } else if self.send_state.is_none() {
+                match try!(self.outbound_messages
+                    .poll()
+                    .map_err(|()| io::Error::new(io::ErrorKind::Other, "unknown"))) {
+                    Async::Ready(Some((buffer, dst))) => {
+                        if dst != self.name_server {
+                            return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                                      format!("mismatched peer: {} and dst: {}",
+                                                              self.name_server,
+                                                              dst)));
+                        }
+
+                        debug!("sending message len: {} to: {}", buffer.len(), dst);
+                        let request = try!(self.request(buffer));
+                        self.send_state = Some((0, request));
+                    }
+                    Async::NotReady => break,
+                    Async::Ready(None) => {
+                        debug!("no messages to send");
+                        break;
+                    }
+                }
+            }
+        }
+
+        loop {
+            if let Some((len, mut message)) = try!(parse_response(&self.read_buf)) {
+                self.read_buf.drain(..len);
+
+                let id = try!(self.outstanding.pop_front().ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::InvalidData, "response without a request")
+                }));
+                if message.len() >= 2 {
+                    message[0] = (id >> 8) as u8;
+                    message[1] = id as u8;
+                }
+
+                return Ok(Async::Ready(Some(message)));
+            }
+
+            let mut buffer = [0u8; 4096];
+            let read = try_nb!(self.socket.read(&mut buffer));
+            if read == 0 {
+                debug!("zero bytes read, stream closed?");
+
+                if self.read_buf.is_empty() {
+                    return Ok(Async::Ready(None));
+                } else {
+                    return Err(io::Error::new(io::ErrorKind::BrokenPipe,
+                                              "closed while reading response"));
+                }
+            }
+
+            self.read_buf.extend_from_slice(&buffer[..read]);
+        }
+    }
+}
+
+/// Parses the first HTTP response in the buffer, returning its length and the DNS message, or
+///  None if it is not complete yet
+///
+/// Only successful responses with a `Content-Length` are accepted, any other status is an error.
+fn parse_response(buffer: &[u8]) -> io::Result<Option<(usize, Vec<u8>)>> {
+    let header_len = match buffer.windows(4).position(|w| w == b"\r\n\r\n") {
+        Some(end) => end + 4,
+        None if buffer.len() > MAX_HEADER_LEN => {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "HTTP header too large"))
+        }
+        None => return Ok(None),
+    };
+
+    let header = try!(str::from_utf8(&buffer[..header_len - 4])
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "HTTP header is not UTF-8")));
+    let mut lines = header.split("\r\n");
+
+    let status_line = lines.next().unwrap_or("");
+    let mut status = status_line.splitn(3, ' ');
+    match (status.next(), status.next()) {
+        (Some(version), Some("200")) if version.starts_with("HTTP/1.") => (),
+        (Some(version), Some(_)) if version.starts_with("HTTP/1.") => {
+            return Err(io::Error::new(io::ErrorKind::Other,
+                                      format!("HTTP error: {}", status_line)))
+        }
+        _ => {
+            return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                      format!("bad HTTP status line: {}", status_line)))
+        }
+    }
+
+    let mut content_length: Option<usize> = None;
+    for line in lines {
+        let mut field = line.splitn(2, ':');
+        let name = field.next().unwrap_or("").trim().to_lowercase();
+        let value = field.next().unwrap_or("").trim().to_lowercase();
+
+        match &name as &str {
+            "content-length" => {
+                content_length = Some(try!(value.parse().map_err(|_| {
+                    io::Error::new(io::ErrorKind::InvalidData,
+                                   format!("bad Content-Length: {}", value))
+                })))
+            }
+            "content-type" if value.split(';').next().unwrap_or("").trim() !=
+                              DNS_MESSAGE_MEDIA_TYPE => {
+                return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                          format!("unexpected Content-Type: {}", value)))
+            }
+            "transfer-encoding" if value != "identity" => {
+                return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                          format!("unsupported Transfer-Encoding: {}", value)))
+            }
+            _ => (),
+        }
+    }
+
+    let content_length = try!(content_length.ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidData, "response without a Content-Length")
+    }));
+    if content_length > u16::max_value() as usize {
+        return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                  format!("bad message length: {}", content_length)));
+    }
+
+    if buffer.len() < header_len + content_length {
+        return Ok(None);
+    }
+
+    Ok(Some((header_len + content_length,
+             buffer[header_len..header_len + content_length].to_vec())))
+}
+
+pub struct HttpsClientStreamBuilder {
+    tls: TlsStreamBuilder,
+    path: String,
+    method: HttpsMethod,
+}
+
+impl HttpsClientStreamBuilder {
+    #[cfg(target_os = "macos")]
+    pub fn add_ca(&mut self, ca: SecCertificate) {
+        self.tls.add_ca(ca);
+    }
+
+    #[cfg(target_os = "linux")]
+    pub fn add_ca(&mut self, ca: OpensslX509) {
+        self.tls.add_ca(ca);
+    }
+
+    /// Client side identity for client auth in TLS (aka mutual TLS auth)
+    pub fn identity(&mut self, pkcs12: Pkcs12) {
+        self.tls.identity(pkcs12);
+    }
+
+    /// Requires the certificate of the server to also be issued for the IP address, see
+    ///  `TlsStreamBuilder::verify_ip_address()`
+    pub fn verify_ip_address(&mut self, ip_address: IpAddr) {
+        self.tls.verify_ip_address(ip_address);
+    }
+
+    /// The local address to connect from, see `TlsStreamBuilder::bind_addr()`
+    pub fn bind_addr(&mut self, bind_addr: SocketAddr) {
+        self.tls.bind_addr(bind_addr);
+    }
+
+    /// The URI path of the DNS API on the server, `DEFAULT_DNS_QUERY_PATH` if this is not called
+    pub fn path(&mut self, path: &str) {
+        self.path = path.to_string();
+    }
+
+    /// GET or POST requests, POST if this is not called
+    pub fn method(&mut self, method: HttpsMethod) {
+        self.method = method;
+    }
+
+    /// Creates a new HttpsClientStream to the specified name_server
+    ///
+    /// # Arguments
+    ///
+    /// * `name_server` - IP and Port for the remote DNS resolver, usually port 443
+    /// * `subject_name` - The name of the server, as associated to its certificate, and the Host
+    /// * `loop_handle` - The reactor Core handle
+    pub fn build(self,
+                 name_server: SocketAddr,
+                 subject_name: String,
+                 loop_handle: Handle)
+                 -> (Box<Future<Item = HttpsClientStream, Error = io::Error>>,
+                     Box<ClientStreamHandle>) {
+        let (message_sender, outbound_messages) = unbounded();
+        let path = self.path;
+        let method = self.method;
+        let host = subject_name.clone();
+
+        let stream: Box<Future<Item = HttpsClientStream, Error = io::Error>> =
+            Box::new(self.tls.connect(name_server, subject_name, loop_handle).map(move |s| {
+                HttpsClientStream::from_stream_with_receiver(s,
+                                                             name_server,
+                                                             host,
+                                                             path,
+                                                             method,
+                                                             outbound_messages)
+            }));
+
+        let sender = Box::new(BufClientStreamHandle {
+            name_server: name_server,
+            sender: message_sender,
+        });
+
+        (stream, sender)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    use futures::Stream;
+    use tokio_core::net::TcpStream as TokioTcpStream;
+    use tokio_core::reactor::Core;
+
+    use super::*;
+
+    const RESPONSE: &'static [u8] = b"HTTP/1.1 200 OK\r\nContent-Type: application/dns-message\r\n\
+                                      Content-Length: 4\r\n\r\n\x00\x00\x81\x80";
+
+    #[test]
+    fn test_parse_response() {
+        assert_eq!(parse_response(&RESPONSE[..20]).unwrap(), None);
+        assert_eq!(parse_response(&RESPONSE[..RESPONSE.len() - 1]).unwrap(), None);
+        assert_eq!(parse_response(RESPONSE).unwrap(),
+                   Some((RESPONSE.len(), vec![0x00, 0x00, 0x81, 0x80])));
+
+        // the next response is left in the buffer
+        let mut pipelined = RESPONSE.to_vec();
+        pipelined.extend_from_slice(&RESPONSE[..10]);
+        assert_eq!(parse_response(&pipelined).unwrap().unwrap().0, RESPONSE.len());
+
+        assert!(parse_response(b"HTTP/1.1 415 Unsupported Media Type\r\n\r\n").is_err());
+        assert!(parse_response(b"HTTP/1.1 200 OK\r\nContent-Type: text/html\r\n\r\n").is_err());
+        assert!(parse_response(b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n").is_err());
+        assert!(parse_response(b"HTTP/1.1 200 OK\r\n\r\n").is_err());
+    }
+
+    fn exchange(method: HttpsMethod) -> (String, Vec<u8>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let server_addr = listener.local_addr().unwrap();
+
+        let server = thread::spawn(move || {
+            let (mut socket, _) = listener.accept().unwrap();
+            let mut request = Vec::new();
+            let mut buffer = [0u8; 1024];
+            while !request.ends_with(&[0xAB, 0xCD]) && !request.ends_with(b"\r\n\r\n") {
+                let read = socket.read(&mut buffer).unwrap();
+                request.extend_from_slice(&buffer[..read]);
+            }
+
+            socket.write_all(RESPONSE).unwrap();
+            String::from_utf8_lossy(&request).into_owned()
+        });
+
+        let mut io_loop = Core::new().unwrap();
+        let handle = io_loop.handle();
+        let socket = io_loop.run(TokioTcpStream::connect(&server_addr, &handle)).unwrap();
+        let (stream, mut sender) = HttpsClientStream::from_stream(socket,
+                                                                  server_addr,
+                                                                  "dns.example.com".to_string(),
+                                                                  "/dns-query".to_string(),
+                                                                  method);
+
+        sender.send((vec![0x12, 0x34, 0xAB, 0xCD], server_addr)).unwrap();
+        let (response, _) = io_loop.run(stream.into_future()).ok().unwrap();
+
+        (server.join().unwrap(), response.unwrap())
+    }
+
+    #[test]
+    fn test_post() {
+        let (request, response) = exchange(HttpsMethod::Post);
+        assert!(request.starts_with("POST /dns-query HTTP/1.1\r\n"));
+        assert!(request.contains("Host: dns.example.com\r\n"));
+        assert!(request.contains("Content-Type: application/dns-message\r\n"));
+        assert!(request.contains("Content-Length: 4\r\n"));
+
+        // the id of the request
+        assert_eq!(response, vec![0x12, 0x34, 0x81, 0x80]);
+    }
+
+    #[test]
+    fn test_get() {
+        let (request, response) = exchange(HttpsMethod::Get);
+
+        // the id is 0, and the encoding unpadded
+        assert!(request.starts_with("GET /dns-query?dns=AACrzQ HTTP/1.1\r\n"));
+        assert!(request.contains("Accept: application/dns-message\r\n"));
+        assert_eq!(response, vec![0x12, 0x34, 0x81, 0x80]);
+    }
+}
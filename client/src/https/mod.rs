@@ -0,0 +1,15 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! DNS over HTTPS, RFC 8484, related components for DNS.
+
+mod https_client_connection;
+mod https_client_stream;
+
+pub use self::https_client_connection::{HttpsClientConnection, HttpsClientConnectionBuilder};
+pub use self::https_client_stream::{HttpsClientStream, HttpsClientStreamBuilder, HttpsMethod,
+                                    DEFAULT_DNS_QUERY_PATH};
@@ -38,12 +38,15 @@ extern crate lazy_static;
 #[macro_use]
 extern crate log;
 extern crate native_tls;
+extern crate net2;
 #[cfg(feature = "openssl")]
 extern crate openssl;
 extern crate rand;
 #[cfg(feature = "ring")]
 extern crate ring;
 extern crate rustc_serialize;
+#[cfg(test)]
+extern crate tempdir;
 #[cfg(target_os = "macos")]
 extern crate security_framework;
 extern crate time;
@@ -52,16 +55,24 @@ extern crate tokio_core;
 extern crate tokio_tls;
 #[cfg(feature = "ring")]
 extern crate untrusted;
+#[cfg(windows)]
+extern crate winreg;
 
+pub mod cache;
 pub mod client;
+pub mod clock;
 pub mod error;
+pub mod https;
 pub mod logger;
+pub mod mdns;
 pub mod op;
+pub mod recursor;
 pub mod rr;
 pub mod tcp;
 pub mod tls;
 pub mod udp;
 pub mod serialize;
+pub mod system_conf;
 
 use std::io;
 use std::net::SocketAddr;
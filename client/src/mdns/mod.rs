@@ -0,0 +1,43 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Multicast DNS, [RFC 6762](https://tools.ietf.org/html/rfc6762), and DNS-Based Service
+//!  Discovery on top of it, [RFC 6763](https://tools.ietf.org/html/rfc6763)
+
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+
+mod service_discovery;
+
+pub use self::service_discovery::{ServiceDiscovery, ServiceDiscoveryStream, ServiceEvent,
+                                  ServiceInstance};
+
+/// the port of mDNS queries and responses
+pub const MDNS_PORT: u16 = 5353;
+
+/// Returns the mDNS group, `224.0.0.251:5353` or `[ff02::fb]:5353`, of the family of `addr`
+pub fn mdns_group(addr: &SocketAddr) -> SocketAddr {
+    match *addr {
+        SocketAddr::V4(..) => {
+            SocketAddr::new(IpAddr::V4(Ipv4Addr::new(224, 0, 0, 251)), MDNS_PORT)
+        }
+        SocketAddr::V6(..) => {
+            SocketAddr::new(IpAddr::V6(Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 0xfb)),
+                            MDNS_PORT)
+        }
+    }
+}
+
+/// Binds a socket to the mDNS port, which has joined the IPv4 group on `interface`
+///
+/// Another responder on the host, which has bound the port exclusively, makes this fail.
+pub fn bind_mdns_ipv4(interface: &Ipv4Addr) -> io::Result<UdpSocket> {
+    let socket = try!(UdpSocket::bind((Ipv4Addr::new(0, 0, 0, 0), MDNS_PORT)));
+    try!(socket.join_multicast_v4(&Ipv4Addr::new(224, 0, 0, 251), interface));
+
+    Ok(socket)
+}
@@ -0,0 +1,632 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::cmp;
+use std::collections::{HashMap, VecDeque};
+use std::io;
+use std::net::{IpAddr, SocketAddr, UdpSocket};
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::{Async, Poll, Stream};
+use tokio_core::net::UdpSocket as TokioUdpSocket;
+use tokio_core::reactor::{Handle, Interval};
+
+use clock::{Clock, SystemClock};
+use mdns::mdns_group;
+use op::{Message, MessageType, OpCode, Query};
+use rr::{DNSClass, LowerName, Name, RData, Record, RecordType};
+
+/// seconds to the second browse query, doubled after each query, RFC 6762 section 5.2
+const FIRST_QUERY_INTERVAL: u32 = 1;
+/// the longest interval between browse queries, one hour
+const MAX_QUERY_INTERVAL: u32 = 3600;
+
+/// An instance of the service, resolved to its host, port and addresses
+#[derive(Clone, Debug, PartialEq)]
+pub struct ServiceInstance {
+    name: Name,
+    host: Name,
+    port: u16,
+    txt: Vec<String>,
+    addresses: Vec<IpAddr>,
+}
+
+impl ServiceInstance {
+    /// The name of the instance, e.g. `Office Printer._ipp._tcp.local.`
+    pub fn get_name(&self) -> &Name {
+        &self.name
+    }
+
+    /// The user visible name of the instance, the first label of the name, e.g. `Office Printer`
+    pub fn get_instance(&self) -> &str {
+        &self.name[0]
+    }
+
+    /// The host of the service, the target of its SRV record
+    pub fn get_host(&self) -> &Name {
+        &self.host
+    }
+
+    /// The port on which the service listens
+    pub fn get_port(&self) -> u16 {
+        self.port
+    }
+
+    /// The key/value pairs of the TXT record of the instance, e.g. `rp=printers/office`, empty if
+    ///  it has none
+    pub fn get_txt(&self) -> &[String] {
+        &self.txt
+    }
+
+    /// The addresses of the host
+    pub fn get_addresses(&self) -> &[IpAddr] {
+        &self.addresses
+    }
+}
+
+/// A change to the instances of the service on the link
+#[derive(Clone, Debug, PartialEq)]
+pub enum ServiceEvent {
+    /// The instance was discovered and resolved, or it changed since it was last found
+    ServiceFound(ServiceInstance),
+    /// The instance of the name said goodbye, or its PTR record expired
+    ServiceRemoved(Name),
+}
+
+/// An instance which a PTR record points to, as far as it is resolved
+struct Instance {
+    ptr: Record,
+    received: u32,
+    srv: Option<(Name, u16)>,
+    txt: Vec<String>,
+    found: Option<ServiceInstance>,
+}
+
+impl Instance {
+    /// The name of the instance, as the PTR record has it
+    fn get_name(&self) -> &Name {
+        match *self.ptr.get_rdata() {
+            RData::PTR(ref name) => name,
+            _ => panic!("only PTR records are browsed"),
+        }
+    }
+}
+
+/// Browses for the instances of a service type on the link, and resolves them, RFC 6763
+///
+/// The discovery does no IO, see `ServiceDiscoveryStream`. Its `next_query()` is multicast to
+///  the group when it is due, and every mDNS response received is passed to its
+///  `handle_message()`, which returns the instances found or removed. Those removed when their
+///  PTR records expire are returned by `expire()`. All times, `now`, are seconds since the epoch.
+///
+/// The PTR records of the service type name are browsed for, with the first query at once, the
+///  next after one second, and intervals doubling up to an hour, with the PTR records already
+///  known in the answer section, RFC 6762 section 7.1. The SRV and TXT records of each instance,
+///  and the A and AAAA records of its host, are taken from the additional records of the
+///  responses, or else queried for along with the next browse query.
+pub struct ServiceDiscovery {
+    service_name: Name,
+    instances: HashMap<LowerName, Instance>,
+    hosts: HashMap<LowerName, Vec<IpAddr>>,
+    next_query: Option<u32>,
+    query_interval: u32,
+    unresolved: bool,
+}
+
+impl ServiceDiscovery {
+    /// Returns a discovery for the service type, e.g. `_ipp._tcp`, in the `local.` domain
+    pub fn new(service_type: &str) -> io::Result<Self> {
+        let service_name = try!(Name::parse(&format!("{}.local.", service_type.trim_matches('.')),
+                                            None)
+            .map_err(|e| {
+                io::Error::new(io::ErrorKind::InvalidInput,
+                               format!("invalid service type {}: {}", service_type, e))
+            }));
+
+        Ok(ServiceDiscovery {
+            service_name: service_name,
+            instances: HashMap::new(),
+            hosts: HashMap::new(),
+            next_query: None,
+            query_interval: FIRST_QUERY_INTERVAL,
+            unresolved: false,
+        })
+    }
+
+    /// The name browsed for, the service type in the `local.` domain, e.g. `_ipp._tcp.local.`
+    pub fn get_service_name(&self) -> &Name {
+        &self.service_name
+    }
+
+    /// The instances found and not removed since
+    pub fn get_instances(&self) -> Vec<&ServiceInstance> {
+        self.instances.values().filter_map(|instance| instance.found.as_ref()).collect()
+    }
+
+    /// Returns the query to multicast, if one is due
+    ///
+    /// This is the browse query once its interval has passed, or the queries for the records of
+    ///  instances which the last responses did not resolve.
+    pub fn next_query(&mut self, now: u32) -> Option<Message> {
+        let browse = self.next_query.map_or(true, |next_query| next_query <= now);
+        if !browse && !self.unresolved {
+            return None;
+        }
+
+        let mut message = Message::new();
+        message.id(0).message_type(MessageType::Query).op_code(OpCode::Query);
+
+        if browse {
+            self.next_query = Some(now.saturating_add(self.query_interval));
+            self.query_interval = cmp::min(self.query_interval.saturating_mul(2),
+                                           MAX_QUERY_INTERVAL);
+
+            message.add_query(Self::query(self.service_name.clone(), RecordType::PTR));
+
+            // known-answer suppression, only for records with more than half their ttl left
+            for instance in self.instances.values() {
+                let ttl = instance.ptr.get_ttl();
+                let elapsed = now.saturating_sub(instance.received);
+                if elapsed < ttl / 2 {
+                    let mut known = instance.ptr.clone();
+                    known.ttl(ttl - elapsed);
+                    message.add_answer(known);
+                }
+            }
+        }
+
+        self.unresolved = false;
+        for instance in self.instances.values() {
+            let name = instance.get_name();
+            match instance.srv {
+                None => {
+                    message.add_query(Self::query(name.clone(), RecordType::SRV));
+                    message.add_query(Self::query(name.clone(), RecordType::TXT));
+                }
+                Some((ref host, _)) if !self.hosts.contains_key(&LowerName::new(host)) => {
+                    message.add_query(Self::query(host.clone(), RecordType::A));
+                    message.add_query(Self::query(host.clone(), RecordType::AAAA));
+                }
+                _ => (),
+            }
+        }
+
+        Some(message)
+    }
+
+    /// Takes the records of an mDNS response, returns the instances found and removed
+    pub fn handle_message(&mut self, message: &Message, now: u32) -> Vec<ServiceEvent> {
+        if message.get_message_type() != MessageType::Response ||
+           message.get_op_code() != OpCode::Query {
+            return vec![];
+        }
+
+        let mut events: Vec<ServiceEvent> = Vec::new();
+        let records = message.get_answers().iter().chain(message.get_additionals());
+
+        // the PTR records first, they add the instances the other records are for
+        for record in records.clone().filter(|r| r.get_rr_type() == RecordType::PTR) {
+            if LowerName::new(record.get_name()) != LowerName::new(&self.service_name) {
+                continue;
+            }
+            let instance_name = match *record.get_rdata() {
+                RData::PTR(ref name) => LowerName::new(name),
+                _ => continue,
+            };
+
+            // a goodbye, RFC 6762 section 10.1
+            if record.get_ttl() == 0 {
+                if let Some(instance) = self.instances.remove(&instance_name) {
+                    if instance.found.is_some() {
+                        events.push(ServiceEvent::ServiceRemoved(instance.get_name().clone()));
+                    }
+                }
+                continue;
+            }
+
+            let mut ptr = record.clone();
+            ptr.mdns_cache_flush(false);
+            let instance = self.instances.entry(instance_name).or_insert_with(|| {
+                Instance {
+                    ptr: ptr.clone(),
+                    received: now,
+                    srv: None,
+                    txt: vec![],
+                    found: None,
+                }
+            });
+            instance.ptr = ptr;
+            instance.received = now;
+        }
+
+        let mut flushed: Vec<LowerName> = Vec::new();
+        for record in records {
+            let name = LowerName::new(record.get_name());
+            match *record.get_rdata() {
+                RData::SRV(ref srv) => {
+                    if let Some(instance) = self.instances.get_mut(&name) {
+                        instance.srv = if record.get_ttl() == 0 {
+                            None
+                        } else {
+                            Some((srv.get_target().clone(), srv.get_port()))
+                        };
+                    }
+                }
+                RData::TXT(ref txt) => {
+                    if let Some(instance) = self.instances.get_mut(&name) {
+                        // an empty TXT record is a single empty string, RFC 6763 section 6.1
+                        instance.txt = txt.get_txt_data()
+                            .iter()
+                            .filter(|s| !s.is_empty())
+                            .cloned()
+                            .collect();
+                    }
+                }
+                RData::A(address) => {
+                    Self::update_address(&mut self.hosts,
+                                         &mut flushed,
+                                         name,
+                                         IpAddr::V4(address),
+                                         record)
+                }
+                RData::AAAA(address) => {
+                    Self::update_address(&mut self.hosts,
+                                         &mut flushed,
+                                         name,
+                                         IpAddr::V6(address),
+                                         record)
+                }
+                _ => (),
+            }
+        }
+
+        // report the instances which are resolved, or have changed
+        for instance in self.instances.values_mut() {
+            let resolved = match instance.srv {
+                Some((ref host, port)) => {
+                    self.hosts.get(&LowerName::new(host)).map(|addresses| {
+                        ServiceInstance {
+                            name: instance.get_name().clone(),
+                            host: host.clone(),
+                            port: port,
+                            txt: instance.txt.clone(),
+                            addresses: addresses.clone(),
+                        }
+                    })
+                }
+                None => None,
+            };
+
+            match resolved {
+                Some(resolved) => {
+                    if instance.found.as_ref() != Some(&resolved) {
+                        events.push(ServiceEvent::ServiceFound(resolved.clone()));
+                        instance.found = Some(resolved);
+                    }
+                }
+                None => self.unresolved = true,
+            }
+        }
+
+        events
+    }
+
+    /// Removes the instances whose PTR records have expired, returns those which were found
+    pub fn expire(&mut self, now: u32) -> Vec<ServiceEvent> {
+        let expired: Vec<LowerName> = self.instances
+            .iter()
+            .filter(|&(_, instance)| {
+                instance.received.saturating_add(instance.ptr.get_ttl()) <= now
+            })
+            .map(|(name, _)| name.clone())
+            .collect();
+
+        let mut events: Vec<ServiceEvent> = Vec::new();
+        for name in expired {
+            if let Some(instance) = self.instances.remove(&name) {
+                if instance.found.is_some() {
+                    events.push(ServiceEvent::ServiceRemoved(instance.get_name().clone()));
+                }
+            }
+        }
+
+        events
+    }
+
+    fn query(name: Name, query_type: RecordType) -> Query {
+        let mut query = Query::new();
+        query.name(name).query_type(query_type).query_class(DNSClass::IN);
+        query
+    }
+
+    /// An address record with the cache-flush bit replaces the addresses of the host received
+    ///  before the message, RFC 6762 section 10.2, a goodbye removes the address
+    fn update_address(hosts: &mut HashMap<LowerName, Vec<IpAddr>>,
+                      flushed: &mut Vec<LowerName>,
+                      name: LowerName,
+                      address: IpAddr,
+                      record: &Record) {
+        if record.get_ttl() == 0 {
+            let empty = hosts.get_mut(&name).map_or(false, |addresses| {
+                addresses.retain(|a| *a != address);
+                addresses.is_empty()
+            });
+            if empty {
+                hosts.remove(&name);
+            }
+            return;
+        }
+
+        if record.is_mdns_cache_flush() && !flushed.contains(&name) {
+            hosts.remove(&name);
+            flushed.push(name.clone());
+        }
+
+        let addresses = hosts.entry(name).or_insert_with(Vec::new);
+        if !addresses.contains(&address) {
+            addresses.push(address);
+        }
+    }
+}
+
+/// A stream of the instances of a service found and removed on the link, by a `ServiceDiscovery`
+///  on an mDNS socket
+#[must_use = "streams do nothing unless polled"]
+pub struct ServiceDiscoveryStream {
+    discovery: ServiceDiscovery,
+    socket: TokioUdpSocket,
+    group: SocketAddr,
+    ticks: Interval,
+    clock: Arc<Clock>,
+    events: VecDeque<ServiceEvent>,
+}
+
+impl ServiceDiscoveryStream {
+    /// Browses for the service type, e.g. `_ipp._tcp`, on the socket
+    ///
+    /// # Arguments
+    ///
+    /// * `service_type` - the service type and protocol
+    /// * `socket` - a UDP socket bound to the mDNS port, which has joined the group, see
+    ///              `bind_mdns_ipv4()`
+    /// * `loop_handle` - the reactor Core handle
+    pub fn new(service_type: &str,
+               socket: UdpSocket,
+               loop_handle: &Handle)
+               -> io::Result<ServiceDiscoveryStream> {
+        Self::with_clock(service_type, socket, loop_handle, Arc::new(SystemClock))
+    }
+
+    /// Browses for the service type on the socket, timing the queries and TTLs by the clock
+    pub fn with_clock(service_type: &str,
+                      socket: UdpSocket,
+                      loop_handle: &Handle,
+                      clock: Arc<Clock>)
+                      -> io::Result<ServiceDiscoveryStream> {
+        let group = mdns_group(&try!(socket.local_addr()));
+
+        Ok(ServiceDiscoveryStream {
+            discovery: try!(ServiceDiscovery::new(service_type)),
+            socket: try!(TokioUdpSocket::from_socket(socket, loop_handle)),
+            group: group,
+            ticks: try!(Interval::new(Duration::from_secs(1), loop_handle)),
+            clock: clock,
+            events: VecDeque::new(),
+        })
+    }
+
+    /// The discovery, with the instances found so far
+    pub fn get_discovery(&self) -> &ServiceDiscovery {
+        &self.discovery
+    }
+}
+
+impl Stream for ServiceDiscoveryStream {
+    type Item = ServiceEvent;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        loop {
+            if let Some(event) = self.events.pop_front() {
+                return Ok(Async::Ready(Some(event)));
+            }
+
+            let now = self.clock.now();
+            self.events.extend(self.discovery.expire(now));
+            if let Some(query) = self.discovery.next_query(now) {
+                let bytes = try!(query.to_vec()
+                    .map_err(|e| io::Error::new(io::ErrorKind::Other, format!("{}", e))));
+                match self.socket.send_to(&bytes, &self.group) {
+                    Ok(_) => (),
+                    // the query is sent again with the next one
+                    Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                        debug!("mDNS socket not ready for the query")
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+
+            // the ticks wake the stream for the queries and expiry
+            let mut ticked = false;
+            while let Async::Ready(tick) = try!(self.ticks.poll()) {
+                if tick.is_none() {
+                    return Ok(Async::Ready(None));
+                }
+                ticked = true;
+            }
+
+            let mut buf = [0u8; 9000];
+            match self.socket.recv_from(&mut buf) {
+                Ok((len, src)) => {
+                    match Message::from_vec(&buf[..len]) {
+                        Ok(message) => {
+                            self.events.extend(self.discovery.handle_message(&message, now))
+                        }
+                        Err(e) => debug!("dropping mDNS message from {}: {}", src, e),
+                    }
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    if !ticked && self.events.is_empty() {
+                        return Ok(Async::NotReady);
+                    }
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+
+    use op::*;
+    use rr::*;
+    use rr::rdata::{SRV, TXT};
+    use super::*;
+
+    fn name(name: &str) -> Name {
+        Name::parse(name, None).unwrap()
+    }
+
+    fn record(owner: &str, ttl: u32, rdata: RData) -> Record {
+        let rr_type = rdata.to_record_type();
+        let mut record = Record::from_rdata(name(owner), ttl, rr_type, rdata);
+        record.mdns_cache_flush(rr_type != RecordType::PTR);
+        record
+    }
+
+    fn response(answers: Vec<Record>, additionals: Vec<Record>) -> Message {
+        let mut message = Message::new();
+        message.id(0)
+            .message_type(MessageType::Response)
+            .op_code(OpCode::Query)
+            .authoritative(true)
+            .add_answers(answers);
+        message.insert_additionals(additionals);
+        message
+    }
+
+    fn ptr(ttl: u32) -> Record {
+        record("_ipp._tcp.local.",
+               ttl,
+               RData::PTR(name("Office Printer._ipp._tcp.local.")))
+    }
+
+    fn srv() -> Record {
+        record("Office Printer._ipp._tcp.local.",
+               120,
+               RData::SRV(SRV::new(0, 0, 631, name("printer.local."))))
+    }
+
+    fn a(address: Ipv4Addr) -> Record {
+        record("printer.local.", 120, RData::A(address))
+    }
+
+    #[test]
+    fn test_browse_intervals() {
+        let mut discovery = ServiceDiscovery::new("_ipp._tcp").unwrap();
+        assert_eq!(discovery.get_service_name(), &name("_ipp._tcp.local."));
+
+        let query = discovery.next_query(1000).unwrap();
+        assert_eq!(query.get_queries()[0].get_name(), &name("_ipp._tcp.local."));
+        assert_eq!(query.get_queries()[0].get_query_type(), RecordType::PTR);
+        assert!(discovery.next_query(1000).is_none());
+
+        assert!(discovery.next_query(1001).is_some());
+        assert!(discovery.next_query(1002).is_none());
+        assert!(discovery.next_query(1003).is_some());
+        assert!(discovery.next_query(1006).is_none());
+        assert!(discovery.next_query(1007).is_some());
+    }
+
+    #[test]
+    fn test_found_and_removed() {
+        let mut discovery = ServiceDiscovery::new("_ipp._tcp").unwrap();
+        discovery.next_query(1000);
+
+        // resolved from the additional records
+        let txt = record("Office Printer._ipp._tcp.local.",
+                         4500,
+                         RData::TXT(TXT::new(vec!["rp=printers/office".to_string()])));
+        let events = discovery.handle_message(&response(vec![ptr(4500)],
+                                                        vec![srv(),
+                                                             txt,
+                                                             a(Ipv4Addr::new(192, 168, 0, 2))]),
+                                              1000);
+        assert_eq!(events.len(), 1);
+        let instance = match events[0] {
+            ServiceEvent::ServiceFound(ref instance) => instance.clone(),
+            ref event => panic!("unexpected event: {:?}", event),
+        };
+        assert_eq!(instance.get_instance(), "Office Printer");
+        assert_eq!(instance.get_host(), &name("printer.local."));
+        assert_eq!(instance.get_port(), 631);
+        assert_eq!(instance.get_txt(), &["rp=printers/office".to_string()]);
+        assert_eq!(instance.get_addresses(),
+                   &[IpAddr::V4(Ipv4Addr::new(192, 168, 0, 2))]);
+        assert_eq!(discovery.get_instances().len(), 1);
+
+        // the known answer is in the next browse query, nothing changed
+        let query = discovery.next_query(1001).unwrap();
+        assert_eq!(query.get_answers().len(), 1);
+        assert_eq!(query.get_answers()[0].get_ttl(), 4499);
+        assert!(discovery.handle_message(&response(vec![ptr(4500)], vec![]), 1001).is_empty());
+
+        // a new address flushes the old one
+        let events =
+            discovery.handle_message(&response(vec![a(Ipv4Addr::new(192, 168, 0, 9))], vec![]),
+                                     1002);
+        match events[0] {
+            ServiceEvent::ServiceFound(ref instance) => {
+                assert_eq!(instance.get_addresses(),
+                           &[IpAddr::V4(Ipv4Addr::new(192, 168, 0, 9))])
+            }
+            ref event => panic!("unexpected event: {:?}", event),
+        }
+
+        // goodbye
+        let events = discovery.handle_message(&response(vec![ptr(0)], vec![]), 1003);
+        assert_eq!(events,
+                   vec![ServiceEvent::ServiceRemoved(name("Office Printer._ipp._tcp.local."))]);
+        assert!(discovery.get_instances().is_empty());
+    }
+
+    #[test]
+    fn test_resolve_and_expire() {
+        let mut discovery = ServiceDiscovery::new("_ipp._tcp").unwrap();
+        discovery.next_query(1000);
+
+        // only the PTR, the SRV and TXT are queried for before the next browse query
+        assert!(discovery.handle_message(&response(vec![ptr(60)], vec![]), 1000).is_empty());
+        let query = discovery.next_query(1000).unwrap();
+        assert_eq!(query.get_queries().len(), 2);
+        assert_eq!(query.get_queries()[0].get_query_type(), RecordType::SRV);
+        assert!(discovery.next_query(1000).is_none());
+
+        assert!(discovery.handle_message(&response(vec![srv()], vec![]), 1000).is_empty());
+        let query = discovery.next_query(1000).unwrap();
+        assert_eq!(query.get_queries()[0].get_name(), &name("printer.local."));
+
+        let events =
+            discovery.handle_message(&response(vec![a(Ipv4Addr::new(192, 168, 0, 2))], vec![]),
+                                     1000);
+        assert_eq!(events.len(), 1);
+        assert!(discovery.next_query(1000).is_none());
+
+        // records of other services are ignored
+        let other = record("_http._tcp.local.",
+                           60,
+                           RData::PTR(name("Web._http._tcp.local.")));
+        assert!(discovery.handle_message(&response(vec![other], vec![]), 1000).is_empty());
+
+        assert!(discovery.expire(1059).is_empty());
+        assert_eq!(discovery.expire(1060),
+                   vec![ServiceEvent::ServiceRemoved(name("Office Printer._ipp._tcp.local."))]);
+    }
+}
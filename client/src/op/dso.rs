@@ -0,0 +1,314 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! TLVs carried in DNS Stateful Operations messages
+
+use ::error::*;
+use ::op::Query;
+use ::rr::{DNSClass, Name, RData, Record, RecordType};
+use ::serialize::binary::*;
+
+/// The TTL of a record in a PUSH TLV, which has been removed,
+///  [RFC 8765](https://tools.ietf.org/html/rfc8765#section-6.3.1)
+pub const PUSH_REMOVED_TTL: u32 = 0xFFFFFFFF;
+
+/// The type of a DSO TLV, [RFC 8490](https://tools.ietf.org/html/rfc8490#section-10.3)
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum DsoType {
+    /// Keepalive, establishes the session and negotiates its timeouts
+    KeepAlive,
+    /// Retry Delay, sent by a server to ask the client to close the session and back off
+    RetryDelay,
+    /// Encryption Padding, only valid as an additional TLV
+    EncryptionPadding,
+    /// DNS Push SUBSCRIBE, [RFC 8765](https://tools.ietf.org/html/rfc8765#section-6.2)
+    Subscribe,
+    /// DNS Push PUSH, [RFC 8765](https://tools.ietf.org/html/rfc8765#section-6.3)
+    Push,
+    /// DNS Push UNSUBSCRIBE, [RFC 8765](https://tools.ietf.org/html/rfc8765#section-6.4)
+    Unsubscribe,
+    /// DNS Push RECONFIRM, [RFC 8765](https://tools.ietf.org/html/rfc8765#section-6.5)
+    Reconfirm,
+    /// Unknown, or not supported by this implementation
+    Unknown(u16),
+}
+
+impl From<u16> for DsoType {
+    fn from(value: u16) -> DsoType {
+        match value {
+            1 => DsoType::KeepAlive,
+            2 => DsoType::RetryDelay,
+            3 => DsoType::EncryptionPadding,
+            0x40 => DsoType::Subscribe,
+            0x41 => DsoType::Push,
+            0x42 => DsoType::Unsubscribe,
+            0x43 => DsoType::Reconfirm,
+            _ => DsoType::Unknown(value),
+        }
+    }
+}
+
+impl From<DsoType> for u16 {
+    fn from(value: DsoType) -> u16 {
+        match value {
+            DsoType::KeepAlive => 1,
+            DsoType::RetryDelay => 2,
+            DsoType::EncryptionPadding => 3,
+            DsoType::Subscribe => 0x40,
+            DsoType::Push => 0x41,
+            DsoType::Unsubscribe => 0x42,
+            DsoType::Reconfirm => 0x43,
+            DsoType::Unknown(value) => value,
+        }
+    }
+}
+
+/// A TLV following the header of a message with the `OpCode::Dso`
+///
+/// [RFC 8490, DNS Stateful Operations, March 2019](https://tools.ietf.org/html/rfc8490#section-5.4)
+///
+/// ```text
+///                                           1   1   1   1   1   1
+///   0   1   2   3   4   5   6   7   8   9   0   1   2   3   4   5
+/// +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+/// |                           DSO-TYPE                            |
+/// +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+/// |                          DSO-LENGTH                           |
+/// +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+/// |                                                               |
+/// /                           DSO-DATA                            /
+/// /                                                               /
+/// |                                                               |
+/// +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+/// ```
+///
+/// The first TLV of a message is its primary TLV, which determines how the message is handled,
+///  any following TLVs are additional TLVs. All intervals are in milliseconds. Names in TLVs are
+///  never compressed.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum DsoTlv {
+    /// the inactivity timeout and keepalive interval for the session
+    KeepAlive {
+        /// time the session may be idle, before the client is to close it
+        inactivity_timeout: u32,
+        /// time between keepalive traffic, if there is no other traffic
+        keepalive_interval: u32,
+    },
+    /// the time the client should wait before reconnecting, after closing the session
+    RetryDelay(u32),
+    /// padding to obscure the length of an encrypted message
+    EncryptionPadding(Vec<u8>),
+    /// subscribe to changes of the records matching the name, type and class
+    Subscribe(Query),
+    /// records added to a subscription, or removed if their TTL is `PUSH_REMOVED_TTL`
+    Push(Vec<Record>),
+    /// cancels the subscription requested with this message id
+    Unsubscribe(u16),
+    /// asks the server to verify the record is still valid, its TTL is ignored
+    Reconfirm(Record),
+    /// Unknown, the data is kept as read
+    Unknown(u16, Vec<u8>),
+}
+
+impl DsoTlv {
+    /// Returns the DSO-TYPE of this TLV
+    pub fn get_dso_type(&self) -> DsoType {
+        match *self {
+            DsoTlv::KeepAlive { .. } => DsoType::KeepAlive,
+            DsoTlv::RetryDelay(..) => DsoType::RetryDelay,
+            DsoTlv::EncryptionPadding(..) => DsoType::EncryptionPadding,
+            DsoTlv::Subscribe(..) => DsoType::Subscribe,
+            DsoTlv::Push(..) => DsoType::Push,
+            DsoTlv::Unsubscribe(..) => DsoType::Unsubscribe,
+            DsoTlv::Reconfirm(..) => DsoType::Reconfirm,
+            DsoTlv::Unknown(dso_type, _) => DsoType::Unknown(dso_type),
+        }
+    }
+
+    fn emit_data(&self, encoder: &mut BinEncoder) -> EncodeResult {
+        match *self {
+            DsoTlv::KeepAlive { inactivity_timeout, keepalive_interval } => {
+                try!(encoder.emit_u32(inactivity_timeout));
+                encoder.emit_u32(keepalive_interval)
+            }
+            DsoTlv::RetryDelay(delay) => encoder.emit_u32(delay),
+            DsoTlv::EncryptionPadding(ref data) |
+            DsoTlv::Unknown(_, ref data) => encoder.emit_vec(data),
+            DsoTlv::Subscribe(ref query) => query.emit(encoder),
+            DsoTlv::Push(ref records) => {
+                for record in records {
+                    try!(record.emit(encoder));
+                }
+                Ok(())
+            }
+            DsoTlv::Unsubscribe(id) => encoder.emit_u16(id),
+            DsoTlv::Reconfirm(ref record) => {
+                try!(record.get_name().emit(encoder));
+                try!(record.get_rr_type().emit(encoder));
+                try!(record.get_dns_class().emit(encoder));
+                record.get_rdata().emit(encoder)
+            }
+        }
+    }
+
+    fn read_data(dso_type: DsoType, data: Vec<u8>) -> DecodeResult<DsoTlv> {
+        match dso_type {
+            DsoType::EncryptionPadding => return Ok(DsoTlv::EncryptionPadding(data)),
+            DsoType::Unknown(dso_type) => return Ok(DsoTlv::Unknown(dso_type, data)),
+            _ => (),
+        }
+
+        let length = data.len();
+        let mut decoder = BinDecoder::new(&data);
+
+        let tlv = match dso_type {
+            DsoType::KeepAlive => {
+                if length != 8 {
+                    return Err(DecodeErrorKind::Message("keepalive DSO-LENGTH must be 8")
+                        .into());
+                }
+
+                DsoTlv::KeepAlive {
+                    inactivity_timeout: try!(decoder.read_u32()),
+                    keepalive_interval: try!(decoder.read_u32()),
+                }
+            }
+            DsoType::RetryDelay => {
+                if length != 4 {
+                    return Err(DecodeErrorKind::Message("retry delay DSO-LENGTH must be 4")
+                        .into());
+                }
+
+                DsoTlv::RetryDelay(try!(decoder.read_u32()))
+            }
+            DsoType::Unsubscribe => {
+                if length != 2 {
+                    return Err(DecodeErrorKind::Message("unsubscribe DSO-LENGTH must be 2")
+                        .into());
+                }
+
+                DsoTlv::Unsubscribe(try!(decoder.read_u16()))
+            }
+            DsoType::Subscribe => DsoTlv::Subscribe(try!(Query::read(&mut decoder))),
+            DsoType::Push => {
+                let mut records = Vec::new();
+                while decoder.len() > 0 {
+                    records.push(try!(Record::read(&mut decoder)));
+                }
+                DsoTlv::Push(records)
+            }
+            DsoType::Reconfirm => {
+                let name = try!(Name::read(&mut decoder));
+                let rr_type = try!(RecordType::read(&mut decoder));
+                let dns_class = try!(DNSClass::read(&mut decoder));
+                let rdata_length = decoder.len() as u16;
+                let rdata = try!(RData::read(&mut decoder, rr_type, rdata_length));
+
+                let mut record = Record::with(name, rr_type, 0);
+                record.dns_class(dns_class).rdata(rdata);
+                DsoTlv::Reconfirm(record)
+            }
+            DsoType::EncryptionPadding |
+            DsoType::Unknown(_) => unreachable!(),
+        };
+
+        if decoder.len() > 0 {
+            return Err(DecodeErrorKind::Message("DSO-DATA longer than its TLV").into());
+        }
+        Ok(tlv)
+    }
+}
+
+impl BinSerializable<DsoTlv> for DsoTlv {
+    fn read(decoder: &mut BinDecoder) -> DecodeResult<DsoTlv> {
+        let dso_type = DsoType::from(try!(decoder.read_u16()));
+        let length = try!(decoder.read_u16()) as usize;
+        if length > decoder.len() {
+            return Err(DecodeErrorKind::Message("DSO-LENGTH exceeds the message").into());
+        }
+
+        Self::read_data(dso_type, try!(decoder.read_vec(length)))
+    }
+
+    fn emit(&self, encoder: &mut BinEncoder) -> EncodeResult {
+        let mut data = Vec::new();
+        {
+            let mut data_encoder = BinEncoder::new(&mut data);
+            data_encoder.set_canonical_names(true);
+            try!(self.emit_data(&mut data_encoder));
+        }
+
+        if data.len() > u16::max_value() as usize {
+            return Err("DSO-DATA exceeds 65535 bytes".into());
+        }
+
+        try!(encoder.emit_u16(self.get_dso_type().into()));
+        try!(encoder.emit_u16(data.len() as u16));
+        encoder.emit_vec(&data)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::Ipv4Addr;
+
+    use super::*;
+    use ::op::Query;
+    use ::rr::{Name, RData, Record, RecordType};
+    use ::serialize::binary::*;
+
+    #[test]
+    fn test_read_and_emit() {
+        let name = Name::parse("www.example.com.", None).unwrap();
+        let mut query = Query::new();
+        query.name(name.clone()).query_type(RecordType::A);
+
+        let mut added = Record::with(name.clone(), RecordType::A, 300);
+        added.rdata(RData::A(Ipv4Addr::new(127, 0, 0, 1)));
+        let mut removed = Record::with(name.clone(), RecordType::A, PUSH_REMOVED_TTL);
+        removed.rdata(RData::A(Ipv4Addr::new(127, 0, 0, 2)));
+
+        let tlvs = vec![DsoTlv::KeepAlive {
+                            inactivity_timeout: 15000,
+                            keepalive_interval: 3600000,
+                        },
+                        DsoTlv::RetryDelay(5000),
+                        DsoTlv::EncryptionPadding(vec![0; 12]),
+                        DsoTlv::Subscribe(query),
+                        DsoTlv::Push(vec![added.clone(), removed]),
+                        DsoTlv::Unsubscribe(10),
+                        DsoTlv::Reconfirm(added),
+                        DsoTlv::Unknown(0xF000, vec![1, 2, 3])];
+
+        for tlv in tlvs {
+            let mut bytes = Vec::new();
+            {
+                let mut encoder = BinEncoder::new(&mut bytes);
+                tlv.emit(&mut encoder).expect("emit failed");
+            }
+
+            let mut decoder = BinDecoder::new(&bytes);
+            assert_eq!(DsoTlv::read(&mut decoder).expect("read failed"), tlv);
+            assert_eq!(decoder.len(), 0);
+        }
+    }
+
+    #[test]
+    fn test_read_bad_length() {
+        // keepalive with only the inactivity timeout
+        let bytes = [0, 1, 0, 4, 0, 0, 0x3A, 0x98];
+        assert!(DsoTlv::read(&mut BinDecoder::new(&bytes)).is_err());
+
+        // longer than the remaining message
+        let bytes = [0, 2, 0, 8, 0, 0, 0x13, 0x88];
+        assert!(DsoTlv::read(&mut BinDecoder::new(&bytes)).is_err());
+
+        // unsubscribe with trailing data
+        let bytes = [0, 0x42, 0, 3, 0, 10, 0];
+        assert!(DsoTlv::read(&mut BinDecoder::new(&bytes)).is_err());
+    }
+}
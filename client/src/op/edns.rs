@@ -31,6 +31,8 @@ pub struct Edns {
     version: u8,
     // Is DNSSec supported (from TTL)
     dnssec_ok: bool,
+    // the remaining 15 bits of flags, unknown to this implementation but preserved (from TTL)
+    z_flags: u16,
     // max payload size, minimum of 512, (from RR CLASS)
     max_payload: u16,
 
@@ -43,6 +45,7 @@ impl Edns {
             rcode_high: 0,
             version: 0,
             dnssec_ok: false,
+            z_flags: 0,
             max_payload: 512,
             options: OPT::default(),
         }
@@ -57,6 +60,11 @@ impl Edns {
     pub fn is_dnssec_ok(&self) -> bool {
         self.dnssec_ok
     }
+    /// The flags following the DO bit, these are unassigned and must be zero when sent by this
+    ///  implementation, but are kept so that a forwarded request is passed along unchanged.
+    pub fn get_z_flags(&self) -> u16 {
+        self.z_flags
+    }
     pub fn get_max_payload(&self) -> u16 {
         self.max_payload
     }
@@ -76,6 +84,10 @@ impl Edns {
     pub fn set_dnssec_ok(&mut self, dnssec_ok: bool) {
         self.dnssec_ok = dnssec_ok
     }
+    /// Only the low 15 bits are used, the high bit is the DO flag
+    pub fn set_z_flags(&mut self, z_flags: u16) {
+        self.z_flags = z_flags & 0x7FFF
+    }
     pub fn set_max_payload(&mut self, max_payload: u16) {
         self.max_payload = max_payload
     }
@@ -91,6 +103,7 @@ impl<'a> From<&'a Record> for Edns {
         let rcode_high: u8 = ((value.get_ttl() & 0xFF000000u32) >> 24) as u8;
         let version: u8 = ((value.get_ttl() & 0x00FF0000u32) >> 16) as u8;
         let dnssec_ok: bool = value.get_ttl() & 0x00008000 == 0x00008000;
+        let z_flags: u16 = (value.get_ttl() & 0x00007FFF) as u16;
         let max_payload: u16 = if u16::from(value.get_dns_class()) < 512 {
             512
         } else {
@@ -115,6 +128,7 @@ impl<'a> From<&'a Record> for Edns {
             rcode_high: rcode_high,
             version: version,
             dnssec_ok: dnssec_ok,
+            z_flags: z_flags,
             max_payload: max_payload,
             options: options,
         }
@@ -138,6 +152,7 @@ impl<'a> From<&'a Edns> for Record {
         if value.is_dnssec_ok() {
             ttl |= 0x00008000;
         }
+        ttl |= (value.get_z_flags() & 0x7FFF) as u32;
         record.ttl(ttl);
 
         // now for each option, write out the option array
@@ -160,7 +175,9 @@ fn test_encode_decode() {
     edns.set_max_payload(0x8008);
     edns.set_version(0x40);
     edns.set_rcode_high(0x01);
+    edns.set_z_flags(0x4001);
     edns.set_option(EdnsOption::DAU(SupportedAlgorithms::all()));
+    edns.set_option(EdnsOption::Unknown(65001, vec![1, 2, 3]));
 
     let record: Record = (&edns).into();
     let edns_decode: Edns = (&record).into();
@@ -169,5 +186,6 @@ fn test_encode_decode() {
     assert_eq!(edns.get_max_payload(), edns_decode.get_max_payload());
     assert_eq!(edns.get_version(), edns_decode.get_version());
     assert_eq!(edns.get_rcode_high(), edns_decode.get_rcode_high());
+    assert_eq!(edns.get_z_flags(), edns_decode.get_z_flags());
     assert_eq!(edns.get_options(), edns_decode.get_options());
 }
@@ -0,0 +1,349 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Incremental zone transfer, IXFR, the differences between versions of a zone
+
+use std::collections::BTreeMap;
+
+use ::error::*;
+use rr::{RData, Record, RecordSet, RecordType, RrKey};
+
+/// The changes which took a zone from one serial to the next, RFC 1995
+#[derive(Clone, Debug, PartialEq)]
+pub struct ZoneDelta {
+    old_soa: Record,
+    removed: Vec<Record>,
+    new_soa: Record,
+    added: Vec<Record>,
+}
+
+impl ZoneDelta {
+    /// Creates a new delta
+    ///
+    /// # Arguments
+    ///
+    /// * `old_soa` - the SOA of the zone before the change
+    /// * `removed` - the records which were deleted, or replaced
+    /// * `new_soa` - the SOA of the zone after the change
+    /// * `added` - the records which were added, or replace the removed ones
+    pub fn new(old_soa: Record, removed: Vec<Record>, new_soa: Record, added: Vec<Record>) -> Self {
+        ZoneDelta {
+            old_soa: old_soa,
+            removed: removed,
+            new_soa: new_soa,
+            added: added,
+        }
+    }
+
+    /// the SOA of the zone before the change
+    pub fn get_old_soa(&self) -> &Record {
+        &self.old_soa
+    }
+
+    /// the records which were deleted
+    pub fn get_removed(&self) -> &[Record] {
+        &self.removed
+    }
+
+    /// the SOA of the zone after the change
+    pub fn get_new_soa(&self) -> &Record {
+        &self.new_soa
+    }
+
+    /// the records which were added
+    pub fn get_added(&self) -> &[Record] {
+        &self.added
+    }
+
+    /// The serial the delta applies to
+    pub fn get_old_serial(&self) -> u32 {
+        soa_serial(&self.old_soa).unwrap_or(0)
+    }
+
+    /// The serial of the zone once the delta is applied
+    pub fn get_new_serial(&self) -> u32 {
+        soa_serial(&self.new_soa).unwrap_or(0)
+    }
+
+    /// The records as they are sent in an IXFR response, the old SOA, the removed records, the new
+    ///  SOA and the added records
+    pub fn records(&self) -> Vec<&Record> {
+        let mut records: Vec<&Record> = vec![&self.old_soa];
+        records.extend(self.removed.iter());
+        records.push(&self.new_soa);
+        records.extend(self.added.iter());
+        records
+    }
+
+    /// Applies the changes to the records of the zone
+    ///
+    /// # Arguments
+    ///
+    /// * `zone` - the records of the zone, its SOA must have the serial of the old SOA
+    pub fn apply(&self, zone: &mut BTreeMap<RrKey, RecordSet>) -> ClientResult<()> {
+        let soa_key = RrKey::new(self.old_soa.get_name(), RecordType::SOA);
+        let serial = zone.get(&soa_key)
+            .and_then(|rrset| rrset.iter().next())
+            .and_then(soa_serial);
+        if serial != Some(self.get_old_serial()) {
+            return Err(ClientErrorKind::Msg(format!("delta from serial: {} does not apply to: \
+                                                     {:?}",
+                                                    self.get_old_serial(),
+                                                    serial))
+                .into());
+        }
+
+        let new_serial = self.get_new_serial();
+        // removing the last NS of a rrset is permitted here, it is replaced by the added ones
+        for record in &self.removed {
+            let key = RrKey::new(record.get_name(), record.get_rr_type());
+            let remaining: Vec<Record> = match zone.get(&key) {
+                Some(rrset) => {
+                    rrset.iter().filter(|r| r.get_rdata() != record.get_rdata()).cloned().collect()
+                }
+                None => continue,
+            };
+
+            zone.remove(&key);
+            for remaining in remaining {
+                insert(zone, remaining, new_serial);
+            }
+        }
+
+        for record in &self.added {
+            insert(zone, record.clone(), new_serial);
+        }
+
+        // the serial only increases, the SOA is replaced rather than inserted
+        let mut soa = RecordSet::new(self.new_soa.get_name(), RecordType::SOA, new_serial);
+        soa.insert(self.new_soa.clone(), new_serial);
+        zone.insert(soa_key, soa);
+
+        Ok(())
+    }
+}
+
+/// The answer to an IXFR query, RFC 1995 section 4
+#[derive(Clone, Debug, PartialEq)]
+pub enum IxfrResponse {
+    /// the zone has not changed since the serial of the request, the current SOA
+    UpToDate(Record),
+    /// the deltas from the serial of the request to the current one, in order
+    Incremental(Vec<ZoneDelta>),
+    /// the deltas are not known to the server, all the records of the zone, as in an AXFR, with
+    ///  the SOA once
+    Full(Vec<Record>),
+}
+
+impl IxfrResponse {
+    /// Reads the answers of an IXFR response
+    ///
+    /// Responses which are not complete, e.g. only the first message of a transfer which was
+    ///  split over several, are an error.
+    ///
+    /// # Arguments
+    ///
+    /// * `records` - the answers of the response, the SOA of the current version of the zone
+    ///               first
+    pub fn from_records(records: &[Record]) -> ClientResult<Self> {
+        let current_serial = match records.first().and_then(soa_serial) {
+            Some(serial) => serial,
+            None => {
+                return Err(ClientErrorKind::Message("IXFR response does not start with SOA").into())
+            }
+        };
+
+        if records.len() == 1 {
+            return Ok(IxfrResponse::UpToDate(records[0].clone()));
+        }
+        if records.last().and_then(soa_serial) != Some(current_serial) {
+            return Err(ClientErrorKind::Message("IXFR response is incomplete").into());
+        }
+
+        // a full transfer has the current SOA second only if the zone is nothing else
+        match soa_serial(&records[1]) {
+            Some(serial) if serial != current_serial => (),
+            _ => return Ok(IxfrResponse::Full(records[..records.len() - 1].to_vec())),
+        }
+
+        let mut deltas: Vec<ZoneDelta> = Vec::new();
+        let mut index = 1;
+        let last = records.len() - 1;
+        while index < last {
+            let old_soa = &records[index];
+            let removed = take_until_soa(&records[index + 1..last]);
+            index += 1 + removed.len();
+
+            if index >= last {
+                return Err(ClientErrorKind::Message("IXFR delta has no new SOA").into());
+            }
+            let new_soa = &records[index];
+            let added = take_until_soa(&records[index + 1..last]);
+            index += 1 + added.len();
+
+            deltas.push(ZoneDelta::new(old_soa.clone(),
+                                       removed.to_vec(),
+                                       new_soa.clone(),
+                                       added.to_vec()));
+        }
+
+        Ok(IxfrResponse::Incremental(deltas))
+    }
+
+    /// Applies the response to the records of the zone
+    ///
+    /// # Arguments
+    ///
+    /// * `zone` - the records of the zone, at the serial the IXFR was requested with, they are
+    ///            replaced by a full transfer
+    pub fn apply(&self, zone: &mut BTreeMap<RrKey, RecordSet>) -> ClientResult<()> {
+        match *self {
+            IxfrResponse::UpToDate(_) => Ok(()),
+            IxfrResponse::Incremental(ref deltas) => {
+                for delta in deltas {
+                    try!(delta.apply(zone));
+                }
+                Ok(())
+            }
+            IxfrResponse::Full(ref records) => {
+                let serial = records.first().and_then(soa_serial).unwrap_or(0);
+                zone.clear();
+                for record in records {
+                    insert(zone, record.clone(), serial);
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+fn insert(zone: &mut BTreeMap<RrKey, RecordSet>, record: Record, serial: u32) {
+    let rrset = RecordSet::new(record.get_name(), record.get_rr_type(), serial);
+    zone.entry(RrKey::new(record.get_name(), record.get_rr_type()))
+        .or_insert(rrset)
+        .insert(record, serial);
+}
+
+/// The records up to the next SOA
+fn take_until_soa(records: &[Record]) -> &[Record] {
+    let end = records.iter()
+        .position(|record| record.get_rr_type() == RecordType::SOA)
+        .unwrap_or(records.len());
+    &records[..end]
+}
+
+fn soa_serial(record: &Record) -> Option<u32> {
+    match *record.get_rdata() {
+        RData::SOA(ref soa) => Some(soa.get_serial()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+    use std::net::Ipv4Addr;
+
+    use rr::*;
+    use rr::rdata::SOA;
+    use super::*;
+
+    fn origin() -> Name {
+        Name::parse("example.com.", None).unwrap()
+    }
+
+    fn soa(serial: u32) -> Record {
+        Record::from_rdata(origin(),
+                           3600,
+                           RecordType::SOA,
+                           RData::SOA(SOA::new(Name::parse("ns.example.com.", None).unwrap(),
+                                               Name::parse("root.example.com.", None).unwrap(),
+                                               serial,
+                                               3600,
+                                               600,
+                                               86400,
+                                               3600)))
+    }
+
+    fn a(name: &str, last: u8) -> Record {
+        Record::from_rdata(Name::parse(name, Some(&origin())).unwrap(),
+                           3600,
+                           RecordType::A,
+                           RData::A(Ipv4Addr::new(192, 0, 2, last)))
+    }
+
+    fn zone(records: &[Record]) -> BTreeMap<RrKey, RecordSet> {
+        let mut zone = BTreeMap::new();
+        IxfrResponse::Full(records.to_vec()).apply(&mut zone).unwrap();
+        zone
+    }
+
+    /// the records, without the serials of the rrsets
+    fn flatten(zone: &BTreeMap<RrKey, RecordSet>) -> Vec<Record> {
+        zone.values().flat_map(|rrset| rrset.iter().cloned()).collect()
+    }
+
+    #[test]
+    fn test_incremental() {
+        // RFC 1995 section 7, two deltas
+        let answers = vec![soa(3),
+                           soa(1),
+                           a("www", 1),
+                           soa(2),
+                           a("www", 2),
+                           soa(2),
+                           a("www", 2),
+                           soa(3),
+                           a("www", 3),
+                           a("mail", 3),
+                           soa(3)];
+
+        let response = IxfrResponse::from_records(&answers).unwrap();
+        match response {
+            IxfrResponse::Incremental(ref deltas) => {
+                assert_eq!(deltas.len(), 2);
+                assert_eq!(deltas[0].get_old_serial(), 1);
+                assert_eq!(deltas[0].get_new_serial(), 2);
+                assert_eq!(deltas[1].get_added(), &[a("www", 3), a("mail", 3)]);
+                assert_eq!(deltas[1].records().len(), 5);
+            }
+            ref other => panic!("expected deltas: {:?}", other),
+        }
+
+        let mut records = zone(&[soa(1), a("www", 1), a("ftp", 1)]);
+        response.apply(&mut records).unwrap();
+        assert_eq!(flatten(&records),
+                   flatten(&zone(&[soa(3), a("www", 3), a("mail", 3), a("ftp", 1)])));
+
+        // the deltas no longer apply
+        assert!(response.apply(&mut records).is_err());
+    }
+
+    #[test]
+    fn test_up_to_date_and_full() {
+        assert_eq!(IxfrResponse::from_records(&[soa(3)]).unwrap(),
+                   IxfrResponse::UpToDate(soa(3)));
+
+        let full = IxfrResponse::from_records(&[soa(3), a("www", 3), soa(3)]).unwrap();
+        assert_eq!(full, IxfrResponse::Full(vec![soa(3), a("www", 3)]));
+
+        let mut records = zone(&[soa(1), a("www", 1), a("ftp", 1)]);
+        full.apply(&mut records).unwrap();
+        assert_eq!(flatten(&records), flatten(&zone(&[soa(3), a("www", 3)])));
+
+        assert_eq!(IxfrResponse::from_records(&[soa(3), soa(3)]).unwrap(),
+                   IxfrResponse::Full(vec![soa(3)]));
+    }
+
+    #[test]
+    fn test_incomplete() {
+        assert!(IxfrResponse::from_records(&[]).is_err());
+        assert!(IxfrResponse::from_records(&[a("www", 1)]).is_err());
+        assert!(IxfrResponse::from_records(&[soa(3), soa(1), a("www", 1)]).is_err());
+        assert!(IxfrResponse::from_records(&[soa(3), soa(1), a("www", 1), soa(3)]).is_err());
+    }
+}
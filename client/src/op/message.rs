@@ -16,18 +16,20 @@
 
 //! Basic protocol message for DNS
 
-use std::fmt::Debug;
+use std::fmt::{Debug, Write};
 use std::mem;
+use std::net::SocketAddr;
+use std::time::Duration;
 
 use ::error::*;
-use rr::{Record, RecordType};
-#[cfg(feature = "openssl")]
-use rr::{DNSClass, Name, RData};
+use rr::{DNSClass, Name, RData, Record, RecordType};
 #[cfg(feature = "openssl")]
 use rr::rdata::SIG;
-use rr::dnssec::Signer;
-use serialize::binary::{BinEncoder, BinDecoder, BinSerializable, EncodeMode};
-use super::{MessageType, Header, Query, Edns, OpCode, ResponseCode};
+use rr::rdata::TSIG;
+use rr::rdata::opt::{ClientSubnet, EdnsCode, EdnsOption};
+use rr::dnssec::{Signer, TSigner, TsigChain};
+use serialize::binary::{BinEncoder, BinDecoder, BinSerializable, DecodeLimits, EncodeMode};
+use super::{MessageType, Header, Query, Edns, DsoTlv, OpCode, ResponseCode};
 
 /// The basic request and response datastructure, used for all DNS protocols.
 ///
@@ -71,6 +73,9 @@ use super::{MessageType, Header, Query, Edns, OpCode, ResponseCode};
 ///
 /// By default Message is a Query. Use the Message::as_update() to create and update, or
 ///  Message::new_update()
+///
+/// A message with the `OpCode::Dso` carries TLVs after the header rather than records, see
+///  [RFC 8490](https://tools.ietf.org/html/rfc8490#section-5.4) and `DsoTlv`.
 #[derive(Clone, Debug, PartialEq)]
 pub struct Message {
     header: Header,
@@ -80,6 +85,7 @@ pub struct Message {
     additionals: Vec<Record>,
     sig0: Vec<Record>,
     edns: Option<Edns>,
+    dso_tlvs: Vec<DsoTlv>,
 }
 
 impl Message {
@@ -92,6 +98,7 @@ impl Message {
             additionals: Vec::new(),
             sig0: Vec::new(),
             edns: None,
+            dso_tlvs: Vec::new(),
         }
     }
 
@@ -105,6 +112,9 @@ impl Message {
         message
     }
 
+    /// Returns the header, queries and EDNS of this message, without any records and with TC set
+    ///
+    /// This is sent in place of a response which does not fit in a UDP datagram.
     pub fn truncate(&self) -> Self {
         let mut truncated: Message = Message::new();
         truncated.id(self.get_id());
@@ -115,11 +125,13 @@ impl Message {
         truncated.recursion_desired(self.is_recursion_desired());
         truncated.recursion_available(self.is_recursion_available());
         truncated.response_code(self.get_response_code());
+        truncated.add_queries(self.get_queries().iter().cloned());
         if self.get_edns().is_some() {
             truncated.set_edns(self.get_edns().unwrap().clone());
         }
 
-        // TODO, perhaps just quickly add a few response records here? that we know would fit?
+        // no records, a partial RRSet would be taken for the whole of it, the requestor is to
+        //  retry over TCP
         truncated
     }
 
@@ -159,8 +171,17 @@ impl Message {
         self.header.checking_disabled(checking_disabled);
         self
     }
+    /// Sets the response code, the high bits of extended codes (e.g. BADVERS) are stored in the
+    ///  Edns, which will be created if necessary.
     pub fn response_code(&mut self, response_code: ResponseCode) -> &mut Self {
         self.header.response_code(response_code);
+
+        let high = response_code.high();
+        if high != 0 {
+            self.get_edns_mut().set_rcode_high(high);
+        } else if let Some(edns) = self.edns.as_mut() {
+            edns.set_rcode_high(0);
+        }
         self
     }
 
@@ -278,12 +299,22 @@ impl Message {
         self
     }
 
+    /// Adds the SIG(0) or TSIG record which signs the message, it is emitted last
     pub fn add_sig0(&mut self, record: Record) -> &mut Self {
-        assert_eq!(RecordType::SIG, record.get_rr_type());
+        assert!(record.get_rr_type() == RecordType::SIG ||
+                record.get_rr_type() == RecordType::TSIG);
         self.sig0.push(record);
         self
     }
 
+    /// Adds a TLV to a DSO message, the first one added is the primary TLV
+    ///
+    /// TLVs are only read from messages with the `OpCode::Dso`.
+    pub fn add_dso_tlv(&mut self, tlv: DsoTlv) -> &mut Self {
+        self.dso_tlvs.push(tlv);
+        self
+    }
+
     /// see `Header::get_id()`
     pub fn get_id(&self) -> u16 {
         self.header.get_id()
@@ -414,6 +445,29 @@ impl Message {
         self.edns.as_ref()
     }
 
+    /// Returns the TLVs of a DSO message, the first is the primary TLV
+    pub fn get_dso_tlvs(&self) -> &[DsoTlv] {
+        &self.dso_tlvs
+    }
+
+    /// Returns the client subnet of the EDNS options, RFC 7871, None if there is none or it is
+    ///  malformed
+    pub fn get_client_subnet(&self) -> Option<&ClientSubnet> {
+        match self.get_edns().and_then(|edns| edns.get_option(&EdnsCode::Subnet)) {
+            Some(&EdnsOption::ClientSubnet(ref subnet)) => Some(subnet),
+            _ => None,
+        }
+    }
+
+    /// Returns the Closest Trust Point of the CHAIN option, RFC 7901, None if there is none or it
+    ///  is malformed
+    pub fn get_chain_trust_point(&self) -> Option<&Name> {
+        match self.get_edns().and_then(|edns| edns.get_option(&EdnsCode::Chain)) {
+            Some(&EdnsOption::Chain(ref name)) => Some(name),
+            _ => None,
+        }
+    }
+
     /// If edns is_none, this will create a new default Edns.
     pub fn get_edns_mut(&mut self) -> &mut Edns {
         if self.edns.is_none() {
@@ -423,6 +477,11 @@ impl Message {
         self.edns.as_mut().unwrap()
     }
 
+    /// Removes the EDNS record, e.g. to send a query again to a server which does not support it
+    pub fn take_edns(&mut self) -> Option<Edns> {
+        self.edns.take()
+    }
+
     /// # Return value
     ///
     /// the max payload value as it's defined in the EDNS section.
@@ -507,7 +566,8 @@ impl Message {
                 records.push(record)
             } else {
                 match record.get_rr_type() {
-                    RecordType::SIG => {
+                    // SIG(0) and TSIG both sign the whole message
+                    RecordType::SIG | RecordType::TSIG => {
                         saw_sig0 = true;
                         sig0s.push(record);
                     }
@@ -551,6 +611,12 @@ impl Message {
         Message::read(&mut decoder)
     }
 
+    /// Decodes the message, returning an error if any of the limits are exceeded.
+    pub fn from_vec_with_limits(buffer: &[u8], limits: DecodeLimits) -> DecodeResult<Message> {
+        let mut decoder = BinDecoder::with_limits(buffer, limits);
+        Message::read(&mut decoder)
+    }
+
     pub fn to_vec(&self) -> Result<Vec<u8>, EncodeError> {
         let mut buffer = Vec::with_capacity(512);
         {
@@ -561,6 +627,171 @@ impl Message {
         Ok(buffer)
     }
 
+    /// The message as dig shows it, for display and logging
+    ///
+    /// The header with its flags and counts, the OPT pseudo-section if there is EDNS, each
+    ///  section which has records, in the presentation format of master files, and the size of
+    ///  the encoded message, with the time the query took and the server which answered, if they
+    ///  are given. The sections of an update are named after their use in RFC 2136.
+    ///
+    /// # Arguments
+    ///
+    /// * `query_time` - the time between sending the query and receiving this response
+    /// * `server` - the name server this response was received from
+    ///
+    /// ```
+    /// use trust_dns::op::{Message, MessageType, Query};
+    /// use trust_dns::rr::{Name, RecordType};
+    ///
+    /// let mut query = Query::new();
+    /// query.name(Name::parse("www.example.com.", None).unwrap()).query_type(RecordType::A);
+    /// let mut response = Message::new();
+    /// response.id(7).message_type(MessageType::Response).add_query(query);
+    ///
+    /// let dig = response.to_dig_string(None, None);
+    /// assert!(dig.starts_with(";; ->>HEADER<<- opcode: QUERY, status: NOERROR, id: 7\n"));
+    /// assert!(dig.contains(";; QUESTION SECTION:\n;www.example.com.\t\tIN\tA\n"));
+    /// ```
+    pub fn to_dig_string(&self,
+                         query_time: Option<Duration>,
+                         server: Option<SocketAddr>)
+                         -> String {
+        let header = self.update_header_counts(true);
+        let is_update = self.get_op_code() == OpCode::Update;
+        let op_code = match self.get_op_code() {
+            OpCode::Unknown(value) => format!("RESERVED{}", value),
+            op_code => format!("{:?}", op_code).to_uppercase(),
+        };
+
+        let flags = [(self.get_message_type() == MessageType::Response, "qr"),
+                     (self.is_authoritative(), "aa"),
+                     (self.is_truncated(), "tc"),
+                     (self.is_recursion_desired(), "rd"),
+                     (self.is_recursion_available(), "ra"),
+                     (self.is_authentic_data(), "ad"),
+                     (self.is_checking_disabled(), "cd")]
+            .iter()
+            .filter(|&&(is_set, _)| is_set)
+            .map(|&(_, flag)| flag)
+            .collect::<Vec<&str>>()
+            .join(" ");
+        let count_names = if is_update {
+            ["ZONE", "PREREQ", "UPDATE", "ADDITIONAL"]
+        } else {
+            ["QUERY", "ANSWER", "AUTHORITY", "ADDITIONAL"]
+        };
+
+        // writing to a String does not fail
+        let mut dig = String::new();
+        let _ = writeln!(dig,
+                         ";; ->>HEADER<<- opcode: {}, status: {}, id: {}",
+                         op_code,
+                         format!("{:?}", self.get_response_code()).to_uppercase(),
+                         self.get_id());
+        let _ = writeln!(dig,
+                         ";; flags: {}; {}: {}, {}: {}, {}: {}, {}: {}",
+                         flags,
+                         count_names[0],
+                         header.get_query_count(),
+                         count_names[1],
+                         header.get_answer_count(),
+                         count_names[2],
+                         header.get_name_server_count(),
+                         count_names[3],
+                         header.get_additional_count());
+
+        if let Some(ref edns) = self.edns {
+            let _ = writeln!(dig, "\n;; OPT PSEUDOSECTION:");
+            let _ = writeln!(dig,
+                             "; EDNS: version: {}, flags:{}; udp: {}",
+                             edns.get_version(),
+                             if edns.is_dnssec_ok() { " do" } else { "" },
+                             edns.get_max_payload());
+
+            let mut options: Vec<(&EdnsCode, &EdnsOption)> =
+                edns.get_options().get_options().iter().collect();
+            options.sort_by_key(|&(code, _)| u16::from(*code));
+            for (code, option) in options {
+                let _ = match *option {
+                    EdnsOption::DAU(algorithms) |
+                    EdnsOption::DHU(algorithms) |
+                    EdnsOption::N3U(algorithms) => {
+                        let algorithms = algorithms.iter()
+                            .map(|algorithm| algorithm.to_str())
+                            .collect::<Vec<&str>>()
+                            .join(" ");
+                        writeln!(dig, "; {:?}: {}", code, algorithms)
+                    }
+                    EdnsOption::ClientSubnet(ref subnet) => {
+                        writeln!(dig,
+                                 "; CLIENT-SUBNET: {}/{}/{}",
+                                 subnet.get_address(),
+                                 subnet.get_source_prefix(),
+                                 subnet.get_scope_prefix())
+                    }
+                    EdnsOption::Chain(ref name) => writeln!(dig, "; CHAIN: {}", name),
+                    EdnsOption::Unknown(code, ref data) => {
+                        let hex = data.iter().map(|b| format!("{:02X}", b)).collect::<String>();
+                        writeln!(dig, "; OPT={}: {}", code, hex)
+                    }
+                };
+            }
+        }
+
+        let additionals: Vec<&Record> = self.additionals.iter().chain(self.sig0.iter()).collect();
+        let sections = if is_update {
+            [("PREREQUISITE", self.answers.iter().collect::<Vec<&Record>>()),
+             ("UPDATE", self.name_servers.iter().collect()),
+             ("ADDITIONAL", additionals)]
+        } else {
+            [("ANSWER", self.answers.iter().collect::<Vec<&Record>>()),
+             ("AUTHORITY", self.name_servers.iter().collect()),
+             ("ADDITIONAL", additionals)]
+        };
+
+        if !self.queries.is_empty() {
+            let _ = writeln!(dig,
+                             "\n;; {} SECTION:",
+                             if is_update { "ZONE" } else { "QUESTION" });
+            for query in &self.queries {
+                let _ = writeln!(dig,
+                                 ";{}\t\t{}\t{}",
+                                 query.get_name(),
+                                 query.get_query_class(),
+                                 query.get_query_type());
+            }
+        }
+
+        for &(name, ref records) in sections.iter().filter(|&&(_, ref r)| !r.is_empty()) {
+            let _ = writeln!(dig, "\n;; {} SECTION:", name);
+            for record in records {
+                let _ = writeln!(dig,
+                                 "{}\t{}\t{}\t{}\t{}",
+                                 record.get_name(),
+                                 record.get_ttl(),
+                                 record.get_dns_class(),
+                                 record.get_rr_type(),
+                                 record.get_rdata());
+            }
+        }
+
+        let _ = writeln!(dig, "");
+        if let Some(query_time) = query_time {
+            let _ = writeln!(dig,
+                             ";; Query time: {} msec",
+                             query_time.as_secs() * 1000 +
+                             (query_time.subsec_nanos() / 1_000_000) as u64);
+        }
+        if let Some(server) = server {
+            let _ = writeln!(dig, ";; SERVER: {}#{}({})", server.ip(), server.port(), server.ip());
+        }
+        if let Ok(buffer) = self.to_vec() {
+            let _ = writeln!(dig, ";; MSG SIZE  rcvd: {}", buffer.len());
+        }
+
+        dig
+    }
+
     // TODO: where's the 'right' spot for this function
     #[cfg(feature = "openssl")]
     pub fn sign(&mut self, signer: &Signer, inception_time: u32) -> DnsSecResult<()> {
@@ -617,6 +848,47 @@ impl Message {
     pub fn sign(&mut self, _: &Signer, _: u32) -> DnsSecResult<()> {
         Err(DnsSecErrorKind::Message("openssl feature not enabled").into())
     }
+
+    /// Signs the message with TSIG, RFC 8945, once it is otherwise complete
+    ///
+    /// The id of the message is its original id in the TSIG record, a client which assigns its
+    ///  own ids, as `ClientFuture` does, changes it after signing; the receiver verifies the MAC
+    ///  with the original id, section 4.3.2.
+    ///
+    /// # Arguments
+    ///
+    /// * `tsigner` - the signer with the secret shared with the receiver
+    /// * `chain` - the MAC which this one follows, if the message is a response
+    /// * `time_signed` - seconds since the epoch, UTC
+    ///
+    /// # Return
+    ///
+    /// The MAC of the message, which the MAC of the response, or of the next message, follows.
+    pub fn sign_tsig(&mut self,
+                     tsigner: &TSigner,
+                     chain: TsigChain,
+                     time_signed: u64)
+                     -> DnsSecResult<Vec<u8>> {
+        debug!("signing message with tsig: {:?}", self);
+        let mac: Vec<u8> = try!(tsigner.sign_message(self, chain, time_signed));
+
+        let mut tsig = Record::new();
+        tsig.name(tsigner.get_key_name().clone());
+        tsig.rr_type(RecordType::TSIG);
+        // the class MUST be ANY and the TTL MUST be 0, section 4.2
+        tsig.dns_class(DNSClass::ANY);
+        tsig.ttl(0);
+        tsig.rdata(RData::TSIG(TSIG::new(tsigner.get_algorithm().to_name(),
+                                         time_signed,
+                                         tsigner.get_fudge(),
+                                         mac.clone(),
+                                         self.get_id(),
+                                         0,
+                                         vec![])));
+
+        self.add_sig0(tsig);
+        Ok(mac)
+    }
 }
 
 /// to reduce errors in using the Message struct as an Update, this will do the call throughs
@@ -714,8 +986,23 @@ impl UpdateMessage for Message {
 
 impl BinSerializable<Message> for Message {
     fn read(decoder: &mut BinDecoder) -> DecodeResult<Self> {
+        let max_message_size = decoder.limits().max_message_size;
+        if decoder.len() > max_message_size {
+            return Err(DecodeErrorKind::MessageTooLong(decoder.len(), max_message_size).into());
+        }
+
         let header = try!(Header::read(decoder));
 
+        // verify the claimed counts before allocating anything for them
+        let max_records = decoder.limits().max_records;
+        let record_count = header.get_query_count() as usize +
+                           header.get_answer_count() as usize +
+                           header.get_name_server_count() as usize +
+                           header.get_additional_count() as usize;
+        if record_count > max_records {
+            return Err(DecodeErrorKind::TooManyRecords(record_count, max_records).into());
+        }
+
         // TODO/FIXME: return just header, and in the case of the rest of message getting an error.
         //  this could improve error detection while decoding.
 
@@ -735,6 +1022,14 @@ impl BinSerializable<Message> for Message {
         let (name_servers, _, _) = try!(Self::read_records(decoder, name_server_count, false));
         let (additionals, edns, sig0) = try!(Self::read_records(decoder, additional_count, true));
 
+        // DSO messages have TLVs through to the end of the message
+        let mut dso_tlvs = Vec::new();
+        if header.get_op_code() == OpCode::Dso {
+            while decoder.len() > 0 {
+                dso_tlvs.push(try!(DsoTlv::read(decoder)));
+            }
+        }
+
         Ok(Message {
             header: header,
             queries: queries,
@@ -743,6 +1038,7 @@ impl BinSerializable<Message> for Message {
             additionals: additionals,
             sig0: sig0,
             edns: edns,
+            dso_tlvs: dso_tlvs,
         })
     }
 
@@ -773,6 +1069,10 @@ impl BinSerializable<Message> for Message {
         if include_sig0 {
             try!(Self::emit_records(encoder, &self.sig0));
         }
+
+        for tlv in &self.dso_tlvs {
+            try!(tlv.emit(encoder));
+        }
         Ok(())
     }
 }
@@ -882,3 +1182,170 @@ fn test_legit_message() {
 
     assert_eq!(message.get_id(), 4096);
 }
+
+#[test]
+fn test_decode_limits() {
+    let mut message = Message::new();
+    message.id(10)
+        .message_type(MessageType::Response)
+        .add_answer(Record::new())
+        .add_answer(Record::new())
+        .add_name_server(Record::new());
+
+    let buf = message.to_vec().unwrap();
+
+    // no limits
+    assert!(Message::from_vec_with_limits(&buf, DecodeLimits::default()).is_ok());
+
+    let mut limits = DecodeLimits::default();
+    limits.max_records = 2;
+    assert!(Message::from_vec_with_limits(&buf, limits).is_err());
+
+    let mut limits = DecodeLimits::default();
+    limits.max_names = 2;
+    assert!(Message::from_vec_with_limits(&buf, limits).is_err());
+
+    let mut limits = DecodeLimits::default();
+    limits.max_message_size = buf.len() - 1;
+    assert!(Message::from_vec_with_limits(&buf, limits).is_err());
+}
+
+#[test]
+fn test_pointer_loop() {
+    let buf: Vec<u8> = vec![
+  0x10,0x00,0x01,0x00, // id = 4096, query, recursion_desired
+  0x00,0x01,0x00,0x00, // 1 query, 0 answer,
+  0x00,0x00,0x00,0x00, // 0 namesservers, 0 additional record
+
+  0xC0,0x0C,           // name pointer to itself
+  0x00,0x01,0x00,0x01, // ReordType = A, Class = IN
+  ];
+
+    assert!(Message::from_vec(&buf).is_err());
+}
+
+#[test]
+fn test_pointer_loop_labels() {
+    let buf: Vec<u8> = vec![
+  0x10,0x00,0x01,0x00, // id = 4096, query, recursion_desired
+  0x00,0x01,0x00,0x00, // 1 query, 0 answer,
+  0x00,0x00,0x00,0x00, // 0 namesservers, 0 additional record
+
+  0x01,b'a',0xC0,0x0C, // a label, then a pointer back to it
+  0x00,0x01,0x00,0x01, // ReordType = A, Class = IN
+  ];
+
+    assert!(Message::from_vec(&buf).is_err());
+
+    // two hops, the label at 12 hides the name at 14, whose label hides the pointer to it
+    let buf: Vec<u8> = vec![
+  0x10,0x00,0x01,0x00, // id = 4096, query, recursion_desired
+  0x00,0x01,0x00,0x00, // 1 query, 0 answer,
+  0x00,0x00,0x00,0x00, // 0 namesservers, 0 additional record
+
+  0x02,b'a',0x02,      // a label, its second octet starts the name at 14
+  0xC0,0x0E,           // name pointer to 14
+  0xC0,0x0C,           // name pointer to 12, after the label of 14
+  0x00,0x01,0x00,0x01, // ReordType = A, Class = IN
+  ];
+
+    assert!(Message::from_vec(&buf).is_err());
+}
+
+#[test]
+fn test_extended_response_code() {
+    let mut message = Message::new();
+    message.id(10)
+        .message_type(MessageType::Response)
+        .response_code(ResponseCode::BADVERS);
+
+    assert_eq!(message.get_response_code(), ResponseCode::BADVERS);
+    assert_eq!(message.get_edns().expect("edns not created").get_rcode_high(), 1);
+
+    message.update_counts();
+    test_emit_and_read(message);
+}
+
+#[test]
+fn test_emit_and_read_dso() {
+    let mut message = Message::new();
+    message.id(10)
+        .op_code(OpCode::Dso)
+        .add_dso_tlv(DsoTlv::KeepAlive {
+            inactivity_timeout: 15000,
+            keepalive_interval: 3600000,
+        })
+        .add_dso_tlv(DsoTlv::EncryptionPadding(vec![0; 8]));
+
+    test_emit_and_read(message);
+
+    // the TLVs are only read for DSO messages
+    let mut message = Message::new();
+    message.id(10).add_dso_tlv(DsoTlv::RetryDelay(5000));
+    let buf = message.to_vec().unwrap();
+    assert!(Message::from_vec(&buf).expect("read failed").get_dso_tlvs().is_empty());
+}
+
+#[test]
+fn test_to_dig_string() {
+    use std::net::Ipv4Addr;
+    use rr::{Name, RData};
+
+    let name = Name::parse("www.example.com.", None).unwrap();
+    let mut query = Query::new();
+    query.name(name.clone()).query_type(RecordType::A);
+
+    let mut edns = Edns::new();
+    edns.set_max_payload(4096);
+    edns.set_dnssec_ok(true);
+    edns.set_option(EdnsOption::ClientSubnet(ClientSubnet::new("192.0.2.0".parse().unwrap(),
+                                                                24)));
+
+    let mut message = Message::new();
+    message.id(1234)
+        .message_type(MessageType::Response)
+        .authoritative(true)
+        .recursion_desired(true)
+        .add_query(query)
+        .add_answer(Record::from_rdata(name,
+                                       300,
+                                       RecordType::A,
+                                       RData::A(Ipv4Addr::new(192, 0, 2, 1))))
+        .set_edns(edns);
+
+    let size = message.to_vec().unwrap().len();
+    let dig = message.to_dig_string(Some(Duration::from_millis(12)),
+                                    Some("192.0.2.53:53".parse().unwrap()));
+    let expected = format!(";; ->>HEADER<<- opcode: QUERY, status: NOERROR, id: 1234\n\
+                            ;; flags: qr aa rd; QUERY: 1, ANSWER: 1, AUTHORITY: 0, ADDITIONAL: 1\n\
+                            \n\
+                            ;; OPT PSEUDOSECTION:\n\
+                            ; EDNS: version: 0, flags: do; udp: 4096\n\
+                            ; CLIENT-SUBNET: 192.0.2.0/24/0\n\
+                            \n\
+                            ;; QUESTION SECTION:\n\
+                            ;www.example.com.\t\tIN\tA\n\
+                            \n\
+                            ;; ANSWER SECTION:\n\
+                            www.example.com.\t300\tIN\tA\t192.0.2.1\n\
+                            \n\
+                            ;; Query time: 12 msec\n\
+                            ;; SERVER: 192.0.2.53#53(192.0.2.53)\n\
+                            ;; MSG SIZE  rcvd: {}\n",
+                           size);
+    assert_eq!(dig, expected);
+
+    // the sections of an update
+    let mut zone = Query::new();
+    zone.name(Name::parse("example.com.", None).unwrap()).query_type(RecordType::SOA);
+    let mut update = Message::new();
+    update.op_code(OpCode::Update).add_query(zone);
+    update.add_name_server(Record::with(Name::parse("new.example.com.", None).unwrap(),
+                                        RecordType::A,
+                                        0));
+    let dig = update.to_dig_string(None, None);
+    assert!(dig.contains("; ZONE: 1, PREREQ: 0, UPDATE: 1, ADDITIONAL: 0\n"));
+    assert!(dig.contains(";; ZONE SECTION:\n;example.com.\t\tIN\tSOA\n"));
+    assert!(dig.contains(";; UPDATE SECTION:\nnew.example.com.\t0\tIN\tA\t"));
+    assert!(!dig.contains("Query time"));
+}
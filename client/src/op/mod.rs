@@ -17,19 +17,25 @@
 //! Operations to send with a `Client` or server, e.g. `Query`, `Message`, or `UpdateMessage` can
 //! be used to gether to either query or update resource records sets.
 
+pub mod dso;
 mod edns;
 pub mod header;
+mod ixfr;
 pub mod message;
 pub mod op_code;
+mod proxy_message;
 pub mod query;
 pub mod request_handler;
 pub mod response_code;
 
+pub use self::dso::{DsoTlv, DsoType};
 pub use self::edns::Edns;
 pub use self::header::Header;
 pub use self::header::MessageType;
+pub use self::ixfr::{IxfrResponse, ZoneDelta};
 pub use self::message::{Message, UpdateMessage};
 pub use self::op_code::OpCode;
+pub use self::proxy_message::ProxyMessage;
 pub use self::query::Query;
 pub use self::request_handler::RequestHandler;
 pub use self::response_code::ResponseCode;
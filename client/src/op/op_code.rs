@@ -37,7 +37,7 @@ use ::error::*;
 ///
 ///                 3-15            reserved for future use
 /// ```
-#[derive(Debug, PartialEq, PartialOrd, Copy, Clone)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Hash, Copy, Clone)]
 #[allow(dead_code)]
 pub enum OpCode {
     /// Query request [RFC 1035](https://tools.ietf.org/html/rfc1035)
@@ -51,6 +51,13 @@ pub enum OpCode {
 
     /// Update message [RFC 2136](https://tools.ietf.org/html/rfc2136)
     Update,
+
+    /// DNS Stateful Operations [RFC 8490](https://tools.ietf.org/html/rfc8490)
+    Dso,
+
+    /// Any other value, e.g. the obsolete IQUERY or an op code assigned after this was written,
+    ///  these are decoded so that servers can answer them with NOTIMP
+    Unknown(u8),
 }
 
 /// Convert from OpCode to u8
@@ -74,7 +81,9 @@ impl From<OpCode> for u8 {
             // 3	Unassigned
             OpCode::Notify => 4,
             OpCode::Update => 5,
-            // 6-15	Unassigned
+            OpCode::Dso => 6,
+            // 7-15	Unassigned
+            OpCode::Unknown(value) => value,
         }
     }
 }
@@ -87,6 +96,9 @@ impl From<OpCode> for u8 {
 ///
 /// let var: OpCode = OpCode::from_u8(0).unwrap();
 /// assert_eq!(OpCode::Query, var);
+///
+/// let var: OpCode = OpCode::from_u8(7).unwrap();
+/// assert_eq!(OpCode::Unknown(7), var);
 /// ```
 impl OpCode {
     pub fn from_u8(value: u8) -> DecodeResult<Self> {
@@ -95,6 +107,9 @@ impl OpCode {
             2 => Ok(OpCode::Status),
             4 => Ok(OpCode::Notify),
             5 => Ok(OpCode::Update),
+            6 => Ok(OpCode::Dso),
+            // the op code is four bits
+            1 | 3 | 7...15 => Ok(OpCode::Unknown(value)),
             _ => Err(DecodeErrorKind::Msg(format!("unknown OpCode: {}", value)).into()),
         }
     }
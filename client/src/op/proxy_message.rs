@@ -0,0 +1,318 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! A received message which can be forwarded with minimal rewriting
+
+use std::cmp;
+
+use ::error::*;
+use op::Message;
+use rr::{Name, RecordType};
+use rr::rdata::opt::{EdnsCode, EdnsOption};
+use serialize::binary::{BinDecoder, BinSerializable};
+
+/// Length of the message header
+const HEADER_LEN: usize = 12;
+
+/// A message kept as the bytes it was received as, for proxying
+///
+/// Decoding into a `Message` and encoding it again normalizes it: names are compressed
+///  differently, records are reordered and unknown flags may be lost. A proxy should forward what
+///  it received instead. Only the query id and the EDNS options can be changed here, everything
+///  else, including record types and flags which are not understood, is passed on byte for byte.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProxyMessage {
+    buffer: Vec<u8>,
+    /// offset of the RDLENGTH of the OPT record, if any
+    opt: Option<usize>,
+}
+
+impl ProxyMessage {
+    /// Checks the structure of the message, without decoding the records
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - the message as received
+    pub fn from_vec(buffer: Vec<u8>) -> DecodeResult<Self> {
+        let opt = try!(find_opt(&buffer));
+
+        Ok(ProxyMessage {
+            buffer: buffer,
+            opt: opt,
+        })
+    }
+
+    /// Decodes the message, e.g. to decide where to forward it
+    pub fn to_message(&self) -> DecodeResult<Message> {
+        Message::from_vec(&self.buffer)
+    }
+
+    /// The message as it will be forwarded
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buffer
+    }
+
+    /// Returns the message as it will be forwarded
+    pub fn into_vec(self) -> Vec<u8> {
+        self.buffer
+    }
+
+    pub fn get_id(&self) -> u16 {
+        read_u16(&self.buffer, 0)
+    }
+
+    /// Replaces the query id, e.g. with the one of the original request
+    pub fn set_id(&mut self, id: u16) {
+        write_u16(&mut self.buffer, 0, id);
+    }
+
+    /// Returns true if the message has an OPT record, i.e. uses EDNS
+    pub fn has_edns(&self) -> bool {
+        self.opt.is_some()
+    }
+
+    /// Removes every EDNS option with the code, the other options keep their order
+    ///
+    /// # Return value
+    ///
+    /// The number of options that were removed
+    pub fn remove_edns_option(&mut self, code: EdnsCode) -> usize {
+        let rdlength_at = match self.opt {
+            Some(rdlength_at) => rdlength_at,
+            None => return 0,
+        };
+
+        let rdata_at = rdlength_at + 2;
+        let end = rdata_at + read_u16(&self.buffer, rdlength_at) as usize;
+
+        let mut rdata = Vec::with_capacity(end - rdata_at);
+        let mut removed = 0;
+        let mut i = rdata_at;
+        while i + 4 <= end {
+            let next = cmp::min(i + 4 + read_u16(&self.buffer, i + 2) as usize, end);
+
+            if EdnsCode::from(read_u16(&self.buffer, i)) == code {
+                removed += 1;
+            } else {
+                rdata.extend_from_slice(&self.buffer[i..next]);
+            }
+            i = next;
+        }
+
+        if removed > 0 {
+            self.replace_opt_rdata(rdlength_at, rdata);
+        }
+        removed
+    }
+
+    /// Appends the EDNS option after the existing ones
+    ///
+    /// # Return value
+    ///
+    /// False if the message has no OPT record, EDNS is not added to a message which did not use
+    ///  it, or if the OPT record would become too long.
+    pub fn add_edns_option(&mut self, option: &EdnsOption) -> bool {
+        let rdlength_at = match self.opt {
+            Some(rdlength_at) => rdlength_at,
+            None => return false,
+        };
+
+        let rdata_at = rdlength_at + 2;
+        let end = rdata_at + read_u16(&self.buffer, rdlength_at) as usize;
+
+        let data: Vec<u8> = option.into();
+        if (end - rdata_at) + 4 + data.len() > u16::max_value() as usize {
+            return false;
+        }
+
+        let mut rdata = self.buffer[rdata_at..end].to_vec();
+        let mut header = [0_u8; 4];
+        write_u16(&mut header, 0, EdnsCode::from(option).into());
+        write_u16(&mut header, 2, data.len() as u16);
+        rdata.extend_from_slice(&header);
+        rdata.extend_from_slice(&data);
+
+        self.replace_opt_rdata(rdlength_at, rdata);
+        true
+    }
+
+    fn replace_opt_rdata(&mut self, rdlength_at: usize, rdata: Vec<u8>) {
+        let rdata_at = rdlength_at + 2;
+        let end = rdata_at + read_u16(&self.buffer, rdlength_at) as usize;
+
+        let mut buffer = Vec::with_capacity(self.buffer.len() - (end - rdata_at) + rdata.len());
+        buffer.extend_from_slice(&self.buffer[..rdata_at]);
+        buffer.extend_from_slice(&rdata);
+        buffer.extend_from_slice(&self.buffer[end..]);
+        write_u16(&mut buffer, rdlength_at, rdata.len() as u16);
+
+        self.buffer = buffer;
+    }
+}
+
+fn read_u16(buffer: &[u8], at: usize) -> u16 {
+    ((buffer[at] as u16) << 8) | buffer[at + 1] as u16
+}
+
+fn write_u16(buffer: &mut [u8], at: usize, value: u16) {
+    buffer[at] = (value >> 8) as u8;
+    buffer[at + 1] = value as u8;
+}
+
+/// Walks the message, checking that every record is within the buffer, and returns the offset of
+///  the RDLENGTH of the OPT record
+fn find_opt(buffer: &[u8]) -> DecodeResult<Option<usize>> {
+    if buffer.len() < HEADER_LEN {
+        return Err(DecodeErrorKind::Message("message is shorter than the header").into());
+    }
+
+    let mut decoder = BinDecoder::new(buffer);
+    try!(decoder.read_vec(4)); // id and flags
+    let query_count = try!(decoder.read_u16()) as usize;
+    let answer_count = try!(decoder.read_u16()) as usize;
+    let name_server_count = try!(decoder.read_u16()) as usize;
+    let additional_count = try!(decoder.read_u16()) as usize;
+
+    for _ in 0..query_count {
+        try!(Name::read(&mut decoder));
+        try!(decoder.read_vec(4)); // type and class
+    }
+
+    let mut opt = None;
+    for i in 0..(answer_count + name_server_count + additional_count) {
+        try!(Name::read(&mut decoder));
+        let rr_type = try!(decoder.read_u16());
+        try!(decoder.read_vec(6)); // class and ttl
+        let rdlength_at = decoder.index();
+        let rdlength = try!(decoder.read_u16());
+        let rdata = try!(decoder.read_vec(rdlength as usize));
+
+        if rr_type != u16::from(RecordType::OPT) || i < answer_count + name_server_count {
+            continue;
+        }
+
+        if opt.is_some() {
+            return Err(DecodeErrorKind::Message("more than one OPT record").into());
+        }
+
+        // the options must fill the rdata exactly, so that they can be edited
+        let mut j = 0;
+        while j + 4 <= rdata.len() {
+            j += 4 + read_u16(&rdata, j + 2) as usize;
+        }
+        if j != rdata.len() {
+            return Err(DecodeErrorKind::IncorrectRDataLengthRead(j, rdata.len()).into());
+        }
+
+        opt = Some(rdlength_at);
+    }
+
+    Ok(opt)
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::Ipv4Addr;
+
+    use op::*;
+    use rr::*;
+    use rr::rdata::opt::{EdnsCode, EdnsOption};
+    use super::*;
+
+    fn response(edns: bool) -> Vec<u8> {
+        let mut message = Message::new();
+        message.id(10)
+            .message_type(MessageType::Response)
+            .op_code(OpCode::Query);
+        message.add_answer(Record::from_rdata(Name::parse("www.example.com.", None).unwrap(),
+                                              86400,
+                                              RecordType::A,
+                                              RData::A(Ipv4Addr::new(93, 184, 216, 34))));
+
+        if edns {
+            let edns = message.get_edns_mut();
+            edns.set_max_payload(1232);
+            edns.set_option(EdnsOption::Unknown(65001, vec![1, 2, 3]));
+        }
+
+        message.to_vec().unwrap()
+    }
+
+    #[test]
+    fn test_set_id() {
+        let bytes = response(true);
+        let mut message = ProxyMessage::from_vec(bytes.clone()).unwrap();
+
+        message.set_id(0xF00F);
+        assert_eq!(message.get_id(), 0xF00F);
+        assert_eq!(&message.as_bytes()[2..], &bytes[2..]);
+        assert_eq!(message.to_message().unwrap().get_id(), 0xF00F);
+    }
+
+    #[test]
+    fn test_edns_options() {
+        let bytes = response(true);
+        let mut message = ProxyMessage::from_vec(bytes.clone()).unwrap();
+        assert!(message.has_edns());
+
+        assert_eq!(message.remove_edns_option(EdnsCode::Cookie), 0);
+        assert_eq!(message.as_bytes(), &bytes[..]);
+
+        assert!(message.add_edns_option(&EdnsOption::Unknown(u16::from(EdnsCode::Padding),
+                                                              vec![0; 8])));
+        assert_eq!(message.as_bytes().len(), bytes.len() + 12);
+        {
+            let decoded = message.to_message().unwrap();
+            let edns = decoded.get_edns().expect("edns missing");
+            assert_eq!(edns.get_max_payload(), 1232);
+            assert!(edns.get_option(&EdnsCode::Padding).is_some());
+            assert!(edns.get_option(&EdnsCode::Unknown(65001)).is_some());
+        }
+
+        // back to the original
+        assert_eq!(message.remove_edns_option(EdnsCode::Padding), 1);
+        assert_eq!(message.as_bytes(), &bytes[..]);
+
+        assert_eq!(message.remove_edns_option(EdnsCode::Unknown(65001)), 1);
+        let decoded = message.to_message().unwrap();
+        assert!(decoded.get_edns().expect("edns missing").get_options().get_options().is_empty());
+        assert_eq!(decoded.get_answers().len(), 1);
+    }
+
+    #[test]
+    fn test_no_edns() {
+        let bytes = response(false);
+        let mut message = ProxyMessage::from_vec(bytes.clone()).unwrap();
+
+        assert!(!message.has_edns());
+        assert!(!message.add_edns_option(&EdnsOption::Unknown(65001, vec![])));
+        assert_eq!(message.into_vec(), bytes);
+    }
+
+    #[test]
+    fn test_unknown_type_preserved() {
+        let mut bytes = response(true);
+        // the answer follows the header, a compressed name can't be at the start, so this is the
+        //  full name: 3www7example3com0, then the type
+        let type_at = 12 + 17;
+        assert_eq!(&bytes[type_at..type_at + 2], &[0, 1]);
+        bytes[type_at] = 0xFF;
+        bytes[type_at + 1] = 0x00;
+
+        let mut message = ProxyMessage::from_vec(bytes.clone()).unwrap();
+        message.set_id(10);
+        assert_eq!(message.into_vec(), bytes);
+    }
+
+    #[test]
+    fn test_truncated() {
+        let bytes = response(true);
+
+        assert!(ProxyMessage::from_vec(bytes[..bytes.len() - 1].to_vec()).is_err());
+        assert!(ProxyMessage::from_vec(bytes[..4].to_vec()).is_err());
+    }
+}
@@ -16,12 +16,18 @@
 
 //! Query struct for looking up resource records
 
+use std::fmt;
+use std::str::FromStr;
+
 use rr::domain::Name;
 use rr::record_type::RecordType;
 use rr::dns_class::DNSClass;
 use ::serialize::binary::*;
 use ::error::*;
 
+/// the top bit of the QCLASS in mDNS queries
+const MDNS_UNICAST_RESPONSE: u16 = 0x8000;
+
 /// Query struct for looking up resource records, basically a resource record without RDATA.
 ///
 /// [RFC 1035, DOMAIN NAMES - IMPLEMENTATION AND SPECIFICATION, November 1987](https://tools.ietf.org/html/rfc1035)
@@ -51,6 +57,7 @@ pub struct Query {
     name: Name,
     query_type: RecordType,
     query_class: DNSClass,
+    mdns_unicast_response: bool,
 }
 
 impl Query {
@@ -60,6 +67,7 @@ impl Query {
             name: Name::new(),
             query_type: RecordType::A,
             query_class: DNSClass::IN,
+            mdns_unicast_response: false,
         }
     }
 
@@ -77,6 +85,13 @@ impl Query {
         self
     }
 
+    /// requests a unicast response to an mDNS query, the top bit of the QCLASS, see
+    ///  `is_mdns_unicast_response()`
+    pub fn mdns_unicast_response(&mut self, mdns_unicast_response: bool) -> &mut Self {
+        self.mdns_unicast_response = mdns_unicast_response;
+        self
+    }
+
     /// ```text
     /// QNAME           a domain name represented as a sequence of labels, where
     ///                 each label consists of a length octet followed by that
@@ -106,36 +121,100 @@ impl Query {
     pub fn get_query_class(&self) -> DNSClass {
         self.query_class
     }
+
+    /// [RFC 6762](https://tools.ietf.org/html/rfc6762#section-18.12), Multicast DNS, February 2013
+    ///
+    /// ```text
+    /// 18.12.  Repurposing of Top Bit of qclass in Question Section
+    ///
+    ///    In the Question Section of a Multicast DNS query, the top bit of the
+    ///    qclass field is used to indicate that unicast responses are preferred
+    ///    for this particular question.  (See Section 5.4.)
+    /// ```
+    ///
+    /// The bit is stripped from the QCLASS when decoding any message.
+    pub fn is_mdns_unicast_response(&self) -> bool {
+        self.mdns_unicast_response
+    }
 }
 
 impl BinSerializable<Query> for Query {
     fn read(decoder: &mut BinDecoder) -> DecodeResult<Self> {
         let name = try!(Name::read(decoder));
         let query_type = try!(RecordType::read(decoder));
-        let query_class = try!(DNSClass::read(decoder));
+        let query_class = try!(decoder.read_u16());
 
         Ok(Query {
             name: name,
             query_type: query_type,
-            query_class: query_class,
+            query_class: try!(DNSClass::from_u16(query_class & !MDNS_UNICAST_RESPONSE)),
+            mdns_unicast_response: query_class & MDNS_UNICAST_RESPONSE != 0,
         })
     }
 
     fn emit(&self, encoder: &mut BinEncoder) -> EncodeResult {
         try!(self.name.emit(encoder));
         try!(self.query_type.emit(encoder));
-        try!(self.query_class.emit(encoder));
+        let query_class: u16 = self.query_class.into();
+        if self.mdns_unicast_response {
+            try!(encoder.emit_u16(query_class | MDNS_UNICAST_RESPONSE));
+        } else {
+            try!(encoder.emit_u16(query_class));
+        }
 
         Ok(())
     }
 }
 
+/// The question as dig shows it, e.g. `www.example.com. IN A`
+impl fmt::Display for Query {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {} {}", self.name, self.query_class, self.query_type)
+    }
+}
+
+/// Parses a name, then the type with an optional class on either side, e.g. `example.com. MX`
+///
+/// The name is taken as fully qualified and the class is IN by default.
+impl FromStr for Query {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> ParseResult<Self> {
+        let mut fields = s.split_whitespace();
+        let name = match fields.next() {
+            Some(name) => try!(Name::parse(name, Some(&Name::root()))),
+            None => return Err(ParseErrorKind::MissingToken("name".to_string()).into()),
+        };
+
+        let fields: Vec<&str> = fields.collect();
+        let (query_class, query_type) = match fields.len() {
+            0 => return Err(ParseErrorKind::MissingToken("type".to_string()).into()),
+            1 => (DNSClass::IN, try!(RecordType::from_str(fields[0]))),
+            2 => {
+                match DNSClass::from_str(fields[0]) {
+                    Ok(query_class) => (query_class, try!(RecordType::from_str(fields[1]))),
+                    Err(_) => {
+                        (try!(DNSClass::from_str(fields[1])),
+                         try!(RecordType::from_str(fields[0])))
+                    }
+                }
+            }
+            _ => return Err(ParseErrorKind::Message("unexpected field after the type").into()),
+        };
+
+        let mut query = Query::new();
+        query.name(name).query_type(query_type).query_class(query_class);
+        Ok(query)
+    }
+}
+
 #[test]
 fn test_read_and_emit() {
     let expect = Query {
         name: Name::with_labels(vec!["WWW".to_string(), "example".to_string(), "com".to_string()]),
         query_type: RecordType::AAAA,
         query_class: DNSClass::IN,
+        mdns_unicast_response: false,
     };
 
     let mut byte_vec: Vec<u8> = Vec::with_capacity(512);
@@ -148,3 +227,42 @@ fn test_read_and_emit() {
     let got = Query::read(&mut decoder).unwrap();
     assert_eq!(got, expect);
 }
+
+#[test]
+fn test_mdns_unicast_response() {
+    let mut expect = Query::new();
+    expect.name(Name::parse("printer.local.", None).unwrap())
+        .query_type(RecordType::ANY)
+        .mdns_unicast_response(true);
+
+    let mut byte_vec: Vec<u8> = Vec::with_capacity(512);
+    {
+        let mut encoder = BinEncoder::new(&mut byte_vec);
+        expect.emit(&mut encoder).unwrap();
+    }
+    assert_eq!(&byte_vec[byte_vec.len() - 2..], &[0x80, 0x01]);
+
+    let mut decoder = BinDecoder::new(&byte_vec);
+    let got = Query::read(&mut decoder).unwrap();
+    assert_eq!(got.get_query_class(), DNSClass::IN);
+    assert!(got.is_mdns_unicast_response());
+    assert_eq!(got, expect);
+}
+
+#[test]
+fn test_from_str() {
+    let query: Query = "www.example.com. AAAA".parse().unwrap();
+    assert_eq!(query.get_name(), &Name::parse("www.example.com.", None).unwrap());
+    assert_eq!(query.get_query_type(), RecordType::AAAA);
+    assert_eq!(query.get_query_class(), DNSClass::IN);
+    assert_eq!(query.to_string(), "www.example.com. IN AAAA");
+
+    let query: Query = "example.com mx ch".parse().unwrap();
+    assert_eq!(query.to_string(), "example.com. CH MX");
+
+    // ANY alone is the type
+    assert_eq!("example.com. ANY".parse::<Query>().unwrap().get_query_type(),
+               RecordType::ANY);
+    assert!("example.com.".parse::<Query>().is_err());
+    assert!("example.com. A A".parse::<Query>().is_err());
+}
@@ -92,6 +92,9 @@ pub enum ResponseCode {
     /// Name not contained in zone [RFC 2136](https://tools.ietf.org/html/rfc2136)
     NotZone,
 
+    /// DSO-TYPE Not Implemented [RFC 8490](https://tools.ietf.org/html/rfc8490#section-10.2)
+    DSOTYPENI,
+
     /// Bad OPT Version [RFC 6891](https://tools.ietf.org/html/rfc6891#section-9)
     BADVERS,
 
@@ -130,9 +133,9 @@ impl ResponseCode {
         (u16::from(*self) & 0x000F) as u8
     }
 
-    /// returns the high 12 bits for the edns portion of the response code
-    pub fn high(&self) {
-        (u16::from(*self) & 0x0FF0) >> 4;
+    /// returns the high 8 bits for the edns portion of the response code
+    pub fn high(&self) -> u8 {
+        ((u16::from(*self) & 0x0FF0) >> 4) as u8
     }
 
     pub fn from(high: u8, low: u8) -> ResponseCode {
@@ -152,6 +155,7 @@ impl ResponseCode {
             ResponseCode::NXRRSet => "RR Set does not exist",  // 8	  NXRRSet	RR Set that should exist does not	[RFC2136]
             ResponseCode::NotAuth => "Not authorized",  // 9	  NotAuth	Server Not Authoritative for zone	[RFC2136]
             ResponseCode::NotZone => "Name not in zone", // 10	NotZone	Name not contained in zone	[RFC2136]
+            ResponseCode::DSOTYPENI => "DSO-TYPE not implemented", // 11	DSOTYPENI	DSO-TYPE Not Implemented	[RFC8490]
             ResponseCode::BADVERS => "Bad option verions", // 16	BADVERS	Bad OPT Version	[RFC6891]
             ResponseCode::BADSIG => "TSIG Failure", // 16	BADSIG	TSIG Signature Failure	[RFC2845]
             ResponseCode::BADKEY => "Key not recognized", // 17	BADKEY	Key not recognized	[RFC2845]
@@ -193,7 +197,8 @@ impl From<ResponseCode> for u16 {
             ResponseCode::NXRRSet => 8,  // 8	  NXRRSet	RR Set that should exist does not	[RFC2136]
             ResponseCode::NotAuth => 9,  // 9	  NotAuth	Server Not Authoritative for zone	[RFC2136]
             ResponseCode::NotZone => 10, // 10	NotZone	Name not contained in zone	[RFC2136]
-            // 11-15	Unassigned
+            ResponseCode::DSOTYPENI => 11, // 11	DSOTYPENI	DSO-TYPE Not Implemented	[RFC8490]
+            // 12-15	Unassigned
             ResponseCode::BADVERS => 16, // 16	BADVERS	Bad OPT Version	[RFC6891]
             ResponseCode::BADSIG => 16, // 16	BADSIG	TSIG Signature Failure	[RFC2845]
             ResponseCode::BADKEY => 17, // 17	BADKEY	Key not recognized	[RFC2845]
@@ -235,9 +240,11 @@ impl From<u16> for ResponseCode {
             8 => ResponseCode::NXRRSet,   // 8	NXRRSet	RR Set that should exist does not	[RFC2136]
             9 => ResponseCode::NotAuth,   // 9	NotAuth	Server Not Authoritative for zone	[RFC2136]
             10 => ResponseCode::NotZone,   // 10	NotZone	Name not contained in zone	[RFC2136]
-            // this looks to be backwards compat for 4 bit ResponseCodes.
-            //16 => ResponseCode::BADVERS,   // 16	BADVERS	Bad OPT Version	[RFC6891]
-            16 => ResponseCode::BADSIG,    // 16	BADSIG	TSIG Signature Failure	[RFC2845]
+            11 => ResponseCode::DSOTYPENI, // 11	DSOTYPENI	DSO-TYPE Not Implemented	[RFC8490]
+            // BADSIG shares 16, but is only ever carried in the TSIG error field, never as the
+            //  message rcode (which is the only place this conversion is used).
+            16 => ResponseCode::BADVERS,   // 16	BADVERS	Bad OPT Version	[RFC6891]
+            //16 => ResponseCode::BADSIG,    // 16	BADSIG	TSIG Signature Failure	[RFC2845]
             17 => ResponseCode::BADKEY,    // 17	BADKEY	Key not recognized	[RFC2845]
             18 => ResponseCode::BADTIME,   // 18	BADTIME	Signature out of time window	[RFC2845]
             19 => ResponseCode::BADMODE,   // 19	BADMODE	Bad TKEY Mode	[RFC2930]
@@ -0,0 +1,106 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use op::Message;
+use rr::{Name, Record};
+
+/// Returns true if the record is at or below the zone, i.e. the server for the zone may speak
+///  for it
+pub fn in_bailiwick(record: &Record, zone: &Name) -> bool {
+    zone.zone_of(record.get_name())
+}
+
+/// Removes every record outside of the zone of the server that sent the response
+///
+/// A server is only trusted for the names in the zone it was queried as a server for, anything
+///  else it sends, e.g. extra addresses in the additional section, is a way to poison a cache and
+///  must be neither cached nor followed.
+///
+/// # Arguments
+///
+/// * `response` - the response from a server for `zone`
+/// * `zone` - the zone the server was queried for, from the delegation that was followed
+///
+/// # Return value
+///
+/// The number of records that were removed
+pub fn strip_out_of_bailiwick(response: &mut Message, zone: &Name) -> usize {
+    let mut removed = 0;
+
+    let answers = retain(response.take_answers(), zone, &mut removed);
+    response.insert_answers(answers);
+
+    let name_servers = retain(response.take_name_servers(), zone, &mut removed);
+    response.insert_name_servers(name_servers);
+
+    let additionals = retain(response.take_additionals(), zone, &mut removed);
+    response.insert_additionals(additionals);
+
+    if removed > 0 {
+        debug!("removed {} records outside of zone: {}", removed, zone);
+    }
+
+    removed
+}
+
+fn retain(records: Vec<Record>, zone: &Name, removed: &mut usize) -> Vec<Record> {
+    let count = records.len();
+    let records: Vec<Record> = records.into_iter().filter(|r| in_bailiwick(r, zone)).collect();
+
+    *removed += count - records.len();
+    records
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::Ipv4Addr;
+
+    use op::*;
+    use rr::*;
+    use super::*;
+
+    fn a(name: &str) -> Record {
+        Record::from_rdata(Name::parse(name, None).unwrap(),
+                           86400,
+                           RecordType::A,
+                           RData::A(Ipv4Addr::new(192, 0, 2, 1)))
+    }
+
+    #[test]
+    fn test_strip_out_of_bailiwick() {
+        let zone = Name::parse("example.com.", None).unwrap();
+
+        let mut response = Message::new();
+        response.add_answer(a("www.example.com."));
+        response.add_answer(a("www.example.net."));
+        response.add_name_server(Record::from_rdata(Name::parse("sub.example.com.", None)
+                                                        .unwrap(),
+                                                    86400,
+                                                    RecordType::NS,
+                                                    RData::NS(Name::parse("ns.example.net.",
+                                                                          None)
+                                                        .unwrap())));
+        response.add_name_server(Record::from_rdata(Name::parse("com.", None).unwrap(),
+                                                    86400,
+                                                    RecordType::NS,
+                                                    RData::NS(Name::parse("ns.example.com.",
+                                                                          None)
+                                                        .unwrap())));
+        response.add_additional(a("ns.sub.example.com."));
+        response.add_additional(a("ns.example.net."));
+
+        assert_eq!(strip_out_of_bailiwick(&mut response, &zone), 3);
+        assert_eq!(response.get_answers(), &[a("www.example.com.")]);
+        assert_eq!(response.get_name_servers().len(), 1);
+        assert_eq!(response.get_additionals(), &[a("ns.sub.example.com.")]);
+
+        // everything is in the bailiwick of the root
+        let mut response = Message::new();
+        response.add_answer(a("www.example.net."));
+        assert_eq!(strip_out_of_bailiwick(&mut response, &Name::root()), 0);
+    }
+}
@@ -0,0 +1,621 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::cmp;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::time::Duration;
+
+use ::error::*;
+use op::Message;
+use rr::{LowerName, Name, RData};
+
+use super::bailiwick::in_bailiwick;
+
+/// Default time, in seconds, that what was learned about a server is remembered
+pub const DEFAULT_SERVER_TTL: u32 = 900;
+
+/// RTT assumed for a server that has not been queried yet, in milliseconds, low enough that new
+///  servers are tried before servers known to be slow.
+const UNKNOWN_RTT_MS: u32 = 376;
+
+/// Consecutive failures, timeouts or connection errors, after which a server is considered down
+pub const DOWN_AFTER_FAILURES: u32 = 3;
+
+/// Seconds a server is considered down after the first failures, doubled on each further failure
+///  up to the server TTL
+const DOWN_BACKOFF: u32 = 5;
+
+/// What is known about a server's support for EDNS, RFC 6891
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EdnsSupport {
+    /// The server has not been queried with EDNS yet
+    Unknown,
+    /// The server answered an EDNS query with an OPT record
+    Supported {
+        /// highest version understood by the server
+        version: u8,
+        /// the UDP payload size the server advertised
+        max_payload: u16,
+    },
+    /// The server answered FORMERR, or without an OPT record, to an EDNS query
+    Unsupported,
+}
+
+/// What the infrastructure cache knows about a single server address
+#[derive(Clone, Debug)]
+pub struct ServerInfo {
+    srtt_ms: Option<u32>,
+    edns: EdnsSupport,
+    tcp_only: bool,
+    lame: HashMap<LowerName, u32>,
+    failures: u32,
+    down_until: u32,
+    expires: u32,
+}
+
+impl ServerInfo {
+    fn new(expires: u32) -> Self {
+        ServerInfo {
+            srtt_ms: None,
+            edns: EdnsSupport::Unknown,
+            tcp_only: false,
+            lame: HashMap::new(),
+            failures: 0,
+            down_until: 0,
+            expires: expires,
+        }
+    }
+
+    /// The smoothed round trip time to the server, if it has answered a query
+    pub fn get_srtt(&self) -> Option<Duration> {
+        self.srtt_ms.map(|ms| Duration::from_millis(ms as u64))
+    }
+
+    /// What is known about the server's EDNS support
+    pub fn get_edns(&self) -> EdnsSupport {
+        self.edns
+    }
+
+    /// Returns true if UDP should not be used for the server, e.g. it truncates all responses
+    pub fn is_tcp_only(&self) -> bool {
+        self.tcp_only
+    }
+
+    /// The number of failures since the server last responded
+    pub fn get_failures(&self) -> u32 {
+        self.failures
+    }
+
+    /// Returns true if the server has failed too often and should not be tried before the backoff
+    ///  period is over
+    pub fn is_down(&self, now: u32) -> bool {
+        self.down_until > now
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Entry<T> {
+    value: T,
+    expires: u32,
+}
+
+impl<T> Entry<T> {
+    fn get(&self, now: u32) -> Option<&T> {
+        if self.expires > now {
+            Some(&self.value)
+        } else {
+            None
+        }
+    }
+}
+
+/// A cache of the infrastructure used for resolving, rather than of answers.
+///
+/// Delegations (zone to name server names) and name server addresses are kept with the TTLs of
+///  the records they were learned from. Per-address data, RTT, EDNS support, TCP only and lameness
+///  for a zone, are kept for the server TTL since they were last updated. None of this is ever
+///  used to answer a query.
+///
+/// All times, `now`, are seconds since the epoch.
+#[derive(Clone, Debug)]
+pub struct InfraCache {
+    delegations: HashMap<LowerName, Entry<Vec<Name>>>,
+    addrs: HashMap<LowerName, Entry<Vec<IpAddr>>>,
+    servers: HashMap<IpAddr, ServerInfo>,
+    server_ttl: u32,
+}
+
+impl Default for InfraCache {
+    fn default() -> Self {
+        InfraCache::new(DEFAULT_SERVER_TTL)
+    }
+}
+
+impl InfraCache {
+    /// Returns a new, empty, cache
+    ///
+    /// # Arguments
+    ///
+    /// * `server_ttl` - seconds that what was learned about a server address is kept
+    pub fn new(server_ttl: u32) -> Self {
+        InfraCache {
+            delegations: HashMap::new(),
+            addrs: HashMap::new(),
+            servers: HashMap::new(),
+            server_ttl: server_ttl,
+        }
+    }
+
+    /// Stores the name servers for a zone, from the NS records of a referral or answer
+    pub fn insert_delegation(&mut self,
+                             zone: &Name,
+                             name_servers: Vec<Name>,
+                             ttl: u32,
+                             now: u32) {
+        self.delegations.insert(LowerName::new(zone),
+                                Entry {
+                                    value: name_servers,
+                                    expires: now.saturating_add(ttl),
+                                });
+    }
+
+    /// Returns the name servers for exactly this zone
+    pub fn get_delegation(&self, zone: &Name, now: u32) -> Option<&[Name]> {
+        self.delegations
+            .get(&LowerName::new(zone))
+            .and_then(|entry| entry.get(now))
+            .map(|name_servers| &name_servers[..])
+    }
+
+    /// Returns the closest enclosing zone of `name` that has name servers cached, and those
+    ///  name servers
+    pub fn find_delegation(&self, name: &Name, now: u32) -> Option<(Name, &[Name])> {
+        let mut zone = name.clone();
+        loop {
+            if let Some(name_servers) = self.get_delegation(&zone, now) {
+                return Some((zone, name_servers));
+            }
+
+            if zone.is_root() {
+                return None;
+            }
+            zone = zone.base_name();
+        }
+    }
+
+    /// Stores the delegation, and glue, from a referral by a server for `zone`
+    ///
+    /// Only NS records for a zone below `zone`, and addresses of those name servers within
+    ///  `zone`, are used. Anything else is out of the bailiwick of the server and is ignored.
+    ///
+    /// # Return value
+    ///
+    /// The zone that was delegated to, if the response was a referral
+    pub fn insert_referral(&mut self, zone: &Name, response: &Message, now: u32) -> Option<Name> {
+        let mut child: Option<Name> = None;
+        let mut name_servers: Vec<Name> = Vec::new();
+        let mut ttl = u32::max_value();
+
+        for record in response.get_name_servers() {
+            let target = match *record.get_rdata() {
+                RData::NS(ref target) => target,
+                _ => continue,
+            };
+
+            let owner = record.get_name();
+            if !in_bailiwick(record, zone) || owner.num_labels() <= zone.num_labels() {
+                debug!("ignoring referral to {} from server for: {}", owner, zone);
+                continue;
+            }
+
+            // there can only be one delegation in a referral
+            if child.as_ref().map_or(false, |child| child != owner) {
+                continue;
+            }
+
+            child = Some(owner.clone());
+            ttl = cmp::min(ttl, record.get_ttl());
+            name_servers.push(target.clone());
+        }
+
+        let child = match child {
+            Some(child) => child,
+            None => return None,
+        };
+
+        for name_server in &name_servers {
+            let mut addrs = Vec::new();
+            let mut addr_ttl = u32::max_value();
+
+            for record in response.get_additionals() {
+                if record.get_name() != name_server || !in_bailiwick(record, zone) {
+                    continue;
+                }
+
+                let addr = match *record.get_rdata() {
+                    RData::A(ref addr) => IpAddr::V4(*addr),
+                    RData::AAAA(ref addr) => IpAddr::V6(*addr),
+                    _ => continue,
+                };
+
+                addr_ttl = cmp::min(addr_ttl, record.get_ttl());
+                addrs.push(addr);
+            }
+
+            if !addrs.is_empty() {
+                self.insert_addrs(name_server, addrs, addr_ttl, now);
+            }
+        }
+
+        self.insert_delegation(&child, name_servers, ttl, now);
+        Some(child)
+    }
+
+    /// Stores the addresses of a name server, from its A and AAAA records
+    pub fn insert_addrs(&mut self, name_server: &Name, addrs: Vec<IpAddr>, ttl: u32, now: u32) {
+        self.addrs.insert(LowerName::new(name_server),
+                          Entry {
+                              value: addrs,
+                              expires: now.saturating_add(ttl),
+                          });
+    }
+
+    /// Returns the addresses of the name server
+    pub fn get_addrs(&self, name_server: &Name, now: u32) -> Option<&[IpAddr]> {
+        self.addrs
+            .get(&LowerName::new(name_server))
+            .and_then(|entry| entry.get(now))
+            .map(|addrs| &addrs[..])
+    }
+
+    /// Returns what is known about the server address
+    pub fn get_server(&self, addr: &IpAddr, now: u32) -> Option<&ServerInfo> {
+        self.servers.get(addr).and_then(|info| if info.expires > now {
+            Some(info)
+        } else {
+            None
+        })
+    }
+
+    fn server_mut(&mut self, addr: IpAddr, now: u32) -> &mut ServerInfo {
+        let expires = now.saturating_add(self.server_ttl);
+        let info = self.servers.entry(addr).or_insert_with(|| ServerInfo::new(expires));
+
+        // forget everything about an expired server, only this update is known
+        if info.expires <= now {
+            *info = ServerInfo::new(expires);
+        }
+        info.expires = expires;
+        info
+    }
+
+    /// Records the time taken for the server to respond
+    ///
+    /// The RTT is smoothed, the new sample counts for 1/8th, as in RFC 6298.
+    pub fn record_rtt(&mut self, addr: IpAddr, rtt: Duration, now: u32) {
+        let sample = rtt.as_secs()
+            .saturating_mul(1000)
+            .saturating_add((rtt.subsec_nanos() / 1_000_000) as u64);
+        let sample = if sample > u32::max_value() as u64 {
+            u32::max_value()
+        } else {
+            sample as u32
+        };
+
+        let info = self.server_mut(addr, now);
+        info.srtt_ms = Some(match info.srtt_ms {
+            Some(srtt) => ((srtt as u64 * 7 + sample as u64) / 8) as u32,
+            None => sample,
+        });
+
+        // it answered, so it is up again
+        info.failures = 0;
+        info.down_until = 0;
+    }
+
+    /// Records that the server did not respond, or could not be connected to
+    ///
+    /// After `DOWN_AFTER_FAILURES` consecutive failures the server is down, each failure after
+    ///  that doubles the time before it is tried again, up to the server TTL.
+    pub fn record_failure(&mut self, addr: IpAddr, now: u32) {
+        let server_ttl = self.server_ttl;
+        let info = self.server_mut(addr, now);
+        info.failures = info.failures.saturating_add(1);
+
+        if info.failures >= DOWN_AFTER_FAILURES {
+            let doublings = info.failures - DOWN_AFTER_FAILURES;
+            let backoff = if doublings >= 16 {
+                server_ttl
+            } else {
+                cmp::min(DOWN_BACKOFF << doublings, server_ttl)
+            };
+
+            debug!("{} is down for {}s after {} failures", addr, backoff, info.failures);
+            info.down_until = now.saturating_add(backoff);
+        }
+    }
+
+    /// Returns true if the server is down, see `record_failure()`
+    pub fn is_down(&self, addr: &IpAddr, now: u32) -> bool {
+        self.get_server(addr, now).map_or(false, |info| info.is_down(now))
+    }
+
+    /// Records the server's EDNS support
+    pub fn set_edns(&mut self, addr: IpAddr, edns: EdnsSupport, now: u32) {
+        self.server_mut(addr, now).edns = edns;
+    }
+
+    /// Records that only TCP should be used with the server
+    pub fn set_tcp_only(&mut self, addr: IpAddr, tcp_only: bool, now: u32) {
+        self.server_mut(addr, now).tcp_only = tcp_only;
+    }
+
+    /// Records that the server is not authoritative for the zone it was delegated
+    pub fn mark_lame(&mut self, addr: IpAddr, zone: &Name, now: u32) {
+        let expires = now.saturating_add(self.server_ttl);
+        self.server_mut(addr, now).lame.insert(LowerName::new(zone), expires);
+    }
+
+    /// Returns true if the server was found to be lame for the zone
+    pub fn is_lame(&self, addr: &IpAddr, zone: &Name, now: u32) -> bool {
+        self.get_server(addr, now)
+            .and_then(|info| info.lame.get(&LowerName::new(zone)))
+            .map_or(false, |expires| *expires > now)
+    }
+
+    /// Orders the addresses fastest first, servers never queried are given a moderate RTT so
+    ///  that they are tried before slow ones
+    pub fn sort_by_rtt(&self, addrs: &mut [IpAddr], now: u32) {
+        addrs.sort_by_key(|addr| {
+            self.get_server(addr, now)
+                .and_then(|info| info.srtt_ms)
+                .unwrap_or(UNKNOWN_RTT_MS)
+        });
+    }
+
+    /// Returns the servers for the zone which are neither lame nor down, fastest first
+    ///
+    /// # Return value
+    ///
+    /// `AllServersUnavailable` if there are no servers left to try for the zone
+    pub fn select_servers(&self,
+                          zone: &Name,
+                          addrs: &[IpAddr],
+                          now: u32)
+                          -> ClientResult<Vec<IpAddr>> {
+        let mut selected: Vec<IpAddr> = addrs.iter()
+            .filter(|addr| !self.is_lame(addr, zone, now) && !self.is_down(addr, now))
+            .cloned()
+            .collect();
+
+        if selected.is_empty() {
+            return Err(ClientErrorKind::AllServersUnavailable(zone.clone()).into());
+        }
+
+        self.sort_by_rtt(&mut selected, now);
+        Ok(selected)
+    }
+
+    /// Removes all expired entries
+    pub fn purge(&mut self, now: u32) {
+        let expired: Vec<LowerName> = self.delegations
+            .iter()
+            .filter(|&(_, entry)| entry.expires <= now)
+            .map(|(zone, _)| zone.clone())
+            .collect();
+        for zone in expired {
+            self.delegations.remove(&zone);
+        }
+
+        let expired: Vec<LowerName> = self.addrs
+            .iter()
+            .filter(|&(_, entry)| entry.expires <= now)
+            .map(|(name_server, _)| name_server.clone())
+            .collect();
+        for name_server in expired {
+            self.addrs.remove(&name_server);
+        }
+
+        let expired: Vec<IpAddr> = self.servers
+            .iter()
+            .filter(|&(_, info)| info.expires <= now)
+            .map(|(addr, _)| *addr)
+            .collect();
+        for addr in expired {
+            self.servers.remove(&addr);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::{IpAddr, Ipv4Addr};
+    use std::time::Duration;
+
+    use error::*;
+    use op::Message;
+    use rr::{Name, RData, Record, RecordType};
+    use super::*;
+
+    fn name(name: &str) -> Name {
+        Name::parse(name, None).unwrap()
+    }
+
+    #[test]
+    fn test_find_delegation() {
+        let mut cache = InfraCache::default();
+        cache.insert_delegation(&Name::root(), vec![name("a.root-servers.net.")], 518400, 0);
+        cache.insert_delegation(&name("Example.COM."), vec![name("ns.example.com.")], 100, 0);
+
+        let (zone, name_servers) = cache.find_delegation(&name("www.example.com."), 10).unwrap();
+        assert_eq!(zone, name("example.com."));
+        assert_eq!(name_servers, &[name("ns.example.com.")]);
+
+        // the delegation to example.com expired, fall back to the root
+        let (zone, _) = cache.find_delegation(&name("www.example.com."), 100).unwrap();
+        assert!(zone.is_root());
+    }
+
+    #[test]
+    fn test_insert_referral() {
+        let mut cache = InfraCache::default();
+        let zone = name("com.");
+
+        let mut referral = Message::new();
+        referral.add_name_server(Record::from_rdata(name("example.com."),
+                                                    172800,
+                                                    RecordType::NS,
+                                                    RData::NS(name("ns1.example.com."))));
+        referral.add_name_server(Record::from_rdata(name("example.com."),
+                                                    172800,
+                                                    RecordType::NS,
+                                                    RData::NS(name("ns.example.net."))));
+        // the server for com. may not speak for net.
+        referral.add_name_server(Record::from_rdata(name("example.net."),
+                                                    172800,
+                                                    RecordType::NS,
+                                                    RData::NS(name("ns1.example.com."))));
+        referral.add_additional(Record::from_rdata(name("ns1.example.com."),
+                                                   3600,
+                                                   RecordType::A,
+                                                   RData::A(Ipv4Addr::new(192, 0, 2, 1))));
+        referral.add_additional(Record::from_rdata(name("ns.example.net."),
+                                                   3600,
+                                                   RecordType::A,
+                                                   RData::A(Ipv4Addr::new(10, 0, 0, 1))));
+
+        assert_eq!(cache.insert_referral(&zone, &referral, 0), Some(name("example.com.")));
+        assert_eq!(cache.get_delegation(&name("example.com."), 0).unwrap(),
+                   &[name("ns1.example.com."), name("ns.example.net.")]);
+        assert!(cache.get_delegation(&name("example.net."), 0).is_none());
+        assert_eq!(cache.get_addrs(&name("ns1.example.com."), 0).unwrap(),
+                   &[IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1))]);
+        assert!(cache.get_addrs(&name("ns.example.net."), 0).is_none());
+
+        // not a referral
+        assert!(cache.insert_referral(&zone, &Message::new(), 0).is_none());
+    }
+
+    #[test]
+    fn test_addrs_expire() {
+        let mut cache = InfraCache::default();
+        let addr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+        cache.insert_addrs(&name("ns.example.com."), vec![addr], 60, 0);
+
+        assert_eq!(cache.get_addrs(&name("NS.example.com."), 59).unwrap(), &[addr]);
+        assert!(cache.get_addrs(&name("ns.example.com."), 60).is_none());
+    }
+
+    #[test]
+    fn test_server_info() {
+        let mut cache = InfraCache::new(100);
+        let addr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+
+        cache.record_rtt(addr, Duration::from_millis(80), 0);
+        cache.record_rtt(addr, Duration::from_millis(160), 0);
+        cache.set_edns(addr,
+                       EdnsSupport::Supported {
+                           version: 0,
+                           max_payload: 1232,
+                       },
+                       0);
+        cache.set_tcp_only(addr, true, 0);
+        cache.mark_lame(addr, &name("example.com."), 0);
+
+        {
+            let info = cache.get_server(&addr, 50).unwrap();
+            assert_eq!(info.get_srtt(), Some(Duration::from_millis(90)));
+            assert!(info.is_tcp_only());
+            assert_eq!(info.get_edns(),
+                       EdnsSupport::Supported {
+                           version: 0,
+                           max_payload: 1232,
+                       });
+        }
+        assert!(cache.is_lame(&addr, &name("example.com."), 50));
+        assert!(!cache.is_lame(&addr, &name("example.net."), 50));
+
+        // the server ttl is separate from the record ttls
+        assert!(cache.get_server(&addr, 100).is_none());
+        assert!(!cache.is_lame(&addr, &name("example.com."), 100));
+
+        cache.purge(100);
+        cache.set_edns(addr, EdnsSupport::Unsupported, 100);
+        assert!(!cache.get_server(&addr, 100).unwrap().is_tcp_only());
+    }
+
+    #[test]
+    fn test_down_backoff() {
+        let mut cache = InfraCache::new(100);
+        let addr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+
+        cache.record_failure(addr, 0);
+        cache.record_failure(addr, 0);
+        assert!(!cache.is_down(&addr, 0));
+
+        cache.record_failure(addr, 0);
+        assert!(cache.is_down(&addr, 4));
+        assert!(!cache.is_down(&addr, 5));
+
+        cache.record_failure(addr, 5);
+        assert!(cache.is_down(&addr, 14));
+        assert!(!cache.is_down(&addr, 15));
+
+        // capped at the server ttl
+        for _ in 0..20 {
+            cache.record_failure(addr, 20);
+        }
+        assert!(!cache.is_down(&addr, 120));
+
+        cache.record_rtt(addr, Duration::from_millis(20), 20);
+        assert!(!cache.is_down(&addr, 20));
+        assert_eq!(cache.get_server(&addr, 20).unwrap().get_failures(), 0);
+    }
+
+    #[test]
+    fn test_select_servers() {
+        let mut cache = InfraCache::default();
+        let zone = name("example.com.");
+        let lame = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+        let down = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 2));
+        let good = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 3));
+
+        cache.mark_lame(lame, &zone, 0);
+        for _ in 0..DOWN_AFTER_FAILURES {
+            cache.record_failure(down, 0);
+        }
+
+        assert_eq!(cache.select_servers(&zone, &[lame, down, good], 0).unwrap(),
+                   vec![good]);
+        // lame for example.com only
+        assert_eq!(cache.select_servers(&name("example.net."), &[lame, down], 0).unwrap(),
+                   vec![lame]);
+
+        if let &ClientErrorKind::AllServersUnavailable(ref name) =
+            cache.select_servers(&zone, &[lame, down], 0).unwrap_err().kind() {
+            assert_eq!(name, &zone);
+        } else {
+            assert!(false);
+        }
+    }
+
+    #[test]
+    fn test_sort_by_rtt() {
+        let mut cache = InfraCache::default();
+        let fast = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+        let slow = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 2));
+        let unknown = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 3));
+
+        cache.record_rtt(fast, Duration::from_millis(20), 0);
+        cache.record_rtt(slow, Duration::from_millis(1000), 0);
+
+        let mut addrs = [slow, unknown, fast];
+        cache.sort_by_rtt(&mut addrs, 0);
+        assert_eq!(addrs, [fast, unknown, slow]);
+    }
+}
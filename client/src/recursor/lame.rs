@@ -0,0 +1,99 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use op::{Message, ResponseCode};
+use rr::{Name, RecordType};
+
+/// Returns true if the response shows that the server is lame for the zone, RFC 4697 section 2.3
+///
+/// A server which was delegated the zone must answer authoritatively, or refer to a zone below
+///  it. Refusing the query, or answering with cached data or a referral to the same or a parent
+///  zone, means that it does not serve the zone.
+///
+/// # Arguments
+///
+/// * `response` - response from the server
+/// * `zone` - the zone the server was delegated
+pub fn is_lame_response(response: &Message, zone: &Name) -> bool {
+    match response.get_response_code() {
+        ResponseCode::Refused | ResponseCode::NotAuth => return true,
+        _ => (),
+    }
+
+    if response.is_authoritative() {
+        return false;
+    }
+
+    // only a referral to a child zone is fine without AA
+    if !response.get_answers().is_empty() {
+        return true;
+    }
+
+    let mut referrals = response.get_name_servers()
+        .iter()
+        .filter(|record| record.get_rr_type() == RecordType::NS)
+        .peekable();
+
+    if referrals.peek().is_none() {
+        return true;
+    }
+
+    !referrals.all(|record| {
+        let name = record.get_name();
+        zone.zone_of(name) && name.num_labels() > zone.num_labels()
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use op::*;
+    use rr::*;
+    use super::*;
+
+    fn ns(owner: &str) -> Record {
+        Record::from_rdata(Name::parse(owner, None).unwrap(),
+                           86400,
+                           RecordType::NS,
+                           RData::NS(Name::parse("ns.example.net.", None).unwrap()))
+    }
+
+    #[test]
+    fn test_authoritative() {
+        let mut response = Message::new();
+        response.authoritative(true);
+
+        assert!(!is_lame_response(&response, &Name::parse("example.com.", None).unwrap()));
+    }
+
+    #[test]
+    fn test_refused() {
+        let mut response = Message::new();
+        response.authoritative(true).response_code(ResponseCode::Refused);
+
+        assert!(is_lame_response(&response, &Name::parse("example.com.", None).unwrap()));
+    }
+
+    #[test]
+    fn test_referrals() {
+        let zone = Name::parse("example.com.", None).unwrap();
+
+        let mut down = Message::new();
+        down.add_name_server(ns("sub.example.com."));
+        assert!(!is_lame_response(&down, &zone));
+
+        let mut same = Message::new();
+        same.add_name_server(ns("example.com."));
+        assert!(is_lame_response(&same, &zone));
+
+        let mut up = Message::new();
+        up.add_name_server(ns("com."));
+        assert!(is_lame_response(&up, &zone));
+
+        // neither answer nor referral
+        assert!(is_lame_response(&Message::new(), &zone));
+    }
+}
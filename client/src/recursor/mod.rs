@@ -0,0 +1,23 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Building blocks for resolving iteratively, starting from the root servers.
+
+mod bailiwick;
+mod infra_cache;
+mod lame;
+mod primer;
+mod related_answers;
+mod root_hints;
+
+pub use self::bailiwick::{in_bailiwick, strip_out_of_bailiwick};
+pub use self::infra_cache::{EdnsSupport, InfraCache, ServerInfo, DEFAULT_SERVER_TTL,
+                             DOWN_AFTER_FAILURES};
+pub use self::lame::is_lame_response;
+pub use self::primer::{prime, prime_with_clock, Primer};
+pub use self::related_answers::strip_unrelated_answers;
+pub use self::root_hints::RootHints;
@@ -0,0 +1,275 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::cell::RefCell;
+use std::net::SocketAddr;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use futures::{failed, finished, Future};
+use rand::{self, Rng};
+
+use client::ClientHandle;
+use clock::{Clock, SystemClock};
+use ::error::*;
+use op::{Message, MessageType, OpCode, Query};
+use rr::{DNSClass, Name, RecordType};
+
+use super::RootHints;
+
+/// Sends the `./NS` priming query, RFC 8109, and returns the primed root servers
+///
+/// # Arguments
+///
+/// * `client` - handle connected to one of the root servers from the hints
+/// * `hints` - root hints, used for any root server without an address in the response
+pub fn prime<H>(client: &mut H,
+                hints: RootHints)
+                -> Box<Future<Item = RootHints, Error = ClientError>>
+    where H: ClientHandle
+{
+    prime_with_clock(client, hints, Arc::new(SystemClock))
+}
+
+/// Sends the `./NS` priming query, the TTLs in the response are counted from the clock's time
+///
+/// See `prime()`.
+pub fn prime_with_clock<H>(client: &mut H,
+                           hints: RootHints,
+                           clock: Arc<Clock>)
+                           -> Box<Future<Item = RootHints, Error = ClientError>>
+    where H: ClientHandle
+{
+    let mut message: Message = Message::new();
+    message.id(rand::random())
+        .message_type(MessageType::Query)
+        .op_code(OpCode::Query)
+        .recursion_desired(false);
+
+    // the full root NS set with glue does not fit in 512 bytes
+    {
+        let edns = message.get_edns_mut();
+        edns.set_max_payload(1500);
+        edns.set_version(0);
+    }
+
+    let mut query: Query = Query::new();
+    query.name(Name::root()).query_class(DNSClass::IN).query_type(RecordType::NS);
+    message.add_query(query);
+
+    Box::new(client.send(message).and_then(move |response| {
+        hints.from_priming_response(&response, clock.now())
+    }))
+}
+
+/// Keeps the root servers primed
+///
+/// The first request for the root servers sends the priming query, as do requests after the
+///  primed set has expired. If priming fails the previously known addresses, either the expired
+///  primed set or the hints, are returned so that resolution can continue.
+pub struct Primer<H, F>
+    where H: ClientHandle,
+          F: Fn(SocketAddr) -> H
+{
+    hints: RootHints,
+    primed: Rc<RefCell<Option<RootHints>>>,
+    connect: F,
+    clock: Arc<Clock>,
+}
+
+impl<H, F> Primer<H, F>
+    where H: ClientHandle,
+          F: Fn(SocketAddr) -> H
+{
+    /// Returns a new Primer
+    ///
+    /// # Arguments
+    ///
+    /// * `hints` - root hints, see `RootHints::from_file()` or `RootHints::default()`
+    /// * `connect` - returns a handle for sending the priming query to the root server address
+    pub fn new(hints: RootHints, connect: F) -> Primer<H, F> {
+        Self::with_clock(hints, connect, Arc::new(SystemClock))
+    }
+
+    /// Returns a new Primer, expiring the primed root servers by the clock
+    pub fn with_clock(hints: RootHints, connect: F, clock: Arc<Clock>) -> Primer<H, F> {
+        Primer {
+            hints: hints,
+            primed: Rc::new(RefCell::new(None)),
+            connect: connect,
+            clock: clock,
+        }
+    }
+
+    /// Returns the currently primed root servers, if they have not expired
+    pub fn get_primed(&self) -> Option<RootHints> {
+        let now = self.clock.now();
+        self.primed.borrow().as_ref().and_then(|primed| if primed.is_expired(now) {
+            None
+        } else {
+            Some(primed.clone())
+        })
+    }
+
+    /// Returns the addresses of the root servers, priming them first if necessary
+    pub fn root_servers(&mut self) -> Box<Future<Item = Vec<SocketAddr>, Error = ClientError>> {
+        if let Some(primed) = self.get_primed() {
+            return Box::new(finished(primed.get_name_servers()));
+        }
+
+        // an expired primed set is still a better place to start than the hints
+        let fallback = self.primed
+            .borrow()
+            .as_ref()
+            .map_or_else(|| self.hints.get_name_servers(),
+                         |primed| primed.get_name_servers());
+
+        let server = match rand::thread_rng().choose(&fallback) {
+            Some(server) => *server,
+            None => {
+                return Box::new(failed(ClientErrorKind::Message("no root server addresses")
+                    .into()))
+            }
+        };
+
+        debug!("priming root servers with: {}", server);
+        let mut client = (self.connect)(server);
+        let primed = self.primed.clone();
+
+        let priming = prime_with_clock(&mut client, self.hints.clone(), self.clock.clone());
+
+        Box::new(priming.then(move |result| match result {
+            Ok(hints) => {
+                let servers = hints.get_name_servers();
+                *primed.borrow_mut() = Some(hints);
+                Ok(servers)
+            }
+            Err(e) => {
+                warn!("priming with {} failed, continuing with known root servers: {}",
+                      server,
+                      e);
+                Ok(fallback)
+            }
+        }))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::Cell;
+    use std::net::{Ipv4Addr, SocketAddr};
+    use std::rc::Rc;
+    use std::sync::Arc;
+
+    use futures::*;
+
+    use client::*;
+    use clock::ManualClock;
+    use error::*;
+    use op::*;
+    use recursor::RootHints;
+    use rr::*;
+    use super::*;
+
+    #[derive(Clone)]
+    struct RootClient {
+        sent: Rc<Cell<usize>>,
+        ttl: u32,
+    }
+
+    impl ClientHandle for RootClient {
+        fn send(&mut self, query: Message) -> Box<Future<Item = Message, Error = ClientError>> {
+            self.sent.set(self.sent.get() + 1);
+            assert!(!query.is_recursion_desired());
+            assert_eq!(query.get_queries()[0].get_query_type(), RecordType::NS);
+
+            let mut message = Message::new();
+            message.id(query.get_id());
+            message.add_answer(Record::from_rdata(Name::root(),
+                                                  self.ttl,
+                                                  RecordType::NS,
+                                                  RData::NS(Name::parse("a.root-servers.net.",
+                                                                        None)
+                                                      .unwrap())));
+            message.add_additional(Record::from_rdata(Name::parse("a.root-servers.net.", None)
+                                                          .unwrap(),
+                                                      self.ttl,
+                                                      RecordType::A,
+                                                      RData::A(Ipv4Addr::new(198, 41, 0, 4))));
+
+            Box::new(finished(message))
+        }
+    }
+
+    #[test]
+    fn test_prime_once() {
+        let sent = Rc::new(Cell::new(0));
+        let client = RootClient {
+            sent: sent.clone(),
+            ttl: 3600,
+        };
+        let mut primer = Primer::new(RootHints::default(), move |_| client.clone());
+
+        let expect: SocketAddr = "198.41.0.4:53".parse().unwrap();
+        assert_eq!(primer.root_servers().wait().unwrap(), vec![expect]);
+        assert_eq!(primer.root_servers().wait().unwrap(), vec![expect]);
+        assert_eq!(sent.get(), 1);
+    }
+
+    #[test]
+    fn test_reprime_when_expired() {
+        let sent = Rc::new(Cell::new(0));
+        let client = RootClient {
+            sent: sent.clone(),
+            ttl: 0,
+        };
+        let mut primer = Primer::new(RootHints::default(), move |_| client.clone());
+
+        primer.root_servers().wait().unwrap();
+        assert!(primer.get_primed().is_none());
+        primer.root_servers().wait().unwrap();
+        assert_eq!(sent.get(), 2);
+    }
+
+    #[test]
+    fn test_reprime_with_clock() {
+        let sent = Rc::new(Cell::new(0));
+        let client = RootClient {
+            sent: sent.clone(),
+            ttl: 3600,
+        };
+        let clock = ManualClock::new(1000);
+        let mut primer = Primer::with_clock(RootHints::default(),
+                                            move |_| client.clone(),
+                                            Arc::new(clock.clone()));
+
+        primer.root_servers().wait().unwrap();
+        clock.advance(3599);
+        assert!(primer.get_primed().is_some());
+        clock.advance(1);
+        assert!(primer.get_primed().is_none());
+        primer.root_servers().wait().unwrap();
+        assert_eq!(sent.get(), 2);
+    }
+
+    #[derive(Clone)]
+    struct FailingClient;
+
+    impl ClientHandle for FailingClient {
+        fn send(&mut self, _: Message) -> Box<Future<Item = Message, Error = ClientError>> {
+            Box::new(failed(ClientErrorKind::Timeout.into()))
+        }
+    }
+
+    #[test]
+    fn test_priming_failure_uses_hints() {
+        let mut primer = Primer::new(RootHints::default(), |_| FailingClient);
+
+        assert_eq!(primer.root_servers().wait().unwrap().len(), 26);
+        assert!(primer.get_primed().is_none());
+    }
+}
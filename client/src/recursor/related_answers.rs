@@ -0,0 +1,196 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use op::{Message, Query};
+use rr::{DNSClass, Name, RData, Record, RecordType};
+
+/// Removes every answer which is not related to the question
+///
+/// An answer is related if its owner is the name of the question, or a name the CNAME chain from
+///  it leads to, and its type is the one asked for, or a CNAME of the chain, or an RRSIG covering
+///  either. Anything else in the answer section of a sloppy, or malicious, upstream would be
+///  cached, or handed to the requestor, as if it had been asked for. Only the first CNAME of a
+///  name is followed, and zone transfers are not checked.
+///
+/// # Arguments
+///
+/// * `response` - the response from an upstream
+/// * `query` - the question of the request that was sent
+///
+/// # Return value
+///
+/// The number of records that were removed
+pub fn strip_unrelated_answers(response: &mut Message, query: &Query) -> usize {
+    match query.get_query_type() {
+        RecordType::AXFR | RecordType::IXFR => return 0,
+        _ => (),
+    }
+
+    let answers = response.take_answers();
+    let count = answers.len();
+
+    let answers = related_answers(answers, query);
+    let removed = count - answers.len();
+    response.insert_answers(answers);
+
+    if removed > 0 {
+        debug!("removed {} answers unrelated to: {} {:?}",
+               removed,
+               query.get_name(),
+               query.get_query_type());
+    }
+
+    removed
+}
+
+fn related_answers(answers: Vec<Record>, query: &Query) -> Vec<Record> {
+    let query_type = query.get_query_type();
+    let query_class = query.get_query_class();
+
+    // the chain, in whatever order the answers are, with the CNAMEs it was followed by
+    let mut chain: Vec<Name> = vec![query.get_name().clone()];
+    let mut followed: Vec<usize> = vec![];
+    if query_type != RecordType::CNAME && query_type != RecordType::ANY {
+        loop {
+            let mut next: Option<(usize, Name)> = None;
+            for (index, record) in answers.iter().enumerate() {
+                if let RData::CNAME(ref target) = *record.get_rdata() {
+                    let already_followed =
+                        followed.iter().any(|&f| answers[f].get_name() == record.get_name());
+
+                    if chain.contains(record.get_name()) && !already_followed &&
+                       !chain.contains(target) {
+                        next = Some((index, target.clone()));
+                        break;
+                    }
+                }
+            }
+
+            match next {
+                Some((index, target)) => {
+                    followed.push(index);
+                    chain.push(target);
+                }
+                None => break,
+            }
+        }
+    }
+
+    // a CNAME can not coexist with other data
+    let aliases: Vec<Name> = followed.iter().map(|&f| answers[f].get_name().clone()).collect();
+
+    answers.into_iter()
+        .enumerate()
+        .filter(|&(index, ref record)| {
+            if query_class != DNSClass::ANY && record.get_dns_class() != query_class {
+                return false;
+            }
+            if !chain.contains(record.get_name()) {
+                return false;
+            }
+
+            match (record.get_rr_type(), record.get_rdata()) {
+                (_, _) if query_type == RecordType::ANY => true,
+                (RecordType::CNAME, _) if query_type != RecordType::CNAME => {
+                    followed.contains(&index)
+                }
+                (RecordType::RRSIG, &RData::SIG(ref sig)) if query_type != RecordType::RRSIG => {
+                    sig.get_type_covered() == query_type ||
+                    sig.get_type_covered() == RecordType::CNAME
+                }
+                (rr_type, _) => rr_type == query_type && !aliases.contains(record.get_name()),
+            }
+        })
+        .map(|(_, record)| record)
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::Ipv4Addr;
+
+    use op::*;
+    use rr::*;
+    use super::*;
+
+    fn a(name: &str) -> Record {
+        Record::from_rdata(Name::parse(name, None).unwrap(),
+                           86400,
+                           RecordType::A,
+                           RData::A(Ipv4Addr::new(192, 0, 2, 1)))
+    }
+
+    fn cname(name: &str, target: &str) -> Record {
+        Record::from_rdata(Name::parse(name, None).unwrap(),
+                           86400,
+                           RecordType::CNAME,
+                           RData::CNAME(Name::parse(target, None).unwrap()))
+    }
+
+    fn query(name: &str, query_type: RecordType) -> Query {
+        let mut query = Query::new();
+        query.name(Name::parse(name, None).unwrap()).query_type(query_type);
+        query
+    }
+
+    #[test]
+    fn test_strip_unrelated_answers() {
+        let mut response = Message::new();
+        response.add_answer(a("www.example.net."));
+        response.add_answer(cname("web.example.com.", "www.example.net."));
+        response.add_answer(cname("WWW.example.com.", "web.example.com."));
+        response.add_answer(a("www.example.com."));
+        response.add_answer(a("bank.example.org."));
+        response.add_answer(Record::from_rdata(Name::parse("www.example.net.", None).unwrap(),
+                                               86400,
+                                               RecordType::AAAA,
+                                               RData::AAAA("2001:db8::1".parse().unwrap())));
+
+        let removed = strip_unrelated_answers(&mut response,
+                                              &query("www.example.com.", RecordType::A));
+        assert_eq!(removed, 3);
+        assert_eq!(response.get_answers(),
+                   &[a("www.example.net."),
+                     cname("web.example.com.", "www.example.net."),
+                     cname("WWW.example.com.", "web.example.com.")]);
+    }
+
+    #[test]
+    fn test_strip_second_cname() {
+        // only the first CNAME of a name is followed
+        let mut response = Message::new();
+        response.add_answer(cname("www.example.com.", "web.example.com."));
+        response.add_answer(cname("www.example.com.", "bank.example.org."));
+        response.add_answer(a("web.example.com."));
+        response.add_answer(a("bank.example.org."));
+
+        let removed = strip_unrelated_answers(&mut response,
+                                              &query("www.example.com.", RecordType::A));
+        assert_eq!(removed, 2);
+        assert_eq!(response.get_answers(),
+                   &[cname("www.example.com.", "web.example.com."), a("web.example.com.")]);
+    }
+
+    #[test]
+    fn test_cname_and_any_not_followed() {
+        let mut response = Message::new();
+        response.add_answer(cname("www.example.com.", "web.example.com."));
+        response.add_answer(a("web.example.com."));
+
+        let mut cname_response = response.clone();
+        assert_eq!(strip_unrelated_answers(&mut cname_response,
+                                           &query("www.example.com.", RecordType::CNAME)),
+                   1);
+
+        let mut any_response = response.clone();
+        assert_eq!(strip_unrelated_answers(&mut any_response,
+                                           &query("www.example.com.", RecordType::ANY)),
+                   1);
+        assert_eq!(any_response.get_answers(),
+                   &[cname("www.example.com.", "web.example.com.")]);
+    }
+}
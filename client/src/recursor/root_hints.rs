@@ -0,0 +1,267 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::cmp;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::Read;
+use std::net::{IpAddr, SocketAddr};
+use std::path::Path;
+
+use ::error::*;
+use op::{Message, ResponseCode};
+use rr::{DNSClass, LowerName, Name, RData, Record, RecordType, RrKey};
+use serialize::txt::{Lexer, Parser};
+
+/// The IANA root hints, with the class added to each record
+const ROOT_HINTS: &'static str = include_str!("named.root");
+
+/// The root name servers and their addresses.
+///
+/// Hints are loaded from a root hints file, e.g. `named.root`, and are only good for finding a
+///  root server to send the priming query to, RFC 8109. The primed set replaces the hints until
+///  the TTL of the root NS set, or of one of the addresses, runs out.
+#[derive(Clone, Debug)]
+pub struct RootHints {
+    servers: BTreeMap<LowerName, Vec<IpAddr>>,
+    expires: Option<u32>,
+}
+
+impl Default for RootHints {
+    fn default() -> RootHints {
+        RootHints::from_master(ROOT_HINTS).expect("Error parsing named.root")
+    }
+}
+
+impl RootHints {
+    /// Reads root hints from a master file
+    ///
+    /// Only the NS records at the root and the A and AAAA records of those name servers are
+    ///  used, anything else in the file is ignored.
+    pub fn from_file(path: &Path) -> ParseResult<RootHints> {
+        let mut file = try!(File::open(path));
+        let mut buf = String::new();
+        try!(file.read_to_string(&mut buf));
+
+        RootHints::from_master(&buf)
+    }
+
+    /// Parses root hints from the text of a master file
+    pub fn from_master(text: &str) -> ParseResult<RootHints> {
+        let (_, records) = try!(Parser::new().parse(Lexer::new(text), Some(Name::root())));
+
+        let ns_names: Vec<Name> = match records.get(&RrKey::new(&Name::root(), RecordType::NS)) {
+            Some(rrset) => rrset.iter().filter_map(|r| ns_name(r)).collect(),
+            None => {
+                return Err(ParseErrorKind::Message("no NS records for the root in hints").into())
+            }
+        };
+
+        let mut servers = BTreeMap::new();
+        for name in ns_names {
+            let mut addrs = Vec::new();
+            for rtype in &[RecordType::A, RecordType::AAAA] {
+                if let Some(rrset) = records.get(&RrKey::new(&name, *rtype)) {
+                    addrs.extend(rrset.iter().filter_map(|r| ip_addr(r)));
+                }
+            }
+
+            servers.insert(LowerName::new(&name), addrs);
+        }
+
+        if servers.values().all(|addrs| addrs.is_empty()) {
+            return Err(ParseErrorKind::Message("no addresses for the root servers in hints")
+                .into());
+        }
+
+        Ok(RootHints {
+            servers: servers,
+            expires: None,
+        })
+    }
+
+    /// Builds the primed set of root servers from the response to a `./NS` priming query
+    ///
+    /// Addresses in the additional section are only accepted for the name servers in the answer,
+    ///  a name server without any address in the response keeps the addresses from these hints.
+    ///
+    /// # Arguments
+    ///
+    /// * `response` - response to the priming query
+    /// * `now` - current time in seconds since the epoch, the TTLs in the response are from now
+    pub fn from_priming_response(&self, response: &Message, now: u32) -> ClientResult<RootHints> {
+        if response.get_response_code() != ResponseCode::NoError {
+            return Err(ClientErrorKind::ErrorResponse(response.get_response_code()).into());
+        }
+
+        let mut ttl: Option<u32> = None;
+        let mut servers = BTreeMap::new();
+        for record in response.get_answers() {
+            if !record.get_name().is_root() || record.get_dns_class() != DNSClass::IN {
+                continue;
+            }
+
+            if let Some(name) = ns_name(record) {
+                ttl = Some(ttl.map_or(record.get_ttl(), |ttl| cmp::min(ttl, record.get_ttl())));
+                servers.insert(LowerName::new(&name), Vec::new());
+            }
+        }
+
+        if servers.is_empty() {
+            return Err(ClientErrorKind::Message("no NS records for the root in priming response")
+                .into());
+        }
+
+        for record in response.get_additionals() {
+            if let Some(addr) = ip_addr(record) {
+                if let Some(addrs) = servers.get_mut(&LowerName::new(record.get_name())) {
+                    ttl = Some(ttl.map_or(record.get_ttl(), |ttl| cmp::min(ttl, record.get_ttl())));
+                    addrs.push(addr);
+                }
+            }
+        }
+
+        for (name, addrs) in servers.iter_mut() {
+            if addrs.is_empty() {
+                if let Some(hint) = self.servers.get(name) {
+                    debug!("no address for {} in priming response, using hints", name);
+                    addrs.extend(hint.iter().cloned());
+                }
+            }
+        }
+
+        if servers.values().all(|addrs| addrs.is_empty()) {
+            return Err(ClientErrorKind::Message("no addresses for the root servers in priming \
+                                                 response")
+                .into());
+        }
+
+        Ok(RootHints {
+            servers: servers,
+            expires: ttl.map(|ttl| now.saturating_add(ttl)),
+        })
+    }
+
+    /// Returns true if these were primed, and not simply loaded from hints
+    pub fn is_primed(&self) -> bool {
+        self.expires.is_some()
+    }
+
+    /// Returns true if these must be primed (again) before use, hints are always expired
+    pub fn is_expired(&self, now: u32) -> bool {
+        self.expires.map_or(true, |expires| expires <= now)
+    }
+
+    /// The names of all the root servers
+    pub fn get_names(&self) -> Vec<Name> {
+        self.servers.keys().map(|name| (**name).clone()).collect()
+    }
+
+    /// The addresses of the named root server
+    pub fn get_addrs(&self, name: &Name) -> &[IpAddr] {
+        self.servers.get(&LowerName::new(name)).map(|addrs| &addrs[..]).unwrap_or(&[])
+    }
+
+    /// All the root server addresses, on the DNS port
+    pub fn get_name_servers(&self) -> Vec<SocketAddr> {
+        self.servers
+            .values()
+            .flat_map(|addrs| addrs.iter())
+            .map(|addr| SocketAddr::new(*addr, 53))
+            .collect()
+    }
+}
+
+fn ns_name(record: &Record) -> Option<Name> {
+    match *record.get_rdata() {
+        RData::NS(ref name) => Some(name.clone()),
+        _ => None,
+    }
+}
+
+fn ip_addr(record: &Record) -> Option<IpAddr> {
+    match *record.get_rdata() {
+        RData::A(ref addr) => Some(IpAddr::V4(*addr)),
+        RData::AAAA(ref addr) => Some(IpAddr::V6(*addr)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::{IpAddr, Ipv4Addr};
+
+    use op::Message;
+    use rr::{Name, RData, Record, RecordType};
+    use super::*;
+
+    fn ns(target: &str, ttl: u32) -> Record {
+        Record::from_rdata(Name::root(),
+                           ttl,
+                           RecordType::NS,
+                           RData::NS(Name::parse(target, None).unwrap()))
+    }
+
+    fn a(name: &str, addr: Ipv4Addr, ttl: u32) -> Record {
+        Record::from_rdata(Name::parse(name, None).unwrap(), ttl, RecordType::A, RData::A(addr))
+    }
+
+    #[test]
+    fn test_default_hints() {
+        let hints = RootHints::default();
+
+        assert_eq!(hints.get_names().len(), 13);
+        assert_eq!(hints.get_name_servers().len(), 26);
+        assert_eq!(hints.get_addrs(&Name::parse("a.root-servers.net.", None).unwrap()),
+                   &[IpAddr::V4(Ipv4Addr::new(198, 41, 0, 4)),
+                     "2001:503:ba3e::2:30".parse().unwrap()]);
+        assert!(!hints.is_primed());
+        assert!(hints.is_expired(0));
+    }
+
+    #[test]
+    fn test_hints_without_ns() {
+        assert!(RootHints::from_master("$ORIGIN .\n\
+                                        A.ROOT-SERVERS.NET. 3600000 IN A 198.41.0.4\n")
+            .is_err());
+    }
+
+    #[test]
+    fn test_priming_response() {
+        let hints = RootHints::default();
+
+        let mut response = Message::new();
+        response.add_answer(ns("a.root-servers.net.", 518400));
+        response.add_answer(ns("b.root-servers.net.", 518400));
+        response.add_additional(a("A.ROOT-SERVERS.NET.", Ipv4Addr::new(198, 41, 0, 5), 3600));
+        // out of place, not one of the root servers
+        response.add_additional(a("evil.example.", Ipv4Addr::new(10, 0, 0, 1), 3600000));
+
+        let primed = hints.from_priming_response(&response, 1000).unwrap();
+
+        assert!(primed.is_primed());
+        assert!(!primed.is_expired(1000));
+        assert!(primed.is_expired(4600));
+        assert_eq!(primed.get_names().len(), 2);
+        assert_eq!(primed.get_addrs(&Name::parse("a.root-servers.net.", None).unwrap()),
+                   &[IpAddr::V4(Ipv4Addr::new(198, 41, 0, 5))]);
+        // b has no glue, so the hints are kept
+        assert_eq!(primed.get_addrs(&Name::parse("b.root-servers.net.", None).unwrap()).len(),
+                   2);
+        assert!(primed.get_addrs(&Name::parse("evil.example.", None).unwrap()).is_empty());
+    }
+
+    #[test]
+    fn test_priming_response_without_ns() {
+        let hints = RootHints::default();
+
+        let mut response = Message::new();
+        response.add_additional(a("a.root-servers.net.", Ipv4Addr::new(198, 41, 0, 4), 3600));
+
+        assert!(hints.from_priming_response(&response, 0).is_err());
+    }
+}
@@ -18,6 +18,8 @@
 
 use std::convert::From;
 use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
 
 use ::serialize::binary::*;
 use ::error::*;
@@ -34,16 +36,17 @@ pub enum DNSClass {
 }
 
 impl DNSClass {
-    /// Convert from &str to DNSClass
+    /// Convert from &str to DNSClass, ignoring case as in master files
     ///
     /// ```
     /// use trust_dns::rr::dns_class::DNSClass;
     ///
     /// let var: DNSClass = DNSClass::from_str("IN").unwrap();
     /// assert_eq!(DNSClass::IN, var);
+    /// assert_eq!(DNSClass::CH, DNSClass::from_str("ch").unwrap());
     /// ```
     pub fn from_str(str: &str) -> DecodeResult<Self> {
-        match str {
+        match &str.to_uppercase() as &str {
             "IN" => Ok(DNSClass::IN),
             "CH" => Ok(DNSClass::CH),
             "HS" => Ok(DNSClass::HS),
@@ -111,6 +114,30 @@ impl From<DNSClass> for &'static str {
     }
 }
 
+/// Parses the mnemonic of the class, e.g. for command line arguments and config files
+///
+/// ```
+/// use trust_dns::rr::dns_class::DNSClass;
+///
+/// let var: DNSClass = "HS".parse().unwrap();
+/// assert_eq!(DNSClass::HS, var);
+/// ```
+impl FromStr for DNSClass {
+    type Err = DecodeError;
+
+    fn from_str(s: &str) -> DecodeResult<Self> {
+        DNSClass::from_str(s)
+    }
+}
+
+/// The mnemonic of the class, as in master files
+impl fmt::Display for DNSClass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mnemonic: &'static str = (*self).into();
+        f.write_str(mnemonic)
+    }
+}
+
 /// Convert from DNSClass to u16
 ///
 /// ```
@@ -0,0 +1,166 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! A bundle of the chain of trust of an answer, for verifying it offline, e.g. for auditing
+
+use ::error::*;
+use op::Query;
+use rr::{LowerName, Name, Record, RecordType};
+use rr::dnssec::{verify_chain, TrustAnchor};
+use serialize::binary::{BinDecoder, BinEncoder, BinSerializable};
+
+/// The answer to a query, with its RRSIGs, and every DNSKEY and DS rrset, with their RRSIGs, from
+///  the root down to the zone of the answer, see `Client::export_dnssec_chain`
+///
+/// The bundle is self-contained: `verify` checks it against a trust anchor without any queries,
+///  at the time it was collected, or any other time within the RRSIG validity periods, see
+///  `verify_chain`. `to_vec` and `from_vec` store it as the question followed by the count of
+///  the records and the records, each in the wire format.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DnssecChain {
+    query: Query,
+    records: Vec<Record>,
+}
+
+impl DnssecChain {
+    /// A bundle of the records of the chain of the answer to the query
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - the question which was answered
+    /// * `records` - the answer and the DNSKEY, DS and RRSIG records of the chain, in any order
+    pub fn new(query: Query, records: Vec<Record>) -> Self {
+        DnssecChain {
+            query: query,
+            records: records,
+        }
+    }
+
+    /// The question which was answered
+    pub fn get_query(&self) -> &Query {
+        &self.query
+    }
+
+    /// All the records of the bundle, including the RRSIGs
+    pub fn get_records(&self) -> &[Record] {
+        &self.records
+    }
+
+    /// The zones of the chain, those with DNSKEY records in the bundle, from the root down
+    pub fn get_zones(&self) -> Vec<Name> {
+        let mut zones: Vec<Name> = Vec::new();
+        for record in &self.records {
+            if record.get_rr_type() == RecordType::DNSKEY && !zones.contains(record.get_name()) {
+                zones.push(record.get_name().clone());
+            }
+        }
+
+        zones.sort_by_key(|zone| zone.num_labels());
+        zones
+    }
+
+    /// Verifies the chain from the trust anchor down to the answer, without any queries
+    ///
+    /// # Arguments
+    ///
+    /// * `trust_anchor` - the trusted keys to start from
+    /// * `now` - seconds since the Unix epoch at which the RRSIGs must be valid, e.g. when the
+    ///           chain was collected, to audit it later
+    ///
+    /// # Return value
+    ///
+    /// The verified records answering the query, without the RRSIGs, or an error if any rrset
+    ///  of the bundle could not be verified or it has no answer.
+    pub fn verify(&self, trust_anchor: &TrustAnchor, now: u32) -> DnsSecResult<Vec<Record>> {
+        let verified = try!(verify_chain(&self.records, trust_anchor, now));
+
+        let name = LowerName::new(self.query.get_name());
+        let answers: Vec<Record> = verified.into_iter()
+            .filter(|record| LowerName::new(record.get_name()) == name)
+            .filter(|record| {
+                let rr_type = record.get_rr_type();
+                rr_type == self.query.get_query_type() || rr_type == RecordType::CNAME
+            })
+            .collect();
+
+        if answers.is_empty() {
+            return Err(DnsSecErrorKind::Msg(format!("no answer in chain for: {}", self.query))
+                .into());
+        }
+
+        Ok(answers)
+    }
+
+    /// Decodes a bundle stored by `to_vec`
+    pub fn from_vec(buffer: &[u8]) -> DecodeResult<Self> {
+        let mut decoder = BinDecoder::new(buffer);
+        Self::read(&mut decoder)
+    }
+
+    /// Encodes the bundle, for storing
+    pub fn to_vec(&self) -> Result<Vec<u8>, EncodeError> {
+        let mut buffer = Vec::new();
+        {
+            let mut encoder = BinEncoder::new(&mut buffer);
+            try!(self.emit(&mut encoder));
+        }
+
+        Ok(buffer)
+    }
+}
+
+impl BinSerializable<DnssecChain> for DnssecChain {
+    fn read(decoder: &mut BinDecoder) -> DecodeResult<Self> {
+        let query = try!(Query::read(decoder));
+        let count = try!(decoder.read_u16());
+
+        let mut records = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            records.push(try!(Record::read(decoder)));
+        }
+
+        Ok(DnssecChain::new(query, records))
+    }
+
+    fn emit(&self, encoder: &mut BinEncoder) -> EncodeResult {
+        if self.records.len() > u16::max_value() as usize {
+            return Err(EncodeErrorKind::Msg("too many records in chain".to_string()).into());
+        }
+
+        try!(self.query.emit(encoder));
+        try!(encoder.emit_u16(self.records.len() as u16));
+        for record in &self.records {
+            try!(record.emit(encoder));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::Ipv4Addr;
+
+    use op::Query;
+    use rr::{Name, RData, Record, RecordType};
+    use super::*;
+
+    #[test]
+    fn test_to_from_vec() {
+        let mut query = Query::new();
+        query.name(Name::parse("www.example.com.", None).unwrap()).query_type(RecordType::A);
+        let records = vec![Record::from_rdata(Name::parse("www.example.com.", None).unwrap(),
+                                              86400,
+                                              RecordType::A,
+                                              RData::A(Ipv4Addr::new(93, 184, 216, 34)))];
+        let chain = DnssecChain::new(query, records);
+
+        let buffer = chain.to_vec().unwrap();
+        assert_eq!(DnssecChain::from_vec(&buffer).unwrap(), chain);
+        assert!(DnssecChain::from_vec(&buffer[..buffer.len() - 1]).is_err());
+    }
+}
@@ -202,25 +202,20 @@ impl KeyPair {
                 //  string.  The generation of a public key is defined in Section 5.1.5
                 //  in [I-D.irtf-cfrg-eddsa].
                 //
-                // **NOTE: not specified in the RFC is the byte order, assuming it is
-                //  BigEndian/NetworkByteOrder.
+                // the value is the encoding of RFC 8032, as the examples of RFC 8080 show, and as
+                //  ring uses it.
                 if public_key.len() != 32 {
                     return Err(DnsSecErrorKind::Msg(format!("expected 32 byte public_key: {}",
                                                             public_key.len()))
                         .into());
                 }
 
-                // these are LittleEndian encoded bytes... we need to special case
-                //  serialzation/deserialization for endianess. why, Intel, why...
-                let mut public_key = public_key.to_vec();
-                public_key.reverse();
-
                 let mut ed_key_pair = Ed25519KeyPairBytes {
                     private_key: [0_u8; 32],
                     public_key: [0_u8; 32],
                 };
 
-                ed_key_pair.public_key.copy_from_slice(&public_key);
+                ed_key_pair.public_key.copy_from_slice(public_key);
                 Ok(KeyPair::ED25519(ed_key_pair))
             }
             #[cfg(not(all(feature = "openssl", feature = "ring")))]
@@ -280,11 +275,7 @@ impl KeyPair {
             }
             #[cfg(feature = "ring")]
       KeyPair::ED25519(ref ed_key) => {
-                // this is "little endian" encoded bytes... we need to special case
-                //  serialzation/deserialization for endianess. why, Intel, why...
-                let mut pub_key = ed_key.public_key.to_vec();
-                pub_key.reverse();
-                Ok(pub_key)
+                Ok(ed_key.public_key.to_vec())
             }
             // #[cfg(not(all(feature = "openssl", feature = "ring")))]
             // _ => Err(DnsSecErrorKind::Message("openssl nor ring feature(s) not enabled").into()),
@@ -17,6 +17,7 @@
 //! dns security extension related modules
 
 mod algorithm;
+mod chain;
 mod digest_type;
 mod key_format;
 mod keypair;
@@ -24,15 +25,20 @@ mod nsec3;
 mod signer;
 mod supported_algorithm;
 mod trust_anchor;
+mod tsigner;
+mod verifier;
 
 pub use self::algorithm::Algorithm;
+pub use self::chain::DnssecChain;
 pub use self::digest_type::DigestType;
 pub use self::key_format::KeyFormat;
 pub use self::keypair::KeyPair;
-pub use self::nsec3::Nsec3HashAlgorithm;
+pub use self::nsec3::{hashed_owner_name, Nsec3HashAlgorithm};
 pub use self::signer::Signer;
 pub use self::supported_algorithm::SupportedAlgorithms;
 pub use self::trust_anchor::TrustAnchor;
+pub use self::tsigner::{split_tsig, TSigner, TsigAlgorithm, TsigChain, DEFAULT_FUDGE};
+pub use self::verifier::{is_sig_current, verify_chain, verify_rrset_with_dnskey};
 
 pub use error::DnsSecError;
 pub use error::DnsSecErrorKind;
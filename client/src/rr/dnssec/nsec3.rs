@@ -15,13 +15,15 @@
  */
 #[cfg(feature = "openssl")]
 use std::io::Write;
+use std::rc::Rc;
+
+use data_encoding::base32hex;
 #[cfg(feature = "openssl")]
 use openssl::hash;
 
 use ::error::*;
 #[cfg(feature = "openssl")]
 use rr::dnssec::DigestType;
-#[cfg(feature = "openssl")]
 use rr::Name;
 #[cfg(feature = "openssl")]
 use serialize::binary::{BinEncoder, BinSerializable};
@@ -177,6 +179,17 @@ impl Nsec3HashAlgorithm {
     }
 }
 
+/// The owner name of the NSEC3 record of a name with the hash, its base32hex, in lowercase, as a
+///  label of the zone, RFC 5155 section 3
+///
+/// # Arguments
+///
+/// * `hash` - the hash of the name, see `Nsec3HashAlgorithm::hash`
+/// * `zone` - the origin of the zone of the name
+pub fn hashed_owner_name(hash: &[u8], zone: &Name) -> Name {
+    zone.prepend_label(Rc::new(base32hex::encode(hash).to_lowercase()))
+}
+
 impl From<Nsec3HashAlgorithm> for u8 {
     fn from(a: Nsec3HashAlgorithm) -> u8 {
         match a {
@@ -248,11 +261,20 @@ fn test_known_hashes() {
                "t644ebqk9bibcna874givr6joj62mlhv");
 }
 
+#[test]
+#[cfg(feature = "openssl")]
+fn test_hashed_owner_name() {
+    let zone = Name::parse("example.", None).unwrap();
+    let name = Name::parse("a.example.", None).unwrap();
+    let hash = Nsec3HashAlgorithm::SHA1.hash(&[0xAAu8, 0xBBu8, 0xCCu8, 0xDDu8], &name, 12).unwrap();
+
+    assert_eq!(hashed_owner_name(&hash, &zone),
+               Name::parse("35mthgpgcu1qg68fab165klnsnk3dpvl.example.", None).unwrap());
+}
+
 #[cfg(test)]
 #[cfg(feature = "openssl")]
 fn hash_with_base32(name: &str) -> String {
-    use data_encoding::base32hex;
-
     // NSEC3PARAM 1 0 12 aabbccdd
     let known_name = Name::parse(name, Some(&Name::new())).unwrap();
     let known_salt = [0xAAu8, 0xBBu8, 0xCCu8, 0xDDu8];
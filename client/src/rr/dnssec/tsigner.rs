@@ -0,0 +1,485 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+#[cfg(feature = "openssl")]
+use openssl::pkey::PKey;
+#[cfg(feature = "openssl")]
+use openssl::sign::Signer;
+
+use std::fmt;
+use std::str::FromStr;
+
+use op::{Header, Message, Query};
+use rr::{DNSClass, Name, RData, Record, RecordType};
+use rr::dnssec::{DigestType, DnsSecError, DnsSecErrorKind, DnsSecResult};
+use rr::rdata::{tsig, TSIG};
+use error::EncodeResult;
+use serialize::binary::{BinDecoder, BinEncoder, BinSerializable, EncodeMode};
+
+/// The seconds of clock skew permitted between the signer and the receiver, RFC 8945 section 10
+pub const DEFAULT_FUDGE: u16 = 300;
+
+/// The HMAC algorithms of TSIG, RFC 8945 section 6, those which are mandatory or recommended
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum TsigAlgorithm {
+    /// hmac-sha256, mandatory
+    HmacSha256,
+    /// hmac-sha384
+    HmacSha384,
+    /// hmac-sha512
+    HmacSha512,
+}
+
+impl TsigAlgorithm {
+    /// The name of the algorithm in the TSIG record
+    pub fn to_name(&self) -> Name {
+        let name = match *self {
+            TsigAlgorithm::HmacSha256 => "hmac-sha256",
+            TsigAlgorithm::HmacSha384 => "hmac-sha384",
+            TsigAlgorithm::HmacSha512 => "hmac-sha512",
+        };
+
+        Name::new().label(name)
+    }
+}
+
+impl FromStr for TsigAlgorithm {
+    type Err = DnsSecError;
+
+    /// Parses the name of the algorithm, e.g. `hmac-sha256`, with or without the trailing dot
+    fn from_str(s: &str) -> DnsSecResult<Self> {
+        match s.trim_right_matches('.').to_lowercase().as_str() {
+            "hmac-sha256" => Ok(TsigAlgorithm::HmacSha256),
+            "hmac-sha384" => Ok(TsigAlgorithm::HmacSha384),
+            "hmac-sha512" => Ok(TsigAlgorithm::HmacSha512),
+            _ => Err(DnsSecErrorKind::Msg(format!("unsupported tsig algorithm: {}", s)).into()),
+        }
+    }
+}
+
+impl From<TsigAlgorithm> for DigestType {
+    fn from(algorithm: TsigAlgorithm) -> Self {
+        match algorithm {
+            TsigAlgorithm::HmacSha256 => DigestType::SHA256,
+            TsigAlgorithm::HmacSha384 => DigestType::SHA384,
+            TsigAlgorithm::HmacSha512 => DigestType::SHA512,
+        }
+    }
+}
+
+/// What the MAC of a message covers besides the message, RFC 8945 sections 4.3 and 5.3
+#[derive(Debug, Clone, Copy)]
+pub enum TsigChain<'a> {
+    /// A request, the MAC covers the message and all the TSIG variables
+    Request,
+    /// The first response to a request, the MAC also covers the MAC of the request
+    Response(&'a [u8]),
+    /// Each later signed message of a multi-message response, e.g. AXFR, the MAC covers the MAC
+    ///  of the prior signed message, and only the timers of the TSIG variables
+    Continuation(&'a [u8]),
+}
+
+/// Signs and verifies messages with a secret shared with the peer, TSIG of RFC 8945
+pub struct TSigner {
+    key_name: Name,
+    algorithm: TsigAlgorithm,
+    key: Vec<u8>,
+    fudge: u16,
+}
+
+impl TSigner {
+    /// Returns a signer with the shared secret
+    ///
+    /// # Arguments
+    ///
+    /// * `key_name` - the name of the key, as the peer knows it, e.g. `tsig-key.example.com.`
+    /// * `algorithm` - the HMAC algorithm of the key
+    /// * `key` - the shared secret, the decoded bytes and not the base64 of the key files
+    pub fn new(key_name: Name, algorithm: TsigAlgorithm, key: Vec<u8>) -> Self {
+        TSigner {
+            key_name: key_name,
+            algorithm: algorithm,
+            key: key,
+            fudge: DEFAULT_FUDGE,
+        }
+    }
+
+    /// Sets the seconds of clock skew permitted, `DEFAULT_FUDGE` if not set
+    pub fn fudge(&mut self, fudge: u16) -> &mut Self {
+        self.fudge = fudge;
+        self
+    }
+
+    /// The owner name of the TSIG records
+    pub fn get_key_name(&self) -> &Name {
+        &self.key_name
+    }
+
+    pub fn get_algorithm(&self) -> TsigAlgorithm {
+        self.algorithm
+    }
+
+    pub fn get_fudge(&self) -> u16 {
+        self.fudge
+    }
+
+    /// Computes the MAC of the message, before its TSIG record is added
+    ///
+    /// [RFC 8945](https://tools.ietf.org/html/rfc8945#section-4.3.3), TSIG, November 2020
+    ///
+    /// ```text
+    /// 4.3.3.  TSIG Variables
+    ///
+    ///    Also included in the digest is certain information present in the
+    ///    TSIG RR.  Adding this data provides further protection against an
+    ///    attempt to interfere with the message.
+    ///
+    ///    | Source | Field Name     | Notes                                 |
+    ///    | TSIG RR| NAME           | Key name, in canonical wire format    |
+    ///    | TSIG RR| CLASS          | MUST be ANY                           |
+    ///    | TSIG RR| TTL            | MUST be 0                             |
+    ///    | RDATA  | Algorithm Name | in canonical wire format              |
+    ///    | RDATA  | Time Signed    | in network byte order                 |
+    ///    | RDATA  | Fudge          | in network byte order                 |
+    ///    | RDATA  | Error          | in network byte order                 |
+    ///    | RDATA  | Other Len      | in network byte order                 |
+    ///    | RDATA  | Other Data     | exactly as transmitted                |
+    /// ```
+    ///
+    /// # Arguments
+    ///
+    /// * `message` - the message, with the id it is signed with, see `TSIG::get_original_id`
+    /// * `chain` - the MAC which this one follows, if the message is a response
+    /// * `time_signed` - seconds since the epoch, UTC
+    pub fn sign_message(&self,
+                        message: &Message,
+                        chain: TsigChain,
+                        time_signed: u64)
+                        -> DnsSecResult<Vec<u8>> {
+        let mut buf: Vec<u8> = Vec::with_capacity(512);
+
+        {
+            // the signing mode leaves out the TSIG, and its count, if the message has one
+            let mut encoder: BinEncoder = BinEncoder::with_mode(&mut buf, EncodeMode::Signing);
+            try!(message.emit(&mut encoder)
+                .map_err(|e| DnsSecErrorKind::Msg(format!("could not encode message: {}", e))));
+        }
+
+        self.mac(chain, &buf, time_signed, self.fudge, 0, &[])
+    }
+
+    /// Verifies the TSIG of a received message, which must be its last record
+    ///
+    /// The message is verified as it was received, names may be compressed differently once it
+    ///  is decoded and encoded again.
+    ///
+    /// # Arguments
+    ///
+    /// * `unsigned` - the messages received since the last signed one, without their length
+    ///                prefixes, at most 99 of them may be left unsigned in a multi-message
+    ///                response, RFC 8945 section 5.3.1, empty otherwise
+    /// * `buffer` - the received message
+    /// * `chain` - the MAC which this one follows, if the message is a response
+    /// * `now` - seconds since the epoch, UTC
+    ///
+    /// # Return
+    ///
+    /// The TSIG of the message, its MAC is the one which the next message follows.
+    pub fn verify_message(&self,
+                          unsigned: &[u8],
+                          buffer: &[u8],
+                          chain: TsigChain,
+                          now: u64)
+                          -> DnsSecResult<TSIG> {
+        let (key_name, tsig, message) = try!(split_tsig(buffer));
+
+        if key_name != self.key_name {
+            return Err(DnsSecErrorKind::Msg(format!("unexpected tsig key: {}", key_name)).into());
+        }
+        if *tsig.get_algorithm() != self.algorithm.to_name() {
+            return Err(DnsSecErrorKind::Msg(format!("unexpected tsig algorithm: {}",
+                                                    tsig.get_algorithm()))
+                .into());
+        }
+        if tsig.get_error() != 0 {
+            return Err(DnsSecErrorKind::Msg(format!("tsig error: {}", tsig.get_error())).into());
+        }
+
+        let mut signed: Vec<u8> = Vec::with_capacity(unsigned.len() + message.len());
+        signed.extend_from_slice(unsigned);
+        signed.extend_from_slice(&message);
+
+        let mac = try!(self.mac(chain,
+                                &signed,
+                                tsig.get_time_signed(),
+                                tsig.get_fudge(),
+                                tsig.get_error(),
+                                tsig.get_other_data()));
+        if !constant_time_eq(&mac, tsig.get_mac()) {
+            return Err(DnsSecErrorKind::Message("tsig mac does not match").into());
+        }
+
+        // the time is checked once the MAC is, section 5.2.3
+        let skew = if now > tsig.get_time_signed() {
+            now - tsig.get_time_signed()
+        } else {
+            tsig.get_time_signed() - now
+        };
+        if skew > tsig.get_fudge() as u64 {
+            return Err(DnsSecErrorKind::Msg(format!("tsig time signed is {}s off", skew)).into());
+        }
+
+        Ok(tsig)
+    }
+
+    fn mac(&self,
+           chain: TsigChain,
+           message: &[u8],
+           time_signed: u64,
+           fudge: u16,
+           error: u16,
+           other_data: &[u8])
+           -> DnsSecResult<Vec<u8>> {
+        let mut buf: Vec<u8> = Vec::with_capacity(message.len() + 128);
+
+        {
+            let mut encoder: BinEncoder = BinEncoder::new(&mut buf);
+            try!(self.emit_signed_data(&mut encoder,
+                                       chain,
+                                       message,
+                                       time_signed,
+                                       fudge,
+                                       error,
+                                       other_data)
+                .map_err(|e| DnsSecErrorKind::Msg(format!("could not encode tsig data: {}", e))));
+        }
+
+        self.hmac(&buf)
+    }
+
+    fn emit_signed_data(&self,
+                        encoder: &mut BinEncoder,
+                        chain: TsigChain,
+                        message: &[u8],
+                        time_signed: u64,
+                        fudge: u16,
+                        error: u16,
+                        other_data: &[u8])
+                        -> EncodeResult {
+        match chain {
+            TsigChain::Request => (),
+            TsigChain::Response(prior_mac) |
+            TsigChain::Continuation(prior_mac) => {
+                try!(encoder.emit_u16(prior_mac.len() as u16));
+                try!(encoder.emit_vec(prior_mac));
+            }
+        }
+
+        try!(encoder.emit_vec(message));
+
+        // later messages of a response only cover the timers, section 5.3.1
+        if let TsigChain::Continuation(_) = chain {
+            try!(tsig::emit_time(encoder, time_signed));
+            return encoder.emit_u16(fudge);
+        }
+
+        try!(self.key_name.emit_as_canonical(encoder, true));
+        try!(DNSClass::ANY.emit(encoder));
+        try!(encoder.emit_u32(0));
+        try!(self.algorithm.to_name().emit_as_canonical(encoder, true));
+        try!(tsig::emit_time(encoder, time_signed));
+        try!(encoder.emit_u16(fudge));
+        try!(encoder.emit_u16(error));
+        try!(encoder.emit_u16(other_data.len() as u16));
+        encoder.emit_vec(other_data)
+    }
+
+    #[cfg(feature = "openssl")]
+    fn hmac(&self, data: &[u8]) -> DnsSecResult<Vec<u8>> {
+        let digest_type = try!(DigestType::from(self.algorithm).to_openssl_digest());
+        let pkey = try!(PKey::hmac(&self.key));
+        let mut signer = try!(Signer::new(digest_type, &pkey));
+        try!(signer.update(data));
+        signer.finish().map_err(|e| e.into())
+    }
+
+    #[cfg(not(feature = "openssl"))]
+    fn hmac(&self, _: &[u8]) -> DnsSecResult<Vec<u8>> {
+        Err(DnsSecErrorKind::Message("openssl feature not enabled").into())
+    }
+}
+
+/// The secret is left out
+impl fmt::Debug for TSigner {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TSigner")
+            .field("key_name", &self.key_name)
+            .field("algorithm", &self.algorithm)
+            .field("fudge", &self.fudge)
+            .finish()
+    }
+}
+
+/// Returns the key name and TSIG of the message, and the message as it was before it was signed
+///
+/// That is the message without its TSIG, with the original id, and one less additional record,
+///  RFC 8945 section 4.3.1.
+pub fn split_tsig(buffer: &[u8]) -> DnsSecResult<(Name, TSIG, Vec<u8>)> {
+    let mut decoder = BinDecoder::new(buffer);
+    let header = try!(Header::read(&mut decoder)
+        .map_err(|e| DnsSecErrorKind::Msg(format!("could not decode header: {}", e))));
+    for _ in 0..header.get_query_count() {
+        try!(Query::read(&mut decoder)
+            .map_err(|e| DnsSecErrorKind::Msg(format!("could not decode query: {}", e))));
+    }
+
+    let record_count = header.get_answer_count() as usize +
+                       header.get_name_server_count() as usize +
+                       header.get_additional_count() as usize;
+    let mut last: Option<(usize, Record)> = None;
+    for _ in 0..record_count {
+        let start = decoder.index();
+        let record = try!(Record::read(&mut decoder)
+            .map_err(|e| DnsSecErrorKind::Msg(format!("could not decode record: {}", e))));
+        last = Some((start, record));
+    }
+
+    // the TSIG must be the last record, section 5.2
+    let (start, record) = match last {
+        Some((start, record)) => {
+            if header.get_additional_count() == 0 || record.get_rr_type() != RecordType::TSIG {
+                return Err(DnsSecErrorKind::Message("message is not signed with tsig").into());
+            }
+            (start, record)
+        }
+        None => return Err(DnsSecErrorKind::Message("message is not signed with tsig").into()),
+    };
+
+    let tsig = match *record.get_rdata() {
+        RData::TSIG(ref tsig) => tsig.clone(),
+        _ => return Err(DnsSecErrorKind::Message("message is not signed with tsig").into()),
+    };
+
+    let mut message = buffer[..start].to_vec();
+    let original_id = tsig.get_original_id();
+    message[0] = (original_id >> 8) as u8;
+    message[1] = original_id as u8;
+    let additional_count = header.get_additional_count() - 1;
+    message[10] = (additional_count >> 8) as u8;
+    message[11] = additional_count as u8;
+
+    Ok((record.get_name().clone(), tsig, message))
+}
+
+/// Compares MACs without returning early, to not reveal how much of a forged MAC matched
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+
+    a.iter().zip(b.iter()).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+#[cfg(feature = "openssl")]
+mod tests {
+    use op::{Message, MessageType, OpCode, Query};
+    use rr::{Name, RecordType};
+    use serialize::binary::{BinEncoder, BinSerializable};
+    use super::*;
+
+    fn signer() -> TSigner {
+        TSigner::new(Name::parse("transfer-key.example.com.", None).unwrap(),
+                     TsigAlgorithm::HmacSha256,
+                     b"the secret of the transfers".to_vec())
+    }
+
+    fn encode(message: &Message) -> Vec<u8> {
+        let mut buf: Vec<u8> = Vec::new();
+        {
+            let mut encoder = BinEncoder::new(&mut buf);
+            message.emit(&mut encoder).unwrap();
+        }
+        buf
+    }
+
+    fn message(id: u16, message_type: MessageType) -> Message {
+        let mut message = Message::new();
+        message.id(id).message_type(message_type).op_code(OpCode::Query);
+        let mut query = Query::new();
+        query.name(Name::parse("example.com.", None).unwrap()).query_type(RecordType::AXFR);
+        message.add_query(query);
+        message
+    }
+
+    #[test]
+    fn test_sign_verify() {
+        let tsigner = signer();
+        let mut request = message(10, MessageType::Query);
+        let request_mac = request.sign_tsig(&tsigner, TsigChain::Request, 1000).unwrap();
+        let request_bytes = encode(&request);
+
+        let tsig = tsigner.verify_message(&[], &request_bytes, TsigChain::Request, 1100).unwrap();
+        assert_eq!(tsig.get_mac(), &request_mac[..]);
+
+        // outside of the fudge
+        assert!(tsigner.verify_message(&[], &request_bytes, TsigChain::Request, 1301).is_err());
+
+        // another key
+        let other = TSigner::new(tsigner.get_key_name().clone(),
+                                 TsigAlgorithm::HmacSha256,
+                                 b"another secret".to_vec());
+        assert!(other.verify_message(&[], &request_bytes, TsigChain::Request, 1000).is_err());
+
+        // the id is changed after signing, it is verified with the original one
+        let mut request_bytes = request_bytes;
+        request_bytes[0] = 0xff;
+        assert!(tsigner.verify_message(&[], &request_bytes, TsigChain::Request, 1000).is_ok());
+
+        // tampered
+        let last = request_bytes.len() - 20;
+        request_bytes[last] ^= 0x01;
+        assert!(tsigner.verify_message(&[], &request_bytes, TsigChain::Request, 1000).is_err());
+    }
+
+    #[test]
+    fn test_response_chain() {
+        let tsigner = signer();
+        let mut request = message(10, MessageType::Query);
+        let request_mac = request.sign_tsig(&tsigner, TsigChain::Request, 1000).unwrap();
+
+        let mut first = message(10, MessageType::Response);
+        let first_mac = first.sign_tsig(&tsigner, TsigChain::Response(&request_mac), 1001)
+            .unwrap();
+        let first_bytes = encode(&first);
+
+        assert!(tsigner.verify_message(&[],
+                                    &first_bytes,
+                                    TsigChain::Response(&request_mac),
+                                    1001)
+            .is_ok());
+        // a response is not verified as a request
+        assert!(tsigner.verify_message(&[], &first_bytes, TsigChain::Request, 1001).is_err());
+
+        let mut last = message(10, MessageType::Response);
+        last.sign_tsig(&tsigner, TsigChain::Continuation(&first_mac), 1002).unwrap();
+        let last_bytes = encode(&last);
+        assert!(tsigner.verify_message(&[],
+                                    &last_bytes,
+                                    TsigChain::Continuation(&first_mac),
+                                    1002)
+            .is_ok());
+
+        // the MAC covers any unsigned messages since the prior signed one, there were none
+        let unsigned = encode(&message(10, MessageType::Response));
+        assert!(tsigner.verify_message(&unsigned,
+                                    &last_bytes,
+                                    TsigChain::Continuation(&first_mac),
+                                    1002)
+            .is_err());
+    }
+}
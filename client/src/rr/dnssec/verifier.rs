@@ -0,0 +1,718 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Offline verification of DNSSec signed records, from the trust anchor down.
+
+use std::collections::HashMap;
+
+use ::error::*;
+use rr::{DNSClass, LowerName, Name, RData, Record, RecordType};
+#[cfg(feature = "openssl")]
+use rr::dnssec::{KeyPair, Signer};
+use rr::dnssec::TrustAnchor;
+use rr::rdata::{DNSKEY, DS, SIG};
+
+/// Verifies the RRSIG over the rrset with the DNSKEY
+///
+/// # Arguments
+///
+/// * `dnskey` - the key of the signer
+/// * `sig` - the RRSIG data covering the rrset
+/// * `name` - owner of the rrset
+/// * `dns_class` - class of the rrset
+/// * `records` - the rrset, records of other types or names are ignored
+#[cfg(feature = "openssl")]
+pub fn verify_rrset_with_dnskey(dnskey: &DNSKEY,
+                                sig: &SIG,
+                                name: &Name,
+                                dns_class: DNSClass,
+                                records: &[Record])
+                                -> DnsSecResult<()> {
+    if dnskey.is_revoke() {
+        debug!("revoked");
+        return Err(DnsSecErrorKind::Message("revoked").into());
+    } // TODO: does this need to be validated? RFC 5011
+    if !dnskey.is_zone_key() {
+        return Err(DnsSecErrorKind::Message("is not a zone key").into());
+    }
+    if *dnskey.get_algorithm() != sig.get_algorithm() {
+        return Err(DnsSecErrorKind::Message("mismatched algorithm").into());
+    }
+
+    let pkey = try!(KeyPair::from_public_bytes(dnskey.get_public_key(), *dnskey.get_algorithm())
+        .map_err(|e| {
+            debug!("error getting key from vec: {}", e);
+            DnsSecError::from(DnsSecErrorKind::Message("error getting key from vec"))
+        }));
+
+    let signer: Signer = Signer::new_verifier(*dnskey.get_algorithm(),
+                                              pkey,
+                                              sig.get_signer_name().clone(),
+                                              dnskey.is_zone_key(),
+                                              false);
+
+    let rrset_hash = try!(signer.hash_rrset_with_sig(name, dns_class, sig, records));
+    try!(signer.verify(&rrset_hash, sig.get_sig()));
+
+    debug!("verified rrset: {}, type: {:?}", name, sig.get_type_covered());
+    Ok(())
+}
+
+/// Will always return an error. To enable record verification compile with the openssl feature.
+#[cfg(not(feature = "openssl"))]
+pub fn verify_rrset_with_dnskey(_: &DNSKEY,
+                                _: &SIG,
+                                _: &Name,
+                                _: DNSClass,
+                                _: &[Record])
+                                -> DnsSecResult<()> {
+    Err(DnsSecErrorKind::Message("openssl feature not enabled").into())
+}
+
+/// Returns true if `now` is within the validity period of the signature, RFC 4034 3.1.5
+///
+/// The times are compared with serial number arithmetic, RFC 1982, so that they keep working
+///  after 2106.
+pub fn is_sig_current(sig: &SIG, now: u32) -> bool {
+    now.wrapping_sub(sig.get_sig_inception()) as i32 >= 0 &&
+    sig.get_sig_expiration().wrapping_sub(now) as i32 >= 0
+}
+
+#[test]
+fn test_is_sig_current() {
+    use rr::dnssec::Algorithm;
+
+    let sig = SIG::new(RecordType::A,
+                       Algorithm::RSASHA256,
+                       2,
+                       3600,
+                       2000,
+                       1000,
+                       0,
+                       Name::root(),
+                       vec![]);
+
+    assert!(!is_sig_current(&sig, 999));
+    assert!(is_sig_current(&sig, 1000));
+    assert!(is_sig_current(&sig, 2000));
+    assert!(!is_sig_current(&sig, 2001));
+
+    // the validity period wraps around
+    let sig = SIG::new(RecordType::A,
+                       Algorithm::RSASHA256,
+                       2,
+                       3600,
+                       10,
+                       u32::max_value() - 10,
+                       0,
+                       Name::root(),
+                       vec![]);
+    assert!(is_sig_current(&sig, u32::max_value()));
+    assert!(is_sig_current(&sig, 5));
+    assert!(!is_sig_current(&sig, 11));
+}
+
+struct Rrset {
+    name: Name,
+    record_type: RecordType,
+    dns_class: DNSClass,
+    records: Vec<Record>,
+    rrsigs: Vec<SIG>,
+    verified: bool,
+}
+
+impl Rrset {
+    fn dnskeys(&self) -> Vec<&DNSKEY> {
+        self.records
+            .iter()
+            .filter_map(|r| match *r.get_rdata() {
+                RData::DNSKEY(ref dnskey) => Some(dnskey),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn dss(&self) -> Vec<&DS> {
+        self.records
+            .iter()
+            .filter_map(|r| match *r.get_rdata() {
+                RData::DS(ref ds) => Some(ds),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// True if any of the RRSIGs current at `now` verifies with any of the keys of the signer
+    fn is_signed_by(&self, signer: &Name, keys: &[&DNSKEY], now: u32) -> bool {
+        self.rrsigs
+            .iter()
+            .filter(|sig| sig.get_signer_name() == signer && signer.zone_of(&self.name))
+            .filter(|sig| is_sig_current(sig, now))
+            .any(|sig| {
+                keys.iter().any(|key| {
+                    verify_rrset_with_dnskey(key, sig, &self.name, self.dns_class, &self.records)
+                        .is_ok()
+                })
+            })
+    }
+}
+
+/// Verifies a set of signed records end to end, from the trust anchor down.
+///
+/// The records are typically everything collected while resolving, e.g. the answers to the
+///  DNSKEY and DS queries for each zone from the root down, and the signed answer itself.
+///
+/// * A DNSKEY rrset is trusted if one of its keys is in the trust anchor, or is covered by a
+///   verified DS rrset for the same name, and one of those keys signed the DNSKEY rrset. Bare
+///   DNSKEY rrsets are only trusted for the keys in the trust anchor, as in `SecureClientHandle`.
+/// * Any other rrset, including DS rrsets, is verified if an RRSIG over it validates with a key
+///   from a trusted DNSKEY rrset of the signer, and the signer is at or above the owner name.
+///
+/// Only RRSIGs whose validity period includes `now` are used. That is the current time, e.g.
+///  `SystemClock.now()`, or the time the records were collected, to verify captured responses
+///  long after they were signed.
+///
+/// # Arguments
+///
+/// * `records` - all records of the chain, including RRSIGs
+/// * `trust_anchor` - the trusted keys to start from
+/// * `now` - seconds since the Unix epoch, at which the RRSIGs must be valid
+///
+/// # Return value
+///
+/// All the verified records without the RRSIGs, or an error naming the first rrset which could
+///  not be verified.
+pub fn verify_chain(records: &[Record],
+                    trust_anchor: &TrustAnchor,
+                    now: u32)
+                    -> DnsSecResult<Vec<Record>> {
+    let mut rrsets: Vec<Rrset> = Vec::new();
+    let mut index: HashMap<(LowerName, RecordType, DNSClass), usize> = HashMap::new();
+
+    for record in records.iter().filter(|r| r.get_rr_type() != RecordType::RRSIG) {
+        let key = (LowerName::new(record.get_name()), record.get_rr_type(), record.get_dns_class());
+        let idx = *index.entry(key).or_insert_with(|| {
+            rrsets.push(Rrset {
+                name: record.get_name().clone(),
+                record_type: record.get_rr_type(),
+                dns_class: record.get_dns_class(),
+                records: Vec::new(),
+                rrsigs: Vec::new(),
+                verified: false,
+            });
+            rrsets.len() - 1
+        });
+
+        rrsets[idx].records.push(record.clone());
+    }
+
+    for record in records.iter().filter(|r| r.get_rr_type() == RecordType::RRSIG) {
+        if let RData::SIG(ref sig) = *record.get_rdata() {
+            let key = (LowerName::new(record.get_name()),
+                       sig.get_type_covered(),
+                       record.get_dns_class());
+            if let Some(idx) = index.get(&key) {
+                rrsets[*idx].rrsigs.push(sig.clone());
+            }
+        }
+    }
+
+    // trusted keys by zone, only ever grows, so repeat until nothing more can be verified
+    let mut trusted: HashMap<LowerName, Vec<DNSKEY>> = HashMap::new();
+    loop {
+        let mut progress = false;
+
+        for idx in 0..rrsets.len() {
+            if rrsets[idx].verified {
+                continue;
+            }
+
+            let verified = if rrsets[idx].record_type == RecordType::DNSKEY {
+                let rrset = &rrsets[idx];
+                let keys = rrset.dnskeys();
+
+                // the keys which may sign the DNSKEY rrset
+                let anchored: Vec<&DNSKEY> = keys.iter()
+                    .filter(|key| trust_anchor.contains(key.get_public_key()))
+                    .cloned()
+                    .collect();
+                let entry_points: Vec<&DNSKEY> = if !anchored.is_empty() {
+                    anchored
+                } else {
+                    let dss: Vec<&DS> = rrsets.iter()
+                        .filter(|ds| {
+                            ds.verified && ds.record_type == RecordType::DS &&
+                            ds.dns_class == rrset.dns_class &&
+                            ds.name == rrset.name
+                        })
+                        .flat_map(|ds| ds.dss())
+                        .collect();
+
+                    keys.iter()
+                        .filter(|key| {
+                            dss.iter().any(|ds| ds.covers(&rrset.name, key).unwrap_or(false))
+                        })
+                        .cloned()
+                        .collect()
+                };
+
+                if entry_points.is_empty() {
+                    false
+                } else if rrset.rrsigs.is_empty() {
+                    entry_points.iter().all(|key| trust_anchor.contains(key.get_public_key()))
+                } else {
+                    rrset.is_signed_by(&rrset.name, &entry_points, now)
+                }
+            } else {
+                let rrset = &rrsets[idx];
+                rrset.rrsigs.iter().any(|sig| {
+                    trusted.get(&LowerName::new(sig.get_signer_name())).map_or(false, |keys| {
+                        let keys: Vec<&DNSKEY> = keys.iter().collect();
+                        rrset.is_signed_by(sig.get_signer_name(), &keys, now)
+                    })
+                })
+            };
+
+            if verified {
+                let rrset = &mut rrsets[idx];
+                debug!("verified chain to: {}, {:?}", rrset.name, rrset.record_type);
+
+                if rrset.record_type == RecordType::DNSKEY {
+                    // without a signature, only the anchored keys are vouched for
+                    if rrset.rrsigs.is_empty() {
+                        rrset.records.retain(|record| match *record.get_rdata() {
+                            RData::DNSKEY(ref key) => trust_anchor.contains(key.get_public_key()),
+                            _ => false,
+                        });
+                    }
+
+                    let keys: Vec<DNSKEY> = rrset.dnskeys().into_iter().cloned().collect();
+                    trusted.entry(LowerName::new(&rrset.name))
+                        .or_insert_with(Vec::new)
+                        .extend(keys);
+                }
+
+                rrset.verified = true;
+                progress = true;
+            }
+        }
+
+        if !progress {
+            break;
+        }
+    }
+
+    if let Some(rrset) = rrsets.iter().find(|rrset| !rrset.verified) {
+        return Err(DnsSecErrorKind::Msg(format!("could not verify chain to: {}, {:?}",
+                                                rrset.name,
+                                                rrset.record_type))
+            .into());
+    }
+
+    Ok(rrsets.into_iter().flat_map(|rrset| rrset.records).collect())
+}
+
+#[cfg(test)]
+#[cfg(feature = "openssl")]
+mod test {
+    use std::net::Ipv4Addr;
+    use std::str;
+
+    use chrono::Duration;
+    use data_encoding::{base32hex, base64};
+    use openssl::rsa::Rsa;
+
+    use rr::*;
+    use rr::dnssec::*;
+    use rr::rdata::{DNSKEY, DS, MX, NSEC3, SIG};
+    use super::*;
+
+    /// within the validity period of the signatures by `sign()`
+    const NOW: u32 = 1500;
+
+    struct Zone {
+        name: Name,
+        signer: Signer,
+        dnskey: Record,
+        ds: Record,
+    }
+
+    fn zone(name: &str) -> Zone {
+        let name = Name::parse(name, None).unwrap();
+        let key = KeyPair::from_rsa(Rsa::generate(1024).unwrap()).unwrap();
+
+        let dnskey = key.to_dnskey(Algorithm::RSASHA256).unwrap();
+        let ds = key.to_ds(&name, Algorithm::RSASHA256, DigestType::SHA256).unwrap();
+        let signer = Signer::new(Algorithm::RSASHA256,
+                                 key,
+                                 name.clone(),
+                                 Duration::max_value(),
+                                 true,
+                                 false);
+
+        Zone {
+            dnskey: Record::from_rdata(name.clone(),
+                                       86400,
+                                       RecordType::DNSKEY,
+                                       RData::DNSKEY(dnskey)),
+            ds: Record::from_rdata(name.clone(), 86400, RecordType::DS, RData::DS(ds)),
+            name: name,
+            signer: signer,
+        }
+    }
+
+    /// Returns the RRSIG over the records by the zone
+    fn sign(zone: &Zone, records: &[Record]) -> Record {
+        let name = records[0].get_name().clone();
+        let sig = SIG::new(records[0].get_rr_type(),
+                           Algorithm::RSASHA256,
+                           name.num_labels(),
+                           86400,
+                           2000,
+                           1000,
+                           zone.signer.calculate_key_tag().unwrap(),
+                           zone.name.clone(),
+                           vec![]);
+
+        let hash = zone.signer.hash_rrset_with_sig(&name, DNSClass::IN, &sig, records).unwrap();
+        let sig = SIG::new(sig.get_type_covered(),
+                           sig.get_algorithm(),
+                           sig.get_num_labels(),
+                           sig.get_original_ttl(),
+                           sig.get_sig_expiration(),
+                           sig.get_sig_inception(),
+                           sig.get_key_tag(),
+                           sig.get_signer_name().clone(),
+                           zone.signer.sign(&hash).unwrap());
+
+        Record::from_rdata(name, 86400, RecordType::RRSIG, RData::SIG(sig))
+    }
+
+    fn trust_anchor(zone: &Zone) -> TrustAnchor {
+        let mut trust_anchor = TrustAnchor::new();
+        if let RData::DNSKEY(ref dnskey) = *zone.dnskey.get_rdata() {
+            trust_anchor.insert_trust_anchor(dnskey.get_public_key().to_vec());
+        }
+        trust_anchor
+    }
+
+    fn www() -> Record {
+        Record::from_rdata(Name::parse("www.example.com.", None).unwrap(),
+                           86400,
+                           RecordType::A,
+                           RData::A(Ipv4Addr::new(93, 184, 216, 34)))
+    }
+
+    fn chain(root: &Zone, example: &Zone, answer: &Record) -> Vec<Record> {
+        vec![root.dnskey.clone(),
+             sign(root, &[root.dnskey.clone()]),
+             example.ds.clone(),
+             sign(root, &[example.ds.clone()]),
+             example.dnskey.clone(),
+             sign(example, &[example.dnskey.clone()]),
+             answer.clone(),
+             sign(example, &[www()])]
+    }
+
+    #[test]
+    fn test_verify_chain() {
+        let root = zone(".");
+        let example = zone("example.com.");
+
+        let verified = verify_chain(&chain(&root, &example, &www()), &trust_anchor(&root), NOW)
+            .expect("chain should verify");
+
+        assert_eq!(verified.len(), 4);
+        assert!(verified.contains(&www()));
+
+        // order doesn't matter
+        let mut records = chain(&root, &example, &www());
+        records.reverse();
+        assert!(verify_chain(&records, &trust_anchor(&root), NOW).is_ok());
+    }
+
+    #[test]
+    fn test_verify_chain_validity_period() {
+        let root = zone(".");
+        let example = zone("example.com.");
+        let records = chain(&root, &example, &www());
+
+        assert!(verify_chain(&records, &trust_anchor(&root), 999).is_err());
+        assert!(verify_chain(&records, &trust_anchor(&root), 1000).is_ok());
+        assert!(verify_chain(&records, &trust_anchor(&root), 2000).is_ok());
+        assert!(verify_chain(&records, &trust_anchor(&root), 2001).is_err());
+    }
+
+    #[test]
+    fn test_dnssec_chain() {
+        let root = zone(".");
+        let example = zone("example.com.");
+
+        let mut query = ::op::Query::new();
+        query.name(www().get_name().clone()).query_type(RecordType::A);
+        let chain = DnssecChain::new(query, chain(&root, &example, &www()));
+        assert_eq!(chain.get_zones(), vec![root.name.clone(), example.name.clone()]);
+
+        // stored and verified offline
+        let chain = DnssecChain::from_vec(&chain.to_vec().unwrap()).unwrap();
+        assert_eq!(chain.verify(&trust_anchor(&root), NOW).unwrap(), vec![www()]);
+        assert!(chain.verify(&trust_anchor(&example), NOW).is_err());
+        assert!(chain.verify(&trust_anchor(&root), 2001).is_err());
+
+        // the chain must answer the query
+        let mut query = ::op::Query::new();
+        query.name(www().get_name().clone()).query_type(RecordType::AAAA);
+        let chain = DnssecChain::new(query, chain.get_records().to_vec());
+        assert!(chain.verify(&trust_anchor(&root), NOW).is_err());
+    }
+
+    #[test]
+    fn test_verify_chain_tampered() {
+        let root = zone(".");
+        let example = zone("example.com.");
+
+        let mut tampered = www();
+        tampered.rdata(RData::A(Ipv4Addr::new(192, 0, 2, 1)));
+
+        assert!(verify_chain(&chain(&root, &example, &tampered), &trust_anchor(&root), NOW)
+            .is_err());
+    }
+
+    #[test]
+    fn test_verify_chain_broken() {
+        let root = zone(".");
+        let example = zone("example.com.");
+
+        // without the DS the child key is not trusted
+        let records: Vec<Record> = chain(&root, &example, &www())
+            .into_iter()
+            .filter(|r| match *r.get_rdata() {
+                RData::DS(_) => false,
+                RData::SIG(ref sig) => sig.get_type_covered() != RecordType::DS,
+                _ => true,
+            })
+            .collect();
+        assert!(verify_chain(&records, &trust_anchor(&root), NOW).is_err());
+
+        // a different root key is not the trust anchor
+        let other = zone(".");
+        assert!(verify_chain(&chain(&root, &example, &www()), &trust_anchor(&other), NOW)
+            .is_err());
+    }
+
+    #[test]
+    fn test_verify_chain_unsigned_dnskeys() {
+        let root = zone(".");
+        let other = zone(".");
+        let records = |signer: &Zone| {
+            vec![root.dnskey.clone(), other.dnskey.clone(), www(), sign(signer, &[www()])]
+        };
+
+        // the unsigned DNSKEY rrset only vouches for the anchored key
+        let verified = verify_chain(&records(&root), &trust_anchor(&root), NOW)
+            .expect("chain should verify");
+        assert!(verified.contains(&www()));
+        assert!(verified.contains(&root.dnskey));
+        assert!(!verified.contains(&other.dnskey));
+
+        assert!(verify_chain(&records(&other), &trust_anchor(&root), NOW).is_err());
+    }
+
+    /// An NSEC3 record of the example zone of RFC 5155, salt AABBCCDD, 12 iterations, opt-out
+    fn rfc5155_nsec3(owner: &str, next: &str, types: Vec<RecordType>) -> Record {
+        let next = base32hex::decode(next.to_uppercase().as_bytes()).unwrap();
+        Record::from_rdata(Name::parse(&format!("{}.example.", owner), None).unwrap(),
+                           3600,
+                           RecordType::NSEC3,
+                           RData::NSEC3(NSEC3::new(Nsec3HashAlgorithm::SHA1,
+                                                   true,
+                                                   12,
+                                                   vec![0xAA, 0xBB, 0xCC, 0xDD],
+                                                   next,
+                                                   types)))
+    }
+
+    /// [RFC 5155](https://tools.ietf.org/html/rfc5155#appendix-B.1), NSEC3, the records of the
+    ///  name error example, signed by a generated key for `example.`
+    #[test]
+    fn test_rfc5155_examples() {
+        let example = zone("example.");
+        let nsec3s = [rfc5155_nsec3("0p9mhaveqvm6t7vbl5lop2u3t2rp3tom",
+                                    "2t7b4g4vsa5smi47k61mv5bv1a22bojr",
+                                    vec![RecordType::MX,
+                                         RecordType::DNSKEY,
+                                         RecordType::NS,
+                                         RecordType::SOA,
+                                         RecordType::NSEC3PARAM,
+                                         RecordType::RRSIG]),
+                      rfc5155_nsec3("b4um86eghhds6nea196smvmlo4ors995",
+                                    "gjeqe526plbf1g8mklp59enfd789njgi",
+                                    vec![RecordType::MX, RecordType::RRSIG]),
+                      rfc5155_nsec3("35mthgpgcu1qg68fab165klnsnk3dpvl",
+                                    "b4um86eghhds6nea196smvmlo4ors995",
+                                    vec![RecordType::NS, RecordType::DS, RecordType::RRSIG])];
+
+        // the owners are the hashes of names of the zone
+        for &(name, nsec3) in &[("example.", &nsec3s[0]),
+                                 ("x.w.example.", &nsec3s[1]),
+                                 ("a.example.", &nsec3s[2])] {
+            let name = Name::parse(name, None).unwrap();
+            let hash = Nsec3HashAlgorithm::SHA1.hash(&[0xAA, 0xBB, 0xCC, 0xDD], &name, 12).unwrap();
+            assert_eq!(&hashed_owner_name(&hash, &example.name), nsec3.get_name());
+        }
+
+        let mut records = vec![example.dnskey.clone(), sign(&example, &[example.dnskey.clone()])];
+        for nsec3 in &nsec3s {
+            records.push(nsec3.clone());
+            records.push(sign(&example, &[nsec3.clone()]));
+        }
+
+        let verified = verify_chain(&records, &trust_anchor(&example), NOW)
+            .expect("example should verify");
+        for nsec3 in &nsec3s {
+            assert!(verified.contains(nsec3));
+        }
+
+        // the next hashed owner name is covered by the signature
+        records[2] = rfc5155_nsec3("0p9mhaveqvm6t7vbl5lop2u3t2rp3tom",
+                                   "2vptu5timamqttgl4luu9kg21e0aor3s",
+                                   vec![RecordType::MX,
+                                        RecordType::DNSKEY,
+                                        RecordType::NS,
+                                        RecordType::SOA,
+                                        RecordType::NSEC3PARAM,
+                                        RecordType::RRSIG]);
+        assert!(verify_chain(&records, &trust_anchor(&example), NOW).is_err());
+    }
+
+    /// Verifies the rrset of an RFC example, with its DNSKEY as the trust anchor
+    fn verify_example(dnskey: DNSKEY,
+                      rrset: Record,
+                      sig: SIG,
+                      now: u32)
+                      -> DnsSecResult<Vec<Record>> {
+        let mut trust_anchor = TrustAnchor::new();
+        trust_anchor.insert_trust_anchor(dnskey.get_public_key().to_vec());
+
+        let records = [Record::from_rdata(sig.get_signer_name().clone(),
+                                          3600,
+                                          RecordType::DNSKEY,
+                                          RData::DNSKEY(dnskey)),
+                       Record::from_rdata(rrset.get_name().clone(),
+                                          3600,
+                                          RecordType::RRSIG,
+                                          RData::SIG(sig)),
+                       rrset];
+        verify_chain(&records, &trust_anchor, now)
+    }
+
+    fn decode(encoded: &str) -> Vec<u8> {
+        base64::decode(encoded.as_bytes()).unwrap()
+    }
+
+    /// [RFC 5702](https://tools.ietf.org/html/rfc5702#section-6), RSA/SHA-2 for DNSSEC, examples
+    #[test]
+    fn test_rfc5702_examples() {
+        let examples = [(Algorithm::RSASHA256,
+                         9033,
+                         "AwEAAcFcGsaxxdgiuuGmCkVImy4h99CqT7jwY3pexPGcnUFtR2Fh36BponcwtkZ4cAg\
+                          tvd4Qs8PkxUdp6p/DlUmObdk=",
+                         "kRCOH6u7l0QGy9qpC9l1sLncJcOKFLJ7GhiUOibu4teYp5VE9RncriShZNz85mwlMgNEac\
+                          FYK/lPtPiVYP4bwg=="),
+                        (Algorithm::RSASHA512,
+                         3740,
+                         "AwEAAdHoNTOW+et86KuJOWRDp1pndvwb6Y83nSVXXyLA3DLroROUkN6X0O6pnWnjJQujX/Ay\
+                          hqFDxj13tOnD9u/1kTg7cV6rklMrZDtJCQ5PCl/D7QNPsgVsMu1J2Q8gpMpztNFLpPBz1bWXj\
+                          DtaR7ZQBlZ3PFY12ZTSncorffcGmhOL",
+                         "tsb4wnjRUDnB1BUi+t6TMTXThjVnG+eCkWqjvvjhzQL1d0YRoOe0CbxrVDYd0xDtsuJRae\
+                          Uw1ep94PzEWzr0iGYgZBWm/zpq+9fOuagYJRfDqfReKBzMweOLDiNa8iP5g9vMhpuv6OPlv\
+                          pXwm9Sa9ZXIbNl1MBGk0fthPgxdDLw=")];
+
+        let www = Record::from_rdata(Name::parse("www.example.net.", None).unwrap(),
+                                     3600,
+                                     RecordType::A,
+                                     RData::A(Ipv4Addr::new(192, 0, 2, 91)));
+        for &(algorithm, key_tag, public_key, signature) in &examples {
+            let dnskey = DNSKEY::new(true, false, false, algorithm, decode(public_key));
+            // valid from 20000101000000 to 20300101000000
+            let sig = |signature: Vec<u8>| {
+                SIG::new(RecordType::A,
+                         algorithm,
+                         3,
+                         3600,
+                         1893456000,
+                         946684800,
+                         key_tag,
+                         Name::parse("example.net.", None).unwrap(),
+                         signature)
+            };
+
+            let verified =
+                verify_example(dnskey.clone(), www.clone(), sig(decode(signature)), 1500000000)
+                    .expect("example should verify");
+            assert!(verified.contains(&www));
+
+            // outside of the validity period
+            assert!(verify_example(dnskey.clone(), www.clone(), sig(decode(signature)), 946684799)
+                .is_err());
+            assert!(verify_example(dnskey.clone(), www.clone(), sig(decode(signature)), 1893456001)
+                .is_err());
+
+            let mut tampered = decode(signature);
+            tampered[0] ^= 1;
+            assert!(verify_example(dnskey, www.clone(), sig(tampered), 1500000000).is_err());
+        }
+    }
+
+    /// [RFC 8080](https://tools.ietf.org/html/rfc8080#section-6), Ed25519 for DNSSEC, examples
+    #[cfg(feature = "ring")]
+    #[test]
+    fn test_rfc8080_examples() {
+        let examples = [(3613,
+                         "l02Woi0iS8Aa25FQkUd9RMzZHJpBoRQwAQEX1SxZJA4=",
+                         "3aa5ab37efce57f737fc1627013fee07bdf241bd10f3b1964ab55c78e79a304b",
+                         "oL9krJun7xfBOIWcGHi7mag5/hdZrKWw15jPGrHpjQeRAvTdszaPD+QLs3fx8A4M3e23mRZ9\
+                          VrbpMngwcrqNAg=="),
+                        (35217,
+                         "zPnZ/QwEe7S8C5SPz2OfS5RR40ATk2/rYnE9xHIEijs=",
+                         "401781b934e392de492ec77ae2e15d70f6575a1c0bc59c5275c04ebe80c6614c",
+                         "zXQ0bkYgQTEFyfLyi9QoiY6D8ZdYo4wyUhVioYZXFdT410QPRITQSqJSnzQoSm5poJ7gD7AQ\
+                          R0O7KuI5k2pcBg==")];
+
+        let origin = Name::parse("example.com.", None).unwrap();
+        let mx = Record::from_rdata(origin.clone(),
+                                    3600,
+                                    RecordType::MX,
+                                    RData::MX(MX::new(10,
+                                                      Name::parse("mail.example.com.", None)
+                                                          .unwrap())));
+        for &(key_tag, public_key, digest, signature) in &examples {
+            let dnskey = DNSKEY::new(true, true, false, Algorithm::ED25519, decode(public_key));
+            let digest: Vec<u8> = digest.as_bytes()
+                .chunks(2)
+                .map(|hex| u8::from_str_radix(str::from_utf8(hex).unwrap(), 16).unwrap())
+                .collect();
+            let ds = DS::new(key_tag, Algorithm::ED25519, DigestType::SHA256, digest);
+            assert!(ds.covers(&origin, &dnskey).unwrap());
+
+            let sig = SIG::new(RecordType::MX,
+                               Algorithm::ED25519,
+                               2,
+                               3600,
+                               1440021600,
+                               1438207200,
+                               key_tag,
+                               origin.clone(),
+                               decode(signature));
+            let verified = verify_example(dnskey.clone(), mx.clone(), sig.clone(), 1439000000)
+                .expect("example should verify");
+            assert!(verified.contains(&mx));
+
+            assert!(verify_example(dnskey, mx.clone(), sig, 1440021601).is_err());
+        }
+    }
+}
@@ -16,6 +16,8 @@
 
 //! domain name, aka labels, implementaton
 
+#[allow(unused_imports)]
+use std::ascii::AsciiExt;
 use std::ops::Index;
 use std::sync::Arc as Rc;
 use std::fmt;
@@ -110,6 +112,9 @@ impl Name {
 
     /// Creates a new Name with all labels lowercased
     ///
+    /// Per RFC 4343 only US-ASCII characters are case-folded, all other characters are left as
+    ///  they are, otherwise distinct labels (e.g. `\u{212A}` KELVIN SIGN and `k`) could collide.
+    ///
     /// # Examples
     ///
     /// ```
@@ -122,7 +127,7 @@ impl Name {
     pub fn to_lowercase(&self) -> Name {
         let mut new_labels = Vec::with_capacity(self.labels.len());
         for label in self.labels.iter() {
-            new_labels.push(label.to_lowercase());
+            new_labels.push(label.to_ascii_lowercase());
         }
 
         Self::with_labels(new_labels)
@@ -236,8 +241,15 @@ impl Name {
     /// let name = Name::parse("example.com.", None).unwrap();
     /// assert_eq!(name.base_name(), Name::new().label("com"));
     /// assert_eq!(*name[0], String::from("example"));
+    ///
+    /// assert!(Name::parse(".", None).unwrap().is_root());
     /// ```
     pub fn parse(local: &str, origin: Option<&Self>) -> ParseResult<Self> {
+        // the root has no labels, there is no empty label before the dot
+        if local == "." {
+            return Ok(Name::root());
+        }
+
         let mut name = Name::new();
         let mut label = String::new();
         // split the local part
@@ -281,11 +293,18 @@ impl Name {
                 }
                 ParseState::Escape3(i, ii) => {
                     if ch.is_numeric() {
-                        let val: u32 = (i << 16) + (ii << 8) +
+                        // \DDD is the decimal value of the octet, e.g. \046 is '.'
+                        let val: u32 = (i * 100) + (ii * 10) +
                                        try!(ch.to_digit(10)
                             .ok_or(ParseError::from(ParseErrorKind::Msg(format!("illegal char: \
                                                                                  {}",
                                                                                 ch)))));
+                        if val > 255 {
+                            return Err(ParseErrorKind::Msg(format!("escaped value out of range: \
+                                                                    {}",
+                                                                   val))
+                                .into());
+                        }
                         let new: char = try!(char::from_u32(val)
                             .ok_or(ParseError::from(ParseErrorKind::Msg(format!("illegal char: \
                                                                                  {}",
@@ -393,7 +412,7 @@ impl Name {
 
         for (l, r) in self_labels.iter().zip(other_labels.iter()) {
             if ignore_case {
-                match (*l).to_lowercase().cmp(&(*r).to_lowercase()) {
+                match (*l).to_ascii_lowercase().cmp(&(*r).to_ascii_lowercase()) {
                     o @ Ordering::Less |
                     o @ Ordering::Greater => return o,
                     Ordering::Equal => continue,
@@ -418,14 +437,87 @@ impl Name {
     pub fn to_string(&self) -> String {
         format!("{}", self)
     }
+
+    /// The mailbox name of the email address, as in the RNAME of an SOA or the mbox of an RP
+    ///
+    /// [RFC 1035, section 8](https://tools.ietf.org/html/rfc1035#section-8), the local part is
+    ///  the first label, dots and all, the domain the rest, so `john.doe@example.com` is
+    ///  `john\.doe.example.com.`. Either part may have characters other than ASCII, as in
+    ///  internationalized email, RFC 6530; the labels are kept as they are, UTF-8.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use trust_dns::rr::domain::Name;
+    ///
+    /// let mailbox = Name::from_email("john.doe@example.com").unwrap();
+    /// assert_eq!(mailbox.to_string(), "john\\.doe.example.com.");
+    /// assert_eq!(mailbox.to_email().unwrap(), "john.doe@example.com");
+    /// ```
+    pub fn from_email(email: &str) -> ParseResult<Self> {
+        let invalid = |reason: &str| {
+            ParseError::from(ParseErrorKind::Msg(format!("invalid email address {}: {}",
+                                                         email,
+                                                         reason)))
+        };
+
+        let mut parts = email.splitn(2, '@');
+        let local = parts.next().unwrap_or("");
+        let domain = try!(parts.next().ok_or_else(|| invalid("no '@'")));
+        let domain = domain.trim_right_matches('.');
+        if local.is_empty() || domain.is_empty() {
+            return Err(invalid("empty local part or domain"));
+        }
+        if domain.contains('@') {
+            return Err(invalid("more than one '@'"));
+        }
+
+        let mut labels = vec![local.to_string()];
+        for label in domain.split('.') {
+            if label.is_empty() {
+                return Err(invalid("empty label in the domain"));
+            }
+            labels.push(label.to_string());
+        }
+        if labels.iter().any(|label| label.len() > 63) {
+            return Err(invalid("label longer than 63 octets"));
+        }
+
+        Ok(Name::with_labels(labels))
+    }
+
+    /// The email address of the mailbox name, see `from_email()`
+    ///
+    /// An error if the name is not that of a mailbox: it has no domain, or has an `@`, the usual
+    ///  mistake of writing the email address itself, which is instead written with a `.`.
+    pub fn to_email(&self) -> ParseResult<String> {
+        if self.labels.len() < 2 {
+            return Err(ParseErrorKind::Msg(format!("mailbox has no domain: {}", self)).into());
+        }
+        if self.labels.iter().any(|label| label.contains('@')) {
+            return Err(ParseErrorKind::Msg(format!("mailbox has an '@', which is written as a \
+                                                    '.', e.g. hostmaster.example.com.: {}",
+                                                   self))
+                .into());
+        }
+        if self.labels[1..].iter().any(|label| label.contains('.')) {
+            return Err(ParseErrorKind::Msg(format!("mailbox domain has an escaped '.': {}", self))
+                .into());
+        }
+
+        let domain: Vec<&str> = self.labels[1..].iter().map(|label| label.as_str()).collect();
+        Ok(format!("{}@{}", self.labels[0], domain.join(".")))
+    }
 }
 
 impl Hash for Name {
     fn hash<H>(&self, state: &mut H)
         where H: Hasher
     {
+        // the length is included so that label boundaries are part of the hash, i.e. `a\.b` vs `a.b`
         for label in self.labels.iter() {
-            state.write(label.to_lowercase().as_bytes());
+            state.write_usize(label.len());
+            state.write(label.to_ascii_lowercase().as_bytes());
         }
     }
 }
@@ -449,6 +541,9 @@ impl BinSerializable<Name> for Name {
     ///  all names will be stored lowercase internally.
     /// This will consume the portions of the Vec which it is reading...
     fn read(decoder: &mut BinDecoder) -> DecodeResult<Name> {
+        try!(decoder.count_name());
+        let name_at = decoder.index();
+
         let mut state: LabelParseState = LabelParseState::LabelLengthOrPointer;
         let mut labels: Vec<Rc<String>> = Vec::with_capacity(3); // most labels will be around three, e.g. www.example.com
 
@@ -499,6 +594,15 @@ impl BinSerializable<Name> for Name {
                 // etc.
                 LabelParseState::Pointer => {
                     let location = try!(decoder.read_u16()) & 0x3FFF; // get rid of the two high order bits
+
+                    // pointers may only refer to names before the start of this one, each hop
+                    //  goes further back, this prevents pointer loops
+                    if location as usize >= name_at {
+                        return Err(DecodeErrorKind::Message("label pointer does not point to \
+                                                             a prior name")
+                            .into());
+                    }
+
                     let mut pointer = decoder.clone(location);
                     let pointed = try!(Name::read(&mut pointer));
 
@@ -526,11 +630,21 @@ impl BinSerializable<Name> for Name {
     }
 }
 
-/// FIXME: this needs to escape characters in the labels.
+/// Labels are escaped such that the output can be read back with `Name::parse`, e.g. a `.` within
+///  a label is written as `\.`, and control characters as `\DDD`.
 impl fmt::Display for Name {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         for label in &*self.labels {
-            try!(write!(f, "{}.", label));
+            for ch in label.chars() {
+                match ch {
+                    '.' | '\\' | '(' | ')' | ';' | '"' => try!(write!(f, "\\{}", ch)),
+                    ch if (ch as u32) <= 0x20 || ch as u32 == 0x7F => {
+                        try!(write!(f, "\\{:03}", ch as u32))
+                    }
+                    ch => try!(write!(f, "{}", ch)),
+                }
+            }
+            try!(write!(f, "."));
         }
         if self.is_root() {
             try!(write!(f, "."));
@@ -681,6 +795,23 @@ mod tests {
         assert_eq!(fourth, r_test);
     }
 
+    #[test]
+    fn test_pointer_counts_names() {
+        let mut bytes: Vec<u8> = Vec::with_capacity(512);
+        {
+            let mut e = BinEncoder::new(&mut bytes);
+            Name::new().label("ra").label("rb").emit(&mut e).unwrap();
+            Name::new().label("rb").emit(&mut e).unwrap();
+        }
+
+        // the name behind the pointer is read as well
+        let mut limits = DecodeLimits::default();
+        limits.max_names = 2;
+        let mut d = BinDecoder::with_limits(&bytes, limits);
+        assert!(Name::read(&mut d).is_ok());
+        assert!(Name::read(&mut d).is_err());
+    }
+
     #[test]
     fn test_base_name() {
         let zone = Name::new().label("example").label("com");
@@ -760,6 +891,71 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_escaped_label_not_split() {
+        let escaped = Name::parse("a\\046b.example.", None).unwrap();
+        let split = Name::parse("a.b.example.", None).unwrap();
+
+        assert_eq!(escaped.num_labels(), 2);
+        assert_eq!(&escaped[0], "a.b");
+        assert!(escaped != split);
+        assert_eq!(escaped.to_string(), "a\\.b.example.");
+        assert_eq!(Name::parse(&escaped.to_string(), None).unwrap(), escaped);
+
+        assert!(Name::parse("a\\256b.example.", None).is_err());
+    }
+
+    #[test]
+    fn test_hash_label_boundaries() {
+        use std::collections::hash_map::DefaultHasher;
+        use std::hash::{Hash, Hasher};
+
+        let hash = |name: &Name| {
+            let mut hasher = DefaultHasher::new();
+            name.hash(&mut hasher);
+            hasher.finish()
+        };
+
+        let escaped = Name::parse("a\\.b.example.", None).unwrap();
+        let split = Name::parse("a.b.example.", None).unwrap();
+        assert!(hash(&escaped) != hash(&split));
+        assert_eq!(hash(&split), hash(&Name::parse("A.b.EXAMPLE.", None).unwrap()));
+    }
+
+    #[test]
+    fn test_non_ascii_case() {
+        // KELVIN SIGN lowercases to 'k' in unicode, but must not match in DNS
+        let kelvin = Name::new().label("\u{212A}").label("example");
+        let k = Name::new().label("k").label("example");
+
+        assert!(kelvin != k);
+        assert_eq!(kelvin.to_lowercase(), kelvin);
+    }
+
+    #[test]
+    fn test_email() {
+        let mailbox = Name::from_email("john.doe@example.com.").unwrap();
+        assert_eq!(mailbox,
+                   Name::with_labels(vec!["john.doe".to_string(),
+                                          "example".to_string(),
+                                          "com".to_string()]));
+        assert_eq!(mailbox.to_email().unwrap(), "john.doe@example.com");
+
+        // internationalized, RFC 6530
+        let mailbox = Name::from_email("δοκιμή@παράδειγμα.δοκιμή").unwrap();
+        assert_eq!(mailbox.to_email().unwrap(), "δοκιμή@παράδειγμα.δοκιμή");
+
+        assert!(Name::from_email("example.com").is_err());
+        assert!(Name::from_email("@example.com").is_err());
+        assert!(Name::from_email("a@b@example.com").is_err());
+        assert!(Name::from_email("admin@example..com").is_err());
+
+        assert!(Name::parse("hostmaster@example.com.", None).unwrap().to_email().is_err());
+        assert!(Name::parse("com.", None).unwrap().to_email().is_err());
+        assert!(Name::root().to_email().is_err());
+        assert!(Name::parse("admin.example\\.com.", None).unwrap().to_email().is_err());
+    }
+
     #[test]
     fn test_cmp_ignore_case() {
         let root = Some(Name::with_labels(vec![]));
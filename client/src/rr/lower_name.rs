@@ -0,0 +1,95 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! canonical, lowercase, name for use as a key in caches and lookups
+
+use std::fmt;
+use std::ops::Deref;
+
+use rr::Name;
+
+/// A `Name` which has been normalized to lowercase, for use as a key in caches and lookups.
+///
+/// Only US-ASCII characters are lowercased (RFC 4343), and labels are kept distinct, such that
+///  `a\046b.example.` (a single `a.b` label) never matches `a.b.example.`.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord)]
+pub struct LowerName(Name);
+
+impl LowerName {
+    /// Normalizes the name into a new `LowerName`
+    pub fn new(name: &Name) -> Self {
+        LowerName(name.to_lowercase())
+    }
+
+    /// Returns the inner, already lowercased, `Name`
+    pub fn into_name(self) -> Name {
+        self.0
+    }
+}
+
+impl From<Name> for LowerName {
+    fn from(name: Name) -> Self {
+        LowerName::new(&name)
+    }
+}
+
+impl<'a> From<&'a Name> for LowerName {
+    fn from(name: &'a Name) -> Self {
+        LowerName::new(name)
+    }
+}
+
+impl Deref for LowerName {
+    type Target = Name;
+
+    fn deref(&self) -> &Name {
+        &self.0
+    }
+}
+
+impl PartialEq<Name> for LowerName {
+    fn eq(&self, other: &Name) -> bool {
+        self.0 == *other
+    }
+}
+
+impl fmt::Display for LowerName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    #[test]
+    fn test_lowercase() {
+        let name = Name::parse("WWW.Example.COM.", None).unwrap();
+        let lower = LowerName::from(&name);
+
+        assert_eq!(lower.to_string(), "www.example.com.");
+        assert_eq!(lower, name);
+    }
+
+    #[test]
+    fn test_escaped_keys_distinct() {
+        let mut map = HashMap::new();
+
+        map.insert(LowerName::from(Name::parse("a\\046b.example.", None).unwrap()), 1);
+        map.insert(LowerName::from(Name::parse("a.b.example.", None).unwrap()), 2);
+        map.insert(LowerName::from(Name::parse("A.B.Example.", None).unwrap()), 3);
+
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.get(&LowerName::from(Name::parse("a\\.b.example.", None).unwrap())),
+                   Some(&1));
+        assert_eq!(map.get(&LowerName::from(Name::parse("a.b.example.", None).unwrap())),
+                   Some(&3));
+    }
+}
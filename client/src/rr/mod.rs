@@ -19,8 +19,10 @@
 pub mod dns_class;
 pub mod dnssec;
 pub mod domain;
+pub mod lower_name;
 pub mod rdata;
 pub mod record_data;
+mod record_meta;
 pub mod record_type;
 pub mod resource;
 mod rr_key;
@@ -28,12 +30,15 @@ mod rr_set;
 
 pub use self::domain::Name;
 pub use self::dns_class::DNSClass;
+pub use self::lower_name::LowerName;
 pub use self::record_data::RData;
+pub use self::record_meta::{RecordMeta, RecordMetaTable};
 pub use self::record_type::RecordType;
 pub use self::resource::Record;
 pub use self::rr_key::RrKey;
 pub use self::rr_set::IntoRecordSet;
-pub use self::rr_set::RecordSet;
+pub use self::rr_set::{RecordSet, RecordSetLimits, RecordSetLimitError};
+pub use self::rr_set::{DEFAULT_MAX_RRSET_BYTES, DEFAULT_MAX_RRSET_RECORDS};
 
 #[deprecated = "will be removed post 0.9.x, use RecordSet"]
 pub type RrSet = RecordSet;
@@ -248,6 +248,15 @@ pub fn read(decoder: &mut BinDecoder, rdata_length: u16) -> DecodeResult<DNSKEY>
     Ok(DNSKEY::new(zone_key, secure_entry_point, revoke, algorithm, public_key))
 }
 
+/// Checks that there is a public key
+pub fn validate(dnskey: &DNSKEY) -> DecodeResult<()> {
+    if dnskey.public_key.is_empty() {
+        return Err(DecodeErrorKind::InvalidRData("DNSKEY has no public key".to_string()).into());
+    }
+
+    Ok(())
+}
+
 pub fn emit(encoder: &mut BinEncoder, rdata: &DNSKEY) -> EncodeResult {
     let mut flags: u16 = 0;
     if rdata.is_zone_key() {
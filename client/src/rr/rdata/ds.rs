@@ -182,6 +182,27 @@ pub fn read(decoder: &mut BinDecoder, rdata_length: u16) -> DecodeResult<DS> {
     Ok(DS::new(key_tag, algorithm, digest_type, digest))
 }
 
+/// Checks that the digest is as long as the output of the digest type
+pub fn validate(ds: &DS) -> DecodeResult<()> {
+    let expected = match ds.digest_type {
+        DigestType::SHA1 => 20,
+        DigestType::SHA256 => 32,
+        DigestType::SHA384 => 48,
+        DigestType::SHA512 => 64,
+        DigestType::ED25519 => return Ok(()),
+    };
+
+    if ds.digest.len() != expected {
+        return Err(DecodeErrorKind::InvalidRData(format!("DS digest of {} bytes, {:?} is {}",
+                                                         ds.digest.len(),
+                                                         ds.digest_type,
+                                                         expected))
+            .into());
+    }
+
+    Ok(())
+}
+
 pub fn emit(encoder: &mut BinEncoder, rdata: &DS) -> EncodeResult {
     try!(encoder.emit_u16(rdata.get_key_tag()));
     try!(rdata.get_algorithm().emit(encoder)); // always 3 for now
@@ -227,3 +248,11 @@ pub fn test_covers() {
 
     assert!(ds_rdata.covers(&name, &dnskey_rdata).unwrap());
 }
+
+#[test]
+pub fn test_validate() {
+    assert!(validate(&DS::new(0xF00F, Algorithm::RSASHA256, DigestType::SHA256, vec![0; 32]))
+        .is_ok());
+    assert!(validate(&DS::new(0xF00F, Algorithm::RSASHA256, DigestType::SHA256, vec![0; 20]))
+        .is_err());
+}
@@ -0,0 +1,552 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! location record, the geographic position of the owner
+
+use std::fmt;
+
+use ::serialize::txt::*;
+use ::serialize::binary::*;
+use ::error::*;
+
+/// the latitude and longitude of the equator and the prime meridian, in thousandths of a second of
+///  arc
+const EQUATOR: u32 = 1 << 31;
+/// the altitude of the WGS 84 reference spheroid, in centimeters above the base of the altitude
+const SPHEROID: u32 = 10_000_000;
+
+/// 1m
+const DEFAULT_SIZE: u8 = 0x12;
+/// 10,000m
+const DEFAULT_HORIZ_PRE: u8 = 0x16;
+/// 10m
+const DEFAULT_VERT_PRE: u8 = 0x13;
+
+/// [RFC 1876, A Means for Expressing Location Information in the Domain Name System, January 1996](https://tools.ietf.org/html/rfc1876#section-2)
+///
+/// ```text
+/// 2. RDATA Format
+///
+///        MSB                                           LSB
+///        +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+///       0|        VERSION        |         SIZE          |
+///        +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+///       2|       HORIZ PRE       |       VERT PRE        |
+///        +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+///       4|                   LATITUDE                    |
+///        +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+///       6|                   LATITUDE                    |
+///        +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+///       8|                   LONGITUDE                   |
+///        +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+///      10|                   LONGITUDE                   |
+///        +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+///      12|                   ALTITUDE                    |
+///        +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+///      14|                   ALTITUDE                    |
+///        +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+///
+/// VERSION      Version number of the representation.  This must be zero.
+///              Implementations are required to check this field and make
+///              no assumptions about the format of unrecognized versions.
+/// ```
+///
+/// Only version 0 is decoded, `RData::read` keeps other versions opaque.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct LOC {
+    size: u8,
+    horiz_pre: u8,
+    vert_pre: u8,
+    latitude: u32,
+    longitude: u32,
+    altitude: u32,
+}
+
+impl LOC {
+    /// Creates a new LOC record data, all the values as they are encoded
+    pub fn new(size: u8,
+               horiz_pre: u8,
+               vert_pre: u8,
+               latitude: u32,
+               longitude: u32,
+               altitude: u32)
+               -> LOC {
+        LOC {
+            size: size,
+            horiz_pre: horiz_pre,
+            vert_pre: vert_pre,
+            latitude: latitude,
+            longitude: longitude,
+            altitude: altitude,
+        }
+    }
+
+    /// ```text
+    /// SIZE         The diameter of a sphere enclosing the described entity, in
+    ///              centimeters, expressed as a pair of four-bit unsigned
+    ///              integers, each ranging from zero to nine, with the most
+    ///              significant four bits representing the base and the second
+    ///              number representing the power of ten by which to multiply
+    ///              the base.
+    /// ```
+    pub fn get_size(&self) -> u8 {
+        self.size
+    }
+
+    /// ```text
+    /// HORIZ PRE    The horizontal precision of the data, in centimeters,
+    ///              expressed using the same representation as SIZE.
+    /// ```
+    pub fn get_horiz_pre(&self) -> u8 {
+        self.horiz_pre
+    }
+
+    /// ```text
+    /// VERT PRE     The vertical precision of the data, in centimeters,
+    ///              expressed using the sane representation as for SIZE.
+    /// ```
+    pub fn get_vert_pre(&self) -> u8 {
+        self.vert_pre
+    }
+
+    /// ```text
+    /// LATITUDE     The latitude of the center of the sphere described by the
+    ///              SIZE field, expressed as a 32-bit integer, most significant
+    ///              octet first (network standard byte order), in thousandths
+    ///              of a second of arc.  2^31 represents the equator; numbers
+    ///              above that are north latitude.
+    /// ```
+    pub fn get_latitude(&self) -> u32 {
+        self.latitude
+    }
+
+    /// ```text
+    /// LONGITUDE    The longitude of the center of the sphere described by the
+    ///              SIZE field, expressed as a 32-bit integer, most significant
+    ///              octet first (network standard byte order), in thousandths
+    ///              of a second of arc, rounded away from the prime meridian.
+    ///              2^31 represents the prime meridian; numbers above that are
+    ///              east longitude.
+    /// ```
+    pub fn get_longitude(&self) -> u32 {
+        self.longitude
+    }
+
+    /// ```text
+    /// ALTITUDE     The altitude of the center of the sphere described by the
+    ///              SIZE field, expressed as a 32-bit integer, most significant
+    ///              octet first (network standard byte order), in centimeters,
+    ///              from a base of 100,000m below the [WGS 84] reference
+    ///              spheroid used by GPS (semimajor axis a=6378137.0,
+    ///              reciprocal flattening rf=298.257223563).
+    /// ```
+    pub fn get_altitude(&self) -> u32 {
+        self.altitude
+    }
+
+    /// Creates a new LOC record data from decimal degrees, north and east are positive, and the
+    ///  altitude in meters above the WGS 84 reference spheroid, with the default size of 1m,
+    ///  horizontal precision of 10,000m and vertical precision of 10m
+    ///
+    /// # Return value
+    ///
+    /// None if the latitude is beyond the poles, the longitude beyond 180 degrees, or the altitude
+    ///  is out of the range of the encoding, about -100km to 42,849km
+    pub fn from_degrees(latitude: f64, longitude: f64, altitude: f64) -> Option<LOC> {
+        // written so that NaN is out of range too
+        if !(latitude.abs() <= 90.0 && longitude.abs() <= 180.0) {
+            return None;
+        }
+
+        let altitude = (altitude * 100.0).round() + SPHEROID as f64;
+        if !(altitude >= 0.0 && altitude <= u32::max_value() as f64) {
+            return None;
+        }
+
+        Some(LOC::new(DEFAULT_SIZE,
+                      DEFAULT_HORIZ_PRE,
+                      DEFAULT_VERT_PRE,
+                      to_angle(latitude),
+                      to_angle(longitude),
+                      altitude as u32))
+    }
+
+    /// The latitude and longitude in decimal degrees, north and east are positive, and the
+    ///  altitude in meters above the WGS 84 reference spheroid
+    pub fn to_decimal_degrees(&self) -> (f64, f64, f64) {
+        (from_angle(self.latitude),
+         from_angle(self.longitude),
+         (self.altitude as f64 - SPHEROID as f64) / 100.0)
+    }
+
+    /// The size, the diameter of the sphere enclosing the entity, in meters
+    pub fn get_size_meters(&self) -> f64 {
+        precision_to_centimeters(self.size) as f64 / 100.0
+    }
+
+    /// The horizontal precision in meters
+    pub fn get_horiz_pre_meters(&self) -> f64 {
+        precision_to_centimeters(self.horiz_pre) as f64 / 100.0
+    }
+
+    /// The vertical precision in meters
+    pub fn get_vert_pre_meters(&self) -> f64 {
+        precision_to_centimeters(self.vert_pre) as f64 / 100.0
+    }
+
+    /// Sets the size and precisions, in meters, each is truncated to one significant digit as
+    ///  it is encoded
+    ///
+    /// # Return value
+    ///
+    /// None if any is negative or greater than 90,000km, the largest which can be encoded
+    pub fn with_precision(mut self, size: f64, horiz_pre: f64, vert_pre: f64) -> Option<LOC> {
+        self.size = match centimeters_to_precision((size * 100.0).round()) {
+            Some(size) => size,
+            None => return None,
+        };
+        self.horiz_pre = match centimeters_to_precision((horiz_pre * 100.0).round()) {
+            Some(horiz_pre) => horiz_pre,
+            None => return None,
+        };
+        self.vert_pre = match centimeters_to_precision((vert_pre * 100.0).round()) {
+            Some(vert_pre) => vert_pre,
+            None => return None,
+        };
+        Some(self)
+    }
+}
+
+/// decimal degrees as thousandths of a second from the equator or prime meridian
+fn to_angle(degrees: f64) -> u32 {
+    (EQUATOR as i64 + (degrees * 3_600_000.0).round() as i64) as u32
+}
+
+/// thousandths of a second from the equator or prime meridian as decimal degrees
+fn from_angle(angle: u32) -> f64 {
+    (angle as i64 - EQUATOR as i64) as f64 / 3_600_000.0
+}
+
+/// the size or a precision as centimeters, the base times ten to the power of the exponent
+fn precision_to_centimeters(precision: u8) -> u64 {
+    (precision >> 4) as u64 * 10u64.pow((precision & 0x0F) as u32)
+}
+
+/// centimeters as the base and the power of ten of the size or a precision, truncated to one
+///  significant digit, None if negative or beyond 9e9
+fn centimeters_to_precision(centimeters: f64) -> Option<u8> {
+    // written so that NaN is out of range too
+    if !(centimeters >= 0.0 && centimeters < 10_000_000_000.0) {
+        return None;
+    }
+
+    let mut base = centimeters as u64;
+    let mut exponent: u8 = 0;
+    while base > 9 {
+        base /= 10;
+        exponent += 1;
+    }
+
+    Some(((base as u8) << 4) | exponent)
+}
+
+pub fn read(decoder: &mut BinDecoder) -> DecodeResult<LOC> {
+    let version = try!(decoder.read_u8());
+    if version != 0 {
+        return Err(DecodeErrorKind::Msg(format!("unsupported LOC version: {}", version)).into());
+    }
+
+    Ok(LOC::new(try!(decoder.read_u8()),
+                try!(decoder.read_u8()),
+                try!(decoder.read_u8()),
+                try!(decoder.read_u32()),
+                try!(decoder.read_u32()),
+                try!(decoder.read_u32())))
+}
+
+pub fn emit(encoder: &mut BinEncoder, loc: &LOC) -> EncodeResult {
+    try!(encoder.emit(0));
+    try!(encoder.emit(loc.get_size()));
+    try!(encoder.emit(loc.get_horiz_pre()));
+    try!(encoder.emit(loc.get_vert_pre()));
+    try!(encoder.emit_u32(loc.get_latitude()));
+    try!(encoder.emit_u32(loc.get_longitude()));
+    try!(encoder.emit_u32(loc.get_altitude()));
+    Ok(())
+}
+
+/// [RFC 1876](https://tools.ietf.org/html/rfc1876#section-3), Master File Format
+///
+/// ```text
+/// The LOC record is expressed in a master file in the following format:
+///
+/// <owner> <TTL> <class> LOC ( d1 [m1 [s1]] {"N"|"S"} d2 [m2 [s2]]
+///                             {"E"|"W"} alt["m"] [siz["m"] [hp["m"]
+///                             [vp["m"]]]] )
+///
+/// If omitted, minutes and seconds default to zero, size defaults to 1m,
+/// horizontal precision defaults to 10000m, and vertical precision
+/// defaults to 10m.
+/// ```
+pub fn parse(tokens: &Vec<Token>) -> ParseResult<LOC> {
+    let mut words: Vec<&str> = Vec::new();
+    for t in tokens {
+        match *t {
+            Token::CharData(ref s) => words.push(s),
+            Token::List(ref v) => words.extend(v.iter().map(|s| s as &str)),
+            _ => return Err(ParseErrorKind::UnexpectedToken(t.clone()).into()),
+        }
+    }
+    let mut words = words.into_iter();
+
+    let latitude = try!(parse_angle(&mut words, "N", "S", 90));
+    let longitude = try!(parse_angle(&mut words, "E", "W", 180));
+
+    let altitude = try!(words.next()
+        .ok_or(ParseError::from(ParseErrorKind::MissingToken("altitude".to_string())))
+        .and_then(parse_meters));
+    if altitude < -(SPHEROID as i64) || altitude > (u32::max_value() - SPHEROID) as i64 {
+        return Err(ParseErrorKind::Msg(format!("altitude out of range: {}", altitude)).into());
+    }
+
+    let size = match words.next() {
+        Some(word) => try!(parse_precision(word)),
+        None => DEFAULT_SIZE,
+    };
+    let horiz_pre = match words.next() {
+        Some(word) => try!(parse_precision(word)),
+        None => DEFAULT_HORIZ_PRE,
+    };
+    let vert_pre = match words.next() {
+        Some(word) => try!(parse_precision(word)),
+        None => DEFAULT_VERT_PRE,
+    };
+
+    if let Some(word) = words.next() {
+        return Err(ParseErrorKind::Msg(format!("unexpected LOC data: {}", word)).into());
+    }
+
+    Ok(LOC::new(size,
+                horiz_pre,
+                vert_pre,
+                latitude,
+                longitude,
+                (altitude + SPHEROID as i64) as u32))
+}
+
+/// degrees, optionally minutes and seconds, then the hemisphere, as thousandths of a second from
+///  the equator or prime meridian
+fn parse_angle<'a, I>(words: &mut I,
+                      positive: &str,
+                      negative: &str,
+                      max_degrees: i64)
+                      -> ParseResult<u32>
+    where I: Iterator<Item = &'a str>
+{
+    let mut parts: Vec<i64> = Vec::with_capacity(3);
+    let sign: i64;
+    loop {
+        let word = try!(words.next().ok_or(ParseError::from(ParseErrorKind::MissingToken(
+            format!("{} or {}", positive, negative)))));
+        let upper = word.to_uppercase();
+        if upper == positive {
+            sign = 1;
+            break;
+        } else if upper == negative {
+            sign = -1;
+            break;
+        } else if parts.len() == 2 {
+            // seconds, to the thousandth
+            parts.push(try!(parse_fixed(word, 3)));
+        } else if parts.len() < 2 {
+            parts.push(try!(word.parse::<u32>()) as i64);
+        } else {
+            return Err(ParseErrorKind::Msg(format!("expected {} or {}: {}",
+                                                   positive,
+                                                   negative,
+                                                   word))
+                .into());
+        }
+    }
+
+    let degrees = try!(parts.get(0)
+        .cloned()
+        .ok_or(ParseError::from(ParseErrorKind::MissingToken("degrees".to_string()))));
+    let minutes = parts.get(1).cloned().unwrap_or(0);
+    let seconds = parts.get(2).cloned().unwrap_or(0);
+    let thousandths = ((degrees * 60 + minutes) * 60) * 1000 + seconds;
+    if minutes >= 60 || seconds < 0 || seconds >= 60_000 || thousandths > max_degrees * 3_600_000 {
+        return Err(ParseErrorKind::Msg(format!("{} angle out of range: {:?}",
+                                               positive,
+                                               parts))
+            .into());
+    }
+
+    Ok((EQUATOR as i64 + sign * thousandths) as u32)
+}
+
+/// a decimal number with at most `decimals` digits after the point, as an integer of that scale,
+///  e.g. 1.5 with two decimals is 150
+fn parse_fixed(word: &str, decimals: usize) -> ParseResult<i64> {
+    let (negative, number) = if word.starts_with('-') {
+        (true, &word[1..])
+    } else {
+        (false, word)
+    };
+    let (whole, fraction) = match number.find('.') {
+        Some(dot) => (&number[..dot], &number[dot + 1..]),
+        None => (number, ""),
+    };
+    if fraction.len() > decimals || !fraction.chars().all(|ch| ch.is_digit(10)) {
+        return Err(ParseErrorKind::Msg(format!("invalid number: {}", word)).into());
+    }
+
+    let mut value = try!(whole.parse::<u32>()) as i64;
+    for index in 0..decimals {
+        value = value * 10 + fraction.as_bytes().get(index).map_or(0, |b| (b - b'0') as i64);
+    }
+
+    Ok(if negative { -value } else { value })
+}
+
+/// meters, with an optional `m`, as centimeters
+fn parse_meters(word: &str) -> ParseResult<i64> {
+    parse_fixed(word.trim_right_matches(|ch| ch == 'm' || ch == 'M'), 2)
+}
+
+/// the size or a precision, in meters, as the base and the power of ten of the centimeters,
+///  truncated to one significant digit
+fn parse_precision(word: &str) -> ParseResult<u8> {
+    let centimeters = try!(parse_meters(word));
+    centimeters_to_precision(centimeters as f64).ok_or_else(|| {
+        ParseErrorKind::Msg(format!("precision out of range: {}", word)).into()
+    })
+}
+
+/// The master file format, with the seconds to the thousandth and all the precisions, e.g.
+///  `42 21 54.000 N 71 6 18.000 W -24m 30m 10000m 10m`
+impl fmt::Display for LOC {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(write_angle(f, self.latitude, "N", "S"));
+        try!(write!(f, " "));
+        try!(write_angle(f, self.longitude, "E", "W"));
+        try!(write!(f, " "));
+        try!(write_meters(f, self.altitude as i64 - SPHEROID as i64));
+        for &precision in &[self.size, self.horiz_pre, self.vert_pre] {
+            try!(write!(f, " "));
+            try!(write_meters(f, precision_to_centimeters(precision) as i64));
+        }
+        Ok(())
+    }
+}
+
+fn write_angle(f: &mut fmt::Formatter, angle: u32, positive: &str, negative: &str) -> fmt::Result {
+    let (thousandths, hemisphere) = if angle >= EQUATOR {
+        (angle - EQUATOR, positive)
+    } else {
+        (EQUATOR - angle, negative)
+    };
+
+    write!(f,
+           "{} {} {}.{:03} {}",
+           thousandths / 3_600_000,
+           thousandths / 60_000 % 60,
+           thousandths / 1000 % 60,
+           thousandths % 1000,
+           hemisphere)
+}
+
+fn write_meters(f: &mut fmt::Formatter, centimeters: i64) -> fmt::Result {
+    let sign = if centimeters < 0 { "-" } else { "" };
+    let centimeters = centimeters.abs();
+    if centimeters % 100 == 0 {
+        write!(f, "{}{}m", sign, centimeters / 100)
+    } else {
+        write!(f, "{}{}.{:02}m", sign, centimeters / 100, centimeters % 100)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ::serialize::txt::*;
+    use ::serialize::binary::*;
+    use super::*;
+
+    fn tokens(text: &str) -> Vec<Token> {
+        text.split_whitespace().map(|word| Token::CharData(word.to_string())).collect()
+    }
+
+    #[test]
+    fn test_parse() {
+        // RFC 1876 section 4
+        let loc = parse(&tokens("42 21 54 N 71 06 18 W -24m 30m")).unwrap();
+        assert_eq!(loc,
+                   LOC::new(0x33, 0x16, 0x13, 2_299_997_648, 1_891_505_648, 9_997_600));
+        assert_eq!(loc.to_string(),
+                   "42 21 54.000 N 71 6 18.000 W -24m 30m 10000m 10m");
+        assert_eq!(parse(&tokens(&loc.to_string())).unwrap(), loc);
+
+        let loc = parse(&tokens("52 22 23.5 n 4 53 32.25 e 2.50m 10m 100m 2m")).unwrap();
+        assert_eq!(loc.to_string(),
+                   "52 22 23.500 N 4 53 32.250 E 2.50m 10m 100m 2m");
+
+        assert_eq!(parse(&tokens("0 N 0 E 0")).unwrap().to_string(),
+                   "0 0 0.000 N 0 0 0.000 E 0m 1m 10000m 10m");
+
+        assert!(parse(&tokens("91 N 0 E 0m")).is_err());
+        assert!(parse(&tokens("42 60 N 0 E 0m")).is_err());
+        assert!(parse(&tokens("42 N 0 E")).is_err());
+        assert!(parse(&tokens("42 21 54 N 71 06 18 W -24m 30m 1m 1m 1m")).is_err());
+    }
+
+    #[test]
+    fn test_read() {
+        let rdata = parse(&tokens("42 21 54 N 71 06 18 W -24m 30m")).unwrap();
+
+        let mut bytes = Vec::new();
+        {
+            let mut encoder: BinEncoder = BinEncoder::new(&mut bytes);
+            assert!(emit(&mut encoder, &rdata).is_ok());
+        }
+        assert_eq!(bytes.len(), 16);
+
+        let read_rdata = read(&mut BinDecoder::new(&bytes));
+        assert_eq!(rdata, read_rdata.unwrap());
+
+        bytes[0] = 1;
+        assert!(read(&mut BinDecoder::new(&bytes)).is_err());
+    }
+
+    #[test]
+    fn test_degrees() {
+        let loc = parse(&tokens("42 21 54 N 71 06 18 W -24m 30m")).unwrap();
+        let (latitude, longitude, altitude) = loc.to_decimal_degrees();
+        assert!((latitude - 42.365).abs() < 1e-9);
+        assert!((longitude + 71.105).abs() < 1e-9);
+        assert_eq!(altitude, -24.0);
+        assert_eq!(loc.get_size_meters(), 30.0);
+        assert_eq!(loc.get_horiz_pre_meters(), 10_000.0);
+        assert_eq!(loc.get_vert_pre_meters(), 10.0);
+
+        let from_degrees = LOC::from_degrees(42.365, -71.105, -24.0)
+            .unwrap()
+            .with_precision(30.0, 10_000.0, 10.0)
+            .unwrap();
+        assert_eq!(from_degrees, loc);
+
+        // truncated to one significant digit
+        let loc = LOC::from_degrees(0.0, 0.0, 0.0).unwrap().with_precision(0.5, 25.0, 99.0);
+        assert_eq!(loc.unwrap().to_string(),
+                   "0 0 0.000 N 0 0 0.000 E 0m 0.50m 20m 90m");
+
+        assert!(LOC::from_degrees(90.5, 0.0, 0.0).is_none());
+        assert!(LOC::from_degrees(0.0, -180.5, 0.0).is_none());
+        assert!(LOC::from_degrees(0.0, 0.0, -100_001.0).is_none());
+        assert!(LOC::from_degrees(::std::f64::NAN, 0.0, 0.0).is_none());
+        assert!(LOC::from_degrees(0.0, 0.0, 0.0).unwrap().with_precision(-1.0, 1.0, 1.0).is_none());
+    }
+}
@@ -23,6 +23,7 @@ pub mod a;
 pub mod aaaa;
 pub mod ds;
 pub mod dnskey;
+pub mod loc;
 pub mod mx;
 pub mod name;
 pub mod null;
@@ -30,20 +31,29 @@ pub mod nsec;
 pub mod nsec3;
 pub mod nsec3param;
 pub mod opt;
+pub mod rp;
 pub mod sig;
 pub mod soa;
 pub mod srv;
+pub mod svcb;
+pub mod tlsa;
+pub mod tsig;
 pub mod txt;
 
 pub use self::dnskey::DNSKEY;
 pub use self::ds::DS;
+pub use self::loc::LOC;
 pub use self::mx::MX;
 pub use self::nsec::NSEC;
 pub use self::nsec3::NSEC3;
 pub use self::nsec3param::NSEC3PARAM;
 pub use self::null::NULL;
 pub use self::opt::OPT;
+pub use self::rp::RP;
 pub use self::sig::SIG;
 pub use self::srv::SRV;
 pub use self::soa::SOA;
+pub use self::svcb::SVCB;
+pub use self::tlsa::TLSA;
+pub use self::tsig::TSIG;
 pub use self::txt::TXT;
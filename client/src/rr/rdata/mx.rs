@@ -78,6 +78,27 @@ pub fn read(decoder: &mut BinDecoder) -> DecodeResult<MX> {
     Ok(MX::new(try!(decoder.read_u16()), try!(Name::read(decoder))))
 }
 
+/// [RFC 7505](https://tools.ietf.org/html/rfc7505#section-3), A "Null MX" No Service Resource
+///  Record, June 2015
+///
+/// ```text
+/// 3.  The Null MX Resource Record
+///
+///    The null MX RR is an ordinary MX record with an RDATA section
+///    consisting of preference number 0 and a zero-length label, written
+///    in master files as ".", as the exchange domain, to denote that there
+///    exists no mail exchanger for a domain.
+/// ```
+pub fn validate(mx: &MX) -> DecodeResult<()> {
+    if mx.exchange.is_root() && mx.preference != 0 {
+        return Err(DecodeErrorKind::InvalidRData(format!("null MX with preference: {}",
+                                                         mx.preference))
+            .into());
+    }
+
+    Ok(())
+}
+
 /// [RFC 4034](https://tools.ietf.org/html/rfc4034#section-6), DNSSEC Resource Records, March 2005
 ///
 /// ```text
@@ -139,3 +160,10 @@ pub fn test() {
             format!("error decoding: {:?}", read_rdata.unwrap_err()));
     assert_eq!(rdata, read_rdata.unwrap());
 }
+
+#[test]
+fn test_validate() {
+    assert!(validate(&MX::new(16, Name::new().label("mail").label("example").label("com"))).is_ok());
+    assert!(validate(&MX::new(0, Name::root())).is_ok());
+    assert!(validate(&MX::new(10, Name::root())).is_err());
+}
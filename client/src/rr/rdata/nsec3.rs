@@ -264,6 +264,25 @@ pub fn read(decoder: &mut BinDecoder, rdata_length: u16) -> DecodeResult<NSEC3>
                   record_types))
 }
 
+/// Checks that the salt and the hash fit their single byte length fields, and that there is a
+///  hash, RFC 5155 section 3.2
+pub fn validate(nsec3: &NSEC3) -> DecodeResult<()> {
+    if nsec3.salt.len() > 255 {
+        return Err(DecodeErrorKind::InvalidRData(format!("NSEC3 salt of {} bytes, maximum is 255",
+                                                         nsec3.salt.len()))
+            .into());
+    }
+
+    let hash_len = nsec3.next_hashed_owner_name.len();
+    if hash_len == 0 || hash_len > 255 {
+        return Err(DecodeErrorKind::InvalidRData(format!("NSEC3 hash of {} bytes, must be 1-255",
+                                                         hash_len))
+            .into());
+    }
+
+    Ok(())
+}
+
 pub fn decode_type_bit_maps(decoder: &mut BinDecoder,
                             bit_map_len: usize)
                             -> DecodeResult<Vec<RecordType>> {
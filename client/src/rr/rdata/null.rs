@@ -85,9 +85,83 @@ pub fn emit(encoder: &mut BinEncoder, nil: &NULL) -> EncodeResult {
     Ok(())
 }
 
-#[allow(unused)]
+/// [RFC 3597](https://tools.ietf.org/html/rfc3597#section-5), Handling of Unknown DNS Resource
+///  Record (RR) Types, September 2003
+///
+/// ```text
+/// 5.  Text Representation
+///
+///    The RDATA section of an RR of unknown type is represented as a
+///    sequence of white space separated words as follows:
+///
+///       The special token \# (a backslash immediately followed by a hash
+///       sign), which identifies the RDATA as having the generic encoding
+///       defined herein rather than a traditional type-specific encoding.
+///
+///       An unsigned decimal integer specifying the RDATA length in octets.
+///
+///       Zero or more words of hexadecimal data encoding the actual RDATA
+///       field, each containing an even number of hexadecimal digits.
+/// ```
+///
+/// NULL records are not permitted in master files otherwise, this is the form of any type which
+///  is not known, see `is_generic`.
 pub fn parse(tokens: &Vec<Token>) -> ParseResult<NULL> {
-    unimplemented!()
+    if !is_generic(tokens) {
+        return Err(ParseErrorKind::Message("expected the generic form: \\# <length> <hex>")
+            .into());
+    }
+
+    let mut token = tokens.iter().skip(1);
+    let length: usize = try!(token.next()
+        .ok_or(ParseError::from(ParseErrorKind::MissingToken("length".to_string())))
+        .and_then(|t| if let &Token::CharData(ref s) = t {
+            Ok(try!(s.parse()))
+        } else {
+            Err(ParseErrorKind::UnexpectedToken(t.clone()).into())
+        }));
+
+    // the data may be split over lines in parentheses
+    let mut words: Vec<&str> = Vec::new();
+    for t in token {
+        match *t {
+            Token::CharData(ref hex) => words.push(hex),
+            Token::List(ref v) => words.extend(v.iter().map(|s| s as &str)),
+            _ => return Err(ParseErrorKind::UnexpectedToken(t.clone()).into()),
+        }
+    }
+
+    let mut anything: Vec<u8> = Vec::with_capacity(length);
+    for hex in words {
+        if hex.len() % 2 != 0 || !hex.chars().all(|ch| ch.is_digit(16)) {
+            return Err(ParseErrorKind::Msg(format!("invalid hex data: {}", hex)).into());
+        }
+
+        for index in 0..hex.len() / 2 {
+            anything.push(try!(u8::from_str_radix(&hex[index * 2..index * 2 + 2], 16)));
+        }
+    }
+
+    if anything.len() != length {
+        return Err(ParseErrorKind::Msg(format!("expected {} bytes of data, found: {}",
+                                               length,
+                                               anything.len()))
+            .into());
+    }
+
+    if anything.is_empty() {
+        Ok(NULL::new())
+    } else {
+        Ok(NULL::with(anything))
+    }
+}
+
+/// true if the tokens start with `\#`, the generic form of RFC 3597
+pub fn is_generic(tokens: &[Token]) -> bool {
+    match tokens.first() {
+        Some(&Token::CharData(ref data)) => data == "\\#",
+        _ => false,
+    }
 }
 
 #[test]
@@ -107,3 +181,21 @@ pub fn test() {
             format!("error decoding: {:?}", read_rdata.unwrap_err()));
     assert_eq!(rdata, read_rdata.unwrap());
 }
+
+#[test]
+fn test_parse() {
+    let tokens = vec![Token::CharData("\\#".to_string()),
+                      Token::CharData("4".to_string()),
+                      Token::CharData("0A00".to_string()),
+                      Token::CharData("00ff".to_string())];
+    assert_eq!(parse(&tokens).unwrap(), NULL::with(vec![10, 0, 0, 255]));
+
+    let empty = vec![Token::CharData("\\#".to_string()), Token::CharData("0".to_string())];
+    assert_eq!(parse(&empty).unwrap(), NULL::new());
+
+    let wrong_length = vec![Token::CharData("\\#".to_string()),
+                            Token::CharData("3".to_string()),
+                            Token::CharData("0A00".to_string())];
+    assert!(parse(&wrong_length).is_err());
+    assert!(parse(&vec![Token::CharData("0A00".to_string())]).is_err());
+}
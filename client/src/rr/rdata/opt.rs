@@ -16,10 +16,13 @@
 
 //! option record for passing protocol options between the client and server
 
+use std::cmp;
 use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 
 use ::serialize::binary::*;
 use ::error::*;
+use rr::Name;
 use rr::dnssec::SupportedAlgorithms;
 
 /// The OPT record type is used for ExtendedDNS records.
@@ -285,7 +288,7 @@ pub enum EdnsCode {
     /// [RFC 6975, NSEC3 Hash Understood](https://tools.ietf.org/html/rfc6975)
     N3U,
 
-    /// [edns-client-subnet, Optional](https://tools.ietf.org/html/draft-vandergaast-edns-client-subnet-02)
+    /// [RFC 7871, Client Subnet, Optional](https://tools.ietf.org/html/rfc7871)
     Subnet,
 
     /// [RFC 7314, EDNS EXPIRE, Optional](https://tools.ietf.org/html/rfc7314)
@@ -368,6 +371,14 @@ pub enum EdnsOption {
     /// [RFC 6975, NSEC3 Hash Understood](https://tools.ietf.org/html/rfc6975)
     N3U(SupportedAlgorithms),
 
+    /// [RFC 7871, Client Subnet in DNS Queries](https://tools.ietf.org/html/rfc7871)
+    ClientSubnet(ClientSubnet),
+
+    /// [RFC 7901, CHAIN Query Requests in DNS](https://tools.ietf.org/html/rfc7901), the Closest
+    ///  Trust Point, the zone from which the chain of trust is requested, or in a response the
+    ///  zone from which it starts
+    Chain(Name),
+
     /// Unknown, used to deal with unknown or unsupported codes
     Unknown(u16, Vec<u8>),
 }
@@ -378,6 +389,8 @@ impl EdnsOption {
             EdnsOption::DAU(ref algorithms) |
             EdnsOption::DHU(ref algorithms) |
             EdnsOption::N3U(ref algorithms) => algorithms.len(),
+            EdnsOption::ClientSubnet(ref subnet) => subnet.len(),
+            EdnsOption::Chain(..) => Vec::from(self).len() as u16,
             EdnsOption::Unknown(_, ref data) => data.len() as u16, // TODO: should we verify?
         }
     }
@@ -390,6 +403,18 @@ impl<'a> From<(EdnsCode, &'a [u8])> for EdnsOption {
             EdnsCode::DAU => EdnsOption::DAU(value.1.into()),
             EdnsCode::DHU => EdnsOption::DHU(value.1.into()),
             EdnsCode::N3U => EdnsOption::N3U(value.1.into()),
+            // a malformed subnet is kept as is, a server answers it with FORMERR
+            EdnsCode::Subnet => {
+                read_client_subnet(value.1)
+                    .map(EdnsOption::ClientSubnet)
+                    .unwrap_or_else(|| EdnsOption::Unknown(value.0.into(), value.1.to_vec()))
+            }
+            // as is a malformed trust point, which a server answers with FORMERR
+            EdnsCode::Chain => {
+                read_chain(value.1)
+                    .map(EdnsOption::Chain)
+                    .unwrap_or_else(|| EdnsOption::Unknown(value.0.into(), value.1.to_vec()))
+            }
             _ => EdnsOption::Unknown(value.0.into(), value.1.to_vec()),
         }
     }
@@ -401,6 +426,19 @@ impl<'a> From<&'a EdnsOption> for Vec<u8> {
             EdnsOption::DAU(ref algorithms) |
             EdnsOption::DHU(ref algorithms) |
             EdnsOption::N3U(ref algorithms) => algorithms.into(),
+            EdnsOption::ClientSubnet(ref subnet) => subnet.into(),
+            EdnsOption::Chain(ref name) => {
+                // the name is never compressed, RFC 7901 section 4
+                let mut data = Vec::with_capacity(name.len() + 1);
+                {
+                    let mut encoder = BinEncoder::new(&mut data);
+                    encoder.set_canonical_names(true);
+                    if let Err(err) = name.emit(&mut encoder) {
+                        warn!("could not encode the CHAIN option: {}: {}", name, err);
+                    }
+                }
+                data
+            }
             EdnsOption::Unknown(_, ref data) => data.clone(), // gah, clone needed or make a crazy api.
         }
     }
@@ -412,7 +450,191 @@ impl<'a> From<&'a EdnsOption> for EdnsCode {
             EdnsOption::DAU(..) => EdnsCode::DAU,
             EdnsOption::DHU(..) => EdnsCode::DHU,
             EdnsOption::N3U(..) => EdnsCode::N3U,
-            EdnsOption::Unknown(code, _) => EdnsCode::Unknown(code),
+            EdnsOption::ClientSubnet(..) => EdnsCode::Subnet,
+            EdnsOption::Chain(..) => EdnsCode::Chain,
+            // the code may be known, but the option data not supported, keep the same key as read
+            EdnsOption::Unknown(code, _) => code.into(),
+        }
+    }
+}
+
+/// [RFC 7871, Client Subnet in DNS Queries, May 2016](https://tools.ietf.org/html/rfc7871#section-6)
+///
+/// ```text
+/// 6.  Option Format
+///
+///                 +0 (MSB)                            +1 (LSB)
+///       +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+///    0: |                          OPTION-CODE                          |
+///       +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+///    2: |                         OPTION-LENGTH                         |
+///       +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+///    4: |                            FAMILY                             |
+///       +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+///    6: |     SOURCE PREFIX-LENGTH      |     SCOPE PREFIX-LENGTH       |
+///       +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+///    8: |                           ADDRESS...                          /
+///       +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+/// ```
+///
+/// The address is truncated to the octets of the source prefix, the bits beyond it are zero.
+#[derive(Debug, PartialOrd, PartialEq, Eq, Clone, Hash)]
+pub struct ClientSubnet {
+    address: IpAddr,
+    source_prefix: u8,
+    scope_prefix: u8,
+}
+
+impl ClientSubnet {
+    /// Creates a new client subnet, for a query, with a scope prefix of zero
+    ///
+    /// # Arguments
+    ///
+    /// * `address` - the address of the client, the bits beyond the source prefix are cleared
+    /// * `source_prefix` - the leftmost bits of the address which are disclosed, RFC 7871
+    ///                     recommends at most 24 for IPv4 and 56 for IPv6
+    pub fn new(address: IpAddr, source_prefix: u8) -> Self {
+        let source_prefix = cmp::min(source_prefix, max_prefix(&address));
+        ClientSubnet {
+            address: mask_address(&address, source_prefix),
+            source_prefix: source_prefix,
+            scope_prefix: 0,
+        }
+    }
+
+    /// The address, with the bits beyond the source prefix cleared
+    pub fn get_address(&self) -> IpAddr {
+        self.address
+    }
+
+    /// The leftmost bits of the address which are disclosed by the client
+    pub fn get_source_prefix(&self) -> u8 {
+        self.source_prefix
+    }
+
+    /// The leftmost bits of the address which the answer applies to, zero in queries, and in
+    ///  answers which do not depend on the address of the client
+    pub fn get_scope_prefix(&self) -> u8 {
+        self.scope_prefix
+    }
+
+    /// Sets the scope prefix, for the response, at most the length of the address
+    pub fn set_scope_prefix(&mut self, scope_prefix: u8) {
+        self.scope_prefix = cmp::min(scope_prefix, max_prefix(&self.address));
+    }
+
+    /// The length of the option data
+    pub fn len(&self) -> u16 {
+        4 + address_len(self.source_prefix) as u16
+    }
+}
+
+impl<'a> From<&'a ClientSubnet> for Vec<u8> {
+    fn from(value: &'a ClientSubnet) -> Vec<u8> {
+        let (family, octets) = match value.address {
+            IpAddr::V4(ref address) => (1u16, address.octets().to_vec()),
+            IpAddr::V6(ref address) => (2u16, address.octets().to_vec()),
+        };
+
+        let mut data = Vec::with_capacity(value.len() as usize);
+        data.push((family >> 8) as u8);
+        data.push(family as u8);
+        data.push(value.source_prefix);
+        data.push(value.scope_prefix);
+        data.extend_from_slice(&octets[..address_len(value.source_prefix)]);
+        data
+    }
+}
+
+/// None if the option is malformed, the family is unknown, a prefix is too long, the address is
+///  not truncated to the source prefix or has bits set beyond it, RFC 7871 section 6
+fn read_client_subnet(data: &[u8]) -> Option<ClientSubnet> {
+    if data.len() < 4 {
+        return None;
+    }
+
+    let family = (data[0] as u16) << 8 | data[1] as u16;
+    let source_prefix = data[2];
+    let scope_prefix = data[3];
+    let octets = &data[4..];
+    if octets.len() != address_len(source_prefix) {
+        return None;
+    }
+
+    let address = match family {
+        1 if source_prefix <= 32 && scope_prefix <= 32 => {
+            let mut address = [0u8; 4];
+            address[..octets.len()].copy_from_slice(octets);
+            IpAddr::V4(Ipv4Addr::from(address))
+        }
+        2 if source_prefix <= 128 && scope_prefix <= 128 => {
+            let mut address = [0u8; 16];
+            address[..octets.len()].copy_from_slice(octets);
+            IpAddr::V6(Ipv6Addr::from(address))
+        }
+        _ => return None,
+    };
+
+    if mask_address(&address, source_prefix) != address {
+        return None;
+    }
+
+    Some(ClientSubnet {
+        address: address,
+        source_prefix: source_prefix,
+        scope_prefix: scope_prefix,
+    })
+}
+
+/// None if the option is not a single fully qualified name in the wire format, RFC 7901 section 4
+fn read_chain(data: &[u8]) -> Option<Name> {
+    let mut decoder = BinDecoder::new(data);
+    match Name::read(&mut decoder) {
+        Ok(ref name) if decoder.index() != data.len() => {
+            debug!("trailing data after the CHAIN option: {}", name);
+            None
+        }
+        Ok(name) => Some(name),
+        Err(err) => {
+            debug!("malformed CHAIN option: {}", err);
+            None
+        }
+    }
+}
+
+/// the octets of the address needed for the prefix
+fn address_len(prefix: u8) -> usize {
+    (prefix as usize + 7) / 8
+}
+
+fn max_prefix(address: &IpAddr) -> u8 {
+    match *address {
+        IpAddr::V4(..) => 32,
+        IpAddr::V6(..) => 128,
+    }
+}
+
+/// the address with the bits beyond the prefix cleared
+fn mask_address(address: &IpAddr, prefix: u8) -> IpAddr {
+    fn mask(octets: &mut [u8], prefix: u8) {
+        for (index, octet) in octets.iter_mut().enumerate() {
+            let bits = (prefix as usize).saturating_sub(index * 8);
+            if bits < 8 {
+                *octet &= !(0xFFu8 >> bits);
+            }
+        }
+    }
+
+    match *address {
+        IpAddr::V4(ref address) => {
+            let mut octets = address.octets();
+            mask(&mut octets, prefix);
+            IpAddr::V4(Ipv4Addr::from(octets))
+        }
+        IpAddr::V6(ref address) => {
+            let mut octets = address.octets();
+            mask(&mut octets, prefix);
+            IpAddr::V6(Ipv6Addr::from(octets))
         }
     }
 }
@@ -435,3 +657,81 @@ pub fn test() {
             format!("error decoding: {:?}", read_rdata.unwrap_err()));
     assert_eq!(rdata, read_rdata.unwrap());
 }
+
+#[test]
+pub fn test_unknown_options() {
+    let mut rdata = OPT::default();
+    rdata.insert(EdnsOption::Unknown(3, vec![0xDE, 0xAD]));
+    rdata.insert(EdnsOption::Unknown(65001, vec![]));
+
+    assert!(rdata.get(&EdnsCode::NSID).is_some());
+    assert!(rdata.get(&EdnsCode::Unknown(65001)).is_some());
+
+    let mut bytes = Vec::new();
+    {
+        let mut encoder: BinEncoder = BinEncoder::new(&mut bytes);
+        assert!(emit(&mut encoder, &rdata).is_ok());
+    }
+
+    let mut decoder: BinDecoder = BinDecoder::new(&bytes);
+    let read_rdata = read(&mut decoder, bytes.len() as u16).expect("error decoding");
+    assert_eq!(rdata, read_rdata);
+}
+
+#[test]
+pub fn test_client_subnet() {
+    let subnet = ClientSubnet::new("192.0.2.129".parse().unwrap(), 25);
+    assert_eq!(subnet.get_address(), "192.0.2.128".parse::<IpAddr>().unwrap());
+    let data: Vec<u8> = (&subnet).into();
+    assert_eq!(data, vec![0, 1, 25, 0, 192, 0, 2, 128]);
+
+    let mut rdata = OPT::default();
+    rdata.insert(EdnsOption::ClientSubnet(subnet.clone()));
+    let mut v6 = ClientSubnet::new("2001:db8:ffff::1".parse().unwrap(), 36);
+    v6.set_scope_prefix(200);
+    assert_eq!(v6.get_scope_prefix(), 128);
+    assert_eq!(v6.get_address(), "2001:db8:f000::".parse::<IpAddr>().unwrap());
+    assert_eq!(v6.len(), 9);
+
+    let mut bytes = Vec::new();
+    {
+        let mut encoder: BinEncoder = BinEncoder::new(&mut bytes);
+        assert!(emit(&mut encoder, &rdata).is_ok());
+    }
+
+    let read_rdata = read(&mut BinDecoder::new(&bytes), bytes.len() as u16)
+        .expect("error decoding");
+    assert_eq!(read_rdata.get(&EdnsCode::Subnet),
+               Some(&EdnsOption::ClientSubnet(subnet)));
+
+    let option = EdnsOption::ClientSubnet(v6);
+    let data: Vec<u8> = (&option).into();
+    assert_eq!(EdnsOption::from((EdnsCode::Subnet, &data as &[u8])), option);
+
+    // bits set beyond the source prefix, an address which is not truncated, an unknown family
+    for data in &[vec![0u8, 1, 25, 0, 192, 0, 2, 129],
+                  vec![0u8, 1, 24, 0, 192, 0, 2, 0],
+                  vec![0u8, 3, 0, 0]] {
+        assert_eq!(EdnsOption::from((EdnsCode::Subnet, &data[..])),
+                   EdnsOption::Unknown(8, data.clone()));
+    }
+}
+
+#[test]
+pub fn test_chain() {
+    let option = EdnsOption::Chain(Name::parse("example.com.", None).unwrap());
+    let data: Vec<u8> = (&option).into();
+    assert_eq!(data, b"\x07example\x03com\x00".to_vec());
+    assert_eq!(option.len(), 13);
+    assert_eq!(EdnsOption::from((EdnsCode::Chain, &data as &[u8])), option);
+
+    let root = EdnsOption::Chain(Name::root());
+    assert_eq!(Vec::<u8>::from(&root), vec![0]);
+    assert_eq!(EdnsOption::from((EdnsCode::Chain, &[0u8] as &[u8])), root);
+
+    // an empty option, a name which is not terminated, trailing data
+    for data in &[vec![], vec![3u8, b'c', b'o', b'm'], vec![0u8, 0]] {
+        assert_eq!(EdnsOption::from((EdnsCode::Chain, &data[..])),
+                   EdnsOption::Unknown(13, data.clone()));
+    }
+}
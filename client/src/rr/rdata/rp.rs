@@ -0,0 +1,115 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! responsible person, who to contact about the owner of the record
+
+use ::serialize::txt::*;
+use ::serialize::binary::*;
+use ::error::*;
+use rr::domain::Name;
+
+/// [RFC 1183, New DNS RR Definitions, October 1990](https://tools.ietf.org/html/rfc1183#section-2.2)
+///
+/// ```text
+/// 2.2. The Responsible Person RR
+///
+///    The format of the RP RR is:
+///
+///       <owner> <ttl> <class> RP <mbox-dname> <txt-dname>
+///
+///    Both RDATA fields are required in all RP RRs.
+///
+///    The first field, <mbox-dname>, is a domain name that specifies the
+///    mailbox for the responsible person.  Its format in master files uses
+///    the DNS convention for mailbox encoding, identical to that used for
+///    the RNAME mailbox field in the SOA RR.  The root domain name (just
+///    ".") may be specified for <mbox-dname> to indicate that no mailbox is
+///    available.
+///
+///    The second field, <txt-dname>, is a domain name for which TXT RR's
+///    exist.  A subsequent query can be performed to retrieve the
+///    associated TXT resource records at <txt-dname>.  This provides a
+///    level of indirection so that the entity can be referred to from
+///    multiple places in the DNS.  The root domain name (just ".") may be
+///    specified for <txt-dname> to indicate that the TXT_DNAME is absent,
+///    and no associated TXT RR exists.
+/// ```
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct RP {
+    mbox: Name,
+    txt: Name,
+}
+
+impl RP {
+    pub fn new(mbox: Name, txt: Name) -> RP {
+        RP {
+            mbox: mbox,
+            txt: txt,
+        }
+    }
+
+    /// the mailbox of the responsible person, encoded as the RNAME of the SOA, the root if there
+    ///  is none, see `Name::to_email()`
+    pub fn get_mbox(&self) -> &Name {
+        &self.mbox
+    }
+
+    /// the name with TXT records describing the responsible person, the root if there are none
+    pub fn get_txt(&self) -> &Name {
+        &self.txt
+    }
+}
+
+pub fn read(decoder: &mut BinDecoder) -> DecodeResult<RP> {
+    Ok(RP::new(try!(Name::read(decoder)), try!(Name::read(decoder))))
+}
+
+/// RP is one of the types with names lowercased in the canonical form, RFC 4034 section 6.2
+pub fn emit(encoder: &mut BinEncoder, rp: &RP) -> EncodeResult {
+    let is_canonical_names = encoder.is_canonical_names();
+    try!(rp.get_mbox().emit_with_lowercase(encoder, is_canonical_names));
+    try!(rp.get_txt().emit_with_lowercase(encoder, is_canonical_names));
+    Ok(())
+}
+
+pub fn parse(tokens: &Vec<Token>, origin: Option<&Name>) -> ParseResult<RP> {
+    let mut token = tokens.iter();
+
+    let mbox: Name = try!(token.next()
+        .ok_or(ParseErrorKind::MissingToken("mbox".to_string()).into())
+        .and_then(|t| if let &Token::CharData(ref s) = t {
+            Name::parse(s, origin)
+        } else {
+            Err(ParseErrorKind::UnexpectedToken(t.clone()).into())
+        }));
+    let txt: Name = try!(token.next()
+        .ok_or(ParseErrorKind::MissingToken("txt".to_string()).into())
+        .and_then(|t| if let &Token::CharData(ref s) = t {
+            Name::parse(s, origin)
+        } else {
+            Err(ParseErrorKind::UnexpectedToken(t.clone()).into())
+        }));
+
+    Ok(RP::new(mbox, txt))
+}
+
+#[test]
+pub fn test() {
+    let rdata = RP::new(Name::new().label("admin").label("example").label("com"),
+                        Name::new().label("contact").label("example").label("com"));
+
+    let mut bytes = Vec::new();
+    let mut encoder: BinEncoder = BinEncoder::new(&mut bytes);
+    assert!(emit(&mut encoder, &rdata).is_ok());
+    let bytes = encoder.as_bytes();
+
+    let mut decoder: BinDecoder = BinDecoder::new(bytes);
+    let read_rdata = read(&mut decoder);
+    assert!(read_rdata.is_ok(),
+            format!("error decoding: {:?}", read_rdata.unwrap_err()));
+    assert_eq!(rdata, read_rdata.unwrap());
+}
@@ -137,7 +137,7 @@ impl SOA {
     /// # Return value
     ///
     /// A `domain-name` which specifies the mailbox of the person responsible for this zone, i.e.
-    /// the responsible name.
+    /// the responsible name. `Name::to_email()` is its email address.
     pub fn get_rname(&self) -> &Name {
         &self.rname
     }
@@ -222,6 +222,28 @@ pub fn read(decoder: &mut BinDecoder) -> DecodeResult<SOA> {
     })
 }
 
+/// Checks that the timers are not negative, and that the zone does not expire before it is due to
+///  be refreshed
+pub fn validate(soa: &SOA) -> DecodeResult<()> {
+    if soa.refresh < 0 || soa.retry < 0 || soa.expire < 0 {
+        return Err(DecodeErrorKind::InvalidRData(format!("SOA timers are negative, refresh: {} \
+                                                          retry: {} expire: {}",
+                                                         soa.refresh,
+                                                         soa.retry,
+                                                         soa.expire))
+            .into());
+    }
+
+    if soa.expire < soa.refresh {
+        return Err(DecodeErrorKind::InvalidRData(format!("SOA expire: {} is less than refresh: {}",
+                                                         soa.expire,
+                                                         soa.refresh))
+            .into());
+    }
+
+    Ok(())
+}
+
 /// [RFC 4034](https://tools.ietf.org/html/rfc4034#section-6), DNSSEC Resource Records, March 2005
 ///
 /// This is accurate for all currently known name records.
@@ -276,37 +298,31 @@ pub fn parse(tokens: &Vec<Token>, origin: Option<&Name>) -> ParseResult<SOA> {
         } else {
             Err(ParseErrorKind::UnexpectedToken(t.clone()).into())
         }));
-    let mut list = try!(token.next()
-            .ok_or(ParseError::from(ParseErrorKind::MissingToken("List".to_string())))
-            .and_then(|t| if let &Token::List(ref v) = t {
-                Ok(v)
-            } else {
-                Err(ParseErrorKind::UnexpectedToken(t.clone()).into())
-            }))
-        .iter();
+    // the numbers are usually in parentheses, across lines, but need not be
+    let mut numbers: Vec<&str> = Vec::new();
+    for t in token {
+        match *t {
+            Token::CharData(ref s) => numbers.push(s),
+            Token::List(ref v) => numbers.extend(v.iter().map(|s| s as &str)),
+            _ => return Err(ParseErrorKind::UnexpectedToken(t.clone()).into()),
+        }
+    }
+    let mut numbers = numbers.into_iter();
 
-    let serial: u32 = try!(list.next()
+    let serial: u32 = try!(numbers.next()
         .ok_or(ParseError::from(ParseErrorKind::MissingToken("serial".to_string())))
         .and_then(|s| Ok(try!(s.parse()))));
-    let refresh: i32 = try!(list.next()
-        .ok_or(ParseError::from(ParseErrorKind::MissingToken("refresh".to_string())))
-        .and_then(|s| Ok(try!(s.parse()))));
-    let retry: i32 = try!(list.next()
-        .ok_or(ParseError::from(ParseErrorKind::MissingToken("retry".to_string())))
-        .and_then(|s| Ok(try!(s.parse()))));
-    let expire: i32 = try!(list.next()
-        .ok_or(ParseError::from(ParseErrorKind::MissingToken("expire".to_string())))
-        .and_then(|s| Ok(try!(s.parse()))));
-    let minimum: u32 = try!(list.next()
-        .ok_or(ParseError::from(ParseErrorKind::MissingToken("minimum".to_string())))
-        .and_then(|s| Ok(try!(s.parse()))));
-
 
-    // let serial: u32 = try!(token.next().ok_or(ParseError::MissingToken("serial".to_string())).and_then(|t| if let &Token::CharData(ref s) = t {Ok(try!(s.parse()))} else {Err(ParseError::UnexpectedToken(t.clone()))} ));
-    // let refresh: i32 = try!(token.next().ok_or(ParseError::MissingToken("refresh".to_string())).and_then(|t| if let &Token::CharData(ref s) = t {Ok(try!(s.parse()))} else {Err(ParseError::UnexpectedToken(t.clone()))} ));
-    // let retry: i32 = try!(token.next().ok_or(ParseError::MissingToken("retry".to_string())).and_then(|t| if let &Token::CharData(ref s) = t {Ok(try!(s.parse()))} else {Err(ParseError::UnexpectedToken(t.clone()))} ));
-    // let expire: i32 = try!(token.next().ok_or(ParseError::MissingToken("expire".to_string())).and_then(|t| if let &Token::CharData(ref s) = t {Ok(try!(s.parse()))} else {Err(ParseError::UnexpectedToken(t.clone()))} ));
-    // let minimum: u32 = try!(token.next().ok_or(ParseError::MissingToken("minimum".to_string())).and_then(|t| if let &Token::CharData(ref s) = t {Ok(try!(s.parse()))} else {Err(ParseError::UnexpectedToken(t.clone()))} ));
+    // the timers may have units, e.g. 1h30m, as TTLs do
+    let mut timer = |name: &str| {
+        numbers.next()
+            .ok_or(ParseError::from(ParseErrorKind::MissingToken(name.to_string())))
+            .and_then(|s| Parser::parse_time(s))
+    };
+    let refresh = try!(timer("refresh")) as i32;
+    let retry = try!(timer("retry")) as i32;
+    let expire = try!(timer("expire")) as i32;
+    let minimum: u32 = try!(timer("minimum"));
 
     Ok(SOA::new(mname, rname, serial, refresh, retry, expire, minimum))
 }
@@ -334,3 +350,20 @@ fn test() {
             format!("error decoding: {:?}", read_rdata.unwrap_err()));
     assert_eq!(rdata, read_rdata.unwrap());
 }
+
+#[test]
+fn test_validate() {
+    let soa = |refresh, retry, expire| {
+        SOA::new(Name::new().label("m").label("example").label("com"),
+                 Name::new().label("r").label("example").label("com"),
+                 1,
+                 refresh,
+                 retry,
+                 expire,
+                 5)
+    };
+
+    assert!(validate(&soa(7200, 3600, 1209600)).is_ok());
+    assert!(validate(&soa(-1, 3600, 1209600)).is_err());
+    assert!(validate(&soa(7200, 3600, 3600)).is_err());
+}
@@ -0,0 +1,504 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! SVCB records, the alternative endpoints of a service and their parameters
+
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use ::serialize::txt::*;
+use ::serialize::binary::*;
+use ::error::*;
+use rr::domain::Name;
+
+/// [RFC 9460, SVCB and HTTPS RRs, November 2023](https://tools.ietf.org/html/rfc9460#section-2.2)
+///
+/// ```text
+/// 2.2.  RDATA Wire Format
+///
+///    The RDATA for the SVCB RR consists of:
+///
+///    *  a 2-octet field for SvcPriority as an integer in network byte
+///       order.
+///
+///    *  the uncompressed, fully qualified TargetName, represented as a
+///       sequence of length-prefixed labels per Section 3.1 of [RFC1035].
+///
+///    *  the SvcParams, consuming the remainder of the record (so smaller
+///       than 65535 octets and constrained by the RDATA and DNS message
+///       sizes).
+///
+///    When the list of SvcParams is non-empty, it contains a series of
+///    SvcParamKey=SvcParamValue pairs, represented as:
+///
+///    *  a 2-octet field containing the SvcParamKey as an integer in
+///       network byte order.
+///
+///    *  a 2-octet field containing the length of the SvcParamValue as an
+///       integer between 0 and 65535 in network byte order.
+///
+///    *  an octet string of this length whose contents are the
+///       SvcParamValue in a format determined by the SvcParamKey.
+/// ```
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct SVCB {
+    priority: u16,
+    target: Name,
+    params: Vec<SvcParam>,
+}
+
+/// [RFC 9460](https://tools.ietf.org/html/rfc9460#section-14.3.2), SvcParamKeys, with `dohpath`
+///  of [RFC 9461](https://tools.ietf.org/html/rfc9461#section-5)
+///
+/// A parameter of the endpoint, the keys which are not known here are kept as they were received.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub enum SvcParam {
+    /// 0, the keys which the client must understand to use the record
+    Mandatory(Vec<u16>),
+    /// 1, the protocols of the endpoint, e.g. `dot` or `h2`
+    Alpn(Vec<String>),
+    /// 2, the default protocol of the scheme is not supported
+    NoDefaultAlpn,
+    /// 3, the port of the endpoint
+    Port(u16),
+    /// 4, addresses of the target, which may be used before they are resolved
+    Ipv4Hint(Vec<Ipv4Addr>),
+    /// 6, addresses of the target, which may be used before they are resolved
+    Ipv6Hint(Vec<Ipv6Addr>),
+    /// 7, the URI template of DNS over HTTPS, e.g. `/dns-query{?dns}`
+    DohPath(String),
+    /// any other key, with its value
+    Unknown(u16, Vec<u8>),
+}
+
+impl SvcParam {
+    /// The SvcParamKey of the parameter
+    pub fn get_key(&self) -> u16 {
+        match *self {
+            SvcParam::Mandatory(..) => 0,
+            SvcParam::Alpn(..) => 1,
+            SvcParam::NoDefaultAlpn => 2,
+            SvcParam::Port(..) => 3,
+            SvcParam::Ipv4Hint(..) => 4,
+            SvcParam::Ipv6Hint(..) => 6,
+            SvcParam::DohPath(..) => 7,
+            SvcParam::Unknown(key, _) => key,
+        }
+    }
+}
+
+impl SVCB {
+    /// The parameters are ordered by their keys, as in the wire format
+    pub fn new(priority: u16, target: Name, mut params: Vec<SvcParam>) -> SVCB {
+        params.sort_by_key(|param| param.get_key());
+
+        SVCB {
+            priority: priority,
+            target: target,
+            params: params,
+        }
+    }
+
+    /// the priority of the endpoint, lower values are preferred, zero is the alias form
+    pub fn get_priority(&self) -> u16 {
+        self.priority
+    }
+
+    /// true if the record is an alias of the owner name to the target, rather than an endpoint
+    pub fn is_alias(&self) -> bool {
+        self.priority == 0
+    }
+
+    /// the name of the endpoint, the root name is the owner name itself
+    pub fn get_target(&self) -> &Name {
+        &self.target
+    }
+
+    /// the parameters of the endpoint, ordered by their keys
+    pub fn get_params(&self) -> &[SvcParam] {
+        &self.params
+    }
+
+    /// the protocols of the endpoint, empty if none are listed
+    pub fn get_alpn(&self) -> &[String] {
+        for param in &self.params {
+            if let SvcParam::Alpn(ref alpn) = *param {
+                return alpn;
+            }
+        }
+        &[]
+    }
+
+    /// the port of the endpoint, if it is not the default of the protocol
+    pub fn get_port(&self) -> Option<u16> {
+        for param in &self.params {
+            if let SvcParam::Port(port) = *param {
+                return Some(port);
+            }
+        }
+        None
+    }
+
+    /// the URI template of DNS over HTTPS, RFC 9461
+    pub fn get_dohpath(&self) -> Option<&str> {
+        for param in &self.params {
+            if let SvcParam::DohPath(ref path) = *param {
+                return Some(path);
+            }
+        }
+        None
+    }
+
+    /// the addresses of the `ipv4hint` and `ipv6hint` parameters
+    pub fn get_address_hints(&self) -> Vec<IpAddr> {
+        let mut addresses: Vec<IpAddr> = Vec::new();
+        for param in &self.params {
+            match *param {
+                SvcParam::Ipv4Hint(ref hints) => {
+                    addresses.extend(hints.iter().map(|a| IpAddr::V4(*a)))
+                }
+                SvcParam::Ipv6Hint(ref hints) => {
+                    addresses.extend(hints.iter().map(|a| IpAddr::V6(*a)))
+                }
+                _ => (),
+            }
+        }
+        addresses
+    }
+}
+
+/// The name of the key in the presentation format, `keyNNNNN` for those which have none
+fn key_name(key: u16) -> String {
+    match key {
+        0 => "mandatory".to_string(),
+        1 => "alpn".to_string(),
+        2 => "no-default-alpn".to_string(),
+        3 => "port".to_string(),
+        4 => "ipv4hint".to_string(),
+        5 => "ech".to_string(),
+        6 => "ipv6hint".to_string(),
+        7 => "dohpath".to_string(),
+        _ => format!("key{}", key),
+    }
+}
+
+fn key_from_str(name: &str) -> ParseResult<u16> {
+    match name {
+        "mandatory" => Ok(0),
+        "alpn" => Ok(1),
+        "no-default-alpn" => Ok(2),
+        "port" => Ok(3),
+        "ipv4hint" => Ok(4),
+        "ech" => Ok(5),
+        "ipv6hint" => Ok(6),
+        "dohpath" => Ok(7),
+        _ if name.starts_with("key") => Ok(try!(name[3..].parse())),
+        _ => Err(ParseErrorKind::Msg(format!("unknown SvcParamKey: {}", name)).into()),
+    }
+}
+
+fn read_param(key: u16, value: Vec<u8>) -> DecodeResult<SvcParam> {
+    match key {
+        7 => return Ok(SvcParam::DohPath(try!(String::from_utf8(value)))),
+        0...6 => (),
+        _ => return Ok(SvcParam::Unknown(key, value)),
+    }
+
+    let param = {
+        let mut decoder = BinDecoder::new(&value);
+        match key {
+            0 => {
+                let mut keys: Vec<u16> = Vec::new();
+                while decoder.len() > 0 {
+                    keys.push(try!(decoder.read_u16()));
+                }
+                SvcParam::Mandatory(keys)
+            }
+            1 => {
+                let mut alpn: Vec<String> = Vec::new();
+                while decoder.len() > 0 {
+                    alpn.push(try!(decoder.read_character_data()));
+                }
+                SvcParam::Alpn(alpn)
+            }
+            2 => SvcParam::NoDefaultAlpn,
+            3 => SvcParam::Port(try!(decoder.read_u16())),
+            4 => {
+                if value.len() % 4 != 0 {
+                    return Err(DecodeErrorKind::Message("ipv4hint is not a list of addresses")
+                        .into());
+                }
+                SvcParam::Ipv4Hint(value.chunks(4)
+                    .map(|a| Ipv4Addr::new(a[0], a[1], a[2], a[3]))
+                    .collect())
+            }
+            6 => {
+                if value.len() % 16 != 0 {
+                    return Err(DecodeErrorKind::Message("ipv6hint is not a list of addresses")
+                        .into());
+                }
+                SvcParam::Ipv6Hint(value.chunks(16)
+                    .map(|a| {
+                        let segment = |i: usize| (a[i * 2] as u16) << 8 | a[i * 2 + 1] as u16;
+                        Ipv6Addr::new(segment(0),
+                                      segment(1),
+                                      segment(2),
+                                      segment(3),
+                                      segment(4),
+                                      segment(5),
+                                      segment(6),
+                                      segment(7))
+                    })
+                    .collect())
+            }
+            _ => SvcParam::Unknown(key, value.clone()),
+        }
+    };
+
+    Ok(param)
+}
+
+fn emit_param(encoder: &mut BinEncoder, param: &SvcParam) -> EncodeResult {
+    match *param {
+        SvcParam::Mandatory(ref keys) => {
+            for key in keys {
+                try!(encoder.emit_u16(*key));
+            }
+        }
+        SvcParam::Alpn(ref alpn) => {
+            for id in alpn {
+                try!(encoder.emit_character_data(id));
+            }
+        }
+        SvcParam::NoDefaultAlpn => (),
+        SvcParam::Port(port) => try!(encoder.emit_u16(port)),
+        SvcParam::Ipv4Hint(ref hints) => {
+            for address in hints {
+                try!(encoder.emit_vec(&address.octets()));
+            }
+        }
+        SvcParam::Ipv6Hint(ref hints) => {
+            for address in hints {
+                for segment in &address.segments() {
+                    try!(encoder.emit_u16(*segment));
+                }
+            }
+        }
+        SvcParam::DohPath(ref path) => try!(encoder.emit_vec(path.as_bytes())),
+        SvcParam::Unknown(_, ref value) => try!(encoder.emit_vec(value)),
+    }
+    Ok(())
+}
+
+pub fn read(decoder: &mut BinDecoder, rdata_length: u16) -> DecodeResult<SVCB> {
+    let start = decoder.index();
+    let priority = try!(decoder.read_u16());
+    let target = try!(Name::read(decoder));
+
+    let mut params: Vec<SvcParam> = Vec::new();
+    while decoder.index() - start < rdata_length as usize {
+        let key = try!(decoder.read_u16());
+        if params.last().map_or(false, |last| last.get_key() >= key) {
+            return Err(DecodeErrorKind::Message("SvcParamKeys are not in increasing order")
+                .into());
+        }
+
+        let length = try!(decoder.read_u16());
+        params.push(try!(read_param(key, try!(decoder.read_vec(length as usize)))));
+    }
+
+    if decoder.index() - start != rdata_length as usize {
+        return Err(DecodeErrorKind::Message("SVCB rdata is longer than its length").into());
+    }
+
+    Ok(SVCB::new(priority, target, params))
+}
+
+pub fn emit(encoder: &mut BinEncoder, svcb: &SVCB) -> EncodeResult {
+    try!(encoder.emit_u16(svcb.get_priority()));
+    // the target is never compressed
+    try!(svcb.get_target().emit_as_canonical(encoder, true));
+
+    for param in svcb.get_params() {
+        let mut value: Vec<u8> = Vec::new();
+        {
+            let mut value_encoder = BinEncoder::new(&mut value);
+            try!(emit_param(&mut value_encoder, param));
+        }
+
+        try!(encoder.emit_u16(param.get_key()));
+        try!(encoder.emit_u16(value.len() as u16));
+        try!(encoder.emit_vec(&value));
+    }
+    Ok(())
+}
+
+/// [RFC 9460, SVCB and HTTPS RRs, November 2023](https://tools.ietf.org/html/rfc9460#section-2.1)
+///
+/// ```text
+/// 2.1.  Zone-File Presentation Format
+///
+///    The presentation format <RDATA> of the record is:
+///
+///    SvcPriority TargetName SvcParams
+///
+///    SvcParams is a whitespace-separated list with each SvcParam
+///    consisting of a SvcParamKey=SvcParamValue pair or a standalone
+///    SvcParamKey.
+/// ```
+///
+/// Lists are separated by commas, without escapes, values may be quoted. Values of keys which
+///  are not known here may have `\DDD` escapes.
+pub fn parse(tokens: &Vec<Token>, origin: Option<&Name>) -> ParseResult<SVCB> {
+    let mut token = tokens.iter().map(|t| if let Token::CharData(ref s) = *t {
+        Ok(s)
+    } else {
+        Err(ParseError::from(ParseErrorKind::UnexpectedToken(t.clone())))
+    });
+
+    let (priority, target): (u16, Name) = {
+        let mut next = |field: &str| {
+            token.next().unwrap_or(Err(ParseErrorKind::MissingToken(field.to_string()).into()))
+        };
+
+        (try!(try!(next("priority")).parse()), try!(Name::parse(try!(next("target")), origin)))
+    };
+
+    let mut params: Vec<SvcParam> = Vec::new();
+    for param in token {
+        let param = try!(param);
+        let (key, value) = match param.find('=') {
+            Some(index) => (&param[..index], param[index + 1..].trim_matches('"')),
+            None => (param.as_str(), ""),
+        };
+        let key = try!(key_from_str(key));
+        let list = || value.split(',').filter(|v| !v.is_empty());
+
+        params.push(match key {
+            0 => SvcParam::Mandatory(try!(list().map(key_from_str).collect::<ParseResult<_>>())),
+            1 => SvcParam::Alpn(list().map(|id| id.to_string()).collect()),
+            2 => SvcParam::NoDefaultAlpn,
+            3 => SvcParam::Port(try!(value.parse())),
+            4 => {
+                SvcParam::Ipv4Hint(try!(list()
+                    .map(|a| a.parse::<Ipv4Addr>())
+                    .collect::<Result<_, _>>()))
+            }
+            6 => {
+                SvcParam::Ipv6Hint(try!(list()
+                    .map(|a| a.parse::<Ipv6Addr>())
+                    .collect::<Result<_, _>>()))
+            }
+            7 => SvcParam::DohPath(value.to_string()),
+            _ => SvcParam::Unknown(key, try!(unescape(value))),
+        });
+    }
+
+    Ok(SVCB::new(priority, target, params))
+}
+
+/// The octets of a value with `\DDD` escapes
+fn unescape(value: &str) -> ParseResult<Vec<u8>> {
+    let bytes = value.as_bytes();
+    let mut octets: Vec<u8> = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        if bytes[index] == b'\\' && index + 3 < bytes.len() &&
+           bytes[index + 1..index + 4].iter().all(|b| (*b as char).is_digit(10)) {
+            octets.push(try!(value[index + 1..index + 4].parse()));
+            index += 4;
+        } else {
+            octets.push(bytes[index]);
+            index += 1;
+        }
+    }
+    Ok(octets)
+}
+
+impl fmt::Display for SVCB {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(write!(f, "{} {}", self.priority, self.target));
+
+        for param in &self.params {
+            try!(write!(f, " {}", key_name(param.get_key())));
+            match *param {
+                SvcParam::Mandatory(ref keys) => {
+                    let names = keys.iter().map(|key| key_name(*key)).collect::<Vec<_>>();
+                    try!(write!(f, "={}", names.join(",")));
+                }
+                SvcParam::Alpn(ref alpn) => try!(write!(f, "={}", alpn.join(","))),
+                SvcParam::NoDefaultAlpn => (),
+                SvcParam::Port(port) => try!(write!(f, "={}", port)),
+                SvcParam::Ipv4Hint(ref hints) => {
+                    let hints = hints.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+                    try!(write!(f, "={}", hints.join(",")));
+                }
+                SvcParam::Ipv6Hint(ref hints) => {
+                    let hints = hints.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+                    try!(write!(f, "={}", hints.join(",")));
+                }
+                SvcParam::DohPath(ref path) => try!(write!(f, "=\"{}\"", path)),
+                SvcParam::Unknown(_, ref value) => {
+                    try!(write!(f, "=\""));
+                    for octet in value {
+                        match *octet {
+                            b'!' | b'#'...b'[' | b']'...b'~' => {
+                                try!(write!(f, "{}", *octet as char))
+                            }
+                            _ => try!(write!(f, "\\{:03}", octet)),
+                        }
+                    }
+                    try!(write!(f, "\""));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[test]
+pub fn test() {
+    let rdata = SVCB::new(1,
+                          Name::parse("dot.example.net.", None).unwrap(),
+                          vec![SvcParam::Port(853),
+                               SvcParam::Alpn(vec!["dot".to_string(), "h2".to_string()]),
+                               SvcParam::Ipv4Hint(vec![Ipv4Addr::new(192, 0, 2, 1)]),
+                               SvcParam::Ipv6Hint(vec![Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0,
+                                                                     0, 0, 1)]),
+                               SvcParam::DohPath("/dns-query{?dns}".to_string()),
+                               SvcParam::Unknown(65001, vec![b'a', 0])]);
+    assert_eq!(rdata.get_params()[0], SvcParam::Alpn(vec!["dot".to_string(), "h2".to_string()]));
+    assert_eq!(rdata.get_port(), Some(853));
+    assert_eq!(rdata.get_address_hints().len(), 2);
+
+    let mut bytes = Vec::new();
+    let mut encoder: BinEncoder = BinEncoder::new(&mut bytes);
+    assert!(emit(&mut encoder, &rdata).is_ok());
+    let bytes = encoder.as_bytes();
+
+    let mut decoder: BinDecoder = BinDecoder::new(bytes);
+    let read_rdata = read(&mut decoder, bytes.len() as u16);
+    assert!(read_rdata.is_ok(),
+            format!("error decoding: {:?}", read_rdata.unwrap_err()));
+    assert_eq!(rdata, read_rdata.unwrap());
+
+    // the presentation format is read back
+    let tokens = rdata.to_string()
+        .split(' ')
+        .map(|s| Token::CharData(s.to_string()))
+        .collect::<Vec<_>>();
+    assert_eq!(parse(&tokens, None).unwrap(), rdata);
+    assert_eq!(tokens[2], Token::CharData("alpn=dot,h2".to_string()));
+    assert_eq!(tokens[7], Token::CharData("key65001=\"a\\000\"".to_string()));
+
+    assert!(parse(&tokens[..1].to_vec(), None).is_err());
+    assert!(parse(&vec![Token::CharData("1".to_string()),
+                        Token::CharData(".".to_string()),
+                        Token::CharData("nonsense=1".to_string())],
+                  None)
+        .is_err());
+}
@@ -0,0 +1,328 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! TLSA records, the certificates or public keys of TLS services, for DANE
+
+use ::serialize::txt::*;
+use ::serialize::binary::*;
+use ::error::*;
+
+/// [RFC 6698, DANE TLSA, August 2012](https://tools.ietf.org/html/rfc6698#section-2.1)
+///
+/// ```text
+/// 2.1.  TLSA RDATA Wire Format
+///
+///    The RDATA for a TLSA RR consists of a one-octet certificate usage
+///    field, a one-octet selector field, a one-octet matching type field,
+///    and the certificate association data field.
+///
+///                         1 1 1 1 1 1 1 1 1 1 2 2 2 2 2 2 2 2 2 2 3 3
+///     0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+///    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+///    |  Cert. Usage  |   Selector    | Matching Type |               /
+///    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+               /
+///    /                                                               /
+///    /                 Certificate Association Data                  /
+///    /                                                               /
+///    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// ```
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct TLSA {
+    cert_usage: CertUsage,
+    selector: Selector,
+    matching: Matching,
+    cert_data: Vec<u8>,
+}
+
+/// [RFC 6698, DANE TLSA, August 2012](https://tools.ietf.org/html/rfc6698#section-2.1.1), with
+///  the acronyms of [RFC 7218](https://tools.ietf.org/html/rfc7218#section-2.1)
+///
+/// ```text
+/// 2.1.1.  The Certificate Usage Field
+///
+///    A one-octet value, called "certificate usage", specifies the provided
+///    association that will be used to match the certificate presented in
+///    the TLS handshake.
+/// ```
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum CertUsage {
+    /// PKIX-TA, a CA of the chain, which must also pass PKIX validation
+    CA,
+    /// PKIX-EE, the end entity certificate, which must also pass PKIX validation
+    Service,
+    /// DANE-TA, a trust anchor of the chain, whether or not it is otherwise trusted
+    TrustAnchor,
+    /// DANE-EE, the end entity certificate, without PKIX validation
+    DomainIssued,
+    /// a value which is not assigned
+    Unassigned(u8),
+    /// 255, private use
+    Private,
+}
+
+impl From<u8> for CertUsage {
+    fn from(usage: u8) -> Self {
+        match usage {
+            0 => CertUsage::CA,
+            1 => CertUsage::Service,
+            2 => CertUsage::TrustAnchor,
+            3 => CertUsage::DomainIssued,
+            255 => CertUsage::Private,
+            _ => CertUsage::Unassigned(usage),
+        }
+    }
+}
+
+impl From<CertUsage> for u8 {
+    fn from(usage: CertUsage) -> Self {
+        match usage {
+            CertUsage::CA => 0,
+            CertUsage::Service => 1,
+            CertUsage::TrustAnchor => 2,
+            CertUsage::DomainIssued => 3,
+            CertUsage::Private => 255,
+            CertUsage::Unassigned(usage) => usage,
+        }
+    }
+}
+
+/// [RFC 6698, DANE TLSA, August 2012](https://tools.ietf.org/html/rfc6698#section-2.1.2)
+///
+/// ```text
+/// 2.1.2.  The Selector Field
+///
+///    A one-octet value, called "selector", specifies which part of the TLS
+///    certificate presented by the server will be matched against the
+///    association data.
+/// ```
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum Selector {
+    /// the DER of the full certificate
+    Full,
+    /// the DER of the SubjectPublicKeyInfo of the certificate
+    Spki,
+    /// a value which is not assigned
+    Unassigned(u8),
+    /// 255, private use
+    Private,
+}
+
+impl From<u8> for Selector {
+    fn from(selector: u8) -> Self {
+        match selector {
+            0 => Selector::Full,
+            1 => Selector::Spki,
+            255 => Selector::Private,
+            _ => Selector::Unassigned(selector),
+        }
+    }
+}
+
+impl From<Selector> for u8 {
+    fn from(selector: Selector) -> Self {
+        match selector {
+            Selector::Full => 0,
+            Selector::Spki => 1,
+            Selector::Private => 255,
+            Selector::Unassigned(selector) => selector,
+        }
+    }
+}
+
+/// [RFC 6698, DANE TLSA, August 2012](https://tools.ietf.org/html/rfc6698#section-2.1.3)
+///
+/// ```text
+/// 2.1.3.  The Matching Type Field
+///
+///    A one-octet value, called "matching type", specifies how the
+///    certificate association is presented.
+/// ```
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum Matching {
+    /// the selected content itself
+    Raw,
+    /// the SHA-256 hash of the selected content
+    Sha256,
+    /// the SHA-512 hash of the selected content
+    Sha512,
+    /// a value which is not assigned
+    Unassigned(u8),
+    /// 255, private use
+    Private,
+}
+
+impl From<u8> for Matching {
+    fn from(matching: u8) -> Self {
+        match matching {
+            0 => Matching::Raw,
+            1 => Matching::Sha256,
+            2 => Matching::Sha512,
+            255 => Matching::Private,
+            _ => Matching::Unassigned(matching),
+        }
+    }
+}
+
+impl From<Matching> for u8 {
+    fn from(matching: Matching) -> Self {
+        match matching {
+            Matching::Raw => 0,
+            Matching::Sha256 => 1,
+            Matching::Sha512 => 2,
+            Matching::Private => 255,
+            Matching::Unassigned(matching) => matching,
+        }
+    }
+}
+
+impl TLSA {
+    pub fn new(cert_usage: CertUsage,
+               selector: Selector,
+               matching: Matching,
+               cert_data: Vec<u8>)
+               -> TLSA {
+        TLSA {
+            cert_usage: cert_usage,
+            selector: selector,
+            matching: matching,
+            cert_data: cert_data,
+        }
+    }
+
+    /// how the certificate of the TLS handshake is matched
+    pub fn get_cert_usage(&self) -> CertUsage {
+        self.cert_usage
+    }
+
+    /// the part of the certificate which is matched
+    pub fn get_selector(&self) -> Selector {
+        self.selector
+    }
+
+    /// how the selected part of the certificate is presented in the association data
+    pub fn get_matching(&self) -> Matching {
+        self.matching
+    }
+
+    /// the certificate association data, the selected part of the certificate or its hash
+    pub fn get_cert_data(&self) -> &[u8] {
+        &self.cert_data
+    }
+}
+
+pub fn read(decoder: &mut BinDecoder, rdata_length: u16) -> DecodeResult<TLSA> {
+    if rdata_length < 3 {
+        return Err(DecodeErrorKind::Message("TLSA rdata is shorter than its fixed fields").into());
+    }
+
+    let cert_usage = CertUsage::from(try!(decoder.read_u8()));
+    let selector = Selector::from(try!(decoder.read_u8()));
+    let matching = Matching::from(try!(decoder.read_u8()));
+    let cert_data = try!(decoder.read_vec(rdata_length as usize - 3));
+
+    Ok(TLSA::new(cert_usage, selector, matching, cert_data))
+}
+
+pub fn emit(encoder: &mut BinEncoder, tlsa: &TLSA) -> EncodeResult {
+    try!(encoder.emit(tlsa.get_cert_usage().into()));
+    try!(encoder.emit(tlsa.get_selector().into()));
+    try!(encoder.emit(tlsa.get_matching().into()));
+    try!(encoder.emit_vec(tlsa.get_cert_data()));
+    Ok(())
+}
+
+/// [RFC 6698, DANE TLSA, August 2012](https://tools.ietf.org/html/rfc6698#section-2.2)
+///
+/// ```text
+/// 2.2.  TLSA RR Presentation Format
+///
+///    The presentation format of the RDATA portion is as follows:
+///
+///    o  The certificate usage field MUST be represented as an 8-bit
+///       unsigned integer.
+///
+///    o  The selector field MUST be represented as an 8-bit unsigned
+///       integer.
+///
+///    o  The matching type field MUST be represented as an 8-bit unsigned
+///       integer.
+///
+///    o  The certificate association data field MUST be represented as a
+///       string of hexadecimal characters.  Whitespace is allowed within
+///       the string of hexadecimal characters, as described in [RFC1035].
+/// ```
+pub fn parse(tokens: &Vec<Token>) -> ParseResult<TLSA> {
+    let mut token = tokens.iter();
+
+    let mut next_u8 = |field: &str| -> ParseResult<u8> {
+        token.next()
+            .ok_or(ParseError::from(ParseErrorKind::MissingToken(field.to_string())))
+            .and_then(|t| if let &Token::CharData(ref s) = t {
+                Ok(try!(s.parse()))
+            } else {
+                Err(ParseErrorKind::UnexpectedToken(t.clone()).into())
+            })
+    };
+
+    let cert_usage = CertUsage::from(try!(next_u8("cert usage")));
+    let selector = Selector::from(try!(next_u8("selector")));
+    let matching = Matching::from(try!(next_u8("matching type")));
+
+    // the data may be split over words, or lines in parentheses
+    let mut cert_data: Vec<u8> = Vec::new();
+    for t in tokens.iter().skip(3) {
+        let words: Vec<&str> = match *t {
+            Token::CharData(ref hex) => vec![hex],
+            Token::List(ref v) => v.iter().map(|s| s as &str).collect(),
+            _ => return Err(ParseErrorKind::UnexpectedToken(t.clone()).into()),
+        };
+
+        for hex in words {
+            if hex.len() % 2 != 0 || !hex.chars().all(|ch| ch.is_digit(16)) {
+                return Err(ParseErrorKind::Msg(format!("invalid hex data: {}", hex)).into());
+            }
+
+            for index in 0..hex.len() / 2 {
+                cert_data.push(try!(u8::from_str_radix(&hex[index * 2..index * 2 + 2], 16)));
+            }
+        }
+    }
+
+    if cert_data.is_empty() {
+        return Err(ParseErrorKind::MissingToken("cert association data".to_string()).into());
+    }
+
+    Ok(TLSA::new(cert_usage, selector, matching, cert_data))
+}
+
+#[test]
+pub fn test() {
+    let rdata = TLSA::new(CertUsage::DomainIssued,
+                          Selector::Spki,
+                          Matching::Sha256,
+                          vec![0x0C, 0x72, 0xAC, 0x70]);
+
+    let mut bytes = Vec::new();
+    let mut encoder: BinEncoder = BinEncoder::new(&mut bytes);
+    assert!(emit(&mut encoder, &rdata).is_ok());
+    let bytes = encoder.as_bytes();
+
+    let mut decoder: BinDecoder = BinDecoder::new(bytes);
+    let read_rdata = read(&mut decoder, bytes.len() as u16);
+    assert!(read_rdata.is_ok(),
+            format!("error decoding: {:?}", read_rdata.unwrap_err()));
+    assert_eq!(rdata, read_rdata.unwrap());
+
+    let tokens = vec![Token::CharData("3".to_string()),
+                      Token::CharData("1".to_string()),
+                      Token::CharData("1".to_string()),
+                      Token::CharData("0c72".to_string()),
+                      Token::CharData("AC70".to_string())];
+    assert_eq!(parse(&tokens).unwrap(), rdata);
+    assert!(parse(&tokens[..3].to_vec()).is_err());
+    assert_eq!(u8::from(CertUsage::from(7)), 7);
+}
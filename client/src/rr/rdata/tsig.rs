@@ -0,0 +1,180 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! TSIG records, the transaction signatures of a message with a shared secret
+
+use ::serialize::binary::*;
+use ::error::*;
+use ::rr::domain::Name;
+
+/// [RFC 8945, Secret Key Transaction Authentication for DNS, November 2020](https://tools.ietf.org/html/rfc8945#section-4.2)
+///
+/// ```text
+/// 4.2.  TSIG Record Format
+///
+///    The RDATA for the TSIG RR consists of a number of fields, described
+///    below:
+///
+///                         1 1 1 1 1 1 1 1 1 1 2 2 2 2 2 2 2 2 2 2 3 3
+///     0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+///    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+///    /                         Algorithm Name                        /
+///    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+///    |                                                               |
+///    |          Time Signed          +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+///    |                               |            Fudge              |
+///    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+///    |          MAC Size             |                               /
+///    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+             MAC               /
+///    /                                                               /
+///    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+///    |          Original ID          |            Error              |
+///    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+///    |          Other Len            |                               /
+///    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+           Other Data          /
+///    /                                                               /
+///    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// ```
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct TSIG {
+    algorithm: Name,
+    time_signed: u64,
+    fudge: u16,
+    mac: Vec<u8>,
+    original_id: u16,
+    error: u16,
+    other_data: Vec<u8>,
+}
+
+impl TSIG {
+    pub fn new(algorithm: Name,
+               time_signed: u64,
+               fudge: u16,
+               mac: Vec<u8>,
+               original_id: u16,
+               error: u16,
+               other_data: Vec<u8>)
+               -> TSIG {
+        TSIG {
+            algorithm: algorithm,
+            time_signed: time_signed,
+            fudge: fudge,
+            mac: mac,
+            original_id: original_id,
+            error: error,
+            other_data: other_data,
+        }
+    }
+
+    /// the name of the MAC algorithm, e.g. `hmac-sha256.`
+    pub fn get_algorithm(&self) -> &Name {
+        &self.algorithm
+    }
+
+    /// seconds since the epoch when the message was signed, only the low 48 bits are sent
+    pub fn get_time_signed(&self) -> u64 {
+        self.time_signed
+    }
+
+    /// seconds of error permitted in the time signed
+    pub fn get_fudge(&self) -> u16 {
+        self.fudge
+    }
+
+    /// the message authentication code
+    pub fn get_mac(&self) -> &[u8] {
+        &self.mac
+    }
+
+    /// the id of the message when it was signed
+    pub fn get_original_id(&self) -> u16 {
+        self.original_id
+    }
+
+    /// the extended rcode of TSIG, e.g. BADSIG, 0 in requests
+    pub fn get_error(&self) -> u16 {
+        self.error
+    }
+
+    /// the server time on BADTIME errors, otherwise empty
+    pub fn get_other_data(&self) -> &[u8] {
+        &self.other_data
+    }
+}
+
+pub fn read(decoder: &mut BinDecoder, rdata_length: u16) -> DecodeResult<TSIG> {
+    let start_idx = decoder.index();
+
+    let algorithm = try!(Name::read(decoder));
+    let time_high = try!(decoder.read_u16()) as u64;
+    let time_low = try!(decoder.read_u32()) as u64;
+    let fudge = try!(decoder.read_u16());
+    let mac_size = try!(decoder.read_u16());
+    let mac = try!(decoder.read_vec(mac_size as usize));
+    let original_id = try!(decoder.read_u16());
+    let error = try!(decoder.read_u16());
+    let other_len = try!(decoder.read_u16());
+    let other_data = try!(decoder.read_vec(other_len as usize));
+
+    if decoder.index() - start_idx != rdata_length as usize {
+        return Err(DecodeErrorKind::IncorrectRDataLengthRead(decoder.index() - start_idx,
+                                                             rdata_length as usize)
+            .into());
+    }
+
+    Ok(TSIG::new(algorithm,
+                 time_high << 32 | time_low,
+                 fudge,
+                 mac,
+                 original_id,
+                 error,
+                 other_data))
+}
+
+pub fn emit(encoder: &mut BinEncoder, tsig: &TSIG) -> EncodeResult {
+    // the algorithm name is never compressed, RFC 8945 section 4.2
+    try!(tsig.get_algorithm().emit_as_canonical(encoder, true));
+    try!(emit_time(encoder, tsig.get_time_signed()));
+    try!(encoder.emit_u16(tsig.get_fudge()));
+    try!(encoder.emit_u16(tsig.get_mac().len() as u16));
+    try!(encoder.emit_vec(tsig.get_mac()));
+    try!(encoder.emit_u16(tsig.get_original_id()));
+    try!(encoder.emit_u16(tsig.get_error()));
+    try!(encoder.emit_u16(tsig.get_other_data().len() as u16));
+    try!(encoder.emit_vec(tsig.get_other_data()));
+    Ok(())
+}
+
+/// Emits the 48-bit time signed of TSIG
+pub fn emit_time(encoder: &mut BinEncoder, time_signed: u64) -> EncodeResult {
+    try!(encoder.emit_u16((time_signed >> 32) as u16));
+    encoder.emit_u32(time_signed as u32)
+}
+
+#[test]
+fn test() {
+    let rdata = TSIG::new(Name::parse("hmac-sha256.", None).unwrap(),
+                          0x0000_1234_5678_9abc,
+                          300,
+                          vec![1, 2, 3, 4],
+                          0xbeef,
+                          0,
+                          vec![]);
+
+    let mut bytes = Vec::new();
+    let mut encoder: BinEncoder = BinEncoder::new(&mut bytes);
+    assert!(emit(&mut encoder, &rdata).is_ok());
+    let bytes = encoder.as_bytes();
+
+    println!("bytes: {:?}", bytes);
+
+    let mut decoder: BinDecoder = BinDecoder::new(bytes);
+    let read_rdata = read(&mut decoder, bytes.len() as u16);
+    assert!(read_rdata.is_ok(),
+            format!("error decoding: {:?}", read_rdata.unwrap_err()));
+    assert_eq!(rdata, read_rdata.unwrap());
+}
@@ -70,6 +70,21 @@ pub fn read(decoder: &mut BinDecoder, rdata_length: u16) -> DecodeResult<TXT> {
     Ok(TXT::new(strings))
 }
 
+/// Checks that there is at least one string, and that each fits in a `<character-string>`
+pub fn validate(txt: &TXT) -> DecodeResult<()> {
+    if txt.txt_data.is_empty() {
+        return Err(DecodeErrorKind::InvalidRData("TXT has no strings".to_string()).into());
+    }
+
+    if let Some(s) = txt.txt_data.iter().find(|s| s.len() > 255) {
+        return Err(DecodeErrorKind::InvalidRData(format!("TXT string of {} bytes, maximum is 255",
+                                                         s.len()))
+            .into());
+    }
+
+    Ok(())
+}
+
 pub fn emit(encoder: &mut BinEncoder, txt: &TXT) -> EncodeResult {
     for s in txt.get_txt_data() {
         try!(encoder.emit_character_data(s));
@@ -107,3 +122,10 @@ fn test() {
             format!("error decoding: {:?}", read_rdata.unwrap_err()));
     assert_eq!(rdata, read_rdata.unwrap());
 }
+
+#[test]
+fn test_validate() {
+    assert!(validate(&TXT::new(vec!["abc".to_string(), "def".to_string()])).is_ok());
+    assert!(validate(&TXT::new(vec![])).is_err());
+    assert!(validate(&TXT::new(vec![::std::iter::repeat("a").take(256).collect()])).is_err());
+}
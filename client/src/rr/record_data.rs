@@ -20,6 +20,7 @@ use std::net::{Ipv4Addr, Ipv6Addr};
 #[cfg(test)]
 use std::convert::From;
 use std::cmp::Ordering;
+use std::fmt;
 
 use ::error::*;
 use ::serialize::binary::*;
@@ -27,7 +28,8 @@ use ::serialize::txt::*;
 use super::domain::Name;
 use super::record_type::RecordType;
 use super::rdata;
-use super::rdata::{DNSKEY, DS, MX, NSEC, NSEC3, NSEC3PARAM, NULL, OPT, SIG, SOA, SRV, TXT};
+use super::rdata::{DNSKEY, DS, LOC, MX, NSEC, NSEC3, NSEC3PARAM, NULL, OPT, RP, SIG, SOA, SRV,
+                   SVCB, TLSA, TSIG, TXT};
 
 /// Record data enum variants
 ///
@@ -112,6 +114,17 @@ pub enum RData {
     // the description of name server logic in [RFC-1034] for details.
     CNAME(Name),
 
+    // RFC 6672                   DNAME Redirection                  June 2012
+    //
+    // 2.1.  Format of the DNAME RR
+    //
+    //    The format of the DNAME RR is identical to that of the CNAME RR, whose
+    //    RDATA is a single <target>, a domain name.
+    //
+    //    The DNAME RR substitutes its owner name with the target in all the
+    //    names below the owner, it has no effect on the owner name itself.
+    DNAME(Name),
+
     // RFC 4034                DNSSEC Resource Records               March 2005
     //
     // 2.1.  DNSKEY RDATA Wire Format
@@ -287,6 +300,34 @@ pub enum RData {
     // [RFC-974].
     MX(MX),
 
+    // RFC 1876        Location Information in the DNS         January 1996
+    //
+    // The geographic location of the owner, version 0 of the format
+    LOC(LOC),
+
+    // 3.3.3. MB RDATA format (EXPERIMENTAL)
+    //
+    //     +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+    //     /                   MADNAME                     /
+    //     /                                               /
+    //     +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+    //
+    // MADNAME         A <domain-name> which specifies a host which has the
+    //                 specified mailbox.
+    MB(Name),
+
+    // 3.3.6. MG RDATA format (EXPERIMENTAL)
+    //
+    // MGMNAME         A <domain-name> which specifies a mailbox which is a
+    //                 member of the mail group specified by the domain name.
+    MG(Name),
+
+    // 3.3.8. MR RDATA format (EXPERIMENTAL)
+    //
+    // NEWNAME         A <domain-name> which specifies a mailbox which is the
+    //                 proper rename of the specified mailbox.
+    MR(Name),
+
     // 3.3.10. NULL RDATA format (EXPERIMENTAL)
     //
     //     +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
@@ -326,6 +367,11 @@ pub enum RData {
     // class information are normally queried using IN class protocols.
     NS(Name),
 
+    // RFC 1183              New DNS RR Definitions              October 1990
+    //
+    // <owner> <ttl> <class> RP <mbox-dname> <txt-dname>
+    RP(RP),
+
     // RFC 4034                DNSSEC Resource Records               March 2005
     //
     // 4.1.  NSEC RDATA Wire Format
@@ -612,6 +658,11 @@ pub enum RData {
     //SOA { mname: Name, rname: Name, serial: u32, refresh: i32, retry: i32, expire: i32, minimum: u32, },
     SOA(SOA),
 
+    // RFC 4408                 Sender Policy Framework              April 2006
+    //
+    // The SPF RR is identical in format to TXT, RFC 7208 obsoletes it in favor of TXT
+    SPF(TXT),
+
     // RFC 2782                       DNS SRV RR                  February 2000
     //
     // The format of the SRV RR
@@ -619,6 +670,22 @@ pub enum RData {
     //  _Service._Proto.Name TTL Class SRV Priority Weight Port Target
     SRV(SRV),
 
+    // RFC 9460                   SVCB and HTTPS RRs               November 2023
+    //
+    // An alternative endpoint of the service at the owner name, with its parameters
+    SVCB(SVCB),
+
+    // RFC 6698                  DANE TLSA                  August 2012
+    //
+    // The certificate, or its public key, of the TLS service at the owner name,
+    //  _<port>._<protocol>.<name>
+    TLSA(TLSA),
+
+    // RFC 8945           Secret Key Transaction Authentication      November 2020
+    //
+    // The transaction signature of a message, always the last record of the additional section
+    TSIG(TSIG),
+
     // 3.3.14. TXT RDATA format
     //
     //     +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
@@ -632,6 +699,11 @@ pub enum RData {
     // TXT RRs are used to hold descriptive text.  The semantics of the text
     // depends on the domain where it is found.
     TXT(TXT),
+
+    // RFC 3597        Handling of Unknown DNS RR Types       September 2003
+    //
+    // Any other type, the data is opaque, the type and the data as it was received
+    Unknown(u16, NULL),
 }
 
 impl RData {
@@ -639,29 +711,48 @@ impl RData {
                  tokens: &Vec<Token>,
                  origin: Option<&Name>)
                  -> ParseResult<Self> {
+        // RFC 3597, the generic form is accepted for any type, decoded for those which are known
+        if rdata::null::is_generic(tokens) {
+            let null = try!(rdata::null::parse(tokens));
+            let bytes = null.get_anything().cloned().unwrap_or(Vec::new());
+            return Ok(try!(RData::read(&mut BinDecoder::new(&bytes),
+                                       record_type,
+                                       bytes.len() as u16)));
+        }
+
         let rdata = match record_type {
             RecordType::A => RData::A(try!(rdata::a::parse(tokens))),
             RecordType::AAAA => RData::AAAA(try!(rdata::aaaa::parse(tokens))),
-            RecordType::ANY => panic!("parsing ANY doesn't make sense"), // valid panic, never should happen
-            RecordType::AXFR => panic!("parsing AXFR doesn't make sense"), // valid panic, never should happen
             RecordType::CNAME => RData::CNAME(try!(rdata::name::parse(tokens, origin))),
-            RecordType::KEY => panic!("KEY should be dynamically generated"), // valid panic, never should happen
-            RecordType::DNSKEY => panic!("DNSKEY should be dynamically generated"), // valid panic, never should happen
-            RecordType::DS => panic!("DS should be dynamically generated"), // valid panic, never should happen
-            RecordType::IXFR => panic!("parsing IXFR doesn't make sense"), // valid panic, never should happen
+            RecordType::DNAME => RData::DNAME(try!(rdata::name::parse(tokens, origin))),
+            RecordType::LOC => RData::LOC(try!(rdata::loc::parse(tokens))),
+            RecordType::MB => RData::MB(try!(rdata::name::parse(tokens, origin))),
+            RecordType::MG => RData::MG(try!(rdata::name::parse(tokens, origin))),
+            RecordType::MR => RData::MR(try!(rdata::name::parse(tokens, origin))),
             RecordType::MX => RData::MX(try!(rdata::mx::parse(tokens, origin))),
             RecordType::NULL => RData::NULL(try!(rdata::null::parse(tokens))),
             RecordType::NS => RData::NS(try!(rdata::name::parse(tokens, origin))),
-            RecordType::NSEC => panic!("NSEC should be dynamically generated"), // valid panic, never should happen
-            RecordType::NSEC3 => panic!("NSEC3 should be dynamically generated"), // valid panic, never should happen
-            RecordType::NSEC3PARAM => panic!("NSEC3PARAM should be dynamically generated"), // valid panic, never should happen
-            RecordType::OPT => panic!("parsing OPT doesn't make sense"), // valid panic, never should happen
             RecordType::PTR => RData::PTR(try!(rdata::name::parse(tokens, origin))),
-            RecordType::RRSIG => panic!("RRSIG should be dynamically generated"), // valid panic, never should happen
-            RecordType::SIG => panic!("parsing SIG doesn't make sense"), // valid panic, never should happen
+            RecordType::RP => RData::RP(try!(rdata::rp::parse(tokens, origin))),
             RecordType::SOA => RData::SOA(try!(rdata::soa::parse(tokens, origin))),
+            RecordType::SPF => RData::SPF(try!(rdata::txt::parse(tokens))),
             RecordType::SRV => RData::SRV(try!(rdata::srv::parse(tokens, origin))),
+            RecordType::SVCB => RData::SVCB(try!(rdata::svcb::parse(tokens, origin))),
+            RecordType::TLSA => RData::TLSA(try!(rdata::tlsa::parse(tokens))),
             RecordType::TXT => RData::TXT(try!(rdata::txt::parse(tokens))),
+            // the DNSSEC records are generated by signing, the others are not records of a zone
+            RecordType::ANY | RecordType::AXFR | RecordType::DNSKEY | RecordType::DS |
+            RecordType::IXFR | RecordType::KEY | RecordType::MAILB | RecordType::NSEC |
+            RecordType::NSEC3 | RecordType::NSEC3PARAM | RecordType::OPT | RecordType::RRSIG |
+            RecordType::SIG | RecordType::TSIG => {
+                return Err(ParseErrorKind::UnsupportedRecordType(record_type).into())
+            }
+            RecordType::Unknown(_) => {
+                return Err(ParseErrorKind::Msg(format!("{} is only in the generic form: \\# \
+                                                        <length> <hex>",
+                                                       record_type))
+                    .into())
+            }
         };
 
         Ok(rdata)
@@ -704,6 +795,10 @@ impl RData {
                 debug!("reading CNAME");
                 RData::CNAME(try!(rdata::name::read(decoder)))
             }
+            RecordType::DNAME => {
+                debug!("reading DNAME");
+                RData::DNAME(try!(rdata::name::read(decoder)))
+            }
             RecordType::KEY => {
                 debug!("reading KEY");
                 RData::KEY(try!(rdata::dnskey::read(decoder, rdata_length)))
@@ -719,6 +814,31 @@ impl RData {
             rt @ RecordType::IXFR => {
                 return Err(DecodeErrorKind::UnknownRecordTypeValue(rt.into()).into())
             }
+            RecordType::LOC => {
+                debug!("reading LOC");
+                // other versions of the format are kept as they are
+                if decoder.peek() == Some(0) {
+                    RData::LOC(try!(rdata::loc::read(decoder)))
+                } else {
+                    RData::Unknown(record_type.into(),
+                                   try!(rdata::null::read(decoder, rdata_length)))
+                }
+            }
+            rt @ RecordType::MAILB => {
+                return Err(DecodeErrorKind::UnknownRecordTypeValue(rt.into()).into())
+            }
+            RecordType::MB => {
+                debug!("reading MB");
+                RData::MB(try!(rdata::name::read(decoder)))
+            }
+            RecordType::MG => {
+                debug!("reading MG");
+                RData::MG(try!(rdata::name::read(decoder)))
+            }
+            RecordType::MR => {
+                debug!("reading MR");
+                RData::MR(try!(rdata::name::read(decoder)))
+            }
             RecordType::MX => {
                 debug!("reading MX");
                 RData::MX(try!(rdata::mx::read(decoder)))
@@ -751,6 +871,10 @@ impl RData {
                 debug!("reading PTR");
                 RData::PTR(try!(rdata::name::read(decoder)))
             }
+            RecordType::RP => {
+                debug!("reading RP");
+                RData::RP(try!(rdata::rp::read(decoder)))
+            }
             RecordType::RRSIG => {
                 debug!("reading RRSIG");
                 RData::SIG(try!(rdata::sig::read(decoder, rdata_length)))
@@ -763,14 +887,34 @@ impl RData {
                 debug!("reading SOA");
                 RData::SOA(try!(rdata::soa::read(decoder)))
             }
+            RecordType::SPF => {
+                debug!("reading SPF");
+                RData::SPF(try!(rdata::txt::read(decoder, rdata_length)))
+            }
             RecordType::SRV => {
                 debug!("reading SRV");
                 RData::SRV(try!(rdata::srv::read(decoder)))
             }
+            RecordType::SVCB => {
+                debug!("reading SVCB");
+                RData::SVCB(try!(rdata::svcb::read(decoder, rdata_length)))
+            }
+            RecordType::TLSA => {
+                debug!("reading TLSA");
+                RData::TLSA(try!(rdata::tlsa::read(decoder, rdata_length)))
+            }
+            RecordType::TSIG => {
+                debug!("reading TSIG");
+                RData::TSIG(try!(rdata::tsig::read(decoder, rdata_length)))
+            }
             RecordType::TXT => {
                 debug!("reading TXT");
                 RData::TXT(try!(rdata::txt::read(decoder, rdata_length)))
             }
+            RecordType::Unknown(value) => {
+                debug!("reading Unknown: {}", value);
+                RData::Unknown(value, try!(rdata::null::read(decoder, rdata_length)))
+            }
         };
 
         // we should have read rdata_length, but we did not
@@ -779,9 +923,34 @@ impl RData {
             return Err(DecodeErrorKind::IncorrectRDataLengthRead(read, rdata_length as usize)
                 .into());
         }
+
+        // lenient, what others send is passed on, but noted
+        if let Err(e) = result.validate() {
+            warn!("received {:?}: {}", record_type, e);
+        }
         Ok(result)
     }
 
+    /// Checks the semantics of the data, beyond what is required to decode it
+    ///
+    /// e.g. TXT strings which fit in a `<character-string>`, a DS digest of the correct length, or
+    ///  SOA timers that are not negative. Decoding only warns about invalid data, an `Authority`
+    ///  rejects it.
+    pub fn validate(&self) -> DecodeResult<()> {
+        match *self {
+            RData::DS(ref ds) => rdata::ds::validate(ds),
+            RData::KEY(ref key) => rdata::dnskey::validate(key),
+            RData::DNSKEY(ref dnskey) => rdata::dnskey::validate(dnskey),
+            RData::MX(ref mx) => rdata::mx::validate(mx),
+            RData::NSEC3(ref nsec3) => rdata::nsec3::validate(nsec3),
+            RData::SOA(ref soa) => rdata::soa::validate(soa),
+            RData::TXT(ref txt) |
+            RData::SPF(ref txt) => rdata::txt::validate(txt),
+            // A and AAAA are fixed length, which is checked when they are read
+            _ => Ok(()),
+        }
+    }
+
     /// [RFC 4034](https://tools.ietf.org/html/rfc4034#section-6), DNSSEC Resource Records, March 2005
     ///
     /// ```text
@@ -804,11 +973,18 @@ impl RData {
             RData::AAAA(ref address) => rdata::aaaa::emit(encoder, address),
             // to_lowercase for rfc4034 and rfc6840
             RData::CNAME(ref name) => rdata::name::emit(encoder, name),
+            // to_lowercase for rfc4034 and rfc6840
+            RData::DNAME(ref name) => rdata::name::emit(encoder, name),
             RData::DS(ref ds) => rdata::ds::emit(encoder, ds),
             RData::KEY(ref key) => rdata::dnskey::emit(encoder, key),
             RData::DNSKEY(ref dnskey) => rdata::dnskey::emit(encoder, dnskey),
             // to_lowercase for rfc4034 and rfc6840
             RData::MX(ref mx) => rdata::mx::emit(encoder, mx),
+            RData::LOC(ref loc) => rdata::loc::emit(encoder, loc),
+            // to_lowercase for rfc4034 and rfc6840
+            RData::MB(ref name) |
+            RData::MG(ref name) |
+            RData::MR(ref name) => rdata::name::emit(encoder, name),
             RData::NULL(ref null) => rdata::null::emit(encoder, null),
             // to_lowercase for rfc4034 and rfc6840
             RData::NS(ref name) => rdata::name::emit(encoder, name),
@@ -819,12 +995,19 @@ impl RData {
             // to_lowercase for rfc4034 and rfc6840
             RData::PTR(ref name) => rdata::name::emit(encoder, name),
             // to_lowercase for rfc4034 and rfc6840
+            RData::RP(ref rp) => rdata::rp::emit(encoder, rp),
+            // to_lowercase for rfc4034 and rfc6840
             RData::SIG(ref sig) => rdata::sig::emit(encoder, sig),
             // to_lowercase for rfc4034 and rfc6840
             RData::SOA(ref soa) => rdata::soa::emit(encoder, soa),
             // to_lowercase for rfc4034 and rfc6840
             RData::SRV(ref srv) => rdata::srv::emit(encoder, srv),
-            RData::TXT(ref txt) => rdata::txt::emit(encoder, txt),
+            RData::SVCB(ref svcb) => rdata::svcb::emit(encoder, svcb),
+            RData::TLSA(ref tlsa) => rdata::tlsa::emit(encoder, tlsa),
+            RData::TSIG(ref tsig) => rdata::tsig::emit(encoder, tsig),
+            RData::TXT(ref txt) |
+            RData::SPF(ref txt) => rdata::txt::emit(encoder, txt),
+            RData::Unknown(_, ref null) => rdata::null::emit(encoder, null),
         }
     }
 
@@ -833,9 +1016,14 @@ impl RData {
             RData::A(..) => RecordType::A,
             RData::AAAA(..) => RecordType::AAAA,
             RData::CNAME(..) => RecordType::CNAME,
+            RData::DNAME(..) => RecordType::DNAME,
             RData::DS(..) => RecordType::DS,
             RData::KEY(..) => RecordType::KEY,
             RData::DNSKEY(..) => RecordType::DNSKEY,
+            RData::LOC(..) => RecordType::LOC,
+            RData::MB(..) => RecordType::MB,
+            RData::MG(..) => RecordType::MG,
+            RData::MR(..) => RecordType::MR,
             RData::MX(..) => RecordType::MX,
             RData::NS(..) => RecordType::NS,
             RData::NSEC(..) => RecordType::NSEC,
@@ -844,10 +1032,16 @@ impl RData {
             RData::NULL(..) => RecordType::NULL,
             RData::OPT(..) => RecordType::OPT,
             RData::PTR(..) => RecordType::PTR,
+            RData::RP(..) => RecordType::RP,
             RData::SIG(..) => RecordType::SIG,
             RData::SOA(..) => RecordType::SOA,
+            RData::SPF(..) => RecordType::SPF,
             RData::SRV(..) => RecordType::SRV,
+            RData::SVCB(..) => RecordType::SVCB,
+            RData::TLSA(..) => RecordType::TLSA,
+            RData::TSIG(..) => RecordType::TSIG,
             RData::TXT(..) => RecordType::TXT,
+            RData::Unknown(value, _) => RecordType::Unknown(value),
         }
     }
 }
@@ -861,9 +1055,14 @@ impl<'a> From<&'a RData> for RecordType {
             RData::A(..) => RecordType::A,
             RData::AAAA(..) => RecordType::AAAA,
             RData::CNAME(..) => RecordType::CNAME,
+            RData::DNAME(..) => RecordType::DNAME,
             RData::DS(..) => RecordType::DS,
             RData::KEY(..) => RecordType::KEY,
             RData::DNSKEY(..) => RecordType::DNSKEY,
+            RData::LOC(..) => RecordType::LOC,
+            RData::MB(..) => RecordType::MB,
+            RData::MG(..) => RecordType::MG,
+            RData::MR(..) => RecordType::MR,
             RData::MX(..) => RecordType::MX,
             RData::NS(..) => RecordType::NS,
             RData::NSEC(..) => RecordType::NSEC,
@@ -872,10 +1071,98 @@ impl<'a> From<&'a RData> for RecordType {
             RData::NULL(..) => RecordType::NULL,
             RData::OPT(..) => RecordType::OPT,
             RData::PTR(..) => RecordType::PTR,
+            RData::RP(..) => RecordType::RP,
             RData::SIG(..) => RecordType::SIG,
             RData::SOA(..) => RecordType::SOA,
+            RData::SPF(..) => RecordType::SPF,
             RData::SRV(..) => RecordType::SRV,
+            RData::SVCB(..) => RecordType::SVCB,
+            RData::TLSA(..) => RecordType::TLSA,
+            RData::TSIG(..) => RecordType::TSIG,
             RData::TXT(..) => RecordType::TXT,
+            RData::Unknown(value, _) => RecordType::Unknown(value),
+        }
+    }
+}
+
+/// The presentation format of master files, RFC 1035 section 5.1
+///
+/// The types that `parse()` reads are written the same way, so that they can be read back. Other
+///  types are written in the generic format of RFC 3597, section 5, e.g. `\# 2 0102`.
+impl fmt::Display for RData {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RData::A(ref address) => write!(f, "{}", address),
+            RData::AAAA(ref address) => write!(f, "{}", address),
+            RData::CNAME(ref name) |
+            RData::DNAME(ref name) |
+            RData::MB(ref name) |
+            RData::MG(ref name) |
+            RData::MR(ref name) |
+            RData::NS(ref name) |
+            RData::PTR(ref name) => write!(f, "{}", name),
+            RData::LOC(ref loc) => write!(f, "{}", loc),
+            RData::MX(ref mx) => write!(f, "{} {}", mx.get_preference(), mx.get_exchange()),
+            RData::RP(ref rp) => write!(f, "{} {}", rp.get_mbox(), rp.get_txt()),
+            RData::SOA(ref soa) => {
+                write!(f,
+                       "{} {} {} {} {} {} {}",
+                       soa.get_mname(),
+                       soa.get_rname(),
+                       soa.get_serial(),
+                       soa.get_refresh(),
+                       soa.get_retry(),
+                       soa.get_expire(),
+                       soa.get_minimum())
+            }
+            RData::SRV(ref srv) => {
+                write!(f,
+                       "{} {} {} {}",
+                       srv.get_priority(),
+                       srv.get_weight(),
+                       srv.get_port(),
+                       srv.get_target())
+            }
+            RData::SVCB(ref svcb) => write!(f, "{}", svcb),
+            RData::TLSA(ref tlsa) => {
+                try!(write!(f,
+                            "{} {} {} ",
+                            u8::from(tlsa.get_cert_usage()),
+                            u8::from(tlsa.get_selector()),
+                            u8::from(tlsa.get_matching())));
+                for byte in tlsa.get_cert_data() {
+                    try!(write!(f, "{:02X}", byte));
+                }
+                Ok(())
+            }
+            RData::TXT(ref txt) |
+            RData::SPF(ref txt) => {
+                for (index, string) in txt.get_txt_data().iter().enumerate() {
+                    if index > 0 {
+                        try!(write!(f, " "));
+                    }
+                    try!(write!(f, "\""));
+                    for ch in string.chars() {
+                        match ch {
+                            '"' | '\\' => try!(write!(f, "\\{}", ch)),
+                            ch => try!(write!(f, "{}", ch)),
+                        }
+                    }
+                    try!(write!(f, "\""));
+                }
+                Ok(())
+            }
+            _ => {
+                let bytes = self.to_bytes();
+                try!(write!(f, "\\# {}", bytes.len()));
+                if !bytes.is_empty() {
+                    try!(write!(f, " "));
+                }
+                for byte in bytes {
+                    try!(write!(f, "{:02X}", byte));
+                }
+                Ok(())
+            }
         }
     }
 }
@@ -923,7 +1210,9 @@ mod tests {
     use ::serialize::binary::*;
     use serialize::binary::bin_tests::test_emit_data_set;
     use rr::domain::Name;
-    use rr::rdata::{MX, SOA, SRV, TXT};
+    use rr::rdata::{MX, NULL, RP, SOA, SRV, TXT};
+    use rr::record_type::RecordType;
+    use serialize::txt::Token;
 
     fn get_data() -> Vec<(RData, Vec<u8>)> {
         vec![(RData::CNAME(Name::with_labels(vec!["www".to_string(),
@@ -931,6 +1220,9 @@ mod tests {
                                                   "com".to_string()])),
               vec![3, b'w', b'w', b'w', 7, b'e', b'x', b'a', b'm', b'p', b'l', b'e', 3, b'c',
                    b'o', b'm', 0]),
+             (RData::DNAME(Name::with_labels(vec!["example".to_string(),
+                                                  "net".to_string()])),
+              vec![7, b'e', b'x', b'a', b'm', b'p', b'l', b'e', 3, b'n', b'e', b't', 0]),
              (RData::MX(MX::new(256, Name::with_labels(vec!["n".to_string()]))),
               vec![1, 0, 1, b'n', 0]),
              (RData::NS(Name::with_labels(vec!["www".to_string(),
@@ -973,7 +1265,14 @@ mod tests {
                                                          "example".to_string(),
                                                          "com".to_string()]))),
               vec![0x00, 0x01, 0x00, 0x02, 0x00, 0x03, 3, b'w', b'w', b'w', 7, b'e', b'x', b'a',
-                   b'm', b'p', b'l', b'e', 3, b'c', b'o', b'm', 0])]
+                   b'm', b'p', b'l', b'e', 3, b'c', b'o', b'm', 0]),
+             (RData::MB(Name::with_labels(vec!["n".to_string()])), vec![1, b'n', 0]),
+             (RData::RP(RP::new(Name::with_labels(vec!["a".to_string()]),
+                                Name::with_labels(vec!["b".to_string()]))),
+              vec![1, b'a', 0, 1, b'b', 0]),
+             (RData::SPF(TXT::new(vec!["v=spf1".to_string()])),
+              vec![6, b'v', b'=', b's', b'p', b'f', b'1']),
+             (RData::Unknown(1234, NULL::with(vec![1, 2, 3])), vec![1, 2, 3])]
     }
 
     // TODO this test kinda sucks, shows the problem with not storing the binary parts
@@ -1073,4 +1372,23 @@ mod tests {
     fn test_write_to() {
         test_emit_data_set(get_data(), |e, d| d.emit(e));
     }
+
+    #[test]
+    fn test_parse_generic() {
+        let tokens = |text: &str| -> Vec<Token> {
+            text.split_whitespace().map(|word| Token::CharData(word.to_string())).collect()
+        };
+
+        assert_eq!(RData::parse(RecordType::A, &tokens("\\# 4 C0000201"), None).unwrap(),
+                   RData::A(Ipv4Addr::new(192, 0, 2, 1)));
+        assert_eq!(RData::parse(RecordType::Unknown(1234), &tokens("\\# 2 0102"), None).unwrap(),
+                   RData::Unknown(1234, NULL::with(vec![1, 2])));
+        assert!(RData::parse(RecordType::Unknown(1234), &tokens("0102"), None).is_err());
+
+        // a LOC of a version which is not known is kept as it is
+        let loc = tokens("\\# 16 01120000000000000000000000000000");
+        let rdata = RData::parse(RecordType::LOC, &loc, None).unwrap();
+        assert_eq!(rdata.to_record_type(), RecordType::LOC);
+        assert_eq!(rdata.to_string(), "\\# 16 01120000000000000000000000000000");
+    }
 }
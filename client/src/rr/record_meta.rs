@@ -0,0 +1,207 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! annotations of records, their comments and tags in master files, kept apart from the records
+
+use std::collections::BTreeMap;
+use std::collections::btree_map;
+
+use rr::{DNSClass, RData, Record, RecordType, RrKey};
+use rr::rr_set::is_commented_type;
+
+/// The comments and tags of a record, as written in master files
+///
+/// The comments on the lines before the entry of the record, and the one at the end of its line,
+///  are those of the record. A comment `@<name>=<value>`, e.g. `; @source=ipam`, is a tag, such
+///  as where the record came from, rather than a comment.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RecordMeta {
+    comments: Vec<String>,
+    tags: BTreeMap<String, String>,
+}
+
+impl RecordMeta {
+    /// Creates metadata without comments or tags
+    pub fn new() -> Self {
+        RecordMeta::default()
+    }
+
+    /// Sorts the comments of an entry into its comments and tags, see `Lexer::take_comments`
+    ///
+    /// The RRSIG, NSEC and NSEC3 records written as comments by `RecordSet::to_zone_file` are
+    ///  skipped, they are generated again when the zone is signed.
+    pub fn from_comments(comments: Vec<String>) -> Self {
+        let mut meta = RecordMeta::new();
+        for comment in comments {
+            if is_commented_entry(&comment) {
+                continue;
+            }
+
+            match parse_tag(&comment) {
+                Some((name, value)) => meta.set_tag(name, value),
+                None => meta.add_comment(comment),
+            }
+        }
+        meta
+    }
+
+    /// Adds a comment, without its `;`
+    pub fn add_comment(&mut self, comment: String) {
+        self.comments.push(comment);
+    }
+
+    pub fn get_comments(&self) -> &[String] {
+        &self.comments
+    }
+
+    /// Sets the tag, replacing any previous value
+    pub fn set_tag(&mut self, name: String, value: String) {
+        self.tags.insert(name, value);
+    }
+
+    pub fn get_tag(&self, name: &str) -> Option<&str> {
+        self.tags.get(name).map(|value| value.as_str())
+    }
+
+    pub fn get_tags(&self) -> &BTreeMap<String, String> {
+        &self.tags
+    }
+
+    /// Returns true if there are neither comments nor tags
+    pub fn is_empty(&self) -> bool {
+        self.comments.is_empty() && self.tags.is_empty()
+    }
+
+    /// The lines of the comments, then the tags, as they are written in master files, without
+    ///  their `;`
+    pub fn to_comments(&self) -> Vec<String> {
+        let mut lines: Vec<String> = Vec::new();
+        for comment in &self.comments {
+            lines.extend(comment.lines().map(|line| line.to_string()));
+        }
+        for (name, value) in &self.tags {
+            lines.push(format!("@{}={}", name, value.replace('\n', " ")));
+        }
+        lines
+    }
+}
+
+/// The metadata of the records of a zone, which the records do not carry themselves
+///
+/// The metadata is that of a record, by its name, type and data, whatever its TTL. It is kept
+///  for a zone read from a master file, see `Parser::parse_with_meta`, so that the comments
+///  and tags are written again when the zone is, see `RecordSet::to_annotated_zone_file`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RecordMetaTable {
+    entries: BTreeMap<(RrKey, RData), RecordMeta>,
+}
+
+impl RecordMetaTable {
+    /// Creates an empty table
+    pub fn new() -> Self {
+        RecordMetaTable::default()
+    }
+
+    /// Returns the metadata of the record, if any
+    pub fn get(&self, record: &Record) -> Option<&RecordMeta> {
+        self.entries.get(&Self::key(record))
+    }
+
+    /// Returns the metadata of the record to change, added if there is none
+    pub fn get_mut(&mut self, record: &Record) -> &mut RecordMeta {
+        self.entries.entry(Self::key(record)).or_insert_with(RecordMeta::new)
+    }
+
+    /// Sets the metadata of the record, an empty one removes it
+    ///
+    /// # Return value
+    ///
+    /// The previous metadata of the record, if any
+    pub fn insert(&mut self, record: &Record, meta: RecordMeta) -> Option<RecordMeta> {
+        if meta.is_empty() {
+            return self.remove(record);
+        }
+        self.entries.insert(Self::key(record), meta)
+    }
+
+    /// Removes the metadata of the record
+    pub fn remove(&mut self, record: &Record) -> Option<RecordMeta> {
+        self.entries.remove(&Self::key(record))
+    }
+
+    /// The number of records with metadata
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The metadata of all the records, by their key and data
+    pub fn iter(&self) -> btree_map::Iter<(RrKey, RData), RecordMeta> {
+        self.entries.iter()
+    }
+
+    fn key(record: &Record) -> (RrKey, RData) {
+        (RrKey::new(record.get_name(), record.get_rr_type()), record.get_rdata().clone())
+    }
+}
+
+/// Returns true if the comment is the entry of a generated DNSSEC record, as `RecordSet::to_zone_file`
+///  writes them, `<name> <ttl> <class> <type> <rdata>`
+fn is_commented_entry(comment: &str) -> bool {
+    let fields: Vec<&str> = comment.split_whitespace().take(5).collect();
+    fields.len() == 5 && fields[1].parse::<u32>().is_ok() &&
+    DNSClass::from_str(fields[2]).is_ok() &&
+    RecordType::from_str(fields[3]).map(is_commented_type).unwrap_or(false)
+}
+
+/// The name and value of a tag, `@<name>=<value>`, the name without whitespace
+fn parse_tag(comment: &str) -> Option<(String, String)> {
+    if !comment.starts_with('@') {
+        return None;
+    }
+
+    let mut parts = comment[1..].splitn(2, '=');
+    let name = parts.next().unwrap_or("");
+    let value = match parts.next() {
+        Some(value) => value,
+        None => return None,
+    };
+    if name.is_empty() || name.contains(char::is_whitespace) {
+        return None;
+    }
+
+    Some((name.to_string(), value.trim().to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_comments() {
+        let meta = RecordMeta::from_comments(vec!["managed by ipam".to_string(),
+                                                  "@source=ipam".to_string(),
+                                                  "@not a tag=1".to_string(),
+                                                  "www 300 IN RRSIG A 8 3 300 1 0 1 e. AA=="
+                                                      .to_string(),
+                                                  "www 300 IN A 192.0.2.1".to_string()]);
+
+        assert_eq!(meta.get_comments(),
+                   &["managed by ipam".to_string(),
+                     "@not a tag=1".to_string(),
+                     "www 300 IN A 192.0.2.1".to_string()]);
+        assert_eq!(meta.get_tag("source"), Some("ipam"));
+        assert_eq!(meta.to_comments(),
+                   vec!["managed by ipam".to_string(),
+                        "@not a tag=1".to_string(),
+                        "www 300 IN A 192.0.2.1".to_string(),
+                        "@source=ipam".to_string()]);
+    }
+}
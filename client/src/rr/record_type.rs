@@ -18,6 +18,8 @@
 
 use std::convert::From;
 use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
 
 use ::serialize::binary::*;
 use ::error::*;
@@ -38,7 +40,7 @@ pub enum RecordType {
     CNAME, //	5	RFC 1035[1]	Canonical name record
     //  DHCID,      //	49	RFC 4701	DHCP identifier
     //  DLV,        //	32769	RFC 4431	DNSSEC Lookaside Validation record
-    //  DNAME,      //	39	RFC 2672	Delegation Name
+    DNAME, //	39	RFC 6672	Delegation Name, redirection of a subtree
     DNSKEY, //	48	RFC 4034	DNS Key record: RSASHA256 and RSASHA512, RFC5702
     DS, //	43	RFC 4034	Delegation signer: RSASHA256 and RSASHA512, RFC5702
     //  HIP,        //	55	RFC 5205	Host Identity Protocol
@@ -46,7 +48,11 @@ pub enum RecordType {
     IXFR, //	251	RFC 1996	Incremental Zone Transfer
     KEY, //	25	RFC 2535[3] and RFC 2930[4]	Key record
     //  KX,         //	36	RFC 2230	Key eXchanger record
-    //  LOC,        //	29	RFC 1876	Location record
+    LOC, //	29	RFC 1876	Location record
+    MAILB, //	253	RFC 1035[1]	Mailbox-related records, MB, MG or MR, only in queries
+    MB, //	7	RFC 1035[1]	Mailbox domain name, experimental
+    MG, //	8	RFC 1035[1]	Mail group member, experimental
+    MR, //	9	RFC 1035[1]	Mail rename domain name, experimental
     MX, //	15	RFC 1035[1]	Mail exchange record
     //  NAPTR,      //	35	RFC 3403	Naming Authority Pointer
     NS, //	2	RFC 1035[1]	Name server record
@@ -57,52 +63,89 @@ pub enum RecordType {
     OPT, //	41	RFC 6891	Option
     PTR, //	12	RFC 1035[1]	Pointer record
     RRSIG, //	46	RFC 4034	DNSSEC signature: RSASHA256 and RSASHA512, RFC5702
-    //  RP,         //	17	RFC 1183	Responsible person
+    RP, //	17	RFC 1183	Responsible person
     SIG, //	24	RFC 2535 (2931)	Signature, to support 2137 Update
     SOA, //	6	RFC 1035[1] and RFC 2308[9]	Start of [a zone of] authority record
+    SPF, //	99	RFC 4408	Sender Policy Framework, obsoleted by TXT in RFC 7208
     SRV, //	33	RFC 2782	Service locator
     //  SSHFP,      //	44	RFC 4255	SSH Public Key Fingerprint
+    SVCB, //	64	RFC 9460	Service binding
     //  TA,         //	32768	N/A	DNSSEC Trust Authorities
     //  TKEY,       //	249	RFC 2930	Secret key record
-    //  TLSA,       //	52	RFC 6698	TLSA certificate association
-    //  TSIG,       //	250	RFC 2845	Transaction Signature
+    TLSA, //	52	RFC 6698	TLSA certificate association
+    TSIG, //	250	RFC 8945	Transaction Signature
     TXT, //	16	RFC 1035[1]	Text record
+    /// any other type, its data is opaque, RFC 3597
+    Unknown(u16),
 }
 
 impl RecordType {
-    /// Convert from RecordType to &str
+    /// Convert from &str to RecordType, ignoring case as in master files
     ///
     /// ```
     /// use trust_dns::rr::record_type::RecordType;
     ///
     /// let var: RecordType = RecordType::from_str("A").unwrap();
     /// assert_eq!(RecordType::A, var);
+    /// assert_eq!(RecordType::DNSKEY, RecordType::from_str("dnskey").unwrap());
+    ///
+    /// // the generic form, RFC 3597
+    /// assert_eq!(RecordType::A, RecordType::from_str("TYPE1").unwrap());
+    /// assert_eq!(RecordType::Unknown(1234), RecordType::from_str("type1234").unwrap());
     /// ```
     pub fn from_str(str: &str) -> DecodeResult<Self> {
-        match str {
+        match &str.to_uppercase() as &str {
             "A" => Ok(RecordType::A),
             "AAAA" => Ok(RecordType::AAAA),
             "CNAME" => Ok(RecordType::CNAME),
+            "DNAME" => Ok(RecordType::DNAME),
+            "DNSKEY" => Ok(RecordType::DNSKEY),
+            "DS" => Ok(RecordType::DS),
+            "KEY" => Ok(RecordType::KEY),
+            "LOC" => Ok(RecordType::LOC),
+            "MAILB" => Ok(RecordType::MAILB),
+            "MB" => Ok(RecordType::MB),
+            "MG" => Ok(RecordType::MG),
+            "MR" => Ok(RecordType::MR),
             "NULL" => Ok(RecordType::NULL),
             "MX" => Ok(RecordType::MX),
             "NS" => Ok(RecordType::NS),
+            "NSEC" => Ok(RecordType::NSEC),
+            "NSEC3" => Ok(RecordType::NSEC3),
+            "NSEC3PARAM" => Ok(RecordType::NSEC3PARAM),
+            "OPT" => Ok(RecordType::OPT),
             "PTR" => Ok(RecordType::PTR),
+            "RP" => Ok(RecordType::RP),
+            "RRSIG" => Ok(RecordType::RRSIG),
+            "SIG" => Ok(RecordType::SIG),
             "SOA" => Ok(RecordType::SOA),
+            "SPF" => Ok(RecordType::SPF),
             "SRV" => Ok(RecordType::SRV),
+            "SVCB" => Ok(RecordType::SVCB),
+            "TLSA" => Ok(RecordType::TLSA),
+            "TSIG" => Ok(RecordType::TSIG),
             "TXT" => Ok(RecordType::TXT),
             "ANY" | "*" => Ok(RecordType::ANY),
             "AXFR" => Ok(RecordType::AXFR),
+            "IXFR" => Ok(RecordType::IXFR),
+            upper if upper.starts_with("TYPE") => {
+                match upper[4..].parse::<u16>() {
+                    Ok(value) => Self::from_u16(value),
+                    Err(_) => Err(DecodeErrorKind::UnknownRecordTypeStr(str.to_string()).into()),
+                }
+            }
             _ => Err(DecodeErrorKind::UnknownRecordTypeStr(str.to_string()).into()),
         }
     }
 
-    /// Convert from RecordType to &str
+    /// Convert from u16 to RecordType, types which are not known are `Unknown`
     ///
     /// ```
     /// use trust_dns::rr::record_type::RecordType;
     ///
     /// let var = RecordType::from_u16(1).unwrap();
     /// assert_eq!(RecordType::A, var);
+    /// assert_eq!(RecordType::Unknown(1234), RecordType::from_u16(1234).unwrap());
     /// ```
     pub fn from_u16(value: u16) -> DecodeResult<Self> {
         match value {
@@ -111,9 +154,16 @@ impl RecordType {
             255 => Ok(RecordType::ANY),
             252 => Ok(RecordType::AXFR),
             5 => Ok(RecordType::CNAME),
+            39 => Ok(RecordType::DNAME),
             48 => Ok(RecordType::DNSKEY),
             43 => Ok(RecordType::DS),
+            251 => Ok(RecordType::IXFR),
             25 => Ok(RecordType::KEY),
+            29 => Ok(RecordType::LOC),
+            253 => Ok(RecordType::MAILB),
+            7 => Ok(RecordType::MB),
+            8 => Ok(RecordType::MG),
+            9 => Ok(RecordType::MR),
             15 => Ok(RecordType::MX),
             2 => Ok(RecordType::NS),
             47 => Ok(RecordType::NSEC),
@@ -122,13 +172,17 @@ impl RecordType {
             0 => Ok(RecordType::NULL),
             41 => Ok(RecordType::OPT),
             12 => Ok(RecordType::PTR),
+            17 => Ok(RecordType::RP),
             46 => Ok(RecordType::RRSIG),
             24 => Ok(RecordType::SIG),
             6 => Ok(RecordType::SOA),
+            99 => Ok(RecordType::SPF),
             33 => Ok(RecordType::SRV),
+            64 => Ok(RecordType::SVCB),
+            52 => Ok(RecordType::TLSA),
+            250 => Ok(RecordType::TSIG),
             16 => Ok(RecordType::TXT),
-            // TODO: this should probably return a generic value wrapper.
-            _ => Err(DecodeErrorKind::UnknownRecordTypeValue(value).into()),
+            _ => Ok(RecordType::Unknown(value)),
         }
     }
 }
@@ -167,10 +221,16 @@ impl From<RecordType> for &'static str {
             RecordType::ANY => "ANY",
             RecordType::AXFR => "AXFR",
             RecordType::CNAME => "CNAME",
+            RecordType::DNAME => "DNAME",
             RecordType::DNSKEY => "DNSKEY",
             RecordType::DS => "DS",
             RecordType::IXFR => "IXFR",
             RecordType::KEY => "KEY",
+            RecordType::LOC => "LOC",
+            RecordType::MAILB => "MAILB",
+            RecordType::MB => "MB",
+            RecordType::MG => "MG",
+            RecordType::MR => "MR",
             RecordType::MX => "MX",
             RecordType::NULL => "NULL",
             RecordType::NS => "NS",
@@ -179,11 +239,55 @@ impl From<RecordType> for &'static str {
             RecordType::NSEC3PARAM => "NSEC3PARAM",
             RecordType::OPT => "OPT",
             RecordType::PTR => "PTR",
+            RecordType::RP => "RP",
             RecordType::RRSIG => "RRSIG",
             RecordType::SIG => "SIG",
             RecordType::SOA => "SOA",
+            RecordType::SPF => "SPF",
             RecordType::SRV => "SRV",
+            RecordType::SVCB => "SVCB",
+            RecordType::TLSA => "TLSA",
+            RecordType::TSIG => "TSIG",
             RecordType::TXT => "TXT",
+            // the number is only in the Display form, TYPEnnn
+            RecordType::Unknown(_) => "Unknown",
+        }
+    }
+}
+
+/// Parses the mnemonic of the type, e.g. for command line arguments and config files
+///
+/// ```
+/// use trust_dns::rr::record_type::RecordType;
+///
+/// let var: RecordType = "AAAA".parse().unwrap();
+/// assert_eq!(RecordType::AAAA, var);
+/// ```
+impl FromStr for RecordType {
+    type Err = DecodeError;
+
+    fn from_str(s: &str) -> DecodeResult<Self> {
+        RecordType::from_str(s)
+    }
+}
+
+/// The mnemonic of the type, as in master files, or TYPEnnn for types which are not known,
+///  RFC 3597
+///
+/// ```
+/// use trust_dns::rr::record_type::RecordType;
+///
+/// assert_eq!("MX", RecordType::MX.to_string());
+/// assert_eq!("TYPE1234", RecordType::Unknown(1234).to_string());
+/// ```
+impl fmt::Display for RecordType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RecordType::Unknown(value) => write!(f, "TYPE{}", value),
+            _ => {
+                let mnemonic: &'static str = (*self).into();
+                f.write_str(mnemonic)
+            }
         }
     }
 }
@@ -205,10 +309,16 @@ impl From<RecordType> for u16 {
             RecordType::ANY => 255,
             RecordType::AXFR => 252,
             RecordType::CNAME => 5,
+            RecordType::DNAME => 39,
             RecordType::KEY => 25,
             RecordType::DNSKEY => 48,
             RecordType::DS => 43,
             RecordType::IXFR => 251,
+            RecordType::LOC => 29,
+            RecordType::MAILB => 253,
+            RecordType::MB => 7,
+            RecordType::MG => 8,
+            RecordType::MR => 9,
             RecordType::MX => 15,
             RecordType::NS => 2,
             RecordType::NULL => 0,
@@ -217,11 +327,17 @@ impl From<RecordType> for u16 {
             RecordType::NSEC3PARAM => 51,
             RecordType::OPT => 41,
             RecordType::PTR => 12,
+            RecordType::RP => 17,
             RecordType::RRSIG => 46,
             RecordType::SIG => 24,
             RecordType::SOA => 6,
+            RecordType::SPF => 99,
             RecordType::SRV => 33,
+            RecordType::SVCB => 64,
+            RecordType::TLSA => 52,
+            RecordType::TSIG => 250,
             RecordType::TXT => 16,
+            RecordType::Unknown(value) => value,
         }
     }
 }
@@ -276,3 +392,18 @@ fn test_order() {
 
     assert_eq!(ordered, unordered);
 }
+
+#[test]
+fn test_unknown() {
+    for value in [7u16, 8, 9, 17, 29, 52, 99, 253, 1234, 65535].iter() {
+        let rtype = RecordType::from_u16(*value).unwrap();
+        assert_eq!(u16::from(rtype), *value);
+        assert_eq!(RecordType::from_str(&rtype.to_string()).unwrap(), rtype);
+    }
+
+    assert_eq!(RecordType::from_str("SPF").unwrap(), RecordType::SPF);
+    assert_eq!(RecordType::from_str("TYPE99").unwrap(), RecordType::SPF);
+    assert!(RecordType::from_str("TYPE65536").is_err());
+    assert!(RecordType::from_str("TYPE").is_err());
+    assert!(RecordType::Unknown(1234) > RecordType::SRV);
+}
@@ -18,17 +18,24 @@
 
 use std::sync::Arc as Rc;
 use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
 
 use ::serialize::binary::*;
+use ::serialize::txt::{Lexer, Parser, Token};
 use ::error::*;
 use rr::dns_class::DNSClass;
 use rr::domain;
 use rr::IntoRecordSet;
+use rr::rdata;
 use rr::rdata::NULL;
 use rr::RData;
 use rr::RecordType;
 use rr::RecordSet;
 
+/// the top bit of the CLASS in mDNS responses
+const MDNS_CACHE_FLUSH: u16 = 0x8000;
+
 /// Resource records are storage value in DNS, into which all key/value pair data is stored.
 ///
 /// [RFC 1035](https://tools.ietf.org/html/rfc1035), DOMAIN NAMES - IMPLEMENTATION AND SPECIFICATION, November 1987
@@ -69,6 +76,7 @@ pub struct Record {
     dns_class: DNSClass,
     ttl: u32,
     rdata: RData,
+    mdns_cache_flush: bool,
 }
 
 impl Record {
@@ -84,6 +92,7 @@ impl Record {
             dns_class: DNSClass::IN,
             ttl: 0,
             rdata: RData::NULL(NULL::new()),
+            mdns_cache_flush: false,
         }
     }
 
@@ -101,6 +110,7 @@ impl Record {
             dns_class: DNSClass::IN,
             ttl: ttl,
             rdata: RData::NULL(NULL::new()),
+            mdns_cache_flush: false,
         }
     }
 
@@ -123,6 +133,7 @@ impl Record {
             dns_class: DNSClass::IN,
             ttl: ttl,
             rdata: rdata,
+            mdns_cache_flush: false,
         }
     }
 
@@ -181,6 +192,13 @@ impl Record {
         self
     }
 
+    /// marks the record as the complete set of its name and type in an mDNS response, the top
+    ///  bit of the CLASS, see `is_mdns_cache_flush()`
+    pub fn mdns_cache_flush(&mut self, mdns_cache_flush: bool) -> &mut Self {
+        self.mdns_cache_flush = mdns_cache_flush;
+        self
+    }
+
     pub fn get_name(&self) -> &domain::Name {
         &self.name_labels
     }
@@ -202,6 +220,22 @@ impl Record {
     pub fn unwrap_rdata(self) -> RData {
         self.rdata
     }
+
+    /// [RFC 6762](https://tools.ietf.org/html/rfc6762#section-10.2), Multicast DNS, February 2013
+    ///
+    /// ```text
+    /// 10.2.  Announcements to Flush Outdated Cache Entries
+    ///
+    ///    ... the host sets the most significant bit of the rrclass
+    ///    field of the resource record.  This bit, the "cache-flush" bit, tells
+    ///    neighboring hosts that this is not a shared record type.
+    /// ```
+    ///
+    /// The bit is stripped from the CLASS when decoding any message, and it is not part of the
+    ///  equality of records.
+    pub fn is_mdns_cache_flush(&self) -> bool {
+        self.mdns_cache_flush
+    }
 }
 
 impl IntoRecordSet for Record {
@@ -222,6 +256,7 @@ impl BinSerializable<Record> for Record {
         let record_type: RecordType = try!(RecordType::read(decoder));
 
         // CLASS           two octets containing one of the RR CLASS codes.
+        let mut mdns_cache_flush = false;
         let class: DNSClass = if record_type == RecordType::OPT {
             // verify that the OPT record is Root
             if !name_labels.is_root() {
@@ -232,7 +267,10 @@ impl BinSerializable<Record> for Record {
             DNSClass::for_opt(try!(decoder.read_u16()))
 
         } else {
-            try!(DNSClass::read(decoder))
+            // the top bit is the cache-flush bit of mDNS, RFC 6762
+            let class = try!(decoder.read_u16());
+            mdns_cache_flush = class & MDNS_CACHE_FLUSH != 0;
+            try!(DNSClass::from_u16(class & !MDNS_CACHE_FLUSH))
         };
 
         // TTL             a 32 bit signed integer that specifies the time interval
@@ -249,6 +287,10 @@ impl BinSerializable<Record> for Record {
         // RDLENGTH        an unsigned 16 bit integer that specifies the length in
         //                octets of the RDATA field.
         let rd_length: u16 = try!(decoder.read_u16());
+        if rd_length > decoder.limits().max_rdata_len {
+            return Err(DecodeErrorKind::RDataTooLong(rd_length, decoder.limits().max_rdata_len)
+                .into());
+        }
 
         // this is to handle updates, RFC 2136, which uses 0 to indicate certain aspects of
         //  pre-requisites
@@ -267,13 +309,18 @@ impl BinSerializable<Record> for Record {
             dns_class: class,
             ttl: ttl,
             rdata: rdata,
+            mdns_cache_flush: mdns_cache_flush,
         })
     }
 
     fn emit(&self, encoder: &mut BinEncoder) -> EncodeResult {
         try!(self.name_labels.emit(encoder));
         try!(self.rr_type.emit(encoder));
-        try!(self.dns_class.emit(encoder));
+        if self.mdns_cache_flush {
+            try!(encoder.emit_u16(u16::from(self.dns_class) | MDNS_CACHE_FLUSH));
+        } else {
+            try!(self.dns_class.emit(encoder));
+        }
         try!(encoder.emit_u32(self.ttl));
 
         // gah... need to write rdata before we know the size of rdata...
@@ -283,6 +330,7 @@ impl BinSerializable<Record> for Record {
             let mut tmp_encoder: BinEncoder = BinEncoder::with_offset(&mut tmp_buf,
                                                                       encoder.offset() + 2, /*for u16 len*/
                                                                       EncodeMode::Normal);
+            tmp_encoder.set_canonical_names(encoder.is_canonical_names());
             try!(self.rdata.emit(&mut tmp_encoder));
         }
 
@@ -300,6 +348,103 @@ impl BinSerializable<Record> for Record {
     }
 }
 
+/// The record as a single line of a master file, e.g. `www.example.com. 300 IN A 192.0.2.1`
+impl fmt::Display for Record {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f,
+               "{} {} {} {} {}",
+               self.name_labels,
+               self.ttl,
+               self.dns_class,
+               self.rr_type,
+               self.rdata)
+    }
+}
+
+/// Parses a single line of a master file, the inverse of `Display`
+///
+/// The name must be fully qualified, there is no origin, and the TTL is required. The class, IN
+///  by default, and the TTL may be in either order before the type. The types which are read
+///  from master files are supported, A, AAAA, CNAME, LOC, MB, MG, MR, MX, NS, PTR, RP, SOA, SPF,
+///  SRV, SVCB, TLSA and TXT, any other type only in the generic form of RFC 3597,
+///  `\# <length> <hex>`.
+///
+/// ```
+/// use trust_dns::rr::{Record, RecordType};
+///
+/// let record: Record = "www.example.com. 300 IN A 192.0.2.1".parse().unwrap();
+/// assert_eq!(record.get_rr_type(), RecordType::A);
+/// assert_eq!(record.get_ttl(), 300);
+/// assert_eq!(record.to_string(), "www.example.com. 300 IN A 192.0.2.1");
+/// ```
+impl FromStr for Record {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> ParseResult<Self> {
+        let mut lexer = Lexer::new(s);
+        let mut tokens: Vec<Token> = Vec::new();
+        let mut end_of_line = false;
+        while let Some(token) = try!(lexer.next_token()) {
+            match token {
+                Token::EOL => end_of_line = true,
+                token if end_of_line => {
+                    return Err(ParseErrorKind::UnexpectedToken(token).into())
+                }
+                token => tokens.push(token),
+            }
+        }
+        let mut tokens = tokens.into_iter();
+
+        let name = match tokens.next() {
+            Some(Token::CharData(name)) => try!(domain::Name::parse(&name, None)),
+            Some(token) => return Err(ParseErrorKind::UnexpectedToken(token).into()),
+            None => return Err(ParseErrorKind::MissingToken("name".to_string()).into()),
+        };
+
+        // the TTL and class may come in either order, then the type
+        let mut ttl: Option<u32> = None;
+        let mut dns_class: Option<DNSClass> = None;
+        let rr_type: RecordType;
+        loop {
+            let data = match tokens.next() {
+                Some(Token::CharData(data)) => data,
+                Some(token) => return Err(ParseErrorKind::UnexpectedToken(token).into()),
+                None => return Err(ParseErrorKind::MissingToken("type".to_string()).into()),
+            };
+
+            // a TTL starts with a digit, `Parser::parse_time()` would take e.g. HS as 0
+            if ttl.is_none() && data.starts_with(|ch: char| ch.is_digit(10)) {
+                ttl = Some(try!(Parser::parse_time(&data)));
+            } else if dns_class.is_none() && DNSClass::from_str(&data).is_ok() {
+                dns_class = Some(try!(DNSClass::from_str(&data)));
+            } else {
+                rr_type = try!(RecordType::from_str(&data));
+                break;
+            }
+        }
+
+        let rdata_tokens: Vec<Token> = tokens.collect();
+        match rr_type {
+            RecordType::A | RecordType::AAAA | RecordType::CNAME | RecordType::DNAME |
+            RecordType::LOC | RecordType::MB | RecordType::MG | RecordType::MR | RecordType::MX |
+            RecordType::NS | RecordType::PTR | RecordType::RP | RecordType::SOA |
+            RecordType::SPF | RecordType::SRV | RecordType::SVCB | RecordType::TLSA |
+            RecordType::TXT => (),
+            // the generic form of RFC 3597, `\# <length> <hex>`
+            _ if rdata::null::is_generic(&rdata_tokens) => (),
+            _ => return Err(ParseErrorKind::Message("record type can not be parsed").into()),
+        }
+
+        let ttl =
+            try!(ttl.ok_or(ParseError::from(ParseErrorKind::Message("record ttl not specified"))));
+        let rdata = try!(RData::parse(rr_type, &rdata_tokens, None));
+
+        let mut record = Record::from_rdata(name, ttl, rr_type, rdata);
+        record.dns_class(dns_class.unwrap_or(DNSClass::IN));
+        Ok(record)
+    }
+}
+
 impl PartialEq for Record {
     /// Equality or records, as defined by
     ///  [RFC 2136](https://tools.ietf.org/html/rfc2136), DNS Update, April 1997
@@ -420,6 +565,28 @@ mod tests {
         assert_eq!(got, record);
     }
 
+    #[test]
+    fn test_emit_and_read_mdns_cache_flush() {
+        let mut record = Record::new();
+        record.name(Name::new().label("printer").label("local"))
+            .ttl(120)
+            .rdata(RData::A(Ipv4Addr::new(192, 168, 0, 1)))
+            .mdns_cache_flush(true);
+
+        let mut vec_bytes: Vec<u8> = Vec::with_capacity(512);
+        {
+            let mut encoder = BinEncoder::new(&mut vec_bytes);
+            record.emit(&mut encoder).unwrap();
+        }
+
+        let mut decoder = BinDecoder::new(&vec_bytes);
+        let got = Record::read(&mut decoder).unwrap();
+
+        assert_eq!(got.get_dns_class(), DNSClass::IN);
+        assert!(got.is_mdns_cache_flush());
+        assert_eq!(got, record);
+    }
+
     #[test]
     fn test_order() {
         let mut record = Record::new();
@@ -454,4 +621,51 @@ mod tests {
             assert_eq!(r.cmp(g), Ordering::Less);
         }
     }
+
+    #[test]
+    fn test_from_str() {
+        let records = ["www.example.com. 300 IN A 192.0.2.1",
+                       "www.example.com. 300 IN AAAA 2001:db8::1",
+                       "example.com. 3600 IN MX 10 mail.example.com.",
+                       "example.com. 3600 IN SOA ns.example.com. hostmaster.example.com. 1 \
+                        7200 600 3600000 60",
+                       "_ldap._tcp.example.com. 60 IN SRV 0 5 389 ldap.example.com.",
+                       "example.com. 60 IN TXT \"v=spf1 -all\" \"say \\\"hi\\\"\"",
+                       "example.com. 60 IN SPF \"v=spf1 -all\"",
+                       "example.com. 60 IN RP admin.example.com. contact.example.com.",
+                       "example.com. 60 IN LOC 42 21 54.000 N 71 6 18.000 W -24m 30m 10000m 10m",
+                       "example.com. 60 IN MB mail.example.com.",
+                       "_853._tcp.example.com. 60 IN TLSA 3 1 1 0C72AC70",
+                       "_dns.resolver.arpa. 60 IN SVCB 1 dot.example.net. alpn=dot port=853",
+                       "example.com. 60 IN TYPE1234 \\# 3 0A0B0C",
+                       "example.com. 60 IN TYPE1234 \\# 0"];
+
+        for string in records.iter() {
+            let record: Record = string.parse().unwrap();
+            assert_eq!(record.to_string(), *string);
+        }
+
+        // either order of TTL and class, IN by default, any case
+        let record: Record = "www.example.com. in 1h cname web.example.com.".parse().unwrap();
+        assert_eq!(record.get_ttl(), 3600);
+        assert_eq!(record.get_dns_class(), DNSClass::IN);
+        assert_eq!(record,
+                   "www.example.com. 3600 CNAME web.example.com.".parse::<Record>().unwrap());
+        assert_eq!(record.to_string(),
+                   "www.example.com. 3600 IN CNAME web.example.com.");
+        let record: Record = "www.example.com. HS 60 A 192.0.2.1".parse().unwrap();
+        assert_eq!(record.get_dns_class(), DNSClass::HS);
+
+        // the generic form of a known type, RFC 3597
+        assert_eq!("www.example.com. 60 IN A \\# 4 C0000201".parse::<Record>().unwrap(),
+                   "www.example.com. 60 IN A 192.0.2.1".parse::<Record>().unwrap());
+
+        assert!("www.example.com. IN A 192.0.2.1".parse::<Record>().is_err());
+        assert!("www.example.com. 60 IN TYPE1234 0A0B0C".parse::<Record>().is_err());
+        assert!("www.example.com. 60 IN A \\# 3 C00002".parse::<Record>().is_err());
+        assert!("www.example.com. 60 IN DNSKEY 256 3 8 AwEAAQ==".parse::<Record>().is_err());
+        assert!("www.example.com. 60 IN A 192.0.2.1\nwww.example.com. 60 IN A 192.0.2.2"
+            .parse::<Record>()
+            .is_err());
+    }
 }
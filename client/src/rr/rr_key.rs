@@ -1,11 +1,11 @@
 use std::cmp::Ordering;
 
-use rr::{Name, RecordType};
+use rr::{LowerName, Name, RecordType};
 
 /// Accessor key for RRSets in the Authority.
 #[derive(Eq, PartialEq, Debug, Hash, Clone)]
 pub struct RrKey {
-    pub name: Name,
+    pub name: LowerName,
     pub record_type: RecordType,
 }
 
@@ -19,11 +19,10 @@ impl RrKey {
     ///
     /// # Return value
     ///
-    /// A new key to access the Authorities.
-    /// TODO: make all cloned params pass by value.
+    /// A new key to access the Authorities, the name is normalized to lowercase.
     pub fn new(name: &Name, record_type: RecordType) -> RrKey {
         RrKey {
-            name: name.clone(),
+            name: LowerName::new(name),
             record_type: record_type,
         }
     }
@@ -4,12 +4,81 @@
 // http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
+use std::fmt;
 use std::iter::Chain;
 use std::slice::Iter;
 use std::vec;
 
-use rr::{DNSClass, Name, Record, RecordType, RData};
+use data_encoding::base64;
+
+use rr::{DNSClass, Name, Record, RecordMetaTable, RecordType, RData};
 use rr::dnssec::{Algorithm, SupportedAlgorithms};
+use serialize::binary::BinEncoder;
+
+/// The most records in an RRset, by default
+pub const DEFAULT_MAX_RRSET_RECORDS: usize = 100;
+/// The most bytes of record data in an RRset, by default, more than fits in a message
+pub const DEFAULT_MAX_RRSET_BYTES: usize = 65535;
+
+/// Limits on the size of an RRset, see `RecordSet::try_insert`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RecordSetLimits {
+    max_records: usize,
+    max_bytes: usize,
+}
+
+impl Default for RecordSetLimits {
+    fn default() -> Self {
+        RecordSetLimits {
+            max_records: DEFAULT_MAX_RRSET_RECORDS,
+            max_bytes: DEFAULT_MAX_RRSET_BYTES,
+        }
+    }
+}
+
+impl RecordSetLimits {
+    /// the most records in the RRset
+    pub fn max_records(&mut self, max_records: usize) -> &mut Self {
+        self.max_records = max_records;
+        self
+    }
+
+    /// the most bytes of the record data of all the records, as encoded
+    pub fn max_bytes(&mut self, max_bytes: usize) -> &mut Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    pub fn get_max_records(&self) -> usize {
+        self.max_records
+    }
+
+    pub fn get_max_bytes(&self) -> usize {
+        self.max_bytes
+    }
+}
+
+/// The limit an insert into an RRset would exceed
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RecordSetLimitError {
+    /// more records than `RecordSetLimits::max_records`
+    TooManyRecords(usize),
+    /// more bytes than `RecordSetLimits::max_bytes`
+    TooManyBytes(usize),
+}
+
+impl fmt::Display for RecordSetLimitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RecordSetLimitError::TooManyRecords(records) => {
+                write!(f, "too many records in rrset: {}", records)
+            }
+            RecordSetLimitError::TooManyBytes(bytes) => {
+                write!(f, "too many bytes in rrset: {}", bytes)
+            }
+        }
+    }
+}
 
 /// Set of resource records associated to a name and type
 #[derive(Clone, Debug, PartialEq)]
@@ -293,7 +362,8 @@ impl RecordSet {
             // CNAME  compare only NAME, CLASS, and TYPE -- it is not possible
             //         to have more than one CNAME RR, even if their data fields
             //         differ.
-            RecordType::CNAME => {
+            // DNAME  likewise there is at most one DNAME at a name, RFC 6672 section 2.4
+            RecordType::CNAME | RecordType::DNAME => {
                 assert!(self.records.len() <= 1);
                 self.records.clear();
             }
@@ -333,6 +403,56 @@ impl RecordSet {
         }
     }
 
+    /// Inserts the record as `insert` does, unless the RRset would then exceed the limits
+    ///
+    /// A record which replaces another, one with the same record data, or the SOA or CNAME, is
+    ///  only checked against the limits on its own.
+    ///
+    /// # Arguments
+    ///
+    /// * `record` - `Record` asserts that the `name` and `record_type` match the `RecordSet`.
+    /// * `serial` - current serial number of the `SOA` record, see `insert`
+    /// * `limits` - the most records and bytes the RRset may have after the insert
+    ///
+    /// # Return value
+    ///
+    /// True if the record was inserted, or the limit which it would exceed, in which case the
+    ///  RRset is unchanged.
+    pub fn try_insert(&mut self,
+                      record: Record,
+                      serial: u32,
+                      limits: &RecordSetLimits)
+                      -> Result<bool, RecordSetLimitError> {
+        try!(self.check_limits(&record, limits));
+        Ok(self.insert(record, serial))
+    }
+
+    /// Returns the limit which the RRset would exceed if the record were inserted
+    pub fn check_limits(&self,
+                        record: &Record,
+                        limits: &RecordSetLimits)
+                        -> Result<(), RecordSetLimitError> {
+        let (records, bytes) = match record.get_rr_type() {
+            RecordType::SOA | RecordType::CNAME | RecordType::DNAME => (1, rdata_len(record)),
+            _ if self.records.iter().any(|r| r.get_rdata() == record.get_rdata()) => {
+                (self.records.len(),
+                 self.records.iter().map(rdata_len).sum::<usize>())
+            }
+            _ => {
+                (self.records.len() + 1,
+                 self.records.iter().map(rdata_len).sum::<usize>() + rdata_len(record))
+            }
+        };
+
+        if records > limits.max_records {
+            Err(RecordSetLimitError::TooManyRecords(records))
+        } else if bytes > limits.max_bytes {
+            Err(RecordSetLimitError::TooManyBytes(bytes))
+        } else {
+            Ok(())
+        }
+    }
+
     /// Removes the Resource Record if it exists.
     ///
     /// # Arguments
@@ -384,12 +504,130 @@ impl RecordSet {
 
         removed
     }
+
+    /// Renders the records, followed by their RRSIGs, as RFC 1035 master file entries, one per
+    ///  line
+    ///
+    /// Owner names in the origin are relative to it, `@` being the origin itself, other names are
+    ///  fully qualified. The RRSIG, NSEC and NSEC3 records, which are generated when a zone is
+    ///  signed and not read from master files, are written as comments. The keys and DS records
+    ///  are written in the generic form, `\# <length> <hex>`.
+    ///
+    /// # Arguments
+    ///
+    /// * `origin` - the `$ORIGIN` the entries are read with, usually that of the zone
+    pub fn to_zone_file(&self, origin: &Name) -> String {
+        self.to_annotated_zone_file(origin, &RecordMetaTable::new())
+    }
+
+    /// Renders the records as `to_zone_file` does, each preceded by its comments and tags, as
+    ///  they were read with `Parser::parse_with_meta`
+    ///
+    /// # Arguments
+    ///
+    /// * `origin` - the `$ORIGIN` the entries are read with, usually that of the zone
+    /// * `meta` - the metadata of the records of the zone
+    pub fn to_annotated_zone_file(&self, origin: &Name, meta: &RecordMetaTable) -> String {
+        let mut zone_file = String::new();
+        for record in self.records.iter().chain(self.rrsigs.iter()) {
+            if let Some(meta) = meta.get(record) {
+                for comment in meta.to_comments() {
+                    zone_file.push_str("; ");
+                    zone_file.push_str(&comment);
+                    zone_file.push('\n');
+                }
+            }
+            zone_file.push_str(&zone_file_entry(record, origin));
+            zone_file.push('\n');
+        }
+        zone_file
+    }
+}
+
+/// Returns true for the signatures and denial records, which are generated when a zone is signed
+///  and not read from master files, these are written as comments, see `RecordSet::to_zone_file`
+///
+/// The keys, DNSKEY and KEY, and the DS of delegations are zone data, written as entries.
+pub fn is_commented_type(record_type: RecordType) -> bool {
+    match record_type {
+        RecordType::RRSIG | RecordType::NSEC | RecordType::NSEC3 => true,
+        _ => false,
+    }
+}
+
+/// The master file entry of the record, see `RecordSet::to_zone_file`
+fn zone_file_entry(record: &Record, origin: &Name) -> String {
+    let rdata = match *record.get_rdata() {
+        RData::SIG(ref sig) => {
+            format!("{} {} {} {} {} {} {} {} {}",
+                    sig.get_type_covered(),
+                    u8::from(sig.get_algorithm()),
+                    sig.get_num_labels(),
+                    sig.get_original_ttl(),
+                    sig.get_sig_expiration(),
+                    sig.get_sig_inception(),
+                    sig.get_key_tag(),
+                    sig.get_signer_name(),
+                    base64::encode(sig.get_sig()))
+        }
+        RData::NSEC(ref nsec) => {
+            let mut rdata = nsec.get_next_domain_name().to_string();
+            for record_type in nsec.get_type_bit_maps() {
+                rdata.push(' ');
+                rdata.push_str(&record_type.to_string());
+            }
+            rdata
+        }
+        ref rdata => rdata.to_string(),
+    };
+
+    let comment = if is_commented_type(record.get_rr_type()) {
+        "; "
+    } else {
+        ""
+    };
+
+    format!("{}{} {} {} {} {}",
+            comment,
+            relative_name(record.get_name(), origin),
+            record.get_ttl(),
+            record.get_dns_class(),
+            record.get_rr_type(),
+            rdata)
+}
+
+/// The name relative to the origin, if it is in it, i.e. the labels before those of the origin
+fn relative_name(name: &Name, origin: &Name) -> String {
+    if origin.is_root() || !origin.zone_of(name) {
+        return name.to_string();
+    }
+
+    // every label is followed by a `.`, the one before the origin included
+    let name = name.to_string();
+    let origin = origin.to_string();
+    if name.len() <= origin.len() {
+        "@".to_string()
+    } else {
+        name[..name.len() - origin.len() - 1].to_string()
+    }
 }
 
 pub trait IntoRecordSet: Sized {
     fn into_record_set(self) -> RecordSet;
 }
 
+/// the length of the record data as encoded, uncompressed
+fn rdata_len(record: &Record) -> usize {
+    let mut bytes = Vec::new();
+    {
+        let mut encoder = BinEncoder::new(&mut bytes);
+        if let Err(e) = record.get_rdata().emit(&mut encoder) {
+            warn!("could not encode {:?}: {}", record.get_rr_type(), e);
+        }
+    }
+    bytes.len()
+}
+
 impl IntoRecordSet for RecordSet {
     fn into_record_set(self) -> Self {
         self
@@ -737,4 +975,55 @@ mod test {
                 false
             }));
     }
+
+    #[test]
+    fn test_to_zone_file() {
+        let origin = Name::parse("example.com.", None).unwrap();
+        let a = |name: &str| {
+            Record::from_rdata(Name::parse(name, None).unwrap(),
+                               300,
+                               RecordType::A,
+                               RData::A(Ipv4Addr::new(192, 0, 2, 1)))
+        };
+
+        assert_eq!(RecordSet::from(a("www.example.com.")).to_zone_file(&origin),
+                   "www 300 IN A 192.0.2.1\n");
+        assert_eq!(RecordSet::from(a("Example.COM.")).to_zone_file(&origin),
+                   "@ 300 IN A 192.0.2.1\n");
+        assert_eq!(RecordSet::from(a("a\\.b.example.com.")).to_zone_file(&origin),
+                   "a\\.b 300 IN A 192.0.2.1\n");
+        assert_eq!(RecordSet::from(a("www.example.net.")).to_zone_file(&origin),
+                   "www.example.net. 300 IN A 192.0.2.1\n");
+        assert_eq!(RecordSet::from(a("www.example.com.")).to_zone_file(&Name::root()),
+                   "www.example.com. 300 IN A 192.0.2.1\n");
+    }
+
+    #[test]
+    fn test_try_insert_limits() {
+        let name = Name::parse("www.example.com.", None).unwrap();
+        let a = |last: u8| {
+            Record::from_rdata(name.clone(),
+                               86400,
+                               RecordType::A,
+                               RData::A(Ipv4Addr::new(93, 184, 216, last)))
+        };
+
+        let mut limits = RecordSetLimits::default();
+        limits.max_records(2);
+        let mut rrset = RecordSet::new(&name, RecordType::A, 0);
+        assert_eq!(rrset.try_insert(a(1), 0, &limits), Ok(true));
+        assert_eq!(rrset.try_insert(a(2), 0, &limits), Ok(true));
+        // the same rdata doesn't grow the rrset
+        assert_eq!(rrset.try_insert(a(2), 0, &limits), Ok(false));
+        assert_eq!(rrset.try_insert(a(3), 0, &limits),
+                   Err(RecordSetLimitError::TooManyRecords(3)));
+        assert_eq!(rrset.len(), 2);
+
+        let mut limits = RecordSetLimits::default();
+        limits.max_bytes(8);
+        assert_eq!(rrset.check_limits(&a(1), &limits), Ok(()));
+        assert_eq!(rrset.try_insert(a(3), 0, &limits),
+                   Err(RecordSetLimitError::TooManyBytes(12)));
+        assert_eq!(rrset.len(), 2);
+    }
 }
@@ -15,6 +15,45 @@
  */
 use error::{DecodeErrorKind, DecodeResult};
 
+/// Limits enforced while decoding, these protect against small malicious packets which claim
+///  large numbers of records or large lengths.
+///
+/// The default is to not limit anything beyond what the DNS wire format already imposes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DecodeLimits {
+    /// maximum size of the entire message in bytes
+    pub max_message_size: usize,
+    /// maximum number of queries and records across all sections, as claimed by the header
+    pub max_records: usize,
+    /// maximum number of names which may be read from a single message
+    pub max_names: usize,
+    /// maximum rdata length of any single record
+    pub max_rdata_len: u16,
+}
+
+impl DecodeLimits {
+    /// Reasonable limits for a server accepting requests from untrusted sources.
+    pub fn for_requests() -> Self {
+        DecodeLimits {
+            max_message_size: u16::max_value() as usize,
+            max_records: 256,
+            max_names: 512,
+            max_rdata_len: u16::max_value(),
+        }
+    }
+}
+
+impl Default for DecodeLimits {
+    fn default() -> Self {
+        DecodeLimits {
+            max_message_size: usize::max_value(),
+            max_records: usize::max_value(),
+            max_names: usize::max_value(),
+            max_rdata_len: u16::max_value(),
+        }
+    }
+}
+
 /// This is non-destructive to the inner buffer, b/c for pointer types we need to perform a reverse
 ///  seek to lookup names
 ///
@@ -25,16 +64,40 @@ use error::{DecodeErrorKind, DecodeResult};
 pub struct BinDecoder<'a> {
     buffer: &'a [u8],
     index: usize,
+    limits: DecodeLimits,
+    names_read: usize,
 }
 
 impl<'a> BinDecoder<'a> {
     pub fn new(buffer: &'a [u8]) -> Self {
+        Self::with_limits(buffer, DecodeLimits::default())
+    }
+
+    /// Creates a new decoder which will enforce the specified limits
+    pub fn with_limits(buffer: &'a [u8], limits: DecodeLimits) -> Self {
         BinDecoder {
             buffer: buffer,
             index: 0,
+            limits: limits,
+            names_read: 0,
         }
     }
 
+    /// The limits this decoder is enforcing
+    pub fn limits(&self) -> &DecodeLimits {
+        &self.limits
+    }
+
+    /// Records that a name is being read, returns an error if this exceeds `max_names`
+    pub fn count_name(&mut self) -> DecodeResult<()> {
+        self.names_read += 1;
+        if self.names_read > self.limits.max_names {
+            return Err(DecodeErrorKind::TooManyNames(self.limits.max_names).into());
+        }
+
+        Ok(())
+    }
+
     pub fn pop(&mut self) -> DecodeResult<u8> {
         if self.index < self.buffer.len() {
             let byte = self.buffer[self.index];
@@ -62,11 +125,13 @@ impl<'a> BinDecoder<'a> {
     }
 
     /// This is a pretty efficient clone, as the buffer is never cloned, and only the index is set
-    ///  to the value passed in
+    ///  to the value passed in, the names read so far still count against the limits
     pub fn clone(&self, index_at: u16) -> BinDecoder {
         BinDecoder {
             buffer: self.buffer,
             index: index_at as usize,
+            limits: self.limits,
+            names_read: self.names_read,
         }
     }
 
@@ -16,7 +16,7 @@
 mod decoder;
 mod encoder;
 
-pub use self::decoder::BinDecoder;
+pub use self::decoder::{BinDecoder, DecodeLimits};
 pub use self::encoder::BinEncoder;
 pub use self::encoder::EncodeMode;
 
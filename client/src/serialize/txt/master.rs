@@ -13,13 +13,27 @@
  * See the License for the specific language governing permissions and
  * limitations under the License.
  */
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, VecDeque};
+use std::fs::File;
+use std::io::Read;
+use std::mem;
+use std::path::{Path, PathBuf};
 
 use ::error::*;
-use rr::{Name, IntoRecordSet, RecordType, Record, DNSClass, RData, RrKey, RecordSet};
+use rr::{Name, IntoRecordSet, RecordType, Record, DNSClass, RData, RrKey, RecordSet, RecordMeta,
+         RecordMetaTable};
 
 use super::master_lex::{Lexer, Token};
 
+/// `$INCLUDE`s nested deeper than this are an error, e.g. a file including itself
+const MAX_INCLUDE_DEPTH: usize = 8;
+
+/// Entries a single `$GENERATE` may create at most
+const MAX_GENERATE_ENTRIES: u32 = 65536;
+
+/// The widest number of a `$GENERATE` modifier, that of a label, as BIND limits it
+const MAX_GENERATE_WIDTH: usize = 63;
+
 /// ```text
 /// 5. MASTER FILES
 ///
@@ -120,11 +134,72 @@ use super::master_lex::{Lexer, Token};
 /// ;               Semicolon is used to start a comment; the remainder of
 ///                 the line is ignored.
 /// ```
-pub struct Parser;
+///
+/// In addition, the `$GENERATE` entry of BIND creates a record for each number of a range, see
+///  `generate()`.
+///
+/// Errors are located in the file, `ParseErrorKind::Located`, and either the first ends the
+///  parsing, or all are reported at once, see `collect_errors()`.
+pub struct Parser {
+    include_dir: Option<PathBuf>,
+    file: Option<PathBuf>,
+    collect_errors: bool,
+    /// the files read by `$INCLUDE` in the last parse
+    included: Vec<PathBuf>,
+}
+
+/// What carries over from one entry to the next, and into `$INCLUDE`d and `$GENERATE`d entries
+struct Context {
+    origin: Option<Name>,
+    current_name: Option<Name>,
+    ttl: Option<u32>,
+    class: Option<DNSClass>,
+    records: BTreeMap<RrKey, RecordSet>,
+    file: Option<PathBuf>,
+    errors: Vec<ParseError>,
+    generating: bool,
+    /// the comments since the last entry, those of the next record
+    comments: Vec<String>,
+    meta: RecordMetaTable,
+    /// the files of the `$INCLUDE` entries, whether or not they could be read
+    included: Vec<PathBuf>,
+}
 
 impl Parser {
     pub fn new() -> Self {
-        Parser
+        Parser {
+            include_dir: None,
+            file: None,
+            collect_errors: false,
+            included: Vec::new(),
+        }
+    }
+
+    /// Sets the directory in which relative `$INCLUDE` file names are found, usually that of the
+    ///  zone files, otherwise they are relative to the working directory
+    pub fn include_dir(&mut self, include_dir: &Path) -> &mut Self {
+        self.include_dir = Some(include_dir.to_owned());
+        self
+    }
+
+    /// Sets the name of the zone file, which the locations of errors are in
+    pub fn file_name(&mut self, file: &Path) -> &mut Self {
+        self.file = Some(file.to_owned());
+        self
+    }
+
+    /// Whether to skip the entries with errors and go on, to report all the errors of the zone
+    ///  in one pass, as `ParseErrorKind::Multiple` if there is more than one, rather than only
+    ///  the first
+    pub fn collect_errors(&mut self, collect_errors: bool) -> &mut Self {
+        self.collect_errors = collect_errors;
+        self
+    }
+
+    /// Returns the files of the `$INCLUDE` entries of the last parse, those which could not be
+    ///  read too, to tell whether the zone has changed since it was read
+    pub fn get_included(&self) -> &[PathBuf] {
+        &self.included
     }
 
     // TODO: change this function to load into an Authority, using the update_records() method
@@ -132,182 +207,486 @@ impl Parser {
                  lexer: Lexer,
                  origin: Option<Name>)
                  -> ParseResult<(Name, BTreeMap<RrKey, RecordSet>)> {
-        let mut lexer = lexer;
-        let mut records: BTreeMap<RrKey, RecordSet> = BTreeMap::new();
+        let (origin, records, _) = try!(self.parse_with_meta(lexer, origin));
+        Ok((origin, records))
+    }
 
-        let mut origin: Option<Name> = origin;
-        let mut current_name: Option<Name> = None;
+    /// Parses the entries as `parse` does, with the comments and tags of the records
+    ///
+    /// The comments on the lines before an entry, and at the end of its line, are those of its
+    ///  record, see `RecordMeta`. Those of `$GENERATE` entries are not kept, while those before
+    ///  `$ORIGIN`, `$TTL` and `$INCLUDE` entries are of the next record.
+    pub fn parse_with_meta(&mut self,
+                           lexer: Lexer,
+                           origin: Option<Name>)
+                           -> ParseResult<(Name, BTreeMap<RrKey, RecordSet>, RecordMetaTable)> {
+        let mut context = Context {
+            origin: origin,
+            current_name: None,
+            ttl: None,
+            class: None,
+            records: BTreeMap::new(),
+            file: self.file.clone(),
+            errors: Vec::new(),
+            generating: false,
+            comments: Vec::new(),
+            meta: RecordMetaTable::new(),
+            included: Vec::new(),
+        };
+        let result = self.parse_entries(lexer, &mut context, 0);
+        self.included = mem::replace(&mut context.included, Vec::new());
+        try!(result);
+
+        if context.errors.len() == 1 {
+            return Err(context.errors.remove(0));
+        } else if !context.errors.is_empty() {
+            return Err(ParseErrorKind::Multiple(context.errors).into());
+        }
+
+        //
+        // build the Authority and return.
+        let origin = try!(context.origin.ok_or(ParseError::from(ParseErrorKind::Message("$ORIGIN was not specified"))));
+        Ok((origin, context.records, context.meta))
+    }
+
+    fn parse_entries(&self, lexer: Lexer, context: &mut Context, depth: usize) -> ParseResult<()> {
+        let mut lexer = lexer;
         let mut rtype: Option<RecordType> = None;
-        let mut ttl: Option<u32> = None;
-        let mut class: Option<DNSClass> = None;
         let mut state = State::StartLine;
         let mut tokens: Vec<Token> = Vec::new();
+        let mut list: VecDeque<Token> = VecDeque::new();
+        // errors of the entry as a whole, found at its end, are located at its start
+        let mut entry_position = (1, 1);
 
-        while let Some(t) = try!(lexer.next_token()) {
-            state = match state {
-                State::StartLine => {
-                    // current_name is not reset on the next line b/c it might be needed from the previous
-                    rtype = None;
-                    tokens.clear();
-
-                    match t {
-                        // if Dollar, then $INCLUDE or $ORIGIN
-                        Token::Include => unimplemented!(),
-                        Token::Origin => State::Origin,
-                        Token::Ttl => State::Ttl,
-
-                        // if CharData, then Name then ttl_class_type
-                        Token::CharData(ref data) => {
-                            current_name = Some(try!(Name::parse(data, origin.as_ref())));
-                            State::TtlClassType
+        loop {
+            let t = match list.pop_front() {
+                Some(t) => t,
+                None => {
+                    let next = lexer.next_token();
+                    context.comments.extend(lexer.take_comments());
+                    match next {
+                        // line terminations are not recognized within parentheses, the items are
+                        //  read as if they were on the line
+                        Ok(Some(Token::List(items))) => {
+                            list.extend(items.into_iter().map(|item| if item == "@" {
+                                Token::At
+                            } else {
+                                Token::CharData(item)
+                            }));
+                            continue;
                         }
-
-                        // @ is a placeholder for specifying the current origin
-                        Token::At => {
-                            current_name = origin.clone(); // TODO a COW or RC would reduce copies...
-                            State::TtlClassType
+                        Ok(Some(t)) => t,
+                        Ok(None) => break,
+                        // the lexer cannot go on within the line, only with the next
+                        Err(error) => {
+                            try!(self.recover(context, error.into(), lexer.get_position(), None));
+                            lexer.skip_line();
+                            state = State::StartLine;
+                            continue;
                         }
+                    }
+                }
+            };
 
-                        // if blank, then nothing or ttl_class_type
-                        Token::Blank => State::TtlClassType,
-                        Token::EOL => State::StartLine, // probably a comment
-                        _ => return Err(ParseErrorKind::UnexpectedToken(t).into()),
+            if let State::StartLine = state {
+                entry_position = lexer.get_token_position();
+            }
+
+            state = match self.next_state(state, &t, &mut rtype, &mut tokens, context, depth) {
+                Ok(state) => state,
+                Err(error) => {
+                    // the rest of the entry is skipped, if the error is not at its end
+                    if t == Token::EOL {
+                        try!(self.recover(context, error, entry_position, None));
+                        State::StartLine
+                    } else {
+                        try!(self.recover(context, error, lexer.get_token_position(), Some(t)));
+                        State::Skip
                     }
                 }
-                State::Ttl => {
-                    match t {
-                        Token::CharData(ref data) => {
-                            ttl = Some(try!(Self::parse_time(data)));
-                            State::StartLine
-                        }
-                        _ => return Err(ParseErrorKind::UnexpectedToken(t).into()),
+            };
+        }
+
+        // the last entry need not end with a line break
+        let result = match state {
+            State::Record => Self::insert_record(context, rtype, &tokens),
+            State::Include => self.include(&tokens, context, depth),
+            State::Generate => self.generate(&tokens, context, depth),
+            _ => Ok(()),
+        };
+        if let Err(error) = result {
+            try!(self.recover(context, error, entry_position, None));
+        }
+
+        Ok(())
+    }
+
+    /// The state after the token of an entry
+    fn next_state(&self,
+                  state: State,
+                  t: &Token,
+                  rtype: &mut Option<RecordType>,
+                  tokens: &mut Vec<Token>,
+                  context: &mut Context,
+                  depth: usize)
+                  -> ParseResult<State> {
+        Ok(match state {
+            State::StartLine => {
+                // current_name is not reset on the next line b/c it might be needed from the previous
+                *rtype = None;
+                tokens.clear();
+
+                match *t {
+                    // if Dollar, then $INCLUDE, $ORIGIN, $TTL or $GENERATE
+                    Token::Include => State::Include,
+                    Token::Origin => State::Origin,
+                    Token::Ttl => State::Ttl,
+                    Token::Generate => State::Generate,
+
+                    // if CharData, then Name then ttl_class_type
+                    Token::CharData(ref data) => {
+                        context.current_name = Some(try!(Name::parse(data, context.origin.as_ref())));
+                        State::TtlClassType
                     }
+
+                    // @ is a placeholder for specifying the current origin
+                    Token::At => {
+                        context.current_name = context.origin.clone(); // TODO a COW or RC would reduce copies...
+                        State::TtlClassType
+                    }
+
+                    // if blank, then nothing or ttl_class_type
+                    Token::Blank => State::TtlClassType,
+                    Token::EOL => State::StartLine, // probably a comment
+                    _ => return Err(ParseErrorKind::UnexpectedToken(t.clone()).into()),
                 }
-                State::Origin => {
-                    match t {
-                        Token::CharData(ref data) => {
-                            // TODO an origin was specified, should this be legal? definitely confusing...
-                            origin = Some(try!(Name::parse(data, None)));
-                            State::StartLine
-                        }
-                        _ => return Err(ParseErrorKind::UnexpectedToken(t).into()),
+            }
+            State::Ttl => {
+                match *t {
+                    Token::CharData(ref data) => {
+                        context.ttl = Some(try!(Self::parse_time(data)));
+                        State::StartLine
                     }
+                    _ => return Err(ParseErrorKind::UnexpectedToken(t.clone()).into()),
                 }
-                State::Include => unimplemented!(),
-                State::TtlClassType => {
-                    match t {
-                        // if number, TTL
-                        // Token::Number(ref num) => ttl = Some(*num),
-                        // One of Class or Type (these cannot be overlapping!)
-                        Token::CharData(ref data) => {
-                            // if it's a number it's a ttl
-                            let result: ParseResult<u32> = Self::parse_time(data);
-                            if result.is_ok() {
-                                ttl = result.ok();
-                                State::TtlClassType // hm, should this go to just ClassType?
-                            } else {
-                                // if can parse DNSClass, then class
-                                let result = DNSClass::from_str(data);
-                                if result.is_ok() {
-                                    class = result.ok();
-                                    State::TtlClassType
-                                } else {
-
-                                    // if can parse RecordType, then RecordType
-                                    rtype = Some(try!(RecordType::from_str(data)));
-                                    State::Record
-                                }
-                            }
-                        }
-                        // could be nothing if started with blank and is a comment, i.e. EOL
-                        Token::EOL => {
-                            State::StartLine // next line
-                        }
-                        _ => return Err(ParseErrorKind::UnexpectedToken(t).into()),
+            }
+            State::Origin => {
+                match *t {
+                    Token::CharData(ref data) => {
+                        // a relative origin is relative to the current one
+                        context.origin = Some(try!(Name::parse(data, context.origin.as_ref())));
+                        State::StartLine
                     }
+                    _ => return Err(ParseErrorKind::UnexpectedToken(t.clone()).into()),
                 }
-                State::Record => {
-                    // b/c of ownership rules, perhaps, just collect all the RData components as a list of
-                    //  tokens to pass into the processor
-                    match t {
-                        Token::EOL => {
-                            // call out to parsers for difference record types
-                            let rdata = try!(RData::parse(try!(rtype.ok_or(ParseError::from(ParseErrorKind::Message("record type not specified")))), &tokens, origin.as_ref()));
-
-                            // verify that we have everything we need for the record
-                            let mut record = Record::new();
-                            // TODO COW or RC would reduce mem usage, perhaps Name should have an intern()...
-                            //  might want to wait until RC.weak() stabilizes, as that would be needed for global
-                            //  memory where you want
-                            record.name(try!(current_name.clone().ok_or(ParseError::from(ParseErrorKind::Message("record name not specified")))));
-                            record.rr_type(rtype.unwrap());
-                            record.dns_class(try!(class.ok_or(ParseError::from(ParseErrorKind::Message("record class not specified")))));
-
-                            // slightly annoying, need to grab the TTL, then move rdata into the record,
-                            //  then check the Type again and have custom add logic.
-                            match rtype.unwrap() {
-                                RecordType::SOA => {
-                                    // TTL for the SOA is set internally...
-                                    // expire is for the SOA, minimum is default for records
-                                    if let RData::SOA(ref soa) = rdata {
-                                        // TODO, this looks wrong, get_expire() should be get_minimum(), right?
-                                        record.ttl(soa.get_expire() as u32); // the spec seems a little inaccurate with u32 and i32
-                                        if ttl.is_none() {
-                                            ttl = Some(soa.get_minimum());
-                                        } // TODO: should this only set it if it's not set?
-                                    } else {
-                                        assert!(false,
-                                                "Invalid RData here, expected SOA: {:?}",
-                                                rdata);
-                                    }
-                                }
-                                _ => {
-                                    record.ttl(try!(ttl.ok_or(ParseError::from(ParseErrorKind::Message("record ttl not specified")))));
-                                }
-                            }
-
-                            // TODO validate record, e.g. the name of SRV record allows _ but others do not.
-
-                            // move the rdata into record...
-                            record.rdata(rdata);
-
-                            // add to the map
-                            let key = RrKey::new(record.get_name(), record.get_rr_type());
-
-                            match rtype.unwrap() {
-                                RecordType::SOA => {
-                                    let set = record.into_record_set();
-                                    if records.insert(key, set).is_some() {
-                                        return Err(ParseErrorKind::Message("SOA is already \
-                                                                            specified")
-                                            .into());
-                                    }
-                                }
-                                _ => {
-                                    // add a Vec if it's not there, then add the record to the list
-                                    let mut set = records.entry(key)
-                                        .or_insert(RecordSet::new(record.get_name(),
-                                                                  record.get_rr_type(),
-                                                                  0));
-                                    set.insert(record, 0);
-                                }
-                            }
-
-                            State::StartLine
-                        }
-                        _ => {
-                            tokens.push(t);
+            }
+            State::Include => {
+                match *t {
+                    Token::EOL => {
+                        try!(self.include(tokens, context, depth));
+                        State::StartLine
+                    }
+                    _ => {
+                        tokens.push(t.clone());
+                        State::Include
+                    }
+                }
+            }
+            State::Generate => {
+                match *t {
+                    Token::EOL => {
+                        try!(self.generate(tokens, context, depth));
+                        State::StartLine
+                    }
+                    _ => {
+                        tokens.push(t.clone());
+                        State::Generate
+                    }
+                }
+            }
+            State::TtlClassType => {
+                match *t {
+                    // One of TTL, Class or Type (these cannot be overlapping!)
+                    Token::CharData(ref data) => {
+                        // a TTL starts with a digit, a class of HS is not a TTL of 0 hours
+                        if data.starts_with(|ch: char| ch.is_digit(10)) {
+                            context.ttl = Some(try!(Self::parse_time(data)));
+                            State::TtlClassType // hm, should this go to just ClassType?
+                        } else if let Ok(class) = DNSClass::from_str(data) {
+                            context.class = Some(class);
+                            State::TtlClassType
+                        } else {
+                            // if can parse RecordType, then RecordType
+                            *rtype = Some(try!(RecordType::from_str(data)));
                             State::Record
                         }
                     }
+                    // could be nothing if started with blank and is a comment, i.e. EOL
+                    Token::EOL => {
+                        State::StartLine // next line
+                    }
+                    _ => return Err(ParseErrorKind::UnexpectedToken(t.clone()).into()),
                 }
             }
+            State::Record => {
+                // b/c of ownership rules, perhaps, just collect all the RData components as a list of
+                //  tokens to pass into the processor
+                match *t {
+                    Token::EOL => {
+                        try!(Self::insert_record(context, *rtype, tokens));
+                        State::StartLine
+                    }
+                    // @ is the origin in the data as well, e.g. the target of a CNAME
+                    Token::At => {
+                        let origin = try!(context.origin.as_ref().ok_or(ParseError::from(
+                            ParseErrorKind::Message("$ORIGIN was not specified"))));
+                        tokens.push(Token::CharData(origin.to_string()));
+                        State::Record
+                    }
+                    _ => {
+                        tokens.push(t.clone());
+                        State::Record
+                    }
+                }
+            }
+            State::Skip => {
+                match *t {
+                    Token::EOL => State::StartLine,
+                    _ => State::Skip,
+                }
+            }
+        })
+    }
+
+    /// Locates the error in the file, unless it is of a `$GENERATE`d entry, which is located as
+    ///  the `$GENERATE`, or of an `$INCLUDE`d file, which is already. When collecting errors,
+    ///  the error is kept and the parsing goes on, otherwise it is returned.
+    fn recover(&self,
+               context: &mut Context,
+               error: ParseError,
+               position: (usize, usize),
+               token: Option<Token>)
+               -> ParseResult<()> {
+        if context.generating {
+            return Err(error);
         }
+        // the comments of the entry are not those of the next
+        context.comments.clear();
 
-        //
-        // build the Authority and return.
-        let origin = try!(origin.ok_or(ParseError::from(ParseErrorKind::Message("$ORIGIN was not specified"))));
-        Ok((origin, records))
+        let is_located = match *error.kind() {
+            ParseErrorKind::Located(..) |
+            ParseErrorKind::Multiple(..) => true,
+            _ => false,
+        };
+        let error = if is_located {
+            error
+        } else {
+            let location = ParseErrorLocation::new(context.file.clone(),
+                                                   position.0,
+                                                   position.1,
+                                                   token);
+            ParseErrorKind::Located(location, Box::new(error)).into()
+        };
+
+        if self.collect_errors {
+            context.errors.push(error);
+            Ok(())
+        } else {
+            Err(error)
+        }
+    }
+
+    fn insert_record(context: &mut Context,
+                     rtype: Option<RecordType>,
+                     tokens: &Vec<Token>)
+                     -> ParseResult<()> {
+        // call out to parsers for difference record types
+        let rdata = try!(RData::parse(try!(rtype.ok_or(ParseError::from(ParseErrorKind::Message("record type not specified")))), tokens, context.origin.as_ref()));
+
+        // a mailbox is often written as the email address, with its '@', rather than a name
+        match rdata {
+            RData::SOA(ref soa) => {
+                try!(soa.get_rname().to_email());
+            }
+            RData::RP(ref rp) if !rp.get_mbox().is_root() => {
+                try!(rp.get_mbox().to_email());
+            }
+            _ => (),
+        }
+
+        // verify that we have everything we need for the record
+        let mut record = Record::new();
+        // TODO COW or RC would reduce mem usage, perhaps Name should have an intern()...
+        //  might want to wait until RC.weak() stabilizes, as that would be needed for global
+        //  memory where you want
+        record.name(try!(context.current_name.clone().ok_or(ParseError::from(ParseErrorKind::Message("record name not specified")))));
+        record.rr_type(rtype.unwrap());
+        record.dns_class(try!(context.class.ok_or(ParseError::from(ParseErrorKind::Message("record class not specified")))));
+
+        // slightly annoying, need to grab the TTL, then move rdata into the record,
+        //  then check the Type again and have custom add logic.
+        match rtype.unwrap() {
+            RecordType::SOA => {
+                // TTL for the SOA is set internally...
+                // expire is for the SOA, minimum is default for records
+                if let RData::SOA(ref soa) = rdata {
+                    // TODO, this looks wrong, get_expire() should be get_minimum(), right?
+                    record.ttl(soa.get_expire() as u32); // the spec seems a little inaccurate with u32 and i32
+                    if context.ttl.is_none() {
+                        context.ttl = Some(soa.get_minimum());
+                    } // TODO: should this only set it if it's not set?
+                } else {
+                    assert!(false,
+                            "Invalid RData here, expected SOA: {:?}",
+                            rdata);
+                }
+            }
+            _ => {
+                record.ttl(try!(context.ttl.ok_or(ParseError::from(ParseErrorKind::Message("record ttl not specified")))));
+            }
+        }
+
+        // TODO validate record, e.g. the name of SRV record allows _ but others do not.
+
+        // move the rdata into record...
+        record.rdata(rdata);
+
+        if !context.generating {
+            let comments = mem::replace(&mut context.comments, Vec::new());
+            context.meta.insert(&record, RecordMeta::from_comments(comments));
+        }
+
+        // add to the map
+        let key = RrKey::new(record.get_name(), record.get_rr_type());
+
+        match rtype.unwrap() {
+            RecordType::SOA => {
+                let set = record.into_record_set();
+                if context.records.insert(key, set).is_some() {
+                    return Err(ParseErrorKind::Message("SOA is already \
+                                                        specified")
+                        .into());
+                }
+            }
+            _ => {
+                // add a Vec if it's not there, then add the record to the list
+                let mut set = context.records
+                    .entry(key)
+                    .or_insert(RecordSet::new(record.get_name(),
+                                              record.get_rr_type(),
+                                              0));
+                set.insert(record, 0);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reads the entries of `$INCLUDE <file-name> [<domain-name>]`, the origin and current name
+    ///  revert to what they were once the file has been read
+    fn include(&self, tokens: &[Token], context: &mut Context, depth: usize) -> ParseResult<()> {
+        if depth >= MAX_INCLUDE_DEPTH {
+            return Err(ParseErrorKind::Message("$INCLUDE nested too deeply").into());
+        }
+
+        let mut tokens = tokens.iter();
+        let file_name = match tokens.next() {
+            Some(&Token::CharData(ref file_name)) => file_name,
+            Some(t) => return Err(ParseErrorKind::UnexpectedToken(t.clone()).into()),
+            None => {
+                return Err(ParseErrorKind::MissingToken("$INCLUDE file name".to_string()).into())
+            }
+        };
+        let origin = match tokens.next() {
+            Some(&Token::CharData(ref origin)) => {
+                Some(try!(Name::parse(origin, context.origin.as_ref())))
+            }
+            Some(&Token::At) | None => context.origin.clone(),
+            Some(t) => return Err(ParseErrorKind::UnexpectedToken(t.clone()).into()),
+        };
+
+        let path = match self.include_dir {
+            Some(ref include_dir) => include_dir.join(file_name),
+            None => PathBuf::from(file_name),
+        };
+        debug!("including: {:?}", path);
+        context.included.push(path.clone());
+
+        let mut text = String::new();
+        try!(try!(File::open(&path)).read_to_string(&mut text));
+
+        let origin = mem::replace(&mut context.origin, origin);
+        let current_name = context.current_name.take();
+        let file = mem::replace(&mut context.file, Some(path));
+        let result = self.parse_entries(Lexer::new(&text), context, depth + 1);
+        context.origin = origin;
+        context.current_name = current_name;
+        context.file = file;
+
+        result
+    }
+
+    /// Reads the entries of `$GENERATE <range> <lhs> [<ttl>] [<class>] <type> <rhs>`, of BIND
+    ///
+    /// An entry `<lhs> [<ttl>] [<class>] <type> <rhs>` is read for each number in the range,
+    ///  `start-stop[/step]`. In `lhs` and `rhs` a `$` is replaced by the number, and
+    ///  `${offset[,width[,base]]}` by the number plus the offset, padded with zeros to the width,
+    ///  in base `d`, `o`, `x` or `X`. `\$` is a `$`.
+    fn generate(&self, tokens: &[Token], context: &mut Context, depth: usize) -> ParseResult<()> {
+        let fields: Vec<String> = try!(tokens.iter()
+            .map(|t| match *t {
+                Token::CharData(ref data) => Ok(data.clone()),
+                Token::At => Ok("@".to_string()),
+                ref t => Err(ParseError::from(ParseErrorKind::UnexpectedToken(t.clone()))),
+            })
+            .collect());
+
+        if fields.len() < 4 {
+            return Err(ParseErrorKind::MissingToken("$GENERATE range, lhs, type or rhs"
+                    .to_string())
+                .into());
+        }
+
+        let (start, stop, step) = try!(generate_range(&fields[0]));
+        context.comments.clear();
+        let ttl_class_type = fields[2..fields.len() - 1].join(" ");
+
+        let generating = mem::replace(&mut context.generating, true);
+        let result = self.generate_entries(&fields[1],
+                                           &ttl_class_type,
+                                           &fields[fields.len() - 1],
+                                           (start, stop, step),
+                                           context,
+                                           depth);
+        context.generating = generating;
+
+        result
+    }
+
+    /// Reads the entries of a `$GENERATE`, their errors are those of the `$GENERATE`
+    fn generate_entries(&self,
+                        lhs: &str,
+                        ttl_class_type: &str,
+                        rhs: &str,
+                        (start, stop, step): (u32, u32, u32),
+                        context: &mut Context,
+                        depth: usize)
+                        -> ParseResult<()> {
+        let mut number = start;
+        while number <= stop {
+            let entry = format!("{} {} {}\n",
+                                try!(generate_substitute(lhs, number)),
+                                ttl_class_type,
+                                try!(generate_substitute(rhs, number)));
+            try!(self.parse_entries(Lexer::new(&entry), context, depth));
+
+            number = match number.checked_add(step) {
+                Some(number) => number,
+                None => break,
+            };
+        }
+
+        Ok(())
     }
 
     /// parses the string following the rules from:
@@ -383,12 +762,108 @@ impl Parser {
     }
 }
 
+/// Parses the `start-stop[/step]` range of `$GENERATE`
+fn generate_range(range: &str) -> ParseResult<(u32, u32, u32)> {
+    let invalid = || ParseError::from(ParseErrorKind::Msg(format!("invalid $GENERATE range: {}",
+                                                                  range)));
+
+    let (start_stop, step): (&str, u32) = match range.find('/') {
+        Some(slash) => (&range[..slash], try!(range[slash + 1..].parse())),
+        None => (range, 1),
+    };
+    let (start, stop): (u32, u32) = match start_stop.find('-') {
+        Some(dash) => (try!(start_stop[..dash].parse()), try!(start_stop[dash + 1..].parse())),
+        None => return Err(invalid()),
+    };
+
+    if step == 0 || start > stop || (stop - start) / step >= MAX_GENERATE_ENTRIES {
+        return Err(invalid());
+    }
+
+    Ok((start, stop, step))
+}
+
+/// Replaces the `$`s of a `$GENERATE` template with the number
+fn generate_substitute(template: &str, number: u32) -> ParseResult<String> {
+    let mut result = String::new();
+    let mut chars = template.chars().peekable();
+
+    while let Some(ch) = chars.next() {
+        let next = chars.peek().cloned();
+        match ch {
+            '\\' if next == Some('$') => {
+                chars.next();
+                result.push('$');
+            }
+            '$' if next == Some('{') => {
+                chars.next();
+                let mut modifier = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(ch) => modifier.push(ch),
+                        None => {
+                            return Err(ParseErrorKind::Msg(format!("unclosed $GENERATE \
+                                                                    modifier: {}",
+                                                                   template))
+                                .into())
+                        }
+                    }
+                }
+
+                result.push_str(&try!(generate_modified(&modifier, number)));
+            }
+            '$' => result.push_str(&number.to_string()),
+            ch => result.push(ch),
+        }
+    }
+
+    Ok(result)
+}
+
+/// Formats the number with the `offset[,width[,base]]` modifier of a `$GENERATE` template
+fn generate_modified(modifier: &str, number: u32) -> ParseResult<String> {
+    let mut fields = modifier.split(',');
+    let offset: i64 = try!(fields.next().unwrap_or("0").trim_left_matches('+').parse());
+    let width: usize = match fields.next() {
+        Some(width) => try!(width.parse()),
+        None => 0,
+    };
+    if width > MAX_GENERATE_WIDTH {
+        return Err(ParseErrorKind::Msg(format!("$GENERATE width wider than a label: {}", width))
+            .into());
+    }
+
+    let number = match (number as i64).checked_add(offset) {
+        Some(number) if number >= 0 => number,
+        Some(number) => {
+            return Err(ParseErrorKind::Msg(format!("negative $GENERATE number: {}", number))
+                .into())
+        }
+        None => {
+            return Err(ParseErrorKind::Msg(format!("$GENERATE offset out of range: {}", offset))
+                .into())
+        }
+    };
+
+    match fields.next().unwrap_or("d") {
+        "d" => Ok(format!("{:0width$}", number, width = width)),
+        "o" => Ok(format!("{:0width$o}", number, width = width)),
+        "x" => Ok(format!("{:0width$x}", number, width = width)),
+        "X" => Ok(format!("{:0width$X}", number, width = width)),
+        base => Err(ParseErrorKind::Msg(format!("unsupported $GENERATE base: {}", base)).into()),
+    }
+}
+
 #[allow(unused)]
+#[derive(Clone, Copy)]
 enum State {
     StartLine, // start of line, @, $<WORD>, Name, Blank
     TtlClassType, // [<TTL>] [<class>] <type>,
     Ttl, // $TTL <time>
     Record,
-    Include, // $INCLUDE <filename>
+    Include, // $INCLUDE <filename> [<origin>]
+    Generate, // $GENERATE <range> <lhs> [<ttl>] [<class>] <type> <rhs>
     Origin,
+    Skip, // the rest of an entry with an error
 }
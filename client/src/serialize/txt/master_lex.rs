@@ -8,12 +8,22 @@
 use std::iter::Peekable;
 use std::str::Chars;
 use std::char;
+use std::fmt;
+use std::mem;
 
 use error::{LexerResult, LexerError, LexerErrorKind};
 
 pub struct Lexer<'a> {
     txt: Peekable<Chars<'a>>,
     state: State,
+    line: usize,
+    column: usize,
+    token_line: usize,
+    token_column: usize,
+    /// the text of the comment being read
+    comment: String,
+    /// the comments read since `take_comments`
+    comments: Vec<String>,
 }
 
 impl<'a> Lexer<'a> {
@@ -21,9 +31,41 @@ impl<'a> Lexer<'a> {
         Lexer {
             txt: txt.chars().peekable(),
             state: State::StartLine,
+            line: 1,
+            column: 1,
+            token_line: 1,
+            token_column: 1,
+            comment: String::new(),
+            comments: Vec::new(),
         }
     }
 
+    /// Returns the comments read since this was last called, without their `;` and surrounding
+    ///  whitespace, empty ones are skipped, as are those within parentheses
+    pub fn take_comments(&mut self) -> Vec<String> {
+        mem::replace(&mut self.comments, Vec::new())
+    }
+
+    /// The line and column, counted from 1, of the next character
+    pub fn get_position(&self) -> (usize, usize) {
+        (self.line, self.column)
+    }
+
+    /// The line and column, counted from 1, where the last token read started
+    pub fn get_token_position(&self) -> (usize, usize) {
+        (self.token_line, self.token_column)
+    }
+
+    /// Skips the rest of the line, to go on with the next after an error
+    pub fn skip_line(&mut self) {
+        while let Some(ch) = self.next_char() {
+            if ch == '\n' {
+                break;
+            }
+        }
+        self.state = State::StartLine;
+    }
+
     pub fn next_token(&mut self) -> LexerResult<Option<Token>> {
         let mut char_data_vec: Option<Vec<String>> = None;
         let mut char_data: Option<String> = None;
@@ -38,6 +80,17 @@ impl<'a> Lexer<'a> {
             // handy line for debugging
             // debug!("ch = {:?}; state = {:?}(c: {:?}, v: {:?})", ch, self.state, char_data, char_data_vec);
 
+            // a token starts where the lexer stops skipping whitespace and comments
+            match self.state {
+                State::StartLine |
+                State::RestOfLine |
+                State::Comment { is_list: false } => {
+                    self.token_line = self.line;
+                    self.token_column = self.column;
+                }
+                _ => (),
+            }
+
             // continuing states should pass back the state as the last statement,
             //  terminal states should set the state internally and return the proper Token::*.
             // TODO: there is some non-ideal copying going on in here...
@@ -49,6 +102,12 @@ impl<'a> Lexer<'a> {
                         }
                         // white space at the start of line is a Blank
                         Some(ch) if ch.is_whitespace() => self.state = State::Blank,
+                        // control entries, elsewhere $ is data, e.g. in $GENERATE templates
+                        Some('$') => {
+                            self.next_char();
+                            char_data = Some(String::new());
+                            self.state = State::Dollar;
+                        }
                         Some(_) => self.state = State::RestOfLine,
                         None => {
                             self.state = State::EOF;
@@ -59,29 +118,24 @@ impl<'a> Lexer<'a> {
                     match ch {
                         Some('@') => self.state = State::At,
                         Some('(') => {
-                            self.txt.next();
+                            self.next_char();
                             char_data_vec = Some(Vec::new());
                             self.state = State::List;
                         }
                         Some(')') => {
                             return Err(LexerErrorKind::IllegalCharacter(ch.unwrap_or(')')).into())
                         }
-                        Some('$') => {
-                            self.txt.next();
-                            char_data = Some(String::new());
-                            self.state = State::Dollar;
-                        }
                         Some('\r') | Some('\n') => {
                             self.state = State::EOL;
                         }
                         Some('"') => {
-                            self.txt.next();
+                            self.next_char();
                             char_data = Some(String::new());
-                            self.state = State::Quote;
+                            self.state = State::Quote { is_list: false };
                         }
                         Some(';') => self.state = State::Comment { is_list: false },
                         Some(ch) if ch.is_whitespace() => {
-                            self.txt.next();
+                            self.next_char();
                         } // gobble other whitespace
                         Some(ch) if !ch.is_control() && !ch.is_whitespace() => {
                             char_data = Some(String::new());
@@ -95,7 +149,7 @@ impl<'a> Lexer<'a> {
                 }
                 State::Blank => {
                     // consume the whitespace
-                    self.txt.next();
+                    self.next_char();
                     self.state = State::RestOfLine;
                     return Ok(Some(Token::Blank));
                 }
@@ -105,30 +159,44 @@ impl<'a> Lexer<'a> {
                             if is_list {
                                 self.state = State::List;
                             } else {
+                                self.end_comment();
                                 self.state = State::EOL;
                             }
                         } // out of the comment
-                        Some(_) => {
-                            self.txt.next();
+                        Some(ch) => {
+                            self.next_char();
+                            // those of the fields within parentheses are not kept
+                            if !is_list {
+                                self.comment.push(ch);
+                            }
                         } // advance the token by default and maintain state
                         None => {
+                            self.end_comment();
                             self.state = State::EOF;
                         }
                     }
                 }
-                State::Quote => {
+                State::Quote { is_list } => {
                     match ch {
                         // end and gobble the '"'
+                        Some('"') if is_list => {
+                            self.state = State::List;
+                            self.next_char();
+                            try!(char_data_vec.as_mut()
+                                .ok_or(LexerError::from(LexerErrorKind::IllegalState("char_data_vec \
+                                                                                      is None")))
+                                .map(|v| v.push(char_data.take().unwrap_or("".into()))));
+                        }
                         Some('"') => {
                             self.state = State::RestOfLine;
-                            self.txt.next();
+                            self.next_char();
                             return Ok(Some(Token::CharData(char_data.take().unwrap_or("".into()))));
                         }
                         Some('\\') => {
                             try!(Self::push_to_str(&mut char_data, try!(self.escape_seq())));
                         }
                         Some(ch) => {
-                            self.txt.next();
+                            self.next_char();
                             try!(Self::push_to_str(&mut char_data, ch));
                         }
                         None => return Err(LexerErrorKind::UnclosedQuotedString.into()),
@@ -138,7 +206,7 @@ impl<'a> Lexer<'a> {
                     match ch {
                         // even this is a little broad for what's actually possible in a dollar...
                         Some('A'...'Z') => {
-                            self.txt.next();
+                            self.next_char();
                             try!(Self::push_to_str(&mut char_data, ch.unwrap()));
                         }
                         // finishes the Dollar...
@@ -150,6 +218,8 @@ impl<'a> Lexer<'a> {
 
                             if "INCLUDE" == dollar {
                                 return Ok(Some(Token::Include));
+                            } else if "GENERATE" == dollar {
+                                return Ok(Some(Token::Generate));
                             } else if "ORIGIN" == dollar {
                                 return Ok(Some(Token::Origin));
                             } else if "TTL" == dollar {
@@ -165,11 +235,11 @@ impl<'a> Lexer<'a> {
                 State::List => {
                     match ch {
                         Some(';') => {
-                            self.txt.next();
+                            self.next_char();
                             self.state = State::Comment { is_list: true }
                         }
                         Some(')') => {
-                            self.txt.next();
+                            self.next_char();
                             self.state = State::RestOfLine;
                             return char_data_vec.take()
                                 .ok_or(LexerErrorKind::IllegalState("char_data_vec is None")
@@ -177,7 +247,12 @@ impl<'a> Lexer<'a> {
                                 .map(|v| Some(Token::List(v)));
                         }
                         Some(ch) if ch.is_whitespace() => {
-                            self.txt.next();
+                            self.next_char();
+                        }
+                        Some('"') => {
+                            self.next_char();
+                            char_data = Some(String::new());
+                            self.state = State::Quote { is_list: true };
                         }
                         Some(ch) if !ch.is_control() && !ch.is_whitespace() => {
                             char_data = Some(String::new());
@@ -208,7 +283,7 @@ impl<'a> Lexer<'a> {
                         // TODO: this next one can be removed, but will keep unescaping for quoted strings
                         //Some('\\') => { try!(Self::push_to_str(&mut char_data, try!(self.escape_seq()))); },
                         Some(ch) if !ch.is_control() && !ch.is_whitespace() => {
-                            self.txt.next();
+                            self.next_char();
                             try!(Self::push_to_str(&mut char_data, ch));
                         }
                         Some(ch) => return Err(LexerErrorKind::UnrecognizedChar(ch).into()),
@@ -221,17 +296,17 @@ impl<'a> Lexer<'a> {
                     }
                 }
                 State::At => {
-                    self.txt.next();
+                    self.next_char();
                     self.state = State::RestOfLine;
                     return Ok(Some(Token::At));
                 }
                 State::EOL => {
                     match ch {
                         Some('\r') => {
-                            self.txt.next();
+                            self.next_char();
                         }
                         Some('\n') => {
-                            self.txt.next();
+                            self.next_char();
                             self.state = State::StartLine;
                             return Ok(Some(Token::EOL));
                         }
@@ -241,7 +316,7 @@ impl<'a> Lexer<'a> {
                 }
                 // to exhaust all cases, this should never be run...
                 State::EOF => {
-                    self.txt.next(); // making sure we consume the last... it will always return None after.
+                    self.next_char(); // making sure we consume the last... it will always return None after.
                     return Ok(None);
                 }
             }
@@ -251,6 +326,15 @@ impl<'a> Lexer<'a> {
         unreachable!("The above match statement should have found a terminal state");
     }
 
+    /// Keeps the comment which was read, see `take_comments`
+    fn end_comment(&mut self) {
+        let comment = self.comment.trim_left_matches(';').trim().to_string();
+        self.comment.clear();
+        if !comment.is_empty() {
+            self.comments.push(comment);
+        }
+    }
+
     fn push_to_str(collect: &mut Option<String>, ch: char) -> LexerResult<()> {
         collect.as_mut()
             .ok_or(LexerErrorKind::IllegalState("collect is None").into())
@@ -259,26 +343,23 @@ impl<'a> Lexer<'a> {
 
     fn escape_seq(&mut self) -> LexerResult<char> {
         // escaped character, let's decode it.
-        self.txt.next(); // consume the escape
+        self.next_char(); // consume the escape
         let ch = try!(self.peek().ok_or(LexerError::from(LexerErrorKind::EOF)));
 
         if !ch.is_control() {
             if ch.is_numeric() {
                 // in this case it's an excaped octal: \DDD
-                let d1: u32 = try!(try!(self.txt
-                    .next()
+                let d1: u32 = try!(try!(self.next_char()
                     .ok_or(LexerError::from(LexerErrorKind::EOF))
                     .map(|c| {
                         c.to_digit(10).ok_or(LexerError::from(LexerErrorKind::IllegalCharacter(c)))
                     }))); // gobble
-                let d2: u32 = try!(try!(self.txt
-                    .next()
+                let d2: u32 = try!(try!(self.next_char()
                     .ok_or(LexerError::from(LexerErrorKind::EOF))
                     .map(|c| {
                         c.to_digit(10).ok_or(LexerError::from(LexerErrorKind::IllegalCharacter(c)))
                     }))); // gobble
-                let d3: u32 = try!(try!(self.txt
-                    .next()
+                let d3: u32 = try!(try!(self.next_char()
                     .ok_or(LexerError::from(LexerErrorKind::EOF))
                     .map(|c| {
                         c.to_digit(10).ok_or(LexerError::from(LexerErrorKind::IllegalCharacter(c)))
@@ -291,7 +372,7 @@ impl<'a> Lexer<'a> {
                 return Ok(ch);
             } else {
                 // this is an excaped char: \X
-                self.txt.next(); // gobble the char
+                self.next_char(); // gobble the char
                 return Ok(ch);
             }
         } else {
@@ -303,6 +384,20 @@ impl<'a> Lexer<'a> {
     fn peek(&mut self) -> Option<char> {
         self.txt.peek().map(|c| *c)
     }
+
+    /// Consumes the next character, keeping track of the position
+    fn next_char(&mut self) -> Option<char> {
+        let ch = self.txt.next();
+        match ch {
+            Some('\n') => {
+                self.line += 1;
+                self.column = 1;
+            }
+            Some(_) => self.column += 1,
+            None => (),
+        }
+        ch
+    }
 }
 
 #[derive(Copy, Clone, PartialEq, Debug)]
@@ -315,7 +410,7 @@ pub enum State {
     //  Name,              // CharData + '.' + CharData
     Comment { is_list: bool }, // ;.*
     At, // @
-    Quote, // ".*"
+    Quote { is_list: bool }, // ".*"
     Dollar, // $
     EOL, // \n or \r\n
     EOF,
@@ -330,9 +425,27 @@ pub enum Token {
     Include, // $INCLUDE
     Origin, // $ORIGIN
     Ttl, // $TTL
+    Generate, // $GENERATE
     EOL, // \n or \r\n
 }
 
+impl fmt::Display for Token {
+    /// The token as it is written in a zone file
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Token::Blank => write!(f, "blank"),
+            Token::List(ref items) => write!(f, "({})", items.join(" ")),
+            Token::CharData(ref data) => write!(f, "{}", data),
+            Token::At => write!(f, "@"),
+            Token::Include => write!(f, "$INCLUDE"),
+            Token::Origin => write!(f, "$ORIGIN"),
+            Token::Ttl => write!(f, "$TTL"),
+            Token::Generate => write!(f, "$GENERATE"),
+            Token::EOL => write!(f, "end of line"),
+        }
+    }
+}
+
 #[cfg(test)]
 mod lex_test {
     use super::*;
@@ -457,6 +570,16 @@ mod lex_test {
         assert_eq!(next_token(&mut Lexer::new("$ORIGIN")).unwrap(),
                    Token::Origin);
         assert_eq!(next_token(&mut Lexer::new("$TTL")).unwrap(), Token::Ttl);
+        assert_eq!(next_token(&mut Lexer::new("$GENERATE")).unwrap(),
+                   Token::Generate);
+        assert_eq!(next_token(&mut Lexer::new("a$")).unwrap(),
+                   Token::CharData("a$".to_string()));
+
+        let mut lexer = Lexer::new("a ${1,3,x}");
+        assert_eq!(next_token(&mut lexer).unwrap(),
+                   Token::CharData("a".to_string()));
+        assert_eq!(next_token(&mut lexer).unwrap(),
+                   Token::CharData("${1,3,x}".to_string()));
         assert_eq!(next_token(&mut Lexer::new("\n")), Some(Token::EOL));
         assert_eq!(next_token(&mut Lexer::new("\r\n")), Some(Token::EOL));
     }
@@ -491,6 +614,70 @@ mod lex_test {
         assert_eq!(next_token(&mut lexer).unwrap(),
                    Token::List(vec!["abc".to_string()]));
         assert_eq!(next_token(&mut lexer), None);
+
+        let mut lexer = Lexer::new("(\"a b\"\n\"(c)\")");
+        assert_eq!(next_token(&mut lexer).unwrap(),
+                   Token::List(vec!["a b".to_string(), "(c)".to_string()]));
+        assert_eq!(next_token(&mut lexer), None);
+    }
+
+    #[test]
+    fn position() {
+        let mut lexer = Lexer::new("www  A 192.0.2.1 ; comment\n  TXT \"two\nlines\"\n(a\nb)");
+        assert_eq!(next_token(&mut lexer).unwrap(),
+                   Token::CharData("www".to_string()));
+        assert_eq!(lexer.get_token_position(), (1, 1));
+        assert_eq!(next_token(&mut lexer).unwrap(),
+                   Token::CharData("A".to_string()));
+        assert_eq!(lexer.get_token_position(), (1, 6));
+        assert_eq!(next_token(&mut lexer).unwrap(),
+                   Token::CharData("192.0.2.1".to_string()));
+        assert_eq!(lexer.get_token_position(), (1, 8));
+        assert_eq!(next_token(&mut lexer).unwrap(), Token::EOL);
+        assert_eq!(lexer.get_token_position(), (1, 27));
+        assert_eq!(next_token(&mut lexer).unwrap(), Token::Blank);
+        assert_eq!(lexer.get_token_position(), (2, 1));
+        assert_eq!(next_token(&mut lexer).unwrap(),
+                   Token::CharData("TXT".to_string()));
+        assert_eq!(lexer.get_token_position(), (2, 3));
+        assert_eq!(next_token(&mut lexer).unwrap(),
+                   Token::CharData("two\nlines".to_string()));
+        assert_eq!(lexer.get_token_position(), (2, 7));
+        assert_eq!(lexer.get_position(), (3, 7));
+        assert_eq!(next_token(&mut lexer).unwrap(), Token::EOL);
+        assert_eq!(next_token(&mut lexer).unwrap(),
+                   Token::List(vec!["a".to_string(), "b".to_string()]));
+        assert_eq!(lexer.get_token_position(), (4, 1));
+        assert_eq!(lexer.get_position(), (5, 3));
+    }
+
+    #[test]
+    fn comments() {
+        let mut lexer = Lexer::new(";; first\n; \nwww A 192.0.2.1 ; second\n@ SOA ( a ; c\nb \
+                                    )\n;last");
+        assert_eq!(next_token(&mut lexer).unwrap(), Token::EOL);
+        assert_eq!(next_token(&mut lexer).unwrap(), Token::EOL);
+        assert_eq!(lexer.take_comments(), vec!["first".to_string()]);
+        assert!(lexer.take_comments().is_empty());
+        while let Some(token) = lexer.next_token().unwrap() {
+            if let Token::List(_) = token {
+                assert_eq!(lexer.take_comments(), vec!["second".to_string()]);
+            }
+        }
+        assert_eq!(lexer.take_comments(), vec!["last".to_string()]);
+    }
+
+    #[test]
+    fn skip_line() {
+        let mut lexer = Lexer::new("a ) b\nc");
+        assert_eq!(next_token(&mut lexer).unwrap(),
+                   Token::CharData("a".to_string()));
+        assert!(lexer.next_token().is_err());
+        assert_eq!(lexer.get_position(), (1, 3));
+        lexer.skip_line();
+        assert_eq!(next_token(&mut lexer).unwrap(),
+                   Token::CharData("c".to_string()));
+        assert_eq!(lexer.get_token_position(), (2, 1));
     }
 
     #[test]
@@ -0,0 +1,238 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+
+use ::error::*;
+use rr::{LowerName, RecordType};
+use rr::domain::Name;
+
+/// The names and addresses of the hosts file, hosts(5)
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Hosts {
+    by_name: HashMap<LowerName, Vec<IpAddr>>,
+    by_address: HashMap<IpAddr, Vec<Name>>,
+}
+
+impl Hosts {
+    /// Hosts without any names
+    pub fn new() -> Self {
+        Hosts::default()
+    }
+
+    /// Adds the name of the address, after any it already has
+    pub fn insert(&mut self, name: Name, address: IpAddr) {
+        let addresses = self.by_name.entry(LowerName::new(&name)).or_insert_with(Vec::new);
+        if !addresses.contains(&address) {
+            addresses.push(address);
+        }
+
+        let names = self.by_address.entry(address).or_insert_with(Vec::new);
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+
+    /// Returns true if there are no names
+    pub fn is_empty(&self) -> bool {
+        self.by_name.is_empty()
+    }
+
+    /// The addresses of the name, in the order of the file, None if the name is not a host
+    pub fn get_addresses(&self, name: &Name) -> Option<&[IpAddr]> {
+        self.by_name.get(&LowerName::new(name)).map(|addresses| addresses.as_slice())
+    }
+
+    /// The names of the address, the canonical name first, then the aliases
+    pub fn get_names(&self, address: &IpAddr) -> Option<&[Name]> {
+        self.by_address.get(address).map(|names| names.as_slice())
+    }
+
+    /// The addresses of the name of the type, `A` or `AAAA`
+    ///
+    /// None if the name is not a host, or the type is not that of an address, the query is then
+    ///  for the name servers; an empty list if the host has no address of the type, NODATA.
+    pub fn lookup(&self, name: &Name, record_type: RecordType) -> Option<Vec<IpAddr>> {
+        let is_ipv4 = match record_type {
+            RecordType::A => true,
+            RecordType::AAAA => false,
+            _ => return None,
+        };
+
+        self.get_addresses(name).map(|addresses| {
+            addresses.iter()
+                .filter(|address| match **address {
+                    IpAddr::V4(_) => is_ipv4,
+                    IpAddr::V6(_) => !is_ipv4,
+                })
+                .cloned()
+                .collect()
+        })
+    }
+
+    /// The canonical name of the address of the `in-addr.arpa.` or `ip6.arpa.` name, for a PTR
+    ///  query, None if the name is not that of an address of a host
+    pub fn lookup_ptr(&self, name: &Name) -> Option<&Name> {
+        self.by_address
+            .iter()
+            .find(|&(address, _)| reverse_name(address) == *name)
+            .and_then(|(_, names)| names.first())
+    }
+}
+
+/// The `in-addr.arpa.` or `ip6.arpa.` name of the address, which PTR queries are for
+pub fn reverse_name(address: &IpAddr) -> Name {
+    let mut labels: Vec<String> = match *address {
+        IpAddr::V4(ref ipv4) => ipv4.octets().iter().rev().map(|octet| octet.to_string()).collect(),
+        IpAddr::V6(ref ipv6) => {
+            ipv6.octets()
+                .iter()
+                .rev()
+                .flat_map(|octet| vec![octet & 0xf, octet >> 4])
+                .map(|nibble| format!("{:x}", nibble))
+                .collect()
+        }
+    };
+
+    match *address {
+        IpAddr::V4(_) => labels.push("in-addr".to_string()),
+        IpAddr::V6(_) => labels.push("ip6".to_string()),
+    }
+    labels.push("arpa".to_string());
+
+    Name::with_labels(labels)
+}
+
+/// Parses the contents of a hosts file
+///
+/// Each line is an address followed by the canonical name of the host and any aliases, anything
+///  after a `#` is a comment. Malformed addresses and names are ignored, as the resolver of the
+///  host would.
+pub fn parse_hosts(hosts: &str) -> Hosts {
+    let mut parsed = Hosts::new();
+    let root = Name::root();
+
+    for line in hosts.lines() {
+        let line = match line.find('#') {
+            Some(comment) => &line[..comment],
+            None => line,
+        };
+
+        let mut words = line.split_whitespace();
+        let address = match words.next().map(|address| address.parse::<IpAddr>()) {
+            Some(Ok(address)) => address,
+            Some(Err(_)) => {
+                warn!("ignoring line of hosts: {}", line);
+                continue;
+            }
+            None => continue,
+        };
+
+        for name in words {
+            match Name::parse(name, Some(&root)) {
+                Ok(name) => parsed.insert(name, address),
+                Err(e) => warn!("ignoring name of hosts {}: {}", name, e),
+            }
+        }
+    }
+
+    parsed
+}
+
+/// The path of the hosts file, `/etc/hosts` on unix
+#[cfg(unix)]
+pub fn hosts_path() -> PathBuf {
+    PathBuf::from("/etc/hosts")
+}
+
+/// The path of the hosts file, `System32\drivers\etc\hosts` of the Windows directory
+#[cfg(windows)]
+pub fn hosts_path() -> PathBuf {
+    use std::env;
+
+    env::var_os("SystemRoot")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("C:\\Windows"))
+        .join("System32\\drivers\\etc\\hosts")
+}
+
+/// Reads the hosts file of the host, see `hosts_path()`
+pub fn read_hosts() -> ClientResult<Hosts> {
+    read_hosts_file(&hosts_path())
+}
+
+/// Reads the hosts file at the path
+pub fn read_hosts_file(path: &Path) -> ClientResult<Hosts> {
+    let mut hosts = String::new();
+    try!(try!(File::open(path)).read_to_string(&mut hosts));
+
+    Ok(parse_hosts(&hosts))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+    use rr::RecordType;
+    use rr::domain::Name;
+    use super::*;
+
+    fn name(name: &str) -> Name {
+        Name::parse(name, None).unwrap()
+    }
+
+    #[test]
+    fn test_parse_hosts() {
+        let hosts = parse_hosts("# static names\n\
+                                 127.0.0.1 localhost\n\
+                                 ::1 localhost ip6-localhost # loopback\n\
+                                 192.0.2.10 printer.example.com. Printer\n\
+                                 192.0.2.11 printer.example.com\n\
+                                 not-an-address ignored\n");
+
+        let printer = [IpAddr::V4(Ipv4Addr::new(192, 0, 2, 10)),
+                       IpAddr::V4(Ipv4Addr::new(192, 0, 2, 11))];
+        assert_eq!(hosts.get_addresses(&name("printer.example.com.")), Some(&printer[..]));
+        assert_eq!(hosts.get_addresses(&name("PRINTER.")), Some(&printer[..1]));
+        assert_eq!(hosts.get_names(&IpAddr::V6(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 1))),
+                   Some(&[name("localhost."), name("ip6-localhost.")][..]));
+        assert!(hosts.get_addresses(&name("ignored.")).is_none());
+    }
+
+    #[test]
+    fn test_lookup() {
+        let hosts = parse_hosts("127.0.0.1 localhost\n::1 localhost\n192.0.2.1 www.example.com\n");
+
+        assert_eq!(hosts.lookup(&name("localhost."), RecordType::A),
+                   Some(vec![IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1))]));
+        assert_eq!(hosts.lookup(&name("localhost."), RecordType::AAAA),
+                   Some(vec![IpAddr::V6(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 1))]));
+        assert_eq!(hosts.lookup(&name("www.example.com."), RecordType::AAAA), Some(vec![]));
+        assert_eq!(hosts.lookup(&name("www.example.com."), RecordType::MX), None);
+        assert_eq!(hosts.lookup(&name("example.com."), RecordType::A), None);
+
+        assert_eq!(hosts.lookup_ptr(&name("1.2.0.192.in-addr.arpa.")),
+                   Some(&name("www.example.com.")));
+        assert_eq!(hosts.lookup_ptr(&name("1.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.\
+                                           0.0.0.0.0.0.ip6.arpa.")),
+                   Some(&name("localhost.")));
+        assert!(hosts.lookup_ptr(&name("2.2.0.192.in-addr.arpa.")).is_none());
+    }
+
+    #[test]
+    fn test_reverse_name() {
+        assert_eq!(reverse_name(&IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1))),
+                   name("1.2.0.192.in-addr.arpa."));
+        assert_eq!(reverse_name(&IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 0x53))),
+                   name("3.5.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.8.b.d.0.1.0.0.2.\
+                         ip6.arpa."));
+    }
+}
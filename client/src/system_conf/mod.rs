@@ -0,0 +1,171 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! The resolver configuration of the host, its name servers, search domains and options, from
+//!  `/etc/resolv.conf` on unix and the registry on Windows, and its hosts file, see
+//!  `HostsClientHandle`
+//!
+//! ```no_run
+//! use trust_dns::client::{Client, SyncClient};
+//! use trust_dns::rr::{DNSClass, RecordType};
+//! use trust_dns::system_conf::read_system_conf;
+//!
+//! let conf = read_system_conf().unwrap();
+//! let client = SyncClient::new(conf.udp_client_connection().unwrap());
+//!
+//! let response = client.search("www", &conf.get_search_list(), DNSClass::IN, RecordType::A)
+//!     .unwrap();
+//! ```
+
+mod hosts;
+mod resolv_conf;
+#[cfg(windows)]
+mod windows;
+
+use std::cmp;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::time::Duration;
+
+use client::{BasicClientHandle, FailoverClientHandle, RetryPolicy, SearchList, SyncClient,
+             TimeoutConfig};
+use ::error::*;
+use rr::domain::Name;
+use udp::UdpClientConnection;
+
+pub use self::hosts::{hosts_path, parse_hosts, read_hosts, read_hosts_file, reverse_name, Hosts};
+pub use self::resolv_conf::parse_resolv_conf;
+
+/// The path of the resolver configuration file on unix
+pub const RESOLV_CONF_PATH: &'static str = "/etc/resolv.conf";
+
+/// The name servers, search domains and options of the resolver of the host
+#[derive(Debug, Clone, PartialEq)]
+pub struct SystemConf {
+    name_servers: Vec<SocketAddr>,
+    search: Vec<Name>,
+    ndots: usize,
+    timeout: Duration,
+    attempts: usize,
+    rotate: bool,
+}
+
+impl Default for SystemConf {
+    /// The defaults of resolv.conf(5), without any name servers
+    fn default() -> Self {
+        SystemConf {
+            name_servers: vec![],
+            search: vec![],
+            ndots: 1,
+            timeout: Duration::from_secs(5),
+            attempts: 2,
+            rotate: false,
+        }
+    }
+}
+
+impl SystemConf {
+    /// The name servers to query, in order
+    ///
+    /// If none are configured, this is the name server on the local host, as with resolv.conf.
+    pub fn get_name_servers(&self) -> &[SocketAddr] {
+        &self.name_servers
+    }
+
+    /// The domains which relative names are looked up in, in order
+    pub fn get_search(&self) -> &[Name] {
+        &self.search
+    }
+
+    /// The number of dots a name must have to be first looked up as it is, before the search
+    ///  domains
+    pub fn get_ndots(&self) -> usize {
+        self.ndots
+    }
+
+    /// The time to wait for a response from a name server
+    pub fn get_timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// The number of times the name servers are queried before giving up
+    pub fn get_attempts(&self) -> usize {
+        self.attempts
+    }
+
+    /// Whether the queries should be spread over the name servers rather than tried in order
+    pub fn is_rotate(&self) -> bool {
+        self.rotate
+    }
+
+    /// The default timeouts with the query timeout of the configuration
+    pub fn get_timeouts(&self) -> TimeoutConfig {
+        TimeoutConfig { query: self.timeout, ..TimeoutConfig::default() }
+    }
+
+    /// The retries of the configuration, as libc does each of the `attempts` goes to every name
+    ///  server, and its `rotate`, for `FailoverClientHandle`
+    pub fn get_retry_policy(&self) -> RetryPolicy {
+        let attempts = cmp::max(self.attempts, 1) * cmp::max(self.name_servers.len(), 1);
+        RetryPolicy { retries: attempts - 1, rotate: self.rotate, ..RetryPolicy::default() }
+    }
+
+    /// The names to look up for the name, in order, as the resolver of the host does
+    ///
+    /// A fully qualified name, ending with `.`, is only itself. Otherwise the name is first tried
+    ///  as it is, if it has at least `ndots` dots, and then in each of the search domains; a
+    ///  name with fewer dots is tried as it is last. See `SearchList::get_names`.
+    pub fn get_search_names(&self, name: &str) -> ParseResult<Vec<Name>> {
+        self.get_search_list().get_names(name)
+    }
+
+    /// The search domains and `ndots` of the configuration, for `Client::search`
+    pub fn get_search_list(&self) -> SearchList {
+        SearchList::new(self.search.clone(), self.ndots)
+    }
+
+    /// A UDP connection to the first name server, with the timeout of the configuration
+    pub fn udp_client_connection(&self) -> ClientResult<UdpClientConnection> {
+        let name_server = try!(self.name_servers
+            .first()
+            .ok_or(ClientError::from(ClientErrorKind::Message("no name servers configured"))));
+
+        UdpClientConnection::with_timeouts(*name_server, self.get_timeouts())
+    }
+
+    /// A client over UDP to all the name servers, with the timeout and retries of the
+    ///  configuration, see `SyncClient::with_failover`
+    pub fn failover_client(&self)
+                           -> ClientResult<SyncClient<FailoverClientHandle<BasicClientHandle>>> {
+        SyncClient::with_failover(&self.name_servers, self.get_timeouts(), self.get_retry_policy())
+    }
+
+    /// Without configured name servers, the name server on the local host
+    fn default_name_servers(&mut self) {
+        if self.name_servers.is_empty() {
+            self.name_servers.push(SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 53));
+        }
+    }
+}
+
+/// Reads the resolver configuration of the host, `/etc/resolv.conf` on unix
+#[cfg(unix)]
+pub fn read_system_conf() -> ClientResult<SystemConf> {
+    use std::fs::File;
+    use std::io::Read;
+
+    let mut resolv_conf = String::new();
+    try!(try!(File::open(RESOLV_CONF_PATH)).read_to_string(&mut resolv_conf));
+
+    Ok(parse_resolv_conf(&resolv_conf))
+}
+
+/// Reads the resolver configuration of the host, the TCP/IP parameters of the registry on
+///  Windows
+#[cfg(windows)]
+pub fn read_system_conf() -> ClientResult<SystemConf> {
+    windows::read_system_conf()
+}
@@ -0,0 +1,167 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::cmp;
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+
+use rr::domain::Name;
+use system_conf::SystemConf;
+
+/// At most this many name servers are used, the rest are ignored, as by glibc
+const MAX_NAME_SERVERS: usize = 3;
+/// At most this many search domains are used
+const MAX_SEARCH: usize = 6;
+
+/// Parses the contents of a resolv.conf(5) file
+///
+/// Lines with `nameserver`, `domain`, `search` and `options`, of which `ndots`, `timeout`,
+///  `attempts` and `rotate`, are read, anything else is ignored, as are malformed values, as the
+///  resolver of the host would. The last of `domain` and `search` is the search list.
+pub fn parse_resolv_conf(resolv_conf: &str) -> SystemConf {
+    let mut conf = SystemConf::default();
+    let root = Name::root();
+
+    for line in resolv_conf.lines() {
+        let line = line.trim();
+        if line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+
+        let mut words = line.split_whitespace();
+        let keyword = match words.next() {
+            Some(keyword) => keyword,
+            None => continue,
+        };
+
+        match keyword {
+            "nameserver" => {
+                let address = match words.next().map(|address| address.parse::<IpAddr>()) {
+                    Some(Ok(address)) => address,
+                    _ => {
+                        warn!("ignoring nameserver of resolv.conf: {}", line);
+                        continue;
+                    }
+                };
+
+                if conf.name_servers.len() < MAX_NAME_SERVERS {
+                    conf.name_servers.push(SocketAddr::new(address, 53));
+                }
+            }
+            "domain" | "search" => {
+                conf.search = words.filter_map(|domain| match Name::parse(domain, Some(&root)) {
+                        Ok(domain) => Some(domain),
+                        Err(e) => {
+                            warn!("ignoring search domain of resolv.conf {}: {}", domain, e);
+                            None
+                        }
+                    })
+                    .take(if keyword == "domain" { 1 } else { MAX_SEARCH })
+                    .collect();
+            }
+            "options" => {
+                for option in words {
+                    let mut option = option.splitn(2, ':');
+                    let name = option.next().unwrap_or("");
+                    let value = option.next().and_then(|value| value.parse::<usize>().ok());
+
+                    match (name, value) {
+                        ("ndots", Some(ndots)) => conf.ndots = cmp::min(ndots, 15),
+                        ("timeout", Some(timeout)) => {
+                            conf.timeout = Duration::from_secs(cmp::min(timeout, 30) as u64)
+                        }
+                        ("attempts", Some(attempts)) => conf.attempts = cmp::min(attempts, 5),
+                        ("rotate", _) => conf.rotate = true,
+                        _ => debug!("ignoring option of resolv.conf: {}", name),
+                    }
+                }
+            }
+            _ => debug!("ignoring line of resolv.conf: {}", line),
+        }
+    }
+
+    conf.default_name_servers();
+    conf
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+    use std::time::Duration;
+
+    use rr::domain::Name;
+    use super::*;
+
+    #[test]
+    fn test_parse_resolv_conf() {
+        let conf = parse_resolv_conf("# generated by dhcp\n\
+                                      ; another comment\n\
+                                      nameserver 192.0.2.53\n\
+                                      nameserver 2001:db8::53\n\
+                                      nameserver not-an-address\n\
+                                      domain example.net\n\
+                                      search example.com corp.example.com.\n\
+                                      options ndots:2 timeout:3 attempts:9 rotate edns0\n\
+                                      sortlist 130.155.160.0/255.255.240.0\n");
+
+        assert_eq!(conf.get_name_servers(),
+                   &[SocketAddr::new(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 53)), 53),
+                     SocketAddr::new(IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 0x53)),
+                                     53)]);
+        assert_eq!(conf.get_search(),
+                   &[Name::parse("example.com.", None).unwrap(),
+                     Name::parse("corp.example.com.", None).unwrap()]);
+        assert_eq!(conf.get_ndots(), 2);
+        assert_eq!(conf.get_timeout(), Duration::from_secs(3));
+        assert_eq!(conf.get_timeouts().query, Duration::from_secs(3));
+        assert_eq!(conf.get_attempts(), 5);
+        assert!(conf.is_rotate());
+        assert_eq!(conf.get_retry_policy().retries, 9);
+        assert!(conf.get_retry_policy().rotate);
+    }
+
+    #[test]
+    fn test_defaults() {
+        let conf = parse_resolv_conf("nameserver 192.0.2.1\nnameserver 192.0.2.2\n\
+                                      nameserver 192.0.2.3\nnameserver 192.0.2.4\n");
+        assert_eq!(conf.get_name_servers().len(), 3);
+        assert_eq!(conf.get_ndots(), 1);
+        assert_eq!(conf.get_timeout(), Duration::from_secs(5));
+        assert_eq!(conf.get_attempts(), 2);
+        assert!(!conf.is_rotate());
+
+        // the name server on the local host
+        assert_eq!(parse_resolv_conf("").get_name_servers(),
+                   &[SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 53)]);
+    }
+
+    #[test]
+    fn test_search_names() {
+        let conf = parse_resolv_conf("search example.com example.net\noptions ndots:2\n");
+        let names = |name: &str| {
+            conf.get_search_names(name)
+                .unwrap()
+                .iter()
+                .map(|name| name.to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(names("www"),
+                   vec!["www.example.com.", "www.example.net.", "www."]);
+        assert_eq!(names("www.dept"),
+                   vec!["www.dept.", "www.dept.example.com.", "www.dept.example.net."]);
+        assert_eq!(names("www.example.org."), vec!["www.example.org."]);
+    }
+
+    #[test]
+    fn test_search_list() {
+        let conf = parse_resolv_conf("search example.com example.net\noptions ndots:2\n");
+        let search_list = conf.get_search_list();
+        assert_eq!(search_list.get_search(), conf.get_search());
+        assert_eq!(search_list.get_ndots(), 2);
+    }
+}
@@ -0,0 +1,74 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::net::{IpAddr, SocketAddr};
+
+use winreg::RegKey;
+use winreg::enums::{HKEY_LOCAL_MACHINE, KEY_READ};
+
+use ::error::*;
+use rr::domain::Name;
+use system_conf::SystemConf;
+
+const TCPIP_PARAMETERS: &'static str = "SYSTEM\\CurrentControlSet\\Services\\Tcpip\\Parameters";
+
+/// Reads the name servers and search domains of the TCP/IP parameters
+///
+/// The name servers are those configured for the host, or else those of its interfaces, either
+///  configured or from DHCP. The search list is `SearchList`, or else the primary DNS suffix of
+///  the host, `Domain` or `DhcpDomain`. The options of resolv.conf have their defaults.
+pub fn read_system_conf() -> ClientResult<SystemConf> {
+    let parameters = try!(RegKey::predef(HKEY_LOCAL_MACHINE)
+        .open_subkey_with_flags(TCPIP_PARAMETERS, KEY_READ));
+
+    let mut conf = SystemConf::default();
+
+    let mut name_servers = addresses(&parameters);
+    if name_servers.is_empty() {
+        if let Ok(interfaces) = parameters.open_subkey_with_flags("Interfaces", KEY_READ) {
+            for interface in interfaces.enum_keys().filter_map(|interface| interface.ok()) {
+                if let Ok(interface) = interfaces.open_subkey_with_flags(&interface, KEY_READ) {
+                    for address in addresses(&interface) {
+                        if !name_servers.contains(&address) {
+                            name_servers.push(address);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    conf.name_servers =
+        name_servers.into_iter().map(|address| SocketAddr::new(address, 53)).collect();
+
+    let search = ["SearchList", "Domain", "DhcpDomain"]
+        .iter()
+        .filter_map(|value| parameters.get_value::<String, _>(value).ok())
+        .find(|search| !search.trim().is_empty())
+        .unwrap_or_else(String::new);
+    let root = Name::root();
+    conf.search = search.split(|ch: char| ch == ',' || ch == ' ')
+        .filter(|domain| !domain.is_empty())
+        .filter_map(|domain| Name::parse(domain, Some(&root)).ok())
+        .collect();
+
+    conf.default_name_servers();
+    Ok(conf)
+}
+
+/// The name servers of the key, configured or else from DHCP, separated by spaces or commas
+fn addresses(key: &RegKey) -> Vec<IpAddr> {
+    ["NameServer", "DhcpNameServer"]
+        .iter()
+        .filter_map(|value| key.get_value::<String, _>(value).ok())
+        .find(|addresses| !addresses.trim().is_empty())
+        .map(|addresses| {
+            addresses.split(|ch: char| ch == ',' || ch == ' ')
+                .filter_map(|address| address.parse::<IpAddr>().ok())
+                .collect()
+        })
+        .unwrap_or_else(Vec::new)
+}
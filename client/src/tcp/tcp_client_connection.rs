@@ -22,7 +22,7 @@ use tokio_core::net::TcpStream;
 use tokio_core::reactor::Core;
 
 use ::error::*;
-use client::{ClientConnection, ClientStreamHandle};
+use client::{ClientConnection, ClientStreamHandle, TimeoutConfig};
 use tcp::TcpClientStream;
 
 /// TCP based DNS client
@@ -30,6 +30,7 @@ pub struct TcpClientConnection {
     io_loop: Core,
     tcp_client_stream: Box<Future<Item = TcpClientStream<TcpStream>, Error = io::Error>>,
     client_stream_handle: Box<ClientStreamHandle>,
+    timeouts: TimeoutConfig,
 }
 
 impl TcpClientConnection {
@@ -42,14 +43,42 @@ impl TcpClientConnection {
     ///
     /// * `name_server` - address of the name server to use for queries
     pub fn new(name_server: SocketAddr) -> ClientResult<Self> {
+        Self::with_timeouts(name_server, TimeoutConfig::default())
+    }
+
+    /// Creates a new client connection with the specified timeouts
+    ///
+    /// # Arguments
+    ///
+    /// * `name_server` - address of the name server to use for queries
+    /// * `timeouts` - connect, read, write and query timeouts
+    pub fn with_timeouts(name_server: SocketAddr, timeouts: TimeoutConfig) -> ClientResult<Self> {
+        Self::with_bind_addr(name_server, None, timeouts)
+    }
+
+    /// Creates a new client connection from the local address `bind_addr`, if one is given
+    ///
+    /// # Arguments
+    ///
+    /// * `name_server` - address of the name server to use for queries
+    /// * `bind_addr` - the local IP, and Port or 0, to connect from
+    /// * `timeouts` - connect, read, write and query timeouts
+    pub fn with_bind_addr(name_server: SocketAddr,
+                          bind_addr: Option<SocketAddr>,
+                          timeouts: TimeoutConfig)
+                          -> ClientResult<Self> {
         let io_loop = try!(Core::new());
-        let (tcp_client_stream, handle) = TcpClientStream::<TcpStream>::new(name_server,
-                                                                            io_loop.handle());
+        let (tcp_client_stream, handle) =
+            TcpClientStream::<TcpStream>::with_bind_addr(name_server,
+                                                         bind_addr,
+                                                         io_loop.handle(),
+                                                         timeouts);
 
         Ok(TcpClientConnection {
             io_loop: io_loop,
             tcp_client_stream: tcp_client_stream,
             client_stream_handle: handle,
+            timeouts: timeouts,
         })
     }
 }
@@ -57,6 +86,10 @@ impl TcpClientConnection {
 impl ClientConnection for TcpClientConnection {
     type MessageStream = TcpClientStream<TcpStream>;
 
+    fn get_timeouts(&self) -> TimeoutConfig {
+        self.timeouts
+    }
+
   fn unwrap(self) -> (Core, Box<Future<Item=Self::MessageStream, Error=io::Error>>, Box<ClientStreamHandle>) {
         (self.io_loop, self.tcp_client_stream, self.client_stream_handle)
     }
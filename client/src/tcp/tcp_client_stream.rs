@@ -15,7 +15,7 @@ use tokio_core::reactor::Handle;
 
 use BufClientStreamHandle;
 use tcp::TcpStream;
-use client::ClientStreamHandle;
+use client::{ClientStreamHandle, TimeoutConfig};
 
 #[must_use = "futures do nothing unless polled"]
 pub struct TcpClientStream<S> {
@@ -30,10 +30,37 @@ impl TcpClientStream<TokioTcpStream> {
                loop_handle: Handle)
                -> (Box<Future<Item = TcpClientStream<TokioTcpStream>, Error = io::Error>>,
                    Box<ClientStreamHandle>) {
-        let (stream_future, sender) = TcpStream::new(name_server, loop_handle);
+        Self::with_timeouts(name_server, loop_handle, TimeoutConfig::default())
+    }
+
+    /// Creates a new stream, which fails if a message is not read or written within the read and
+    ///  write timeouts
+    ///
+    /// The connect timeout is applied by `ClientFuture::with_timeouts()`.
+    pub fn with_timeouts(name_server: SocketAddr,
+                         loop_handle: Handle,
+                         timeouts: TimeoutConfig)
+                         -> (Box<Future<Item = TcpClientStream<TokioTcpStream>, Error = io::Error>>,
+                             Box<ClientStreamHandle>) {
+        Self::with_bind_addr(name_server, None, loop_handle, timeouts)
+    }
+
+    /// Creates a new stream with timeouts, connected from `bind_addr` if one is given
+    ///
+    /// The port of `bind_addr` may be 0, in which case the system chooses one.
+    pub fn with_bind_addr
+        (name_server: SocketAddr,
+         bind_addr: Option<SocketAddr>,
+         loop_handle: Handle,
+         timeouts: TimeoutConfig)
+         -> (Box<Future<Item = TcpClientStream<TokioTcpStream>, Error = io::Error>>,
+             Box<ClientStreamHandle>) {
+        let (stream_future, sender) =
+            TcpStream::with_bind_addr(name_server, bind_addr, loop_handle.clone());
 
         let new_future: Box<Future<Item=TcpClientStream<TokioTcpStream>, Error=io::Error>> =
-      Box::new(stream_future.map(move |tcp_stream| {
+      Box::new(stream_future.map(move |mut tcp_stream| {
+        tcp_stream.set_timeouts(timeouts.read, timeouts.write, loop_handle);
         TcpClientStream {
           tcp_stream: tcp_stream,
         }
@@ -6,15 +6,17 @@
 // copied, modified, or distributed except according to those terms.
 
 use std::mem;
-use std::net::SocketAddr;
+use std::net::{SocketAddr, TcpStream as StdTcpStream};
 use std::io;
+use std::time::Duration;
 
-use futures::{Async, Future, Poll};
+use futures::{failed, Async, Future, Poll};
 use futures::stream::{Fuse, Peekable, Stream};
 use futures::sync::mpsc::{unbounded, UnboundedReceiver};
+use net2::TcpBuilder;
 use tokio_core::io::Io;
 use tokio_core::net::TcpStream as TokioTcpStream;
-use tokio_core::reactor::Handle;
+use tokio_core::reactor::{Handle, Timeout};
 
 use BufStreamHandle;
 
@@ -33,6 +35,47 @@ pub enum ReadTcpState {
     Bytes { pos: usize, bytes: Vec<u8> },
 }
 
+/// Read and write timeouts, see `TcpStream::set_timeouts()`
+struct Deadlines {
+    reactor_handle: Handle,
+    read_timeout: Duration,
+    write_timeout: Duration,
+    /// deadline of the message being read, along with the number of messages received before it
+    read: Option<(Timeout, usize)>,
+    /// deadline of the message being written, along with the number of messages sent before it
+    write: Option<(Timeout, usize)>,
+}
+
+/// Clears the deadline if no message is in progress, otherwise starts it for a new message and
+///  fails once it has passed
+///
+/// `message` is the number of messages transferred before the one in progress.
+fn poll_deadline(deadline: &mut Option<(Timeout, usize)>,
+                 in_progress: bool,
+                 message: usize,
+                 timeout: Duration,
+                 reactor_handle: &Handle,
+                 what: &str)
+                 -> io::Result<()> {
+    if !in_progress || timeout == Duration::from_millis(0) {
+        *deadline = None;
+        return Ok(());
+    }
+
+    if deadline.as_ref().map_or(true, |&(_, started)| started != message) {
+        *deadline = Some((try!(Timeout::new(timeout, reactor_handle)), message));
+    }
+
+    if let Some((ref mut timeout_future, _)) = *deadline {
+        if let Async::Ready(()) = try!(timeout_future.poll()) {
+            return Err(io::Error::new(io::ErrorKind::TimedOut,
+                                      format!("{} not completed within {:?}", what, timeout)));
+        }
+    }
+
+    Ok(())
+}
+
 #[must_use = "futures do nothing unless polled"]
 pub struct TcpStream<S> {
     socket: S,
@@ -40,12 +83,62 @@ pub struct TcpStream<S> {
     send_state: Option<WriteTcpState>,
     read_state: ReadTcpState,
     peer_addr: SocketAddr,
+    max_message_len: u16,
+    messages_received: usize,
+    messages_sent: usize,
+    deadlines: Option<Deadlines>,
 }
 
 impl<S> TcpStream<S> {
     pub fn peer_addr(&self) -> SocketAddr {
         self.peer_addr
     }
+
+    /// Sets the largest length prefix that will be accepted from the peer.
+    ///
+    /// Any length prefix greater than this will close the stream with an `InvalidData` error
+    ///  rather than allocating a buffer for it.
+    pub fn set_max_message_len(&mut self, max_message_len: u16) {
+        self.max_message_len = max_message_len;
+    }
+
+    /// Fails the stream with a `TimedOut` error if a message is not completely read, or written,
+    ///  within the timeout. A zero duration disables that timeout.
+    ///
+    /// The read timeout starts with the first byte of the length prefix, so an idle connection
+    ///  does not time out.
+    pub fn set_timeouts(&mut self,
+                        read_timeout: Duration,
+                        write_timeout: Duration,
+                        reactor_handle: Handle) {
+        self.deadlines = Some(Deadlines {
+            reactor_handle: reactor_handle,
+            read_timeout: read_timeout,
+            write_timeout: write_timeout,
+            read: None,
+            write: None,
+        });
+    }
+
+    /// Returns true if part of a message, including its length prefix, has been read but the
+    ///  message is not yet complete.
+    pub fn is_receiving(&self) -> bool {
+        match self.read_state {
+            ReadTcpState::LenBytes { pos, .. } => pos > 0,
+            ReadTcpState::Bytes { .. } => true,
+        }
+    }
+
+    /// Returns true if a message is being written, once `poll` returned `NotReady` none is
+    ///  otherwise waiting to be written.
+    pub fn is_sending(&self) -> bool {
+        self.send_state.is_some()
+    }
+
+    /// The number of messages received from the peer for which no message has yet been sent back.
+    pub fn unanswered_messages(&self) -> usize {
+        self.messages_received.saturating_sub(self.messages_sent)
+    }
 }
 
 impl TcpStream<TokioTcpStream> {
@@ -59,8 +152,23 @@ impl TcpStream<TokioTcpStream> {
         (name_server: SocketAddr,
          loop_handle: Handle)
          -> (Box<Future<Item = TcpStream<TokioTcpStream>, Error = io::Error>>, BufStreamHandle) {
+        Self::with_bind_addr(name_server, None, loop_handle)
+    }
+
+    /// Creates a new future of the connection, from the local address `bind_addr` if one is given
+    ///
+    /// # Arguments
+    ///
+    /// * `name_server` - the IP and Port of the DNS server to connect to
+    /// * `bind_addr` - the local IP, and Port or 0, to connect from, e.g. on multi-homed hosts
+    /// * `loop_handle` - reference to the takio_core::Core for future based IO
+    pub fn with_bind_addr
+        (name_server: SocketAddr,
+         bind_addr: Option<SocketAddr>,
+         loop_handle: Handle)
+         -> (Box<Future<Item = TcpStream<TokioTcpStream>, Error = io::Error>>, BufStreamHandle) {
         let (message_sender, outbound_messages) = unbounded();
-        let tcp = TokioTcpStream::connect(&name_server, &loop_handle);
+        let tcp = connect(name_server, bind_addr, &loop_handle);
 
         // This set of futures collapses the next tcp socket into a stream which can be used for
         //  sending and receiving tcp packets.
@@ -75,6 +183,10 @@ impl TcpStream<TokioTcpStream> {
                         bytes: [0u8; 2],
                     },
                     peer_addr: name_server,
+                    max_message_len: u16::max_value(),
+                    messages_received: 0,
+                    messages_sent: 0,
+                    deadlines: None,
                 }
             }));
 
@@ -82,6 +194,37 @@ impl TcpStream<TokioTcpStream> {
     }
 }
 
+/// Connects to `name_server`, from the local address `bind_addr` if one is given
+///
+/// This is the TCP connection under the DNS framing, shared with TLS and HTTPS.
+pub fn connect(name_server: SocketAddr,
+               bind_addr: Option<SocketAddr>,
+               loop_handle: &Handle)
+               -> Box<Future<Item = TokioTcpStream, Error = io::Error>> {
+    match bind_addr {
+        Some(bind_addr) => {
+            match bound_socket(bind_addr) {
+                Ok(std_stream) => {
+                    TokioTcpStream::connect_stream(std_stream, &name_server, loop_handle)
+                }
+                Err(e) => Box::new(failed(e)),
+            }
+        }
+        None => Box::new(TokioTcpStream::connect(&name_server, loop_handle)),
+    }
+}
+
+/// A socket bound to `bind_addr`, not yet connected
+fn bound_socket(bind_addr: SocketAddr) -> io::Result<StdTcpStream> {
+    let builder = match bind_addr {
+        SocketAddr::V4(_) => try!(TcpBuilder::new_v4()),
+        SocketAddr::V6(_) => try!(TcpBuilder::new_v6()),
+    };
+
+    try!(builder.bind(bind_addr));
+    builder.to_tcp_stream()
+}
+
 impl<S: Io> TcpStream<S> {
     /// Initializes a TcpStream with an existing tokio_core::net::TcpStream.
     ///
@@ -113,6 +256,10 @@ impl<S: Io> TcpStream<S> {
                 bytes: [0u8; 2],
             },
             peer_addr: peer_addr,
+            max_message_len: u16::max_value(),
+            messages_received: 0,
+            messages_sent: 0,
+            deadlines: None,
         }
     }
 }
@@ -122,6 +269,35 @@ impl<S: Io> Stream for TcpStream<S> {
     type Error = io::Error;
 
     fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        let result = try!(self.poll_messages());
+
+        // only while blocked, a ready message is returned and this is polled again
+        if let Async::NotReady = result {
+            let sending = self.send_state.is_some();
+            let receiving = self.is_receiving();
+
+            if let Some(ref mut deadlines) = self.deadlines {
+                try!(poll_deadline(&mut deadlines.write,
+                                   sending,
+                                   self.messages_sent,
+                                   deadlines.write_timeout,
+                                   &deadlines.reactor_handle,
+                                   "write"));
+                try!(poll_deadline(&mut deadlines.read,
+                                   receiving,
+                                   self.messages_received,
+                                   deadlines.read_timeout,
+                                   &deadlines.reactor_handle,
+                                   "read"));
+            }
+        }
+
+        Ok(result)
+    }
+}
+
+impl<S: Io> TcpStream<S> {
+    fn poll_messages(&mut self) -> Poll<Option<(Vec<u8>, SocketAddr)>, io::Error> {
         // this will not accept incoming data while there is data to send
         //  makes this self throttling.
         // TODO: it might be interesting to try and split the sending and receiving futures.
@@ -181,6 +357,7 @@ impl<S: Io> Stream for TcpStream<S> {
                     Some(WriteTcpState::Flushing) => {
                         // At this point we successfully delivered the entire message.
                         mem::replace(&mut self.send_state, None);
+                        self.messages_sent += 1;
                     }
                     None => (),
                 };
@@ -245,6 +422,11 @@ impl<S: Io> Stream for TcpStream<S> {
                     } else {
                         let length = (bytes[0] as u16) << 8 & 0xFF00 | bytes[1] as u16 & 0x00FF;
                         debug!("got length: {}", length);
+                        if length > self.max_message_len {
+                            return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                                      format!("bad message length: {}", length)));
+                        }
+
                         let mut bytes = Vec::with_capacity(length as usize);
                         bytes.resize(length as usize, 0);
 
@@ -300,6 +482,7 @@ impl<S: Io> Stream for TcpStream<S> {
         // if the buffer is ready, return it, if not we're NotReady
         if let Some(buffer) = ret_buf {
             debug!("returning buffer");
+            self.messages_received += 1;
             let src_addr = self.peer_addr;
             return Ok(Async::Ready(Some((buffer, src_addr))));
         } else {
@@ -0,0 +1,245 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! DANE, authentication of TLS services by the TLSA records of their names, RFC 6698
+
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use futures::Future;
+use openssl::x509::X509Ref;
+
+use client::{ClientHandle, SecureClientHandle};
+use ::error::*;
+use rr::{DNSClass, RData, RecordType};
+use rr::domain::Name;
+use rr::dnssec::DigestType;
+use rr::rdata::TLSA;
+use rr::rdata::tlsa::{CertUsage, Matching, Selector};
+
+/// The name of the TLSA records of the TLS service on the port of the name, `_<port>._tcp.<name>`,
+///  RFC 6698 section 3
+pub fn tlsa_name(name: &Name, port: u16) -> Name {
+    name.prepend_label(Rc::new("_tcp".to_string())).prepend_label(Rc::new(format!("_{}", port)))
+}
+
+/// Looks up the TLSA records of the TLS service on the port of the name
+///
+/// The records are only trustworthy if they are validated, hence the `SecureClientHandle`, which
+///  should resolve through a path independent of the service the records are for; the response
+///  is an error if it can not be validated. The Vec is empty if the name has no TLSA records.
+pub fn lookup_tlsa<H>(client: &mut SecureClientHandle<H>,
+                      name: &Name,
+                      port: u16)
+                      -> Box<Future<Item = Vec<TLSA>, Error = ClientError>>
+    where H: ClientHandle + 'static
+{
+    Box::new(client.query(tlsa_name(name, port), DNSClass::IN, RecordType::TLSA)
+        .map(|response| {
+            response.get_answers()
+                .iter()
+                .filter_map(|record| if let &RData::TLSA(ref tlsa) = record.get_rdata() {
+                    Some(tlsa.clone())
+                } else {
+                    None
+                })
+                .collect()
+        }))
+}
+
+/// Returns true if the certificate has the association data of the TLSA record, whatever the
+///  usage of the record
+pub fn tlsa_matches(tlsa: &TLSA, cert: &X509Ref) -> bool {
+    let selected = match tlsa.get_selector() {
+        Selector::Full => cert.to_der(),
+        Selector::Spki => cert.public_key().and_then(|key| key.public_key_to_der()),
+        _ => return false,
+    };
+    let selected = match selected {
+        Ok(selected) => selected,
+        Err(e) => {
+            warn!("could not select the {:?} of the certificate: {}", tlsa.get_selector(), e);
+            return false;
+        }
+    };
+
+    let data = match tlsa.get_matching() {
+        Matching::Raw => Ok(selected),
+        Matching::Sha256 => DigestType::SHA256.hash(&selected),
+        Matching::Sha512 => DigestType::SHA512.hash(&selected),
+        _ => return false,
+    };
+
+    data.map(|data| data == tlsa.get_cert_data()).unwrap_or(false)
+}
+
+/// Verifies the certificate chain of a TLS handshake against TLSA records, in the place of the
+///  trusted certificate authorities
+///
+/// Only the DANE-TA and DANE-EE usages are used, records of the PKIX usages are unusable, as
+///  RFC 7672 section 3.1.3 has them for SMTP. An end entity certificate matching a DANE-EE
+///  record is trusted as it is, RFC 7671 section 5.1, while a chain with a certificate matching
+///  a DANE-TA record is validated from that certificate, including the name of the server.
+pub struct DaneVerifier {
+    tlsa: Vec<TLSA>,
+    trust_anchor_matched: AtomicBool,
+}
+
+impl DaneVerifier {
+    /// The verifier of a handshake, None if none of the records are usable
+    pub fn new(tlsa: Vec<TLSA>) -> Option<Self> {
+        let tlsa = tlsa.into_iter()
+            .filter(|tlsa| match tlsa.get_cert_usage() {
+                CertUsage::TrustAnchor | CertUsage::DomainIssued => true,
+                _ => false,
+            })
+            .collect::<Vec<_>>();
+
+        if tlsa.is_empty() {
+            return None;
+        }
+
+        Some(DaneVerifier {
+            tlsa: tlsa,
+            trust_anchor_matched: AtomicBool::new(false),
+        })
+    }
+
+    /// Verifies the certificate at the depth of the chain, in the order of the TLS library, from
+    ///  the greatest depth to the end entity certificate at zero
+    ///
+    /// # Arguments
+    ///
+    /// * `preverify_ok` - true if the certificate passed the validation of the TLS library, as
+    ///                    far as the chain to it is trusted
+    /// * `depth` - the depth of the certificate in the chain
+    /// * `cert` - the certificate
+    pub fn verify(&self, preverify_ok: bool, depth: u32, cert: &X509Ref) -> bool {
+        let matches = |usage: CertUsage| {
+            self.tlsa
+                .iter()
+                .filter(|tlsa| tlsa.get_cert_usage() == usage)
+                .any(|tlsa| tlsa_matches(tlsa, cert))
+        };
+
+        if depth > 0 {
+            if matches(CertUsage::TrustAnchor) {
+                self.trust_anchor_matched.store(true, Ordering::SeqCst);
+                return true;
+            }
+
+            // certificates above the trust anchor are not used, those below must chain to it
+            return !self.trust_anchor_matched.load(Ordering::SeqCst) || preverify_ok;
+        }
+
+        matches(CertUsage::DomainIssued) ||
+        (self.trust_anchor_matched.load(Ordering::SeqCst) && preverify_ok)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use openssl::asn1::Asn1Time;
+    use openssl::hash::MessageDigest;
+    use openssl::nid;
+    use openssl::pkey::PKey;
+    use openssl::rsa::Rsa;
+    use openssl::x509::{X509, X509NameBuilder};
+
+    use rr::domain::Name;
+    use rr::dnssec::DigestType;
+    use rr::rdata::TLSA;
+    use rr::rdata::tlsa::{CertUsage, Matching, Selector};
+    use super::*;
+
+    fn cert(subject_name: &str) -> X509 {
+        let pkey = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+
+        let mut x509_name = X509NameBuilder::new().unwrap();
+        x509_name.append_entry_by_nid(nid::COMMONNAME, subject_name).unwrap();
+        let x509_name = x509_name.build();
+
+        let mut x509_build = X509::builder().unwrap();
+        x509_build.set_not_before(&Asn1Time::days_from_now(0).unwrap()).unwrap();
+        x509_build.set_not_after(&Asn1Time::days_from_now(256).unwrap()).unwrap();
+        x509_build.set_issuer_name(&x509_name).unwrap();
+        x509_build.set_subject_name(&x509_name).unwrap();
+        x509_build.set_pubkey(&pkey).unwrap();
+        x509_build.sign(&pkey, MessageDigest::sha256()).unwrap();
+        x509_build.build()
+    }
+
+    #[test]
+    fn test_tlsa_name() {
+        assert_eq!(tlsa_name(&Name::parse("dns.example.com.", None).unwrap(), 853),
+                   Name::parse("_853._tcp.dns.example.com.", None).unwrap());
+    }
+
+    #[test]
+    fn test_tlsa_matches() {
+        let cert = cert("dns.example.com");
+        let spki = cert.public_key().unwrap().public_key_to_der().unwrap();
+        let der = cert.to_der().unwrap();
+
+        let tlsa = |selector, matching, data| {
+            TLSA::new(CertUsage::DomainIssued, selector, matching, data)
+        };
+
+        assert!(tlsa_matches(&tlsa(Selector::Full, Matching::Raw, der.clone()), &cert));
+        assert!(tlsa_matches(&tlsa(Selector::Spki,
+                                   Matching::Sha256,
+                                   DigestType::SHA256.hash(&spki).unwrap()),
+                             &cert));
+        assert!(tlsa_matches(&tlsa(Selector::Full,
+                                   Matching::Sha512,
+                                   DigestType::SHA512.hash(&der).unwrap()),
+                             &cert));
+        assert!(!tlsa_matches(&tlsa(Selector::Full,
+                                    Matching::Sha256,
+                                    DigestType::SHA256.hash(&spki).unwrap()),
+                              &cert));
+        assert!(!tlsa_matches(&tlsa(Selector::Spki, Matching::Private, spki.clone()), &cert));
+    }
+
+    #[test]
+    fn test_dane_verifier() {
+        let anchor = cert("ca.example.com");
+        let server = cert("dns.example.com");
+        let sha256 = |cert: &X509| {
+            DigestType::SHA256.hash(&cert.public_key().unwrap().public_key_to_der().unwrap())
+                .unwrap()
+        };
+
+        // PKIX usages are not usable
+        assert!(DaneVerifier::new(vec![TLSA::new(CertUsage::Service,
+                                                 Selector::Spki,
+                                                 Matching::Sha256,
+                                                 sha256(&server))])
+            .is_none());
+
+        // DANE-EE, regardless of the chain
+        let verifier = DaneVerifier::new(vec![TLSA::new(CertUsage::DomainIssued,
+                                                        Selector::Spki,
+                                                        Matching::Sha256,
+                                                        sha256(&server))])
+            .unwrap();
+        assert!(verifier.verify(false, 1, &anchor));
+        assert!(verifier.verify(false, 0, &server));
+        assert!(!verifier.verify(true, 0, &anchor));
+
+        // DANE-TA, the chain below the anchor must be valid
+        let verifier = DaneVerifier::new(vec![TLSA::new(CertUsage::TrustAnchor,
+                                                        Selector::Spki,
+                                                        Matching::Sha256,
+                                                        sha256(&anchor))])
+            .unwrap();
+        assert!(!verifier.verify(true, 0, &server));
+        assert!(verifier.verify(false, 1, &anchor));
+        assert!(!verifier.verify(false, 0, &server));
+        assert!(verifier.verify(true, 0, &server));
+    }
+}
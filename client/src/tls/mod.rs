@@ -16,10 +16,14 @@
 
 //! TCP protocol related components for DNS.
 
+#[cfg(target_os = "linux")]
+mod dane;
 mod tls_client_connection;
 mod tls_client_stream;
 mod tls_stream;
 
+#[cfg(target_os = "linux")]
+pub use self::dane::{lookup_tlsa, tlsa_matches, tlsa_name};
 pub use self::tls_client_connection::{TlsClientConnection, TlsClientConnectionBuilder};
 pub use self::tls_client_stream::{TlsClientStream, TlsClientStreamBuilder};
 pub use self::tls_stream::{TlsStream, TlsStreamBuilder};
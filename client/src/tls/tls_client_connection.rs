@@ -14,7 +14,7 @@
 
 //! TCP based DNS client
 
-use std::net::SocketAddr;
+use std::net::{IpAddr, SocketAddr};
 use std::io;
 
 use futures::Future;
@@ -26,7 +26,8 @@ use security_framework::certificate::SecCertificate;
 use tokio_core::reactor::Core;
 
 use ::error::*;
-use client::{ClientConnection, ClientStreamHandle};
+use client::{ClientConnection, ClientStreamHandle, TimeoutConfig};
+use rr::rdata::TLSA;
 use tls::{TlsClientStream, TlsClientStreamBuilder};
 
 /// TCP based DNS client
@@ -34,23 +35,28 @@ pub struct TlsClientConnection {
     io_loop: Core,
     tls_client_stream: Box<Future<Item = TlsClientStream, Error = io::Error>>,
     client_stream_handle: Box<ClientStreamHandle>,
+    timeouts: TimeoutConfig,
 }
 
 impl TlsClientConnection {
     pub fn builder() -> TlsClientConnectionBuilder {
-        TlsClientConnectionBuilder(TlsClientStream::builder())
+        TlsClientConnectionBuilder(TlsClientStream::builder(), TimeoutConfig::default())
     }
 }
 
 impl ClientConnection for TlsClientConnection {
     type MessageStream = TlsClientStream;
 
+    fn get_timeouts(&self) -> TimeoutConfig {
+        self.timeouts
+    }
+
   fn unwrap(self) -> (Core, Box<Future<Item=Self::MessageStream, Error=io::Error>>, Box<ClientStreamHandle>) {
         (self.io_loop, self.tls_client_stream, self.client_stream_handle)
     }
 }
 
-pub struct TlsClientConnectionBuilder(TlsClientStreamBuilder);
+pub struct TlsClientConnectionBuilder(TlsClientStreamBuilder, TimeoutConfig);
 
 impl TlsClientConnectionBuilder {
     #[cfg(target_os = "macos")]
@@ -68,6 +74,29 @@ impl TlsClientConnectionBuilder {
         self.0.identity(pkcs12);
     }
 
+    /// Authenticates the name server by the TLSA records of its name, see
+    ///  `TlsStreamBuilder::dane()`
+    pub fn dane(&mut self, tlsa: Vec<TLSA>) {
+        self.0.dane(tlsa);
+    }
+
+    /// Requires the certificate of the name server to also be issued for the IP address, see
+    ///  `TlsStreamBuilder::verify_ip_address()`
+    pub fn verify_ip_address(&mut self, ip_address: IpAddr) {
+        self.0.verify_ip_address(ip_address);
+    }
+
+    /// The local address to connect from, see `TlsStreamBuilder::bind_addr()`
+    pub fn bind_addr(&mut self, bind_addr: SocketAddr) {
+        self.0.bind_addr(bind_addr);
+    }
+
+    /// Connect, read, write and query timeouts, the defaults are used if this is not called
+    pub fn timeouts(&mut self, timeouts: TimeoutConfig) {
+        self.0.timeouts(timeouts);
+        self.1 = timeouts;
+    }
+
     /// Creates a new client connection.
     ///
     /// *Note* this has side affects of establishing the connection to the specified DNS server and
@@ -87,6 +116,7 @@ impl TlsClientConnectionBuilder {
             io_loop: io_loop,
             tls_client_stream: tls_client_stream,
             client_stream_handle: handle,
+            timeouts: self.1,
         })
     }
 }
@@ -5,7 +5,7 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
-use std::net::SocketAddr;
+use std::net::{IpAddr, SocketAddr};
 use std::io;
 
 use futures::Future;
@@ -21,7 +21,8 @@ use tokio_tls::TlsStream as TokioTlsStream;
 use BufClientStreamHandle;
 use tcp::TcpClientStream;
 use tls::{TlsStream, TlsStreamBuilder};
-use client::ClientStreamHandle;
+use client::{ClientStreamHandle, TimeoutConfig};
+use rr::rdata::TLSA;
 
 pub type TlsClientStream = TcpClientStream<TokioTlsStream<TokioTcpStream>>;
 
@@ -49,6 +50,28 @@ impl TlsClientStreamBuilder {
         self.0.identity(pkcs12);
     }
 
+    /// Authenticates the name server by the TLSA records of its name, see
+    ///  `TlsStreamBuilder::dane()`
+    pub fn dane(&mut self, tlsa: Vec<TLSA>) {
+        self.0.dane(tlsa);
+    }
+
+    /// Requires the certificate of the name server to also be issued for the IP address, see
+    ///  `TlsStreamBuilder::verify_ip_address()`
+    pub fn verify_ip_address(&mut self, ip_address: IpAddr) {
+        self.0.verify_ip_address(ip_address);
+    }
+
+    /// The local address to connect from, see `TlsStreamBuilder::bind_addr()`
+    pub fn bind_addr(&mut self, bind_addr: SocketAddr) {
+        self.0.bind_addr(bind_addr);
+    }
+
+    /// The read and write timeouts for messages on the stream
+    pub fn timeouts(&mut self, timeouts: TimeoutConfig) {
+        self.0.timeouts(timeouts);
+    }
+
     pub fn build
         (self,
          name_server: SocketAddr,
@@ -5,7 +5,7 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
-use std::net::SocketAddr;
+use std::net::{IpAddr, SocketAddr};
 use std::io;
 
 use futures::{future, Future, IntoFuture};
@@ -19,7 +19,9 @@ use security_framework::certificate::SecCertificate;
 #[cfg(target_os = "linux")]
 use native_tls::backend::openssl::TlsConnectorBuilderExt;
 #[cfg(target_os = "linux")]
-use openssl::x509::X509;
+use openssl::x509::{X509, X509Ref};
+#[cfg(target_os = "linux")]
+use openssl::ssl::SSL_VERIFY_PEER;
 #[cfg(target_os = "linux")]
 use openssl::x509::store::X509StoreBuilder;
 use native_tls::Protocol::Tlsv12;
@@ -28,7 +30,12 @@ use tokio_core::reactor::Handle;
 use tokio_tls::{TlsConnectorExt, TlsStream as TokioTlsStream};
 
 use BufStreamHandle;
+use client::TimeoutConfig;
+use rr::rdata::TLSA;
 use tcp::TcpStream;
+use tcp::tcp_stream;
+#[cfg(target_os = "linux")]
+use tls::dane::DaneVerifier;
 
 pub type TlsStream = TcpStream<TokioTlsStream<TokioTcpStream>>;
 
@@ -38,11 +45,19 @@ impl TlsStream {
         TlsStreamBuilder {
             ca_chain: vec![],
             identity: None,
+            tlsa: vec![],
+            ip_address: None,
+            timeouts: TimeoutConfig::default(),
+            bind_addr: None,
         }
     }
 
     #[cfg(target_os = "linux")]
-    fn new(certs: Vec<X509>, pkcs12: Option<Pkcs12>) -> io::Result<TlsConnector> {
+    fn new(certs: Vec<X509>,
+           pkcs12: Option<Pkcs12>,
+           tlsa: Vec<TLSA>,
+           ip_address: Option<IpAddr>)
+           -> io::Result<TlsConnector> {
         let mut tls = try!(TlsConnector::builder().map_err(|e| {
             io::Error::new(io::ErrorKind::ConnectionRefused,
                            format!("tls error: {}", e))
@@ -73,6 +88,33 @@ impl TlsStream {
                 io::Error::new(io::ErrorKind::ConnectionRefused,
                                format!("tls error: {}", e))
             }));
+
+            // DANE decides on the chain in the place of the trusted certificate authorities
+            let verifier = if tlsa.is_empty() {
+                None
+            } else {
+                Some(try!(DaneVerifier::new(tlsa).ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::ConnectionRefused,
+                                   "tls error: none of the TLSA records are usable")
+                })))
+            };
+
+            if verifier.is_some() || ip_address.is_some() {
+                openssl_ctx_builder.set_verify_callback(SSL_VERIFY_PEER,
+                                                        move |preverify_ok, x509_ctx| {
+                    x509_ctx.current_cert().map_or(false, |cert| {
+                        let depth = x509_ctx.error_depth();
+                        let verified = match verifier {
+                            Some(ref verifier) => verifier.verify(preverify_ok, depth, cert),
+                            None => preverify_ok,
+                        };
+
+                        // the IP address is only of the end entity certificate
+                        verified &&
+                        (depth > 0 || ip_address.map_or(true, |ip| has_ip_address(cert, ip)))
+                    })
+                });
+            }
         }
 
         // if there was a pkcs12 associated, we'll add it to the identity
@@ -89,7 +131,21 @@ impl TlsStream {
     }
 
     #[cfg(target_os = "macos")]
-    fn new(certs: Vec<SecCertificate>, pkcs12: Option<Pkcs12>) -> io::Result<TlsConnector> {
+    fn new(certs: Vec<SecCertificate>,
+           pkcs12: Option<Pkcs12>,
+           tlsa: Vec<TLSA>,
+           ip_address: Option<IpAddr>)
+           -> io::Result<TlsConnector> {
+        if !tlsa.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::ConnectionRefused,
+                                      "tls error: DANE is only supported with openssl"));
+        }
+        if ip_address.is_some() {
+            return Err(io::Error::new(io::ErrorKind::ConnectionRefused,
+                                      "tls error: IP address verification is only supported \
+                                       with openssl"));
+        }
+
         let mut builder = try!(TlsConnector::builder().map_err(|e| {
             io::Error::new(io::ErrorKind::ConnectionRefused,
                            format!("tls error: {}", e))
@@ -133,6 +189,10 @@ pub struct TlsStreamBuilder {
     #[cfg(target_os = "linux")]
     ca_chain: Vec<X509>,
     identity: Option<Pkcs12>,
+    tlsa: Vec<TLSA>,
+    ip_address: Option<IpAddr>,
+    timeouts: TimeoutConfig,
+    bind_addr: Option<SocketAddr>,
 }
 
 impl TlsStreamBuilder {
@@ -157,6 +217,36 @@ impl TlsStreamBuilder {
         self.identity = Some(pkcs12);
     }
 
+    /// Authenticates the name server by the TLSA records of its name, DANE, RFC 6698, rather than
+    ///  by the trusted certificate authorities
+    ///
+    /// The records should be looked up with `tls::lookup_tlsa`, which validates them. Only the
+    ///  DANE-TA and DANE-EE usages are used, the connection fails if none of the records are of
+    ///  those, or if the certificate of the name server does not match any of them. This is only
+    ///  supported with openssl.
+    pub fn dane(&mut self, tlsa: Vec<TLSA>) {
+        self.tlsa = tlsa;
+    }
+
+    /// Requires the certificate of the name server to also be issued for the IP address, in its
+    ///  subjectAltName, as for a designated resolver of DDR, RFC 9462 section 4.2
+    ///
+    /// The name of the server is verified all the same. This is only supported with openssl.
+    pub fn verify_ip_address(&mut self, ip_address: IpAddr) {
+        self.ip_address = Some(ip_address);
+    }
+
+    /// The read and write timeouts for messages on the stream, the connect timeout is applied by
+    ///  `ClientFuture::with_timeouts()`
+    pub fn timeouts(&mut self, timeouts: TimeoutConfig) {
+        self.timeouts = timeouts;
+    }
+
+    /// The local IP, and Port or 0, to connect from, e.g. on multi-homed hosts
+    pub fn bind_addr(&mut self, bind_addr: SocketAddr) {
+        self.bind_addr = Some(bind_addr);
+    }
+
     /// Creates a new TlsStream to the specified name_server
     ///
     /// [RFC 7858](https://tools.ietf.org/html/rfc7858), DNS over TLS, May 2016
@@ -192,37 +282,112 @@ impl TlsStreamBuilder {
                  loop_handle: Handle)
                  -> (Box<Future<Item = TlsStream, Error = io::Error>>, BufStreamHandle) {
         let (message_sender, outbound_messages) = unbounded();
-        let tls_connector = match TlsStream::new(self.ca_chain, self.identity) {
-            Ok(c) => c,
-            Err(e) => {
-                return (Box::new(future::err(e).into_future().map_err(|e| {
-                            io::Error::new(io::ErrorKind::ConnectionRefused,
-                                           format!("tls error: {}", e))
-                        })),
-                        message_sender)
-            }
-        };
-
-        let tcp = TokioTcpStream::connect(&name_server, &loop_handle);
+        let timeouts = self.timeouts;
 
         // This set of futures collapses the next tcp socket into a stream which can be used for
         //  sending and receiving tcp packets.
         let stream: Box<Future<Item = TlsStream, Error = io::Error>> =
-            Box::new(tcp.and_then(move |tcp_stream| {
-                    tls_connector.connect_async(&subject_name, tcp_stream)
-                        .map(move |s| {
-                            TcpStream::from_stream_with_receiver(s, name_server, outbound_messages)
-                        })
-                        .map_err(|e| {
-                            io::Error::new(io::ErrorKind::ConnectionRefused,
-                                           format!("tls error: {}", e))
-                        })
-                })
-                .map_err(|e| {
-                    io::Error::new(io::ErrorKind::ConnectionRefused,
-                                   format!("tls error: {}", e))
+            Box::new(self.connect(name_server, subject_name, loop_handle.clone())
+                .map(move |s| {
+                    let mut stream =
+                        TcpStream::from_stream_with_receiver(s, name_server, outbound_messages);
+                    stream.set_timeouts(timeouts.read, timeouts.write, loop_handle);
+                    stream
                 }));
 
         (stream, message_sender)
     }
+
+    /// Establishes the TLS connection to the specified name_server, without the DNS framing of
+    ///  `build()`, for protocols such as DNS over HTTPS which carry messages differently
+    pub fn connect(self,
+                   name_server: SocketAddr,
+                   subject_name: String,
+                   loop_handle: Handle)
+                   -> Box<Future<Item = TokioTlsStream<TokioTcpStream>, Error = io::Error>> {
+        let tls_connector = match TlsStream::new(self.ca_chain,
+                                                    self.identity,
+                                                    self.tlsa,
+                                                    self.ip_address) {
+            Ok(c) => c,
+            Err(e) => {
+                return Box::new(future::err(e).into_future().map_err(|e| {
+                    io::Error::new(io::ErrorKind::ConnectionRefused,
+                                   format!("tls error: {}", e))
+                }))
+            }
+        };
+
+        let tcp = tcp_stream::connect(name_server, self.bind_addr, &loop_handle);
+
+        Box::new(tcp.and_then(move |tcp_stream| {
+                tls_connector.connect_async(&subject_name, tcp_stream)
+                    .map_err(|e| {
+                        io::Error::new(io::ErrorKind::ConnectionRefused,
+                                       format!("tls error: {}", e))
+                    })
+            })
+            .map_err(|e| {
+                io::Error::new(io::ErrorKind::ConnectionRefused,
+                               format!("tls error: {}", e))
+            }))
+    }
+}
+
+/// Returns true if the IP address is among the subjectAltNames of the certificate
+#[cfg(target_os = "linux")]
+fn has_ip_address(cert: &X509Ref, ip_address: IpAddr) -> bool {
+    let octets = match ip_address {
+        IpAddr::V4(ip) => ip.octets().to_vec(),
+        IpAddr::V6(ip) => ip.octets().to_vec(),
+    };
+
+    cert.subject_alt_names().map_or(false, |names| {
+        names.iter().any(|name| name.ipaddress() == Some(&octets[..]))
+    })
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+    use openssl::asn1::Asn1Time;
+    use openssl::hash::MessageDigest;
+    use openssl::nid;
+    use openssl::pkey::PKey;
+    use openssl::rsa::Rsa;
+    use openssl::x509::{X509, X509NameBuilder};
+    use openssl::x509::extension::SubjectAlternativeName;
+
+    use super::has_ip_address;
+
+    #[test]
+    fn test_has_ip_address() {
+        let pkey = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+
+        let mut x509_name = X509NameBuilder::new().unwrap();
+        x509_name.append_entry_by_nid(nid::COMMONNAME, "dns.example.com").unwrap();
+        let x509_name = x509_name.build();
+
+        let mut x509_build = X509::builder().unwrap();
+        x509_build.set_not_before(&Asn1Time::days_from_now(0).unwrap()).unwrap();
+        x509_build.set_not_after(&Asn1Time::days_from_now(256).unwrap()).unwrap();
+        x509_build.set_issuer_name(&x509_name).unwrap();
+        x509_build.set_subject_name(&x509_name).unwrap();
+        x509_build.set_pubkey(&pkey).unwrap();
+        let subject_alt_names = SubjectAlternativeName::new()
+            .dns("dns.example.com")
+            .ip("192.0.2.53")
+            .ip("2001:db8::53")
+            .build(&x509_build.x509v3_context(None, None))
+            .unwrap();
+        x509_build.append_extension(subject_alt_names).unwrap();
+        x509_build.sign(&pkey, MessageDigest::sha256()).unwrap();
+        let cert = x509_build.build();
+
+        assert!(has_ip_address(&cert, IpAddr::V4(Ipv4Addr::new(192, 0, 2, 53))));
+        assert!(has_ip_address(&cert,
+                               IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 0x53))));
+        assert!(!has_ip_address(&cert, IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1))));
+    }
 }
@@ -21,5 +21,5 @@ mod udp_client_stream;
 mod udp_stream;
 
 pub use self::udp_client_connection::UdpClientConnection;
-pub use self::udp_client_stream::UdpClientStream;
-pub use self::udp_stream::UdpStream;
+pub use self::udp_client_stream::{LocalSocket, SourceStats, SourceValidation, UdpClientStream};
+pub use self::udp_stream::{PerQueryUdpStream, UdpStream};
@@ -21,14 +21,16 @@ use futures::Future;
 use tokio_core::reactor::Core;
 
 use ::error::*;
-use client::{ClientConnection, ClientStreamHandle};
-use udp::UdpClientStream;
+use client::{ClientConnection, ClientStreamHandle, TimeoutConfig};
+use udp::{LocalSocket, SourceValidation, UdpClientStream};
 
 /// UDP based DNS client
 pub struct UdpClientConnection {
+    name_server: SocketAddr,
     io_loop: Core,
     udp_client_stream: Box<Future<Item = UdpClientStream, Error = io::Error>>,
     client_stream_handle: Box<ClientStreamHandle>,
+    timeouts: TimeoutConfig,
 }
 
 impl UdpClientConnection {
@@ -41,20 +43,79 @@ impl UdpClientConnection {
     ///
     /// * `name_server` - address of the name server to use for queries
     pub fn new(name_server: SocketAddr) -> ClientResult<Self> {
+        Self::with_source_validation(name_server, SourceValidation::default())
+    }
+
+    /// Creates a new client connection with the specified timeouts
+    ///
+    /// # Arguments
+    ///
+    /// * `name_server` - address of the name server to use for queries
+    /// * `timeouts` - only the query timeout applies to UDP
+    pub fn with_timeouts(name_server: SocketAddr, timeouts: TimeoutConfig) -> ClientResult<Self> {
+        Self::with_options(name_server,
+                           SourceValidation::default(),
+                           LocalSocket::default(),
+                           timeouts)
+    }
+
+    /// Creates a new client connection, checking the source of responses as specified
+    ///
+    /// # Arguments
+    ///
+    /// * `name_server` - address of the name server to use for queries
+    /// * `source_validation` - how responses from other addresses are handled
+    pub fn with_source_validation(name_server: SocketAddr,
+                                  source_validation: SourceValidation)
+                                  -> ClientResult<Self> {
+        Self::with_options(name_server,
+                           source_validation,
+                           LocalSocket::default(),
+                           TimeoutConfig::default())
+    }
+
+    /// Creates a new client connection, checking the source of responses and sending from the
+    ///  local socket as specified
+    ///
+    /// # Arguments
+    ///
+    /// * `name_server` - address of the name server to use for queries
+    /// * `source_validation` - how responses from other addresses are handled
+    /// * `local_socket` - the local socket queries are sent from, e.g. a pinned port
+    /// * `timeouts` - only the query timeout applies to UDP
+    pub fn with_options(name_server: SocketAddr,
+                        source_validation: SourceValidation,
+                        local_socket: LocalSocket,
+                        timeouts: TimeoutConfig)
+                        -> ClientResult<Self> {
         let io_loop = try!(Core::new());
-        let (udp_client_stream, handle) = UdpClientStream::new(name_server, io_loop.handle());
+        let (udp_client_stream, handle) = UdpClientStream::with_options(name_server,
+                                                                        io_loop.handle(),
+                                                                        source_validation,
+                                                                        local_socket);
 
         Ok(UdpClientConnection {
+            name_server: name_server,
             io_loop: io_loop,
             udp_client_stream: udp_client_stream,
             client_stream_handle: handle,
+            timeouts: timeouts,
         })
     }
+
+    /// The address of the name server queries are sent to
+    pub fn get_name_server(&self) -> SocketAddr {
+        self.name_server
+    }
 }
 
 impl ClientConnection for UdpClientConnection {
     type MessageStream = UdpClientStream;
 
+    fn get_timeouts(&self) -> TimeoutConfig {
+        self.timeouts
+    }
+
   fn unwrap(self) -> (Core, Box<Future<Item=Self::MessageStream, Error=io::Error>>, Box<ClientStreamHandle>) {
         (self.io_loop, self.udp_client_stream, self.client_stream_handle)
     }
@@ -7,18 +7,79 @@
 
 use std::net::SocketAddr;
 use std::io;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
-use futures::{Async, Future, Poll, Stream};
+use futures::{finished, Async, Future, Poll, Stream};
 use tokio_core::reactor::Handle;
 
 use BufClientStreamHandle;
 use client::ClientStreamHandle;
-use udp::UdpStream;
+use udp::{PerQueryUdpStream, UdpStream};
+
+/// How the source of received datagrams is checked against the name server
+///
+/// An off-path attacker can send forged responses from any address, only accepting datagrams from
+///  the name server makes them guess the source address and port as well.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SourceValidation {
+    /// Datagrams from any address are accepted, mismatches are only logged and counted
+    Log,
+    /// Datagrams from any other address than the name server are dropped and counted
+    Drop,
+    /// The socket is connected to the name server, the kernel drops datagrams from any other
+    ///  address, so these are not counted. Messages are sent with `send`.
+    Connect,
+}
+
+impl Default for SourceValidation {
+    fn default() -> Self {
+        SourceValidation::Log
+    }
+}
+
+/// The local socket queries are sent from
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LocalSocket {
+    /// One socket on a random port for all queries
+    Random,
+    /// One socket bound to the address for all queries, e.g. for firewall rules, with port 0 a
+    ///  random port is used
+    Bound(SocketAddr),
+    /// A new socket on a random port for every query, see `PerQueryUdpStream`
+    PerQuery,
+}
+
+impl Default for LocalSocket {
+    fn default() -> Self {
+        LocalSocket::Random
+    }
+}
+
+/// The counts of the datagrams received by a `UdpClientStream`, see
+///  `UdpClientStream::get_source_stats()`
+#[derive(Debug, Default)]
+pub struct SourceStats {
+    mismatched: AtomicUsize,
+}
+
+impl SourceStats {
+    /// Datagrams received from another address than the name server, with
+    ///  `SourceValidation::Drop` these were dropped
+    pub fn get_mismatched(&self) -> usize {
+        self.mismatched.load(Ordering::Relaxed)
+    }
+}
+
+/// Received datagrams along with the address they came from
+type DatagramStream = Box<Stream<Item = (Vec<u8>, SocketAddr), Error = io::Error>>;
 
 #[must_use = "futures do nothing unless polled"]
 pub struct UdpClientStream {
     name_server: SocketAddr,
-    udp_stream: UdpStream,
+    udp_stream: DatagramStream,
+    source_validation: SourceValidation,
+    source_stats: Arc<SourceStats>,
 }
 
 impl UdpClientStream {
@@ -34,13 +95,70 @@ impl UdpClientStream {
         (name_server: SocketAddr,
          loop_handle: Handle)
          -> (Box<Future<Item = UdpClientStream, Error = io::Error>>, Box<ClientStreamHandle>) {
-        let (stream_future, sender) = UdpStream::new(name_server, loop_handle);
+        Self::with_options(name_server,
+                           loop_handle,
+                           SourceValidation::default(),
+                           LocalSocket::default())
+    }
+
+    /// Same as `new`, with the check of the source of responses, see `SourceValidation`
+    pub fn with_source_validation
+        (name_server: SocketAddr,
+         loop_handle: Handle,
+         source_validation: SourceValidation)
+         -> (Box<Future<Item = UdpClientStream, Error = io::Error>>, Box<ClientStreamHandle>) {
+        Self::with_options(name_server,
+                           loop_handle,
+                           source_validation,
+                           LocalSocket::default())
+    }
+
+    /// Same as `new`, with the check of the source of responses and the local socket to use
+    ///
+    /// # Arguments
+    ///
+    /// * `name_server` - address of the name server to use for queries
+    /// * `loop_handle` - handle to the IO loop
+    /// * `source_validation` - how responses from other addresses are handled
+    /// * `local_socket` - the local socket queries are sent from
+    pub fn with_options
+        (name_server: SocketAddr,
+         loop_handle: Handle,
+         source_validation: SourceValidation,
+         local_socket: LocalSocket)
+         -> (Box<Future<Item = UdpClientStream, Error = io::Error>>, Box<ClientStreamHandle>) {
+        let connect = source_validation == SourceValidation::Connect;
+
+        let (stream_future, sender): (Box<Future<Item = DatagramStream, Error = io::Error>>, _) =
+            match local_socket {
+                LocalSocket::Random => {
+                    let (stream_future, sender) =
+                        UdpStream::with_local_addr(name_server, loop_handle, None, connect);
+                    (Box::new(stream_future.map(|stream| Box::new(stream) as DatagramStream)),
+                     sender)
+                }
+                LocalSocket::Bound(local_addr) => {
+                    let (stream_future, sender) = UdpStream::with_local_addr(name_server,
+                                                                             loop_handle,
+                                                                             Some(local_addr),
+                                                                             connect);
+                    (Box::new(stream_future.map(|stream| Box::new(stream) as DatagramStream)),
+                     sender)
+                }
+                LocalSocket::PerQuery => {
+                    let (stream, sender) =
+                        PerQueryUdpStream::new(name_server, loop_handle, connect);
+                    (Box::new(finished(Box::new(stream) as DatagramStream)), sender)
+                }
+            };
 
         let new_future: Box<Future<Item = UdpClientStream, Error = io::Error>> =
             Box::new(stream_future.map(move |udp_stream| {
                 UdpClientStream {
                     name_server: name_server,
                     udp_stream: udp_stream,
+                    source_validation: source_validation,
+                    source_stats: Arc::new(SourceStats::default()),
                 }
             }));
 
@@ -51,6 +169,12 @@ impl UdpClientStream {
 
         (new_future, sender)
     }
+
+    /// The counts of the datagrams received, e.g. to monitor for forged responses, they remain
+    ///  available after the stream is handed to a `ClientFuture`
+    pub fn get_source_stats(&self) -> Arc<SourceStats> {
+        self.source_stats.clone()
+    }
 }
 
 impl Stream for UdpClientStream {
@@ -58,17 +182,28 @@ impl Stream for UdpClientStream {
     type Error = io::Error;
 
     fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
-        match try_ready!(self.udp_stream.poll()) {
-            Some((buffer, src_addr)) => {
-                if src_addr != self.name_server {
-                    debug!("{} does not match name_server: {}",
-                           src_addr,
-                           self.name_server)
-                }
+        loop {
+            match try_ready!(self.udp_stream.poll()) {
+                Some((buffer, src_addr)) => {
+                    if src_addr != self.name_server {
+                        self.source_stats.mismatched.fetch_add(1, Ordering::Relaxed);
+
+                        if let SourceValidation::Log = self.source_validation {
+                            debug!("{} does not match name_server: {}",
+                                   src_addr,
+                                   self.name_server)
+                        } else {
+                            warn!("dropping datagram from: {}, does not match name_server: {}",
+                                  src_addr,
+                                  self.name_server);
+                            continue;
+                        }
+                    }
 
-                Ok(Async::Ready(Some(buffer)))
+                    return Ok(Async::Ready(Some(buffer)));
+                }
+                None => return Ok(Async::Ready(None)),
             }
-            None => Ok(Async::Ready(None)),
         }
     }
 }
@@ -165,3 +300,139 @@ fn udp_client_stream_test(server_addr: IpAddr) {
     succeeded.store(true, std::sync::atomic::Ordering::Relaxed);
     server_handle.join().expect("server thread failed");
 }
+
+#[test]
+fn test_udp_client_stream_drop() {
+    udp_client_stream_validation_test(SourceValidation::Drop, 1)
+}
+
+#[test]
+fn test_udp_client_stream_connect() {
+    // the kernel drops the forged response, it never reaches the stream
+    udp_client_stream_validation_test(SourceValidation::Connect, 0)
+}
+
+#[cfg(test)]
+fn udp_client_stream_validation_test(source_validation: SourceValidation, mismatched: usize) {
+    use tokio_core::reactor::Core;
+
+    use std;
+    let localhost = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
+    let server = std::net::UdpSocket::bind(SocketAddr::new(localhost, 0)).unwrap();
+    server.set_read_timeout(Some(std::time::Duration::from_secs(5))).unwrap();
+    let server_addr = server.local_addr().unwrap();
+    let attacker = std::net::UdpSocket::bind(SocketAddr::new(localhost, 0)).unwrap();
+
+    let server_handle = std::thread::Builder::new()
+        .name("test_udp_client_stream_validation:server".to_string())
+        .spawn(move || {
+            let mut buffer = [0_u8; 512];
+            let (_, addr) = server.recv_from(&mut buffer).expect("receive failed");
+
+            // the forged response arrives first
+            attacker.send_to(b"FORGED", addr).expect("send failed");
+            std::thread::sleep(std::time::Duration::from_millis(100));
+            server.send_to(b"DEADBEEF", addr).expect("send failed");
+        })
+        .unwrap();
+
+    let mut io_loop = Core::new().unwrap();
+    let (stream, mut sender) =
+        UdpClientStream::with_source_validation(server_addr, io_loop.handle(), source_validation);
+    let stream: UdpClientStream = io_loop.run(stream).ok().unwrap();
+    let source_stats = stream.get_source_stats();
+
+    sender.send(b"DEADBEEF".to_vec()).unwrap();
+    let (buffer, _) = io_loop.run(stream.into_future()).ok().unwrap();
+    assert_eq!(&buffer.expect("no buffer received"), b"DEADBEEF");
+    assert_eq!(source_stats.get_mismatched(), mismatched);
+
+    server_handle.join().expect("server thread failed");
+}
+
+#[test]
+fn test_udp_client_stream_bound() {
+    use tokio_core::reactor::Core;
+
+    use std;
+    let localhost = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
+    let server = std::net::UdpSocket::bind(SocketAddr::new(localhost, 0)).unwrap();
+    server.set_read_timeout(Some(std::time::Duration::from_secs(5))).unwrap();
+    let server_addr = server.local_addr().unwrap();
+
+    // find a free port to pin
+    let local_addr = std::net::UdpSocket::bind(SocketAddr::new(localhost, 0))
+        .unwrap()
+        .local_addr()
+        .unwrap();
+
+    let server_handle = std::thread::Builder::new()
+        .name("test_udp_client_stream_bound:server".to_string())
+        .spawn(move || {
+            let mut buffer = [0_u8; 512];
+            let (len, addr) = server.recv_from(&mut buffer).expect("receive failed");
+            assert_eq!(addr, local_addr);
+            server.send_to(&buffer[0..len], addr).expect("send failed");
+        })
+        .unwrap();
+
+    let mut io_loop = Core::new().unwrap();
+    let (stream, mut sender) = UdpClientStream::with_options(server_addr,
+                                                             io_loop.handle(),
+                                                             SourceValidation::Drop,
+                                                             LocalSocket::Bound(local_addr));
+    let stream: UdpClientStream = io_loop.run(stream).ok().unwrap();
+    let source_stats = stream.get_source_stats();
+
+    sender.send(b"DEADBEEF".to_vec()).unwrap();
+    let (buffer, _) = io_loop.run(stream.into_future()).ok().unwrap();
+    assert_eq!(&buffer.expect("no buffer received"), b"DEADBEEF");
+    assert_eq!(source_stats.get_mismatched(), mismatched);
+
+    server_handle.join().expect("server thread failed");
+}
+
+#[test]
+fn test_udp_client_stream_per_query() {
+    use tokio_core::reactor::Core;
+
+    use std;
+    let localhost = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
+    let server = std::net::UdpSocket::bind(SocketAddr::new(localhost, 0)).unwrap();
+    server.set_read_timeout(Some(std::time::Duration::from_secs(5))).unwrap();
+    let server_addr = server.local_addr().unwrap();
+
+    let server_handle = std::thread::Builder::new()
+        .name("test_udp_client_stream_per_query:server".to_string())
+        .spawn(move || {
+            let mut buffer = [0_u8; 512];
+            let (first_len, first) = server.recv_from(&mut buffer).expect("receive failed");
+            let (second_len, second) = server.recv_from(&mut buffer[256..])
+                .expect("receive failed");
+
+            // every query is from a new port
+            assert!(first != second);
+            server.send_to(&buffer[0..first_len], first).expect("send failed");
+            server.send_to(&buffer[256..256 + second_len], second).expect("send failed");
+        })
+        .unwrap();
+
+    let mut io_loop = Core::new().unwrap();
+    let (stream, mut sender) = UdpClientStream::with_options(server_addr,
+                                                             io_loop.handle(),
+                                                             SourceValidation::Drop,
+                                                             LocalSocket::PerQuery);
+    let stream: UdpClientStream = io_loop.run(stream).ok().unwrap();
+
+    sender.send(b"DEADBEEF".to_vec()).unwrap();
+    sender.send(b"FEEDBEEF".to_vec()).unwrap();
+    let (first, stream) = io_loop.run(stream.into_future()).ok().unwrap();
+    let (second, _) = io_loop.run(stream.into_future()).ok().unwrap();
+
+    let mut received = vec![first.expect("no buffer received"),
+                            second.expect("no buffer received")];
+    received.sort();
+    assert_eq!(received, vec![b"DEADBEEF".to_vec(), b"FEEDBEEF".to_vec()]);
+
+    server_handle.join().expect("server thread failed");
+}
@@ -6,6 +6,7 @@
 // copied, modified, or distributed except according to those terms.
 
 use std;
+use std::collections::VecDeque;
 use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
 use std::io;
 
@@ -29,6 +30,8 @@ lazy_static!{
 pub struct UdpStream {
     socket: tokio_core::net::UdpSocket,
     outbound_messages: Peekable<Fuse<UnboundedReceiver<(Vec<u8>, SocketAddr)>>>,
+    /// the socket is connected, messages are sent with `send`, which all platforms allow there
+    connected: bool,
 }
 
 impl UdpStream {
@@ -49,11 +52,49 @@ impl UdpStream {
     pub fn new(name_server: SocketAddr,
                loop_handle: Handle)
                -> (Box<Future<Item = UdpStream, Error = io::Error>>, BufStreamHandle) {
+        Self::with_connect(name_server, loop_handle, false)
+    }
+
+    /// Same as `new`, but the socket may be connected to the name server, the kernel then drops
+    ///  any datagram which is not from the name server.
+    ///
+    /// # Arguments
+    ///
+    /// * `name_server`: socket address for the remote server
+    /// * `loop_handle` - handle to the IO loop
+    /// * `connect` - connect the socket to the `name_server`, messages must only be sent to it
+    pub fn with_connect(name_server: SocketAddr,
+                        loop_handle: Handle,
+                        connect: bool)
+                        -> (Box<Future<Item = UdpStream, Error = io::Error>>, BufStreamHandle) {
+        Self::with_local_addr(name_server, loop_handle, None, connect)
+    }
+
+    /// Same as `with_connect`, but the local address of the socket may be pinned, e.g. for
+    ///  firewall rules. Pinning the port makes responses easier to forge, only do so when needed.
+    ///
+    /// # Arguments
+    ///
+    /// * `name_server`: socket address for the remote server
+    /// * `loop_handle` - handle to the IO loop
+    /// * `local_addr` - address to bind to, with port 0 a random port is used, by default the
+    ///                  unspecified address and a random port
+    /// * `connect` - connect the socket to the `name_server`, messages must only be sent to it
+    pub fn with_local_addr(name_server: SocketAddr,
+                           loop_handle: Handle,
+                           local_addr: Option<SocketAddr>,
+                           connect: bool)
+                           -> (Box<Future<Item = UdpStream, Error = io::Error>>, BufStreamHandle) {
         let (message_sender, outbound_messages) = unbounded();
 
-        // TODO: allow the bind address to be specified...
         // constructs a future for getting the next randomly bound port to a UdpSocket
-        let next_socket = Self::next_bound_local_address(&name_server);
+        let mut next_socket = Self::next_bound_local_address(&name_server);
+        if let Some(local_addr) = local_addr {
+            next_socket.bind_address = local_addr;
+        }
+        if connect {
+            next_socket.connect_to = Some(name_server);
+        }
 
         // This set of futures collapses the next udp socket into a stream which can be used for
         //  sending and receiving udp packets.
@@ -66,6 +107,7 @@ impl UdpStream {
                     UdpStream {
                         socket: socket,
                         outbound_messages: outbound_messages.fuse().peekable(),
+                        connected: connect,
                     }
                 }));
 
@@ -96,6 +138,7 @@ impl UdpStream {
         let stream = UdpStream {
             socket: socket,
             outbound_messages: outbound_messages.fuse().peekable(),
+            connected: false,
         };
 
         (stream, message_sender)
@@ -108,7 +151,10 @@ impl UdpStream {
             SocketAddr::V6(..) => IpAddr::V6(*IPV6_ZERO),
         };
 
-        NextRandomUdpSocket { bind_address: zero_addr }
+        NextRandomUdpSocket {
+            bind_address: SocketAddr::new(zero_addr, 0),
+            connect_to: None,
+        }
     }
 }
 
@@ -129,7 +175,11 @@ impl Stream for UdpStream {
             },
             Async::Ready(_) => {
               // will return if the socket will block
-              try_nb!(self.socket.send_to(buffer, &addr));
+              if self.connected {
+                try_nb!(self.socket.send(buffer));
+              } else {
+                try_nb!(self.socket.send_to(buffer, &addr));
+              }
             },
           }
         },
@@ -159,9 +209,131 @@ impl Stream for UdpStream {
     }
 }
 
+/// The most sockets kept open waiting for responses by a `PerQueryUdpStream`
+const MAX_PER_QUERY_SOCKETS: usize = 256;
+
+/// A client stream which sends every message from a new socket on a random port
+///
+/// This makes each response as hard to forge as possible, at the cost of a socket per query. A
+///  socket is closed once a response from the address it sent to is received, or once there
+///  are more than 256 waiting.
+#[must_use = "futures do nothing unless polled"]
+pub struct PerQueryUdpStream {
+    next_socket: NextRandomUdpSocket,
+    loop_handle: Handle,
+    outbound_messages: Peekable<Fuse<UnboundedReceiver<(Vec<u8>, SocketAddr)>>>,
+    /// sockets waiting for a response, with the address the message was sent to
+    sockets: VecDeque<(tokio_core::net::UdpSocket, SocketAddr)>,
+}
+
+impl PerQueryUdpStream {
+    /// Creates a new stream, sockets are only bound as messages are sent
+    ///
+    /// # Arguments
+    ///
+    /// * `name_server`: socket address for the remote server (used to determine IPv4 or IPv6)
+    /// * `loop_handle` - handle to the IO loop
+    /// * `connect` - connect the sockets to the `name_server`, messages must only be sent to it
+    pub fn new(name_server: SocketAddr,
+               loop_handle: Handle,
+               connect: bool)
+               -> (PerQueryUdpStream, BufStreamHandle) {
+        let (message_sender, outbound_messages) = unbounded();
+
+        let mut next_socket = UdpStream::next_bound_local_address(&name_server);
+        if connect {
+            next_socket.connect_to = Some(name_server);
+        }
+
+        let stream = PerQueryUdpStream {
+            next_socket: next_socket,
+            loop_handle: loop_handle,
+            outbound_messages: outbound_messages.fuse().peekable(),
+            sockets: VecDeque::new(),
+        };
+
+        (stream, message_sender)
+    }
+}
+
+impl Stream for PerQueryUdpStream {
+    type Item = (Vec<u8>, SocketAddr);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        loop {
+            // first try to send, each message from a new socket
+            match try!(self.outbound_messages
+                .peek()
+                .map_err(|()| io::Error::new(io::ErrorKind::Other, "unknown"))) {
+                Async::Ready(Some(&(ref buffer, addr))) => {
+                    let socket = try_ready!(self.next_socket.poll());
+                    let socket = try!(tokio_core::net::UdpSocket::from_socket(socket,
+                                                                              &self.loop_handle));
+
+                    // a new socket has an empty send buffer, so this will not block
+                    if self.next_socket.connect_to.is_some() {
+                        try!(socket.send(buffer));
+                    } else {
+                        try!(socket.send_to(buffer, &addr));
+                    }
+
+                    self.sockets.push_back((socket, addr));
+                    if self.sockets.len() > MAX_PER_QUERY_SOCKETS {
+                        debug!("too many sockets waiting for responses, closing the oldest");
+                        self.sockets.pop_front();
+                    }
+                }
+                _ => (),
+            }
+
+            // now pop the request and check if we should break or continue.
+            match try!(self.outbound_messages
+                .poll()
+                .map_err(|()| io::Error::new(io::ErrorKind::Other, "unknown"))) {
+                Async::Ready(Some(_)) => (),
+                Async::NotReady | Async::Ready(None) => break,
+            }
+        }
+
+        // TODO: this should match edns settings
+        let mut buf = [0u8; 2048];
+
+        for i in 0..self.sockets.len() {
+            let received = match self.sockets[i].0.recv_from(&mut buf) {
+                Ok(received) => received,
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => continue,
+                Err(e) => return Err(e),
+            };
+
+            let (len, src) = received;
+            if src == self.sockets[i].1 {
+                // the response to the one message sent from this socket
+                self.sockets.remove(i);
+            }
+
+            return Ok(Async::Ready(Some((buf.iter().take(len).cloned().collect(), src))));
+        }
+
+        Ok(Async::NotReady)
+    }
+}
+
 #[must_use = "futures do nothing unless polled"]
 struct NextRandomUdpSocket {
-    bind_address: IpAddr,
+    /// with port 0 a random port is chosen
+    bind_address: SocketAddr,
+    connect_to: Option<SocketAddr>,
+}
+
+impl NextRandomUdpSocket {
+    fn bind(&self, addr: &SocketAddr) -> io::Result<std::net::UdpSocket> {
+        let socket = try!(std::net::UdpSocket::bind(addr));
+        if let Some(ref connect_to) = self.connect_to {
+            try!(socket.connect(connect_to));
+        }
+        Ok(socket)
+    }
 }
 
 impl Future for NextRandomUdpSocket {
@@ -170,15 +342,21 @@ impl Future for NextRandomUdpSocket {
 
     /// polls until there is an available next random UDP port.
     ///
-    /// if there is no port available after 10 attempts, returns NotReady
+    /// if there is no port available after 10 attempts, returns NotReady. A pinned port is bound
+    ///  once, failing is an error.
     fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        if self.bind_address.port() != 0 {
+            let bind_address = self.bind_address;
+            return self.bind(&bind_address).map(Async::Ready);
+        }
+
         let mut rand = rand::thread_rng();
 
         for attempt in 0..10 {
-            let zero_addr = SocketAddr::new(self.bind_address,
+            let zero_addr = SocketAddr::new(self.bind_address.ip(),
                                             rand.gen_range(1025_u16, u16::max_value()));
 
-            match std::net::UdpSocket::bind(&zero_addr) {
+            match self.bind(&zero_addr) {
                 Ok(socket) => return Ok(Async::Ready(socket)),
                 Err(err) => debug!("unable to bind port, attempt: {}: {}", attempt, err),
             }
@@ -42,6 +42,8 @@ use openssl::x509::store::X509StoreBuilder;
 use security_framework::certificate::SecCertificate;
 use tokio_core::reactor::Core;
 
+use trust_dns::rr::rdata::TLSA;
+use trust_dns::rr::rdata::tlsa::{CertUsage, Matching, Selector};
 use trust_dns::tls::TlsStream;
 
 // this fails on linux for some reason. It appears that a buffer somewhere is dirty
@@ -50,7 +52,13 @@ use trust_dns::tls::TlsStream;
 // #[cfg(not(target_os = "linux"))]
 #[test]
 fn test_tls_client_stream_ipv4() {
-    tls_client_stream_test(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), false)
+    tls_client_stream_test(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), false, false)
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn test_tls_client_stream_ipv4_dane() {
+    tls_client_stream_test(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), false, true)
 }
 
 // FIXME: mtls is disabled at the moment, it causes a hang on Linux, and is currently not supported on macOS
@@ -58,13 +66,13 @@ fn test_tls_client_stream_ipv4() {
 #[test]
 #[cfg(not(target_os = "macos"))] // ignored until Travis-CI fixes IPv6
 fn test_tls_client_stream_ipv4_mtls() {
-    tls_client_stream_test(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), true)
+    tls_client_stream_test(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), true, false)
 }
 
 #[test]
 #[cfg(not(target_os = "linux"))] // ignored until Travis-CI fixes IPv6
 fn test_tls_client_stream_ipv6() {
-    tls_client_stream_test(IpAddr::V6(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 1)), false)
+    tls_client_stream_test(IpAddr::V6(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 1)), false, false)
 }
 
 const TEST_BYTES: &'static [u8; 8] = b"DEADBEEF";
@@ -148,7 +156,7 @@ fn cert(subject_name: &str, ca_pkey: &PKey, ca_name: &X509Name, _: &X509) -> (PK
 
 
 #[allow(unused_mut)]
-fn tls_client_stream_test(server_addr: IpAddr, mtls: bool) {
+fn tls_client_stream_test(server_addr: IpAddr, mtls: bool, dane: bool) {
     let succeeded = Arc::new(std::sync::atomic::AtomicBool::new(false));
     let succeeded_clone = succeeded.clone();
     thread::Builder::new()
@@ -171,7 +179,7 @@ fn tls_client_stream_test(server_addr: IpAddr, mtls: bool) {
 
     // Generate X509 certificate
     let subject_name = "ns.example.com";
-    let (_ /*server_pkey*/, _ /*server_cert*/, pkcs12) =
+    let (_ /*server_pkey*/, server_cert, pkcs12) =
         cert(subject_name, &root_pkey, &root_name, &root_cert);
 
     let server_pkcs12_der = pkcs12.to_der().unwrap();
@@ -264,7 +272,17 @@ fn tls_client_stream_test(server_addr: IpAddr, mtls: bool) {
 
     // barrier.wait();
     let mut builder = TlsStream::builder();
-    builder.add_ca(trust_chain);
+    if dane {
+        // DANE-EE, the server is trusted by the hash of its key, without the root
+        let spki = server_cert.public_key().unwrap().public_key_to_der().unwrap();
+        let sha256 = openssl::hash::hash(MessageDigest::sha256(), &spki).unwrap();
+        builder.dane(vec![TLSA::new(CertUsage::DomainIssued,
+                                    Selector::Spki,
+                                    Matching::Sha256,
+                                    sha256)]);
+    } else {
+        builder.add_ca(trust_chain);
+    }
 
     if mtls {
         // signed by the same root cert
@@ -8,5 +8,6 @@ for i in client server; do
   echo "executing cargo on $i"
   cargo build
   cargo test
+  if [ $i == server ]; then cargo test --features parallel-signing; fi
   popd
 done
@@ -0,0 +1,20 @@
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate trust_dns_server;
+
+use trust_dns_server::server::H2Connection;
+
+const PREFACE: &'static [u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+
+// the frames of a client after its preface, answering each request with its own message
+fuzz_target!(|data: &[u8]| {
+    let mut out = Vec::new();
+    let mut connection = H2Connection::new("/dns-query".to_string(), &mut out);
+    connection.receive(PREFACE, &mut out);
+    connection.receive(data, &mut out);
+
+    while let Some(message) = connection.next_request() {
+        connection.respond(&message, &mut out);
+    }
+});
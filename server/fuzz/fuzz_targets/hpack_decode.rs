@@ -0,0 +1,19 @@
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate trust_dns_server;
+
+use trust_dns_server::server::{HpackDecoder, HEADER_TABLE_SIZE};
+
+// the input is split into header blocks at each 0xFF, decoded in turn by one decoder until an
+//  error, after which the dynamic table is no longer used
+fuzz_target!(|data: &[u8]| {
+    let mut decoder = HpackDecoder::new();
+    for block in data.split(|&byte| byte == 0xFF) {
+        if decoder.decode(block).is_err() {
+            return;
+        }
+
+        assert!(decoder.get_size() <= HEADER_TABLE_SIZE);
+    }
+});
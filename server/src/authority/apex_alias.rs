@@ -0,0 +1,71 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Flattening of an alias at the apex of a zone, where a CNAME is not allowed, the addresses of
+//!  the target are served in its place
+
+use rand;
+
+use trust_dns::op::{Message, MessageType, OpCode, Query};
+use trust_dns::rr::{DNSClass, Name, RecordType};
+
+/// The target of an apex alias to resolve, see `Authority::start_alias_refresh`
+#[derive(Clone, Debug)]
+pub struct AliasRequest {
+    origin: Name,
+    class: DNSClass,
+    target: Name,
+}
+
+impl AliasRequest {
+    /// Creates a new request
+    ///
+    /// # Arguments
+    ///
+    /// * `origin` - the zone at whose apex the addresses are served
+    /// * `class` - the class of the zone
+    /// * `target` - the name whose A and AAAA records are resolved
+    pub fn new(origin: Name, class: DNSClass, target: Name) -> Self {
+        AliasRequest {
+            origin: origin,
+            class: class,
+            target: target,
+        }
+    }
+
+    pub fn get_origin(&self) -> &Name {
+        &self.origin
+    }
+
+    pub fn get_class(&self) -> DNSClass {
+        self.class
+    }
+
+    pub fn get_target(&self) -> &Name {
+        &self.target
+    }
+
+    /// The queries for the A and AAAA records of the target, recursion desired, as a CNAME at
+    ///  the target is followed to the addresses
+    pub fn get_queries(&self) -> Vec<Message> {
+        [RecordType::A, RecordType::AAAA]
+            .iter()
+            .map(|rr_type| {
+                let mut query = Query::new();
+                query.name(self.target.clone()).query_class(self.class).query_type(*rr_type);
+
+                let mut message = Message::new();
+                message.id(rand::random())
+                    .message_type(MessageType::Query)
+                    .op_code(OpCode::Query)
+                    .recursion_desired(true);
+                message.add_query(query);
+                message
+            })
+            .collect()
+    }
+}
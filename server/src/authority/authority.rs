@@ -13,38 +13,112 @@
  * See the License for the specific language governing permissions and
  * limitations under the License.
  */
-use std::collections::BTreeMap;
+use std::cmp;
+use std::collections::{btree_map, BTreeMap, BTreeSet, VecDeque};
+use std::net::{IpAddr, SocketAddr};
+use std::rc::Rc;
+use std::sync::{Arc, Weak};
+
+#[cfg(feature = "parallel-signing")]
+use crossbeam;
+#[cfg(feature = "parallel-signing")]
+use num_cpus;
+use trust_dns::clock::{Clock, SystemClock};
+use trust_dns::error::*;
+use trust_dns::op::{IxfrResponse, Message, UpdateMessage, ResponseCode, Query, ZoneDelta};
+use trust_dns::rr::{DNSClass, LowerName, Name, RData, Record, RecordMetaTable, RecordType, RrKey,
+                    RecordSet, RecordSetLimits};
+use trust_dns::rr::rdata::{DNSKEY, NSEC, NSEC3, NSEC3PARAM, NULL, SIG, SOA};
+use trust_dns::rr::dnssec::{hashed_owner_name, KeyPair, Signer, SupportedAlgorithms, TSigner};
+
+use authority::{AliasRequest, ChangeListener, ChangeSubscription, InboundTransfer, Journal,
+                NotifyRequest, RecordChange, TransferAcl, TransferLimits, TransferRejection,
+                TransferRequest, UpdatePolicy, UpdateResult, ZoneFile, ZoneType};
+use error::{PersistenceErrorKind, PersistenceResult};
 
-use chrono::UTC;
+/// The number of updates to the zone which are kept for incremental zone transfers, by default
+pub const DEFAULT_MAX_DELTAS: usize = 64;
 
-use trust_dns::error::*;
-use trust_dns::op::{Message, UpdateMessage, ResponseCode, Query};
-use trust_dns::rr::{DNSClass, Name, RData, Record, RecordType, RrKey, RecordSet};
-use trust_dns::rr::rdata::{NSEC, SIG};
-use trust_dns::rr::dnssec::{KeyPair, Signer, SupportedAlgorithms};
+/// The fewest RRsets signed by each thread, fewer are not worth the thread
+#[cfg(feature = "parallel-signing")]
+const MIN_RRSETS_PER_SIGNING_THREAD: usize = 256;
 
-use authority::{Journal, UpdateResult, ZoneType};
-use error::{PersistenceErrorKind, PersistenceResult};
+/// Seconds between attempts to transfer a Slave zone which has no SOA yet, i.e. no retry interval
+const INITIAL_RETRY: u32 = 300;
 
+/// Seconds before the target of the apex alias is resolved again after a failure, and at the
+///  least after a success, whatever the TTL of its addresses
+const ALIAS_RETRY: u32 = 60;
 
 /// Authority is responsible for storing the resource records for a particular zone.
 ///
 /// Authorities default to DNSClass IN. The ZoneType specifies if this should be treated as the
 /// start of authority for the zone, is a slave, or a cached zone.
+///
+/// Cloning an Authority copies the records, the journal and the keys are shared, see
+///  `SharedAuthority` which clones it for every write which changes it.
+#[derive(Clone)]
 pub struct Authority {
     origin: Name,
     class: DNSClass,
-    journal: Option<Journal>,
+    journal: Option<Arc<Journal>>,
+    journal_compaction: Option<usize>,
     records: BTreeMap<RrKey, RecordSet>,
     zone_type: ZoneType,
     allow_update: bool,
+    update_policy: Option<UpdatePolicy>,
+    update_forward: Option<SocketAddr>,
+    primary: Option<SocketAddr>,
+    /// the local address the queries to the primary are sent from, see `set_transfer_source`
+    transfer_source: Option<IpAddr>,
+    /// the TSIG key shared with the primary, see `set_primary_key`
+    primary_key: Option<Arc<TSigner>>,
+    secondaries: Vec<SocketAddr>,
+    /// the serial of which the secondaries were last notified
+    notified_serial: Option<u32>,
+    transfer_acl: Option<TransferAcl>,
+    /// the local addresses on which the zone is answered, None for all of them
+    listen_addrs: Option<Vec<IpAddr>>,
     is_dnssec_enabled: bool,
+    /// the parameters of the NSEC3 chain, which replaces the NSEC chain, see `set_nsec3_param`
+    nsec3_param: Option<NSEC3PARAM>,
     // Private key mapped to the Record of the DNSKey
     //  TODO: these private_keys should be stored securely. Ideally, we have keys only stored per
     //   server instance, but that requires requesting updates from the parent zone, which may or
     //   may not support dynamic updates to register the new key... Trust-DNS will provide support
     //   for this, in some form, perhaps alternate root zones...
-    secure_keys: Vec<Signer>,
+    secure_keys: Vec<Arc<Signer>>,
+    /// the threads signing the zone, with the parallel-signing feature
+    signing_threads: usize,
+    clock: Arc<Clock>,
+    /// when a Slave zone was loaded or last refreshed from its primary, for the SOA expire timer
+    last_refresh: u32,
+    /// when the last refresh from the primary failed, for the SOA retry timer
+    refresh_failed_at: Option<u32>,
+    /// a NOTIFY was received, the zone is refreshed without waiting for the refresh timer
+    notified: bool,
+    /// a transfer from the primary is in progress
+    refreshing: bool,
+    /// the listeners are dropped once unsubscribed, or once nothing else references them
+    change_listeners: Vec<(Weak<ChangeListener>, ChangeSubscription)>,
+    /// the changes of the most recent updates, oldest first, for IXFR
+    deltas: VecDeque<ZoneDelta>,
+    max_deltas: usize,
+    transfer_limits: TransferLimits,
+    /// the most records and bytes of an RRset after a dynamic update
+    rrset_limits: RecordSetLimits,
+    /// the last transfer from the primary which was rejected, and why
+    quarantined: Option<(TransferRejection, Vec<Record>)>,
+    /// the name whose addresses are served at the apex, see `set_apex_alias`
+    apex_alias: Option<Name>,
+    /// when the target of the apex alias is next resolved
+    alias_refresh_at: u32,
+    /// a resolution of the target of the apex alias is in progress
+    alias_refreshing: bool,
+    /// the file the zone was loaded from, see `reload_records`
+    zone_file: Option<ZoneFile>,
+    /// the comments and tags of the records, see `set_record_meta`
+    record_meta: RecordMetaTable,
 }
 
 impl Authority {
@@ -73,11 +147,39 @@ impl Authority {
             origin: origin,
             class: DNSClass::IN,
             journal: None,
+            journal_compaction: None,
             records: records,
             zone_type: zone_type,
             allow_update: allow_update,
+            update_policy: None,
+            update_forward: None,
+            primary: None,
+            transfer_source: None,
+            primary_key: None,
+            secondaries: Vec::new(),
+            notified_serial: None,
+            transfer_acl: None,
+            listen_addrs: None,
             is_dnssec_enabled: is_dnssec_enabled,
+            nsec3_param: None,
             secure_keys: Vec::new(),
+            signing_threads: default_signing_threads(),
+            clock: Arc::new(SystemClock),
+            last_refresh: SystemClock.now(),
+            refresh_failed_at: None,
+            notified: false,
+            refreshing: false,
+            change_listeners: Vec::new(),
+            deltas: VecDeque::new(),
+            max_deltas: DEFAULT_MAX_DELTAS,
+            transfer_limits: TransferLimits::default(),
+            rrset_limits: RecordSetLimits::default(),
+            quarantined: None,
+            apex_alias: None,
+            alias_refresh_at: 0,
+            alias_refreshing: false,
+            zone_file: None,
+            record_meta: RecordMetaTable::new(),
         }
     }
 
@@ -98,7 +200,36 @@ impl Authority {
         // TODO: also generate the CDS and CDNSKEY
         let serial = self.get_serial();
         self.upsert(dnskey, serial);
-        self.secure_keys.push(signer);
+        self.secure_keys.push(Arc::new(signer));
+        Ok(())
+    }
+
+    /// Imports the public key of another signer of the zone into the DNSKEY rrset, RFC 8901
+    ///
+    /// When multiple providers sign the zone, each with their own keys, the DNSKEY rrset served by
+    ///  every provider must hold the zone signing keys of all of them, so that records signed by
+    ///  any provider validate. The key is only published and signed over, it is never used to
+    ///  sign, see `add_secure_key()` for that. Importing the same key again has no effect.
+    ///
+    /// # Arguments
+    ///
+    /// * `dnskey` - public zone signing key of the other provider
+    pub fn import_zone_signing_key(&mut self, dnskey: DNSKEY) -> DnsSecResult<()> {
+        if !dnskey.is_zone_key() {
+            return Err(DnsSecErrorKind::Message("is not a zone key").into());
+        }
+        if dnskey.is_revoke() {
+            return Err(DnsSecErrorKind::Message("revoked").into());
+        }
+
+        let zone_ttl = self.get_minimum_ttl();
+        let dnskey = Record::from_rdata(self.origin.clone(),
+                                        zone_ttl,
+                                        RecordType::DNSKEY,
+                                        RData::DNSKEY(dnskey));
+
+        let serial = self.get_serial();
+        self.upsert(dnskey, serial);
         Ok(())
     }
 
@@ -128,6 +259,12 @@ impl Authority {
             }
         }
 
+        // the deltas of the updates, for IXFR, are kept across restarts
+        self.deltas = try!(journal.select_deltas()).into_iter().collect();
+        while self.deltas.len() > self.max_deltas {
+            self.deltas.pop_front();
+        }
+
         // zone signing was off during load, now sign the zone.
         if self.is_dnssec_enabled {
             self.sign_zone().map_err(|e| e.into())
@@ -145,29 +282,67 @@ impl Authority {
             let serial = self.get_serial();
 
             info!("persisting zone to journal at SOA.serial: {}", serial);
+            try!(journal.insert_snapshot(serial, &self.journal_snapshot()));
+        }
 
-            // TODO: THIS NEEDS TO BE IN A TRANSACTION!!!
-            try!(journal.insert_record(serial, Record::new().rr_type(RecordType::AXFR)));
+        Ok(())
+    }
 
-            for rr_set in self.records.values() {
-                // TODO: should we preserve rr_sets or not?
-                for record in rr_set.iter() {
-                    try!(journal.insert_record(serial, record));
-                }
-            }
+    /// Replaces the updates in the journal with a snapshot of the zone, see `Journal::compact`,
+    ///  does nothing if there is no associated Journal.
+    pub fn compact_journal(&self) -> PersistenceResult<()> {
+        if let Some(journal) = self.journal.as_ref() {
+            let serial = self.get_serial();
 
-            // TODO: COMMIT THE TRANSACTION!!!
+            info!("compacting journal of zone: {} at SOA.serial: {}", self.origin, serial);
+            try!(journal.compact(serial, &self.journal_snapshot()));
         }
 
         Ok(())
     }
 
+    /// All the records of the zone, as persisted to the journal
+    fn journal_snapshot(&self) -> Vec<Record> {
+        // TODO: should we preserve rr_sets or not?
+        self.records.values().flat_map(|rr_set| rr_set.iter().cloned()).collect()
+    }
+
+    /// Compacts the journal once it holds more than this many records, after an update, None to
+    ///  keep every update, see `compact_journal`
+    pub fn set_journal_compaction(&mut self, max_records: Option<usize>) {
+        self.journal_compaction = max_records;
+    }
+
+    /// The number of records in the journal above which it is compacted, None if never
+    pub fn get_journal_compaction(&self) -> Option<usize> {
+        self.journal_compaction
+    }
+
+    /// Compacts the journal if it has grown beyond `get_journal_compaction`, the update is already
+    ///  persisted, so a failure is only logged
+    fn check_journal_compaction(&self) {
+        let (journal, max_records) = match (self.journal.as_ref(), self.journal_compaction) {
+            (Some(journal), Some(max_records)) => (journal, max_records),
+            _ => return,
+        };
+
+        match journal.get_len() {
+            Ok(len) if len as usize > max_records => {
+                if let Err(error) = self.compact_journal() {
+                    error!("could not compact the journal of zone: {}: {}", self.origin, error);
+                }
+            }
+            Ok(_) => (),
+            Err(error) => error!("could not count the journal of zone: {}: {}", self.origin, error),
+        }
+    }
+
     pub fn journal(&mut self, journal: Journal) {
-        self.journal = Some(journal);
+        self.journal = Some(Arc::new(journal));
     }
 
     pub fn get_journal(&self) -> Option<&Journal> {
-        self.journal.as_ref()
+        self.journal.as_ref().map(|journal| &**journal)
     }
 
     /// Enables the zone for dynamic DNS updates
@@ -175,169 +350,1046 @@ impl Authority {
         self.allow_update = allow_update;
     }
 
-    /// Retrieve the Signer, which contains the private keys, for this zone
-    pub fn get_secure_keys(&self) -> &[Signer] {
-        &self.secure_keys
+    /// Restricts the names and types each update key may change, without a policy any key
+    ///  which is valid for the zone may update any record in it.
+    pub fn set_update_policy(&mut self, update_policy: Option<UpdatePolicy>) {
+        self.update_policy = update_policy;
     }
 
-    /// Get the origin of this zone, i.e. example.com is the origin for www.example.com
-    pub fn get_origin(&self) -> &Name {
-        &self.origin
+    /// The update policy for the zone, if any
+    pub fn get_update_policy(&self) -> Option<&UpdatePolicy> {
+        self.update_policy.as_ref()
     }
 
-    /// What type is this zone
-    pub fn get_zone_type(&self) -> ZoneType {
-        self.zone_type
+    /// For Slave zones, updates will be forwarded to this primary master (RFC 2136 section 6)
+    ///  rather than being rejected.
+    pub fn set_update_forward(&mut self, primary: Option<SocketAddr>) {
+        self.update_forward = primary;
     }
 
-    /// Get all the
-    pub fn get_records(&self) -> &BTreeMap<RrKey, RecordSet> {
-        &self.records
+    /// The primary master to which updates are forwarded, only used for Slave zones
+    pub fn get_update_forward(&self) -> Option<SocketAddr> {
+        self.update_forward
     }
 
-    /// Returns the SOA of the authority.
+    /// For Slave zones, the primary master from which the zone is transferred, and refreshed as the
+    ///  timers of its SOA require, RFC 1035 section 4.3.5. Without one the zone is never refreshed.
+    pub fn set_primary(&mut self, primary: Option<SocketAddr>) {
+        self.primary = primary;
+    }
+
+    /// The primary master from which the zone is transferred, only used for Slave zones
+    pub fn get_primary(&self) -> Option<SocketAddr> {
+        self.primary
+    }
+
+    /// For Slave zones, the local address the SOA and transfer queries to the primary are sent
+    ///  from, on hosts with several, e.g. the one the primary permits transfers to. None for any.
+    pub fn set_transfer_source(&mut self, transfer_source: Option<IpAddr>) {
+        self.transfer_source = transfer_source;
+    }
+
+    /// The local address the queries to the primary are sent from, only used for Slave zones
+    pub fn get_transfer_source(&self) -> Option<IpAddr> {
+        self.transfer_source
+    }
+
+    /// For Slave zones, the TSIG key shared with the primary, RFC 8945; the SOA and transfer
+    ///  queries are signed with it, and responses which it does not verify are rejected
+    pub fn set_primary_key(&mut self, primary_key: Option<Arc<TSigner>>) {
+        self.primary_key = primary_key;
+    }
+
+    /// The TSIG key shared with the primary, only used for Slave zones
+    pub fn get_primary_key(&self) -> Option<&TSigner> {
+        self.primary_key.as_ref().map(|key| &**key)
+    }
+
+    /// The secondaries which are sent a NOTIFY, RFC 1996, whenever the serial of the zone changes,
+    ///  i.e. after a dynamic update, a transfer from the primary, or when the zone is loaded
+    pub fn set_secondaries(&mut self, secondaries: Vec<SocketAddr>) {
+        self.secondaries = secondaries;
+    }
+
+    /// The secondaries which are notified of changes to the zone
+    pub fn get_secondaries(&self) -> &[SocketAddr] {
+        &self.secondaries
+    }
+
+    /// Returns true if the serial of the zone changed since the secondaries were last notified
+    pub fn is_notify_due(&self) -> bool {
+        !self.secondaries.is_empty() && self.serial().is_some() &&
+        self.notified_serial != self.serial()
+    }
+
+    /// Returns the NOTIFY to send to the secondaries, if the serial of the zone changed since they
+    ///  were last notified, see `set_secondaries`
     ///
-    /// *Note*: This will only return the SOA, if this is fullfilling a request, a standard lookup
-    ///  should be used, see `get_soa_secure()`, which will optionally return RRSIGs.
-    pub fn get_soa(&self) -> Option<&Record> {
-        // SOA should be origin|SOA
-        self.lookup(&self.origin,
-                    RecordType::SOA,
-                    false,
-                    SupportedAlgorithms::new())
-            .first()
-            .map(|v| *v)
+    /// The serial is then taken to be notified, it is for the caller to retry the NOTIFY until
+    ///  each secondary responds.
+    pub fn start_notify(&mut self) -> Option<NotifyRequest> {
+        if !self.is_notify_due() {
+            return None;
+        }
+
+        let soa = match self.get_soa() {
+            Some(soa) => soa.clone(),
+            None => return None,
+        };
+
+        self.notified_serial = self.serial();
+        Some(NotifyRequest::new(self.origin.clone(), self.class, soa, self.secondaries.clone()))
     }
 
-    /// Returns the SOA record for the zone
-    pub fn get_soa_secure(&self,
-                          is_secure: bool,
-                          supported_algorithms: SupportedAlgorithms)
-                          -> Vec<&Record> {
-        self.lookup(&self.origin,
-                    RecordType::SOA,
-                    is_secure,
-                    supported_algorithms)
+    /// Restricts zone transfers, AXFR, to the clients permitted by the access control list, when
+    ///  None any client may transfer the zone
+    pub fn set_transfer_acl(&mut self, transfer_acl: Option<TransferAcl>) {
+        self.transfer_acl = transfer_acl;
     }
 
-    /// Returns the minimum ttl (as used in the SOA record)
-    pub fn get_minimum_ttl(&self) -> u32 {
-        self.get_soa().map_or(0, |soa| if let &RData::SOA(ref rdata) = soa.get_rdata() {
-            rdata.get_minimum()
-        } else {
-            0
-        })
+    /// The clients which may transfer the zone, if restricted
+    pub fn get_transfer_acl(&self) -> Option<&TransferAcl> {
+        self.transfer_acl.as_ref()
     }
 
-    /// get the current serial number for the zone.
-    pub fn get_serial(&self) -> u32 {
-        let soa = if let Some(ref soa_record) = self.get_soa() {
-            soa_record.clone()
-        } else {
-            warn!("no soa record found for zone: {}", self.origin);
-            return 0;
+    /// Binds the zone to the local addresses of the listeners on which it is answered, when None
+    ///  it is answered on every listener
+    ///
+    /// This isolates the zones of tenants sharing a server, a request for the zone received on
+    ///  any other address is refused, see `Catalog::is_served_on`. A listener bound to the
+    ///  unspecified address, e.g. `0.0.0.0`, does not match a specific address: a UDP socket
+    ///  does not know the address a datagram was sent to, so `named` refuses to start with such
+    ///  zones and a listener on the unspecified address.
+    pub fn set_listen_addrs(&mut self, listen_addrs: Option<Vec<IpAddr>>) {
+        self.listen_addrs = listen_addrs;
+    }
+
+    /// The local addresses on which the zone is answered, if restricted
+    pub fn get_listen_addrs(&self) -> Option<&[IpAddr]> {
+        self.listen_addrs.as_ref().map(|addrs| addrs.as_slice())
+    }
+
+    /// Returns true if the zone is answered on the local address, see `set_listen_addrs`
+    pub fn is_listening_on(&self, local: IpAddr) -> bool {
+        match self.listen_addrs {
+            Some(ref addrs) => addrs.contains(&local),
+            None => true,
+        }
+    }
+
+    /// Returns true if the client may transfer the zone, see `set_transfer_acl`
+    ///
+    /// A request signed with SIG(0) is verified with the KEYs of the zone, as updates are. One
+    ///  signed with TSIG is verified before it is passed to the authority, see
+    ///  `Catalog::verify_tsig`, and matches the rules of its key.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - the AXFR query
+    /// * `src` - the address of the client
+    /// * `tsig_key` - the name of the TSIG key which verified the request, if it was signed
+    pub fn is_transfer_permitted(&self,
+                                 request: &Message,
+                                 src: IpAddr,
+                                 tsig_key: Option<&Name>)
+                                 -> bool {
+        match self.transfer_acl {
+            Some(ref transfer_acl) => {
+                let signer = match tsig_key {
+                    Some(tsig_key) => Some(tsig_key),
+                    None => self.verify_sig0(request).map(|sig| sig.get_signer_name()),
+                };
+                transfer_acl.is_permitted(src, signer)
+            }
+            None => true,
+        }
+    }
+
+    /// The records of a zone transfer, RFC 5936 section 2.2
+    ///
+    /// The SOA is first and last, in between are all the other records, along with every RRSIG,
+    ///  including those of the SOA.
+    pub fn get_transfer_records(&self) -> Vec<&Record> {
+        let soa_key = RrKey::new(&self.origin, RecordType::SOA);
+        let soa = match self.records.get(&soa_key) {
+            Some(soa) => soa,
+            None => return vec![],
         };
 
-        if let &RData::SOA(ref soa_rdata) = soa.get_rdata() {
-            soa_rdata.get_serial()
-        } else {
-            panic!("This was not an SOA record"); // valid panic, never should happen
+        let mut records: Vec<&Record> = soa.iter().collect();
+        records.extend(soa.get_rrsigs());
+        for (_, rrset) in self.records.iter().filter(|&(key, _)| *key != soa_key) {
+            records.extend(rrset.iter());
+            records.extend(rrset.get_rrsigs());
         }
+        records.extend(soa.iter());
+
+        records
     }
 
-    fn increment_soa_serial(&mut self) -> u32 {
-        let mut soa = if let Some(ref mut soa_record) = self.get_soa() {
-            soa_record.clone()
-        } else {
-            warn!("no soa record found for zone: {}", self.origin);
-            return 0;
+    /// The number of updates which are kept to answer incremental zone transfers, zero disables
+    ///  them, `DEFAULT_MAX_DELTAS` by default
+    ///
+    /// The oldest changes are dropped once there are more. Only dynamic updates are kept, and only
+    ///  for zones which are not signed, as signing replaces the RRSIGs of the zone. The changes are
+    ///  persisted to the journal, if there is one, and recovered with the zone.
+    pub fn set_max_deltas(&mut self, max_deltas: usize) {
+        self.max_deltas = max_deltas;
+        while self.deltas.len() > max_deltas {
+            self.deltas.pop_front();
+        }
+
+        if let Some(ref journal) = self.journal {
+            if let Err(error) = journal.trim_deltas(max_deltas) {
+                error!("could not trim the deltas of zone: {}: {}", self.origin, error);
+            }
+        }
+    }
+
+    /// the changes of the most recent updates, oldest first
+    pub fn get_deltas(&self) -> &VecDeque<ZoneDelta> {
+        &self.deltas
+    }
+
+    /// The records of an incremental zone transfer, RFC 1995 section 4
+    ///
+    /// If the zone has not changed since the serial, this is just the SOA. If the changes since
+    ///  are known, the SOA is followed by those of each update, see `ZoneDelta::records`, and the
+    ///  SOA again. Otherwise it is the whole zone, as `get_transfer_records`.
+    ///
+    /// # Arguments
+    ///
+    /// * `serial` - the serial of the version of the zone which the client has
+    pub fn get_ixfr_records(&self, serial: u32) -> Vec<&Record> {
+        let soa = match self.get_soa() {
+            Some(soa) => soa,
+            None => return vec![],
         };
 
-        let serial = if let &mut RData::SOA(ref mut soa_rdata) = soa.get_rdata_mut() {
-            soa_rdata.increment_serial();
-            soa_rdata.get_serial()
-        } else {
-            panic!("This was not an SOA record"); // valid panic, never should happen
+        // the same or a newer version, with serial number arithmetic, RFC 1982
+        if serial.wrapping_sub(self.get_serial()) as i32 >= 0 {
+            return vec![soa];
+        }
+
+        // the SOA may have been replaced other than by an update
+        let current = self.get_serial();
+        let is_current = self.deltas.back().map(ZoneDelta::get_new_serial) == Some(current);
+        let start = match self.deltas.iter().position(|delta| delta.get_old_serial() == serial) {
+            Some(start) if is_current => start,
+            _ => return self.get_transfer_records(),
         };
 
-        self.upsert(soa, serial);
-        return serial;
+        let mut records: Vec<&Record> = vec![soa];
+        for delta in self.deltas.iter().skip(start) {
+            records.extend(delta.records());
+        }
+        records.push(soa);
+
+        records
     }
 
-    pub fn get_ns(&self,
-                  is_secure: bool,
-                  supported_algorithms: SupportedAlgorithms)
-                  -> Vec<&Record> {
-        self.lookup(&self.origin,
-                    RecordType::NS,
-                    is_secure,
-                    supported_algorithms)
+    /// Source of the inception time of the RRSIGs when the zone is signed, and of the expire
+    ///  timer of a Slave zone, which restarts, the system clock by default
+    pub fn set_clock(&mut self, clock: Arc<Clock>) {
+        self.last_refresh = clock.now();
+        self.clock = clock;
     }
 
-    /// [RFC 2136](https://tools.ietf.org/html/rfc2136), DNS Update, April 1997
+    /// The number of threads which sign the zone, with the parallel-signing feature, one per CPU
+    ///  by default; without the feature the zone is always signed on the calling thread
+    pub fn set_signing_threads(&mut self, signing_threads: usize) {
+        self.signing_threads = signing_threads;
+    }
+
+    /// Returns the number of threads which sign the zone
+    pub fn get_signing_threads(&self) -> usize {
+        self.signing_threads
+    }
+
+    /// Denies the existence of names and types with NSEC3, RFC 5155, rather than NSEC, when the
+    ///  zone is next secured, see `secure_zone`
     ///
-    /// ```text
+    /// The hash algorithm, iterations and salt of the parameters are those of the NSEC3 chain. If
+    ///  the parameters are Opt-Out, delegations without a DS are left out of the chain, and the
+    ///  NSEC3 records have the Opt-Out flag; the NSEC3PARAM record of the zone has no flags.
+    pub fn set_nsec3_param(&mut self, nsec3_param: Option<NSEC3PARAM>) {
+        self.nsec3_param = nsec3_param;
+    }
+
+    /// Returns the parameters of the NSEC3 chain, None if the zone is secured with NSEC
+    pub fn get_nsec3_param(&self) -> Option<&NSEC3PARAM> {
+        self.nsec3_param.as_ref()
+    }
+
+    /// Restarts the expire timer, to be called whenever a refresh of this Slave zone from its
+    ///  primary succeeds, i.e. the serial of the primary was checked or the zone transferred
+    pub fn refreshed(&mut self) {
+        if self.is_expired() {
+            info!("zone: {} refreshed, no longer expired", self.origin);
+        }
+        self.last_refresh = self.clock.now();
+        self.refresh_failed_at = None;
+        self.notified = false;
+        self.refreshing = false;
+    }
+
+    /// To be called when a refresh of this Slave zone from its primary fails, the next attempt is
+    ///  then made after the retry interval of the SOA
+    pub fn refresh_failed(&mut self) {
+        self.refresh_failed_at = Some(self.clock.now());
+        self.refreshing = false;
+    }
+
+    /// To be called on a NOTIFY from the primary, RFC 1996, the zone is then refreshed without
+    ///  waiting for the refresh timer
     ///
-    /// 3.2 - Process Prerequisite Section
+    /// # Arguments
     ///
-    ///   Next, the Prerequisite Section is checked to see that all
-    ///   prerequisites are satisfied by the current state of the zone.  Using
-    ///   the definitions expressed in Section 1.2, if any RR's NAME is not
-    ///   within the zone specified in the Zone Section, signal NOTZONE to the
-    ///   requestor.
+    /// * `serial` - the serial of the SOA in the NOTIFY, if any, when it is not newer than that of
+    ///              the zone the NOTIFY is ignored
     ///
-    /// 3.2.1. For RRs in this section whose CLASS is ANY, test to see that
-    ///   TTL and RDLENGTH are both zero (0), else signal FORMERR to the
-    ///   requestor.  If TYPE is ANY, test to see that there is at least one RR
-    ///   in the zone whose NAME is the same as that of the Prerequisite RR,
-    ///   else signal NXDOMAIN to the requestor.  If TYPE is not ANY, test to
-    ///   see that there is at least one RR in the zone whose NAME and TYPE are
-    ///   the same as that of the Prerequisite RR, else signal NXRRSET to the
-    ///   requestor.
+    /// # Return value
     ///
-    /// 3.2.2. For RRs in this section whose CLASS is NONE, test to see that
-    ///   the TTL and RDLENGTH are both zero (0), else signal FORMERR to the
-    ///   requestor.  If the TYPE is ANY, test to see that there are no RRs in
-    ///   the zone whose NAME is the same as that of the Prerequisite RR, else
-    ///   signal YXDOMAIN to the requestor.  If the TYPE is not ANY, test to
-    ///   see that there are no RRs in the zone whose NAME and TYPE are the
-    ///   same as that of the Prerequisite RR, else signal YXRRSET to the
-    ///   requestor.
+    /// true if a refresh is now due
+    pub fn notified(&mut self, serial: Option<u32>) -> bool {
+        if self.zone_type != ZoneType::Slave || self.primary.is_none() {
+            return false;
+        }
+
+        if let (Some(current), Some(serial)) = (self.serial(), serial) {
+            // with serial number arithmetic, RFC 1982
+            if serial.wrapping_sub(current) as i32 <= 0 {
+                debug!("notify for zone: {} serial: {} is not newer than: {}",
+                       self.origin,
+                       serial,
+                       current);
+                return false;
+            }
+        }
+
+        self.notified = true;
+        self.is_refresh_due()
+    }
+
+    /// Returns true if this Slave zone should now be refreshed from its primary
     ///
-    /// 3.2.3. For RRs in this section whose CLASS is the same as the ZCLASS,
-    ///   test to see that the TTL is zero (0), else signal FORMERR to the
-    ///   requestor.  Then, build an RRset for each unique <NAME,TYPE> and
-    ///   compare each resulting RRset for set equality (same members, no more,
-    ///   no less) with RRsets in the zone.  If any Prerequisite RRset is not
-    ///   entirely and exactly matched by a zone RRset, signal NXRRSET to the
-    ///   requestor.  If any RR in this section has a CLASS other than ZCLASS
-    ///   or NONE or ANY, signal FORMERR to the requestor.
+    /// That is after a NOTIFY, when the refresh interval of the SOA has passed since the last
+    ///  refresh, or the retry interval since a failed one, RFC 1035 section 3.3.13. A zone without
+    ///  an SOA is transferred straight away, and retried every few minutes.
+    pub fn is_refresh_due(&self) -> bool {
+        if self.zone_type != ZoneType::Slave || self.primary.is_none() || self.refreshing {
+            return false;
+        }
+
+        if self.notified {
+            return true;
+        }
+
+        let now = self.clock.now();
+        let (refresh, retry) = match self.soa() {
+            Some(soa) => {
+                (cmp::max(soa.get_refresh(), 0) as u32, cmp::max(soa.get_retry(), 0) as u32)
+            }
+            None => (0, INITIAL_RETRY),
+        };
+
+        match self.refresh_failed_at {
+            Some(failed_at) => now.saturating_sub(failed_at) >= retry,
+            None => now.saturating_sub(self.last_refresh) >= refresh,
+        }
+    }
+
+    /// Starts a refresh of this Slave zone, if one is due, see `is_refresh_due()`
     ///
-    /// 3.2.4 - Table Of Metavalues Used In Prerequisite Section
+    /// # Return value
     ///
-    ///   CLASS    TYPE     RDATA    Meaning
-    ///   ------------------------------------------------------------
-    ///   ANY      ANY      empty    Name is in use
-    ///   ANY      rrset    empty    RRset exists (value independent)
-    ///   NONE     ANY      empty    Name is not in use
-    ///   NONE     rrset    empty    RRset does not exist
-    ///   zone     rrset    rr       RRset exists (value dependent)
-    /// ```
-    pub fn verify_prerequisites(&self, pre_requisites: &[Record]) -> UpdateResult<()> {
-        //   3.2.5 - Pseudocode for Prerequisite Section Processing
-        //
-        //      for rr in prerequisites
-        //           if (rr.ttl != 0)
-        //                return (FORMERR)
-        //           if (zone_of(rr.name) != ZNAME)
-        //                return (NOTZONE);
-        //           if (rr.class == ANY)
-        //                if (rr.rdlength != 0)
-        //                     return (FORMERR)
-        //                if (rr.type == ANY)
-        //                     if (!zone_name<rr.name>)
-        //                          return (NXDOMAIN)
-        //                else
+    /// The transfer to request from the primary, its result is then installed with
+    ///  `install_transfer()`, or the failure noted with `refresh_failed()`.
+    pub fn start_refresh(&mut self) -> Option<TransferRequest> {
+        if !self.is_refresh_due() {
+            return None;
+        }
+
+        let primary = match self.primary {
+            Some(primary) => primary,
+            None => return None,
+        };
+
+        self.refreshing = true;
+        let mut request = TransferRequest::new(self.origin.clone(),
+                                               self.class,
+                                               primary,
+                                               self.serial(),
+                                               self.transfer_limits);
+        request.source(self.transfer_source).tsigner(self.primary_key.clone());
+        Some(request)
+    }
+
+    /// Serves the A and AAAA records of the target at the apex of this Master zone, where a CNAME
+    ///  is not allowed, RFC 1034 section 3.6.2, None to serve those of the zone
+    ///
+    /// The addresses are those of the target as last resolved, see `start_alias_refresh`, they
+    ///  replace any at the apex, and are resolved again once their TTL expires. Until the target
+    ///  is first resolved the apex has the addresses of the zone, if any.
+    pub fn set_apex_alias(&mut self, target: Option<Name>) {
+        self.apex_alias = target;
+        self.alias_refresh_at = 0;
+        self.alias_refreshing = false;
+    }
+
+    /// Returns the name whose addresses are served at the apex, if any
+    pub fn get_apex_alias(&self) -> Option<&Name> {
+        self.apex_alias.as_ref()
+    }
+
+    /// Returns true if the target of the apex alias should now be resolved, i.e. straight away,
+    ///  then when the TTL of its addresses expires, or a while after a failure
+    pub fn is_alias_refresh_due(&self) -> bool {
+        self.zone_type == ZoneType::Master && self.apex_alias.is_some() &&
+        !self.alias_refreshing && self.clock.now() >= self.alias_refresh_at
+    }
+
+    /// Starts the resolution of the target of the apex alias, if one is due, see
+    ///  `is_alias_refresh_due()`
+    ///
+    /// # Return value
+    ///
+    /// The target to resolve, its addresses are then installed with `install_alias_addresses()`,
+    ///  or the failure noted with `alias_refresh_failed()`.
+    pub fn start_alias_refresh(&mut self) -> Option<AliasRequest> {
+        if !self.is_alias_refresh_due() {
+            return None;
+        }
+
+        let target = match self.apex_alias {
+            Some(ref target) => target.clone(),
+            None => return None,
+        };
+
+        self.alias_refreshing = true;
+        Some(AliasRequest::new(self.origin.clone(), self.class, target))
+    }
+
+    /// Replaces the A and AAAA records at the apex with the addresses of the target of the alias
+    ///
+    /// The records are those of the answers for the target, any other than A and AAAA, e.g. the
+    ///  CNAMEs leading to them, are ignored. The addresses are served at the apex with the least
+    ///  of their TTLs, and resolved again once it expires. The zone is only updated, signed and
+    ///  its serial incremented, if the addresses changed. Without any addresses the resolution
+    ///  failed, the apex keeps those it has.
+    ///
+    /// # Return value
+    ///
+    /// true if the addresses at the apex changed
+    pub fn install_alias_addresses(&mut self, records: &[Record]) -> UpdateResult<bool> {
+        self.alias_refreshing = false;
+
+        let mut addresses: Vec<Record> = records.iter()
+            .filter(|r| r.get_rr_type() == RecordType::A || r.get_rr_type() == RecordType::AAAA)
+            .cloned()
+            .collect();
+        if addresses.is_empty() {
+            warn!("no addresses for the alias: {:?} of zone: {}",
+                  self.apex_alias,
+                  self.origin);
+            self.alias_refresh_failed();
+            return Ok(false);
+        }
+
+        let ttl = addresses.iter().map(|r| r.get_ttl()).min().unwrap_or(0);
+        for address in &mut addresses {
+            address.name(self.origin.clone()).dns_class(self.class).ttl(ttl);
+        }
+
+        let mut update: Vec<Record> = Vec::new();
+        for rr_type in &[RecordType::A, RecordType::AAAA] {
+            let resolved: Vec<&Record> =
+                addresses.iter().filter(|r| r.get_rr_type() == *rr_type).collect();
+            let current: Vec<&RData> = self.records
+                .get(&RrKey::new(&self.origin, *rr_type))
+                .map(|rrset| rrset.iter().map(|r| r.get_rdata()).collect())
+                .unwrap_or(vec![]);
+
+            let unchanged = resolved.len() == current.len() &&
+                            resolved.iter().all(|r| current.contains(&r.get_rdata()));
+            if unchanged {
+                continue;
+            }
+
+            let mut delete = Record::with(self.origin.clone(), *rr_type, 0);
+            delete.dns_class(DNSClass::ANY).rdata(RData::NULL(NULL::new()));
+            update.push(delete);
+            update.extend(resolved.into_iter().cloned());
+        }
+
+        self.alias_refresh_at = self.clock.now().saturating_add(cmp::max(ttl, ALIAS_RETRY));
+        if update.is_empty() {
+            return Ok(false);
+        }
+
+        info!("alias: {:?} of zone: {} resolved to new addresses",
+              self.apex_alias,
+              self.origin);
+        self.update_records(&update, true)
+    }
+
+    /// To be called when the resolution of the target of the apex alias fails, the apex keeps
+    ///  the addresses it has, and the target is resolved again after a while
+    pub fn alias_refresh_failed(&mut self) {
+        self.alias_refresh_at = self.clock.now().saturating_add(ALIAS_RETRY);
+        self.alias_refreshing = false;
+    }
+
+    /// When the zone was loaded, or last refreshed, seconds since the Unix epoch
+    pub fn get_last_refresh(&self) -> u32 {
+        self.last_refresh
+    }
+
+    /// Returns true if this is a Slave zone which has not been refreshed for longer than the
+    ///  expire interval of its SOA, RFC 1035 section 3.3.13
+    ///
+    /// An expired zone is no longer authoritative, queries for it are answered with SERVFAIL
+    ///  until it is refreshed, see `refreshed()`. Master zones never expire.
+    pub fn is_expired(&self) -> bool {
+        if self.zone_type != ZoneType::Slave {
+            return false;
+        }
+
+        match self.soa() {
+            Some(soa) if soa.get_expire() >= 0 => {
+                self.clock.now().saturating_sub(self.last_refresh) > soa.get_expire() as u32
+            }
+            _ => false,
+        }
+    }
+
+    /// Limits on the transfers of this Slave zone from its primary, see `install_transfer`
+    pub fn set_transfer_limits(&mut self, transfer_limits: TransferLimits) {
+        self.transfer_limits = transfer_limits;
+    }
+
+    /// Returns the limits on transfers from the primary, to receive one with `InboundTransfer`
+    pub fn get_transfer_limits(&self) -> &TransferLimits {
+        &self.transfer_limits
+    }
+
+    /// Limits on the size of any RRset after a dynamic update, see `check_rrset_limits`
+    ///
+    /// Records loaded from the zone file, or transferred from the primary, are not limited.
+    pub fn set_rrset_limits(&mut self, rrset_limits: RecordSetLimits) {
+        self.rrset_limits = rrset_limits;
+    }
+
+    /// Returns the limits on the size of an RRset after a dynamic update
+    pub fn get_rrset_limits(&self) -> &RecordSetLimits {
+        &self.rrset_limits
+    }
+
+    /// Replaces the records of the zone with those of a transfer from the primary, AXFR or IXFR
+    ///
+    /// The transfer is checked first, it is rejected if it exceeded the limits, is not complete,
+    ///  has records outside of the zone, does not increase the serial, or leaves the zone without
+    ///  an SOA or NS at its apex. A rejected transfer is not installed, the zone is unchanged, and
+    ///  it is kept for inspection, see `get_quarantined`. A transfer which is just the SOA, as
+    ///  the zone is up to date, only restarts the expire timer, as does one with the serial of
+    ///  the zone, the primary being up to date with it, though it is not installed.
+    ///
+    /// # Arguments
+    ///
+    /// * `transfer` - the complete transfer
+    pub fn install_transfer(&mut self, transfer: InboundTransfer) -> Result<(), TransferRejection> {
+        let checked = self.check_transfer(&transfer);
+        match checked {
+            Ok(Some((records, response))) => {
+                self.records = records;
+                info!("installed transfer of zone: {}, {} records, serial: {}",
+                      self.origin,
+                      transfer.get_records().len(),
+                      self.get_serial());
+
+                match response {
+                    IxfrResponse::Incremental(deltas) => {
+                        for delta in deltas {
+                            self.push_delta(delta);
+                        }
+                    }
+                    _ => self.clear_deltas(),
+                }
+            }
+            Ok(None) => debug!("zone: {} is up to date", self.origin),
+            Err(rejection) => {
+                warn!("quarantined transfer of zone: {}: {}", self.origin, rejection);
+                // the primary has the serial of the zone, which is then up to date
+                let is_up_to_date = match rejection {
+                    TransferRejection::SerialNotIncreased(current, received) => current == received,
+                    _ => false,
+                };
+                if is_up_to_date {
+                    self.refreshed();
+                } else {
+                    self.refresh_failed();
+                }
+                self.quarantined = Some((rejection.clone(), transfer.into_records()));
+                return Err(rejection);
+            }
+        }
+
+        self.quarantined = None;
+        self.refreshed();
+        Ok(())
+    }
+
+    /// The records of the zone once the transfer is applied, with the parsed response, or None if
+    ///  the zone is up to date
+    fn check_transfer(&self,
+                      transfer: &InboundTransfer)
+                      -> Result<Option<(BTreeMap<RrKey, RecordSet>, IxfrResponse)>,
+                                TransferRejection> {
+        if let Some(rejection) = transfer.get_rejection() {
+            return Err(rejection.clone());
+        }
+
+        let records = transfer.get_records();
+        let response = try!(IxfrResponse::from_records(records)
+            .map_err(|e| TransferRejection::Malformed(e.to_string())));
+        // a lone SOA only answers an IXFR from its serial, RFC 1995 section 4
+        if let IxfrResponse::UpToDate(ref soa) = response {
+            let serial = match *soa.get_rdata() {
+                RData::SOA(ref soa) => Some(soa.get_serial()),
+                _ => None,
+            };
+            if serial.is_none() || serial != transfer.get_request_serial() {
+                let reason = "only the SOA, not of the serial requested".to_string();
+                return Err(TransferRejection::Malformed(reason));
+            }
+            return Ok(None);
+        }
+
+        if let Some(record) = records.iter().find(|r| !self.origin.zone_of(r.get_name())) {
+            return Err(TransferRejection::OutOfZone(record.get_name().clone()));
+        }
+
+        // with serial number arithmetic, RFC 1982, any serial is newer than none, i.e. the first
+        //  transfer of the zone
+        let received = match *records[0].get_rdata() {
+            RData::SOA(ref soa) => soa.get_serial(),
+            _ => return Err(TransferRejection::ApexDeleted(RecordType::SOA)),
+        };
+        if let Some(current) = self.serial() {
+            if received.wrapping_sub(current) as i32 <= 0 {
+                return Err(TransferRejection::SerialNotIncreased(current, received));
+            }
+        }
+
+        let mut zone = self.records.clone();
+        try!(response.apply(&mut zone).map_err(|e| TransferRejection::Malformed(e.to_string())));
+
+        for record_type in &[RecordType::SOA, RecordType::NS] {
+            let rrset = zone.get(&RrKey::new(&self.origin, *record_type));
+            if rrset.map_or(true, |rrset| rrset.is_empty()) {
+                return Err(TransferRejection::ApexDeleted(*record_type));
+            }
+        }
+
+        Ok(Some((zone, response)))
+    }
+
+    /// The last transfer from the primary which was rejected, the reason and its records, until a
+    ///  transfer is installed
+    pub fn get_quarantined(&self) -> Option<(&TransferRejection, &[Record])> {
+        self.quarantined.as_ref().map(|&(ref rejection, ref records)| (rejection, &records[..]))
+    }
+
+    /// Sets the file the zone was loaded from, which it is reloaded from, see `Catalog::reload`
+    pub fn set_zone_file(&mut self, zone_file: Option<ZoneFile>) {
+        self.zone_file = zone_file;
+    }
+
+    /// Returns the file the zone was loaded from, if any
+    pub fn get_zone_file(&self) -> Option<&ZoneFile> {
+        self.zone_file.as_ref()
+    }
+
+    /// Replaces all the records of the zone with those reloaded from its file
+    ///
+    /// Only zones which are not dynamically updated are reloaded, the journal would otherwise be
+    ///  out of date with the zone, nor Slave zones, which are transferred from their primary. The
+    ///  change listeners are notified of the differences, which are kept for IXFR. The serial of
+    ///  the file must be greater than that of the zone, the reload is refused otherwise. A signed
+    ///  zone is signed again, which increments its serial, from that of the zone if the file's is
+    ///  not greater. The zone is only replaced once the reloaded records are signed.
+    ///
+    /// # Arguments
+    ///
+    /// * `zone_file` - the file the records were read from, as of the read
+    /// * `records` - the records of the file, with an SOA at the origin
+    /// * `record_meta` - the comments and tags of the records in the file
+    pub fn reload_records(&mut self,
+                          zone_file: ZoneFile,
+                          records: BTreeMap<RrKey, RecordSet>,
+                          record_meta: RecordMetaTable)
+                          -> ParseResult<()> {
+        if self.zone_type == ZoneType::Slave {
+            return Err(ParseErrorKind::Msg(format!("slave zone: {} is transferred, not reloaded",
+                                                   self.origin))
+                .into());
+        }
+        if self.journal.is_some() {
+            return Err(ParseErrorKind::Msg(format!("zone: {} is dynamically updated, its \
+                                                    journal would be lost by a reload",
+                                                   self.origin))
+                .into());
+        }
+
+        let new_soa = match records.get(&RrKey::new(&self.origin, RecordType::SOA))
+            .and_then(|rrset| rrset.iter().next()) {
+            Some(soa) if soa.get_rr_type() == RecordType::SOA => soa.clone(),
+            _ => {
+                return Err(ParseErrorKind::Msg(format!("no SOA for zone: {}", self.origin)).into())
+            }
+        };
+        let new_serial = match *new_soa.get_rdata() {
+            RData::SOA(ref soa) => soa.get_serial(),
+            _ => panic!("This was not an SOA record"), // valid panic, checked above
+        };
+        let is_increased = self.serial()
+            .map_or(true, |serial| new_serial.wrapping_sub(serial) as i32 > 0);
+        if !is_increased && !self.is_signed() {
+            // the file is not read again until it is modified, the serial is then checked again
+            self.zone_file = Some(zone_file);
+            return Err(ParseErrorKind::Msg(format!("serial: {} of zone: {} is not greater \
+                                                    than: {}, not reloaded",
+                                                   new_serial,
+                                                   self.origin,
+                                                   self.get_serial()))
+                .into());
+        }
+
+        let old_soa = self.get_soa().cloned();
+        let keys: BTreeSet<RrKey> = self.records.keys().chain(records.keys()).cloned().collect();
+        let before = self.snapshot(keys.into_iter().collect());
+
+        // the zone is replaced once the reloaded records are signed, it is unchanged otherwise
+        let mut reloaded = self.clone();
+        reloaded.records = records;
+        reloaded.record_meta = record_meta;
+
+        let changes = reloaded.changes(before);
+        if reloaded.is_signed() {
+            if !is_increased {
+                // signing increments the serial of the zone, which must not go backwards
+                let current = self.get_serial();
+                let mut soa = new_soa;
+                let rdata = match *soa.get_rdata() {
+                    RData::SOA(ref rdata) => {
+                        SOA::new(rdata.get_mname().clone(),
+                                 rdata.get_rname().clone(),
+                                 current,
+                                 rdata.get_refresh(),
+                                 rdata.get_retry(),
+                                 rdata.get_expire(),
+                                 rdata.get_minimum())
+                    }
+                    _ => panic!("This was not an SOA record"), // valid panic, checked above
+                };
+                soa.rdata(RData::SOA(rdata));
+                reloaded.insert(soa, current);
+            }
+
+            if let Err(e) = reloaded.secure_zone() {
+                self.zone_file = Some(zone_file);
+                return Err(ParseErrorKind::Msg(format!("failure securing zone: {}", e)).into());
+            }
+
+            // the changes of the RRSIGs are not known, the zone can only be transferred whole
+            reloaded.clear_deltas();
+        } else if let Some(old_soa) = old_soa {
+            reloaded.record_delta(old_soa, changes.clone());
+        }
+
+        // the addresses of the apex alias are replaced with those of the file, until resolved
+        if reloaded.apex_alias.is_some() {
+            reloaded.alias_refresh_at = 0;
+        }
+
+        reloaded.zone_file = Some(zone_file);
+        *self = reloaded;
+        self.notify_changes(&changes);
+
+        info!("reloaded zone: {} from: {:?}, serial: {}",
+              self.origin,
+              self.zone_file.as_ref().map(|f| f.get_path()),
+              self.get_serial());
+        Ok(())
+    }
+
+    /// Sets the comments and tags of the records, as read from the zone file, which are written
+    ///  with the records by `to_zone_file`
+    ///
+    /// The metadata is not kept in the journal, nor transferred to secondaries. That of a record
+    ///  is written whenever a record of the same name, type and data is in the zone. When an
+    ///  update or the signing of the zone replaces a record, e.g. the SOA with its next serial,
+    ///  the metadata moves to the new record, that of deleted records is dropped.
+    pub fn set_record_meta(&mut self, record_meta: RecordMetaTable) {
+        self.record_meta = record_meta;
+    }
+
+    /// Returns the comments and tags of the records
+    pub fn get_record_meta(&self) -> &RecordMetaTable {
+        &self.record_meta
+    }
+
+    /// Returns the comments and tags of the records to change, e.g. to tag where records came from
+    pub fn get_record_meta_mut(&mut self) -> &mut RecordMetaTable {
+        &mut self.record_meta
+    }
+
+    /// Adds a listener, which is notified of every subsequent change to the records of this zone
+    ///
+    /// Only a weak reference to the listener is kept, it is notified until it is dropped, or until
+    ///  the returned subscription is unsubscribed.
+    pub fn add_change_listener(&mut self, listener: &Arc<ChangeListener>) -> ChangeSubscription {
+        let subscription = ChangeSubscription::new();
+        self.subscribe_changes(listener, &subscription);
+        subscription
+    }
+
+    /// Adds a listener, like `add_change_listener()`, to be unsubscribed with an existing
+    ///  subscription, e.g. one shared by several zones
+    pub fn subscribe_changes(&mut self,
+                             listener: &Arc<ChangeListener>,
+                             subscription: &ChangeSubscription) {
+        self.change_listeners.push((Arc::downgrade(listener), subscription.clone()));
+    }
+
+    /// Retrieve the Signer, which contains the private keys, for this zone
+    pub fn get_secure_keys(&self) -> &[Arc<Signer>] {
+        &self.secure_keys
+    }
+
+    /// True if DNSSec is enabled for the zone and there is at least one key to sign it with
+    pub fn is_signed(&self) -> bool {
+        self.is_dnssec_enabled && !self.secure_keys.is_empty()
+    }
+
+    /// Get the origin of this zone, i.e. example.com is the origin for www.example.com
+    pub fn get_origin(&self) -> &Name {
+        &self.origin
+    }
+
+    /// What type is this zone
+    pub fn get_zone_type(&self) -> ZoneType {
+        self.zone_type
+    }
+
+    /// Get all the
+    pub fn get_records(&self) -> &BTreeMap<RrKey, RecordSet> {
+        &self.records
+    }
+
+    /// Renders the zone as an RFC 1035 master file, which `Parser` reads back, e.g. to inspect,
+    ///  or keep, a zone changed by dynamic updates
+    ///
+    /// The `$ORIGIN` is that of the zone, followed by the SOA, then the other records in the order
+    ///  of their names and types. RRSIG, NSEC and NSEC3 records are written as comments, they are
+    ///  generated again when the zone is signed, see `RecordSet::to_zone_file`. The comments and tags of each
+    ///  record precede it, see `set_record_meta`.
+    pub fn to_zone_file(&self) -> String {
+        let mut zone_file = format!("$ORIGIN {}\n", self.origin);
+
+        let soa_key = RrKey::new(&self.origin, RecordType::SOA);
+        if let Some(soa) = self.records.get(&soa_key) {
+            zone_file.push_str(&soa.to_annotated_zone_file(&self.origin, &self.record_meta));
+        }
+        for (_, rrset) in self.records.iter().filter(|&(key, _)| *key != soa_key) {
+            zone_file.push_str(&rrset.to_annotated_zone_file(&self.origin, &self.record_meta));
+        }
+
+        zone_file
+    }
+
+    /// Returns the SOA of the authority.
+    ///
+    /// *Note*: This will only return the SOA, if this is fullfilling a request, a standard lookup
+    ///  should be used, see `get_soa_secure()`, which will optionally return RRSIGs.
+    pub fn get_soa(&self) -> Option<&Record> {
+        // SOA should be origin|SOA
+        self.lookup(&self.origin,
+                    RecordType::SOA,
+                    false,
+                    SupportedAlgorithms::new())
+            .first()
+            .map(|v| *v)
+    }
+
+    /// Returns the SOA record for the zone
+    pub fn get_soa_secure(&self,
+                          is_secure: bool,
+                          supported_algorithms: SupportedAlgorithms)
+                          -> Vec<&Record> {
+        self.lookup(&self.origin,
+                    RecordType::SOA,
+                    is_secure,
+                    supported_algorithms)
+    }
+
+    /// Returns the minimum ttl (as used in the SOA record)
+    pub fn get_minimum_ttl(&self) -> u32 {
+        self.soa().map_or(0, |soa| soa.get_minimum())
+    }
+
+    /// get the current serial number for the zone.
+    pub fn get_serial(&self) -> u32 {
+        match self.serial() {
+            Some(serial) => serial,
+            None => {
+                warn!("no soa record found for zone: {}", self.origin);
+                0
+            }
+        }
+    }
+
+    /// Returns the SOA data of the zone, None if the zone has no SOA
+    pub fn soa(&self) -> Option<&SOA> {
+        self.get_soa().and_then(|record| match *record.get_rdata() {
+            RData::SOA(ref soa) => Some(soa),
+            _ => None,
+        })
+    }
+
+    /// Returns the serial of the zone, None if the zone has no SOA
+    ///
+    /// Unlike `get_serial()`, a zone without an SOA is not mistaken for serial 0.
+    pub fn serial(&self) -> Option<u32> {
+        self.soa().map(|soa| soa.get_serial())
+    }
+
+    /// Returns the names of the name servers of the zone, from the NS records at the origin
+    pub fn ns(&self) -> Vec<&Name> {
+        self.lookup(&self.origin,
+                    RecordType::NS,
+                    false,
+                    SupportedAlgorithms::new())
+            .into_iter()
+            .filter_map(|record| match *record.get_rdata() {
+                RData::NS(ref name) => Some(name),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Returns the keys of the DNSKEY rrset at the origin, those of the `secure_keys` and any
+    ///  imported with `import_zone_signing_key()`
+    pub fn dnskeys(&self) -> Vec<&DNSKEY> {
+        self.lookup(&self.origin,
+                    RecordType::DNSKEY,
+                    false,
+                    SupportedAlgorithms::new())
+            .into_iter()
+            .filter_map(|record| match *record.get_rdata() {
+                RData::DNSKEY(ref dnskey) => Some(dnskey),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn increment_soa_serial(&mut self) -> u32 {
+        let mut soa = if let Some(ref mut soa_record) = self.get_soa() {
+            soa_record.clone()
+        } else {
+            warn!("no soa record found for zone: {}", self.origin);
+            return 0;
+        };
+
+        let old_soa = soa.clone();
+        let serial = if let &mut RData::SOA(ref mut soa_rdata) = soa.get_rdata_mut() {
+            soa_rdata.increment_serial();
+            soa_rdata.get_serial()
+        } else {
+            panic!("This was not an SOA record"); // valid panic, never should happen
+        };
+
+        if let Some(meta) = self.record_meta.remove(&old_soa) {
+            self.record_meta.insert(&soa, meta);
+        }
+        self.insert(soa, serial);
+        return serial;
+    }
+
+    pub fn get_ns(&self,
+                  is_secure: bool,
+                  supported_algorithms: SupportedAlgorithms)
+                  -> Vec<&Record> {
+        self.lookup(&self.origin,
+                    RecordType::NS,
+                    is_secure,
+                    supported_algorithms)
+    }
+
+    /// [RFC 2136](https://tools.ietf.org/html/rfc2136), DNS Update, April 1997
+    ///
+    /// ```text
+    ///
+    /// 3.2 - Process Prerequisite Section
+    ///
+    ///   Next, the Prerequisite Section is checked to see that all
+    ///   prerequisites are satisfied by the current state of the zone.  Using
+    ///   the definitions expressed in Section 1.2, if any RR's NAME is not
+    ///   within the zone specified in the Zone Section, signal NOTZONE to the
+    ///   requestor.
+    ///
+    /// 3.2.1. For RRs in this section whose CLASS is ANY, test to see that
+    ///   TTL and RDLENGTH are both zero (0), else signal FORMERR to the
+    ///   requestor.  If TYPE is ANY, test to see that there is at least one RR
+    ///   in the zone whose NAME is the same as that of the Prerequisite RR,
+    ///   else signal NXDOMAIN to the requestor.  If TYPE is not ANY, test to
+    ///   see that there is at least one RR in the zone whose NAME and TYPE are
+    ///   the same as that of the Prerequisite RR, else signal NXRRSET to the
+    ///   requestor.
+    ///
+    /// 3.2.2. For RRs in this section whose CLASS is NONE, test to see that
+    ///   the TTL and RDLENGTH are both zero (0), else signal FORMERR to the
+    ///   requestor.  If the TYPE is ANY, test to see that there are no RRs in
+    ///   the zone whose NAME is the same as that of the Prerequisite RR, else
+    ///   signal YXDOMAIN to the requestor.  If the TYPE is not ANY, test to
+    ///   see that there are no RRs in the zone whose NAME and TYPE are the
+    ///   same as that of the Prerequisite RR, else signal YXRRSET to the
+    ///   requestor.
+    ///
+    /// 3.2.3. For RRs in this section whose CLASS is the same as the ZCLASS,
+    ///   test to see that the TTL is zero (0), else signal FORMERR to the
+    ///   requestor.  Then, build an RRset for each unique <NAME,TYPE> and
+    ///   compare each resulting RRset for set equality (same members, no more,
+    ///   no less) with RRsets in the zone.  If any Prerequisite RRset is not
+    ///   entirely and exactly matched by a zone RRset, signal NXRRSET to the
+    ///   requestor.  If any RR in this section has a CLASS other than ZCLASS
+    ///   or NONE or ANY, signal FORMERR to the requestor.
+    ///
+    /// 3.2.4 - Table Of Metavalues Used In Prerequisite Section
+    ///
+    ///   CLASS    TYPE     RDATA    Meaning
+    ///   ------------------------------------------------------------
+    ///   ANY      ANY      empty    Name is in use
+    ///   ANY      rrset    empty    RRset exists (value independent)
+    ///   NONE     ANY      empty    Name is not in use
+    ///   NONE     rrset    empty    RRset does not exist
+    ///   zone     rrset    rr       RRset exists (value dependent)
+    /// ```
+    pub fn verify_prerequisites(&self, pre_requisites: &[Record]) -> UpdateResult<()> {
+        //   3.2.5 - Pseudocode for Prerequisite Section Processing
+        //
+        //      for rr in prerequisites
+        //           if (rr.ttl != 0)
+        //                return (FORMERR)
+        //           if (zone_of(rr.name) != ZNAME)
+        //                return (NOTZONE);
+        //           if (rr.class == ANY)
+        //                if (rr.rdlength != 0)
+        //                     return (FORMERR)
+        //                if (rr.type == ANY)
+        //                     if (!zone_name<rr.name>)
+        //                          return (NXDOMAIN)
+        //                else
         //                     if (!zone_rrset<rr.name, rr.type>)
         //                          return (NXRRSET)
         //           if (rr.class == NONE)
@@ -477,16 +1529,43 @@ impl Authority {
         }
 
         // verify sig0, currently the only authorization that is accepted.
-        let sig0s: &[Record] = update_message.get_sig0();
-        debug!("authorizing with: {:?}", sig0s);
-        if !sig0s.is_empty() &&
-           sig0s.iter()
+        let verified_sig: Option<&SIG> = self.verify_sig0(update_message);
+
+        if let Some(sig) = verified_sig {
+            // the key is valid for the zone, now check it may change these records
+            if let Some(ref update_policy) = self.update_policy {
+                if !update_policy.is_permitted(sig.get_signer_name(),
+                                               &self.origin,
+                                               update_message.get_updates()) {
+                    warn!("update policy refused update from: {}, id {}",
+                          sig.get_signer_name(),
+                          update_message.get_id());
+                    return Err(ResponseCode::Refused);
+                }
+            }
+
+            return Ok(());
+        } else {
+            warn!("no sig0 matched registered records: id {}",
+                  update_message.get_id());
+        }
+
+        // getting here, we will always default to rejecting the request
+        //  the code will only ever explcitly return authrorized actions.
+        Err(ResponseCode::Refused)
+    }
+
+    /// Returns the SIG(0) of the message verified with one of the KEYs of the zone, RFC 2931
+    fn verify_sig0<'m>(&self, message: &'m Message) -> Option<&'m SIG> {
+        let sig0s: &[Record] = message.get_sig0();
+        debug!("verifying sig0s: {:?}", sig0s);
+        sig0s.iter()
             .filter_map(|sig0| if let &RData::SIG(ref sig) = sig0.get_rdata() {
                 Some(sig)
             } else {
                 None
             })
-            .any(|sig| {
+            .find(|sig| {
                 let name = sig.get_signer_name();
                 let keys = self.lookup(name, RecordType::KEY, false, SupportedAlgorithms::new());
                 debug!("found keys {:?}", keys);
@@ -514,7 +1593,7 @@ impl Authority {
                                                                   false,
                                                                   true);
 
-                        signer.verify_message(update_message, sig.get_sig())
+                        signer.verify_message(message, sig.get_sig())
                             .map(|_| {
                                 info!("verified sig: {:?} with key: {:?}", sig, key);
                                 true
@@ -524,16 +1603,7 @@ impl Authority {
                                 false
                             })
                     })
-            }) {
-            return Ok(());
-        } else {
-            warn!("no sig0 matched registered records: id {}",
-                  update_message.get_id());
-        }
-
-        // getting here, we will always default to rejecting the request
-        //  the code will only ever explcitly return authrorized actions.
-        Err(ResponseCode::Refused)
+            })
     }
 
     /// [RFC 2136](https://tools.ietf.org/html/rfc2136), DNS Update, April 1997
@@ -588,11 +1658,17 @@ impl Authority {
             let class: DNSClass = rr.get_dns_class();
             if class == self.class {
                 match rr.get_rr_type() {
-                    RecordType::ANY | RecordType::AXFR | RecordType::IXFR => {
+                    RecordType::ANY | RecordType::AXFR | RecordType::IXFR | RecordType::MAILB => {
                         return Err(ResponseCode::FormErr)
                     }
                     _ => (),
                 }
+
+                // upsert() would drop it, the requestor should know why
+                if let Err(e) = rr.get_rdata().validate() {
+                    warn!("update for {} rejected: {}", rr.get_name(), e);
+                    return Err(ResponseCode::FormErr);
+                }
             } else {
                 match class {
                     DNSClass::ANY => {
@@ -605,7 +1681,7 @@ impl Authority {
                             return Err(ResponseCode::FormErr);
                         }
                         match rr.get_rr_type() {
-                            RecordType::AXFR | RecordType::IXFR => {
+                            RecordType::AXFR | RecordType::IXFR | RecordType::MAILB => {
                                 return Err(ResponseCode::FormErr)
                             }
                             _ => (),
@@ -616,9 +1692,8 @@ impl Authority {
                             return Err(ResponseCode::FormErr);
                         }
                         match rr.get_rr_type() {
-                            RecordType::ANY | RecordType::AXFR | RecordType::IXFR => {
-                                return Err(ResponseCode::FormErr)
-                            }
+                            RecordType::ANY | RecordType::AXFR | RecordType::IXFR |
+                            RecordType::MAILB => return Err(ResponseCode::FormErr),
                             _ => (),
                         }
                     }
@@ -630,6 +1705,86 @@ impl Authority {
         return Ok(());
     }
 
+    /// Checks that no RRset would exceed the limits after the update, see `set_rrset_limits`
+    ///
+    /// The update is applied to copies of the RRsets it adds to, so that records deleted earlier
+    ///  in the update make room for those added later. This is checked before any of the update
+    ///  is applied, an update over the limits is refused as a whole and the zone is unchanged.
+    ///
+    /// # Arguments
+    ///
+    /// * `records` - the update section, already checked by `pre_scan`
+    pub fn check_rrset_limits(&self, records: &[Record]) -> UpdateResult<()> {
+        let serial = self.get_serial();
+        let mut rrsets: BTreeMap<RrKey, RecordSet> = BTreeMap::new();
+
+        for rr in records {
+            let rr_key = RrKey::new(rr.get_name(), rr.get_rr_type());
+
+            match rr.get_dns_class() {
+                class @ _ if class == self.class => {
+                    let rrset = rrsets.entry(rr_key)
+                        .or_insert_with(|| {
+                            self.records
+                                .get(&RrKey::new(rr.get_name(), rr.get_rr_type()))
+                                .cloned()
+                                .unwrap_or_else(|| {
+                                    RecordSet::new(rr.get_name(), rr.get_rr_type(), serial)
+                                })
+                        });
+
+                    if let Err(e) = rrset.try_insert(rr.clone(), serial, &self.rrset_limits) {
+                        warn!("refusing update of {} {:?}: {}",
+                              rr.get_name(),
+                              rr.get_rr_type(),
+                              e);
+                        return Err(ResponseCode::Refused);
+                    }
+                }
+                DNSClass::ANY => {
+                    // the deleted rrsets are empty for any later additions
+                    let keys = if rr.get_rr_type() == RecordType::ANY {
+                        self.records
+                            .keys()
+                            .chain(rrsets.keys())
+                            .filter(|k| &k.name == rr.get_name())
+                            .cloned()
+                            .collect::<Vec<RrKey>>()
+                    } else {
+                        vec![rr_key]
+                    };
+
+                    for key in keys {
+                        // an SOA or NS at the origin is never deleted, and can't grow past one
+                        if key.name == self.origin &&
+                           (key.record_type == RecordType::SOA ||
+                            key.record_type == RecordType::NS) {
+                            continue;
+                        }
+
+                        let rrset = RecordSet::new(&key.name, key.record_type, serial);
+                        rrsets.insert(key, rrset);
+                    }
+                }
+                DNSClass::NONE => {
+                    if let Some(rrset) = rrsets.get_mut(&rr_key) {
+                        rrset.remove(rr, serial);
+                        continue;
+                    }
+
+                    if let Some(rrset) = self.records.get(&rr_key) {
+                        let mut rrset = rrset.clone();
+                        rrset.remove(rr, serial);
+                        rrsets.insert(rr_key, rrset);
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        Ok(())
+    }
+
     /// Updates the specified records according to the update section.
     ///
     /// [RFC 2136](https://tools.ietf.org/html/rfc2136), DNS Update, April 1997
@@ -657,6 +1812,8 @@ impl Authority {
                           -> UpdateResult<bool> {
         let mut updated = false;
         let serial: u32 = self.get_serial();
+        let old_soa = self.get_soa().cloned();
+        let before = self.snapshot(self.changed_keys(records));
 
         // the persistence act as a write-ahead log. The WAL will also be used for recovery of a zone
         //  subsequent to a failure of the server.
@@ -724,7 +1881,8 @@ impl Authority {
 
                     // zone     rrset    rr       Add to an RRset
                     info!("upserting record: {:?}", rr);
-                    updated = self.upsert(rr.clone(), serial) || updated;
+                    updated = self.insert(rr.clone(), serial) || updated;
+                    self.warn_occluded(rr);
                 }
                 DNSClass::ANY => {
                     // This is a delete of entire RRSETs, either many or one. In either case, the spec is clear:
@@ -793,21 +1951,36 @@ impl Authority {
             }
         }
 
+        if !updated {
+            return Ok(false);
+        }
+
+        let changes = self.changes(before);
+        self.move_record_meta(&changes);
+        self.notify_changes(&changes);
+
         // update the serial...
-        if updated && auto_signing_and_increment {
+        if auto_signing_and_increment {
             if self.is_dnssec_enabled {
-                try!(self.secure_zone().map_err(|e| {
+                try!(self.secure_zone_update(records).map_err(|e| {
                     error!("failure securing zone: {}", e);
                     ResponseCode::ServFail
-                }))
+                }));
+
+                // the changes of the RRSIGs are not known, the zone can only be transferred whole
+                self.clear_deltas();
             } else {
                 // the secure_zone() function increments the SOA during it's operation, if we're not
                 //  dnssec, then we need to do it here...
                 self.increment_soa_serial();
+                if let Some(old_soa) = old_soa {
+                    self.record_delta(old_soa, changes);
+                }
             }
         }
 
-        Ok(updated)
+        self.check_journal_compaction();
+        Ok(true)
     }
 
     /// Inserts or updates a `Record` depending on it's existence in the authority.
@@ -821,10 +1994,32 @@ impl Authority {
     ///
     /// # Return value
     ///
-    /// Ok() on success or Err() with the `ResponseCode` associated with the error.
+    /// True if the authority changed, false if the record was already present or its data is
+    ///  invalid, see `RData::validate()`.
     pub fn upsert(&mut self, record: Record, serial: u32) -> bool {
+        let before = self.snapshot(vec![RrKey::new(record.get_name(), record.get_rr_type())]);
+        let upserted = self.insert(record, serial);
+        if upserted {
+            let changes = self.changes(before);
+            self.move_record_meta(&changes);
+            self.notify_changes(&changes);
+        }
+
+        upserted
+    }
+
+    /// Inserts or updates the record, without notifying the change listeners
+    fn insert(&mut self, record: Record, serial: u32) -> bool {
         assert_eq!(self.class, record.get_dns_class());
 
+        if let Err(e) = record.get_rdata().validate() {
+            warn!("not inserting {} {:?}: {}",
+                  record.get_name(),
+                  record.get_rr_type(),
+                  e);
+            return false;
+        }
+
         let rr_key = RrKey::new(record.get_name(), record.get_rr_type());
         let records: &mut RecordSet = self.records
             .entry(rr_key)
@@ -833,6 +2028,180 @@ impl Authority {
         records.insert(record, serial)
     }
 
+    /// Changes are only compared if there are change listeners, deltas are kept for IXFR, or the
+    ///  records have metadata to move
+    fn is_tracking_changes(&self) -> bool {
+        !self.change_listeners.is_empty() || self.max_deltas > 0 || !self.record_meta.is_empty()
+    }
+
+    /// The RRSets which the update records may change, none if changes are not tracked
+    fn changed_keys(&self, records: &[Record]) -> Vec<RrKey> {
+        if !self.is_tracking_changes() {
+            return Vec::new();
+        }
+
+        let mut keys = Vec::new();
+        for rr in records {
+            if rr.get_dns_class() == DNSClass::ANY && rr.get_rr_type() == RecordType::ANY {
+                keys.extend(self.records.keys().filter(|k| &k.name == rr.get_name()).cloned());
+            } else {
+                keys.push(RrKey::new(rr.get_name(), rr.get_rr_type()));
+            }
+        }
+
+        keys
+    }
+
+    /// Copies the records of the RRSets, to compare against once they are changed
+    fn snapshot(&self, keys: Vec<RrKey>) -> BTreeMap<RrKey, Vec<Record>> {
+        if !self.is_tracking_changes() {
+            return BTreeMap::new();
+        }
+
+        keys.into_iter()
+            .map(|key| {
+                let records = self.records
+                    .get(&key)
+                    .map_or_else(Vec::new, |rrset| rrset.iter().cloned().collect());
+                (key, records)
+            })
+            .collect()
+    }
+
+    /// The differences to the snapshot, a changed TTL is reported as the record being added
+    fn changes(&self, before: BTreeMap<RrKey, Vec<Record>>) -> Vec<RecordChange> {
+        let mut changes = Vec::new();
+        for (key, old) in before {
+            let new: Vec<Record> = self.records
+                .get(&key)
+                .map_or_else(Vec::new, |rrset| rrset.iter().cloned().collect());
+
+            changes.extend(old.iter()
+                .filter(|r| !new.contains(r))
+                .cloned()
+                .map(RecordChange::Removed));
+            changes.extend(new.iter()
+                .filter(|r| !old.iter().any(|o| o == *r && o.get_ttl() == r.get_ttl()))
+                .cloned()
+                .map(RecordChange::Added));
+        }
+
+        changes
+    }
+
+    /// Moves the metadata of each removed record to a record added to the same RRSet, in their
+    ///  order, the metadata of removed records which are not replaced is dropped
+    fn move_record_meta(&mut self, changes: &[RecordChange]) {
+        if self.record_meta.is_empty() {
+            return;
+        }
+
+        let mut added: Vec<&Record> = changes.iter()
+            .filter_map(|change| match *change {
+                RecordChange::Added(ref record) => Some(record),
+                RecordChange::Removed(_) => None,
+            })
+            .collect();
+
+        for change in changes {
+            let removed = match *change {
+                RecordChange::Removed(ref record) => record,
+                RecordChange::Added(_) => continue,
+            };
+            let meta = match self.record_meta.remove(removed) {
+                Some(meta) => meta,
+                None => continue,
+            };
+
+            let replacement = added.iter()
+                .position(|r| {
+                    r.get_name() == removed.get_name() &&
+                    r.get_rr_type() == removed.get_rr_type() &&
+                    self.record_meta.get(r).is_none()
+                });
+            if let Some(index) = replacement {
+                let record = added.remove(index);
+                self.record_meta.insert(record, meta);
+            }
+        }
+    }
+
+    /// Notifies the change listeners of the changes, if there are any
+    fn notify_changes(&mut self, changes: &[RecordChange]) {
+        self.change_listeners
+            .retain(|&(ref listener, ref subscription)| {
+                subscription.is_active() && listener.upgrade().is_some()
+            });
+        if changes.is_empty() {
+            return;
+        }
+
+        for &(ref listener, _) in &self.change_listeners {
+            if let Some(listener) = listener.upgrade() {
+                listener.records_changed(&self.origin, changes);
+            }
+        }
+    }
+
+    /// Keeps the changes of an update to the zone for IXFR, the SOA being the one before the update
+    fn record_delta(&mut self, old_soa: Record, changes: Vec<RecordChange>) {
+        if self.max_deltas == 0 {
+            return;
+        }
+        let new_soa = match self.get_soa() {
+            Some(new_soa) => new_soa.clone(),
+            None => return,
+        };
+
+        // the SOAs delimit the changes, an updated SOA is the new one
+        let mut removed = Vec::new();
+        let mut added = Vec::new();
+        for change in changes {
+            match change {
+                RecordChange::Removed(ref record) if record.get_rr_type() == RecordType::SOA => (),
+                RecordChange::Added(ref record) if record.get_rr_type() == RecordType::SOA => (),
+                RecordChange::Removed(record) => removed.push(record),
+                RecordChange::Added(record) => added.push(record),
+            }
+        }
+
+        self.push_delta(ZoneDelta::new(old_soa, removed, new_soa, added));
+    }
+
+    /// Keeps the delta, dropping the oldest if there are too many
+    fn push_delta(&mut self, delta: ZoneDelta) {
+        if self.max_deltas == 0 {
+            return;
+        }
+
+        // the deltas are a chain, each from the serial the one before is to
+        let old_serial = delta.get_old_serial();
+        if self.deltas.back().map_or(false, |last| last.get_new_serial() != old_serial) {
+            self.clear_deltas();
+        }
+        if self.deltas.len() == self.max_deltas {
+            self.deltas.pop_front();
+        }
+
+        // the update is already persisted, after a restart IXFR falls back to the whole zone
+        if let Some(ref journal) = self.journal {
+            if let Err(error) = journal.insert_delta(&delta, self.max_deltas) {
+                error!("could not persist the delta of zone: {}: {}", self.origin, error);
+            }
+        }
+        self.deltas.push_back(delta);
+    }
+
+    /// Forgets the deltas, in the journal as well, once the changes to the zone are not known
+    fn clear_deltas(&mut self) {
+        self.deltas.clear();
+        if let Some(ref journal) = self.journal {
+            if let Err(error) = journal.clear_deltas() {
+                error!("could not clear the deltas of zone: {}: {}", self.origin, error);
+            }
+        }
+    }
+
     /// Takes the UpdateMessage, extracts the Records, and applies the changes to the record set.
     ///
     /// [RFC 2136](https://tools.ietf.org/html/rfc2136), DNS Update, April 1997
@@ -895,6 +2264,7 @@ impl Authority {
         try!(self.authorize(update));
         try!(self.verify_prerequisites(update.get_pre_requisites()));
         try!(self.pre_scan(update.get_updates()));
+        try!(self.check_rrset_limits(update.get_updates()));
 
         self.update_records(update.get_updates(), true)
     }
@@ -908,13 +2278,14 @@ impl Authority {
     ///
     /// # Return value
     ///
-    /// Returns a vectory containing the results of the query, it will be empty if not found. If
-    ///  `is_secure` is true, in the case of no records found then NSEC records will be returned.
+    /// Returns a vectory containing the results of the query, it will be empty if not found. A
+    ///  name which does not exist is answered from the wildcard for it, see `find_wildcard`, the
+    ///  records are then synthesized with the name as their owner, see `lookup_wildcard`.
     pub fn search(&self,
                   query: &Query,
                   is_secure: bool,
                   supported_algorithms: SupportedAlgorithms)
-                  -> Vec<&Record> {
+                  -> Vec<Record> {
         let record_type: RecordType = query.get_query_type();
 
         // if this is an AXFR zone transfer, verify that this is either the slave or master
@@ -927,6 +2298,11 @@ impl Authority {
             }
         }
 
+        // records below a zone cut or a DNAME are stored but not answered, see `get_occlusion`
+        if RecordType::AXFR != record_type && self.is_occluded(query.get_name()) {
+            return vec![];
+        }
+
         // it would be better to stream this back, rather than packaging everything up in an array
         //  though for UDP it would still need to be bundled
         let mut query_result: Vec<_> = self.lookup(query.get_name(),
@@ -934,6 +2310,15 @@ impl Authority {
                                                    is_secure,
                                                    supported_algorithms);
 
+        // a CNAME is the answer for every other type at its name, RFC 1034 section 4.3.2
+        if query_result.is_empty() && record_type != RecordType::CNAME &&
+           record_type != RecordType::ANY && record_type != RecordType::AXFR {
+            query_result = self.lookup(query.get_name(),
+                                       RecordType::CNAME,
+                                       is_secure,
+                                       supported_algorithms);
+        }
+
         if RecordType::AXFR == record_type {
             if let Some(soa) = self.get_soa() {
                 let mut xfr: Vec<&Record> = query_result;
@@ -945,9 +2330,18 @@ impl Authority {
             } else {
                 return vec![]; // TODO is this an error?
             }
+        } else if query_result.is_empty() {
+            // a name which does not exist may be answered from a wildcard, RFC 4592
+            if let Some(wildcard) = self.find_wildcard(query.get_name()) {
+                return self.lookup_wildcard(query.get_name(),
+                                            &wildcard,
+                                            record_type,
+                                            is_secure,
+                                            supported_algorithms);
+            }
         }
 
-        query_result
+        query_result.into_iter().cloned().collect()
     }
 
     /// Looks up all Resource Records matching the giving `Name` and `RecordType`.
@@ -958,12 +2352,14 @@ impl Authority {
     /// * `rtype` - The `RecordType`, to lookup. `RecordType::ANY` will return all records matching
     ///             `name`. `RecordType::AXFR` will return all record types except `RecordType::SOA`
     ///             due to the requirements that on zone transfers the `RecordType::SOA` must both
-    ///             preceed and follow all other records.
+    ///             preceed and follow all other records. `RecordType::MAILB` will return the
+    ///             MB, MG and MR records matching `name`.
     /// * `is_secure` - If the DO bit is set on the EDNS OPT record, then return RRSIGs as well.
     ///
     /// # Return value
     ///
     /// None if there are no matching records, otherwise a `Vec` containing the found records.
+    ///  Wildcards match only themselves here, see `search` for their expansion.
     pub fn lookup(&self,
                   name: &Name,
                   rtype: RecordType,
@@ -980,13 +2376,27 @@ impl Authority {
 
         // Collect the records from each rr_set
         let result: Vec<&Record> = match rtype {
-            RecordType::ANY | RecordType::AXFR => {
+            RecordType::ANY => {
+                self.records_from(name)
+                    .take_while(|&(rr_key, _)| *rr_key.name == *name)
+                    .fold(Vec::<&Record>::new(), |mut vec, (_, rr_set)| {
+                        vec.append(&mut rr_set.get_records(is_secure, supported_algorithms));
+                        vec
+                    })
+            }
+            RecordType::AXFR => {
                 self.records
                     .values()
-                    .filter(|rr_set| {
-                        rtype == RecordType::ANY || rr_set.get_record_type() != RecordType::SOA
+                    .filter(|rr_set| rr_set.get_record_type() != RecordType::SOA)
+                    .fold(Vec::<&Record>::new(), |mut vec, rr_set| {
+                        vec.append(&mut rr_set.get_records(is_secure, supported_algorithms));
+                        vec
                     })
-                    .filter(|rr_set| rtype == RecordType::AXFR || rr_set.get_name() == name)
+            }
+            RecordType::MAILB => {
+                [RecordType::MB, RecordType::MG, RecordType::MR]
+                    .iter()
+                    .filter_map(|rtype| self.records.get(&RrKey::new(name, *rtype)))
                     .fold(Vec::<&Record>::new(), |mut vec, rr_set| {
                         vec.append(&mut rr_set.get_records(is_secure, supported_algorithms));
                         vec
@@ -1002,6 +2412,130 @@ impl Authority {
         result
     }
 
+    /// Returns true if the name exists in the zone, this is the case if there are records at the
+    ///  name or it is an empty non-terminal, and it is not occluded, see `get_occlusion`.
+    ///
+    /// A name which exists but has no records of the requested type is answered with NODATA, i.e.
+    ///  NoError with no answers, rather than NXDomain.
+    pub fn contains_name(&self, name: &Name) -> bool {
+        if !self.origin.zone_of(name) || self.is_occluded(name) {
+            return false;
+        }
+
+        // the hashed owner names of NSEC3 records are not names of the zone, RFC 5155 section 7.2.8
+        self.records_from(name)
+            .take_while(|&(rr_key, _)| name.zone_of(&rr_key.name))
+            .any(|(rr_key, rr_set)| !rr_set.is_empty() && rr_key.record_type != RecordType::NSEC3)
+    }
+
+    /// The record sets of the name and of the names after it, in canonical order, see
+    ///  `Name::cmp`
+    ///
+    /// The names below a name directly follow it in that order, so those of the name and of its
+    ///  subtree are found without going through the whole zone.
+    fn records_from(&self, name: &Name) -> btree_map::Range<RrKey, RecordSet> {
+        // NULL is the type of the lowest value
+        self.records.range(RrKey::new(name, RecordType::NULL)..)
+    }
+
+    /// The wildcard which answers for the name, RFC 4592 section 3.3.1, None if there is none
+    ///
+    /// The wildcard is the source of synthesis, `*.` the closest encloser of the name, the
+    ///  nearest ancestor which exists. It only answers for names which do not exist themselves,
+    ///  nor are occluded, see `get_occlusion`.
+    pub fn find_wildcard(&self, name: &Name) -> Option<Name> {
+        if !self.origin.zone_of(name) || self.contains_name(name) || self.is_occluded(name) {
+            return None;
+        }
+
+        let mut closest_encloser = name.base_name();
+        while closest_encloser.num_labels() > self.origin.num_labels() &&
+              !self.contains_name(&closest_encloser) {
+            closest_encloser = closest_encloser.base_name();
+        }
+
+        let wildcard = closest_encloser.prepend_label(Rc::new("*".to_string()));
+        let exists = self.records_from(&wildcard)
+            .take_while(|&(rr_key, _)| *rr_key.name == wildcard)
+            .any(|(_, rr_set)| !rr_set.is_empty());
+        if exists {
+            Some(wildcard)
+        } else {
+            None
+        }
+    }
+
+    /// The records of the wildcard for the name, with the name as their owner, RFC 4592 section
+    ///  3.4.3, see `find_wildcard`
+    ///
+    /// A CNAME at the wildcard answers for every type. The RRSIGs keep their labels, which tell
+    ///  validators that the records were synthesized, and NSECs are not synthesized.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - the name of the query, which does not exist in the zone
+    /// * `wildcard` - the wildcard for the name
+    /// * `rtype` - the type of the query, the types as for `lookup`
+    /// * `is_secure` - If the DO bit is set on the EDNS OPT record, then return RRSIGs as well.
+    pub fn lookup_wildcard(&self,
+                           name: &Name,
+                           wildcard: &Name,
+                           rtype: RecordType,
+                           is_secure: bool,
+                           supported_algorithms: SupportedAlgorithms)
+                           -> Vec<Record> {
+        let mut records = self.lookup(wildcard, rtype, is_secure, supported_algorithms);
+        if records.is_empty() && rtype != RecordType::CNAME {
+            records = self.lookup(wildcard, RecordType::CNAME, is_secure, supported_algorithms);
+        }
+
+        records.into_iter()
+            .filter(|record| {
+                let covered = match *record.get_rdata() {
+                    RData::SIG(ref sig) => sig.get_type_covered(),
+                    _ => record.get_rr_type(),
+                };
+                covered != RecordType::NSEC && covered != RecordType::NSEC3
+            })
+            .map(|record| {
+                let mut record = record.clone();
+                record.name(name.clone());
+                record
+            })
+            .collect()
+    }
+
+    /// Returns all empty non-terminals in the zone, in canonical order.
+    ///
+    /// These are names without any records of their own, which exist because there are records
+    ///  below them, e.g. `b.example.com.` when only `a.b.example.com.` has records.
+    pub fn get_empty_non_terminals(&self) -> Vec<Name> {
+        let names: BTreeSet<&LowerName> = self.records
+            .iter()
+            .filter(|&(_, rr_set)| !rr_set.is_empty())
+            .map(|(rr_key, _)| &rr_key.name)
+            .collect();
+        let mut empty_non_terminals: BTreeSet<LowerName> = BTreeSet::new();
+
+        for name in &names {
+            if !self.origin.zone_of(name) {
+                continue;
+            }
+
+            let mut parent: Name = name.base_name();
+            while parent.num_labels() > self.origin.num_labels() {
+                let lower = LowerName::new(&parent);
+                if !names.contains(&lower) {
+                    empty_non_terminals.insert(lower);
+                }
+
+                parent = parent.base_name();
+            }
+        }
+
+        empty_non_terminals.into_iter().map(LowerName::into_name).collect()
+    }
+
     /// Return the NSEC records based on the given name
     ///
     /// # Arguments
@@ -1024,11 +2558,170 @@ impl Authority {
             })
     }
 
+    /// Return the NSEC3 records which deny the existence of the name, or of the type at the name,
+    ///  RFC 5155 section 7.2
+    ///
+    /// For a name which exists this is the NSEC3 matching it, for NODATA. Otherwise, or for a name
+    ///  left out of an Opt-Out chain, e.g. in the DS query of an insecure delegation, it is the
+    ///  closest encloser proof: the NSEC3 matching the closest encloser, the one covering the next
+    ///  closer name, and, for NXDOMAIN, the one covering the wildcard at the closest encloser.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - the lookup name for which there are no records
+    /// * `is_secure` - if true then it will return RRSIG records as well
+    pub fn get_nsec3_records(&self,
+                             name: &Name,
+                             is_secure: bool,
+                             supported_algorithms: SupportedAlgorithms)
+                             -> Vec<&Record> {
+        let nsec3_param = match self.nsec3_param {
+            Some(ref nsec3_param) => nsec3_param,
+            None => return vec![],
+        };
+        if !self.origin.zone_of(name) {
+            return vec![];
+        }
+
+        let exists = self.contains_name(name);
+        let mut rr_sets: Vec<&RecordSet> = Vec::new();
+        if let Some(matching) = self.nsec3_matching(nsec3_param, name) {
+            if exists {
+                rr_sets.push(matching);
+            }
+        }
+
+        if rr_sets.is_empty() {
+            // the closest encloser is the nearest ancestor in the chain, at least the origin
+            let mut next_closer = name.clone();
+            let mut closest_encloser = name.base_name();
+            loop {
+                if let Some(matching) = self.nsec3_matching(nsec3_param, &closest_encloser) {
+                    rr_sets.push(matching);
+                    break;
+                }
+                if closest_encloser.num_labels() <= self.origin.num_labels() {
+                    break;
+                }
+
+                next_closer = closest_encloser;
+                closest_encloser = next_closer.base_name();
+            }
+
+            rr_sets.extend(self.nsec3_covering(nsec3_param, &next_closer));
+            if !exists {
+                let wildcard = closest_encloser.prepend_label(Rc::new("*".to_string()));
+                rr_sets.extend(self.nsec3_covering(nsec3_param, &wildcard));
+            }
+        }
+
+        unique_records(rr_sets, is_secure, supported_algorithms)
+    }
+
+    /// Return the NSEC, or NSEC3, records which prove that the wildcard answers for the name,
+    ///  RFC 4035 section 3.1.3.3 and RFC 5155 section 7.2.6, see `find_wildcard`
+    ///
+    /// That is the proof that the name does not exist, the NSEC covering it, or the NSEC3
+    ///  covering the next closer name. If the wildcard has no records of the type, for NODATA,
+    ///  it is also the proof that the wildcard does not have the type, the NSEC of the wildcard,
+    ///  or the NSEC3s matching the closest encloser and the wildcard, sections 3.1.3.4 and 7.2.5.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - the name of the query, which does not exist in the zone
+    /// * `wildcard` - the wildcard for the name
+    /// * `is_nodata` - if the wildcard has no records of the type of the query
+    /// * `is_secure` - if true then it will return RRSIG records as well
+    pub fn get_wildcard_proof(&self,
+                              name: &Name,
+                              wildcard: &Name,
+                              is_nodata: bool,
+                              is_secure: bool,
+                              supported_algorithms: SupportedAlgorithms)
+                              -> Vec<&Record> {
+        let mut rr_sets: Vec<&RecordSet> = Vec::new();
+        if let Some(ref nsec3_param) = self.nsec3_param {
+            let closest_encloser = wildcard.base_name();
+            let next_closer = name.trim_to(closest_encloser.num_labels() as usize + 1);
+            rr_sets.extend(self.nsec3_covering(nsec3_param, &next_closer));
+            if is_nodata {
+                rr_sets.extend(self.nsec3_matching(nsec3_param, &closest_encloser));
+                rr_sets.extend(self.nsec3_matching(nsec3_param, wildcard));
+            }
+        } else {
+            rr_sets.extend(self.nsec_covering(name));
+            if is_nodata {
+                rr_sets.extend(self.records.get(&RrKey::new(wildcard, RecordType::NSEC)));
+            }
+        }
+
+        unique_records(rr_sets, is_secure, supported_algorithms)
+    }
+
+    /// The NSEC which covers the name, that of the greatest owner name less than the name
+    fn nsec_covering(&self, name: &Name) -> Option<&RecordSet> {
+        // the names of a signed zone have their NSEC, only glue is passed on the way back
+        self.records
+            .range(..RrKey::new(name, RecordType::NULL))
+            .rev()
+            .map(|(_, rr_set)| rr_set)
+            .find(|rr_set| rr_set.get_record_type() == RecordType::NSEC && !rr_set.is_empty())
+    }
+
+    /// The hash of the name with the parameters of the NSEC3 chain, as its NSEC3 owner name
+    fn nsec3_owner_name(&self, nsec3_param: &NSEC3PARAM, name: &Name) -> Option<Name> {
+        match nsec3_param.get_hash_algorithm()
+            .hash(nsec3_param.get_salt(), name, nsec3_param.get_iterations()) {
+            Ok(hash) => Some(hashed_owner_name(&hash, &self.origin)),
+            Err(e) => {
+                warn!("could not hash {} for nsec3: {}", name, e);
+                None
+            }
+        }
+    }
+
+    /// The NSEC3 of the name, if the name is in the NSEC3 chain
+    fn nsec3_matching(&self, nsec3_param: &NSEC3PARAM, name: &Name) -> Option<&RecordSet> {
+        self.nsec3_owner_name(nsec3_param, name)
+            .and_then(|owner| self.records.get(&RrKey::new(&owner, RecordType::NSEC3)))
+    }
+
+    /// The NSEC3 which covers the name, that with the greatest hash less than that of the name
+    ///
+    /// The chain wraps around, hashes less than the first are covered by the last NSEC3.
+    fn nsec3_covering(&self, nsec3_param: &NSEC3PARAM, name: &Name) -> Option<&RecordSet> {
+        self.nsec3_owner_name(nsec3_param, name).and_then(|owner| self.nsec3_preceding(&owner))
+    }
+
+    /// The NSEC3 before the hashed owner name in the chain, whether or not there is an NSEC3 of
+    ///  the owner name, the last NSEC3 precedes the first
+    fn nsec3_preceding(&self, owner: &Name) -> Option<&RecordSet> {
+        // the hashes are all of the same length, and base32hex keeps their order, as the canonical
+        //  order of the owner names does, see `records_from`. Past the last hash, which is made
+        //  of digits and the letters up to `v`, the search wraps around to it.
+        let is_nsec3 = |&(rr_key, rr_set): &(&RrKey, &RecordSet)| {
+            rr_key.record_type == RecordType::NSEC3 && !rr_set.is_empty()
+        };
+        let past_hashes = self.origin.prepend_label(Rc::new("w".to_string()));
+
+        self.records
+            .range(..RrKey::new(owner, RecordType::NULL))
+            .rev()
+            .find(&is_nsec3)
+            .or_else(|| {
+                self.records
+                    .range(..RrKey::new(&past_hashes, RecordType::NULL))
+                    .rev()
+                    .find(&is_nsec3)
+            })
+            .map(|(_, rr_set)| rr_set)
+    }
+
     /// (Re)generates the nsec records, increments the serial number nad signs the zone
     pub fn secure_zone(&mut self) -> DnsSecResult<()> {
         // TODO: only call nsec_zone after adds/deletes
         // needs to be called before incrementing the soa serial, to make sur IXFR works properly
-        self.nsec_zone();
+        try!(self.nsec_zone());
 
         // need to resign any records at the current serial number and bump the number.
         // first bump the serial number on the SOA, so that it is resigned with the new serial.
@@ -1038,18 +2731,67 @@ impl Authority {
         self.sign_zone()
     }
 
-    /// Creates all nsec records needed for the zone, replaces any existing records.
-    fn nsec_zone(&mut self) {
+    /// Secures the zone after a dynamic update of the records, as `secure_zone` does, but an NSEC3
+    ///  chain is only updated for the names of the update, see `update_nsec3_chain`, and only the
+    ///  RRsets of the update, the NSEC3 records which changed, and the SOA, are signed again
+    ///
+    /// The whole zone is signed and the whole chain generated if there is none with the current
+    ///  parameters, or if the update may change delegations or DNAMEs, which names are in the
+    ///  chain, or the SOA, the TTL of the NSEC3 records.
+    fn secure_zone_update(&mut self, records: &[Record]) -> DnsSecResult<()> {
+        let nsec3_param = match self.nsec3_param.clone() {
+            Some(nsec3_param) => nsec3_param,
+            None => return self.secure_zone(),
+        };
+
+        let whole_chain = records.iter().any(|rr| match rr.get_rr_type() {
+            RecordType::SOA | RecordType::NS | RecordType::DS | RecordType::DNAME |
+            RecordType::ANY => true,
+            _ => false,
+        });
+        if whole_chain || self.secure_keys.is_empty() || !self.has_nsec3_chain(&nsec3_param) {
+            return self.secure_zone();
+        }
+
+        let names: Vec<Name> = records.iter().map(|rr| rr.get_name().clone()).collect();
+        let mut changed: BTreeSet<RrKey> = try!(self.update_nsec3_chain(&nsec3_param, &names));
+        changed.extend(records.iter().map(|rr| RrKey::new(rr.get_name(), rr.get_rr_type())));
+
+        self.increment_soa_serial();
+        changed.insert(RrKey::new(&self.origin, RecordType::SOA));
+        self.sign_changed_rrsets(&changed)
+    }
+
+    /// True if the zone has an NSEC3 chain with the parameters, including the Opt-Out flag
+    fn has_nsec3_chain(&self, nsec3_param: &NSEC3PARAM) -> bool {
+        let published = RData::NSEC3PARAM(NSEC3PARAM::new(nsec3_param.get_hash_algorithm(),
+                                                           false,
+                                                           nsec3_param.get_iterations(),
+                                                           nsec3_param.get_salt().to_vec()));
+        let has_param = self.records
+            .get(&RrKey::new(&self.origin, RecordType::NSEC3PARAM))
+            .map_or(false, |rr_set| rr_set.iter().any(|r| r.get_rdata() == &published));
+
+        has_param &&
+        self.nsec3_matching(nsec3_param, &self.origin)
+            .and_then(nsec3_of)
+            .map_or(false, |nsec3| nsec3.is_opt_out() == nsec3_param.is_opt_out())
+    }
+
+    /// Creates all nsec, or nsec3, records needed for the zone, replaces any existing records.
+    fn nsec_zone(&mut self) -> DnsSecResult<()> {
         // only create nsec records for secure zones
         if self.secure_keys.is_empty() {
-            return;
+            return Ok(());
         }
-        debug!("generating nsec records: {}", self.origin);
 
-        // first remove all existing nsec records
+        // first remove all existing nsec and nsec3 records, either chain may replace the other
         let delete_keys: Vec<RrKey> = self.records
             .keys()
-            .filter(|k| k.record_type == RecordType::NSEC)
+            .filter(|k| match k.record_type {
+                RecordType::NSEC | RecordType::NSEC3 | RecordType::NSEC3PARAM => true,
+                _ => false,
+            })
             .cloned()
             .collect();
 
@@ -1057,6 +2799,11 @@ impl Authority {
             self.records.remove(&key);
         }
 
+        if let Some(nsec3_param) = self.nsec3_param.clone() {
+            return self.nsec3_zone(&nsec3_param);
+        }
+        debug!("generating nsec records: {}", self.origin);
+
         // now go through and generate the nsec records
         let ttl = self.get_minimum_ttl();
         let serial = self.get_serial();
@@ -1066,17 +2813,17 @@ impl Authority {
             let mut nsec_info: Option<(&Name, Vec<RecordType>)> = None;
             for key in self.records.keys() {
                 match nsec_info {
-                    None => nsec_info = Some((&key.name, vec![key.record_type])),
-                    Some((name, ref mut vec)) if name == &key.name => vec.push(key.record_type),
+                    None => nsec_info = Some((&*key.name, vec![key.record_type])),
+                    Some((name, ref mut vec)) if name == &*key.name => vec.push(key.record_type),
                     Some((name, vec)) => {
                         // names aren't equal, create the NSEC record
                         let mut record = Record::with(name.clone(), RecordType::NSEC, ttl);
-                        let rdata = NSEC::new(key.name.clone(), vec);
+                        let rdata = NSEC::new((*key.name).clone(), vec);
                         record.rdata(RData::NSEC(rdata));
                         records.push(record);
 
                         // new record...
-                        nsec_info = Some((&key.name, vec![key.record_type]))
+                        nsec_info = Some((&*key.name, vec![key.record_type]))
                     }
                 }
             }
@@ -1093,14 +2840,316 @@ impl Authority {
 
         // insert all the nsec records
         for record in records {
-            self.upsert(record, serial);
+            self.insert(record, serial);
+        }
+
+        Ok(())
+    }
+
+    /// Creates the NSEC3 chain of the zone and its NSEC3PARAM, RFC 5155 section 7.1
+    ///
+    /// Every name of the zone is in the chain, including empty non-terminals, but not the glue
+    ///  below delegations, nor names below a DNAME, nor, if the chain is Opt-Out, delegations
+    ///  without a DS.
+    fn nsec3_zone(&mut self, nsec3_param: &NSEC3PARAM) -> DnsSecResult<()> {
+        debug!("generating nsec3 records: {}", self.origin);
+        let ttl = self.get_minimum_ttl();
+        let serial = self.get_serial();
+        let hash_algorithm = nsec3_param.get_hash_algorithm();
+        let iterations = nsec3_param.get_iterations();
+        let salt = nsec3_param.get_salt();
+        let opt_out = nsec3_param.is_opt_out();
+
+        // the NSEC3PARAM is at the apex, and so in its type bit map, the flags are always zero
+        let mut record = Record::with(self.origin.clone(), RecordType::NSEC3PARAM, ttl);
+        record.rdata(RData::NSEC3PARAM(NSEC3PARAM::new(hash_algorithm,
+                                                       false,
+                                                       iterations,
+                                                       salt.to_vec())));
+        self.insert(record, serial);
+
+        let mut names: BTreeMap<Name, Vec<RecordType>> = BTreeMap::new();
+        for (key, rr_set) in self.records.iter() {
+            if rr_set.is_empty() || !self.origin.zone_of(&key.name) ||
+               self.is_occluded(&key.name) {
+                continue;
+            }
+
+            names.entry((*key.name).clone()).or_insert_with(Vec::new).push(key.record_type);
+        }
+
+        for name in self.get_empty_non_terminals() {
+            if !self.is_occluded(&name) {
+                names.insert(name, vec![]);
+            }
+        }
+
+        let mut hashes: Vec<(Vec<u8>, Vec<RecordType>)> = Vec::with_capacity(names.len());
+        for (name, mut types) in names {
+            if opt_out && name != self.origin && types.contains(&RecordType::NS) &&
+               !types.contains(&RecordType::DS) {
+                continue;
+            }
+
+            // all the records of the zone are signed, those of empty non-terminals are none
+            if !types.is_empty() {
+                types.push(RecordType::RRSIG);
+            }
+
+            hashes.push((try!(hash_algorithm.hash(salt, &name, iterations)), types));
+        }
+        hashes.sort_by(|a, b| a.0.cmp(&b.0));
+
+        // each NSEC3 is followed by the next hash, the last by the first
+        let mut records: Vec<Record> = Vec::with_capacity(hashes.len());
+        for (i, &(ref hash, ref types)) in hashes.iter().enumerate() {
+            let next_hash = hashes[(i + 1) % hashes.len()].0.clone();
+
+            records.push(nsec3_record(nsec3_param,
+                                      hashed_owner_name(hash, &self.origin),
+                                      next_hash,
+                                      types.clone(),
+                                      ttl));
+        }
+
+        for record in records {
+            self.insert(record, serial);
+        }
+
+        Ok(())
+    }
+
+    /// Updates the NSEC3 chain for changes to the records of the names, rather than generating
+    ///  it again, RFC 5155 section 7.1
+    ///
+    /// A name which is added to the chain is linked in after the NSEC3 which covered it, one which
+    ///  is removed is unlinked from the NSEC3 before it, and the type bit maps of the others are
+    ///  updated. Besides the names themselves, the names above them may have become, or may no
+    ///  longer be, empty non-terminals.
+    ///
+    /// # Return value
+    ///
+    /// The keys of the NSEC3 RRsets which were added or changed, to be signed again
+    fn update_nsec3_chain(&mut self,
+                          nsec3_param: &NSEC3PARAM,
+                          names: &[Name])
+                          -> DnsSecResult<BTreeSet<RrKey>> {
+        debug!("updating nsec3 records: {}", self.origin);
+        let ttl = self.get_minimum_ttl();
+        let serial = self.get_serial();
+        let mut changed = BTreeSet::new();
+
+        for (name, types) in self.nsec3_names(nsec3_param, names) {
+            let hash = try!(nsec3_param.get_hash_algorithm()
+                .hash(nsec3_param.get_salt(), &name, nsec3_param.get_iterations()));
+            let owner = hashed_owner_name(&hash, &self.origin);
+            let key = RrKey::new(&owner, RecordType::NSEC3);
+            let current = self.records.get(&key).and_then(nsec3_of);
+
+            match (current, types) {
+                (Some(current), Some(types)) => {
+                    if current.get_type_bit_maps() != &types[..] {
+                        let next = current.get_next_hashed_owner_name().to_vec();
+                        let record = nsec3_record(nsec3_param, owner, next, types, ttl);
+                        changed.insert(self.put_nsec3(record, serial));
+                    }
+                }
+                (Some(current), None) => {
+                    self.records.remove(&key);
+
+                    // the NSEC3 before it now covers the name, up to the NSEC3 after it
+                    let preceding = self.nsec3_before(&owner);
+                    if let Some((preceding_owner, preceding)) = preceding {
+                        let next = current.get_next_hashed_owner_name().to_vec();
+                        let types = preceding.get_type_bit_maps().to_vec();
+                        let record = nsec3_record(nsec3_param, preceding_owner, next, types, ttl);
+                        changed.insert(self.put_nsec3(record, serial));
+                    }
+                }
+                (None, Some(types)) => {
+                    // the NSEC3 which covered the name is now followed by it
+                    let preceding = self.nsec3_before(&owner);
+                    let next = if let Some((preceding_owner, preceding)) = preceding {
+                        let preceding_types = preceding.get_type_bit_maps().to_vec();
+                        let record = nsec3_record(nsec3_param,
+                                                  preceding_owner,
+                                                  hash.clone(),
+                                                  preceding_types,
+                                                  ttl);
+                        changed.insert(self.put_nsec3(record, serial));
+                        preceding.get_next_hashed_owner_name().to_vec()
+                    } else {
+                        hash.clone()
+                    };
+
+                    let record = nsec3_record(nsec3_param, owner, next, types, ttl);
+                    changed.insert(self.put_nsec3(record, serial));
+                }
+                (None, None) => (),
+            }
+        }
+
+        Ok(changed)
+    }
+
+    /// The owner name and record data of the NSEC3 before the owner name, see `nsec3_preceding`
+    fn nsec3_before(&self, owner: &Name) -> Option<(Name, NSEC3)> {
+        self.nsec3_preceding(owner)
+            .and_then(|rr_set| nsec3_of(rr_set).map(|nsec3| (rr_set.get_name().clone(), nsec3)))
+    }
+
+    /// Replaces the NSEC3 at its owner name, if any, with the record, returns the key of its RRset
+    fn put_nsec3(&mut self, record: Record, serial: u32) -> RrKey {
+        let key = RrKey::new(record.get_name(), RecordType::NSEC3);
+        self.records.remove(&key);
+        self.insert(record, serial);
+        key
+    }
+
+    /// The type bit maps of the names, and their ancestors in the zone, in the NSEC3 chain, or None
+    ///  for those not in the chain, as `nsec3_zone` generates them
+    fn nsec3_names(&self,
+                   nsec3_param: &NSEC3PARAM,
+                   names: &[Name])
+                   -> Vec<(Name, Option<Vec<RecordType>>)> {
+        // the types at each name, and whether there are names below it
+        let mut affected: BTreeMap<Name, (Vec<RecordType>, bool)> = BTreeMap::new();
+        for name in names {
+            if !self.origin.zone_of(name) {
+                continue;
+            }
+
+            let mut name = name.clone();
+            while name.num_labels() > self.origin.num_labels() {
+                let base_name = name.base_name();
+                affected.insert(name, (Vec::new(), false));
+                name = base_name;
+            }
+            affected.insert(name, (Vec::new(), false));
+        }
+
+        for (key, rr_set) in self.records.iter() {
+            if rr_set.is_empty() || key.record_type == RecordType::NSEC ||
+               key.record_type == RecordType::NSEC3 {
+                continue;
+            }
+
+            for (name, &mut (ref mut types, ref mut has_names_below)) in affected.iter_mut() {
+                if *name == *key.name {
+                    types.push(key.record_type);
+                } else if name.zone_of(&key.name) {
+                    *has_names_below = true;
+                }
+            }
+        }
+
+        affected.into_iter()
+            .map(|(name, (mut types, has_names_below))| {
+                let insecure_delegation = nsec3_param.is_opt_out() && name != self.origin &&
+                                          types.contains(&RecordType::NS) &&
+                                          !types.contains(&RecordType::DS);
+                if insecure_delegation || self.is_occluded(&name) ||
+                   (types.is_empty() && !has_names_below) {
+                    return (name, None);
+                }
+
+                if !types.is_empty() {
+                    types.push(RecordType::RRSIG);
+                }
+                (name, Some(types))
+            })
+            .collect()
+    }
+
+    /// The delegation, NS, or DNAME record set which occludes the name, None if it is not
+    ///  occluded
+    ///
+    /// Records below a zone cut are not authoritative, and those below a DNAME are redirected to
+    ///  its target, RFC 6672 section 2.3, so neither are answered even though they are stored,
+    ///  e.g. when added by an update. Glue below a delegation is only given in referrals. An NS
+    ///  at the apex is not a zone cut, but a DNAME at the apex occludes the rest of the zone.
+    pub fn get_occlusion(&self, name: &Name) -> Option<&RecordSet> {
+        if !self.origin.zone_of(name) {
+            return None;
+        }
+
+        // the DNAME, or NS, closest to the apex occludes all the names below it
+        let mut ancestors: Vec<Name> = Vec::new();
+        let mut ancestor = name.base_name();
+        while ancestor.num_labels() > self.origin.num_labels() {
+            let parent = ancestor.base_name();
+            ancestors.push(ancestor);
+            ancestor = parent;
+        }
+
+        if *name != self.origin {
+            if let Some(dname) = self.get_cut(&self.origin, RecordType::DNAME) {
+                return Some(dname);
+            }
+        }
+
+        for ancestor in ancestors.iter().rev() {
+            if let Some(cut) = self.get_cut(ancestor, RecordType::DNAME)
+                .or_else(|| self.get_cut(ancestor, RecordType::NS)) {
+                return Some(cut);
+            }
+        }
+
+        None
+    }
+
+    /// True if the name is below a delegation or a DNAME of the zone, see `get_occlusion`
+    pub fn is_occluded(&self, name: &Name) -> bool {
+        self.get_occlusion(name).is_some()
+    }
+
+    /// The non-empty record set of the type at the name
+    fn get_cut(&self, name: &Name, record_type: RecordType) -> Option<&RecordSet> {
+        self.records.get(&RrKey::new(name, record_type)).and_then(|rr_set| if rr_set.is_empty() {
+            None
+        } else {
+            Some(rr_set)
+        })
+    }
+
+    /// Warns of an update which adds records that are not answered, or hides existing ones
+    fn warn_occluded(&self, record: &Record) {
+        let name = record.get_name();
+        if let Some(occlusion) = self.get_occlusion(name) {
+            let is_glue = occlusion.get_record_type() == RecordType::NS &&
+                          (record.get_rr_type() == RecordType::A ||
+                           record.get_rr_type() == RecordType::AAAA);
+            if !is_glue {
+                warn!("update added {} {:?} occluded by {} {:?}",
+                      name,
+                      record.get_rr_type(),
+                      occlusion.get_name(),
+                      occlusion.get_record_type());
+            }
+            return;
+        }
+
+        let is_cut = record.get_rr_type() == RecordType::DNAME ||
+                     (record.get_rr_type() == RecordType::NS && *name != self.origin);
+        if is_cut &&
+           self.records
+            .iter()
+            .any(|(key, rr_set)| {
+                let is_glue = record.get_rr_type() == RecordType::NS &&
+                              (key.record_type == RecordType::A ||
+                               key.record_type == RecordType::AAAA);
+                !rr_set.is_empty() && !is_glue && *key.name != *name && name.zone_of(&key.name)
+            }) {
+            warn!("update added {} {:?} which occludes the records below it",
+                  name,
+                  record.get_rr_type());
         }
     }
 
     /// Signs any records in the zone that have serial numbers greater than or equal to `serial`
     fn sign_zone(&mut self) -> DnsSecResult<()> {
         debug!("signing zone: {}", self.origin);
-        let inception = UTC::now();
+        let inception = self.clock.now();
         let zone_ttl = self.get_minimum_ttl();
 
         // TODO: should this be an error?
@@ -1108,80 +3157,201 @@ impl Authority {
             warn!("attempt to sign_zone for dnssec, but no keys available!")
         }
 
-        for rr_set in self.records.iter_mut().filter_map(|(_, rr_set)| {
-            // do not sign zone DNSKEY's that's the job of the parent zone
-            if rr_set.get_record_type() == RecordType::DNSKEY {
-                return None;
-            }
-            rr_set.get_rrsigs().is_empty();
-            Some(rr_set)
-        }) {
-
-            debug!("signing rr_set: {}", rr_set.get_name());
-            rr_set.clear_rrsigs();
-            let rrsig_temp = Record::with(rr_set.get_name().clone(), RecordType::RRSIG, zone_ttl);
-
-            for signer in self.secure_keys.iter() {
-                let expiration = inception + signer.get_sig_duration();
-
-                let hash =
-                    signer.hash_rrset(rr_set.get_name(),
-                                      self.class,
-                                      rr_set.get_name().num_labels(),
-                                      rr_set.get_record_type(),
-                                      signer.get_algorithm(),
-                                      rr_set.get_ttl(),
-                                      expiration.timestamp() as u32,
-                                      inception.timestamp() as u32,
-                                      try!(signer.calculate_key_tag()),
-                                      signer.get_signer_name(),
-                                      // TODO: this is a nasty clone... the issue is that the vec
-                                      //  from get_records is of Vec<&R>, but we really want &[R]
-                                      &rr_set.get_records(false, SupportedAlgorithms::new())
-                                          .into_iter()
-                                          .cloned()
-                                          .collect::<Vec<Record>>());
-
-                // TODO, maybe chain these with some ETL operations instead?
-                if hash.is_err() {
-                    error!("could not hash rrset to sign: {}", hash.unwrap_err());
-                    continue;
-                }
-                let hash = hash.unwrap();
+        // this includes the DNSKEY rrset, which must carry the keys of every signer of the zone,
+        //  see `import_zone_signing_key()`
+        self.sign_rrsets(inception, zone_ttl)
+    }
 
-                let signature = signer.sign(&hash);
+    /// Signs the RRsets of the keys again, those which are no longer in the zone are skipped
+    fn sign_changed_rrsets(&mut self, keys: &BTreeSet<RrKey>) -> DnsSecResult<()> {
+        debug!("signing {} changed rrsets of zone: {}", keys.len(), self.origin);
+        let inception = self.clock.now();
+        let zone_ttl = self.get_minimum_ttl();
 
-                if signature.is_err() {
-                    error!("could not sign hash of rrset: {}", signature.unwrap_err());
-                    continue;
-                }
-                let signature = signature.unwrap();
-
-                let mut rrsig = rrsig_temp.clone();
-                rrsig.rdata(RData::SIG(SIG::new(// type_covered: RecordType,
-                                                rr_set.get_record_type(),
-                                                // algorithm: Algorithm,
-                                                signer.get_algorithm(),
-                                                // num_labels: u8,
-                                                rr_set.get_name().num_labels(),
-                                                // original_ttl: u32,
-                                                rr_set.get_ttl(),
-                                                // sig_expiration: u32,
-                                                expiration.timestamp() as u32,
-                                                // sig_inception: u32,
-                                                inception.timestamp() as u32,
-                                                // key_tag: u16,
-                                                try!(signer.calculate_key_tag()),
-                                                // signer_name: Name,
-                                                signer.get_signer_name().clone(),
-                                                // sig: Vec<u8>
-                                                signature)));
-
-                rr_set.insert_rrsig(rrsig);
-                debug!("signed rr_set: {}", rr_set.get_name());
+        for key in keys {
+            if let Some(rr_set) = self.records.get_mut(key) {
+                try!(sign_rrset(rr_set, &self.secure_keys, self.class, inception, zone_ttl));
             }
         }
 
         Ok(())
     }
+
+    /// Signs each RRset with all the keys, sequentially
+    #[cfg(not(feature = "parallel-signing"))]
+    fn sign_rrsets(&mut self, inception: u32, zone_ttl: u32) -> DnsSecResult<()> {
+        for rr_set in self.records.values_mut() {
+            try!(sign_rrset(rr_set, &self.secure_keys, self.class, inception, zone_ttl));
+        }
+
+        Ok(())
+    }
+
+    /// Signs each RRset with all the keys, across `signing_threads` threads
+    ///
+    /// Each RRset is signed by exactly one thread, with the keys in order, so the RRSIGs are the
+    ///  same as if the zone were signed sequentially.
+    #[cfg(feature = "parallel-signing")]
+    fn sign_rrsets(&mut self, inception: u32, zone_ttl: u32) -> DnsSecResult<()> {
+        let mut rr_sets: Vec<&mut RecordSet> = self.records.values_mut().collect();
+        let threads = cmp::max(self.signing_threads, 1);
+        let chunk_size = cmp::max((rr_sets.len() + threads - 1) / threads,
+                                  MIN_RRSETS_PER_SIGNING_THREAD);
+
+        let secure_keys = &self.secure_keys;
+        let class = self.class;
+        let results: Vec<DnsSecResult<()>> = crossbeam::scope(|scope| {
+            let handles = rr_sets.chunks_mut(chunk_size)
+                .map(|chunk| {
+                    scope.spawn(move || -> DnsSecResult<()> {
+                        for rr_set in chunk.iter_mut() {
+                            try!(sign_rrset(rr_set, secure_keys, class, inception, zone_ttl));
+                        }
+
+                        Ok(())
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            handles.into_iter().map(|handle| handle.join()).collect()
+        });
+
+        for result in results {
+            try!(result);
+        }
+
+        Ok(())
+    }
+}
+
+/// The records of the record sets, each only once, as one NSEC3 may both match and cover, or
+///  cover more than one of the names of a proof
+fn unique_records<'a>(rr_sets: Vec<&'a RecordSet>,
+                      is_secure: bool,
+                      supported_algorithms: SupportedAlgorithms)
+                      -> Vec<&'a Record> {
+    let mut unique: Vec<&RecordSet> = Vec::with_capacity(rr_sets.len());
+    for rr_set in rr_sets {
+        if !unique.iter().any(|u| u.get_name() == rr_set.get_name()) {
+            unique.push(rr_set);
+        }
+    }
+
+    unique.into_iter()
+        .flat_map(|rr_set| rr_set.get_records(is_secure, supported_algorithms).into_iter())
+        .collect()
+}
+
+/// The NSEC3 of the owner name, with the parameters of the chain
+fn nsec3_record(nsec3_param: &NSEC3PARAM,
+                owner: Name,
+                next_hashed_owner_name: Vec<u8>,
+                type_bit_maps: Vec<RecordType>,
+                ttl: u32)
+                -> Record {
+    let mut record = Record::with(owner, RecordType::NSEC3, ttl);
+    record.rdata(RData::NSEC3(NSEC3::new(nsec3_param.get_hash_algorithm(),
+                                         nsec3_param.is_opt_out(),
+                                         nsec3_param.get_iterations(),
+                                         nsec3_param.get_salt().to_vec(),
+                                         next_hashed_owner_name,
+                                         type_bit_maps)));
+    record
+}
+
+/// The NSEC3 record data of the RRset, there is only ever one at an owner name
+fn nsec3_of(rr_set: &RecordSet) -> Option<NSEC3> {
+    rr_set.iter()
+        .filter_map(|r| if let &RData::NSEC3(ref nsec3) = r.get_rdata() {
+            Some(nsec3.clone())
+        } else {
+            None
+        })
+        .next()
+}
+
+/// One thread per CPU
+#[cfg(feature = "parallel-signing")]
+fn default_signing_threads() -> usize {
+    num_cpus::get()
+}
+
+/// Signing is sequential without the parallel-signing feature
+#[cfg(not(feature = "parallel-signing"))]
+fn default_signing_threads() -> usize {
+    1
+}
+
+/// Replaces the RRSIGs of the RRset with a new one for each of the keys, in order
+fn sign_rrset(rr_set: &mut RecordSet,
+              secure_keys: &[Arc<Signer>],
+              class: DNSClass,
+              inception: u32,
+              zone_ttl: u32)
+              -> DnsSecResult<()> {
+    debug!("signing rr_set: {}", rr_set.get_name());
+    rr_set.clear_rrsigs();
+    let rrsig_temp = Record::with(rr_set.get_name().clone(), RecordType::RRSIG, zone_ttl);
+
+    for signer in secure_keys.iter() {
+        let expiration = inception.wrapping_add(signer.get_sig_duration().num_seconds() as u32);
+
+        let hash =
+            signer.hash_rrset(rr_set.get_name(),
+                              class,
+                              rr_set.get_name().num_labels(),
+                              rr_set.get_record_type(),
+                              signer.get_algorithm(),
+                              rr_set.get_ttl(),
+                              expiration,
+                              inception,
+                              try!(signer.calculate_key_tag()),
+                              signer.get_signer_name(),
+                              // TODO: this is a nasty clone... the issue is that the vec
+                              //  from get_records is of Vec<&R>, but we really want &[R]
+                              &rr_set.get_records(false, SupportedAlgorithms::new())
+                                  .into_iter()
+                                  .cloned()
+                                  .collect::<Vec<Record>>());
+
+        // TODO, maybe chain these with some ETL operations instead?
+        if hash.is_err() {
+            error!("could not hash rrset to sign: {}", hash.unwrap_err());
+            continue;
+        }
+        let hash = hash.unwrap();
+
+        let signature = signer.sign(&hash);
+
+        if signature.is_err() {
+            error!("could not sign hash of rrset: {}", signature.unwrap_err());
+            continue;
+        }
+        let signature = signature.unwrap();
+
+        let mut rrsig = rrsig_temp.clone();
+        rrsig.rdata(RData::SIG(SIG::new(// type_covered: RecordType,
+                                        rr_set.get_record_type(),
+                                        // algorithm: Algorithm,
+                                        signer.get_algorithm(),
+                                        // num_labels: u8,
+                                        rr_set.get_name().num_labels(),
+                                        // original_ttl: u32,
+                                        rr_set.get_ttl(),
+                                        // sig_expiration: u32,
+                                        expiration,
+                                        // sig_inception: u32,
+                                        inception,
+                                        // key_tag: u16,
+                                        try!(signer.calculate_key_tag()),
+                                        // signer_name: Name,
+                                        signer.get_signer_name().clone(),
+                                        // sig: Vec<u8>
+                                        signature)));
+
+        rr_set.insert_rrsig(rrsig);
+        debug!("signed rr_set: {}", rr_set.get_name());
+    }
+
+    Ok(())
 }
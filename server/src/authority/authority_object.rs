@@ -0,0 +1,138 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use trust_dns::error::DnsSecResult;
+use trust_dns::op::{Message, Query};
+use trust_dns::rr::{Name, Record, RecordType};
+use trust_dns::rr::dnssec::SupportedAlgorithms;
+
+use authority::{SharedAuthority, UpdateResult, ZoneType};
+
+/// The authority of a zone of the `Catalog`, whatever stores its records
+///
+/// An `Authority` keeps the records of the zone in memory, loaded from a zone file, the journal
+///  or transferred from a primary, see `SharedAuthority`. Other backends may be file or database
+///  backed, or compute the answers as they are queried. The methods take `&self`, as the catalog
+///  answers through a shared reference, a backend which changes synchronizes itself; records are
+///  returned by value, so that they need not be stored.
+///
+/// The catalog answers a query for a zone of another backend with the records of `search`, or if
+///  there are none with NXDOMAIN, or NODATA if `contains_name`, and the SOA record of the zone,
+///  or with SERVFAIL if it is not `is_available`, see `KeyValueAuthority` for an external store.
+///  Occlusion, wildcards, NSEC and NSEC3 denials, the chasing of CNAMEs, the CHAIN of trust,
+///  zone transfers, the refreshes of Slave zones, NOTIFY, reloads, `listen_addrs` and the
+///  statistics beyond the queries and NXDOMAINs are only for zones in memory, see
+///  `get_in_memory`. `named` refuses the options and the records of a zone in a store which
+///  need them, see `ZoneConfig::get_in_memory_options`.
+pub trait AuthorityObject {
+    /// The name of the zone
+    fn get_origin(&self) -> Name;
+
+    /// Whether the zone is a Master or a Slave, only Master zones are updated
+    fn get_zone_type(&self) -> ZoneType;
+
+    /// The records of the name and type, with their RRSIGs if `is_secure`, ANY for all of them
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - the owner of the records
+    /// * `rtype` - the type of the records, the SOA is returned for any name of the zone
+    /// * `is_secure` - if true, the RRSIGs of the records are returned as well
+    /// * `supported_algorithms` - the algorithms of the RRSIGs the requestor supports
+    fn lookup(&self,
+              name: &Name,
+              rtype: RecordType,
+              is_secure: bool,
+              supported_algorithms: SupportedAlgorithms)
+              -> Vec<Record>;
+
+    /// The records answering the query, by default those of `lookup`
+    fn search(&self,
+              query: &Query,
+              is_secure: bool,
+              supported_algorithms: SupportedAlgorithms)
+              -> Vec<Record> {
+        self.lookup(query.get_name(),
+                    query.get_query_type(),
+                    is_secure,
+                    supported_algorithms)
+    }
+
+    /// True if the name has records of any type, by default if `lookup` returns any for ANY
+    fn contains_name(&self, name: &Name) -> bool {
+        !self.lookup(name, RecordType::ANY, false, SupportedAlgorithms::new()).is_empty()
+    }
+
+    /// Applies a dynamic update to the zone, RFC 2136, which the backend authorizes
+    ///
+    /// # Return value
+    ///
+    /// true if the zone changed, otherwise the response code of the failure
+    fn update(&self, update: &Message) -> UpdateResult<bool>;
+
+    /// Signs the zone with its keys, after it is loaded or changed
+    fn secure_zone(&self) -> DnsSecResult<()>;
+
+    /// False if the backend could not read the records of the last `search`, e.g. its store is
+    ///  unreachable, the query is then answered with SERVFAIL rather than as if there were none
+    fn is_available(&self) -> bool {
+        true
+    }
+
+    /// The zone if it is an `Authority` in memory, None for the other backends
+    fn get_in_memory(&self) -> Option<&SharedAuthority> {
+        None
+    }
+}
+
+impl AuthorityObject for SharedAuthority {
+    fn get_origin(&self) -> Name {
+        self.read().get_origin().clone()
+    }
+
+    fn get_zone_type(&self) -> ZoneType {
+        self.read().get_zone_type()
+    }
+
+    fn lookup(&self,
+              name: &Name,
+              rtype: RecordType,
+              is_secure: bool,
+              supported_algorithms: SupportedAlgorithms)
+              -> Vec<Record> {
+        let authority = self.read();
+        let records: Vec<Record> = authority.lookup(name, rtype, is_secure, supported_algorithms)
+            .into_iter()
+            .cloned()
+            .collect();
+        records
+    }
+
+    fn search(&self,
+              query: &Query,
+              is_secure: bool,
+              supported_algorithms: SupportedAlgorithms)
+              -> Vec<Record> {
+        self.read().search(query, is_secure, supported_algorithms)
+    }
+
+    fn contains_name(&self, name: &Name) -> bool {
+        self.read().contains_name(name)
+    }
+
+    fn update(&self, update: &Message) -> UpdateResult<bool> {
+        self.write().update(update)
+    }
+
+    fn secure_zone(&self) -> DnsSecResult<()> {
+        self.write().secure_zone()
+    }
+
+    fn get_in_memory(&self) -> Option<&SharedAuthority> {
+        Some(self)
+    }
+}
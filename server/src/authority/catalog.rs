@@ -16,29 +16,74 @@
 // TODO, I've implemented this as a seperate entity from the cache, but I wonder if the cache
 //  should be the only "front-end" for lookups, where if that misses, then we go to the catalog
 //  then, if requested, do a recursive lookup... i.e. the catalog would only point to files.
-use std::collections::HashMap;
-use std::sync::RwLock;
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::u16;
 
+use trust_dns::clock::{Clock, SystemClock};
+use trust_dns::error::{ParseErrorKind, ParseResult};
 use trust_dns::op::{Edns, Message, MessageType, OpCode, Query, UpdateMessage, RequestHandler,
                     ResponseCode};
-use trust_dns::rr::{Name, RecordType};
-use trust_dns::rr::dnssec::{Algorithm, SupportedAlgorithms};
+use trust_dns::rr::{Name, RData, Record, RecordSet, RecordType};
+use trust_dns::rr::dnssec::{split_tsig, Algorithm, DnsSecErrorKind, DnsSecResult,
+                            SupportedAlgorithms, TSigner, TsigChain};
+use trust_dns::rr::rdata::TSIG;
 use trust_dns::rr::rdata::opt::{EdnsCode, EdnsOption};
+use trust_dns::serialize::binary::{BinEncoder, BinSerializable};
 
-use authority::{Authority, ZoneType};
+use authority::{AliasRequest, Authority, AuthorityObject, ChangeListener, ChangeSubscription,
+                ForwardAuthority, InboundTransfer, NotifyRequest, SharedAuthority, TransferRequest,
+                ZoneStats, ZoneType};
+use authority::stats::{ForwardStats, ZoneCounters};
+
+/// The highest EDNS version implemented, requests for greater versions receive BADVERS
+const EDNS_VERSION: u8 = 0;
+
+/// The records of a zone transfer are split over messages of at most this many bytes
+const MAX_TRANSFER_MESSAGE_LEN: usize = 16384;
+
+/// The room left in a response for the OPT record, which is added once it is answered, with the
+///  DAU, DHU and client subnet options
+const ADDITIONAL_OPT_ALLOWANCE: usize = 64;
+
+/// The most CNAMEs followed for an answer, the client follows the rest of a longer chain
+pub const MAX_CNAME_CHAIN: usize = 8;
 
 /// Set of authorities, zones, available to this server.
+///
+/// The zones are `AuthorityObject`s, an `Authority` in memory, see `upsert`, or any other backend,
+///  see `upsert_object`.
 pub struct Catalog {
-    authorities: HashMap<Name, RwLock<Authority>>,
+    authorities: HashMap<Name, Box<AuthorityObject>>,
+    forwarders: HashMap<Name, Arc<ForwardAuthority>>,
+    counters: HashMap<Name, Mutex<ZoneCounters>>,
+    op_code_handlers: HashMap<OpCode, Box<RequestHandler + Send + Sync>>,
+    /// the keys shared with clients and peers, TSIG, by name
+    tsig_keys: HashMap<Name, Arc<TSigner>>,
+    /// the time the zones were last loaded, see `ZoneStats::get_last_loaded`
+    clock: Arc<Clock>,
 }
 
 impl RequestHandler for Catalog {
     /// Determine's what needs to happen given the type of request, i.e. Query or Update.
     ///
+    /// The answers may come from any zone, whatever its listen addresses, see `handle_request_on`
+    ///  for the requests received by a listener.
+    ///
     /// # Arguments
     ///
     /// * `request` - the requested action to perform.
     fn handle_request(&self, request: &Message) -> Message {
+        self.respond(request, None, Some(request.get_max_payload()))
+    }
+}
+
+impl Catalog {
+    /// Answers the request, from the zones served on the local address if it is known, within
+    ///  the size the transport carries
+    fn respond(&self, request: &Message, local: Option<IpAddr>, max_size: Option<u16>) -> Message {
         info!("request id: {} type: {:?} op_code: {:?}",
               request.get_id(),
               request.get_message_type(),
@@ -49,27 +94,69 @@ impl RequestHandler for Catalog {
 
         // check if it's edns
         if let Some(req_edns) = request.get_edns() {
-            let mut response = Message::new();
-            response.id(request.get_id());
-
             let mut resp_edns: Edns = Edns::new();
 
-            // check our version against the request
-            // TODO: what version are we?
-            let our_version = 0;
+            // check our version against the request, the response always carries our version
+            //  so that the requestor learns what we implement (RFC 6891 section 6.1.3)
             resp_edns.set_dnssec_ok(true);
             resp_edns.set_max_payload(if req_edns.get_max_payload() < 512 {
                 512
             } else {
                 req_edns.get_max_payload()
             });
-            resp_edns.set_version(our_version);
+            resp_edns.set_version(EDNS_VERSION);
 
-            if req_edns.get_version() > our_version {
+            if req_edns.get_version() > EDNS_VERSION {
                 warn!("request edns version greater than {}: {}",
-                      our_version,
+                      EDNS_VERSION,
                       req_edns.get_version());
+
+                let mut response = Message::new();
+                response.id(request.get_id())
+                    .message_type(MessageType::Response)
+                    .op_code(request.get_op_code())
+                    .recursion_desired(request.is_recursion_desired())
+                    .checking_disabled(request.is_checking_disabled());
+                for query in request.get_queries() {
+                    response.add_query(query.clone());
+                }
+
+                // the extended rcode is stored in the edns, so it must be set first
+                response.set_edns(resp_edns);
                 response.response_code(ResponseCode::BADVERS);
+                return response;
+            }
+
+            // RFC 7871 section 7.1.2, a malformed client subnet is answered with FORMERR
+            if let Some(&EdnsOption::Unknown(..)) = req_edns.get_option(&EdnsCode::Subnet) {
+                warn!("malformed client subnet in request id: {}", request.get_id());
+
+                let mut response = Message::error_msg(request.get_id(),
+                                                      request.get_op_code(),
+                                                      ResponseCode::FormErr);
+                response.add_queries(request.get_queries().iter().cloned());
+                response.set_edns(resp_edns);
+                return response;
+            }
+
+            // RFC 7901 section 5.4, a malformed Closest Trust Point, or one which is not an
+            //  ancestor of the name queried, is answered with FORMERR
+            let is_bad_chain = match req_edns.get_option(&EdnsCode::Chain) {
+                Some(&EdnsOption::Chain(ref trust_point)) => {
+                    !request.get_queries()
+                        .iter()
+                        .all(|query| trust_point.zone_of(query.get_name()))
+                }
+                Some(_) => true,
+                None => false,
+            };
+            if is_bad_chain {
+                warn!("bad CHAIN option in request id: {}", request.get_id());
+
+                let mut response = Message::error_msg(request.get_id(),
+                                                      request.get_op_code(),
+                                                      ResponseCode::FormErr);
+                response.add_queries(request.get_queries().iter().cloned());
                 response.set_edns(resp_edns);
                 return response;
             }
@@ -87,7 +174,7 @@ impl RequestHandler for Catalog {
             MessageType::Query => {
                 match request.get_op_code() {
                     OpCode::Query => {
-                        let response = self.lookup(&request);
+                        let response = self.lookup_on(&request, local, max_size);
                         debug!("query response: {:?}", response);
                         response
                         // TODO, handle recursion here or in the catalog?
@@ -98,11 +185,24 @@ impl RequestHandler for Catalog {
                         debug!("update response: {:?}", response);
                         response
                     }
-                    c @ _ => {
-                        error!("unimplemented op_code: {:?}", c);
-                        Message::error_msg(request.get_id(),
-                                           request.get_op_code(),
-                                           ResponseCode::NotImp)
+                    op_code => {
+                        if let Some(handler) = self.op_code_handlers.get(&op_code) {
+                            debug!("op_code: {:?} has a registered handler", op_code);
+                            handler.handle_request(request)
+                        } else if op_code == OpCode::Notify {
+                            let response = self.notify(request);
+                            debug!("notify response: {:?}", response);
+                            response
+                        } else {
+                            // STATUS, DSO without a session, e.g. over UDP, and anything unassigned
+                            //  or obsolete, e.g. IQUERY
+                            warn!("unimplemented op_code: {:?}", op_code);
+                            let mut response = Message::error_msg(request.get_id(),
+                                                                  op_code,
+                                                                  ResponseCode::NotImp);
+                            response.add_queries(request.get_queries().iter().cloned());
+                            response
+                        }
                     }
                 }
             }
@@ -129,6 +229,21 @@ impl RequestHandler for Catalog {
             resp_edns.set_option(dau);
             resp_edns.set_option(dhu);
 
+            // RFC 7871 section 7.2.1, the client subnet is echoed, with the scope of the answer set
+            //  by a handler, otherwise zero as the answers of the authorities are the same for any
+            //  client
+            if let Some(subnet) = request.get_client_subnet() {
+                let mut subnet = subnet.clone();
+                subnet.set_scope_prefix(response.get_client_subnet()
+                    .map_or(0, |subnet| subnet.get_scope_prefix()));
+                resp_edns.set_option(EdnsOption::ClientSubnet(subnet));
+            }
+
+            // RFC 7901 section 5.3, the start of the chain of trust which was added, if any
+            if let Some(trust_point) = response.get_chain_trust_point() {
+                resp_edns.set_option(EdnsOption::Chain(trust_point.clone()));
+            }
+
             response.set_edns(resp_edns);
             // TODO: if DNSSec supported, sign the package with SIG0
             // get this servers private key ideally use pkcs11
@@ -137,15 +252,215 @@ impl RequestHandler for Catalog {
 
         response
     }
-}
 
-impl Catalog {
     pub fn new() -> Self {
-        Catalog { authorities: HashMap::new() }
+        Catalog {
+            authorities: HashMap::new(),
+            forwarders: HashMap::new(),
+            counters: HashMap::new(),
+            op_code_handlers: HashMap::new(),
+            tsig_keys: HashMap::new(),
+            clock: Arc::new(SystemClock),
+        }
     }
 
+    /// Replaces the clock of the time the zones are loaded, the system clock by default
+    pub fn set_clock(&mut self, clock: Arc<Clock>) {
+        self.clock = clock;
+    }
+
+    /// Adds a key shared with clients, TSIG of RFC 8945, replacing any previous one of the name
+    ///
+    /// Requests signed with the key are verified with it, see `verify_tsig`, and match the
+    ///  `TransferRule`s of its name.
+    pub fn add_tsig_key(&mut self, tsigner: Arc<TSigner>) {
+        self.tsig_keys.insert(tsigner.get_key_name().clone(), tsigner);
+    }
+
+    /// The key shared with clients of the name, if there is one
+    pub fn get_tsig_key(&self, name: &Name) -> Option<&Arc<TSigner>> {
+        self.tsig_keys.get(name)
+    }
+
+    /// Verifies the TSIG of a request, RFC 8945 section 5.2
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - the request, as decoded
+    /// * `buffer` - the request as it was received, the MAC is verified over these bytes
+    /// * `now` - seconds since the epoch, UTC
+    ///
+    /// # Return
+    ///
+    /// None if the request is not signed with TSIG, otherwise the key which verified it and its
+    ///  TSIG, with which the responses are signed, see `ResponseHandle::sign_with`. An error if
+    ///  the key is unknown, or does not verify the request, which is then answered with NOTAUTH.
+    pub fn verify_tsig(&self,
+                       request: &Message,
+                       buffer: &[u8],
+                       now: u64)
+                       -> DnsSecResult<Option<(Arc<TSigner>, TSIG)>> {
+        let is_signed = UpdateMessage::get_sig0(request)
+            .iter()
+            .any(|record| record.get_rr_type() == RecordType::TSIG);
+        if !is_signed {
+            return Ok(None);
+        }
+
+        let (key_name, _, _) = try!(split_tsig(buffer));
+        let tsigner = match self.tsig_keys.get(&key_name) {
+            Some(tsigner) => tsigner,
+            None => {
+                return Err(DnsSecErrorKind::Msg(format!("unknown tsig key: {}", key_name)).into())
+            }
+        };
+
+        let tsig = try!(tsigner.verify_message(&[], buffer, TsigChain::Request, now));
+        Ok(Some((tsigner.clone(), tsig)))
+    }
+
+    /// Registers a handler for requests with the op code, replacing any previous one
+    ///
+    /// This is for op codes which the catalog does not implement itself, e.g. STATUS, or ones
+    ///  assigned after it was written, such as DSO. A handler for NOTIFY replaces the default,
+    ///  which marks the Slave zone for a refresh from its primary, see `notify`. QUERY and
+    ///  UPDATE are always answered by the authorities, handlers for them are not registered.
+    ///
+    /// # Arguments
+    ///
+    /// * `op_code` - requests with this op code are passed to the handler
+    /// * `handler` - returns the response, which is sent as is
+    pub fn set_op_code_handler(&mut self,
+                               op_code: OpCode,
+                               handler: Box<RequestHandler + Send + Sync>) {
+        match op_code {
+            OpCode::Query | OpCode::Update => {
+                warn!("op_code: {:?} is handled by the authorities, ignoring the handler",
+                      op_code)
+            }
+            _ => {
+                self.op_code_handlers.insert(op_code, handler);
+            }
+        }
+    }
+
+    /// Adds the zone, or replaces it if it is being reloaded, its statistics are kept
     pub fn upsert(&mut self, name: Name, authority: Authority) {
-        self.authorities.insert(name, RwLock::new(authority));
+        self.upsert_object(name, Box::new(SharedAuthority::new(authority)));
+    }
+
+    /// Adds the zone of any backend, or replaces it, see `AuthorityObject`
+    pub fn upsert_object(&mut self, name: Name, authority: Box<AuthorityObject>) {
+        let now = self.clock.now();
+        self.counters
+            .entry(authority.get_origin())
+            .or_insert_with(|| Mutex::new(ZoneCounters::new(now)))
+            .lock()
+            .unwrap() // poison errors should panic
+            .loaded(now);
+        self.authorities.insert(name, authority);
+    }
+
+    /// Adds a zone whose queries are forwarded, replacing any previous one of the name
+    ///
+    /// The zones of `upsert` are answered locally, those beneath them included, even if one is
+    ///  beneath a forwarded zone.
+    pub fn upsert_forward(&mut self, name: Name, forwarder: ForwardAuthority) {
+        self.forwarders.insert(name, Arc::new(forwarder));
+    }
+
+    /// Returns the forwarded zone to which the query should be sent
+    ///
+    /// This is only the case for a query, other than a zone transfer, with a single question,
+    ///  whose closest enclosing zone in the catalog is forwarded, see `ForwardAuthority`.
+    pub fn get_forward(&self, request: &Message) -> Option<Arc<ForwardAuthority>> {
+        if request.get_message_type() != MessageType::Query ||
+           request.get_op_code() != OpCode::Query {
+            return None;
+        }
+
+        let queries = request.get_queries();
+        if queries.len() != 1 || is_transfer_type(queries[0].get_query_type()) {
+            return None;
+        }
+
+        let mut name = queries[0].get_name().clone();
+        loop {
+            if self.authorities.contains_key(&name) {
+                return None;
+            }
+            if let Some(forwarder) = self.forwarders.get(&name) {
+                return Some(forwarder.clone());
+            }
+            if name.is_root() {
+                return None;
+            }
+            name = name.base_name();
+        }
+    }
+
+    /// Adds the listener to every zone in the catalog, zones added afterwards are not included,
+    ///  see `Authority::add_change_listener()`
+    ///
+    /// The returned subscription unsubscribes the listener from all of the zones.
+    pub fn add_change_listener(&self, listener: &Arc<ChangeListener>) -> ChangeSubscription {
+        let subscription = ChangeSubscription::new();
+        for authority in self.in_memory() {
+            authority.write().subscribe_changes(listener, &subscription);
+        }
+
+        subscription
+    }
+
+    /// Returns a snapshot of the statistics of every zone in memory, by zone name
+    pub fn stats(&self) -> BTreeMap<Name, ZoneStats> {
+        self.authorities
+            .iter()
+            .filter_map(|(name, authority)| authority.get_in_memory().map(|a| (name, a)))
+            .map(|(name, authority)| {
+                let authority = authority.read();
+                let counters = self.counters
+                    .get(authority.get_origin())
+                    .map_or_else(|| ZoneCounters::new(self.clock.now()),
+                                 |c| c.lock().unwrap().clone());
+
+                (name.clone(), ZoneStats::new(&authority, counters))
+            })
+            .collect()
+    }
+
+    /// Returns a snapshot of the cache and latency statistics of every forwarded zone, by zone
+    ///  name
+    pub fn forward_stats(&self) -> BTreeMap<Name, ForwardStats> {
+        self.forwarders
+            .iter()
+            .map(|(name, forwarder)| (name.clone(), ForwardStats::new(forwarder)))
+            .collect()
+    }
+
+    /// Updates the counters of the zone
+    fn count<F>(&self, origin: &Name, f: F)
+        where F: FnOnce(&mut ZoneCounters)
+    {
+        if let Some(counters) = self.counters.get(origin) {
+            f(&mut counters.lock().unwrap()); // poison errors should panic
+        }
+    }
+
+    /// Returns true if the zone is expired, logging and counting when it expires or is refreshed,
+    ///  see `Authority::is_expired`
+    fn check_expired(&self, authority: &Authority) -> bool {
+        let expired = authority.is_expired();
+        self.count(authority.get_origin(), |c| if c.expired(expired) {
+            if expired {
+                warn!("zone: {} expired, answering SERVFAIL until refreshed, last refresh: {}",
+                      authority.get_origin(),
+                      authority.get_last_refresh());
+            } else {
+                info!("zone: {} is no longer expired", authority.get_origin());
+            }
+        });
+        expired
     }
 
     /// Update the zone given the Update request.
@@ -215,11 +530,12 @@ impl Catalog {
             return response;
         }
 
-        if let Some(authority) = self.find_auth_recurse(zones[0].get_name()) {
-            let mut authority = authority.write().unwrap(); // poison errors should panic...
+        if let Some(authority) = self.find_zone(zones[0].get_name()) {
             match authority.get_zone_type() {
                 ZoneType::Slave => {
-                    error!("slave forwarding for update not yet implemented");
+                    // forwarding is async, and handled by the server, see `get_update_forward`
+                    warn!("update forwarding not configured for slave zone: {}",
+                          authority.get_origin());
                     response.response_code(ResponseCode::NotImp);
                     return response;
                 }
@@ -228,6 +544,7 @@ impl Catalog {
                     match update_result {
                         // successful update
                         Ok(..) => {
+                            self.count(&authority.get_origin(), |c| c.update());
                             response.response_code(ResponseCode::NoError);
                         }
                         Err(response_code) => {
@@ -247,92 +564,1116 @@ impl Catalog {
         }
     }
 
+    /// Answers a NOTIFY, RFC 1996
+    ///
+    /// The question must name a zone of this catalog, otherwise the response is NOTAUTH. A Slave
+    ///  zone with a primary is then due for a refresh, unless the SOA in the answers has a serial
+    ///  which is not newer than that of the zone, see `Authority::notified`. The refresh itself is
+    ///  started with `start_refreshes`.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - a NOTIFY message
+    pub fn notify(&self, request: &Message) -> Message {
+        let mut response: Message = Message::new();
+        response.id(request.get_id());
+        response.op_code(OpCode::Notify);
+        response.message_type(MessageType::Response);
+        response.add_queries(request.get_queries().iter().cloned());
+
+        // RFC 1996 section 3.7, QDCOUNT is 1, the zone apex with usually type SOA
+        let queries = request.get_queries();
+        if queries.len() != 1 {
+            response.response_code(ResponseCode::FormErr);
+            return response;
+        }
+
+        let authority = self.authorities
+            .get(queries[0].get_name())
+            .and_then(|authority| authority.get_in_memory());
+        if let Some(authority) = authority {
+            let mut authority = authority.write();
+
+            // RFC 1996 section 3.7, the answer may contain the new SOA
+            let serial = request.get_answers()
+                .iter()
+                .filter_map(|record| match *record.get_rdata() {
+                    RData::SOA(ref soa) => Some(soa.get_serial()),
+                    _ => None,
+                })
+                .next();
+
+            let due = authority.notified(serial);
+            info!("notify for zone: {} type: {:?} serial: {:?}, refresh due: {}",
+                  authority.get_origin(),
+                  authority.get_zone_type(),
+                  serial,
+                  due);
+
+            response.authoritative(true);
+            response.response_code(ResponseCode::NoError);
+        } else {
+            response.response_code(ResponseCode::NotAuth);
+        }
+
+        response
+    }
+
+    /// Returns false if the request is a NOTIFY for a Slave zone which is not from its primary,
+    ///  which the server refuses, RFC 1996 section 3.10
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - the request received
+    /// * `src` - the address the request was received from
+    pub fn is_notify_from_primary(&self, request: &Message, src: IpAddr) -> bool {
+        if request.get_op_code() != OpCode::Notify {
+            return true;
+        }
+
+        request.get_queries()
+            .iter()
+            .filter_map(|query| self.authorities.get(query.get_name()))
+            .filter_map(|authority| authority.get_in_memory())
+            .all(|authority| {
+                let authority = authority.read();
+                authority.get_zone_type() != ZoneType::Slave ||
+                authority.get_primary().map_or(true, |primary| primary.ip() == src)
+            })
+    }
+
+    /// Starts the refreshes of all the Slave zones which are due, see
+    ///  `Authority::start_refresh`
+    ///
+    /// # Return value
+    ///
+    /// The transfers to request from the primaries, the result of each is passed to
+    ///  `finish_refresh`.
+    pub fn start_refreshes(&self) -> Vec<TransferRequest> {
+        self.in_memory()
+            // the zone is only written, i.e. copied, if it is due
+            .filter(|authority| authority.read().is_refresh_due())
+            .filter_map(|authority| authority.write().start_refresh())
+            .collect()
+    }
+
+    /// Returns the NOTIFYs to send to the secondaries of all the zones which changed since the
+    ///  secondaries were last notified, see `Authority::start_notify`
+    pub fn start_notifies(&self) -> Vec<NotifyRequest> {
+        self.in_memory()
+            // the zone is only written, i.e. copied, if it is due
+            .filter(|authority| authority.read().is_notify_due())
+            .filter_map(|authority| authority.write().start_notify())
+            .collect()
+    }
+
+    /// Installs a transfer received for a refresh, or notes that the refresh failed
+    ///
+    /// # Arguments
+    ///
+    /// * `origin` - the zone which was refreshed, see `TransferRequest::get_origin`
+    /// * `result` - the transfer from the primary, None if the serial of the primary is not newer
+    ///              than that of the zone, or the error requesting it
+    pub fn finish_refresh(&self, origin: &Name, result: io::Result<Option<InboundTransfer>>) {
+        let authority = match self.authorities.get(origin).and_then(|a| a.get_in_memory()) {
+            Some(authority) => authority,
+            None => {
+                warn!("refresh of unknown zone: {}", origin);
+                return;
+            }
+        };
+
+        let mut authority = authority.write();
+        match result {
+            Ok(Some(transfer)) => {
+                // a rejected transfer is quarantined and logged by the authority
+                let _ = authority.install_transfer(transfer);
+            }
+            Ok(None) => {
+                debug!("zone: {} is up to date with primary: {:?}",
+                       origin,
+                       authority.get_primary());
+                authority.refreshed();
+            }
+            Err(e) => {
+                warn!("refresh of zone: {} from primary: {:?} failed: {}",
+                      origin,
+                      authority.get_primary(),
+                      e);
+                authority.refresh_failed();
+            }
+        }
+    }
+
+    /// Returns the apex aliases of all the zones which are due to be resolved, see
+    ///  `Authority::start_alias_refresh`
+    ///
+    /// # Return value
+    ///
+    /// The targets to resolve, with `resolve_alias` if they are in the catalog, otherwise by the
+    ///  forwarded zone of the queries, see `AliasRequest::get_queries`. The addresses, or the
+    ///  failure, are then passed to `finish_alias_refresh`.
+    pub fn start_alias_refreshes(&self) -> Vec<AliasRequest> {
+        self.in_memory()
+            // the zone is only written, i.e. copied, if it is due
+            .filter(|authority| authority.read().is_alias_refresh_due())
+            .filter_map(|authority| authority.write().start_alias_refresh())
+            .collect()
+    }
+
+    /// Resolves the target of an apex alias from the zones of the catalog, following CNAMEs
+    ///
+    /// # Return value
+    ///
+    /// The answers for the A and AAAA records of the target, None if it is not in a zone of the
+    ///  catalog, or is beneath a forwarded zone, see `get_forward`
+    pub fn resolve_alias(&self, request: &AliasRequest) -> Option<Vec<Record>> {
+        let queries = request.get_queries();
+        if self.find_zone(request.get_target()).is_none() ||
+           queries.iter().any(|query| self.get_forward(query).is_some()) {
+            return None;
+        }
+
+        let mut answers: Vec<Record> = Vec::new();
+        for query in &queries {
+            answers.extend(self.lookup(query).get_answers().iter().cloned());
+        }
+        Some(answers)
+    }
+
+    /// Installs the addresses resolved for an apex alias, or notes that the resolution failed
+    ///
+    /// # Arguments
+    ///
+    /// * `origin` - the zone of the alias, see `AliasRequest::get_origin`
+    /// * `result` - the answers for the target, or the error resolving them
+    pub fn finish_alias_refresh(&self, origin: &Name, result: io::Result<Vec<Record>>) {
+        let authority = match self.authorities.get(origin).and_then(|a| a.get_in_memory()) {
+            Some(authority) => authority,
+            None => {
+                warn!("alias of unknown zone: {}", origin);
+                return;
+            }
+        };
+
+        let mut authority = authority.write();
+        match result {
+            Ok(answers) => {
+                if let Err(code) = authority.install_alias_addresses(&answers) {
+                    warn!("addresses of the alias of zone: {} not installed: {:?}", origin, code);
+                }
+            }
+            Err(e) => {
+                warn!("resolving the alias: {:?} of zone: {} failed: {}",
+                      authority.get_apex_alias(),
+                      origin,
+                      e);
+                authority.alias_refresh_failed();
+            }
+        }
+    }
+
+    /// Reloads the zone from its file, the records of the file replace those of the zone
+    ///
+    /// The file is read and parsed before the zone is locked, and the new version of the zone is
+    ///  swapped in at once, queries being answered meanwhile see either the old or the new
+    ///  version, never a mix of both. If the file has errors, the zone is left as it was, see
+    ///  `Authority::reload_records` for the zones which are not reloaded.
+    ///
+    /// # Arguments
+    ///
+    /// * `origin` - the zone to reload, which was loaded from a file, see `ZoneFile`
+    pub fn reload(&self, origin: &Name) -> ParseResult<()> {
+        let authority = match self.authorities.get(origin).and_then(|a| a.get_in_memory()) {
+            Some(authority) => authority,
+            None => return Err(ParseErrorKind::Msg(format!("unknown zone: {}", origin)).into()),
+        };
+
+        let zone_file = authority.read().get_zone_file().cloned();
+        let mut zone_file = match zone_file {
+            Some(zone_file) => zone_file,
+            None => {
+                return Err(ParseErrorKind::Msg(format!("zone: {} has no zone file", origin))
+                    .into())
+            }
+        };
+
+        let (_, records, meta) = match zone_file.load(Some(origin.clone())) {
+            Ok(loaded) => loaded,
+            Err(e) => {
+                // the file is not read again until it is modified, see `reload_modified`
+                authority.write().set_zone_file(Some(zone_file));
+                return Err(e);
+            }
+        };
+
+        try!(authority.write().reload_records(zone_file, records, meta));
+        let now = self.clock.now();
+        self.count(origin, |counters| counters.loaded(now));
+        Ok(())
+    }
+
+    /// Reloads all the zones whose files were modified since they were last read, see `reload`
+    ///
+    /// A file whose reload failed is only read again once it is modified again.
+    ///
+    /// # Return value
+    ///
+    /// The zones which were reloaded, e.g. to notify their secondaries, see `start_notifies`
+    pub fn reload_modified(&self) -> Vec<Name> {
+        let modified: Vec<Name> = self.authorities
+            .iter()
+            .filter(|&(_, authority)| {
+                authority.get_in_memory()
+                    .and_then(|authority| authority.read().get_zone_file().map(|f| f.is_modified()))
+                    .unwrap_or(false)
+            })
+            .map(|(origin, _)| origin.clone())
+            .collect();
+
+        modified.into_iter()
+            .filter(|origin| match self.reload(origin) {
+                Ok(()) => true,
+                Err(e) => {
+                    warn!("reloading zone: {} failed: {}", origin, e);
+                    false
+                }
+            })
+            .collect()
+    }
+
+    /// Returns the primary master to which the request should be forwarded.
+    ///
+    /// This is only the case for an Update to a Slave zone which has forwarding configured, see
+    ///  `Authority::set_update_forward`, the response from the primary is then relayed back to
+    ///  the requestor, RFC 2136 section 6.
+    pub fn get_update_forward(&self, request: &Message) -> Option<SocketAddr> {
+        if request.get_message_type() != MessageType::Query ||
+           request.get_op_code() != OpCode::Update {
+            return None;
+        }
+
+        let zones: &[Query] = request.get_zones();
+        if zones.len() != 1 || zones[0].get_query_type() != RecordType::SOA {
+            return None;
+        }
+
+        self.find_auth_recurse(zones[0].get_name()).and_then(|authority| {
+            let authority = authority.read();
+            match authority.get_zone_type() {
+                ZoneType::Slave => authority.get_update_forward(),
+                _ => None,
+            }
+        })
+    }
+
     /// Given the requested query, lookup and return any matching results.
     ///
+    /// The header flags of the response follow RFC 1035 and RFC 4035 section 3.1.6:
+    ///
+    /// * `AA` is set for all answers, including negative ones, from Master and Slave zones.
+    ///   Expired Slave zones are answered with SERVFAIL, see `Authority::is_expired`.
+    /// * `AD` is never set, the data is served from the zone, not validated by this server.
+    /// * `RD` and `CD` are copied from the request, `RA` is clear as there is no recursion.
+    ///
     /// # Arguments
     ///
     /// * `request` - the query message.
     pub fn lookup(&self, request: &Message) -> Message {
+        self.lookup_on(request, None, Some(request.get_max_payload()))
+    }
+
+    /// Answers the request received on the local address, see `RequestHandler::handle_request`
+    ///
+    /// The answers only come from the zones served on the address, see `is_served_on`: the
+    ///  CNAMEs of the answers are not followed into the zones of other addresses, nor are the
+    ///  additional records taken from them, so that the zones of a tenant are never answered on
+    ///  the addresses of another. The server refuses the requests for such zones themselves.
+    ///
+    /// The additional records are added as long as the response fits the transport, the payload
+    ///  size of the request over UDP, a whole message over TCP, where `RequestHandler` assumes
+    ///  the payload size.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - the request received
+    /// * `local` - the address of the listener the request was received on
+    /// * `max_size` - the largest response the transport carries, None if only the size of a
+    ///                message limits it, e.g. TCP, see `ResponseHandle::get_max_size`
+    pub fn handle_request_on(&self,
+                             request: &Message,
+                             local: IpAddr,
+                             max_size: Option<u16>)
+                             -> Message {
+        self.respond(request, Some(local), max_size)
+    }
+
+    /// Answers the query from the zones served on the local address if it is known, see `lookup`
+    fn lookup_on(&self,
+                 request: &Message,
+                 local: Option<IpAddr>,
+                 max_size: Option<u16>)
+                 -> Message {
         let mut response: Message = Message::new();
         response.id(request.get_id());
         response.op_code(OpCode::Query);
         response.message_type(MessageType::Response);
+        response.recursion_desired(request.is_recursion_desired());
+        response.recursion_available(false);
+        response.checking_disabled(request.is_checking_disabled());
+        response.authentic_data(false);
         response.add_queries(request.get_queries().into_iter().cloned());
 
+        let (is_dnssec, supported_algorithms) = request.get_edns()
+            .map_or((false, SupportedAlgorithms::new()), |edns| {
+                let supported_algorithms = if let Some(&EdnsOption::DAU(algs)) =
+                    edns.get_option(&EdnsCode::DAU) {
+                    algs
+                } else {
+                    Default::default()
+                };
+
+                (edns.is_dnssec_ok(), supported_algorithms)
+            });
+
         // TODO: the spec is very unclear on what to do with multiple queries
         //  we will search for each, in the future, maybe make this threaded to respond even faster.
         for query in request.get_queries() {
-            if let Some(ref_authority) = self.find_auth_recurse(query.get_name()) {
-                let authority = &ref_authority.read().unwrap(); // poison errors should panic
-                debug!("found authority: {:?}", authority.get_origin());
-                let (is_dnssec, supported_algorithms) = request.get_edns()
-                    .map_or((false, SupportedAlgorithms::new()), |edns| {
-                        let supported_algorithms = if let Some(&EdnsOption::DAU(algs)) =
-                            edns.get_option(&EdnsCode::DAU) {
-                            algs
-                        } else {
-                            Default::default()
-                        };
-
-                        (edns.is_dnssec_ok(), supported_algorithms)
-                    });
-
-                let records = authority.search(query, is_dnssec, supported_algorithms);
-                if !records.is_empty() {
-                    response.response_code(ResponseCode::NoError);
-                    response.authoritative(true);
-                    response.add_answers(records.into_iter().cloned());
-
-                    // get the NS records
-                    let ns = authority.get_ns(is_dnssec, supported_algorithms);
-                    if ns.is_empty() {
-                        warn!("there are no NS records for: {:?}", authority.get_origin());
-                    } else {
-                        response.add_name_servers(ns.into_iter().cloned());
-                    }
-                } else {
-                    if is_dnssec {
-                        // get NSEC records
-                        let nsecs =
-                            authority.get_nsec_records(query.get_name(),
-                                                       is_dnssec,
-                                                       supported_algorithms);
-                        response.add_name_servers(nsecs.into_iter().cloned());
-                    }
-
-                    // in the not found case it's standard to return the SOA in the authority section
+            let zone = match self.find_zone(query.get_name()) {
+                Some(zone) => zone,
+                None => {
+                    // we found nothing.
                     response.response_code(ResponseCode::NXDomain);
+                    continue;
+                }
+            };
+            debug!("found authority: {:?}", zone.get_origin());
 
-                    let soa = authority.get_soa_secure(is_dnssec, supported_algorithms);
-                    if soa.is_empty() {
-                        warn!("there is no SOA record for: {:?}", authority.get_origin());
-                    } else {
-                        response.add_name_servers(soa.into_iter().cloned());
-                    }
+            match zone.get_in_memory() {
+                Some(authority) => {
+                    self.answer_in_memory(&authority.read(),
+                                          query,
+                                          is_dnssec,
+                                          supported_algorithms,
+                                          local,
+                                          &mut response)
+                }
+                None => {
+                    self.answer_object(zone, query, is_dnssec, supported_algorithms, &mut response)
                 }
-            } else {
-                // we found nothing.
-                response.response_code(ResponseCode::NXDomain);
             }
         }
 
-        // TODO a lot of things do a recursive query for non-A or AAAA records, and return those in
-        //  additional
+        if let (true, Some(trust_point), Some(query)) = (is_dnssec,
+                                                         request.get_chain_trust_point(),
+                                                         request.get_queries().first()) {
+            self.add_chain_records(trust_point,
+                                   query,
+                                   supported_algorithms,
+                                   max_size.is_none(),
+                                   &mut response);
+        }
+        self.add_additional_records(max_size.unwrap_or(u16::MAX) as usize,
+                                    is_dnssec,
+                                    supported_algorithms,
+                                    local,
+                                    &mut response);
         response
     }
 
+    /// Answers the query from a zone in memory
+    fn answer_in_memory(&self,
+                        authority: &Authority,
+                        query: &Query,
+                        is_dnssec: bool,
+                        supported_algorithms: SupportedAlgorithms,
+                        local: Option<IpAddr>,
+                        response: &mut Message) {
+        self.count(authority.get_origin(), |c| c.query(query.get_query_type()));
+
+        // the data of an expired zone may be stale, it is not answered from, RFC 1035
+        if self.check_expired(authority) {
+            response.response_code(ResponseCode::ServFail);
+            return;
+        }
+
+        // the client is not known here, restricted transfers are only done by `transfer`
+        if is_transfer_type(query.get_query_type()) && authority.get_transfer_acl().is_some() {
+            response.response_code(ResponseCode::Refused);
+            return;
+        }
+
+        response.authoritative(authority.get_zone_type().is_authoritative());
+
+        // without a connection the deltas may not fit, the SOA tells the client to retry
+        //  the IXFR over TCP, RFC 1995 section 2
+        if query.get_query_type() == RecordType::IXFR &&
+           query.get_name() == authority.get_origin() {
+            response.response_code(ResponseCode::NoError);
+            response.add_answers(authority.get_soa().into_iter().cloned());
+            return;
+        }
+        // below a zone cut the query is referred to the child zone, below a DNAME it is
+        //  redirected to the target, the records stored there are not answered
+        if let Some(occlusion) = authority.get_occlusion(query.get_name()) {
+            occluded_answer(authority,
+                            occlusion,
+                            query,
+                            is_dnssec,
+                            supported_algorithms,
+                            response);
+            self.chase_cnames(query, is_dnssec, supported_algorithms, local, response);
+            return;
+        }
+
+        let records = authority.search(query, is_dnssec, supported_algorithms);
+        if !records.is_empty() {
+            response.response_code(ResponseCode::NoError);
+            response.add_answers(records);
+            if is_dnssec {
+                add_wildcard_proof(authority, query.get_name(), supported_algorithms, response);
+            }
+
+            // a chain ending at a name without records is answered as that name, without the NS
+            if self.chase_cnames(query, is_dnssec, supported_algorithms, local, response) {
+                return;
+            }
+
+            // get the NS records
+            let ns = authority.get_ns(is_dnssec, supported_algorithms);
+            if ns.is_empty() {
+                warn!("there are no NS records for: {:?}", authority.get_origin());
+            } else {
+                response.add_name_servers(ns.into_iter().cloned());
+            }
+        } else {
+            self.deny_name(authority,
+                           query.get_name(),
+                           is_dnssec,
+                           supported_algorithms,
+                           response);
+        }
+    }
+
+    /// Answers that the name has no records of the type queried, NODATA, or does not exist,
+    ///  NXDOMAIN, with the SOA of the zone, and the NSEC or NSEC3 records proving it if DNSSEC
+    ///  is requested
+    ///
+    /// A name which does not exist but has a wildcard, see `Authority::find_wildcard`, is NODATA
+    ///  if the wildcard has no records of the type, with the proof of that.
+    fn deny_name(&self,
+                 authority: &Authority,
+                 name: &Name,
+                 is_dnssec: bool,
+                 supported_algorithms: SupportedAlgorithms,
+                 response: &mut Message) {
+        let wildcard = authority.find_wildcard(name);
+        if is_dnssec {
+            // get NSEC, or NSEC3, records
+            let nsecs = if let Some(ref wildcard) = wildcard {
+                authority.get_wildcard_proof(name, wildcard, true, is_dnssec, supported_algorithms)
+            } else if authority.get_nsec3_param().is_some() {
+                authority.get_nsec3_records(name, is_dnssec, supported_algorithms)
+            } else {
+                authority.get_nsec_records(name, is_dnssec, supported_algorithms)
+            };
+            response.add_name_servers(nsecs.into_iter().cloned());
+        }
+
+        // in the not found case it's standard to return the SOA in the authority section
+        //  names which exist, including empty non-terminals, are NODATA not NXDomain
+        if wildcard.is_some() || authority.contains_name(name) {
+            response.response_code(ResponseCode::NoError);
+        } else {
+            self.count(authority.get_origin(), |c| c.nx_domain());
+            response.response_code(ResponseCode::NXDomain);
+        }
+
+        let soa = authority.get_soa_secure(is_dnssec, supported_algorithms);
+        if soa.is_empty() {
+            warn!("there is no SOA record for: {:?}", authority.get_origin());
+        } else {
+            response.add_name_servers(soa.into_iter().cloned());
+        }
+    }
+
+    /// Answers the query from a zone of another backend, see `AuthorityObject`
+    ///
+    /// The answers are the records of `AuthorityObject::search`, with the NS records of the zone,
+    ///  otherwise the response is NXDOMAIN, or NODATA if the name exists, with the SOA record.
+    ///  Zone transfers of other backends are not implemented.
+    fn answer_object(&self,
+                     zone: &AuthorityObject,
+                     query: &Query,
+                     is_dnssec: bool,
+                     supported_algorithms: SupportedAlgorithms,
+                     response: &mut Message) {
+        let origin = zone.get_origin();
+        self.count(&origin, |c| c.query(query.get_query_type()));
+
+        if is_transfer_type(query.get_query_type()) {
+            response.response_code(ResponseCode::NotImp);
+            return;
+        }
+        response.authoritative(zone.get_zone_type().is_authoritative());
+
+        let records = zone.search(query, is_dnssec, supported_algorithms);
+        let exists = !records.is_empty() || zone.contains_name(query.get_name());
+        if !zone.is_available() {
+            warn!("zone: {} is unavailable, answering SERVFAIL", origin);
+            response.response_code(ResponseCode::ServFail);
+            return;
+        }
+
+        if !records.is_empty() {
+            response.response_code(ResponseCode::NoError);
+            response.add_answers(records);
+            response.add_name_servers(zone.lookup(&origin,
+                                                  RecordType::NS,
+                                                  is_dnssec,
+                                                  supported_algorithms));
+            return;
+        }
+
+        if exists {
+            response.response_code(ResponseCode::NoError);
+        } else {
+            self.count(&origin, |c| c.nx_domain());
+            response.response_code(ResponseCode::NXDomain);
+        }
+        response.add_name_servers(zone.lookup(&origin,
+                                              RecordType::SOA,
+                                              is_dnssec,
+                                              supported_algorithms));
+    }
+
+    /// Adds the addresses of the targets of the NS, MX and SRV records of the answer and
+    ///  authority sections to the additional section, RFC 1034 section 3.7 and RFC 2782
+    ///
+    /// The A and AAAA records, with their RRSIGs if DNSSEC is requested, are those in the zones of
+    ///  the catalog served on the local address, see `is_served_on`, not occluded nor expired.
+    ///  Each RRSet is added only if the response still fits the transport, leaving room for the
+    ///  OPT record, so that it is never truncated for the sake of the additional section, RFC
+    ///  2181 section 9. The size is counted without name compression, so the response usually
+    ///  has room to spare.
+    fn add_additional_records(&self,
+                              max_size: usize,
+                              is_dnssec: bool,
+                              supported_algorithms: SupportedAlgorithms,
+                              local: Option<IpAddr>,
+                              response: &mut Message) {
+        let mut targets: Vec<Name> = Vec::new();
+        for record in response.get_answers().iter().chain(response.get_name_servers()) {
+            let target = match *record.get_rdata() {
+                RData::NS(ref target) => target,
+                RData::MX(ref mx) => mx.get_exchange(),
+                RData::SRV(ref srv) => srv.get_target(),
+                _ => continue,
+            };
+            if !target.is_root() && !targets.contains(target) {
+                targets.push(target.clone());
+            }
+        }
+        if targets.is_empty() {
+            return;
+        }
+
+        let mut len = encoded_len(&*response) + ADDITIONAL_OPT_ALLOWANCE;
+        for target in &targets {
+            let authority = match self.find_served_on(target, local) {
+                Some(authority) => authority,
+                None => continue,
+            };
+            // the glue of a referral is added by `occluded_answer`
+            if authority.is_expired() || authority.is_occluded(target) {
+                continue;
+            }
+
+            for address_type in &[RecordType::A, RecordType::AAAA] {
+                let found =
+                    authority.lookup(target, *address_type, is_dnssec, supported_algorithms);
+                let records: Vec<Record> = found.into_iter()
+                    .filter(|record| !response.get_additionals().contains(record))
+                    .cloned()
+                    .collect();
+                if records.is_empty() {
+                    continue;
+                }
+
+                let records_len = records.iter().map(encoded_len).sum::<usize>();
+                if len + records_len > max_size {
+                    debug!("no room for the {:?} records of: {} in the additional section",
+                           address_type,
+                           target);
+                    continue;
+                }
+
+                len += records_len;
+                for record in records {
+                    response.add_additional(record);
+                }
+            }
+        }
+    }
+
+    /// Adds the DNSKEY and DS records, with their RRSIGs, of the chain of trust of the zone of the
+    ///  query to the authority section, from the Closest Trust Point of the requestor down, RFC
+    ///  7901
+    ///
+    /// The chain is followed up from the zone of the query through the zones of the catalog, each
+    ///  of which must be signed, not expired, and have DS records in its parent. It ends at the
+    ///  trust point, or at the last zone which could be added, which the CHAIN option of the
+    ///  response names, see `Message::get_chain_trust_point`; without a signed zone there is no
+    ///  chain and no option.
+    ///
+    /// A chain is many times the size of the query, so it is only sent over a connection, the
+    ///  handshake of which validates the source address, RFC 7901 section 7.2. Otherwise the
+    ///  response is truncated instead, without the chain, so that the requestor retries over TCP;
+    ///  server cookies, the other means of validating the source, are not checked.
+    fn add_chain_records(&self,
+                         trust_point: &Name,
+                         query: &Query,
+                         supported_algorithms: SupportedAlgorithms,
+                         is_connected: bool,
+                         response: &mut Message) {
+        let mut zone = match self.find_auth_recurse(query.get_name()) {
+            Some(authority) => authority.read().get_origin().clone(),
+            None => return,
+        };
+        if !trust_point.zone_of(&zone) {
+            return;
+        }
+
+        let mut chain: Vec<Record> = Vec::new();
+        let mut start: Option<Name> = None;
+        loop {
+            let authority = match self.find_auth_recurse(&zone) {
+                Some(authority) => authority.read(),
+                None => break,
+            };
+            if authority.is_expired() {
+                break;
+            }
+
+            let dnskeys = authority.lookup(&zone, RecordType::DNSKEY, true, supported_algorithms);
+            if dnskeys.is_empty() {
+                break;
+            }
+            chain.extend(dnskeys.into_iter().cloned());
+            start = Some(zone.clone());
+            if &zone == trust_point || zone.is_root() {
+                break;
+            }
+
+            // the DS records of the zone are in its parent
+            let parent = match self.find_auth_recurse(&zone.base_name()) {
+                Some(parent) => parent.read(),
+                None => break,
+            };
+            if parent.is_expired() || !trust_point.zone_of(parent.get_origin()) {
+                break;
+            }
+
+            let ds = parent.lookup(&zone, RecordType::DS, true, supported_algorithms);
+            if ds.is_empty() {
+                break;
+            }
+            chain.extend(ds.into_iter().cloned());
+            zone = parent.get_origin().clone();
+        }
+
+        let start = match start {
+            Some(start) => start,
+            None => return,
+        };
+        if !is_connected {
+            debug!("truncating the chain of trust from: {} for: {} without a connection",
+                   start,
+                   query.get_name());
+            response.truncated(true);
+            return;
+        }
+
+        debug!("adding the chain of trust from: {} for: {}", start, query.get_name());
+        let chain: Vec<Record> = chain.into_iter()
+            .filter(|record| !response.get_name_servers().contains(record))
+            .collect();
+        response.add_name_servers(chain);
+        response.get_edns_mut().set_option(EdnsOption::Chain(start));
+    }
+
+    /// Follows the CNAME answering the query through the zones of the catalog, adding the records
+    ///  of each target to the answers, RFC 1034 section 4.3.2
+    ///
+    /// The chain ends at a target with records of the type of the query, at a target outside the
+    ///  zones served on the local address, see `is_served_on`, or which is occluded, at a loop, or
+    ///  after `MAX_CNAME_CHAIN` CNAMEs; the client follows it on from there. CNAME and ANY queries
+    ///  are not followed. A target without records of the type, or which does not exist, ends the
+    ///  chain as well, the response is then NODATA or NXDOMAIN as for the target, RFC 6604, and
+    ///  true is returned.
+    fn chase_cnames(&self,
+                    query: &Query,
+                    is_dnssec: bool,
+                    supported_algorithms: SupportedAlgorithms,
+                    local: Option<IpAddr>,
+                    response: &mut Message)
+                    -> bool {
+        let query_type = query.get_query_type();
+        if query_type == RecordType::CNAME || query_type == RecordType::ANY ||
+           is_transfer_type(query_type) {
+            return false;
+        }
+
+        let mut visited: Vec<Name> = vec![query.get_name().clone()];
+        for _ in 0..MAX_CNAME_CHAIN {
+            let target = match cname_target(response.get_answers(), &visited[visited.len() - 1]) {
+                Some(target) => target,
+                None => return false,
+            };
+            if visited.contains(&target) {
+                warn!("CNAME loop at: {} for: {}", target, query.get_name());
+                return false;
+            }
+
+            let authority = match self.find_served_on(&target, local) {
+                Some(authority) => authority,
+                None => return false,
+            };
+            if authority.is_expired() || authority.is_occluded(&target) {
+                return false;
+            }
+
+            let mut chased = query.clone();
+            chased.name(target.clone());
+            let records = authority.search(&chased, is_dnssec, supported_algorithms);
+            if records.is_empty() {
+                self.deny_name(&authority, &target, is_dnssec, supported_algorithms, response);
+                return true;
+            }
+
+            response.add_answers(records);
+            if is_dnssec {
+                add_wildcard_proof(&authority, &target, supported_algorithms, response);
+            }
+            visited.push(target);
+        }
+
+        false
+    }
+
+    /// Answers a zone transfer, AXFR, with the records of the zone in as many messages as needed,
+    ///  RFC 5936, or an incremental one, IXFR, with the changes since the serial of the SOA in the
+    ///  authority section of the request, RFC 1995
+    ///
+    /// The SOA is the first and the last record, see `Authority::get_transfer_records` and
+    ///  `Authority::get_ixfr_records`, each message holds as many as fit in 16 KiB, only the first
+    ///  one has the question. A client which the `TransferAcl` of the zone does not permit is
+    ///  refused.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - an AXFR or IXFR query, received on a connection
+    /// * `src` - the address of the client
+    /// * `tsig_key` - the name of the key which verified the request, see `verify_tsig`
+    pub fn transfer(&self,
+                    request: &Message,
+                    src: IpAddr,
+                    tsig_key: Option<&Name>)
+                    -> Vec<Message> {
+        let mut response: Message = Message::new();
+        response.id(request.get_id());
+        response.op_code(OpCode::Query);
+        response.message_type(MessageType::Response);
+        response.add_queries(request.get_queries().iter().cloned());
+
+        let queries = request.get_queries();
+        if queries.len() != 1 || !is_transfer_type(queries[0].get_query_type()) {
+            response.response_code(ResponseCode::FormErr);
+            return vec![response];
+        }
+        let query_type = queries[0].get_query_type();
+
+        // the version of the zone the client has, RFC 1995 section 3
+        let ixfr_serial = request.get_name_servers()
+            .iter()
+            .filter_map(|record| match *record.get_rdata() {
+                RData::SOA(ref soa) => Some(soa.get_serial()),
+                _ => None,
+            })
+            .next();
+        if query_type == RecordType::IXFR && ixfr_serial.is_none() {
+            response.response_code(ResponseCode::FormErr);
+            return vec![response];
+        }
+
+        // only the apex of a zone may be transferred, and only from memory
+        let authority = match self.authorities.get(queries[0].get_name()) {
+            Some(authority) => {
+                match authority.get_in_memory() {
+                    Some(authority) => authority.read(),
+                    None => {
+                        response.response_code(ResponseCode::NotImp);
+                        return vec![response];
+                    }
+                }
+            }
+            None => {
+                response.response_code(ResponseCode::NotAuth);
+                return vec![response];
+            }
+        };
+        self.count(authority.get_origin(), |c| c.query(query_type));
+
+        if !authority.get_zone_type().is_authoritative() {
+            response.response_code(ResponseCode::NotAuth);
+            return vec![response];
+        }
+        if self.check_expired(&authority) {
+            response.response_code(ResponseCode::ServFail);
+            return vec![response];
+        }
+        if !authority.is_transfer_permitted(request, src, tsig_key) {
+            warn!("refusing transfer of zone: {} to: {}", authority.get_origin(), src);
+            response.response_code(ResponseCode::Refused);
+            return vec![response];
+        }
+
+        let records = match ixfr_serial {
+            Some(serial) if query_type == RecordType::IXFR => authority.get_ixfr_records(serial),
+            _ => authority.get_transfer_records(),
+        };
+        if records.is_empty() {
+            warn!("there is no SOA record for: {:?}", authority.get_origin());
+            response.response_code(ResponseCode::ServFail);
+            return vec![response];
+        }
+        info!("transferring zone: {} to: {}, {:?} {} records",
+              authority.get_origin(),
+              src,
+              query_type,
+              records.len());
+
+        response.authoritative(true);
+        response.response_code(ResponseCode::NoError);
+        let mut next: Message = Message::new();
+        next.id(request.get_id())
+            .op_code(OpCode::Query)
+            .message_type(MessageType::Response)
+            .authoritative(true)
+            .response_code(ResponseCode::NoError);
+
+        // the lengths are without name compression, so the messages are at most this long
+        let mut responses: Vec<Message> = Vec::new();
+        let mut len = 12 + queries.iter().map(encoded_len).sum::<usize>();
+        for record in records {
+            let record_len = encoded_len(record);
+            if !response.get_answers().is_empty() && len + record_len > MAX_TRANSFER_MESSAGE_LEN {
+                responses.push(response);
+                response = next.clone();
+                len = 12;
+            }
+
+            response.add_answer(record.clone());
+            len += record_len;
+        }
+        responses.push(response);
+
+        responses
+    }
+
+    /// Returns true if every zone the request is for is answered on the local address, see
+    ///  `Authority::set_listen_addrs`
+    ///
+    /// The zones are those of the questions, or for an UPDATE of the zone section. A request
+    ///  which is not for any zone of the catalog is not restricted, it is answered as usual. The
+    ///  server refuses any other request before it is dispatched, so that a zone bound to the
+    ///  addresses of one tenant is never answered, transferred nor updated on those of another.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - the request received
+    /// * `local` - the address of the listener the request was received on
+    pub fn is_served_on(&self, request: &Message, local: IpAddr) -> bool {
+        request.get_queries().iter().all(|query| {
+            self.find_auth_recurse(query.get_name())
+                .map_or(true, |authority| authority.read().is_listening_on(local))
+        })
+    }
+
+    /// The closest enclosing zone in memory of the name, if it is served on the local address,
+    ///  any zone if the address is not known, see `is_served_on`
+    fn find_served_on(&self, name: &Name, local: Option<IpAddr>) -> Option<Arc<Authority>> {
+        let authority = match self.find_auth_recurse(name) {
+            Some(authority) => authority.read(),
+            None => return None,
+        };
+        match local {
+            Some(local) if !authority.is_listening_on(local) => None,
+            _ => Some(authority),
+        }
+    }
+
+    /// Returns true if any zone in memory is only answered on some local addresses, see
+    ///  `Authority::set_listen_addrs`
+    ///
+    /// Such zones need listeners bound to specific addresses, over UDP a listener on the
+    ///  unspecified address refuses them, as it does not know the address a request was sent to.
+    pub fn has_listen_addrs(&self) -> bool {
+        self.in_memory().any(|authority| authority.read().get_listen_addrs().is_some())
+    }
+
     /// recursively searches the catalog for a matching auhtority.
-    fn find_auth_recurse(&self, name: &Name) -> Option<&RwLock<Authority>> {
+    ///
+    /// This is the closest enclosing zone of the name if it is in memory, None if it is of another
+    ///  backend, see `find_zone`.
+    pub fn find_auth_recurse(&self, name: &Name) -> Option<&SharedAuthority> {
+        self.find_zone(name).and_then(|authority| authority.get_in_memory())
+    }
+
+    /// Returns the closest enclosing zone of the name, of any backend
+    pub fn find_zone(&self, name: &Name) -> Option<&AuthorityObject> {
         let authority = self.authorities.get(name);
-        if authority.is_some() {
-            return authority;
+        if let Some(authority) = authority {
+            return Some(&**authority);
         } else {
             let name = name.base_name();
             if !name.is_root() {
-                return self.find_auth_recurse(&name);
+                return self.find_zone(&name);
+            }
+        }
+
+        None
+    }
+
+    /// The zones in memory
+    fn in_memory<'a>(&'a self) -> Box<Iterator<Item = &'a SharedAuthority> + 'a> {
+        Box::new(self.authorities.values().filter_map(|authority| authority.get_in_memory()))
+    }
+}
+
+/// AXFR and IXFR, the query types of zone transfers
+fn is_transfer_type(query_type: RecordType) -> bool {
+    query_type == RecordType::AXFR || query_type == RecordType::IXFR
+}
+
+/// Answers a query for a name which is occluded, see `Authority::get_occlusion`
+///
+/// Below a delegation this is a referral, RFC 1034 section 4.3.2, with the NS records of the zone
+///  cut, and their DS if DNSSEC is requested, in the authority section and the glue in the
+///  additional section. Below a DNAME it is the DNAME and the CNAME it synthesizes for the name,
+///  RFC 6672 section 3.1, or YXDOMAIN if the substituted name is too long.
+fn occluded_answer(authority: &Authority,
+                   occlusion: &RecordSet,
+                   query: &Query,
+                   is_dnssec: bool,
+                   supported_algorithms: SupportedAlgorithms,
+                   response: &mut Message) {
+    let cut = occlusion.get_name();
+    let records = occlusion.get_records(is_dnssec, supported_algorithms);
+
+    if occlusion.get_record_type() == RecordType::NS {
+        response.authoritative(false);
+        response.response_code(ResponseCode::NoError);
+        for record in &records {
+            if let RData::NS(ref target) = *record.get_rdata() {
+                if !cut.zone_of(target) {
+                    continue;
+                }
+
+                for glue_type in &[RecordType::A, RecordType::AAAA] {
+                    for glue in authority.lookup(target, *glue_type, false, supported_algorithms) {
+                        response.add_additional(glue.clone());
+                    }
+                }
             }
         }
 
+        response.add_name_servers(records.into_iter().cloned());
+        if is_dnssec {
+            let ds = authority.lookup(cut, RecordType::DS, is_dnssec, supported_algorithms);
+            response.add_name_servers(ds.into_iter().cloned());
+        }
+        return;
+    }
+
+    let synthesized = records.iter()
+        .filter_map(|record| if let RData::DNAME(ref target) = *record.get_rdata() {
+            Some((record.get_ttl(), target))
+        } else {
+            None
+        })
+        .next()
+        .map(|(ttl, target)| (ttl, dname_substitution(query.get_name(), cut, target)));
+
+    response.add_answers(records.into_iter().cloned());
+    match synthesized {
+        Some((ttl, Some(substituted))) => {
+            response.response_code(ResponseCode::NoError);
+            response.add_answer(Record::from_rdata(query.get_name().clone(),
+                                                   ttl,
+                                                   RecordType::CNAME,
+                                                   RData::CNAME(substituted)));
+        }
+        Some((_, None)) => {
+            response.response_code(ResponseCode::YXDomain);
+        }
+        None => {
+            response.response_code(ResponseCode::ServFail);
+        }
+    }
+}
+
+/// Adds the proof that the name does not exist to the authority section, if its records were
+///  synthesized from a wildcard, see `Authority::get_wildcard_proof`
+fn add_wildcard_proof(authority: &Authority,
+                      name: &Name,
+                      supported_algorithms: SupportedAlgorithms,
+                      response: &mut Message) {
+    if let Some(wildcard) = authority.find_wildcard(name) {
+        let proof =
+            authority.get_wildcard_proof(name, &wildcard, false, true, supported_algorithms);
+        response.add_name_servers(proof.into_iter().cloned());
+    }
+}
+
+/// The target of the CNAME of the name among the answers
+fn cname_target(answers: &[Record], name: &Name) -> Option<Name> {
+    answers.iter()
+        .filter(|record| record.get_name() == name)
+        .filter_map(|record| if let RData::CNAME(ref target) = *record.get_rdata() {
+            Some(target.clone())
+        } else {
+            None
+        })
+        .next()
+}
+
+/// The name with the owner of the DNAME replaced by its target, None if that is longer than 255
+///  octets, RFC 6672 section 2.2
+fn dname_substitution(name: &Name, owner: &Name, target: &Name) -> Option<Name> {
+    let mut labels: Vec<String> = Vec::new();
+    let mut suffix = name.clone();
+    while suffix != *owner && !suffix.is_root() {
+        labels.push(suffix[0].clone());
+        suffix = suffix.base_name();
+    }
+
+    let mut substituted = Name::with_labels(labels);
+    substituted.append(target);
+    // len() counts a dot for each label, the wire form adds the root label
+    if substituted.len() + 1 > 255 {
         None
+    } else {
+        Some(substituted)
+    }
+}
+
+/// The length of the value once encoded, without name compression
+fn encoded_len<S: BinSerializable<S>>(value: &S) -> usize {
+    let mut buffer: Vec<u8> = Vec::new();
+    let mut encoder = BinEncoder::new(&mut buffer);
+    if let Err(e) = value.emit(&mut encoder) {
+        warn!("could not encode: {}", e);
     }
+    encoder.len()
 }
@@ -0,0 +1,66 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use trust_dns::rr::{Name, Record};
+
+/// A record added to, or removed from, an `Authority`
+#[derive(Clone, Debug, PartialEq)]
+pub enum RecordChange {
+    /// the record was inserted, or replaced a record with other data
+    Added(Record),
+    /// the record was deleted, or replaced by a record with other data
+    Removed(Record),
+}
+
+impl RecordChange {
+    /// Returns the record which was added or removed
+    pub fn get_record(&self) -> &Record {
+        match *self {
+            RecordChange::Added(ref record) |
+            RecordChange::Removed(ref record) => record,
+        }
+    }
+}
+
+/// Notified of the changes to the records of an `Authority`
+///
+/// Changes by `Authority::upsert()` and by dynamic updates are reported, once the update is
+///  applied. Signatures, NSEC records, and the SOA serial increment following an update, are not
+///  reported.
+pub trait ChangeListener {
+    /// Called with all changes made at once, while the `Authority` is locked for writing
+    fn records_changed(&self, origin: &Name, changes: &[RecordChange]);
+}
+
+/// Returned when a `ChangeListener` is added, to stop its notifications
+///
+/// Dropping the subscription does not unsubscribe the listener, the `Authority` only holds a weak
+///  reference to the listener though, and forgets it once the listener is dropped.
+#[derive(Clone, Debug)]
+pub struct ChangeSubscription {
+    active: Arc<AtomicBool>,
+}
+
+impl ChangeSubscription {
+    /// Creates an active subscription, see `Authority::subscribe_changes()`
+    pub fn new() -> Self {
+        ChangeSubscription { active: Arc::new(AtomicBool::new(true)) }
+    }
+
+    /// No further changes are reported to the listener, by every `Authority` it was added to
+    pub fn unsubscribe(&self) {
+        self.active.store(false, Ordering::SeqCst);
+    }
+
+    /// Returns true until `unsubscribe()` is called
+    pub fn is_active(&self) -> bool {
+        self.active.load(Ordering::SeqCst)
+    }
+}
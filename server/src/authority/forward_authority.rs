@@ -0,0 +1,391 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Zones whose queries are forwarded to upstream resolvers, conditional forwarding
+
+use std::fmt;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use trust_dns::cache::{Cached, DnsCache, DnsCacheStats, LatencyHistogram};
+use trust_dns::clock::{Clock, SystemClock};
+use trust_dns::error::*;
+use trust_dns::op::{Message, MessageType, OpCode, ResponseCode};
+use trust_dns::rr::{Name, Record};
+
+/// A resolver to which the queries of a `ForwardAuthority` are sent
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ForwardUpstream {
+    Udp(SocketAddr),
+    Tcp(SocketAddr),
+    /// DNS over TLS, RFC 7858, with the name the certificate of the resolver is issued for
+    Tls(SocketAddr, String),
+}
+
+impl ForwardUpstream {
+    /// The address of the resolver
+    pub fn get_addr(&self) -> SocketAddr {
+        match *self {
+            ForwardUpstream::Udp(addr) |
+            ForwardUpstream::Tcp(addr) |
+            ForwardUpstream::Tls(addr, _) => addr,
+        }
+    }
+}
+
+impl FromStr for ForwardUpstream {
+    type Err = ParseError;
+
+    /// Parses `192.0.2.1:53` or `udp://192.0.2.1:53`, `tcp://192.0.2.1:53`, or
+    ///  `tls://192.0.2.1:853#dns.example.com` with the name of the certificate after the `#`
+    fn from_str(upstream: &str) -> ParseResult<Self> {
+        let (protocol, addr) = match upstream.find("://") {
+            Some(separator) => (&upstream[..separator], &upstream[separator + 3..]),
+            None => ("udp", upstream),
+        };
+
+        match protocol {
+            "udp" => Ok(ForwardUpstream::Udp(try!(SocketAddr::from_str(addr)))),
+            "tcp" => Ok(ForwardUpstream::Tcp(try!(SocketAddr::from_str(addr)))),
+            "tls" => {
+                match addr.find('#') {
+                    Some(hash) if hash + 1 < addr.len() => {
+                        Ok(ForwardUpstream::Tls(try!(SocketAddr::from_str(&addr[..hash])),
+                                                addr[hash + 1..].to_string()))
+                    }
+                    _ => Err(ParseErrorKind::MissingToken("tls name".to_string()).into()),
+                }
+            }
+            _ => Err(ParseErrorKind::Msg(format!("unknown protocol: {}", upstream)).into()),
+        }
+    }
+}
+
+/// A difference between the responses of the upstreams and of the shadow of a `ForwardAuthority`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ShadowDivergence {
+    /// The response codes differ
+    ResponseCode {
+        primary: ResponseCode,
+        shadow: ResponseCode,
+    },
+    /// The answers differ, the records only in the response of the upstream, and only in that of
+    ///  the shadow, with their TTLs set to zero
+    Answers {
+        only_primary: Vec<Record>,
+        only_shadow: Vec<Record>,
+    },
+}
+
+impl ShadowDivergence {
+    /// The differences between the response of an upstream and that of the shadow to a query
+    ///
+    /// The answers are compared as sets, regardless of their order and their TTLs, which a cache
+    ///  counts down.
+    pub fn compare(primary: &Message, shadow: &Message) -> Vec<ShadowDivergence> {
+        let mut divergences = Vec::new();
+        if primary.get_response_code() != shadow.get_response_code() {
+            divergences.push(ShadowDivergence::ResponseCode {
+                primary: primary.get_response_code(),
+                shadow: shadow.get_response_code(),
+            });
+        }
+
+        let primary_answers = without_ttls(primary.get_answers());
+        let shadow_answers = without_ttls(shadow.get_answers());
+        let only_primary: Vec<Record> = primary_answers.iter()
+            .filter(|record| !shadow_answers.contains(record))
+            .cloned()
+            .collect();
+        let only_shadow: Vec<Record> = shadow_answers.iter()
+            .filter(|record| !primary_answers.contains(record))
+            .cloned()
+            .collect();
+        if !only_primary.is_empty() || !only_shadow.is_empty() {
+            divergences.push(ShadowDivergence::Answers {
+                only_primary: only_primary,
+                only_shadow: only_shadow,
+            });
+        }
+
+        divergences
+    }
+}
+
+impl fmt::Display for ShadowDivergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ShadowDivergence::ResponseCode { primary, shadow } => {
+                write!(f, "response code: {:?}, shadow: {:?}", primary, shadow)
+            }
+            ShadowDivergence::Answers { ref only_primary, ref only_shadow } => {
+                write!(f,
+                       "answers only from the upstream: {:?}, only from the shadow: {:?}",
+                       only_primary,
+                       only_shadow)
+            }
+        }
+    }
+}
+
+/// The records, sorted and without duplicates, with their TTLs set to zero
+fn without_ttls(records: &[Record]) -> Vec<Record> {
+    let mut records: Vec<Record> = records.iter()
+        .cloned()
+        .map(|mut record| {
+            record.ttl(0);
+            record
+        })
+        .collect();
+    records.sort();
+    records.dedup();
+    records
+}
+
+/// The counters of the comparisons with the shadow of a `ForwardAuthority`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ShadowStats {
+    compared: u64,
+    diverged: u64,
+    failed: u64,
+}
+
+impl ShadowStats {
+    /// The queries whose responses from an upstream and from the shadow were compared
+    pub fn get_compared(&self) -> u64 {
+        self.compared
+    }
+
+    /// The compared queries whose responses differed
+    pub fn get_diverged(&self) -> u64 {
+        self.diverged
+    }
+
+    /// The queries to which the shadow did not respond
+    pub fn get_failed(&self) -> u64 {
+        self.failed
+    }
+}
+
+/// A zone whose queries are forwarded to upstream resolvers, rather than answered from records
+///
+/// With a `ForwardAuthority` in the `Catalog` for some zones, and `Authority`s for others, one
+///  server answers the local zones and forwards the rest, e.g. for split horizon. A query is
+///  forwarded if the closest enclosing zone of its name is this one, see `Catalog::get_forward`,
+///  to each upstream in turn until one responds, by the `ServerFuture`.
+///
+/// The responses of the upstreams are cached if a `DnsCache` is set, answers for their TTL and
+///  NXDOMAIN and NODATA as RFC 2308 allows. The latencies of the answers from the cache and from
+///  the upstreams are kept apart, see `Catalog::forward_stats`.
+///
+/// A shadow resolver, e.g. a new one being evaluated, may be set, to which each query answered
+///  by an upstream is sent as well, once the upstream has responded. Its response is only compared
+///  with that of the upstream, differences are logged, see `ShadowDivergence`, and counted.
+pub struct ForwardAuthority {
+    origin: Name,
+    upstreams: Vec<ForwardUpstream>,
+    cache: Option<Mutex<DnsCache>>,
+    clock: Arc<Clock>,
+    hit_latency: Mutex<LatencyHistogram>,
+    upstream_latency: Mutex<LatencyHistogram>,
+    shadow: Option<ForwardUpstream>,
+    shadow_stats: Mutex<ShadowStats>,
+}
+
+impl ForwardAuthority {
+    /// A zone forwarded to the upstreams, in the order they are tried, without caching
+    pub fn new(origin: Name, upstreams: Vec<ForwardUpstream>) -> Self {
+        ForwardAuthority {
+            origin: origin,
+            upstreams: upstreams,
+            cache: None,
+            clock: Arc::new(SystemClock),
+            hit_latency: Mutex::new(LatencyHistogram::default()),
+            upstream_latency: Mutex::new(LatencyHistogram::default()),
+            shadow: None,
+            shadow_stats: Mutex::new(ShadowStats::default()),
+        }
+    }
+
+    /// The name of the zone
+    pub fn get_origin(&self) -> &Name {
+        &self.origin
+    }
+
+    /// The resolvers the queries are forwarded to, in the order they are tried
+    pub fn get_upstreams(&self) -> &[ForwardUpstream] {
+        &self.upstreams
+    }
+
+    /// Caches the responses of the upstreams, None to forward every query
+    pub fn set_cache(&mut self, cache: Option<DnsCache>) {
+        self.cache = cache.map(Mutex::new);
+    }
+
+    /// Returns true if the responses of the upstreams are cached
+    pub fn is_caching(&self) -> bool {
+        self.cache.is_some()
+    }
+
+    /// The counters of the cache, and the number of entries, None if it does not cache
+    pub fn get_cache_stats(&self) -> Option<(DnsCacheStats, usize)> {
+        self.cache.as_ref().map(|cache| {
+            let cache = cache.lock().unwrap(); // poison errors should panic
+            (cache.get_stats(), cache.len())
+        })
+    }
+
+    /// The latencies of the answers from the cache
+    pub fn get_hit_latency(&self) -> LatencyHistogram {
+        self.hit_latency.lock().unwrap().clone() // poison errors should panic
+    }
+
+    /// The latencies of the responses of the upstreams
+    pub fn get_upstream_latency(&self) -> LatencyHistogram {
+        self.upstream_latency.lock().unwrap().clone() // poison errors should panic
+    }
+
+    /// Records the time an upstream took to respond to a forwarded query
+    pub fn record_upstream_latency(&self, latency: Duration) {
+        self.upstream_latency.lock().unwrap().record(latency); // poison errors should panic
+    }
+
+    /// Sends the queries answered by an upstream to the shadow as well, None for no shadow
+    pub fn set_shadow(&mut self, shadow: Option<ForwardUpstream>) {
+        self.shadow = shadow;
+    }
+
+    /// The resolver the queries are compared with, None if there is none
+    pub fn get_shadow(&self) -> Option<&ForwardUpstream> {
+        self.shadow.as_ref()
+    }
+
+    /// The counters of the comparisons with the shadow, None if there is none
+    pub fn get_shadow_stats(&self) -> Option<ShadowStats> {
+        self.shadow.as_ref().map(|_| {
+            *self.shadow_stats.lock().unwrap() // poison errors should panic
+        })
+    }
+
+    /// Compares the responses of the upstream and of the shadow to a query, logging any
+    ///  differences, and returns them
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - the query which was forwarded
+    /// * `primary` - the response of the upstream
+    /// * `shadow` - the response of the shadow, None if it did not respond
+    pub fn compare_shadow(&self,
+                          request: &Message,
+                          primary: &Message,
+                          shadow: Option<&Message>)
+                          -> Vec<ShadowDivergence> {
+        let mut stats = self.shadow_stats.lock().unwrap(); // poison errors should panic
+        let shadow = match shadow {
+            Some(shadow) => shadow,
+            None => {
+                stats.failed += 1;
+                return vec![];
+            }
+        };
+
+        let divergences = ShadowDivergence::compare(primary, shadow);
+        stats.compared += 1;
+        if !divergences.is_empty() {
+            stats.diverged += 1;
+            for query in request.get_queries() {
+                for divergence in &divergences {
+                    warn!("shadow of zone: {} diverges for: {}: {}",
+                          self.origin,
+                          query,
+                          divergence);
+                }
+            }
+        }
+
+        divergences
+    }
+
+    /// Sets the clock by which cached responses expire, the system clock by default
+    pub fn set_clock(&mut self, clock: Arc<Clock>) {
+        self.clock = clock;
+    }
+
+    /// The response to the query from the cache, None if it is not cached
+    ///
+    /// Queries with the CHAIN option, RFC 7901, are never answered from the cache, which does not
+    ///  keep the chain of trust the upstream adds to the authority section; they are forwarded with
+    ///  the option, so that the requestor receives the chain in a single round trip.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - a query with a single question
+    pub fn get_cached(&self, request: &Message) -> Option<Message> {
+        let cache = match self.cache {
+            Some(ref cache) => cache,
+            None => return None,
+        };
+        let query = match request.get_queries().first() {
+            Some(query) => query,
+            None => return None,
+        };
+        if request.get_chain_trust_point().is_some() {
+            return None;
+        }
+
+        let start = Instant::now();
+        let cached = cache.lock()
+            .unwrap() // poison errors should panic
+            .get(query, self.clock.now());
+        let cached = match cached {
+            Some(cached) => cached,
+            None => return None,
+        };
+
+        let mut response = Message::new();
+        response.id(request.get_id())
+            .message_type(MessageType::Response)
+            .op_code(OpCode::Query)
+            .recursion_desired(request.is_recursion_desired())
+            .recursion_available(true)
+            .checking_disabled(request.is_checking_disabled());
+        response.add_query(query.clone());
+
+        match cached {
+            Cached::Records(records) => {
+                response.add_answers(records);
+            }
+            Cached::NxDomain(soa) => {
+                response.response_code(ResponseCode::NXDomain);
+                response.add_name_server(soa);
+            }
+            Cached::NoData(soa) => {
+                response.add_name_server(soa);
+            }
+        }
+
+        self.hit_latency.lock().unwrap().record(start.elapsed()); // poison errors should panic
+        Some(response)
+    }
+
+    /// Caches the response of an upstream to the query, if caching is enabled
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - the query which was forwarded
+    /// * `response` - the response of the upstream
+    pub fn cache_response(&self, request: &Message, response: &Message) {
+        if let (Some(cache), Some(query)) = (self.cache.as_ref(),
+                                            request.get_queries().first()) {
+            cache.lock()
+                .unwrap() // poison errors should panic
+                .insert_response(query, response, self.clock.now());
+        }
+    }
+}
@@ -0,0 +1,407 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Zone transfers received by a Slave zone from its primary, checked before they are installed
+
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+
+use trust_dns::op::{Message, MessageType, OpCode, Query, ResponseCode};
+use trust_dns::rr::{DNSClass, Name, RData, Record, RecordType};
+use trust_dns::rr::dnssec::TSigner;
+use trust_dns::rr::rdata::SOA;
+
+/// The most records accepted in a zone transfer, by default
+pub const DEFAULT_MAX_TRANSFER_RECORDS: usize = 1_000_000;
+/// The most messages a zone transfer is accepted in, by default
+pub const DEFAULT_MAX_TRANSFER_MESSAGES: usize = 100_000;
+/// The most bytes accepted in a zone transfer, by default
+pub const DEFAULT_MAX_TRANSFER_BYTES: usize = 256 * 1024 * 1024;
+
+/// Limits on a zone transfer received from the primary, a transfer exceeding any of them is
+///  rejected
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TransferLimits {
+    max_records: usize,
+    max_messages: usize,
+    max_bytes: usize,
+}
+
+impl Default for TransferLimits {
+    fn default() -> Self {
+        TransferLimits {
+            max_records: DEFAULT_MAX_TRANSFER_RECORDS,
+            max_messages: DEFAULT_MAX_TRANSFER_MESSAGES,
+            max_bytes: DEFAULT_MAX_TRANSFER_BYTES,
+        }
+    }
+}
+
+impl TransferLimits {
+    /// the most records in the answers of all the messages
+    pub fn max_records(&mut self, max_records: usize) -> &mut Self {
+        self.max_records = max_records;
+        self
+    }
+
+    /// the most messages the transfer is received in
+    pub fn max_messages(&mut self, max_messages: usize) -> &mut Self {
+        self.max_messages = max_messages;
+        self
+    }
+
+    /// the most bytes of all the messages, as received
+    pub fn max_bytes(&mut self, max_bytes: usize) -> &mut Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    pub fn get_max_records(&self) -> usize {
+        self.max_records
+    }
+
+    pub fn get_max_messages(&self) -> usize {
+        self.max_messages
+    }
+
+    pub fn get_max_bytes(&self) -> usize {
+        self.max_bytes
+    }
+}
+
+/// The reason a zone transfer was not installed
+#[derive(Clone, Debug, PartialEq)]
+pub enum TransferRejection {
+    /// the primary answered with an error
+    ErrorResponse(ResponseCode),
+    /// more records than `TransferLimits::max_records`
+    TooManyRecords(usize),
+    /// more messages than `TransferLimits::max_messages`
+    TooManyMessages(usize),
+    /// more bytes than `TransferLimits::max_bytes`
+    TooManyBytes(usize),
+    /// the records are not a complete AXFR or IXFR response
+    Malformed(String),
+    /// the serial of the transfer is not newer than that of the zone, the current and the received
+    SerialNotIncreased(u32, u32),
+    /// a record which is not in the zone
+    OutOfZone(Name),
+    /// the transferred zone has no SOA or NS at its apex
+    ApexDeleted(RecordType),
+}
+
+impl fmt::Display for TransferRejection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TransferRejection::ErrorResponse(response_code) => {
+                write!(f, "error response: {}", response_code.to_str())
+            }
+            TransferRejection::TooManyRecords(records) => {
+                write!(f, "too many records: {}", records)
+            }
+            TransferRejection::TooManyMessages(messages) => {
+                write!(f, "too many messages: {}", messages)
+            }
+            TransferRejection::TooManyBytes(bytes) => write!(f, "too many bytes: {}", bytes),
+            TransferRejection::Malformed(ref reason) => write!(f, "malformed: {}", reason),
+            TransferRejection::SerialNotIncreased(current, received) => {
+                write!(f, "serial: {} is not newer than: {}", received, current)
+            }
+            TransferRejection::OutOfZone(ref name) => write!(f, "out of zone record: {}", name),
+            TransferRejection::ApexDeleted(record_type) => {
+                write!(f, "no {:?} at the apex of the zone", record_type)
+            }
+        }
+    }
+}
+
+/// A zone transfer, AXFR or IXFR, being received from the primary
+///
+/// The messages are added as they are received, the limits are checked as they are, so that the
+///  transfer can be abandoned as soon as one is exceeded. The transfer is then installed, or
+///  quarantined if it is rejected, with `Authority::install_transfer`.
+#[derive(Clone, Debug)]
+pub struct InboundTransfer {
+    limits: TransferLimits,
+    request_serial: Option<u32>,
+    records: Vec<Record>,
+    messages: usize,
+    bytes: usize,
+    complete: bool,
+    rejection: Option<TransferRejection>,
+}
+
+impl InboundTransfer {
+    /// Starts receiving an AXFR
+    ///
+    /// # Arguments
+    ///
+    /// * `limits` - usually those of the zone, see `Authority::get_transfer_limits`
+    pub fn new(limits: TransferLimits) -> Self {
+        Self::with_serial(limits, None)
+    }
+
+    /// Starts receiving the transfer of a request, see `TransferRequest::get_serial`
+    ///
+    /// # Arguments
+    ///
+    /// * `limits` - usually those of the zone, see `Authority::get_transfer_limits`
+    /// * `request_serial` - the serial of the zone an IXFR is requested from, None for an AXFR
+    pub fn with_serial(limits: TransferLimits, request_serial: Option<u32>) -> Self {
+        InboundTransfer {
+            limits: limits,
+            request_serial: request_serial,
+            records: Vec::new(),
+            messages: 0,
+            bytes: 0,
+            complete: false,
+            rejection: None,
+        }
+    }
+
+    /// Adds the answers of a message of the transfer
+    ///
+    /// # Arguments
+    ///
+    /// * `message` - the next response from the primary
+    /// * `len` - the length of the message as it was received
+    ///
+    /// # Return value
+    ///
+    /// The rejection if the message exceeds the limits, or is an error, no further messages should
+    ///  be received then.
+    pub fn add_message(&mut self, message: &Message, len: usize) -> Result<(), TransferRejection> {
+        if let Some(ref rejection) = self.rejection {
+            return Err(rejection.clone());
+        }
+
+        self.messages += 1;
+        self.bytes += len;
+
+        let rejection = if message.get_response_code() != ResponseCode::NoError {
+            Some(TransferRejection::ErrorResponse(message.get_response_code()))
+        } else if self.messages > self.limits.max_messages {
+            Some(TransferRejection::TooManyMessages(self.messages))
+        } else if self.bytes > self.limits.max_bytes {
+            Some(TransferRejection::TooManyBytes(self.bytes))
+        } else if self.records.len() + message.get_answers().len() > self.limits.max_records {
+            Some(TransferRejection::TooManyRecords(self.records.len() +
+                                                   message.get_answers().len()))
+        } else {
+            None
+        };
+
+        match rejection {
+            Some(rejection) => {
+                self.rejection = Some(rejection.clone());
+                Err(rejection)
+            }
+            None => {
+                self.records.extend(message.get_answers().iter().cloned());
+                self.complete = self.ends_transfer();
+                Ok(())
+            }
+        }
+    }
+
+    /// true if the records end with the SOA they start with, RFC 5936 section 2.2 and RFC 1995
+    ///  section 4; only the first and the last records are looked at, so that the records of a
+    ///  large transfer are not parsed again with each message
+    fn ends_transfer(&self) -> bool {
+        let serial = match self.records.first().map(|record| record.get_rdata()) {
+            Some(&RData::SOA(ref soa)) => soa.get_serial(),
+            // nothing to wait for, it is rejected as malformed
+            Some(_) => return true,
+            None => return false,
+        };
+
+        // only the SOA, for an IXFR when the zone is up to date with the primary, the first
+        //  message of an AXFR may hold just the SOA as well
+        if self.records.len() == 1 {
+            return self.request_serial == Some(serial);
+        }
+
+        match self.records.last().map(|record| record.get_rdata()) {
+            Some(&RData::SOA(ref soa)) => soa.get_serial() == serial,
+            _ => false,
+        }
+    }
+
+    /// true once the last message is received, the final SOA ends the transfer, or a limit is
+    ///  exceeded
+    pub fn is_complete(&self) -> bool {
+        self.rejection.is_some() || self.complete
+    }
+
+    /// the serial of the zone an IXFR was requested from, None for an AXFR
+    pub fn get_request_serial(&self) -> Option<u32> {
+        self.request_serial
+    }
+
+    /// the answers of all the messages so far
+    pub fn get_records(&self) -> &[Record] {
+        &self.records
+    }
+
+    /// Returns the answers of all the messages
+    pub fn into_records(self) -> Vec<Record> {
+        self.records
+    }
+
+    /// the number of messages received
+    pub fn get_message_count(&self) -> usize {
+        self.messages
+    }
+
+    /// the number of bytes received
+    pub fn get_byte_count(&self) -> usize {
+        self.bytes
+    }
+
+    /// the reason the transfer is rejected, if one of the limits was exceeded
+    pub fn get_rejection(&self) -> Option<&TransferRejection> {
+        self.rejection.as_ref()
+    }
+}
+
+/// A refresh of a Slave zone, the transfer to request from its primary, see
+///  `Authority::start_refresh`
+#[derive(Clone, Debug)]
+pub struct TransferRequest {
+    origin: Name,
+    class: DNSClass,
+    primary: SocketAddr,
+    serial: Option<u32>,
+    limits: TransferLimits,
+    source: Option<IpAddr>,
+    tsigner: Option<Arc<TSigner>>,
+}
+
+impl TransferRequest {
+    /// Creates a new request
+    ///
+    /// # Arguments
+    ///
+    /// * `origin` - the zone to transfer
+    /// * `class` - the class of the zone
+    /// * `primary` - the address of the primary master
+    /// * `serial` - the serial of the zone, for an IXFR, or None for an AXFR of the whole zone
+    /// * `limits` - the limits on the transfer, see `InboundTransfer`
+    pub fn new(origin: Name,
+               class: DNSClass,
+               primary: SocketAddr,
+               serial: Option<u32>,
+               limits: TransferLimits)
+               -> Self {
+        TransferRequest {
+            origin: origin,
+            class: class,
+            primary: primary,
+            serial: serial,
+            limits: limits,
+            source: None,
+            tsigner: None,
+        }
+    }
+
+    /// the local address the queries to the primary are sent from, by default any
+    pub fn source(&mut self, source: Option<IpAddr>) -> &mut Self {
+        self.source = source;
+        self
+    }
+
+    /// the key of the primary, the queries are signed with it and the responses verified, TSIG
+    pub fn tsigner(&mut self, tsigner: Option<Arc<TSigner>>) -> &mut Self {
+        self.tsigner = tsigner;
+        self
+    }
+
+    /// The same request, for an AXFR of the whole zone, for primaries which do not implement IXFR
+    pub fn to_axfr(&self) -> Self {
+        let mut axfr = self.clone();
+        axfr.serial = None;
+        axfr
+    }
+
+    pub fn get_origin(&self) -> &Name {
+        &self.origin
+    }
+
+    pub fn get_primary(&self) -> SocketAddr {
+        self.primary
+    }
+
+    /// the serial of the zone, None when the whole zone is requested
+    pub fn get_serial(&self) -> Option<u32> {
+        self.serial
+    }
+
+    pub fn get_limits(&self) -> &TransferLimits {
+        &self.limits
+    }
+
+    pub fn get_source(&self) -> Option<IpAddr> {
+        self.source
+    }
+
+    pub fn get_tsigner(&self) -> Option<&Arc<TSigner>> {
+        self.tsigner.as_ref()
+    }
+
+    /// Returns the query for the transfer, an IXFR with the SOA of the zone in the authority
+    ///  section, RFC 1995 section 3, or an AXFR without a serial
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - the id of the query
+    pub fn to_message(&self, id: u16) -> Message {
+        let mut message: Message = Message::new();
+        message.id(id)
+            .message_type(MessageType::Query)
+            .op_code(OpCode::Query);
+
+        let query_type = if self.serial.is_some() {
+            RecordType::IXFR
+        } else {
+            RecordType::AXFR
+        };
+
+        let mut query: Query = Query::new();
+        query.name(self.origin.clone()).query_class(self.class).query_type(query_type);
+        message.add_query(query);
+
+        if let Some(serial) = self.serial {
+            // only the serial of the SOA is compared by the primary
+            let soa = SOA::new(self.origin.clone(), self.origin.clone(), serial, 0, 0, 0, 0);
+            let mut soa =
+                Record::from_rdata(self.origin.clone(), 0, RecordType::SOA, RData::SOA(soa));
+            soa.dns_class(self.class);
+            message.add_name_server(soa);
+        }
+
+        message
+    }
+
+    /// Returns the query for the SOA of the zone, to compare the serial of the primary with that
+    ///  of the zone before a transfer is requested, RFC 1034 section 4.3.5
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - the id of the query
+    pub fn to_soa_query(&self, id: u16) -> Message {
+        let mut message: Message = Message::new();
+        message.id(id)
+            .message_type(MessageType::Query)
+            .op_code(OpCode::Query);
+
+        let mut query: Query = Query::new();
+        query.name(self.origin.clone()).query_class(self.class).query_type(RecordType::SOA);
+        message.add_query(query);
+        message
+    }
+}
@@ -0,0 +1,473 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Zones served from an external key-value store, e.g. Redis, rather than from memory
+
+use std::collections::HashMap;
+use std::io;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use trust_dns::error::DnsSecResult;
+use trust_dns::op::{Message, Query, ResponseCode};
+use trust_dns::rr::{DNSClass, Name, RData, Record, RecordType};
+use trust_dns::rr::dnssec::SupportedAlgorithms;
+use trust_dns::serialize::binary::{BinDecoder, BinEncoder, BinSerializable};
+
+use authority::{AuthorityObject, UpdateResult, ZoneType};
+
+/// Seconds for which the values read from the store are cached, by default
+pub const DEFAULT_CACHE_TTL: u64 = 10;
+
+/// The most values which are cached, by default
+pub const DEFAULT_CACHE_ENTRIES: usize = 10000;
+
+/// A store of values by key, e.g. a Redis server, see `RedisStore`
+///
+/// The methods take `&self`, a store which is shared synchronizes itself.
+pub trait KeyValueStore {
+    /// Returns the value of the key, None if it has none
+    fn get(&self, key: &str) -> io::Result<Option<Vec<u8>>>;
+
+    /// Sets the value of the key, replacing any it had
+    fn set(&self, key: &str, value: &[u8]) -> io::Result<()>;
+
+    /// Removes the key and its value, if it has one
+    fn delete(&self, key: &str) -> io::Result<()>;
+
+    /// Sets the values of the keys, or removes those which are None, by default one after the
+    ///  other; a store with transactions makes all the changes or none of them
+    fn write(&self, changes: &[(&str, Option<&[u8]>)]) -> io::Result<()> {
+        for &(key, value) in changes {
+            try!(match value {
+                Some(value) => self.set(key, value),
+                None => self.delete(key),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// A value read from the store, None if the key had none
+struct CacheEntry {
+    value: Option<Vec<u8>>,
+    read_at: Instant,
+}
+
+/// A Master zone whose records are kept in a `KeyValueStore`, rather than in memory
+///
+/// Each RRset is the value of the key `<zone>|<name>|<type>`, its records in the wire format, and
+///  the types of the RRsets of each name that of `<zone>|<name>`, separated by spaces; the names
+///  are in lower case. The values read are cached for a while, see `set_cache`, so that a zone
+///  changed by another writer of the store is served as changed once they expire, those changed
+///  with `update_records` are removed from the cache straight away.
+///
+/// While the store fails, the values in the cache are served even if they expired, the queries
+///  for any others are answered with SERVFAIL, see `AuthorityObject::is_available`. The zone is
+///  not signed, and dynamic updates are refused, as their SIG(0) can not be verified without the
+///  keys of the zone; the server does not load such a zone with `allow_update`.
+pub struct KeyValueAuthority {
+    origin: Name,
+    class: DNSClass,
+    store: Box<KeyValueStore>,
+    cache: Mutex<HashMap<String, CacheEntry>>,
+    cache_ttl: Duration,
+    max_cache_entries: usize,
+    /// false once reading the store failed, since the last search
+    available: AtomicBool,
+}
+
+impl KeyValueAuthority {
+    /// Creates a zone served from the store, whose records are not read until they are queried
+    ///
+    /// # Arguments
+    ///
+    /// * `origin` - the name of the zone, the prefix of its keys in the store
+    /// * `store` - the store of the records, see `update_records` to add them
+    pub fn new(origin: Name, store: Box<KeyValueStore>) -> Self {
+        KeyValueAuthority {
+            origin: origin,
+            class: DNSClass::IN,
+            store: store,
+            cache: Mutex::new(HashMap::new()),
+            cache_ttl: Duration::from_secs(DEFAULT_CACHE_TTL),
+            max_cache_entries: DEFAULT_CACHE_ENTRIES,
+            available: AtomicBool::new(true),
+        }
+    }
+
+    /// Caches each value read from the store for the ttl, at most max_entries of them, a zero
+    ///  ttl reads every value from the store as it is queried
+    pub fn set_cache(&mut self, ttl: Duration, max_entries: usize) {
+        self.cache_ttl = ttl;
+        self.max_cache_entries = max_entries;
+    }
+
+    /// Returns for how long the values read from the store are cached
+    pub fn get_cache_ttl(&self) -> Duration {
+        self.cache_ttl
+    }
+
+    /// Changes the records of the zone in the store, with the rules of the update section of a
+    ///  dynamic update, RFC 2136 section 3.4.2
+    ///
+    /// Records of the class of the zone are added, those of class ANY delete the RRset of their
+    ///  type, or all those of the name for type ANY, those of class NONE delete the record with
+    ///  their data. The SOA and NS records at the apex are never deleted. The changed values are
+    ///  removed from the cache.
+    ///
+    /// # Arguments
+    ///
+    /// * `records` - the records to add or delete, all of them in the zone
+    /// * `increment_serial` - if true, the serial of the SOA is incremented when the zone
+    ///                        changed, false to load the records of a zone file
+    ///
+    /// # Return value
+    ///
+    /// true if the zone changed, SERVFAIL if the store failed, which may leave the zone partially
+    ///  changed, though each RRset is written with the types of its name, see
+    ///  `KeyValueStore::write`
+    pub fn update_records(&self, records: &[Record], increment_serial: bool) -> UpdateResult<bool> {
+        if let Some(record) = records.iter().find(|r| !self.origin.zone_of(r.get_name())) {
+            warn!("record: {} is not in zone: {}", record.get_name(), self.origin);
+            return Err(ResponseCode::NotZone);
+        }
+
+        let mut updated = false;
+        for rr in records {
+            let name = rr.get_name();
+            let rr_type = rr.get_rr_type();
+            let is_apex = name == &self.origin;
+
+            match rr.get_dns_class() {
+                class @ _ if class == self.class => {
+                    let mut rrset = try!(self.fetch_records(name, rr_type));
+                    if rrset.iter().any(|r| r == rr) {
+                        continue;
+                    }
+
+                    // the SOA and a CNAME are replaced, others with the same data as well
+                    match rr_type {
+                        RecordType::SOA | RecordType::CNAME => rrset.clear(),
+                        _ => rrset.retain(|r| r.get_rdata() != rr.get_rdata()),
+                    }
+                    rrset.push(rr.clone());
+                    try!(self.store_records(name, rr_type, &rrset));
+                    updated = true;
+                }
+                DNSClass::ANY => {
+                    let types = match rr_type {
+                        RecordType::ANY => try!(self.fetch_types(name)),
+                        rr_type @ _ => vec![rr_type],
+                    };
+
+                    for rr_type in types {
+                        if is_apex && (rr_type == RecordType::SOA || rr_type == RecordType::NS) {
+                            continue;
+                        }
+
+                        if !try!(self.fetch_records(name, rr_type)).is_empty() {
+                            try!(self.store_records(name, rr_type, &[]));
+                            updated = true;
+                        }
+                    }
+                }
+                DNSClass::NONE => {
+                    if rr_type == RecordType::SOA {
+                        continue;
+                    }
+
+                    let mut rrset = try!(self.fetch_records(name, rr_type));
+                    let before = rrset.len();
+                    rrset.retain(|r| r.get_rdata() != rr.get_rdata());
+                    if rrset.len() == before ||
+                       (is_apex && rr_type == RecordType::NS && rrset.is_empty()) {
+                        continue;
+                    }
+
+                    try!(self.store_records(name, rr_type, &rrset));
+                    updated = true;
+                }
+                class @ _ => {
+                    info!("unexpected DNS Class: {:?}", class);
+                    return Err(ResponseCode::FormErr);
+                }
+            }
+        }
+
+        if updated && increment_serial {
+            try!(self.increment_soa_serial());
+        }
+        Ok(updated)
+    }
+
+    /// Increments the serial of the SOA in the store, if the zone has one
+    fn increment_soa_serial(&self) -> UpdateResult<()> {
+        let mut soa = try!(self.fetch_records(&self.origin, RecordType::SOA));
+        if soa.is_empty() {
+            return Ok(());
+        }
+
+        if let RData::SOA(ref mut rdata) = *soa[0].get_rdata_mut() {
+            rdata.increment_serial();
+        }
+        self.store_records(&self.origin, RecordType::SOA, &soa[..1])
+    }
+
+    /// The key of the RRset of the name and type
+    fn records_key(&self, name: &Name, rr_type: RecordType) -> String {
+        format!("{}|{}|{}",
+                self.origin.to_lowercase(),
+                name.to_lowercase(),
+                rr_type)
+    }
+
+    /// The key of the types of the RRsets of the name
+    fn types_key(&self, name: &Name) -> String {
+        format!("{}|{}", self.origin.to_lowercase(), name.to_lowercase())
+    }
+
+    /// The records of the RRset, through the cache
+    fn read_records(&self, name: &Name, rr_type: RecordType) -> Vec<Record> {
+        self.read(&self.records_key(name, rr_type))
+            .map(|value| decode_records(&value))
+            .unwrap_or(vec![])
+    }
+
+    /// The types of the RRsets of the name, through the cache
+    fn read_types(&self, name: &Name) -> Vec<RecordType> {
+        self.read(&self.types_key(name))
+            .map(|value| decode_types(&value))
+            .unwrap_or(vec![])
+    }
+
+    /// Reads the value of the key from the cache, or if it expired from the store, noting when
+    ///  the store fails and there is no value in the cache
+    fn read(&self, key: &str) -> Option<Vec<u8>> {
+        let now = Instant::now();
+        let cached: Option<Option<Vec<u8>>> = {
+            let cache = self.cache.lock().unwrap(); // poison errors should panic
+            match cache.get(key) {
+                Some(entry) if now.duration_since(entry.read_at) < self.cache_ttl => {
+                    return entry.value.clone();
+                }
+                Some(entry) => Some(entry.value.clone()),
+                None => None,
+            }
+        };
+
+        let value = match self.store.get(key) {
+            Ok(value) => value,
+            Err(e) => {
+                warn!("reading key: {} of zone: {} from the store failed: {}",
+                      key,
+                      self.origin,
+                      e);
+                // the expired value is better than none
+                return match cached {
+                    Some(value) => value,
+                    None => {
+                        self.available.store(false, Ordering::Relaxed);
+                        None
+                    }
+                };
+            }
+        };
+
+        if self.max_cache_entries > 0 {
+            let mut cache = self.cache.lock().unwrap(); // poison errors should panic
+            if cache.len() >= self.max_cache_entries && !cache.contains_key(key) {
+                // the expired values go first, otherwise all of them
+                let ttl = self.cache_ttl;
+                let expired: Vec<String> = cache.iter()
+                    .filter(|&(_, entry)| now.duration_since(entry.read_at) >= ttl)
+                    .map(|(expired, _)| expired.clone())
+                    .collect();
+                for expired in expired {
+                    cache.remove(&expired);
+                }
+                if cache.len() >= self.max_cache_entries {
+                    cache.clear();
+                }
+            }
+
+            cache.insert(key.to_string(),
+                         CacheEntry {
+                             value: value.clone(),
+                             read_at: now,
+                         });
+        }
+        value
+    }
+
+    /// The records of the RRset, from the store
+    fn fetch_records(&self, name: &Name, rr_type: RecordType) -> UpdateResult<Vec<Record>> {
+        let value = try!(self.fetch(&self.records_key(name, rr_type)));
+        Ok(value.map(|value| decode_records(&value)).unwrap_or(vec![]))
+    }
+
+    /// The types of the RRsets of the name, from the store
+    fn fetch_types(&self, name: &Name) -> UpdateResult<Vec<RecordType>> {
+        let value = try!(self.fetch(&self.types_key(name)));
+        Ok(value.map(|value| decode_types(&value)).unwrap_or(vec![]))
+    }
+
+    /// Reads the value of the key from the store, not the cache, to change it
+    fn fetch(&self, key: &str) -> UpdateResult<Option<Vec<u8>>> {
+        self.store.get(key).map_err(|e| {
+            error!("reading key: {} from the store failed: {}", key, e);
+            ResponseCode::ServFail
+        })
+    }
+
+    /// Writes the RRset to the store, deleting it if it is empty, along with the types of its
+    ///  name, and removes both from the cache
+    fn store_records(&self,
+                     name: &Name,
+                     rr_type: RecordType,
+                     records: &[Record])
+                     -> UpdateResult<()> {
+        let records_key = self.records_key(name, rr_type);
+        let types_key = self.types_key(name);
+        {
+            let mut cache = self.cache.lock().unwrap(); // poison errors should panic
+            cache.remove(&records_key);
+            cache.remove(&types_key);
+        }
+
+        let mut types = try!(self.fetch_types(name));
+        types.retain(|t| *t != rr_type);
+        let value = if records.is_empty() {
+            None
+        } else {
+            types.push(rr_type);
+            match encode_records(records) {
+                Some(value) => Some(value),
+                None => return Err(ResponseCode::FormErr),
+            }
+        };
+        let types_value = if types.is_empty() {
+            None
+        } else {
+            Some(encode_types(&types))
+        };
+
+        // the RRset and the types of its name are changed together
+        let changes = [(&records_key[..], value.as_ref().map(|value| &value[..])),
+                       (&types_key[..], types_value.as_ref().map(|types| types.as_bytes()))];
+        self.store.write(&changes).map_err(|e| {
+            error!("writing records: {} {} to the store failed: {}", name, rr_type, e);
+            ResponseCode::ServFail
+        })
+    }
+}
+
+impl AuthorityObject for KeyValueAuthority {
+    fn get_origin(&self) -> Name {
+        self.origin.clone()
+    }
+
+    fn get_zone_type(&self) -> ZoneType {
+        ZoneType::Master
+    }
+
+    fn lookup(&self,
+              name: &Name,
+              rtype: RecordType,
+              _: bool,
+              _: SupportedAlgorithms)
+              -> Vec<Record> {
+        let types = self.read_types(name);
+        match rtype {
+            RecordType::ANY => {
+                let mut records = Vec::new();
+                for rr_type in types {
+                    records.extend(self.read_records(name, rr_type));
+                }
+                records
+            }
+            _ if types.contains(&rtype) => self.read_records(name, rtype),
+            // the CNAME of the name answers for every type
+            _ if types.contains(&RecordType::CNAME) => self.read_records(name, RecordType::CNAME),
+            _ => vec![],
+        }
+    }
+
+    fn search(&self,
+              query: &Query,
+              is_secure: bool,
+              supported_algorithms: SupportedAlgorithms)
+              -> Vec<Record> {
+        self.available.store(true, Ordering::Relaxed);
+        self.lookup(query.get_name(),
+                    query.get_query_type(),
+                    is_secure,
+                    supported_algorithms)
+    }
+
+    fn contains_name(&self, name: &Name) -> bool {
+        !self.read_types(name).is_empty()
+    }
+
+    fn update(&self, _: &Message) -> UpdateResult<bool> {
+        warn!("update attempted on zone: {} of a key-value store", self.origin);
+        Err(ResponseCode::Refused)
+    }
+
+    fn secure_zone(&self) -> DnsSecResult<()> {
+        Ok(())
+    }
+
+    fn is_available(&self) -> bool {
+        self.available.load(Ordering::Relaxed)
+    }
+}
+
+/// The records of an RRset in the wire format, without name compression
+fn encode_records(records: &[Record]) -> Option<Vec<u8>> {
+    let mut value: Vec<u8> = Vec::with_capacity(512);
+    {
+        let mut encoder = BinEncoder::new(&mut value);
+        encoder.set_canonical_names(true);
+        for record in records {
+            if let Err(e) = record.emit(&mut encoder) {
+                warn!("could not encode record: {:?}: {}", record, e);
+                return None;
+            }
+        }
+    }
+
+    Some(value)
+}
+
+fn decode_records(value: &[u8]) -> Vec<Record> {
+    let mut decoder = BinDecoder::new(value);
+    let mut records = Vec::new();
+    while decoder.len() > 0 {
+        match Record::read(&mut decoder) {
+            Ok(record) => records.push(record),
+            Err(e) => {
+                warn!("bad records in the store: {}", e);
+                break;
+            }
+        }
+    }
+
+    records
+}
+
+fn encode_types(types: &[RecordType]) -> String {
+    types.iter().map(|t| t.to_string()).collect::<Vec<String>>().join(" ")
+}
+
+fn decode_types(value: &[u8]) -> Vec<RecordType> {
+    String::from_utf8_lossy(value)
+        .split_whitespace()
+        .filter_map(|t| RecordType::from_str(t).ok())
+        .collect()
+}
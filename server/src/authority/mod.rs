@@ -28,10 +28,49 @@ pub enum ZoneType {
     Forward,
 }
 
+impl ZoneType {
+    /// Master and Slave zones are authoritative, answers from them have the AA bit set
+    pub fn is_authoritative(&self) -> bool {
+        match *self {
+            ZoneType::Master | ZoneType::Slave => true,
+            ZoneType::Hint | ZoneType::Forward => false,
+        }
+    }
+}
+
 pub mod authority;
+mod apex_alias;
+mod authority_object;
 mod catalog;
+mod change_listener;
+mod forward_authority;
+mod inbound_transfer;
+mod key_value_authority;
+mod outbound_notify;
 pub mod persistence;
+mod redis_store;
+mod shared_authority;
+mod stats;
+mod transfer_acl;
+mod update_policy;
+mod zone_file;
 
+pub use self::apex_alias::AliasRequest;
 pub use self::authority::Authority;
-pub use self::catalog::Catalog;
+pub use self::authority_object::AuthorityObject;
+pub use self::catalog::{Catalog, MAX_CNAME_CHAIN};
+pub use self::change_listener::{ChangeListener, ChangeSubscription, RecordChange};
+pub use self::forward_authority::{ForwardAuthority, ForwardUpstream, ShadowDivergence,
+                                  ShadowStats};
+pub use self::inbound_transfer::{InboundTransfer, TransferLimits, TransferRejection,
+                                 TransferRequest};
+pub use self::key_value_authority::{KeyValueAuthority, KeyValueStore, DEFAULT_CACHE_ENTRIES,
+                                    DEFAULT_CACHE_TTL};
+pub use self::outbound_notify::NotifyRequest;
 pub use self::persistence::Journal;
+pub use self::redis_store::RedisStore;
+pub use self::shared_authority::{AuthorityWriteGuard, SharedAuthority};
+pub use self::stats::{ForwardStats, ZoneStats};
+pub use self::transfer_acl::{TransferAcl, TransferRule};
+pub use self::update_policy::{NameMatch, UpdatePolicy, UpdateRule};
+pub use self::zone_file::ZoneFile;
@@ -0,0 +1,57 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! NOTIFY messages sent to the secondaries of a zone when it changes, RFC 1996
+
+use std::net::SocketAddr;
+
+use trust_dns::rr::{DNSClass, Name, Record};
+
+/// The secondaries to notify of a new serial of a zone, see `Authority::start_notify`
+#[derive(Clone, Debug)]
+pub struct NotifyRequest {
+    origin: Name,
+    class: DNSClass,
+    soa: Record,
+    secondaries: Vec<SocketAddr>,
+}
+
+impl NotifyRequest {
+    /// Creates a new request
+    ///
+    /// # Arguments
+    ///
+    /// * `origin` - the zone which changed
+    /// * `class` - the class of the zone
+    /// * `soa` - the SOA of the zone, with the new serial, sent in the answers of the NOTIFY
+    /// * `secondaries` - the addresses to send the NOTIFY to
+    pub fn new(origin: Name, class: DNSClass, soa: Record, secondaries: Vec<SocketAddr>) -> Self {
+        NotifyRequest {
+            origin: origin,
+            class: class,
+            soa: soa,
+            secondaries: secondaries,
+        }
+    }
+
+    pub fn get_origin(&self) -> &Name {
+        &self.origin
+    }
+
+    pub fn get_class(&self) -> DNSClass {
+        self.class
+    }
+
+    /// the SOA of the zone, RFC 1996 section 3.7
+    pub fn get_soa(&self) -> &Record {
+        &self.soa
+    }
+
+    pub fn get_secondaries(&self) -> &[SocketAddr] {
+        &self.secondaries
+    }
+}
@@ -5,18 +5,20 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 use std::iter::Iterator;
+use std::mem;
 use std::path::Path;
 
 use time;
 use rusqlite;
 use rusqlite::Connection;
 
-use trust_dns::rr::Record;
+use trust_dns::op::ZoneDelta;
+use trust_dns::rr::{Record, RecordType};
 use trust_dns::serialize::binary::{BinDecoder, BinEncoder, BinSerializable};
 
 use error::{PersistenceErrorKind, PersistenceResult};
 
-pub const CURRENT_VERSION: i64 = 1;
+pub const CURRENT_VERSION: i64 = 2;
 
 /// The Journal is the audit log of all changes to a zone after initial creation.
 pub struct Journal {
@@ -71,12 +73,7 @@ impl Journal {
         assert!(self.version == CURRENT_VERSION,
                 "schema version mismatch, schema_up() resolves this");
 
-        let mut serial_record: Vec<u8> = Vec::with_capacity(512);
-        {
-            let mut encoder = BinEncoder::new(&mut serial_record);
-            try!(record.emit(&mut encoder));
-        }
-
+        let serial_record = try!(encode_record(record));
         let timestamp = time::get_time();
         let client_id: i64 = 0; // TODO: we need better id information about the client, like pub_key
         let soa_serial: i64 = soa_serial as i64;
@@ -97,8 +94,150 @@ impl Journal {
     }
 
     /// Inserts a set of records into the Journal, a convenience method for insert_record
+    ///
+    /// The records are inserted in a single transaction, an update is never partially persisted.
     pub fn insert_records(&self, soa_serial: u32, records: &[Record]) -> PersistenceResult<()> {
-        // TODO: NEED TRANSACTION HERE
+        self.in_transaction(|| {
+            for record in records {
+                try!(self.insert_record(soa_serial, record));
+            }
+
+            Ok(())
+        })
+    }
+
+    /// Appends a snapshot of the whole zone, marked by an AXFR record which clears the zone when
+    ///  recovering, see `Authority::recover_with_journal`, in a single transaction
+    ///
+    /// # Arguments
+    ///
+    /// * `soa_serial` - the serial of the zone
+    /// * `records` - all the records of the zone
+    pub fn insert_snapshot(&self, soa_serial: u32, records: &[Record]) -> PersistenceResult<()> {
+        self.in_transaction(|| self.insert_snapshot_records(soa_serial, records))
+    }
+
+    /// Replaces everything in the journal with a snapshot of the zone, in a single transaction
+    ///
+    /// The updates are otherwise kept forever, so that the journal, and the time to recover the
+    ///  zone from it, grows with every update. After compaction the zone is recovered from the
+    ///  snapshot alone, the history of the updates before it is lost.
+    ///
+    /// # Arguments
+    ///
+    /// * `soa_serial` - the serial of the zone
+    /// * `records` - all the records of the zone
+    pub fn compact(&self, soa_serial: u32, records: &[Record]) -> PersistenceResult<()> {
+        assert!(self.version == CURRENT_VERSION,
+                "schema version mismatch, schema_up() resolves this");
+
+        self.in_transaction(|| {
+            try!(self.conn.execute("DELETE FROM records", &[]));
+            self.insert_snapshot_records(soa_serial, records)
+        })
+    }
+
+    /// The number of records in the journal, of the updates and of the snapshots
+    pub fn get_len(&self) -> PersistenceResult<i64> {
+        assert!(self.version == CURRENT_VERSION,
+                "schema version mismatch, schema_up() resolves this");
+
+        let count: i64 =
+            try!(self.conn.query_row("SELECT COUNT(*) FROM records", &[], |row| row.get(0)));
+        Ok(count)
+    }
+
+    /// Appends the changes of an update, for incremental zone transfers, dropping the oldest
+    ///  deltas beyond `max_deltas`, in a single transaction
+    ///
+    /// The deltas are kept apart from the records of the zone, compaction does not remove them.
+    ///
+    /// # Arguments
+    ///
+    /// * `delta` - the changes from the serial of the delta before, if any
+    /// * `max_deltas` - the most deltas which are kept
+    pub fn insert_delta(&self, delta: &ZoneDelta, max_deltas: usize) -> PersistenceResult<()> {
+        assert!(self.version == CURRENT_VERSION,
+                "schema version mismatch, schema_up() resolves this");
+
+        self.in_transaction(|| {
+            let delta_id: i64 = try!(self.conn.query_row("SELECT COALESCE(MAX(delta_id), 0) + 1 \
+                                                          FROM deltas",
+                                                         &[],
+                                                         |row| row.get(0)));
+            for record in delta.records() {
+                let serial_record = try!(encode_record(record));
+                let count = try!(self.conn.execute("INSERT INTO deltas (delta_id, record) \
+                                                    VALUES ($1, $2)",
+                                                   &[&delta_id, &serial_record]));
+                if count != 1 {
+                    return Err(PersistenceErrorKind::WrongInsertCount(count, 1).into());
+                }
+            }
+
+            self.delete_oldest_deltas(max_deltas)
+        })
+    }
+
+    /// Drops the oldest deltas beyond `max_deltas`
+    pub fn trim_deltas(&self, max_deltas: usize) -> PersistenceResult<()> {
+        assert!(self.version == CURRENT_VERSION,
+                "schema version mismatch, schema_up() resolves this");
+
+        self.delete_oldest_deltas(max_deltas)
+    }
+
+    /// Drops all the deltas, e.g. once the changes to the zone are no longer known
+    pub fn clear_deltas(&self) -> PersistenceResult<()> {
+        self.trim_deltas(0)
+    }
+
+    /// The deltas kept for incremental zone transfers, oldest first
+    pub fn select_deltas(&self) -> PersistenceResult<Vec<ZoneDelta>> {
+        assert!(self.version == CURRENT_VERSION,
+                "schema version mismatch, schema_up() resolves this");
+
+        let mut stmt = try!(self.conn
+            .prepare("SELECT delta_id, record FROM deltas ORDER BY delta_id, _rowid_"));
+        let rows = try!(stmt.query_and_then(&[], |row| -> PersistenceResult<(i64, Record)> {
+            let delta_id: i64 = try!(row.get_checked(0));
+            let record_bytes: Vec<u8> = try!(row.get_checked(1));
+            let mut decoder = BinDecoder::new(&record_bytes);
+            Ok((delta_id, try!(Record::read(&mut decoder))))
+        }));
+
+        // the rows of each delta are consecutive
+        let mut deltas = Vec::new();
+        let mut records: Vec<Record> = Vec::new();
+        let mut current_id = None;
+        for row in rows {
+            let (delta_id, record) = try!(row);
+            if current_id != Some(delta_id) && !records.is_empty() {
+                deltas.push(try!(to_delta(mem::replace(&mut records, vec![]))));
+            }
+            current_id = Some(delta_id);
+            records.push(record);
+        }
+        if !records.is_empty() {
+            deltas.push(try!(to_delta(records)));
+        }
+
+        Ok(deltas)
+    }
+
+    fn delete_oldest_deltas(&self, max_deltas: usize) -> PersistenceResult<()> {
+        let max_deltas = max_deltas as i64;
+        try!(self.conn.execute("DELETE FROM deltas WHERE delta_id NOT IN (SELECT DISTINCT \
+                                delta_id FROM deltas ORDER BY delta_id DESC LIMIT $1)",
+                               &[&max_deltas]));
+        Ok(())
+    }
+
+    fn insert_snapshot_records(&self,
+                               soa_serial: u32,
+                               records: &[Record])
+                               -> PersistenceResult<()> {
+        try!(self.insert_record(soa_serial, Record::new().rr_type(RecordType::AXFR)));
         for record in records {
             try!(self.insert_record(soa_serial, record));
         }
@@ -106,6 +245,25 @@ impl Journal {
         Ok(())
     }
 
+    /// Runs the inserts in a transaction, which is rolled back if any of them fails
+    fn in_transaction<F>(&self, inserts: F) -> PersistenceResult<()>
+        where F: FnOnce() -> PersistenceResult<()>
+    {
+        try!(self.conn.execute_batch("BEGIN TRANSACTION"));
+        match inserts() {
+            Ok(()) => {
+                try!(self.conn.execute_batch("COMMIT"));
+                Ok(())
+            }
+            Err(err) => {
+                if let Err(rollback_err) = self.conn.execute_batch("ROLLBACK") {
+                    error!("could not roll back the journal: {}", rollback_err);
+                }
+                Err(err)
+            }
+        }
+    }
+
     /// Selects a record from the given row_id.
     ///
     /// This allows for the entire set of records to be iterated through, by starting at 0, and
@@ -207,6 +365,7 @@ impl Journal {
             match self.version + 1 {
                 0 => self.version = try!(self.init_up()),
                 1 => self.version = try!(self.records_up()),
+                2 => self.version = try!(self.deltas_up()),
                 _ => panic!("incorrect version somewhere"), // valid panic, non-recoverable state
             }
 
@@ -255,6 +414,50 @@ impl Journal {
 
         Ok(1)
     }
+
+    /// adds the deltas table, the changes of the updates for incremental zone transfers, the
+    ///  records of each delta in the order of `ZoneDelta::records`
+    fn deltas_up(&self) -> PersistenceResult<i64> {
+        try!(self.conn.execute("CREATE TABLE deltas (
+                                  \
+                                    delta_id       INTEGER NOT NULL,
+                                  \
+                                    record         BLOB NOT NULL
+                                \
+                                    )",
+                               &[]));
+
+        Ok(2)
+    }
+}
+
+/// The record in the wire format, as it is stored
+fn encode_record(record: &Record) -> PersistenceResult<Vec<u8>> {
+    let mut serial_record: Vec<u8> = Vec::with_capacity(512);
+    {
+        let mut encoder = BinEncoder::new(&mut serial_record);
+        try!(record.emit(&mut encoder));
+    }
+
+    Ok(serial_record)
+}
+
+/// The delta of the records, as `ZoneDelta::records` orders them, the SOAs delimiting the changes
+fn to_delta(mut records: Vec<Record>) -> PersistenceResult<ZoneDelta> {
+    let new_soa_index = records.iter()
+        .skip(1)
+        .position(|record| record.get_rr_type() == RecordType::SOA)
+        .map(|index| index + 1);
+    let new_soa_index = match new_soa_index {
+        Some(index) if records[0].get_rr_type() == RecordType::SOA => index,
+        _ => return Err(PersistenceErrorKind::RecoveryError("delta without SOA").into()),
+    };
+
+    let added = records.split_off(new_soa_index + 1);
+    let new_soa = records.pop().expect("new soa was found above");
+    let removed = records.split_off(1);
+    let old_soa = records.pop().expect("old soa was checked above");
+    Ok(ZoneDelta::new(old_soa, removed, new_soa, added))
 }
 
 pub struct JournalIter<'j> {
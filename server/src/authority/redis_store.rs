@@ -0,0 +1,279 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! A `KeyValueStore` on a Redis server, with the GET, SET, DEL, MULTI and EXEC commands of its
+//!  protocol, RESP
+
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::sync::Mutex;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender, SyncSender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use authority::KeyValueStore;
+
+/// The longest value read from the server, longer ones are an error
+const MAX_VALUE_LEN: usize = 16 * 1024 * 1024;
+
+/// The most replies in the reply to an EXEC
+const MAX_ARRAY_LEN: usize = 1024;
+
+/// Seconds for which commands fail straight away after the server failed, or did not reply in
+///  time, so that an unreachable server does not hold up every query
+const RETRY_INTERVAL_SECS: u64 = 5;
+
+/// A reply of the server, errors aside
+enum Reply {
+    Status,
+    Integer,
+    Bulk(Option<Vec<u8>>),
+    Array(Vec<Reply>),
+}
+
+/// Commands sent together, with the sender of their replies, to the thread of the connection
+struct Exchange {
+    commands: Vec<Vec<Vec<u8>>>,
+    replies: SyncSender<io::Result<Vec<Reply>>>,
+}
+
+/// A store of the records of `KeyValueAuthority` zones on a Redis server
+///
+/// The commands are sent on a single connection, by a thread of the store, which connects for
+///  the first command and again after any failure, e.g. when the server restarted; the thread
+///  answering the queries only waits for the reply, for at most the timeout. Once the server
+///  fails, or does not reply in time, the commands fail straight away for a few seconds, the
+///  values are then served from the cache of the zone.
+pub struct RedisStore {
+    addr: SocketAddr,
+    timeout: Duration,
+    exchanges: Mutex<Sender<Exchange>>,
+    /// when the commands are sent again, after a failure
+    retry_at: Mutex<Option<Instant>>,
+}
+
+impl RedisStore {
+    /// Creates a store on the server, which is connected to once it is first used
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - the address of the server, usually port 6379
+    /// * `timeout` - for the reply to each command, including the connection to the server
+    pub fn new(addr: SocketAddr, timeout: Duration) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || exchange_all(addr, timeout, receiver));
+
+        RedisStore {
+            addr: addr,
+            timeout: timeout,
+            exchanges: Mutex::new(sender),
+            retry_at: Mutex::new(None),
+        }
+    }
+
+    pub fn get_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Sends the command and returns the reply of the server
+    fn command(&self, args: &[&[u8]]) -> io::Result<Reply> {
+        let command = args.iter().map(|arg| arg.to_vec()).collect();
+        let mut replies = try!(self.exchange(vec![command]));
+        replies.pop().ok_or_else(|| unexpected_reply("no reply"))
+    }
+
+    /// Sends the commands together, and returns their replies, unless the server failed lately
+    fn exchange(&self, commands: Vec<Vec<Vec<u8>>>) -> io::Result<Vec<Reply>> {
+        let now = Instant::now();
+        {
+            let retry_at = self.retry_at.lock().unwrap(); // poison errors should panic
+            if retry_at.map_or(false, |retry_at| now < retry_at) {
+                return Err(io::Error::new(io::ErrorKind::NotConnected,
+                                          format!("redis: {} failed lately", self.addr)));
+            }
+        }
+
+        let (sender, receiver) = mpsc::sync_channel(1);
+        let exchange = Exchange {
+            commands: commands,
+            replies: sender,
+        };
+        // poison errors should panic
+        try!(self.exchanges.lock().unwrap().send(exchange).map_err(|_| {
+            io::Error::new(io::ErrorKind::BrokenPipe, "redis connection thread stopped")
+        }));
+
+        let result = match receiver.recv_timeout(self.timeout) {
+            Ok(result) => result,
+            Err(RecvTimeoutError::Timeout) => {
+                Err(io::Error::new(io::ErrorKind::TimedOut,
+                                   format!("redis: {} did not reply in time", self.addr)))
+            }
+            Err(RecvTimeoutError::Disconnected) => {
+                Err(io::Error::new(io::ErrorKind::BrokenPipe, "redis connection thread stopped"))
+            }
+        };
+
+        if result.is_err() {
+            let retry_at = now + Duration::from_secs(RETRY_INTERVAL_SECS);
+            *self.retry_at.lock().unwrap() = Some(retry_at); // poison errors should panic
+        }
+        result
+    }
+}
+
+impl KeyValueStore for RedisStore {
+    fn get(&self, key: &str) -> io::Result<Option<Vec<u8>>> {
+        match try!(self.command(&[b"GET", key.as_bytes()])) {
+            Reply::Bulk(value) => Ok(value),
+            _ => Err(unexpected_reply("GET")),
+        }
+    }
+
+    fn set(&self, key: &str, value: &[u8]) -> io::Result<()> {
+        match try!(self.command(&[b"SET", key.as_bytes(), value])) {
+            Reply::Status => Ok(()),
+            _ => Err(unexpected_reply("SET")),
+        }
+    }
+
+    fn delete(&self, key: &str) -> io::Result<()> {
+        match try!(self.command(&[b"DEL", key.as_bytes()])) {
+            Reply::Integer => Ok(()),
+            _ => Err(unexpected_reply("DEL")),
+        }
+    }
+
+    /// Sets and deletes the values in a transaction, MULTI and EXEC, all of them or none
+    fn write(&self, changes: &[(&str, Option<&[u8]>)]) -> io::Result<()> {
+        let mut commands: Vec<Vec<Vec<u8>>> = vec![vec![b"MULTI".to_vec()]];
+        for &(key, value) in changes {
+            commands.push(match value {
+                Some(value) => vec![b"SET".to_vec(), key.as_bytes().to_vec(), value.to_vec()],
+                None => vec![b"DEL".to_vec(), key.as_bytes().to_vec()],
+            });
+        }
+        commands.push(vec![b"EXEC".to_vec()]);
+
+        match try!(self.exchange(commands)).pop() {
+            Some(Reply::Array(ref replies)) if replies.len() == changes.len() => Ok(()),
+            _ => Err(unexpected_reply("EXEC")),
+        }
+    }
+}
+
+/// Sends the commands of the exchanges on the connection to the server, until the store is dropped
+fn exchange_all(addr: SocketAddr, timeout: Duration, exchanges: Receiver<Exchange>) {
+    let mut connection: Option<BufReader<TcpStream>> = None;
+    for exchange in exchanges.iter() {
+        if connection.is_none() {
+            debug!("connecting to redis: {}", addr);
+            match connect(addr, timeout) {
+                Ok(stream) => connection = Some(BufReader::new(stream)),
+                Err(e) => {
+                    // the store may have given up waiting for the reply
+                    let _ = exchange.replies.send(Err(e));
+                    continue;
+                }
+            }
+        }
+
+        let result = match connection.as_mut() {
+            Some(reader) => exchange_commands(reader, &exchange.commands),
+            None => unreachable!("connected above"),
+        };
+
+        // after a failure the state of the connection is unknown, the next command reconnects
+        if result.is_err() {
+            connection = None;
+        }
+        let _ = exchange.replies.send(result);
+    }
+}
+
+fn connect(addr: SocketAddr, timeout: Duration) -> io::Result<TcpStream> {
+    let stream = try!(TcpStream::connect(addr));
+    try!(stream.set_read_timeout(Some(timeout)));
+    try!(stream.set_write_timeout(Some(timeout)));
+    try!(stream.set_nodelay(true));
+    Ok(stream)
+}
+
+/// Writes the commands, each as an array of bulk strings, and reads their replies
+fn exchange_commands(reader: &mut BufReader<TcpStream>,
+                     commands: &[Vec<Vec<u8>>])
+                     -> io::Result<Vec<Reply>> {
+    let mut buffer: Vec<u8> = Vec::new();
+    for args in commands {
+        buffer.extend_from_slice(format!("*{}\r\n", args.len()).as_bytes());
+        for arg in args {
+            buffer.extend_from_slice(format!("${}\r\n", arg.len()).as_bytes());
+            buffer.extend_from_slice(arg);
+            buffer.extend_from_slice(b"\r\n");
+        }
+    }
+
+    try!(reader.get_mut().write_all(&buffer));
+    commands.iter().map(|_| read_reply(reader)).collect()
+}
+
+fn read_reply<R: BufRead>(reader: &mut R) -> io::Result<Reply> {
+    let mut line = String::new();
+    if try!(reader.read_line(&mut line)) == 0 {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "redis closed the connection"));
+    }
+    let line = line.trim_right_matches("\r\n");
+    if line.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "empty reply from redis"));
+    }
+
+    let mut chars = line.chars();
+    let kind = chars.next();
+    let rest = chars.as_str();
+    match kind {
+        Some('+') => Ok(Reply::Status),
+        Some('-') => Err(io::Error::new(io::ErrorKind::Other, format!("redis error: {}", rest))),
+        Some(':') => {
+            try!(rest.parse::<i64>().map_err(|_| unexpected_reply(line)));
+            Ok(Reply::Integer)
+        }
+        Some('$') => {
+            let len = try!(rest.parse::<i64>().map_err(|_| unexpected_reply(line)));
+            if len < 0 {
+                return Ok(Reply::Bulk(None));
+            }
+            if len as u64 > MAX_VALUE_LEN as u64 {
+                return Err(unexpected_reply(line));
+            }
+
+            // the value is followed by CRLF
+            let mut value = vec![0; len as usize + 2];
+            try!(reader.read_exact(&mut value));
+            value.truncate(len as usize);
+            Ok(Reply::Bulk(Some(value)))
+        }
+        Some('*') => {
+            let len = try!(rest.parse::<i64>().map_err(|_| unexpected_reply(line)));
+            // a null array, e.g. an aborted transaction
+            if len < 0 || len as u64 > MAX_ARRAY_LEN as u64 {
+                return Err(unexpected_reply(line));
+            }
+
+            let mut replies = Vec::with_capacity(len as usize);
+            for _ in 0..len {
+                replies.push(try!(read_reply(reader)));
+            }
+            Ok(Reply::Array(replies))
+        }
+        _ => Err(unexpected_reply(line)),
+    }
+}
+
+fn unexpected_reply(reply: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData,
+                   format!("unexpected reply from redis: {}", reply))
+}
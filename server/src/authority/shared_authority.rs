@@ -0,0 +1,94 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex, MutexGuard, RwLock};
+
+use authority::Authority;
+
+/// The authority of a zone, read through immutable snapshots of it and written copy on write
+///
+/// A read takes the lock only to clone the `Arc` of the current version, lookups and transfers
+///  are then answered from that version, without any lock, however long they take. A write
+///  builds the next version on a copy of the current one, without holding the lock, e.g. while
+///  the zone is signed, and only takes it to replace the current version once done; reads
+///  holding the prior version are unaffected. Writes are made one at a time.
+pub struct SharedAuthority {
+    current: RwLock<Arc<Authority>>,
+    /// held for the whole of a write, so that one does not undo another
+    writer: Mutex<()>,
+}
+
+impl SharedAuthority {
+    /// Shares the authority, as its first version
+    pub fn new(authority: Authority) -> Self {
+        SharedAuthority {
+            current: RwLock::new(Arc::new(authority)),
+            writer: Mutex::new(()),
+        }
+    }
+
+    /// Returns the current version of the authority, which a later write does not change
+    pub fn read(&self) -> Arc<Authority> {
+        self.current.read().unwrap().clone() // poison errors should panic
+    }
+
+    /// Returns the authority to change, the changes are seen by reads once the guard is dropped
+    ///
+    /// The zone is copied on the first change through the guard, a write which only looks at the
+    ///  authority leaves the current version as it is.
+    pub fn write(&self) -> AuthorityWriteGuard {
+        let writer = self.writer.lock().unwrap(); // poison errors should panic
+        AuthorityWriteGuard {
+            current: &self.current,
+            base: self.read(),
+            next: None,
+            _writer: writer,
+        }
+    }
+}
+
+/// The authority being written, see `SharedAuthority::write`
+pub struct AuthorityWriteGuard<'a> {
+    current: &'a RwLock<Arc<Authority>>,
+    /// the version the write started from
+    base: Arc<Authority>,
+    /// the next version, copied from the base on the first change
+    next: Option<Authority>,
+    _writer: MutexGuard<'a, ()>,
+}
+
+impl<'a> Deref for AuthorityWriteGuard<'a> {
+    type Target = Authority;
+
+    fn deref(&self) -> &Authority {
+        match self.next {
+            Some(ref next) => next,
+            None => &self.base,
+        }
+    }
+}
+
+impl<'a> DerefMut for AuthorityWriteGuard<'a> {
+    /// Copies the current version first, no lock is held while the copy is changed
+    fn deref_mut(&mut self) -> &mut Authority {
+        if self.next.is_none() {
+            self.next = Some((*self.base).clone());
+        }
+
+        self.next.as_mut().expect("copied above")
+    }
+}
+
+impl<'a> Drop for AuthorityWriteGuard<'a> {
+    /// Replaces the current version with the changed copy, if any
+    fn drop(&mut self) {
+        if let Some(next) = self.next.take() {
+            *self.current.write().unwrap() = Arc::new(next); // poison errors should panic
+        }
+    }
+}
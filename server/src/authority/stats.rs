@@ -0,0 +1,217 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Runtime statistics for the zones in a `Catalog`, see `Catalog::stats()`
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, TimeZone, UTC};
+
+use trust_dns::cache::{DnsCacheStats, LatencyHistogram};
+use trust_dns::rr::{Name, RecordType};
+
+use authority::{Authority, ForwardAuthority, ShadowStats, ZoneType};
+
+/// Counters kept by the `Catalog` for each zone as requests are handled
+#[derive(Clone, Debug)]
+pub struct ZoneCounters {
+    queries: HashMap<RecordType, u64>,
+    nx_domain: u64,
+    updates: u64,
+    transfers: u64,
+    expired: bool,
+    expirations: u64,
+    /// seconds since the epoch, see `Clock`
+    loaded: u32,
+}
+
+impl ZoneCounters {
+    /// New counters of a zone loaded at `now`, in seconds since the epoch
+    pub fn new(now: u32) -> Self {
+        ZoneCounters {
+            queries: HashMap::new(),
+            nx_domain: 0,
+            updates: 0,
+            transfers: 0,
+            expired: false,
+            expirations: 0,
+            loaded: now,
+        }
+    }
+
+    pub fn query(&mut self, query_type: RecordType) {
+        *self.queries.entry(query_type).or_insert(0) += 1;
+
+        match query_type {
+            RecordType::AXFR | RecordType::IXFR => self.transfers += 1,
+            _ => (),
+        }
+    }
+
+    pub fn nx_domain(&mut self) {
+        self.nx_domain += 1;
+    }
+
+    pub fn update(&mut self) {
+        self.updates += 1;
+    }
+
+    /// Records whether the zone is expired, returning true if it was not before, or the reverse
+    pub fn expired(&mut self, expired: bool) -> bool {
+        if expired == self.expired {
+            return false;
+        }
+
+        self.expired = expired;
+        if expired {
+            self.expirations += 1;
+        }
+        true
+    }
+
+    /// The zone was (re)loaded, the counters are kept
+    pub fn loaded(&mut self, now: u32) {
+        self.loaded = now;
+    }
+}
+
+/// A snapshot of the statistics for a single zone
+#[derive(Clone, Debug)]
+pub struct ZoneStats {
+    origin: Name,
+    zone_type: ZoneType,
+    counters: ZoneCounters,
+    records: usize,
+    signed: bool,
+    expired: bool,
+}
+
+impl ZoneStats {
+    pub fn new(authority: &Authority, counters: ZoneCounters) -> Self {
+        ZoneStats {
+            origin: authority.get_origin().clone(),
+            zone_type: authority.get_zone_type(),
+            counters: counters,
+            records: authority.get_records().values().map(|rrset| rrset.iter().len()).sum(),
+            signed: authority.is_signed(),
+            expired: authority.is_expired(),
+        }
+    }
+
+    /// The zone these are for
+    pub fn get_origin(&self) -> &Name {
+        &self.origin
+    }
+
+    pub fn get_zone_type(&self) -> ZoneType {
+        self.zone_type
+    }
+
+    /// Number of queries for each record type
+    pub fn get_queries(&self) -> &HashMap<RecordType, u64> {
+        &self.counters.queries
+    }
+
+    /// Number of queries of all types
+    pub fn get_query_count(&self) -> u64 {
+        self.counters.queries.values().sum()
+    }
+
+    /// Number of queries answered with NXDomain
+    pub fn get_nx_domain_count(&self) -> u64 {
+        self.counters.nx_domain
+    }
+
+    /// Number of updates applied to the zone
+    pub fn get_update_count(&self) -> u64 {
+        self.counters.updates
+    }
+
+    /// Number of AXFR and IXFR requests
+    pub fn get_transfer_count(&self) -> u64 {
+        self.counters.transfers
+    }
+
+    /// Number of times the zone was found to have expired, see `Authority::is_expired`
+    pub fn get_expiration_count(&self) -> u64 {
+        self.counters.expirations
+    }
+
+    /// When the zone was last loaded into the `Catalog`
+    pub fn get_last_loaded(&self) -> DateTime<UTC> {
+        UTC.timestamp(self.counters.loaded as i64, 0)
+    }
+
+    /// Number of records in the zone, not counting RRSIGs
+    pub fn get_record_count(&self) -> usize {
+        self.records
+    }
+
+    /// True if the zone is signed with at least one key
+    pub fn is_signed(&self) -> bool {
+        self.signed
+    }
+
+    /// True if this is a Slave zone which expired, it is answered with SERVFAIL
+    pub fn is_expired(&self) -> bool {
+        self.expired
+    }
+}
+
+/// A snapshot of the statistics for a forwarded zone, see `Catalog::forward_stats()`
+#[derive(Clone, Debug)]
+pub struct ForwardStats {
+    origin: Name,
+    cache: Option<(DnsCacheStats, usize)>,
+    hit_latency: LatencyHistogram,
+    upstream_latency: LatencyHistogram,
+    shadow: Option<ShadowStats>,
+}
+
+impl ForwardStats {
+    pub fn new(forwarder: &ForwardAuthority) -> Self {
+        ForwardStats {
+            origin: forwarder.get_origin().clone(),
+            cache: forwarder.get_cache_stats(),
+            hit_latency: forwarder.get_hit_latency(),
+            upstream_latency: forwarder.get_upstream_latency(),
+            shadow: forwarder.get_shadow_stats(),
+        }
+    }
+
+    /// The zone these are for
+    pub fn get_origin(&self) -> &Name {
+        &self.origin
+    }
+
+    /// The hits, misses, expired entries and evictions of the cache, overall and by record type,
+    ///  None if the zone does not cache
+    pub fn get_cache_stats(&self) -> Option<&DnsCacheStats> {
+        self.cache.as_ref().map(|&(ref stats, _)| stats)
+    }
+
+    /// Number of entries in the cache, including expired ones not yet evicted
+    pub fn get_cache_len(&self) -> usize {
+        self.cache.as_ref().map_or(0, |&(_, len)| len)
+    }
+
+    /// Latencies of the answers from the cache
+    pub fn get_hit_latency(&self) -> &LatencyHistogram {
+        &self.hit_latency
+    }
+
+    /// Latencies of the responses of the upstreams, for the queries not answered from the cache
+    pub fn get_upstream_latency(&self) -> &LatencyHistogram {
+        &self.upstream_latency
+    }
+
+    /// The comparisons of the responses of the upstreams with those of the shadow, None if the
+    ///  zone has no shadow
+    pub fn get_shadow_stats(&self) -> Option<&ShadowStats> {
+        self.shadow.as_ref()
+    }
+}
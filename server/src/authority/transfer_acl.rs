@@ -0,0 +1,140 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Access control for zone transfers, restricting which clients may AXFR a zone.
+
+use std::cmp;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use trust_dns::error::*;
+use trust_dns::rr::Name;
+
+/// A client which may transfer the zone
+///
+/// The string form is an address, optionally with a prefix length, or the name of a key with
+///  which the request is signed, a KEY of the zone with SIG(0), or a shared key with TSIG:
+///
+/// ```text
+/// 192.0.2.1
+/// 2001:db8::/32
+/// key <signer>
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub enum TransferRule {
+    /// clients with addresses in the network, the address and the length of its prefix in bits
+    Network(IpAddr, u8),
+    /// requests verified with the key of the signer, SIG(0), or the TSIG key of the name
+    Key(Name),
+}
+
+impl TransferRule {
+    /// returns true if the client, or the key the request is verified with, is permitted
+    pub fn matches(&self, src: IpAddr, signer: Option<&Name>) -> bool {
+        match *self {
+            TransferRule::Network(network, prefix_len) => in_network(src, network, prefix_len),
+            TransferRule::Key(ref key) => signer == Some(key),
+        }
+    }
+}
+
+/// true if the first `prefix_len` bits of the addresses are the same
+fn in_network(address: IpAddr, network: IpAddr, prefix_len: u8) -> bool {
+    let (address, network) = match (address, network) {
+        (IpAddr::V4(address), IpAddr::V4(network)) => {
+            (address.octets().to_vec(), network.octets().to_vec())
+        }
+        (IpAddr::V6(address), IpAddr::V6(network)) => {
+            (address.octets().to_vec(), network.octets().to_vec())
+        }
+        _ => return false,
+    };
+
+    address.iter().zip(network.iter()).enumerate().all(|(index, (address, network))| {
+        let bits = cmp::min((prefix_len as usize).saturating_sub(index * 8), 8);
+        let mask = if bits == 0 { 0 } else { 0xFFu8 << (8 - bits) };
+        address & mask == network & mask
+    })
+}
+
+impl FromStr for TransferRule {
+    type Err = ParseError;
+
+    fn from_str(rule: &str) -> ParseResult<Self> {
+        let mut tokens = rule.split_whitespace();
+
+        let rule = match (tokens.next(), tokens.next()) {
+            (Some("key"), Some(signer)) => {
+                TransferRule::Key(try!(Name::parse(signer, Some(&Name::root()))))
+            }
+            (Some("key"), None) => {
+                return Err(ParseErrorKind::MissingToken("signer".to_string()).into())
+            }
+            (Some(network), None) => {
+                let (address, prefix_len) = match network.find('/') {
+                    Some(slash) => (&network[..slash], Some(try!(network[slash + 1..].parse()))),
+                    None => (network, None),
+                };
+                let address = try!(IpAddr::from_str(address));
+                let max_prefix_len = if let IpAddr::V4(_) = address { 32 } else { 128 };
+
+                match prefix_len {
+                    Some(prefix_len) if prefix_len > max_prefix_len => {
+                        return Err(ParseErrorKind::Msg(format!("invalid prefix length: {}",
+                                                               network))
+                            .into())
+                    }
+                    Some(prefix_len) => TransferRule::Network(address, prefix_len),
+                    None => TransferRule::Network(address, max_prefix_len),
+                }
+            }
+            (Some(other), Some(_)) => {
+                return Err(ParseErrorKind::Msg(format!("expected an address or key: {}", other))
+                    .into())
+            }
+            (None, _) => {
+                return Err(ParseErrorKind::MissingToken("address or key".to_string()).into())
+            }
+        };
+
+        if let Some(other) = tokens.next() {
+            return Err(ParseErrorKind::Msg(format!("unexpected: {}", other)).into());
+        }
+
+        Ok(rule)
+    }
+}
+
+/// The clients which may transfer a zone, any rule matching the client permits the transfer
+///
+/// If no rule matches, the transfer is refused.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TransferAcl {
+    rules: Vec<TransferRule>,
+}
+
+impl TransferAcl {
+    /// Creates a new access control list from the rules
+    pub fn new(rules: Vec<TransferRule>) -> Self {
+        TransferAcl { rules: rules }
+    }
+
+    /// the rules, a client matching any of them is permitted
+    pub fn get_rules(&self) -> &[TransferRule] {
+        &self.rules
+    }
+
+    /// Returns true if the client may transfer the zone
+    ///
+    /// # Arguments
+    ///
+    /// * `src` - the address of the client
+    /// * `signer` - the name of the key which verified the request, if it was signed
+    pub fn is_permitted(&self, src: IpAddr, signer: Option<&Name>) -> bool {
+        self.rules.iter().any(|rule| rule.matches(src, signer))
+    }
+}
@@ -0,0 +1,202 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! BIND style update-policy rules, restricting which names and types a key may update.
+
+use std::str::FromStr;
+
+use trust_dns::error::*;
+use trust_dns::rr::{Name, Record, RecordType};
+
+/// Which record names a rule applies to
+#[derive(Clone, Debug, PartialEq)]
+pub enum NameMatch {
+    /// only the exact name
+    Name(Name),
+    /// the name and all names below it
+    Subdomain(Name),
+    /// the name must be the same as the signer of the update
+    SelfName,
+    /// any name in the zone
+    ZoneSub,
+}
+
+/// A single grant or deny rule.
+///
+/// The string form mirrors BIND's update-policy statement:
+///
+/// ```text
+/// (grant|deny) <signer|*> name <name> [<type> ...]
+/// (grant|deny) <signer|*> subdomain <name> [<type> ...]
+/// (grant|deny) <signer|*> self [<type> ...]
+/// (grant|deny) <signer|*> zonesub [<type> ...]
+/// ```
+///
+/// A signer of `*` matches any key which is valid for updates to the zone, an empty type list
+///  matches all types.
+#[derive(Clone, Debug, PartialEq)]
+pub struct UpdateRule {
+    grant: bool,
+    signer: Option<Name>,
+    name_match: NameMatch,
+    types: Vec<RecordType>,
+}
+
+impl UpdateRule {
+    /// Creates a new rule
+    ///
+    /// # Arguments
+    ///
+    /// * `grant` - true if a matching update is permitted, false if it is denied
+    /// * `signer` - the name of the key this applies to, None for any key
+    /// * `name_match` - the names in the zone this applies to
+    /// * `types` - the record types this applies to, empty for all types
+    pub fn new(grant: bool,
+               signer: Option<Name>,
+               name_match: NameMatch,
+               types: Vec<RecordType>)
+               -> Self {
+        UpdateRule {
+            grant: grant,
+            signer: signer,
+            name_match: name_match,
+            types: types,
+        }
+    }
+
+    /// true if this rule grants the update, false if it denies it
+    pub fn is_grant(&self) -> bool {
+        self.grant
+    }
+
+    /// returns true if this rule applies to the record being updated by the signer
+    pub fn matches(&self, signer: &Name, origin: &Name, record: &Record) -> bool {
+        if let Some(ref rule_signer) = self.signer {
+            if rule_signer != signer {
+                return false;
+            }
+        }
+
+        let name = record.get_name();
+        let name_matches = match self.name_match {
+            NameMatch::Name(ref rule_name) => rule_name == name,
+            NameMatch::Subdomain(ref rule_name) => rule_name.zone_of(name),
+            NameMatch::SelfName => signer == name,
+            NameMatch::ZoneSub => origin.zone_of(name),
+        };
+
+        if !name_matches {
+            return false;
+        }
+
+        // an ANY type is a delete of all rrsets at the name, only rules without types cover that
+        match record.get_rr_type() {
+            _ if self.types.is_empty() => true,
+            RecordType::ANY => false,
+            rr_type => self.types.contains(&rr_type),
+        }
+    }
+}
+
+impl FromStr for UpdateRule {
+    type Err = ParseError;
+
+    fn from_str(rule: &str) -> ParseResult<Self> {
+        let mut tokens = rule.split_whitespace();
+
+        let grant = match tokens.next() {
+            Some("grant") => true,
+            Some("deny") => false,
+            Some(other) => {
+                return Err(ParseErrorKind::Msg(format!("expected grant or deny: {}", other))
+                    .into())
+            }
+            None => return Err(ParseErrorKind::MissingToken("grant or deny".to_string()).into()),
+        };
+
+        let signer = match tokens.next() {
+            Some("*") => None,
+            Some(signer) => Some(try!(Name::parse(signer, Some(&Name::root())))),
+            None => return Err(ParseErrorKind::MissingToken("signer".to_string()).into()),
+        };
+
+        let name_match = match tokens.next() {
+            Some(rule_type @ "name") |
+            Some(rule_type @ "subdomain") => {
+                let name = match tokens.next() {
+                    Some(name) => try!(Name::parse(name, Some(&Name::root()))),
+                    None => return Err(ParseErrorKind::MissingToken("name".to_string()).into()),
+                };
+
+                if rule_type == "name" {
+                    NameMatch::Name(name)
+                } else {
+                    NameMatch::Subdomain(name)
+                }
+            }
+            Some("self") => NameMatch::SelfName,
+            Some("zonesub") => NameMatch::ZoneSub,
+            Some(other) => {
+                return Err(ParseErrorKind::Msg(format!("unknown update-policy rule type: {}",
+                                                       other))
+                    .into())
+            }
+            None => return Err(ParseErrorKind::MissingToken("rule type".to_string()).into()),
+        };
+
+        let mut types = Vec::new();
+        for rr_type in tokens {
+            types.push(try!(RecordType::from_str(rr_type)));
+        }
+
+        Ok(UpdateRule::new(grant, signer, name_match, types))
+    }
+}
+
+/// An ordered list of rules, the first rule matching a record decides if it may be updated.
+///
+/// If no rule matches, the update is denied.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct UpdatePolicy {
+    rules: Vec<UpdateRule>,
+}
+
+impl UpdatePolicy {
+    /// Creates a new policy from the ordered rules
+    pub fn new(rules: Vec<UpdateRule>) -> Self {
+        UpdatePolicy { rules: rules }
+    }
+
+    /// the rules in evaluation order
+    pub fn get_rules(&self) -> &[UpdateRule] {
+        &self.rules
+    }
+
+    /// Returns true if every record in the update may be changed by the signer
+    ///
+    /// # Arguments
+    ///
+    /// * `signer` - the name of the key which verified the update
+    /// * `origin` - the origin of the zone being updated
+    /// * `records` - the update section of the update message
+    pub fn is_permitted(&self, signer: &Name, origin: &Name, records: &[Record]) -> bool {
+        records.iter().all(|record| {
+            let permitted = self.rules
+                .iter()
+                .find(|rule| rule.matches(signer, origin, record))
+                .map_or(false, |rule| rule.is_grant());
+
+            if !permitted {
+                warn!("update of {} {:?} not permitted for: {}",
+                      record.get_name(),
+                      record.get_rr_type(),
+                      signer);
+            }
+            permitted
+        })
+    }
+}
@@ -0,0 +1,124 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! The zone files which zones are loaded, and reloaded, from
+
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use trust_dns::error::ParseResult;
+use trust_dns::rr::{Name, RecordMetaTable, RecordSet, RrKey};
+use trust_dns::serialize::txt::{Lexer, Parser};
+
+use config::TemplateVariables;
+
+/// The zone file of an `Authority`, to reload the zone when it changes, see `Catalog::reload`
+#[derive(Clone, Debug)]
+pub struct ZoneFile {
+    path: PathBuf,
+    include_dir: PathBuf,
+    variables: TemplateVariables,
+    /// when the file was modified, as it was last read
+    modified: Option<SystemTime>,
+    /// the files of its `$INCLUDE`s, and when they were modified, as they were last read
+    included: Vec<(PathBuf, Option<SystemTime>)>,
+}
+
+impl ZoneFile {
+    /// Creates a zone file, which is read with `load`
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - the path of the file
+    /// * `include_dir` - the directory the files of `$INCLUDE` are relative to
+    /// * `variables` - the values of the `${name}` template variables of the file
+    pub fn new(path: PathBuf, include_dir: PathBuf, variables: TemplateVariables) -> Self {
+        ZoneFile {
+            path: path,
+            include_dir: include_dir,
+            variables: variables,
+            modified: None,
+            included: Vec::new(),
+        }
+    }
+
+    pub fn get_path(&self) -> &Path {
+        &self.path
+    }
+
+    /// When the file was modified, as it was last read, None before it is read
+    pub fn get_modified(&self) -> Option<SystemTime> {
+        self.modified
+    }
+
+    /// Returns the files of the `$INCLUDE`s of the file, as it was last read
+    pub fn get_included(&self) -> Vec<&Path> {
+        self.included.iter().map(|&(ref path, _)| path.as_path()).collect()
+    }
+
+    /// Returns true if the file, or one of its `$INCLUDE`s, was modified since it was last read,
+    ///  or was removed
+    pub fn is_modified(&self) -> bool {
+        modified_time(&self.path) != self.modified ||
+        self.included.iter().any(|&(ref path, modified)| modified_time(path) != modified)
+    }
+
+    /// Reads the records of the zone from the file, with their comments and tags, all the errors
+    ///  of the file are reported
+    ///
+    /// The modification time is that of the file before it is read, so that a change while it is
+    ///  read is seen by the next `is_modified()`, and noted even if the file has errors, which
+    ///  are then only reported again once it is changed. Those of the `$INCLUDE`s read before
+    ///  are taken before the read too, those of newly included files once they are read.
+    ///
+    /// # Arguments
+    ///
+    /// * `origin` - the name of the zone, the origin of the relative names in the file, if none
+    ///              it is that of `$ORIGIN`
+    pub fn load(&mut self,
+                origin: Option<Name>)
+                -> ParseResult<(Name, BTreeMap<RrKey, RecordSet>, RecordMetaTable)> {
+        self.modified = modified_time(&self.path);
+        let included: BTreeMap<PathBuf, Option<SystemTime>> = self.included
+            .iter()
+            .map(|&(ref path, _)| (path.clone(), modified_time(path)))
+            .collect();
+
+        let mut file = try!(File::open(&self.path));
+        let mut buf = String::new();
+
+        // TODO, this should really use something to read line by line or some other method to
+        //  keep the usage down. and be a custom lexer...
+        try!(file.read_to_string(&mut buf));
+        // the zone file is a template, e.g. of the A records of the host
+        let buf = self.variables.substitute_defined(&buf);
+        let lexer = Lexer::new(&buf);
+        // $INCLUDE files are relative to the zone directory
+        let mut parser = Parser::new();
+        let result = parser.include_dir(&self.include_dir)
+            .file_name(&self.path)
+            .collect_errors(true)
+            .parse_with_meta(lexer, origin);
+
+        self.included = parser.get_included()
+            .iter()
+            .map(|path| {
+                let modified = included.get(path).cloned().unwrap_or_else(|| modified_time(path));
+                (path.clone(), modified)
+            })
+            .collect();
+        result
+    }
+}
+
+/// When the file was last modified, None if it cannot be read
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
@@ -16,28 +16,38 @@
 
 //! Configuration module for the server binary, `named`.
 
+use std::collections::BTreeMap;
+use std::env;
 use std::fs::File;
 use std::io::Read;
-use std::net::{Ipv4Addr, Ipv6Addr};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::time::Duration;
 
 use log::LogLevel;
 use rustc_serialize::Decodable;
+use rustc_serialize::base64::FromBase64;
+use rustc_serialize::hex::FromHex;
 use toml::{Decoder, Value};
 
 use trust_dns::error::*;
-use trust_dns::rr::Name;
-use trust_dns::rr::dnssec::{Algorithm, KeyFormat};
+use trust_dns::rr::{Name, RecordSetLimits};
+use trust_dns::rr::dnssec::{Algorithm, KeyFormat, Nsec3HashAlgorithm, TsigAlgorithm};
+use trust_dns::rr::rdata::NSEC3PARAM;
 
-use authority::ZoneType;
+use authority::{ForwardUpstream, TransferAcl, TransferRule, UpdatePolicy, UpdateRule, ZoneType,
+                DEFAULT_CACHE_TTL};
+use server::{ListenerPolicy, MdnsConfig, MdnsService, TcpLimits};
 use error::{ConfigErrorKind, ConfigResult, ConfigError};
 
 static DEFAULT_PATH: &'static str = "/var/named"; // TODO what about windows (do I care? ;)
 static DEFAULT_PORT: u16 = 53;
 static DEFAULT_TLS_PORT: u16 = 853;
 static DEFAULT_TCP_REQUEST_TIMEOUT: u64 = 5;
+static DEFAULT_TCP_READ_TIMEOUT: u64 = 2;
+static DEFAULT_TCP_WRITE_TIMEOUT: u64 = 2;
+static DEFAULT_TCP_MAX_UNANSWERED: usize = 16;
 
 #[derive(RustcDecodable, Debug)]
 pub struct Config {
@@ -46,10 +56,21 @@ pub struct Config {
     listen_port: Option<u16>,
     tls_listen_port: Option<u16>,
     tcp_request_timeout: Option<u64>,
+    tcp_read_timeout: Option<u64>,
+    tcp_write_timeout: Option<u64>,
+    tcp_max_message_len: Option<u16>,
+    tcp_max_unanswered: Option<usize>,
+    udp_policy: Option<ListenerPolicyConfig>,
+    tcp_policy: Option<ListenerPolicyConfig>,
+    tls_policy: Option<ListenerPolicyConfig>,
     log_level: Option<String>,
     directory: Option<String>,
+    zone_watch_secs: Option<u64>,
     zones: Vec<ZoneConfig>,
     tls_cert: Option<TlsCertConfig>,
+    mdns: Option<MdnsResponderConfig>,
+    variables: Option<BTreeMap<String, String>>,
+    tsig_keys: Option<Vec<TsigKeyConfig>>,
 }
 
 impl Config {
@@ -81,6 +102,44 @@ impl Config {
     pub fn get_tcp_request_timeout(&self) -> Duration {
         Duration::from_secs(self.tcp_request_timeout.unwrap_or(DEFAULT_TCP_REQUEST_TIMEOUT))
     }
+    /// time allowed to receive an entire request once its first byte has arrived
+    pub fn get_tcp_read_timeout(&self) -> Duration {
+        Duration::from_secs(self.tcp_read_timeout.unwrap_or(DEFAULT_TCP_READ_TIMEOUT))
+    }
+    /// time allowed to write an entire response
+    pub fn get_tcp_write_timeout(&self) -> Duration {
+        Duration::from_secs(self.tcp_write_timeout.unwrap_or(DEFAULT_TCP_WRITE_TIMEOUT))
+    }
+    /// largest request length prefix which will be accepted on TCP connections
+    pub fn get_tcp_max_message_len(&self) -> u16 {
+        self.tcp_max_message_len.unwrap_or(u16::max_value())
+    }
+    /// number of requests a TCP connection may have outstanding before it is closed
+    pub fn get_tcp_max_unanswered(&self) -> usize {
+        self.tcp_max_unanswered.unwrap_or(DEFAULT_TCP_MAX_UNANSWERED)
+    }
+    /// all the limits for TCP and TLS connections
+    pub fn get_tcp_limits(&self) -> TcpLimits {
+        TcpLimits {
+            idle_timeout: self.get_tcp_request_timeout(),
+            read_timeout: self.get_tcp_read_timeout(),
+            write_timeout: self.get_tcp_write_timeout(),
+            max_message_len: self.get_tcp_max_message_len(),
+            max_unanswered: self.get_tcp_max_unanswered(),
+        }
+    }
+    /// requests accepted on the UDP sockets, by default all
+    pub fn get_udp_policy(&self) -> ListenerPolicy {
+        self.udp_policy.as_ref().map_or(ListenerPolicy::default(), |p| p.get_policy())
+    }
+    /// requests accepted on the TCP listeners, by default all
+    pub fn get_tcp_policy(&self) -> ListenerPolicy {
+        self.tcp_policy.as_ref().map_or(ListenerPolicy::default(), |p| p.get_policy())
+    }
+    /// requests accepted on the TLS listeners, by default all
+    pub fn get_tls_policy(&self) -> ListenerPolicy {
+        self.tls_policy.as_ref().map_or(ListenerPolicy::default(), |p| p.get_policy())
+    }
 
     // TODO: also support env_logger
     /// specify the log level which should be used, ["Trace", "Debug", "Info", "Warn", "Error"]
@@ -102,6 +161,11 @@ impl Config {
     pub fn get_directory(&self) -> &Path {
         self.directory.as_ref().map_or(Path::new(DEFAULT_PATH), |s| Path::new(s))
     }
+    /// the interval at which the zone files are checked for changes and reloaded, none if they
+    ///  are not watched, see `ServerFuture::watch_zone_files`
+    pub fn get_zone_watch_interval(&self) -> Option<Duration> {
+        self.zone_watch_secs.map(Duration::from_secs)
+    }
     /// the set of zones which should be loaded
     pub fn get_zones(&self) -> &[ZoneConfig] {
         &self.zones
@@ -110,25 +174,262 @@ impl Config {
     pub fn get_tls_cert(&self) -> Option<&TlsCertConfig> {
         self.tls_cert.as_ref()
     }
+    /// the host name and services announced by mDNS, none if the responder is disabled
+    pub fn get_mdns(&self) -> Option<&MdnsResponderConfig> {
+        self.mdns.as_ref()
+    }
+    /// the keys shared with clients and peers, TSIG, for transfers and NOTIFYs
+    pub fn get_tsig_keys(&self) -> &[TsigKeyConfig] {
+        self.tsig_keys.as_ref().map_or(&[][..], |keys| &keys[..])
+    }
+    /// the template variables of the zone files, the `variables` of the config and the facts of
+    ///  the host, see `TemplateVariables`
+    pub fn get_variables(&self) -> TemplateVariables {
+        let mut variables = TemplateVariables::from_host();
+        if let Some(ref config_variables) = self.variables {
+            for (name, value) in config_variables {
+                variables.insert(name.clone(), value.clone());
+            }
+        }
+        variables
+    }
 }
 
 impl FromStr for Config {
     type Err = ConfigError;
 
     fn from_str(toml: &str) -> ConfigResult<Config> {
-        let value: Value = try!(toml.parse().map_err(|vec| ConfigErrorKind::VecParserError(vec)));
+        let mut value: Value = try!(toml.parse()
+            .map_err(|vec| ConfigErrorKind::VecParserError(vec)));
+
+        // the zone definitions are templates, the facts of the host are only gathered for them
+        if toml.contains("${") {
+            let mut variables = TemplateVariables::from_host();
+            if let Some(&Value::Table(ref table)) = value.lookup("variables") {
+                for (name, value) in table {
+                    if let Value::String(ref value) = *value {
+                        variables.insert(name.clone(), value.clone());
+                    }
+                }
+            }
+
+            if let Value::Table(ref mut table) = value {
+                if let Some(zones) = table.get_mut("zones") {
+                    try!(variables.substitute_value(zones));
+                }
+            }
+        }
+
         let mut decoder: Decoder = Decoder::new(value);
         Ok(try!(Self::decode(&mut decoder)))
     }
 }
 
+/// The values of the `${name}` template variables of the zone definitions and zone files
+///
+/// With them, one config can be deployed to several hosts, each with e.g. its own A record,
+///  `www IN A ${ipv4}`. A variable is one of the `variables` table of the config, otherwise a
+///  fact of the host, `hostname`, `ipv4` or `ipv6`; the environment is not looked at, so that
+///  none of it is ever published in a zone. Names start with a letter or `_`, so the `${offset}`
+///  of `$GENERATE` is left as it is. An undefined variable is an error in the zone definitions,
+///  see `substitute`, in zone files it is left as it is, e.g. in the text of a TXT record, see
+///  `substitute_defined`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TemplateVariables {
+    values: BTreeMap<String, String>,
+}
+
+impl TemplateVariables {
+    /// Variables without any values
+    pub fn new() -> Self {
+        TemplateVariables::default()
+    }
+
+    /// The facts of the host which could be determined: `hostname`, and `ipv4` and `ipv6`, the
+    ///  addresses of the host which the default routes are from
+    pub fn from_host() -> Self {
+        let mut variables = TemplateVariables::new();
+        if let Some(hostname) = host_name() {
+            variables.insert("hostname".to_string(), hostname);
+        }
+        let unspecified_v4 = IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0));
+        if let Some(ipv4) = route_address(unspecified_v4, IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1))) {
+            variables.insert("ipv4".to_string(), ipv4.to_string());
+        }
+        let unspecified_v6 = IpAddr::V6(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 0));
+        let documentation_v6 = IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1));
+        if let Some(ipv6) = route_address(unspecified_v6, documentation_v6) {
+            variables.insert("ipv6".to_string(), ipv6.to_string());
+        }
+        variables
+    }
+
+    /// Defines the variable, replacing any value it had
+    pub fn insert(&mut self, name: String, value: String) {
+        self.values.insert(name, value);
+    }
+
+    /// The value of the variable, None if it is undefined
+    pub fn get(&self, name: &str) -> Option<String> {
+        self.values.get(name).cloned()
+    }
+
+    /// Replaces the variables in the text with their values, an undefined variable is an error
+    pub fn substitute(&self, text: &str) -> ConfigResult<String> {
+        self.replace(text, true)
+    }
+
+    /// Replaces the defined variables in the text with their values, leaving the text of the
+    ///  others as it is, e.g. that of a zone file, where `${...}` may be data
+    pub fn substitute_defined(&self, text: &str) -> String {
+        self.replace(text, false).expect("undefined variables are left as they are")
+    }
+
+    /// Replaces the variables in the text, if `is_strict` an undefined one is an error
+    fn replace(&self, text: &str, is_strict: bool) -> ConfigResult<String> {
+        let mut substituted = String::with_capacity(text.len());
+        let mut rest = text;
+
+        while let Some(start) = rest.find("${") {
+            substituted.push_str(&rest[..start]);
+            rest = &rest[start..];
+
+            let name = rest[2..].find('}').map(|end| &rest[2..2 + end]);
+            match name {
+                Some(name) if is_variable_name(name) && (is_strict || self.get(name).is_some()) => {
+                    let value = try!(self.get(name)
+                        .ok_or(ConfigErrorKind::UndefinedVariable(name.to_string())));
+                    substituted.push_str(&value);
+                    rest = &rest[name.len() + 3..];
+                }
+                _ => {
+                    substituted.push_str("${");
+                    rest = &rest[2..];
+                }
+            }
+        }
+
+        substituted.push_str(rest);
+        Ok(substituted)
+    }
+
+    /// Replaces the variables in all the strings of the value
+    fn substitute_value(&self, value: &mut Value) -> ConfigResult<()> {
+        match *value {
+            Value::String(ref mut string) => {
+                let substituted = try!(self.substitute(string));
+                *string = substituted;
+            }
+            Value::Array(ref mut array) => {
+                for value in array {
+                    try!(self.substitute_value(value));
+                }
+            }
+            Value::Table(ref mut table) => {
+                for (_, value) in table {
+                    try!(self.substitute_value(value));
+                }
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+}
+
+/// Returns true if the name is that of a template variable rather than e.g. a `$GENERATE` offset
+fn is_variable_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some('a'...'z') | Some('A'...'Z') | Some('_') => (),
+        _ => return false,
+    }
+    chars.all(|ch| match ch {
+        'a'...'z' | 'A'...'Z' | '0'...'9' | '_' => true,
+        _ => false,
+    })
+}
+
+/// The name of the host, from the kernel or the environment
+fn host_name() -> Option<String> {
+    for path in &["/proc/sys/kernel/hostname", "/etc/hostname"] {
+        let mut hostname = String::new();
+        if File::open(path).and_then(|mut file| file.read_to_string(&mut hostname)).is_ok() {
+            let hostname = hostname.trim();
+            if !hostname.is_empty() {
+                return Some(hostname.to_string());
+            }
+        }
+    }
+
+    env::var("HOSTNAME").or_else(|_| env::var("COMPUTERNAME")).ok()
+}
+
+/// The local address of the route to the remote address, connecting a UDP socket sends nothing
+fn route_address(unspecified: IpAddr, remote: IpAddr) -> Option<IpAddr> {
+    UdpSocket::bind(SocketAddr::new(unspecified, 0))
+        .and_then(|socket| {
+            try!(socket.connect(SocketAddr::new(remote, 53)));
+            socket.local_addr()
+        })
+        .ok()
+        .map(|local| local.ip())
+        .and_then(|ip| if ip == unspecified { None } else { Some(ip) })
+}
+
+/// The requests accepted on a type of listener, anything not specified is allowed
+#[derive(RustcDecodable, PartialEq, Debug)]
+pub struct ListenerPolicyConfig {
+    allow_any: Option<bool>,
+    allow_zone_transfer: Option<bool>,
+    allow_notify: Option<bool>,
+    allow_update: Option<bool>,
+    allow_unsupported_op_codes: Option<bool>,
+}
+
+impl ListenerPolicyConfig {
+    /// the policy, see `ListenerPolicy`
+    pub fn get_policy(&self) -> ListenerPolicy {
+        let default = ListenerPolicy::default();
+
+        ListenerPolicy {
+            any: self.allow_any.unwrap_or(default.any),
+            zone_transfer: self.allow_zone_transfer.unwrap_or(default.zone_transfer),
+            notify: self.allow_notify.unwrap_or(default.notify),
+            update: self.allow_update.unwrap_or(default.update),
+            unsupported_op_codes: self.allow_unsupported_op_codes
+                .unwrap_or(default.unsupported_op_codes),
+        }
+    }
+}
+
 #[derive(RustcDecodable, PartialEq, Debug)]
 pub struct ZoneConfig {
     zone: String, // TODO: make Domain::Name decodable
     zone_type: ZoneType,
     file: String,
     allow_update: Option<bool>,
+    journal_compaction: Option<usize>,
+    update_policy: Option<Vec<String>>,
+    update_forward: Option<String>,
+    primary: Option<String>,
+    transfer_source: Option<String>,
+    primary_key: Option<String>,
+    secondaries: Option<Vec<String>>,
+    allow_transfer: Option<Vec<String>>,
+    listen_addrs: Option<Vec<String>>,
+    apex_alias: Option<String>,
+    redis_store: Option<String>,
+    store_cache_secs: Option<u64>,
+    forwarders: Option<Vec<String>>,
+    forward_cache: Option<bool>,
+    forward_shadow: Option<String>,
+    max_rrset_records: Option<usize>,
+    max_rrset_bytes: Option<usize>,
     enable_dnssec: Option<bool>,
+    enable_nsec3: Option<bool>,
+    nsec3_iterations: Option<u16>,
+    nsec3_salt: Option<String>,
+    nsec3_opt_out: Option<bool>,
     keys: Vec<KeyConfig>,
 }
 
@@ -145,7 +446,28 @@ impl ZoneConfig {
             zone_type: zone_type,
             file: file,
             allow_update: allow_update,
+            journal_compaction: None,
+            update_policy: None,
+            update_forward: None,
+            primary: None,
+            transfer_source: None,
+            primary_key: None,
+            secondaries: None,
+            allow_transfer: None,
+            listen_addrs: None,
+            apex_alias: None,
+            redis_store: None,
+            store_cache_secs: None,
+            forwarders: None,
+            forward_cache: None,
+            forward_shadow: None,
+            max_rrset_records: None,
+            max_rrset_bytes: None,
             enable_dnssec: enable_dnssec,
+            enable_nsec3: None,
+            nsec3_iterations: None,
+            nsec3_salt: None,
+            nsec3_opt_out: None,
             keys: keys,
         }
     }
@@ -174,11 +496,231 @@ impl ZoneConfig {
         self.allow_update.unwrap_or(false)
     }
 
+    /// the number of records in the journal of the updates above which it is replaced with a
+    ///  snapshot of the zone, None to keep every update, see `Authority::set_journal_compaction`
+    pub fn get_journal_compaction(&self) -> Option<usize> {
+        self.journal_compaction
+    }
+
+    /// the update-policy rules for the zone, in evaluation order, see `UpdateRule` for the format
+    ///
+    /// None if no policy is configured, in which case any update key may change any record
+    pub fn get_update_policy(&self) -> ParseResult<Option<UpdatePolicy>> {
+        if let Some(ref rules) = self.update_policy {
+            let mut update_rules = Vec::with_capacity(rules.len());
+            for rule in rules {
+                update_rules.push(try!(UpdateRule::from_str(rule)));
+            }
+
+            Ok(Some(UpdatePolicy::new(update_rules)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// for Slave zones, the address of the primary master to which updates are forwarded
+    pub fn get_update_forward(&self) -> ParseResult<Option<SocketAddr>> {
+        if let Some(ref primary) = self.update_forward {
+            Ok(Some(try!(SocketAddr::from_str(primary))))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// for Slave zones, the address of the primary master from which the zone is transferred
+    pub fn get_primary(&self) -> ParseResult<Option<SocketAddr>> {
+        if let Some(ref primary) = self.primary {
+            Ok(Some(try!(SocketAddr::from_str(primary))))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// for Slave zones, the local address the queries to the primary are sent from, see
+    ///  `Authority::set_transfer_source`
+    pub fn get_transfer_source(&self) -> ParseResult<Option<IpAddr>> {
+        match self.transfer_source {
+            Some(ref source) => Ok(Some(try!(IpAddr::from_str(source)))),
+            None => Ok(None),
+        }
+    }
+
+    /// for Slave zones, the name of the TSIG key shared with the primary, one of `tsig_keys`, see
+    ///  `Authority::set_primary_key`
+    pub fn get_primary_key(&self) -> ParseResult<Option<Name>> {
+        match self.primary_key {
+            Some(ref key) => Ok(Some(try!(Name::parse(key, Some(&Name::root()))))),
+            None => Ok(None),
+        }
+    }
+
+    /// the addresses of the secondaries which are sent a NOTIFY when the zone changes
+    pub fn get_secondaries(&self) -> ParseResult<Vec<SocketAddr>> {
+        let mut secondaries = Vec::new();
+        if let Some(ref addrs) = self.secondaries {
+            for addr in addrs {
+                secondaries.push(try!(SocketAddr::from_str(addr)));
+            }
+        }
+
+        Ok(secondaries)
+    }
+
+    /// the clients which may transfer the zone, see `TransferRule` for the format
+    ///
+    /// None if transfers are not restricted, in which case any client may transfer the zone
+    pub fn get_transfer_acl(&self) -> ParseResult<Option<TransferAcl>> {
+        if let Some(ref rules) = self.allow_transfer {
+            let mut transfer_rules = Vec::with_capacity(rules.len());
+            for rule in rules {
+                transfer_rules.push(try!(TransferRule::from_str(rule)));
+            }
+
+            Ok(Some(TransferAcl::new(transfer_rules)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// the local addresses of the listeners on which the zone is answered, isolating it from the
+    ///  zones of other tenants, see `Authority::set_listen_addrs`
+    ///
+    /// None if the zone is answered on every listener
+    pub fn get_listen_addrs(&self) -> ParseResult<Option<Vec<IpAddr>>> {
+        if let Some(ref addrs) = self.listen_addrs {
+            let mut listen_addrs = Vec::with_capacity(addrs.len());
+            for addr in addrs {
+                listen_addrs.push(try!(IpAddr::from_str(addr)));
+            }
+
+            Ok(Some(listen_addrs))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// for Master zones, the name whose A and AAAA records are served at the apex, in place of a
+    ///  CNAME, see `Authority::set_apex_alias`
+    pub fn get_apex_alias(&self) -> ParseResult<Option<Name>> {
+        match self.apex_alias {
+            Some(ref alias) => Ok(Some(try!(Name::parse(alias, Some(&Name::new()))))),
+            None => Ok(None),
+        }
+    }
+
+    /// the address of a Redis server from which the records of the zone are served, rather than
+    ///  from memory, see `KeyValueAuthority`; the zone file only seeds the store, when it does not
+    ///  have the SOA of the zone
+    pub fn get_redis_store(&self) -> ParseResult<Option<SocketAddr>> {
+        match self.redis_store {
+            Some(ref redis) => Ok(Some(try!(SocketAddr::from_str(redis)))),
+            None => Ok(None),
+        }
+    }
+
+    /// the options set for the zone which only a zone in memory supports, not one served from a
+    ///  store, see `redis_store` and `AuthorityObject`; a zone in a store must be a Master zone
+    pub fn get_in_memory_options(&self) -> Vec<&'static str> {
+        let mut options = Vec::new();
+        if self.zone_type != ZoneType::Master {
+            options.push("zone_type");
+        }
+        if self.is_update_allowed() {
+            options.push("allow_update");
+        }
+
+        let set = [("journal_compaction", self.journal_compaction.is_some()),
+                   ("update_policy", self.update_policy.is_some()),
+                   ("update_forward", self.update_forward.is_some()),
+                   ("primary", self.primary.is_some()),
+                   ("transfer_source", self.transfer_source.is_some()),
+                   ("primary_key", self.primary_key.is_some()),
+                   ("secondaries", self.secondaries.as_ref().map_or(false, |s| !s.is_empty())),
+                   ("allow_transfer", self.allow_transfer.is_some()),
+                   ("listen_addrs", self.listen_addrs.is_some()),
+                   ("apex_alias", self.apex_alias.is_some()),
+                   ("max_rrset_records", self.max_rrset_records.is_some()),
+                   ("max_rrset_bytes", self.max_rrset_bytes.is_some()),
+                   ("enable_dnssec", self.is_dnssec_enabled()),
+                   ("enable_nsec3", self.enable_nsec3.unwrap_or(false)),
+                   ("keys", !self.keys.is_empty())];
+        options.extend(set.iter().filter(|&&(_, is_set)| is_set).map(|&(option, _)| option));
+        options
+    }
+
+    /// how long the values read from the store of the zone are cached, see `redis_store`
+    pub fn get_store_cache_ttl(&self) -> Duration {
+        Duration::from_secs(self.store_cache_secs.unwrap_or(DEFAULT_CACHE_TTL))
+    }
+
+    /// for Forward zones, the resolvers the queries are forwarded to, in the order they are
+    ///  tried, see `ForwardUpstream` for the format
+    pub fn get_forwarders(&self) -> ParseResult<Vec<ForwardUpstream>> {
+        let mut forwarders = Vec::new();
+        if let Some(ref upstreams) = self.forwarders {
+            for upstream in upstreams {
+                forwarders.push(try!(ForwardUpstream::from_str(upstream)));
+            }
+        }
+
+        Ok(forwarders)
+    }
+
+    /// for Forward zones, cache the responses of the resolvers, false by default
+    pub fn is_forward_cache_enabled(&self) -> bool {
+        self.forward_cache.unwrap_or(false)
+    }
+
+    /// for Forward zones, a resolver whose responses are compared with those of the forwarders,
+    ///  without being answered, see `ForwardAuthority::set_shadow`
+    pub fn get_forward_shadow(&self) -> ParseResult<Option<ForwardUpstream>> {
+        match self.forward_shadow {
+            Some(ref shadow) => Ok(Some(try!(ForwardUpstream::from_str(shadow)))),
+            None => Ok(None),
+        }
+    }
+
+    /// the most records, and bytes of record data, of any RRset after a dynamic update
+    pub fn get_rrset_limits(&self) -> RecordSetLimits {
+        let mut limits = RecordSetLimits::default();
+        if let Some(max_records) = self.max_rrset_records {
+            limits.max_records(max_records);
+        }
+        if let Some(max_bytes) = self.max_rrset_bytes {
+            limits.max_bytes(max_bytes);
+        }
+
+        limits
+    }
+
     /// declare that this zone should be signed, see keys for configuration of the keys for signing
     pub fn is_dnssec_enabled(&self) -> bool {
         self.enable_dnssec.unwrap_or(false)
     }
 
+    /// the parameters of the NSEC3 chain of a signed zone, None if it is denied with NSEC
+    ///
+    /// The salt is hex, none by default, and there are no additional iterations by default.
+    pub fn get_nsec3_param(&self) -> ParseResult<Option<NSEC3PARAM>> {
+        if !self.enable_nsec3.unwrap_or(false) {
+            return Ok(None);
+        }
+
+        let salt = match self.nsec3_salt {
+            Some(ref salt) => {
+                try!(salt.from_hex().map_err(|_| {
+                    ParseError::from(ParseErrorKind::Message("nsec3_salt is not hex"))
+                }))
+            }
+            None => Vec::new(),
+        };
+
+        Ok(Some(NSEC3PARAM::new(Nsec3HashAlgorithm::SHA1,
+                                self.nsec3_opt_out.unwrap_or(false),
+                                self.nsec3_iterations.unwrap_or(0),
+                                salt)))
+    }
+
     /// the configuration for the keys used for auth and/or dnssec zone signing.
     pub fn get_keys(&self) -> &[KeyConfig] {
         &self.keys
@@ -284,6 +826,42 @@ impl KeyConfig {
     }
 }
 
+/// A key shared with clients and peers, TSIG of RFC 8945
+///
+/// ```text
+/// [[tsig_keys]]
+/// name = "transfer-key.example.com."
+/// algorithm = "hmac-sha256"
+/// secret = "<base64>"
+/// ```
+#[derive(RustcDecodable, PartialEq, Debug)]
+pub struct TsigKeyConfig {
+    name: String,
+    algorithm: String,
+    secret: String,
+}
+
+impl TsigKeyConfig {
+    /// the name of the key, as the peers know it, the owner of the TSIG records
+    pub fn get_name(&self) -> ParseResult<Name> {
+        Name::parse(&self.name, Some(&Name::root()))
+    }
+
+    /// the HMAC algorithm of the key, e.g. `hmac-sha256`, see `TsigAlgorithm`
+    pub fn get_algorithm(&self) -> ParseResult<TsigAlgorithm> {
+        TsigAlgorithm::from_str(&self.algorithm)
+            .map_err(|e| ParseErrorKind::Msg(format!("{}", e)).into())
+    }
+
+    /// the shared secret, decoded from base64
+    pub fn get_secret(&self) -> ParseResult<Vec<u8>> {
+        self.secret.from_base64().map_err(|e| {
+            ParseErrorKind::Msg(format!("secret of tsig key: {} is not base64: {}", self.name, e))
+                .into()
+        })
+    }
+}
+
 /// Configuration for a TLS certificate
 #[derive(RustcDecodable, PartialEq, Debug)]
 pub struct TlsCertConfig {
@@ -311,3 +889,48 @@ impl TlsCertConfig {
         &self.subject_name
     }
 }
+
+/// Configuration of the mDNS responder
+#[derive(RustcDecodable, PartialEq, Debug)]
+pub struct MdnsResponderConfig {
+    hostname: String,
+    addresses: Vec<String>,
+    services: Vec<MdnsServiceConfig>,
+}
+
+impl MdnsResponderConfig {
+    /// the host name, addresses and services to claim, see `MdnsResponder`
+    pub fn get_config(&self) -> ParseResult<MdnsConfig> {
+        let mut addresses = Vec::with_capacity(self.addresses.len());
+        for address in &self.addresses {
+            addresses.push(try!(IpAddr::from_str(address)));
+        }
+
+        Ok(MdnsConfig {
+            hostname: self.hostname.clone(),
+            addresses: addresses,
+            services: self.services.iter().map(|s| s.get_service()).collect(),
+        })
+    }
+}
+
+/// A service announced by the mDNS responder
+#[derive(RustcDecodable, PartialEq, Debug)]
+pub struct MdnsServiceConfig {
+    instance: String,
+    service_type: String,
+    port: u16,
+    txt: Vec<String>,
+}
+
+impl MdnsServiceConfig {
+    /// the service, see `MdnsService`
+    pub fn get_service(&self) -> MdnsService {
+        MdnsService {
+            instance: self.instance.clone(),
+            service_type: self.service_type.clone(),
+            port: self.port,
+            txt: self.txt.clone(),
+        }
+    }
+}
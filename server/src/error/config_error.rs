@@ -54,5 +54,10 @@ error_chain! {
         description("parser errors")
         display("parser errors: {:?}", vec)
       }
+
+      UndefinedVariable(name: String) {
+        description("undefined template variable")
+        display("undefined template variable: ${{{}}}", name)
+      }
     }
 }
@@ -27,18 +27,26 @@
 //! * New features for securing public information
 
 extern crate chrono;
+#[cfg(feature = "parallel-signing")]
+extern crate crossbeam;
 #[macro_use]
 extern crate error_chain;
 #[macro_use]
 extern crate futures;
+#[cfg(unix)]
+extern crate libc;
 #[macro_use]
 extern crate log;
 extern crate native_tls;
+#[cfg(feature = "parallel-signing")]
+extern crate num_cpus;
 extern crate openssl;
+extern crate rand;
 extern crate rusqlite;
 extern crate rustc_serialize;
 extern crate time;
 extern crate toml;
+#[macro_use]
 extern crate tokio_core;
 extern crate tokio_tls;
 extern crate trust_dns;
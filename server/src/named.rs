@@ -30,6 +30,10 @@
 //!    -p PORT, --port=PORT    Override the listening port
 //!    --tls-port=PORT         Override the listening port for TLS connections
 //! ```
+//!
+//! With systemd socket activation, the sockets passed in `LISTEN_FDS` are used rather than binding
+//!  to the configured addresses and ports; the TCP sockets named `tls`, `FileDescriptorName=tls`,
+//!  are for DNS over TLS.
 
 extern crate chrono;
 extern crate docopt;
@@ -43,10 +47,12 @@ extern crate trust_dns_server;
 
 use std::fs;
 use std::fs::File;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
 use std::net::{Ipv4Addr, IpAddr, SocketAddr, TcpListener, ToSocketAddrs, UdpSocket};
 use std::path::{Path, PathBuf};
 use std::io::{Read, Write};
+use std::sync::Arc;
+use std::time::Duration as StdDuration;
 
 use chrono::Duration;
 use docopt::Docopt;
@@ -56,16 +62,22 @@ use openssl::{hash, nid, pkcs12};
 use openssl::x509::*;
 use openssl::x509::extension::*;
 
+use trust_dns::cache::DnsCache;
 use trust_dns::error::ParseResult;
 use trust_dns::logger;
 use trust_dns::version;
-use trust_dns::serialize::txt::{Lexer, Parser};
-use trust_dns::rr::Name;
-use trust_dns::rr::dnssec::{Algorithm, KeyPair, Signer};
+use trust_dns::rr::{Name, Record, RecordType};
+use trust_dns::rr::dnssec::{Algorithm, KeyPair, Signer, SupportedAlgorithms, TSigner};
+
+use trust_dns_server::authority::{Authority, AuthorityObject, Catalog, ForwardAuthority, Journal,
+                                  KeyValueAuthority, RedisStore, ZoneFile, ZoneType,
+                                  DEFAULT_CACHE_ENTRIES};
+use trust_dns_server::config::{Config, KeyConfig, TemplateVariables, TlsCertConfig, ZoneConfig};
+use trust_dns_server::server::{bind_mdns_ipv4, listen_fds, ActivatedSocket, MdnsResponder,
+                               ServerFuture, TLS_FD_NAME};
 
-use trust_dns_server::authority::{Authority, Catalog, Journal, ZoneType};
-use trust_dns_server::config::{Config, KeyConfig, TlsCertConfig, ZoneConfig};
-use trust_dns_server::server::ServerFuture;
+/// seconds to wait for each command to the Redis server of a zone, see `redis_store`
+const REDIS_TIMEOUT: u64 = 2;
 
 // the Docopt usage string.
 //  http://docopt.org
@@ -97,29 +109,136 @@ struct Args {
     pub flag_tls_port: Option<u16>,
 }
 
-fn parse_file(file: File,
+fn parse_file(zone_file: &mut ZoneFile,
               origin: Option<Name>,
               zone_type: ZoneType,
               allow_update: bool,
               is_dnssec_enabled: bool)
               -> ParseResult<Authority> {
-    let mut file = file;
-    let mut buf = String::new();
+    // all the errors of the zone are reported, at where they are in the files
+    let (origin, records, meta) = try!(zone_file.load(origin));
+
+    let mut authority = Authority::new(origin, records, zone_type, allow_update, is_dnssec_enabled);
+    authority.set_record_meta(meta);
+    Ok(authority)
+}
 
-    // TODO, this should really use something to read line by line or some other method to
-    //  keep the usage down. and be a custom lexer...
-    try!(file.read_to_string(&mut buf));
-    let lexer = Lexer::new(&buf);
-    let (origin, records) = try!(Parser::new().parse(lexer, origin));
+/// A zone whose queries are forwarded to the resolvers of the config, its file is not read
+fn load_forward_zone(zone_config: &ZoneConfig) -> Result<ForwardAuthority, String> {
+    let zone_name: Name = zone_config.get_zone().expect("bad zone name");
+    let upstreams = try!(zone_config.get_forwarders()
+        .map_err(|e| format!("bad forwarders for zone: {}: {}", zone_name, e)));
+    if upstreams.is_empty() {
+        return Err(format!("no forwarders for zone: {}", zone_name));
+    }
+
+    let mut forwarder = ForwardAuthority::new(zone_name.clone(), upstreams);
+    if zone_config.is_forward_cache_enabled() {
+        forwarder.set_cache(Some(DnsCache::default()));
+    }
+    let shadow = try!(zone_config.get_forward_shadow()
+        .map_err(|e| format!("bad forward_shadow for zone: {}: {}", zone_name, e)));
+    if let Some(ref shadow) = shadow {
+        info!("comparing the forwarders of zone: {} with: {:?}", zone_name, shadow);
+    }
+    forwarder.set_shadow(shadow);
 
-    Ok(Authority::new(origin, records, zone_type, allow_update, is_dnssec_enabled))
+    info!("forwarding zone: {} to: {:?}", zone_name, forwarder.get_upstreams());
+    Ok(forwarder)
 }
 
-fn load_zone(zone_dir: &Path, zone_config: &ZoneConfig) -> Result<Authority, String> {
+/// A zone served from a Redis server, its file is only loaded into the store if the store does
+///  not have the SOA of the zone
+///
+/// The catalog answers such a zone with its records alone, see `AuthorityObject`, the options
+///  and records which need a zone in memory are refused rather than silently ignored.
+fn load_key_value_zone(zone_dir: &Path,
+                       zone_config: &ZoneConfig,
+                       variables: &TemplateVariables,
+                       redis: SocketAddr)
+                       -> Result<KeyValueAuthority, String> {
+    let zone_name: Name = zone_config.get_zone().expect("bad zone name");
+    let options = zone_config.get_in_memory_options();
+    if !options.is_empty() {
+        return Err(format!("{} not supported for zones in redis, only for zones in memory",
+                           options.join(", ")));
+    }
+
+    let store = RedisStore::new(redis, StdDuration::from_secs(REDIS_TIMEOUT));
+    let mut authority = KeyValueAuthority::new(zone_name.clone(), Box::new(store));
+    authority.set_cache(zone_config.get_store_cache_ttl(), DEFAULT_CACHE_ENTRIES);
+
+    let soa = authority.lookup(&zone_name, RecordType::SOA, false, SupportedAlgorithms::new());
+    if !authority.is_available() {
+        return Err(format!("could not read the zone from redis: {}", redis));
+    }
+
+    let zone_path: PathBuf = zone_dir.to_owned().join(zone_config.get_file());
+    if soa.is_empty() && zone_path.exists() {
+        info!("loading zone file: {:?} into redis: {}", zone_path, redis);
+        let mut zone_file =
+            ZoneFile::new(zone_path.clone(), zone_dir.to_owned(), variables.clone());
+        let zone = try!(parse_file(&mut zone_file,
+                                   Some(zone_name.clone()),
+                                   ZoneType::Master,
+                                   false,
+                                   false)
+            .map_err(|e| format!("error reading zone: {:?}: {}", zone_path, e)));
+
+        let records: Vec<Record> = zone.get_transfer_records().into_iter().cloned().collect();
+        if let Some(record) = records.iter().find(|r| needs_in_memory(&zone_name, r)) {
+            return Err(format!("{} {:?} in zone file: {:?} is not supported for zones in redis, \
+                                only for zones in memory",
+                               record.get_name(),
+                               record.get_rr_type(),
+                               zone_path));
+        }
+        try!(authority.update_records(&records, false)
+            .map_err(|e| format!("error storing zone in redis: {:?}", e)));
+    } else if soa.is_empty() {
+        warn!("zone: {} has no SOA in redis: {}", zone_name, redis);
+    }
+
+    info!("serving zone: {} from redis: {}", zone_name, redis);
+    Ok(authority)
+}
+
+/// True for a wildcard, a delegation or a DNAME, which only a zone in memory answers for the
+///  names they stand for, see `Authority::search` and `Authority::get_occlusion`
+fn needs_in_memory(origin: &Name, record: &Record) -> bool {
+    let name = record.get_name();
+    (name.num_labels() > 0 && name[0] == "*") || record.get_rr_type() == RecordType::DNAME ||
+    (record.get_rr_type() == RecordType::NS && name != origin)
+}
+
+/// The TSIG keys of the config, by name, see `Catalog::add_tsig_key`
+fn load_tsig_keys(config: &Config) -> Result<HashMap<Name, Arc<TSigner>>, String> {
+    let mut tsig_keys = HashMap::new();
+    for key_config in config.get_tsig_keys() {
+        let name = try!(key_config.get_name().map_err(|e| format!("bad tsig key name: {}", e)));
+        let algorithm = try!(key_config.get_algorithm()
+            .map_err(|e| format!("bad algorithm for tsig key: {}: {}", name, e)));
+        let secret = try!(key_config.get_secret()
+            .map_err(|e| format!("bad secret for tsig key: {}: {}", name, e)));
+
+        info!("adding tsig key: {} algorithm: {:?}", name, algorithm);
+        tsig_keys.insert(name.clone(), Arc::new(TSigner::new(name, algorithm, secret)));
+    }
+
+    Ok(tsig_keys)
+}
+
+fn load_zone(zone_dir: &Path,
+             zone_config: &ZoneConfig,
+             variables: &TemplateVariables,
+             tsig_keys: &HashMap<Name, Arc<TSigner>>)
+             -> Result<Authority, String> {
     let zone_name: Name = zone_config.get_zone().expect("bad zone name");
     let zone_path: PathBuf = zone_dir.to_owned().join(zone_config.get_file());
     let journal_path: PathBuf = zone_path.with_extension("jrnl");
     let original_key_path: PathBuf = zone_path.with_extension("key");
+    let primary = try!(zone_config.get_primary()
+        .map_err(|e| format!("bad primary for zone: {}: {}", zone_name, e)));
 
     // load the zone
     let mut authority = if zone_config.is_update_allowed() && journal_path.exists() {
@@ -142,15 +261,15 @@ fn load_zone(zone_dir: &Path, zone_config: &ZoneConfig) -> Result<Authority, Str
     } else if zone_path.exists() {
         info!("loading zone file: {:?}", zone_path);
 
-        let zone_file = try!(File::open(&zone_path)
-            .map_err(|e| format!("error opening zone file: {:?}: {}", zone_path, e)));
-
-        let mut authority = try!(parse_file(zone_file,
+        let mut zone_file =
+            ZoneFile::new(zone_path.clone(), zone_dir.to_owned(), variables.clone());
+        let mut authority = try!(parse_file(&mut zone_file,
                                             Some(zone_name.clone()),
                                             zone_config.get_zone_type(),
                                             zone_config.is_update_allowed(),
                                             zone_config.is_dnssec_enabled())
             .map_err(|e| format!("error reading zone: {:?}: {}", zone_path, e)));
+        authority.set_zone_file(Some(zone_file));
 
         // if dynamic update is enabled, enable the journal
         if zone_config.is_update_allowed() {
@@ -167,10 +286,70 @@ fn load_zone(zone_dir: &Path, zone_config: &ZoneConfig) -> Result<Authority, Str
 
         info!("loaded zone: {}", zone_name);
         authority
+    } else if zone_config.get_zone_type() == ZoneType::Slave && primary.is_some() {
+        // the zone is transferred from the primary once the server is running
+        info!("no zone file for slave zone: {}, transferring it from the primary",
+              zone_name);
+        Authority::new(zone_name.clone(),
+                       BTreeMap::new(),
+                       ZoneType::Slave,
+                       zone_config.is_update_allowed(),
+                       zone_config.is_dnssec_enabled())
     } else {
         return Err(format!("no zone file defined at: {:?}", zone_path));
     };
 
+    let update_policy = try!(zone_config.get_update_policy()
+        .map_err(|e| format!("bad update_policy for zone: {}: {}", zone_name, e)));
+    authority.set_update_policy(update_policy);
+
+    let update_forward = try!(zone_config.get_update_forward()
+        .map_err(|e| format!("bad update_forward for zone: {}: {}", zone_name, e)));
+    authority.set_update_forward(update_forward);
+    authority.set_primary(primary);
+
+    let transfer_source = try!(zone_config.get_transfer_source()
+        .map_err(|e| format!("bad transfer_source for zone: {}: {}", zone_name, e)));
+    authority.set_transfer_source(transfer_source);
+
+    let primary_key = match try!(zone_config.get_primary_key()
+        .map_err(|e| format!("bad primary_key for zone: {}: {}", zone_name, e))) {
+        Some(key_name) => {
+            match tsig_keys.get(&key_name) {
+                Some(tsigner) => Some(tsigner.clone()),
+                None => {
+                    return Err(format!("primary_key: {} of zone: {} is not in tsig_keys",
+                                       key_name,
+                                       zone_name))
+                }
+            }
+        }
+        None => None,
+    };
+    authority.set_primary_key(primary_key);
+    authority.set_journal_compaction(zone_config.get_journal_compaction());
+
+    let secondaries = try!(zone_config.get_secondaries()
+        .map_err(|e| format!("bad secondaries for zone: {}: {}", zone_name, e)));
+    authority.set_secondaries(secondaries);
+
+    let transfer_acl = try!(zone_config.get_transfer_acl()
+        .map_err(|e| format!("bad allow_transfer for zone: {}: {}", zone_name, e)));
+    authority.set_transfer_acl(transfer_acl);
+
+    let listen_addrs = try!(zone_config.get_listen_addrs()
+        .map_err(|e| format!("bad listen_addrs for zone: {}: {}", zone_name, e)));
+    authority.set_listen_addrs(listen_addrs);
+    authority.set_rrset_limits(zone_config.get_rrset_limits());
+
+    let apex_alias = try!(zone_config.get_apex_alias()
+        .map_err(|e| format!("bad apex_alias for zone: {}: {}", zone_name, e)));
+    authority.set_apex_alias(apex_alias);
+
+    let nsec3_param = try!(zone_config.get_nsec3_param()
+        .map_err(|e| format!("bad nsec3 for zone: {}: {}", zone_name, e)));
+    authority.set_nsec3_param(nsec3_param);
+
     // load any keys for the Zone, if it is a dynamic update zone, then keys are required
     if zone_config.is_dnssec_enabled() {
         // old backward compatible logic, TODO: deprecated
@@ -402,11 +581,40 @@ pub fn main() {
         args.flag_zonedir.as_ref().map(|s| Path::new(s)).unwrap_or(config.get_directory());
 
     let mut catalog: Catalog = Catalog::new();
+    let variables = config.get_variables();
+    let tsig_keys = load_tsig_keys(&config).expect("could not load the tsig keys");
+    for tsigner in tsig_keys.values() {
+        catalog.add_tsig_key(tsigner.clone());
+    }
+
     // configure our server based on the config_path
     for zone in config.get_zones() {
         let zone_name = zone.get_zone().expect(&format!("bad zone name in {:?}", config_path));
 
-        match load_zone(zone_dir, zone) {
+        if zone.get_zone_type() == ZoneType::Forward {
+            match load_forward_zone(zone) {
+                Ok(forwarder) => catalog.upsert_forward(zone_name, forwarder),
+                Err(error) => error!("could not load zone {}: {}", zone_name, error),
+            }
+            continue;
+        }
+
+        match zone.get_redis_store() {
+            Ok(Some(redis)) => {
+                match load_key_value_zone(zone_dir, zone, &variables, redis) {
+                    Ok(authority) => catalog.upsert_object(zone_name, Box::new(authority)),
+                    Err(error) => error!("could not load zone {}: {}", zone_name, error),
+                }
+                continue;
+            }
+            Ok(None) => (),
+            Err(error) => {
+                error!("could not load zone {}: bad redis_store: {}", zone_name, error);
+                continue;
+            }
+        }
+
+        match load_zone(zone_dir, zone, &variables, &tsig_keys) {
             Ok(authority) => catalog.upsert(zone_name, authority),
             Err(error) => error!("could not load zone {}: {}", zone_name, error),
         }
@@ -421,20 +629,65 @@ pub fn main() {
         .chain(v6addr.into_iter().map(|x| IpAddr::V6(x)))
         .collect();
     let listen_port: u16 = args.flag_port.unwrap_or(config.get_listen_port());
-    let tcp_request_timeout = config.get_tcp_request_timeout();
+    let tcp_limits = config.get_tcp_limits();
+    let udp_policy = config.get_udp_policy();
+    let tcp_policy = config.get_tcp_policy();
+    let tls_policy = config.get_tls_policy();
 
     if listen_addrs.len() == 0 {
         listen_addrs.push(IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)));
     }
-    let sockaddrs: Vec<SocketAddr> =
-        listen_addrs.iter().flat_map(|x| (*x, listen_port).to_socket_addrs().unwrap()).collect();
-    let udp_sockets: Vec<UdpSocket> = sockaddrs.iter()
-        .map(|x| UdpSocket::bind(x).expect(&format!("could not bind to udp: {}", x)))
-        .collect();
-    let tcp_listeners: Vec<TcpListener> = sockaddrs.iter()
-        .map(|x| TcpListener::bind(x).expect(&format!("could not bind to tcp: {}", x)))
-        .collect();
 
+    // with socket activation the sockets passed by systemd are used, rather than binding any
+    let activated = listen_fds().expect("could not adopt the sockets of socket activation");
+    let (udp_sockets, tcp_listeners, mut activated_tls) = if activated.is_empty() {
+        let sockaddrs: Vec<SocketAddr> = listen_addrs.iter()
+            .flat_map(|x| (*x, listen_port).to_socket_addrs().unwrap())
+            .collect();
+        let udp_sockets: Vec<UdpSocket> = sockaddrs.iter()
+            .map(|x| UdpSocket::bind(x).expect(&format!("could not bind to udp: {}", x)))
+            .collect();
+        let tcp_listeners: Vec<TcpListener> = sockaddrs.iter()
+            .map(|x| TcpListener::bind(x).expect(&format!("could not bind to tcp: {}", x)))
+            .collect();
+        (udp_sockets, tcp_listeners, None)
+    } else {
+        info!("adopting {} sockets of socket activation", activated.len());
+        let mut udp_sockets: Vec<UdpSocket> = Vec::new();
+        let mut tcp_listeners: Vec<TcpListener> = Vec::new();
+        let mut tls_listeners: Vec<TcpListener> = Vec::new();
+        for socket in activated {
+            let is_tls = socket.is_tls();
+            match socket {
+                ActivatedSocket::Udp { socket, .. } => udp_sockets.push(socket),
+                ActivatedSocket::Tcp { listener, .. } => {
+                    if is_tls {
+                        tls_listeners.push(listener);
+                    } else {
+                        tcp_listeners.push(listener);
+                    }
+                }
+            }
+        }
+        (udp_sockets, tcp_listeners, Some(tls_listeners))
+    };
+
+    // a UDP socket on the unspecified address does not know the address a datagram was sent to,
+    //  the zones bound to addresses would be refused over UDP but answered over TCP
+    if catalog.has_listen_addrs() {
+        let locals = udp_sockets.iter()
+            .map(|socket| socket.local_addr())
+            .chain(tcp_listeners.iter()
+                .chain(activated_tls.iter().flat_map(|listeners| listeners.iter()))
+                .map(|listener| listener.local_addr()));
+        for local in locals {
+            let local = local.expect("listener is not bound?");
+            if is_unspecified(local.ip()) {
+                panic!("zones with listen_addrs need listeners on specific addresses, not: {}",
+                       local);
+            }
+        }
+    }
 
     // now, run the server, based on the config
     let mut server = ServerFuture::new(catalog).expect("error creating ServerFuture");
@@ -442,25 +695,35 @@ pub fn main() {
     // load all the listeners
     for udp_socket in udp_sockets {
         info!("listening for UDP on {:?}", udp_socket);
-        server.register_socket(udp_socket);
+        server.register_socket_with_policy(udp_socket, udp_policy);
     }
 
     // and TCP as necessary
     for tcp_listener in tcp_listeners {
         info!("listening for TCP on {:?}", tcp_listener);
-        server.register_listener(tcp_listener, tcp_request_timeout)
+        server.register_listener_with_policy(tcp_listener, tcp_limits, tcp_policy)
             .expect("could not register TCP listener");
     }
 
     // and TLS as necessary
     if let Some(tls_cert_config) = config.get_tls_cert() {
-        let tls_listen_port: u16 = args.flag_tls_port.unwrap_or(config.get_tls_listen_port());
-        let tls_sockaddrs: Vec<SocketAddr> = listen_addrs.iter()
-            .flat_map(|x| (*x, tls_listen_port).to_socket_addrs().unwrap())
-            .collect();
-        let tls_listeners: Vec<TcpListener> = tls_sockaddrs.iter()
-            .map(|x| TcpListener::bind(x).expect(&format!("could not bind to tls: {}", x)))
-            .collect();
+        let tls_listeners: Vec<TcpListener> = match activated_tls.take() {
+            Some(ref tls_listeners) if tls_listeners.is_empty() => {
+                panic!("a tls certificate was specified, but systemd passed no socket named: {}",
+                       TLS_FD_NAME)
+            }
+            Some(tls_listeners) => tls_listeners,
+            None => {
+                let tls_listen_port: u16 =
+                    args.flag_tls_port.unwrap_or(config.get_tls_listen_port());
+                let tls_sockaddrs: Vec<SocketAddr> = listen_addrs.iter()
+                    .flat_map(|x| (*x, tls_listen_port).to_socket_addrs().unwrap())
+                    .collect();
+                tls_sockaddrs.iter()
+                    .map(|x| TcpListener::bind(x).expect(&format!("could not bind to tls: {}", x)))
+                    .collect()
+            }
+        };
         if tls_listeners.is_empty() {
             warn!("a tls certificate was specified, but no TCP addresses configured to listen on");
         }
@@ -472,11 +735,34 @@ pub fn main() {
             let tls_cert = load_cert(tls_cert_config).expect("error loading tls certificate file");
 
             info!("listening for TLS on {:?}", tls_listener);
-            server.register_tls_listener(tls_listener, tcp_request_timeout, tls_cert)
+            server.register_tls_listener_with_policy(tls_listener, tcp_limits, tls_cert, tls_policy)
                 .expect("could not register TLS listener");
         }
     }
 
+    if activated_tls.map_or(false, |tls_listeners| !tls_listeners.is_empty()) {
+        panic!("sockets for TLS were passed by systemd, but no tls certificate was specified");
+    }
+
+    // and the mDNS responder, on all interfaces
+    if let Some(mdns) = config.get_mdns() {
+        let mdns_config = mdns.get_config().expect("bad mdns config");
+        let socket = bind_mdns_ipv4(&Ipv4Addr::new(0, 0, 0, 0))
+            .expect("could not bind to mdns, is another responder running?");
+
+        info!("responding to mDNS for: {} on {:?}",
+              mdns_config.hostname,
+              socket);
+        server.register_mdns_socket(socket, MdnsResponder::new(mdns_config))
+            .expect("could not register mDNS socket");
+    }
+
+    // the zones are reloaded as their files change
+    if let Some(interval) = config.get_zone_watch_interval() {
+        info!("watching the zone files every {:?}", interval);
+        server.watch_zone_files(interval).expect("could not watch the zone files");
+    }
+
     // config complete, starting!
     banner();
     info!("awaiting connections...");
@@ -488,6 +774,14 @@ pub fn main() {
     info!("Trust-DNS {} stopping", trust_dns::version());
 }
 
+/// true for `0.0.0.0` and `::`, on which a listener accepts the requests for any local address
+fn is_unspecified(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => ip.is_unspecified(),
+        IpAddr::V6(ip) => ip.is_unspecified(),
+    }
+}
+
 fn banner() {
     info!("");
     info!("    o                      o            o             ");
@@ -0,0 +1,232 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! DNS Push Notifications, [RFC 8765](https://tools.ietf.org/html/rfc8765)
+
+use std::cell::RefCell;
+use std::sync::Arc;
+
+use trust_dns::op::{DsoTlv, DsoType, Message, OpCode, Query, ResponseCode};
+use trust_dns::op::dso::PUSH_REMOVED_TTL;
+use trust_dns::rr::{DNSClass, Name, Record, RecordType};
+use trust_dns::rr::dnssec::SupportedAlgorithms;
+
+use authority::{Catalog, ChangeListener, ChangeSubscription, RecordChange};
+use server::{DsoConfig, DsoHandler, DsoSession, ResponseHandle};
+
+/// Subscriptions of clients to changes of records, which are pushed over their DSO sessions
+///
+/// Once registered, see `register()`, a SUBSCRIBE for a name in the catalog is answered with the
+///  current records, followed by the records added to or removed from any zone as they change:
+///
+/// ```
+/// use std::sync::Arc;
+/// use trust_dns_server::authority::Catalog;
+/// use trust_dns_server::server::{DnsPush, DsoConfig};
+///
+/// let catalog = Catalog::new();
+/// let mut dso = DsoConfig::default();
+/// let subscription = DnsPush::register(&Arc::new(DnsPush::new()), &catalog, &mut dso);
+/// // then ServerFuture::new(catalog) and set_dso_config(dso)
+/// ```
+pub struct DnsPush {
+    subscriptions: RefCell<Vec<Subscription>>,
+}
+
+struct Subscription {
+    id: u16,
+    query: Query,
+    push_handle: ResponseHandle,
+}
+
+impl DnsPush {
+    /// Returns a new instance, without any subscriptions
+    pub fn new() -> Self {
+        DnsPush { subscriptions: RefCell::new(Vec::new()) }
+    }
+
+    /// Listens for changes to the zones of the catalog, and handles SUBSCRIBE, UNSUBSCRIBE and
+    ///  RECONFIRM requests of the DSO sessions using `dso`
+    ///
+    /// The catalog only holds a weak reference, changes are pushed while `dso` or the sessions
+    ///  using it hold the handlers, or until the returned subscription is unsubscribed.
+    pub fn register(push: &Arc<DnsPush>,
+                    catalog: &Catalog,
+                    dso: &mut DsoConfig)
+                    -> ChangeSubscription {
+        let listener: Arc<ChangeListener> = push.clone();
+        let subscription = catalog.add_change_listener(&listener);
+        for dso_type in &[DsoType::Subscribe, DsoType::Unsubscribe, DsoType::Reconfirm] {
+            dso.set_handler(*dso_type, push.clone());
+        }
+
+        subscription
+    }
+
+    /// Returns the number of subscriptions, of all clients
+    pub fn get_subscription_count(&self) -> usize {
+        self.subscriptions.borrow().len()
+    }
+
+    fn subscribe(&self,
+                 session: &mut DsoSession,
+                 id: u16,
+                 query: &Query,
+                 catalog: &Catalog)
+                 -> Message {
+        // the response carries the id of the request, it can not be unidirectional
+        if id == 0 {
+            return Message::error_msg(id, OpCode::Dso, ResponseCode::FormErr);
+        }
+
+        let records: Vec<Record> = match catalog.find_auth_recurse(query.get_name()) {
+            Some(authority) => {
+                let authority = authority.read();
+                authority.lookup(query.get_name(),
+                            query.get_query_type(),
+                            false,
+                            SupportedAlgorithms::new())
+                    .into_iter()
+                    .filter(|r| Self::matches(query, r))
+                    .cloned()
+                    .collect()
+            }
+            None => return Message::error_msg(id, OpCode::Dso, ResponseCode::NotAuth),
+        };
+
+        debug!("subscribing id: {} to: {} {:?}",
+               id,
+               query.get_name(),
+               query.get_query_type());
+        self.subscriptions.borrow_mut().push(Subscription {
+            id: id,
+            query: query.clone(),
+            push_handle: session.get_push_handle().clone(),
+        });
+
+        if !records.is_empty() {
+            session.send_after_response(Self::push(records));
+        }
+        Message::error_msg(id, OpCode::Dso, ResponseCode::NoError)
+    }
+
+    fn unsubscribe(&self, session: &DsoSession, subscribe_id: u16) {
+        let dst = session.get_push_handle().get_dst();
+        self.subscriptions
+            .borrow_mut()
+            .retain(|s| s.id != subscribe_id || s.push_handle.get_dst() != dst);
+    }
+
+    /// pushes the removal of the record to the subscriptions of the session, if it is not in
+    ///  the catalog
+    fn reconfirm(&self, session: &DsoSession, record: &Record, catalog: &Catalog) {
+        if let Some(authority) = catalog.find_auth_recurse(record.get_name()) {
+            let authority = authority.read();
+            if authority.lookup(record.get_name(),
+                        record.get_rr_type(),
+                        false,
+                        SupportedAlgorithms::new())
+                .into_iter()
+                .any(|r| r == record) {
+                return;
+            }
+        }
+
+        let mut removed = record.clone();
+        removed.ttl(PUSH_REMOVED_TTL);
+        let dst = session.get_push_handle().get_dst();
+        self.send(&[removed],
+                  |subscription| subscription.push_handle.get_dst() == dst);
+    }
+
+    /// sends the records matching each subscription, those to closed connections are removed
+    fn send<F>(&self, records: &[Record], filter: F)
+        where F: Fn(&Subscription) -> bool
+    {
+        let mut subscriptions = self.subscriptions.borrow_mut();
+        let mut closed = Vec::new();
+
+        for (index, subscription) in subscriptions.iter_mut().enumerate() {
+            if !filter(subscription) {
+                continue;
+            }
+
+            let matched: Vec<Record> = records.iter()
+                .filter(|r| Self::matches(&subscription.query, r))
+                .cloned()
+                .collect();
+            if matched.is_empty() {
+                continue;
+            }
+
+            if let Err(e) = subscription.push_handle.send(Self::push(matched)) {
+                debug!("dropping subscription id: {} to: {}, {}",
+                       subscription.id,
+                       subscription.push_handle.get_dst(),
+                       e);
+                closed.push(index);
+            }
+        }
+
+        for index in closed.into_iter().rev() {
+            subscriptions.remove(index);
+        }
+    }
+
+    fn matches(query: &Query, record: &Record) -> bool {
+        record.get_name() == query.get_name() &&
+        (query.get_query_type() == RecordType::ANY ||
+         query.get_query_type() == record.get_rr_type()) &&
+        (query.get_query_class() == DNSClass::ANY ||
+         query.get_query_class() == record.get_dns_class())
+    }
+
+    fn push(records: Vec<Record>) -> Message {
+        let mut message = Message::new();
+        message.id(0).op_code(OpCode::Dso).add_dso_tlv(DsoTlv::Push(records));
+        message
+    }
+}
+
+impl DsoHandler for DnsPush {
+    fn handle_request(&self,
+                      session: &mut DsoSession,
+                      request: &Message,
+                      catalog: &Catalog)
+                      -> Option<Message> {
+        let id = request.get_id();
+        match request.get_dso_tlvs()[0] {
+            DsoTlv::Subscribe(ref query) => Some(self.subscribe(session, id, query, catalog)),
+            DsoTlv::Unsubscribe(subscribe_id) => {
+                self.unsubscribe(session, subscribe_id);
+                None
+            }
+            DsoTlv::Reconfirm(ref record) => {
+                self.reconfirm(session, record, catalog);
+                None
+            }
+            _ => Some(Message::error_msg(id, OpCode::Dso, ResponseCode::DSOTYPENI)),
+        }
+    }
+}
+
+impl ChangeListener for DnsPush {
+    fn records_changed(&self, _origin: &Name, changes: &[RecordChange]) {
+        let records: Vec<Record> = changes.iter()
+            .map(|change| match *change {
+                RecordChange::Added(ref record) => record.clone(),
+                RecordChange::Removed(ref record) => {
+                    let mut removed = record.clone();
+                    removed.ttl(PUSH_REMOVED_TTL);
+                    removed
+                }
+            })
+            .collect();
+
+        self.send(&records, |_| true);
+    }
+}
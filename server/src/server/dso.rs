@@ -0,0 +1,271 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! DNS Stateful Operations sessions, [RFC 8490](https://tools.ietf.org/html/rfc8490)
+
+use std::cell::Cell;
+use std::cmp;
+use std::collections::HashMap;
+use std::io;
+use std::mem;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::Duration;
+
+use trust_dns::op::{DsoTlv, DsoType, Message, MessageType, OpCode, ResponseCode};
+
+use authority::Catalog;
+use server::ResponseHandle;
+
+/// the server may abort a session no sooner than this, after the inactivity timeout expired
+const MIN_ABORT_SECS: u64 = 5;
+
+/// Handles DSO requests for a primary TLV type, e.g. DNS Push, see `DnsPush`
+pub trait DsoHandler {
+    /// Returns the response to the request, which is ignored for unidirectional messages
+    ///
+    /// A response with `ResponseCode::NoError` establishes the session. Messages to the client
+    ///  which must follow the response are queued with `DsoSession::send_after_response()`, later
+    ///  ones are sent through a clone of `DsoSession::get_push_handle()`.
+    fn handle_request(&self,
+                      session: &mut DsoSession,
+                      request: &Message,
+                      catalog: &Catalog)
+                      -> Option<Message>;
+}
+
+/// The timeouts offered to clients, and the handlers for DSO types beyond those of RFC 8490
+///
+/// Sessions are only available on TCP and TLS listeners, registered with the `ServerFuture`
+///  after this is set.
+#[derive(Clone)]
+pub struct DsoConfig {
+    /// time after which the client is to close an idle session, the server aborts it after
+    ///  twice this, `u32::max_value()` milliseconds or more is infinite
+    pub inactivity_timeout: Duration,
+    /// time between keepalive traffic from the client, if there is no other traffic
+    pub keepalive_interval: Duration,
+    handlers: HashMap<DsoType, Arc<DsoHandler>>,
+}
+
+impl DsoConfig {
+    /// Sets the handler for requests with the `dso_type` as the primary TLV
+    ///
+    /// Keepalive, Retry Delay and Encryption Padding are handled by the session, handlers for
+    ///  these are ignored.
+    pub fn set_handler(&mut self, dso_type: DsoType, handler: Arc<DsoHandler>) {
+        match dso_type {
+            DsoType::KeepAlive |
+            DsoType::RetryDelay |
+            DsoType::EncryptionPadding => {
+                warn!("ignoring handler for dso_type: {:?}, handled by the session", dso_type)
+            }
+            _ => {
+                self.handlers.insert(dso_type, handler);
+            }
+        }
+    }
+}
+
+impl Default for DsoConfig {
+    fn default() -> Self {
+        // RFC 8490 section 6.2 suggests 15 seconds and 1 hour
+        DsoConfig {
+            inactivity_timeout: Duration::from_secs(15),
+            keepalive_interval: Duration::from_secs(3600),
+            handlers: HashMap::new(),
+        }
+    }
+}
+
+/// The DSO state of a single TCP or TLS connection
+///
+/// The session is established by the first successful DSO request, after which the inactivity
+///  timeout replaces the idle timeout of the listener. A protocol error is returned as an `Err`
+///  from `handle_request()`, upon which the connection is to be closed.
+pub struct DsoSession {
+    config: DsoConfig,
+    established: bool,
+    idle_timeout: Rc<Cell<Duration>>,
+    push_handle: ResponseHandle,
+    pending: Vec<Message>,
+}
+
+impl DsoSession {
+    /// Returns a new session, not yet established
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - the timeouts and handlers for this session
+    /// * `idle_timeout` - the idle timeout of the connection, see `TimeoutStream`
+    /// * `push_handle` - the connection to the client, for unidirectional messages
+    pub fn new(config: DsoConfig,
+               idle_timeout: Rc<Cell<Duration>>,
+               push_handle: ResponseHandle)
+               -> Self {
+        DsoSession {
+            config: config,
+            established: false,
+            idle_timeout: idle_timeout,
+            push_handle: push_handle,
+            pending: Vec::new(),
+        }
+    }
+
+    /// Returns true once a DSO request has succeeded on this connection
+    pub fn is_established(&self) -> bool {
+        self.established
+    }
+
+    /// Returns the handle for pushing unidirectional messages to the client
+    pub fn get_push_handle(&self) -> &ResponseHandle {
+        &self.push_handle
+    }
+
+    /// Sends a unidirectional message to the client, only allowed on an established session
+    pub fn send(&mut self, message: Message) -> io::Result<()> {
+        if !self.established {
+            return Err(io::Error::new(io::ErrorKind::NotConnected,
+                                      "DSO session is not established"));
+        }
+
+        self.push_handle.send(message)
+    }
+
+    /// Queues a unidirectional message, sent once the response to the current request is sent
+    ///
+    /// These are discarded if the session is not established by then.
+    pub fn send_after_response(&mut self, message: Message) {
+        self.pending.push(message);
+    }
+
+    /// Sends the messages queued by `send_after_response()`
+    pub fn send_pending(&mut self) -> io::Result<()> {
+        if !self.established {
+            self.pending.clear();
+            return Ok(());
+        }
+
+        for message in mem::replace(&mut self.pending, Vec::new()) {
+            try!(self.push_handle.send(message));
+        }
+        Ok(())
+    }
+
+    /// Asks the client to close the session, and not to reconnect within `delay`
+    pub fn retry_delay(&mut self, delay: Duration) -> io::Result<()> {
+        let mut message = Self::message(0);
+        message.add_dso_tlv(DsoTlv::RetryDelay(to_millis(delay)));
+        self.send(message)
+    }
+
+    /// Handles a message with the `OpCode::Dso`
+    ///
+    /// # Return value
+    ///
+    /// The response to send, None for unidirectional messages, or an error if the connection is
+    ///  to be closed.
+    pub fn handle_request(&mut self,
+                          request: &Message,
+                          catalog: &Catalog)
+                          -> io::Result<Option<Message>> {
+        // no requests are sent by the server, these would be responses to unidirectional ones
+        if request.get_message_type() == MessageType::Response {
+            return Err(Self::protocol_error("unexpected DSO response"));
+        }
+
+        let id = request.get_id();
+        if !request.get_queries().is_empty() || !request.get_answers().is_empty() ||
+           !request.get_name_servers().is_empty() ||
+           !request.get_additionals().is_empty() {
+            return Ok(Self::response(id, ResponseCode::FormErr));
+        }
+
+        let primary = match request.get_dso_tlvs().first() {
+            Some(primary) => primary.clone(),
+            None => return Ok(Self::response(id, ResponseCode::FormErr)),
+        };
+
+        match primary {
+            DsoTlv::KeepAlive { .. } if id == 0 => {
+                Err(Self::protocol_error("unidirectional keepalive from a client"))
+            }
+            DsoTlv::KeepAlive { .. } => {
+                self.establish();
+                let mut response = Self::message(id);
+                response.message_type(MessageType::Response)
+                    .add_dso_tlv(DsoTlv::KeepAlive {
+                        inactivity_timeout: to_millis(self.config.inactivity_timeout),
+                        keepalive_interval: to_millis(self.config.keepalive_interval),
+                    });
+                Ok(Some(response))
+            }
+            DsoTlv::RetryDelay(..) => Err(Self::protocol_error("retry delay from a client")),
+            DsoTlv::EncryptionPadding(..) => Ok(Self::response(id, ResponseCode::FormErr)),
+            _ => {
+                let handler = self.config.handlers.get(&primary.get_dso_type()).cloned();
+                match handler {
+                    Some(handler) => {
+                        let response = handler.handle_request(self, request, catalog);
+                        if id == 0 {
+                            return Ok(None);
+                        }
+
+                        if response.as_ref()
+                            .map_or(false, |r| r.get_response_code() == ResponseCode::NoError) {
+                            self.establish();
+                        }
+                        Ok(response)
+                    }
+                    None if id == 0 => {
+                        Err(Self::protocol_error("unknown primary TLV in a unidirectional message"))
+                    }
+                    None => Ok(Self::response(id, ResponseCode::DSOTYPENI)),
+                }
+            }
+        }
+    }
+
+    fn establish(&mut self) {
+        if !self.established {
+            debug!("DSO session established");
+            self.established = true;
+        }
+
+        // RFC 8490 section 7.1.1, twice the inactivity timeout, or five seconds
+        let idle_timeout = if to_millis(self.config.inactivity_timeout) == u32::max_value() {
+            Duration::from_secs(0)
+        } else {
+            cmp::max(self.config.inactivity_timeout * 2,
+                     Duration::from_secs(MIN_ABORT_SECS))
+        };
+        self.idle_timeout.set(idle_timeout);
+    }
+
+    fn message(id: u16) -> Message {
+        let mut message = Message::new();
+        message.id(id).op_code(OpCode::Dso);
+        message
+    }
+
+    fn response(id: u16, response_code: ResponseCode) -> Option<Message> {
+        Some(Message::error_msg(id, OpCode::Dso, response_code))
+    }
+
+    fn protocol_error(reason: &str) -> io::Error {
+        warn!("DSO protocol error, closing the connection: {}", reason);
+        io::Error::new(io::ErrorKind::InvalidData, reason.to_string())
+    }
+}
+
+/// milliseconds, as in DSO TLVs, where u32::max_value() is infinite
+fn to_millis(duration: Duration) -> u32 {
+    let millis = duration.as_secs()
+        .saturating_mul(1000)
+        .saturating_add((duration.subsec_nanos() / 1_000_000) as u64);
+    cmp::min(millis, u32::max_value() as u64) as u32
+}
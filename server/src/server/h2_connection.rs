@@ -0,0 +1,673 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! The server side of an HTTP/2 connection, RFC 9113, enough of it for DNS over HTTPS
+
+use std::cmp;
+use std::collections::{HashMap, VecDeque};
+use std::mem;
+
+use server::hpack::{self, HpackDecoder, HpackError, MAX_HEADER_LIST_SIZE};
+use server::https_stream::{dns_request, max_age, HttpRequest, DNS_MESSAGE_MEDIA_TYPE};
+
+/// The connection preface of the client, RFC 9113 section 3.4
+pub const PREFACE: &'static [u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+
+/// The streams a client may have open at once, those beyond are refused
+const MAX_CONCURRENT_STREAMS: u32 = 100;
+/// The largest header block of a request, with its CONTINUATION frames
+const MAX_HEADER_BLOCK_LEN: usize = 8192;
+/// The CONTINUATION frames of a header block, even empty ones do not go on without end
+const MAX_CONTINUATION_FRAMES: usize = 16;
+/// The largest frame payload either side may send without a SETTINGS_MAX_FRAME_SIZE
+const DEFAULT_MAX_FRAME_SIZE: usize = 16384;
+/// The flow control window of the connection and of each stream, RFC 9113 section 6.9.2
+const DEFAULT_WINDOW_SIZE: i64 = 65535;
+const MAX_WINDOW_SIZE: i64 = 0x7FFF_FFFF;
+const FRAME_HEADER_LEN: usize = 9;
+
+const DATA: u8 = 0x0;
+const HEADERS: u8 = 0x1;
+const PRIORITY: u8 = 0x2;
+const RST_STREAM: u8 = 0x3;
+const SETTINGS: u8 = 0x4;
+const PUSH_PROMISE: u8 = 0x5;
+const PING: u8 = 0x6;
+const GOAWAY: u8 = 0x7;
+const WINDOW_UPDATE: u8 = 0x8;
+const CONTINUATION: u8 = 0x9;
+
+const FLAG_END_STREAM: u8 = 0x1;
+const FLAG_ACK: u8 = 0x1;
+const FLAG_END_HEADERS: u8 = 0x4;
+const FLAG_PADDED: u8 = 0x8;
+const FLAG_PRIORITY: u8 = 0x20;
+
+const SETTINGS_ENABLE_PUSH: u16 = 0x2;
+const SETTINGS_MAX_CONCURRENT_STREAMS: u16 = 0x3;
+const SETTINGS_INITIAL_WINDOW_SIZE: u16 = 0x4;
+const SETTINGS_MAX_FRAME_SIZE: u16 = 0x5;
+const SETTINGS_MAX_HEADER_LIST_SIZE: u16 = 0x6;
+
+/// The error codes of RST_STREAM and GOAWAY, RFC 9113 section 7
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ErrorCode {
+    Protocol = 0x1,
+    FlowControl = 0x3,
+    StreamClosed = 0x5,
+    FrameSize = 0x6,
+    RefusedStream = 0x7,
+    Compression = 0x9,
+    EnhanceYourCalm = 0xB,
+}
+
+/// A stream opened by a request, until its response is sent
+struct H2Stream {
+    /// the fields of the request, once its header block is received
+    headers: Option<Vec<(String, String)>>,
+    body: Vec<u8>,
+    /// the client has sent all of the request
+    end_stream: bool,
+    /// the DATA of the response the client is ready to receive
+    send_window: i64,
+}
+
+/// The server side of DNS over HTTPS over an HTTP/2 connection
+///
+/// The frames read from the connection are passed to `receive`, and the frames to send in reply
+///  are appended to the buffer given to each method, for the caller to write. The requests are
+///  handed out one at a time by `next_request`, the next once `respond` has answered the one
+///  before, so that each response is sent on the stream of its request. Requests which are not
+///  for the catalog are answered as they arrive, see `dns_request`.
+pub struct H2Connection {
+    path: String,
+    decoder: HpackDecoder,
+    read_buf: Vec<u8>,
+    preface_read: bool,
+    streams: HashMap<u32, H2Stream>,
+    /// the highest stream opened by the client, new streams have higher ids
+    last_stream_id: u32,
+    /// the stream and END_STREAM of the header block continued by CONTINUATION frames
+    continuation: Option<(u32, bool)>,
+    /// the CONTINUATION frames of the header block so far
+    continuation_frames: usize,
+    header_block: Vec<u8>,
+    /// the DNS messages of the requests received, waiting for those before to be answered
+    requests: VecDeque<(u32, Vec<u8>)>,
+    /// the stream of the request handed out, whose response is awaited
+    current: Option<u32>,
+    /// the DATA of the responses waiting for flow control window, in order
+    pending: VecDeque<(u32, Vec<u8>)>,
+    send_window: i64,
+    initial_window: i64,
+    max_frame_size: usize,
+    /// the client sent GOAWAY, no more streams are opened
+    goaway: bool,
+    /// the connection failed, GOAWAY is sent and nothing else
+    closed: bool,
+}
+
+impl H2Connection {
+    /// Starts the connection, appending the SETTINGS of the server to `out`
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - the URI path of the DNS API, requests for any other path are answered 404
+    /// * `out` - the frames to send to the client
+    pub fn new(path: String, out: &mut Vec<u8>) -> Self {
+        let mut settings = Vec::with_capacity(12);
+        settings.extend_from_slice(&u16_bytes(SETTINGS_MAX_CONCURRENT_STREAMS));
+        settings.extend_from_slice(&u32_bytes(MAX_CONCURRENT_STREAMS));
+        settings.extend_from_slice(&u16_bytes(SETTINGS_MAX_HEADER_LIST_SIZE));
+        settings.extend_from_slice(&u32_bytes(MAX_HEADER_LIST_SIZE as u32));
+        write_frame(out, SETTINGS, 0, 0, &settings);
+
+        H2Connection {
+            path: path,
+            decoder: HpackDecoder::new(),
+            read_buf: Vec::new(),
+            preface_read: false,
+            streams: HashMap::new(),
+            last_stream_id: 0,
+            continuation: None,
+            continuation_frames: 0,
+            header_block: Vec::new(),
+            requests: VecDeque::new(),
+            current: None,
+            pending: VecDeque::new(),
+            send_window: DEFAULT_WINDOW_SIZE,
+            initial_window: DEFAULT_WINDOW_SIZE,
+            max_frame_size: DEFAULT_MAX_FRAME_SIZE,
+            goaway: false,
+            closed: false,
+        }
+    }
+
+    /// The connection is to be closed once `out` is written, after an error or the GOAWAY of
+    ///  the client once its streams are answered
+    pub fn is_closed(&self) -> bool {
+        self.closed || (self.goaway && self.streams.is_empty())
+    }
+
+    /// No request is in progress, see `DrainStream`
+    pub fn is_idle(&self) -> bool {
+        self.streams.is_empty() && self.read_buf.is_empty()
+    }
+
+    /// Handles the bytes read from the connection, starting with the preface of the client
+    pub fn receive(&mut self, bytes: &[u8], out: &mut Vec<u8>) {
+        if self.closed {
+            return;
+        }
+
+        self.read_buf.extend_from_slice(bytes);
+        let read_buf = mem::replace(&mut self.read_buf, Vec::new());
+        let mut pos = 0;
+
+        if !self.preface_read {
+            if read_buf.len() < PREFACE.len() {
+                self.read_buf = read_buf;
+                return;
+            }
+            if !read_buf.starts_with(PREFACE) {
+                return self.connection_error(ErrorCode::Protocol, out);
+            }
+
+            pos = PREFACE.len();
+            self.preface_read = true;
+        }
+
+        while !self.closed && read_buf.len() - pos >= FRAME_HEADER_LEN {
+            let header = &read_buf[pos..pos + FRAME_HEADER_LEN];
+            let len = (header[0] as usize) << 16 | (header[1] as usize) << 8 | header[2] as usize;
+            let frame_type = header[3];
+            let flags = header[4];
+            let stream_id = read_u32(&header[5..]) & 0x7FFF_FFFF;
+
+            if len > DEFAULT_MAX_FRAME_SIZE {
+                return self.connection_error(ErrorCode::FrameSize, out);
+            }
+            if read_buf.len() - pos < FRAME_HEADER_LEN + len {
+                break;
+            }
+
+            pos += FRAME_HEADER_LEN;
+            self.frame(frame_type, flags, stream_id, &read_buf[pos..pos + len], out);
+            pos += len;
+        }
+
+        if !self.closed {
+            self.read_buf = read_buf[pos..].to_vec();
+        }
+    }
+
+    /// Returns the DNS message of the next request, once the one before is answered
+    pub fn next_request(&mut self) -> Option<Vec<u8>> {
+        if self.current.is_some() || self.closed {
+            return None;
+        }
+
+        while let Some((stream_id, message)) = self.requests.pop_front() {
+            // the client may have reset the stream meanwhile
+            if self.streams.contains_key(&stream_id) {
+                self.current = Some(stream_id);
+                return Some(message);
+            }
+        }
+
+        None
+    }
+
+    /// Sends the DNS message in response to the request handed out by `next_request`
+    pub fn respond(&mut self, message: &[u8], out: &mut Vec<u8>) {
+        let stream_id = match self.current.take() {
+            Some(stream_id) => stream_id,
+            None => return,
+        };
+        if self.closed || !self.streams.contains_key(&stream_id) {
+            debug!("response to reset stream: {}", stream_id);
+            return;
+        }
+
+        // cacheable for the lowest TTL of the answers, RFC 8484 section 5.1
+        let mut block = Vec::new();
+        hpack::encode_field(&mut block, ":status", "200");
+        hpack::encode_field(&mut block, "content-type", DNS_MESSAGE_MEDIA_TYPE);
+        hpack::encode_field(&mut block, "content-length", &message.len().to_string());
+        hpack::encode_field(&mut block,
+                            "cache-control",
+                            &format!("max-age={}", max_age(message)));
+        write_frame(out, HEADERS, FLAG_END_HEADERS, stream_id, &block);
+
+        self.pending.push_back((stream_id, message.to_vec()));
+        self.send_data(out);
+    }
+
+    fn frame(&mut self,
+             frame_type: u8,
+             flags: u8,
+             stream_id: u32,
+             payload: &[u8],
+             out: &mut Vec<u8>) {
+        // nothing may come between a header block and its CONTINUATION, RFC 9113 section 6.10
+        if let Some((continued, _)) = self.continuation {
+            if frame_type != CONTINUATION || stream_id != continued {
+                return self.connection_error(ErrorCode::Protocol, out);
+            }
+        }
+
+        match frame_type {
+            DATA => self.data(flags, stream_id, payload, out),
+            HEADERS => self.headers(flags, stream_id, payload, out),
+            PRIORITY if stream_id == 0 => self.connection_error(ErrorCode::Protocol, out),
+            PRIORITY if payload.len() != 5 => self.connection_error(ErrorCode::FrameSize, out),
+            PRIORITY => (),
+            RST_STREAM if stream_id == 0 || stream_id > self.last_stream_id => {
+                self.connection_error(ErrorCode::Protocol, out)
+            }
+            RST_STREAM if payload.len() != 4 => self.connection_error(ErrorCode::FrameSize, out),
+            RST_STREAM => {
+                debug!("stream: {} reset by the client, error: {}",
+                       stream_id,
+                       read_u32(payload));
+                self.close_stream(stream_id);
+            }
+            SETTINGS => self.settings(flags, stream_id, payload, out),
+            // only servers push
+            PUSH_PROMISE => self.connection_error(ErrorCode::Protocol, out),
+            PING if stream_id != 0 => self.connection_error(ErrorCode::Protocol, out),
+            PING if payload.len() != 8 => self.connection_error(ErrorCode::FrameSize, out),
+            PING if flags & FLAG_ACK == FLAG_ACK => (),
+            PING => write_frame(out, PING, FLAG_ACK, 0, payload),
+            GOAWAY if stream_id != 0 => self.connection_error(ErrorCode::Protocol, out),
+            GOAWAY => {
+                debug!("GOAWAY from the client, error: {}",
+                       if payload.len() >= 8 { read_u32(&payload[4..]) } else { 0 });
+                self.goaway = true;
+            }
+            WINDOW_UPDATE => self.window_update(stream_id, payload, out),
+            CONTINUATION => self.continuation_frame(flags, stream_id, payload, out),
+            // unknown frames are ignored, RFC 9113 section 4.1
+            _ => (),
+        }
+    }
+
+    fn headers(&mut self, flags: u8, stream_id: u32, payload: &[u8], out: &mut Vec<u8>) {
+        // only the client opens streams, with odd ids
+        if stream_id % 2 == 0 {
+            return self.connection_error(ErrorCode::Protocol, out);
+        }
+
+        let mut fragment = match unpad(flags, payload) {
+            Some(fragment) => fragment,
+            None => return self.connection_error(ErrorCode::Protocol, out),
+        };
+        if flags & FLAG_PRIORITY == FLAG_PRIORITY {
+            if fragment.len() < 5 {
+                return self.connection_error(ErrorCode::FrameSize, out);
+            }
+            fragment = &fragment[5..];
+        }
+
+        if stream_id > self.last_stream_id {
+            self.last_stream_id = stream_id;
+
+            // the header block is still decoded, to keep the dynamic table, then refused
+            if !self.goaway && self.streams.len() < MAX_CONCURRENT_STREAMS as usize {
+                self.streams.insert(stream_id,
+                                    H2Stream {
+                                        headers: None,
+                                        body: Vec::new(),
+                                        end_stream: false,
+                                        send_window: self.initial_window,
+                                    });
+            }
+        } else if !self.streams.contains_key(&stream_id) {
+            return self.connection_error(ErrorCode::StreamClosed, out);
+        }
+
+        self.header_block.clear();
+        self.continuation_frames = 0;
+        self.continuation = Some((stream_id, flags & FLAG_END_STREAM == FLAG_END_STREAM));
+        self.header_fragment(flags, stream_id, fragment, out);
+    }
+
+    /// Continues the header block, a flood of small or empty frames is refused
+    fn continuation_frame(&mut self,
+                          flags: u8,
+                          stream_id: u32,
+                          fragment: &[u8],
+                          out: &mut Vec<u8>) {
+        self.continuation_frames += 1;
+        if self.continuation_frames > MAX_CONTINUATION_FRAMES {
+            return self.connection_error(ErrorCode::EnhanceYourCalm, out);
+        }
+
+        self.header_fragment(flags, stream_id, fragment, out);
+    }
+
+    /// Adds the fragment to the header block, which is decoded once it ends
+    fn header_fragment(&mut self,
+                       flags: u8,
+                       stream_id: u32,
+                       fragment: &[u8],
+                       out: &mut Vec<u8>) {
+        let end_stream = match self.continuation {
+            Some((continued, end_stream)) if continued == stream_id => end_stream,
+            _ => return self.connection_error(ErrorCode::Protocol, out),
+        };
+
+        self.header_block.extend_from_slice(fragment);
+        if self.header_block.len() > MAX_HEADER_BLOCK_LEN {
+            return self.connection_error(ErrorCode::EnhanceYourCalm, out);
+        }
+        if flags & FLAG_END_HEADERS == 0 {
+            return;
+        }
+
+        self.continuation = None;
+        let block = mem::replace(&mut self.header_block, Vec::new());
+        let fields = match self.decoder.decode(&block) {
+            Ok(fields) => fields,
+            Err(HpackError::Compression) => {
+                return self.connection_error(ErrorCode::Compression, out)
+            }
+            // the rest of the block is not decoded, so neither are the blocks after it
+            Err(HpackError::HeaderListSize) => {
+                return self.connection_error(ErrorCode::EnhanceYourCalm, out)
+            }
+        };
+
+        let complete = match self.streams.get_mut(&stream_id) {
+            Some(stream) => {
+                if stream.headers.is_none() {
+                    stream.headers = Some(fields);
+                    stream.end_stream = end_stream;
+                    Some(end_stream)
+                } else if end_stream && !stream.end_stream {
+                    // trailers end the request
+                    stream.end_stream = true;
+                    Some(true)
+                } else {
+                    None
+                }
+            }
+            None => {
+                debug!("refusing stream: {}, too many streams", stream_id);
+                return write_rst_stream(out, stream_id, ErrorCode::RefusedStream);
+            }
+        };
+
+        match complete {
+            Some(true) => self.request(stream_id, out),
+            Some(false) => (),
+            None => self.stream_error(stream_id, ErrorCode::Protocol, out),
+        }
+    }
+
+    fn data(&mut self, flags: u8, stream_id: u32, payload: &[u8], out: &mut Vec<u8>) {
+        if stream_id == 0 || stream_id > self.last_stream_id {
+            return self.connection_error(ErrorCode::Protocol, out);
+        }
+
+        // the window of the connection is returned at once, that of the stream is larger than
+        //  any request, RFC 9113 section 6.9
+        if !payload.is_empty() {
+            write_frame(out, WINDOW_UPDATE, 0, 0, &u32_bytes(payload.len() as u32));
+        }
+
+        let data = match unpad(flags, payload) {
+            Some(data) => data,
+            None => return self.connection_error(ErrorCode::Protocol, out),
+        };
+
+        let receiving = self.streams
+            .get(&stream_id)
+            .map_or(false, |stream| stream.headers.is_some() && !stream.end_stream);
+        if !receiving {
+            return self.stream_error(stream_id, ErrorCode::StreamClosed, out);
+        }
+
+        let too_large = match self.streams.get_mut(&stream_id) {
+            Some(stream) => {
+                stream.body.extend_from_slice(data);
+                stream.end_stream = flags & FLAG_END_STREAM == FLAG_END_STREAM;
+                stream.body.len() > u16::max_value() as usize
+            }
+            None => false,
+        };
+
+        if too_large {
+            self.respond_status(stream_id, 413, out);
+        } else if flags & FLAG_END_STREAM == FLAG_END_STREAM {
+            self.request(stream_id, out);
+        }
+    }
+
+    /// The request of the stream is received in full, it is queued for the catalog or answered
+    fn request(&mut self, stream_id: u32, out: &mut Vec<u8>) {
+        let request = match self.streams.get_mut(&stream_id) {
+            Some(stream) => {
+                let headers = stream.headers.take().unwrap_or_default();
+                dns_request(field(&headers, ":method"),
+                            field(&headers, ":path"),
+                            field(&headers, "content-type"),
+                            &stream.body,
+                            &self.path)
+            }
+            None => return,
+        };
+
+        match request {
+            HttpRequest::Dns(message) => self.requests.push_back((stream_id, message)),
+            HttpRequest::Error(status) => {
+                debug!("HTTP error: {} for stream: {}", status, stream_id);
+                self.respond_status(stream_id, status, out)
+            }
+        }
+    }
+
+    /// Answers the stream with the status and no body, which closes it
+    fn respond_status(&mut self, stream_id: u32, status: u16, out: &mut Vec<u8>) {
+        let mut block = Vec::new();
+        hpack::encode_field(&mut block, ":status", &status.to_string());
+        hpack::encode_field(&mut block, "content-length", "0");
+        write_frame(out,
+                    HEADERS,
+                    FLAG_END_HEADERS | FLAG_END_STREAM,
+                    stream_id,
+                    &block);
+
+        self.close_stream(stream_id);
+    }
+
+    fn settings(&mut self, flags: u8, stream_id: u32, payload: &[u8], out: &mut Vec<u8>) {
+        if stream_id != 0 {
+            return self.connection_error(ErrorCode::Protocol, out);
+        }
+        if flags & FLAG_ACK == FLAG_ACK {
+            if !payload.is_empty() {
+                self.connection_error(ErrorCode::FrameSize, out);
+            }
+            return;
+        }
+        if payload.len() % 6 != 0 {
+            return self.connection_error(ErrorCode::FrameSize, out);
+        }
+
+        for setting in payload.chunks(6) {
+            let value = read_u32(&setting[2..]);
+
+            match (setting[0] as u16) << 8 | setting[1] as u16 {
+                SETTINGS_ENABLE_PUSH if value > 1 => {
+                    return self.connection_error(ErrorCode::Protocol, out)
+                }
+                SETTINGS_INITIAL_WINDOW_SIZE if value as i64 > MAX_WINDOW_SIZE => {
+                    return self.connection_error(ErrorCode::FlowControl, out)
+                }
+                // the change applies to the streams already open, RFC 9113 section 6.9.2
+                SETTINGS_INITIAL_WINDOW_SIZE => {
+                    let delta = value as i64 - self.initial_window;
+                    for stream in self.streams.values_mut() {
+                        stream.send_window += delta;
+                    }
+                    self.initial_window = value as i64;
+                }
+                SETTINGS_MAX_FRAME_SIZE if value < DEFAULT_MAX_FRAME_SIZE as u32 ||
+                                           value > 0xFF_FFFF => {
+                    return self.connection_error(ErrorCode::Protocol, out)
+                }
+                SETTINGS_MAX_FRAME_SIZE => self.max_frame_size = value as usize,
+                // the server pushes nothing, and uses no dynamic table for the responses
+                _ => (),
+            }
+        }
+
+        write_frame(out, SETTINGS, FLAG_ACK, 0, &[]);
+        self.send_data(out);
+    }
+
+    fn window_update(&mut self, stream_id: u32, payload: &[u8], out: &mut Vec<u8>) {
+        if payload.len() != 4 {
+            return self.connection_error(ErrorCode::FrameSize, out);
+        }
+
+        let increment = (read_u32(payload) & 0x7FFF_FFFF) as i64;
+        if increment == 0 {
+            return self.connection_error(ErrorCode::Protocol, out);
+        }
+
+        if stream_id == 0 {
+            self.send_window += increment;
+            if self.send_window > MAX_WINDOW_SIZE {
+                return self.connection_error(ErrorCode::FlowControl, out);
+            }
+        } else {
+            let overflow = match self.streams.get_mut(&stream_id) {
+                Some(stream) => {
+                    stream.send_window += increment;
+                    stream.send_window > MAX_WINDOW_SIZE
+                }
+                // the stream may have been closed as the window was updated
+                None => false,
+            };
+
+            if overflow {
+                return self.stream_error(stream_id, ErrorCode::FlowControl, out);
+            }
+        }
+
+        self.send_data(out);
+    }
+
+    /// Sends the DATA of the responses, in order, as far as the flow control windows allow
+    fn send_data(&mut self, out: &mut Vec<u8>) {
+        while let Some((stream_id, mut data)) = self.pending.pop_front() {
+            loop {
+                let stream_window = match self.streams.get(&stream_id) {
+                    Some(stream) => stream.send_window,
+                    None => break,
+                };
+
+                let window = cmp::max(cmp::min(self.send_window, stream_window), 0) as usize;
+                let len = cmp::min(cmp::min(data.len(), window), self.max_frame_size);
+                if len == 0 && !data.is_empty() {
+                    self.pending.push_front((stream_id, data));
+                    return;
+                }
+
+                let end_stream = len == data.len();
+                write_frame(out,
+                            DATA,
+                            if end_stream { FLAG_END_STREAM } else { 0 },
+                            stream_id,
+                            &data[..len]);
+                data.drain(..len);
+
+                self.send_window -= len as i64;
+                if let Some(stream) = self.streams.get_mut(&stream_id) {
+                    stream.send_window -= len as i64;
+                }
+
+                if end_stream {
+                    self.close_stream(stream_id);
+                    break;
+                }
+            }
+        }
+    }
+
+    fn close_stream(&mut self, stream_id: u32) {
+        self.streams.remove(&stream_id);
+        self.requests.retain(|&(id, _)| id != stream_id);
+        self.pending.retain(|&(id, _)| id != stream_id);
+    }
+
+    fn stream_error(&mut self, stream_id: u32, code: ErrorCode, out: &mut Vec<u8>) {
+        debug!("stream: {} error: {:?}", stream_id, code);
+        write_rst_stream(out, stream_id, code);
+        self.close_stream(stream_id);
+    }
+
+    /// Sends GOAWAY and closes the connection, RFC 9113 section 5.4.1
+    fn connection_error(&mut self, code: ErrorCode, out: &mut Vec<u8>) {
+        debug!("connection error: {:?}", code);
+
+        let mut payload = Vec::with_capacity(8);
+        payload.extend_from_slice(&u32_bytes(self.last_stream_id));
+        payload.extend_from_slice(&u32_bytes(code as u32));
+        write_frame(out, GOAWAY, 0, 0, &payload);
+
+        self.closed = true;
+        self.read_buf.clear();
+    }
+}
+
+/// The value of the field of the request, empty if it has none
+fn field<'a>(headers: &'a [(String, String)], name: &str) -> &'a str {
+    headers.iter()
+        .find(|&&(ref field, _)| field == name)
+        .map_or("", |&(_, ref value)| value.as_str())
+}
+
+/// Returns the payload of a DATA or HEADERS frame without its padding, None if the padding is
+///  longer than the frame, RFC 9113 section 6.1
+fn unpad(flags: u8, payload: &[u8]) -> Option<&[u8]> {
+    if flags & FLAG_PADDED == 0 {
+        return Some(payload);
+    }
+
+    match payload.first() {
+        Some(&pad_len) if (pad_len as usize) < payload.len() => {
+            Some(&payload[1..payload.len() - pad_len as usize])
+        }
+        _ => None,
+    }
+}
+
+fn write_frame(out: &mut Vec<u8>, frame_type: u8, flags: u8, stream_id: u32, payload: &[u8]) {
+    let len = payload.len();
+    out.extend_from_slice(&[(len >> 16) as u8, (len >> 8) as u8, len as u8, frame_type, flags]);
+    out.extend_from_slice(&u32_bytes(stream_id));
+    out.extend_from_slice(payload);
+}
+
+fn write_rst_stream(out: &mut Vec<u8>, stream_id: u32, code: ErrorCode) {
+    write_frame(out, RST_STREAM, 0, stream_id, &u32_bytes(code as u32));
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    (bytes[0] as u32) << 24 | (bytes[1] as u32) << 16 | (bytes[2] as u32) << 8 | bytes[3] as u32
+}
+
+fn u16_bytes(value: u16) -> [u8; 2] {
+    [(value >> 8) as u8, value as u8]
+}
+
+fn u32_bytes(value: u32) -> [u8; 4] {
+    [(value >> 24) as u8, (value >> 16) as u8, (value >> 8) as u8, value as u8]
+}
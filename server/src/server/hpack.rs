@@ -0,0 +1,403 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! HPACK, the header compression of HTTP/2, RFC 7541
+
+use std::collections::{HashMap, VecDeque};
+
+/// The largest dynamic table of the decoder, the default SETTINGS_HEADER_TABLE_SIZE, which the
+///  server does not change
+pub const HEADER_TABLE_SIZE: usize = 4096;
+
+/// The largest header list the decoder returns, the SETTINGS_MAX_HEADER_LIST_SIZE of the server,
+///  counted as the fields of the dynamic table are, RFC 9113 section 6.5.2
+pub const MAX_HEADER_LIST_SIZE: usize = 16384;
+
+/// The size of each entry of the dynamic table beyond its name and value, RFC 7541 section 4.1
+const ENTRY_OVERHEAD: usize = 32;
+
+/// The static table, RFC 7541 Appendix A, its first entry is index 1
+const STATIC_TABLE: &'static [(&'static str, &'static str)] = &[
+    (":authority", ""),
+    (":method", "GET"),
+    (":method", "POST"),
+    (":path", "/"),
+    (":path", "/index.html"),
+    (":scheme", "http"),
+    (":scheme", "https"),
+    (":status", "200"),
+    (":status", "204"),
+    (":status", "206"),
+    (":status", "304"),
+    (":status", "400"),
+    (":status", "404"),
+    (":status", "500"),
+    ("accept-charset", ""),
+    ("accept-encoding", "gzip, deflate"),
+    ("accept-language", ""),
+    ("accept-ranges", ""),
+    ("accept", ""),
+    ("access-control-allow-origin", ""),
+    ("age", ""),
+    ("allow", ""),
+    ("authorization", ""),
+    ("cache-control", ""),
+    ("content-disposition", ""),
+    ("content-encoding", ""),
+    ("content-language", ""),
+    ("content-length", ""),
+    ("content-location", ""),
+    ("content-range", ""),
+    ("content-type", ""),
+    ("cookie", ""),
+    ("date", ""),
+    ("etag", ""),
+    ("expect", ""),
+    ("expires", ""),
+    ("from", ""),
+    ("host", ""),
+    ("if-match", ""),
+    ("if-modified-since", ""),
+    ("if-none-match", ""),
+    ("if-range", ""),
+    ("if-unmodified-since", ""),
+    ("last-modified", ""),
+    ("link", ""),
+    ("location", ""),
+    ("max-forwards", ""),
+    ("proxy-authenticate", ""),
+    ("proxy-authorization", ""),
+    ("range", ""),
+    ("referer", ""),
+    ("refresh", ""),
+    ("retry-after", ""),
+    ("server", ""),
+    ("set-cookie", ""),
+    ("strict-transport-security", ""),
+    ("transfer-encoding", ""),
+    ("user-agent", ""),
+    ("vary", ""),
+    ("via", ""),
+    ("www-authenticate", ""),
+];
+
+/// The Huffman code of each octet, and of EOS last, with its length in bits, RFC 7541 Appendix B
+const HUFFMAN_CODES: &'static [(u32, u8)] = &[
+    (0x1ff8, 13), (0x7fffd8, 23), (0xfffffe2, 28), (0xfffffe3, 28), (0xfffffe4, 28),
+    (0xfffffe5, 28), (0xfffffe6, 28), (0xfffffe7, 28), (0xfffffe8, 28), (0xffffea, 24),
+    (0x3ffffffc, 30), (0xfffffe9, 28), (0xfffffea, 28), (0x3ffffffd, 30), (0xfffffeb, 28),
+    (0xfffffec, 28), (0xfffffed, 28), (0xfffffee, 28), (0xfffffef, 28), (0xffffff0, 28),
+    (0xffffff1, 28), (0xffffff2, 28), (0x3ffffffe, 30), (0xffffff3, 28), (0xffffff4, 28),
+    (0xffffff5, 28), (0xffffff6, 28), (0xffffff7, 28), (0xffffff8, 28), (0xffffff9, 28),
+    (0xffffffa, 28), (0xffffffb, 28), (0x14, 6), (0x3f8, 10), (0x3f9, 10), (0xffa, 12),
+    (0x1ff9, 13), (0x15, 6), (0xf8, 8), (0x7fa, 11), (0x3fa, 10), (0x3fb, 10), (0xf9, 8),
+    (0x7fb, 11), (0xfa, 8), (0x16, 6), (0x17, 6), (0x18, 6), (0x0, 5), (0x1, 5), (0x2, 5),
+    (0x19, 6), (0x1a, 6), (0x1b, 6), (0x1c, 6), (0x1d, 6), (0x1e, 6), (0x1f, 6), (0x5c, 7),
+    (0xfb, 8), (0x7ffc, 15), (0x20, 6), (0xffb, 12), (0x3fc, 10), (0x1ffa, 13), (0x21, 6),
+    (0x5d, 7), (0x5e, 7), (0x5f, 7), (0x60, 7), (0x61, 7), (0x62, 7), (0x63, 7), (0x64, 7),
+    (0x65, 7), (0x66, 7), (0x67, 7), (0x68, 7), (0x69, 7), (0x6a, 7), (0x6b, 7), (0x6c, 7),
+    (0x6d, 7), (0x6e, 7), (0x6f, 7), (0x70, 7), (0x71, 7), (0x72, 7), (0xfc, 8), (0x73, 7),
+    (0xfd, 8), (0x1ffb, 13), (0x7fff0, 19), (0x1ffc, 13), (0x3ffc, 14), (0x22, 6), (0x7ffd, 15),
+    (0x3, 5), (0x23, 6), (0x4, 5), (0x24, 6), (0x5, 5), (0x25, 6), (0x26, 6), (0x27, 6), (0x6, 5),
+    (0x74, 7), (0x75, 7), (0x28, 6), (0x29, 6), (0x2a, 6), (0x7, 5), (0x2b, 6), (0x76, 7),
+    (0x2c, 6), (0x8, 5), (0x9, 5), (0x2d, 6), (0x77, 7), (0x78, 7), (0x79, 7), (0x7a, 7),
+    (0x7b, 7), (0x7ffe, 15), (0x7fc, 11), (0x3ffd, 14), (0x1ffd, 13), (0xffffffc, 28),
+    (0xfffe6, 20), (0x3fffd2, 22), (0xfffe7, 20), (0xfffe8, 20), (0x3fffd3, 22), (0x3fffd4, 22),
+    (0x3fffd5, 22), (0x7fffd9, 23), (0x3fffd6, 22), (0x7fffda, 23), (0x7fffdb, 23),
+    (0x7fffdc, 23), (0x7fffdd, 23), (0x7fffde, 23), (0xffffeb, 24), (0x7fffdf, 23),
+    (0xffffec, 24), (0xffffed, 24), (0x3fffd7, 22), (0x7fffe0, 23), (0xffffee, 24),
+    (0x7fffe1, 23), (0x7fffe2, 23), (0x7fffe3, 23), (0x7fffe4, 23), (0x1fffdc, 21),
+    (0x3fffd8, 22), (0x7fffe5, 23), (0x3fffd9, 22), (0x7fffe6, 23), (0x7fffe7, 23),
+    (0xffffef, 24), (0x3fffda, 22), (0x1fffdd, 21), (0xfffe9, 20), (0x3fffdb, 22), (0x3fffdc, 22),
+    (0x7fffe8, 23), (0x7fffe9, 23), (0x1fffde, 21), (0x7fffea, 23), (0x3fffdd, 22),
+    (0x3fffde, 22), (0xfffff0, 24), (0x1fffdf, 21), (0x3fffdf, 22), (0x7fffeb, 23),
+    (0x7fffec, 23), (0x1fffe0, 21), (0x1fffe1, 21), (0x3fffe0, 22), (0x1fffe2, 21),
+    (0x7fffed, 23), (0x3fffe1, 22), (0x7fffee, 23), (0x7fffef, 23), (0xfffea, 20), (0x3fffe2, 22),
+    (0x3fffe3, 22), (0x3fffe4, 22), (0x7ffff0, 23), (0x3fffe5, 22), (0x3fffe6, 22),
+    (0x7ffff1, 23), (0x3ffffe0, 26), (0x3ffffe1, 26), (0xfffeb, 20), (0x7fff1, 19),
+    (0x3fffe7, 22), (0x7ffff2, 23), (0x3fffe8, 22), (0x1ffffec, 25), (0x3ffffe2, 26),
+    (0x3ffffe3, 26), (0x3ffffe4, 26), (0x7ffffde, 27), (0x7ffffdf, 27), (0x3ffffe5, 26),
+    (0xfffff1, 24), (0x1ffffed, 25), (0x7fff2, 19), (0x1fffe3, 21), (0x3ffffe6, 26),
+    (0x7ffffe0, 27), (0x7ffffe1, 27), (0x3ffffe7, 26), (0x7ffffe2, 27), (0xfffff2, 24),
+    (0x1fffe4, 21), (0x1fffe5, 21), (0x3ffffe8, 26), (0x3ffffe9, 26), (0xffffffd, 28),
+    (0x7ffffe3, 27), (0x7ffffe4, 27), (0x7ffffe5, 27), (0xfffec, 20), (0xfffff3, 24),
+    (0xfffed, 20), (0x1fffe6, 21), (0x3fffe9, 22), (0x1fffe7, 21), (0x1fffe8, 21), (0x7ffff3, 23),
+    (0x3fffea, 22), (0x3fffeb, 22), (0x1ffffee, 25), (0x1ffffef, 25), (0xfffff4, 24),
+    (0xfffff5, 24), (0x3ffffea, 26), (0x7ffff4, 23), (0x3ffffeb, 26), (0x7ffffe6, 27),
+    (0x3ffffec, 26), (0x3ffffed, 26), (0x7ffffe7, 27), (0x7ffffe8, 27), (0x7ffffe9, 27),
+    (0x7ffffea, 27), (0x7ffffeb, 27), (0xffffffe, 28), (0x7ffffec, 27), (0x7ffffed, 27),
+    (0x7ffffee, 27), (0x7ffffef, 27), (0x7fffff0, 27), (0x3ffffee, 26), (0x3fffffff, 30),
+];
+
+/// The symbol of EOS, which may not appear in a string, only as its padding
+const HUFFMAN_EOS: u16 = 256;
+
+/// Why a header block could not be decoded, either way the dynamic table can no longer be relied
+///  on and the connection must be closed
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HpackError {
+    /// the block is malformed or refers to no entry, a COMPRESSION_ERROR, RFC 7541 section 2.3.3
+    Compression,
+    /// the fields are larger than `MAX_HEADER_LIST_SIZE`, the rest of the block is not decoded
+    HeaderListSize,
+}
+
+/// Decodes the header blocks received on a connection, in the order they are received, the
+///  dynamic table is shared by all of them
+pub struct HpackDecoder {
+    dynamic_table: VecDeque<(Vec<u8>, Vec<u8>)>,
+    size: usize,
+    max_size: usize,
+    huffman: HashMap<(u8, u32), u16>,
+}
+
+impl HpackDecoder {
+    /// Returns a decoder with an empty dynamic table of `HEADER_TABLE_SIZE`
+    pub fn new() -> Self {
+        let huffman = HUFFMAN_CODES.iter()
+            .enumerate()
+            .map(|(symbol, &(code, len))| ((len, code), symbol as u16))
+            .collect();
+
+        HpackDecoder {
+            dynamic_table: VecDeque::new(),
+            size: 0,
+            max_size: HEADER_TABLE_SIZE,
+            huffman: huffman,
+        }
+    }
+
+    /// The size of the dynamic table, the sum of its entries, RFC 7541 section 4.1
+    pub fn get_size(&self) -> usize {
+        self.size
+    }
+
+    /// Decodes a complete header block, returning its fields in order
+    ///
+    /// The fields are counted as they are decoded, so that a small block of references to large
+    ///  entries of the dynamic table is refused before it is expanded.
+    pub fn decode(&mut self, block: &[u8]) -> Result<Vec<(String, String)>, HpackError> {
+        let mut fields = Vec::new();
+        let mut list_size = 0;
+        let mut pos = 0;
+
+        while pos < block.len() {
+            let field = try!(self.decode_field(block, &mut pos, fields.is_empty())
+                .map_err(|()| HpackError::Compression));
+
+            if let Some((name, value)) = field {
+                list_size += name.len() + value.len() + ENTRY_OVERHEAD;
+                if list_size > MAX_HEADER_LIST_SIZE {
+                    return Err(HpackError::HeaderListSize);
+                }
+
+                fields.push((to_string(&name), to_string(&value)));
+            }
+        }
+
+        Ok(fields)
+    }
+
+    /// Decodes the field at `pos`, None for a dynamic table size update, which may only come
+    ///  before the first field
+    fn decode_field(&mut self,
+                    block: &[u8],
+                    pos: &mut usize,
+                    first: bool)
+                    -> Result<Option<(Vec<u8>, Vec<u8>)>, ()> {
+        let representation = block[*pos];
+
+        if representation & 0x80 == 0x80 {
+            // indexed, RFC 7541 section 6.1
+            let index = try!(read_integer(block, pos, 7));
+            self.get(index).map(Some)
+        } else if representation & 0xE0 == 0x20 {
+            // a dynamic table size update, only before the fields, RFC 7541 section 6.3
+            let max_size = try!(read_integer(block, pos, 5));
+            if !first || max_size > HEADER_TABLE_SIZE {
+                return Err(());
+            }
+
+            self.max_size = max_size;
+            self.evict(0);
+            Ok(None)
+        } else {
+            // a literal, with incremental indexing or without, RFC 7541 section 6.2
+            let indexing = representation & 0xC0 == 0x40;
+            let index = try!(read_integer(block, pos, if indexing { 6 } else { 4 }));
+            let name = if index == 0 {
+                try!(self.read_string(block, pos))
+            } else {
+                try!(self.get(index)).0
+            };
+            let value = try!(self.read_string(block, pos));
+
+            if indexing {
+                self.insert(name.clone(), value.clone());
+            }
+            Ok(Some((name, value)))
+        }
+    }
+
+    fn get(&self, index: usize) -> Result<(Vec<u8>, Vec<u8>), ()> {
+        if index == 0 {
+            Err(())
+        } else if index <= STATIC_TABLE.len() {
+            let (name, value) = STATIC_TABLE[index - 1];
+            Ok((name.as_bytes().to_vec(), value.as_bytes().to_vec()))
+        } else {
+            self.dynamic_table.get(index - STATIC_TABLE.len() - 1).cloned().ok_or(())
+        }
+    }
+
+    /// Adds the field to the dynamic table, evicting the oldest entries to make room for it, a
+    ///  field larger than the table empties it, RFC 7541 section 4.4
+    fn insert(&mut self, name: Vec<u8>, value: Vec<u8>) {
+        let size = name.len() + value.len() + ENTRY_OVERHEAD;
+        self.evict(size);
+
+        if size <= self.max_size {
+            self.size += size;
+            self.dynamic_table.push_front((name, value));
+        }
+    }
+
+    fn evict(&mut self, room: usize) {
+        while self.size + room > self.max_size {
+            match self.dynamic_table.pop_back() {
+                Some((name, value)) => self.size -= name.len() + value.len() + ENTRY_OVERHEAD,
+                None => break,
+            }
+        }
+    }
+
+    /// Reads a string literal, RFC 7541 section 5.2
+    fn read_string(&self, block: &[u8], pos: &mut usize) -> Result<Vec<u8>, ()> {
+        let huffman = block.get(*pos).map_or(false, |first| first & 0x80 == 0x80);
+        let len = try!(read_integer(block, pos, 7));
+        if block.len() - *pos < len {
+            return Err(());
+        }
+
+        let string = &block[*pos..*pos + len];
+        *pos += len;
+
+        if huffman {
+            self.huffman_decode(string)
+        } else {
+            Ok(string.to_vec())
+        }
+    }
+
+    fn huffman_decode(&self, string: &[u8]) -> Result<Vec<u8>, ()> {
+        let mut decoded = Vec::with_capacity(string.len() * 2);
+        let mut code = 0u32;
+        let mut len = 0u8;
+
+        for &byte in string {
+            for shift in (0..8).rev() {
+                code = code << 1 | (byte >> shift & 1) as u32;
+                len += 1;
+
+                match self.huffman.get(&(len, code)) {
+                    Some(&HUFFMAN_EOS) => return Err(()),
+                    Some(&symbol) => {
+                        decoded.push(symbol as u8);
+                        code = 0;
+                        len = 0;
+                    }
+                    // no code is longer than EOS
+                    None if len >= 30 => return Err(()),
+                    None => (),
+                }
+            }
+        }
+
+        // the padding is shorter than an octet, and the start of EOS, RFC 7541 section 5.2
+        if len > 7 || code != (1 << len) - 1 {
+            return Err(());
+        }
+
+        Ok(decoded)
+    }
+}
+
+impl Default for HpackDecoder {
+    fn default() -> Self {
+        HpackDecoder::new()
+    }
+}
+
+/// Appends the field to the header block as a literal without indexing, with the name of the
+///  static table if it has it, RFC 7541 section 6.2.2. The dynamic table of the peer is not used.
+pub fn encode_field(block: &mut Vec<u8>, name: &str, value: &str) {
+    match STATIC_TABLE.iter().position(|&(static_name, _)| static_name == name) {
+        Some(index) => encode_integer(block, 0x00, 4, index + 1),
+        None => {
+            block.push(0x00);
+            encode_string(block, name);
+        }
+    }
+
+    encode_string(block, value);
+}
+
+/// Reads an integer with an N-bit prefix, RFC 7541 section 5.1
+fn read_integer(block: &[u8], pos: &mut usize, prefix: u8) -> Result<usize, ()> {
+    let mask = ((1u16 << prefix) - 1) as u8;
+    let first = try!(block.get(*pos).ok_or(()));
+    *pos += 1;
+
+    let mut value = (first & mask) as usize;
+    if value < mask as usize {
+        return Ok(value);
+    }
+
+    let mut shift = 0;
+    loop {
+        let byte = try!(block.get(*pos).ok_or(()));
+        *pos += 1;
+
+        // nothing in a header block is this large
+        if shift > 21 {
+            return Err(());
+        }
+
+        value += ((byte & 0x7F) as usize) << shift;
+        shift += 7;
+
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+}
+
+fn encode_integer(block: &mut Vec<u8>, flags: u8, prefix: u8, value: usize) {
+    let mask = ((1u16 << prefix) - 1) as usize;
+    if value < mask {
+        block.push(flags | value as u8);
+        return;
+    }
+
+    block.push(flags | mask as u8);
+    let mut value = value - mask;
+    while value >= 0x80 {
+        block.push((value & 0x7F) as u8 | 0x80);
+        value >>= 7;
+    }
+    block.push(value as u8);
+}
+
+/// Appends the string literal, without Huffman coding
+fn encode_string(block: &mut Vec<u8>, string: &str) {
+    encode_integer(block, 0x00, 7, string.len());
+    block.extend_from_slice(string.as_bytes());
+}
+
+fn to_string(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes).into_owned()
+}
@@ -0,0 +1,345 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::io;
+use std::net::SocketAddr;
+use std::str;
+
+use futures::{Async, Poll};
+use futures::stream::{Fuse, Stream};
+use futures::sync::mpsc::{unbounded, UnboundedReceiver};
+use rustc_serialize::base64::FromBase64;
+use tokio_core::io::Io;
+
+use trust_dns::BufStreamHandle;
+use trust_dns::op::Message;
+
+use server::DrainStream;
+use server::h2_connection::{H2Connection, PREFACE};
+
+/// The media type of DNS messages in requests and responses, RFC 8484 section 6
+pub const DNS_MESSAGE_MEDIA_TYPE: &'static str = "application/dns-message";
+
+/// Requests whose header is larger than this are answered 431 and the connection closed
+const MAX_HEADER_LEN: usize = 8192;
+
+/// A request read from the connection
+#[derive(Debug, PartialEq)]
+pub enum HttpRequest {
+    /// the DNS message of a GET or POST to the path
+    Dns(Vec<u8>),
+    /// the request is not answered by the catalog, the status of the response
+    Error(u16),
+}
+
+/// The server side of DNS over HTTPS, RFC 8484, over an HTTP/2 or an HTTP/1.1 connection
+///
+/// The DNS messages of the requests are returned by the stream, the responses sent to the
+///  `BufStreamHandle` are written back as the HTTP responses. Requests are answered one at a time,
+///  pipelined requests and those of concurrent HTTP/2 streams wait for the response to the one
+///  before. A connection which starts with the HTTP/2 preface speaks HTTP/2, with ALPN `h2` or
+///  with prior knowledge, see `H2Connection`, any other HTTP/1.1.
+#[must_use = "futures do nothing unless polled"]
+pub struct HttpsStream<S> {
+    socket: S,
+    src_addr: SocketAddr,
+    path: String,
+    outbound_messages: Fuse<UnboundedReceiver<(Vec<u8>, SocketAddr)>>,
+    /// the DNS message of the current request has been returned, its response is not yet sent
+    awaiting_response: bool,
+    /// the connection is closed once the current request is answered
+    close: bool,
+    /// the bytes to write, of the HTTP responses or the HTTP/2 frames
+    write_buf: Vec<u8>,
+    /// the bytes written are yet to be flushed
+    flush: bool,
+    /// the bytes read of the HTTP/1.1 requests which are not yet handled
+    read_buf: Vec<u8>,
+    /// the HTTP/2 connection, once the client has sent the preface
+    http2: Option<H2Connection>,
+}
+
+impl<S: Io> HttpsStream<S> {
+    /// Initializes a stream over an accepted connection, usually a TLS stream
+    ///
+    /// # Arguments
+    ///
+    /// * `socket` - the established IO stream for communication
+    /// * `src_addr` - address of the client
+    /// * `path` - the URI path of the DNS API, requests for any other path are answered 404
+    pub fn from_stream(socket: S, src_addr: SocketAddr, path: String) -> (Self, BufStreamHandle) {
+        let (message_sender, outbound_messages) = unbounded();
+
+        let stream = HttpsStream {
+            socket: socket,
+            src_addr: src_addr,
+            path: path,
+            outbound_messages: outbound_messages.fuse(),
+            awaiting_response: false,
+            close: false,
+            write_buf: Vec::new(),
+            flush: false,
+            read_buf: Vec::new(),
+            http2: None,
+        };
+
+        (stream, message_sender)
+    }
+}
+
+impl<S: Io> DrainStream for HttpsStream<S> {
+    fn is_idle(&self) -> bool {
+        !self.awaiting_response && self.write_buf.is_empty() && self.read_buf.is_empty() &&
+        self.http2.as_ref().map_or(true, |http2| http2.is_idle())
+    }
+}
+
+impl<S: Io> Stream for HttpsStream<S> {
+    type Item = (Vec<u8>, SocketAddr);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        loop {
+            // whatever is to be sent is written before anything else is done
+            if !self.write_buf.is_empty() {
+                let wrote = try_nb!(self.socket.write(&self.write_buf));
+                if wrote == 0 {
+                    return Err(io::Error::new(io::ErrorKind::WriteZero,
+                                              "closed while writing response"));
+                }
+
+                self.write_buf.drain(..wrote);
+                self.flush = true;
+                continue;
+            }
+            if self.flush {
+                try_nb!(self.socket.flush());
+                self.flush = false;
+            }
+
+            let closed = self.http2.as_ref().map_or(false, |http2| http2.is_closed());
+            if closed || (self.close && !self.awaiting_response) {
+                debug!("closing connection from: {}", self.src_addr);
+                return Ok(Async::Ready(None));
+            }
+
+            if self.awaiting_response {
+                match try!(self.outbound_messages
+                    .poll()
+                    .map_err(|()| io::Error::new(io::ErrorKind::Other, "unknown"))) {
+                    Async::Ready(Some((buffer, _))) => {
+                        self.awaiting_response = false;
+                        match self.http2 {
+                            Some(ref mut http2) => http2.respond(&buffer, &mut self.write_buf),
+                            None => {
+                                self.write_buf.extend_from_slice(&dns_response(&buffer,
+                                                                               self.close))
+                            }
+                        }
+                        continue;
+                    }
+                    Async::Ready(None) => return Ok(Async::Ready(None)),
+                    // HTTP/2 frames are still read, the client may need to open the window for
+                    //  the response
+                    Async::NotReady if self.http2.is_none() => return Ok(Async::NotReady),
+                    Async::NotReady => (),
+                }
+            } else if self.http2.is_some() {
+                if let Some(message) = self.http2.as_mut().and_then(|http2| http2.next_request()) {
+                    self.awaiting_response = true;
+                    return Ok(Async::Ready(Some((message, self.src_addr))));
+                }
+            } else if self.read_buf.starts_with(PREFACE) {
+                debug!("HTTP/2 connection from: {}", self.src_addr);
+                let mut http2 = H2Connection::new(self.path.clone(), &mut self.write_buf);
+                http2.receive(&self.read_buf, &mut self.write_buf);
+                self.read_buf.clear();
+                self.http2 = Some(http2);
+                continue;
+            } else if !PREFACE.starts_with(&self.read_buf) {
+                if let Some((len, request, close)) = parse_request(&self.read_buf, &self.path) {
+                    self.read_buf.drain(..len);
+                    self.close = close;
+
+                    match request {
+                        HttpRequest::Dns(message) => {
+                            self.awaiting_response = true;
+                            return Ok(Async::Ready(Some((message, self.src_addr))));
+                        }
+                        HttpRequest::Error(status) => {
+                            debug!("HTTP error: {} for request from: {}", status, self.src_addr);
+                            self.write_buf.extend_from_slice(&error_response(status, close));
+                        }
+                    }
+                    continue;
+                }
+            }
+
+            let mut buffer = [0u8; 4096];
+            let read = try_nb!(self.socket.read(&mut buffer));
+            if read == 0 {
+                debug!("zero bytes read, stream closed?");
+                return Ok(Async::Ready(None));
+            }
+
+            match self.http2 {
+                Some(ref mut http2) => http2.receive(&buffer[..read], &mut self.write_buf),
+                None => self.read_buf.extend_from_slice(&buffer[..read]),
+            }
+        }
+    }
+}
+
+/// Parses the first HTTP request in the buffer, returning its length, the request, and whether
+///  the connection is to be closed after the response, or None if it is not complete yet
+///
+/// A request which can not be read is answered with an error, and the connection closed.
+fn parse_request(buffer: &[u8], path: &str) -> Option<(usize, HttpRequest, bool)> {
+    let fatal = |status| Some((buffer.len(), HttpRequest::Error(status), true));
+
+    let header_len = match buffer.windows(4).position(|w| w == b"\r\n\r\n") {
+        Some(end) => end + 4,
+        None if buffer.len() > MAX_HEADER_LEN => return fatal(431),
+        None => return None,
+    };
+
+    let header = match str::from_utf8(&buffer[..header_len - 4]) {
+        Ok(header) => header,
+        Err(_) => return fatal(400),
+    };
+    let mut lines = header.split("\r\n");
+
+    let mut request_line = lines.next().unwrap_or("").split(' ');
+    let (method, target, version) =
+        match (request_line.next(), request_line.next(), request_line.next()) {
+            (Some(method), Some(target), Some(version)) if version.starts_with("HTTP/1.") => {
+                (method, target, version)
+            }
+            _ => return fatal(400),
+        };
+
+    let mut content_length = 0;
+    let mut content_type = String::new();
+    let mut close = version == "HTTP/1.0";
+    for line in lines {
+        let mut field = line.splitn(2, ':');
+        let name = field.next().unwrap_or("").trim().to_lowercase();
+        let value = field.next().unwrap_or("").trim().to_lowercase();
+
+        match &name as &str {
+            "content-length" => {
+                content_length = match value.parse() {
+                    Ok(content_length) => content_length,
+                    Err(_) => return fatal(400),
+                }
+            }
+            "content-type" => content_type = value,
+            "connection" => close = value == "close",
+            // the end of the body is not known
+            "transfer-encoding" if value != "identity" => return fatal(501),
+            _ => (),
+        }
+    }
+
+    if content_length > u16::max_value() as usize {
+        return fatal(413);
+    }
+    if buffer.len() < header_len + content_length {
+        return None;
+    }
+    let body = &buffer[header_len..header_len + content_length];
+
+    Some((header_len + content_length,
+          dns_request(method, target, &content_type, body, path),
+          close))
+}
+
+/// The request of the method and target, the DNS message of a GET or POST to the path
+pub fn dns_request(method: &str,
+                   target: &str,
+                   content_type: &str,
+                   body: &[u8],
+                   path: &str)
+                   -> HttpRequest {
+    let (target_path, query) = match target.find('?') {
+        Some(start) => (&target[..start], &target[start + 1..]),
+        None => (target, ""),
+    };
+
+    let request = if target_path != path {
+        HttpRequest::Error(404)
+    } else {
+        match method {
+            // base64url without padding, RFC 8484 section 6
+            "GET" => {
+                match query.split('&')
+                    .find(|param| param.starts_with("dns="))
+                    .and_then(|param| param[4..].from_base64().ok()) {
+                    Some(message) => HttpRequest::Dns(message),
+                    None => HttpRequest::Error(400),
+                }
+            }
+            "POST" if content_type.split(';').next().unwrap_or("").trim().to_lowercase() !=
+                      DNS_MESSAGE_MEDIA_TYPE => HttpRequest::Error(415),
+            "POST" => HttpRequest::Dns(body.to_vec()),
+            _ => HttpRequest::Error(405),
+        }
+    };
+
+    // anything shorter than a header is not a DNS message
+    match request {
+        HttpRequest::Dns(ref message) if message.len() < 12 => HttpRequest::Error(400),
+        request => request,
+    }
+}
+
+/// The lowest TTL of the answers of the DNS message, or if there are none of its authority
+///  section, the response is cacheable for as long, RFC 8484 section 5.1
+pub fn max_age(message: &[u8]) -> u32 {
+    Message::from_vec(message)
+        .ok()
+        .and_then(|message| {
+            if message.get_answers().is_empty() {
+                message.get_name_servers().iter().map(|record| record.get_ttl()).min()
+            } else {
+                message.get_answers().iter().map(|record| record.get_ttl()).min()
+            }
+        })
+        .unwrap_or(0)
+}
+
+/// The HTTP response for the DNS message, see `max_age`
+fn dns_response(message: &[u8], close: bool) -> Vec<u8> {
+    let mut response = format!("HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\n\
+                                Cache-Control: max-age={}\r\n{}\r\n",
+                               DNS_MESSAGE_MEDIA_TYPE,
+                               message.len(),
+                               max_age(message),
+                               if close { "Connection: close\r\n" } else { "" })
+        .into_bytes();
+    response.extend_from_slice(message);
+    response
+}
+
+fn error_response(status: u16, close: bool) -> Vec<u8> {
+    let reason = match status {
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        413 => "Payload Too Large",
+        415 => "Unsupported Media Type",
+        431 => "Request Header Fields Too Large",
+        501 => "Not Implemented",
+        _ => "Error",
+    };
+
+    format!("HTTP/1.1 {} {}\r\nContent-Length: 0\r\n{}\r\n",
+            status,
+            reason,
+            if close { "Connection: close\r\n" } else { "" })
+        .into_bytes()
+}
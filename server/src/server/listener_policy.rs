@@ -0,0 +1,73 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use trust_dns::op::{Message, OpCode};
+use trust_dns::rr::RecordType;
+
+/// The requests which are accepted on a socket or listener, others are answered with REFUSED
+///
+/// This allows e.g. a public UDP socket to refuse zone transfers and NOTIFY, while a TCP listener
+///  on an internal address accepts them:
+///
+/// ```
+/// use trust_dns_server::server::ListenerPolicy;
+///
+/// let public = ListenerPolicy {
+///     zone_transfer: false,
+///     notify: false,
+///     ..ListenerPolicy::default()
+/// };
+/// ```
+///
+/// The default accepts everything, leaving it to the `Catalog` and its authorities to decide.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ListenerPolicy {
+    /// queries for the ANY type
+    pub any: bool,
+    /// queries for the AXFR and IXFR types
+    pub zone_transfer: bool,
+    /// NOTIFY messages
+    pub notify: bool,
+    /// dynamic updates, including those forwarded to the primary of a slave zone
+    pub update: bool,
+    /// op codes which are not implemented, i.e. STATUS and unassigned ones, when false these are
+    ///  REFUSED rather than answered with NOTIMP, or passed to a handler registered with the
+    ///  `Catalog`
+    pub unsupported_op_codes: bool,
+}
+
+impl ListenerPolicy {
+    /// Returns true if the request may be handled, otherwise it is to be refused
+    pub fn is_allowed(&self, request: &Message) -> bool {
+        match request.get_op_code() {
+            OpCode::Query => {
+                request.get_queries().iter().all(|query| match query.get_query_type() {
+                    RecordType::ANY => self.any,
+                    RecordType::AXFR | RecordType::IXFR => self.zone_transfer,
+                    _ => true,
+                })
+            }
+            OpCode::Notify => self.notify,
+            OpCode::Update => self.update,
+            // only handled by the DSO session of a TCP or TLS connection, see `DsoConfig`
+            OpCode::Dso => true,
+            OpCode::Status | OpCode::Unknown(_) => self.unsupported_op_codes,
+        }
+    }
+}
+
+impl Default for ListenerPolicy {
+    fn default() -> Self {
+        ListenerPolicy {
+            any: true,
+            zone_transfer: true,
+            notify: true,
+            update: true,
+            unsupported_op_codes: true,
+        }
+    }
+}
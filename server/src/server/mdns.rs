@@ -0,0 +1,472 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Multicast DNS responder, [RFC 6762](https://tools.ietf.org/html/rfc6762)
+
+use std::net::{IpAddr, SocketAddr};
+
+pub use trust_dns::mdns::{bind_mdns_ipv4, mdns_group, MDNS_PORT};
+use trust_dns::op::{Message, MessageType, OpCode, Query};
+use trust_dns::rr::{DNSClass, Name, RData, Record, RecordType};
+use trust_dns::rr::rdata::{SRV, TXT};
+
+/// milliseconds between the calls to `MdnsResponder::next_message()`
+pub const MDNS_PROBE_INTERVAL: u64 = 250;
+
+/// ttl of the records which contain the host name, RFC 6762 section 10
+const HOST_TTL: u32 = 120;
+/// ttl of the other records
+const SERVICE_TTL: u32 = 4500;
+/// responses to legacy unicast queries, which are not cached as carefully
+const LEGACY_TTL: u32 = 10;
+
+/// probes sent for the names, each after `MDNS_PROBE_INTERVAL`
+const PROBE_COUNT: u32 = 3;
+/// intervals between the two announcements, i.e. one second
+const ANNOUNCE_INTERVALS: u32 = 4;
+
+/// A service announced by the `MdnsResponder`, as in DNS-Based Service Discovery, RFC 6763
+#[derive(Clone, Debug, PartialEq)]
+pub struct MdnsService {
+    /// user visible name of the instance, e.g. `Office Printer`
+    pub instance: String,
+    /// service type and protocol, e.g. `_ipp._tcp`
+    pub service_type: String,
+    /// port on which the service listens
+    pub port: u16,
+    /// key/value pairs of the TXT record, e.g. `rp=printers/office`
+    pub txt: Vec<String>,
+}
+
+/// The host name and services claimed by an `MdnsResponder`
+#[derive(Clone, Debug, PartialEq)]
+pub struct MdnsConfig {
+    /// host name, without the `local.` domain
+    pub hostname: String,
+    /// addresses of the host name
+    pub addresses: Vec<IpAddr>,
+    /// services on the host
+    pub services: Vec<MdnsService>,
+}
+
+/// A name claimed on the link, renamed on conflicts
+struct Claim {
+    label: String,
+    domain: Name,
+    is_host: bool,
+    renames: u32,
+}
+
+impl Claim {
+    fn get_name(&self) -> Name {
+        let label = match (self.renames, self.is_host) {
+            (0, _) => self.label.clone(),
+            (renames, true) => format!("{}-{}", self.label, renames + 1),
+            (renames, false) => format!("{} ({})", self.label, renames + 1),
+        };
+
+        let mut name = Name::with_labels(vec![label]);
+        name.append(&self.domain);
+        name
+    }
+}
+
+/// Responds to mDNS queries for the host name and services of the configuration
+///
+/// The responder does no IO, see `ServerFuture::register_mdns_socket()`. Every
+///  `MDNS_PROBE_INTERVAL` its `next_message()` is multicast: three probes for the unique names,
+///  followed by two announcements of all records. Queries are answered once the names are
+///  claimed. A name which another host has claimed is renamed, `host-2` or `Instance (2)`, and
+///  probed again. When done, the `goodbye()` removes the records from the caches on the link.
+pub struct MdnsResponder {
+    config: MdnsConfig,
+    /// the host name, and the instance name of each service
+    claims: Vec<Claim>,
+    records: Vec<Record>,
+    /// intervals since probing started
+    ticks: u32,
+    /// intervals to wait before probing again, after losing a tie-break
+    deferred: u32,
+}
+
+impl MdnsResponder {
+    /// Returns a responder, which has to probe its names before answering queries
+    pub fn new(config: MdnsConfig) -> Self {
+        let local = Name::parse("local.", None).expect("local. is a valid name");
+
+        let mut claims = vec![Claim {
+                                  label: config.hostname.clone(),
+                                  domain: local.clone(),
+                                  is_host: true,
+                                  renames: 0,
+                              }];
+        for service in &config.services {
+            claims.push(Claim {
+                label: service.instance.clone(),
+                domain: Self::service_type_name(service, &local),
+                is_host: false,
+                renames: 0,
+            });
+        }
+
+        let mut responder = MdnsResponder {
+            config: config,
+            claims: claims,
+            records: Vec::new(),
+            ticks: 0,
+            deferred: 0,
+        };
+        responder.build_records();
+        responder
+    }
+
+    /// Returns the host name currently claimed, including the `local.` domain
+    pub fn get_hostname(&self) -> Name {
+        self.claims[0].get_name()
+    }
+
+    /// Returns all records of the host name and services, with the cache-flush bit set on the
+    ///  unique ones
+    pub fn get_records(&self) -> &[Record] {
+        &self.records
+    }
+
+    /// Returns true once probing has completed, and the names are announced
+    pub fn is_announced(&self) -> bool {
+        self.ticks > PROBE_COUNT
+    }
+
+    /// Returns the probe or announcement to multicast, to be called every
+    ///  `MDNS_PROBE_INTERVAL`
+    pub fn next_message(&mut self) -> Option<Message> {
+        if self.deferred > 0 {
+            self.deferred -= 1;
+            return None;
+        }
+
+        let tick = self.ticks;
+        self.ticks = self.ticks.saturating_add(1);
+
+        if tick < PROBE_COUNT {
+            Some(self.probe(tick == 0))
+        } else if tick == PROBE_COUNT || tick == PROBE_COUNT + ANNOUNCE_INTERVALS {
+            Some(self.announcement(self.records.iter().cloned()))
+        } else {
+            None
+        }
+    }
+
+    /// Handles an mDNS message received from `src`, returns a response and its destination
+    ///
+    /// Responses of other hosts are checked for conflicts with the names, as are their probes
+    ///  while this responder is probing. Queries are answered by multicast, or unicast to `src`
+    ///  if all questions request it, or if `src` is not the mDNS port (a legacy unicast query).
+    pub fn handle_message(&mut self,
+                          message: &Message,
+                          src: SocketAddr)
+                          -> Option<(Message, SocketAddr)> {
+        if message.get_op_code() != OpCode::Query {
+            return None;
+        }
+
+        match message.get_message_type() {
+            MessageType::Response => {
+                self.check_conflicts(message);
+                None
+            }
+            MessageType::Query => {
+                if !self.is_announced() {
+                    self.check_probe(message);
+                    return None;
+                }
+
+                self.answer(message, src)
+            }
+        }
+    }
+
+    /// Returns the announcement of all records with a ttl of 0, which are then removed from the
+    ///  caches, None if nothing was announced yet
+    pub fn goodbye(&self) -> Option<Message> {
+        if !self.is_announced() {
+            return None;
+        }
+
+        Some(self.announcement(self.records.iter().map(|record| {
+            let mut record = record.clone();
+            record.ttl(0);
+            record
+        })))
+    }
+
+    fn service_type_name(service: &MdnsService, local: &Name) -> Name {
+        let mut name = Name::with_labels(service.service_type
+            .split('.')
+            .filter(|label| !label.is_empty())
+            .map(|label| label.to_string())
+            .collect());
+        name.append(local);
+        name
+    }
+
+    fn build_records(&mut self) {
+        let local = &self.claims[0].domain;
+        let hostname = self.claims[0].get_name();
+        let services_name = Name::parse("_services._dns-sd._udp", Some(local))
+            .expect("_services._dns-sd._udp. is a valid name");
+
+        let mut records: Vec<Record> = Vec::new();
+        for address in &self.config.addresses {
+            match *address {
+                IpAddr::V4(address) => {
+                    records.push(Self::record(hostname.clone(), HOST_TTL, RData::A(address), true))
+                }
+                IpAddr::V6(address) => {
+                    records.push(Self::record(hostname.clone(),
+                                              HOST_TTL,
+                                              RData::AAAA(address),
+                                              true))
+                }
+            }
+        }
+
+        for (service, claim) in self.config.services.iter().zip(&self.claims[1..]) {
+            let instance = claim.get_name();
+            let txt = if service.txt.is_empty() {
+                vec![String::new()]
+            } else {
+                service.txt.clone()
+            };
+
+            let enumeration = Self::record(services_name.clone(),
+                                           SERVICE_TTL,
+                                           RData::PTR(claim.domain.clone()),
+                                           false);
+            if !records.contains(&enumeration) {
+                records.push(enumeration);
+            }
+            records.push(Self::record(claim.domain.clone(),
+                                      SERVICE_TTL,
+                                      RData::PTR(instance.clone()),
+                                      false));
+            records.push(Self::record(instance.clone(),
+                                      HOST_TTL,
+                                      RData::SRV(SRV::new(0, 0, service.port, hostname.clone())),
+                                      true));
+            records.push(Self::record(instance, SERVICE_TTL, RData::TXT(TXT::new(txt)), true));
+        }
+
+        self.records = records;
+    }
+
+    fn record(name: Name, ttl: u32, rdata: RData, unique: bool) -> Record {
+        let rr_type = match rdata {
+            RData::A(..) => RecordType::A,
+            RData::AAAA(..) => RecordType::AAAA,
+            RData::PTR(..) => RecordType::PTR,
+            RData::SRV(..) => RecordType::SRV,
+            _ => RecordType::TXT,
+        };
+
+        let mut record = Record::from_rdata(name, ttl, rr_type, rdata);
+        record.mdns_cache_flush(unique);
+        record
+    }
+
+    /// names of the unique records, which need to be probed
+    fn unique_names(&self) -> Vec<Name> {
+        let mut names: Vec<Name> = Vec::new();
+        for record in self.records.iter().filter(|r| r.is_mdns_cache_flush()) {
+            if !names.contains(record.get_name()) {
+                names.push(record.get_name().clone());
+            }
+        }
+
+        names
+    }
+
+    fn probe(&self, unicast_response: bool) -> Message {
+        let mut message = Message::new();
+        message.id(0).message_type(MessageType::Query).op_code(OpCode::Query);
+
+        for name in self.unique_names() {
+            let mut query = Query::new();
+            query.name(name)
+                .query_type(RecordType::ANY)
+                .mdns_unicast_response(unicast_response);
+            message.add_query(query);
+        }
+
+        // the records proposed for the names, RFC 6762 section 8.2
+        message.add_name_servers(self.records.iter().filter(|r| r.is_mdns_cache_flush()).cloned());
+        message
+    }
+
+    fn announcement<I: Iterator<Item = Record>>(&self, records: I) -> Message {
+        let mut message = Message::new();
+        message.id(0)
+            .message_type(MessageType::Response)
+            .op_code(OpCode::Query)
+            .authoritative(true)
+            .add_answers(records);
+        message
+    }
+
+    /// renames the names for which another host responded with other records
+    fn check_conflicts(&mut self, message: &Message) {
+        let probing = !self.is_announced();
+        let conflicts: Vec<Name> = message.get_answers()
+            .iter()
+            .chain(message.get_additionals())
+            .filter(|record| record.get_ttl() != 0 && !self.records.contains(record))
+            .filter(|record| {
+                // while probing any record of the name is a conflict, RFC 6762 section 8.1
+                self.records.iter().any(|r| {
+                    r.is_mdns_cache_flush() && r.get_name() == record.get_name() &&
+                    (probing || r.get_rr_type() == record.get_rr_type())
+                })
+            })
+            .map(|record| record.get_name().clone())
+            .collect();
+
+        for name in conflicts {
+            self.rename(&name);
+        }
+    }
+
+    /// [RFC 6762](https://tools.ietf.org/html/rfc6762#section-8.2), Multicast DNS, February 2013
+    ///
+    /// ```text
+    /// 8.2.  Simultaneous Probe Tiebreaking
+    ///
+    ///    ... the two records are compared and the lexicographically later data wins.
+    ///
+    ///    ... If the host finds that its own data is lexicographically earlier,
+    ///    then it defers to the winning host by waiting one second, and then
+    ///    begins probing for this record again.
+    /// ```
+    fn check_probe(&mut self, message: &Message) {
+        for name in self.unique_names() {
+            let mut theirs: Vec<(u16, RecordType, RData)> = message.get_name_servers()
+                .iter()
+                .filter(|r| *r.get_name() == name)
+                .map(|r| (u16::from(r.get_dns_class()), r.get_rr_type(), r.get_rdata().clone()))
+                .collect();
+            if theirs.is_empty() {
+                continue;
+            }
+
+            let mut ours: Vec<(u16, RecordType, RData)> = self.records
+                .iter()
+                .filter(|r| r.is_mdns_cache_flush() && *r.get_name() == name)
+                .map(|r| (u16::from(r.get_dns_class()), r.get_rr_type(), r.get_rdata().clone()))
+                .collect();
+
+            theirs.sort();
+            ours.sort();
+            if ours < theirs {
+                info!("deferring to the simultaneous probe for: {}", name);
+                self.ticks = 0;
+                self.deferred = ANNOUNCE_INTERVALS;
+                return;
+            }
+        }
+    }
+
+    fn rename(&mut self, name: &Name) {
+        if let Some(claim) = self.claims.iter_mut().find(|c| c.get_name() == *name) {
+            claim.renames += 1;
+            warn!("mDNS name: {} is claimed by another host, probing: {}",
+                  name,
+                  claim.get_name());
+        } else {
+            return;
+        }
+
+        self.build_records();
+        self.ticks = 0;
+    }
+
+    fn answer(&self, message: &Message, src: SocketAddr) -> Option<(Message, SocketAddr)> {
+        let mut answers: Vec<Record> = Vec::new();
+        let mut unicast_response = true;
+        for query in message.get_queries() {
+            unicast_response &= query.is_mdns_unicast_response();
+
+            for record in self.records.iter().filter(|r| Self::matches(query, r)) {
+                // known-answer suppression, RFC 6762 section 7.1
+                if message.get_answers()
+                    .iter()
+                    .any(|known| known == record && known.get_ttl() >= record.get_ttl() / 2) {
+                    continue;
+                }
+
+                if !answers.contains(record) {
+                    answers.push(record.clone());
+                }
+            }
+        }
+
+        if answers.is_empty() {
+            return None;
+        }
+
+        // the records a browser looks up next, RFC 6763 section 12
+        let mut additionals: Vec<Record> = Vec::new();
+        for answer in &answers {
+            let (target, types): (&Name, &[RecordType]) = match *answer.get_rdata() {
+                RData::PTR(ref target) => (target, &[RecordType::SRV, RecordType::TXT][..]),
+                RData::SRV(ref srv) => (srv.get_target(), &[RecordType::A, RecordType::AAAA][..]),
+                _ => continue,
+            };
+
+            for record in self.records.iter().filter(|r| {
+                r.get_name() == target && types.contains(&r.get_rr_type())
+            }) {
+                if !answers.contains(record) && !additionals.contains(record) {
+                    additionals.push(record.clone());
+                }
+            }
+        }
+
+        let mut response = Message::new();
+        response.message_type(MessageType::Response)
+            .op_code(OpCode::Query)
+            .authoritative(true);
+
+        // RFC 6762 section 6.7, a legacy resolver expects a conventional response
+        if src.port() != MDNS_PORT {
+            let legacy = |mut record: Record| {
+                let ttl = record.get_ttl();
+                record.ttl(ttl.min(LEGACY_TTL)).mdns_cache_flush(false);
+                record
+            };
+
+            response.id(message.get_id())
+                .add_queries(message.get_queries().iter().cloned())
+                .add_answers(answers.into_iter().map(&legacy));
+            response.insert_additionals(additionals.into_iter().map(&legacy).collect());
+            return Some((response, src));
+        }
+
+        response.id(0).add_answers(answers);
+        response.insert_additionals(additionals);
+        if unicast_response {
+            Some((response, src))
+        } else {
+            Some((response, mdns_group(&src)))
+        }
+    }
+
+    fn matches(query: &Query, record: &Record) -> bool {
+        record.get_name() == query.get_name() &&
+        (query.get_query_type() == RecordType::ANY ||
+         query.get_query_type() == record.get_rr_type()) &&
+        (query.get_query_class() == DNSClass::ANY ||
+         query.get_query_class() == record.get_dns_class())
+    }
+}
@@ -0,0 +1,124 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::net::SocketAddr;
+use std::rc::Rc;
+
+use trust_dns::op::{Message, MessageType, RequestHandler};
+
+/// A layer around the `RequestHandler` of the server, e.g. for logging, rate limiting, access
+///  control, metrics or rewriting responses, see `MiddlewareChain`
+pub trait Middleware {
+    /// Returns the response to the request
+    ///
+    /// The layer passes the request on with `next.handle_request()`, to the next layer or finally
+    ///  to the handler, and may change the response it returns. Or it short-circuits, answering
+    ///  without calling `next`, e.g. with `ResponseCode::Refused`.
+    ///
+    /// The requests which the server answers after the chain returns, asynchronously or in
+    ///  several messages, e.g. zone transfers or forwarded queries, pass through the layers as
+    ///  well, but the response they see is only a placeholder, see `is_deferred`.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - the request received
+    /// * `src` - the address of the client
+    /// * `next` - the rest of the chain
+    fn handle_request(&self, request: &Message, src: SocketAddr, next: Next) -> Message;
+}
+
+/// The layers after the current one and the handler they end in
+#[derive(Clone, Copy)]
+pub struct Next<'a> {
+    layers: &'a [Rc<Middleware>],
+    handler: &'a RequestHandler,
+}
+
+impl<'a> Next<'a> {
+    /// Passes the request on to the next layer, or to the handler after the last one
+    pub fn handle_request(self, request: &Message, src: SocketAddr) -> Message {
+        match self.layers.split_first() {
+            Some((layer, layers)) => {
+                layer.handle_request(request,
+                                     src,
+                                     Next {
+                                         layers: layers,
+                                         handler: self.handler,
+                                     })
+            }
+            None => self.handler.handle_request(request),
+        }
+    }
+}
+
+/// Returns the placeholder response to a request which is answered after the chain returns
+pub fn deferred_response(request: &Message) -> Message {
+    let mut response = Message::new();
+    response.id(request.get_id())
+        .message_type(MessageType::Query)
+        .op_code(request.get_op_code());
+    response
+}
+
+/// Returns true if the response is only the placeholder of a request which the server answers
+///  after the chain returns, see `Middleware::handle_request`
+///
+/// Such a response is not sent, a layer short-circuiting the request still answers it instead.
+pub fn is_deferred(response: &Message) -> bool {
+    response.get_message_type() == MessageType::Query
+}
+
+/// The layers which the requests pass through, in order, before the `RequestHandler`, and the
+///  responses after it, in reverse order
+///
+/// The chain of the `ServerFuture` sees every request before the server dispatches it, see
+///  `ServerFuture::set_middleware`.
+#[derive(Clone, Default)]
+pub struct MiddlewareChain {
+    layers: Vec<Rc<Middleware>>,
+}
+
+impl MiddlewareChain {
+    /// A chain without any layers, which passes the requests straight to the handler
+    pub fn new() -> Self {
+        MiddlewareChain { layers: Vec::new() }
+    }
+
+    /// Adds the layer inside those already added, the first one added sees the requests first
+    pub fn push(&mut self, layer: Rc<Middleware>) {
+        self.layers.push(layer);
+    }
+
+    /// The number of layers
+    pub fn len(&self) -> usize {
+        self.layers.len()
+    }
+
+    /// Returns true if there are no layers
+    pub fn is_empty(&self) -> bool {
+        self.layers.is_empty()
+    }
+
+    /// Passes the request through the layers to the handler, returning the response
+    ///
+    /// # Arguments
+    ///
+    /// * `handler` - answers the requests which every layer passes on
+    /// * `request` - the request received
+    /// * `src` - the address of the client
+    pub fn handle_request(&self,
+                          handler: &RequestHandler,
+                          request: &Message,
+                          src: SocketAddr)
+                          -> Message {
+        Next {
+                layers: &self.layers,
+                handler: handler,
+            }
+            .handle_request(request, src)
+    }
+}
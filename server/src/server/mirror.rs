@@ -0,0 +1,405 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::cell::Cell;
+use std::io::{self, Write};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender, TrySendError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use rand;
+
+use trust_dns::op::{Message, OpCode};
+
+use server::{is_deferred, Middleware, Next};
+
+/// Where a `MirrorMiddleware` sends the sampled queries
+pub trait MirrorSink {
+    /// Receives a sampled query, with the response to it if responses are mirrored
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - the query received
+    /// * `response` - the response of the server to the query
+    /// * `src` - the address of the client
+    fn mirror(&self, request: &Message, response: Option<&Message>, src: SocketAddr);
+}
+
+/// Mirrors a sample of the queries, and optionally the responses, to a `MirrorSink`, for passive
+///  analysis or for canary testing of a new version of the server
+///
+/// Only queries are mirrored, not e.g. updates, so that a canary does not apply them twice. The
+///  query is answered as usual, it is mirrored once the response is known.
+pub struct MirrorMiddleware {
+    sink: Box<MirrorSink>,
+    sample_rate: f64,
+    mirror_responses: bool,
+}
+
+impl MirrorMiddleware {
+    /// Mirrors the queries to the sink, without the responses
+    ///
+    /// # Arguments
+    ///
+    /// * `sink` - where the sampled queries are sent
+    /// * `sample_rate` - the fraction of the queries which are mirrored, from 0.0 for none to 1.0
+    ///                   for all
+    pub fn new(sink: Box<MirrorSink>, sample_rate: f64) -> Self {
+        MirrorMiddleware {
+            sink: sink,
+            sample_rate: sample_rate.max(0.0).min(1.0),
+            mirror_responses: false,
+        }
+    }
+
+    /// The fraction of the queries which are mirrored
+    pub fn get_sample_rate(&self) -> f64 {
+        self.sample_rate
+    }
+
+    /// Mirrors the responses to the sampled queries as well, except those the server answers
+    ///  after the chain, e.g. of forwarded queries, see `is_deferred`
+    pub fn set_mirror_responses(&mut self, mirror_responses: bool) {
+        self.mirror_responses = mirror_responses;
+    }
+
+    /// Returns true if the responses to the sampled queries are mirrored
+    pub fn is_mirroring_responses(&self) -> bool {
+        self.mirror_responses
+    }
+
+    fn is_sampled(&self) -> bool {
+        self.sample_rate >= 1.0 ||
+        (self.sample_rate > 0.0 && rand::random::<f64>() < self.sample_rate)
+    }
+}
+
+impl Middleware for MirrorMiddleware {
+    fn handle_request(&self, request: &Message, src: SocketAddr, next: Next) -> Message {
+        let response = next.handle_request(request, src);
+        if request.get_op_code() == OpCode::Query && self.is_sampled() {
+            self.sink.mirror(request,
+                             if self.mirror_responses && !is_deferred(&response) {
+                                 Some(&response)
+                             } else {
+                                 None
+                             },
+                             src);
+        }
+
+        response
+    }
+}
+
+/// Sends the sampled queries to another DNS server over UDP, e.g. a canary, whose responses are
+///  not read
+pub struct UdpMirrorSink {
+    socket: UdpSocket,
+    target: SocketAddr,
+}
+
+impl UdpMirrorSink {
+    /// Returns a sink to the server, from an ephemeral port of the unspecified address
+    pub fn new(target: SocketAddr) -> io::Result<Self> {
+        let bind_addr = match target {
+            SocketAddr::V4(_) => SocketAddr::new(IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)), 0),
+            SocketAddr::V6(_) => {
+                SocketAddr::new(IpAddr::V6(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 0)), 0)
+            }
+        };
+        let socket = try!(UdpSocket::bind(bind_addr));
+        // a slow target must not hold up the answers
+        try!(socket.set_nonblocking(true));
+
+        Ok(UdpMirrorSink {
+            socket: socket,
+            target: target,
+        })
+    }
+
+    /// The server the queries are sent to
+    pub fn get_target(&self) -> SocketAddr {
+        self.target
+    }
+}
+
+impl MirrorSink for UdpMirrorSink {
+    fn mirror(&self, request: &Message, _: Option<&Message>, _: SocketAddr) {
+        match request.to_vec() {
+            Ok(buffer) => {
+                if let Err(e) = self.socket.send_to(&buffer, self.target) {
+                    debug!("could not mirror query to: {}: {}", self.target, e);
+                }
+            }
+            Err(e) => debug!("could not encode mirrored query: {}", e),
+        }
+    }
+}
+
+/// A sampled query, and the response to it, as sent by a `ChannelMirrorSink`
+///
+/// The messages are in the wire format, which, unlike a `Message`, can be sent to other threads,
+///  `Message::from_vec` decodes them.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MirroredExchange {
+    src: SocketAddr,
+    request: Vec<u8>,
+    response: Option<Vec<u8>>,
+}
+
+impl MirroredExchange {
+    /// The address of the client
+    pub fn get_src(&self) -> SocketAddr {
+        self.src
+    }
+
+    /// The query received
+    pub fn get_request(&self) -> &[u8] {
+        &self.request
+    }
+
+    /// The response of the server, if responses are mirrored
+    pub fn get_response(&self) -> Option<&[u8]> {
+        self.response.as_ref().map(|response| &response[..])
+    }
+}
+
+/// Sends the sampled queries over a bounded channel, e.g. to a thread which analyses them
+///
+/// The queries are not sent while the channel is full, rather than holding up the answers or
+///  queueing without bound behind a slow receiver, they are counted as dropped instead.
+pub struct ChannelMirrorSink {
+    sender: SyncSender<MirroredExchange>,
+    dropped: Cell<u64>,
+}
+
+impl ChannelMirrorSink {
+    /// Returns a sink to the channel, of `sync_channel`, the queries are dropped once the
+    ///  receiver is
+    pub fn new(sender: SyncSender<MirroredExchange>) -> Self {
+        ChannelMirrorSink {
+            sender: sender,
+            dropped: Cell::new(0),
+        }
+    }
+
+    /// The number of queries dropped since the channel was full
+    pub fn get_dropped(&self) -> u64 {
+        self.dropped.get()
+    }
+}
+
+impl MirrorSink for ChannelMirrorSink {
+    fn mirror(&self, request: &Message, response: Option<&Message>, src: SocketAddr) {
+        let request = match request.to_vec() {
+            Ok(request) => request,
+            Err(e) => {
+                debug!("could not encode mirrored query: {}", e);
+                return;
+            }
+        };
+        let response = response.and_then(|response| response.to_vec().ok());
+
+        // the receiver may have stopped listening, which is not an error of the server
+        let exchange = MirroredExchange {
+            src: src,
+            request: request,
+            response: response,
+        };
+        if let Err(TrySendError::Full(_)) = self.sender.try_send(exchange) {
+            self.dropped.set(self.dropped.get() + 1);
+        }
+    }
+}
+
+/// The content type of the Frame Streams of dnstap
+const DNSTAP_CONTENT_TYPE: &'static str = "protobuf:dnstap.Dnstap";
+
+/// The dnstap messages queued for the writer thread of a `DnstapSink`, beyond which they are
+///  dropped
+pub const DNSTAP_QUEUE_LEN: usize = 4096;
+
+/// Writes the sampled queries, and responses, as dnstap messages, http://dnstap.info, to a Frame
+///  Streams writer, e.g. the `UnixStream` of a dnstap collector
+///
+/// The messages are of the `AUTH_QUERY` and `AUTH_RESPONSE` types, with the address of the
+///  client and the times at which they were mirrored. The stream is started when the sink is
+///  created, and stopped by `finish`.
+///
+/// The messages are written by a thread of the sink, so that a slow collector does not hold up
+///  the answers; up to `DNSTAP_QUEUE_LEN` are queued for it, further ones are dropped.
+pub struct DnstapSink<W: Write + Send + 'static> {
+    sender: SyncSender<Vec<u8>>,
+    writer: JoinHandle<io::Result<W>>,
+    identity: Vec<u8>,
+    dropped: Cell<u64>,
+}
+
+impl<W: Write + Send + 'static> DnstapSink<W> {
+    /// Starts the Frame Stream on the writer, and the thread writing to it
+    ///
+    /// # Arguments
+    ///
+    /// * `writer` - the connection to the collector
+    /// * `identity` - the name of the server in the messages, e.g. the hostname
+    pub fn new(mut writer: W, identity: &str) -> io::Result<Self> {
+        // the start control frame, with the content type
+        let mut start: Vec<u8> = Vec::new();
+        start.extend(&u32_be(FSTRM_CONTROL_START));
+        start.extend(&u32_be(FSTRM_CONTROL_FIELD_CONTENT_TYPE));
+        start.extend(&u32_be(DNSTAP_CONTENT_TYPE.len() as u32));
+        start.extend(DNSTAP_CONTENT_TYPE.as_bytes());
+        try!(write_control_frame(&mut writer, &start));
+        try!(writer.flush());
+
+        let (sender, receiver) = sync_channel(DNSTAP_QUEUE_LEN);
+        let writer = try!(thread::Builder::new()
+            .name("dnstap writer".to_string())
+            .spawn(move || write_frames(writer, receiver)));
+
+        Ok(DnstapSink {
+            sender: sender,
+            writer: writer,
+            identity: identity.as_bytes().to_vec(),
+            dropped: Cell::new(0),
+        })
+    }
+
+    /// The number of messages dropped since the queue was full, or the writer failed
+    pub fn get_dropped(&self) -> u64 {
+        self.dropped.get()
+    }
+
+    /// Stops the Frame Stream, once the queued messages are written, returning the writer
+    pub fn finish(self) -> io::Result<W> {
+        let DnstapSink { sender, writer, .. } = self;
+        drop(sender);
+        match writer.join() {
+            Ok(result) => result,
+            Err(_) => Err(io::Error::new(io::ErrorKind::Other, "dnstap writer panicked")),
+        }
+    }
+
+    /// The dnstap message of the query or response, as a data frame
+    fn write_message(&self, message_type: u64, message: &Message, src: SocketAddr) {
+        let buffer = match message.to_vec() {
+            Ok(buffer) => buffer,
+            Err(e) => {
+                debug!("could not encode message for dnstap: {}", e);
+                return;
+            }
+        };
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or(Duration::from_secs(0));
+        let is_query = message_type == DNSTAP_AUTH_QUERY;
+
+        // dnstap.Message
+        let mut inner: Vec<u8> = Vec::new();
+        protobuf_varint_field(&mut inner, 1, message_type);
+        let (family, address) = match src.ip() {
+            IpAddr::V4(ip) => (1, ip.octets().to_vec()),
+            IpAddr::V6(ip) => (2, ip.octets().to_vec()),
+        };
+        protobuf_varint_field(&mut inner, 2, family);
+        protobuf_bytes_field(&mut inner, 4, &address);
+        protobuf_varint_field(&mut inner, 6, src.port() as u64);
+        let (time_sec, time_nsec, message_field) = if is_query { (8, 9, 10) } else { (12, 13, 14) };
+        protobuf_varint_field(&mut inner, time_sec, now.as_secs());
+        protobuf_fixed32_field(&mut inner, time_nsec, now.subsec_nanos());
+        protobuf_bytes_field(&mut inner, message_field, &buffer);
+
+        // dnstap.Dnstap, of the MESSAGE type
+        let mut frame: Vec<u8> = Vec::new();
+        protobuf_bytes_field(&mut frame, 1, &self.identity);
+        protobuf_bytes_field(&mut frame, 14, &inner);
+        protobuf_varint_field(&mut frame, 15, 1);
+
+        if self.sender.try_send(frame).is_err() {
+            self.dropped.set(self.dropped.get() + 1);
+        }
+    }
+}
+
+impl<W: Write + Send + 'static> MirrorSink for DnstapSink<W> {
+    fn mirror(&self, request: &Message, response: Option<&Message>, src: SocketAddr) {
+        self.write_message(DNSTAP_AUTH_QUERY, request, src);
+        if let Some(response) = response {
+            self.write_message(DNSTAP_AUTH_RESPONSE, response, src);
+        }
+    }
+}
+
+/// The Frame Streams control frame types and fields
+const FSTRM_CONTROL_START: u32 = 0x02;
+const FSTRM_CONTROL_STOP: u32 = 0x03;
+const FSTRM_CONTROL_FIELD_CONTENT_TYPE: u32 = 0x01;
+
+/// The dnstap.Message types of an authoritative server
+const DNSTAP_AUTH_QUERY: u64 = 1;
+const DNSTAP_AUTH_RESPONSE: u64 = 2;
+
+/// Writes the data frames until the sender is dropped, then stops the stream; the writer is
+///  flushed whenever the queue is drained, so that a burst is written at once
+fn write_frames<W: Write>(mut writer: W, frames: Receiver<Vec<u8>>) -> io::Result<W> {
+    while let Ok(frame) = frames.recv() {
+        let mut result = write_data_frame(&mut writer, &frame);
+        while result.is_ok() {
+            match frames.try_recv() {
+                Ok(frame) => result = write_data_frame(&mut writer, &frame),
+                Err(_) => break,
+            }
+        }
+        if let Err(e) = result.and_then(|_| writer.flush()) {
+            warn!("could not write dnstap messages, no longer writing: {}", e);
+            return Err(e);
+        }
+    }
+
+    try!(write_control_frame(&mut writer, &u32_be(FSTRM_CONTROL_STOP)));
+    try!(writer.flush());
+    Ok(writer)
+}
+
+fn write_data_frame<W: Write>(writer: &mut W, frame: &[u8]) -> io::Result<()> {
+    try!(writer.write_all(&u32_be(frame.len() as u32)));
+    writer.write_all(frame)
+}
+
+/// A control frame is escaped by a zero length, then has its own length
+fn write_control_frame<W: Write>(writer: &mut W, control: &[u8]) -> io::Result<()> {
+    try!(writer.write_all(&u32_be(0)));
+    try!(writer.write_all(&u32_be(control.len() as u32)));
+    writer.write_all(control)
+}
+
+fn u32_be(value: u32) -> [u8; 4] {
+    [(value >> 24) as u8, (value >> 16) as u8, (value >> 8) as u8, value as u8]
+}
+
+fn protobuf_varint(buffer: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buffer.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    buffer.push(value as u8);
+}
+
+fn protobuf_varint_field(buffer: &mut Vec<u8>, field: u64, value: u64) {
+    protobuf_varint(buffer, field << 3);
+    protobuf_varint(buffer, value);
+}
+
+fn protobuf_fixed32_field(buffer: &mut Vec<u8>, field: u64, value: u32) {
+    protobuf_varint(buffer, (field << 3) | 5);
+    buffer.extend(&[value as u8, (value >> 8) as u8, (value >> 16) as u8, (value >> 24) as u8]);
+}
+
+fn protobuf_bytes_field(buffer: &mut Vec<u8>, field: u64, value: &[u8]) {
+    protobuf_varint(buffer, (field << 3) | 2);
+    protobuf_varint(buffer, value.len() as u64);
+    buffer.extend(value);
+}
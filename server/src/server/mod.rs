@@ -16,12 +16,45 @@
 
 //! `Server` component for hosting a domain name servers operations.
 
+mod dns_push;
+mod dso;
+mod h2_connection;
+mod hpack;
+mod https_stream;
+mod listener_policy;
+mod mdns;
+mod middleware;
+mod mirror;
+mod query_forwarder;
 mod request_stream;
 mod server_future;
+mod shutdown;
+mod socket_activation;
+mod tcp_guard_stream;
 mod timeout_stream;
+mod update_forwarder;
+mod zone_transfer;
 
+pub use self::dns_push::DnsPush;
+pub use self::dso::{DsoConfig, DsoHandler, DsoSession};
+pub use self::h2_connection::H2Connection;
+pub use self::hpack::{HpackDecoder, HpackError, HEADER_TABLE_SIZE, MAX_HEADER_LIST_SIZE};
+pub use self::https_stream::HttpsStream;
+pub use self::listener_policy::ListenerPolicy;
+pub use self::mdns::{bind_mdns_ipv4, mdns_group, MdnsConfig, MdnsResponder, MdnsService,
+                     MDNS_PORT, MDNS_PROBE_INTERVAL};
+pub use self::middleware::{is_deferred, Middleware, MiddlewareChain, Next};
+pub use self::mirror::{ChannelMirrorSink, DnstapSink, MirrorMiddleware, MirrorSink,
+                       MirroredExchange, UdpMirrorSink, DNSTAP_QUEUE_LEN};
+pub use self::query_forwarder::QueryForwarder;
 pub use self::request_stream::Request;
 pub use self::request_stream::RequestStream;
 pub use self::request_stream::ResponseHandle;
-pub use self::server_future::ServerFuture;
+pub use self::server_future::{ServerFuture, DEFAULT_SHUTDOWN_GRACE};
+pub use self::shutdown::{Draining, DrainStream, InFlight, ShutdownHandle, ShutdownSignal,
+                         UntilShutdown};
+pub use self::socket_activation::{listen_fds, ActivatedSocket, SD_LISTEN_FDS_START, TLS_FD_NAME};
+pub use self::tcp_guard_stream::{TcpGuardStream, TcpLimits};
 pub use self::timeout_stream::TimeoutStream;
+pub use self::update_forwarder::forward_update;
+pub use self::zone_transfer::request_transfer;
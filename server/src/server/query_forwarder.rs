@@ -0,0 +1,210 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use futures::{failed, Future};
+use tokio_core::reactor::Handle;
+
+use trust_dns::client::{BasicClientHandle, ClientFuture, ClientHandle, FailoverClientHandle,
+                        RetryPolicy, TimeoutConfig};
+use trust_dns::op::Message;
+use trust_dns::rr::Name;
+use trust_dns::tcp::TcpClientStream;
+use trust_dns::tls::TlsClientStream;
+use trust_dns::udp::UdpClientStream;
+
+use authority::{ForwardAuthority, ForwardUpstream};
+use server::ShutdownHandle;
+
+/// Forwards the queries for forwarded zones to their upstream resolvers, see
+///  `Catalog::get_forward`.
+///
+/// The clients of the upstreams of a zone are made for its first query and reused for the
+///  following ones, so the connections to TCP and TLS upstreams stay open between queries. The
+///  clients of a zone are made again after a query to it fails, a closed connection would
+///  otherwise fail all of them. Clones share the clients.
+#[derive(Clone)]
+pub struct QueryForwarder {
+    timeouts: TimeoutConfig,
+    shutdown: ShutdownHandle,
+    clients: Rc<RefCell<HashMap<Name, UpstreamClients>>>,
+}
+
+/// The clients of the upstreams of a forwarded zone, and of its shadow
+#[derive(Clone)]
+struct UpstreamClients {
+    upstreams: FailoverClientHandle<BasicClientHandle>,
+    shadow: Option<BasicClientHandle>,
+}
+
+impl QueryForwarder {
+    /// Returns a forwarder without any clients yet
+    ///
+    /// # Arguments
+    ///
+    /// * `timeouts` - the `query` timeout applies to each upstream, the others to the connections
+    /// * `shutdown` - the queries to the shadows are in flight for it, those of `forward` are
+    ///                tracked by the caller
+    pub fn new(timeouts: TimeoutConfig, shutdown: ShutdownHandle) -> Self {
+        QueryForwarder {
+            timeouts: timeouts,
+            shutdown: shutdown,
+            clients: Rc::new(RefCell::new(HashMap::new())),
+        }
+    }
+
+    /// Forwards a query for a forwarded zone to its upstream resolvers.
+    ///
+    /// The query is sent to each upstream in turn, until one responds, and the response is
+    ///  returned with the ID of the query for relaying back to the original requestor. It is
+    ///  cached by the zone, if it caches, see `ForwardAuthority::get_cached`, and its latency
+    ///  recorded, including that of any upstream which failed before. If the zone has a shadow,
+    ///  see `ForwardAuthority::set_shadow`, the query is then sent to it, without delaying the
+    ///  response, and the responses compared.
+    ///
+    /// # Arguments
+    ///
+    /// * `forwarder` - the zone the query is for
+    /// * `request` - the query message as received from the requestor
+    /// * `handle` - reactor on which the query will be sent, and the clients run
+    pub fn forward(&self,
+                   forwarder: Arc<ForwardAuthority>,
+                   request: &Message,
+                   handle: &Handle)
+                   -> Box<Future<Item = Message, Error = io::Error>> {
+        let UpstreamClients { mut upstreams, shadow } = match self.get_clients(&forwarder,
+                                                                                handle) {
+            Some(clients) => clients,
+            None => {
+                return Box::new(failed(io::Error::new(io::ErrorKind::NotFound,
+                                                      format!("no upstreams for zone: {}",
+                                                              forwarder.get_origin()))))
+            }
+        };
+
+        let query = request.clone();
+        let id = request.get_id();
+        let start = Instant::now();
+        let handle = handle.clone();
+        let clients = self.clients.clone();
+        let shutdown = self.shutdown.clone();
+        let failed_clients = self.clients.clone();
+        let origin = forwarder.get_origin().clone();
+        Box::new(upstreams.send(request.clone())
+            .map(move |mut response| {
+                forwarder.record_upstream_latency(start.elapsed());
+                forwarder.cache_response(&query, &response);
+                if let Some(shadow) = shadow {
+                    shadow_query(forwarder,
+                                 shadow,
+                                 clients.clone(),
+                                 &shutdown,
+                                 query,
+                                 response.clone(),
+                                 &handle);
+                }
+                response.id(id);
+                response
+            })
+            .map_err(move |e| {
+                failed_clients.borrow_mut().remove(&origin);
+                io::Error::new(io::ErrorKind::Other,
+                               format!("no response to forwarded query: {}", e))
+            }))
+    }
+
+    /// The clients of the zone, made if there are none yet, None if it has no upstreams
+    fn get_clients(&self,
+                   forwarder: &ForwardAuthority,
+                   handle: &Handle)
+                   -> Option<UpstreamClients> {
+        let mut clients = self.clients.borrow_mut();
+        if let Some(existing) = clients.get_mut(forwarder.get_origin()) {
+            // the client of a shadow which failed is made again
+            if existing.shadow.is_none() {
+                existing.shadow = forwarder.get_shadow()
+                    .map(|shadow| upstream_client(shadow, self.timeouts, handle));
+            }
+            return Some(existing.clone());
+        }
+
+        let upstreams: Vec<BasicClientHandle> = forwarder.get_upstreams()
+            .iter()
+            .map(|upstream| upstream_client(upstream, self.timeouts, handle))
+            .collect();
+        if upstreams.is_empty() {
+            return None;
+        }
+
+        let policy = RetryPolicy {
+            retries: upstreams.len() - 1,
+            backoff: Duration::from_millis(0),
+            ..RetryPolicy::default()
+        };
+        let made = UpstreamClients {
+            upstreams: FailoverClientHandle::new(upstreams, policy, handle.clone()),
+            shadow: forwarder.get_shadow()
+                .map(|shadow| upstream_client(shadow, self.timeouts, handle)),
+        };
+        clients.insert(forwarder.get_origin().clone(), made.clone());
+        Some(made)
+    }
+}
+
+/// Sends the query to the shadow of the zone, comparing its response with that of the upstream,
+///  see `ForwardAuthority::compare_shadow`
+fn shadow_query(forwarder: Arc<ForwardAuthority>,
+                mut shadow: BasicClientHandle,
+                clients: Rc<RefCell<HashMap<Name, UpstreamClients>>>,
+                shutdown: &ShutdownHandle,
+                query: Message,
+                primary: Message,
+                handle: &Handle) {
+    let in_flight = shutdown.track();
+    handle.spawn(shadow.send(query.clone()).then(move |result| {
+        if let Err(ref e) = result {
+            debug!("no response from the shadow of zone: {}: {}", forwarder.get_origin(), e);
+            if let Some(failed) = clients.borrow_mut().get_mut(forwarder.get_origin()) {
+                failed.shadow = None;
+            }
+        }
+        forwarder.compare_shadow(&query, &primary, result.as_ref().ok());
+        drop(in_flight);
+        Ok::<(), ()>(())
+    }));
+}
+
+/// A client of the upstream over its protocol
+fn upstream_client(upstream: &ForwardUpstream,
+                   timeouts: TimeoutConfig,
+                   handle: &Handle)
+                   -> BasicClientHandle {
+    match *upstream {
+        ForwardUpstream::Udp(addr) => {
+            let (stream, stream_handle) = UdpClientStream::new(addr, handle.clone());
+            ClientFuture::with_timeouts(stream, stream_handle, handle.clone(), timeouts, None)
+        }
+        ForwardUpstream::Tcp(addr) => {
+            let (stream, stream_handle) =
+                TcpClientStream::with_timeouts(addr, handle.clone(), timeouts);
+            ClientFuture::with_timeouts(stream, stream_handle, handle.clone(), timeouts, None)
+        }
+        ForwardUpstream::Tls(addr, ref subject_name) => {
+            let mut builder = TlsClientStream::builder();
+            builder.timeouts(timeouts);
+            let (stream, stream_handle) =
+                builder.build(addr, subject_name.clone(), handle.clone());
+            ClientFuture::with_timeouts(stream, stream_handle, handle.clone(), timeouts, None)
+        }
+    }
+}
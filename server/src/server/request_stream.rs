@@ -1,15 +1,20 @@
 use std::io;
 use std::net::SocketAddr;
+use std::sync::Arc;
 
 use futures::{Async, Poll, Stream};
 
 use trust_dns::BufStreamHandle;
+use trust_dns::clock::{Clock, SystemClock};
 use trust_dns::op::Message;
-use trust_dns::serialize::binary::{BinDecoder, BinEncoder, BinSerializable};
+use trust_dns::rr::dnssec::{TSigner, TsigChain};
+use trust_dns::serialize::binary::{BinDecoder, BinEncoder, BinSerializable, DecodeLimits};
 
 pub struct Request {
     pub message: Message,
     pub src: SocketAddr,
+    /// the message as it was received, a TSIG is verified over these bytes
+    pub buffer: Vec<u8>,
 }
 
 /// ReqeustStreams take in bytes, deserialize and pass on Messages
@@ -21,20 +26,49 @@ pub struct Request {
 pub struct RequestStream<S> {
     stream: S,
     stream_handle: BufStreamHandle,
+    decode_limits: DecodeLimits,
+    /// responses are limited to the payload size of the request, i.e. this is a UDP stream
+    truncate: bool,
 }
 
 impl<S> RequestStream<S> {
-    /// Creates a new RequestStream
+    /// Creates a new RequestStream, requests are decoded with `DecodeLimits::for_requests()`
     ///
     /// # Arguments
     /// * `stream` - Stream from which requests will be read
     /// * `stream_handle` - Handle to which responses will be posted
     pub fn new(stream: S, stream_handle: BufStreamHandle) -> Self {
+        Self::with_limits(stream, stream_handle, DecodeLimits::for_requests())
+    }
+
+    /// Creates a new RequestStream
+    ///
+    /// # Arguments
+    /// * `stream` - Stream from which requests will be read
+    /// * `stream_handle` - Handle to which responses will be posted
+    /// * `decode_limits` - requests exceeding these limits are dropped
+    pub fn with_limits(stream: S,
+                       stream_handle: BufStreamHandle,
+                       decode_limits: DecodeLimits)
+                       -> Self {
         RequestStream {
             stream: stream,
             stream_handle: stream_handle,
+            decode_limits: decode_limits,
+            truncate: false,
         }
     }
+
+    /// Creates a new RequestStream for datagrams, responses which do not fit the payload size of
+    ///  the request are truncated
+    ///
+    /// # Arguments
+    /// * `stream` - Stream from which requests will be read
+    /// * `stream_handle` - Handle to which responses will be posted
+    /// * `decode_limits` - requests exceeding these limits are dropped
+    pub fn for_udp(stream: S, stream_handle: BufStreamHandle, decode_limits: DecodeLimits) -> Self {
+        RequestStream { truncate: true, ..Self::with_limits(stream, stream_handle, decode_limits) }
+    }
 }
 
 impl<S> Stream for RequestStream<S>
@@ -57,17 +91,28 @@ impl<S> Stream for RequestStream<S>
                     //       forward the request to another sender such that we could pull serialization off
                     //       the IO thread.
                     // decode any messages that are ready
-                    let mut decoder = BinDecoder::new(&buffer);
-                    match Message::read(&mut decoder) {
+                    let decoded = {
+                        let mut decoder = BinDecoder::with_limits(&buffer, self.decode_limits);
+                        Message::read(&mut decoder)
+                    };
+                    match decoded {
                         Ok(message) => {
                             debug!("received message: {}", message.get_id());
+                            let max_size = if self.truncate {
+                                Some(message.get_max_payload())
+                            } else {
+                                None
+                            };
                             let request = Request {
                                 message: message,
                                 src: addr,
+                                buffer: buffer,
                             };
                             let response_handle = ResponseHandle {
                                 dst: addr,
                                 stream_handle: self.stream_handle.clone(),
+                                max_size: max_size,
+                                tsig: None,
                             };
                             return Ok(Async::Ready(Some((request, response_handle))));
                         }
@@ -83,21 +128,140 @@ impl<S> Stream for RequestStream<S>
     }
 }
 
+/// The TSIG key of a request, with which its responses are signed, see `ResponseHandle::sign_with`
+#[derive(Clone)]
+struct ResponseTsig {
+    tsigner: Arc<TSigner>,
+    /// the MAC of the request, then that of the last response sent
+    prior_mac: Vec<u8>,
+    /// no response was sent yet
+    is_first: bool,
+}
+
 /// A handler for wraping a BufStreamHandle, which will properly serialize the message and add the
 ///  associated destination.
+#[derive(Clone)]
 pub struct ResponseHandle {
     dst: SocketAddr,
     stream_handle: BufStreamHandle,
+    /// largest response the requestor accepts, None if there is no limit, e.g. TCP
+    max_size: Option<u16>,
+    tsig: Option<ResponseTsig>,
 }
 
 impl ResponseHandle {
+    /// Returns a handle for sending messages to `dst` on a connection, these are never truncated
+    pub fn new(dst: SocketAddr, stream_handle: BufStreamHandle) -> Self {
+        ResponseHandle {
+            dst: dst,
+            stream_handle: stream_handle,
+            max_size: None,
+            tsig: None,
+        }
+    }
+
+    /// Signs every message sent with the TSIG key which verified the request, RFC 8945 section 5.3
+    ///
+    /// The first message covers the MAC of the request, each later one, e.g. of an AXFR, the MAC
+    ///  of the message before it.
+    ///
+    /// # Arguments
+    ///
+    /// * `tsigner` - the key which verified the request
+    /// * `request_mac` - the MAC of the TSIG of the request
+    pub fn sign_with(&mut self, tsigner: Arc<TSigner>, request_mac: Vec<u8>) {
+        self.tsig = Some(ResponseTsig {
+            tsigner: tsigner,
+            prior_mac: request_mac,
+            is_first: true,
+        });
+    }
+
+    /// Returns the address to which messages are sent
+    pub fn get_dst(&self) -> SocketAddr {
+        self.dst
+    }
+
+    /// Returns the largest response the requestor accepts, None if there is no limit, e.g. TCP
+    pub fn get_max_size(&self) -> Option<u16> {
+        self.max_size
+    }
+
     /// Serializes and sends a message to to the wrapped handle
+    ///
+    /// [RFC 2181](https://tools.ietf.org/html/rfc2181#section-9), Clarifications to the DNS
+    ///  Specification, July 1997
+    ///
+    /// ```text
+    /// 9. The TC (truncated) header bit
+    ///
+    ///    Where TC is set, the partial RRSet that would not completely fit may
+    ///    be left in the response.  When a DNS client receives a reply with TC
+    ///    set, it should ignore that response, and query again, using a
+    ///    mechanism, such as a TCP connection, that will permit larger replies.
+    /// ```
+    ///
+    /// A response which does not fit `max_size`, even with name compression, is replaced with an
+    ///  empty one with TC set, see `Message::truncate()`, rather than sending partial RRSets.
     pub fn send(&mut self, response: Message) -> io::Result<()> {
         debug!("sending message: {}", response.get_id());
+        let unsigned = if self.tsig.is_some() && self.max_size.is_some() {
+            Some(response.clone())
+        } else {
+            None
+        };
+        let (response, mut mac) = try!(self.sign(response));
+        let mut buffer = try!(Self::encode(&response));
+
+        if let Some(max_size) = self.max_size {
+            if buffer.len() > max_size as usize {
+                debug!("truncating message: {}, {} bytes exceeds the max payload: {}",
+                       response.get_id(),
+                       buffer.len(),
+                       max_size);
+                // the truncated message is signed in place of the one which did not fit
+                let truncated = unsigned.as_ref().unwrap_or(&response).truncate();
+                let (truncated, truncated_mac) = try!(self.sign(truncated));
+                buffer = try!(Self::encode(&truncated));
+                mac = truncated_mac;
+            }
+        }
+
+        if let (Some(tsig), Some(mac)) = (self.tsig.as_mut(), mac) {
+            tsig.prior_mac = mac;
+            tsig.is_first = false;
+        }
+
+        self.stream_handle
+            .send((buffer, self.dst))
+            .map_err(|_| io::Error::new(io::ErrorKind::Other, "unknown"))
+    }
+
+    /// Signs the message if the request was signed, returning it with its MAC
+    fn sign(&self, mut message: Message) -> io::Result<(Message, Option<Vec<u8>>)> {
+        let tsig = match self.tsig {
+            Some(ref tsig) => tsig,
+            None => return Ok((message, None)),
+        };
+
+        let chain = if tsig.is_first {
+            TsigChain::Response(&tsig.prior_mac)
+        } else {
+            TsigChain::Continuation(&tsig.prior_mac)
+        };
+        let mac = try!(message.sign_tsig(&tsig.tsigner, chain, SystemClock.now() as u64)
+            .map_err(|e| {
+                io::Error::new(io::ErrorKind::Other, format!("error signing message: {}", e))
+            }));
+
+        Ok((message, Some(mac)))
+    }
+
+    fn encode(message: &Message) -> io::Result<Vec<u8>> {
         let mut buffer = Vec::with_capacity(512);
         let encode_result = {
             let mut encoder: BinEncoder = BinEncoder::new(&mut buffer);
-            response.emit(&mut encoder)
+            message.emit(&mut encoder)
         };
 
         try!(encode_result.map_err(|e| {
@@ -105,8 +269,6 @@ impl ResponseHandle {
                            format!("error encoding message: {}", e))
         }));
 
-        self.stream_handle
-            .send((buffer, self.dst))
-            .map_err(|_| io::Error::new(io::ErrorKind::Other, "unknown"))
+        Ok(buffer)
     }
 }
@@ -5,58 +5,317 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 use std;
+use std::cell::RefCell;
 use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::rc::Rc;
 use std::sync::Arc;
 use std::time::Duration;
 
-use futures::{Async, Future, Poll, Stream};
+use futures::{lazy, Future, Stream};
 use native_tls::Pkcs12;
 use native_tls::Protocol::Tlsv12;
-use native_tls::TlsAcceptor;
+use native_tls::{TlsAcceptor, TlsAcceptorBuilder};
+#[cfg(target_os = "linux")]
+use native_tls::backend::openssl::TlsAcceptorBuilderExt;
 use tokio_core;
-use tokio_core::reactor::Core;
+use tokio_core::reactor::{Core, Handle, Interval, Timeout};
 use tokio_tls::TlsAcceptorExt;
 
-use trust_dns::op::RequestHandler;
+use trust_dns::client::{NotifySender, TimeoutConfig};
+use trust_dns::clock::{Clock, SystemClock};
+use trust_dns::https::DEFAULT_DNS_QUERY_PATH;
+use trust_dns::op::{Message, MessageType, OpCode, RequestHandler, ResponseCode};
+use trust_dns::rr::{Record, RecordType};
+use trust_dns::serialize::binary::DecodeLimits;
 use trust_dns::udp::UdpStream;
 use trust_dns::tcp::TcpStream;
 use trust_dns::tls::TlsStream;
 
-use server::{Request, RequestStream, ResponseHandle, TimeoutStream};
+use server::middleware::deferred_response;
+use server::{forward_update, mdns_group, request_transfer, Draining, DsoConfig,
+             DsoSession, HttpsStream, ListenerPolicy, MdnsResponder, MiddlewareChain,
+             QueryForwarder, Request, RequestStream, ResponseHandle, ShutdownHandle,
+             TcpGuardStream, TcpLimits, TimeoutStream, UntilShutdown, MDNS_PROBE_INTERVAL};
 use authority::Catalog;
 
+/// seconds to wait for the primary to respond to a forwarded update
+const UPDATE_FORWARD_TIMEOUT: u64 = 10;
+/// seconds to wait for each upstream of a forwarded zone to respond to a query
+const QUERY_FORWARD_TIMEOUT: u64 = 5;
+/// seconds between checks for slave zones which are due for a refresh from their primary, and
+///  for apex aliases which are due to be resolved
+const ZONE_REFRESH_INTERVAL: u64 = 5;
+/// seconds within which a zone transfer from the primary must be received
+const ZONE_TRANSFER_TIMEOUT: u64 = 60;
+/// seconds to wait for a secondary to respond to a NOTIFY, before it is retransmitted
+const NOTIFY_TIMEOUT: u64 = 5;
+/// times a NOTIFY is retransmitted to a secondary which does not respond
+const NOTIFY_RETRIES: usize = 4;
+/// seconds after a shutdown within which the requests in flight are to be answered
+pub const DEFAULT_SHUTDOWN_GRACE: u64 = 5;
+/// milliseconds between checks for the requests in flight, while shutting down
+const SHUTDOWN_DRAIN_INTERVAL: u64 = 100;
+/// seconds within which a client must complete the TLS handshake of a DNS over HTTPS connection
+const TLS_HANDSHAKE_TIMEOUT: u64 = 10;
+/// the protocols of DNS over HTTPS negotiated with ALPN, in the order of preference
+const HTTPS_ALPN_PROTOCOLS: &'static [&'static [u8]] = &[b"h2", b"http/1.1"];
+
 // TODO, would be nice to have a Slab for buffers here...
 pub struct ServerFuture {
     io_loop: Core,
     catalog: Arc<Catalog>, // should the catalog just be static?
+    decode_limits: DecodeLimits,
+    dso: DsoConfig,
+    middleware: MiddlewareChain,
+    forwarder: QueryForwarder,
+    shutdown: ShutdownHandle,
+    shutdown_grace: Duration,
 }
 
 impl ServerFuture {
     /// Creates a new ServerFuture with the specified Catalog of Zones.
+    ///
+    /// Slave zones with a primary are transferred from it whenever they are due for a refresh,
+    ///  see `Authority::set_primary`, and the secondaries of all zones are notified whenever the
+    ///  serial changes, see `Authority::set_secondaries`, while the server is running. The
+    ///  targets of apex aliases are resolved as their addresses expire, see
+    ///  `Authority::set_apex_alias`.
     pub fn new(catalog: Catalog) -> io::Result<ServerFuture> {
+        let io_loop = try!(Core::new());
+        let catalog = Arc::new(catalog);
+        let handle = io_loop.handle();
+
+        // zones without an SOA are transferred as soon as the server runs, the others once their
+        //  refresh timer expires, the secondaries are notified of the zones as loaded
+        let shutdown = ShutdownHandle::new();
+        let forwarder = QueryForwarder::new(TimeoutConfig {
+                                                query: Duration::from_secs(QUERY_FORWARD_TIMEOUT),
+                                                ..TimeoutConfig::default()
+                                            },
+                                            shutdown.clone());
+
+        let refresh_catalog = catalog.clone();
+        let refresh_forwarder = forwarder.clone();
+        let refresh_shutdown = shutdown.clone();
+        let refresh_handle = handle.clone();
+        handle.spawn(lazy(move || {
+            spawn_refreshes(&refresh_catalog, &refresh_shutdown, &refresh_handle);
+            spawn_notifies(&refresh_catalog, &refresh_shutdown, &refresh_handle);
+            spawn_alias_refreshes(&refresh_catalog,
+                                  &refresh_forwarder,
+                                  &refresh_shutdown,
+                                  &refresh_handle);
+            Ok::<(), ()>(())
+        }));
+
+        let refresh_catalog = catalog.clone();
+        let refresh_forwarder = forwarder.clone();
+        let refresh_shutdown = shutdown.clone();
+        let refresh_handle = handle.clone();
+        let refreshes = try!(Interval::new(Duration::from_secs(ZONE_REFRESH_INTERVAL), &handle));
+        handle.spawn(refreshes.for_each(move |_| {
+                spawn_refreshes(&refresh_catalog, &refresh_shutdown, &refresh_handle);
+                spawn_notifies(&refresh_catalog, &refresh_shutdown, &refresh_handle);
+                spawn_alias_refreshes(&refresh_catalog,
+                                      &refresh_forwarder,
+                                      &refresh_shutdown,
+                                      &refresh_handle);
+                Ok(())
+            })
+            .map_err(|e| debug!("error in zone refreshes: {}", e)));
+
         Ok(ServerFuture {
-            io_loop: try!(Core::new()),
-            catalog: Arc::new(catalog),
+            io_loop: io_loop,
+            catalog: catalog,
+            decode_limits: DecodeLimits::for_requests(),
+            dso: DsoConfig::default(),
+            middleware: MiddlewareChain::new(),
+            forwarder: forwarder,
+            shutdown: shutdown,
+            shutdown_grace: Duration::from_secs(DEFAULT_SHUTDOWN_GRACE),
         })
     }
 
+    /// Sets the limits used when decoding requests from any subsequently registered socket or
+    ///  listener, requests exceeding these are dropped.
+    pub fn set_decode_limits(&mut self, decode_limits: DecodeLimits) {
+        self.decode_limits = decode_limits;
+    }
+
+    /// Sets the DSO timeouts and handlers for any subsequently registered TCP or TLS listener,
+    ///  each connection has its own `DsoSession`.
+    pub fn set_dso_config(&mut self, dso: DsoConfig) {
+        self.dso = dso;
+    }
+
+    /// Sets the layers around the `Catalog` for any subsequently registered socket or listener
+    ///
+    /// The layers see each request before it is dispatched, and may answer it themselves. Those
+    ///  answered by the catalog with a single response pass through them with their response:
+    ///  queries, updates of local zones, NOTIFYs, and the op codes of
+    ///  `Catalog::set_op_code_handler`. Zone transfers, DSO sessions, queries for forwarded zones
+    ///  and updates forwarded to a primary are answered after the chain returns, the layers see
+    ///  a placeholder response for them, see `server::is_deferred`. Requests refused by the
+    ///  `ListenerPolicy` or by the listen addresses of a zone do not reach them.
+    pub fn set_middleware(&mut self, middleware: MiddlewareChain) {
+        self.middleware = middleware;
+    }
+
+    /// Returns the handle to shut the server down, from any thread, see `listen`
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        self.shutdown.clone()
+    }
+
+    /// Sets how long `listen` waits for the requests in flight to be answered after a shutdown,
+    ///  `DEFAULT_SHUTDOWN_GRACE` seconds by default
+    pub fn set_shutdown_grace_period(&mut self, grace: Duration) {
+        self.shutdown_grace = grace;
+    }
+
+    /// Watches the files the zones were loaded from, reloading a zone whenever its file is
+    ///  modified, see `Catalog::reload_modified`
+    ///
+    /// The modification times of the files are checked at the interval, the secondaries are
+    ///  notified of the reloaded zones. A modified file is read on the thread of the server, the
+    ///  queries for the zone are answered from the previous version of the zone meanwhile.
+    ///
+    /// # Arguments
+    ///
+    /// * `interval` - the time between checks of the files
+    pub fn watch_zone_files(&self, interval: Duration) -> io::Result<()> {
+        let handle = self.io_loop.handle();
+        let watch_catalog = self.catalog.clone();
+        let watch_forwarder = self.forwarder.clone();
+        let watch_shutdown = self.shutdown.clone();
+        let watch_handle = handle.clone();
+        let watches = try!(Interval::new(interval, &handle));
+        handle.spawn(watches.for_each(move |_| {
+                if !watch_catalog.reload_modified().is_empty() {
+                    spawn_notifies(&watch_catalog, &watch_shutdown, &watch_handle);
+                    spawn_alias_refreshes(&watch_catalog,
+                                          &watch_forwarder,
+                                          &watch_shutdown,
+                                          &watch_handle);
+                }
+                Ok(())
+            })
+            .map_err(|e| debug!("error in zone file watches: {}", e)));
+
+        Ok(())
+    }
+
     /// Register a UDP socket. Should be bound before calling this function.
     pub fn register_socket(&self, socket: std::net::UdpSocket) {
+        self.register_socket_with_policy(socket, ListenerPolicy::default())
+    }
+
+    /// Register a UDP socket, requests which the policy does not allow are refused.
+    ///
+    /// A socket bound to the unspecified address does not know the address a request was sent
+    ///  to, the zones bound to addresses, see `Catalog::has_listen_addrs`, are refused on it.
+    ///
+    /// # Arguments
+    /// * `socket` - a bound UDP socket
+    /// * `policy` - the requests accepted on this socket, see `ListenerPolicy`
+    pub fn register_socket_with_policy(&self,
+                                       socket: std::net::UdpSocket,
+                                       policy: ListenerPolicy) {
         debug!("registered udp: {:?}", socket);
+        let local = socket.local_addr().expect("socket is not bound?").ip();
+        let is_unspecified = match local {
+            IpAddr::V4(ip) => ip.is_unspecified(),
+            IpAddr::V6(ip) => ip.is_unspecified(),
+        };
+        if is_unspecified && self.catalog.has_listen_addrs() {
+            warn!("the zones with listen addresses are refused on the udp socket: {}", local);
+        }
 
         // create the new UdpStream
         let (buf_stream, stream_handle) = UdpStream::with_bound(socket, self.io_loop.handle());
-        let request_stream = RequestStream::new(buf_stream, stream_handle);
+        let request_stream = RequestStream::for_udp(buf_stream, stream_handle, self.decode_limits);
         let catalog = self.catalog.clone();
+        let middleware = self.middleware.clone();
+        let forwarder = self.forwarder.clone();
+        let shutdown = self.shutdown.clone();
+        let handle = self.io_loop.handle();
 
         // this spawns a ForEach future which handles all the requests into a Catalog.
         self.io_loop.handle().spawn(// TODO dedup with below into generic func
                                     request_stream.for_each(move |(request, response_handle)| {
-                Self::handle_request(request, response_handle, catalog.clone())
+                Self::handle_request(request,
+                                     response_handle,
+                                     catalog.clone(),
+                                     &middleware,
+                                     &forwarder,
+                                     policy,
+                                     local,
+                                     None,
+                                     &shutdown,
+                                     &handle)
             })
             .map_err(|e| debug!("error in UDP request_stream handler: {}", e)));
     }
 
+    /// Register a UDP socket for the mDNS responder, it is independent of the catalog.
+    ///
+    /// The responder probes for its names and announces them, then answers the queries. When the
+    ///  server is dropped, the goodbye is sent. See `bind_mdns_ipv4()`.
+    ///
+    /// # Arguments
+    /// * `socket` - a UDP socket bound to the mDNS port, which has joined the group
+    /// * `responder` - the names and services to claim on the link
+    pub fn register_mdns_socket(&self,
+                                socket: std::net::UdpSocket,
+                                responder: MdnsResponder)
+                                -> io::Result<()> {
+        let group = mdns_group(&try!(socket.local_addr()));
+        let goodbye_socket = try!(socket.try_clone());
+        debug!("registered mdns: {:?}", socket);
+
+        let handle = self.io_loop.handle();
+        let (buf_stream, stream_handle) = UdpStream::with_bound(socket, handle.clone());
+        let request_stream =
+            RequestStream::for_udp(buf_stream, stream_handle.clone(), self.decode_limits);
+        let responder = Rc::new(RefCell::new(responder));
+
+        let request_responder = responder.clone();
+        let request_handle = stream_handle.clone();
+        handle.spawn(request_stream.for_each(move |(request, _)| {
+                let response = request_responder.borrow_mut()
+                    .handle_message(&request.message, request.src);
+
+                match response {
+                    Some((response, dst)) => {
+                        ResponseHandle::new(dst, request_handle.clone()).send(response)
+                    }
+                    None => Ok(()),
+                }
+            })
+            .map_err(|e| debug!("error in mDNS request_stream handler: {}", e)));
+
+        // the goodbye is sent once the probes are no longer polled
+        let goodbye = MdnsGoodbye {
+            socket: goodbye_socket,
+            group: group,
+            responder: responder,
+        };
+        let probes = try!(Interval::new(Duration::from_millis(MDNS_PROBE_INTERVAL), &handle));
+        handle.spawn(probes.for_each(move |_| {
+                let message = goodbye.responder.borrow_mut().next_message();
+
+                match message {
+                    Some(message) => {
+                        ResponseHandle::new(group, stream_handle.clone()).send(message)
+                    }
+                    None => Ok(()),
+                }
+            })
+            .map_err(|e| debug!("error in mDNS probes: {}", e)));
+
+        Ok(())
+    }
+
     /// Register a TcpListener to the Server. This should already be bound to either an IPv6 or an
     ///  IPv4 address.
     ///
@@ -73,27 +332,101 @@ impl ServerFuture {
                              listener: std::net::TcpListener,
                              timeout: Duration)
                              -> io::Result<()> {
+        self.register_listener_with_limits(listener, TcpLimits::new(timeout))
+    }
+
+    /// Register a TcpListener to the Server, with the idle, read and write timeouts and the
+    ///  default limits.
+    ///
+    /// # Arguments
+    /// * `listener` - a bound TCP socket
+    /// * `timeouts` - the `tcp_idle`, `read` and `write` timeouts apply, see `TcpLimits`
+    pub fn register_listener_with_timeouts(&self,
+                                           listener: std::net::TcpListener,
+                                           timeouts: TimeoutConfig)
+                                           -> io::Result<()> {
+        self.register_listener_with_limits(listener, TcpLimits::from(timeouts))
+    }
+
+    /// Register a TcpListener to the Server, with limits protecting against slow or abusive
+    ///  clients.
+    ///
+    /// # Arguments
+    /// * `listener` - a bound TCP socket
+    /// * `limits` - idle, read and write timeouts, along with size and request limits, see
+    ///              `TcpLimits`
+    pub fn register_listener_with_limits(&self,
+                                         listener: std::net::TcpListener,
+                                         limits: TcpLimits)
+                                         -> io::Result<()> {
+        self.register_listener_with_policy(listener, limits, ListenerPolicy::default())
+    }
+
+    /// Register a TcpListener to the Server, requests which the policy does not allow are
+    ///  refused.
+    ///
+    /// # Arguments
+    /// * `listener` - a bound TCP socket
+    /// * `limits` - idle, read and write timeouts, along with size and request limits, see
+    ///              `TcpLimits`
+    /// * `policy` - the requests accepted on this listener, see `ListenerPolicy`
+    pub fn register_listener_with_policy(&self,
+                                         listener: std::net::TcpListener,
+                                         limits: TcpLimits,
+                                         policy: ListenerPolicy)
+                                         -> io::Result<()> {
         let handle = self.io_loop.handle();
         let catalog = self.catalog.clone();
+        let decode_limits = self.decode_limits;
+        let dso = self.dso.clone();
+        let middleware = self.middleware.clone();
+        let forwarder = self.forwarder.clone();
+        let shutdown = self.shutdown.clone();
         // TODO: this is an awkward interface with socketaddr...
         let addr = try!(listener.local_addr());
         let listener = tokio_core::net::TcpListener::from_listener(listener, &addr, &handle)
             .expect("could not register listener");
         debug!("registered tcp: {:?}", listener);
 
-        // for each incoming request...
-        self.io_loop.handle().spawn(listener.incoming()
-            .for_each(move |(tcp_stream, src_addr)| {
+        // for each incoming request, until the shutdown...
+        let incoming = UntilShutdown::new(listener.incoming(), &shutdown);
+        self.io_loop.handle().spawn(incoming.for_each(move |(tcp_stream, src_addr)| {
                 debug!("accepted request from: {}", src_addr);
+                // a listener on the unspecified address accepts on any of the local addresses
+                let local = tcp_stream.local_addr().unwrap_or(addr).ip();
                 // take the created stream...
                 let (buf_stream, stream_handle) = TcpStream::from_stream(tcp_stream, src_addr);
-                let timeout_stream = try!(TimeoutStream::new(buf_stream, timeout, handle.clone()));
-                let request_stream = RequestStream::new(timeout_stream, stream_handle);
+                let guard_stream = TcpGuardStream::new(buf_stream, limits, handle.clone());
+                // the connection is in flight until it ends, which it does once idle after the
+                //  shutdown
+                let guard_stream = Draining::new(guard_stream, &shutdown);
+                let timeout_stream = try!(TimeoutStream::new(guard_stream,
+                                                             limits.idle_timeout,
+                                                             handle.clone()));
+                let mut session = DsoSession::new(dso.clone(),
+                                                  timeout_stream.get_timeout_duration(),
+                                                  ResponseHandle::new(src_addr,
+                                                                      stream_handle.clone()));
+                let request_stream =
+                    RequestStream::with_limits(timeout_stream, stream_handle, decode_limits);
                 let catalog = catalog.clone();
+                let middleware = middleware.clone();
+                let forwarder = forwarder.clone();
+                let shutdown = shutdown.clone();
+                let request_handle = handle.clone();
 
                 // and spawn to the io_loop
                 handle.spawn(request_stream.for_each(move |(request, response_handle)| {
-                        Self::handle_request(request, response_handle, catalog.clone())
+                        Self::handle_request(request,
+                                             response_handle,
+                                             catalog.clone(),
+                                             &middleware,
+                                             &forwarder,
+                                             policy,
+                                             local,
+                                             Some(&mut session),
+                                             &shutdown,
+                                             &request_handle)
                     })
                     .map_err(move |e| {
                         debug!("error in TCP request_stream src: {:?} error: {}",
@@ -126,49 +459,101 @@ impl ServerFuture {
                                  timeout: Duration,
                                  pkcs12: Pkcs12)
                                  -> io::Result<()> {
+        self.register_tls_listener_with_limits(listener, TcpLimits::new(timeout), pkcs12)
+    }
+
+    /// Register a TlsListener to the Server, with the idle, read and write timeouts and the
+    ///  default limits.
+    ///
+    /// # Arguments
+    /// * `listener` - a bound TCP (needs to be on a different port from standard TCP connections) socket
+    /// * `timeouts` - the `tcp_idle`, `read` and `write` timeouts apply, see `TcpLimits`
+    /// * `pkcs12` - certificate used to announce to clients
+    pub fn register_tls_listener_with_timeouts(&self,
+                                               listener: std::net::TcpListener,
+                                               timeouts: TimeoutConfig,
+                                               pkcs12: Pkcs12)
+                                               -> io::Result<()> {
+        self.register_tls_listener_with_limits(listener, TcpLimits::from(timeouts), pkcs12)
+    }
+
+    /// Register a TlsListener to the Server, with limits protecting against slow or abusive
+    ///  clients.
+    ///
+    /// # Arguments
+    /// * `listener` - a bound TCP (needs to be on a different port from standard TCP connections) socket
+    /// * `limits` - idle, read and write timeouts, along with size and request limits, see
+    ///              `TcpLimits`
+    /// * `pkcs12` - certificate used to announce to clients
+    pub fn register_tls_listener_with_limits(&self,
+                                             listener: std::net::TcpListener,
+                                             limits: TcpLimits,
+                                             pkcs12: Pkcs12)
+                                             -> io::Result<()> {
+        self.register_tls_listener_with_policy(listener, limits, pkcs12, ListenerPolicy::default())
+    }
+
+    /// Register a TlsListener to the Server, requests which the policy does not allow are
+    ///  refused.
+    ///
+    /// # Arguments
+    /// * `listener` - a bound TCP (needs to be on a different port from standard TCP connections) socket
+    /// * `limits` - idle, read and write timeouts, along with size and request limits, see
+    ///              `TcpLimits`
+    /// * `pkcs12` - certificate used to announce to clients
+    /// * `policy` - the requests accepted on this listener, see `ListenerPolicy`
+    pub fn register_tls_listener_with_policy(&self,
+                                             listener: std::net::TcpListener,
+                                             limits: TcpLimits,
+                                             pkcs12: Pkcs12,
+                                             policy: ListenerPolicy)
+                                             -> io::Result<()> {
         let handle = self.io_loop.handle();
         let catalog = self.catalog.clone();
+        let decode_limits = self.decode_limits;
+        let dso = self.dso.clone();
+        let middleware = self.middleware.clone();
+        let forwarder = self.forwarder.clone();
+        let shutdown = self.shutdown.clone();
         // TODO: this is an awkward interface with socketaddr...
         let addr = listener.local_addr().expect("listener is not bound?");
         let listener = tokio_core::net::TcpListener::from_listener(listener, &addr, &handle)
             .expect("could not register listener");
         debug!("registered tcp: {:?}", listener);
 
-        let mut builder = try!(TlsAcceptor::builder(pkcs12).map_err(|e| {
-            io::Error::new(io::ErrorKind::ConnectionRefused,
-                           format!("tls error: {}", e))
-        }));
-        try!(builder.supported_protocols(&[Tlsv12]).map_err(|e| {
-            io::Error::new(io::ErrorKind::ConnectionRefused,
-                           format!("tls error: {}", e))
-        }));
-        let tls_acceptor = try!(builder.build().map_err(|e| {
-            io::Error::new(io::ErrorKind::ConnectionRefused,
-                           format!("tls error: {}", e))
-        }));
+        let tls_acceptor = try!(Self::tls_acceptor(pkcs12, &[]));
 
-        // for each incoming request...
+        // for each incoming request, until the shutdown...
         self.io_loop.handle().spawn(
-      listener.incoming()
+      UntilShutdown::new(listener.incoming(), &shutdown)
               .for_each(move |(tcp_stream, src_addr)| {
                 debug!("accepted request from: {}", src_addr);
-                let timeout = timeout.clone();
                 let handle = handle.clone();
                 let catalog = catalog.clone();
+                let dso = dso.clone();
+                let middleware = middleware.clone();
+                let forwarder = forwarder.clone();
+                let shutdown = shutdown.clone();
+                let local = tcp_stream.local_addr().unwrap_or(addr).ip();
 
                 // take the created stream...
                 tls_acceptor.accept_async(tcp_stream)
                             .map_err(|e| io::Error::new(io::ErrorKind::ConnectionRefused, format!("tls error: {}", e)))
                             .and_then(move |tls_stream| {
                               let (buf_stream, stream_handle) = TlsStream::from_stream(tls_stream, src_addr.clone());
-                              let timeout_stream = try!(TimeoutStream::new(buf_stream, timeout, handle.clone()));
-                              let request_stream = RequestStream::new(timeout_stream, stream_handle);
+                              let guard_stream = TcpGuardStream::new(buf_stream, limits, handle.clone());
+                              let guard_stream = Draining::new(guard_stream, &shutdown);
+                              let timeout_stream = try!(TimeoutStream::new(guard_stream, limits.idle_timeout, handle.clone()));
+                              let mut session = DsoSession::new(dso, timeout_stream.get_timeout_duration(), ResponseHandle::new(src_addr, stream_handle.clone()));
+                              let request_stream =
+                    RequestStream::with_limits(timeout_stream, stream_handle, decode_limits);
                               let catalog = catalog.clone();
+                              let request_handle = handle.clone();
 
                               // and spawn to the io_loop
                               handle.spawn(
                                 request_stream.for_each(move |(request, response_handle)| {
-                                  Self::handle_request(request, response_handle, catalog.clone())
+                                  Self::handle_request(request, response_handle, catalog.clone(), &middleware, &forwarder, policy, local, Some(&mut session), &shutdown, &request_handle)
                                 })
                                 .map_err(move |e| debug!("error in TCP request_stream src: {:?} error: {}", src_addr, e))
                               );
@@ -183,34 +568,638 @@ impl ServerFuture {
         Ok(())
     }
 
+    /// Register an HttpsListener to the Server, for DNS over HTTPS requests to `/dns-query`. The
+    ///  listener should already be bound to either an IPv6 or an IPv4 address.
+    ///
+    /// # Arguments
+    /// * `listener` - a bound TCP socket, usually on port 443
+    /// * `timeout` - timeout duration of incoming requests, any connection that does not send
+    ///               requests within this time period will be closed
+    /// * `pkcs12` - certificate used to announce to clients
+    pub fn register_https_listener(&self,
+                                   listener: std::net::TcpListener,
+                                   timeout: Duration,
+                                   pkcs12: Pkcs12)
+                                   -> io::Result<()> {
+        self.register_https_listener_with_policy(listener,
+                                                 timeout,
+                                                 pkcs12,
+                                                 DEFAULT_DNS_QUERY_PATH,
+                                                 ListenerPolicy::default())
+    }
+
+    /// Register an HttpsListener to the Server, requests which the policy does not allow are
+    ///  refused.
+    ///
+    /// The GET and POST requests of RFC 8484 are accepted over HTTP/2, or HTTP/1.1 for the clients
+    ///  which do not negotiate `h2` with ALPN, see `HttpsStream`, and handled by the `Catalog` like
+    ///  the requests over UDP and TCP. DSO is not available. The TLS handshake of each connection
+    ///  runs on its own, and must be completed within 10 seconds.
+    ///
+    /// # Arguments
+    /// * `listener` - a bound TCP socket, usually on port 443
+    /// * `timeout` - timeout duration of incoming requests, any connection that does not send
+    ///               requests within this time period will be closed
+    /// * `pkcs12` - certificate used to announce to clients
+    /// * `path` - the URI path of the DNS API, requests for any other path are answered 404
+    /// * `policy` - the requests accepted on this listener, see `ListenerPolicy`
+    pub fn register_https_listener_with_policy(&self,
+                                               listener: std::net::TcpListener,
+                                               timeout: Duration,
+                                               pkcs12: Pkcs12,
+                                               path: &str,
+                                               policy: ListenerPolicy)
+                                               -> io::Result<()> {
+        let handle = self.io_loop.handle();
+        let catalog = self.catalog.clone();
+        let decode_limits = self.decode_limits;
+        let middleware = self.middleware.clone();
+        let forwarder = self.forwarder.clone();
+        let shutdown = self.shutdown.clone();
+        let path = path.to_string();
+        let addr = try!(listener.local_addr());
+        let listener = try!(tokio_core::net::TcpListener::from_listener(listener, &addr, &handle));
+        debug!("registered https: {:?}", listener);
+
+        let tls_acceptor = try!(Self::tls_acceptor(pkcs12, HTTPS_ALPN_PROTOCOLS));
+
+        // for each incoming request, until the shutdown...
+        let incoming = UntilShutdown::new(listener.incoming(), &shutdown);
+        self.io_loop.handle().spawn(incoming.for_each(move |(tcp_stream, src_addr)| {
+                debug!("accepted request from: {}", src_addr);
+                let reactor_handle = handle.clone();
+                let catalog = catalog.clone();
+                let path = path.clone();
+                let middleware = middleware.clone();
+                let forwarder = forwarder.clone();
+                let shutdown = shutdown.clone();
+                let local = tcp_stream.local_addr().unwrap_or(addr).ip();
+
+                // the handshake of each connection is spawned, and must complete in time, so that
+                //  a slow client does not hold up the others
+                let deadline = try!(Timeout::new(Duration::from_secs(TLS_HANDSHAKE_TIMEOUT),
+                                                 &handle))
+                    .and_then(|()| {
+                        Err(io::Error::new(io::ErrorKind::TimedOut, "tls handshake timed out"))
+                    });
+                let handshake = tls_acceptor.accept_async(tcp_stream)
+                    .map_err(|e| {
+                        io::Error::new(io::ErrorKind::ConnectionRefused,
+                                       format!("tls error: {}", e))
+                    })
+                    .select(deadline)
+                    .map(|(tls_stream, _)| tls_stream)
+                    .map_err(|(e, _)| e);
+
+                handle.spawn(handshake.and_then(move |tls_stream| {
+                        let (https_stream, stream_handle) =
+                            HttpsStream::from_stream(tls_stream, src_addr, path);
+                        let timeout_stream =
+                            try!(TimeoutStream::new(Draining::new(https_stream, &shutdown),
+                                                    timeout,
+                                                    reactor_handle.clone()));
+                        let request_stream = RequestStream::with_limits(timeout_stream,
+                                                                        stream_handle,
+                                                                        decode_limits);
+                        let request_handle = reactor_handle.clone();
+
+                        let requests = request_stream.for_each(move |(request, response)| {
+                                Self::handle_request(request,
+                                                     response,
+                                                     catalog.clone(),
+                                                     &middleware,
+                                                     &forwarder,
+                                                     policy,
+                                                     local,
+                                                     None,
+                                                     &shutdown,
+                                                     &request_handle)
+                            })
+                            .map_err(move |e| {
+                                debug!("error in HTTPS request_stream src: {:?} error: {}",
+                                       src_addr,
+                                       e)
+                            });
+
+                        // and spawn to the io_loop
+                        reactor_handle.spawn(requests);
+                        Ok(())
+                    })
+                    .map_err(move |e| {
+                        debug!("error in TLS handshake src: {:?} error: {}", src_addr, e)
+                    }));
+
+                Ok(())
+            })
+            .map_err(|e| debug!("error in inbound https_stream: {}", e)));
+
+        Ok(())
+    }
+
+    /// The acceptor of TLS connections, which negotiates the first of the ALPN protocols the client
+    ///  supports, ALPN is only available with OpenSSL
+    fn tls_acceptor(pkcs12: Pkcs12, alpn_protocols: &[&[u8]]) -> io::Result<TlsAcceptor> {
+        let mut builder = try!(TlsAcceptor::builder(pkcs12).map_err(|e| {
+            io::Error::new(io::ErrorKind::ConnectionRefused,
+                           format!("tls error: {}", e))
+        }));
+        try!(builder.supported_protocols(&[Tlsv12]).map_err(|e| {
+            io::Error::new(io::ErrorKind::ConnectionRefused,
+                           format!("tls error: {}", e))
+        }));
+        if !alpn_protocols.is_empty() {
+            try!(Self::set_alpn_protocols(&mut builder, alpn_protocols));
+        }
+
+        builder.build().map_err(|e| {
+            io::Error::new(io::ErrorKind::ConnectionRefused,
+                           format!("tls error: {}", e))
+        })
+    }
+
+    #[cfg(target_os = "linux")]
+    fn set_alpn_protocols(builder: &mut TlsAcceptorBuilder,
+                          alpn_protocols: &[&[u8]])
+                          -> io::Result<()> {
+        let openssl_builder = builder.builder_mut();
+        let openssl_ctx_builder = openssl_builder.builder_mut();
+        openssl_ctx_builder.set_alpn_protocols(alpn_protocols).map_err(|e| {
+            io::Error::new(io::ErrorKind::ConnectionRefused,
+                           format!("tls error: {}", e))
+        })
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn set_alpn_protocols(_: &mut TlsAcceptorBuilder, _: &[&[u8]]) -> io::Result<()> {
+        debug!("ALPN is not supported, HTTP/2 clients need prior knowledge");
+        Ok(())
+    }
+
+    /// Runs the server until its shutdown is requested, see `shutdown_handle`
+    ///
+    /// On the shutdown the listeners stop accepting connections, and the requests which then
+    ///  arrive on the sockets and connections are refused. Everything in flight is finished within
+    ///  the grace period, see `set_shutdown_grace_period`, the rest is abandoned: the requests
+    ///  forwarded to a primary or to the upstreams of a zone, the queries to shadows, the zone
+    ///  transfers from primaries and the NOTIFYs to secondaries, and the open connections, which
+    ///  are closed once the responses queued on them are written, see `Draining`.
+    ///
     /// TODO how to do threads? should we do a bunch of listener threads and then query threads?
     /// Ideally the processing would be n-threads for recieving, which hand off to m-threads for
     ///  request handling. It would generally be the case that n <= m.
     pub fn listen(&mut self) -> io::Result<()> {
         info!("Server starting up");
-        try!(self.io_loop.run(Forever));
+        try!(self.io_loop.run(self.shutdown.signal()));
+
+        info!("Server shutting down, requests in flight: {}",
+              self.shutdown.get_in_flight());
+        let handle = self.io_loop.handle();
+        let shutdown = self.shutdown.clone();
+        let drained = try!(Interval::new(Duration::from_millis(SHUTDOWN_DRAIN_INTERVAL), &handle))
+            .take_while(move |_| Ok(shutdown.get_in_flight() > 0))
+            .for_each(|_| Ok(()))
+            .map(|_| true);
+        let grace = try!(Timeout::new(self.shutdown_grace, &handle)).map(|_| false);
+
+        let (drained, _) = try!(self.io_loop.run(drained.select(grace)).map_err(|(e, _)| e));
+        if !drained {
+            warn!("abandoning requests in flight: {}, the grace period expired",
+                  self.shutdown.get_in_flight());
+        }
 
-        Err(io::Error::new(io::ErrorKind::Interrupted,
-                           "Server stopping due to interruption"))
+        info!("Server stopped");
+        Ok(())
     }
 
     fn handle_request(request: Request,
                       mut response_handle: ResponseHandle,
-                      catalog: Arc<Catalog>)
+                      catalog: Arc<Catalog>,
+                      middleware: &MiddlewareChain,
+                      query_forwarder: &QueryForwarder,
+                      policy: ListenerPolicy,
+                      local: IpAddr,
+                      session: Option<&mut DsoSession>,
+                      shutdown: &ShutdownHandle,
+                      reactor_handle: &Handle)
                       -> io::Result<()> {
-        let response = catalog.handle_request(&request.message);
-        response_handle.send(response)
+        // the requests in flight are answered after the shutdown, but new ones are refused, e.g.
+        //  on the connections which are still open
+        if shutdown.is_shutdown() {
+            debug!("refusing request id: {} from: {}, shutting down",
+                   request.message.get_id(),
+                   request.src);
+
+            let mut response = Message::error_msg(request.message.get_id(),
+                                                  request.message.get_op_code(),
+                                                  ResponseCode::Refused);
+            response.add_queries(request.message.get_queries().iter().cloned());
+            return response_handle.send(response);
+        }
+
+        // a request signed with TSIG is verified before anything else, RFC 8945 section 5.2, and
+        //  all of its responses are signed with the same key
+        let now = SystemClock.now() as u64;
+        let tsig_key = match catalog.verify_tsig(&request.message, &request.buffer, now) {
+            Ok(Some((tsigner, tsig))) => {
+                let key_name = tsigner.get_key_name().clone();
+                response_handle.sign_with(tsigner, tsig.get_mac().to_vec());
+                Some(key_name)
+            }
+            Ok(None) => None,
+            Err(e) => {
+                warn!("refusing request id: {} from: {}, tsig not verified: {}",
+                      request.message.get_id(),
+                      request.src,
+                      e);
+
+                let mut response = Message::error_msg(request.message.get_id(),
+                                                      request.message.get_op_code(),
+                                                      ResponseCode::NotAuth);
+                response.add_queries(request.message.get_queries().iter().cloned());
+                return response_handle.send(response);
+            }
+        };
+
+        if !policy.is_allowed(&request.message) {
+            info!("refusing request id: {} op_code: {:?} from: {}, not allowed on this listener",
+                  request.message.get_id(),
+                  request.message.get_op_code(),
+                  request.src);
+
+            let mut response = Message::error_msg(request.message.get_id(),
+                                                  request.message.get_op_code(),
+                                                  ResponseCode::Refused);
+            response.add_queries(request.message.get_queries().iter().cloned());
+            return response_handle.send(response);
+        }
+
+        // the zones of a tenant are only answered on its own addresses, whatever the op_code
+        if !catalog.is_served_on(&request.message, local) {
+            info!("refusing request id: {} op_code: {:?} from: {}, zone not served on: {}",
+                  request.message.get_id(),
+                  request.message.get_op_code(),
+                  request.src,
+                  local);
+
+            let mut response = Message::error_msg(request.message.get_id(),
+                                                  request.message.get_op_code(),
+                                                  ResponseCode::Refused);
+            response.add_queries(request.message.get_queries().iter().cloned());
+            return response_handle.send(response);
+        }
+
+        // a NOTIFY for a slave zone is only accepted from its primary, RFC 1996 section 3.10
+        if !catalog.is_notify_from_primary(&request.message, request.src.ip()) {
+            info!("refusing notify id: {} from: {}, not the primary",
+                  request.message.get_id(),
+                  request.src);
+
+            let mut response = Message::error_msg(request.message.get_id(),
+                                                  request.message.get_op_code(),
+                                                  ResponseCode::Refused);
+            response.add_queries(request.message.get_queries().iter().cloned());
+            return response_handle.send(response);
+        }
+
+        // every request passes through the middleware first, which may answer it, the catalog
+        //  answers it at the end of the chain unless it is dispatched below
+        let dispatcher = Dispatcher::new(&catalog,
+                                         local,
+                                         response_handle.get_max_size(),
+                                         session.is_some());
+        let response = middleware.handle_request(&dispatcher, &request.message, request.src);
+        let message = match dispatcher.take_deferred() {
+            Some(message) => message,
+            None => {
+                try!(response_handle.send(response));
+
+                // a NOTIFY from the primary may have made a slave zone due for a refresh, and an
+                //  update may have changed a zone of which the secondaries are to be notified,
+                //  RFC 1996
+                match request.message.get_op_code() {
+                    OpCode::Notify => spawn_refreshes(&catalog, shutdown, reactor_handle),
+                    OpCode::Update => spawn_notifies(&catalog, shutdown, reactor_handle),
+                    _ => (),
+                }
+
+                return Ok(());
+            }
+        };
+
+        // zone transfers on connections are split over as many messages as needed, on UDP and
+        //  HTTPS there is only the one response of the catalog
+        if session.is_some() && is_transfer(&message) {
+            for response in catalog.transfer(&message, request.src.ip(), tsig_key.as_ref()) {
+                try!(response_handle.send(response));
+            }
+            return Ok(());
+        }
+
+        // DSO needs a session, without one, i.e. on UDP, the catalog answers NOTIMP
+        if let (OpCode::Dso, Some(session)) = (message.get_op_code(), session) {
+            if let Some(response) = try!(session.handle_request(&message, &catalog)) {
+                try!(response_handle.send(response));
+            }
+            return session.send_pending();
+        }
+
+        // updates to slave zones may be forwarded to the primary, which needs to happen async
+        if let Some(primary) = catalog.get_update_forward(&message) {
+            let update = message;
+            info!("forwarding update id: {} from: {} to primary: {}",
+                  update.get_id(),
+                  request.src,
+                  primary);
+
+            let id = update.get_id();
+            let op_code = update.get_op_code();
+            let in_flight = shutdown.track();
+            reactor_handle.spawn(forward_update(&update,
+                                                request.buffer,
+                                                primary,
+                                                Duration::from_secs(UPDATE_FORWARD_TIMEOUT),
+                                                reactor_handle)
+                .then(move |result| {
+                    let response = result.unwrap_or_else(|e| {
+                        warn!("forwarding update id: {} to primary: {} failed: {}",
+                              id,
+                              primary,
+                              e);
+                        Message::error_msg(id, op_code, ResponseCode::ServFail)
+                    });
+
+                    let sent = response_handle.send(response)
+                        .map_err(|e| debug!("error relaying update response: {}", e));
+                    drop(in_flight);
+                    sent
+                }));
+
+            return Ok(());
+        }
+
+        // queries for forwarded zones not answered from the cache are answered by the upstreams
+        if let Some(forwarder) = catalog.get_forward(&message) {
+            info!("forwarding query id: {} from: {} for zone: {}",
+                  message.get_id(),
+                  request.src,
+                  forwarder.get_origin());
+
+            let mut servfail = Message::error_msg(message.get_id(),
+                                                  message.get_op_code(),
+                                                  ResponseCode::ServFail);
+            servfail.add_queries(message.get_queries().iter().cloned());
+            let in_flight = shutdown.track();
+            reactor_handle.spawn(query_forwarder.forward(forwarder, &message, reactor_handle)
+                .then(move |result| {
+                    let response = result.unwrap_or_else(|e| {
+                        warn!("{}", e);
+                        servfail
+                    });
+
+                    let sent = response_handle.send(response)
+                        .map_err(|e| debug!("error relaying forwarded response: {}", e));
+                    drop(in_flight);
+                    sent
+                }));
+
+            return Ok(());
+        }
+
+        let max_size = response_handle.get_max_size();
+        response_handle.send(catalog.handle_request_on(&message, local, max_size))
     }
 }
 
-struct Forever;
+/// The handler at the end of the `MiddlewareChain` of the server: it answers the requests from
+///  the catalog, but defers those which are answered asynchronously or in several messages,
+///  zone transfers, DSO, forwarded updates and queries, to `handle_request`
+///
+/// The layers see the response of `deferred_response` for a deferred request, which is not sent.
+struct Dispatcher<'a> {
+    catalog: &'a Catalog,
+    local: IpAddr,
+    max_size: Option<u16>,
+    connected: bool,
+    deferred: RefCell<Option<Message>>,
+}
+
+impl<'a> Dispatcher<'a> {
+    /// local is the address of the listener, max_size the limit of its transport, connected is
+    ///  true on TCP and TLS connections, which have a DSO session
+    fn new(catalog: &'a Catalog, local: IpAddr, max_size: Option<u16>, connected: bool) -> Self {
+        Dispatcher {
+            catalog: catalog,
+            local: local,
+            max_size: max_size,
+            connected: connected,
+            deferred: RefCell::new(None),
+        }
+    }
+
+    /// The request as passed on by the last layer, if it was deferred
+    fn take_deferred(&self) -> Option<Message> {
+        self.deferred.borrow_mut().take()
+    }
+}
+
+impl<'a> RequestHandler for Dispatcher<'a> {
+    fn handle_request(&self, request: &Message) -> Message {
+        let mut deferred = (self.connected &&
+                            (is_transfer(request) || request.get_op_code() == OpCode::Dso)) ||
+                           self.catalog.get_update_forward(request).is_some();
+        if !deferred {
+            if let Some(forwarder) = self.catalog.get_forward(request) {
+                if let Some(response) = forwarder.get_cached(request) {
+                    return response;
+                }
+                deferred = true;
+            }
+        }
+
+        if !deferred {
+            return self.catalog.handle_request_on(request, self.local, self.max_size);
+        }
+
+        *self.deferred.borrow_mut() = Some(request.clone());
+        deferred_response(request)
+    }
+}
+
+/// Requests the transfers of all the slave zones which are due for a refresh, each is installed
+///  as it is received, see `Catalog::start_refreshes`
+///
+/// The transfers are in flight for the shutdown, after which no more are started, as with
+///  `spawn_notifies` and `spawn_alias_refreshes`.
+fn spawn_refreshes(catalog: &Arc<Catalog>, shutdown: &ShutdownHandle, handle: &Handle) {
+    if shutdown.is_shutdown() {
+        return;
+    }
+
+    for request in catalog.start_refreshes() {
+        info!("refreshing zone: {} serial: {:?} from primary: {}",
+              request.get_origin(),
+              request.get_serial(),
+              request.get_primary());
+
+        let catalog = catalog.clone();
+        let origin = request.get_origin().clone();
+        let notify_shutdown = shutdown.clone();
+        let notify_handle = handle.clone();
+        let in_flight = shutdown.track();
+        handle.spawn(request_transfer(&request,
+                                      Duration::from_secs(ZONE_TRANSFER_TIMEOUT),
+                                      handle)
+            .then(move |result| {
+                catalog.finish_refresh(&origin, result);
+                // the secondaries of this secondary are notified of a newly installed transfer
+                spawn_notifies(&catalog, &notify_shutdown, &notify_handle);
+            drop(in_flight);
+            Ok::<(), ()>(())
+        }));
+    }
+}
+
+/// Sends a NOTIFY to the secondaries of all the zones which changed since they were last
+///  notified, see `Catalog::start_notifies`
+fn spawn_notifies(catalog: &Arc<Catalog>, shutdown: &ShutdownHandle, handle: &Handle) {
+    if shutdown.is_shutdown() {
+        return;
+    }
+
+    for request in catalog.start_notifies() {
+        info!("notifying secondaries: {:?} of zone: {}",
+              request.get_secondaries(),
+              request.get_origin());
+
+        // each NOTIFY is retransmitted until the secondary responds, RFC 1996 section 3.6
+        let timeouts = TimeoutConfig {
+            query: Duration::from_secs(NOTIFY_TIMEOUT),
+            ..TimeoutConfig::default()
+        };
+        let mut sender =
+            NotifySender::new(request.get_secondaries(), timeouts, NOTIFY_RETRIES, None, handle);
+
+        let origin = request.get_origin().clone();
+        let in_flight = shutdown.track();
+        let notifies = sender.notify(origin.clone(),
+                                     request.get_class(),
+                                     Some(request.get_soa().clone()));
+        handle.spawn(notifies.then(move |results| {
+            match results {
+                Ok(results) => {
+                    for (secondary, result) in results {
+                        match result {
+                            Ok(ResponseCode::NoError) => (),
+                            Ok(response_code) => {
+                                warn!("notify of zone: {} to secondary: {} answered: {}",
+                                      origin,
+                                      secondary,
+                                      response_code.to_str())
+                            }
+                            Err(e) => {
+                                warn!("notify of zone: {} to secondary: {} failed: {}",
+                                      origin,
+                                      secondary,
+                                      e)
+                            }
+                        }
+                    }
+                }
+                Err(e) => warn!("notify of zone: {} failed: {}", origin, e),
+            }
+            drop(in_flight);
+            Ok::<(), ()>(())
+        }));
+    }
+}
+
+/// Resolves the targets of all the apex aliases which are due, from the catalog or the forwarded
+///  zone of the target, and installs their addresses, see `Catalog::start_alias_refreshes`
+fn spawn_alias_refreshes(catalog: &Arc<Catalog>,
+                         query_forwarder: &QueryForwarder,
+                         shutdown: &ShutdownHandle,
+                         handle: &Handle) {
+    if shutdown.is_shutdown() {
+        return;
+    }
+
+    for request in catalog.start_alias_refreshes() {
+        debug!("resolving alias: {} of zone: {}",
+               request.get_target(),
+               request.get_origin());
+
+        if let Some(answers) = catalog.resolve_alias(&request) {
+            catalog.finish_alias_refresh(request.get_origin(), Ok(answers));
+            spawn_notifies(catalog, shutdown, handle);
+            continue;
+        }
+
+        let queries = request.get_queries();
+        let forwarder = match catalog.get_forward(&queries[0]) {
+            Some(forwarder) => forwarder,
+            None => {
+                let error = io::Error::new(io::ErrorKind::NotFound,
+                                           format!("no zone for the alias: {}",
+                                                   request.get_target()));
+                catalog.finish_alias_refresh(request.get_origin(), Err(error));
+                continue;
+            }
+        };
+
+        let catalog = catalog.clone();
+        let origin = request.get_origin().clone();
+        let notify_shutdown = shutdown.clone();
+        let notify_handle = handle.clone();
+        let in_flight = shutdown.track();
+        handle.spawn(query_forwarder.forward(forwarder.clone(), &queries[0], handle)
+            .join(query_forwarder.forward(forwarder, &queries[1], handle))
+            .map(|(a, aaaa)| {
+                let answers: Vec<Record> =
+                    a.get_answers().iter().chain(aaaa.get_answers()).cloned().collect();
+                answers
+            })
+            .then(move |result| {
+                catalog.finish_alias_refresh(&origin, result);
+                // the secondaries are notified of new addresses at the apex
+                spawn_notifies(&catalog, &notify_shutdown, &notify_handle);
+            drop(in_flight);
+            Ok::<(), ()>(())
+        }));
+    }
+}
+
+/// true if the request is a zone transfer, AXFR or IXFR
+fn is_transfer(request: &Message) -> bool {
+    request.get_message_type() == MessageType::Query && request.get_op_code() == OpCode::Query &&
+    request.get_queries().len() == 1 &&
+    match request.get_queries()[0].get_query_type() {
+        RecordType::AXFR | RecordType::IXFR => true,
+        _ => false,
+    }
+}
+
+/// Sends the goodbye of the mDNS responder when dropped, straight to the socket as the reactor
+///  may be gone
+struct MdnsGoodbye {
+    socket: std::net::UdpSocket,
+    group: SocketAddr,
+    responder: Rc<RefCell<MdnsResponder>>,
+}
 
-impl Future for Forever {
-    type Item = ();
-    type Error = io::Error;
+impl Drop for MdnsGoodbye {
+    fn drop(&mut self) {
+        let goodbye = match self.responder.borrow().goodbye() {
+            Some(goodbye) => goodbye,
+            None => return,
+        };
 
-    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
-        // run forever...
-        Ok(Async::NotReady)
+        match goodbye.to_vec() {
+            Ok(buffer) => {
+                if let Err(e) = self.socket.send_to(&buffer, self.group) {
+                    warn!("could not send the mDNS goodbye: {}", e);
+                }
+            }
+            Err(e) => warn!("could not encode the mDNS goodbye: {}", e),
+        }
     }
 }
@@ -0,0 +1,218 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! The shutdown of a `ServerFuture`, requested from any thread, and the requests still in flight
+
+use std::collections::HashMap;
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use futures::{Async, Future, Poll, Stream};
+use futures::task::{park, Task};
+
+struct ShutdownState {
+    requested: AtomicBool,
+    in_flight: AtomicUsize,
+    next_signal: AtomicUsize,
+    /// the tasks waiting on a `ShutdownSignal`, by its id
+    tasks: Mutex<HashMap<usize, Task>>,
+}
+
+/// Requests the shutdown of a `ServerFuture`, see `ServerFuture::shutdown_handle`
+///
+/// The handle may be cloned and sent to other threads, e.g. to shut the server down on a signal
+///  or a restart of the application embedding it.
+#[derive(Clone)]
+pub struct ShutdownHandle {
+    state: Arc<ShutdownState>,
+}
+
+impl ShutdownHandle {
+    /// Creates a handle, of which the shutdown is not yet requested
+    pub fn new() -> Self {
+        ShutdownHandle {
+            state: Arc::new(ShutdownState {
+                requested: AtomicBool::new(false),
+                in_flight: AtomicUsize::new(0),
+                next_signal: AtomicUsize::new(0),
+                tasks: Mutex::new(HashMap::new()),
+            }),
+        }
+    }
+
+    /// Requests the shutdown, the listeners stop accepting connections and `ServerFuture::listen`
+    ///  returns once the requests in flight are answered, or the grace period expires
+    ///
+    /// A shutdown is final, requesting it again has no effect.
+    pub fn shutdown(&self) {
+        if self.state.requested.swap(true, Ordering::SeqCst) {
+            return;
+        }
+
+        let tasks = self.state.tasks.lock().unwrap(); // poison errors should panic
+        for task in tasks.values() {
+            task.unpark();
+        }
+    }
+
+    /// Returns true if the shutdown was requested
+    pub fn is_shutdown(&self) -> bool {
+        self.state.requested.load(Ordering::SeqCst)
+    }
+
+    /// A future which completes once the shutdown is requested
+    pub fn signal(&self) -> ShutdownSignal {
+        ShutdownSignal {
+            state: self.state.clone(),
+            id: self.state.next_signal.fetch_add(1, Ordering::SeqCst),
+        }
+    }
+
+    /// Counts a request, or a connection, as in flight until the returned guard is dropped
+    pub fn track(&self) -> InFlight {
+        self.state.in_flight.fetch_add(1, Ordering::SeqCst);
+        InFlight { state: self.state.clone() }
+    }
+
+    /// The number of requests in flight, e.g. forwarded and awaiting the response of upstream, and
+    ///  of open connections, see `Draining`
+    pub fn get_in_flight(&self) -> usize {
+        self.state.in_flight.load(Ordering::SeqCst)
+    }
+}
+
+impl Default for ShutdownHandle {
+    fn default() -> Self {
+        ShutdownHandle::new()
+    }
+}
+
+/// Completes once the shutdown of its `ShutdownHandle` is requested
+pub struct ShutdownSignal {
+    state: Arc<ShutdownState>,
+    id: usize,
+}
+
+impl Future for ShutdownSignal {
+    type Item = ();
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<(), io::Error> {
+        if self.state.requested.load(Ordering::SeqCst) {
+            return Ok(Async::Ready(()));
+        }
+
+        {
+            let mut tasks = self.state.tasks.lock().unwrap(); // poison errors should panic
+            tasks.insert(self.id, park());
+        }
+
+        // the shutdown may have been requested while the task was registered
+        if self.state.requested.load(Ordering::SeqCst) {
+            Ok(Async::Ready(()))
+        } else {
+            Ok(Async::NotReady)
+        }
+    }
+}
+
+impl Drop for ShutdownSignal {
+    fn drop(&mut self) {
+        let mut tasks = self.state.tasks.lock().unwrap(); // poison errors should panic
+        tasks.remove(&self.id);
+    }
+}
+
+/// A connection which knows whether it is idle, see `Draining`
+pub trait DrainStream: Stream {
+    /// Returns true if no request is being read, none is awaiting its response, and no response
+    ///  is being written, as of the last `poll` which returned `NotReady`
+    fn is_idle(&self) -> bool;
+}
+
+/// Counts a connection as in flight, see `ShutdownHandle::track`, until it ends, and ends it once
+///  the shutdown is requested and it is idle
+///
+/// The connection thus stays open after the shutdown until the responses already queued on it,
+///  e.g. of a zone transfer, are written, and the requests in flight on it are answered.
+pub struct Draining<S> {
+    stream: S,
+    signal: ShutdownSignal,
+    _in_flight: InFlight,
+}
+
+impl<S> Draining<S> {
+    pub fn new(stream: S, shutdown: &ShutdownHandle) -> Self {
+        Draining {
+            stream: stream,
+            signal: shutdown.signal(),
+            _in_flight: shutdown.track(),
+        }
+    }
+}
+
+impl<S: DrainStream<Error = io::Error>> Stream for Draining<S> {
+    type Item = S::Item;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Option<S::Item>, io::Error> {
+        let result = try!(self.stream.poll());
+        if let Async::NotReady = result {
+            // the signal is polled in any case, so that the shutdown wakes the connection up
+            if let Async::Ready(()) = try!(self.signal.poll()) {
+                if self.stream.is_idle() {
+                    debug!("shutting down, closing the idle connection");
+                    return Ok(Async::Ready(None));
+                }
+            }
+        }
+
+        Ok(result)
+    }
+}
+
+/// A request in flight, until dropped, see `ShutdownHandle::track`
+pub struct InFlight {
+    state: Arc<ShutdownState>,
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.state.in_flight.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Ends the underlying Stream once the shutdown is requested, e.g. the connections accepted by a
+///  listener, which is then dropped and closed
+pub struct UntilShutdown<S> {
+    stream: S,
+    signal: ShutdownSignal,
+}
+
+impl<S> UntilShutdown<S> {
+    pub fn new(stream: S, shutdown: &ShutdownHandle) -> Self {
+        UntilShutdown {
+            stream: stream,
+            signal: shutdown.signal(),
+        }
+    }
+}
+
+impl<S: Stream<Error = io::Error>> Stream for UntilShutdown<S> {
+    type Item = S::Item;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Option<S::Item>, io::Error> {
+        if let Async::Ready(()) = try!(self.signal.poll()) {
+            debug!("shutting down, no longer accepting");
+            return Ok(Async::Ready(None));
+        }
+
+        self.stream.poll()
+    }
+}
@@ -0,0 +1,214 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! The sockets passed by systemd with socket activation, `LISTEN_FDS`, see `sd_listen_fds(3)`
+
+use std::env;
+use std::io;
+use std::net::{TcpListener, UdpSocket};
+
+/// The first file descriptor passed by systemd, the others follow it
+pub const SD_LISTEN_FDS_START: i32 = 3;
+/// The name, `FileDescriptorName=` in the socket unit, of the TCP sockets for DNS over TLS
+pub const TLS_FD_NAME: &'static str = "tls";
+/// The name systemd gives the sockets of a unit without `FileDescriptorName=`
+const UNKNOWN_FD_NAME: &'static str = "unknown";
+
+/// A socket passed by systemd, already bound, to be registered with the `ServerFuture`
+#[derive(Debug)]
+pub enum ActivatedSocket {
+    /// A datagram socket, see `ServerFuture::register_socket`
+    Udp { name: String, socket: UdpSocket },
+    /// A stream socket, listening, see `ServerFuture::register_listener`, or
+    ///  `ServerFuture::register_tls_listener` if its name is `TLS_FD_NAME`
+    Tcp { name: String, listener: TcpListener },
+}
+
+impl ActivatedSocket {
+    /// The name of the socket, that of `LISTEN_FDNAMES`
+    pub fn get_name(&self) -> &str {
+        match *self {
+            ActivatedSocket::Udp { ref name, .. } |
+            ActivatedSocket::Tcp { ref name, .. } => name,
+        }
+    }
+
+    /// Returns true if this is a TCP socket for DNS over TLS, named `TLS_FD_NAME`
+    pub fn is_tls(&self) -> bool {
+        match *self {
+            ActivatedSocket::Tcp { ref name, .. } => name == TLS_FD_NAME,
+            ActivatedSocket::Udp { .. } => false,
+        }
+    }
+}
+
+/// Returns the sockets passed to this process by systemd, none without socket activation
+///
+/// The sockets are passed as the file descriptors from `SD_LISTEN_FDS_START`, `LISTEN_FDS` of
+///  them, for the process `LISTEN_PID`, with their names in `LISTEN_FDNAMES`. The variables are
+///  removed from the environment, and the descriptors are closed on exec, so that they are not
+///  passed on to child processes. Sockets which are neither UDP nor TCP, over IPv4 or IPv6, are
+///  ignored and left open. A TCP socket which is not listening, i.e. a connection passed by a
+///  socket unit with `Accept=yes`, is an error.
+///
+/// Since the sockets stay bound while the server restarts, e.g. `systemctl restart`, the
+///  requests which arrive meanwhile are queued for the new process rather than refused.
+pub fn listen_fds() -> io::Result<Vec<ActivatedSocket>> {
+    let pid = env::var("LISTEN_PID").ok();
+    let fds = env::var("LISTEN_FDS").ok();
+    let names = env::var("LISTEN_FDNAMES").ok();
+    env::remove_var("LISTEN_PID");
+    env::remove_var("LISTEN_FDS");
+    env::remove_var("LISTEN_FDNAMES");
+
+    let (pid, fds) = match (pid, fds) {
+        (Some(pid), Some(fds)) => (pid, fds),
+        _ => return Ok(Vec::new()),
+    };
+
+    // the variables may have been inherited from a parent which was activated
+    let pid: u32 = try!(pid.trim()
+        .parse()
+        .map_err(|_| invalid_env(format!("LISTEN_PID is not a pid: {}", pid))));
+    if Some(pid) != sys::get_pid() {
+        debug!("ignoring LISTEN_FDS, LISTEN_PID: {} is another process", pid);
+        return Ok(Vec::new());
+    }
+
+    let count: usize = try!(fds.trim()
+        .parse()
+        .map_err(|_| invalid_env(format!("LISTEN_FDS is not a count: {}", fds))));
+    let names: Vec<String> = match names {
+        Some(names) => names.split(':').map(|name| name.to_string()).collect(),
+        None => Vec::new(),
+    };
+    if !names.is_empty() && names.len() != count {
+        return Err(invalid_env(format!("LISTEN_FDNAMES has {} names for {} fds",
+                                       names.len(),
+                                       count)));
+    }
+
+    let mut sockets: Vec<ActivatedSocket> = Vec::with_capacity(count);
+    for index in 0..count {
+        let fd = SD_LISTEN_FDS_START + index as i32;
+        let name = names.get(index)
+            .cloned()
+            .unwrap_or_else(|| UNKNOWN_FD_NAME.to_string());
+
+        match try!(sys::adopt(fd, name)) {
+            Some(socket) => {
+                debug!("adopted socket fd: {} {:?}", fd, socket);
+                sockets.push(socket);
+            }
+            None => warn!("ignoring socket fd: {}, it is neither UDP nor TCP over IP", fd),
+        }
+    }
+
+    Ok(sockets)
+}
+
+fn invalid_env(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+#[cfg(unix)]
+mod sys {
+    use std::io;
+    use std::mem;
+    use std::net::{TcpListener, UdpSocket};
+    use std::os::unix::io::FromRawFd;
+
+    use libc;
+
+    use super::ActivatedSocket;
+
+    pub fn get_pid() -> Option<u32> {
+        // getpid can not fail
+        Some(unsafe { libc::getpid() } as u32)
+    }
+
+    /// Takes ownership of the socket, if it is UDP or TCP over IPv4 or IPv6
+    pub fn adopt(fd: i32, name: String) -> io::Result<Option<ActivatedSocket>> {
+        // the descriptors are checked before being owned, which closes them once dropped
+        unsafe {
+            if libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) < 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            let mut addr: libc::sockaddr_storage = mem::zeroed();
+            let mut addr_len = mem::size_of::<libc::sockaddr_storage>() as libc::socklen_t;
+            if libc::getsockname(fd,
+                                 &mut addr as *mut _ as *mut libc::sockaddr,
+                                 &mut addr_len) < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            match addr.ss_family as i32 {
+                libc::AF_INET | libc::AF_INET6 => (),
+                _ => return Ok(None),
+            }
+
+            let mut socket_type: libc::c_int = 0;
+            let mut type_len = mem::size_of::<libc::c_int>() as libc::socklen_t;
+            if libc::getsockopt(fd,
+                                libc::SOL_SOCKET,
+                                libc::SO_TYPE,
+                                &mut socket_type as *mut _ as *mut libc::c_void,
+                                &mut type_len) < 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            match socket_type {
+                libc::SOCK_DGRAM => {
+                    Ok(Some(ActivatedSocket::Udp {
+                        name: name,
+                        socket: UdpSocket::from_raw_fd(fd),
+                    }))
+                }
+                libc::SOCK_STREAM => {
+                    // with Accept=yes systemd passes a connection, rather than a listener
+                    let mut accepting: libc::c_int = 0;
+                    let mut accepting_len = mem::size_of::<libc::c_int>() as libc::socklen_t;
+                    if libc::getsockopt(fd,
+                                        libc::SOL_SOCKET,
+                                        libc::SO_ACCEPTCONN,
+                                        &mut accepting as *mut _ as *mut libc::c_void,
+                                        &mut accepting_len) < 0 {
+                        return Err(io::Error::last_os_error());
+                    }
+                    if accepting == 0 {
+                        return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                                  format!("socket fd: {} is not listening, \
+                                                           Accept=yes is not supported",
+                                                          fd)));
+                    }
+
+                    Ok(Some(ActivatedSocket::Tcp {
+                        name: name,
+                        listener: TcpListener::from_raw_fd(fd),
+                    }))
+                }
+                _ => Ok(None),
+            }
+        }
+    }
+}
+
+/// Without systemd there is no socket activation, `LISTEN_PID` never matches
+#[cfg(not(unix))]
+mod sys {
+    use std::io;
+
+    use super::ActivatedSocket;
+
+    pub fn get_pid() -> Option<u32> {
+        None
+    }
+
+    pub fn adopt(_: i32, _: String) -> io::Result<Option<ActivatedSocket>> {
+        Ok(None)
+    }
+}
@@ -0,0 +1,149 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::io;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use futures::{Async, Future, Poll, Stream};
+use tokio_core::io::Io;
+use tokio_core::reactor::{Handle, Timeout};
+
+use trust_dns::client::TimeoutConfig;
+use trust_dns::tcp::TcpStream;
+
+use server::DrainStream;
+
+/// Limits applied to each inbound TCP (and TLS) connection.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TcpLimits {
+    /// connections which do not start a request within this time are closed
+    pub idle_timeout: Duration,
+    /// once the first byte of a request is read, the entire request must arrive within this time
+    pub read_timeout: Duration,
+    /// each response must be entirely written within this time
+    pub write_timeout: Duration,
+    /// the largest length prefix which will be accepted for a request
+    pub max_message_len: u16,
+    /// the number of requests which may be read without any response having been written back
+    pub max_unanswered: usize,
+}
+
+impl TcpLimits {
+    /// Limits with the specified idle timeout and the defaults for everything else.
+    pub fn new(idle_timeout: Duration) -> Self {
+        TcpLimits { idle_timeout: idle_timeout, ..TcpLimits::default() }
+    }
+}
+
+impl Default for TcpLimits {
+    fn default() -> Self {
+        TcpLimits {
+            idle_timeout: Duration::from_secs(5),
+            read_timeout: Duration::from_secs(2),
+            write_timeout: Duration::from_secs(2),
+            max_message_len: u16::max_value(),
+            max_unanswered: 16,
+        }
+    }
+}
+
+impl From<TimeoutConfig> for TcpLimits {
+    /// Limits with the idle, read and write timeouts, and the defaults for everything else
+    fn from(timeouts: TimeoutConfig) -> Self {
+        TcpLimits {
+            idle_timeout: timeouts.tcp_idle,
+            read_timeout: timeouts.read,
+            write_timeout: timeouts.write,
+            ..TcpLimits::default()
+        }
+    }
+}
+
+/// This wraps a `TcpStream` and closes the connection on abusive behavior.
+///
+/// The idle timeout is left to `TimeoutStream`, this guards against slow partial requests
+///  (slowloris), oversized length prefixes, and peers which flood requests faster than they
+///  are answered (or with requests that are never answered, e.g. malformed messages).
+pub struct TcpGuardStream<S> {
+    stream: TcpStream<S>,
+    reactor_handle: Handle,
+    limits: TcpLimits,
+    read_deadline: Option<Timeout>,
+}
+
+impl<S> TcpGuardStream<S> {
+    pub fn new(mut stream: TcpStream<S>, limits: TcpLimits, reactor_handle: Handle) -> Self {
+        stream.set_max_message_len(limits.max_message_len);
+        // reads are timed out by poll() below, which also logs the peer
+        stream.set_timeouts(Duration::from_millis(0), limits.write_timeout, reactor_handle.clone());
+
+        TcpGuardStream {
+            stream: stream,
+            reactor_handle: reactor_handle,
+            limits: limits,
+            read_deadline: None,
+        }
+    }
+}
+
+impl<S: Io> DrainStream for TcpGuardStream<S> {
+    fn is_idle(&self) -> bool {
+        !self.stream.is_receiving() && !self.stream.is_sending() &&
+        self.stream.unanswered_messages() == 0
+    }
+}
+
+impl<S: Io> Stream for TcpGuardStream<S> {
+    type Item = (Vec<u8>, SocketAddr);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        match try!(self.stream.poll()) {
+            Async::Ready(Some(message)) => {
+                self.read_deadline = None;
+
+                if self.stream.unanswered_messages() > self.limits.max_unanswered {
+                    warn!("closing connection from: {}, too many unanswered requests",
+                          self.stream.peer_addr());
+                    return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                              format!("more than {} unanswered requests",
+                                                      self.limits.max_unanswered)));
+                }
+
+                Ok(Async::Ready(Some(message)))
+            }
+            Async::Ready(None) => Ok(Async::Ready(None)),
+            Async::NotReady => {
+                if !self.stream.is_receiving() {
+                    self.read_deadline = None;
+                    return Ok(Async::NotReady);
+                }
+
+                // a request has been started, it needs to complete before the deadline
+                if self.read_deadline.is_none() &&
+                   self.limits.read_timeout > Duration::from_millis(0) {
+                    self.read_deadline = Some(try!(Timeout::new(self.limits.read_timeout,
+                                                                &self.reactor_handle)));
+                }
+
+                if let Some(ref mut read_deadline) = self.read_deadline {
+                    try_ready!(read_deadline.poll());
+
+                    warn!("closing connection from: {}, request not received within {:?}",
+                          self.stream.peer_addr(),
+                          self.limits.read_timeout);
+                    return Err(io::Error::new(io::ErrorKind::TimedOut,
+                                              format!("partial request for longer than {:?}",
+                                                      self.limits.read_timeout)));
+                }
+
+                Ok(Async::NotReady)
+            }
+        }
+    }
+}
@@ -1,5 +1,7 @@
+use std::cell::Cell;
 use std::io;
 use std::mem;
+use std::rc::Rc;
 use std::time::Duration;
 
 use futures::{Async, Future, Poll, Stream};
@@ -11,7 +13,7 @@ use tokio_core::reactor::{Handle, Timeout};
 pub struct TimeoutStream<S> {
     stream: S,
     reactor_handle: Handle,
-    timeout_duration: Duration,
+    timeout_duration: Rc<Cell<Duration>>,
     timeout: Option<Timeout>,
 }
 
@@ -24,11 +26,16 @@ impl<S> TimeoutStream<S> {
         Ok(TimeoutStream {
             stream: stream,
             reactor_handle: reactor_handle,
-            timeout_duration: timeout_duration,
+            timeout_duration: Rc::new(Cell::new(timeout_duration)),
             timeout: timeout,
         })
     }
 
+    /// Returns the timeout duration, a change takes effect when the timeout is next reset
+    pub fn get_timeout_duration(&self) -> Rc<Cell<Duration>> {
+        self.timeout_duration.clone()
+    }
+
     fn timeout(timeout_duration: Duration, reactor_handle: &Handle) -> io::Result<Option<Timeout>> {
         if timeout_duration > Duration::from_millis(0) {
             Ok(Some(try!(Timeout::new(timeout_duration, reactor_handle))))
@@ -50,7 +57,8 @@ impl<S, I> Stream for TimeoutStream<S>
             r @ Ok(Async::Ready(_)) |
             r @ Err(_) => {
                 // reset the timeout to wait for the next request...
-                let timeout = try!(Self::timeout(self.timeout_duration, &self.reactor_handle));
+                let timeout = try!(Self::timeout(self.timeout_duration.get(),
+                                                 &self.reactor_handle));
                 drop(mem::replace(&mut self.timeout, timeout));
 
                 return r;
@@ -64,7 +72,7 @@ impl<S, I> Stream for TimeoutStream<S>
                 match try_ready!(self.timeout.as_mut().unwrap().poll()) {
                     () => {
             debug!("timeout on stream");
-            return Err(io::Error::new(io::ErrorKind::TimedOut, format!("nothing ready in {:?}", self.timeout_duration)))
+            return Err(io::Error::new(io::ErrorKind::TimedOut, format!("nothing ready in {:?}", self.timeout_duration.get())))
           }
                 }
             }
@@ -0,0 +1,86 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::io;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use futures::{failed, Future, Stream};
+use tokio_core::reactor::{Handle, Timeout};
+
+use trust_dns::op::Message;
+use trust_dns::tcp::TcpClientStream;
+
+/// Forwards an UPDATE received by a slave zone to the primary master, RFC 2136 section 6.
+///
+/// The update is sent over a new TCP connection exactly as it was received, a SIG(0) or TSIG is
+///  calculated over those bytes, so that the primary is able to authorize it, and the primary's
+///  response is returned for relaying back to the original requestor.
+///
+/// # Arguments
+///
+/// * `update` - the update message as received from the requestor
+/// * `buffer` - the bytes of the update as received, see `Request::buffer`
+/// * `primary` - address of the primary master for the zone
+/// * `timeout` - the response must be received within this time
+/// * `handle` - reactor on which the connection will be made
+pub fn forward_update(update: &Message,
+                      buffer: Vec<u8>,
+                      primary: SocketAddr,
+                      timeout: Duration,
+                      handle: &Handle)
+                      -> Box<Future<Item = Message, Error = io::Error>> {
+    let id = update.get_id();
+
+    let timeout = match Timeout::new(timeout, handle) {
+        Ok(timeout) => timeout,
+        Err(e) => return Box::new(failed(e)),
+    };
+
+    let (stream, mut stream_handle) = TcpClientStream::new(primary, handle.clone());
+    if let Err(e) = stream_handle.send(buffer) {
+        return Box::new(failed(e));
+    }
+
+    let response = stream.and_then(|stream| stream.into_future().map_err(|(e, _)| e))
+        .and_then(move |(buffer, _)| {
+            // the connection is only needed until the response is received
+            drop(stream_handle);
+
+            let buffer = try!(buffer.ok_or_else(|| {
+                io::Error::new(io::ErrorKind::UnexpectedEof,
+                               format!("connection closed by primary: {}", primary))
+            }));
+
+            let response = try!(Message::from_vec(&buffer).map_err(|e| {
+                io::Error::new(io::ErrorKind::InvalidData,
+                               format!("bad response from primary: {}: {}", primary, e))
+            }));
+
+            if response.get_id() != id {
+                return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                          format!("response id {} from primary: {} does not \
+                                                   match update: {}",
+                                                  response.get_id(),
+                                                  primary,
+                                                  id)));
+            }
+
+            Ok(response)
+        });
+
+    let timeout = timeout.and_then(move |_| {
+        Err::<Message, _>(io::Error::new(io::ErrorKind::TimedOut,
+                                         format!("no response to forwarded update from \
+                                                  primary: {}",
+                                                 primary)))
+    });
+
+    Box::new(response.select(timeout)
+        .map(|(response, _)| response)
+        .map_err(|(e, _)| e))
+}
@@ -0,0 +1,405 @@
+// Copyright 2015-2017 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::{failed, finished, Async, Future, Poll, Stream};
+use rand;
+use tokio_core::net::TcpStream as TokioTcpStream;
+use tokio_core::reactor::{Handle, Timeout};
+
+use trust_dns::client::{ClientStreamHandle, TimeoutConfig};
+use trust_dns::clock::{Clock, SystemClock};
+use trust_dns::op::{Message, ResponseCode, UpdateMessage};
+use trust_dns::rr::{RData, RecordType};
+use trust_dns::rr::dnssec::{TSigner, TsigChain};
+use trust_dns::tcp::TcpClientStream;
+
+use authority::{InboundTransfer, TransferRejection, TransferRequest};
+
+/// The most messages of a response which may be left unsigned between signed ones, RFC 8945
+///  section 5.3.1
+const MAX_UNSIGNED_MESSAGES: usize = 99;
+
+type TransferFuture = Box<Future<Item = InboundTransfer, Error = io::Error>>;
+type RefreshFuture = Box<Future<Item = Option<InboundTransfer>, Error = io::Error>>;
+
+/// Refreshes a Slave zone from its primary master, requesting a zone transfer, IXFR or AXFR,
+///  RFC 1995 and RFC 5936, if the primary has a newer serial.
+///
+/// The serial of the SOA of the primary is queried first, RFC 1034 section 4.3.5, a zone is only
+///  transferred if it is newer than that of the zone, or if the zone has none. The messages of
+///  the transfer are received over a new TCP connection until the transfer is complete, or
+///  rejected by its limits, it is then to be installed with `Authority::install_transfer`. A
+///  primary which answers an IXFR with NOTIMP or FORMERR is asked for an AXFR instead.
+///
+/// The queries are sent from the source address of the request, if it has one, and signed with
+///  its TSIG key; the responses must then be signed by the primary, RFC 8945 section 5.3.
+///
+/// # Arguments
+///
+/// * `request` - the transfer to request, see `Authority::start_refresh`
+/// * `timeout` - the serial query, and the whole transfer, must each be received within this time
+/// * `handle` - reactor on which the connection will be made
+///
+/// # Return value
+///
+/// The transfer, or None if the zone is up to date, see `Catalog::finish_refresh`
+pub fn request_transfer(request: &TransferRequest,
+                        timeout: Duration,
+                        handle: &Handle)
+                        -> RefreshFuture {
+    let serial = match request.get_serial() {
+        Some(serial) => serial,
+        None => return Box::new(receive_transfer(request, timeout, handle).map(Some)),
+    };
+
+    let request = request.clone();
+    let handle = handle.clone();
+    let serial_query = query_serial(&request, timeout, &handle);
+    Box::new(serial_query.and_then(move |primary_serial| -> RefreshFuture {
+        // with serial number arithmetic, RFC 1982
+        if primary_serial.wrapping_sub(serial) as i32 <= 0 {
+            debug!("zone: {} serial: {} is up to date with primary: {}, serial: {}",
+                   request.get_origin(),
+                   serial,
+                   request.get_primary(),
+                   primary_serial);
+            return Box::new(finished(None));
+        }
+
+        let axfr = request.to_axfr();
+        let transfer = receive_transfer(&request, timeout, &handle);
+        Box::new(transfer.and_then(move |transfer| -> TransferFuture {
+                match transfer.get_rejection() {
+                    Some(&TransferRejection::ErrorResponse(ResponseCode::NotImp)) |
+                    Some(&TransferRejection::ErrorResponse(ResponseCode::FormErr)) => {
+                        info!("primary: {} does not implement IXFR, requesting AXFR of: {}",
+                              axfr.get_primary(),
+                              axfr.get_origin());
+                        receive_transfer(&axfr, timeout, &handle)
+                    }
+                    _ => Box::new(finished(transfer)),
+                }
+            })
+            .map(Some))
+    }))
+}
+
+/// Queries the serial of the SOA of the zone on the primary
+fn query_serial(request: &TransferRequest,
+                timeout: Duration,
+                handle: &Handle)
+                -> Box<Future<Item = u32, Error = io::Error>> {
+    let primary = request.get_primary();
+    let origin = request.get_origin().clone();
+    let id: u16 = rand::random();
+    let (buffer, mut verifier) = match encode_query(request, request.to_soa_query(id)) {
+        Ok(encoded) => encoded,
+        Err(e) => return Box::new(failed(e)),
+    };
+
+    let timeout = match Timeout::new(timeout, handle) {
+        Ok(timeout) => timeout,
+        Err(e) => return Box::new(failed(e)),
+    };
+
+    let (stream, mut stream_handle) = connect(request, handle);
+    if let Err(e) = stream_handle.send(buffer) {
+        return Box::new(failed(e));
+    }
+
+    let soa_origin = origin.clone();
+    let serial = stream.and_then(move |stream| {
+            stream.filter_map(move |buffer| {
+                    let decoded = Message::from_vec(&buffer);
+                    match decoded {
+                        Ok(ref message) if message.get_id() != id => None,
+                        Ok(message) => Some((message, buffer)),
+                        Err(e) => {
+                            warn!("bad soa response from primary: {}: {}", primary, e);
+                            None
+                        }
+                    }
+                })
+                .into_future()
+                .map_err(|(e, _)| e)
+        })
+        .and_then(move |(response, _)| {
+            // the connection is closed once the response is received
+            drop(stream_handle);
+
+            let (message, buffer) = match response {
+                Some(response) => response,
+                None => {
+                    return Err(io::Error::new(io::ErrorKind::UnexpectedEof,
+                                              format!("connection closed by primary: {}",
+                                                      primary)))
+                }
+            };
+            if let Some(ref mut verifier) = verifier {
+                try!(verifier.verify(&message, &buffer, primary));
+            }
+            if message.get_response_code() != ResponseCode::NoError {
+                return Err(io::Error::new(io::ErrorKind::Other,
+                                          format!("primary: {} answered the soa query with: \
+                                                   {:?}",
+                                                  primary,
+                                                  message.get_response_code())));
+            }
+
+            message.get_answers()
+                .iter()
+                .filter(|record| record.get_name() == &soa_origin)
+                .filter_map(|record| match *record.get_rdata() {
+                    RData::SOA(ref soa) => Some(soa.get_serial()),
+                    _ => None,
+                })
+                .next()
+                .ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::InvalidData,
+                                   format!("no soa for zone: {} from primary: {}",
+                                           soa_origin,
+                                           primary))
+                })
+        });
+
+    let timeout = timeout.and_then(move |_| {
+        Err::<u32, _>(io::Error::new(io::ErrorKind::TimedOut,
+                                     format!("soa query for zone: {} to primary: {} timed out",
+                                             origin,
+                                             primary)))
+    });
+
+    Box::new(serial.select(timeout)
+        .map(|(serial, _)| serial)
+        .map_err(|(e, _)| e))
+}
+
+/// Requests the transfer, without falling back to AXFR
+fn receive_transfer(request: &TransferRequest,
+                    timeout: Duration,
+                    handle: &Handle)
+                    -> TransferFuture {
+    let primary = request.get_primary();
+    let id: u16 = rand::random();
+    let (buffer, verifier) = match encode_query(request, request.to_message(id)) {
+        Ok(encoded) => encoded,
+        Err(e) => return Box::new(failed(e)),
+    };
+
+    let timeout = match Timeout::new(timeout, handle) {
+        Ok(timeout) => timeout,
+        Err(e) => return Box::new(failed(e)),
+    };
+
+    let (stream, mut stream_handle) = connect(request, handle);
+    if let Err(e) = stream_handle.send(buffer) {
+        return Box::new(failed(e));
+    }
+
+    let limits = *request.get_limits();
+    let request_serial = request.get_serial();
+    let transfer = stream.and_then(move |stream| {
+        ReceiveTransfer {
+            stream: stream,
+            stream_handle: stream_handle,
+            id: id,
+            primary: primary,
+            transfer: Some(InboundTransfer::with_serial(limits, request_serial)),
+            verifier: verifier,
+        }
+    });
+
+    let origin = request.get_origin().clone();
+    let timeout = timeout.and_then(move |_| {
+        Err::<InboundTransfer, _>(io::Error::new(io::ErrorKind::TimedOut,
+                                                 format!("transfer of zone: {} from primary: \
+                                                          {} timed out",
+                                                         origin,
+                                                         primary)))
+    });
+
+    Box::new(transfer.select(timeout)
+        .map(|(transfer, _)| transfer)
+        .map_err(|(e, _)| e))
+}
+
+/// Receives the messages of a transfer from the primary into an `InboundTransfer`
+struct ReceiveTransfer {
+    stream: TcpClientStream<TokioTcpStream>,
+    // the connection is closed when this is dropped
+    #[allow(dead_code)]
+    stream_handle: Box<ClientStreamHandle>,
+    id: u16,
+    primary: SocketAddr,
+    transfer: Option<InboundTransfer>,
+    verifier: Option<ResponseVerifier>,
+}
+
+impl Future for ReceiveTransfer {
+    type Item = InboundTransfer;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        loop {
+            let buffer = match try_ready!(self.stream.poll()) {
+                Some(buffer) => buffer,
+                None => {
+                    // the primary closed the connection, an incomplete transfer is rejected as
+                    //  malformed when it is installed
+                    warn!("connection closed by primary: {} during transfer", self.primary);
+                    break;
+                }
+            };
+
+            let message = try!(Message::from_vec(&buffer).map_err(|e| {
+                io::Error::new(io::ErrorKind::InvalidData,
+                               format!("bad response from primary: {}: {}", self.primary, e))
+            }));
+
+            if message.get_id() != self.id {
+                warn!("ignoring response id {} from primary: {}, expected: {}",
+                      message.get_id(),
+                      self.primary,
+                      self.id);
+                continue;
+            }
+
+            if let Some(ref mut verifier) = self.verifier {
+                try!(verifier.verify(&message, &buffer, self.primary));
+            }
+
+            let transfer = self.transfer.as_mut().expect("transfer polled after completion");
+            let _ = transfer.add_message(&message, buffer.len());
+            if transfer.is_complete() {
+                // the last message must be signed, section 5.3.1
+                if let Some(ref verifier) = self.verifier {
+                    if !verifier.is_last_signed() {
+                        return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                                  format!("last response from primary: {} is \
+                                                           not signed",
+                                                          self.primary)));
+                    }
+                }
+                break;
+            }
+        }
+
+        Ok(Async::Ready(self.transfer.take().expect("transfer polled after completion")))
+    }
+}
+
+/// Connects to the primary, from the source address of the request if it has one
+fn connect(request: &TransferRequest,
+           handle: &Handle)
+           -> (Box<Future<Item = TcpClientStream<TokioTcpStream>, Error = io::Error>>,
+               Box<ClientStreamHandle>) {
+    let bind_addr = request.get_source().map(|source| SocketAddr::new(source, 0));
+    TcpClientStream::with_bind_addr(request.get_primary(),
+                                    bind_addr,
+                                    handle.clone(),
+                                    TimeoutConfig::default())
+}
+
+/// Encodes the query, signed with the TSIG key of the primary if the request has one, along with
+///  the verifier of the responses to it
+fn encode_query(request: &TransferRequest,
+                mut query: Message)
+                -> io::Result<(Vec<u8>, Option<ResponseVerifier>)> {
+    let verifier = match request.get_tsigner() {
+        Some(tsigner) => {
+            let now = SystemClock.now() as u64;
+            let mac = try!(query.sign_tsig(tsigner, TsigChain::Request, now).map_err(|e| {
+                io::Error::new(io::ErrorKind::Other, format!("could not sign query: {}", e))
+            }));
+            Some(ResponseVerifier::new(tsigner.clone(), mac))
+        }
+        None => None,
+    };
+
+    let buffer = try!(query.to_vec().map_err(|e| {
+        io::Error::new(io::ErrorKind::InvalidData, format!("could not encode query: {}", e))
+    }));
+    Ok((buffer, verifier))
+}
+
+/// Verifies the TSIG of the responses of the primary to a signed query, RFC 8945 section 5.3
+///
+/// The first response must be signed, later ones of a transfer may be left unsigned, up to
+///  `MAX_UNSIGNED_MESSAGES` in a row, the MAC of the next signed one covers them.
+struct ResponseVerifier {
+    tsigner: Arc<TSigner>,
+    /// the MAC of the query, then that of the last signed response
+    prior_mac: Vec<u8>,
+    /// no response was verified yet
+    is_first: bool,
+    /// the unsigned responses since the last signed one, as they were received
+    unsigned: Vec<u8>,
+    unsigned_count: usize,
+}
+
+impl ResponseVerifier {
+    fn new(tsigner: Arc<TSigner>, query_mac: Vec<u8>) -> Self {
+        ResponseVerifier {
+            tsigner: tsigner,
+            prior_mac: query_mac,
+            is_first: true,
+            unsigned: Vec::new(),
+            unsigned_count: 0,
+        }
+    }
+
+    /// Verifies the next response, or keeps it for the next signed one if it is not signed
+    fn verify(&mut self, message: &Message, buffer: &[u8], primary: SocketAddr) -> io::Result<()> {
+        let is_signed = UpdateMessage::get_sig0(message)
+            .iter()
+            .any(|record| record.get_rr_type() == RecordType::TSIG);
+
+        if !is_signed {
+            self.unsigned_count += 1;
+            if self.is_first || self.unsigned_count > MAX_UNSIGNED_MESSAGES {
+                return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                          format!("response from primary: {} is not signed",
+                                                  primary)));
+            }
+
+            self.unsigned.extend_from_slice(buffer);
+            return Ok(());
+        }
+
+        let tsig = {
+            let chain = if self.is_first {
+                TsigChain::Response(&self.prior_mac)
+            } else {
+                TsigChain::Continuation(&self.prior_mac)
+            };
+            try!(self.tsigner
+                .verify_message(&self.unsigned, buffer, chain, SystemClock.now() as u64)
+                .map_err(|e| {
+                    io::Error::new(io::ErrorKind::InvalidData,
+                                   format!("response from primary: {} not verified: {}",
+                                           primary,
+                                           e))
+                }))
+        };
+
+        self.prior_mac = tsig.get_mac().to_vec();
+        self.is_first = false;
+        self.unsigned.clear();
+        self.unsigned_count = 0;
+        Ok(())
+    }
+
+    /// true if a response was verified, and none was received unsigned since
+    fn is_last_signed(&self) -> bool {
+        !self.is_first && self.unsigned_count == 0
+    }
+}
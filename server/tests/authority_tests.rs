@@ -7,13 +7,16 @@ extern crate trust_dns_server;
 
 use std::collections::BTreeMap;
 use std::net::*;
+use std::sync::{Arc, Mutex};
 
 use rusqlite::*;
 
+use trust_dns::clock::ManualClock;
 use trust_dns::rr::*;
 use trust_dns::rr::dnssec::*;
 use trust_dns::rr::rdata::*;
 use trust_dns::op::*;
+use trust_dns::serialize::txt::{Lexer, Parser};
 use trust_dns_server::authority::*;
 
 mod common;
@@ -129,6 +132,63 @@ fn test_authority() {
                    .clone());
 }
 
+#[test]
+fn test_empty_non_terminals() {
+    let mut example = create_example();
+    assert!(example.get_empty_non_terminals().is_empty());
+
+    for name in &["a.b.c.example.com.", "d.c.example.com.", "e.www.example.com."] {
+        example.upsert(Record::new()
+                           .name(Name::parse(name, None).unwrap())
+                           .ttl(86400)
+                           .rr_type(RecordType::A)
+                           .dns_class(DNSClass::IN)
+                           .rdata(RData::A(Ipv4Addr::new(93, 184, 216, 34)))
+                           .clone(),
+                       0);
+    }
+
+    // www.example.com. has records, and is not empty
+    assert_eq!(example.get_empty_non_terminals(),
+               vec![Name::parse("c.example.com.", None).unwrap(),
+                    Name::parse("b.c.example.com.", None).unwrap()]);
+
+    assert!(example.contains_name(&Name::parse("c.example.com.", None).unwrap()));
+    assert!(example.contains_name(&Name::parse("B.c.example.com.", None).unwrap()));
+    assert!(example.contains_name(&Name::parse("www.example.com.", None).unwrap()));
+    assert!(!example.contains_name(&Name::parse("x.c.example.com.", None).unwrap()));
+    assert!(!example.contains_name(&Name::parse("example.net.", None).unwrap()));
+}
+
+#[test]
+fn test_zone_apex() {
+    let example = create_example();
+    assert_eq!(example.serial(), Some(2015082403));
+    assert_eq!(example.soa().unwrap().get_mname(),
+               &Name::parse("sns.dns.icann.org.", None).unwrap());
+    assert_eq!(example.soa().unwrap().get_minimum(), example.get_minimum_ttl());
+
+    let mut ns = example.ns();
+    ns.sort();
+    assert_eq!(ns,
+               vec![&Name::parse("a.iana-servers.net.", None).unwrap(),
+                    &Name::parse("b.iana-servers.net.", None).unwrap()]);
+    assert!(example.dnskeys().is_empty());
+
+    let secure = create_secure_example();
+    assert_eq!(secure.dnskeys().len(), 1);
+    assert!(secure.dnskeys()[0].is_zone_key());
+
+    let empty = Authority::new(Name::parse("example.net.", None).unwrap(),
+                               BTreeMap::new(),
+                               ZoneType::Master,
+                               false,
+                               false);
+    assert_eq!(empty.serial(), None);
+    assert_eq!(empty.get_serial(), 0);
+    assert!(empty.ns().is_empty());
+}
+
 #[test]
 fn test_authorize() {
     let authority: Authority = create_example();
@@ -453,6 +513,41 @@ fn test_pre_scan() {
                Err(ResponseCode::FormErr));
 }
 
+#[test]
+fn test_check_rrset_limits() {
+    let up_name = Name::new().label("www").label("example").label("com");
+    let a = |last: u8, dns_class: DNSClass| {
+        Record::new()
+            .name(up_name.clone())
+            .ttl(if dns_class == DNSClass::IN { 86400 } else { 0 })
+            .rr_type(RecordType::A)
+            .dns_class(dns_class)
+            .rdata(RData::A(Ipv4Addr::new(93, 184, 216, last)))
+            .clone()
+    };
+
+    let mut authority: Authority = create_example();
+    let mut limits = RecordSetLimits::default();
+    limits.max_records(2);
+    authority.set_rrset_limits(limits);
+
+    // www.example.com. already has one A record
+    assert!(authority.check_rrset_limits(&[a(1, DNSClass::IN)]).is_ok());
+    assert_eq!(authority.check_rrset_limits(&[a(1, DNSClass::IN), a(2, DNSClass::IN)]),
+               Err(ResponseCode::Refused));
+
+    // deletes earlier in the update make room
+    assert!(authority.check_rrset_limits(&[a(34, DNSClass::NONE),
+                                           a(1, DNSClass::IN),
+                                           a(2, DNSClass::IN)])
+        .is_ok());
+
+    let mut delete_rrset = a(0, DNSClass::ANY);
+    delete_rrset.rdata(RData::NULL(NULL::new()));
+    assert!(authority.check_rrset_limits(&[delete_rrset, a(1, DNSClass::IN), a(2, DNSClass::IN)])
+        .is_ok());
+}
+
 #[test]
 fn test_update() {
     let new_name = Name::new().label("new").label("example").label("com");
@@ -659,6 +754,63 @@ fn test_update() {
     assert_eq!(serial + 6, authority.get_serial());
 }
 
+#[test]
+fn test_occlusion() {
+    let name = |name: &str| Name::parse(name, None).unwrap();
+    let record = |owner: &str, rdata: RData| {
+        Record::from_rdata(name(owner), 86400, rdata.to_record_type(), rdata)
+    };
+    let search = |authority: &Authority, owner: &str, rtype: RecordType| {
+        let mut query: Query = Query::new();
+        query.name(name(owner)).query_type(rtype);
+        authority.search(&query, false, SupportedAlgorithms::new()).len()
+    };
+
+    let mut authority: Authority = create_example();
+    authority.set_allow_update(true);
+
+    // a delegation with its glue, and data below it, and a DNAME with data below it
+    let updates = [record("sub.example.com.", RData::NS(name("ns.sub.example.com."))),
+                   record("ns.sub.example.com.", RData::A(Ipv4Addr::new(192, 0, 2, 1))),
+                   record("www.sub.example.com.", RData::A(Ipv4Addr::new(192, 0, 2, 2))),
+                   record("alias.example.com.", RData::DNAME(name("example.net."))),
+                   record("www.alias.example.com.", RData::A(Ipv4Addr::new(192, 0, 2, 3)))];
+    assert!(authority.update_records(&updates, false).expect("update failed"));
+
+    // the cuts themselves are answered
+    assert!(!authority.is_occluded(&name("sub.example.com.")));
+    assert!(!authority.is_occluded(&name("alias.example.com.")));
+    assert!(!authority.is_occluded(&name("www.example.com.")));
+    assert_eq!(search(&authority, "sub.example.com.", RecordType::NS), 1);
+    assert_eq!(search(&authority, "alias.example.com.", RecordType::DNAME), 1);
+
+    // but nothing below them, although it is stored
+    for occluded in &["ns.sub.example.com.", "www.sub.example.com.", "www.alias.example.com."] {
+        assert!(authority.is_occluded(&name(occluded)));
+        assert!(!authority.contains_name(&name(occluded)));
+        assert_eq!(search(&authority, occluded, RecordType::A), 0);
+        assert_eq!(authority.lookup(&name(occluded),
+                                    RecordType::A,
+                                    false,
+                                    SupportedAlgorithms::new())
+                       .len(),
+                   1);
+    }
+    assert_eq!(authority.get_occlusion(&name("a.www.sub.example.com."))
+                   .map(|rr_set| rr_set.get_record_type()),
+               Some(RecordType::NS));
+    assert_eq!(authority.get_occlusion(&name("www.alias.example.com."))
+                   .map(|rr_set| rr_set.get_record_type()),
+               Some(RecordType::DNAME));
+
+    // the occluded records are still transferred
+    let mut axfr: Query = Query::new();
+    axfr.name(name("example.com.")).query_type(RecordType::AXFR);
+    let transferred = authority.search(&axfr, false, SupportedAlgorithms::new());
+    assert!(transferred.iter().any(|r| *r.get_name() == name("www.alias.example.com.")));
+    assert!(transferred.iter().any(|r| *r.get_name() == name("www.sub.example.com.")));
+}
+
 #[test]
 fn test_zone_signing() {
     let authority: Authority = create_secure_example();
@@ -693,6 +845,247 @@ fn test_zone_signing() {
     }
 }
 
+#[test]
+fn test_import_zone_signing_key() {
+    use openssl::rsa::Rsa;
+
+    let mut authority: Authority = create_secure_example();
+    let origin = authority.get_origin().clone();
+
+    // the key of another provider signing the same zone
+    let other = KeyPair::from_rsa(Rsa::generate(2048).unwrap())
+        .unwrap()
+        .to_dnskey(Algorithm::RSASHA256)
+        .unwrap();
+
+    authority.import_zone_signing_key(other.clone()).expect("import failed");
+    authority.import_zone_signing_key(other.clone()).expect("import failed");
+    authority.secure_zone().expect("signing failed");
+
+    let results = authority.lookup(&origin, RecordType::DNSKEY, true, SupportedAlgorithms::all());
+    let dnskeys: Vec<&DNSKEY> = results.iter()
+        .filter_map(|r| if let &RData::DNSKEY(ref dnskey) = r.get_rdata() {
+            Some(dnskey)
+        } else {
+            None
+        })
+        .collect();
+
+    assert_eq!(dnskeys.len(), 2);
+    assert!(dnskeys.contains(&&other));
+
+    // the merged rrset is signed
+    assert!(results.iter().any(|r| if let &RData::SIG(ref rrsig) = r.get_rdata() {
+        rrsig.get_type_covered() == RecordType::DNSKEY
+    } else {
+        false
+    }));
+
+    let not_zone_key = DNSKEY::new(false,
+                                   false,
+                                   false,
+                                   Algorithm::RSASHA256,
+                                   other.get_public_key().to_vec());
+    assert!(authority.import_zone_signing_key(not_zone_key).is_err());
+}
+
+#[test]
+fn test_sign_zone_with_clock() {
+    let mut authority: Authority = create_secure_example();
+    let origin = authority.get_origin().clone();
+
+    authority.set_clock(Arc::new(ManualClock::new(1000)));
+    authority.secure_zone().expect("signing failed");
+
+    let results = authority.lookup(&origin, RecordType::SOA, true, SupportedAlgorithms::all());
+    let rrsigs: Vec<&SIG> = results.iter()
+        .filter_map(|r| if let &RData::SIG(ref rrsig) = r.get_rdata() {
+            Some(rrsig)
+        } else {
+            None
+        })
+        .collect();
+
+    assert!(!rrsigs.is_empty());
+    for rrsig in rrsigs {
+        assert_eq!(rrsig.get_sig_inception(), 1000);
+        // signed for a week
+        assert_eq!(rrsig.get_sig_expiration(), 1000 + 7 * 24 * 60 * 60);
+    }
+}
+
+#[test]
+#[cfg(feature = "parallel-signing")]
+fn test_parallel_signing() {
+    use chrono::Duration;
+    use openssl::rsa::Rsa;
+
+    let rsa = Rsa::generate(2048).unwrap();
+    let der = rsa.private_key_to_der().unwrap();
+
+    // a zone large enough to be split across the threads
+    let records = (0..1024u16)
+        .map(|i| {
+            Record::from_rdata(Name::parse(&format!("host{}.example.com.", i), None).unwrap(),
+                               86400,
+                               RecordType::A,
+                               RData::A(Ipv4Addr::new(192, 0, (i >> 8) as u8, i as u8)))
+        })
+        .collect::<Vec<Record>>();
+
+    let signed = |signing_threads: usize| {
+        let mut authority: Authority = create_example();
+        authority.update_records(&records, false).expect("update failed");
+        authority.set_clock(Arc::new(ManualClock::new(1000)));
+        authority.set_signing_threads(signing_threads);
+
+        let key = KeyPair::from_rsa(Rsa::private_key_from_der(&der).unwrap()).unwrap();
+        let origin = authority.get_origin().clone();
+        authority.add_secure_key(Signer::new(Algorithm::RSASHA256,
+                                              key,
+                                              origin,
+                                              Duration::weeks(1),
+                                              true,
+                                              true))
+            .expect("add key failed");
+        authority.secure_zone().expect("signing failed");
+        authority
+    };
+
+    let sequential = signed(1);
+    let parallel = signed(4);
+
+    assert!(sequential.get_records().values().all(|rrset| !rrset.get_rrsigs().is_empty()));
+    assert!(sequential.get_records() == parallel.get_records());
+}
+
+#[test]
+fn test_reject_invalid_rdata() {
+    let mut authority: Authority = create_example();
+    let name = Name::new().label("mail").label("example").label("com");
+    let invalid_null_mx = Record::new()
+        .name(name.clone())
+        .ttl(86400)
+        .rr_type(RecordType::MX)
+        .dns_class(DNSClass::IN)
+        .rdata(RData::MX(MX::new(10, Name::root())))
+        .clone();
+    let serial = authority.get_serial();
+
+    assert_eq!(authority.pre_scan(&[invalid_null_mx.clone()]), Err(ResponseCode::FormErr));
+    assert!(!authority.upsert(invalid_null_mx, serial));
+    assert!(authority.lookup(&name, RecordType::MX, false, SupportedAlgorithms::new()).is_empty());
+
+    let valid_null_mx = Record::new()
+        .name(name.clone())
+        .ttl(86400)
+        .rr_type(RecordType::MX)
+        .dns_class(DNSClass::IN)
+        .rdata(RData::MX(MX::new(0, Name::root())))
+        .clone();
+    assert!(authority.upsert(valid_null_mx, serial));
+}
+
+struct TestListener(Mutex<Vec<RecordChange>>);
+
+impl ChangeListener for TestListener {
+    fn records_changed(&self, origin: &Name, changes: &[RecordChange]) {
+        assert_eq!(origin, &Name::parse("example.com.", None).unwrap());
+        self.0.lock().unwrap().extend(changes.iter().cloned());
+    }
+}
+
+#[test]
+fn test_change_listener() {
+    let mut authority: Authority = create_example();
+    let listener = Arc::new(TestListener(Mutex::new(Vec::new())));
+    let change_listener: Arc<ChangeListener> = listener.clone();
+    let subscription = authority.add_change_listener(&change_listener);
+    drop(change_listener);
+
+    let name = Name::new().label("www").label("example").label("com");
+    let old = authority.lookup(&name, RecordType::A, false, SupportedAlgorithms::new())[0].clone();
+    let new = Record::new()
+        .name(name.clone())
+        .ttl(86400)
+        .rr_type(RecordType::A)
+        .dns_class(DNSClass::IN)
+        .rdata(RData::A(Ipv4Addr::new(93, 184, 216, 24)))
+        .clone();
+    let serial = authority.get_serial();
+
+    // unchanged
+    assert!(!authority.upsert(old.clone(), serial));
+    assert!(listener.0.lock().unwrap().is_empty());
+
+    assert!(authority.upsert(new.clone(), serial));
+    assert_eq!(*listener.0.lock().unwrap(), vec![RecordChange::Added(new.clone())]);
+    listener.0.lock().unwrap().clear();
+
+    // delete the RRSet
+    let delete = Record::new()
+        .name(name.clone())
+        .ttl(0)
+        .rr_type(RecordType::A)
+        .dns_class(DNSClass::ANY)
+        .rdata(RData::NULL(NULL::new()))
+        .clone();
+    assert!(authority.update_records(&[delete.clone()], true).unwrap());
+    assert_eq!(*listener.0.lock().unwrap(),
+               vec![RecordChange::Removed(old.clone()), RecordChange::Removed(new)]);
+    listener.0.lock().unwrap().clear();
+
+    // no longer notified once unsubscribed
+    subscription.unsubscribe();
+    assert!(authority.upsert(old.clone(), serial));
+    assert!(listener.0.lock().unwrap().is_empty());
+
+    // the authority does not keep a dropped listener alive
+    let dropped: Arc<ChangeListener> = Arc::new(TestListener(Mutex::new(Vec::new())));
+    let weak = Arc::downgrade(&dropped);
+    authority.add_change_listener(&dropped);
+    drop(dropped);
+    assert!(weak.upgrade().is_none());
+    assert!(authority.update_records(&[delete], true).unwrap());
+}
+
+#[test]
+fn test_ixfr_deltas() {
+    let mut authority: Authority = create_example();
+    authority.set_max_deltas(2);
+    let name = Name::new().label("www").label("example").label("com");
+    let serials: Vec<u32> = (1..4)
+        .map(|last| {
+            let serial = authority.get_serial();
+            let record = Record::from_rdata(name.clone(),
+                                            86400,
+                                            RecordType::A,
+                                            RData::A(Ipv4Addr::new(192, 0, 2, last)));
+            assert!(authority.update_records(&[record], true).unwrap());
+            serial
+        })
+        .collect();
+
+    // only the most recent are kept
+    assert_eq!(authority.get_deltas().len(), 2);
+    assert_eq!(authority.get_deltas()[0].get_old_serial(), serials[1]);
+
+    let records = authority.get_ixfr_records(serials[1]);
+    assert_eq!(records.len(), 8);
+    assert_eq!(records.first(), records.last());
+    assert_eq!(records.first().unwrap().get_rdata(),
+               authority.get_soa().unwrap().get_rdata());
+
+    let serial = authority.get_serial();
+    assert_eq!(authority.get_ixfr_records(serial).len(), 1);
+    assert_eq!(authority.get_ixfr_records(serial.wrapping_add(1)).len(), 1);
+    assert_eq!(authority.get_ixfr_records(serials[0]).len(),
+               authority.get_transfer_records().len());
+
+    authority.set_max_deltas(0);
+    assert!(authority.get_deltas().is_empty());
+}
+
 #[test]
 fn test_get_nsec() {
     let name = Name::new().label("zzz").label("example").label("com");
@@ -705,6 +1098,290 @@ fn test_get_nsec() {
     }
 }
 
+#[test]
+fn test_wildcard() {
+    let name = |name: &str| Name::parse(name, None).unwrap();
+    let record = |owner: &str, rdata: RData| {
+        Record::from_rdata(name(owner), 86400, rdata.to_record_type(), rdata)
+    };
+
+    let mut authority: Authority = create_secure_example();
+    let updates = [record("*.example.com.", RData::A(Ipv4Addr::new(192, 0, 2, 10))),
+                   record("*.www.example.com.", RData::CNAME(name("www.example.com.")))];
+    assert!(authority.update_records(&updates, true).expect("update failed"));
+
+    // the wildcard at the closest encloser answers for names which do not exist
+    let wildcard = name("*.example.com.");
+    assert_eq!(authority.find_wildcard(&name("foo.example.com.")), Some(wildcard.clone()));
+    assert_eq!(authority.find_wildcard(&name("a.b.example.com.")), Some(wildcard.clone()));
+    assert_eq!(authority.find_wildcard(&name("a.www.example.com.")),
+               Some(name("*.www.example.com.")));
+    assert_eq!(authority.find_wildcard(&name("www.example.com.")), None);
+    assert_eq!(authority.find_wildcard(&name("www.example.net.")), None);
+
+    // a search answers from the wildcard, with the name as the owner
+    let foo = name("foo.example.com.");
+    let mut query: Query = Query::new();
+    query.name(foo.clone()).query_type(RecordType::A);
+    let answers = authority.search(&query, false, SupportedAlgorithms::new());
+    assert_eq!(answers.len(), 1);
+    assert_eq!(*answers[0].get_name(), foo);
+    assert_eq!(answers[0].get_rdata(), &RData::A(Ipv4Addr::new(192, 0, 2, 10)));
+    query.name(name("www.example.com.")).query_type(RecordType::MX);
+    assert!(authority.search(&query, false, SupportedAlgorithms::new()).is_empty());
+
+    // the records, and their RRSIGs, are synthesized with the name as the owner
+    let records = authority.lookup_wildcard(&foo,
+                                            &wildcard,
+                                            RecordType::A,
+                                            true,
+                                            SupportedAlgorithms::all());
+    assert!(records.iter().any(|r| r.get_rr_type() == RecordType::A));
+    assert!(records.iter().any(|r| r.get_rr_type() == RecordType::RRSIG));
+    for record in &records {
+        assert_eq!(*record.get_name(), foo);
+        if let RData::SIG(ref sig) = *record.get_rdata() {
+            assert_eq!(sig.get_num_labels(), 2);
+        }
+    }
+    assert!(authority.lookup_wildcard(&foo,
+                                      &wildcard,
+                                      RecordType::MX,
+                                      false,
+                                      SupportedAlgorithms::new())
+        .is_empty());
+
+    // a CNAME at the wildcard answers for every type
+    let cname = authority.lookup_wildcard(&name("a.www.example.com."),
+                                          &name("*.www.example.com."),
+                                          RecordType::A,
+                                          false,
+                                          SupportedAlgorithms::new());
+    assert_eq!(cname.len(), 1);
+    assert_eq!(cname[0].get_rdata(), &RData::CNAME(name("www.example.com.")));
+
+    // the NSEC covering the name proves that it does not exist
+    let proof = authority.get_wildcard_proof(&foo,
+                                             &wildcard,
+                                             false,
+                                             true,
+                                             SupportedAlgorithms::all());
+    let nsecs: Vec<&Record> =
+        proof.iter().cloned().filter(|r| r.get_rr_type() == RecordType::NSEC).collect();
+    assert_eq!(nsecs.len(), 1);
+    assert!(*nsecs[0].get_name() < foo);
+    if let RData::NSEC(ref nsec) = *nsecs[0].get_rdata() {
+        assert!(*nsec.get_next_domain_name() > foo);
+    } else {
+        panic!("expected an NSEC"); // valid panic, in test
+    }
+
+    // and for NODATA the NSEC of the wildcard that it does not have the type
+    let proof = authority.get_wildcard_proof(&foo,
+                                             &wildcard,
+                                             true,
+                                             false,
+                                             SupportedAlgorithms::new());
+    assert!(proof.iter().any(|r| *r.get_name() == wildcard));
+}
+
+#[test]
+fn test_nsec3() {
+    let www_name = Name::parse("www.example.com.", None).unwrap();
+    let sub_name = Name::parse("sub.example.com.", None).unwrap();
+    let nsec3_param = |opt_out: bool| {
+        NSEC3PARAM::new(Nsec3HashAlgorithm::SHA1, opt_out, 12, vec![0xAA, 0xBB, 0xCC, 0xDD])
+    };
+    let nsec3s = |authority: &Authority| {
+        authority.get_records()
+            .values()
+            .filter(|rr_set| rr_set.get_record_type() == RecordType::NSEC3)
+            .flat_map(|rr_set| rr_set.get_records(false, SupportedAlgorithms::new()).into_iter())
+            .filter_map(|r| if let &RData::NSEC3(ref nsec3) = r.get_rdata() {
+                Some((r.get_name().clone(), nsec3.clone()))
+            } else {
+                None
+            })
+            .collect::<Vec<(Name, NSEC3)>>()
+    };
+
+    let mut authority: Authority = create_secure_example();
+    let origin = authority.get_origin().clone();
+
+    // an insecure delegation, without a DS
+    authority.update_records(&[Record::new()
+                                   .name(sub_name.clone())
+                                   .ttl(86400)
+                                   .rr_type(RecordType::NS)
+                                   .dns_class(DNSClass::IN)
+                                   .rdata(RData::NS(Name::parse("ns.sub.example.com.", None)
+                                       .unwrap()))
+                                   .clone()],
+                                false)
+        .expect("update failed");
+
+    authority.set_nsec3_param(Some(nsec3_param(false)));
+    authority.secure_zone().expect("signing failed");
+
+    // the NSEC chain is replaced, the apex has the NSEC3PARAM without flags
+    assert!(authority.get_nsec_records(&www_name, false, SupportedAlgorithms::new()).is_empty());
+    let params =
+        authority.lookup(&origin, RecordType::NSEC3PARAM, false, SupportedAlgorithms::new());
+    assert_eq!(params.len(), 1);
+    assert_eq!(params[0].get_rdata(), &RData::NSEC3PARAM(nsec3_param(false)));
+
+    // example.com., sub.example.com. and www.example.com., the hashes are a cycle
+    let chain = nsec3s(&authority);
+    assert_eq!(chain.len(), 3);
+    for &(ref name, ref nsec3) in &chain {
+        assert!(!nsec3.is_opt_out());
+        assert!(!authority.contains_name(name));
+        assert!(chain.iter()
+            .any(|&(ref next, _)| next == &hashed_owner_name(nsec3.get_next_hashed_owner_name(),
+                                                              &origin)));
+    }
+
+    // NODATA is the NSEC3 which matches the name
+    let nodata = authority.get_nsec3_records(&www_name, true, SupportedAlgorithms::all());
+    assert_eq!(nodata.iter().filter(|r| r.get_rr_type() == RecordType::NSEC3).count(), 1);
+    assert!(nodata.iter().any(|r| r.get_rr_type() == RecordType::RRSIG));
+    if let &RData::NSEC3(ref nsec3) = nodata[0].get_rdata() {
+        assert!(nsec3.get_type_bit_maps().contains(&RecordType::A));
+        assert!(nsec3.get_type_bit_maps().contains(&RecordType::RRSIG));
+    } else {
+        panic!("expected NSEC3: {:?}", nodata[0]);
+    }
+
+    // NXDOMAIN is the closest encloser proof, the NSEC3 of example.com. and those covering
+    //  zzz.example.com. and *.example.com.
+    let origin_hash = Nsec3HashAlgorithm::SHA1.hash(&[0xAA, 0xBB, 0xCC, 0xDD], &origin, 12)
+        .unwrap();
+    let nxdomain = authority.get_nsec3_records(&Name::parse("zzz.example.com.", None).unwrap(),
+                                               false,
+                                               SupportedAlgorithms::new());
+    assert!(!nxdomain.is_empty() && nxdomain.len() <= 3);
+    assert!(nxdomain.iter().any(|r| r.get_name() == &hashed_owner_name(&origin_hash, &origin)));
+
+    // with Opt-Out the insecure delegation is left out of the chain, a DS query of it is
+    //  answered with the closest encloser proof
+    authority.set_nsec3_param(Some(nsec3_param(true)));
+    authority.secure_zone().expect("signing failed");
+
+    let chain = nsec3s(&authority);
+    assert_eq!(chain.len(), 2);
+    assert!(chain.iter().all(|&(_, ref nsec3)| nsec3.is_opt_out()));
+
+    let opt_out = authority.get_nsec3_records(&sub_name, false, SupportedAlgorithms::new());
+    assert!(opt_out.iter().any(|r| r.get_name() == &hashed_owner_name(&origin_hash, &origin)));
+    assert!(opt_out.len() <= 2);
+
+    // and back to NSEC
+    authority.set_nsec3_param(None);
+    authority.secure_zone().expect("signing failed");
+    assert!(nsec3s(&authority).is_empty());
+    assert!(!authority.get_nsec_records(&www_name, false, SupportedAlgorithms::new()).is_empty());
+}
+
+#[test]
+fn test_nsec3_update() {
+    use chrono::Duration;
+    use openssl::rsa::Rsa;
+
+    let example = create_example();
+    let origin = example.get_origin().clone();
+    let mut authority = Authority::new(origin.clone(),
+                                       example.get_records().clone(),
+                                       ZoneType::Master,
+                                       false,
+                                       true);
+    let key = KeyPair::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+    let dnskey = key.to_dnskey(Algorithm::RSASHA256).unwrap();
+    authority.add_secure_key(Signer::new(Algorithm::RSASHA256,
+                                          key,
+                                          origin.clone(),
+                                          Duration::weeks(1),
+                                          true,
+                                          true))
+        .expect("add key failed");
+    authority.set_nsec3_param(Some(NSEC3PARAM::new(Nsec3HashAlgorithm::SHA1,
+                                                   false,
+                                                   12,
+                                                   vec![0xAA, 0xBB, 0xCC, 0xDD])));
+    let clock = ManualClock::new(1000);
+    authority.set_clock(Arc::new(clock.clone()));
+    authority.secure_zone().expect("signing failed");
+
+    let chain = |authority: &Authority| {
+        authority.get_records()
+            .values()
+            .filter(|rr_set| rr_set.get_record_type() == RecordType::NSEC3)
+            .flat_map(|rr_set| rr_set.get_records(false, SupportedAlgorithms::new()).into_iter())
+            .cloned()
+            .collect::<Vec<Record>>()
+    };
+    let a = |name: &str, dns_class: DNSClass| {
+        Record::new()
+            .name(Name::parse(name, None).unwrap())
+            .ttl(if dns_class == DNSClass::IN { 86400 } else { 0 })
+            .rr_type(RecordType::A)
+            .dns_class(dns_class)
+            .rdata(RData::A(Ipv4Addr::new(192, 0, 2, 1)))
+            .clone()
+    };
+
+    // the inception of the RRSIG over the RRset, which is verified
+    let signed_at = |authority: &Authority, name: &Name, rr_type: RecordType| {
+        let rr_set = authority.get_records().get(&RrKey::new(name, rr_type)).unwrap();
+        let records: Vec<Record> =
+            rr_set.get_records(false, SupportedAlgorithms::new()).into_iter().cloned().collect();
+        let sig = match *rr_set.get_rrsigs()[0].get_rdata() {
+            RData::SIG(ref sig) => sig.clone(),
+            ref rdata => panic!("expected RRSIG: {:?}", rdata),
+        };
+
+        verify_rrset_with_dnskey(&dnskey, &sig, name, DNSClass::IN, &records)
+            .expect("RRSIG did not verify");
+        sig.get_sig_inception()
+    };
+
+    // new names, one below the empty non-terminal b.example.com.
+    let before = chain(&authority);
+    clock.advance(60);
+    authority.update_records(&[a("new.example.com.", DNSClass::IN),
+                                 a("a.b.example.com.", DNSClass::IN)],
+                                true)
+        .expect("update failed");
+    let updated = chain(&authority);
+    assert_eq!(updated.len(), 5);
+
+    // only the RRsets of the update, the NSEC3 records which changed, and the SOA are signed again
+    for nsec3 in &updated {
+        let inception = signed_at(&authority, nsec3.get_name(), RecordType::NSEC3);
+        assert_eq!(inception, if before.contains(nsec3) { 1000 } else { 1060 });
+    }
+    let new_name = Name::parse("new.example.com.", None).unwrap();
+    assert_eq!(signed_at(&authority, &new_name, RecordType::A), 1060);
+    assert_eq!(signed_at(&authority, &origin, RecordType::SOA), 1060);
+    assert_eq!(signed_at(&authority, &origin, RecordType::NS), 1000);
+    assert_eq!(signed_at(&authority, &origin, RecordType::DNSKEY), 1000);
+
+    // the same as the whole chain generated again
+    authority.secure_zone().expect("signing failed");
+    assert_eq!(updated, chain(&authority));
+
+    // a type deleted at a name, and names which are deleted, the empty non-terminal with them
+    authority.update_records(&[a("www.example.com.", DNSClass::NONE),
+                                 a("new.example.com.", DNSClass::NONE),
+                                 a("a.b.example.com.", DNSClass::NONE)],
+                                true)
+        .expect("update failed");
+    let updated = chain(&authority);
+    assert_eq!(updated.len(), 2);
+
+    authority.secure_zone().expect("signing failed");
+    assert_eq!(updated, chain(&authority));
+}
+
 #[test]
 fn test_journal() {
     // test that this message can be inserted
@@ -801,3 +1478,130 @@ fn test_recovery() {
         })
     }));
 }
+
+#[test]
+fn test_journal_compaction() {
+    let conn = Connection::open_in_memory().expect("could not create in memory DB");
+    let mut journal = Journal::new(conn).unwrap();
+    journal.schema_up().unwrap();
+
+    let mut authority = create_example();
+    authority.journal(journal);
+    authority.persist_to_journal().unwrap();
+    let snapshot_len = authority.get_journal().unwrap().get_len().unwrap();
+    assert_eq!(snapshot_len as usize,
+               authority.get_records().values().map(|rr_set| rr_set.iter().count()).sum::<usize>() +
+               1);
+
+    // each update is appended, until the journal is compacted
+    authority.set_journal_compaction(Some(snapshot_len as usize + 2));
+    let new_name = Name::parse("new.example.com.", None).unwrap();
+    for last in 1..4 {
+        let record = Record::from_rdata(new_name.clone(),
+                                        86400,
+                                        RecordType::A,
+                                        RData::A(Ipv4Addr::new(10, 0, 0, last)));
+        authority.update_records(&[record], true).unwrap();
+    }
+
+    // the third update went over the limit, the journal is a snapshot of the zone again
+    let len = authority.get_journal().unwrap().get_len().unwrap();
+    assert_eq!(len, snapshot_len + 3);
+    let records = authority.get_journal().unwrap().iter().collect::<Vec<Record>>();
+    assert_eq!(records[0].get_rr_type(), RecordType::AXFR);
+    assert_eq!(records.iter().filter(|r| r.get_rr_type() == RecordType::AXFR).count(), 1);
+
+    let mut recovered_authority = Authority::new(authority.get_origin().clone(),
+                                                 BTreeMap::new(),
+                                                 ZoneType::Master,
+                                                 false,
+                                                 false);
+    recovered_authority.recover_with_journal(authority.get_journal().unwrap())
+        .expect("recovery");
+    assert_eq!(recovered_authority.get_soa(), authority.get_soa());
+    assert_eq!(recovered_authority.lookup(&new_name,
+                                          RecordType::A,
+                                          false,
+                                          SupportedAlgorithms::new())
+                   .len(),
+               3);
+}
+
+#[test]
+fn test_journal_deltas() {
+    let conn = Connection::open_in_memory().expect("could not create in memory DB");
+    let mut journal = Journal::new(conn).unwrap();
+    journal.schema_up().unwrap();
+
+    let mut authority = create_example();
+    authority.journal(journal);
+    authority.persist_to_journal().unwrap();
+    authority.set_max_deltas(2);
+
+    let name = Name::parse("www.example.com.", None).unwrap();
+    let serials: Vec<u32> = (1..4)
+        .map(|last| {
+            let serial = authority.get_serial();
+            let record = Record::from_rdata(name.clone(),
+                                            86400,
+                                            RecordType::A,
+                                            RData::A(Ipv4Addr::new(192, 0, 2, last)));
+            authority.update_records(&[record], true).unwrap();
+            serial
+        })
+        .collect();
+    assert_eq!(authority.get_journal().unwrap().select_deltas().unwrap(),
+               authority.get_deltas().iter().cloned().collect::<Vec<_>>());
+
+    // the deltas survive a restart, IXFR is still answered incrementally
+    let mut recovered_authority = Authority::new(authority.get_origin().clone(),
+                                                 BTreeMap::new(),
+                                                 ZoneType::Master,
+                                                 true,
+                                                 false);
+    recovered_authority.recover_with_journal(authority.get_journal().unwrap())
+        .expect("recovery");
+    assert_eq!(recovered_authority.get_deltas(), authority.get_deltas());
+    assert_eq!(recovered_authority.get_deltas()[0].get_old_serial(), serials[1]);
+    assert_eq!(recovered_authority.get_ixfr_records(serials[1]),
+               authority.get_ixfr_records(serials[1]));
+
+    authority.set_max_deltas(0);
+    assert!(authority.get_journal().unwrap().select_deltas().unwrap().is_empty());
+}
+
+#[test]
+fn test_to_zone_file() {
+    let example = create_secure_example();
+    let zone_file = example.to_zone_file();
+
+    assert!(zone_file.starts_with("$ORIGIN example.com.\n@ 3600 IN SOA sns.dns.icann.org. "));
+    assert!(zone_file.contains("\n@ 86400 IN A 93.184.216.34\n"));
+    assert!(zone_file.contains("\nwww 86400 IN TXT \"v=spf1 -all\"\n"));
+    assert!(zone_file.contains(" IN RRSIG A 8 2 86400 "));
+    assert!(zone_file.contains(" IN NSEC example.com. "));
+    assert!(zone_file.lines()
+        .filter(|line| line.contains(" RRSIG ") || line.contains(" NSEC "))
+        .all(|line| line.starts_with("; ")));
+    // the keys are zone data, rather than generated by signing
+    assert!(zone_file.lines()
+        .any(|line| line.starts_with("@ ") && line.contains(" IN DNSKEY \\# ")));
+
+    // read back, the RRSIG and NSEC records are comments
+    let (origin, records) = Parser::new().parse(Lexer::new(&zone_file), None).unwrap();
+    assert_eq!(&origin, example.get_origin());
+    for (key, rrset) in example.get_records() {
+        match key.record_type {
+            // the TTL of the SOA is read as its expire
+            RecordType::SOA => {
+                assert_eq!(records.get(key).unwrap().iter().next().unwrap().get_rdata(),
+                           rrset.iter().next().unwrap().get_rdata())
+            }
+            RecordType::NSEC => assert!(records.get(key).is_none()),
+            _ => {
+                assert_eq!(records.get(key).unwrap().iter().collect::<Vec<_>>(),
+                           rrset.iter().collect::<Vec<_>>())
+            }
+        }
+    }
+}
@@ -1,20 +1,30 @@
 extern crate chrono;
 extern crate futures;
 extern crate openssl;
+extern crate tempdir;
 extern crate trust_dns;
 extern crate trust_dns_server;
 
+use std::io::{self, Write};
 use std::net::*;
 use std::collections::*;
+use std::sync::Arc;
+use std::time::Duration;
 
+use tempdir::TempDir;
+
+use trust_dns::clock::ManualClock;
 use trust_dns::op::*;
 use trust_dns::rr::*;
+use trust_dns::rr::dnssec::{Algorithm, DigestType, KeyPair, Signer, SupportedAlgorithms,
+                            TSigner, TsigAlgorithm, TsigChain};
 use trust_dns::rr::rdata::*;
 
 use trust_dns_server::authority::*;
+use trust_dns_server::config::TemplateVariables;
 
 mod common;
-use common::authority::create_example;
+use common::authority::{create_example, create_secure_example};
 
 pub fn create_test() -> Authority {
     let origin: Name = Name::parse("test.com.", None).unwrap();
@@ -169,6 +179,397 @@ fn test_catalog_lookup() {
                &RData::A(Ipv4Addr::new(93, 184, 216, 34)));
 }
 
+#[test]
+fn test_catalog_snapshots() {
+    let example = create_example();
+    let origin = example.get_origin().clone();
+    let serial = example.get_serial();
+
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert(origin.clone(), example);
+    let shared = catalog.find_auth_recurse(&origin).unwrap();
+
+    // a read keeps its version of the zone while the next is written
+    let snapshot = shared.read();
+    let record = Record::from_rdata(Name::parse("new.example.com.", None).unwrap(),
+                                    86400,
+                                    RecordType::A,
+                                    RData::A(Ipv4Addr::new(192, 0, 2, 1)));
+    assert!(shared.write().update_records(&[record], true).unwrap());
+
+    let new_name = Name::parse("new.example.com.", None).unwrap();
+    assert_eq!(snapshot.get_serial(), serial);
+    assert!(!snapshot.contains_name(&new_name));
+
+    let current = shared.read();
+    assert_eq!(current.get_serial(), serial + 1);
+    assert!(current.contains_name(&new_name));
+    assert!(&*snapshot as *const Authority != &*current as *const Authority);
+
+    // reads during a write get the current version, the next replaces it once written
+    let before_ptr: *const Authority = &*current;
+    {
+        let mut next = shared.write();
+        next.refreshed();
+        assert_eq!(before_ptr, &*shared.read() as *const Authority);
+    }
+    assert!(before_ptr != &*shared.read() as *const Authority);
+
+    // a write which changes nothing leaves the version as it is
+    let before_ptr: *const Authority = &*shared.read();
+    assert!(!shared.write().is_refresh_due());
+    assert_eq!(before_ptr, &*shared.read() as *const Authority);
+}
+
+fn write_zone_file(path: &std::path::Path, serial: u32, www: &str) {
+    let mut file = std::fs::File::create(path).unwrap();
+    write!(file,
+           "@ 3600 IN SOA ns.reload.example. hostmaster.reload.example. {} 3600 600 86400 300
+@ 3600 IN NS ns
+ns 3600 IN A 192.0.2.1
+www 3600 IN A {}
+",
+           serial,
+           www)
+        .unwrap();
+}
+
+#[test]
+fn test_zone_file_variables() {
+    let temp_dir = TempDir::new("trust_dns_zone_file_variables").unwrap();
+    let zone_dir = temp_dir.path().to_path_buf();
+    let zone_path = zone_dir.join("variables.example.zone");
+    {
+        let mut file = std::fs::File::create(&zone_path).unwrap();
+        file.write_all(b"@ 3600 IN SOA ns hostmaster 1 3600 600 86400 300
+www 3600 IN A ${ipv4}
+txt 3600 IN TXT \"${TDNS_TEST_SECRET}\"
+")
+            .unwrap();
+    }
+    std::env::set_var("TDNS_TEST_SECRET", "not for the zone");
+
+    let origin = Name::parse("variables.example.", None).unwrap();
+    let mut variables = TemplateVariables::new();
+    variables.insert("ipv4".to_string(), "192.0.2.10".to_string());
+    let mut zone_file = ZoneFile::new(zone_path, zone_dir, variables);
+    let (_, records, _) = zone_file.load(Some(origin.clone())).unwrap();
+    let authority = Authority::new(origin, records, ZoneType::Master, false, false);
+
+    let lookup = |name: &str, rtype: RecordType| {
+        authority.lookup(&Name::parse(name, None).unwrap(),
+                          rtype,
+                          false,
+                          SupportedAlgorithms::new())
+            .into_iter()
+            .map(|record| record.get_rdata().clone())
+            .collect::<Vec<RData>>()
+    };
+    assert_eq!(lookup("www.variables.example.", RecordType::A),
+               vec![RData::A(Ipv4Addr::new(192, 0, 2, 10))]);
+
+    // the text of a record which is not a defined variable is data, the environment is not read
+    assert_eq!(lookup("txt.variables.example.", RecordType::TXT),
+               vec![RData::TXT(TXT::new(vec!["${TDNS_TEST_SECRET}".to_string()]))]);
+}
+
+#[test]
+fn test_catalog_reload() {
+    let temp_dir = TempDir::new("trust_dns_catalog_reload").unwrap();
+    let zone_dir = temp_dir.path().to_path_buf();
+    let zone_path = zone_dir.join("reload.example.zone");
+    write_zone_file(&zone_path, 1, "192.0.2.2");
+
+    let origin = Name::parse("reload.example.", None).unwrap();
+    let mut zone_file = ZoneFile::new(zone_path.clone(), zone_dir, TemplateVariables::new());
+    let (_, records, _) = zone_file.load(Some(origin.clone())).unwrap();
+    assert!(!zone_file.is_modified());
+    let mut authority = Authority::new(origin.clone(), records, ZoneType::Master, false, false);
+    authority.set_zone_file(Some(zone_file));
+
+    // only zones which were loaded from a file are reloaded
+    let example = create_example();
+    let example_origin = example.get_origin().clone();
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert(example_origin.clone(), example);
+    catalog.upsert(origin.clone(), authority);
+    assert!(catalog.reload(&example_origin).is_err());
+    assert!(catalog.reload_modified().is_empty());
+
+    let www = || {
+        let mut query: Query = Query::new();
+        query.name(Name::parse("www.reload.example.", None).unwrap()).query_type(RecordType::A);
+        let mut question: Message = Message::new();
+        question.add_query(query);
+        let response = catalog.lookup(&question);
+        response.get_answers()[0].get_rdata().clone()
+    };
+    assert_eq!(www(), RData::A(Ipv4Addr::new(192, 0, 2, 2)));
+
+    // a read during the reload keeps the version it has
+    let shared = catalog.find_auth_recurse(&origin).unwrap();
+    let snapshot = shared.read();
+    write_zone_file(&zone_path, 2, "192.0.2.3");
+    catalog.reload(&origin).unwrap();
+    assert_eq!(www(), RData::A(Ipv4Addr::new(192, 0, 2, 3)));
+    assert_eq!(shared.read().get_serial(), 2);
+    assert_eq!(snapshot.get_serial(), 1);
+    assert_eq!(shared.read().get_deltas().len(), 1);
+
+    // a file with errors is not installed, the zone is unchanged
+    {
+        let mut file = std::fs::File::create(&zone_path).unwrap();
+        file.write_all(b"www 3600 IN A not.an.address\n").unwrap();
+    }
+    assert!(catalog.reload(&origin).is_err());
+    assert_eq!(www(), RData::A(Ipv4Addr::new(192, 0, 2, 3)));
+    assert!(!shared.read().get_zone_file().unwrap().is_modified());
+
+    // a serial which is not greater is refused, the zone is unchanged
+    write_zone_file(&zone_path, 2, "192.0.2.4");
+    assert!(catalog.reload(&origin).is_err());
+    assert_eq!(www(), RData::A(Ipv4Addr::new(192, 0, 2, 3)));
+    assert_eq!(shared.read().get_deltas().len(), 1);
+    assert!(!shared.read().get_zone_file().unwrap().is_modified());
+
+    // a change to an $INCLUDEd file is a change to the zone
+    let include_path = zone_dir.join("reload.example.include");
+    {
+        let mut file = std::fs::File::create(&zone_path).unwrap();
+        write!(file,
+               "@ 3600 IN SOA ns.reload.example. hostmaster.reload.example. 3 3600 600 86400 300
+@ 3600 IN NS ns
+ns 3600 IN A 192.0.2.1
+$INCLUDE reload.example.include
+")
+            .unwrap();
+        let mut file = std::fs::File::create(&include_path).unwrap();
+        file.write_all(b"www 3600 IN A 192.0.2.5\n").unwrap();
+    }
+    assert_eq!(catalog.reload_modified(), vec![origin.clone()]);
+    assert_eq!(www(), RData::A(Ipv4Addr::new(192, 0, 2, 5)));
+    assert_eq!(shared.read().get_zone_file().unwrap().get_included(),
+               vec![include_path.as_path()]);
+    assert!(!shared.read().get_zone_file().unwrap().is_modified());
+    {
+        let mut file = std::fs::File::create(&include_path).unwrap();
+        file.write_all(b"www 3600 IN A 192.0.2.6\nftp 3600 IN A 192.0.2.7\n").unwrap();
+    }
+    assert!(shared.read().get_zone_file().unwrap().is_modified());
+
+    // a removed file is not reloaded
+    std::fs::remove_file(&zone_path).unwrap();
+    assert!(catalog.reload_modified().is_empty());
+    assert!(catalog.reload_modified().is_empty());
+    assert_eq!(shared.read().get_serial(), 3);
+}
+
+#[test]
+fn test_catalog_edns_version() {
+    let example = create_example();
+    let origin = example.get_origin().clone();
+
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert(origin.clone(), example);
+
+    let mut query: Query = Query::new();
+    query.name(origin.clone());
+
+    let mut question: Message = Message::new();
+    question.id(10).add_query(query);
+
+    // unknown flags and options must be ignored by the responder
+    {
+        let edns = question.get_edns_mut();
+        edns.set_z_flags(0x0100);
+        edns.set_option(opt::EdnsOption::Unknown(65001, vec![1, 2, 3]));
+    }
+
+    let result: Message = catalog.handle_request(&question);
+    assert_eq!(result.get_response_code(), ResponseCode::NoError);
+    assert!(!result.get_answers().is_empty());
+
+    let resp_edns = result.get_edns().expect("edns not here");
+    assert_eq!(resp_edns.get_version(), 0);
+    assert_eq!(resp_edns.get_z_flags(), 0);
+    assert!(resp_edns.get_option(&opt::EdnsCode::Unknown(65001)).is_none());
+
+    // a version we don't implement
+    question.get_edns_mut().set_version(1);
+
+    let result: Message = catalog.handle_request(&question);
+    assert_eq!(result.get_id(), 10);
+    assert_eq!(result.get_message_type(), MessageType::Response);
+    assert_eq!(result.get_response_code(), ResponseCode::BADVERS);
+    assert_eq!(result.get_queries(), question.get_queries());
+    assert!(result.get_answers().is_empty());
+    assert_eq!(result.get_edns().expect("edns not here").get_version(), 0);
+}
+
+#[test]
+fn test_catalog_client_subnet() {
+    let example = create_example();
+    let origin = example.get_origin().clone();
+
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert(origin.clone(), example);
+
+    let mut query: Query = Query::new();
+    query.name(origin.clone());
+
+    let mut question: Message = Message::new();
+    question.id(10).add_query(query);
+
+    let subnet = opt::ClientSubnet::new(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1)), 24);
+    question.get_edns_mut().set_option(opt::EdnsOption::ClientSubnet(subnet.clone()));
+    assert_eq!(question.get_client_subnet(), Some(&subnet));
+
+    // the answer is the same for any client, the subnet is echoed with a scope of zero
+    let result: Message = catalog.handle_request(&question);
+    assert_eq!(result.get_response_code(), ResponseCode::NoError);
+    assert!(!result.get_answers().is_empty());
+    assert_eq!(result.get_client_subnet(), Some(&subnet));
+    assert_eq!(result.get_client_subnet().unwrap().get_scope_prefix(), 0);
+
+    // a malformed subnet, here with bits set beyond the source prefix
+    question.get_edns_mut()
+        .set_option(opt::EdnsOption::Unknown(8, vec![0, 1, 24, 0, 192, 0, 2, 1]));
+    assert!(question.get_client_subnet().is_none());
+
+    let result: Message = catalog.handle_request(&question);
+    assert_eq!(result.get_response_code(), ResponseCode::FormErr);
+    assert_eq!(result.get_queries(), question.get_queries());
+    assert!(result.get_answers().is_empty());
+}
+
+#[test]
+fn test_catalog_op_codes() {
+    let example = create_example();
+    let origin = example.get_origin().clone();
+
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert(origin.clone(), example);
+
+    let mut query: Query = Query::new();
+    query.name(origin.clone()).query_type(RecordType::SOA);
+
+    let mut question: Message = Message::new();
+    question.id(10).add_query(query);
+
+    for op_code in &[OpCode::Status, OpCode::Unknown(1), OpCode::Unknown(7), OpCode::Dso] {
+        question.op_code(*op_code);
+
+        let result: Message = catalog.handle_request(&question);
+        assert_eq!(result.get_id(), 10);
+        assert_eq!(result.get_op_code(), *op_code);
+        assert_eq!(result.get_response_code(), ResponseCode::NotImp);
+        assert_eq!(result.get_queries(), question.get_queries());
+    }
+
+    // NOTIFY is acknowledged for zones in the catalog
+    question.op_code(OpCode::Notify);
+    let result: Message = catalog.handle_request(&question);
+    assert_eq!(result.get_op_code(), OpCode::Notify);
+    assert_eq!(result.get_response_code(), ResponseCode::NoError);
+    assert!(result.is_authoritative());
+    assert_eq!(result.get_queries(), question.get_queries());
+
+    let mut query: Query = Query::new();
+    query.name(Name::parse("example.net.", None).unwrap()).query_type(RecordType::SOA);
+    let mut other_zone: Message = Message::new();
+    other_zone.id(11).op_code(OpCode::Notify).add_query(query);
+    assert_eq!(catalog.handle_request(&other_zone).get_response_code(),
+               ResponseCode::NotAuth);
+}
+
+struct TestHandler;
+
+impl RequestHandler for TestHandler {
+    fn handle_request(&self, request: &Message) -> Message {
+        Message::error_msg(request.get_id(), request.get_op_code(), ResponseCode::Refused)
+    }
+}
+
+#[test]
+fn test_catalog_op_code_handler() {
+    let example = create_example();
+    let origin = example.get_origin().clone();
+
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert(origin.clone(), example);
+    catalog.set_op_code_handler(OpCode::Unknown(7), Box::new(TestHandler));
+    catalog.set_op_code_handler(OpCode::Notify, Box::new(TestHandler));
+    // ignored, queries are always answered by the authorities
+    catalog.set_op_code_handler(OpCode::Query, Box::new(TestHandler));
+
+    let mut query: Query = Query::new();
+    query.name(origin.clone()).query_type(RecordType::SOA);
+
+    let mut question: Message = Message::new();
+    question.id(10).add_query(query);
+
+    let result: Message = catalog.handle_request(&question);
+    assert_eq!(result.get_response_code(), ResponseCode::NoError);
+    assert!(!result.get_answers().is_empty());
+
+    for op_code in &[OpCode::Unknown(7), OpCode::Notify] {
+        question.op_code(*op_code);
+        let result: Message = catalog.handle_request(&question);
+        assert_eq!(result.get_op_code(), *op_code);
+        assert_eq!(result.get_response_code(), ResponseCode::Refused);
+    }
+
+    // others are still not implemented
+    question.op_code(OpCode::Unknown(8));
+    assert_eq!(catalog.handle_request(&question).get_response_code(),
+               ResponseCode::NotImp);
+}
+
+#[test]
+fn test_catalog_header_flags() {
+    let example = create_example();
+    let origin = example.get_origin().clone();
+
+    let hint_origin: Name = Name::parse("hint.com.", None).unwrap();
+    let hint = Authority::new(hint_origin.clone(),
+                              BTreeMap::new(),
+                              ZoneType::Hint,
+                              false,
+                              false);
+
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert(origin.clone(), example);
+    catalog.upsert(hint_origin.clone(), hint);
+
+    for &(rd, cd, ad) in &[(false, false, false),
+                           (true, false, false),
+                           (false, true, false),
+                           (false, false, true),
+                           (true, true, true)] {
+        for &(ref name, authoritative) in &[(origin.clone(), true),
+                                            (Name::parse("nx.example.com.", None).unwrap(), true),
+                                            (hint_origin.clone(), false)] {
+            let mut query: Query = Query::new();
+            query.name(name.clone());
+
+            let mut question: Message = Message::new();
+            question.add_query(query)
+                .recursion_desired(rd)
+                .checking_disabled(cd)
+                .authentic_data(ad);
+
+            let result: Message = catalog.lookup(&question);
+
+            assert_eq!(result.get_message_type(), MessageType::Response);
+            assert_eq!(result.is_authoritative(), authoritative, "{}", name);
+            assert!(!result.is_authentic_data(), "AD set for {}", name);
+            assert!(!result.is_recursion_available());
+            assert_eq!(result.is_recursion_desired(), rd);
+            assert_eq!(result.is_checking_disabled(), cd);
+        }
+    }
+}
+
 #[test]
 fn test_catalog_nx_soa() {
     let example = create_example();
@@ -203,6 +604,55 @@ fn test_catalog_nx_soa() {
                                     3600)));
 }
 
+#[test]
+fn test_catalog_nodata() {
+    let mut example = create_example();
+    let origin = example.get_origin().clone();
+
+    // creates the empty non-terminal ent.example.com.
+    example.upsert(Record::new()
+                       .name(Name::parse("deep.ent.example.com.", None).unwrap())
+                       .ttl(86400)
+                       .rr_type(RecordType::A)
+                       .dns_class(DNSClass::IN)
+                       .rdata(RData::A(Ipv4Addr::new(93, 184, 216, 34)))
+                       .clone(),
+                   0);
+
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert(origin.clone(), example);
+
+    for &(name, rr_type) in &[("ent.example.com.", RecordType::A),
+                              ("www.example.com.", RecordType::MX),
+                              ("deep.ent.example.com.", RecordType::AAAA)] {
+        let mut query: Query = Query::new();
+        query.name(Name::parse(name, None).unwrap()).query_type(rr_type);
+
+        let mut question: Message = Message::new();
+        question.add_query(query);
+
+        let result: Message = catalog.lookup(&question);
+
+        assert_eq!(result.get_response_code(), ResponseCode::NoError, "{}", name);
+        assert!(result.is_authoritative());
+        assert!(result.get_answers().is_empty());
+
+        let ns: &[Record] = result.get_name_servers();
+        assert_eq!(ns.len(), 1);
+        assert_eq!(ns.first().unwrap().get_rr_type(), RecordType::SOA);
+    }
+
+    // below the deepest name is still NXDomain
+    let mut query: Query = Query::new();
+    query.name(Name::parse("nx.deep.ent.example.com.", None).unwrap());
+
+    let mut question: Message = Message::new();
+    question.add_query(query);
+
+    assert_eq!(catalog.lookup(&question).get_response_code(),
+               ResponseCode::NXDomain);
+}
+
 #[test]
 fn test_axfr() {
     let test = create_test();
@@ -328,3 +778,1340 @@ fn test_axfr() {
 
     assert_eq!(expected_set, answers);
 }
+
+#[test]
+fn test_catalog_stats() {
+    let example = create_example();
+    let origin = example.get_origin().clone();
+
+    let clock = ManualClock::new(1000);
+    let mut catalog: Catalog = Catalog::new();
+    catalog.set_clock(Arc::new(clock.clone()));
+    catalog.upsert(origin.clone(), example);
+
+    for &(name, rr_type) in &[("www.example.com.", RecordType::A),
+                              ("www.example.com.", RecordType::A),
+                              ("nx.example.com.", RecordType::AAAA),
+                              ("example.com.", RecordType::AXFR)] {
+        let mut query: Query = Query::new();
+        query.name(Name::parse(name, None).unwrap()).query_type(rr_type);
+
+        let mut question: Message = Message::new();
+        question.add_query(query);
+
+        catalog.lookup(&question);
+    }
+
+    let stats = catalog.stats();
+    let zone = stats.get(&origin).unwrap();
+
+    assert_eq!(zone.get_origin(), &origin);
+    assert_eq!(zone.get_zone_type(), ZoneType::Master);
+    assert_eq!(zone.get_query_count(), 4);
+    assert_eq!(zone.get_queries().get(&RecordType::A), Some(&2));
+    assert_eq!(zone.get_nx_domain_count(), 1);
+    assert_eq!(zone.get_transfer_count(), 1);
+    assert_eq!(zone.get_update_count(), 0);
+    assert!(zone.get_record_count() > 0);
+    assert!(!zone.is_signed());
+    assert_eq!(zone.get_last_loaded().timestamp(), 1000);
+
+    // reloading keeps the counters
+    clock.advance(60);
+    catalog.upsert(origin.clone(), create_example());
+    let stats = catalog.stats();
+    let zone = stats.get(&origin).unwrap();
+    assert_eq!(zone.get_query_count(), 4);
+    assert_eq!(zone.get_last_loaded().timestamp(), 1060);
+}
+
+#[test]
+fn test_catalog_slave_expiry() {
+    let example = create_example();
+    let origin = example.get_origin().clone();
+    let clock = ManualClock::new(1000);
+
+    let mut slave = Authority::new(origin.clone(),
+                                   example.get_records().clone(),
+                                   ZoneType::Slave,
+                                   false,
+                                   false);
+    slave.set_clock(Arc::new(clock.clone()));
+
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert(origin.clone(), slave);
+
+    let mut query: Query = Query::new();
+    query.name(Name::parse("www.example.com.", None).unwrap()).query_type(RecordType::A);
+    let mut question: Message = Message::new();
+    question.add_query(query);
+
+    // the SOA expire is 1209600 seconds
+    clock.advance(1209600);
+    let response = catalog.lookup(&question);
+    assert_eq!(response.get_response_code(), ResponseCode::NoError);
+    assert!(response.is_authoritative());
+
+    clock.advance(1);
+    let response = catalog.lookup(&question);
+    assert_eq!(response.get_response_code(), ResponseCode::ServFail);
+    assert!(!response.is_authoritative());
+    assert!(response.get_answers().is_empty());
+    catalog.lookup(&question);
+
+    let stats = catalog.stats();
+    assert!(stats.get(&origin).unwrap().is_expired());
+    assert_eq!(stats.get(&origin).unwrap().get_expiration_count(), 1);
+
+    // a successful refresh restarts the timer
+    catalog.find_auth_recurse(&origin).unwrap().write().refreshed();
+    let response = catalog.lookup(&question);
+    assert_eq!(response.get_response_code(), ResponseCode::NoError);
+    assert!(!response.get_answers().is_empty());
+
+    let stats = catalog.stats();
+    assert!(!stats.get(&origin).unwrap().is_expired());
+    assert_eq!(stats.get(&origin).unwrap().get_expiration_count(), 1);
+}
+
+#[test]
+fn test_slave_refresh() {
+    let example = create_example();
+    let origin = example.get_origin().clone();
+    let serial = example.get_serial();
+    let primary: SocketAddr = "192.0.2.1:53".parse().unwrap();
+    let clock = ManualClock::new(1000);
+
+    let mut slave = Authority::new(origin.clone(),
+                                   example.get_records().clone(),
+                                   ZoneType::Slave,
+                                   false,
+                                   false);
+    slave.set_clock(Arc::new(clock.clone()));
+    slave.set_primary(Some(primary));
+
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert(origin.clone(), slave);
+    assert!(catalog.start_refreshes().is_empty());
+
+    // the SOA refresh is 7200 seconds
+    clock.advance(7200);
+    let requests = catalog.start_refreshes();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].get_origin(), &origin);
+    assert_eq!(requests[0].get_primary(), primary);
+    assert_eq!(requests[0].get_serial(), Some(serial));
+    assert_eq!(requests[0].to_message(1).get_queries()[0].get_query_type(),
+               RecordType::IXFR);
+    assert_eq!(requests[0].to_axfr().to_message(1).get_queries()[0].get_query_type(),
+               RecordType::AXFR);
+
+    // only one refresh at a time
+    assert!(catalog.start_refreshes().is_empty());
+
+    // the SOA retry is 3600 seconds
+    catalog.finish_refresh(&origin, Err(io::Error::new(io::ErrorKind::TimedOut, "timed out")));
+    clock.advance(3599);
+    assert!(catalog.start_refreshes().is_empty());
+    clock.advance(1);
+    assert_eq!(catalog.start_refreshes().len(), 1);
+
+    // the serial of the primary is not newer, the refresh timer is restarted
+    catalog.finish_refresh(&origin, Ok(None));
+    assert!(catalog.start_refreshes().is_empty());
+    clock.advance(7199);
+    assert!(catalog.start_refreshes().is_empty());
+    clock.advance(1);
+    assert_eq!(catalog.start_refreshes().len(), 1);
+
+    // the zone is up to date
+    let mut transfer = InboundTransfer::with_serial(TransferLimits::default(), Some(serial));
+    let mut response = Message::new();
+    response.add_answers(example.get_ixfr_records(serial).into_iter().cloned());
+    transfer.add_message(&response, 512).unwrap();
+    catalog.finish_refresh(&origin, Ok(Some(transfer)));
+    assert!(catalog.start_refreshes().is_empty());
+
+    // a transfer with the serial of the zone is not installed, but is no failure either
+    clock.advance(7200);
+    assert_eq!(catalog.start_refreshes().len(), 1);
+    let mut transfer = InboundTransfer::new(TransferLimits::default());
+    let mut response = Message::new();
+    response.add_answers(example.get_transfer_records().into_iter().cloned());
+    transfer.add_message(&response, 512).unwrap();
+    catalog.finish_refresh(&origin, Ok(Some(transfer)));
+    clock.advance(3600);
+    assert!(catalog.start_refreshes().is_empty());
+    clock.advance(3600);
+    assert_eq!(catalog.start_refreshes().len(), 1);
+    catalog.finish_refresh(&origin, Ok(None));
+
+    // a NOTIFY with the current serial is ignored, one without a serial or a newer one is not
+    let mut query: Query = Query::new();
+    query.name(origin.clone()).query_type(RecordType::SOA);
+    let mut notify: Message = Message::new();
+    notify.id(10).op_code(OpCode::Notify).add_query(query);
+    notify.add_answer(example.get_soa().unwrap().clone());
+    assert_eq!(catalog.handle_request(&notify).get_response_code(),
+               ResponseCode::NoError);
+    assert!(catalog.start_refreshes().is_empty());
+
+    notify.take_answers();
+    assert_eq!(catalog.handle_request(&notify).get_response_code(),
+               ResponseCode::NoError);
+    assert_eq!(catalog.start_refreshes().len(), 1);
+
+    // only the primary may notify the zone
+    assert!(catalog.is_notify_from_primary(&notify, primary.ip()));
+    assert!(!catalog.is_notify_from_primary(&notify, "192.0.2.2".parse().unwrap()));
+    let mut query_message = notify.clone();
+    query_message.op_code(OpCode::Query);
+    assert!(catalog.is_notify_from_primary(&query_message, "192.0.2.2".parse().unwrap()));
+}
+
+#[test]
+fn test_slave_initial_transfer() {
+    let origin = Name::parse("example.com.", None).unwrap();
+    let mut slave = Authority::new(origin.clone(), BTreeMap::new(), ZoneType::Slave, false, false);
+    slave.set_clock(Arc::new(ManualClock::new(1000)));
+
+    // without a primary the zone is never refreshed
+    assert!(slave.start_refresh().is_none());
+
+    slave.set_primary(Some("192.0.2.1:53".parse().unwrap()));
+    let request = slave.start_refresh().expect("zone without an soa is transferred");
+    assert_eq!(request.get_serial(), None);
+    assert_eq!(request.to_message(1).get_queries()[0].get_query_type(),
+               RecordType::AXFR);
+
+    // any serial is accepted for the first transfer
+    let example = create_example();
+    let mut transfer = InboundTransfer::new(TransferLimits::default());
+    let mut response = Message::new();
+    response.add_answers(example.get_transfer_records().into_iter().cloned());
+    transfer.add_message(&response, 512).unwrap();
+    assert_eq!(slave.install_transfer(transfer), Ok(()));
+    assert_eq!(slave.serial(), example.serial());
+    assert!(slave.start_refresh().is_none());
+
+    // master zones are never refreshed
+    let mut master = create_example();
+    master.set_primary(Some("192.0.2.1:53".parse().unwrap()));
+    assert!(!master.notified(None));
+    assert!(master.start_refresh().is_none());
+}
+
+#[test]
+fn test_apex_alias() {
+    let example = create_example();
+    let target = Name::parse("www.example.com.", None).unwrap();
+    let clock = ManualClock::new(1000);
+
+    let mut test = create_test();
+    let origin = test.get_origin().clone();
+    let serial = test.get_serial();
+    test.set_clock(Arc::new(clock.clone()));
+    test.set_apex_alias(Some(target.clone()));
+
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert(example.get_origin().clone(), example);
+    catalog.upsert(origin.clone(), test);
+
+    // the target is resolved straight away, only one resolution at a time
+    let requests = catalog.start_alias_refreshes();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].get_origin(), &origin);
+    assert_eq!(requests[0].get_target(), &target);
+    assert!(catalog.start_alias_refreshes().is_empty());
+
+    // the A differs, the AAAA of the apex is the same as that of the target
+    let answers = catalog.resolve_alias(&requests[0]).expect("target is in the catalog");
+    assert_eq!(answers.len(), 2);
+    catalog.finish_alias_refresh(&origin, Ok(answers));
+    {
+        let authority = catalog.find_auth_recurse(&origin).unwrap().read();
+        let addresses = authority.lookup(&origin, RecordType::A, false, SupportedAlgorithms::new());
+        assert_eq!(addresses.len(), 1);
+        assert_eq!(addresses[0].get_name(), &origin);
+        assert_eq!(addresses[0].get_rdata(), &RData::A(Ipv4Addr::new(93, 184, 216, 34)));
+        assert_eq!(authority.get_serial(), serial + 1);
+    }
+
+    // resolved again when the TTL of the addresses expires, unchanged the zone is not updated
+    clock.advance(86399);
+    assert!(catalog.start_alias_refreshes().is_empty());
+    clock.advance(1);
+    let requests = catalog.start_alias_refreshes();
+    assert_eq!(requests.len(), 1);
+    let answers = catalog.resolve_alias(&requests[0]).unwrap();
+    catalog.finish_alias_refresh(&origin, Ok(answers));
+    assert_eq!(catalog.find_auth_recurse(&origin).unwrap().read().get_serial(),
+               serial + 1);
+
+    // a failure keeps the addresses, and is retried after a minute
+    clock.advance(86400);
+    assert_eq!(catalog.start_alias_refreshes().len(), 1);
+    let timed_out = io::Error::new(io::ErrorKind::TimedOut, "timed out");
+    catalog.finish_alias_refresh(&origin, Err(timed_out));
+    clock.advance(59);
+    assert!(catalog.start_alias_refreshes().is_empty());
+    clock.advance(1);
+    assert_eq!(catalog.start_alias_refreshes().len(), 1);
+    catalog.finish_alias_refresh(&origin, Ok(vec![]));
+    assert_eq!(catalog.find_auth_recurse(&origin)
+                   .unwrap()
+                   .read()
+                   .lookup(&origin, RecordType::A, false, SupportedAlgorithms::new())
+                   .len(),
+               1);
+
+    // targets outside the catalog are resolved by the forwarded zones
+    let outside = AliasRequest::new(origin.clone(),
+                                    DNSClass::IN,
+                                    Name::parse("www.example.net.", None).unwrap());
+    assert!(catalog.resolve_alias(&outside).is_none());
+}
+
+#[test]
+fn test_notify_secondaries() {
+    let mut example = create_example();
+    let origin = example.get_origin().clone();
+    let serial = example.get_serial();
+    let secondary: SocketAddr = "192.0.2.2:53".parse().unwrap();
+    example.set_secondaries(vec![secondary]);
+
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert(origin.clone(), example);
+
+    // the secondaries are notified of the zone as loaded
+    let requests = catalog.start_notifies();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].get_origin(), &origin);
+    assert_eq!(requests[0].get_secondaries(), &[secondary]);
+    match *requests[0].get_soa().get_rdata() {
+        RData::SOA(ref soa) => assert_eq!(soa.get_serial(), serial),
+        ref other => panic!("expected soa: {:?}", other),
+    }
+    assert!(catalog.start_notifies().is_empty());
+
+    // and again once it changes
+    let record = Record::from_rdata(Name::parse("new.example.com.", None).unwrap(),
+                                    86400,
+                                    RecordType::A,
+                                    RData::A(Ipv4Addr::new(192, 0, 2, 1)));
+    assert!(catalog.find_auth_recurse(&origin)
+        .unwrap()
+        .write()
+        .update_records(&[record], true)
+        .unwrap());
+
+    let requests = catalog.start_notifies();
+    assert_eq!(requests.len(), 1);
+    match *requests[0].get_soa().get_rdata() {
+        RData::SOA(ref soa) => assert_eq!(soa.get_serial(), serial + 1),
+        ref other => panic!("expected soa: {:?}", other),
+    }
+}
+
+#[test]
+fn test_catalog_transfer() {
+    let mut example = create_example();
+    let origin = example.get_origin().clone();
+    for i in 0..1000 {
+        let name = Name::parse(&format!("host-{}.example.com.", i), None).unwrap();
+        example.upsert(Record::from_rdata(name,
+                                          86400,
+                                          RecordType::A,
+                                          RData::A(Ipv4Addr::new(192, 0, 2, (i % 256) as u8))),
+                       0);
+    }
+    let transfer_records: Vec<Record> =
+        example.get_transfer_records().into_iter().cloned().collect();
+
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert(origin.clone(), example);
+
+    let mut query: Query = Query::new();
+    query.name(origin.clone()).query_type(RecordType::AXFR);
+    let mut question: Message = Message::new();
+    question.id(10).add_query(query);
+
+    let client = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 7));
+    let responses = catalog.transfer(&question, client, None);
+    assert!(responses.len() > 1);
+    assert_eq!(responses[0].get_queries(), question.get_queries());
+    assert!(responses[1].get_queries().is_empty());
+    for response in &responses {
+        assert_eq!(response.get_id(), 10);
+        assert_eq!(response.get_response_code(), ResponseCode::NoError);
+        assert!(response.is_authoritative());
+        assert!(response.to_vec().unwrap().len() <= 16384);
+    }
+
+    let answers: Vec<Record> =
+        responses.iter().flat_map(|response| response.get_answers().to_vec()).collect();
+    assert_eq!(answers, transfer_records);
+    assert_eq!(answers.first().unwrap().get_rr_type(), RecordType::SOA);
+    assert_eq!(answers.last().unwrap().get_rr_type(), RecordType::SOA);
+
+    // only the apex may be transferred
+    let mut query: Query = Query::new();
+    query.name(Name::parse("www.example.com.", None).unwrap()).query_type(RecordType::AXFR);
+    let mut www_question: Message = Message::new();
+    www_question.add_query(query);
+    let responses = catalog.transfer(&www_question, client, None);
+    assert_eq!(responses.len(), 1);
+    assert_eq!(responses[0].get_response_code(), ResponseCode::NotAuth);
+}
+
+#[test]
+fn test_catalog_transfer_acl() {
+    let mut example = create_example();
+    let origin = example.get_origin().clone();
+    example.set_transfer_acl(Some(TransferAcl::new(vec![
+        TransferRule::Network(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 0)), 24),
+    ])));
+
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert(origin.clone(), example);
+
+    let mut query: Query = Query::new();
+    query.name(origin.clone()).query_type(RecordType::AXFR);
+    let mut question: Message = Message::new();
+    question.add_query(query);
+
+    let permitted = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 7));
+    let responses = catalog.transfer(&question, permitted, None);
+    assert_eq!(responses.len(), 1);
+    assert_eq!(responses[0].get_response_code(), ResponseCode::NoError);
+    assert!(!responses[0].get_answers().is_empty());
+
+    let refused = IpAddr::V4(Ipv4Addr::new(198, 51, 100, 7));
+    let responses = catalog.transfer(&question, refused, None);
+    assert_eq!(responses.len(), 1);
+    assert_eq!(responses[0].get_response_code(), ResponseCode::Refused);
+    assert!(responses[0].get_answers().is_empty());
+
+    // the client is not known to lookup
+    let response = catalog.lookup(&question);
+    assert_eq!(response.get_response_code(), ResponseCode::Refused);
+    assert!(response.get_answers().is_empty());
+}
+
+#[test]
+fn test_catalog_transfer_tsig() {
+    let key_name = Name::parse("transfer-key.example.com.", None).unwrap();
+    let tsigner = Arc::new(TSigner::new(key_name.clone(),
+                                        TsigAlgorithm::HmacSha256,
+                                        b"the secret of the secondaries".to_vec()));
+
+    let mut example = create_example();
+    let origin = example.get_origin().clone();
+    example.set_transfer_acl(Some(TransferAcl::new(vec![TransferRule::Key(key_name.clone())])));
+
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert(origin.clone(), example);
+    catalog.add_tsig_key(tsigner.clone());
+
+    let mut query: Query = Query::new();
+    query.name(origin.clone()).query_type(RecordType::AXFR);
+    let mut question: Message = Message::new();
+    question.id(10).add_query(query);
+
+    // unsigned, the request is not verified, and the client is refused
+    let client = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 7));
+    assert!(catalog.verify_tsig(&question, &question.to_vec().unwrap(), 1000).unwrap().is_none());
+    let responses = catalog.transfer(&question, client, None);
+    assert_eq!(responses[0].get_response_code(), ResponseCode::Refused);
+
+    let unsigned = question.clone();
+    let mac = question.sign_tsig(&tsigner, TsigChain::Request, 1000).unwrap();
+    let buffer = question.to_vec().unwrap();
+    let (verified, tsig) = catalog.verify_tsig(&question, &buffer, 1000).unwrap().unwrap();
+    assert_eq!(verified.get_key_name(), &key_name);
+    assert_eq!(tsig.get_mac(), &mac[..]);
+
+    let responses = catalog.transfer(&question, client, Some(verified.get_key_name()));
+    assert_eq!(responses[0].get_response_code(), ResponseCode::NoError);
+    assert!(!responses[0].get_answers().is_empty());
+
+    // a key which the catalog does not have, and a stale signature, are not verified
+    let other = TSigner::new(Name::parse("other-key.example.com.", None).unwrap(),
+                             TsigAlgorithm::HmacSha256,
+                             b"the secret of the secondaries".to_vec());
+    let mut other_question = unsigned.clone();
+    other_question.sign_tsig(&other, TsigChain::Request, 1000).unwrap();
+    assert!(catalog.verify_tsig(&other_question, &other_question.to_vec().unwrap(), 1000)
+        .is_err());
+    assert!(catalog.verify_tsig(&question, &buffer, 2000).is_err());
+}
+
+#[test]
+fn test_catalog_listen_addrs() {
+    let tenant_a = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 5));
+    let tenant_b = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 6));
+
+    let mut example = create_example();
+    let origin = example.get_origin().clone();
+    example.set_listen_addrs(Some(vec![tenant_a]));
+    assert_eq!(example.get_listen_addrs(), Some(&[tenant_a][..]));
+
+    let other_origin = Name::parse("example.net.", None).unwrap();
+    let other = Authority::new(other_origin.clone(),
+                               BTreeMap::new(),
+                               ZoneType::Master,
+                               false,
+                               false);
+
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert(other_origin.clone(), other);
+    assert!(!catalog.has_listen_addrs());
+    catalog.upsert(origin.clone(), example);
+    assert!(catalog.has_listen_addrs());
+
+    let question = |name: &str| {
+        let mut query: Query = Query::new();
+        query.name(Name::parse(name, None).unwrap()).query_type(RecordType::A);
+        let mut question: Message = Message::new();
+        question.add_query(query);
+        question
+    };
+
+    // the zone, and any name in it, only on the address of its tenant
+    assert!(catalog.is_served_on(&question("www.example.com."), tenant_a));
+    assert!(!catalog.is_served_on(&question("www.example.com."), tenant_b));
+    assert!(!catalog.is_served_on(&question("example.com."), tenant_b));
+
+    // an unbound zone, and names outside the catalog, on any address
+    assert!(catalog.is_served_on(&question("www.example.net."), tenant_a));
+    assert!(catalog.is_served_on(&question("www.example.net."), tenant_b));
+    assert!(catalog.is_served_on(&question("www.example.org."), tenant_b));
+
+    // the wildcard address is not the address of the tenant
+    assert!(!catalog.is_served_on(&question("www.example.com."),
+                                  IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0))));
+}
+
+#[test]
+fn test_catalog_occlusion() {
+    let name = |name: &str| Name::parse(name, None).unwrap();
+    let record = |owner: &str, rdata: RData| {
+        Record::from_rdata(name(owner), 86400, rdata.to_record_type(), rdata)
+    };
+    let long_label = ::std::iter::repeat("a").take(63).collect::<String>();
+    let long_target = format!("{}.{}.net.", long_label, long_label);
+
+    let mut example = create_example();
+    let origin = example.get_origin().clone();
+    let updates = [record("sub.example.com.", RData::NS(name("ns.sub.example.com."))),
+                   record("ns.sub.example.com.", RData::A(Ipv4Addr::new(192, 0, 2, 1))),
+                   record("www.sub.example.com.", RData::A(Ipv4Addr::new(192, 0, 2, 2))),
+                   record("alias.example.com.", RData::DNAME(name("example.net."))),
+                   record("www.alias.example.com.", RData::A(Ipv4Addr::new(192, 0, 2, 3))),
+                   record("long.example.com.", RData::DNAME(name(&long_target)))];
+    assert!(example.update_records(&updates, false).expect("update failed"));
+
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert(origin.clone(), example);
+
+    let question = |name: &Name| {
+        let mut query: Query = Query::new();
+        query.name(name.clone()).query_type(RecordType::A);
+        let mut question: Message = Message::new();
+        question.add_query(query);
+        question
+    };
+
+    // below the delegation, a referral with the glue rather than the stored record
+    let result = catalog.lookup(&question(&name("www.sub.example.com.")));
+    assert_eq!(result.get_response_code(), ResponseCode::NoError);
+    assert!(!result.is_authoritative());
+    assert!(result.get_answers().is_empty());
+    assert_eq!(result.get_name_servers().len(), 1);
+    assert_eq!(result.get_name_servers()[0].get_rdata(),
+               &RData::NS(name("ns.sub.example.com.")));
+    assert_eq!(result.get_additionals().len(), 1);
+    assert_eq!(result.get_additionals()[0].get_rdata(),
+               &RData::A(Ipv4Addr::new(192, 0, 2, 1)));
+
+    // below the DNAME, the DNAME and the CNAME to the substituted name
+    let result = catalog.lookup(&question(&name("www.alias.example.com.")));
+    assert_eq!(result.get_response_code(), ResponseCode::NoError);
+    assert!(result.is_authoritative());
+    let answers = result.get_answers();
+    assert_eq!(answers.len(), 2);
+    assert_eq!(answers[0].get_rdata(), &RData::DNAME(name("example.net.")));
+    assert_eq!(*answers[1].get_name(), name("www.alias.example.com."));
+    assert_eq!(answers[1].get_rdata(), &RData::CNAME(name("www.example.net.")));
+
+    // a substituted name which is too long
+    let long_name = format!("{}.{}.{}.long.example.com.", long_label, long_label, long_label);
+    let result = catalog.lookup(&question(&name(&long_name)));
+    assert_eq!(result.get_response_code(), ResponseCode::YXDomain);
+
+    // the longest substituted name, 255 bytes on the wire with the 133 of the target, and one
+    //  byte longer
+    let label = |len: usize| ::std::iter::repeat("b").take(len).collect::<String>();
+    let longest = format!("{}.{}.long.example.com.", long_label, label(57));
+    let result = catalog.lookup(&question(&name(&longest)));
+    assert_eq!(result.get_response_code(), ResponseCode::NoError);
+    assert_eq!(result.get_answers()[1].get_rdata(),
+               &RData::CNAME(name(&format!("{}.{}.{}", long_label, label(57), long_target))));
+
+    let too_long = format!("{}.{}.long.example.com.", long_label, label(58));
+    let result = catalog.lookup(&question(&name(&too_long)));
+    assert_eq!(result.get_response_code(), ResponseCode::YXDomain);
+}
+
+#[test]
+fn test_catalog_wildcard() {
+    let mut example = create_example();
+    let origin = example.get_origin().clone();
+    let wildcard = Record::from_rdata(Name::parse("*.example.com.", None).unwrap(),
+                                      86400,
+                                      RecordType::A,
+                                      RData::A(Ipv4Addr::new(192, 0, 2, 10)));
+    assert!(example.update_records(&[wildcard], false).expect("update failed"));
+
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert(origin.clone(), example);
+
+    let question = |name: &str, rtype: RecordType| {
+        let mut query: Query = Query::new();
+        query.name(Name::parse(name, None).unwrap()).query_type(rtype);
+        let mut question: Message = Message::new();
+        question.add_query(query);
+        question
+    };
+
+    // synthesized for the name
+    let result = catalog.lookup(&question("foo.example.com.", RecordType::A));
+    assert_eq!(result.get_response_code(), ResponseCode::NoError);
+    assert!(result.is_authoritative());
+    assert_eq!(result.get_answers().len(), 1);
+    assert_eq!(*result.get_answers()[0].get_name(),
+               Name::parse("foo.example.com.", None).unwrap());
+    assert_eq!(result.get_answers()[0].get_rdata(),
+               &RData::A(Ipv4Addr::new(192, 0, 2, 10)));
+
+    // the wildcard exists, so other types are NODATA
+    let result = catalog.lookup(&question("foo.example.com.", RecordType::MX));
+    assert_eq!(result.get_response_code(), ResponseCode::NoError);
+    assert!(result.get_answers().is_empty());
+    assert!(result.get_name_servers().iter().any(|r| r.get_rr_type() == RecordType::SOA));
+
+    // names which exist are not answered from the wildcard, nor are those below them
+    let result = catalog.lookup(&question("www.example.com.", RecordType::A));
+    assert_eq!(result.get_answers()[0].get_rdata(),
+               &RData::A(Ipv4Addr::new(93, 184, 216, 34)));
+    let result = catalog.lookup(&question("foo.www.example.com.", RecordType::A));
+    assert_eq!(result.get_response_code(), ResponseCode::NXDomain);
+}
+
+#[test]
+fn test_catalog_cname_chain() {
+    let name = |name: &str| Name::parse(name, None).unwrap();
+    let cname = |owner: &str, target: &str| {
+        Record::from_rdata(name(owner), 86400, RecordType::CNAME, RData::CNAME(name(target)))
+    };
+
+    let mut example = create_example();
+    let origin = example.get_origin().clone();
+    let updates = [cname("alias.example.com.", "www.example.com."),
+                   cname("other.example.com.", "www.example.net."),
+                   cname("missing.example.com.", "missing.example.net."),
+                   cname("tenant.example.com.", "www.example.org."),
+                   cname("loop1.example.com.", "loop2.example.com."),
+                   cname("loop2.example.com.", "loop1.example.com.")];
+    assert!(example.update_records(&updates, false).expect("update failed"));
+
+    let other_origin = name("example.net.");
+    let mut other = Authority::new(other_origin.clone(),
+                                   BTreeMap::new(),
+                                   ZoneType::Master,
+                                   false,
+                                   false);
+    other.upsert(Record::from_rdata(name("www.example.net."),
+                                    86400,
+                                    RecordType::A,
+                                    RData::A(Ipv4Addr::new(192, 0, 2, 20))),
+                 0);
+
+    // a zone only served on the address of its tenant
+    let tenant = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 5));
+    let public = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 6));
+    let tenant_origin = name("example.org.");
+    let mut tenant_zone = Authority::new(tenant_origin.clone(),
+                                         BTreeMap::new(),
+                                         ZoneType::Master,
+                                         false,
+                                         false);
+    tenant_zone.upsert(Record::from_rdata(name("www.example.org."),
+                                          86400,
+                                          RecordType::A,
+                                          RData::A(Ipv4Addr::new(192, 0, 2, 30))),
+                       0);
+    tenant_zone.set_listen_addrs(Some(vec![tenant]));
+
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert(origin.clone(), example);
+    catalog.upsert(other_origin.clone(), other);
+    catalog.upsert(tenant_origin, tenant_zone);
+
+    let question = |name: &str, rtype: RecordType| {
+        let mut query: Query = Query::new();
+        query.name(Name::parse(name, None).unwrap()).query_type(rtype);
+        let mut question: Message = Message::new();
+        question.add_query(query);
+        question
+    };
+    let rdatas = |result: &Message| {
+        result.get_answers().iter().map(|r| r.get_rdata().clone()).collect::<Vec<RData>>()
+    };
+
+    // the CNAME and the records of its target
+    let result = catalog.lookup(&question("alias.example.com.", RecordType::A));
+    assert_eq!(result.get_response_code(), ResponseCode::NoError);
+    assert_eq!(rdatas(&result),
+               vec![RData::CNAME(name("www.example.com.")),
+                    RData::A(Ipv4Addr::new(93, 184, 216, 34))]);
+
+    // into another zone of the catalog
+    let result = catalog.lookup(&question("other.example.com.", RecordType::A));
+    assert_eq!(rdatas(&result),
+               vec![RData::CNAME(name("www.example.net.")),
+                    RData::A(Ipv4Addr::new(192, 0, 2, 20))]);
+
+    // a target which does not exist is NXDOMAIN, one without records of the type NODATA, not a
+    //  referral to the NS of the first zone
+    let result = catalog.lookup(&question("missing.example.com.", RecordType::A));
+    assert_eq!(result.get_response_code(), ResponseCode::NXDomain);
+    assert_eq!(rdatas(&result), vec![RData::CNAME(name("missing.example.net."))]);
+    assert!(result.get_name_servers().iter().all(|r| r.get_rr_type() != RecordType::NS));
+    let result = catalog.lookup(&question("other.example.com.", RecordType::AAAA));
+    assert_eq!(result.get_response_code(), ResponseCode::NoError);
+    assert_eq!(rdatas(&result), vec![RData::CNAME(name("www.example.net."))]);
+    assert!(result.get_name_servers().iter().all(|r| r.get_rr_type() != RecordType::NS));
+
+    // the zone of a tenant is only entered on its own address
+    let request = question("tenant.example.com.", RecordType::A);
+    let result = catalog.handle_request_on(&request, tenant, Some(512));
+    assert_eq!(rdatas(&result),
+               vec![RData::CNAME(name("www.example.org.")),
+                    RData::A(Ipv4Addr::new(192, 0, 2, 30))]);
+    let result = catalog.handle_request_on(&request, public, Some(512));
+    assert_eq!(result.get_response_code(), ResponseCode::NoError);
+    assert_eq!(rdatas(&result), vec![RData::CNAME(name("www.example.org."))]);
+
+    // the CNAME itself is not followed
+    let result = catalog.lookup(&question("alias.example.com.", RecordType::CNAME));
+    assert_eq!(rdatas(&result), vec![RData::CNAME(name("www.example.com."))]);
+
+    // a loop ends where it started
+    let result = catalog.lookup(&question("loop1.example.com.", RecordType::A));
+    assert_eq!(rdatas(&result),
+               vec![RData::CNAME(name("loop2.example.com.")),
+                    RData::CNAME(name("loop1.example.com."))]);
+}
+
+#[test]
+fn test_catalog_additional() {
+    let name = |name: &str| Name::parse(name, None).unwrap();
+    let a = |owner: &str, last: u8| {
+        let address = Ipv4Addr::new(192, 0, 2, last);
+        Record::from_rdata(name(owner), 86400, RecordType::A, RData::A(address))
+    };
+
+    let mut example = create_example();
+    let origin = example.get_origin().clone();
+    let mut updates = vec![Record::from_rdata(origin.clone(),
+                                              86400,
+                                              RecordType::MX,
+                                              RData::MX(MX::new(10, name("mail.example.com.")))),
+                           Record::from_rdata(name("big.example.com."),
+                                              86400,
+                                              RecordType::MX,
+                                              RData::MX(MX::new(10, name("big.example.com.")))),
+                           Record::from_rdata(name("_sip._udp.example.com."),
+                                              86400,
+                                              RecordType::SRV,
+                                              RData::SRV(SRV::new(0,
+                                                                  0,
+                                                                  5060,
+                                                                  name("sip.example.net.")))),
+                           a("mail.example.com.", 25)];
+    for last in 0..40 {
+        updates.push(a("big.example.com.", 100 + last));
+    }
+    assert!(example.update_records(&updates, false).expect("update failed"));
+
+    let other_origin = name("example.net.");
+    let mut other = Authority::new(other_origin.clone(),
+                                   BTreeMap::new(),
+                                   ZoneType::Master,
+                                   false,
+                                   false);
+    other.upsert(a("sip.example.net.", 50), 0);
+    let tenant = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 5));
+    let public = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 6));
+    other.set_listen_addrs(Some(vec![tenant]));
+
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert(origin.clone(), example);
+    catalog.upsert(other_origin.clone(), other);
+
+    let question = |name: &str, rtype: RecordType, max_payload: Option<u16>| {
+        let mut query: Query = Query::new();
+        query.name(Name::parse(name, None).unwrap()).query_type(rtype);
+        let mut question: Message = Message::new();
+        question.add_query(query);
+        if let Some(max_payload) = max_payload {
+            let mut edns = Edns::new();
+            edns.set_max_payload(max_payload);
+            question.set_edns(edns);
+        }
+        question
+    };
+
+    // the address of the exchange
+    let result = catalog.lookup(&question("example.com.", RecordType::MX, None));
+    assert_eq!(result.get_answers().len(), 1);
+    assert_eq!(result.get_additionals(), &[a("mail.example.com.", 25)]);
+
+    // of the target in another zone, the NS of the authority section are not in the catalog
+    let result = catalog.lookup(&question("_sip._udp.example.com.", RecordType::SRV, None));
+    assert_eq!(result.get_additionals(), &[a("sip.example.net.", 50)]);
+
+    // only from the zones served on the address the query was received on
+    let request = question("_sip._udp.example.com.", RecordType::SRV, None);
+    let result = catalog.handle_request_on(&request, tenant, Some(512));
+    assert_eq!(result.get_additionals(), &[a("sip.example.net.", 50)]);
+    let result = catalog.handle_request_on(&request, public, Some(512));
+    assert!(result.get_additionals().is_empty());
+
+    // addresses which would not fit are left out, rather than the response truncated
+    let result = catalog.lookup(&question("big.example.com.", RecordType::MX, None));
+    assert_eq!(result.get_answers().len(), 1);
+    assert!(result.get_additionals().is_empty());
+
+    let result = catalog.lookup(&question("big.example.com.", RecordType::MX, Some(4096)));
+    assert_eq!(result.get_additionals().len(), 40);
+
+    // over TCP the message size is the limit, whatever the payload size of the request
+    let request = question("big.example.com.", RecordType::MX, None);
+    let result = catalog.handle_request_on(&request, public, None);
+    assert_eq!(result.get_additionals().len(), 40);
+    let result = catalog.handle_request_on(&request, public, Some(512));
+    assert!(result.get_additionals().is_empty());
+
+    // none for other types
+    let result = catalog.lookup(&question("mail.example.com.", RecordType::A, None));
+    assert!(result.get_additionals().is_empty());
+}
+
+#[test]
+fn test_catalog_chain() {
+    use openssl::rsa::Rsa;
+
+    let name = |name: &str| Name::parse(name, None).unwrap();
+
+    // example.com. is signed, with the DS of the signed sub.example.com.
+    let mut example = create_secure_example();
+    let origin = example.get_origin().clone();
+    let sub_origin = name("sub.example.com.");
+    let ds = DS::new(1, Algorithm::RSASHA256, DigestType::SHA256, vec![0xAB; 32]);
+    let updates = [Record::from_rdata(sub_origin.clone(),
+                                      86400,
+                                      RecordType::NS,
+                                      RData::NS(name("ns.sub.example.com."))),
+                   Record::from_rdata(sub_origin.clone(), 86400, RecordType::DS, RData::DS(ds))];
+    assert!(example.update_records(&updates, true).expect("update failed"));
+
+    let mut sub = Authority::new(sub_origin.clone(),
+                                 BTreeMap::new(),
+                                 ZoneType::Master,
+                                 false,
+                                 true);
+    sub.upsert(Record::from_rdata(sub_origin.clone(),
+                                  3600,
+                                  RecordType::SOA,
+                                  RData::SOA(SOA::new(name("ns.sub.example.com."),
+                                                      name("root.sub.example.com."),
+                                                      1,
+                                                      3600,
+                                                      60,
+                                                      86400,
+                                                      3600))),
+               1);
+    sub.upsert(Record::from_rdata(name("www.sub.example.com."),
+                                  3600,
+                                  RecordType::A,
+                                  RData::A(Ipv4Addr::new(192, 0, 2, 1))),
+               1);
+    let key = KeyPair::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+    sub.add_secure_key(Signer::new(Algorithm::RSASHA256,
+                                   key,
+                                   sub_origin.clone(),
+                                   ::chrono::Duration::weeks(1),
+                                   true,
+                                   true))
+        .expect("add key failed");
+    sub.secure_zone();
+
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert(origin.clone(), example);
+    catalog.upsert(sub_origin.clone(), sub);
+
+    let question = |trust_point: Option<opt::EdnsOption>, is_dnssec: bool| {
+        let mut query: Query = Query::new();
+        query.name(name("www.sub.example.com.")).query_type(RecordType::A);
+        let mut question: Message = Message::new();
+        question.add_query(query);
+        {
+            let edns = question.get_edns_mut();
+            edns.set_dnssec_ok(is_dnssec);
+            if let Some(trust_point) = trust_point {
+                edns.set_option(trust_point);
+            }
+        }
+        question
+    };
+    let chain = |trust_point: &Name| Some(opt::EdnsOption::Chain(trust_point.clone()));
+    let chain_types = |response: &Message| {
+        response.get_name_servers()
+            .iter()
+            .filter_map(|record| match *record.get_rdata() {
+                RData::DNSKEY(..) | RData::DS(..) => {
+                    Some((record.get_name().clone(), record.get_rr_type()))
+                }
+                _ => None,
+            })
+            .collect::<Vec<(Name, RecordType)>>()
+    };
+
+    // over TCP, the size of a message is the only limit
+    let local = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
+    let over_tcp = |question: &Message| catalog.handle_request_on(question, local, None);
+
+    // from the parent, the keys of both zones and the DS in between, each signed
+    let response = over_tcp(&question(chain(&origin), true));
+    assert!(!response.is_truncated());
+    assert_eq!(response.get_response_code(), ResponseCode::NoError);
+    assert_eq!(response.get_answers().len(), 2);
+    assert_eq!(chain_types(&response),
+               vec![(sub_origin.clone(), RecordType::DNSKEY),
+                    (sub_origin.clone(), RecordType::DS),
+                    (origin.clone(), RecordType::DNSKEY)]);
+    let covered = response.get_name_servers()
+        .iter()
+        .filter_map(|record| match *record.get_rdata() {
+            RData::SIG(ref sig) => Some(sig.get_type_covered()),
+            _ => None,
+        })
+        .filter(|rr_type| *rr_type == RecordType::DNSKEY || *rr_type == RecordType::DS)
+        .count();
+    assert_eq!(covered, 3);
+    assert_eq!(response.get_chain_trust_point(), Some(&origin));
+
+    // the chain starts at the trust point, even if there is more above
+    let response = over_tcp(&question(chain(&sub_origin), true));
+    assert_eq!(chain_types(&response),
+               vec![(sub_origin.clone(), RecordType::DNSKEY)]);
+    assert_eq!(response.get_chain_trust_point(), Some(&sub_origin));
+
+    // from the root, as far up as the catalog has signed zones
+    let response = over_tcp(&question(chain(&Name::root()), true));
+    assert_eq!(chain_types(&response).len(), 3);
+    assert_eq!(response.get_chain_trust_point(), Some(&origin));
+
+    // over UDP the requestor is told to retry over TCP, the chain is not sent to an unvalidated
+    //  source address
+    let over_udp = |question: &Message| catalog.handle_request_on(question, local, Some(4096));
+    for response in &[catalog.handle_request(&question(chain(&origin), true)),
+                      over_udp(&question(chain(&origin), true))] {
+        assert_eq!(response.get_response_code(), ResponseCode::NoError);
+        assert!(response.is_truncated());
+        assert!(chain_types(response).is_empty());
+        assert!(response.get_chain_trust_point().is_none());
+    }
+
+    // without DO, or the option, there is no chain
+    for response in &[over_tcp(&question(chain(&Name::root()), false)),
+                      over_tcp(&question(None, true))] {
+        assert_eq!(response.get_response_code(), ResponseCode::NoError);
+        assert!(!response.is_truncated());
+        assert!(chain_types(response).is_empty());
+        assert!(response.get_chain_trust_point().is_none());
+    }
+
+    // a trust point which is not an ancestor of the name, a malformed one
+    for trust_point in vec![opt::EdnsOption::Chain(name("example.net.")),
+                            opt::EdnsOption::Unknown(13, vec![3, b'c', b'o'])] {
+        let response = catalog.handle_request(&question(Some(trust_point), true));
+        assert_eq!(response.get_response_code(), ResponseCode::FormErr);
+        assert!(response.get_name_servers().is_empty());
+    }
+}
+
+/// A zone answering the address in the first label of the name, e.g. `192-0-2-1.ip.example.net.`
+struct AddressZone {
+    origin: Name,
+}
+
+impl AuthorityObject for AddressZone {
+    fn get_origin(&self) -> Name {
+        self.origin.clone()
+    }
+
+    fn get_zone_type(&self) -> ZoneType {
+        ZoneType::Master
+    }
+
+    fn lookup(&self,
+              name: &Name,
+              rtype: RecordType,
+              _: bool,
+              _: SupportedAlgorithms)
+              -> Vec<Record> {
+        match rtype {
+            RecordType::SOA => {
+                let origin = self.origin.clone();
+                let soa = SOA::new(origin.clone(), origin.clone(), 1, 3600, 60, 86400, 60);
+                vec![Record::from_rdata(origin, 60, rtype, RData::SOA(soa))]
+            }
+            RecordType::A | RecordType::ANY if name.num_labels() == 4 => {
+                match name[0].replace("-", ".").parse::<Ipv4Addr>() {
+                    Ok(address) => {
+                        vec![Record::from_rdata(name.clone(), 60, RecordType::A, RData::A(address))]
+                    }
+                    Err(..) => vec![],
+                }
+            }
+            _ => vec![],
+        }
+    }
+
+    fn update(&self, _: &Message) -> UpdateResult<bool> {
+        Err(ResponseCode::Refused)
+    }
+
+    fn secure_zone(&self) -> trust_dns::error::DnsSecResult<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_catalog_object() {
+    let origin = Name::parse("ip.example.net.", None).unwrap();
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert(Name::parse("example.com.", None).unwrap(), create_example());
+    catalog.upsert_object(origin.clone(), Box::new(AddressZone { origin: origin.clone() }));
+
+    let question = |name: &str, rtype: RecordType| {
+        let mut query: Query = Query::new();
+        query.name(Name::parse(name, None).unwrap()).query_type(rtype);
+        let mut question: Message = Message::new();
+        question.add_query(query);
+        question
+    };
+
+    let response = catalog.lookup(&question("192-0-2-1.ip.example.net.", RecordType::A));
+    assert_eq!(response.get_response_code(), ResponseCode::NoError);
+    assert!(response.is_authoritative());
+    assert_eq!(response.get_answers().len(), 1);
+    assert_eq!(response.get_answers()[0].get_rdata(),
+               &RData::A(Ipv4Addr::new(192, 0, 2, 1)));
+
+    // the name has an address, but no records of the type
+    let response = catalog.lookup(&question("192-0-2-1.ip.example.net.", RecordType::AAAA));
+    assert_eq!(response.get_response_code(), ResponseCode::NoError);
+    assert!(response.get_answers().is_empty());
+    assert_eq!(response.get_name_servers()[0].get_rr_type(), RecordType::SOA);
+
+    let response = catalog.lookup(&question("www.ip.example.net.", RecordType::A));
+    assert_eq!(response.get_response_code(), ResponseCode::NXDomain);
+    assert_eq!(response.get_name_servers()[0].get_rr_type(), RecordType::SOA);
+
+    // the zones in memory are answered as before
+    let response = catalog.lookup(&question("www.example.com.", RecordType::A));
+    assert_eq!(response.get_response_code(), ResponseCode::NoError);
+    assert!(!response.get_answers().is_empty());
+
+    // updates are applied by the backend, transfers are only of zones in memory
+    let mut update: Message = Message::new();
+    let mut zone: Query = Query::new();
+    zone.name(origin.clone()).query_type(RecordType::SOA);
+    update.op_code(OpCode::Update).add_zone(zone);
+    assert_eq!(catalog.update(&update).get_response_code(), ResponseCode::Refused);
+
+    let responses = catalog.transfer(&question("ip.example.net.", RecordType::AXFR),
+                                     IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+                                     None);
+    assert_eq!(responses.len(), 1);
+    assert_eq!(responses[0].get_response_code(), ResponseCode::NotImp);
+
+    assert!(catalog.find_zone(&Name::parse("www.ip.example.net.", None).unwrap()).is_some());
+    assert!(catalog.find_auth_recurse(&Name::parse("www.ip.example.net.", None).unwrap())
+        .is_none());
+    assert!(!catalog.stats().contains_key(&origin));
+}
+
+#[test]
+fn test_catalog_forward() {
+    let upstream = ForwardUpstream::Udp("192.0.2.53:53".parse().unwrap());
+    let example = create_example();
+    let origin = example.get_origin().clone();
+
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert(origin.clone(), example);
+    catalog.upsert_forward(Name::parse("corp.example.com.", None).unwrap(),
+                           ForwardAuthority::new(Name::parse("corp.example.com.", None).unwrap(),
+                                                 vec![upstream.clone()]));
+    catalog.upsert_forward(Name::root(),
+                           ForwardAuthority::new(Name::root(), vec![upstream.clone()]));
+
+    let question = |name: &str, query_type: RecordType| {
+        let mut query: Query = Query::new();
+        query.name(Name::parse(name, None).unwrap()).query_type(query_type);
+        let mut question: Message = Message::new();
+        question.add_query(query);
+        question
+    };
+    let forward_origin = |request: &Message| {
+        catalog.get_forward(request).map(|forwarder| forwarder.get_origin().clone())
+    };
+
+    // the closest enclosing zone decides
+    assert_eq!(forward_origin(&question("www.example.com.", RecordType::A)), None);
+    assert_eq!(forward_origin(&question("host.corp.example.com.", RecordType::A)),
+               Some(Name::parse("corp.example.com.", None).unwrap()));
+    assert_eq!(forward_origin(&question("www.example.org.", RecordType::A)),
+               Some(Name::root()));
+    assert_eq!(catalog.get_forward(&question("www.example.org.", RecordType::A))
+                   .unwrap()
+                   .get_upstreams(),
+               &[upstream][..]);
+
+    // neither transfers nor updates are forwarded
+    assert_eq!(forward_origin(&question("corp.example.com.", RecordType::AXFR)), None);
+    let mut update = question("corp.example.com.", RecordType::SOA);
+    update.op_code(OpCode::Update);
+    assert_eq!(forward_origin(&update), None);
+}
+
+#[test]
+fn test_forward_cache() {
+    let origin = Name::parse("example.org.", None).unwrap();
+    let clock = Arc::new(ManualClock::new(1000));
+    let mut forwarder = ForwardAuthority::new(origin.clone(), vec![]);
+    forwarder.set_clock(clock.clone());
+
+    let mut query: Query = Query::new();
+    query.name(Name::parse("www.example.org.", None).unwrap()).query_type(RecordType::A);
+    let mut request: Message = Message::new();
+    request.id(10).recursion_desired(true).add_query(query);
+
+    let mut response: Message = Message::new();
+    response.id(20).message_type(MessageType::Response).add_queries(request.get_queries().to_vec());
+    response.add_answer(Record::from_rdata(Name::parse("www.example.org.", None).unwrap(),
+                                           300,
+                                           RecordType::A,
+                                           RData::A(Ipv4Addr::new(192, 0, 2, 80))));
+
+    // nothing is cached without a cache
+    forwarder.cache_response(&request, &response);
+    assert!(!forwarder.is_caching());
+    assert!(forwarder.get_cached(&request).is_none());
+
+    forwarder.set_cache(Some(trust_dns::cache::DnsCache::default()));
+    forwarder.cache_response(&request, &response);
+
+    clock.advance(100);
+    let cached = forwarder.get_cached(&request).unwrap();
+    assert_eq!(cached.get_id(), 10);
+    assert_eq!(cached.get_message_type(), MessageType::Response);
+    assert!(cached.is_recursion_desired());
+    assert_eq!(cached.get_queries(), request.get_queries());
+    assert_eq!(cached.get_answers().len(), 1);
+    assert_eq!(cached.get_answers()[0].get_ttl(), 200);
+
+    // the chain of trust is not cached, the query is forwarded
+    let mut chain_request = request.clone();
+    chain_request.get_edns_mut().set_option(opt::EdnsOption::Chain(Name::root()));
+    assert!(forwarder.get_cached(&chain_request).is_none());
+
+    clock.advance(200);
+    assert!(forwarder.get_cached(&request).is_none());
+    forwarder.record_upstream_latency(Duration::from_millis(30));
+
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert_forward(origin.clone(), forwarder);
+    let stats = catalog.forward_stats();
+    let stats = &stats[&origin];
+    assert_eq!(stats.get_cache_len(), 0);
+
+    let cache_stats = stats.get_cache_stats().unwrap();
+    assert_eq!(cache_stats.get_hits(), 1);
+    assert_eq!(cache_stats.get_misses(), 1);
+    assert_eq!(cache_stats.get_expired(), 1);
+    assert_eq!(cache_stats.get_type_hit_rate(RecordType::A), Some(0.5));
+
+    assert_eq!(stats.get_hit_latency().get_count(), 1);
+    assert_eq!(stats.get_upstream_latency().get_count(), 1);
+    assert_eq!(stats.get_upstream_latency().get_sum(), Duration::from_millis(30));
+}
+
+#[test]
+fn test_forward_shadow() {
+    let origin = Name::parse("example.org.", None).unwrap();
+    let shadow = ForwardUpstream::Udp("192.0.2.55:53".parse().unwrap());
+    let mut forwarder = ForwardAuthority::new(origin.clone(), vec![]);
+    assert!(forwarder.get_shadow_stats().is_none());
+    forwarder.set_shadow(Some(shadow.clone()));
+    assert_eq!(forwarder.get_shadow(), Some(&shadow));
+
+    let mut query: Query = Query::new();
+    query.name(Name::parse("www.example.org.", None).unwrap()).query_type(RecordType::A);
+    let mut request: Message = Message::new();
+    request.add_query(query);
+
+    let a = |ttl: u32, last: u8| {
+        Record::from_rdata(Name::parse("www.example.org.", None).unwrap(),
+                           ttl,
+                           RecordType::A,
+                           RData::A(Ipv4Addr::new(192, 0, 2, last)))
+    };
+    let mut primary: Message = Message::new();
+    primary.message_type(MessageType::Response);
+    primary.add_answer(a(300, 80));
+    primary.add_answer(a(300, 81));
+
+    // the same records, in another order and with TTLs counted down
+    let mut same = primary.clone();
+    same.take_answers();
+    same.add_answer(a(120, 81));
+    same.add_answer(a(120, 80));
+    assert!(forwarder.compare_shadow(&request, &primary, Some(&same)).is_empty());
+
+    let mut other = same.clone();
+    other.take_answers();
+    other.add_answer(a(300, 80));
+    other.add_answer(a(300, 82));
+    assert_eq!(forwarder.compare_shadow(&request, &primary, Some(&other)),
+               vec![ShadowDivergence::Answers {
+                        only_primary: vec![a(0, 81)],
+                        only_shadow: vec![a(0, 82)],
+                    }]);
+
+    let mut nx_domain = primary.clone();
+    nx_domain.take_answers();
+    nx_domain.response_code(ResponseCode::NXDomain);
+    assert_eq!(forwarder.compare_shadow(&request, &primary, Some(&nx_domain)),
+               vec![ShadowDivergence::ResponseCode {
+                        primary: ResponseCode::NoError,
+                        shadow: ResponseCode::NXDomain,
+                    },
+                    ShadowDivergence::Answers {
+                        only_primary: vec![a(0, 80), a(0, 81)],
+                        only_shadow: vec![],
+                    }]);
+
+    assert!(forwarder.compare_shadow(&request, &primary, None).is_empty());
+
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert_forward(origin.clone(), forwarder);
+    let stats = catalog.forward_stats();
+    let shadow_stats = stats[&origin].get_shadow_stats().unwrap();
+    assert_eq!(shadow_stats.get_compared(), 3);
+    assert_eq!(shadow_stats.get_diverged(), 2);
+    assert_eq!(shadow_stats.get_failed(), 1);
+}
+
+#[test]
+fn test_catalog_ixfr() {
+    let mut example = create_example();
+    let origin = example.get_origin().clone();
+    let first_serial = example.get_serial();
+    let first_records = example.get_records().clone();
+
+    let host = |last: u8| {
+        Record::from_rdata(Name::parse("host.example.com.", None).unwrap(),
+                           86400,
+                           RecordType::A,
+                           RData::A(Ipv4Addr::new(192, 0, 2, last)))
+    };
+    let mut delete = host(1);
+    delete.dns_class(DNSClass::NONE);
+
+    assert!(example.update_records(&[host(1)], true).unwrap());
+    assert!(example.update_records(&[delete, host(2)], true).unwrap());
+    assert_eq!(example.get_deltas().len(), 2);
+    let current_serial = example.get_serial();
+    let current_records = example.get_records().clone();
+
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert(origin.clone(), example);
+
+    let ixfr = |serial: Option<u32>| {
+        let mut query: Query = Query::new();
+        query.name(origin.clone()).query_type(RecordType::IXFR);
+        let mut question: Message = Message::new();
+        question.id(10).add_query(query);
+        if let Some(serial) = serial {
+            let soa = SOA::new(origin.clone(), origin.clone(), serial, 0, 0, 0, 0);
+            question.add_name_server(Record::from_rdata(origin.clone(),
+                                                        0,
+                                                        RecordType::SOA,
+                                                        RData::SOA(soa)));
+        }
+        question
+    };
+    let client = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 7));
+
+    let responses = catalog.transfer(&ixfr(Some(first_serial)), client, None);
+    assert_eq!(responses.len(), 1);
+    assert_eq!(responses[0].get_response_code(), ResponseCode::NoError);
+    assert!(responses[0].is_authoritative());
+
+    let response = IxfrResponse::from_records(responses[0].get_answers()).unwrap();
+    match response {
+        IxfrResponse::Incremental(ref deltas) => {
+            assert_eq!(deltas.len(), 2);
+            assert_eq!(deltas[0].get_old_serial(), first_serial);
+            assert_eq!(deltas[0].get_added(), &[host(1)]);
+            assert_eq!(deltas[1].get_removed(), &[host(1)]);
+            assert_eq!(deltas[1].get_added(), &[host(2)]);
+            assert_eq!(deltas[1].get_new_serial(), current_serial);
+        }
+        ref other => panic!("expected deltas: {:?}", other),
+    }
+
+    let flatten = |zone: &BTreeMap<RrKey, RecordSet>| -> Vec<Record> {
+        zone.values().flat_map(|rrset| rrset.iter().cloned()).collect()
+    };
+    let mut records = first_records;
+    response.apply(&mut records).unwrap();
+    assert_eq!(flatten(&records), flatten(&current_records));
+
+    // up to date, only the SOA
+    let responses = catalog.transfer(&ixfr(Some(current_serial)), client, None);
+    assert_eq!(responses[0].get_answers().len(), 1);
+    assert_eq!(responses[0].get_answers()[0].get_rr_type(), RecordType::SOA);
+
+    // the changes since are not known, the whole zone
+    let responses = catalog.transfer(&ixfr(Some(first_serial - 1)), client, None);
+    match IxfrResponse::from_records(responses[0].get_answers()).unwrap() {
+        IxfrResponse::Full(ref zone) => assert_eq!(zone.len(), flatten(&current_records).len()),
+        ref other => panic!("expected the zone: {:?}", other),
+    }
+
+    // the version of the client is required
+    let responses = catalog.transfer(&ixfr(None), client, None);
+    assert_eq!(responses[0].get_response_code(), ResponseCode::FormErr);
+
+    // without a connection, only the SOA
+    let response = catalog.lookup(&ixfr(Some(first_serial)));
+    assert_eq!(response.get_response_code(), ResponseCode::NoError);
+    assert_eq!(response.get_answers().len(), 1);
+    assert_eq!(response.get_answers()[0].get_rr_type(), RecordType::SOA);
+}
@@ -6,29 +6,34 @@ extern crate tokio_core;
 extern crate trust_dns;
 extern crate trust_dns_server;
 
+use std::collections::VecDeque;
 use std::fmt;
 use std::io;
 use std::net::*;
 use std::cmp::Ordering;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
 
 use chrono::Duration;
-use futures::{Async, Future, finished, Poll};
+use futures::{empty, Async, Future, finished, Poll};
 use futures::stream::{Fuse, Stream};
 use futures::sync::mpsc::{unbounded, UnboundedReceiver};
 use futures::task::park;
 use openssl::rsa::Rsa;
 use tokio_core::reactor::Core;
 
-use trust_dns::client::{ClientFuture, BasicClientHandle, ClientHandle, ClientStreamHandle};
+use trust_dns::client::{ClientFuture, BasicClientHandle, ClientHandle, ClientStreamHandle,
+                        OutstandingConfig, OverflowPolicy, TimeoutConfig};
+use trust_dns::clock::ManualClock;
 use trust_dns::error::*;
-use trust_dns::op::ResponseCode;
+use trust_dns::op::{IxfrResponse, Message, MessageType, OpCode, Query, ResponseCode};
 use trust_dns::rr::domain;
 use trust_dns::rr::{DNSClass, IntoRecordSet, RData, Record, RecordType, RecordSet};
 use trust_dns::rr::dnssec::{Algorithm, KeyPair, Signer};
 use trust_dns::rr::rdata::*;
 use trust_dns::udp::UdpClientStream;
 use trust_dns::tcp::TcpClientStream;
-use trust_dns_server::authority::Catalog;
+use trust_dns_server::authority::{Catalog, UpdatePolicy, UpdateRule};
 
 mod common;
 use common::TestClientStream;
@@ -49,6 +54,31 @@ fn test_query_nonet() {
     io_loop.run(test_query(&mut client)).unwrap();
 }
 
+#[test]
+fn test_send_raw_nonet() {
+    let authority = create_example();
+    let mut catalog = Catalog::new();
+    catalog.upsert(authority.get_origin().clone(), authority);
+
+    let mut io_loop = Core::new().unwrap();
+    let (stream, sender) = TestClientStream::new(catalog);
+    let mut client = ClientFuture::new(stream, sender, io_loop.handle(), None);
+
+    let mut query = Query::new();
+    query.name(domain::Name::parse("www.example.com.", None).unwrap())
+        .query_class(DNSClass::IN)
+        .query_type(RecordType::A);
+    let mut message = Message::new();
+    message.message_type(MessageType::Query).op_code(OpCode::Query).add_query(query);
+
+    let (response, bytes) = io_loop.run(client.send_raw(message)).expect("send failed");
+
+    // the bytes are exactly what the response was parsed from
+    assert_eq!(Message::from_vec(&bytes).unwrap(), response);
+    assert_eq!(((bytes[0] as u16) << 8) | bytes[1] as u16, response.get_id());
+    assert_eq!(response.get_answers().len(), 1);
+}
+
 #[test]
 #[ignore]
 fn test_query_udp_ipv4() {
@@ -173,8 +203,26 @@ fn test_notify() {
 
 /// create a client with a sig0 section
 fn create_sig0_ready_client(io_loop: &Core) -> (BasicClientHandle, domain::Name) {
+    create_sig0_ready_client_with_policy(io_loop, None)
+}
+
+fn create_sig0_ready_client_with_policy(io_loop: &Core,
+                                        update_policy: Option<UpdatePolicy>)
+                                        -> (BasicClientHandle, domain::Name) {
+    let (catalog, signer, origin) = create_sig0_ready_catalog(update_policy);
+
+    let (stream, sender) = TestClientStream::new(catalog);
+    let client = ClientFuture::new(stream, sender, io_loop.handle(), Some(signer));
+
+    (client, origin)
+}
+
+/// the catalog of example.com, with the KEY of the signer for updates
+fn create_sig0_ready_catalog(update_policy: Option<UpdatePolicy>)
+                             -> (Catalog, Signer, domain::Name) {
     let mut authority = create_example();
     authority.set_allow_update(true);
+    authority.set_update_policy(update_policy);
     let origin = authority.get_origin().clone();
 
     let rsa = Rsa::generate(512).unwrap();
@@ -208,10 +256,7 @@ fn create_sig0_ready_client(io_loop: &Core) -> (BasicClientHandle, domain::Name)
     let mut catalog = Catalog::new();
     catalog.upsert(authority.get_origin().clone(), authority);
 
-    let (stream, sender) = TestClientStream::new(catalog);
-    let client = ClientFuture::new(stream, sender, io_loop.handle(), Some(signer));
-
-    (client, origin)
+    (catalog, signer, origin)
 }
 
 #[test]
@@ -252,6 +297,106 @@ fn test_create() {
     assert_eq!(result.get_response_code(), ResponseCode::YXRRSet);
 }
 
+#[test]
+fn test_create_update_policy() {
+    let mut io_loop = Core::new().unwrap();
+    let policy = UpdatePolicy::new(vec![
+        UpdateRule::from_str("grant trusted.example.com. name new.example.com. A AAAA TXT")
+            .unwrap(),
+    ]);
+    let (mut client, origin) = create_sig0_ready_client_with_policy(&io_loop, Some(policy));
+
+    let mut record = Record::with(domain::Name::with_labels(vec!["new".to_string(),
+                                                                 "example".to_string(),
+                                                                 "com".to_string()]),
+                                  RecordType::A,
+                                  Duration::minutes(5).num_seconds() as u32);
+    record.rdata(RData::A(Ipv4Addr::new(100, 10, 100, 10)));
+
+    let result = io_loop.run(client.create(record.clone(), origin.clone())).expect("create failed");
+    assert_eq!(result.get_response_code(), ResponseCode::NoError);
+
+    // type not granted
+    let mut mx = Record::with(record.get_name().clone(),
+                              RecordType::MX,
+                              Duration::minutes(5).num_seconds() as u32);
+    mx.rdata(RData::MX(MX::new(10, origin.clone())));
+
+    let result = io_loop.run(client.create(mx, origin.clone())).expect("create failed");
+    assert_eq!(result.get_response_code(), ResponseCode::Refused);
+
+    // name not granted
+    let mut other = record.clone();
+    other.name(domain::Name::with_labels(vec!["other".to_string(),
+                                              "example".to_string(),
+                                              "com".to_string()]));
+
+    let result = io_loop.run(client.create(other.clone(), origin.clone())).expect("create failed");
+    assert_eq!(result.get_response_code(), ResponseCode::Refused);
+
+    let result = io_loop.run(client.query(other.get_name().clone(),
+                          other.get_dns_class(),
+                          other.get_rr_type()))
+        .expect("query failed");
+    assert!(result.get_answers().is_empty());
+}
+
+/// Records the messages sent by the client, before passing them on
+struct RecordingStreamHandle {
+    sent: Arc<Mutex<Vec<Vec<u8>>>>,
+    stream_handle: Box<ClientStreamHandle>,
+}
+
+impl ClientStreamHandle for RecordingStreamHandle {
+    fn send(&mut self, buffer: Vec<u8>) -> io::Result<()> {
+        self.sent.lock().unwrap().push(buffer.clone());
+        self.stream_handle.send(buffer)
+    }
+}
+
+#[test]
+fn test_create_sig0_clock() {
+    let mut io_loop = Core::new().unwrap();
+    let (catalog, signer, origin) = create_sig0_ready_catalog(None);
+
+    let (stream, stream_handle) = TestClientStream::new(catalog);
+    let sent = Arc::new(Mutex::new(Vec::new()));
+    let stream_handle = RecordingStreamHandle {
+        sent: sent.clone(),
+        stream_handle: stream_handle,
+    };
+    let mut client = ClientFuture::with_clock(stream,
+                                              Box::new(stream_handle),
+                                              io_loop.handle(),
+                                              TimeoutConfig::default(),
+                                              OutstandingConfig::default(),
+                                              Some(signer),
+                                              Arc::new(ManualClock::new(1_500_000_000)));
+
+    let mut record = Record::with(domain::Name::with_labels(vec!["new".to_string(),
+                                                                 "example".to_string(),
+                                                                 "com".to_string()]),
+                                  RecordType::A,
+                                  Duration::minutes(5).num_seconds() as u32);
+    record.rdata(RData::A(Ipv4Addr::new(100, 10, 100, 10)));
+
+    let result = io_loop.run(client.create(record, origin)).expect("create failed");
+    assert_eq!(result.get_response_code(), ResponseCode::NoError);
+
+    // the SIG(0) is valid from the time of the clock, for five minutes
+    let sent = sent.lock().unwrap();
+    assert_eq!(sent.len(), 1);
+    let update = Message::from_vec(&sent[0]).unwrap();
+    let sig0 = update.get_sig0();
+    assert_eq!(sig0.len(), 1);
+    if let &RData::SIG(ref sig) = sig0[0].get_rdata() {
+        assert_eq!(sig.get_sig_inception(), 1_500_000_000);
+        assert_eq!(sig.get_sig_expiration(), 1_500_000_000 + 300);
+    } else {
+        panic!("not a SIG: {:?}", sig0[0]);
+    }
+}
+
 #[test]
 fn test_create_multi() {
     let mut io_loop = Core::new().unwrap();
@@ -821,3 +966,283 @@ fn test_timeout_query_nonet() {
         assert!(false);
     }
 }
+
+#[test]
+fn test_send_with_timeout_nonet() {
+    let mut io_loop = Core::new().unwrap();
+    let (stream, sender) = NeverReturnsClientStream::new();
+    let timeouts = TimeoutConfig {
+        query: std::time::Duration::from_secs(3600),
+        ..TimeoutConfig::default()
+    };
+    let mut client = ClientFuture::with_timeouts(stream, sender, io_loop.handle(), timeouts, None);
+
+    let mut query = Query::new();
+    query.name(domain::Name::new().label("www").label("example").label("com"))
+        .query_type(RecordType::A);
+    let mut message = Message::new();
+    message.add_query(query);
+
+    // the override is used rather than the hour of the default
+    if let &ClientErrorKind::Timeout =
+        io_loop.run(client.send_with_timeout(message, std::time::Duration::from_millis(1)))
+            .unwrap_err()
+            .kind() {
+        ()
+    } else {
+        assert!(false);
+    }
+}
+
+#[test]
+fn test_connect_timeout_nonet() {
+    let mut io_loop = Core::new().unwrap();
+    let (_, sender) = NeverReturnsClientStream::new();
+    let stream: Box<Future<Item = NeverReturnsClientStream, Error = io::Error>> = Box::new(empty());
+    let timeouts = TimeoutConfig {
+        connect: std::time::Duration::from_millis(1),
+        query: std::time::Duration::from_secs(3600),
+        ..TimeoutConfig::default()
+    };
+    let mut client = ClientFuture::with_timeouts(stream, sender, io_loop.handle(), timeouts, None);
+
+    // the connection is never established, the query fails rather than waiting for the hour
+    let name = domain::Name::new().label("www").label("example").label("com");
+    assert!(io_loop.run(client.query(name, DNSClass::IN, RecordType::A)).is_err());
+}
+
+fn outstanding_client(io_loop: &Core, overflow: OverflowPolicy) -> BasicClientHandle {
+    let (stream, sender) = NeverReturnsClientStream::new();
+    let timeouts = TimeoutConfig {
+        query: std::time::Duration::from_secs(3600),
+        ..TimeoutConfig::default()
+    };
+    let outstanding = OutstandingConfig {
+        max_outstanding: 1,
+        overflow: overflow,
+    };
+
+    ClientFuture::with_outstanding(stream, sender, io_loop.handle(), timeouts, outstanding, None)
+}
+
+#[test]
+fn test_outstanding_reject_new_nonet() {
+    let mut io_loop = Core::new().unwrap();
+    let mut client = outstanding_client(&io_loop, OverflowPolicy::RejectNew);
+    let name = domain::Name::new().label("www").label("example").label("com");
+
+    // the first never gets a response, and takes the only place
+    let _first = client.query(name.clone(), DNSClass::IN, RecordType::A);
+    if let &ClientErrorKind::TooManyOutstanding(1) =
+        io_loop.run(client.query(name, DNSClass::IN, RecordType::AAAA)).unwrap_err().kind() {
+        ()
+    } else {
+        assert!(false);
+    }
+
+    let stats = client.get_outstanding_stats();
+    assert_eq!(stats.get_outstanding(), 1);
+    assert_eq!(stats.get_peak(), 1);
+    assert_eq!(stats.get_rejected(), 1);
+    assert_eq!(stats.get_dropped(), 0);
+}
+
+#[test]
+fn test_outstanding_drop_oldest_nonet() {
+    let mut io_loop = Core::new().unwrap();
+    let mut client = outstanding_client(&io_loop, OverflowPolicy::DropOldest);
+    let name = domain::Name::new().label("www").label("example").label("com");
+
+    let first = client.query(name.clone(), DNSClass::IN, RecordType::A);
+    let _second = client.query(name, DNSClass::IN, RecordType::AAAA);
+    if let &ClientErrorKind::TooManyOutstanding(1) = io_loop.run(first).unwrap_err().kind() {
+        ()
+    } else {
+        assert!(false);
+    }
+
+    let stats = client.get_outstanding_stats();
+    assert_eq!(stats.get_outstanding(), 1);
+    assert_eq!(stats.get_rejected(), 0);
+    assert_eq!(stats.get_dropped(), 1);
+}
+
+/// Answers each query twice, first with a response to another question, as a response forged
+///  with a guessed ID would be
+pub struct SpoofedClientStream {
+    outbound_messages: Fuse<UnboundedReceiver<Vec<u8>>>,
+    responses: VecDeque<Vec<u8>>,
+}
+
+impl SpoofedClientStream {
+    pub fn new() -> (Box<Future<Item = Self, Error = io::Error>>, Box<ClientStreamHandle>) {
+        let (message_sender, outbound_messages) = unbounded();
+
+        let stream: Box<Future<Item = SpoofedClientStream, Error = io::Error>> =
+            Box::new(finished(SpoofedClientStream {
+                outbound_messages: outbound_messages.fuse(),
+                responses: VecDeque::new(),
+            }));
+
+        (stream, Box::new(message_sender))
+    }
+
+    fn response(request: &Message, query: Query, address: Ipv4Addr) -> Vec<u8> {
+        let mut response = Message::new();
+        response.id(request.get_id())
+            .message_type(MessageType::Response)
+            .add_answer(Record::from_rdata(query.get_name().clone(),
+                                           86400,
+                                           RecordType::A,
+                                           RData::A(address)))
+            .add_query(query);
+        response.to_vec().expect("could not encode")
+    }
+}
+
+impl Stream for SpoofedClientStream {
+    type Item = Vec<u8>;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        while let Ok(Async::Ready(Some(bytes))) = self.outbound_messages.poll() {
+            let request = Message::from_vec(&bytes).expect("could not decode message");
+            let query = request.get_queries()[0].clone();
+
+            let mut spoofed = query.clone();
+            spoofed.name(domain::Name::new().label("spoofed").label("example").label("com"));
+            let spoofed = Self::response(&request, spoofed, Ipv4Addr::new(192, 0, 2, 66));
+            self.responses.push_back(spoofed);
+            let genuine = Self::response(&request, query, Ipv4Addr::new(192, 0, 2, 1));
+            self.responses.push_back(genuine);
+        }
+
+        match self.responses.pop_front() {
+            Some(response) => Ok(Async::Ready(Some(response))),
+            None => {
+                park().unpark();
+                Ok(Async::NotReady)
+            }
+        }
+    }
+}
+
+impl fmt::Debug for SpoofedClientStream {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SpoofedClientStream")
+    }
+}
+
+#[test]
+fn test_response_echoes_question_nonet() {
+    let mut io_loop = Core::new().unwrap();
+    let (stream, sender) = SpoofedClientStream::new();
+    let mut client = ClientFuture::new(stream, sender, io_loop.handle(), None);
+
+    // the response to another question is ignored, the query waits for the genuine one
+    let name = domain::Name::new().label("www").label("example").label("com");
+    let response = io_loop.run(client.query(name.clone(), DNSClass::IN, RecordType::A))
+        .expect("query failed");
+    assert_eq!(response.get_queries()[0].get_name(), &name);
+    assert_eq!(response.get_answers()[0].get_rdata(),
+               &RData::A(Ipv4Addr::new(192, 0, 2, 1)));
+}
+
+/// Answers each IXFR query with one delta, split over three messages, the later ones without the
+///  question
+pub struct SplitTransferClientStream {
+    outbound_messages: Fuse<UnboundedReceiver<Vec<u8>>>,
+    responses: VecDeque<Vec<u8>>,
+}
+
+impl SplitTransferClientStream {
+    pub fn new() -> (Box<Future<Item = Self, Error = io::Error>>, Box<ClientStreamHandle>) {
+        let (message_sender, outbound_messages) = unbounded();
+
+        let stream: Box<Future<Item = SplitTransferClientStream, Error = io::Error>> =
+            Box::new(finished(SplitTransferClientStream {
+                outbound_messages: outbound_messages.fuse(),
+                responses: VecDeque::new(),
+            }));
+
+        (stream, Box::new(message_sender))
+    }
+
+    fn soa(origin: &domain::Name, serial: u32) -> Record {
+        let soa = SOA::new(origin.clone(), origin.clone(), serial, 3600, 600, 86400, 3600);
+        Record::from_rdata(origin.clone(), 3600, RecordType::SOA, RData::SOA(soa))
+    }
+
+    fn a(origin: &domain::Name, address: Ipv4Addr) -> Record {
+        Record::from_rdata(origin.clone(), 86400, RecordType::A, RData::A(address))
+    }
+
+    fn response(request: &Message, with_query: bool, answers: Vec<Record>) -> Vec<u8> {
+        let mut response = Message::new();
+        response.id(request.get_id()).message_type(MessageType::Response);
+        if with_query {
+            response.add_query(request.get_queries()[0].clone());
+        }
+        for answer in answers {
+            response.add_answer(answer);
+        }
+        response.to_vec().expect("could not encode")
+    }
+}
+
+impl Stream for SplitTransferClientStream {
+    type Item = Vec<u8>;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        while let Ok(Async::Ready(Some(bytes))) = self.outbound_messages.poll() {
+            let request = Message::from_vec(&bytes).expect("could not decode message");
+            let origin = request.get_queries()[0].get_name().clone();
+
+            let first = vec![Self::soa(&origin, 5),
+                             Self::soa(&origin, 4),
+                             Self::a(&origin, Ipv4Addr::new(192, 0, 2, 1))];
+            let second = vec![Self::soa(&origin, 5), Self::a(&origin, Ipv4Addr::new(192, 0, 2, 2))];
+            self.responses.push_back(Self::response(&request, true, first));
+            self.responses.push_back(Self::response(&request, false, second));
+            self.responses.push_back(Self::response(&request, false, vec![Self::soa(&origin, 5)]));
+        }
+
+        match self.responses.pop_front() {
+            Some(response) => Ok(Async::Ready(Some(response))),
+            None => {
+                park().unpark();
+                Ok(Async::NotReady)
+            }
+        }
+    }
+}
+
+impl fmt::Debug for SplitTransferClientStream {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SplitTransferClientStream")
+    }
+}
+
+#[test]
+fn test_ixfr_split_response_nonet() {
+    let mut io_loop = Core::new().unwrap();
+    let (stream, sender) = SplitTransferClientStream::new();
+    let mut client = ClientFuture::new(stream, sender, io_loop.handle(), None);
+
+    // the messages are received until the SOA which ends the transfer
+    let origin = domain::Name::new().label("example").label("com");
+    let response = io_loop.run(client.ixfr(origin.clone(), DNSClass::IN, 4))
+        .expect("ixfr failed");
+    let deltas = match response {
+        IxfrResponse::Incremental(deltas) => deltas,
+        other => panic!("expected deltas: {:?}", other),
+    };
+    assert_eq!(deltas.len(), 1);
+    assert_eq!(deltas[0].get_old_serial(), 4);
+    assert_eq!(deltas[0].get_new_serial(), 5);
+    assert_eq!(deltas[0].get_removed()[0].get_rdata(),
+               &RData::A(Ipv4Addr::new(192, 0, 2, 1)));
+    assert_eq!(deltas[0].get_added()[0].get_rdata(),
+               &RData::A(Ipv4Addr::new(192, 0, 2, 2)));
+}
@@ -60,6 +60,170 @@ fn test_query_nonet() {
     test_query(client);
 }
 
+#[test]
+#[allow(deprecated)]
+fn test_search_nonet() {
+    use trust_dns::client::SearchList;
+
+    let authority = create_example();
+    let mut catalog = Catalog::new();
+    catalog.upsert(authority.get_origin().clone(), authority);
+
+    let client = SyncClient::new(TestClientConnection::new(catalog));
+    let search_list = SearchList::new(vec![domain::Name::parse("example.com.", None).unwrap()],
+                                      1);
+
+    let response = client.search("www", &search_list, DNSClass::IN, RecordType::A)
+        .expect("search failed");
+    assert_eq!(response.get_answers()[0].get_name(),
+               &domain::Name::parse("www.example.com.", None).unwrap());
+
+    // example.com.example.com. does not exist, the NODATA of example.com. is the response
+    let response = client.search("example.com", &search_list, DNSClass::IN, RecordType::MX)
+        .expect("search failed");
+    assert_eq!(response.get_response_code(), ResponseCode::NoError);
+    assert_eq!(response.get_queries()[0].get_name(),
+               &domain::Name::parse("example.com.", None).unwrap());
+    assert!(response.get_answers().is_empty());
+
+    // only the name itself if it is fully qualified
+    let response =
+        client.search("nothing.example.com.", &search_list, DNSClass::IN, RecordType::A)
+            .expect("search failed");
+    assert_eq!(response.get_response_code(), ResponseCode::NXDomain);
+}
+
+#[test]
+#[allow(deprecated)]
+fn test_lookup_ip_nonet() {
+    let authority = create_example();
+    let mut catalog = Catalog::new();
+    catalog.upsert(authority.get_origin().clone(), authority);
+
+    let client = SyncClient::new(TestClientConnection::new(catalog));
+
+    // IPv6 first
+    let addrs = client.lookup_ip(&domain::Name::parse("www.example.com.", None).unwrap())
+        .expect("lookup failed");
+    assert_eq!(addrs,
+               vec![IpAddr::V6(Ipv6Addr::new(0x2606,
+                                             0x2800,
+                                             0x220,
+                                             0x1,
+                                             0x248,
+                                             0x1893,
+                                             0x25c8,
+                                             0x1946)),
+                    IpAddr::V4(Ipv4Addr::new(93, 184, 216, 34))]);
+
+    assert!(client.lookup_ip(&domain::Name::parse("nothing.example.com.", None).unwrap())
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+#[allow(deprecated)]
+fn test_find_update_target_nonet() {
+    let mut authority = create_example();
+    let origin = authority.get_origin().clone();
+    let www = domain::Name::parse("www.example.com.", None).unwrap();
+
+    // a primary master which the catalog resolves
+    let hostmaster = domain::Name::parse("hostmaster.example.com.", None).unwrap();
+    let soa = SOA::new(www.clone(), hostmaster, 2015082404, 7200, 3600, 1209600, 3600);
+    authority.upsert(Record::from_rdata(origin.clone(), 3600, RecordType::SOA, RData::SOA(soa)),
+                     0);
+    let mut catalog = Catalog::new();
+    catalog.upsert(origin.clone(), authority);
+
+    let client = SyncClient::new(TestClientConnection::new(catalog));
+
+    // the apex, a name with other records, and one which does not exist
+    for name in &["example.com.", "www.example.com.", "new.host.example.com."] {
+        let target = client.find_update_target(&domain::Name::parse(name, None).unwrap())
+            .expect("no target");
+        assert_eq!(target.get_zone(), &origin);
+        assert_eq!(target.get_primary(), &www);
+        assert_eq!(target.get_addresses().len(), 2);
+    }
+
+    assert!(client.find_update_target(&domain::Name::parse("www.example.net.", None).unwrap())
+        .is_err());
+}
+
+#[test]
+#[allow(deprecated)]
+fn test_lookup_srv_nonet() {
+    let mut authority = create_example();
+    let www = domain::Name::parse("www.example.com.", None).unwrap();
+    for &(service, priority, port) in &[("_http", 10, 80), ("_https", 0, 443)] {
+        authority.upsert(Record::from_rdata(domain::Name::parse(&format!("{}._tcp.example.com.",
+                                                                        service),
+                                                                None)
+                                                .unwrap(),
+                                            86400,
+                                            RecordType::SRV,
+                                            RData::SRV(SRV::new(priority, 0, port, www.clone()))),
+                         0);
+    }
+    let mut catalog = Catalog::new();
+    catalog.upsert(authority.get_origin().clone(), authority);
+
+    let client = SyncClient::new(TestClientConnection::new(catalog));
+    let example = domain::Name::parse("example.com.", None).unwrap();
+
+    // the addresses of the target are looked up after the SRV records
+    let addrs = client.lookup_srv("http", "tcp", &example).expect("lookup failed");
+    assert_eq!(addrs,
+               vec![SocketAddr::new(IpAddr::V4(Ipv4Addr::new(93, 184, 216, 34)), 80),
+                    SocketAddr::new(IpAddr::V6(Ipv6Addr::new(0x2606,
+                                                             0x2800,
+                                                             0x220,
+                                                             0x1,
+                                                             0x248,
+                                                             0x1893,
+                                                             0x25c8,
+                                                             0x1946)),
+                                    80)]);
+
+    assert_eq!(client.lookup_srv("_https", "_tcp", &example).unwrap().len(), 2);
+    assert!(client.lookup_srv("ldap", "tcp", &example).unwrap().is_empty());
+}
+
+#[test]
+#[allow(deprecated)]
+fn test_discover_designated_resolvers_nonet() {
+    use std::collections::BTreeMap;
+
+    use trust_dns::client::{ddr_name, DesignatedProtocol};
+    use trust_dns::rr::rdata::svcb::SvcParam;
+    use trust_dns_server::authority::{Authority, ZoneType};
+
+    let origin = domain::Name::parse("resolver.arpa.", None).unwrap();
+    let mut resolver_arpa =
+        Authority::new(origin.clone(), BTreeMap::new(), ZoneType::Master, false, false);
+    let www = domain::Name::parse("www.example.com.", None).unwrap();
+    let svcb = SVCB::new(1, www, vec![SvcParam::Alpn(vec!["dot".to_string()])]);
+    resolver_arpa.upsert(Record::from_rdata(ddr_name(), 300, RecordType::SVCB, RData::SVCB(svcb)),
+                         0);
+
+    let authority = create_example();
+    let mut catalog = Catalog::new();
+    catalog.upsert(authority.get_origin().clone(), authority);
+    catalog.upsert(origin, resolver_arpa);
+
+    let client = SyncClient::new(TestClientConnection::new(catalog));
+
+    // the addresses of the target are looked up after the SVCB records
+    let resolvers = client.discover_designated_resolvers().expect("discovery failed");
+    assert_eq!(resolvers.len(), 1);
+    assert_eq!(resolvers[0].get_protocol(), &DesignatedProtocol::Tls);
+    assert_eq!(resolvers[0].get_subject_name(), "www.example.com");
+    assert_eq!(resolvers[0].get_socket_addrs()[0],
+               SocketAddr::new(IpAddr::V4(Ipv4Addr::new(93, 184, 216, 34)), 853));
+    assert_eq!(resolvers[0].get_addresses().len(), 2);
+}
+
 #[test]
 #[ignore]
 #[allow(deprecated)]
@@ -19,16 +19,18 @@ extern crate trust_dns_server;
 
 use std::env;
 use std::path::{Path, PathBuf};
-use std::net::{Ipv4Addr, Ipv6Addr};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 use std::time::Duration;
 
 use log::LogLevel;
 
-use trust_dns::rr::Name;
-use trust_dns::rr::dnssec::Algorithm;
+use trust_dns::rr::{Name, RecordSetLimits};
+use trust_dns::rr::dnssec::{Algorithm, Nsec3HashAlgorithm, TsigAlgorithm};
+use trust_dns::rr::rdata::NSEC3PARAM;
 
-use trust_dns_server::authority::ZoneType;
+use trust_dns_server::authority::{ForwardUpstream, TransferAcl, TransferRule, ZoneType};
 use trust_dns_server::config::*;
+use trust_dns_server::server::{ListenerPolicy, MdnsConfig, MdnsService};
 
 #[test]
 fn test_read_config() {
@@ -115,11 +117,41 @@ fn test_parse_toml() {
     let config: Config = "tcp_request_timeout = 25".parse().unwrap();
     assert_eq!(config.get_tcp_request_timeout(), Duration::from_secs(25));
 
+    let config: Config = "tcp_read_timeout = 3".parse().unwrap();
+    assert_eq!(config.get_tcp_read_timeout(), Duration::from_secs(3));
+    assert_eq!(config.get_tcp_limits().read_timeout, Duration::from_secs(3));
+
+    let config: Config = "tcp_write_timeout = 4".parse().unwrap();
+    assert_eq!(config.get_tcp_write_timeout(), Duration::from_secs(4));
+    assert_eq!(config.get_tcp_limits().write_timeout, Duration::from_secs(4));
+
+    let config: Config = "tcp_max_message_len = 4096".parse().unwrap();
+    assert_eq!(config.get_tcp_max_message_len(), 4096);
+
+    let config: Config = "tcp_max_unanswered = 4".parse().unwrap();
+    assert_eq!(config.get_tcp_max_unanswered(), 4);
+
+    let config: Config = "udp_policy = { allow_zone_transfer = false, allow_notify = false }"
+        .parse()
+        .unwrap();
+    assert_eq!(config.get_udp_policy(),
+               ListenerPolicy {
+                   zone_transfer: false,
+                   notify: false,
+                   ..ListenerPolicy::default()
+               });
+    assert_eq!(config.get_tcp_policy(), ListenerPolicy::default());
+    assert_eq!(config.get_tls_policy(), ListenerPolicy::default());
+
     let config: Config = "log_level = \"Debug\"".parse().unwrap();
     assert_eq!(config.get_log_level(), LogLevel::Debug);
 
     let config: Config = "directory = \"/dev/null\"".parse().unwrap();
     assert_eq!(config.get_directory(), Path::new("/dev/null"));
+    assert_eq!(config.get_zone_watch_interval(), None);
+
+    let config: Config = "zone_watch_secs = 10".parse().unwrap();
+    assert_eq!(config.get_zone_watch_interval(), Some(Duration::from_secs(10)));
 }
 
 #[test]
@@ -177,6 +209,306 @@ signer_name = \"ns.example.com.\"
                false);
 }
 
+#[test]
+fn test_parse_primary() {
+    let config: Config = "
+[[zones]]
+zone = \"example.com\"
+zone_type = \"Slave\"
+file = \"example.com.zone\"
+primary = \"192.0.2.1:53\"
+"
+        .parse()
+        .unwrap();
+
+    assert_eq!(config.get_zones()[0].get_primary().unwrap(),
+               Some("192.0.2.1:53".parse().unwrap()));
+
+    let config: Config = "
+[[zones]]
+zone = \"example.com\"
+zone_type = \"Slave\"
+file = \"example.com.zone\"
+primary = \"192.0.2.1\"
+"
+        .parse()
+        .unwrap();
+    assert!(config.get_zones()[0].get_primary().is_err());
+}
+
+#[test]
+fn test_parse_secondaries() {
+    let config: Config = "
+[[zones]]
+zone = \"example.com\"
+zone_type = \"Master\"
+file = \"example.com.zone\"
+secondaries = [\"192.0.2.2:53\", \"[2001:db8::2]:53\"]
+"
+        .parse()
+        .unwrap();
+
+    assert_eq!(config.get_zones()[0].get_secondaries().unwrap(),
+               vec!["192.0.2.2:53".parse().unwrap(), "[2001:db8::2]:53".parse().unwrap()]);
+}
+
+#[test]
+fn test_parse_redis_store() {
+    let config: Config = "
+[[zones]]
+zone = \"example.com\"
+zone_type = \"Master\"
+file = \"example.com.zone\"
+redis_store = \"127.0.0.1:6379\"
+store_cache_secs = 30
+
+[[zones]]
+zone = \"example.net\"
+zone_type = \"Master\"
+file = \"example.net.zone\"
+"
+        .parse()
+        .unwrap();
+
+    assert_eq!(config.get_zones()[0].get_redis_store().unwrap(),
+               Some("127.0.0.1:6379".parse().unwrap()));
+    assert_eq!(config.get_zones()[0].get_store_cache_ttl(), Duration::from_secs(30));
+    assert_eq!(config.get_zones()[1].get_redis_store().unwrap(), None);
+    assert_eq!(config.get_zones()[1].get_store_cache_ttl(), Duration::from_secs(10));
+}
+
+#[test]
+fn test_in_memory_options() {
+    let config: Config = "
+[[zones]]
+zone = \"example.com\"
+zone_type = \"Master\"
+file = \"example.com.zone\"
+redis_store = \"127.0.0.1:6379\"
+
+[[zones]]
+zone = \"example.net\"
+zone_type = \"Slave\"
+file = \"example.net.zone\"
+redis_store = \"127.0.0.1:6379\"
+primary = \"192.0.2.1:53\"
+listen_addrs = [\"192.0.2.53\"]
+enable_dnssec = true
+"
+        .parse()
+        .unwrap();
+
+    assert!(config.get_zones()[0].get_in_memory_options().is_empty());
+    assert_eq!(config.get_zones()[1].get_in_memory_options(),
+               vec!["zone_type", "primary", "listen_addrs", "enable_dnssec"]);
+}
+
+#[test]
+fn test_parse_apex_alias() {
+    let config: Config = "
+[[zones]]
+zone = \"example.com\"
+zone_type = \"Master\"
+file = \"example.com.zone\"
+apex_alias = \"lb.example.net\"
+"
+        .parse()
+        .unwrap();
+
+    assert_eq!(config.get_zones()[0].get_apex_alias().unwrap(),
+               Some(Name::parse("lb.example.net.", None).unwrap()));
+}
+
+#[test]
+fn test_parse_rrset_limits() {
+    let config: Config = "
+[[zones]]
+zone = \"example.com\"
+zone_type = \"Master\"
+file = \"example.com.zone\"
+max_rrset_records = 10
+journal_compaction = 1000
+"
+        .parse()
+        .unwrap();
+
+    let mut limits = RecordSetLimits::default();
+    limits.max_records(10);
+    assert_eq!(config.get_zones()[0].get_rrset_limits(), limits);
+    assert_eq!(config.get_zones()[0].get_journal_compaction(), Some(1000));
+}
+
+#[test]
+fn test_parse_nsec3() {
+    let config: Config = "
+[[zones]]
+zone = \"example.com\"
+zone_type = \"Master\"
+file = \"example.com.zone\"
+enable_nsec3 = true
+nsec3_iterations = 12
+nsec3_salt = \"aabbccdd\"
+nsec3_opt_out = true
+"
+        .parse()
+        .unwrap();
+
+    assert_eq!(config.get_zones()[0].get_nsec3_param().unwrap(),
+               Some(NSEC3PARAM::new(Nsec3HashAlgorithm::SHA1,
+                                    true,
+                                    12,
+                                    vec![0xAA, 0xBB, 0xCC, 0xDD])));
+}
+
+#[test]
+fn test_parse_allow_transfer() {
+    let config: Config = "
+[[zones]]
+zone = \"example.com\"
+zone_type = \"Master\"
+file = \"example.com.zone\"
+allow_transfer = [\"192.0.2.0/24\", \"2001:db8::1\", \"key transfer.example.com.\"]
+"
+        .parse()
+        .unwrap();
+
+    assert_eq!(config.get_zones()[0].get_transfer_acl().unwrap(),
+               Some(TransferAcl::new(vec![
+                   TransferRule::Network(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 0)), 24),
+                   TransferRule::Network(IpAddr::V6("2001:db8::1".parse().unwrap()), 128),
+                   TransferRule::Key(Name::parse("transfer.example.com.", None).unwrap()),
+               ])));
+
+    let config: Config = "
+[[zones]]
+zone = \"example.com\"
+zone_type = \"Master\"
+file = \"example.com.zone\"
+allow_transfer = [\"192.0.2.0/33\"]
+"
+        .parse()
+        .unwrap();
+    assert!(config.get_zones()[0].get_transfer_acl().is_err());
+}
+
+#[test]
+fn test_parse_tsig_keys() {
+    let config: Config = "
+[[tsig_keys]]
+name = \"transfer-key.example.com.\"
+algorithm = \"hmac-sha256\"
+secret = \"dGhlIHNlY3JldCBvZiB0aGUgdHJhbnNmZXJz\"
+
+[[zones]]
+zone = \"example.com\"
+zone_type = \"Slave\"
+file = \"example.com.zone\"
+primary = \"192.0.2.1:53\"
+primary_key = \"transfer-key.example.com.\"
+transfer_source = \"192.0.2.53\"
+"
+        .parse()
+        .unwrap();
+
+    let keys = config.get_tsig_keys();
+    assert_eq!(keys.len(), 1);
+    assert_eq!(keys[0].get_name().unwrap(),
+               Name::parse("transfer-key.example.com.", None).unwrap());
+    assert_eq!(keys[0].get_algorithm().unwrap(), TsigAlgorithm::HmacSha256);
+    assert_eq!(keys[0].get_secret().unwrap(), b"the secret of the transfers".to_vec());
+
+    let zone = &config.get_zones()[0];
+    assert_eq!(zone.get_primary_key().unwrap(),
+               Some(Name::parse("transfer-key.example.com.", None).unwrap()));
+    assert_eq!(zone.get_transfer_source().unwrap(),
+               Some(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 53))));
+
+    let config: Config = "
+[[tsig_keys]]
+name = \"transfer-key.example.com.\"
+algorithm = \"hmac-md5\"
+secret = \"not base64!\"
+
+[[zones]]
+zone = \"example.com\"
+zone_type = \"Slave\"
+file = \"example.com.zone\"
+transfer_source = \"192.0.2.53:53\"
+"
+        .parse()
+        .unwrap();
+    assert!(config.get_tsig_keys()[0].get_algorithm().is_err());
+    assert!(config.get_tsig_keys()[0].get_secret().is_err());
+    assert!(config.get_zones()[0].get_transfer_source().is_err());
+    assert_eq!(config.get_zones()[0].get_primary_key().unwrap(), None);
+}
+
+#[test]
+fn test_parse_listen_addrs() {
+    let config: Config = "
+[[zones]]
+zone = \"example.com\"
+zone_type = \"Master\"
+file = \"example.com.zone\"
+listen_addrs = [\"10.0.0.5\", \"2001:db8::5\"]
+
+[[zones]]
+zone = \"example.net\"
+zone_type = \"Master\"
+file = \"example.net.zone\"
+"
+        .parse()
+        .unwrap();
+
+    assert_eq!(config.get_zones()[0].get_listen_addrs().unwrap(),
+               Some(vec![IpAddr::V4(Ipv4Addr::new(10, 0, 0, 5)),
+                         IpAddr::V6("2001:db8::5".parse().unwrap())]));
+    assert_eq!(config.get_zones()[1].get_listen_addrs().unwrap(), None);
+
+    let config: Config = "
+[[zones]]
+zone = \"example.com\"
+zone_type = \"Master\"
+file = \"example.com.zone\"
+listen_addrs = [\"10.0.0.256\"]
+"
+        .parse()
+        .unwrap();
+    assert!(config.get_zones()[0].get_listen_addrs().is_err());
+}
+
+#[test]
+fn test_parse_forwarders() {
+    let config: Config = "
+[[zones]]
+zone = \"corp.example.com\"
+zone_type = \"Forward\"
+file = \"\"
+forwarders = [\"192.0.2.53:53\",
+              \"tcp://[2001:db8::53]:53\",
+              \"tls://192.0.2.54:853#dns.example.com\"]
+forward_cache = true
+forward_shadow = \"tcp://192.0.2.55:53\"
+"
+        .parse()
+        .unwrap();
+
+    let zone = &config.get_zones()[0];
+    assert_eq!(zone.get_zone_type(), ZoneType::Forward);
+    assert!(zone.is_forward_cache_enabled());
+    assert_eq!(zone.get_forward_shadow().unwrap(),
+               Some(ForwardUpstream::Tcp("192.0.2.55:53".parse().unwrap())));
+    assert_eq!(zone.get_forwarders().unwrap(),
+               vec![ForwardUpstream::Udp("192.0.2.53:53".parse().unwrap()),
+                    ForwardUpstream::Tcp("[2001:db8::53]:53".parse().unwrap()),
+                    ForwardUpstream::Tls("192.0.2.54:853".parse().unwrap(),
+                                         "dns.example.com".to_string())]);
+
+    assert!("quic://192.0.2.53:53".parse::<ForwardUpstream>().is_err());
+    assert!("tls://192.0.2.54:853".parse::<ForwardUpstream>().is_err());
+    assert!("192.0.2.53".parse::<ForwardUpstream>().is_err());
+}
+
 #[test]
 fn test_parse_tls() {
     // defaults
@@ -200,3 +532,93 @@ tls_listen_port = 8853
     assert_eq!(config.get_tls_cert().unwrap().get_subject_name(),
                "ns.example.com");
 }
+
+#[test]
+fn test_parse_mdns() {
+    let config: Config = "".parse().unwrap();
+    assert_eq!(config.get_mdns(), None);
+
+    let config: Config = "
+[mdns]
+hostname = \"printer\"
+addresses = [\"192.168.0.2\"]
+services = [{ instance = \"Office Printer\", service_type = \"_ipp._tcp\", port = 631, \
+              txt = [\"rp=printers/office\"] }]
+  "
+        .parse()
+        .unwrap();
+
+    assert_eq!(config.get_mdns().unwrap().get_config().unwrap(),
+               MdnsConfig {
+                   hostname: "printer".to_string(),
+                   addresses: vec![IpAddr::V4(Ipv4Addr::new(192, 168, 0, 2))],
+                   services: vec![MdnsService {
+                                      instance: "Office Printer".to_string(),
+                                      service_type: "_ipp._tcp".to_string(),
+                                      port: 631,
+                                      txt: vec!["rp=printers/office".to_string()],
+                                  }],
+               });
+}
+
+#[test]
+fn test_parse_variables() {
+    let config: Config = "
+variables = { site = \"east\", primary = \"192.0.2.1:53\" }
+
+[[zones]]
+zone = \"${site}.example.com\"
+zone_type = \"Slave\"
+file = \"${site}.example.com.zone\"
+primary = \"${primary}\"
+"
+        .parse()
+        .unwrap();
+
+    let zone = &config.get_zones()[0];
+    assert_eq!(zone.get_zone().unwrap(),
+               Name::parse("east.example.com.", None).unwrap());
+    assert_eq!(zone.get_file(), PathBuf::from("east.example.com.zone"));
+    assert_eq!(zone.get_primary().unwrap(), Some("192.0.2.1:53".parse().unwrap()));
+    assert_eq!(config.get_variables().get("site"), Some("east".to_string()));
+
+    assert!("
+[[zones]]
+zone = \"${undefined_variable}.example.com\"
+zone_type = \"Master\"
+file = \"example.com.zone\"
+"
+        .parse::<Config>()
+        .is_err());
+
+    // the environment is not a source of variables
+    env::set_var("TDNS_TEST_PRIMARY", "192.0.2.1:53");
+    assert!("
+[[zones]]
+zone = \"example.com\"
+zone_type = \"Slave\"
+file = \"example.com.zone\"
+primary = \"${TDNS_TEST_PRIMARY}\"
+"
+        .parse::<Config>()
+        .is_err());
+}
+
+#[test]
+fn test_substitute_variables() {
+    let mut variables = TemplateVariables::new();
+    variables.insert("ipv4".to_string(), "192.0.2.10".to_string());
+
+    assert_eq!(variables.substitute("www IN A ${ipv4}\n").unwrap(),
+               "www IN A 192.0.2.10\n");
+    // the offsets of $GENERATE are not variables
+    assert_eq!(variables.substitute("$GENERATE 1-2 host-$ A 192.0.2.${0,3,d} ${ipv4}").unwrap(),
+               "$GENERATE 1-2 host-$ A 192.0.2.${0,3,d} 192.0.2.10");
+    assert_eq!(variables.substitute("unterminated ${ipv4").unwrap(),
+               "unterminated ${ipv4");
+    assert!(variables.substitute("${undefined_variable}").is_err());
+
+    // in zone files only the defined variables are replaced
+    assert_eq!(variables.substitute_defined("txt IN TXT \"${undefined_variable}\" ${ipv4}"),
+               "txt IN TXT \"${undefined_variable}\" 192.0.2.10");
+}
@@ -0,0 +1,189 @@
+extern crate chrono;
+extern crate futures;
+extern crate openssl;
+extern crate trust_dns;
+extern crate trust_dns_server;
+
+use std::cell::Cell;
+use std::net::*;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::Stream;
+use futures::stream::Wait;
+use futures::sync::mpsc::{unbounded, UnboundedReceiver};
+
+use trust_dns::op::*;
+use trust_dns::op::dso::PUSH_REMOVED_TTL;
+use trust_dns::rr::*;
+use trust_dns::rr::rdata::NULL;
+
+use trust_dns_server::authority::Catalog;
+use trust_dns_server::server::{DnsPush, DsoConfig, DsoSession, ResponseHandle};
+
+mod common;
+use common::authority::create_example;
+
+type Pushed = Wait<UnboundedReceiver<(Vec<u8>, SocketAddr)>>;
+
+fn create_session(dso: DsoConfig, port: u16) -> (DsoSession, Pushed) {
+    let (sender, receiver) = unbounded();
+    let addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), port);
+    let session = DsoSession::new(dso,
+                                  Rc::new(Cell::new(Duration::from_secs(5))),
+                                  ResponseHandle::new(addr, sender));
+
+    (session, receiver.wait())
+}
+
+fn subscribe(id: u16, name: &Name, query_type: RecordType) -> Message {
+    let mut query = Query::new();
+    query.name(name.clone()).query_type(query_type);
+
+    let mut message = Message::new();
+    message.id(id).op_code(OpCode::Dso).add_dso_tlv(DsoTlv::Subscribe(query));
+    message
+}
+
+fn next_push(pushed: &mut Pushed) -> Vec<Record> {
+    let (buffer, _) = pushed.next().unwrap().unwrap();
+    let message = Message::from_vec(&buffer).unwrap();
+    assert_eq!(message.get_id(), 0);
+    assert_eq!(message.get_op_code(), OpCode::Dso);
+
+    match message.get_dso_tlvs()[0] {
+        DsoTlv::Push(ref records) => records.clone(),
+        ref tlv => panic!("expected a PUSH: {:?}", tlv),
+    }
+}
+
+#[test]
+fn test_subscribe_and_push() {
+    let example = create_example();
+    let origin = example.get_origin().clone();
+    let mut catalog = Catalog::new();
+    catalog.upsert(origin.clone(), example);
+
+    let push = Arc::new(DnsPush::new());
+    let mut dso = DsoConfig::default();
+    DnsPush::register(&push, &catalog, &mut dso);
+    let (mut session, mut pushed) = create_session(dso.clone(), 5353);
+
+    let www = Name::parse("www.example.com.", None).unwrap();
+    let response = session.handle_request(&subscribe(10, &www, RecordType::A), &catalog)
+        .unwrap()
+        .expect("no response");
+    assert_eq!(response.get_response_code(), ResponseCode::NoError);
+    assert!(session.is_established());
+    assert_eq!(push.get_subscription_count(), 1);
+
+    // the current records follow the response
+    session.send_pending().unwrap();
+    let current = next_push(&mut pushed);
+    assert_eq!(current.len(), 1);
+    assert_eq!(current[0].get_rr_type(), RecordType::A);
+
+    // an added record
+    let added = Record::new()
+        .name(www.clone())
+        .ttl(86400)
+        .rr_type(RecordType::A)
+        .dns_class(DNSClass::IN)
+        .rdata(RData::A(Ipv4Addr::new(93, 184, 216, 24)))
+        .clone();
+    {
+        let mut authority = catalog.find_auth_recurse(&www).unwrap().write();
+        let serial = authority.get_serial();
+
+        // not subscribed to
+        let mut aaaa = added.clone();
+        aaaa.rr_type(RecordType::AAAA)
+            .rdata(RData::AAAA(Ipv6Addr::new(0x2606, 0x2800, 0x220, 0x1, 0, 0, 0, 1)));
+        assert!(authority.upsert(aaaa, serial));
+
+        assert!(authority.upsert(added.clone(), serial));
+    }
+    assert_eq!(next_push(&mut pushed), vec![added.clone()]);
+
+    // the removed RRSet
+    {
+        let delete = Record::new()
+            .name(www.clone())
+            .ttl(0)
+            .rr_type(RecordType::A)
+            .dns_class(DNSClass::ANY)
+            .rdata(RData::NULL(NULL::new()))
+            .clone();
+        let mut authority = catalog.find_auth_recurse(&www).unwrap().write();
+        assert!(authority.update_records(&[delete], true).unwrap());
+    }
+    let removed = next_push(&mut pushed);
+    assert_eq!(removed.len(), 2);
+    assert!(removed.iter().all(|r| r.get_ttl() == PUSH_REMOVED_TTL));
+    assert!(removed.contains(&current[0]));
+    assert!(removed.contains(&added));
+
+    // unsubscribing only affects the subscriptions of the same session
+    let (mut other, _other_pushed) = create_session(dso, 5354);
+    let mut unsubscribe = Message::new();
+    unsubscribe.id(0).op_code(OpCode::Dso).add_dso_tlv(DsoTlv::Unsubscribe(10));
+
+    assert!(other.handle_request(&unsubscribe, &catalog).unwrap().is_none());
+    assert_eq!(push.get_subscription_count(), 1);
+    assert!(session.handle_request(&unsubscribe, &catalog).unwrap().is_none());
+    assert_eq!(push.get_subscription_count(), 0);
+}
+
+#[test]
+fn test_subscribe_errors() {
+    let example = create_example();
+    let origin = example.get_origin().clone();
+    let mut catalog = Catalog::new();
+    catalog.upsert(origin.clone(), example);
+
+    let push = Arc::new(DnsPush::new());
+    let mut dso = DsoConfig::default();
+    DnsPush::register(&push, &catalog, &mut dso);
+    let (mut session, _pushed) = create_session(dso, 5353);
+
+    // not in the catalog
+    let other = Name::parse("www.example.net.", None).unwrap();
+    let response = session.handle_request(&subscribe(10, &other, RecordType::A), &catalog)
+        .unwrap()
+        .expect("no response");
+    assert_eq!(response.get_response_code(), ResponseCode::NotAuth);
+    assert!(!session.is_established());
+    assert_eq!(push.get_subscription_count(), 0);
+}
+
+#[test]
+fn test_reconfirm() {
+    let example = create_example();
+    let origin = example.get_origin().clone();
+    let mut catalog = Catalog::new();
+    catalog.upsert(origin.clone(), example);
+
+    let push = Arc::new(DnsPush::new());
+    let mut dso = DsoConfig::default();
+    DnsPush::register(&push, &catalog, &mut dso);
+    let (mut session, mut pushed) = create_session(dso, 5353);
+
+    let www = Name::parse("www.example.com.", None).unwrap();
+    session.handle_request(&subscribe(10, &www, RecordType::A), &catalog).unwrap();
+    session.send_pending().unwrap();
+    let current = next_push(&mut pushed);
+
+    let mut gone = current[0].clone();
+    gone.rdata(RData::A(Ipv4Addr::new(127, 0, 0, 1)));
+    for record in &[current[0].clone(), gone.clone()] {
+        let mut reconfirm = Message::new();
+        reconfirm.id(0).op_code(OpCode::Dso).add_dso_tlv(DsoTlv::Reconfirm(record.clone()));
+        assert!(session.handle_request(&reconfirm, &catalog).unwrap().is_none());
+    }
+
+    // only the record which is not in the zone is removed
+    let removed = next_push(&mut pushed);
+    assert_eq!(removed, vec![gone]);
+    assert_eq!(removed[0].get_ttl(), PUSH_REMOVED_TTL);
+}
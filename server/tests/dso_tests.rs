@@ -0,0 +1,162 @@
+extern crate futures;
+extern crate trust_dns;
+extern crate trust_dns_server;
+
+use std::cell::Cell;
+use std::net::SocketAddr;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::Stream;
+use futures::sync::mpsc::{unbounded, UnboundedReceiver};
+
+use trust_dns::op::*;
+use trust_dns::rr::Record;
+
+use trust_dns_server::authority::Catalog;
+use trust_dns_server::server::{DsoConfig, DsoHandler, DsoSession, ResponseHandle};
+
+const TEST_TYPE: u16 = 0xF000;
+
+fn create_session(config: DsoConfig)
+                  -> (DsoSession, Rc<Cell<Duration>>, UnboundedReceiver<(Vec<u8>, SocketAddr)>) {
+    let (sender, receiver) = unbounded();
+    let idle_timeout = Rc::new(Cell::new(Duration::from_secs(5)));
+    let addr: SocketAddr = "127.0.0.1:5353".parse().unwrap();
+
+    let session = DsoSession::new(config, idle_timeout.clone(), ResponseHandle::new(addr, sender));
+    (session, idle_timeout, receiver)
+}
+
+fn dso_request(id: u16, tlv: DsoTlv) -> Message {
+    let mut message = Message::new();
+    message.id(id).op_code(OpCode::Dso).add_dso_tlv(tlv);
+    message
+}
+
+#[test]
+fn test_keepalive() {
+    let (mut session, idle_timeout, _receiver) = create_session(DsoConfig::default());
+    assert!(!session.is_established());
+
+    let request = dso_request(10,
+                              DsoTlv::KeepAlive {
+                                  inactivity_timeout: 0,
+                                  keepalive_interval: 0,
+                              });
+    let response = session.handle_request(&request, &Catalog::new()).unwrap().expect("no response");
+
+    assert!(session.is_established());
+    assert_eq!(response.get_id(), 10);
+    assert_eq!(response.get_op_code(), OpCode::Dso);
+    assert_eq!(response.get_message_type(), MessageType::Response);
+    assert_eq!(response.get_response_code(), ResponseCode::NoError);
+    assert_eq!(response.get_dso_tlvs(),
+               &[DsoTlv::KeepAlive {
+                     inactivity_timeout: 15000,
+                     keepalive_interval: 3600000,
+                 }]);
+
+    // twice the inactivity timeout
+    assert_eq!(idle_timeout.get(), Duration::from_secs(30));
+}
+
+#[test]
+fn test_keepalive_infinite() {
+    let config = DsoConfig {
+        inactivity_timeout: Duration::from_millis(u32::max_value() as u64),
+        ..DsoConfig::default()
+    };
+    let (mut session, idle_timeout, _receiver) = create_session(config);
+
+    let request = dso_request(10,
+                              DsoTlv::KeepAlive {
+                                  inactivity_timeout: 0,
+                                  keepalive_interval: 0,
+                              });
+    session.handle_request(&request, &Catalog::new()).unwrap().expect("no response");
+
+    // disabled
+    assert_eq!(idle_timeout.get(), Duration::from_secs(0));
+}
+
+#[test]
+fn test_bad_requests() {
+    let (mut session, _, _receiver) = create_session(DsoConfig::default());
+    let catalog = Catalog::new();
+
+    // unknown type
+    let request = dso_request(10, DsoTlv::Unknown(TEST_TYPE, vec![]));
+    assert_eq!(session.handle_request(&request, &catalog).unwrap().unwrap().get_response_code(),
+               ResponseCode::DSOTYPENI);
+
+    // padding is never the primary TLV
+    let request = dso_request(10, DsoTlv::EncryptionPadding(vec![0; 4]));
+    assert_eq!(session.handle_request(&request, &catalog).unwrap().unwrap().get_response_code(),
+               ResponseCode::FormErr);
+
+    // no TLV
+    let mut request = Message::new();
+    request.id(10).op_code(OpCode::Dso);
+    assert_eq!(session.handle_request(&request, &catalog).unwrap().unwrap().get_response_code(),
+               ResponseCode::FormErr);
+
+    // records
+    let mut request = dso_request(10, DsoTlv::RetryDelay(0));
+    request.add_answer(Record::new());
+    assert_eq!(session.handle_request(&request, &catalog).unwrap().unwrap().get_response_code(),
+               ResponseCode::FormErr);
+
+    assert!(!session.is_established());
+
+    // protocol errors close the connection
+    assert!(session.handle_request(&dso_request(10, DsoTlv::RetryDelay(0)), &catalog).is_err());
+    assert!(session.handle_request(&dso_request(0, DsoTlv::Unknown(TEST_TYPE, vec![])), &catalog)
+        .is_err());
+    assert!(session.handle_request(&dso_request(0,
+                                               DsoTlv::KeepAlive {
+                                                   inactivity_timeout: 0,
+                                                   keepalive_interval: 0,
+                                               }),
+                                &catalog)
+        .is_err());
+}
+
+struct PushHandler;
+
+impl DsoHandler for PushHandler {
+    fn handle_request(&self,
+                      session: &mut DsoSession,
+                      request: &Message,
+                      _catalog: &Catalog)
+                      -> Option<Message> {
+        let response = Message::error_msg(request.get_id(), OpCode::Dso, ResponseCode::NoError);
+        assert!(session.send(response.clone()).is_err());
+
+        Some(response)
+    }
+}
+
+#[test]
+fn test_handler_and_push() {
+    let mut config = DsoConfig::default();
+    config.set_handler(DsoType::Unknown(TEST_TYPE), Arc::new(PushHandler));
+    let (mut session, idle_timeout, receiver) = create_session(config);
+
+    // not established yet
+    assert!(session.retry_delay(Duration::from_secs(1)).is_err());
+
+    let request = dso_request(10, DsoTlv::Unknown(TEST_TYPE, vec![1, 2, 3]));
+    let response = session.handle_request(&request, &Catalog::new()).unwrap().expect("no response");
+    assert_eq!(response.get_response_code(), ResponseCode::NoError);
+    assert!(session.is_established());
+    assert_eq!(idle_timeout.get(), Duration::from_secs(30));
+
+    session.retry_delay(Duration::from_secs(1)).expect("retry delay failed");
+    let (buffer, _) = receiver.wait().next().unwrap().unwrap();
+    let retry = Message::from_vec(&buffer).unwrap();
+    assert_eq!(retry.get_id(), 0);
+    assert_eq!(retry.get_op_code(), OpCode::Dso);
+    assert_eq!(retry.get_dso_tlvs(), &[DsoTlv::RetryDelay(1000)]);
+}
@@ -0,0 +1,155 @@
+extern crate trust_dns;
+extern crate trust_dns_server;
+
+use std::iter;
+
+use trust_dns::op::{Message, Query};
+use trust_dns::rr::{Name, RecordType};
+use trust_dns_server::server::{H2Connection, MAX_HEADER_LIST_SIZE};
+
+const PREFACE: &'static [u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+
+const DATA: u8 = 0x0;
+const HEADERS: u8 = 0x1;
+const SETTINGS: u8 = 0x4;
+const GOAWAY: u8 = 0x7;
+const CONTINUATION: u8 = 0x9;
+const END_STREAM: u8 = 0x1;
+const END_HEADERS: u8 = 0x4;
+const ENHANCE_YOUR_CALM: u32 = 0xB;
+
+/// Appends the HTTP/2 frame, RFC 9113 section 4.1
+fn h2_frame(buffer: &mut Vec<u8>, frame_type: u8, flags: u8, stream_id: u32, payload: &[u8]) {
+    let len = payload.len();
+    buffer.extend_from_slice(&[(len >> 16) as u8, (len >> 8) as u8, len as u8, frame_type, flags]);
+    buffer.extend_from_slice(&u32_bytes(stream_id));
+    buffer.extend_from_slice(payload);
+}
+
+/// Returns the type and payload of each frame sent by the server
+fn frames(mut out: &[u8]) -> Vec<(u8, Vec<u8>)> {
+    let mut frames = Vec::new();
+    while !out.is_empty() {
+        let len = (out[0] as usize) << 16 | (out[1] as usize) << 8 | out[2] as usize;
+        frames.push((out[3], out[9..9 + len].to_vec()));
+        out = &out[9 + len..];
+    }
+
+    frames
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    (bytes[0] as u32) << 24 | (bytes[1] as u32) << 16 | (bytes[2] as u32) << 8 | bytes[3] as u32
+}
+
+fn u32_bytes(value: u32) -> [u8; 4] {
+    [(value >> 24) as u8, (value >> 16) as u8, (value >> 8) as u8, value as u8]
+}
+
+/// The error code of the GOAWAY the server sent last, if it sent one
+fn goaway_error(out: &[u8]) -> Option<u32> {
+    frames(out)
+        .into_iter()
+        .rev()
+        .find(|&(frame_type, _)| frame_type == GOAWAY)
+        .map(|(_, payload)| read_u32(&payload[4..]))
+}
+
+/// The header block of a POST, literals without indexing or Huffman coding
+fn request_block() -> Vec<u8> {
+    let mut block = Vec::new();
+    for &(name, value) in &[(":method", "POST"),
+                            (":scheme", "https"),
+                            (":authority", "ns.example.com"),
+                            (":path", "/dns-query"),
+                            ("content-type", "application/dns-message")] {
+        block.push(0x00);
+        block.push(name.len() as u8);
+        block.extend_from_slice(name.as_bytes());
+        block.push(value.len() as u8);
+        block.extend_from_slice(value.as_bytes());
+    }
+
+    block
+}
+
+/// The DNS message of the request
+fn request_message() -> Vec<u8> {
+    let mut query = Query::new();
+    query.name(Name::parse("www.example.com.", None).unwrap()).query_type(RecordType::A);
+    let mut message = Message::new();
+    message.id(0).add_query(query);
+    message.to_vec().unwrap()
+}
+
+/// Returns the connection after the preface and SETTINGS of the client, and what it sent
+fn connect() -> (H2Connection, Vec<u8>) {
+    let mut out = Vec::new();
+    let mut connection = H2Connection::new("/dns-query".to_string(), &mut out);
+
+    let mut request = PREFACE.to_vec();
+    h2_frame(&mut request, SETTINGS, 0, 0, &[]);
+    connection.receive(&request, &mut out);
+    assert!(!connection.is_closed());
+
+    (connection, out)
+}
+
+#[test]
+fn test_max_header_list_size() {
+    let (mut connection, out) = connect();
+
+    // the limit is advertised, SETTINGS_MAX_HEADER_LIST_SIZE
+    let settings = frames(&out).into_iter().find(|&(frame_type, _)| frame_type == SETTINGS);
+    let settings = settings.expect("no SETTINGS").1;
+    assert!(settings.chunks(6)
+        .any(|setting| setting[..2] == [0x00, 0x06] &&
+                       read_u32(&setting[2..]) == MAX_HEADER_LIST_SIZE as u32));
+
+    // a field of 4000 octets, then a block of references to it, each of a single octet
+    let mut block = vec![0x40, 0x01, b'x', 0x7f, 0xa1, 0x1e];
+    block.extend(iter::repeat(b'a').take(4000));
+    let field_size = 1 + 4000 + 32;
+    let references = vec![0xbe; MAX_HEADER_LIST_SIZE / field_size + 1];
+    let mut request = Vec::new();
+    h2_frame(&mut request, HEADERS, END_HEADERS | END_STREAM, 1, &block);
+    h2_frame(&mut request, HEADERS, END_HEADERS | END_STREAM, 3, &references);
+
+    let mut out = Vec::new();
+    connection.receive(&request, &mut out);
+    assert!(connection.is_closed());
+    assert_eq!(goaway_error(&out), Some(ENHANCE_YOUR_CALM));
+}
+
+#[test]
+fn test_max_continuation_frames() {
+    // a request split across CONTINUATION frames is answered
+    let (mut connection, _) = connect();
+    let block = request_block();
+    let mut request = Vec::new();
+    let mut fragments = block.chunks(block.len() / 3 + 1).peekable();
+    h2_frame(&mut request, HEADERS, 0, 1, fragments.next().unwrap());
+    while let Some(fragment) = fragments.next() {
+        let flags = if fragments.peek().is_none() { END_HEADERS } else { 0 };
+        h2_frame(&mut request, CONTINUATION, flags, 1, fragment);
+    }
+    h2_frame(&mut request, DATA, END_STREAM, 1, &request_message());
+
+    let mut out = Vec::new();
+    connection.receive(&request, &mut out);
+    assert!(!connection.is_closed());
+    assert_eq!(connection.next_request(), Some(request_message()));
+
+    // empty CONTINUATION frames which never end the block
+    let (mut connection, _) = connect();
+    let mut request = Vec::new();
+    h2_frame(&mut request, HEADERS, 0, 1, &block);
+    for _ in 0..100 {
+        h2_frame(&mut request, CONTINUATION, 0, 1, &[]);
+    }
+
+    let mut out = Vec::new();
+    connection.receive(&request, &mut out);
+    assert!(connection.is_closed());
+    assert_eq!(goaway_error(&out), Some(ENHANCE_YOUR_CALM));
+}
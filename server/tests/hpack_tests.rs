@@ -0,0 +1,208 @@
+extern crate trust_dns_server;
+
+use std::iter;
+
+use trust_dns_server::server::{HpackDecoder, HpackError, MAX_HEADER_LIST_SIZE};
+
+// RFC 7541 C.2, a single field each, without Huffman coding
+const C2_1: &'static [u8] = &[
+    0x40, 0x0a, 0x63, 0x75, 0x73, 0x74, 0x6f, 0x6d, 0x2d, 0x6b, 0x65, 0x79, 0x0d, 0x63, 0x75, 0x73,
+    0x74, 0x6f, 0x6d, 0x2d, 0x68, 0x65, 0x61, 0x64, 0x65, 0x72,
+];
+const C2_2: &'static [u8] = &[
+    0x04, 0x0c, 0x2f, 0x73, 0x61, 0x6d, 0x70, 0x6c, 0x65, 0x2f, 0x70, 0x61, 0x74, 0x68,
+];
+const C2_3: &'static [u8] = &[
+    0x10, 0x08, 0x70, 0x61, 0x73, 0x73, 0x77, 0x6f, 0x72, 0x64, 0x06, 0x73, 0x65, 0x63, 0x72, 0x65,
+    0x74,
+];
+const C2_4: &'static [u8] = &[0x82];
+
+// RFC 7541 C.3, three requests on a connection, without Huffman coding
+const C3_1: &'static [u8] = &[
+    0x82, 0x86, 0x84, 0x41, 0x0f, 0x77, 0x77, 0x77, 0x2e, 0x65, 0x78, 0x61, 0x6d, 0x70, 0x6c, 0x65,
+    0x2e, 0x63, 0x6f, 0x6d,
+];
+const C3_2: &'static [u8] = &[
+    0x82, 0x86, 0x84, 0xbe, 0x58, 0x08, 0x6e, 0x6f, 0x2d, 0x63, 0x61, 0x63, 0x68, 0x65,
+];
+const C3_3: &'static [u8] = &[
+    0x82, 0x87, 0x85, 0xbf, 0x40, 0x0a, 0x63, 0x75, 0x73, 0x74, 0x6f, 0x6d, 0x2d, 0x6b, 0x65, 0x79,
+    0x0c, 0x63, 0x75, 0x73, 0x74, 0x6f, 0x6d, 0x2d, 0x76, 0x61, 0x6c, 0x75, 0x65,
+];
+
+// RFC 7541 C.4, the requests of C.3 with Huffman coding
+const C4_1: &'static [u8] = &[
+    0x82, 0x86, 0x84, 0x41, 0x8c, 0xf1, 0xe3, 0xc2, 0xe5, 0xf2, 0x3a, 0x6b, 0xa0, 0xab, 0x90, 0xf4,
+    0xff,
+];
+const C4_2: &'static [u8] = &[
+    0x82, 0x86, 0x84, 0xbe, 0x58, 0x86, 0xa8, 0xeb, 0x10, 0x64, 0x9c, 0xbf,
+];
+const C4_3: &'static [u8] = &[
+    0x82, 0x87, 0x85, 0xbf, 0x40, 0x88, 0x25, 0xa8, 0x49, 0xe9, 0x5b, 0xa9, 0x7d, 0x7f, 0x89, 0x25,
+    0xa8, 0x49, 0xe9, 0x5b, 0xb8, 0xe8, 0xb4, 0xbf,
+];
+
+// RFC 7541 C.5, three responses with a dynamic table of 256 octets, without Huffman coding
+const C5_1: &'static [u8] = &[
+    0x48, 0x03, 0x33, 0x30, 0x32, 0x58, 0x07, 0x70, 0x72, 0x69, 0x76, 0x61, 0x74, 0x65, 0x61, 0x1d,
+    0x4d, 0x6f, 0x6e, 0x2c, 0x20, 0x32, 0x31, 0x20, 0x4f, 0x63, 0x74, 0x20, 0x32, 0x30, 0x31, 0x33,
+    0x20, 0x32, 0x30, 0x3a, 0x31, 0x33, 0x3a, 0x32, 0x31, 0x20, 0x47, 0x4d, 0x54, 0x6e, 0x17, 0x68,
+    0x74, 0x74, 0x70, 0x73, 0x3a, 0x2f, 0x2f, 0x77, 0x77, 0x77, 0x2e, 0x65, 0x78, 0x61, 0x6d, 0x70,
+    0x6c, 0x65, 0x2e, 0x63, 0x6f, 0x6d,
+];
+const C5_2: &'static [u8] = &[
+    0x48, 0x03, 0x33, 0x30, 0x37, 0xc1, 0xc0, 0xbf,
+];
+const C5_3: &'static [u8] = &[
+    0x88, 0xc1, 0x61, 0x1d, 0x4d, 0x6f, 0x6e, 0x2c, 0x20, 0x32, 0x31, 0x20, 0x4f, 0x63, 0x74, 0x20,
+    0x32, 0x30, 0x31, 0x33, 0x20, 0x32, 0x30, 0x3a, 0x31, 0x33, 0x3a, 0x32, 0x32, 0x20, 0x47, 0x4d,
+    0x54, 0xc0, 0x5a, 0x04, 0x67, 0x7a, 0x69, 0x70, 0x77, 0x38, 0x66, 0x6f, 0x6f, 0x3d, 0x41, 0x53,
+    0x44, 0x4a, 0x4b, 0x48, 0x51, 0x4b, 0x42, 0x5a, 0x58, 0x4f, 0x51, 0x57, 0x45, 0x4f, 0x50, 0x49,
+    0x55, 0x41, 0x58, 0x51, 0x57, 0x45, 0x4f, 0x49, 0x55, 0x3b, 0x20, 0x6d, 0x61, 0x78, 0x2d, 0x61,
+    0x67, 0x65, 0x3d, 0x33, 0x36, 0x30, 0x30, 0x3b, 0x20, 0x76, 0x65, 0x72, 0x73, 0x69, 0x6f, 0x6e,
+    0x3d, 0x31,
+];
+
+// RFC 7541 C.6, the responses of C.5 with Huffman coding
+const C6_1: &'static [u8] = &[
+    0x48, 0x82, 0x64, 0x02, 0x58, 0x85, 0xae, 0xc3, 0x77, 0x1a, 0x4b, 0x61, 0x96, 0xd0, 0x7a, 0xbe,
+    0x94, 0x10, 0x54, 0xd4, 0x44, 0xa8, 0x20, 0x05, 0x95, 0x04, 0x0b, 0x81, 0x66, 0xe0, 0x82, 0xa6,
+    0x2d, 0x1b, 0xff, 0x6e, 0x91, 0x9d, 0x29, 0xad, 0x17, 0x18, 0x63, 0xc7, 0x8f, 0x0b, 0x97, 0xc8,
+    0xe9, 0xae, 0x82, 0xae, 0x43, 0xd3,
+];
+const C6_2: &'static [u8] = &[
+    0x48, 0x83, 0x64, 0x0e, 0xff, 0xc1, 0xc0, 0xbf,
+];
+const C6_3: &'static [u8] = &[
+    0x88, 0xc1, 0x61, 0x96, 0xd0, 0x7a, 0xbe, 0x94, 0x10, 0x54, 0xd4, 0x44, 0xa8, 0x20, 0x05, 0x95,
+    0x04, 0x0b, 0x81, 0x66, 0xe0, 0x84, 0xa6, 0x2d, 0x1b, 0xff, 0xc0, 0x5a, 0x83, 0x9b, 0xd9, 0xab,
+    0x77, 0xad, 0x94, 0xe7, 0x82, 0x1d, 0xd7, 0xf2, 0xe6, 0xc7, 0xb3, 0x35, 0xdf, 0xdf, 0xcd, 0x5b,
+    0x39, 0x60, 0xd5, 0xaf, 0x27, 0x08, 0x7f, 0x36, 0x72, 0xc1, 0xab, 0x27, 0x0f, 0xb5, 0x29, 0x1f,
+    0x95, 0x87, 0x31, 0x60, 0x65, 0xc0, 0x03, 0xed, 0x4e, 0xe5, 0xb1, 0x06, 0x3d, 0x50, 0x07,
+];
+
+const REQUESTS: &'static [&'static [(&'static str, &'static str)]] = &[
+    &[(":method", "GET"), (":scheme", "http"), (":path", "/"), (":authority", "www.example.com")],
+    &[(":method", "GET"),
+      (":scheme", "http"),
+      (":path", "/"),
+      (":authority", "www.example.com"),
+      ("cache-control", "no-cache")],
+    &[(":method", "GET"),
+      (":scheme", "https"),
+      (":path", "/index.html"),
+      (":authority", "www.example.com"),
+      ("custom-key", "custom-value")],
+];
+
+const RESPONSES: &'static [&'static [(&'static str, &'static str)]] = &[
+    &[(":status", "302"),
+      ("cache-control", "private"),
+      ("date", "Mon, 21 Oct 2013 20:13:21 GMT"),
+      ("location", "https://www.example.com")],
+    &[(":status", "307"),
+      ("cache-control", "private"),
+      ("date", "Mon, 21 Oct 2013 20:13:21 GMT"),
+      ("location", "https://www.example.com")],
+    &[(":status", "200"),
+      ("cache-control", "private"),
+      ("date", "Mon, 21 Oct 2013 20:13:22 GMT"),
+      ("location", "https://www.example.com"),
+      ("content-encoding", "gzip"),
+      ("set-cookie", "foo=ASDJKHQKBZXOQWEOPIUAXQWEOIU; max-age=3600; version=1")],
+];
+
+/// The dynamic table size update to 256 octets, which the examples of C.5 and C.6 assume
+const TABLE_SIZE_256: &'static [u8] = &[0x3f, 0xe1, 0x01];
+
+/// Decodes the block, checking its fields and the size of the dynamic table after it
+fn assert_decodes(decoder: &mut HpackDecoder,
+                  block: &[u8],
+                  fields: &[(&str, &str)],
+                  table_size: usize) {
+    let decoded = decoder.decode(block).expect("decode failed");
+    let decoded: Vec<(&str, &str)> =
+        decoded.iter().map(|&(ref name, ref value)| (name.as_str(), value.as_str())).collect();
+
+    assert_eq!(decoded, fields);
+    assert_eq!(decoder.get_size(), table_size);
+}
+
+/// Decodes the blocks in order, as they are received on a connection
+fn assert_decodes_all(decoder: &mut HpackDecoder,
+                      blocks: &[&[u8]],
+                      fields: &[&[(&str, &str)]],
+                      table_sizes: &[usize]) {
+    for ((block, fields), table_size) in blocks.iter().zip(fields).zip(table_sizes) {
+        assert_decodes(decoder, block, fields, *table_size);
+    }
+}
+
+#[test]
+fn test_rfc7541_literals() {
+    let mut decoder = HpackDecoder::new();
+    assert_decodes(&mut decoder, C2_1, &[("custom-key", "custom-header")], 55);
+
+    let mut decoder = HpackDecoder::new();
+    assert_decodes(&mut decoder, C2_2, &[(":path", "/sample/path")], 0);
+
+    let mut decoder = HpackDecoder::new();
+    assert_decodes(&mut decoder, C2_3, &[("password", "secret")], 0);
+
+    let mut decoder = HpackDecoder::new();
+    assert_decodes(&mut decoder, C2_4, &[(":method", "GET")], 0);
+}
+
+#[test]
+fn test_rfc7541_requests() {
+    let mut decoder = HpackDecoder::new();
+    assert_decodes_all(&mut decoder, &[C3_1, C3_2, C3_3], REQUESTS, &[57, 110, 164]);
+
+    let mut decoder = HpackDecoder::new();
+    assert_decodes_all(&mut decoder, &[C4_1, C4_2, C4_3], REQUESTS, &[57, 110, 164]);
+}
+
+#[test]
+fn test_rfc7541_responses() {
+    // the first entries are evicted as the table fills
+    let mut decoder = HpackDecoder::new();
+    assert_decodes(&mut decoder, TABLE_SIZE_256, &[], 0);
+    assert_decodes_all(&mut decoder, &[C5_1, C5_2, C5_3], RESPONSES, &[222, 222, 215]);
+
+    let mut decoder = HpackDecoder::new();
+    assert_decodes(&mut decoder, TABLE_SIZE_256, &[], 0);
+    assert_decodes_all(&mut decoder, &[C6_1, C6_2, C6_3], RESPONSES, &[222, 222, 215]);
+}
+
+#[test]
+fn test_compression_error() {
+    // index 0 is in neither table
+    assert_eq!(HpackDecoder::new().decode(&[0x80]), Err(HpackError::Compression));
+    // the dynamic table has no entry yet
+    assert_eq!(HpackDecoder::new().decode(&[0xbe]), Err(HpackError::Compression));
+    // a string longer than the block
+    assert_eq!(HpackDecoder::new().decode(&[0x40, 0x0a, b'a']), Err(HpackError::Compression));
+    // a table size update after a field
+    assert_eq!(HpackDecoder::new().decode(&[0x82, 0x20]), Err(HpackError::Compression));
+}
+
+#[test]
+fn test_max_header_list_size() {
+    // a field of 4000 octets added to the dynamic table, then referred to by index 62
+    let mut block = vec![0x40, 0x01, b'x', 0x7f, 0xa1, 0x1e];
+    block.extend(iter::repeat(b'a').take(4000));
+    let field_size = 1 + 4000 + 32;
+    let references = MAX_HEADER_LIST_SIZE / field_size - 1;
+
+    let mut fitting = block.clone();
+    fitting.extend(iter::repeat(0xbe).take(references));
+    let fields = HpackDecoder::new().decode(&fitting).expect("decode failed");
+    assert_eq!(fields.len(), references + 1);
+
+    // one more reference, of one octet, is past the limit
+    let mut bomb = block;
+    bomb.extend(iter::repeat(0xbe).take(references + 1));
+    assert_eq!(HpackDecoder::new().decode(&bomb), Err(HpackError::HeaderListSize));
+}
@@ -0,0 +1,185 @@
+extern crate futures;
+extern crate openssl;
+extern crate trust_dns;
+extern crate trust_dns_server;
+
+use std::collections::BTreeMap;
+use std::net::Ipv4Addr;
+
+use trust_dns::op::*;
+use trust_dns::rr::*;
+
+use trust_dns_server::authority::*;
+
+mod common;
+use common::authority::create_example;
+
+fn host(name: &str, last: u8) -> Record {
+    Record::from_rdata(Name::parse(name, None).unwrap(),
+                       86400,
+                       RecordType::A,
+                       RData::A(Ipv4Addr::new(192, 0, 2, last)))
+}
+
+fn message(records: &[&Record]) -> Message {
+    let mut message = Message::new();
+    message.message_type(MessageType::Response)
+        .add_answers(records.iter().map(|record| (*record).clone()));
+    message
+}
+
+fn transfer(records: &[&Record]) -> InboundTransfer {
+    ixfr(None, records)
+}
+
+fn ixfr(serial: Option<u32>, records: &[&Record]) -> InboundTransfer {
+    let mut transfer = InboundTransfer::with_serial(TransferLimits::default(), serial);
+    transfer.add_message(&message(records), 512).unwrap();
+    assert!(transfer.is_complete());
+    transfer
+}
+
+fn flatten(zone: &BTreeMap<RrKey, RecordSet>) -> Vec<Record> {
+    zone.values().flat_map(|rrset| rrset.iter().cloned()).collect()
+}
+
+#[test]
+fn test_limits() {
+    let primary = create_example();
+    let records = primary.get_transfer_records();
+    let (first, rest) = records.split_at(3);
+
+    let mut limits = TransferLimits::default();
+    limits.max_messages(1);
+    let mut transfer = InboundTransfer::new(limits);
+    assert!(transfer.add_message(&message(first), 512).is_ok());
+    assert!(!transfer.is_complete());
+    assert_eq!(transfer.add_message(&message(rest), 512),
+               Err(TransferRejection::TooManyMessages(2)));
+    assert!(transfer.is_complete());
+
+    let mut secondary = create_example();
+    let before = flatten(secondary.get_records());
+    assert_eq!(secondary.install_transfer(transfer),
+               Err(TransferRejection::TooManyMessages(2)));
+    assert_eq!(flatten(secondary.get_records()), before);
+    assert_eq!(secondary.get_quarantined().unwrap().1.len(), 3);
+
+    let mut limits = TransferLimits::default();
+    limits.max_records(records.len() - 1);
+    let mut transfer = InboundTransfer::new(limits);
+    assert_eq!(transfer.add_message(&message(&records), 512),
+               Err(TransferRejection::TooManyRecords(records.len())));
+
+    let mut limits = TransferLimits::default();
+    limits.max_bytes(1000);
+    let mut transfer = InboundTransfer::new(limits);
+    assert!(transfer.add_message(&message(first), 600).is_ok());
+    assert_eq!(transfer.add_message(&message(rest), 600),
+               Err(TransferRejection::TooManyBytes(1200)));
+
+    let mut refused = Message::new();
+    refused.response_code(ResponseCode::Refused);
+    let mut transfer = InboundTransfer::new(TransferLimits::default());
+    assert_eq!(transfer.add_message(&refused, 12),
+               Err(TransferRejection::ErrorResponse(ResponseCode::Refused)));
+}
+
+#[test]
+fn test_install_transfer() {
+    let mut primary = create_example();
+    let mut secondary = create_example();
+    let first_serial = secondary.get_serial();
+
+    // the serial must increase
+    assert_eq!(secondary.install_transfer(transfer(&primary.get_transfer_records())),
+               Err(TransferRejection::SerialNotIncreased(first_serial, first_serial)));
+    assert!(secondary.get_quarantined().is_some());
+
+    assert!(primary.update_records(&[host("new.example.com.", 1)], true).unwrap());
+    assert_eq!(secondary.install_transfer(transfer(&primary.get_transfer_records())),
+               Ok(()));
+    assert_eq!(secondary.get_serial(), primary.get_serial());
+    assert_eq!(flatten(secondary.get_records()), flatten(primary.get_records()));
+    assert!(secondary.get_quarantined().is_none());
+
+    // incremental
+    let serial = secondary.get_serial();
+    assert!(primary.update_records(&[host("other.example.com.", 2)], true).unwrap());
+    assert_eq!(secondary.install_transfer(ixfr(Some(serial), &primary.get_ixfr_records(serial))),
+               Ok(()));
+    assert_eq!(secondary.get_serial(), primary.get_serial());
+    assert_eq!(secondary.get_deltas().len(), 1);
+
+    // up to date
+    let serial = secondary.get_serial();
+    assert_eq!(secondary.install_transfer(ixfr(Some(serial), &primary.get_ixfr_records(serial))),
+               Ok(()));
+    assert_eq!(secondary.get_serial(), serial);
+}
+
+#[test]
+fn test_lone_soa() {
+    let primary = create_example();
+    let serial = primary.get_serial();
+    let soa = primary.get_soa().unwrap();
+
+    // only an IXFR from the serial of the SOA is up to date
+    assert!(ixfr(Some(serial), &[soa]).is_complete());
+    for request_serial in &[None, Some(serial.wrapping_sub(1))] {
+        let mut transfer = InboundTransfer::with_serial(TransferLimits::default(),
+                                                        *request_serial);
+        transfer.add_message(&message(&[soa]), 512).unwrap();
+        assert!(!transfer.is_complete());
+
+        // the first message of an AXFR, the rest follows
+        let records = primary.get_transfer_records();
+        transfer.add_message(&message(&records[1..]), 512).unwrap();
+        assert!(transfer.is_complete());
+    }
+
+    // the connection was closed after the SOA
+    let mut secondary = create_example();
+    let mut transfer = InboundTransfer::new(TransferLimits::default());
+    transfer.add_message(&message(&[soa]), 512).unwrap();
+    match secondary.install_transfer(transfer) {
+        Err(TransferRejection::Malformed(_)) => (),
+        other => panic!("expected malformed: {:?}", other),
+    }
+}
+
+#[test]
+fn test_reject_transfer() {
+    let mut primary = create_example();
+    assert!(primary.update_records(&[host("new.example.com.", 1)], true).unwrap());
+    let records = primary.get_transfer_records();
+    let mut secondary = create_example();
+    let before = flatten(secondary.get_records());
+
+    // not complete
+    let incomplete = &records[..records.len() - 1];
+    let mut partial = InboundTransfer::new(TransferLimits::default());
+    partial.add_message(&message(incomplete), 512).unwrap();
+    assert!(!partial.is_complete());
+    match secondary.install_transfer(partial) {
+        Err(TransferRejection::Malformed(_)) => (),
+        other => panic!("expected malformed: {:?}", other),
+    }
+
+    // out of the zone
+    let out_of_zone = host("www.example.net.", 1);
+    let mut with_out_of_zone = records.clone();
+    with_out_of_zone.insert(1, &out_of_zone);
+    assert_eq!(secondary.install_transfer(transfer(&with_out_of_zone)),
+               Err(TransferRejection::OutOfZone(out_of_zone.get_name().clone())));
+
+    // no NS at the apex
+    let without_ns: Vec<&Record> =
+        records.iter().cloned().filter(|r| r.get_rr_type() != RecordType::NS).collect();
+    assert_eq!(secondary.install_transfer(transfer(&without_ns)),
+               Err(TransferRejection::ApexDeleted(RecordType::NS)));
+
+    assert_eq!(flatten(secondary.get_records()), before);
+    assert_eq!(secondary.get_quarantined().unwrap().0,
+               &TransferRejection::ApexDeleted(RecordType::NS));
+}
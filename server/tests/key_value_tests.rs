@@ -0,0 +1,319 @@
+extern crate chrono;
+extern crate futures;
+extern crate openssl;
+extern crate trust_dns;
+extern crate trust_dns_server;
+
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::*;
+use std::rc::Rc;
+use std::thread;
+use std::time::Duration;
+
+use trust_dns::op::*;
+use trust_dns::rr::*;
+use trust_dns::rr::dnssec::SupportedAlgorithms;
+
+use trust_dns_server::authority::*;
+
+mod common;
+use common::authority::create_example;
+
+/// The values of a store in memory, which fails on demand
+#[derive(Default)]
+struct MemoryValues {
+    values: RefCell<HashMap<String, Vec<u8>>>,
+    gets: Cell<usize>,
+    failing: Cell<bool>,
+}
+
+struct MemoryStore(Rc<MemoryValues>);
+
+impl KeyValueStore for MemoryStore {
+    fn get(&self, key: &str) -> io::Result<Option<Vec<u8>>> {
+        if self.0.failing.get() {
+            return Err(io::Error::new(io::ErrorKind::ConnectionRefused, "store is down"));
+        }
+        self.0.gets.set(self.0.gets.get() + 1);
+        Ok(self.0.values.borrow().get(key).cloned())
+    }
+
+    fn set(&self, key: &str, value: &[u8]) -> io::Result<()> {
+        self.0.values.borrow_mut().insert(key.to_string(), value.to_vec());
+        Ok(())
+    }
+
+    fn delete(&self, key: &str) -> io::Result<()> {
+        self.0.values.borrow_mut().remove(key);
+        Ok(())
+    }
+}
+
+fn create_stored_example(values: &Rc<MemoryValues>) -> KeyValueAuthority {
+    let example = create_example();
+    let authority = KeyValueAuthority::new(example.get_origin().clone(),
+                                           Box::new(MemoryStore(values.clone())));
+    let records: Vec<Record> = example.get_transfer_records().into_iter().cloned().collect();
+    assert_eq!(authority.update_records(&records, false), Ok(true));
+    authority
+}
+
+fn question(name: &str, rtype: RecordType) -> Message {
+    let mut query: Query = Query::new();
+    query.name(Name::parse(name, None).unwrap()).query_type(rtype);
+    let mut question: Message = Message::new();
+    question.add_query(query);
+    question
+}
+
+#[test]
+fn test_key_value_catalog() {
+    let values = Rc::new(MemoryValues::default());
+    let authority = create_stored_example(&values);
+    let origin = authority.get_origin();
+    assert!(values.values.borrow().contains_key("example.com.|www.example.com.|A"));
+    assert!(values.values.borrow().contains_key("example.com.|www.example.com."));
+
+    let mut catalog: Catalog = Catalog::new();
+    catalog.upsert_object(origin.clone(), Box::new(authority));
+
+    let response = catalog.lookup(&question("www.example.com.", RecordType::A));
+    assert_eq!(response.get_response_code(), ResponseCode::NoError);
+    assert!(response.is_authoritative());
+    assert_eq!(response.get_answers().len(), 1);
+    assert_eq!(response.get_answers()[0].get_rdata(),
+               &RData::A(Ipv4Addr::new(93, 184, 216, 34)));
+
+    // the values read are cached, the missing ones as well
+    let gets = values.gets.get();
+    catalog.lookup(&question("www.example.com.", RecordType::A));
+    assert_eq!(values.gets.get(), gets);
+
+    let response = catalog.lookup(&question("www.example.com.", RecordType::MX));
+    assert_eq!(response.get_response_code(), ResponseCode::NoError);
+    assert!(response.get_answers().is_empty());
+    assert_eq!(response.get_name_servers()[0].get_rr_type(), RecordType::SOA);
+
+    let response = catalog.lookup(&question("mail.example.com.", RecordType::A));
+    assert_eq!(response.get_response_code(), ResponseCode::NXDomain);
+    let gets = values.gets.get();
+    catalog.lookup(&question("mail.example.com.", RecordType::A));
+    assert_eq!(values.gets.get(), gets);
+
+    // the cached values are served while the store fails, the others are SERVFAIL
+    values.failing.set(true);
+    let response = catalog.lookup(&question("www.example.com.", RecordType::A));
+    assert_eq!(response.get_response_code(), ResponseCode::NoError);
+    assert_eq!(response.get_answers().len(), 1);
+
+    let response = catalog.lookup(&question("ftp.example.com.", RecordType::A));
+    assert_eq!(response.get_response_code(), ResponseCode::ServFail);
+
+    values.failing.set(false);
+    let response = catalog.lookup(&question("ftp.example.com.", RecordType::A));
+    assert_eq!(response.get_response_code(), ResponseCode::NXDomain);
+
+    // dynamic updates can not be authorized
+    let mut update: Message = Message::new();
+    let mut zone: Query = Query::new();
+    zone.name(origin.clone()).query_type(RecordType::SOA);
+    update.op_code(OpCode::Update).add_zone(zone);
+    assert_eq!(catalog.update(&update).get_response_code(), ResponseCode::Refused);
+}
+
+#[test]
+fn test_key_value_update() {
+    let values = Rc::new(MemoryValues::default());
+    let authority = create_stored_example(&values);
+    let origin = authority.get_origin();
+    let www = Name::parse("www.example.com.", None).unwrap();
+    let serial = |authority: &KeyValueAuthority| {
+        match *authority.lookup(&origin, RecordType::SOA, false, SupportedAlgorithms::new())[0]
+            .get_rdata() {
+            RData::SOA(ref soa) => soa.get_serial(),
+            _ => panic!("not an soa"),
+        }
+    };
+    let addresses = |authority: &KeyValueAuthority| {
+        authority.lookup(&www, RecordType::A, false, SupportedAlgorithms::new()).len()
+    };
+    assert_eq!(serial(&authority), 2015082403);
+    assert_eq!(addresses(&authority), 1);
+
+    // the changed values are removed from the cache
+    let added = Record::from_rdata(www.clone(),
+                                   86400,
+                                   RecordType::A,
+                                   RData::A(Ipv4Addr::new(93, 184, 216, 35)));
+    assert_eq!(authority.update_records(&[added.clone()], true), Ok(true));
+    assert_eq!(addresses(&authority), 2);
+    assert_eq!(serial(&authority), 2015082404);
+    assert_eq!(authority.update_records(&[added.clone()], true), Ok(false));
+
+    let mut removed = added.clone();
+    removed.dns_class(DNSClass::NONE);
+    assert_eq!(authority.update_records(&[removed], true), Ok(true));
+    assert_eq!(addresses(&authority), 1);
+
+    // the RRsets of a name, but not the SOA and NS of the apex
+    let mut deleted = Record::with(www.clone(), RecordType::ANY, 0);
+    deleted.dns_class(DNSClass::ANY);
+    assert_eq!(authority.update_records(&[deleted], true), Ok(true));
+    assert!(!authority.contains_name(&www));
+    assert!(!values.values.borrow().contains_key("example.com.|www.example.com."));
+
+    let mut deleted = Record::with(origin.clone(), RecordType::ANY, 0);
+    deleted.dns_class(DNSClass::ANY);
+    authority.update_records(&[deleted], true).unwrap();
+    assert_eq!(authority.lookup(&origin, RecordType::ANY, false, SupportedAlgorithms::new())
+                   .iter()
+                   .filter(|r| r.get_rr_type() != RecordType::SOA &&
+                               r.get_rr_type() != RecordType::NS)
+                   .count(),
+               0);
+    assert_eq!(serial(&authority), 2015082407);
+
+    let outside = Record::with(Name::parse("www.example.net.", None).unwrap(), RecordType::A, 0);
+    assert_eq!(authority.update_records(&[outside], true),
+               Err(ResponseCode::NotZone));
+}
+
+#[test]
+fn test_key_value_cache_ttl() {
+    let values = Rc::new(MemoryValues::default());
+    let mut authority = create_stored_example(&values);
+    let www = Name::parse("www.example.com.", None).unwrap();
+    {
+        let txt = || authority.lookup(&www, RecordType::TXT, false, SupportedAlgorithms::new());
+        assert_eq!(txt().len(), 1);
+
+        // another writer of the store is not seen until the value expires
+        values.values.borrow_mut().remove("example.com.|www.example.com.|TXT");
+        assert_eq!(txt().len(), 1);
+    }
+
+    authority.set_cache(Duration::from_secs(0), DEFAULT_CACHE_ENTRIES);
+    assert!(authority.lookup(&www, RecordType::TXT, false, SupportedAlgorithms::new())
+        .is_empty());
+}
+
+/// The reply of a Redis server to GET, SET and DEL
+fn redis_reply(values: &mut HashMap<Vec<u8>, Vec<u8>>, args: &[Vec<u8>]) -> Vec<u8> {
+    match String::from_utf8_lossy(&args[0]).as_ref() {
+        "GET" => {
+            match values.get(&args[1]) {
+                Some(value) => {
+                    let mut reply = format!("${}\r\n", value.len()).into_bytes();
+                    reply.extend_from_slice(value);
+                    reply.extend_from_slice(b"\r\n");
+                    reply
+                }
+                None => b"$-1\r\n".to_vec(),
+            }
+        }
+        "SET" => {
+            values.insert(args[1].clone(), args[2].clone());
+            b"+OK\r\n".to_vec()
+        }
+        "DEL" => {
+            let deleted = values.remove(&args[1]).map_or(0, |_| 1);
+            format!(":{}\r\n", deleted).into_bytes()
+        }
+        _ => b"-ERR unknown command\r\n".to_vec(),
+    }
+}
+
+/// Answers GET, SET, DEL, MULTI and EXEC on the first connection, as a Redis server would
+fn spawn_redis() -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut writer = stream.try_clone().unwrap();
+        let mut reader = BufReader::new(stream);
+        let mut values: HashMap<Vec<u8>, Vec<u8>> = HashMap::new();
+        // the commands of a transaction, once MULTI is received
+        let mut queued: Option<Vec<Vec<Vec<u8>>>> = None;
+
+        let mut line = String::new();
+        while reader.read_line(&mut line).unwrap() > 0 {
+            let count: usize = line.trim()[1..].parse().unwrap();
+            let mut args: Vec<Vec<u8>> = Vec::new();
+            for _ in 0..count {
+                line.clear();
+                reader.read_line(&mut line).unwrap();
+                let len: usize = line.trim()[1..].parse().unwrap();
+                let mut arg = vec![0; len + 2];
+                reader.read_exact(&mut arg).unwrap();
+                arg.truncate(len);
+                args.push(arg);
+            }
+            line.clear();
+
+            let command = String::from_utf8_lossy(&args[0]).into_owned();
+            let reply: Vec<u8> = match (command.as_ref(), queued.take()) {
+                ("MULTI", None) => {
+                    queued = Some(Vec::new());
+                    b"+OK\r\n".to_vec()
+                }
+                ("EXEC", Some(commands)) => {
+                    let mut reply = format!("*{}\r\n", commands.len()).into_bytes();
+                    for command in commands {
+                        reply.extend(redis_reply(&mut values, &command));
+                    }
+                    reply
+                }
+                (_, Some(mut commands)) => {
+                    commands.push(args);
+                    queued = Some(commands);
+                    b"+QUEUED\r\n".to_vec()
+                }
+                (_, None) => redis_reply(&mut values, &args),
+            };
+            writer.write_all(&reply).unwrap();
+        }
+    });
+
+    addr
+}
+
+#[test]
+fn test_redis_store() {
+    let store = RedisStore::new(spawn_redis(), Duration::from_secs(5));
+
+    assert_eq!(store.get("example.com.|www.example.com.|A").unwrap(), None);
+    store.set("example.com.|www.example.com.|A", b"\x00\r\n\x01").unwrap();
+    assert_eq!(store.get("example.com.|www.example.com.|A").unwrap(),
+               Some(b"\x00\r\n\x01".to_vec()));
+    store.set("example.com.|www.example.com.|A", b"").unwrap();
+    assert_eq!(store.get("example.com.|www.example.com.|A").unwrap(), Some(vec![]));
+    store.delete("example.com.|www.example.com.|A").unwrap();
+    assert_eq!(store.get("example.com.|www.example.com.|A").unwrap(), None);
+
+    // the changes of a write are made in a transaction
+    store.write(&[("example.com.|www.example.com.|A", Some(b"\x01")),
+                 ("example.com.|www.example.com.", Some(b"A")),
+                 ("example.com.|ftp.example.com.", None)])
+        .unwrap();
+    assert_eq!(store.get("example.com.|www.example.com.|A").unwrap(),
+               Some(b"\x01".to_vec()));
+    assert_eq!(store.get("example.com.|www.example.com.").unwrap(),
+               Some(b"A".to_vec()));
+}
+
+#[test]
+fn test_redis_store_timeout() {
+    // the server accepts the connection, but never replies
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let store = RedisStore::new(listener.local_addr().unwrap(), Duration::from_millis(100));
+
+    assert!(store.get("example.com.|www.example.com.|A").is_err());
+
+    // the commands then fail without waiting for the server
+    let error = store.get("example.com.|www.example.com.|A").unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::NotConnected);
+    drop(listener);
+}
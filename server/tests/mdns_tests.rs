@@ -0,0 +1,270 @@
+extern crate trust_dns;
+extern crate trust_dns_server;
+
+use std::net::*;
+
+use trust_dns::mdns::{ServiceDiscovery, ServiceEvent};
+use trust_dns::op::*;
+use trust_dns::rr::*;
+use trust_dns::rr::rdata::SRV;
+
+use trust_dns_server::server::{mdns_group, MdnsConfig, MdnsResponder, MdnsService, MDNS_PORT};
+
+fn create_responder() -> MdnsResponder {
+    MdnsResponder::new(MdnsConfig {
+        hostname: "printer".to_string(),
+        addresses: vec![IpAddr::V4(Ipv4Addr::new(192, 168, 0, 2))],
+        services: vec![MdnsService {
+                           instance: "Office Printer".to_string(),
+                           service_type: "_ipp._tcp".to_string(),
+                           port: 631,
+                           txt: vec!["rp=printers/office".to_string()],
+                       }],
+    })
+}
+
+fn announce(responder: &mut MdnsResponder) {
+    while !responder.is_announced() {
+        responder.next_message().expect("no probe or announcement");
+    }
+}
+
+fn instance_name(instance: &str) -> Name {
+    Name::with_labels(vec![instance.to_string(),
+                           "_ipp".to_string(),
+                           "_tcp".to_string(),
+                           "local".to_string()])
+}
+
+fn query(name: Name, query_type: RecordType, unicast_response: bool) -> Message {
+    let mut query = Query::new();
+    query.name(name).query_type(query_type).mdns_unicast_response(unicast_response);
+
+    let mut message = Message::new();
+    message.id(0).message_type(MessageType::Query).add_query(query);
+    message
+}
+
+fn mdns_src() -> SocketAddr {
+    SocketAddr::new(IpAddr::V4(Ipv4Addr::new(192, 168, 0, 3)), MDNS_PORT)
+}
+
+#[test]
+fn test_probe_and_announce() {
+    let mut responder = create_responder();
+    let hostname = Name::parse("printer.local.", None).unwrap();
+    assert_eq!(responder.get_hostname(), hostname);
+    assert_eq!(responder.get_records().len(), 5);
+
+    // three probes for the unique names, with the proposed records
+    for probe in 0..3 {
+        assert!(!responder.is_announced());
+        let message = responder.next_message().unwrap();
+        assert_eq!(message.get_message_type(), MessageType::Query);
+        assert_eq!(message.get_queries().len(), 2);
+        assert_eq!(message.get_queries()[0].get_name(), &hostname);
+        assert_eq!(message.get_queries()[0].get_query_type(), RecordType::ANY);
+        assert_eq!(message.get_queries()[0].is_mdns_unicast_response(), probe == 0);
+        assert_eq!(message.get_name_servers().len(), 3);
+    }
+
+    // queries are not answered while probing
+    let ptr = query(Name::parse("_ipp._tcp.local.", None).unwrap(),
+                    RecordType::PTR,
+                    false);
+    assert!(responder.handle_message(&ptr, mdns_src()).is_none());
+
+    // then two announcements, a second apart
+    let mut announcements = Vec::new();
+    for _ in 0..8 {
+        if let Some(message) = responder.next_message() {
+            announcements.push(message);
+        }
+    }
+    assert!(responder.is_announced());
+    assert_eq!(announcements.len(), 2);
+    for announcement in announcements {
+        assert_eq!(announcement.get_message_type(), MessageType::Response);
+        assert!(announcement.is_authoritative());
+        assert_eq!(announcement.get_answers(), responder.get_records());
+    }
+
+    let goodbye = responder.goodbye().unwrap();
+    assert_eq!(goodbye.get_answers().len(), 5);
+    assert!(goodbye.get_answers().iter().all(|r| r.get_ttl() == 0));
+}
+
+#[test]
+fn test_answer() {
+    let mut responder = create_responder();
+    assert!(responder.goodbye().is_none());
+    announce(&mut responder);
+
+    // multicast, with the SRV and TXT of the instance
+    let ptr = query(Name::parse("_ipp._tcp.local.", None).unwrap(),
+                    RecordType::PTR,
+                    false);
+    let (response, dst) = responder.handle_message(&ptr, mdns_src()).unwrap();
+    assert_eq!(dst, mdns_group(&mdns_src()));
+    assert_eq!(response.get_id(), 0);
+    assert!(response.get_queries().is_empty());
+    assert_eq!(response.get_answers().len(), 1);
+    assert_eq!(response.get_answers()[0].get_rdata(),
+               &RData::PTR(instance_name("Office Printer")));
+    assert_eq!(response.get_additionals().len(), 2);
+
+    // the known answer is not repeated
+    let mut known = ptr.clone();
+    known.add_answer(response.get_answers()[0].clone());
+    assert!(responder.handle_message(&known, mdns_src()).is_none());
+
+    // unicast response requested
+    let a = query(Name::parse("printer.local.", None).unwrap(),
+                  RecordType::A,
+                  true);
+    let (response, dst) = responder.handle_message(&a, mdns_src()).unwrap();
+    assert_eq!(dst, mdns_src());
+    assert!(response.get_answers()[0].is_mdns_cache_flush());
+
+    // legacy unicast
+    let legacy_src = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(192, 168, 0, 3)), 49152);
+    let mut legacy = query(instance_name("Office Printer"), RecordType::SRV, false);
+    legacy.id(77);
+    let (response, dst) = responder.handle_message(&legacy, legacy_src).unwrap();
+    assert_eq!(dst, legacy_src);
+    assert_eq!(response.get_id(), 77);
+    assert_eq!(response.get_queries(), legacy.get_queries());
+    assert_eq!(response.get_answers()[0].get_ttl(), 10);
+    assert!(!response.get_answers()[0].is_mdns_cache_flush());
+    assert_eq!(response.get_additionals()[0].get_rr_type(), RecordType::A);
+
+    // not ours
+    let other = query(Name::parse("scanner.local.", None).unwrap(),
+                      RecordType::A,
+                      false);
+    assert!(responder.handle_message(&other, mdns_src()).is_none());
+}
+
+#[test]
+fn test_conflicts() {
+    let mut responder = create_responder();
+    responder.next_message().unwrap();
+
+    // another host has the name
+    let mut response = Message::new();
+    response.message_type(MessageType::Response)
+        .add_answer(Record::from_rdata(Name::parse("printer.local.", None).unwrap(),
+                                       120,
+                                       RecordType::A,
+                                       RData::A(Ipv4Addr::new(192, 168, 0, 3))));
+    assert!(responder.handle_message(&response, mdns_src()).is_none());
+
+    let hostname = Name::parse("printer-2.local.", None).unwrap();
+    assert_eq!(responder.get_hostname(), hostname);
+    let srv = responder.get_records()
+        .iter()
+        .find(|r| r.get_rr_type() == RecordType::SRV)
+        .unwrap()
+        .clone();
+    assert_eq!(srv.get_rdata(),
+               &RData::SRV(SRV::new(0, 0, 631, hostname.clone())));
+
+    // probing restarts
+    let probe = responder.next_message().unwrap();
+    assert_eq!(probe.get_message_type(), MessageType::Query);
+    assert_eq!(probe.get_queries()[0].get_name(), &hostname);
+
+    // and of the instance, once announced
+    announce(&mut responder);
+    let mut response = Message::new();
+    response.message_type(MessageType::Response)
+        .add_answer(Record::from_rdata(instance_name("Office Printer"),
+                                       120,
+                                       RecordType::SRV,
+                                       RData::SRV(SRV::new(0, 0, 80, hostname))));
+    responder.handle_message(&response, mdns_src());
+    assert!(!responder.is_announced());
+    assert!(responder.get_records()
+        .iter()
+        .any(|r| *r.get_name() == instance_name("Office Printer (2)")));
+
+    // the own records are not a conflict
+    let mut echo = Message::new();
+    echo.message_type(MessageType::Response)
+        .add_answers(responder.get_records().iter().cloned());
+    let records = responder.get_records().to_vec();
+    responder.handle_message(&echo, mdns_src());
+    assert_eq!(responder.get_records(), &records[..]);
+}
+
+#[test]
+fn test_simultaneous_probe() {
+    let mut responder = create_responder();
+    responder.next_message().unwrap();
+
+    let probe = |address: Ipv4Addr| {
+        let mut message = query(Name::parse("printer.local.", None).unwrap(),
+                                RecordType::ANY,
+                                true);
+        message.add_name_server(Record::from_rdata(Name::parse("printer.local.", None)
+                                                       .unwrap(),
+                                                   120,
+                                                   RecordType::A,
+                                                   RData::A(address)));
+        message
+    };
+
+    // the lexicographically earlier data loses
+    responder.handle_message(&probe(Ipv4Addr::new(192, 168, 0, 1)), mdns_src());
+    assert!(responder.next_message().is_some());
+
+    // defers for a second, then probes again
+    responder.handle_message(&probe(Ipv4Addr::new(192, 168, 0, 3)), mdns_src());
+    for _ in 0..4 {
+        assert!(responder.next_message().is_none());
+    }
+    let message = responder.next_message().unwrap();
+    assert!(message.get_queries()[0].is_mdns_unicast_response());
+    assert_eq!(responder.get_hostname(),
+               Name::parse("printer.local.", None).unwrap());
+}
+
+#[test]
+fn test_service_discovery() {
+    let mut responder = create_responder();
+    announce(&mut responder);
+
+    let mut discovery = ServiceDiscovery::new("_ipp._tcp").unwrap();
+    let browse = discovery.next_query(1000).unwrap();
+    let (response, _) = responder.handle_message(&browse, mdns_src()).expect("no response");
+
+    // the SRV and TXT records are in the additional records of the PTR answer, not the address
+    assert!(discovery.handle_message(&response, 1000).is_empty());
+    let resolve = discovery.next_query(1000).unwrap();
+    assert_eq!(resolve.get_queries()[0].get_name(),
+               &Name::parse("printer.local.", None).unwrap());
+    let (response, _) = responder.handle_message(&resolve, mdns_src()).expect("no response");
+
+    let events = discovery.handle_message(&response, 1000);
+    assert_eq!(events.len(), 1);
+    match events[0] {
+        ServiceEvent::ServiceFound(ref instance) => {
+            assert_eq!(instance.get_name(), &instance_name("Office Printer"));
+            assert_eq!(instance.get_instance(), "Office Printer");
+            assert_eq!(instance.get_port(), 631);
+            assert_eq!(instance.get_txt(), &["rp=printers/office".to_string()]);
+            assert_eq!(instance.get_addresses(),
+                       &[IpAddr::V4(Ipv4Addr::new(192, 168, 0, 2))]);
+        }
+        ref event => panic!("unexpected event: {:?}", event),
+    }
+    assert!(discovery.next_query(1000).is_none());
+
+    // the known answer suppresses the response
+    let browse = discovery.next_query(1001).unwrap();
+    assert!(responder.handle_message(&browse, mdns_src()).is_none());
+
+    let goodbye = responder.goodbye().unwrap();
+    assert_eq!(discovery.handle_message(&goodbye, 1002),
+               vec![ServiceEvent::ServiceRemoved(instance_name("Office Printer"))]);
+}
@@ -0,0 +1,230 @@
+extern crate chrono;
+extern crate futures;
+extern crate openssl;
+extern crate trust_dns;
+extern crate trust_dns_server;
+
+use std::cell::RefCell;
+use std::cmp;
+use std::net::SocketAddr;
+use std::rc::Rc;
+use std::sync::mpsc::sync_channel;
+
+use trust_dns::op::*;
+use trust_dns::rr::*;
+
+use trust_dns_server::authority::Catalog;
+use trust_dns_server::server::{ChannelMirrorSink, DnstapSink, Middleware, MiddlewareChain,
+                               MirrorMiddleware, MirrorSink, Next};
+
+mod common;
+use common::authority::create_example;
+
+/// Records the order in which the layers saw the request and the response
+struct Trace {
+    name: &'static str,
+    trace: Rc<RefCell<Vec<String>>>,
+}
+
+impl Middleware for Trace {
+    fn handle_request(&self, request: &Message, src: SocketAddr, next: Next) -> Message {
+        self.trace.borrow_mut().push(format!("{} request from {}", self.name, src));
+        let response = next.handle_request(request, src);
+        self.trace.borrow_mut().push(format!("{} response", self.name));
+        response
+    }
+}
+
+/// Refuses the requests of one client
+struct Deny(SocketAddr);
+
+impl Middleware for Deny {
+    fn handle_request(&self, request: &Message, src: SocketAddr, next: Next) -> Message {
+        if src == self.0 {
+            return Message::error_msg(request.get_id(),
+                                      request.get_op_code(),
+                                      ResponseCode::Refused);
+        }
+        next.handle_request(request, src)
+    }
+}
+
+/// Lowers the TTL of all the answers
+struct MaxTtl(u32);
+
+impl Middleware for MaxTtl {
+    fn handle_request(&self, request: &Message, src: SocketAddr, next: Next) -> Message {
+        let mut response = next.handle_request(request, src);
+        let answers = response.take_answers()
+            .into_iter()
+            .map(|mut record| {
+                let ttl = cmp::min(record.get_ttl(), self.0);
+                record.ttl(ttl);
+                record
+            })
+            .collect();
+        response.insert_answers(answers);
+        response
+    }
+}
+
+fn create_catalog() -> Catalog {
+    let example = create_example();
+    let origin = example.get_origin().clone();
+
+    let mut catalog = Catalog::new();
+    catalog.upsert(origin, example);
+    catalog
+}
+
+fn query() -> Message {
+    let mut query: Query = Query::new();
+    query.name(Name::parse("www.example.com.", None).unwrap()).query_type(RecordType::A);
+    let mut request = Message::new();
+    request.id(7).add_query(query);
+    request
+}
+
+#[test]
+fn test_empty_chain() {
+    let catalog = create_catalog();
+    let chain = MiddlewareChain::new();
+    assert!(chain.is_empty());
+
+    let src: SocketAddr = "192.0.2.1:53000".parse().unwrap();
+    let response = chain.handle_request(&catalog, &query(), src);
+    assert_eq!(response, catalog.handle_request(&query()));
+}
+
+#[test]
+fn test_chain_order() {
+    let catalog = create_catalog();
+    let trace = Rc::new(RefCell::new(Vec::new()));
+    let denied: SocketAddr = "198.51.100.1:53000".parse().unwrap();
+    let src: SocketAddr = "192.0.2.1:53000".parse().unwrap();
+
+    let mut chain = MiddlewareChain::new();
+    chain.push(Rc::new(Trace {
+        name: "outer",
+        trace: trace.clone(),
+    }));
+    chain.push(Rc::new(Deny(denied)));
+    chain.push(Rc::new(Trace {
+        name: "inner",
+        trace: trace.clone(),
+    }));
+    chain.push(Rc::new(MaxTtl(60)));
+    assert_eq!(chain.len(), 4);
+
+    let response = chain.handle_request(&catalog, &query(), src);
+    assert_eq!(response.get_response_code(), ResponseCode::NoError);
+    assert!(!response.get_answers().is_empty());
+    assert!(response.get_answers().iter().all(|record| record.get_ttl() == 60));
+    assert_eq!(*trace.borrow(),
+               vec!["outer request from 192.0.2.1:53000".to_string(),
+                    "inner request from 192.0.2.1:53000".to_string(),
+                    "inner response".to_string(),
+                    "outer response".to_string()]);
+
+    // short-circuited before the inner layers and the catalog
+    trace.borrow_mut().clear();
+    let response = chain.handle_request(&catalog, &query(), denied);
+    assert_eq!(response.get_response_code(), ResponseCode::Refused);
+    assert!(response.get_answers().is_empty());
+    assert_eq!(*trace.borrow(),
+               vec!["outer request from 198.51.100.1:53000".to_string(),
+                    "outer response".to_string()]);
+}
+
+#[test]
+fn test_mirror() {
+    let catalog = create_catalog();
+    let src: SocketAddr = "192.0.2.1:53000".parse().unwrap();
+
+    // every query, without the responses
+    let (sender, receiver) = sync_channel(8);
+    let mut chain = MiddlewareChain::new();
+    chain.push(Rc::new(MirrorMiddleware::new(Box::new(ChannelMirrorSink::new(sender)), 2.0)));
+    let response = chain.handle_request(&catalog, &query(), src);
+    assert_eq!(response, catalog.handle_request(&query()));
+
+    let exchange = receiver.try_recv().unwrap();
+    assert_eq!(exchange.get_src(), src);
+    assert_eq!(Message::from_vec(exchange.get_request()).unwrap(), query());
+    assert!(exchange.get_response().is_none());
+    assert!(receiver.try_recv().is_err());
+
+    // updates are not mirrored
+    let mut update = query();
+    update.op_code(OpCode::Update);
+    chain.handle_request(&catalog, &update, src);
+    assert!(receiver.try_recv().is_err());
+
+    // with the responses
+    let (sender, receiver) = sync_channel(8);
+    let mut mirror = MirrorMiddleware::new(Box::new(ChannelMirrorSink::new(sender)), 1.0);
+    mirror.set_mirror_responses(true);
+    let mut chain = MiddlewareChain::new();
+    chain.push(Rc::new(mirror));
+    let response = chain.handle_request(&catalog, &query(), src);
+
+    let exchange = receiver.try_recv().unwrap();
+    assert_eq!(Message::from_vec(exchange.get_response().unwrap()).unwrap(),
+               response);
+
+    // none sampled
+    let (sender, receiver) = sync_channel(8);
+    let mirror = MirrorMiddleware::new(Box::new(ChannelMirrorSink::new(sender)), -1.0);
+    assert_eq!(mirror.get_sample_rate(), 0.0);
+    let mut chain = MiddlewareChain::new();
+    chain.push(Rc::new(mirror));
+    for _ in 0..32 {
+        chain.handle_request(&catalog, &query(), src);
+    }
+    assert!(receiver.try_recv().is_err());
+
+    // a receiver which does not keep up, the channel is bounded
+    let (sender, receiver) = sync_channel(2);
+    let sink = ChannelMirrorSink::new(sender);
+    for _ in 0..5 {
+        sink.mirror(&query(), None, src);
+    }
+    assert_eq!(sink.get_dropped(), 3);
+    let mut received = 0;
+    while receiver.try_recv().is_ok() {
+        received += 1;
+    }
+    assert_eq!(received, 2);
+}
+
+#[test]
+fn test_dnstap_sink() {
+    let src: SocketAddr = "192.0.2.1:53000".parse().unwrap();
+    let request = query();
+    let request_bytes = request.to_vec().unwrap();
+
+    // written by the thread of the sink, finish waits for it
+    let sink = DnstapSink::new(Vec::new(), "ns1").unwrap();
+    sink.mirror(&request, None, src);
+    assert_eq!(sink.get_dropped(), 0);
+    let stream = sink.finish().unwrap();
+
+    // the start control frame, with the content type
+    let content_type = b"protobuf:dnstap.Dnstap";
+    assert_eq!(&stream[..4], &[0, 0, 0, 0]);
+    assert_eq!(&stream[4..8], &[0, 0, 0, 34]);
+    assert_eq!(&stream[8..16], &[0, 0, 0, 2, 0, 0, 0, 1]);
+    assert_eq!(&stream[16..20], &[0, 0, 0, content_type.len() as u8]);
+    assert_eq!(&stream[20..42], &content_type[..]);
+
+    // a data frame, with the identity and the query
+    let length = ((stream[42] as usize) << 24) | ((stream[43] as usize) << 16) |
+                 ((stream[44] as usize) << 8) | stream[45] as usize;
+    let frame = &stream[46..46 + length];
+    assert_eq!(&frame[..5], &[0x0a, 3, b'n', b's', b'1']);
+    assert!(frame.windows(request_bytes.len()).any(|window| window == &request_bytes[..]));
+    assert_eq!(&frame[length - 2..], &[0x78, 1]);
+
+    // the stop control frame
+    assert_eq!(&stream[46 + length..], &[0, 0, 0, 0, 0, 0, 0, 4, 0, 0, 0, 3]);
+}
@@ -52,6 +52,7 @@ fn test_secure_query_example<H>(mut client: SecureClientHandle<H>, mut io_loop:
 
     println!("response records: {:?}", response);
     assert!(response.get_edns().expect("edns not here").is_dnssec_ok());
+    assert!(response.is_authentic_data());
 
     assert!(!response.get_answers().is_empty());
     let record = &response.get_answers()[0];
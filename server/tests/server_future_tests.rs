@@ -4,13 +4,21 @@ extern crate native_tls;
 extern crate openssl;
 #[cfg(target_os = "macos")]
 extern crate security_framework;
+extern crate tokio_core;
 extern crate trust_dns;
 extern crate trust_dns_server;
 
+use std::collections::BTreeMap;
 use std::io;
-use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4, UdpSocket, TcpListener};
+use std::io::{Read, Write};
+use std::iter;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV4, UdpSocket, TcpListener};
+use std::rc::Rc;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use futures::Stream;
 use openssl::asn1::*;
@@ -23,15 +31,19 @@ use openssl::x509::*;
 use openssl::x509::extension::*;
 #[cfg(target_os = "macos")]
 use security_framework::certificate::SecCertificate;
+use tokio_core::reactor::Core;
 
 use trust_dns::client::*;
 use trust_dns::op::*;
 use trust_dns::rr::*;
-use trust_dns::udp::UdpClientConnection;
-use trust_dns::tcp::TcpClientConnection;
+use trust_dns::udp::{UdpClientConnection, UdpClientStream};
+use trust_dns::tcp::{TcpClientConnection, TcpClientStream};
 use trust_dns::tls::TlsClientConnection;
+use trust_dns::https::{HttpsClientConnection, HttpsMethod};
 
 use trust_dns_server::ServerFuture;
+use trust_dns_server::server::{is_deferred, ListenerPolicy, Middleware, MiddlewareChain, Next,
+                               TcpLimits};
 use trust_dns_server::authority::*;
 
 mod common;
@@ -87,9 +99,1022 @@ fn test_server_www_tcp() {
     //    assert!(server_result.is_ok(), "server failed: {:?}", server_result);
 }
 
+#[test]
+fn test_server_www_connection_builder() {
+    let addr = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(127, 0, 0, 1), 0));
+    let udp_socket = UdpSocket::bind(&addr).unwrap();
+    let tcp_listener = TcpListener::bind(&addr).unwrap();
+    let udp_addr = udp_socket.local_addr().unwrap();
+    let tcp_addr = tcp_listener.local_addr().unwrap();
+
+    thread::Builder::new()
+        .name("test_server:connection_builder:udp".to_string())
+        .spawn(move || server_thread_udp(udp_socket))
+        .unwrap();
+    thread::Builder::new()
+        .name("test_server:connection_builder:tcp".to_string())
+        .spawn(move || server_thread_tcp(tcp_listener))
+        .unwrap();
+
+    let client_thread = thread::Builder::new()
+        .name("test_server:connection_builder:client".to_string())
+        .spawn(move || {
+            let mut udp = ConnectionBuilder::udp(udp_addr);
+            udp.bind_addr(addr);
+            client_thread_www(udp.build().unwrap());
+            client_thread_www(ConnectionBuilder::tcp(tcp_addr).build().unwrap());
+        })
+        .unwrap();
+
+    let client_result = client_thread.join();
+    assert!(client_result.is_ok(), "client failed: {:?}", client_result);
+}
+
 #[test]
 fn test_server_www_tls() {
     let subject_name = "ns.example.com";
+    let (cert_der, pkcs12_der) = create_cert(subject_name);
+
+    // Server address
+    let addr = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(127, 0, 0, 1), 0));
+    let tcp_listener = TcpListener::bind(&addr).unwrap();
+
+    let ipaddr = tcp_listener.local_addr().unwrap();
+    println!("tcp_listner on port: {}", ipaddr);
+
+    thread::Builder::new()
+        .name("test_server:tls:server".to_string())
+        .spawn(move || server_thread_tls(tcp_listener, pkcs12_der))
+        .unwrap();
+
+    let client_thread = thread::Builder::new()
+        .name("test_server:tcp:client".to_string())
+        .spawn(move || {
+            client_thread_www(lazy_tls_client(ipaddr, subject_name.to_string(), cert_der))
+        })
+        .unwrap();
+
+    let client_result = client_thread.join();
+    //    let server_result = server_thread.join();
+
+    assert!(client_result.is_ok(), "client failed: {:?}", client_result);
+    //    assert!(server_result.is_ok(), "server failed: {:?}", server_result);
+}
+
+#[test]
+fn test_server_www_https() {
+    let subject_name = "ns.example.com";
+    let (cert_der, pkcs12_der) = create_cert(subject_name);
+
+    let addr = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(127, 0, 0, 1), 0));
+    let tcp_listener = TcpListener::bind(&addr).unwrap();
+    let ipaddr = tcp_listener.local_addr().unwrap();
+
+    thread::Builder::new()
+        .name("test_server:https:server".to_string())
+        .spawn(move || server_thread_https(tcp_listener, pkcs12_der))
+        .unwrap();
+
+    let client_thread = thread::Builder::new()
+        .name("test_server:https:client".to_string())
+        .spawn(move || for method in &[HttpsMethod::Post, HttpsMethod::Get] {
+            client_thread_www(lazy_https_client(ipaddr,
+                                                subject_name.to_string(),
+                                                cert_der.clone(),
+                                                *method))
+        })
+        .unwrap();
+
+    let client_result = client_thread.join();
+    assert!(client_result.is_ok(), "client failed: {:?}", client_result);
+}
+
+#[test]
+fn test_server_www_https_h2() {
+    let subject_name = "ns.example.com";
+    let (cert_der, pkcs12_der) = create_cert(subject_name);
+
+    let addr = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(127, 0, 0, 1), 0));
+    let tcp_listener = TcpListener::bind(&addr).unwrap();
+    let ipaddr = tcp_listener.local_addr().unwrap();
+
+    thread::Builder::new()
+        .name("test_server:https_h2:server".to_string())
+        .spawn(move || server_thread_https(tcp_listener, pkcs12_der))
+        .unwrap();
+
+    // a client which never starts its handshake does not hold up the others
+    let _stalled = std::net::TcpStream::connect(ipaddr).unwrap();
+
+    let mut builder = native_tls::TlsConnector::builder().unwrap();
+    builder.add_root_certificate(native_tls::Certificate::from_der(&cert_der).unwrap()).unwrap();
+    let connector = builder.build().unwrap();
+    let tcp_stream = std::net::TcpStream::connect(ipaddr).unwrap();
+    tcp_stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    let mut stream = connector.connect(subject_name, tcp_stream).unwrap();
+
+    // HTTP/2 with prior knowledge, POSTs on two streams at once
+    let mut request = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n".to_vec();
+    h2_frame(&mut request, 0x4, 0x0, 0, &[]);
+    for &stream_id in &[1u32, 3] {
+        let mut query = Query::new();
+        query.name(Name::parse("www.example.com.", None).unwrap()).query_type(RecordType::A);
+        let mut message = Message::new();
+        message.id(0).add_query(query);
+        let message = message.to_vec().unwrap();
+
+        let mut block = Vec::new();
+        for &(name, value) in &[(":method", "POST"),
+                                (":scheme", "https"),
+                                (":authority", subject_name),
+                                (":path", "/dns-query"),
+                                ("content-type", "application/dns-message")] {
+            // a literal without indexing, with a new name and without Huffman coding
+            block.push(0x00);
+            block.push(name.len() as u8);
+            block.extend_from_slice(name.as_bytes());
+            block.push(value.len() as u8);
+            block.extend_from_slice(value.as_bytes());
+        }
+
+        // HEADERS with END_HEADERS, then DATA with END_STREAM
+        h2_frame(&mut request, 0x1, 0x4, stream_id, &block);
+        h2_frame(&mut request, 0x0, 0x1, stream_id, &message);
+    }
+    stream.write_all(&request).unwrap();
+
+    let mut headers = BTreeMap::new();
+    let mut bodies = BTreeMap::new();
+    while bodies.len() < 2 {
+        let mut header = [0u8; 9];
+        stream.read_exact(&mut header).unwrap();
+        let len = (header[0] as usize) << 16 | (header[1] as usize) << 8 | header[2] as usize;
+        let stream_id = (header[5] as u32) << 24 | (header[6] as u32) << 16 |
+                        (header[7] as u32) << 8 | header[8] as u32;
+        let mut payload = vec![0u8; len];
+        stream.read_exact(&mut payload).unwrap();
+
+        match header[3] {
+            0x0 => {
+                assert_eq!(header[4] & 0x1, 0x1, "the response is a single DATA frame");
+                bodies.insert(stream_id, payload);
+            }
+            0x1 => {
+                headers.insert(stream_id, payload);
+            }
+            // SETTINGS, its ACK, and WINDOW_UPDATE
+            0x4 | 0x8 => (),
+            frame_type => panic!("unexpected frame: {}", frame_type),
+        }
+    }
+
+    for stream_id in &[1u32, 3] {
+        // :status 200, a literal with the name of the static table
+        assert!(headers[stream_id].starts_with(&[0x08, 3, b'2', b'0', b'0']));
+
+        let response = Message::from_vec(&bodies[stream_id]).unwrap();
+        assert_eq!(response.get_response_code(), ResponseCode::NoError);
+        assert_eq!(response.get_answers()[0].get_rdata(),
+                   &RData::A(Ipv4Addr::new(93, 184, 216, 34)));
+    }
+}
+
+#[test]
+fn test_server_tcp_partial_request_closed() {
+    let addr = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(127, 0, 0, 1), 0));
+    let tcp_listener = TcpListener::bind(&addr).unwrap();
+
+    let ipaddr = tcp_listener.local_addr().unwrap();
+    println!("tcp_listner on port: {}", ipaddr);
+
+    thread::Builder::new()
+        .name("test_server:tcp_limits:server".to_string())
+        .spawn(move || {
+            let catalog = new_catalog();
+            let mut server = ServerFuture::new(catalog).expect("new tcp server failed");
+            let limits = TcpLimits {
+                idle_timeout: Duration::from_secs(30),
+                read_timeout: Duration::from_millis(100),
+                ..TcpLimits::default()
+            };
+            server.register_listener_with_limits(tcp_listener, limits)
+                .expect("tcp registration failed");
+
+            server.listen().unwrap();
+        })
+        .unwrap();
+
+    // send only the first byte of the length, and then stall
+    let mut stream = std::net::TcpStream::connect(ipaddr).unwrap();
+    stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    stream.write_all(&[0x00]).unwrap();
+
+    // the server should hang up well before the idle timeout
+    let mut buf = [0u8; 2];
+    let read = stream.read(&mut buf).expect("server did not close the connection");
+    assert_eq!(read, 0);
+}
+
+#[test]
+fn test_server_forward_update() {
+    let addr = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(127, 0, 0, 1), 0));
+    let tcp_listener = TcpListener::bind(&addr).unwrap();
+    let primary_addr = tcp_listener.local_addr().unwrap();
+    let udp_socket = UdpSocket::bind(&addr).unwrap();
+    let secondary_addr = udp_socket.local_addr().unwrap();
+
+    let rsa = Rsa::generate(512).unwrap();
+    let key = dnssec::KeyPair::from_rsa(rsa).unwrap();
+    let signer_name = Name::parse("trusted.example.com.", None).unwrap();
+    let signer = dnssec::Signer::new(dnssec::Algorithm::RSASHA256,
+                                     key,
+                                     signer_name.clone(),
+                                     chrono::Duration::max_value(),
+                                     true,
+                                     true);
+
+    // the primary accepts updates signed by the trusted key
+    let mut primary = create_example();
+    primary.set_allow_update(true);
+    let mut auth_key = Record::with(signer_name, RecordType::KEY, 300);
+    auth_key.rdata(RData::KEY(rdata::DNSKEY::new(false,
+                                                 false,
+                                                 false,
+                                                 signer.get_algorithm(),
+                                                 signer.get_key()
+                                                     .to_public_bytes()
+                                                     .expect("to_vec failed"))));
+    primary.upsert(auth_key, 0);
+
+    thread::Builder::new()
+        .name("test_server:forward_update:primary".to_string())
+        .spawn(move || {
+            let mut catalog = Catalog::new();
+            catalog.upsert(primary.get_origin().clone(), primary);
+            let mut server = ServerFuture::new(catalog).expect("new tcp server failed");
+            server.register_listener(tcp_listener, Duration::from_secs(30))
+                .expect("tcp registration failed");
+            server.listen().unwrap();
+        })
+        .unwrap();
+
+    // the secondary has no knowledge of the key, and forwards to the primary
+    thread::Builder::new()
+        .name("test_server:forward_update:secondary".to_string())
+        .spawn(move || {
+            let mut secondary = Authority::new(Name::parse("example.com.", None).unwrap(),
+                                               BTreeMap::new(),
+                                               ZoneType::Slave,
+                                               false,
+                                               false);
+            secondary.set_update_forward(Some(primary_addr));
+
+            let mut catalog = Catalog::new();
+            catalog.upsert(secondary.get_origin().clone(), secondary);
+            let mut server = ServerFuture::new(catalog).expect("new udp server failed");
+            server.register_socket(udp_socket);
+            server.listen().unwrap();
+        })
+        .unwrap();
+
+    let origin = Name::parse("example.com.", None).unwrap();
+    let mut record = Record::with(Name::parse("new.example.com.", None).unwrap(),
+                                  RecordType::A,
+                                  300);
+    record.rdata(RData::A(Ipv4Addr::new(100, 10, 100, 10)));
+
+    let client = SyncClient::with_signer(lazy_udp_client(secondary_addr), signer);
+    let result = client.create(record.clone(), origin.clone()).expect("create failed");
+    assert_eq!(result.get_response_code(), ResponseCode::NoError);
+
+    // the primary has the new record
+    let client = SyncClient::new(lazy_tcp_client(primary_addr));
+    let result = client.query(record.get_name(), DNSClass::IN, RecordType::A)
+        .expect("query failed");
+    assert_eq!(result.get_answers(), &[record.clone()]);
+
+    // unsigned updates are relayed, and refused by the primary
+    let client = SyncClient::new(lazy_udp_client(secondary_addr));
+    let result = client.create(record, origin).expect("create failed");
+    assert_eq!(result.get_response_code(), ResponseCode::Refused);
+}
+
+#[test]
+fn test_server_forward_update_bytes() {
+    use trust_dns::serialize::binary::{BinEncoder, BinSerializable};
+
+    let addr = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(127, 0, 0, 1), 0));
+    let tcp_listener = TcpListener::bind(&addr).unwrap();
+    let primary_addr = tcp_listener.local_addr().unwrap();
+    let udp_socket = UdpSocket::bind(&addr).unwrap();
+    let secondary_addr = udp_socket.local_addr().unwrap();
+
+    // the primary records the update as it arrives, and accepts it
+    let (forwarded_sender, forwarded) = mpsc::channel();
+    thread::Builder::new()
+        .name("test_server:forward_update_bytes:primary".to_string())
+        .spawn(move || {
+            let (mut stream, _) = tcp_listener.accept().unwrap();
+            let mut len = [0u8; 2];
+            stream.read_exact(&mut len).unwrap();
+            let mut buffer = vec![0u8; (len[0] as usize) << 8 | len[1] as usize];
+            stream.read_exact(&mut buffer).unwrap();
+
+            let update = Message::from_vec(&buffer).unwrap();
+            let response = Message::error_msg(update.get_id(),
+                                              update.get_op_code(),
+                                              ResponseCode::NoError)
+                .to_vec()
+                .unwrap();
+            stream.write_all(&[(response.len() >> 8) as u8, response.len() as u8]).unwrap();
+            stream.write_all(&response).unwrap();
+            forwarded_sender.send(buffer).unwrap();
+        })
+        .unwrap();
+
+    thread::Builder::new()
+        .name("test_server:forward_update_bytes:secondary".to_string())
+        .spawn(move || {
+            let mut secondary = Authority::new(Name::parse("example.com.", None).unwrap(),
+                                               BTreeMap::new(),
+                                               ZoneType::Slave,
+                                               false,
+                                               false);
+            secondary.set_update_forward(Some(primary_addr));
+
+            let mut catalog = Catalog::new();
+            catalog.upsert(secondary.get_origin().clone(), secondary);
+            let mut server = ServerFuture::new(catalog).expect("new udp server failed");
+            server.register_socket(udp_socket);
+            server.listen().unwrap();
+        })
+        .unwrap();
+
+    let mut zone = Query::new();
+    zone.name(Name::parse("example.com.", None).unwrap())
+        .query_class(DNSClass::IN)
+        .query_type(RecordType::SOA);
+    let mut record = Record::with(Name::parse("new.example.com.", None).unwrap(),
+                                  RecordType::A,
+                                  300);
+    record.rdata(RData::A(Ipv4Addr::new(100, 10, 100, 10)));
+
+    let mut update = Message::new();
+    update.id(4096)
+        .message_type(MessageType::Query)
+        .op_code(OpCode::Update)
+        .add_query(zone);
+    update.add_name_server(record);
+
+    // without name compression, the way the secondary would not write it
+    let mut sent = Vec::new();
+    {
+        let mut encoder = BinEncoder::new(&mut sent);
+        encoder.set_canonical_names(true);
+        update.emit(&mut encoder).unwrap();
+    }
+    assert!(sent != update.to_vec().unwrap());
+
+    let client = UdpSocket::bind(&addr).unwrap();
+    client.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    client.send_to(&sent, secondary_addr).unwrap();
+
+    let mut buffer = [0u8; 512];
+    let (len, _) = client.recv_from(&mut buffer).expect("no response to the update");
+    let response = Message::from_vec(&buffer[..len]).unwrap();
+    assert_eq!(response.get_id(), 4096);
+    assert_eq!(response.get_response_code(), ResponseCode::NoError);
+
+    assert_eq!(forwarded.recv_timeout(Duration::from_secs(5)).unwrap(), sent);
+}
+
+#[test]
+fn test_server_slave_transfer() {
+    let addr = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(127, 0, 0, 1), 0));
+    let tcp_listener = TcpListener::bind(&addr).unwrap();
+    let primary_addr = tcp_listener.local_addr().unwrap();
+    let udp_socket = UdpSocket::bind(&addr).unwrap();
+    let secondary_addr = udp_socket.local_addr().unwrap();
+
+    thread::Builder::new()
+        .name("test_server:slave_transfer:primary".to_string())
+        .spawn(move || {
+            let primary = create_example();
+            let mut catalog = Catalog::new();
+            catalog.upsert(primary.get_origin().clone(), primary);
+            let mut server = ServerFuture::new(catalog).expect("new tcp server failed");
+            server.register_listener(tcp_listener, Duration::from_secs(30))
+                .expect("tcp registration failed");
+            server.listen().unwrap();
+        })
+        .unwrap();
+
+    // the secondary starts without any records, and transfers the zone from the primary
+    thread::Builder::new()
+        .name("test_server:slave_transfer:secondary".to_string())
+        .spawn(move || {
+            let mut secondary = Authority::new(Name::parse("example.com.", None).unwrap(),
+                                               BTreeMap::new(),
+                                               ZoneType::Slave,
+                                               false,
+                                               false);
+            secondary.set_primary(Some(primary_addr));
+
+            let mut catalog = Catalog::new();
+            catalog.upsert(secondary.get_origin().clone(), secondary);
+            let mut server = ServerFuture::new(catalog).expect("new udp server failed");
+            server.register_socket(udp_socket);
+            server.listen().unwrap();
+        })
+        .unwrap();
+
+    let name = Name::parse("www.example.com.", None).unwrap();
+    let client = SyncClient::new(lazy_udp_client(secondary_addr));
+    let mut answers = Vec::new();
+    for _ in 0..50 {
+        let result = client.query(&name, DNSClass::IN, RecordType::A).expect("query failed");
+        if !result.get_answers().is_empty() {
+            answers = result.get_answers().to_vec();
+            break;
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+
+    let expected = create_example();
+    let expected = expected.lookup(&name, RecordType::A, false, dnssec::SupportedAlgorithms::new());
+    assert_eq!(answers.iter().collect::<Vec<_>>(), expected);
+
+    // a NOTIFY from the primary is acknowledged, the zone is already up to date
+    let mut client = SyncClient::new(lazy_udp_client(secondary_addr));
+    let result = client.notify(Name::parse("example.com.", None).unwrap(),
+                               DNSClass::IN,
+                               RecordType::SOA,
+                               None::<Record>)
+        .expect("notify failed");
+    assert_eq!(result.get_response_code(), ResponseCode::NoError);
+}
+
+#[test]
+fn test_server_slave_transfer_tsig() {
+    let addr = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(127, 0, 0, 1), 0));
+    let tcp_listener = TcpListener::bind(&addr).unwrap();
+    let primary_addr = tcp_listener.local_addr().unwrap();
+    let udp_socket = UdpSocket::bind(&addr).unwrap();
+    let secondary_addr = udp_socket.local_addr().unwrap();
+
+    let key_name = Name::parse("transfer-key.example.com.", None).unwrap();
+    let tsigner = Arc::new(dnssec::TSigner::new(key_name.clone(),
+                                                dnssec::TsigAlgorithm::HmacSha256,
+                                                b"the secret of the secondaries".to_vec()));
+
+    // the primary only permits transfers signed with the key
+    let primary_tsigner = tsigner.clone();
+    thread::Builder::new()
+        .name("test_server:slave_transfer_tsig:primary".to_string())
+        .spawn(move || {
+            let mut primary = create_example();
+            primary.set_transfer_acl(Some(TransferAcl::new(vec![TransferRule::Key(key_name)])));
+            let mut catalog = Catalog::new();
+            catalog.upsert(primary.get_origin().clone(), primary);
+            catalog.add_tsig_key(primary_tsigner);
+            let mut server = ServerFuture::new(catalog).expect("new tcp server failed");
+            server.register_listener(tcp_listener, Duration::from_secs(30))
+                .expect("tcp registration failed");
+            server.listen().unwrap();
+        })
+        .unwrap();
+
+    // the secondary signs its queries, sent from the loopback address, and verifies the responses
+    thread::Builder::new()
+        .name("test_server:slave_transfer_tsig:secondary".to_string())
+        .spawn(move || {
+            let mut secondary = Authority::new(Name::parse("example.com.", None).unwrap(),
+                                               BTreeMap::new(),
+                                               ZoneType::Slave,
+                                               false,
+                                               false);
+            secondary.set_primary(Some(primary_addr));
+            secondary.set_primary_key(Some(tsigner));
+            secondary.set_transfer_source(Some(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1))));
+
+            let mut catalog = Catalog::new();
+            catalog.upsert(secondary.get_origin().clone(), secondary);
+            let mut server = ServerFuture::new(catalog).expect("new udp server failed");
+            server.register_socket(udp_socket);
+            server.listen().unwrap();
+        })
+        .unwrap();
+
+    let name = Name::parse("www.example.com.", None).unwrap();
+    let client = SyncClient::new(lazy_udp_client(secondary_addr));
+    let mut answers = Vec::new();
+    for _ in 0..50 {
+        let result = client.query(&name, DNSClass::IN, RecordType::A).expect("query failed");
+        if !result.get_answers().is_empty() {
+            answers = result.get_answers().to_vec();
+            break;
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+
+    let expected = create_example();
+    let expected = expected.lookup(&name, RecordType::A, false, dnssec::SupportedAlgorithms::new());
+    assert_eq!(answers.iter().collect::<Vec<_>>(), expected);
+}
+
+#[test]
+fn test_server_notify_secondaries() {
+    let addr = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(127, 0, 0, 1), 0));
+    let secondary = UdpSocket::bind(&addr).unwrap();
+    secondary.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    let secondary_addr = secondary.local_addr().unwrap();
+    let udp_socket = UdpSocket::bind(&addr).unwrap();
+
+    let mut primary = create_example();
+    let origin = primary.get_origin().clone();
+    let serial = primary.get_serial();
+    primary.set_secondaries(vec![secondary_addr]);
+
+    thread::Builder::new()
+        .name("test_server:notify_secondaries:primary".to_string())
+        .spawn(move || {
+            let mut catalog = Catalog::new();
+            catalog.upsert(primary.get_origin().clone(), primary);
+            let mut server = ServerFuture::new(catalog).expect("new udp server failed");
+            server.register_socket(udp_socket);
+            server.listen().unwrap();
+        })
+        .unwrap();
+
+    // the secondary is notified once the server runs
+    let mut buf = [0u8; 4096];
+    let (len, _) = secondary.recv_from(&mut buf).expect("no notify received");
+    let notify = Message::from_vec(&buf[..len]).expect("bad notify");
+    assert_eq!(notify.get_op_code(), OpCode::Notify);
+    assert_eq!(notify.get_message_type(), MessageType::Query);
+    assert_eq!(notify.get_queries()[0].get_name(), &origin);
+    assert_eq!(notify.get_queries()[0].get_query_type(), RecordType::SOA);
+    match *notify.get_answers()[0].get_rdata() {
+        RData::SOA(ref soa) => assert_eq!(soa.get_serial(), serial),
+        ref other => panic!("expected soa: {:?}", other),
+    }
+}
+
+/// The example zone with 20 TXT records at big.example.com., 4KB which do not fit in a UDP
+///  response
+fn create_big_example() -> Authority {
+    let mut example = create_example();
+    let big = Name::parse("big.example.com.", None).unwrap();
+    let serial = example.get_serial();
+    for i in 0..20 {
+        let mut txt = Record::with(big.clone(), RecordType::TXT, 86400);
+        txt.rdata(RData::TXT(rdata::TXT::new(vec![format!("{:02}{}",
+                                                          i,
+                                                          iter::repeat("a")
+                                                              .take(198)
+                                                              .collect::<String>())])));
+        assert!(example.upsert(txt, serial));
+    }
+
+    example
+}
+
+#[test]
+fn test_server_truncated_tcp_fallback() {
+    let addr = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(127, 0, 0, 1), 0));
+    let udp_socket = UdpSocket::bind(&addr).unwrap();
+    let udp_addr = udp_socket.local_addr().unwrap();
+    let tcp_listener = TcpListener::bind(&addr).unwrap();
+    let tcp_addr = tcp_listener.local_addr().unwrap();
+
+    // neither set fits in the 1500 bytes the client accepts over UDP
+    let mut example = create_big_example();
+    let origin = example.get_origin().clone();
+    let big = Name::parse("big.example.com.", None).unwrap();
+    let serial = example.get_serial();
+    for i in 0..16 {
+        let mut dnskey = Record::with(origin.clone(), RecordType::DNSKEY, 86400);
+        dnskey.rdata(RData::DNSKEY(rdata::DNSKEY::new(true,
+                                                      false,
+                                                      false,
+                                                      dnssec::Algorithm::RSASHA256,
+                                                      vec![i; 256])));
+        assert!(example.upsert(dnskey, serial));
+    }
+
+    thread::Builder::new()
+        .name("test_server:truncated:server".to_string())
+        .spawn(move || {
+            let mut catalog = Catalog::new();
+            catalog.upsert(example.get_origin().clone(), example);
+            let mut server = ServerFuture::new(catalog).expect("new server failed");
+            server.register_socket(udp_socket);
+            server.register_listener(tcp_listener, Duration::from_secs(30))
+                .expect("tcp registration failed");
+            server.listen().unwrap();
+        })
+        .unwrap();
+
+    let mut io_loop = Core::new().unwrap();
+    let (stream, sender) = UdpClientStream::new(udp_addr, io_loop.handle());
+    let mut udp_client = ClientFuture::new(stream, sender, io_loop.handle(), None);
+    let (stream, sender) = TcpClientStream::new(tcp_addr, io_loop.handle());
+    let tcp_client = ClientFuture::new(stream, sender, io_loop.handle(), None);
+
+    // over UDP, the answer is empty rather than a partial RRSet
+    let response = io_loop.run(udp_client.query(big.clone(), DNSClass::IN, RecordType::TXT))
+        .expect("udp query failed");
+    assert!(response.is_truncated());
+    assert!(response.get_answers().is_empty());
+    assert_eq!(response.get_queries().len(), 1);
+
+    let mut client = TcpFallbackClientHandle::new(udp_client, tcp_client);
+
+    let response = io_loop.run(client.query(big, DNSClass::IN, RecordType::TXT))
+        .expect("txt query failed");
+    assert!(!response.is_truncated());
+    assert_eq!(response.get_answers().len(), 20);
+
+    let response = io_loop.run(client.query(origin, DNSClass::IN, RecordType::DNSKEY))
+        .expect("dnskey query failed");
+    assert!(!response.is_truncated());
+    assert_eq!(response.get_answers()
+                   .iter()
+                   .filter(|r| r.get_rr_type() == RecordType::DNSKEY)
+                   .count(),
+               16);
+
+    // small answers are not retried
+    let response = io_loop.run(client.query(Name::parse("www.example.com.", None).unwrap(),
+                                            DNSClass::IN,
+                                            RecordType::A))
+        .expect("a query failed");
+    assert!(!response.is_truncated());
+    assert_eq!(response.get_answers().len(), 1);
+}
+
+#[test]
+fn test_sync_client_tcp_fallback() {
+    let addr = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(127, 0, 0, 1), 0));
+    let udp_socket = UdpSocket::bind(&addr).unwrap();
+    let udp_addr = udp_socket.local_addr().unwrap();
+    // the fallback is to the same name server, on the same port
+    let tcp_listener = TcpListener::bind(&udp_addr).unwrap();
+
+    let example = create_big_example();
+    thread::Builder::new()
+        .name("test_server:sync_tcp_fallback:server".to_string())
+        .spawn(move || {
+            let mut catalog = Catalog::new();
+            catalog.upsert(example.get_origin().clone(), example);
+            let mut server = ServerFuture::new(catalog).expect("new server failed");
+            server.register_socket(udp_socket);
+            server.register_listener(tcp_listener, Duration::from_secs(30))
+                .expect("tcp registration failed");
+            server.listen().unwrap();
+        })
+        .unwrap();
+
+    let conn = UdpClientConnection::new(udp_addr).unwrap();
+    let client = SyncClient::with_tcp_fallback(conn);
+    let big = Name::parse("big.example.com.", None).unwrap();
+
+    // each truncated response is retried over its own connection
+    for _ in 0..2 {
+        let response = client.query(&big, DNSClass::IN, RecordType::TXT)
+            .expect("txt query failed");
+        assert!(!response.is_truncated());
+        assert_eq!(response.get_answers().len(), 20);
+    }
+}
+
+#[test]
+fn test_server_listener_policy() {
+    let addr = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(127, 0, 0, 1), 0));
+    let udp_socket = UdpSocket::bind(&addr).unwrap();
+    let udp_addr = udp_socket.local_addr().unwrap();
+    let tcp_listener = TcpListener::bind(&addr).unwrap();
+    let tcp_addr = tcp_listener.local_addr().unwrap();
+
+    thread::Builder::new()
+        .name("test_server:listener_policy:server".to_string())
+        .spawn(move || {
+            let mut server = ServerFuture::new(new_catalog()).expect("new server failed");
+            // the public socket refuses zone transfers and NOTIFY, the internal listener does not
+            server.register_socket_with_policy(udp_socket,
+                                               ListenerPolicy {
+                                                   zone_transfer: false,
+                                                   notify: false,
+                                                   ..ListenerPolicy::default()
+                                               });
+            server.register_listener_with_policy(tcp_listener,
+                                               TcpLimits::default(),
+                                               ListenerPolicy::default())
+                .expect("tcp registration failed");
+            server.listen().unwrap();
+        })
+        .unwrap();
+
+    let origin = Name::parse("example.com.", None).unwrap();
+    let www = Name::parse("www.example.com.", None).unwrap();
+
+    let mut client = SyncClient::new(lazy_udp_client(udp_addr));
+    let response = client.query(&origin, DNSClass::IN, RecordType::AXFR).expect("query failed");
+    assert_eq!(response.get_response_code(), ResponseCode::Refused);
+    assert!(response.get_answers().is_empty());
+    let response = client.notify(origin.clone(), DNSClass::IN, RecordType::SOA, None::<RecordSet>)
+        .expect("notify failed");
+    assert_eq!(response.get_response_code(), ResponseCode::Refused);
+    let response = client.query(&www, DNSClass::IN, RecordType::A).expect("query failed");
+    assert_eq!(response.get_response_code(), ResponseCode::NoError);
+    assert_eq!(response.get_answers().len(), 1);
+
+    let client = SyncClient::new(lazy_tcp_client(tcp_addr));
+    let response = client.query(&origin, DNSClass::IN, RecordType::AXFR).expect("query failed");
+    assert_eq!(response.get_response_code(), ResponseCode::NoError);
+    assert!(!response.get_answers().is_empty());
+}
+
+/// Refuses zone transfers and the queries for a zone, counting the requests and the placeholder
+///  responses it sees
+struct Gate {
+    refused: Name,
+    requests: Arc<AtomicUsize>,
+    deferred: Arc<AtomicUsize>,
+}
+
+impl Middleware for Gate {
+    fn handle_request(&self, request: &Message, src: SocketAddr, next: Next) -> Message {
+        self.requests.fetch_add(1, Ordering::SeqCst);
+        let query = &request.get_queries()[0];
+        if query.get_query_type() == RecordType::AXFR || self.refused.zone_of(query.get_name()) {
+            let mut response = Message::error_msg(request.get_id(),
+                                                  request.get_op_code(),
+                                                  ResponseCode::Refused);
+            response.add_queries(request.get_queries().iter().cloned());
+            return response;
+        }
+
+        let response = next.handle_request(request, src);
+        if is_deferred(&response) {
+            self.deferred.fetch_add(1, Ordering::SeqCst);
+        }
+        response
+    }
+}
+
+#[test]
+fn test_server_middleware_before_dispatch() {
+    let addr = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(127, 0, 0, 1), 0));
+    let udp_socket = UdpSocket::bind(&addr).unwrap();
+    let udp_addr = udp_socket.local_addr().unwrap();
+    let tcp_listener = TcpListener::bind(&addr).unwrap();
+    let tcp_addr = tcp_listener.local_addr().unwrap();
+    // an upstream which never answers, a forwarded query would time out
+    let upstream = UdpSocket::bind(&addr).unwrap();
+    let upstream_addr = upstream.local_addr().unwrap();
+
+    let forwarded = Name::parse("forward.test.", None).unwrap();
+    let requests = Arc::new(AtomicUsize::new(0));
+    let deferred = Arc::new(AtomicUsize::new(0));
+    let gate_forwarded = forwarded.clone();
+    let gate_requests = requests.clone();
+    let gate_deferred = deferred.clone();
+    thread::Builder::new()
+        .name("test_server:middleware_before_dispatch:server".to_string())
+        .spawn(move || {
+            let forwarder = ForwardAuthority::new(gate_forwarded.clone(),
+                                                  vec![ForwardUpstream::Udp(upstream_addr)]);
+            let mut catalog = new_catalog();
+            catalog.upsert_forward(gate_forwarded.clone(), forwarder);
+            let mut middleware = MiddlewareChain::new();
+            middleware.push(Rc::new(Gate {
+                refused: gate_forwarded,
+                requests: gate_requests,
+                deferred: gate_deferred,
+            }));
+
+            let mut server = ServerFuture::new(catalog).expect("new server failed");
+            server.set_middleware(middleware);
+            server.register_socket(udp_socket);
+            server.register_listener(tcp_listener, Duration::from_secs(30))
+                .expect("tcp registration failed");
+            server.listen().unwrap();
+        })
+        .unwrap();
+
+    // the forwarded query and the transfer are refused by the layer, before they are dispatched
+    let www = Name::parse("www.forward.test.", None).unwrap();
+    let client = SyncClient::new(lazy_udp_client(udp_addr));
+    let response = client.query(&www, DNSClass::IN, RecordType::A).expect("query failed");
+    assert_eq!(response.get_response_code(), ResponseCode::Refused);
+
+    let origin = Name::parse("example.com.", None).unwrap();
+    let client = SyncClient::new(lazy_tcp_client(tcp_addr));
+    let response = client.query(&origin, DNSClass::IN, RecordType::AXFR).expect("query failed");
+    assert_eq!(response.get_response_code(), ResponseCode::Refused);
+    assert!(response.get_answers().is_empty());
+
+    // an IXFR is passed on, and answered after the chain by the transfer, without the serial of
+    //  the client it is malformed
+    let response = client.query(&origin, DNSClass::IN, RecordType::IXFR).expect("query failed");
+    assert_eq!(response.get_response_code(), ResponseCode::FormErr);
+    assert_eq!(deferred.load(Ordering::SeqCst), 1);
+
+    // the catalog answers the others at the end of the chain
+    let www = Name::parse("www.example.com.", None).unwrap();
+    let response = client.query(&www, DNSClass::IN, RecordType::A).expect("query failed");
+    assert_eq!(response.get_response_code(), ResponseCode::NoError);
+    assert_eq!(response.get_answers().len(), 1);
+    assert_eq!(requests.load(Ordering::SeqCst), 4);
+    assert_eq!(deferred.load(Ordering::SeqCst), 1);
+    drop(upstream);
+}
+
+#[test]
+fn test_server_listen_addrs_udp_wildcard() {
+    let addr = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(0, 0, 0, 0), 0));
+    let udp_socket = UdpSocket::bind(&addr).unwrap();
+    let udp_addr = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(127, 0, 0, 1),
+                                                    udp_socket.local_addr().unwrap().port()));
+
+    thread::Builder::new()
+        .name("test_server:listen_addrs_udp_wildcard:server".to_string())
+        .spawn(move || {
+            // the zone is bound to the very address the datagrams are sent to
+            let mut example = create_example();
+            example.set_listen_addrs(Some(vec![udp_addr.ip()]));
+            let mut catalog = Catalog::new();
+            catalog.upsert(example.get_origin().clone(), example);
+            assert!(catalog.has_listen_addrs());
+
+            let mut server = ServerFuture::new(catalog).expect("new udp server failed");
+            server.register_socket(udp_socket);
+            server.listen().unwrap();
+        })
+        .unwrap();
+
+    // the socket only knows its unspecified address, the zone is refused rather than answered on
+    //  the addresses of other tenants
+    let www = Name::parse("www.example.com.", None).unwrap();
+    let client = SyncClient::new(lazy_udp_client(udp_addr));
+    let response = client.query(&www, DNSClass::IN, RecordType::A).expect("query failed");
+    assert_eq!(response.get_response_code(), ResponseCode::Refused);
+    assert!(response.get_answers().is_empty());
+}
+
+#[test]
+fn test_server_shutdown() {
+    let addr = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(127, 0, 0, 1), 0));
+    let tcp_listener = TcpListener::bind(&addr).unwrap();
+    let tcp_addr = tcp_listener.local_addr().unwrap();
+
+    let (handle_sender, handle_receiver) = mpsc::channel();
+    let (stopped_sender, stopped_receiver) = mpsc::channel();
+    thread::Builder::new()
+        .name("test_server:shutdown:server".to_string())
+        .spawn(move || {
+            let mut server = ServerFuture::new(new_catalog()).expect("new tcp server failed");
+            server.register_listener(tcp_listener, Duration::from_secs(30))
+                .expect("tcp registration failed");
+            server.set_shutdown_grace_period(Duration::from_secs(1));
+            handle_sender.send(server.shutdown_handle()).unwrap();
+
+            stopped_sender.send(server.listen().is_ok()).unwrap();
+        })
+        .unwrap();
+    let shutdown = handle_receiver.recv().unwrap();
+    assert!(!shutdown.is_shutdown());
+
+    let www = Name::parse("www.example.com.", None).unwrap();
+    let client = SyncClient::new(lazy_tcp_client(tcp_addr));
+    let response = client.query(&www, DNSClass::IN, RecordType::A).expect("query failed");
+    assert_eq!(response.get_response_code(), ResponseCode::NoError);
+
+    // nothing is in flight, listen returns well before the grace period expires
+    let start = Instant::now();
+    shutdown.shutdown();
+    assert!(shutdown.is_shutdown());
+    assert_eq!(stopped_receiver.recv_timeout(Duration::from_secs(5)), Ok(true));
+    assert!(start.elapsed() < Duration::from_secs(1));
+    assert_eq!(shutdown.get_in_flight(), 0);
+
+    // the listener was closed
+    assert!(std::net::TcpStream::connect(tcp_addr).is_err());
+}
+
+#[test]
+fn test_server_shutdown_in_flight() {
+    let addr = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(127, 0, 0, 1), 0));
+    let tcp_listener = TcpListener::bind(&addr).unwrap();
+    let tcp_addr = tcp_listener.local_addr().unwrap();
+
+    // an upstream which answers once told to
+    let upstream = UdpSocket::bind(&addr).unwrap();
+    let upstream_addr = upstream.local_addr().unwrap();
+    let (received_sender, received_receiver) = mpsc::channel();
+    let (answer_sender, answer_receiver) = mpsc::channel::<()>();
+    thread::Builder::new()
+        .name("test_server:shutdown_in_flight:upstream".to_string())
+        .spawn(move || {
+            let mut buffer = [0u8; 512];
+            let (len, src) = upstream.recv_from(&mut buffer).unwrap();
+            received_sender.send(()).unwrap();
+            answer_receiver.recv().unwrap();
+
+            let query = Message::from_vec(&buffer[..len]).unwrap();
+            let mut response = Message::new();
+            response.id(query.get_id()).message_type(MessageType::Response);
+            response.add_queries(query.get_queries().iter().cloned());
+            response.add_answer(Record::from_rdata(query.get_queries()[0].get_name().clone(),
+                                                   60,
+                                                   RecordType::A,
+                                                   RData::A(Ipv4Addr::new(192, 0, 2, 1))));
+            upstream.send_to(&response.to_vec().unwrap(), src).unwrap();
+        })
+        .unwrap();
+
+    let (handle_sender, handle_receiver) = mpsc::channel();
+    let (stopped_sender, stopped_receiver) = mpsc::channel();
+    thread::Builder::new()
+        .name("test_server:shutdown_in_flight:server".to_string())
+        .spawn(move || {
+            let forwarded = Name::parse("forward.test.", None).unwrap();
+            let forwarder = ForwardAuthority::new(forwarded.clone(),
+                                                  vec![ForwardUpstream::Udp(upstream_addr)]);
+            let mut catalog = new_catalog();
+            catalog.upsert_forward(forwarded, forwarder);
+
+            let mut server = ServerFuture::new(catalog).expect("new tcp server failed");
+            server.register_listener(tcp_listener, Duration::from_secs(30))
+                .expect("tcp registration failed");
+            server.set_shutdown_grace_period(Duration::from_secs(5));
+            handle_sender.send(server.shutdown_handle()).unwrap();
+
+            stopped_sender.send(server.listen().is_ok()).unwrap();
+        })
+        .unwrap();
+    let shutdown = handle_receiver.recv().unwrap();
+
+    let request = |id: u16, name: &str| {
+        let mut query = Query::new();
+        query.name(Name::parse(name, None).unwrap()).query_type(RecordType::A);
+        let mut request = Message::new();
+        request.id(id).add_query(query);
+        let buffer = request.to_vec().unwrap();
+        let mut framed = vec![(buffer.len() >> 8) as u8, buffer.len() as u8];
+        framed.extend_from_slice(&buffer);
+        framed
+    };
+    let read_response = |stream: &mut std::net::TcpStream| {
+        let mut len = [0u8; 2];
+        stream.read_exact(&mut len).unwrap();
+        let mut buffer = vec![0u8; ((len[0] as usize) << 8) | len[1] as usize];
+        stream.read_exact(&mut buffer).unwrap();
+        Message::from_vec(&buffer).unwrap()
+    };
+
+    // the forwarded query is in flight, with the connection, during the shutdown
+    let mut stream = std::net::TcpStream::connect(tcp_addr).unwrap();
+    stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    stream.write_all(&request(1, "www.forward.test.")).unwrap();
+    received_receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(shutdown.get_in_flight(), 2);
+    shutdown.shutdown();
+
+    // a request on the open connection is refused
+    stream.write_all(&request(2, "www.example.com.")).unwrap();
+    let response = read_response(&mut stream);
+    assert_eq!(response.get_id(), 2);
+    assert_eq!(response.get_response_code(), ResponseCode::Refused);
+    assert!(stopped_receiver.try_recv().is_err());
+
+    // the answer of the upstream is still relayed, after which the connection is closed
+    answer_sender.send(()).unwrap();
+    let response = read_response(&mut stream);
+    assert_eq!(response.get_id(), 1);
+    assert_eq!(response.get_response_code(), ResponseCode::NoError);
+    assert_eq!(response.get_answers().len(), 1);
+    let mut rest = Vec::new();
+    assert_eq!(stream.read_to_end(&mut rest).unwrap(), 0);
+
+    assert_eq!(stopped_receiver.recv_timeout(Duration::from_secs(5)), Ok(true));
+    assert_eq!(shutdown.get_in_flight(), 0);
+}
+
+/// Appends the HTTP/2 frame, RFC 9113 section 4.1
+fn h2_frame(buffer: &mut Vec<u8>, frame_type: u8, flags: u8, stream_id: u32, payload: &[u8]) {
+    let len = payload.len();
+    buffer.extend_from_slice(&[(len >> 16) as u8, (len >> 8) as u8, len as u8, frame_type, flags]);
+    buffer.extend_from_slice(&[(stream_id >> 24) as u8,
+                               (stream_id >> 16) as u8,
+                               (stream_id >> 8) as u8,
+                               stream_id as u8]);
+    buffer.extend_from_slice(payload);
+}
+
+/// Returns a self signed certificate for the subject, and the PKCS12 with its key
+fn create_cert(subject_name: &str) -> (Vec<u8>, Vec<u8>) {
     let rsa = Rsa::generate(2048).unwrap();
     let pkey = PKey::from_rsa(rsa).unwrap();
 
@@ -134,30 +1159,7 @@ fn test_server_www_tls() {
     let pkcs12 = pkcs12_builder.build("mypass", subject_name, &pkey, &cert).unwrap();
     let pkcs12_der = pkcs12.to_der().unwrap();
 
-    // Server address
-    let addr = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(127, 0, 0, 1), 0));
-    let tcp_listener = TcpListener::bind(&addr).unwrap();
-
-    let ipaddr = tcp_listener.local_addr().unwrap();
-    println!("tcp_listner on port: {}", ipaddr);
-
-    thread::Builder::new()
-        .name("test_server:tls:server".to_string())
-        .spawn(move || server_thread_tls(tcp_listener, pkcs12_der))
-        .unwrap();
-
-    let client_thread = thread::Builder::new()
-        .name("test_server:tcp:client".to_string())
-        .spawn(move || {
-            client_thread_www(lazy_tls_client(ipaddr, subject_name.to_string(), cert_der))
-        })
-        .unwrap();
-
-    let client_result = client_thread.join();
-    //    let server_result = server_thread.join();
-
-    assert!(client_result.is_ok(), "client failed: {:?}", client_result);
-    //    assert!(server_result.is_ok(), "server failed: {:?}", server_result);
+    (cert_der, pkcs12_der)
 }
 
 fn lazy_udp_client(ipaddr: SocketAddr) -> UdpClientConnection {
@@ -184,6 +1186,24 @@ fn lazy_tls_client(ipaddr: SocketAddr,
     builder.build(ipaddr, subject_name).unwrap()
 }
 
+fn lazy_https_client(ipaddr: SocketAddr,
+                     subject_name: String,
+                     cert_der: Vec<u8>,
+                     method: HttpsMethod)
+                     -> HttpsClientConnection {
+    let mut builder = HttpsClientConnection::builder();
+
+  #[cfg(target_os = "macos")]
+    let trust_chain = SecCertificate::from_der(&cert_der).unwrap();
+
+  #[cfg(target_os = "linux")]
+    let trust_chain = X509::from_der(&cert_der).unwrap();
+
+    builder.add_ca(trust_chain);
+    builder.method(method);
+    builder.build(ipaddr, subject_name).unwrap()
+}
+
 fn client_thread_www<C: ClientConnection>(conn: C)
     where C::MessageStream: Stream<Item = Vec<u8>, Error = io::Error> + 'static
 {
@@ -255,3 +1275,13 @@ fn server_thread_tls(tls_listener: TcpListener, pkcs12_der: Vec<u8>) {
 
     server.listen().unwrap();
 }
+
+fn server_thread_https(https_listener: TcpListener, pkcs12_der: Vec<u8>) {
+    let catalog = new_catalog();
+    let mut server = ServerFuture::new(catalog).expect("new https server failed");
+    let pkcs12 = native_tls::Pkcs12::from_der(&pkcs12_der, "mypass").expect("Pkcs12::from_der");
+    server.register_https_listener(https_listener, Duration::from_secs(30), pkcs12)
+        .expect("https registration failed");
+
+    server.listen().unwrap();
+}
@@ -0,0 +1,37 @@
+extern crate trust_dns_server;
+
+use std::env;
+use std::io;
+
+use trust_dns_server::server::listen_fds;
+
+// the environment is that of the process, the cases are run in order in the one test
+#[test]
+fn test_listen_fds() {
+    env::remove_var("LISTEN_PID");
+    env::remove_var("LISTEN_FDS");
+    assert!(listen_fds().unwrap().is_empty());
+
+    // the sockets of another process, e.g. a parent, are left alone
+    env::set_var("LISTEN_PID", "1");
+    env::set_var("LISTEN_FDS", "2");
+    env::set_var("LISTEN_FDNAMES", "dns:tls");
+    assert!(listen_fds().unwrap().is_empty());
+    assert!(env::var("LISTEN_PID").is_err());
+    assert!(env::var("LISTEN_FDS").is_err());
+    assert!(env::var("LISTEN_FDNAMES").is_err());
+
+    env::set_var("LISTEN_PID", "named");
+    env::set_var("LISTEN_FDS", "2");
+    assert_eq!(listen_fds().unwrap_err().kind(), io::ErrorKind::InvalidInput);
+
+    // a name for each of the sockets, they are not adopted otherwise
+    if cfg!(target_os = "linux") {
+        let pid = std::fs::read_link("/proc/self").unwrap();
+        env::set_var("LISTEN_PID", pid);
+        env::set_var("LISTEN_FDS", "2");
+        env::set_var("LISTEN_FDNAMES", "dns");
+        assert_eq!(listen_fds().unwrap_err().kind(), io::ErrorKind::InvalidInput);
+        assert!(env::var("LISTEN_FDNAMES").is_err());
+    }
+}
@@ -0,0 +1,53 @@
+extern crate trust_dns;
+extern crate trust_dns_server;
+
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use trust_dns::rr::Name;
+
+use trust_dns_server::authority::{TransferAcl, TransferRule};
+
+fn addr(addr: &str) -> IpAddr {
+    IpAddr::from_str(addr).unwrap()
+}
+
+#[test]
+fn test_parse_rule() {
+    assert_eq!(TransferRule::from_str("192.0.2.1").unwrap(),
+               TransferRule::Network(addr("192.0.2.1"), 32));
+    assert_eq!(TransferRule::from_str("2001:db8::/32").unwrap(),
+               TransferRule::Network(addr("2001:db8::"), 32));
+    assert_eq!(TransferRule::from_str("key transfer.example.com.").unwrap(),
+               TransferRule::Key(Name::parse("transfer.example.com.", None).unwrap()));
+
+    assert!(TransferRule::from_str("").is_err());
+    assert!(TransferRule::from_str("key").is_err());
+    assert!(TransferRule::from_str("host.example.com.").is_err());
+    assert!(TransferRule::from_str("192.0.2.0/33").is_err());
+    assert!(TransferRule::from_str("192.0.2.0/24 key transfer.example.com.").is_err());
+}
+
+#[test]
+fn test_is_permitted() {
+    let key = Name::parse("transfer.example.com.", None).unwrap();
+    let acl = TransferAcl::new(vec![TransferRule::from_str("192.0.2.128/25").unwrap(),
+                                    TransferRule::from_str("2001:db8::/32").unwrap(),
+                                    TransferRule::Key(key.clone())]);
+
+    assert!(acl.is_permitted(addr("192.0.2.129"), None));
+    assert!(acl.is_permitted(addr("192.0.2.255"), None));
+    assert!(!acl.is_permitted(addr("192.0.2.127"), None));
+    assert!(acl.is_permitted(addr("2001:db8:ffff::1"), None));
+    assert!(!acl.is_permitted(addr("2001:db9::1"), None));
+    assert!(!acl.is_permitted(addr("::ffff:192.0.2.129"), None));
+
+    assert!(acl.is_permitted(addr("198.51.100.1"), Some(&key)));
+    assert!(!acl.is_permitted(addr("198.51.100.1"),
+                              Some(&Name::parse("other.example.com.", None).unwrap())));
+
+    // no rules, no transfers
+    assert!(!TransferAcl::default().is_permitted(addr("192.0.2.1"), None));
+    assert!(TransferRule::from_str("0.0.0.0/0").unwrap().matches(addr("203.0.113.1"), None));
+    assert!(!TransferRule::from_str("0.0.0.0/0").unwrap().matches(addr("::1"), None));
+}
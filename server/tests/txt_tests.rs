@@ -1,9 +1,14 @@
+extern crate tempdir;
 extern crate trust_dns;
 extern crate trust_dns_server;
 
+use std::collections::BTreeMap;
+use std::io::Write;
 use std::net::{Ipv4Addr, Ipv6Addr};
 use std::str::FromStr;
 
+use tempdir::TempDir;
+
 use trust_dns::rr::*;
 use trust_dns::rr::dnssec::*;
 use trust_dns::serialize::txt::*;
@@ -272,3 +277,435 @@ venera  A       10.1.0.52
         panic!("Not an SRV record!!!") // valid panic, test code
     }
 }
+
+#[test]
+fn test_master_file_syntax() {
+    let temp_dir = TempDir::new("trust_dns_txt_tests").unwrap();
+    let include_dir = temp_dir.path();
+    {
+        let mut file = std::fs::File::create(include_dir.join("included.zone")).unwrap();
+        file.write_all(b"www A 192.0.2.5\n@ TXT included").unwrap();
+    }
+
+    let lexer = Lexer::new("$ORIGIN example.com.
+$TTL 1h30m
+@   IN  SOA ns1 hostmaster (
+            2017010101 ; SERIAL
+            1h         ; REFRESH
+            15m        ; RETRY
+            1w         ; EXPIRE
+            1h )       ; MINIMUM
+    NS  ns1
+    MX  ( 10
+          mail )
+txt TXT ( \"first string\"
+          \"second string\" )
+www CNAME @
+$GENERATE 1-3/2 host-$ A 192.0.2.${10}
+$INCLUDE included.zone sub
+mail 60 A 192.0.2.1");
+
+    let records = Parser::new()
+        .include_dir(include_dir)
+        .parse(lexer, None);
+    if records.is_err() {
+        panic!("failed to parse: {:?}", records.err())
+    }
+    let (origin, records) = records.unwrap();
+    assert_eq!(origin, Name::parse("example.com.", None).unwrap());
+
+    fn get<'r>(records: &'r BTreeMap<RrKey, RecordSet>,
+               name: &str,
+               record_type: RecordType)
+               -> &'r Record {
+        records.get(&RrKey::new(&Name::parse(name, None).unwrap(), record_type))
+            .and_then(|rrset| rrset.iter().next())
+            .expect(name)
+    }
+
+    if let RData::SOA(ref soa) = *get(&records, "example.com.", RecordType::SOA).get_rdata() {
+        assert_eq!(soa.get_serial(), 2017010101);
+        assert_eq!(soa.get_refresh(), 3600);
+        assert_eq!(soa.get_retry(), 900);
+        assert_eq!(soa.get_expire(), 604800);
+        assert_eq!(soa.get_minimum(), 3600);
+    } else {
+        panic!("Not an SOA record!!!") // valid panic, test code
+    }
+
+    let mx = get(&records, "example.com.", RecordType::MX);
+    assert_eq!(mx.get_ttl(), 5400);
+    if let RData::MX(ref mx) = *mx.get_rdata() {
+        assert_eq!(mx.get_preference(), 10);
+        assert_eq!(mx.get_exchange(), &Name::parse("mail.example.com.", None).unwrap());
+    } else {
+        panic!("Not an MX record!!!") // valid panic, test code
+    }
+
+    if let RData::TXT(ref txt) = *get(&records, "txt.example.com.", RecordType::TXT).get_rdata() {
+        assert_eq!(txt.get_txt_data(),
+                   &["first string".to_string(), "second string".to_string()]);
+    } else {
+        panic!("Not a TXT record!!!") // valid panic, test code
+    }
+
+    assert_eq!(get(&records, "www.example.com.", RecordType::CNAME).get_rdata(),
+               &RData::CNAME(Name::parse("example.com.", None).unwrap()));
+
+    assert_eq!(get(&records, "host-1.example.com.", RecordType::A).get_rdata(),
+               &RData::A(Ipv4Addr::new(192, 0, 2, 11)));
+    assert_eq!(get(&records, "host-3.example.com.", RecordType::A).get_rdata(),
+               &RData::A(Ipv4Addr::new(192, 0, 2, 13)));
+    assert!(records.get(&RrKey::new(&Name::parse("host-2.example.com.", None).unwrap(),
+                                    RecordType::A))
+        .is_none());
+
+    // the included file is read with the origin of the $INCLUDE, which does not carry over
+    assert_eq!(get(&records, "www.sub.example.com.", RecordType::A).get_rdata(),
+               &RData::A(Ipv4Addr::new(192, 0, 2, 5)));
+    assert!(records.get(&RrKey::new(&Name::parse("sub.example.com.", None).unwrap(),
+                                    RecordType::TXT))
+        .is_some());
+
+    let mail = get(&records, "mail.example.com.", RecordType::A);
+    assert_eq!(mail.get_ttl(), 60);
+    assert_eq!(mail.get_rdata(), &RData::A(Ipv4Addr::new(192, 0, 2, 1)));
+}
+
+#[test]
+fn test_legacy_and_unknown_types() {
+    let lexer = Lexer::new("$ORIGIN example.com.
+$TTL 3600
+@   IN  SOA ns1 hostmaster 1 3600 600 86400 3600
+    NS  ns1
+    SPF \"v=spf1 -all\"
+    RP  admin contact
+    LOC ( 42 21 54 N 71 06 18 W
+          -24m 30m )
+box MB  mail
+    MG  member
+    MR  renamed
+odd TYPE1234 \\# 3 0A0B0C
+raw TYPE65280 \\# 4 ( 0A0B
+                      0C0D )
+gen A   \\# 4 C0000201");
+
+    let (origin, records) = Parser::new().parse(lexer, None).unwrap();
+    let get = |name: &str, record_type: RecordType| -> RData {
+        records.get(&RrKey::new(&Name::parse(name, Some(&origin)).unwrap(), record_type))
+            .and_then(|rrset| rrset.iter().next())
+            .expect(name)
+            .get_rdata()
+            .clone()
+    };
+
+    if let RData::SPF(ref txt) = get("@", RecordType::SPF) {
+        assert_eq!(txt.get_txt_data(), &["v=spf1 -all".to_string()]);
+    } else {
+        panic!("Not an SPF record!!!") // valid panic, test code
+    }
+    if let RData::RP(ref rp) = get("@", RecordType::RP) {
+        assert_eq!(rp.get_mbox(), &Name::parse("admin.example.com.", None).unwrap());
+        assert_eq!(rp.get_txt(), &Name::parse("contact.example.com.", None).unwrap());
+    } else {
+        panic!("Not an RP record!!!") // valid panic, test code
+    }
+    assert_eq!(get("@", RecordType::LOC).to_string(),
+               "42 21 54.000 N 71 6 18.000 W -24m 30m 10000m 10m");
+    assert_eq!(get("box", RecordType::MR),
+               RData::MR(Name::parse("renamed.example.com.", None).unwrap()));
+    assert_eq!(get("odd", RecordType::Unknown(1234)),
+               RData::Unknown(1234, rdata::NULL::with(vec![10, 11, 12])));
+    assert_eq!(get("raw", RecordType::Unknown(65280)).to_string(),
+               "\\# 4 0A0B0C0D");
+    assert_eq!(get("gen", RecordType::A), RData::A(Ipv4Addr::new(192, 0, 2, 1)));
+
+    let authority = Authority::new(origin.clone(),
+                                   records.clone(),
+                                   ZoneType::Master,
+                                   false,
+                                   false);
+
+    // MAILB is any of the mailbox types
+    let box_name = Name::parse("box.example.com.", None).unwrap();
+    let mailb = authority.lookup(&box_name,
+                                 RecordType::MAILB,
+                                 false,
+                                 SupportedAlgorithms::new());
+    assert_eq!(mailb.iter().map(|r| r.get_rr_type()).collect::<Vec<_>>(),
+               vec![RecordType::MB, RecordType::MG, RecordType::MR]);
+
+    // written and read back as they are
+    let zone_file = authority.to_zone_file();
+    assert!(zone_file.contains("\nodd 3600 IN TYPE1234 \\# 3 0A0B0C\n"));
+    let (_, written) = Parser::new().parse(Lexer::new(&zone_file), None).unwrap();
+    for (key, rrset) in records.iter().filter(|&(key, _)| key.record_type != RecordType::SOA) {
+        assert_eq!(written.get(key).unwrap().iter().collect::<Vec<_>>(),
+                   rrset.iter().collect::<Vec<_>>());
+    }
+}
+
+#[test]
+fn test_located_errors() {
+    use std::path::Path;
+    use trust_dns::error::*;
+
+    let zone = "$ORIGIN example.com.
+$TTL 1h
+@ IN SOA ns1 hostmaster 1 1h 15m 1w 1h
+www A 192.0.2.1
+bad 1x A 192.0.2.2
+mail A not-an-address
+ftp A 192.0.2.3
+oops ) A 192.0.2.4
+$GENERATE 1-2 host-$ A 192.0.2.${-5}
+";
+
+    fn location(error: &ParseError) -> (usize, usize, Option<Token>) {
+        match *error.kind() {
+            ParseErrorKind::Located(ref location, _) => {
+                assert_eq!(location.get_file(), Some(Path::new("example.zone")));
+                (location.get_line(), location.get_column(), location.get_token().cloned())
+            }
+            ref kind => panic!("not located: {:?}", kind),
+        }
+    }
+
+    // the first error ends the parsing
+    let error = Parser::new()
+        .file_name(Path::new("example.zone"))
+        .parse(Lexer::new(zone), None)
+        .unwrap_err();
+    assert_eq!(location(&error), (5, 5, Some(Token::CharData("1x".to_string()))));
+    assert_eq!(error.to_string(),
+               "example.zone:5:5: invalid time string: 1x, near `1x`");
+
+    // or all are reported at once
+    let error = Parser::new()
+        .file_name(Path::new("example.zone"))
+        .collect_errors(true)
+        .parse(Lexer::new(zone), None)
+        .unwrap_err();
+    let errors = match *error.kind() {
+        ParseErrorKind::Multiple(ref errors) => errors.iter().map(location).collect::<Vec<_>>(),
+        ref kind => panic!("not multiple errors: {:?}", kind),
+    };
+    assert_eq!(errors,
+               vec![(5, 5, Some(Token::CharData("1x".to_string()))),
+                    (6, 1, None),
+                    (8, 6, None),
+                    (9, 1, None)]);
+
+    // a zone without errors is the same either way
+    let (_, records) = Parser::new()
+        .collect_errors(true)
+        .parse(Lexer::new("$ORIGIN example.com.\n$TTL 1h\nwww IN A 192.0.2.1\n"), None)
+        .unwrap();
+    assert_eq!(records.len(), 1);
+}
+
+#[test]
+fn test_unsupported_types() {
+    use trust_dns::error::*;
+
+    // a DS of a delegation, only accepted in the generic form
+    let zone = "$ORIGIN example.com.
+$TTL 1h
+@ IN SOA ns1 hostmaster 1 1h 15m 1w 1h
+sub NS ns1.sub
+sub DS 60485 5 1 2BB183AF5F22588179A53B0A98631FAD1A292118
+";
+
+    let error = Parser::new().parse(Lexer::new(zone), None).unwrap_err();
+    match *error.kind() {
+        ParseErrorKind::Located(ref location, ref error) => {
+            assert_eq!(location.get_line(), 5);
+            match *error.kind() {
+                ParseErrorKind::UnsupportedRecordType(RecordType::DS) => (),
+                ref kind => panic!("not an unsupported type: {:?}", kind),
+            }
+        }
+        ref kind => panic!("not located: {:?}", kind),
+    }
+
+    for record_type in &["DNSKEY", "KEY", "NSEC", "NSEC3", "NSEC3PARAM", "RRSIG"] {
+        let zone = format!("$ORIGIN example.com.\n$TTL 1h\n@ IN {} 256 3 8 AwEAAQ==\n",
+                           record_type);
+        assert!(Parser::new().parse(Lexer::new(&zone), None).is_err(),
+                "{} was parsed",
+                record_type);
+    }
+}
+
+#[test]
+fn test_generate_modifier_limits() {
+    fn parse(generate: &str) -> Result<usize, String> {
+        let zone = format!("$ORIGIN example.com.\n$TTL 1h\n{}\n", generate);
+        Parser::new()
+            .parse(Lexer::new(&zone), None)
+            .map(|(_, records)| records.len())
+            .map_err(|error| error.to_string())
+    }
+
+    // a number as wide as a label
+    assert_eq!(parse("$GENERATE 1-2 ${0,63}.host A 192.0.2.1"), Ok(2));
+
+    // wider is refused before it is formatted
+    for width in &["64", "100000"] {
+        let error = parse(&format!("$GENERATE 1-2 ${{0,{}}}.host A 192.0.2.1", width))
+            .unwrap_err();
+        assert!(error.contains("$GENERATE width wider than a label"), "{}", error);
+    }
+
+    // the offset overflows rather than wraps
+    let error = parse("$GENERATE 1-2 host-$ A 192.0.2.${9223372036854775807}").unwrap_err();
+    assert!(error.contains("$GENERATE offset out of range"), "{}", error);
+    assert_eq!(parse("$GENERATE 1-2 host-$ A 192.0.2.${-1}"), Ok(2));
+}
+
+#[test]
+fn test_mailbox_validated() {
+    use trust_dns::error::*;
+
+    // the email address as the RNAME, rather than its name
+    let error = Parser::new()
+        .parse(Lexer::new("$ORIGIN example.com.
+@ IN SOA ns1 hostmaster@example.com. 1 1h 15m 1w 1h
+"),
+               None)
+        .unwrap_err();
+    match *error.kind() {
+        ParseErrorKind::Located(ref location, _) => assert_eq!(location.get_line(), 2),
+        ref kind => panic!("not located: {:?}", kind),
+    }
+
+    let (_, records) = Parser::new()
+        .parse(Lexer::new("$ORIGIN example.com.
+@ IN SOA ns1 john\\.doe 1 1h 15m 1w 1h
+@ RP . .
+www RP john\\.doe.example.com. contact
+"),
+               None)
+        .unwrap();
+    let soa = records.get(&RrKey::new(&Name::parse("example.com.", None).unwrap(), RecordType::SOA))
+        .and_then(|rrset| rrset.iter().next())
+        .unwrap();
+    if let RData::SOA(ref soa) = *soa.get_rdata() {
+        assert_eq!(soa.get_rname().to_email().unwrap(), "john.doe@example.com");
+    } else {
+        panic!("Not an SOA record!!!") // valid panic, test code
+    }
+
+    assert!(Parser::new()
+        .parse(Lexer::new("$ORIGIN example.com.
+@ IN SOA ns1 hostmaster 1 1h 15m 1w 1h
+www RP admin@example.com. .
+"),
+               None)
+        .is_err());
+}
+
+#[test]
+fn test_comments_round_trip() {
+    let zone = "$ORIGIN example.com.
+$TTL 3600
+; the zone of example.com
+@ IN SOA ns1 hostmaster ( 1 ; SERIAL
+          1h 15m 1w 1h )
+  NS ns1
+; the name server
+; @source=ipam
+ns1 A 192.0.2.1 ; do not change
+; www.example.com. 3600 IN RRSIG A 8 3 3600 1 0 1 example.com. AA==
+www A 192.0.2.2
+";
+    let (origin, records, meta) = Parser::new().parse_with_meta(Lexer::new(zone), None).unwrap();
+    assert_eq!(meta.len(), 2);
+
+    let ns1 = Record::from_rdata(Name::parse("ns1.example.com.", None).unwrap(),
+                                 3600,
+                                 RecordType::A,
+                                 RData::A(Ipv4Addr::new(192, 0, 2, 1)));
+    let ns1_meta = meta.get(&ns1).unwrap();
+    assert_eq!(ns1_meta.get_comments(),
+               &["the name server".to_string(), "do not change".to_string()]);
+    assert_eq!(ns1_meta.get_tag("source"), Some("ipam"));
+    {
+        let soa = records.get(&RrKey::new(&origin, RecordType::SOA)).unwrap();
+        assert_eq!(meta.get(soa.iter().next().unwrap()).unwrap().get_comments(),
+                   &["the zone of example.com".to_string()]);
+    }
+
+    let mut authority = Authority::new(origin, records, ZoneType::Master, false, false);
+    authority.set_record_meta(meta.clone());
+    let zone_file = authority.to_zone_file();
+    assert!(zone_file.starts_with("$ORIGIN example.com.\n; the zone of example.com\n@ "));
+    assert!(zone_file.contains("\n; the name server\n; do not change\n; @source=ipam\n\
+                                ns1 3600 IN A 192.0.2.1\n"));
+    assert!(zone_file.contains("\nwww 3600 IN A 192.0.2.2\n"));
+
+    // the comments and tags are kept as they are through any number of round trips
+    let (origin, records, written) = Parser::new()
+        .parse_with_meta(Lexer::new(&zone_file), None)
+        .unwrap();
+    assert_eq!(written, meta);
+    let mut authority = Authority::new(origin, records, ZoneType::Master, false, false);
+    authority.set_record_meta(written);
+    assert_eq!(authority.to_zone_file(), zone_file);
+}
+
+#[test]
+fn test_comments_through_update() {
+    let zone = "$ORIGIN example.com.
+$TTL 3600
+; the zone of example.com
+@ IN SOA ns1 hostmaster 1 1h 15m 1w 1h
+  NS ns1
+; the name server
+ns1 A 192.0.2.1
+; @source=ipam
+www A 192.0.2.2
+";
+    let (origin, records, meta) = Parser::new().parse_with_meta(Lexer::new(zone), None).unwrap();
+    assert_eq!(meta.len(), 3);
+    let mut authority = Authority::new(origin, records, ZoneType::Master, true, false);
+    authority.set_record_meta(meta);
+
+    let ns1 = Name::parse("ns1.example.com.", None).unwrap();
+    let www = Name::parse("www.example.com.", None).unwrap();
+    let delete = |name: &Name, last: u8| {
+        Record::new()
+            .name(name.clone())
+            .ttl(0)
+            .rr_type(RecordType::A)
+            .dns_class(DNSClass::NONE)
+            .rdata(RData::A(Ipv4Addr::new(192, 0, 2, last)))
+            .clone()
+    };
+    let add = Record::from_rdata(www.clone(),
+                                 3600,
+                                 RecordType::A,
+                                 RData::A(Ipv4Addr::new(192, 0, 2, 3)));
+
+    // the address of www is replaced, ns1 is deleted, and the serial is incremented
+    assert!(authority.update_records(&[delete(&www, 2), add.clone(), delete(&ns1, 1)], true)
+        .unwrap());
+    assert_eq!(authority.get_serial(), 2);
+    assert_eq!(authority.get_record_meta().len(), 2);
+    assert_eq!(authority.get_record_meta().get(&add).unwrap().get_tag("source"),
+               Some("ipam"));
+
+    let zone_file = authority.to_zone_file();
+    assert!(zone_file.starts_with("$ORIGIN example.com.\n; the zone of example.com\n@ "));
+    assert!(zone_file.contains("\n; @source=ipam\nwww 3600 IN A 192.0.2.3\n"));
+    assert!(!zone_file.contains("the name server"));
+
+    // and read back as they were written
+    let (origin, records, written) = Parser::new()
+        .parse_with_meta(Lexer::new(&zone_file), None)
+        .unwrap();
+    assert_eq!(&written, authority.get_record_meta());
+    let mut authority = Authority::new(origin, records, ZoneType::Master, true, false);
+    authority.set_record_meta(written);
+    assert_eq!(authority.to_zone_file(), zone_file);
+}
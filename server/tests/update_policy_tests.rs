@@ -0,0 +1,80 @@
+extern crate trust_dns;
+extern crate trust_dns_server;
+
+use std::str::FromStr;
+
+use trust_dns::rr::{Name, Record, RecordType};
+
+use trust_dns_server::authority::{NameMatch, UpdatePolicy, UpdateRule};
+
+fn record(name: &str, rr_type: RecordType) -> Record {
+    Record::with(Name::parse(name, None).unwrap(), rr_type, 0)
+}
+
+#[test]
+fn test_parse_rule() {
+    let host1 = Name::parse("host1.example.com.", None).unwrap();
+
+    assert_eq!(UpdateRule::from_str("grant host1.example.com. name host1.example.com. A AAAA TXT")
+                   .unwrap(),
+               UpdateRule::new(true,
+                               Some(host1.clone()),
+                               NameMatch::Name(host1.clone()),
+                               vec![RecordType::A, RecordType::AAAA, RecordType::TXT]));
+    assert_eq!(UpdateRule::from_str("deny * subdomain example.com.").unwrap(),
+               UpdateRule::new(false,
+                               None,
+                               NameMatch::Subdomain(Name::parse("example.com.", None).unwrap()),
+                               vec![]));
+    assert_eq!(UpdateRule::from_str("grant * self A").unwrap(),
+               UpdateRule::new(true, None, NameMatch::SelfName, vec![RecordType::A]));
+    assert_eq!(UpdateRule::from_str("grant * zonesub").unwrap(),
+               UpdateRule::new(true, None, NameMatch::ZoneSub, vec![]));
+
+    assert!(UpdateRule::from_str("allow * self").is_err());
+    assert!(UpdateRule::from_str("grant *").is_err());
+    assert!(UpdateRule::from_str("grant * name").is_err());
+    assert!(UpdateRule::from_str("grant * wildcard *.example.com.").is_err());
+    assert!(UpdateRule::from_str("grant * zonesub BOGUS").is_err());
+}
+
+#[test]
+fn test_is_permitted() {
+    let origin = Name::parse("example.com.", None).unwrap();
+    let host1 = Name::parse("host1.example.com.", None).unwrap();
+    let host2 = Name::parse("host2.example.com.", None).unwrap();
+    let admin = Name::parse("admin.example.com.", None).unwrap();
+
+    let policy = UpdatePolicy::new(vec![
+        UpdateRule::from_str("grant host1.example.com. name host1.example.com. A AAAA TXT")
+            .unwrap(),
+        UpdateRule::from_str("grant * self TXT").unwrap(),
+        UpdateRule::from_str("deny admin.example.com. subdomain www.example.com.").unwrap(),
+        UpdateRule::from_str("grant admin.example.com. zonesub").unwrap(),
+    ]);
+
+    let host1_a = record("host1.example.com.", RecordType::A);
+    let host1_mx = record("host1.example.com.", RecordType::MX);
+    let host1_any = record("host1.example.com.", RecordType::ANY);
+    let host2_txt = record("host2.example.com.", RecordType::TXT);
+    let www_a = record("a.www.example.com.", RecordType::A);
+
+    assert!(policy.is_permitted(&host1, &origin, &[host1_a.clone()]));
+    assert!(!policy.is_permitted(&host1, &origin, &[host1_mx.clone()]));
+    assert!(!policy.is_permitted(&host1, &origin, &[host1_any.clone()]));
+
+    // all records must be permitted
+    assert!(!policy.is_permitted(&host1, &origin, &[host1_a.clone(), www_a.clone()]));
+
+    // self only covers the signer's own name
+    assert!(policy.is_permitted(&host2, &origin, &[host2_txt.clone()]));
+    assert!(!policy.is_permitted(&host2, &origin, &[host1_a.clone()]));
+
+    // first match wins
+    assert!(!policy.is_permitted(&admin, &origin, &[www_a]));
+    assert!(policy.is_permitted(&admin, &origin, &[host1_a, host1_mx, host1_any, host2_txt]));
+
+    // no rules, nothing is permitted
+    assert!(!UpdatePolicy::default()
+        .is_permitted(&admin, &origin, &[record("example.com.", RecordType::A)]));
+}